@@ -0,0 +1,140 @@
+use crate::{
+    auth::{AuthService, AuthenticatedUser},
+    error::{authz_error, ApiError},
+};
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, MatchedPath},
+    http::request::Parts,
+};
+use rusty_ai_core::audit::{AuditEntry, AuditTrail};
+use std::sync::Arc;
+use tracing::warn;
+
+/// Extractor for admin-scope mutation handlers. It rejects callers without
+/// the admin permission and must be consumed with [`AdminAction::record`];
+/// a handler that returns without recording (including on error) still
+/// leaves an incomplete entry naming the route, so a forgotten audit call
+/// shows up in the trail instead of going missing.
+pub struct AdminAction {
+    user: AuthenticatedUser,
+    route: String,
+    request_id: Option<String>,
+    trail: Arc<AuditTrail>,
+    recorded: bool,
+}
+
+impl AdminAction {
+    pub(crate) fn new(user: AuthenticatedUser, route: String, request_id: Option<String>, trail: Arc<AuditTrail>) -> Self {
+        Self { user, route, request_id, trail, recorded: false }
+    }
+
+    pub fn user(&self) -> &AuthenticatedUser {
+        &self.user
+    }
+
+    /// Queue the audit entry for the completed action. `before` and `after`
+    /// are short summaries of the changed state, where they are cheap to get.
+    pub fn record(
+        mut self,
+        action: &str,
+        target: &str,
+        before: Option<serde_json::Value>,
+        after: Option<serde_json::Value>,
+    ) {
+        self.recorded = true;
+        let mut entry = AuditEntry::new(self.user.claims.user_id.to_string(), action, target)
+            .request_id(self.request_id.take());
+        entry.before = before;
+        entry.after = after;
+        self.trail.record(entry);
+    }
+}
+
+impl Drop for AdminAction {
+    fn drop(&mut self) {
+        if self.recorded {
+            return;
+        }
+        warn!("Admin request {} finished without an audit record", self.route);
+        let mut entry = AuditEntry::new(self.user.claims.user_id.to_string(), self.route.clone(), "")
+            .request_id(self.request_id.take());
+        entry.complete = false;
+        self.trail.record(entry);
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for AdminAction
+where
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let user = AuthenticatedUser::from_request_parts(parts, state).await?;
+
+        let auth_service = parts
+            .extensions
+            .get::<Arc<AuthService>>()
+            .ok_or_else(|| ApiError::Internal("Auth service not available".to_string()))?;
+        if !auth_service.has_permission(&user.claims, "admin") {
+            return Err(authz_error("This action requires admin permission"));
+        }
+
+        let trail = parts
+            .extensions
+            .get::<Arc<AuditTrail>>()
+            .cloned()
+            .ok_or_else(|| ApiError::Internal("Audit trail not available".to_string()))?;
+        let path = parts
+            .extensions
+            .get::<MatchedPath>()
+            .map(|p| p.as_str().to_string())
+            .unwrap_or_else(|| parts.uri.path().to_string());
+        let request_id = parts
+            .headers
+            .get("x-request-id")
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+
+        Ok(Self::new(user, format!("{} {}", parts.method, path), request_id, trail))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::routes::tests::user;
+    use rusty_ai_core::audit::AuditFilter;
+    use rusty_ai_core::storage::{SqliteStorage, StorageConfig};
+    use uuid::Uuid;
+
+    pub(crate) async fn audit_trail() -> Arc<AuditTrail> {
+        let storage = SqliteStorage::new(&StorageConfig {
+            database_url: "sqlite::memory:".to_string(),
+            max_connections: 1,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        Arc::new(AuditTrail::new(Arc::new(storage), 30))
+    }
+
+    pub(crate) fn admin_action(trail: &Arc<AuditTrail>, route: &str) -> AdminAction {
+        AdminAction::new(user(Uuid::new_v4(), &["admin"]), route.to_string(), Some("req-1".to_string()), trail.clone())
+    }
+
+    #[tokio::test]
+    async fn test_unrecorded_action_leaves_incomplete_entry() {
+        let trail = audit_trail().await;
+        drop(admin_action(&trail, "POST /api/v1/admin/resources/trim"));
+        trail.flush().await;
+
+        let entries = trail.query(&AuditFilter::default()).await.unwrap();
+        assert_eq!(entries.len(), 1);
+        assert!(!entries[0].complete);
+        assert_eq!(entries[0].action, "POST /api/v1/admin/resources/trim");
+        assert_eq!(entries[0].request_id.as_deref(), Some("req-1"));
+    }
+}
@@ -9,6 +9,7 @@ use axum::{
 use chrono::{DateTime, Duration, Utc};
 use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::sync::Arc;
 use tracing::{debug, error, warn};
 use uuid::Uuid;
@@ -20,6 +21,9 @@ pub struct AuthConfig {
     pub refresh_token_expiry_days: i64,
     pub issuer: String,
     pub audience: String,
+    /// Long-lived keys accepted in the `X-API-Key` header, e.g. for scripts
+    /// and companion apps that cannot log in
+    pub api_keys: Vec<ApiKey>,
 }
 
 impl Default for AuthConfig {
@@ -30,10 +34,39 @@ impl Default for AuthConfig {
             refresh_token_expiry_days: 30,
             issuer: "rusty-ai-assistant".to_string(),
             audience: "rusty-ai-users".to_string(),
+            api_keys: Vec::new(),
         }
     }
 }
 
+/// Header API keys are sent in
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+/// An API key and the user it acts as. Only the key's SHA-256 is kept, so
+/// the configuration does not hold usable keys
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiKey {
+    /// Hex SHA-256 of the key, as [`hash_api_key`] computes it
+    pub key_hash: String,
+    pub user_id: Uuid,
+    pub name: String,
+    pub permissions: Vec<String>,
+}
+
+impl ApiKey {
+    pub fn new(key: &str, user_id: Uuid, name: impl Into<String>, permissions: Vec<String>) -> Self {
+        Self { key_hash: hash_api_key(key), user_id, name: name.into(), permissions }
+    }
+}
+
+pub fn hash_api_key(key: &str) -> String {
+    ring::digest::digest(&ring::digest::SHA256, key.as_bytes())
+        .as_ref()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: String,        // Subject (user ID)
@@ -48,50 +81,32 @@ pub struct Claims {
     pub permissions: Vec<String>, // User permissions
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct LoginRequest {
-    pub email: String,
-    pub password: String,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct LoginResponse {
-    pub access_token: String,
-    pub refresh_token: String,
-    pub token_type: String,
-    pub expires_in: i64,
-    pub user: UserInfo,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct RefreshRequest {
-    pub refresh_token: String,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct UserInfo {
-    pub id: Uuid,
-    pub name: String,
-    pub email: String,
-    pub permissions: Vec<String>,
-}
+pub use rusty_ai_common::api::{LoginRequest, LoginResponse, RefreshRequest, UserInfo};
 
 #[derive(Debug, Clone)]
 pub struct AuthService {
     config: AuthConfig,
     encoding_key: EncodingKey,
     decoding_key: DecodingKey,
+    // Configured API keys by hash
+    api_keys: HashMap<String, ApiKey>,
 }
 
 impl AuthService {
     pub fn new(config: AuthConfig) -> Self {
         let encoding_key = EncodingKey::from_secret(config.jwt_secret.as_bytes());
         let decoding_key = DecodingKey::from_secret(config.jwt_secret.as_bytes());
+        let api_keys = config
+            .api_keys
+            .iter()
+            .map(|key| (key.key_hash.to_ascii_lowercase(), key.clone()))
+            .collect();
 
         Self {
             config,
             encoding_key,
             decoding_key,
+            api_keys,
         }
     }
 
@@ -186,6 +201,30 @@ impl AuthService {
         }
     }
 
+    /// The claims of the user an API key acts as. They are valid for as long
+    /// as an access token would be, and each request gets a new session
+    pub fn verify_api_key(&self, key: &str) -> ApiResult<Claims> {
+        let Some(api_key) = self.api_keys.get(&hash_api_key(key)) else {
+            warn!("Unknown API key presented");
+            return Err(ApiError::Authentication("Invalid API key".to_string()));
+        };
+        debug!("API key '{}' verified for user: {}", api_key.name, api_key.user_id);
+
+        let now = Utc::now();
+        Ok(Claims {
+            sub: api_key.user_id.to_string(),
+            name: api_key.name.clone(),
+            email: String::new(),
+            iat: now.timestamp(),
+            exp: (now + Duration::hours(self.config.token_expiry_hours)).timestamp(),
+            iss: self.config.issuer.clone(),
+            aud: self.config.audience.clone(),
+            user_id: api_key.user_id,
+            session_id: Uuid::new_v4(),
+            permissions: api_key.permissions.clone(),
+        })
+    }
+
     fn create_access_token(
         &self,
         user_id: Uuid,
@@ -271,18 +310,28 @@ where
     type Rejection = ApiError;
 
     async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
-        // Extract the authorization header
-        let TypedHeader(Authorization(bearer)) = parts
-            .extract::<TypedHeader<Authorization<Bearer>>>()
-            .await
-            .map_err(|_| ApiError::Authentication("Missing authorization header".to_string()))?;
-
         // Get the auth service from extensions (set by middleware)
         let auth_service = parts
             .extensions
             .get::<Arc<AuthService>>()
+            .cloned()
             .ok_or_else(|| ApiError::Internal("Auth service not available".to_string()))?;
 
+        // An API key is used when one is sent; otherwise a bearer token
+        if let Some(key) = parts.headers.get(API_KEY_HEADER) {
+            let key = key
+                .to_str()
+                .map_err(|_| ApiError::Authentication("Invalid API key".to_string()))?;
+            let claims = auth_service.verify_api_key(key)?;
+            return Ok(AuthenticatedUser { claims });
+        }
+
+        // Extract the authorization header
+        let TypedHeader(Authorization(bearer)) = parts
+            .extract::<TypedHeader<Authorization<Bearer>>>()
+            .await
+            .map_err(|_| ApiError::Authentication("Missing authorization header".to_string()))?;
+
         // Verify the token
         let claims = auth_service.verify_token(bearer.token())?;
 
@@ -357,6 +406,25 @@ mod tests {
         assert!(!response.access_token.is_empty());
     }
 
+    #[test]
+    fn test_api_key_acts_as_its_user() {
+        let user_id = Uuid::new_v4();
+        let config = AuthConfig {
+            api_keys: vec![ApiKey::new("rk_live_secret", user_id, "backup script", vec!["read".to_string()])],
+            ..AuthConfig::default()
+        };
+        assert!(!format!("{:?}", config.api_keys).contains("rk_live_secret"));
+        let auth_service = AuthService::new(config);
+
+        let claims = auth_service.verify_api_key("rk_live_secret").unwrap();
+        assert_eq!(claims.user_id, user_id);
+        assert_eq!(claims.permissions, vec!["read".to_string()]);
+        assert!(auth_service.has_permission(&claims, "read"));
+        assert!(!auth_service.has_permission(&claims, "write"));
+
+        assert!(auth_service.verify_api_key("rk_live_guess").is_err());
+    }
+
     #[tokio::test]
     async fn test_invalid_credentials() {
         let config = AuthConfig::default();
@@ -1,10 +1,9 @@
 use axum::{
-    http::StatusCode,
+    http::{header, HeaderValue, StatusCode},
     response::{IntoResponse, Response},
-    Json,
 };
-use rusty_ai_common::ApiResponse;
-use serde_json::json;
+use rusty_ai_common::api::FieldError;
+use rusty_ai_common::{ApiResponse, ErrorCode};
 use thiserror::Error;
 use tracing::error;
 
@@ -13,15 +12,26 @@ pub enum ApiError {
     #[error("Validation error: {0}")]
     Validation(String),
     
+    // Fields of the request body or query that failed to parse or validate
+    #[error("Invalid fields: {0:?}")]
+    InvalidFields(Vec<FieldError>),
+    
     #[error("Authentication error: {0}")]
     Authentication(String),
     
     #[error("Authorization error: {0}")]
     Authorization(String),
     
+    #[error("Conflict: {0}")]
+    Conflict(String),
+    
     #[error("Rate limit exceeded")]
     RateLimit,
     
+    // The server is saturated; the client should retry after the delay
+    #[error("Server overloaded")]
+    Overloaded { retry_after_secs: u64 },
+    
     #[error("Request too large")]
     RequestTooLarge,
     
@@ -43,61 +53,93 @@ pub enum ApiError {
 
 impl IntoResponse for ApiError {
     fn into_response(self) -> Response {
+        let mut fields = Vec::new();
+        let mut retry_after = None;
         let (status, error_message, error_code) = match self {
             ApiError::Validation(msg) => {
-                (StatusCode::BAD_REQUEST, msg, "VALIDATION_ERROR")
+                (StatusCode::BAD_REQUEST, msg, ErrorCode::ValidationError)
+            }
+            ApiError::InvalidFields(errors) => {
+                let mut names: Vec<&str> = errors.iter().map(|e| e.field.as_str()).collect();
+                names.dedup();
+                let message = format!("Invalid request: {}", names.join(", "));
+                fields = errors;
+                (StatusCode::BAD_REQUEST, message, ErrorCode::ValidationError)
             }
             ApiError::Authentication(msg) => {
-                (StatusCode::UNAUTHORIZED, msg, "AUTHENTICATION_ERROR")
+                (StatusCode::UNAUTHORIZED, msg, ErrorCode::AuthenticationError)
             }
             ApiError::Authorization(msg) => {
-                (StatusCode::FORBIDDEN, msg, "AUTHORIZATION_ERROR")
+                (StatusCode::FORBIDDEN, msg, ErrorCode::AuthorizationError)
+            }
+            ApiError::Conflict(msg) => {
+                (StatusCode::CONFLICT, msg, ErrorCode::Conflict)
             }
             ApiError::RateLimit => {
-                (StatusCode::TOO_MANY_REQUESTS, "Rate limit exceeded".to_string(), "RATE_LIMIT")
+                (StatusCode::TOO_MANY_REQUESTS, "Rate limit exceeded".to_string(), ErrorCode::RateLimit)
+            }
+            ApiError::Overloaded { retry_after_secs } => {
+                retry_after = Some(retry_after_secs);
+                (
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    "Server is busy, try again shortly".to_string(),
+                    ErrorCode::ServiceUnavailable,
+                )
             }
             ApiError::RequestTooLarge => {
-                (StatusCode::PAYLOAD_TOO_LARGE, "Request payload too large".to_string(), "REQUEST_TOO_LARGE")
+                (StatusCode::PAYLOAD_TOO_LARGE, "Request payload too large".to_string(), ErrorCode::RequestTooLarge)
             }
             ApiError::InvalidContentType => {
-                (StatusCode::UNSUPPORTED_MEDIA_TYPE, "Invalid content type".to_string(), "INVALID_CONTENT_TYPE")
+                (StatusCode::UNSUPPORTED_MEDIA_TYPE, "Invalid content type".to_string(), ErrorCode::InvalidContentType)
             }
             ApiError::Serialization(msg) => {
                 error!("Serialization error: {}", msg);
-                (StatusCode::INTERNAL_SERVER_ERROR, "Serialization error".to_string(), "SERIALIZATION_ERROR")
+                (StatusCode::INTERNAL_SERVER_ERROR, "Serialization error".to_string(), ErrorCode::SerializationError)
             }
             ApiError::WebSocket(msg) => {
                 error!("WebSocket error: {}", msg);
-                (StatusCode::BAD_REQUEST, msg, "WEBSOCKET_ERROR")
+                (StatusCode::BAD_REQUEST, msg, ErrorCode::WebsocketError)
             }
             ApiError::CoreService(err) => {
                 error!("Core service error: {}", err);
                 match err {
                     rusty_ai_common::AssistantError::NotFound(msg) => {
-                        (StatusCode::NOT_FOUND, msg, "NOT_FOUND")
+                        (StatusCode::NOT_FOUND, msg, ErrorCode::NotFound)
                     }
                     rusty_ai_common::AssistantError::Unauthorized => {
-                        (StatusCode::UNAUTHORIZED, "Unauthorized".to_string(), "UNAUTHORIZED")
+                        (StatusCode::UNAUTHORIZED, "Unauthorized".to_string(), ErrorCode::Unauthorized)
+                    }
+                    rusty_ai_common::AssistantError::Security(msg) => {
+                        (StatusCode::FORBIDDEN, msg, ErrorCode::AuthorizationError)
+                    }
+                    rusty_ai_common::AssistantError::RateLimited { message, retry_after_secs } => {
+                        retry_after = Some(retry_after_secs);
+                        (StatusCode::TOO_MANY_REQUESTS, message, ErrorCode::RateLimit)
+                    }
+                    rusty_ai_common::AssistantError::Unavailable { message, retry_after_secs } => {
+                        retry_after = Some(retry_after_secs);
+                        (StatusCode::SERVICE_UNAVAILABLE, message, ErrorCode::ServiceUnavailable)
+                    }
+                    rusty_ai_common::AssistantError::Timeout(_) => {
+                        (StatusCode::GATEWAY_TIMEOUT, "Request timed out".to_string(), ErrorCode::ServiceUnavailable)
                     }
                     _ => {
-                        (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error".to_string(), "INTERNAL_ERROR")
+                        (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error".to_string(), ErrorCode::InternalError)
                     }
                 }
             }
             ApiError::Internal(msg) => {
                 error!("Internal error: {}", msg);
-                (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error".to_string(), "INTERNAL_ERROR")
+                (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error".to_string(), ErrorCode::InternalError)
             }
         };
 
-        let response_body = json!({
-            "success": false,
-            "error": error_message,
-            "error_code": error_code,
-            "timestamp": chrono::Utc::now()
-        });
-
-        (status, Json(response_body)).into_response()
+        let response = ApiResponse::<()>::error_with_code(error_code, error_message).with_fields(fields);
+        let mut response = (status, crate::create_response(response)).into_response();
+        if let Some(seconds) = retry_after {
+            response.headers_mut().insert(header::RETRY_AFTER, HeaderValue::from(seconds));
+        }
+        response
     }
 }
 
@@ -147,4 +189,33 @@ mod tests {
         let response = error.into_response();
         assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
     }
+
+    #[test]
+    fn test_rate_limited_core_errors_carry_a_retry_hint() {
+        let limited = || rusty_ai_common::AssistantError::RateLimited {
+            message: "search allows 100 calls per hour".to_string(),
+            retry_after_secs: 120,
+        };
+        for response in [ApiError::CoreService(limited()).into_response(), limited().into_response()] {
+            assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+            assert_eq!(response.headers()[header::RETRY_AFTER], "120");
+        }
+
+        let unloading = || rusty_ai_common::AssistantError::Unavailable {
+            message: "Plugin search is being unloaded".to_string(),
+            retry_after_secs: 1,
+        };
+        for response in [ApiError::CoreService(unloading()).into_response(), unloading().into_response()] {
+            assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+            assert_eq!(response.headers()[header::RETRY_AFTER], "1");
+        }
+    }
+
+    #[test]
+    fn test_security_violation_is_forbidden() {
+        let error = rusty_ai_common::AssistantError::Security("Prohibited import module: env2".to_string());
+        assert_eq!(error.into_response().status(), StatusCode::FORBIDDEN);
+        let error = ApiError::CoreService(rusty_ai_common::AssistantError::Security("denied".to_string()));
+        assert_eq!(error.into_response().status(), StatusCode::FORBIDDEN);
+    }
 }
\ No newline at end of file
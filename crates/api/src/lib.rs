@@ -4,16 +4,20 @@ pub mod websocket;
 pub mod auth;
 pub mod server;
 pub mod error;
+pub mod security;
+pub mod audit;
+pub mod validation;
+pub mod plugin_host;
 
 use axum::{
-    http::StatusCode,
+    http::{header, HeaderValue, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
-use rusty_ai_common::{ApiResponse, AssistantError};
+use rusty_ai_common::{ApiResponse, AssistantError, ErrorCode, Pagination};
 use serde_json::json;
 use std::sync::Arc;
-use tracing::error;
+use tracing::{error, warn};
 
 pub use server::ApiServer;
 
@@ -30,6 +34,14 @@ pub struct ApiConfig {
     pub enable_websockets: bool,
     pub max_request_size: usize,
     pub rate_limit_requests_per_minute: u32,
+    pub plugin_directory: String,
+    /// Remote plugin index manifests offered for installation
+    pub plugin_index_urls: Vec<String>,
+    /// Keys trusted to sign plugins, by key id, and the author each signs
+    /// for. Index artifacts, uploads and plugins loaded from disk are all
+    /// verified against them
+    pub plugin_trusted_keys: std::collections::HashMap<String, rusty_ai_plugins::TrustedKey>,
+    pub security_headers: security::SecurityHeadersConfig,
 }
 
 impl Default for ApiConfig {
@@ -42,75 +54,119 @@ impl Default for ApiConfig {
             enable_websockets: true,
             max_request_size: 16 * 1024 * 1024, // 16MB
             rate_limit_requests_per_minute: 60,
+            plugin_directory: "./plugins".to_string(),
+            plugin_index_urls: vec![],
+            plugin_trusted_keys: Default::default(),
+            security_headers: security::SecurityHeadersConfig::default(),
         }
     }
 }
 
+impl ApiConfig {
+    // Checked before the server starts so a bad origin or policy fails fast
+    // instead of at the first request
+    pub fn validate(&self) -> rusty_ai_common::Result<()> {
+        for origin in self.cors_origins.iter().filter(|o| o.as_str() != "*") {
+            if origin.parse::<axum::http::HeaderValue>().is_err() || !origin.contains("://") {
+                return Err(AssistantError::Configuration(format!("Invalid CORS origin '{}'", origin)));
+            }
+        }
+        self.security_headers.validate()
+    }
+}
+
 // Global error handling
 impl IntoResponse for AssistantError {
     fn into_response(self) -> Response {
-        let (status, error_message) = match self {
+        let retry_after = match &self {
+            AssistantError::RateLimited { retry_after_secs, .. }
+            | AssistantError::Unavailable { retry_after_secs, .. } => Some(*retry_after_secs),
+            _ => None,
+        };
+        let (status, error_message, code) = match self {
             AssistantError::Database(msg) => {
                 error!("Database error: {}", msg);
-                (StatusCode::INTERNAL_SERVER_ERROR, "Database error occurred")
+                (StatusCode::INTERNAL_SERVER_ERROR, "Database error occurred", ErrorCode::DatabaseError)
             }
             AssistantError::Api(msg) => {
                 error!("API error: {}", msg);
-                (StatusCode::BAD_REQUEST, msg.as_str())
+                (StatusCode::BAD_REQUEST, msg.as_str(), ErrorCode::BadRequest)
             }
             AssistantError::VoiceProcessing(msg) => {
                 error!("Voice processing error: {}", msg);
-                (StatusCode::UNPROCESSABLE_ENTITY, "Voice processing failed")
+                (StatusCode::UNPROCESSABLE_ENTITY, "Voice processing failed", ErrorCode::VoiceProcessingError)
             }
             AssistantError::Plugin(msg) => {
                 error!("Plugin error: {}", msg);
-                (StatusCode::SERVICE_UNAVAILABLE, "Plugin service unavailable")
+                (StatusCode::SERVICE_UNAVAILABLE, "Plugin service unavailable", ErrorCode::PluginUnavailable)
             }
             AssistantError::Configuration(msg) => {
                 error!("Configuration error: {}", msg);
-                (StatusCode::INTERNAL_SERVER_ERROR, "Configuration error")
+                (StatusCode::INTERNAL_SERVER_ERROR, "Configuration error", ErrorCode::ConfigurationError)
             }
             AssistantError::NotFound(msg) => {
-                (StatusCode::NOT_FOUND, msg.as_str())
+                (StatusCode::NOT_FOUND, msg.as_str(), ErrorCode::NotFound)
             }
             AssistantError::Unauthorized => {
-                (StatusCode::UNAUTHORIZED, "Unauthorized access")
+                (StatusCode::UNAUTHORIZED, "Unauthorized access", ErrorCode::Unauthorized)
+            }
+            AssistantError::Security(msg) => {
+                warn!("Security violation: {}", msg);
+                (StatusCode::FORBIDDEN, msg.as_str(), ErrorCode::AuthorizationError)
+            }
+            AssistantError::RateLimited { message, .. } => {
+                (StatusCode::TOO_MANY_REQUESTS, message.as_str(), ErrorCode::RateLimit)
+            }
+            AssistantError::Unavailable { message, .. } => {
+                (StatusCode::SERVICE_UNAVAILABLE, message.as_str(), ErrorCode::ServiceUnavailable)
+            }
+            AssistantError::Timeout(msg) => {
+                error!("Timed out: {}", msg);
+                (StatusCode::GATEWAY_TIMEOUT, "Request timed out", ErrorCode::ServiceUnavailable)
             }
             AssistantError::Internal(msg) => {
                 error!("Internal error: {}", msg);
-                (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error")
+                (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error", ErrorCode::InternalError)
             }
         };
 
-        let response = ApiResponse::<()>::error(error_message.to_string());
-        (status, Json(response)).into_response()
+        let response = ApiResponse::<()>::error_with_code(code, error_message);
+        let mut response = (status, create_response(response)).into_response();
+        if let Some(seconds) = retry_after {
+            response.headers_mut().insert(header::RETRY_AFTER, HeaderValue::from(seconds));
+        }
+        response
     }
 }
 
 // Health check response
 #[derive(serde::Serialize)]
 pub struct HealthCheck {
-    pub status: String,
     pub version: String,
     pub uptime: u64,
-    pub services: ServiceHealth,
+    #[serde(flatten)]
+    pub report: rusty_ai_core::health::HealthReport,
 }
 
-#[derive(serde::Serialize)]
-pub struct ServiceHealth {
-    pub database: String,
-    pub storage: String,
-    pub plugins: String,
-    pub voice: String,
+// Common API utilities. Every response body goes through `create_response`,
+// which stamps the id of the request being handled
+pub fn create_response<T>(response: ApiResponse<T>) -> Json<ApiResponse<T>> {
+    match middleware::current_request_id() {
+        Some(request_id) => Json(response.with_request_id(request_id)),
+        None => Json(response),
+    }
 }
 
-// Common API utilities
 pub fn create_success_response<T: serde::Serialize>(data: T) -> Json<ApiResponse<T>> {
-    Json(ApiResponse::success(data))
+    create_response(ApiResponse::success(data))
+}
+
+pub fn create_paginated_response<T: serde::Serialize>(data: T, pagination: Pagination) -> Json<ApiResponse<T>> {
+    create_response(ApiResponse::paginated(data, pagination))
 }
 
 pub fn create_error_response(message: String) -> Json<ApiResponse<()>> {
-    Json(ApiResponse::error(message))
+    create_response(ApiResponse::error(message))
 }
 
 #[cfg(test)]
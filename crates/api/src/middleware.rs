@@ -1,10 +1,12 @@
-use crate::{auth::AuthService, error::ApiError, ApiConfig};
+use crate::{auth::AuthService, error::ApiError, security::{is_tls_request, SecurityHeaders}, ApiConfig};
 use axum::{
     extract::{Request, State},
-    http::{HeaderName, HeaderValue, Method, StatusCode},
+    http::{header, HeaderName, HeaderValue, Method},
     middleware::Next,
     response::{IntoResponse, Response},
 };
+use rusty_ai_common::{ApiResponse, ErrorCode};
+use rusty_ai_core::admission::{AdmissionController, RequestClass};
 use std::{
     collections::HashMap,
     sync::{Arc, Mutex},
@@ -21,7 +23,7 @@ pub fn cors_layer(config: &ApiConfig) -> CorsLayer {
         config
             .cors_origins
             .iter()
-            .map(|origin| origin.parse().unwrap())
+            .filter_map(|origin| origin.parse().ok())
             .collect::<Vec<_>>()
             .into()
     };
@@ -38,6 +40,7 @@ pub fn cors_layer(config: &ApiConfig) -> CorsLayer {
         ])
         .allow_headers([
             HeaderName::from_static("authorization"),
+            HeaderName::from_static(crate::auth::API_KEY_HEADER),
             HeaderName::from_static("content-type"),
             HeaderName::from_static("x-request-id"),
             HeaderName::from_static("x-user-agent"),
@@ -46,7 +49,13 @@ pub fn cors_layer(config: &ApiConfig) -> CorsLayer {
             HeaderName::from_static("x-request-id"),
             HeaderName::from_static("x-response-time"),
         ])
-        .max_age(Duration::from_secs(3600))
+        .max_age(Duration::from_secs(config.security_headers.cors_max_age_secs))
+        // Responses differ by origin, so shared caches must key on it
+        .vary([
+            header::ORIGIN,
+            header::ACCESS_CONTROL_REQUEST_METHOD,
+            header::ACCESS_CONTROL_REQUEST_HEADERS,
+        ])
 }
 
 // Request logging middleware
@@ -70,7 +79,18 @@ pub async fn request_logging_middleware(request: Request, next: Next) -> Respons
     response
 }
 
-// Request ID middleware
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+// The id of the request being handled, for the response envelope; None
+// outside `request_id_middleware`
+pub fn current_request_id() -> Option<String> {
+    REQUEST_ID.try_with(|id| id.clone()).ok()
+}
+
+// Request ID middleware. Handlers and error responses inside it echo the id
+// in the body through `current_request_id`
 pub async fn request_id_middleware(mut request: Request, next: Next) -> Response {
     let request_id = uuid::Uuid::new_v4().to_string();
     
@@ -80,7 +100,7 @@ pub async fn request_id_middleware(mut request: Request, next: Next) -> Response
         HeaderValue::from_str(&request_id).unwrap(),
     );
 
-    let mut response = next.run(request).await;
+    let mut response = REQUEST_ID.scope(request_id.clone(), next.run(request)).await;
     
     // Add request ID to response headers
     response.headers_mut().insert(
@@ -142,7 +162,7 @@ pub async fn rate_limiting_middleware(
     State(rate_limiter): State<Arc<RateLimiter>>,
     request: Request,
     next: Next,
-) -> Result<Response, StatusCode> {
+) -> Result<Response, ApiError> {
     // Extract client identifier (IP address or user ID)
     let client_id = request
         .headers()
@@ -161,10 +181,51 @@ pub async fn rate_limiting_middleware(
         Ok(next.run(request).await)
     } else {
         warn!("Rate limit exceeded for client: {}", client_id);
-        Err(StatusCode::TOO_MANY_REQUESTS)
+        Err(ApiError::RateLimit)
     }
 }
 
+// The admission class of a request. Health probes must answer under load and
+// a WebSocket connection would hold its slot for its whole lifetime, so
+// neither is admitted
+pub fn request_class(method: &Method, path: &str) -> Option<RequestClass> {
+    if path.starts_with("/health") || path == "/ws" {
+        return None;
+    }
+    let Some(path) = path.strip_prefix("/api/v1") else {
+        return Some(RequestClass::InteractiveChat);
+    };
+    let bulk_write = method == Method::POST
+        && matches!(path, "/knowledge/documents" | "/plugins/install" | "/briefing/generate");
+    Some(if path.starts_with("/admin") {
+        RequestClass::Admin
+    } else if path.starts_with("/voice") {
+        RequestClass::Voice
+    } else if bulk_write || path.starts_with("/sync") {
+        RequestClass::Background
+    } else {
+        RequestClass::InteractiveChat
+    })
+}
+
+// Load shedding: holds an admission slot for the request's class while it
+// runs. Background requests are turned away at once when the budget is
+// spent; interactive ones queue briefly first
+pub async fn admission_middleware(
+    State(admission): State<Arc<AdmissionController>>,
+    request: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    let Some(class) = request_class(request.method(), request.uri().path()) else {
+        return Ok(next.run(request).await);
+    };
+    let _permit = admission.admit(class).await.map_err(|overloaded| {
+        warn!("Shedding {} request {} ({:?})", class.as_str(), request.uri(), overloaded.rejection);
+        ApiError::Overloaded { retry_after_secs: overloaded.retry_after.as_secs() }
+    })?;
+    Ok(next.run(request).await)
+}
+
 // Content-Type validation middleware
 pub async fn content_type_middleware(request: Request, next: Next) -> Result<Response, ApiError> {
     // Only validate content type for requests with body
@@ -218,51 +279,78 @@ pub async fn auth_middleware(
     next.run(request).await
 }
 
-// Security headers middleware
-pub async fn security_headers_middleware(request: Request, next: Next) -> Response {
+// Security headers middleware. Headers a handler already set are left alone,
+// so a route can still tighten them (the share pages send no-referrer)
+pub async fn security_headers_middleware(
+    State(security): State<Arc<SecurityHeaders>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let csp = security.csp_for(request.uri().path()).clone();
+    let tls = is_tls_request(request.uri(), request.headers());
+
     let mut response = next.run(request).await;
-    
     let headers = response.headers_mut();
-    
-    // Add security headers
-    headers.insert(
-        HeaderName::from_static("x-content-type-options"),
-        HeaderValue::from_static("nosniff"),
-    );
-    headers.insert(
-        HeaderName::from_static("x-frame-options"),
-        HeaderValue::from_static("DENY"),
-    );
-    headers.insert(
-        HeaderName::from_static("x-xss-protection"),
-        HeaderValue::from_static("1; mode=block"),
-    );
-    headers.insert(
-        HeaderName::from_static("strict-transport-security"),
-        HeaderValue::from_static("max-age=31536000; includeSubDomains"),
-    );
-    headers.insert(
-        HeaderName::from_static("referrer-policy"),
-        HeaderValue::from_static("strict-origin-when-cross-origin"),
-    );
+
+    headers.entry(header::CONTENT_SECURITY_POLICY).or_insert(csp);
+    headers
+        .entry(header::X_CONTENT_TYPE_OPTIONS)
+        .or_insert(HeaderValue::from_static("nosniff"));
+    headers
+        .entry(header::X_FRAME_OPTIONS)
+        .or_insert(HeaderValue::from_static("DENY"));
+    headers
+        .entry(header::REFERRER_POLICY)
+        .or_insert(security.referrer_policy().clone());
+    if tls {
+        headers
+            .entry(header::STRICT_TRANSPORT_SECURITY)
+            .or_insert(security.hsts().clone());
+    }
 
     response
 }
 
-// Error handling middleware
+// Error handling middleware. Rejections from axum's own extractors (a path
+// parameter that is not a uuid, a wrong method) are plain text; they are
+// rewritten into the response envelope so every error has the same shape
 pub async fn error_handling_middleware(request: Request, next: Next) -> Response {
     let response = next.run(request).await;
+    let status = response.status();
     
     // Log errors if status code indicates an error
-    if response.status().is_server_error() {
-        warn!("Server error response: {}", response.status());
-    } else if response.status().is_client_error() {
-        debug!("Client error response: {}", response.status());
+    if status.is_server_error() {
+        warn!("Server error response: {}", status);
+    } else if status.is_client_error() {
+        debug!("Client error response: {}", status);
     }
 
-    response
+    let plain = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map_or(true, |content_type| content_type.starts_with("text/plain"));
+    if !(status.is_client_error() || status.is_server_error()) || !plain {
+        return response;
+    }
+
+    let (parts, body) = response.into_parts();
+    let message = match axum::body::to_bytes(body, MAX_PLAIN_ERROR_BYTES).await {
+        Ok(bytes) if !bytes.is_empty() => String::from_utf8_lossy(&bytes).into_owned(),
+        _ => status.canonical_reason().unwrap_or("Request failed").to_string(),
+    };
+    let envelope = ApiResponse::<()>::error_with_code(ErrorCode::for_status(status.as_u16()), message);
+    let mut wrapped = (status, crate::create_response(envelope)).into_response();
+    for (name, value) in parts.headers.iter() {
+        if name != header::CONTENT_TYPE && name != header::CONTENT_LENGTH {
+            wrapped.headers_mut().append(name.clone(), value.clone());
+        }
+    }
+    wrapped
 }
 
+const MAX_PLAIN_ERROR_BYTES: usize = 64 * 1024;
+
 // Health check middleware - bypass authentication for health checks
 pub async fn health_check_bypass_middleware(request: Request, next: Next) -> Response {
     if request.uri().path() == "/health" || request.uri().path() == "/metrics" {
@@ -324,6 +412,78 @@ mod tests {
         assert!(limiter.check_rate_limit("test_client"));
     }
 
+    #[test]
+    fn test_request_classes() {
+        assert_eq!(request_class(&Method::POST, "/api/v1/conversation/chat"), Some(RequestClass::InteractiveChat));
+        assert_eq!(request_class(&Method::POST, "/api/v1/voice/synthesize"), Some(RequestClass::Voice));
+        assert_eq!(request_class(&Method::POST, "/api/v1/knowledge/documents"), Some(RequestClass::Background));
+        assert_eq!(request_class(&Method::GET, "/api/v1/knowledge/documents"), Some(RequestClass::InteractiveChat));
+        assert_eq!(request_class(&Method::GET, "/api/v1/sync"), Some(RequestClass::Background));
+        assert_eq!(request_class(&Method::PUT, "/api/v1/admin/flags/x"), Some(RequestClass::Admin));
+        assert_eq!(request_class(&Method::GET, "/health/ready"), None);
+    }
+
+    // Slow handlers stand in for embedding and synthesis work
+    fn saturated_app(admission: Arc<AdmissionController>) -> axum::Router {
+        use axum::routing::post;
+
+        let slow = || async {
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            axum::Json(serde_json::json!({"success": true}))
+        };
+        axum::Router::new()
+            .route("/api/v1/knowledge/documents", post(slow))
+            .route("/api/v1/conversation/chat", post(slow))
+            .layer(axum::middleware::from_fn_with_state(admission, admission_middleware))
+    }
+
+    #[tokio::test]
+    async fn test_interactive_requests_succeed_while_background_is_shed() {
+        use rusty_ai_core::admission::AdmissionConfig;
+
+        let admission = Arc::new(
+            AdmissionController::new(AdmissionConfig {
+                max_concurrent: 2,
+                reserved: [(RequestClass::InteractiveChat, 1)].into_iter().collect(),
+                weights: Default::default(),
+                queue_timeout_ms: 2_000,
+                retry_after_secs: 3,
+            })
+            .unwrap(),
+        );
+        let app = saturated_app(admission.clone());
+        let call = |uri: &'static str| {
+            let app = app.clone();
+            tokio::spawn(async move {
+                let request = axum::http::Request::builder().method(Method::POST).uri(uri);
+                send(app, request.body(axum::body::Body::empty()).unwrap()).await
+            })
+        };
+
+        let ingest: Vec<_> = (0..3).map(|_| call("/api/v1/knowledge/documents")).collect();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let chats: Vec<_> = (0..2).map(|_| call("/api/v1/conversation/chat")).collect();
+
+        let mut shed = 0;
+        for handle in ingest {
+            let response = handle.await.unwrap();
+            if response.status() == axum::http::StatusCode::SERVICE_UNAVAILABLE {
+                assert_eq!(response.headers()[header::RETRY_AFTER], "3");
+                shed += 1;
+            }
+        }
+        assert_eq!(shed, 1);
+        // One chat uses the reserved slot, the other queues until it frees
+        for handle in chats {
+            assert_eq!(handle.await.unwrap().status(), axum::http::StatusCode::OK);
+        }
+
+        let snapshot = admission.snapshot();
+        assert_eq!(snapshot.classes[&RequestClass::Background].shed, 1);
+        assert_eq!(snapshot.classes[&RequestClass::InteractiveChat].admitted, 2);
+        assert_eq!(snapshot.classes[&RequestClass::InteractiveChat].in_flight, 0);
+    }
+
     #[test]
     fn test_cors_configuration() {
         let config = ApiConfig {
@@ -335,4 +495,133 @@ mod tests {
         // Would need to test with actual requests in integration tests
         assert!(true); // Placeholder assertion
     }
+
+    fn secured_app(config: &ApiConfig) -> axum::Router {
+        use axum::routing::get;
+
+        let security = Arc::new(SecurityHeaders::new(&config.security_headers).unwrap());
+        axum::Router::new()
+            .route("/api/v1/tasks", get(|| async { axum::Json(serde_json::json!({"success": true})) }))
+            .route(
+                "/share/:token",
+                get(|| async {
+                    (
+                        [(header::REFERRER_POLICY, HeaderValue::from_static("no-referrer"))],
+                        axum::response::Html("<h1>Shared</h1>"),
+                    )
+                }),
+            )
+            .layer(axum::middleware::from_fn_with_state(security, security_headers_middleware))
+            .layer(cors_layer(config))
+    }
+
+    async fn send(app: axum::Router, request: axum::http::Request<axum::body::Body>) -> Response {
+        use tower::ServiceExt;
+        app.oneshot(request).await.unwrap()
+    }
+
+    fn get_request(uri: &str) -> axum::http::request::Builder {
+        axum::http::Request::builder().method(Method::GET).uri(uri)
+    }
+
+    #[tokio::test]
+    async fn test_api_routes_get_strict_security_headers() {
+        let config = ApiConfig::default();
+
+        let response = send(secured_app(&config), get_request("/api/v1/tasks").body(axum::body::Body::empty()).unwrap()).await;
+        let headers = response.headers();
+        assert_eq!(headers[header::CONTENT_SECURITY_POLICY], crate::security::DEFAULT_API_CSP);
+        assert_eq!(headers[header::X_CONTENT_TYPE_OPTIONS], "nosniff");
+        assert_eq!(headers[header::REFERRER_POLICY], "strict-origin-when-cross-origin");
+        // Plain HTTP must not pin the host to HTTPS
+        assert!(headers.get(header::STRICT_TRANSPORT_SECURITY).is_none());
+
+        let response = send(
+            secured_app(&config),
+            get_request("/api/v1/tasks")
+                .header("x-forwarded-proto", "https")
+                .body(axum::body::Body::empty())
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(
+            response.headers()[header::STRICT_TRANSPORT_SECURITY],
+            "max-age=31536000; includeSubDomains"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_share_pages_get_relaxed_policy() {
+        let config = ApiConfig::default();
+
+        let response = send(secured_app(&config), get_request("/share/abc123").body(axum::body::Body::empty()).unwrap()).await;
+        let headers = response.headers();
+        assert_eq!(headers[header::CONTENT_SECURITY_POLICY], crate::security::DEFAULT_SHARE_CSP);
+        assert_eq!(headers[header::X_CONTENT_TYPE_OPTIONS], "nosniff");
+        // The page's own stricter referrer policy wins
+        assert_eq!(headers[header::REFERRER_POLICY], "no-referrer");
+    }
+
+    #[tokio::test]
+    async fn test_preflight_for_allowed_and_disallowed_origins() {
+        let mut config = ApiConfig {
+            cors_origins: vec!["https://app.example.com".to_string()],
+            ..Default::default()
+        };
+        config.security_headers.cors_max_age_secs = 600;
+
+        let preflight = |origin: &str| {
+            axum::http::Request::builder()
+                .method(Method::OPTIONS)
+                .uri("/api/v1/tasks")
+                .header(header::ORIGIN, origin)
+                .header(header::ACCESS_CONTROL_REQUEST_METHOD, "POST")
+                .header(header::ACCESS_CONTROL_REQUEST_HEADERS, "authorization,content-type")
+                .body(axum::body::Body::empty())
+                .unwrap()
+        };
+
+        let response = send(secured_app(&config), preflight("https://app.example.com")).await;
+        assert!(response.status().is_success());
+        let headers = response.headers();
+        assert_eq!(headers[header::ACCESS_CONTROL_ALLOW_ORIGIN], "https://app.example.com");
+        assert_eq!(headers[header::ACCESS_CONTROL_MAX_AGE], "600");
+        let vary: Vec<&str> = headers
+            .get_all(header::VARY)
+            .iter()
+            .flat_map(|v| v.to_str().unwrap().split(','))
+            .map(str::trim)
+            .collect();
+        assert!(vary.iter().any(|v| v.eq_ignore_ascii_case("origin")));
+
+        let response = send(secured_app(&config), preflight("https://evil.example.net")).await;
+        assert!(response.headers().get(header::ACCESS_CONTROL_ALLOW_ORIGIN).is_none());
+
+        // Actual requests from an allowed origin also say the response varies by origin
+        let response = send(
+            secured_app(&config),
+            get_request("/api/v1/tasks")
+                .header(header::ORIGIN, "https://app.example.com")
+                .body(axum::body::Body::empty())
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(response.headers()[header::ACCESS_CONTROL_ALLOW_ORIGIN], "https://app.example.com");
+        assert!(response.headers().get(header::VARY).is_some());
+    }
+
+    #[test]
+    fn test_invalid_configuration_fails_validation() {
+        let mut config = ApiConfig::default();
+        config.security_headers.content_security_policy = "default-src 'self'; script-src 'unsafe-inlin'".to_string();
+        assert!(config.validate().is_err());
+
+        let config = ApiConfig {
+            cors_origins: vec!["app.example.com".to_string()],
+            ..Default::default()
+        };
+        assert!(config.validate().is_err());
+
+        assert!(ApiConfig::default().validate().is_ok());
+    }
 }
\ No newline at end of file
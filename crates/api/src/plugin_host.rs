@@ -0,0 +1,203 @@
+//! The core services behind the plugins' `rusty_ai` host imports, and the
+//! intent handler that lets conversations call plugins by capability.
+use async_trait::async_trait;
+use rusty_ai_common::{AssistantError, Document, Intent, PluginAuditRecord, PluginConfig, PluginSchedule, Result, UserContext};
+use rusty_ai_core::activity::{ActionKind, PerformedAction};
+use rusty_ai_core::intent_handlers::{HandlerOutcome, IntentHandler, IntentRequest};
+use rusty_ai_core::AssistantCore;
+use rusty_ai_plugins::host::{HostServices, KnowledgeSearch, PluginKvStore};
+use rusty_ai_plugins::scheduler::PluginScheduleStore;
+use rusty_ai_plugins::{CallOrigin, PluginAuditSink, PluginConfigStore, PluginContext, SecurityPolicy, WasmPluginManager};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Instant;
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+/// Host services over the core's storage, granted as far as `policy` allows
+pub fn host_services(core: Arc<AssistantCore>, policy: SecurityPolicy) -> HostServices {
+    HostServices {
+        kv: Some(Arc::new(StoragePluginKv(core.clone()))),
+        knowledge: Some(Arc::new(CoreKnowledgeSearch(core))),
+        policy,
+    }
+}
+
+/// Plugin key-value data kept by the core `Storage`
+struct StoragePluginKv(Arc<AssistantCore>);
+
+#[async_trait]
+impl PluginKvStore for StoragePluginKv {
+    async fn get(&self, plugin_id: &str, key: &str) -> Result<Option<Vec<u8>>> {
+        self.0.storage.get_plugin_value(plugin_id, key).await
+    }
+
+    async fn set(&self, plugin_id: &str, key: &str, value: Option<&[u8]>) -> Result<()> {
+        self.0.storage.store_plugin_value(plugin_id, key, value).await
+    }
+}
+
+/// Plugin calls recorded in the core `Storage`, pruned with its other data
+pub struct StoragePluginAudit(pub Arc<AssistantCore>);
+
+#[async_trait]
+impl PluginAuditSink for StoragePluginAudit {
+    async fn record(&self, record: PluginAuditRecord) -> Result<()> {
+        self.0.storage.store_plugin_audit(&record).await
+    }
+}
+
+/// Scheduled plugin jobs kept in the core `Storage`
+pub struct StoragePluginSchedules(pub Arc<AssistantCore>);
+
+#[async_trait]
+impl PluginScheduleStore for StoragePluginSchedules {
+    async fn save(&self, schedule: &PluginSchedule) -> Result<()> {
+        self.0.storage.store_plugin_schedule(schedule).await
+    }
+
+    async fn load(&self) -> Result<Vec<PluginSchedule>> {
+        self.0.storage.list_plugin_schedules().await
+    }
+
+    async fn remove(&self, id: Uuid) -> Result<bool> {
+        self.0.storage.delete_plugin_schedule(id).await
+    }
+}
+
+/// Plugin configuration kept in the core `Storage`
+pub struct StoragePluginConfigs(pub Arc<AssistantCore>);
+
+#[async_trait]
+impl PluginConfigStore for StoragePluginConfigs {
+    async fn save(&self, plugin_id: &str, config: &PluginConfig) -> Result<()> {
+        self.0.storage.store_plugin_config(plugin_id, config).await
+    }
+
+    async fn load(&self, plugin_id: &str) -> Result<Option<PluginConfig>> {
+        self.0.storage.get_plugin_config(plugin_id).await
+    }
+}
+
+/// The same search as `GET /knowledge/search`; documents a plugin reads count
+/// as looked up, as they do for the user's own searches
+struct CoreKnowledgeSearch(Arc<AssistantCore>);
+
+#[async_trait]
+impl KnowledgeSearch for CoreKnowledgeSearch {
+    async fn search(&self, user_id: &str, query: &str, limit: usize) -> Result<Vec<Document>> {
+        debug!("Plugin knowledge search for user {}", user_id);
+        let documents = self.0.storage.search_documents(query, limit).await?;
+        for doc in &documents {
+            if let Err(e) = self.0.storage.record_document_access(doc.id).await {
+                warn!("Failed to record access to document {}: {}", doc.id, e);
+            }
+        }
+        Ok(documents)
+    }
+}
+
+/// Answers command intents with the WASM plugin that declares the command's
+/// capability, best `PluginConfig.priority` first (see
+/// `WasmPluginManager::find_plugins_for_capability`). A plugin that fails
+/// hands the command to the next one; with none left, or none declaring the
+/// capability, the command falls through to the remaining handlers
+pub struct CapabilityDispatchHandler {
+    plugins: Arc<WasmPluginManager>,
+}
+
+impl CapabilityDispatchHandler {
+    pub fn new(plugins: Arc<WasmPluginManager>) -> Self {
+        Self { plugins }
+    }
+}
+
+#[async_trait]
+impl IntentHandler for CapabilityDispatchHandler {
+    fn name(&self) -> &str {
+        "plugin_capabilities"
+    }
+
+    fn can_handle(&self, request: &IntentRequest) -> bool {
+        matches!(request.intent, Intent::Command { .. })
+    }
+
+    async fn handle(&self, request: &IntentRequest, context: &UserContext) -> Result<Option<HandlerOutcome>> {
+        let Intent::Command { action, parameters } = &request.intent else { return Ok(None) };
+        let capability = capability_for(action);
+        let input = serde_json::json!({
+            "action": action,
+            "parameters": parameters,
+            "entities": request.entities,
+            "message": request.message,
+        });
+        // Chat may only reach functions the plugin marks public, and only
+        // with the permission every signed-in user holds
+        let plugin_context = PluginContext {
+            user_id: context.user_id.to_string(),
+            session_id: context.session_id.to_string(),
+            request_id: Uuid::new_v4().to_string(),
+            metadata: HashMap::new(),
+            started_at: Instant::now(),
+            permissions: vec![self.plugins.permission_policy().baseline_permission],
+            origin: CallOrigin::Conversation,
+        };
+
+        let dispatch = match self.plugins.dispatch_capability(&capability, input.to_string().as_bytes(), plugin_context).await {
+            Ok(dispatch) => dispatch,
+            Err(AssistantError::NotFound(_)) => return Ok(None),
+            Err(e) => {
+                warn!("Falling back from plugins for '{}': {}", capability, e);
+                return Ok(None);
+            }
+        };
+        let outcome = HandlerOutcome::text(reply_text(&dispatch.output)).with_performed(
+            PerformedAction::new(ActionKind::PluginCommand, format!("Ran '{}' with {}", capability, dispatch.plugin_id))
+                .resource(&dispatch.plugin_id),
+        );
+        Ok(Some(outcome))
+    }
+}
+
+/// The capability a command asks for: `Weather lookup` and `weather-lookup`
+/// both become `weather_lookup`
+fn capability_for(action: &str) -> String {
+    action
+        .trim()
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("_")
+}
+
+// What a plugin's output adds to the reply: a JSON string, or the
+// `response` or `message` field of an object; other JSON is shown as is
+fn reply_text(output: &[u8]) -> String {
+    match serde_json::from_slice::<serde_json::Value>(output) {
+        Ok(serde_json::Value::String(text)) => text,
+        Ok(value) => ["response", "message"]
+            .iter()
+            .find_map(|field| value.get(field).and_then(|v| v.as_str()).map(str::to_string))
+            .unwrap_or_else(|| value.to_string()),
+        Err(_) => String::from_utf8_lossy(output).into_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_commands_map_to_capabilities_and_outputs_to_text() {
+        assert_eq!(capability_for("weather_lookup"), "weather_lookup");
+        assert_eq!(capability_for(" Weather lookup"), "weather_lookup");
+        assert_eq!(capability_for("weather-lookup!"), "weather_lookup");
+
+        assert_eq!(reply_text(br#""Sunny""#), "Sunny");
+        assert_eq!(reply_text(br#"{"response":"Sunny","temperature":21}"#), "Sunny");
+        assert_eq!(reply_text(br#"{"message":"Hello"}"#), "Hello");
+        assert_eq!(reply_text(br#"{"echo":"hi"}"#), r#"{"echo":"hi"}"#);
+        assert_eq!(reply_text(b"plain text"), "plain text");
+    }
+}
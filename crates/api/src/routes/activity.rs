@@ -0,0 +1,174 @@
+use crate::{
+    auth::AuthenticatedUser,
+    create_paginated_response, create_success_response,
+    error::{ApiError, ApiResult},
+    validation::ValidQuery,
+};
+use axum::{
+    extract::{Path, State},
+    routing::{get, post},
+    Json, Router,
+};
+use rusty_ai_common::Pagination;
+use rusty_ai_core::activity::{ActivityEntry, ActivityFilter};
+use rusty_ai_core::AssistantCore;
+use std::sync::Arc;
+use uuid::Uuid;
+
+pub fn routes(core: Arc<AssistantCore>) -> Router {
+    Router::new()
+        .route("/", get(list_activity))
+        .route("/:id/undo", post(undo_action))
+        .with_state(core)
+}
+
+// What the assistant did on the caller's behalf, newest first. Filters by
+// kind, source, status, session and time; pages with `offset` and `limit`
+async fn list_activity(
+    State(core): State<Arc<AssistantCore>>,
+    user: AuthenticatedUser,
+    ValidQuery(filter): ValidQuery<ActivityFilter>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let page = core
+        .activity
+        .list(user.claims.user_id, &filter)
+        .await
+        .map_err(ApiError::CoreService)?;
+    // The cursor is the offset of the next page
+    let next_cursor = page.has_more.then(|| (page.offset + page.actions.len()).to_string());
+    Ok(create_paginated_response(page, Pagination::new(None, next_cursor)))
+}
+
+// Take an action back: delete the task it created, restore the settings it
+// changed. Actions without an undo, failed ones and undone ones conflict
+async fn undo_action(
+    State(core): State<Arc<AssistantCore>>,
+    Path(id): Path<Uuid>,
+    user: AuthenticatedUser,
+) -> ApiResult<Json<serde_json::Value>> {
+    let action = core.activity.get(user.claims.user_id, id).await.map_err(ApiError::CoreService)?;
+    if !action.can_undo() {
+        return Err(ApiError::Conflict(format!("Action {} cannot be undone", id)));
+    }
+
+    let action = core.activity.undo(action).await.map_err(ApiError::CoreService)?;
+    Ok(create_success_response(ActivityEntry::from(action)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::routes::conversation;
+    use crate::validation::tests::{send, violations};
+    use axum::{async_trait, http::StatusCode};
+    use rusty_ai_common::{Result, Task, TaskPriority, TaskStatus, UserContext};
+    use rusty_ai_core::activity::{ActionKind, PerformedAction, UndoStep};
+    use rusty_ai_core::intent_handlers::{HandlerOutcome, IntentHandler, IntentRequest};
+    use rusty_ai_core::storage::Storage;
+    use rusty_ai_core::CoreConfig;
+
+    // Stands in for a tool call: books a table by creating a task
+    struct BookingTool {
+        storage: Arc<dyn Storage + Send + Sync>,
+    }
+
+    #[async_trait]
+    impl IntentHandler for BookingTool {
+        fn name(&self) -> &str {
+            "booking_tool"
+        }
+
+        fn can_handle(&self, request: &IntentRequest) -> bool {
+            request.text().contains("book a table")
+        }
+
+        async fn handle(&self, _request: &IntentRequest, _context: &UserContext) -> Result<Option<HandlerOutcome>> {
+            let now = chrono::Utc::now();
+            let task = Task {
+                id: Uuid::new_v4(),
+                name: "Confirm table at Figlmüller".to_string(),
+                description: String::new(),
+                status: TaskStatus::Pending,
+                priority: TaskPriority::Medium,
+                due_date: None,
+                tags: vec!["booking".to_string()],
+                created_at: now,
+                updated_at: now,
+            };
+            self.storage.store_task(&task).await?;
+
+            Ok(Some(HandlerOutcome::text("Booked, and added a task to confirm it.").with_performed(
+                PerformedAction::new(ActionKind::TaskCreated, "Created the task 'Confirm table at Figlmüller'")
+                    .resource(task.id)
+                    .undo(UndoStep::DeleteTask { task_id: task.id }),
+            )))
+        }
+    }
+
+    async fn create_test_router() -> (Arc<AssistantCore>, Router) {
+        let core = Arc::new(AssistantCore::new(CoreConfig::default()).await.unwrap());
+        core.orchestrator
+            .handlers()
+            .register(1000, Arc::new(BookingTool { storage: core.storage.clone() }));
+        let router = Router::new()
+            .nest("/conversation", conversation::routes(core.clone()))
+            .nest("/me/activity", routes(core.clone()));
+        (core, router)
+    }
+
+    #[tokio::test]
+    async fn test_tool_call_is_logged_and_can_be_undone() {
+        let (core, router) = create_test_router().await;
+
+        let (status, chat) = send(
+            router.clone(),
+            "POST",
+            "/conversation/chat",
+            Some(serde_json::json!({ "message": "Please book a table for two tonight" })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let action_ids = chat["data"]["action_ids"].as_array().unwrap().clone();
+        assert_eq!(action_ids.len(), 1);
+        let action_id = action_ids[0].as_str().unwrap().to_string();
+        let session_id = chat["data"]["session_id"].as_str().unwrap().to_string();
+
+        // The feed shows the action, tied to the message that caused it
+        let uri = format!("/me/activity?session_id={}&kind=task_created", session_id);
+        let (status, feed) = send(router.clone(), "GET", &uri, None).await;
+        assert_eq!(status, StatusCode::OK);
+        let actions = feed["data"]["actions"].as_array().unwrap();
+        assert_eq!(actions.len(), 1);
+        assert_eq!(actions[0]["id"], action_id.as_str());
+        assert_eq!(actions[0]["status"], "succeeded");
+        assert_eq!(actions[0]["undoable"], true);
+        assert_eq!(actions[0]["trigger"]["message_id"], chat["data"]["conversation_id"]);
+        let task_id: Uuid = actions[0]["resource_id"].as_str().unwrap().parse().unwrap();
+        assert!(core.storage.get_task(task_id).await.unwrap().is_some());
+
+        let uri = format!("/me/activity?session_id={}&kind=notification_sent", session_id);
+        let (_, feed) = send(router.clone(), "GET", &uri, None).await;
+        assert!(feed["data"]["actions"].as_array().unwrap().is_empty());
+
+        // Undo deletes the task, and works once
+        let uri = format!("/me/activity/{}/undo", action_id);
+        let (status, undone) = send(router.clone(), "POST", &uri, None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(undone["data"]["status"], "undone");
+        assert_eq!(undone["data"]["undoable"], false);
+        assert!(core.storage.get_task(task_id).await.unwrap().is_none());
+
+        let (status, _) = send(router.clone(), "POST", &uri, None).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        let uri = format!("/me/activity/{}/undo", Uuid::new_v4());
+        let (status, _) = send(router.clone(), "POST", &uri, None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (status, body) = send(router, "GET", "/me/activity?limit=0&source=cron", None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let fields = violations(&body);
+        assert!(fields.contains(&("limit".to_string(), "range".to_string())));
+        assert!(fields.contains(&("source".to_string(), "one_of".to_string())));
+    }
+}
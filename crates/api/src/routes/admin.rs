@@ -0,0 +1,243 @@
+use crate::{
+    audit::AdminAction,
+    auth::{AuthService, AuthenticatedUser},
+    create_success_response,
+    error::{authz_error, ApiError, ApiResult},
+    validation::ValidJson,
+};
+use axum::{
+    extract::{Path, Query, State},
+    routing::{get, post, put},
+    Extension, Json, Router,
+};
+use rusty_ai_common::{AssistantError, PluginAuditQuery};
+use rusty_ai_core::audit::AuditFilter;
+use rusty_ai_core::flags::{Flag, FlagOverride};
+use rusty_ai_core::AssistantCore;
+use serde::Deserialize;
+use std::sync::Arc;
+
+pub fn routes(core: Arc<AssistantCore>) -> Router {
+    Router::new()
+        .route("/resources", get(get_resources))
+        .route("/resources/trim", post(trim_resources))
+        .route("/storage/slow-queries", get(get_slow_queries))
+        .route("/audit", get(get_audit_entries))
+        .route("/plugin-calls", get(get_plugin_calls))
+        .route("/flags", get(get_flags))
+        .route("/flags/:flag", put(set_flag).delete(reset_flag))
+        .with_state(core)
+}
+
+#[derive(Debug, Deserialize)]
+struct TrimQuery {
+    target: String,
+}
+
+fn require_admin(auth_service: &AuthService, user: &AuthenticatedUser) -> ApiResult<()> {
+    if !auth_service.has_permission(&user.claims, "admin") {
+        return Err(authz_error("Resource administration requires admin permission"));
+    }
+    Ok(())
+}
+
+// Current per-component breakdown and process RSS, the recorded history, and
+// how much of the request budget each class is using
+async fn get_resources(
+    State(core): State<Arc<AssistantCore>>,
+    Extension(auth_service): Extension<Arc<AuthService>>,
+    user: AuthenticatedUser,
+) -> ApiResult<Json<serde_json::Value>> {
+    require_admin(&auth_service, &user)?;
+
+    let current = core.resources.snapshot().await;
+    Ok(create_success_response(serde_json::json!({
+        "current": current,
+        "history": core.resources.history(),
+        "admission": core.admission.snapshot(),
+    })))
+}
+
+async fn trim_resources(
+    State(core): State<Arc<AssistantCore>>,
+    admin: AdminAction,
+    Query(query): Query<TrimQuery>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let result = core.resources.trim(&query.target).await?;
+
+    admin.record(
+        "resources.trim",
+        &query.target,
+        None,
+        serde_json::to_value(&result).ok(),
+    );
+    Ok(create_success_response(result))
+}
+
+// Admin actions, newest first. `write_failures` counts entries that could not
+// be stored, so gaps in the trail are visible
+async fn get_audit_entries(
+    State(core): State<Arc<AssistantCore>>,
+    Extension(auth_service): Extension<Arc<AuthService>>,
+    user: AuthenticatedUser,
+    Query(filter): Query<AuditFilter>,
+) -> ApiResult<Json<serde_json::Value>> {
+    require_admin(&auth_service, &user)?;
+
+    let entries = core.audit.query(&filter).await?;
+    Ok(create_success_response(serde_json::json!({
+        "entries": entries,
+        "write_failures": core.audit.write_failures(),
+    })))
+}
+
+// Recorded plugin calls, newest first, of one plugin with `plugin_id` and
+// within `from`..`to` when given
+async fn get_plugin_calls(
+    State(core): State<Arc<AssistantCore>>,
+    Extension(auth_service): Extension<Arc<AuthService>>,
+    user: AuthenticatedUser,
+    Query(query): Query<PluginAuditQuery>,
+) -> ApiResult<Json<serde_json::Value>> {
+    require_admin(&auth_service, &user)?;
+
+    let calls = core.storage.query_plugin_audits(&query).await?;
+    Ok(create_success_response(serde_json::json!({"calls": calls})))
+}
+
+// Each flag with its configured default and runtime override
+async fn get_flags(
+    State(core): State<Arc<AssistantCore>>,
+    Extension(auth_service): Extension<Arc<AuthService>>,
+    user: AuthenticatedUser,
+) -> ApiResult<Json<serde_json::Value>> {
+    require_admin(&auth_service, &user)?;
+    Ok(create_success_response(core.flags.statuses()))
+}
+
+fn parse_flag(name: &str) -> ApiResult<Flag> {
+    Flag::parse(name)
+        .ok_or_else(|| ApiError::CoreService(AssistantError::NotFound(format!("Unknown feature flag '{}'", name))))
+}
+
+// Replace the flag's override; evaluation picks it up immediately
+async fn set_flag(
+    State(core): State<Arc<AssistantCore>>,
+    admin: AdminAction,
+    Path(name): Path<String>,
+    ValidJson(mut flag_override): ValidJson<FlagOverride>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let flag = parse_flag(&name)?;
+    flag_override.updated_by = Some(admin.user().claims.user_id.to_string());
+    flag_override.updated_at = Some(chrono::Utc::now());
+
+    let before = core.flags.status(flag).flag_override;
+    let status = core.flags.set(flag, Some(flag_override)).await?;
+    admin.record(
+        "flags.update",
+        flag.as_str(),
+        serde_json::to_value(&before).ok(),
+        serde_json::to_value(&status.flag_override).ok(),
+    );
+    Ok(create_success_response(status))
+}
+
+// Drop the override so the configured default applies again
+async fn reset_flag(
+    State(core): State<Arc<AssistantCore>>,
+    admin: AdminAction,
+    Path(name): Path<String>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let flag = parse_flag(&name)?;
+    let before = core.flags.status(flag).flag_override;
+    let status = core.flags.set(flag, None).await?;
+    admin.record("flags.reset", flag.as_str(), serde_json::to_value(&before).ok(), None);
+    Ok(create_success_response(status))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audit::tests::{admin_action, audit_trail};
+    use crate::auth::AuthConfig;
+    use crate::routes::tests::user;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_resources_require_admin() {
+        let auth_service = AuthService::new(AuthConfig::default());
+        assert!(require_admin(&auth_service, &user(Uuid::new_v4(), &["read", "write"])).is_err());
+        assert!(require_admin(&auth_service, &user(Uuid::new_v4(), &["admin"])).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_flag_changes_apply_at_once_and_are_audited() {
+        let core = Arc::new(AssistantCore::new(rusty_ai_core::CoreConfig::default()).await.unwrap());
+        let trail = audit_trail().await;
+        let tester = Uuid::new_v4();
+        assert!(!core.flags.enabled(Flag::HybridSearch, tester));
+
+        let flag_override = FlagOverride { users: [(tester, true)].into(), ..Default::default() };
+        let admin = admin_action(&trail, "PUT /api/v1/admin/flags/:flag");
+        set_flag(State(core.clone()), admin, Path("hybrid_search".to_string()), ValidJson(flag_override))
+            .await
+            .unwrap();
+        assert!(core.flags.enabled(Flag::HybridSearch, tester));
+        assert!(!core.flags.enabled(Flag::HybridSearch, Uuid::new_v4()));
+
+        let admin = admin_action(&trail, "DELETE /api/v1/admin/flags/:flag");
+        reset_flag(State(core.clone()), admin, Path("hybrid_search".to_string())).await.unwrap();
+        assert!(!core.flags.enabled(Flag::HybridSearch, tester));
+
+        let admin = admin_action(&trail, "PUT /api/v1/admin/flags/:flag");
+        let unknown = set_flag(State(core.clone()), admin, Path("warp_drive".to_string()), ValidJson(FlagOverride::default()));
+        assert!(unknown.await.is_err());
+        trail.flush().await;
+
+        let updates = trail
+            .query(&AuditFilter { action: Some("flags.update".to_string()), ..Default::default() })
+            .await
+            .unwrap();
+        assert_eq!(updates.len(), 1);
+        assert_eq!(updates[0].target, "hybrid_search");
+        assert_eq!(updates[0].after.as_ref().unwrap()["users"][tester.to_string()], true);
+        let resets = trail
+            .query(&AuditFilter { action: Some("flags.reset".to_string()), ..Default::default() })
+            .await
+            .unwrap();
+        assert_eq!(resets[0].before.as_ref().unwrap()["updated_by"], updates[0].actor.as_str());
+    }
+
+    #[tokio::test]
+    async fn test_core_reports_session_store() {
+        let core = AssistantCore::new(rusty_ai_core::CoreConfig::default()).await.unwrap();
+        let snapshot = core.resources.snapshot().await;
+        assert!(snapshot.components["session_store"].trimmable);
+
+        let result = core.resources.trim("session_store").await.unwrap();
+        assert_eq!(result.entries_removed, 0);
+    }
+}
+
+// The slowest storage queries seen, slowest first, with per-operation timings
+async fn get_slow_queries(
+    State(core): State<Arc<AssistantCore>>,
+    Extension(auth_service): Extension<Arc<AuthService>>,
+    user: AuthenticatedUser,
+) -> ApiResult<Json<serde_json::Value>> {
+    require_admin(&auth_service, &user)?;
+
+    let body = match core.storage.query_metrics() {
+        Some(metrics) => serde_json::json!({
+            "enabled": true,
+            "slow_queries": metrics.slow_queries(),
+            "operations": metrics.operations(),
+        }),
+        None => serde_json::json!({
+            "enabled": false,
+            "slow_queries": [],
+            "operations": {},
+        }),
+    };
+    Ok(create_success_response(body))
+}
@@ -8,20 +8,10 @@ use axum::{
     routing::post,
     Json, Router,
 };
-use serde::{Deserialize, Serialize};
+use rusty_ai_common::api::{LogoutRequest, LogoutResponse, ValidateTokenRequest, ValidateTokenResponse};
 use std::sync::Arc;
 use tracing::{debug, info, warn};
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct LogoutRequest {
-    pub token: String,
-}
-
-#[derive(Debug, Serialize)]
-pub struct LogoutResponse {
-    pub message: String,
-}
-
 pub fn routes(auth_service: Arc<AuthService>) -> Router {
     Router::new()
         .route("/login", post(login))
@@ -147,20 +137,6 @@ async fn validate_token(
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct ValidateTokenRequest {
-    pub token: String,
-}
-
-#[derive(Debug, Serialize)]
-pub struct ValidateTokenResponse {
-    pub valid: bool,
-    pub user_id: uuid::Uuid,
-    pub email: String,
-    pub expires_at: i64,
-    pub permissions: Vec<String>,
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
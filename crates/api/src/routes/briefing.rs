@@ -1,7 +1,9 @@
-use crate::{auth::AuthenticatedUser, create_success_response, error::ApiResult};
-use axum::{extract::{Path, State}, routing::{get, post}, Json, Router};
-use rusty_ai_core::AssistantCore;
+use crate::{auth::{AuthService, AuthenticatedUser}, create_success_response, error::ApiResult};
+use axum::{extract::{Path, State}, routing::{get, post}, Extension, Json, Router};
+use rusty_ai_common::{api::CreateShareLinkRequest, NotificationCategory};
+use rusty_ai_core::{events::AssistantEvent, notifications::Notification, sharing::SharedResource, AssistantCore};
 use std::sync::Arc;
+use tracing::warn;
 use uuid::Uuid;
 
 pub fn routes(core: Arc<AssistantCore>) -> Router {
@@ -9,6 +11,7 @@ pub fn routes(core: Arc<AssistantCore>) -> Router {
         .route("/today", get(get_today_briefing))
         .route("/generate", post(generate_briefing))
         .route("/:id", get(get_briefing))
+        .route("/:id/share", post(share_briefing))
         .route("/history", get(get_briefing_history))
         .with_state(core)
 }
@@ -43,15 +46,31 @@ async fn generate_briefing(
                 enabled: false,
                 channels: vec![],
                 quiet_hours: None,
+                routing: Default::default(),
+                knowledge_digest: Default::default(),
             },
         },
         active_plugins: vec![],
-        conversation_history: vec![],
+        conversation_history: Default::default(),
     };
     
     let briefing = core.briefing_generator
         .generate_daily_briefing(chrono::Utc::now(), &user_context).await
         .map_err(|e| crate::error::ApiError::CoreService(e))?;
+
+    let notification = Notification::new(
+        user.claims.user_id,
+        NotificationCategory::Briefing,
+        "Your daily briefing is ready",
+        format!("{} sections", briefing.sections.len()),
+    );
+    if let Err(e) = core.notification_router.route(&user_context.preferences, notification).await {
+        warn!("Failed to route briefing notification: {}", e);
+    }
+    core.events.publish(AssistantEvent::BriefingGenerated {
+        user_id: Some(user.claims.user_id),
+        briefing: Arc::new(briefing.clone()),
+    });
     
     Ok(create_success_response(briefing))
 }
@@ -72,6 +91,17 @@ async fn get_briefing(
     }
 }
 
+async fn share_briefing(
+    State(core): State<Arc<AssistantCore>>,
+    Extension(auth_service): Extension<Arc<AuthService>>,
+    Path(id): Path<Uuid>,
+    user: AuthenticatedUser,
+    request: Option<Json<CreateShareLinkRequest>>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let request = request.map(|Json(r)| r).unwrap_or_default();
+    super::share::create_share_link(&core, &auth_service, &user, SharedResource::Briefing(id), request).await
+}
+
 async fn get_briefing_history(
     State(core): State<Arc<AssistantCore>>,
     _user: AuthenticatedUser,
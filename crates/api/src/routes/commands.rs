@@ -0,0 +1,315 @@
+use crate::{
+    auth::{AuthService, AuthenticatedUser},
+    create_success_response,
+    error::{authz_error, ApiError, ApiResult},
+    routes::conversation::{complete_turn, session_for},
+    validation::{check_command_parameters, FieldErrors, ValidJson},
+};
+use axum::{
+    extract::{Path, State},
+    routing::{get, post},
+    Extension, Json, Router,
+};
+use rusty_ai_common::api::{CommandDescriptor, ExecuteCommandRequest};
+use rusty_ai_common::{AssistantError, Intent, TurnKind};
+use rusty_ai_core::{intent_handlers::IntentRequest, AssistantCore};
+use rusty_ai_plugins::{CallOrigin, PluginContext, PluginMarketplace};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tracing::{debug, info};
+use uuid::Uuid;
+
+// Handler commands need the core; plugin capabilities also need the
+// marketplace for their declared schemas and permission policy
+#[derive(Clone)]
+struct CommandsState {
+    core: Arc<AssistantCore>,
+    marketplace: Arc<PluginMarketplace>,
+}
+
+/// The command palette, mounted under `/api/v1/commands`
+pub fn routes(core: Arc<AssistantCore>, marketplace: Arc<PluginMarketplace>) -> Router {
+    Router::new()
+        .route("/", get(list_commands))
+        .route("/:name", post(execute_command))
+        .with_state(CommandsState { core, marketplace })
+}
+
+// Every command the palette can run. Handler commands come first; a plugin
+// capability with the same name as one of them is not listed
+async fn available_commands(state: &CommandsState) -> Vec<CommandDescriptor> {
+    let mut commands: Vec<CommandDescriptor> = state
+        .core
+        .orchestrator
+        .handlers()
+        .commands()
+        .into_iter()
+        .map(|command| CommandDescriptor {
+            parameters: command.input_schema(),
+            name: command.name,
+            description: command.description,
+            required_permission: command.required_permission,
+            plugin_id: None,
+        })
+        .collect();
+
+    let manager = state.marketplace.manager();
+    let policy = manager.permission_policy();
+    for plugin in state.core.plugin_manager.get_active_plugins().await {
+        let metadata = plugin.metadata();
+        let schemas = manager.get_function_schemas(&metadata.id).await;
+        for capability in &metadata.capabilities {
+            if commands.iter().any(|c| &c.name == capability) {
+                continue;
+            }
+            let schema = schemas.iter().find(|s| &s.name == capability);
+            commands.push(CommandDescriptor {
+                name: capability.clone(),
+                description: schema
+                    .map(|s| s.description.clone())
+                    .filter(|d| !d.is_empty())
+                    .unwrap_or_else(|| format!("{} ({})", capability, metadata.name)),
+                parameters: schema
+                    .and_then(|s| s.input_schema.clone())
+                    .unwrap_or_else(|| serde_json::json!({ "type": "object" })),
+                required_permission: Some(
+                    schema
+                        .and_then(|s| s.required_permission.clone())
+                        .unwrap_or_else(|| policy.baseline_permission.clone()),
+                ),
+                plugin_id: Some(metadata.id.clone()),
+            });
+        }
+    }
+    commands
+}
+
+// Listed regardless of the caller's permissions, so clients can show what
+// needs more access
+async fn list_commands(
+    State(state): State<CommandsState>,
+    _user: AuthenticatedUser,
+) -> ApiResult<Json<serde_json::Value>> {
+    let commands = available_commands(&state).await;
+    Ok(create_success_response(serde_json::json!({ "commands": commands })))
+}
+
+// Runs a command without classification or the model. The answer goes
+// through the same post-processing, history and activity log as a chat
+// message, with the turn marked as a command
+async fn execute_command(
+    State(state): State<CommandsState>,
+    Extension(auth_service): Extension<Arc<AuthService>>,
+    Path(name): Path<String>,
+    user: AuthenticatedUser,
+    ValidJson(request): ValidJson<ExecuteCommandRequest>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let start_time = std::time::Instant::now();
+    let core = &state.core;
+
+    let command = available_commands(&state)
+        .await
+        .into_iter()
+        .find(|c| c.name == name)
+        .ok_or_else(|| ApiError::CoreService(AssistantError::NotFound(format!("Command /{} not found", name))))?;
+
+    // Plugin calls are checked, and audited, by the plugin permission policy
+    match &command.plugin_id {
+        Some(plugin_id) => {
+            let context = PluginContext {
+                user_id: user.claims.user_id.to_string(),
+                session_id: request.session_id.map(|id| id.to_string()).unwrap_or_default(),
+                request_id: Uuid::new_v4().to_string(),
+                metadata: HashMap::new(),
+                started_at: std::time::Instant::now(),
+                permissions: user.claims.permissions.clone(),
+                origin: CallOrigin::Api,
+            };
+            let decision = state.marketplace.manager().check_permission(plugin_id, &command.name, &context).await;
+            if !decision.allowed {
+                return Err(authz_error(&decision.reason));
+            }
+        }
+        None => {
+            if let Some(permission) = &command.required_permission {
+                if !auth_service.has_permission(&user.claims, permission) {
+                    return Err(authz_error(&format!("/{} requires the '{}' permission", command.name, permission)));
+                }
+            }
+        }
+    }
+
+    let mut errors = FieldErrors::default();
+    check_command_parameters(&mut errors, &command.parameters, &request.parameters);
+    errors.into_result()?;
+
+    let session_id = session_for(core, user.claims.user_id, request.session_id).await?;
+    let user_context = core
+        .context_manager
+        .read()
+        .await
+        .get_user_context(session_id)
+        .await
+        .map_err(|e| ApiError::CoreService(e))?
+        .clone();
+
+    debug!("Command /{} from user {}", command.name, user.claims.user_id);
+    let (intent, outcome) = match &command.plugin_id {
+        None => {
+            let parameters = request
+                .parameters
+                .iter()
+                .filter(|(_, value)| !value.is_null())
+                .map(|(key, value)| (key.clone(), parameter_text(value)))
+                .collect();
+            let (handled, outcome) = core
+                .orchestrator
+                .handlers()
+                .run_command(&command.name, parameters, &user_context)
+                .await
+                .map_err(|e| ApiError::CoreService(e))?
+                .ok_or_else(|| ApiError::CoreService(AssistantError::NotFound(format!("Command /{} not found", name))))?;
+            (handled.intent, outcome)
+        }
+        // Plugins receive the parameters object as the command's one parameter
+        Some(_) => {
+            let intent = Intent::Command {
+                action: command.name.clone(),
+                parameters: vec![serde_json::Value::Object(request.parameters.clone()).to_string()],
+            };
+            let handled = IntentRequest { intent: intent.clone(), entities: HashMap::new(), message: None };
+            let outcome = core
+                .orchestrator
+                .handle(&handled, &user_context)
+                .await
+                .map_err(|e| ApiError::CoreService(e))?;
+            (intent, outcome)
+        }
+    };
+
+    let command_line = command_line(&command.name, &request.parameters);
+    let response = complete_turn(core, &user_context, command_line, TurnKind::Command, intent, outcome, start_time, None).await?;
+
+    info!(
+        "Command /{} answered for user {} in {}ms",
+        command.name, user.claims.user_id, response.processing_time_ms
+    );
+    Ok(create_success_response(response))
+}
+
+fn parameter_text(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(text) => text.trim().to_string(),
+        other => other.to_string(),
+    }
+}
+
+// How the command appears in the history, e.g. `/task name="buy milk" at=09:30`
+fn command_line(name: &str, parameters: &serde_json::Map<String, serde_json::Value>) -> String {
+    let mut line = format!("/{}", name);
+    for (key, value) in parameters {
+        let text = match value {
+            serde_json::Value::String(text) if !text.is_empty() && !text.contains(char::is_whitespace) => text.clone(),
+            other => other.to_string(),
+        };
+        line.push_str(&format!(" {}={}", key, text));
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::routes::conversation;
+    use crate::routes::tests::{core, marketplace};
+    use crate::validation::tests::{send, violations};
+    use axum::http::StatusCode;
+    use rusty_ai_core::activity::AssistantAction;
+
+    async fn app(dir: &tempfile::TempDir) -> (Arc<AssistantCore>, Router) {
+        let core = core(dir).await;
+        let router = Router::new()
+            .nest("/conversation", conversation::routes(core.clone()))
+            .nest("/commands", routes(core.clone(), marketplace(dir)));
+        (core, router)
+    }
+
+    // The activity entry recorded for a chat or command response
+    async fn performed(core: &AssistantCore, response: &serde_json::Value) -> AssistantAction {
+        let session_id: Uuid = serde_json::from_value(response["session_id"].clone()).unwrap();
+        let action_id: Uuid = serde_json::from_value(response["action_ids"][0].clone()).unwrap();
+        let user_id = core.context_manager.read().await.get_session(session_id).await.unwrap().user_id;
+        core.activity.get(user_id, action_id).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_task_command_answers_like_the_chat_message() {
+        let dir = tempfile::tempdir().unwrap();
+        let (core, router) = app(&dir).await;
+
+        let (status, listed) = send(router.clone(), "GET", "/commands", None).await;
+        assert_eq!(status, StatusCode::OK);
+        let task = listed["data"]["commands"].as_array().unwrap().iter().find(|c| c["name"] == "task").cloned().unwrap();
+        assert_eq!(task["parameters"]["required"], serde_json::json!(["name"]));
+        assert_eq!(task["required_permission"], "write");
+
+        let (status, chat) = send(
+            router.clone(),
+            "POST",
+            "/conversation/chat",
+            Some(serde_json::json!({ "message": "create task buy milk" })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, command) = send(
+            router.clone(),
+            "POST",
+            "/commands/task",
+            Some(serde_json::json!({ "parameters": { "name": "buy milk" } })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (chat, command) = (&chat["data"], &command["data"]);
+        assert_eq!(command["response"], chat["response"]);
+        assert_eq!(command["suggested_actions"], chat["suggested_actions"]);
+        assert_eq!(command["action_ids"].as_array().unwrap().len(), 1);
+
+        let chat_action = performed(&core, chat).await;
+        let command_action = performed(&core, command).await;
+        assert_eq!(command_action.kind, chat_action.kind);
+        assert_eq!(command_action.description, chat_action.description);
+
+        let session_id: Uuid = serde_json::from_value(command["session_id"].clone()).unwrap();
+        let history = core.context_manager.read().await.get_user_context(session_id).await.unwrap().clone();
+        let turn = history.conversation_history.iter().last().unwrap();
+        assert_eq!(turn.kind, TurnKind::Command);
+        assert_eq!(turn.user_input, "/task name=\"buy milk\"");
+    }
+
+    #[tokio::test]
+    async fn test_command_parameters_are_checked_against_the_schema() {
+        let dir = tempfile::tempdir().unwrap();
+        let (_core, router) = app(&dir).await;
+
+        let (status, body) = send(router.clone(), "POST", "/commands/task", Some(serde_json::json!({ "parameters": {} }))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(violations(&body), [("parameters.name".to_string(), "required".to_string())]);
+
+        let parameters = serde_json::json!({ "parameters": { "name": "stretch", "at": "noon", "in": 5, "when": "later" } });
+        let (_, body) = send(router.clone(), "POST", "/commands/remind", Some(parameters)).await;
+        let mut found = violations(&body);
+        found.sort();
+        assert_eq!(
+            found,
+            [
+                ("parameters.at".to_string(), "format".to_string()),
+                ("parameters.in".to_string(), "type".to_string()),
+                ("parameters.when".to_string(), "unknown".to_string()),
+            ]
+        );
+
+        let (status, _) = send(router, "POST", "/commands/teleport", Some(serde_json::json!({}))).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+}
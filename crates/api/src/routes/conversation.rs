@@ -1,71 +1,32 @@
 use crate::{
     auth::AuthenticatedUser,
-    create_success_response,
+    create_paginated_response, create_success_response,
     error::{ApiError, ApiResult},
+    validation::{ValidJson, ValidQuery},
 };
 use axum::{
-    extract::{Path, Query, State},
-    routing::{get, post},
+    extract::{Path, State},
+    routing::{get, post, put},
     Json, Router,
 };
-use rusty_ai_common::{ConversationTurn, Intent};
-use rusty_ai_core::AssistantCore;
-use serde::{Deserialize, Serialize};
+use rusty_ai_common::api::{
+    ChatRequest, ChatResponse, ConversationHistory, CreateSessionRequest, CreateSessionResponse,
+    HistoryQuery, MessageResponse, SuggestedAction,
+};
+use rusty_ai_common::scratchpad::MAX_SCRATCHPAD_BYTES;
+use rusty_ai_common::{Intent, Pagination, TurnKind, UserContext, UserPreferences};
+use rusty_ai_core::{
+    activity::ActionTrigger,
+    events::{AssistantEvent, UserAction},
+    intent_handlers::{HandlerOutcome, IntentRequest},
+    response_processing::ResponseDestination,
+    session_stream::ReplyStream,
+    AssistantCore,
+};
 use std::sync::Arc;
 use tracing::{debug, error, info};
 use uuid::Uuid;
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct ChatRequest {
-    pub message: String,
-    pub session_id: Option<Uuid>,
-    pub context: Option<serde_json::Value>,
-}
-
-#[derive(Debug, Serialize)]
-pub struct ChatResponse {
-    pub response: String,
-    pub session_id: Uuid,
-    pub intent: Intent,
-    pub conversation_id: Uuid,
-    pub processing_time_ms: u64,
-    pub suggested_actions: Vec<SuggestedAction>,
-}
-
-#[derive(Debug, Serialize)]
-pub struct SuggestedAction {
-    pub action_type: String,
-    pub label: String,
-    pub description: String,
-    pub parameters: serde_json::Value,
-}
-
-#[derive(Debug, Serialize)]
-pub struct ConversationHistory {
-    pub session_id: Uuid,
-    pub turns: Vec<ConversationTurn>,
-    pub total_turns: usize,
-    pub created_at: chrono::DateTime<chrono::Utc>,
-    pub last_activity: chrono::DateTime<chrono::Utc>,
-}
-
-#[derive(Debug, Deserialize)]
-pub struct HistoryQuery {
-    pub limit: Option<usize>,
-    pub offset: Option<usize>,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct CreateSessionRequest {
-    pub preferences: Option<rusty_ai_common::UserPreferences>,
-}
-
-#[derive(Debug, Serialize)]
-pub struct CreateSessionResponse {
-    pub session_id: Uuid,
-    pub created_at: chrono::DateTime<chrono::Utc>,
-}
-
 pub fn routes(core: Arc<AssistantCore>) -> Router {
     Router::new()
         .route("/chat", post(chat))
@@ -73,6 +34,8 @@ pub fn routes(core: Arc<AssistantCore>) -> Router {
         .route("/sessions/:session_id", get(get_session).delete(delete_session))
         .route("/sessions/:session_id/history", get(get_conversation_history))
         .route("/sessions/:session_id/context", get(get_session_context))
+        .route("/sessions/:session_id/scratchpad", get(get_session_scratchpad))
+        .route("/sessions/:session_id/preferences", put(update_preferences))
         .route("/active", get(get_active_sessions))
         .with_state(core)
 }
@@ -81,133 +44,179 @@ pub fn routes(core: Arc<AssistantCore>) -> Router {
 async fn chat(
     State(core): State<Arc<AssistantCore>>,
     user: AuthenticatedUser,
-    Json(request): Json<ChatRequest>,
+    ValidJson(request): ValidJson<ChatRequest>,
 ) -> ApiResult<Json<serde_json::Value>> {
     let start_time = std::time::Instant::now();
     
     debug!("Chat request from user {}: {}", user.claims.user_id, request.message);
 
-    // Validate input
-    if request.message.trim().is_empty() {
-        return Err(ApiError::Validation("Message cannot be empty".to_string()));
-    }
-
-    if request.message.len() > 10000 {
-        return Err(ApiError::Validation("Message too long (max 10000 characters)".to_string()));
-    }
-
-    // Get or create session
-    let session_id = match request.session_id {
-        Some(id) => id,
-        None => {
-            // Create new session
-            let preferences = rusty_ai_common::UserPreferences {
-                language: "en".to_string(),
-                timezone: "UTC".to_string(),
-                voice_settings: rusty_ai_common::VoiceSettings {
-                    enabled: false,
-                    voice_id: "default".to_string(),
-                    speed: 1.0,
-                    pitch: 1.0,
-                },
-                notification_settings: rusty_ai_common::NotificationSettings {
-                    enabled: false,
-                    channels: vec![],
-                    quiet_hours: None,
-                },
-            };
-            
-            core.context_manager
-                .write()
-                .await
-                .create_session(user.claims.user_id, preferences)
-                .await
-                .map_err(|e| ApiError::CoreService(e))?
-        }
-    };
+    let session_id = session_for(&core, user.claims.user_id, request.session_id).await?;
 
     // Classify intent
-    let intent = {
+    let classification = {
         let context_manager = core.context_manager.read().await;
         let user_context = context_manager
             .get_user_context(session_id)
             .await
             .map_err(|e| ApiError::CoreService(e))?;
         
-        let classification = core.intent_classifier.classify(&request.message, Some(user_context));
-        classification.intent
+        core.intent_classifier.classify(&request.message, Some(user_context))
     };
+    let intent = classification.intent.clone();
 
-    // Process the message through orchestrator
-    let response = {
-        let context_manager = core.context_manager.read().await;
-        let user_context = context_manager
-            .get_user_context(session_id)
+    // Process the message through the orchestrator's handlers. The context
+    // is cloned so handlers that update the session can take the lock
+    let user_context = core
+        .context_manager
+        .read()
+        .await
+        .get_user_context(session_id)
+        .await
+        .map_err(|e| ApiError::CoreService(e))?
+        .clone();
+    let request_for_handlers = IntentRequest::from_classification(&classification).with_message(request.message.clone());
+    let outcome = core
+        .orchestrator
+        .handle(&request_for_handlers, &user_context)
+        .await
+        .map_err(|e| ApiError::CoreService(e))?;
+    let response = complete_turn(
+        &core,
+        &user_context,
+        request.message.clone(),
+        TurnKind::Message,
+        intent,
+        outcome,
+        start_time,
+        None,
+    )
+    .await?;
+
+    info!(
+        "Chat response generated for user {} in {}ms", 
+        user.claims.user_id, 
+        response.processing_time_ms
+    );
+
+    Ok(create_success_response(response))
+}
+
+// The given session, or a new one with default preferences
+pub(crate) async fn session_for(core: &AssistantCore, user_id: Uuid, session_id: Option<Uuid>) -> ApiResult<Uuid> {
+    match session_id {
+        Some(id) => Ok(id),
+        None => core
+            .context_manager
+            .write()
             .await
-            .map_err(|e| ApiError::CoreService(e))?;
-            
-        core.orchestrator
-            .process_intent(intent.clone(), user_context)
+            .create_session(user_id, default_preferences())
             .await
-            .map_err(|e| ApiError::CoreService(e))?
-    };
+            .map_err(|e| ApiError::CoreService(e)),
+    }
+}
+
+pub(crate) fn default_preferences() -> UserPreferences {
+    UserPreferences {
+        language: "en".to_string(),
+        timezone: "UTC".to_string(),
+        voice_settings: rusty_ai_common::VoiceSettings {
+            enabled: false,
+            voice_id: "default".to_string(),
+            speed: 1.0,
+            pitch: 1.0,
+        },
+        notification_settings: rusty_ai_common::NotificationSettings {
+            enabled: false,
+            channels: vec![],
+            quiet_hours: None,
+            routing: Default::default(),
+            knowledge_digest: Default::default(),
+        },
+    }
+}
+
+// What a chat message and a palette command share once a handler has
+// answered: post-processing, history, the activity log and suggestions.
+// With a `reply`, devices following the session see the response streamed
+// before its turn is stored
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn complete_turn(
+    core: &AssistantCore,
+    user_context: &UserContext,
+    user_input: String,
+    kind: TurnKind,
+    intent: Intent,
+    outcome: HandlerOutcome,
+    start_time: std::time::Instant,
+    reply: Option<&ReplyStream>,
+) -> ApiResult<ChatResponse> {
+    let session_id = user_context.session_id;
+    // The processed text is what the session keeps and the user sees
+    let processed = core.response_processor.process(
+        &outcome.response_text,
+        Some(&user_context.preferences),
+        ResponseDestination::Session,
+    );
+    let response = processed.text;
+    if let Some(reply) = reply {
+        for delta in response.split_inclusive(char::is_whitespace) {
+            reply.delta(delta);
+        }
+    }
 
     // Update conversation history
     {
         let mut context_manager = core.context_manager.write().await;
-        context_manager
-            .add_conversation_turn(session_id, request.message.clone(), response.clone(), intent.clone())
-            .await
-            .map_err(|e| ApiError::CoreService(e))?;
+        let added = match kind {
+            TurnKind::Message => {
+                context_manager
+                    .add_conversation_turn(session_id, user_input, response.clone(), intent.clone())
+                    .await
+            }
+            TurnKind::Command => {
+                context_manager
+                    .add_command_turn(session_id, user_input, response.clone(), intent.clone())
+                    .await
+            }
+        };
+        added.map_err(|e| ApiError::CoreService(e))?;
+
+        // Announced before the lock is released, so a device joining the
+        // session gets the turn in its snapshot or as a frame, never both
+        let stored = context_manager.get_conversation_history(session_id, Some(1)).await;
+        if let Some(turn) = stored.ok().and_then(|mut turns| turns.pop()) {
+            core.session_streams.append(session_id, turn, reply.map(ReplyStream::reply_id));
+        }
     }
 
-    let processing_time = start_time.elapsed().as_millis() as u64;
     let conversation_id = Uuid::new_v4();
+    let trigger = ActionTrigger::Chat { session_id, message_id: conversation_id };
+    let action_ids = core.activity.record(user_context.user_id, &trigger, outcome.performed.clone()).await;
 
-    // Generate suggested actions based on intent
-    let suggested_actions = generate_suggested_actions(&intent, &response);
-
-    info!(
-        "Chat response generated for user {} in {}ms", 
-        user.claims.user_id, 
-        processing_time
-    );
+    let suggested_actions = suggested_actions_for(&intent, &outcome);
 
-    Ok(create_success_response(ChatResponse {
+    Ok(ChatResponse {
         response,
         session_id,
         intent,
         conversation_id,
-        processing_time_ms: processing_time,
+        processing_time_ms: start_time.elapsed().as_millis() as u64,
         suggested_actions,
-    }))
+        sources: outcome.sources,
+        processing: processed.stages,
+        action_ids,
+    })
 }
 
 // Create new conversation session
 async fn create_session(
     State(core): State<Arc<AssistantCore>>,
     user: AuthenticatedUser,
-    Json(request): Json<CreateSessionRequest>,
+    ValidJson(request): ValidJson<CreateSessionRequest>,
 ) -> ApiResult<Json<serde_json::Value>> {
     debug!("Creating new session for user {}", user.claims.user_id);
 
-    let preferences = request.preferences.unwrap_or_else(|| {
-        rusty_ai_common::UserPreferences {
-            language: "en".to_string(),
-            timezone: "UTC".to_string(),
-            voice_settings: rusty_ai_common::VoiceSettings {
-                enabled: false,
-                voice_id: "default".to_string(),
-                speed: 1.0,
-                pitch: 1.0,
-            },
-            notification_settings: rusty_ai_common::NotificationSettings {
-                enabled: false,
-                channels: vec![],
-                quiet_hours: None,
-            },
-        }
-    });
+    let preferences = request.preferences.unwrap_or_else(default_preferences);
 
     let session_id = core
         .context_manager
@@ -218,6 +227,8 @@ async fn create_session(
         .map_err(|e| ApiError::CoreService(e))?;
 
     info!("Created session {} for user {}", session_id, user.claims.user_id);
+    // The greeting arrives over the live connection; the response doesn't wait
+    core.warmup.start(user.claims.user_id, session_id);
 
     Ok(create_success_response(CreateSessionResponse {
         session_id,
@@ -225,6 +236,35 @@ async fn create_session(
     }))
 }
 
+// Replace the preferences of a session
+async fn update_preferences(
+    State(core): State<Arc<AssistantCore>>,
+    Path(session_id): Path<Uuid>,
+    user: AuthenticatedUser,
+    ValidJson(preferences): ValidJson<UserPreferences>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let mut context_manager = core.context_manager.write().await;
+    let session = context_manager
+        .get_session(session_id)
+        .await
+        .map_err(|e| ApiError::CoreService(e))?;
+
+    if session.user_id != user.claims.user_id {
+        return Err(ApiError::Authorization("Access denied to this session".to_string()));
+    }
+
+    context_manager
+        .update_user_preferences(session_id, preferences)
+        .await
+        .map_err(|e| ApiError::CoreService(e))?;
+    core.events.publish(AssistantEvent::UserAction {
+        user_id: user.claims.user_id,
+        action: UserAction::PreferencesUpdated,
+    });
+
+    Ok(create_success_response(MessageResponse::new("Preferences updated")))
+}
+
 // Get session information
 async fn get_session(
     State(core): State<Arc<AssistantCore>>,
@@ -291,7 +331,7 @@ async fn delete_session(
 async fn get_conversation_history(
     State(core): State<Arc<AssistantCore>>,
     Path(session_id): Path<Uuid>,
-    Query(query): Query<HistoryQuery>,
+    ValidQuery(query): ValidQuery<HistoryQuery>,
     user: AuthenticatedUser,
 ) -> ApiResult<Json<serde_json::Value>> {
     debug!("Getting conversation history for session {}", session_id);
@@ -308,24 +348,27 @@ async fn get_conversation_history(
         return Err(ApiError::Authorization("Access denied to this session".to_string()));
     }
 
+    // Pages through the whole history, oldest first
+    let offset = query.offset.unwrap_or(0);
     let turns = context_manager
-        .get_conversation_history(session_id, query.limit)
+        .get_full_history(session_id, offset, query.limit)
         .await
         .map_err(|e| ApiError::CoreService(e))?;
+    let next_offset = offset + turns.len();
+    let pagination = Pagination::new(
+        Some(session.turn_count as u64),
+        (!turns.is_empty() && next_offset < session.turn_count).then(|| next_offset.to_string()),
+    );
 
     let history = ConversationHistory {
         session_id,
-        turns: if let Some(offset) = query.offset {
-            turns.into_iter().skip(offset).collect()
-        } else {
-            turns
-        },
-        total_turns: session.conversation_turns.len(),
+        turns,
+        total_turns: session.turn_count,
         created_at: session.created_at,
         last_activity: session.last_activity,
     };
 
-    Ok(create_success_response(history))
+    Ok(create_paginated_response(history, pagination))
 }
 
 // Get session context
@@ -346,10 +389,37 @@ async fn get_session_context(
     if user_context.user_id != user.claims.user_id {
         return Err(ApiError::Authorization("Access denied to this session".to_string()));
     }
+    core.events.publish(AssistantEvent::UserAction { user_id: user.claims.user_id, action: UserAction::MemoryReviewed });
 
     Ok(create_success_response(user_context))
 }
 
+// The session's working state as handlers and plugins left it; read-only,
+// for debugging multi-step tasks
+async fn get_session_scratchpad(
+    State(core): State<Arc<AssistantCore>>,
+    Path(session_id): Path<Uuid>,
+    user: AuthenticatedUser,
+) -> ApiResult<Json<serde_json::Value>> {
+    let owner = core.context_manager.read().await
+        .get_session(session_id)
+        .await
+        .map_err(|e| ApiError::CoreService(e))?
+        .user_id;
+    if owner != user.claims.user_id {
+        return Err(ApiError::Authorization("Access denied to this session".to_string()));
+    }
+
+    let scratchpad = core.scratchpads.get(session_id);
+    core.events.publish(AssistantEvent::UserAction { user_id: user.claims.user_id, action: UserAction::MemoryReviewed });
+    Ok(create_success_response(serde_json::json!({
+        "session_id": session_id,
+        "size_bytes": scratchpad.size_bytes(),
+        "max_bytes": MAX_SCRATCHPAD_BYTES,
+        "entries": scratchpad,
+    })))
+}
+
 // Get active sessions for user
 async fn get_active_sessions(
     State(core): State<Arc<AssistantCore>>,
@@ -369,7 +439,7 @@ async fn get_active_sessions(
                 "session_id": session.session_id,
                 "created_at": session.created_at,
                 "last_activity": session.last_activity,
-                "turn_count": session.conversation_turns.len(),
+                "turn_count": session.turn_count,
                 "active_plugins": session.context.active_plugins
             })
         })
@@ -378,6 +448,15 @@ async fn get_active_sessions(
     Ok(create_success_response(session_summaries))
 }
 
+/// Actions the handler suggested, or the defaults for the intent
+pub(crate) fn suggested_actions_for(intent: &Intent, outcome: &HandlerOutcome) -> Vec<SuggestedAction> {
+    if outcome.actions.is_empty() {
+        generate_suggested_actions(intent, &outcome.response_text)
+    } else {
+        outcome.actions.clone()
+    }
+}
+
 // Helper function to generate suggested actions
 fn generate_suggested_actions(intent: &Intent, _response: &str) -> Vec<SuggestedAction> {
     match intent {
@@ -437,7 +516,8 @@ fn generate_suggested_actions(intent: &Intent, _response: &str) -> Vec<Suggested
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::auth::{AuthConfig, AuthService, Claims};
+    use crate::auth::{AuthConfig, AuthService};
+    use crate::routes::tests::user;
     use rusty_ai_core::CoreConfig;
     use std::sync::Arc;
 
@@ -445,20 +525,7 @@ mod tests {
         let core_config = CoreConfig::default();
         let core = Arc::new(AssistantCore::new(core_config).await.unwrap());
         
-        let user = AuthenticatedUser {
-            claims: Claims {
-                sub: "test-user".to_string(),
-                name: "Test User".to_string(),
-                email: "test@example.com".to_string(),
-                iat: chrono::Utc::now().timestamp(),
-                exp: (chrono::Utc::now() + chrono::Duration::hours(1)).timestamp(),
-                iss: "test".to_string(),
-                aud: "test".to_string(),
-                user_id: uuid::Uuid::new_v4(),
-                session_id: uuid::Uuid::new_v4(),
-                permissions: vec!["read".to_string(), "write".to_string()],
-            },
-        };
+        let user = user(Uuid::new_v4(), &["read", "write"]);
 
         (core, user)
     }
@@ -492,6 +559,8 @@ mod tests {
                         enabled: false,
                         channels: vec![],
                         quiet_hours: None,
+                        routing: Default::default(),
+                        knowledge_digest: Default::default(),
                     },
                 }
             )
@@ -501,6 +570,74 @@ mod tests {
         assert!(!session_id.is_nil());
     }
 
+    #[tokio::test]
+    async fn test_scratchpad_is_readable_by_the_session_owner_only() {
+        let (core, user) = create_test_setup().await;
+        let preferences = UserPreferences {
+            language: "en".to_string(),
+            timezone: "UTC".to_string(),
+            voice_settings: rusty_ai_common::VoiceSettings {
+                enabled: false,
+                voice_id: "default".to_string(),
+                speed: 1.0,
+                pitch: 1.0,
+            },
+            notification_settings: rusty_ai_common::NotificationSettings {
+                enabled: false,
+                channels: vec![],
+                quiet_hours: None,
+                routing: Default::default(),
+                knowledge_digest: Default::default(),
+            },
+        };
+        let session_id = core
+            .context_manager
+            .write()
+            .await
+            .create_session(user.claims.user_id, preferences)
+            .await
+            .unwrap();
+        core.scratchpads.set(session_id, "trip", serde_json::json!({ "destination": "Lisbon" })).unwrap();
+
+        let Json(body) = get_session_scratchpad(State(core.clone()), Path(session_id), user).await.unwrap();
+        assert_eq!(body["data"]["entries"]["trip"]["destination"], "Lisbon");
+        assert_eq!(body["data"]["max_bytes"], MAX_SCRATCHPAD_BYTES);
+
+        let (_, stranger) = create_test_setup().await;
+        let denied = get_session_scratchpad(State(core), Path(session_id), stranger).await;
+        assert!(matches!(denied, Err(ApiError::Authorization(_))));
+    }
+
+    #[tokio::test]
+    async fn test_invalid_payloads_are_rejected_per_field() {
+        use crate::validation::tests::{send, violations};
+        use axum::http::StatusCode;
+
+        let (core, _) = create_test_setup().await;
+
+        let (status, body) = send(routes(core.clone()), "POST", "/chat", Some(serde_json::json!({ "message": "  " }))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(violations(&body), vec![("message".to_string(), "required".to_string())]);
+
+        let preferences = serde_json::json!({
+            "language": "en",
+            "timezone": "Mars/Olympus",
+            "voice_settings": { "enabled": true, "voice_id": "default", "speed": 9.0, "pitch": 1.0 },
+            "notification_settings": { "enabled": false, "channels": [], "quiet_hours": null }
+        });
+        let uri = format!("/sessions/{}/preferences", Uuid::new_v4());
+        let (status, body) = send(routes(core.clone()), "PUT", &uri, Some(preferences)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let fields = violations(&body);
+        assert!(fields.contains(&("timezone".to_string(), "format".to_string())));
+        assert!(fields.contains(&("voice_settings.speed".to_string(), "range".to_string())));
+
+        let uri = format!("/sessions/{}/history?limit=oops", Uuid::new_v4());
+        let (status, body) = send(routes(core), "GET", &uri, None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(violations(&body), vec![("query".to_string(), "type".to_string())]);
+    }
+
     #[test]
     fn test_suggested_actions_generation() {
         let intent = Intent::Query { query: "test".to_string() };
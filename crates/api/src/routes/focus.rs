@@ -0,0 +1,87 @@
+use crate::{
+    auth::AuthenticatedUser,
+    create_success_response,
+    error::{ApiError, ApiResult},
+    validation::ValidJson,
+};
+use axum::{extract::State, routing::get, Json, Router};
+use chrono::Duration;
+use rusty_ai_core::focus::StartFocusRequest;
+use rusty_ai_core::AssistantCore;
+use std::sync::Arc;
+
+pub fn routes(core: Arc<AssistantCore>) -> Router {
+    Router::new()
+        .route("/", get(get_focus).post(start_focus).delete(end_focus))
+        .with_state(core)
+}
+
+// Whether the caller is focused, until when, and how much is held
+async fn get_focus(
+    State(core): State<Arc<AssistantCore>>,
+    user: AuthenticatedUser,
+) -> ApiResult<Json<serde_json::Value>> {
+    Ok(create_success_response(core.notification_router.focus_status(user.claims.user_id)))
+}
+
+// Hold back everything but security alerts and critical reminders for
+// `duration_minutes`, or until focus is turned off when it is left out.
+// Starting again while focused moves the end
+async fn start_focus(
+    State(core): State<Arc<AssistantCore>>,
+    user: AuthenticatedUser,
+    ValidJson(request): ValidJson<StartFocusRequest>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let duration = request.duration_minutes.map(|minutes| Duration::minutes(minutes.into()));
+    let status = core
+        .notification_router
+        .start_focus(user.claims.user_id, duration)
+        .map_err(ApiError::CoreService)?;
+    Ok(create_success_response(status))
+}
+
+// End focus now; what it held goes out as one digest
+async fn end_focus(
+    State(core): State<Arc<AssistantCore>>,
+    user: AuthenticatedUser,
+) -> ApiResult<Json<serde_json::Value>> {
+    let held = core
+        .notification_router
+        .end_focus(user.claims.user_id)
+        .await
+        .map_err(ApiError::CoreService)?;
+    Ok(create_success_response(serde_json::json!({
+        "ended": held.is_some(),
+        "held": held.unwrap_or(0),
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::routes::tests::{core, user};
+    use uuid::Uuid;
+
+    #[tokio::test]
+    async fn test_focus_starts_reports_and_ends() {
+        let dir = tempfile::tempdir().unwrap();
+        let core = core(&dir).await;
+        let user_id = Uuid::new_v4();
+        let caller = || user(user_id, &["read", "write"]);
+
+        let request = StartFocusRequest { duration_minutes: Some(90) };
+        let Json(started) = start_focus(State(core.clone()), caller(), ValidJson(request)).await.unwrap();
+        assert_eq!(started["data"]["active"], true);
+        assert!(started["data"]["until"].is_string());
+
+        let Json(status) = get_focus(State(core.clone()), caller()).await.unwrap();
+        assert_eq!(status["data"]["held"], 0);
+        assert!(core.notification_router.in_focus(user_id));
+
+        let Json(ended) = end_focus(State(core.clone()), caller()).await.unwrap();
+        assert_eq!(ended["data"]["ended"], true);
+        let Json(again) = end_focus(State(core.clone()), caller()).await.unwrap();
+        assert_eq!(again["data"]["ended"], false);
+        assert!(!core.notification_router.in_focus(user_id));
+    }
+}
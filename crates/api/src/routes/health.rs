@@ -1,19 +1,24 @@
-use crate::{create_success_response, HealthCheck, ServiceHealth};
-use axum::{routing::get, Json, Router};
+use crate::{create_response, create_success_response, HealthCheck};
+use axum::{extract::State, http::{header, StatusCode}, response::IntoResponse, routing::get, Json, Router};
+use rusty_ai_common::{ApiResponse, ErrorCode};
+use rusty_ai_core::AssistantCore;
 use serde_json::json;
+use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 use tracing::debug;
 
-pub fn routes() -> Router {
+pub fn routes(core: Arc<AssistantCore>) -> Router {
     Router::new()
         .route("/", get(health_check))
         .route("/ready", get(readiness_check))
         .route("/live", get(liveness_check))
         .route("/metrics", get(metrics))
+        .with_state(core)
 }
 
-// Basic health check endpoint
-async fn health_check() -> Json<serde_json::Value> {
+// Full component graph: status, latency and last error of every component
+// along with what it depends on
+async fn health_check(State(core): State<Arc<AssistantCore>>) -> Json<ApiResponse<HealthCheck>> {
     debug!("Health check requested");
     
     let uptime = SystemTime::now()
@@ -22,56 +27,51 @@ async fn health_check() -> Json<serde_json::Value> {
         .as_secs();
 
     let health = HealthCheck {
-        status: "healthy".to_string(),
         version: env!("CARGO_PKG_VERSION").to_string(),
         uptime,
-        services: ServiceHealth {
-            database: check_database_health().await,
-            storage: check_storage_health().await,
-            plugins: check_plugins_health().await,
-            voice: check_voice_health().await,
-        },
+        report: core.health.check_all().await,
     };
 
-    create_success_response(health).0
+    create_success_response(health)
 }
 
-// Kubernetes readiness probe
-async fn readiness_check() -> Json<serde_json::Value> {
+// Kubernetes readiness probe; ready while every critical component (and
+// everything it depends on) is available
+async fn readiness_check(State(core): State<Arc<AssistantCore>>) -> impl IntoResponse {
     debug!("Readiness check requested");
     
-    // Check if all critical services are ready
-    let database_ready = check_database_health().await == "healthy";
-    let storage_ready = check_storage_health().await == "healthy";
-    
-    if database_ready && storage_ready {
-        Json(json!({
-            "status": "ready",
-            "timestamp": chrono::Utc::now(),
-            "checks": {
-                "database": "ready",
-                "storage": "ready"
-            }
-        }))
+    let report = core.health.check_all().await;
+    let checks: serde_json::Map<String, serde_json::Value> = report
+        .components
+        .iter()
+        .map(|(id, health)| (id.to_string(), json!(health.status)))
+        .collect();
+    let body = json!({
+        "status": if report.ready { "ready" } else { "not_ready" },
+        "checked_at": report.checked_at,
+        "not_ready": report.not_ready,
+        "checks": checks,
+    });
+
+    if report.ready {
+        (StatusCode::OK, create_success_response(body))
     } else {
-        Json(json!({
-            "status": "not_ready",
-            "timestamp": chrono::Utc::now(),
-            "checks": {
-                "database": if database_ready { "ready" } else { "not_ready" },
-                "storage": if storage_ready { "ready" } else { "not_ready" }
-            }
-        }))
+        let not_ready: Vec<String> = report.not_ready.iter().map(|id| id.to_string()).collect();
+        let mut response = ApiResponse::error_with_code(
+            ErrorCode::ServiceUnavailable,
+            format!("Not ready: {}", not_ready.join(", ")),
+        );
+        response.data = Some(body);
+        (StatusCode::SERVICE_UNAVAILABLE, create_response(response))
     }
 }
 
 // Kubernetes liveness probe
-async fn liveness_check() -> Json<serde_json::Value> {
+async fn liveness_check() -> Json<ApiResponse<serde_json::Value>> {
     debug!("Liveness check requested");
     
-    Json(json!({
+    create_success_response(json!({
         "status": "alive",
-        "timestamp": chrono::Utc::now(),
         "uptime_seconds": SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap()
@@ -80,12 +80,11 @@ async fn liveness_check() -> Json<serde_json::Value> {
 }
 
 // Basic metrics endpoint
-async fn metrics() -> Json<serde_json::Value> {
+async fn metrics(State(core): State<Arc<AssistantCore>>) -> Json<ApiResponse<serde_json::Value>> {
     debug!("Metrics requested");
     
     // In a production system, you would collect real metrics
-    Json(json!({
-        "timestamp": chrono::Utc::now(),
+    create_success_response(json!({
         "system": {
             "memory_usage_mb": get_memory_usage(),
             "cpu_usage_percent": get_cpu_usage(),
@@ -98,7 +97,8 @@ async fn metrics() -> Json<serde_json::Value> {
             "active_sessions": 0, // Would be populated from session manager
             "total_requests": 0,  // Would be populated from metrics collector
             "error_rate": 0.0,    // Would be calculated from error metrics
-            "avg_response_time_ms": 0.0
+            "avg_response_time_ms": 0.0,
+            "admission": core.admission.snapshot()
         },
         "services": {
             "database_connections": 0,
@@ -108,48 +108,17 @@ async fn metrics() -> Json<serde_json::Value> {
     }))
 }
 
-// Helper functions for health checks
-async fn check_database_health() -> String {
-    // In a real implementation, this would:
-    // 1. Try to connect to the database
-    // 2. Execute a simple query
-    // 3. Check connection pool status
-    
-    // For now, assume healthy
-    "healthy".to_string()
-}
-
-async fn check_storage_health() -> String {
-    // In a real implementation, this would:
-    // 1. Check file system availability
-    // 2. Verify write permissions
-    // 3. Check disk space
-    
-    "healthy".to_string()
-}
-
-async fn check_plugins_health() -> String {
-    // In a real implementation, this would:
-    // 1. Check plugin manager status
-    // 2. Verify active plugins are responding
-    // 3. Check plugin resource usage
-    
-    "healthy".to_string()
-}
-
-async fn check_voice_health() -> String {
-    // In a real implementation, this would:
-    // 1. Check voice pipeline status
-    // 2. Verify external API connections (Whisper, ElevenLabs)
-    // 3. Check audio processing capabilities
-    
-    "inactive".to_string()
+// Plugin runtime metrics in the Prometheus text format, mounted at the top
+// level as /metrics for scrapers
+pub async fn prometheus_metrics() -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        rusty_ai_plugins::metrics::gather(),
+    )
 }
 
 fn get_memory_usage() -> u64 {
-    // In a real implementation, use a proper system metrics library
-    // For now, return a placeholder value
-    0
+    rusty_ai_core::resources::process_rss_bytes().unwrap_or(0) / (1024 * 1024)
 }
 
 fn get_cpu_usage() -> f64 {
@@ -161,12 +130,20 @@ fn get_cpu_usage() -> f64 {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use axum::http::StatusCode;
+    use rusty_ai_core::{
+        health::{ComponentId, FnProbe},
+        CoreConfig,
+    };
+    use rusty_ai_common::AssistantError;
     use tower::ServiceExt;
 
+    async fn create_test_core() -> Arc<AssistantCore> {
+        Arc::new(AssistantCore::new(CoreConfig::default()).await.unwrap())
+    }
+
     #[tokio::test]
     async fn test_health_check() {
-        let app = routes();
+        let app = routes(create_test_core().await);
         
         let response = app
             .oneshot(
@@ -183,7 +160,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_readiness_check() {
-        let app = routes();
+        let app = routes(create_test_core().await);
         
         let response = app
             .oneshot(
@@ -200,7 +177,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_liveness_check() {
-        let app = routes();
+        let app = routes(create_test_core().await);
         
         let response = app
             .oneshot(
@@ -214,4 +191,31 @@ mod tests {
 
         assert_eq!(response.status(), StatusCode::OK);
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_not_ready_when_critical_component_fails() {
+        let core = create_test_core().await;
+        core.health.register(
+            ComponentId::Storage,
+            &[],
+            FnProbe(|| async { Err(AssistantError::Database("disk full".to_string())) }),
+        );
+
+        let response = routes(core)
+            .oneshot(
+                axum::http::Request::builder()
+                    .uri("/ready")
+                    .body(axum::body::Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["error_code"], "SERVICE_UNAVAILABLE");
+        assert_eq!(body["data"]["not_ready"], json!(["storage"]));
+        assert_eq!(body["data"]["checks"]["storage"], "unhealthy");
+    }
+}
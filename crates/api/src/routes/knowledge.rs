@@ -1,50 +1,61 @@
-use crate::{auth::AuthenticatedUser, create_success_response, error::ApiResult};
-use axum::{extract::{Path, Query, State}, routing::{get, post}, Json, Router};
-use rusty_ai_core::AssistantCore;
-use serde::{Deserialize, Serialize};
+use crate::{auth::{AuthService, AuthenticatedUser}, create_success_response, error::ApiResult, validation::{ValidJson, ValidQuery}};
+use axum::{extract::{Path, Query, State}, routing::{get, post}, Extension, Json, Router};
+use rusty_ai_core::{events::{AssistantEvent, UserAction}, sharing::SharedResource, AssistantCore};
+use rusty_ai_common::api::{CreateShareLinkRequest, DocumentSearchResponse, DocumentUpload, MessageResponse, SearchQuery, SuggestQuery};
 use std::sync::Arc;
 use uuid::Uuid;
 
-#[derive(Deserialize)]
-pub struct SearchQuery {
-    pub q: String,
-    pub limit: Option<usize>,
-}
-
-#[derive(Serialize, Deserialize)]
-pub struct DocumentUpload {
-    pub title: String,
-    pub content: String,
-    pub tags: Vec<String>,
-}
-
 pub fn routes(core: Arc<AssistantCore>) -> Router {
     Router::new()
         .route("/search", get(search_documents))
+        .route("/suggest", get(suggest))
         .route("/documents", post(upload_document).get(list_documents))
         .route("/documents/:id", get(get_document).delete(delete_document))
+        .route("/documents/:id/share-link", post(share_document))
+        .route("/digests", get(list_digests))
         .with_state(core)
 }
 
 async fn search_documents(
     State(core): State<Arc<AssistantCore>>,
-    Query(query): Query<SearchQuery>,
-    _user: AuthenticatedUser,
+    ValidQuery(query): ValidQuery<SearchQuery>,
+    user: AuthenticatedUser,
 ) -> ApiResult<Json<serde_json::Value>> {
     let limit = query.limit.unwrap_or(10);
     let documents = core.storage.search_documents(&query.q, limit).await
         .map_err(|e| crate::error::ApiError::CoreService(e))?;
+    for doc in &documents {
+        record_access(&core, doc.id).await;
+    }
+    if !documents.is_empty() {
+        core.suggestions.record_search(user.claims.user_id, &query.q).await;
+    }
     
-    Ok(create_success_response(serde_json::json!({
-        "documents": documents,
-        "total": documents.len()
-    })))
+    Ok(create_success_response(DocumentSearchResponse {
+        total: documents.len(),
+        documents,
+    }))
+}
+
+// Up to ten completions for what has been typed: document titles, then
+// tags, then the caller's past searches. Answered from memory, without
+// touching storage or embeddings; each suggestion's `query` is the `q` to
+// run the regular search with
+async fn suggest(
+    State(core): State<Arc<AssistantCore>>,
+    ValidQuery(query): ValidQuery<SuggestQuery>,
+    user: AuthenticatedUser,
+) -> ApiResult<Json<serde_json::Value>> {
+    let suggestions = core.suggestions.suggest(user.claims.user_id, &query.q).await
+        .map_err(|e| crate::error::ApiError::CoreService(e))?;
+
+    Ok(create_success_response(serde_json::json!({ "suggestions": suggestions })))
 }
 
 async fn upload_document(
     State(core): State<Arc<AssistantCore>>,
-    _user: AuthenticatedUser,
-    Json(upload): Json<DocumentUpload>,
+    user: AuthenticatedUser,
+    ValidJson(upload): ValidJson<DocumentUpload>,
 ) -> ApiResult<Json<serde_json::Value>> {
     let document = rusty_ai_common::Document {
         id: Uuid::new_v4(),
@@ -64,6 +75,13 @@ async fn upload_document(
     
     core.storage.store_document(&document).await
         .map_err(|e| crate::error::ApiError::CoreService(e))?;
+    core.suggestions.document_added(&document);
+    core.events.publish(AssistantEvent::UserAction { user_id: user.claims.user_id, action: UserAction::DocumentUploaded });
+    core.events.publish(AssistantEvent::DocumentAdded {
+        user_id: user.claims.user_id,
+        document_id: document.id,
+        title: document.title.clone(),
+    });
     
     Ok(create_success_response(document))
 }
@@ -87,7 +105,10 @@ async fn get_document(
         .map_err(|e| crate::error::ApiError::CoreService(e))?;
     
     match document {
-        Some(doc) => Ok(create_success_response(doc)),
+        Some(doc) => {
+            record_access(&core, doc.id).await;
+            Ok(create_success_response(doc))
+        }
         None => Err(crate::error::ApiError::CoreService(
             rusty_ai_common::AssistantError::NotFound("Document not found".to_string())
         ))
@@ -101,8 +122,108 @@ async fn delete_document(
 ) -> ApiResult<Json<serde_json::Value>> {
     core.storage.delete_document(id).await
         .map_err(|e| crate::error::ApiError::CoreService(e))?;
+    core.suggestions.document_removed(id);
     
-    Ok(create_success_response(serde_json::json!({
-        "message": "Document deleted successfully"
-    })))
-}
\ No newline at end of file
+    Ok(create_success_response(MessageResponse::new("Document deleted successfully")))
+}
+
+// Lookups feed the "most referenced" section of the knowledge digest; a
+// failure to count one should not fail the request
+async fn record_access(core: &AssistantCore, id: Uuid) {
+    if let Err(e) = core.storage.record_document_access(id).await {
+        tracing::warn!("Failed to record access to document {}: {}", id, e);
+    }
+}
+
+async fn list_digests(
+    State(core): State<Arc<AssistantCore>>,
+    user: AuthenticatedUser,
+    Query(query): Query<DigestQuery>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let digests = core.knowledge_digests.history(user.claims.user_id, query.limit.unwrap_or(10).min(52)).await
+        .map_err(|e| crate::error::ApiError::CoreService(e))?;
+
+    Ok(create_success_response(digests))
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct DigestQuery {
+    limit: Option<usize>,
+}
+
+async fn share_document(
+    State(core): State<Arc<AssistantCore>>,
+    Extension(auth_service): Extension<Arc<AuthService>>,
+    Path(id): Path<Uuid>,
+    user: AuthenticatedUser,
+    request: Option<Json<CreateShareLinkRequest>>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let request = request.map(|Json(r)| r).unwrap_or_default();
+    super::share::create_share_link(&core, &auth_service, &user, SharedResource::Document(id), request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validation::tests::{send, violations};
+    use axum::http::StatusCode;
+    use rusty_ai_core::CoreConfig;
+
+    async fn create_test_router() -> Router {
+        routes(Arc::new(AssistantCore::new(CoreConfig::default()).await.unwrap()))
+    }
+
+    fn suggested(body: &serde_json::Value) -> Vec<(String, String)> {
+        body["data"]["suggestions"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|s| (s["text"].as_str().unwrap().to_string(), s["kind"].as_str().unwrap().to_string()))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_suggestions_follow_uploads_searches_and_deletes() {
+        let router = create_test_router().await;
+        // Unique so documents from other runs in the same database do not match
+        let token = format!("zq{}", &Uuid::new_v4().simple().to_string()[..8]);
+        let title = format!("{} budget 2025", token);
+
+        let upload = serde_json::json!({ "title": title, "content": "Rent and groceries", "tags": [format!("{}-finance", token)] });
+        let (status, document) = send(router.clone(), "POST", "/documents", Some(upload)).await;
+        assert_eq!(status, StatusCode::OK);
+        let id = document["data"]["id"].as_str().unwrap().to_string();
+
+        let (status, _) = send(router.clone(), "GET", &format!("/search?q={}%20bud", token), None).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, body) = send(router.clone(), "GET", &format!("/suggest?q={}", token.to_uppercase()), None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            suggested(&body),
+            vec![
+                (title.clone(), "title".to_string()),
+                (format!("{}-finance", token), "tag".to_string()),
+                (format!("{} bud", token), "history".to_string()),
+            ]
+        );
+        assert_eq!(body["data"]["suggestions"][0]["document_id"], id.as_str());
+
+        let (status, _) = send(router.clone(), "DELETE", &format!("/documents/{}", id), None).await;
+        assert_eq!(status, StatusCode::OK);
+        let (_, body) = send(router, "GET", &format!("/suggest?q={}", token), None).await;
+        assert_eq!(suggested(&body), vec![(format!("{} bud", token), "history".to_string())]);
+    }
+
+    #[tokio::test]
+    async fn test_search_and_upload_reject_invalid_payloads() {
+        let (status, body) = send(create_test_router().await, "GET", "/search?q=rust&limit=0", None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(violations(&body), vec![("limit".to_string(), "range".to_string())]);
+
+        let body = serde_json::json!({ "title": "Notes", "content": "", "tags": "finance" });
+        let (status, body) = send(create_test_router().await, "POST", "/documents", Some(body)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(violations(&body), vec![("tags".to_string(), "type".to_string())]);
+    }
+}
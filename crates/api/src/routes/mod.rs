@@ -6,37 +6,54 @@ pub mod knowledge;
 pub mod tasks;
 pub mod briefing;
 pub mod voice;
+pub mod share;
+pub mod admin;
+pub mod sync;
+pub mod activity;
+pub mod onboarding;
+pub mod commands;
+pub mod focus;
 
-use axum::{routing::get, Router};
+use axum::{routing::get, Extension, Router};
 use std::sync::Arc;
 use crate::auth::AuthService;
 use rusty_ai_core::AssistantCore;
+use rusty_ai_plugins::PluginMarketplace;
 
 pub fn create_routes(
     core: Arc<AssistantCore>,
     auth_service: Arc<AuthService>,
+    marketplace: Arc<PluginMarketplace>,
 ) -> Router {
     Router::new()
         // Health check routes (no authentication required)
-        .nest("/health", health::routes())
+        .nest("/health", health::routes(core.clone()))
+        .route("/metrics", get(health::prometheus_metrics))
         
         // Authentication routes
         .nest("/auth", auth::routes(auth_service.clone()))
+
+        // Public read-only share pages (token is the credential)
+        .nest("/share", share::routes(core.clone()))
         
         // Protected routes (require authentication)
-        .nest("/api/v1", protected_routes(core, auth_service))
+        .nest("/api/v1", protected_routes(core, auth_service, marketplace))
 }
 
 fn protected_routes(
     core: Arc<AssistantCore>,
     auth_service: Arc<AuthService>,
+    marketplace: Arc<PluginMarketplace>,
 ) -> Router {
     Router::new()
         // Conversation endpoints
         .nest("/conversation", conversation::routes(core.clone()))
         
+        // Command palette: handler commands and plugin capabilities
+        .nest("/commands", commands::routes(core.clone(), marketplace.clone()))
+
         // Plugin management endpoints
-        .nest("/plugins", plugins::routes(core.clone()))
+        .nest("/plugins", plugins::routes(marketplace))
         
         // Knowledge base endpoints
         .nest("/knowledge", knowledge::routes(core.clone()))
@@ -49,12 +66,242 @@ fn protected_routes(
         
         // Voice interaction endpoints
         .nest("/voice", voice::routes(core.clone()))
+
+        // Share link management
+        .nest("/share-links", share::management_routes(core.clone()))
+
+        // What the assistant did on the caller's behalf, with undo
+        .nest("/me/activity", activity::routes(core.clone()))
+
+        // Focus mode: notifications held for a digest until focus ends
+        .nest("/me/focus", focus::routes(core.clone()))
+
+        // First-run setup steps and sample content
+        .nest("/onboarding", onboarding::routes(core.clone()))
+
+        // Changes since a cursor, for offline-capable clients
+        .nest("/sync", sync::routes(core.clone()))
+
+        // Resource usage, cache trimming and the audit trail (admin only)
+        .nest("/admin", admin::routes(core.clone()))
+
+        // Admin handlers record their changes through the AdminAction extractor
+        .layer(Extension(core.audit.clone()))
         
         // Add auth service to state for authentication middleware
         .with_state(auth_service)
 }
 
 // Fallback handler for unmatched routes
-pub async fn not_found_handler() -> axum::http::StatusCode {
-    axum::http::StatusCode::NOT_FOUND
-}
\ No newline at end of file
+pub async fn not_found_handler(uri: axum::http::Uri) -> rusty_ai_common::AssistantError {
+    rusty_ai_common::AssistantError::NotFound(format!("No route for {}", uri.path()))
+}
+// Contract tests: every JSON endpoint answers with the shared envelope, so
+// a handler that builds its own body fails here. The public share pages are
+// HTML and not covered
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::auth::{AuthConfig, AuthenticatedUser, Claims};
+    use crate::middleware::{auth_middleware, error_handling_middleware, request_id_middleware};
+    use axum::body::Body;
+    use axum::http::{Method, Request, StatusCode};
+    use rusty_ai_common::api::LoginRequest;
+    use rusty_ai_common::ApiResponse;
+    use rusty_ai_core::CoreConfig;
+    use rusty_ai_plugins::{MarketplaceConfig, PluginMarketplace, WasmPluginManager};
+    use tower::ServiceExt;
+    use uuid::Uuid;
+
+    const ENVELOPE_FIELDS: &[&str] = &[
+        "success", "data", "error", "timestamp", "request_id", "pagination", "error_code", "fields", "meta",
+    ];
+
+    // A core whose stores all live in `dir`
+    pub(crate) async fn core(dir: &tempfile::TempDir) -> Arc<AssistantCore> {
+        let mut config = CoreConfig::default();
+        config.storage_config.database_url = format!("sqlite:{}?mode=rwc", dir.path().join("assistant.db").display());
+        config.share_store_path = dir.path().join("share_links.json").display().to_string();
+        config.notification_store_path = dir.path().join("notifications.json").display().to_string();
+        config.focus_store_path = dir.path().join("focus.json").display().to_string();
+        config.onboarding_store_path = dir.path().join("onboarding.json").display().to_string();
+        Arc::new(AssistantCore::new(config).await.unwrap())
+    }
+
+    pub(crate) fn marketplace(dir: &tempfile::TempDir) -> Arc<PluginMarketplace> {
+        Arc::new(
+            PluginMarketplace::new(
+                MarketplaceConfig { plugin_directory: dir.path().join("plugins"), ..Default::default() },
+                Arc::new(WasmPluginManager::new(dir.path().join("plugins")).unwrap()),
+            )
+            .unwrap(),
+        )
+    }
+
+    // The caller as the auth middleware would pass it to a handler
+    pub(crate) fn user(user_id: Uuid, permissions: &[&str]) -> AuthenticatedUser {
+        AuthenticatedUser {
+            claims: Claims {
+                sub: user_id.to_string(),
+                name: "Test User".to_string(),
+                email: "test@example.com".to_string(),
+                iat: chrono::Utc::now().timestamp(),
+                exp: (chrono::Utc::now() + chrono::Duration::hours(1)).timestamp(),
+                iss: "test".to_string(),
+                aud: "test".to_string(),
+                user_id,
+                session_id: Uuid::new_v4(),
+                permissions: permissions.iter().map(|p| p.to_string()).collect(),
+            },
+        }
+    }
+
+    pub(crate) async fn app(dir: &tempfile::TempDir) -> (Router, String) {
+        let core = core(dir).await;
+        let auth_service = Arc::new(AuthService::new(AuthConfig::default()));
+        let token = auth_service
+            .authenticate(LoginRequest { email: "demo@example.com".to_string(), password: "password".to_string() })
+            .await
+            .unwrap()
+            .access_token;
+
+        let router = create_routes(core, auth_service.clone(), marketplace(dir))
+            .fallback(not_found_handler)
+            .layer(axum::middleware::from_fn_with_state(auth_service, auth_middleware))
+            .layer(axum::middleware::from_fn(error_handling_middleware))
+            .layer(axum::middleware::from_fn(request_id_middleware));
+        (router, token)
+    }
+
+    // A token for `app`'s auth service with the admin permission, which
+    // the demo login does not have
+    pub(crate) fn admin_token() -> String {
+        let config = AuthConfig::default();
+        let claims = Claims {
+            iss: config.issuer.clone(),
+            aud: config.audience.clone(),
+            ..user(Uuid::new_v4(), &["admin"]).claims
+        };
+        let key = jsonwebtoken::EncodingKey::from_secret(config.jwt_secret.as_bytes());
+        jsonwebtoken::encode(&jsonwebtoken::Header::default(), &claims, &key).unwrap()
+    }
+
+    pub(crate) async fn envelope(
+        router: &Router,
+        token: Option<&str>,
+        method: Method,
+        uri: &str,
+        body: Option<serde_json::Value>,
+    ) -> ApiResponse<serde_json::Value> {
+        let mut request = Request::builder().method(method.clone()).uri(uri).header("content-type", "application/json");
+        if let Some(token) = token {
+            request = request.header("authorization", format!("Bearer {}", token));
+        }
+        let request = request.body(body.map_or_else(Body::empty, |b| Body::from(b.to_string()))).unwrap();
+
+        let response = router.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let header_id = response.headers()["x-request-id"].to_str().unwrap().to_string();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let raw: serde_json::Value = serde_json::from_slice(&bytes)
+            .unwrap_or_else(|e| panic!("{} {} is not JSON ({}): {}", method, uri, e, String::from_utf8_lossy(&bytes)));
+
+        let extra: Vec<&String> = raw
+            .as_object()
+            .unwrap_or_else(|| panic!("{} {} is not an object: {}", method, uri, raw))
+            .keys()
+            .filter(|key| !ENVELOPE_FIELDS.contains(&key.as_str()))
+            .collect();
+        assert!(extra.is_empty(), "{} {} has fields outside the envelope: {:?}", method, uri, extra);
+
+        let envelope: ApiResponse<serde_json::Value> = serde_json::from_value(raw.clone())
+            .unwrap_or_else(|e| panic!("{} {} does not match the envelope ({}): {}", method, uri, e, raw));
+        assert_eq!(envelope.success, status.is_success(), "{} {}: {}", method, uri, raw);
+        assert_eq!(envelope.request_id.as_deref(), Some(header_id.as_str()), "{} {}", method, uri);
+        if !envelope.success {
+            assert!(envelope.error_code.is_some(), "{} {} failed without an error code: {}", method, uri, raw);
+        }
+        envelope
+    }
+
+    #[tokio::test]
+    async fn test_every_endpoint_answers_with_the_envelope() {
+        let dir = tempfile::tempdir().unwrap();
+        let (router, token) = app(&dir).await;
+        let token = Some(token.as_str());
+
+        let public = [
+            "/health",
+            "/health/ready",
+            "/health/live",
+            "/health/metrics",
+        ];
+        for uri in public {
+            envelope(&router, None, Method::GET, uri, None).await;
+        }
+        let login = serde_json::json!({ "email": "demo@example.com", "password": "password" });
+        envelope(&router, None, Method::POST, "/auth/login", Some(login)).await;
+        let wrong = serde_json::json!({ "email": "demo@example.com", "password": "wrong" });
+        envelope(&router, None, Method::POST, "/auth/login", Some(wrong)).await;
+
+        let session = envelope(&router, token, Method::POST, "/api/v1/conversation/sessions", Some(serde_json::json!({}))).await;
+        let session_id = session.data.unwrap()["session_id"].as_str().unwrap().to_string();
+
+        let protected = [
+            format!("/api/v1/conversation/sessions/{}", session_id),
+            format!("/api/v1/conversation/sessions/{}/history", session_id),
+            "/api/v1/conversation/active".to_string(),
+            "/api/v1/tasks".to_string(),
+            "/api/v1/tasks/time-report".to_string(),
+            "/api/v1/knowledge/documents".to_string(),
+            "/api/v1/knowledge/search?q=rent".to_string(),
+            "/api/v1/knowledge/suggest?q=re".to_string(),
+            "/api/v1/knowledge/digests".to_string(),
+            "/api/v1/briefing/history".to_string(),
+            "/api/v1/plugins".to_string(),
+            "/api/v1/plugins/policy".to_string(),
+            "/api/v1/commands".to_string(),
+            "/api/v1/me/activity".to_string(),
+            "/api/v1/me/focus".to_string(),
+            "/api/v1/sync".to_string(),
+            "/api/v1/onboarding".to_string(),
+            "/api/v1/admin/flags".to_string(),
+            "/api/v1/admin/audit".to_string(),
+            "/api/v1/admin/plugin-calls".to_string(),
+            "/api/v1/tasks/not-a-uuid".to_string(),
+            "/api/v1/no-such-route".to_string(),
+        ];
+        for uri in &protected {
+            envelope(&router, token, Method::GET, uri, None).await;
+        }
+
+        // Rejections from validation and from axum's extractors use it too
+        let invalid = serde_json::json!({ "description": "", "priority": "urgent", "tags": [] });
+        let rejected = envelope(&router, token, Method::POST, "/api/v1/tasks", Some(invalid)).await;
+        assert_eq!(rejected.error_code, Some(rusty_ai_common::ErrorCode::ValidationError));
+        assert!(!rejected.fields.is_empty());
+        let malformed = envelope(&router, token, Method::POST, "/api/v1/tasks/not-a-uuid/complete", None).await;
+        assert_eq!(malformed.error_code, Some(rusty_ai_common::ErrorCode::BadRequest));
+        let unauthenticated = envelope(&router, None, Method::GET, "/api/v1/tasks", None).await;
+        assert_eq!(unauthenticated.error_code, Some(rusty_ai_common::ErrorCode::AuthenticationError));
+    }
+
+    #[tokio::test]
+    async fn test_listings_report_pagination() {
+        let dir = tempfile::tempdir().unwrap();
+        let (router, token) = app(&dir).await;
+        let token = Some(token.as_str());
+
+        let session = envelope(&router, token, Method::POST, "/api/v1/conversation/sessions", Some(serde_json::json!({}))).await;
+        let session_id = session.data.unwrap()["session_id"].as_str().unwrap().to_string();
+        let uri = format!("/api/v1/conversation/sessions/{}/history", session_id);
+        let history = envelope(&router, token, Method::GET, &uri, None).await;
+        assert_eq!(history.pagination, Some(rusty_ai_common::Pagination::new(Some(0), None)));
+
+        for uri in ["/api/v1/me/activity?limit=5", "/api/v1/sync"] {
+            let page = envelope(&router, token, Method::GET, uri, None).await;
+            let pagination = page.pagination.unwrap_or_else(|| panic!("{} has no pagination", uri));
+            assert!(!pagination.has_more(), "{}", uri);
+        }
+    }
+}
@@ -0,0 +1,115 @@
+use crate::{
+    auth::AuthenticatedUser,
+    create_success_response,
+    error::{validation_error, ApiError, ApiResult},
+};
+use axum::{extract::{Path, State}, routing::{get, post}, Json, Router};
+use rusty_ai_core::onboarding::{self, OnboardingStep};
+use rusty_ai_core::{events::AssistantEvent, AssistantCore};
+use std::sync::Arc;
+
+pub fn routes(core: Arc<AssistantCore>) -> Router {
+    Router::new()
+        .route("/", get(get_onboarding))
+        .route("/steps/:step/skip", post(skip_step))
+        .route("/seed", post(seed_samples))
+        .with_state(core)
+}
+
+// The caller's setup steps; each completes when the user does what it asks
+async fn get_onboarding(
+    State(core): State<Arc<AssistantCore>>,
+    user: AuthenticatedUser,
+) -> ApiResult<Json<serde_json::Value>> {
+    Ok(create_success_response(core.onboarding.status(user.claims.user_id)))
+}
+
+async fn skip_step(
+    State(core): State<Arc<AssistantCore>>,
+    Path(step): Path<String>,
+    user: AuthenticatedUser,
+) -> ApiResult<Json<serde_json::Value>> {
+    let step = OnboardingStep::parse(&step)
+        .ok_or_else(|| validation_error(&format!("Unknown onboarding step: {}", step)))?;
+    let status = core.onboarding.skip(user.claims.user_id, step)
+        .map_err(|e| ApiError::CoreService(e))?;
+
+    Ok(create_success_response(status))
+}
+
+// Adds the bundled sample documents and a few example tasks, so search and
+// the briefing have something to show on a fresh install. Once per user
+async fn seed_samples(
+    State(core): State<Arc<AssistantCore>>,
+    user: AuthenticatedUser,
+) -> ApiResult<Json<serde_json::Value>> {
+    let user_id = user.claims.user_id;
+    if !core.onboarding.mark_seeded(user_id).map_err(|e| ApiError::CoreService(e))? {
+        return Err(ApiError::Conflict("Sample content was already added".to_string()));
+    }
+
+    let documents = onboarding::sample_documents();
+    for document in &documents {
+        core.storage.store_document(document).await
+            .map_err(|e| ApiError::CoreService(e))?;
+        core.suggestions.document_added(document);
+    }
+    let tasks = onboarding::sample_tasks();
+    for task in &tasks {
+        core.storage.store_task(task).await
+            .map_err(|e| ApiError::CoreService(e))?;
+        core.events.publish(AssistantEvent::TaskStatusChanged { user_id: Some(user_id), task: task.clone() });
+    }
+
+    Ok(create_success_response(serde_json::json!({
+        "documents": documents.iter().map(|d| serde_json::json!({ "id": d.id, "title": d.title })).collect::<Vec<_>>(),
+        "tasks": tasks.iter().map(|t| serde_json::json!({ "id": t.id, "name": t.name })).collect::<Vec<_>>(),
+        "onboarding": core.onboarding.status(user_id),
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::routes::tests::{core, user};
+    use rusty_ai_core::events::UserAction;
+    use uuid::Uuid;
+
+    #[tokio::test]
+    async fn test_seeding_adds_samples_once_and_actions_complete_steps() {
+        let dir = tempfile::tempdir().unwrap();
+        let core = core(&dir).await;
+        let listener = core.onboarding.listen(&core.events);
+        let user_id = Uuid::new_v4();
+        let caller = || user(user_id, &["read", "write"]);
+
+        let Json(seeded) = seed_samples(State(core.clone()), caller()).await.unwrap();
+        assert_eq!(seeded["data"]["documents"].as_array().unwrap().len(), 3);
+        assert!(seeded["data"]["onboarding"]["seeded_at"].is_string());
+        assert!(!core.storage.search_documents("lease", 10).await.unwrap().is_empty());
+        let pending = core.storage.get_pending_tasks().await.unwrap();
+        assert!(pending.iter().any(|t| t.tags.contains(&onboarding::SAMPLE_TAG.to_string())));
+
+        let again = seed_samples(State(core.clone()), caller()).await;
+        assert!(matches!(again, Err(ApiError::Conflict(_))));
+
+        // Seeding is not the user's own upload
+        let Json(status) = get_onboarding(State(core.clone()), caller()).await.unwrap();
+        assert_eq!(status["data"]["remaining"].as_array().unwrap().len(), 5);
+
+        core.events.publish(AssistantEvent::UserAction { user_id, action: UserAction::DocumentUploaded });
+        let Json(status) = skip_step(State(core.clone()), Path("try_voice".to_string()), caller()).await.unwrap();
+        assert!(status["data"]["remaining"].as_array().unwrap().iter().all(|s| s != "try_voice"));
+        for _ in 0..100 {
+            if !core.onboarding.status(user_id).remaining.contains(&OnboardingStep::UploadDocument) {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        assert_eq!(core.onboarding.status(user_id).remaining.len(), 3);
+
+        let unknown = skip_step(State(core.clone()), Path("fly".to_string()), caller()).await;
+        assert!(unknown.is_err());
+        listener.abort();
+    }
+}
@@ -1,53 +1,621 @@
-use crate::{auth::AuthenticatedUser, create_success_response, error::ApiResult};
-use axum::{extract::{Path, State}, routing::{get, post}, Json, Router};
-use rusty_ai_core::AssistantCore;
+use crate::{
+    audit::AdminAction,
+    auth::AuthenticatedUser,
+    create_success_response,
+    error::{validation_error, ApiError, ApiResult},
+    validation::ValidJson,
+};
+use axum::{
+    extract::{multipart::MultipartError, DefaultBodyLimit, Multipart, Path, State},
+    http::StatusCode,
+    routing::{delete, get, patch, post},
+    Json, Router,
+};
+use rusty_ai_common::api::{
+    ExecutePluginRequest, ExecutePluginResponse, InstallPluginRequest, PluginCutoverRequest, UpdatePluginConfigRequest,
+};
+use rusty_ai_common::AssistantError;
+use rusty_ai_plugins::{
+    CallOrigin, CanaryConfig, PermissionPolicy, PluginContext, PluginHealth, PluginMarketplace, PluginSignature,
+};
+use std::collections::{BTreeSet, HashMap};
 use std::sync::Arc;
+use uuid::Uuid;
 
-pub fn routes(core: Arc<AssistantCore>) -> Router {
+/// Largest module accepted by `POST /api/v1/plugins`
+pub const MAX_PLUGIN_UPLOAD_BYTES: usize = 16 * 1024 * 1024;
+
+// Room for the multipart boundaries and the other fields around the module
+const UPLOAD_OVERHEAD_BYTES: usize = 64 * 1024;
+
+pub fn routes(marketplace: Arc<PluginMarketplace>) -> Router {
     Router::new()
-        .route("/", get(list_plugins))
-        .route("/:plugin_id", get(get_plugin).post(configure_plugin))
+        .route(
+            "/",
+            get(list_plugins)
+                .post(upload_plugin)
+                .layer(DefaultBodyLimit::max(MAX_PLUGIN_UPLOAD_BYTES + UPLOAD_OVERHEAD_BYTES)),
+        )
+        .route("/available", get(list_available_plugins))
+        .route("/install", post(install_plugin))
+        .route("/policy", get(get_permission_policy).put(update_permission_policy))
+        .route("/:plugin_id", get(get_plugin).delete(uninstall_plugin))
+        .route("/:plugin_id/execute", post(execute_plugin))
+        .route("/:plugin_id/config", patch(configure_plugin))
         .route("/:plugin_id/enable", post(enable_plugin))
         .route("/:plugin_id/disable", post(disable_plugin))
-        .with_state(core)
+        .route("/:plugin_id/versions", get(get_plugin_versions))
+        .route("/:plugin_id/cutover", post(cutover_plugin))
+        .route("/:plugin_id/canary", post(start_canary).delete(stop_canary))
+        .with_state(marketplace)
 }
 
+// Every plugin that is loaded or installed, with its versions, config and
+// health
 async fn list_plugins(
-    State(_core): State<Arc<AssistantCore>>,
+    State(marketplace): State<Arc<PluginMarketplace>>,
     _user: AuthenticatedUser,
 ) -> ApiResult<Json<serde_json::Value>> {
-    Ok(create_success_response(serde_json::json!({"plugins": []})))
+    let mut names: BTreeSet<String> = marketplace.manager().list_plugins().await.into_iter().collect();
+    names.extend(marketplace.installed().await.into_iter().map(|plugin| plugin.name));
+    let mut health = marketplace.manager().health_check_all().await;
+
+    let mut plugins = Vec::with_capacity(names.len());
+    for name in names {
+        let plugin_health = health.remove(&name);
+        plugins.push(describe_plugin(&marketplace, &name, plugin_health).await);
+    }
+    Ok(create_success_response(serde_json::json!({"plugins": plugins})))
 }
 
 async fn get_plugin(
-    State(_core): State<Arc<AssistantCore>>,
-    Path(_plugin_id): Path<String>,
+    State(marketplace): State<Arc<PluginMarketplace>>,
     _user: AuthenticatedUser,
+    Path(plugin_id): Path<String>,
 ) -> ApiResult<Json<serde_json::Value>> {
-    Ok(create_success_response(serde_json::json!({"plugin": {}})))
+    let loaded = marketplace.manager().list_plugins().await.contains(&plugin_id);
+    let installed = marketplace.installed().await.iter().any(|plugin| plugin.name == plugin_id);
+    if !loaded && !installed {
+        return Err(not_found(&plugin_id));
+    }
+    let health = marketplace.manager().health_check_all().await.remove(&plugin_id);
+    Ok(create_success_response(describe_plugin(&marketplace, &plugin_id, health).await))
 }
 
-async fn configure_plugin(
-    State(_core): State<Arc<AssistantCore>>,
-    Path(_plugin_id): Path<String>,
+async fn describe_plugin(marketplace: &PluginMarketplace, name: &str, health: Option<PluginHealth>) -> serde_json::Value {
+    let manager = marketplace.manager();
+    serde_json::json!({
+        "id": name,
+        "installed": marketplace.installed().await.into_iter().find(|plugin| plugin.name == name),
+        "versions": manager.plugin_versions(name).await,
+        "config": manager.plugin_config(name).await,
+        "health": health,
+    })
+}
+
+fn not_found(plugin_id: &str) -> ApiError {
+    ApiError::CoreService(AssistantError::NotFound(format!("Plugin not found: {}", plugin_id)))
+}
+
+// Uploading runs third-party code on this host, so it is admin only. The
+// module comes in a `file` field, named by a `name` field or else by its
+// file name without `.wasm`. A `signature` field holds the module's `.sig`
+// JSON; hosts that require signatures refuse modules without one
+async fn upload_plugin(
+    State(marketplace): State<Arc<PluginMarketplace>>,
+    admin: AdminAction,
+    mut multipart: Multipart,
+) -> ApiResult<Json<serde_json::Value>> {
+    let (mut name, mut file_name, mut module, mut signature) = (None, None, None, None);
+    while let Some(field) = multipart.next_field().await.map_err(multipart_error)? {
+        match field.name().map(str::to_string).as_deref() {
+            Some("name") => name = Some(field.text().await.map_err(multipart_error)?),
+            Some("signature") => {
+                let json = field.bytes().await.map_err(multipart_error)?;
+                let parsed: PluginSignature = serde_json::from_slice(&json).map_err(|e| {
+                    validation_error(&format!("`signature` must be a plugin signature with key_id and signature: {}", e))
+                })?;
+                signature = Some(parsed);
+            }
+            Some("file") => {
+                file_name = field.file_name().map(str::to_string);
+                module = Some(field.bytes().await.map_err(multipart_error)?);
+            }
+            _ => {}
+        }
+    }
+    let module = module.ok_or_else(|| validation_error("The module must be sent in a `file` field"))?;
+    if module.len() > MAX_PLUGIN_UPLOAD_BYTES {
+        return Err(ApiError::RequestTooLarge);
+    }
+    let name = name
+        .or_else(|| file_name.map(|file_name| file_name.trim_end_matches(".wasm").to_string()))
+        .ok_or_else(|| validation_error("Name the plugin in a `name` field or by the file name"))?;
+
+    let previous = marketplace.installed().await.into_iter().find(|p| p.name == name);
+    // A module that does not load is the uploader's to fix
+    let installed = marketplace.install_upload(&name, &module, signature.as_ref()).await.map_err(|e| match e {
+        AssistantError::Plugin(message) => validation_error(&message),
+        e => ApiError::CoreService(e),
+    })?;
+
+    admin.record(
+        "plugin.upload",
+        &name,
+        previous.map(|p| serde_json::json!({"version": p.version, "sha256": p.sha256})),
+        Some(serde_json::json!({"version": installed.version, "sha256": installed.sha256})),
+    );
+    Ok(create_success_response(installed))
+}
+
+fn multipart_error(e: MultipartError) -> ApiError {
+    if e.status() == StatusCode::PAYLOAD_TOO_LARGE {
+        ApiError::RequestTooLarge
+    } else {
+        validation_error(&e.body_text())
+    }
+}
+
+// Runs one plugin function as the caller. The manager checks the plugin
+// permission policy and audits the call, refusing with a 403
+async fn execute_plugin(
+    State(marketplace): State<Arc<PluginMarketplace>>,
+    user: AuthenticatedUser,
+    Path(plugin_id): Path<String>,
+    Json(request): Json<ExecutePluginRequest>,
+) -> ApiResult<Json<serde_json::Value>> {
+    if request.function.trim().is_empty() {
+        return Err(validation_error("function must not be empty"));
+    }
+    let manager = marketplace.manager();
+    let context = PluginContext {
+        user_id: user.claims.user_id.to_string(),
+        session_id: request.session_id.map(|id| id.to_string()).unwrap_or_default(),
+        request_id: Uuid::new_v4().to_string(),
+        metadata: HashMap::new(),
+        started_at: std::time::Instant::now(),
+        permissions: user.claims.permissions.clone(),
+        origin: CallOrigin::Api,
+    };
+
+    let input = serde_json::to_vec(&request.input).map_err(|e| ApiError::Serialization(e.to_string()))?;
+    let result = manager
+        .execute_plugin_detailed(&plugin_id, &request.function, &input, context)
+        .await
+        .map_err(ApiError::CoreService)?;
+    let output = serde_json::from_slice(&result.output)
+        .unwrap_or_else(|_| serde_json::Value::String(String::from_utf8_lossy(&result.output).into_owned()));
+
+    Ok(create_success_response(ExecutePluginResponse {
+        plugin_id,
+        function: request.function,
+        output,
+        stdout: result.stdout,
+        stderr: result.stderr,
+        duration_ms: result.duration.as_millis() as u64,
+    }))
+}
+
+async fn list_available_plugins(
+    State(marketplace): State<Arc<PluginMarketplace>>,
     _user: AuthenticatedUser,
-    Json(_config): Json<serde_json::Value>,
 ) -> ApiResult<Json<serde_json::Value>> {
-    Ok(create_success_response(serde_json::json!({"message": "Plugin configured"})))
+    let plugins = marketplace.available().await;
+    Ok(create_success_response(serde_json::json!({"plugins": plugins})))
 }
 
-async fn enable_plugin(
-    State(_core): State<Arc<AssistantCore>>,
-    Path(_plugin_id): Path<String>,
+// Installing runs third-party code on this host, so it is admin only
+async fn install_plugin(
+    State(marketplace): State<Arc<PluginMarketplace>>,
+    admin: AdminAction,
+    Json(request): Json<InstallPluginRequest>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let previous = marketplace.installed().await.into_iter().find(|p| p.name == request.name);
+    let installed = marketplace.install(&request.name, request.version.as_deref()).await
+        .map_err(|e| crate::error::ApiError::CoreService(e))?;
+
+    admin.record(
+        "plugin.install",
+        &request.name,
+        previous.map(|p| serde_json::json!({"version": p.version})),
+        Some(serde_json::json!({"version": installed.version, "sha256": installed.sha256})),
+    );
+    Ok(create_success_response(installed))
+}
+
+async fn uninstall_plugin(
+    State(marketplace): State<Arc<PluginMarketplace>>,
+    admin: AdminAction,
+    Path(plugin_id): Path<String>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let removed = marketplace.uninstall(&plugin_id).await
+        .map_err(|e| crate::error::ApiError::CoreService(e))?;
+
+    admin.record(
+        "plugin.uninstall",
+        &plugin_id,
+        Some(serde_json::json!({"version": removed.version, "sha256": removed.sha256})),
+        None,
+    );
+    Ok(create_success_response(serde_json::json!({"message": "Plugin uninstalled", "plugin": removed})))
+}
+
+// Loaded versions, the active one and the canary, if any
+async fn get_plugin_versions(
+    State(marketplace): State<Arc<PluginMarketplace>>,
     _user: AuthenticatedUser,
+    Path(plugin_id): Path<String>,
 ) -> ApiResult<Json<serde_json::Value>> {
-    Ok(create_success_response(serde_json::json!({"message": "Plugin enabled"})))
+    let versions = marketplace.manager().plugin_versions(&plugin_id).await.ok_or_else(|| {
+        crate::error::ApiError::CoreService(rusty_ai_common::AssistantError::NotFound(format!(
+            "Plugin not found: {}",
+            plugin_id
+        )))
+    })?;
+    Ok(create_success_response(versions))
 }
 
-async fn disable_plugin(
-    State(_core): State<Arc<AssistantCore>>,
-    Path(_plugin_id): Path<String>,
+async fn cutover_plugin(
+    State(marketplace): State<Arc<PluginMarketplace>>,
+    admin: AdminAction,
+    Path(plugin_id): Path<String>,
+    Json(request): Json<PluginCutoverRequest>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let manager = marketplace.manager();
+    let previous = manager.plugin_versions(&plugin_id).await.map(|v| v.active);
+    manager.cutover(&plugin_id, &request.version).await
+        .map_err(|e| crate::error::ApiError::CoreService(e))?;
+
+    admin.record(
+        "plugin.cutover",
+        &plugin_id,
+        previous.map(|version| serde_json::json!({"version": version})),
+        Some(serde_json::json!({"version": request.version})),
+    );
+    Ok(create_success_response(manager.plugin_versions(&plugin_id).await))
+}
+
+async fn start_canary(
+    State(marketplace): State<Arc<PluginMarketplace>>,
+    admin: AdminAction,
+    Path(plugin_id): Path<String>,
+    Json(canary): Json<CanaryConfig>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let manager = marketplace.manager();
+    manager.start_canary(&plugin_id, canary.clone()).await
+        .map_err(|e| crate::error::ApiError::CoreService(e))?;
+
+    admin.record("plugin.canary.start", &plugin_id, None, serde_json::to_value(&canary).ok());
+    Ok(create_success_response(manager.plugin_versions(&plugin_id).await))
+}
+
+async fn stop_canary(
+    State(marketplace): State<Arc<PluginMarketplace>>,
+    admin: AdminAction,
+    Path(plugin_id): Path<String>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let manager = marketplace.manager();
+    let canary = manager.plugin_versions(&plugin_id).await.and_then(|v| v.canary);
+    manager.stop_canary(&plugin_id).await
+        .map_err(|e| crate::error::ApiError::CoreService(e))?;
+
+    admin.record("plugin.canary.stop", &plugin_id, serde_json::to_value(&canary).ok(), None);
+    Ok(create_success_response(manager.plugin_versions(&plugin_id).await))
+}
+
+async fn get_permission_policy(
+    State(marketplace): State<Arc<PluginMarketplace>>,
     _user: AuthenticatedUser,
 ) -> ApiResult<Json<serde_json::Value>> {
-    Ok(create_success_response(serde_json::json!({"message": "Plugin disabled"})))
-}
\ No newline at end of file
+    Ok(create_success_response(marketplace.manager().permission_policy()))
+}
+
+// Replaces the permission policy applied to plugin function calls
+async fn update_permission_policy(
+    State(marketplace): State<Arc<PluginMarketplace>>,
+    admin: AdminAction,
+    Json(policy): Json<PermissionPolicy>,
+) -> ApiResult<Json<serde_json::Value>> {
+    if policy.baseline_permission.trim().is_empty() {
+        return Err(validation_error("baseline_permission must not be empty"));
+    }
+
+    let manager = marketplace.manager();
+    let previous = manager.permission_policy();
+    manager.set_permission_policy(policy.clone());
+
+    admin.record(
+        "plugin.policy.update",
+        "plugin_permission_policy",
+        serde_json::to_value(&previous).ok(),
+        serde_json::to_value(&policy).ok(),
+    );
+    Ok(create_success_response(policy))
+}
+
+// Config applies to every user of the plugin, so changing it is admin only
+async fn configure_plugin(
+    State(marketplace): State<Arc<PluginMarketplace>>,
+    admin: AdminAction,
+    Path(plugin_id): Path<String>,
+    ValidJson(request): ValidJson<UpdatePluginConfigRequest>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let manager = marketplace.manager();
+    let previous = manager.plugin_config(&plugin_id).await;
+    let mut config = previous.clone();
+    config.enabled = request.enabled.unwrap_or(config.enabled);
+    config.priority = request.priority.unwrap_or(config.priority);
+    config.quota = request.quota.unwrap_or(config.quota);
+    config.settings.extend(request.settings);
+    let config = manager.configure_plugin(&plugin_id, config).await.map_err(ApiError::CoreService)?;
+
+    admin.record("plugin.configure", &plugin_id, serde_json::to_value(&previous).ok(), serde_json::to_value(&config).ok());
+    Ok(create_success_response(config))
+}
+
+async fn enable_plugin(
+    State(marketplace): State<Arc<PluginMarketplace>>,
+    admin: AdminAction,
+    Path(plugin_id): Path<String>,
+) -> ApiResult<Json<serde_json::Value>> {
+    set_enabled(&marketplace, admin, &plugin_id, true).await
+}
+
+async fn disable_plugin(
+    State(marketplace): State<Arc<PluginMarketplace>>,
+    admin: AdminAction,
+    Path(plugin_id): Path<String>,
+) -> ApiResult<Json<serde_json::Value>> {
+    set_enabled(&marketplace, admin, &plugin_id, false).await
+}
+
+async fn set_enabled(
+    marketplace: &PluginMarketplace,
+    admin: AdminAction,
+    plugin_id: &str,
+    enabled: bool,
+) -> ApiResult<Json<serde_json::Value>> {
+    let manager = marketplace.manager();
+    let mut config = manager.plugin_config(plugin_id).await;
+    let was_enabled = config.enabled;
+    config.enabled = enabled;
+    // Settings given again are kept as they are
+    let config = manager.configure_plugin(plugin_id, config).await.map_err(ApiError::CoreService)?;
+
+    admin.record(
+        if enabled { "plugin.enable" } else { "plugin.disable" },
+        plugin_id,
+        Some(serde_json::json!({"enabled": was_enabled})),
+        Some(serde_json::json!({"enabled": enabled})),
+    );
+    Ok(create_success_response(config))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audit::tests::{admin_action, audit_trail};
+    use rusty_ai_core::audit::AuditFilter;
+    use crate::routes::tests::{admin_token, app, envelope};
+    use axum::body::Body;
+    use axum::http::{Method, Request};
+    use rusty_ai_common::ErrorCode;
+    use rusty_ai_plugins::{MarketplaceConfig, WasmPluginManager};
+    use tower::ServiceExt;
+
+    // A marketplace whose registry already lists weather 1.0.0
+    fn marketplace_with_installed_plugin(dir: &tempfile::TempDir) -> Arc<PluginMarketplace> {
+        std::fs::write(dir.path().join("weather.wasm"), b"\0asm").unwrap();
+        std::fs::write(
+            dir.path().join("installed.json"),
+            serde_json::json!([{
+                "name": "weather",
+                "version": "1.0.0",
+                "sha256": "abc",
+                "source": "https://plugins.example.com/index.json",
+                "installed_at": chrono::Utc::now(),
+            }])
+            .to_string(),
+        )
+        .unwrap();
+
+        let config = MarketplaceConfig {
+            plugin_directory: dir.path().to_path_buf(),
+            ..Default::default()
+        };
+        let manager = Arc::new(WasmPluginManager::new(dir.path()).unwrap());
+        Arc::new(PluginMarketplace::new(config, manager).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_plugin_deletion_is_audited() {
+        let dir = tempfile::tempdir().unwrap();
+        let marketplace = marketplace_with_installed_plugin(&dir);
+        let trail = audit_trail().await;
+
+        let admin = admin_action(&trail, "DELETE /api/v1/plugins/:plugin_id");
+        let actor = admin.user().claims.user_id.to_string();
+        uninstall_plugin(State(marketplace.clone()), admin, Path("weather".to_string()))
+            .await
+            .unwrap();
+        trail.flush().await;
+
+        assert!(!dir.path().join("weather.wasm").exists());
+        let entries = trail
+            .query(&AuditFilter { action: Some("plugin.uninstall".to_string()), ..Default::default() })
+            .await
+            .unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].actor, actor);
+        assert_eq!(entries[0].target, "weather");
+        assert_eq!(entries[0].before.as_ref().unwrap()["version"], "1.0.0");
+        assert_eq!(entries[0].after, None);
+        assert_eq!(entries[0].request_id.as_deref(), Some("req-1"));
+        assert!(entries[0].complete);
+    }
+
+    #[tokio::test]
+    async fn test_failed_plugin_deletion_leaves_incomplete_entry() {
+        let dir = tempfile::tempdir().unwrap();
+        let marketplace = marketplace_with_installed_plugin(&dir);
+        let trail = audit_trail().await;
+
+        let admin = admin_action(&trail, "DELETE /api/v1/plugins/:plugin_id");
+        assert!(uninstall_plugin(State(marketplace), admin, Path("notes".to_string())).await.is_err());
+        trail.flush().await;
+
+        let entries = trail.query(&AuditFilter::default()).await.unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].action, "DELETE /api/v1/plugins/:plugin_id");
+        assert!(!entries[0].complete);
+    }
+
+    #[tokio::test]
+    async fn test_policy_update_is_audited_with_before_and_after() {
+        let dir = tempfile::tempdir().unwrap();
+        let marketplace = marketplace_with_installed_plugin(&dir);
+        let trail = audit_trail().await;
+
+        let admin = admin_action(&trail, "PUT /api/v1/plugins/policy");
+        update_permission_policy(State(marketplace.clone()), admin, Json(PermissionPolicy::new("plugins:run")))
+            .await
+            .unwrap();
+        trail.flush().await;
+
+        assert_eq!(marketplace.manager().permission_policy().baseline_permission, "plugins:run");
+        let entries = trail
+            .query(&AuditFilter { action: Some("plugin.policy.update".to_string()), ..Default::default() })
+            .await
+            .unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].before.as_ref().unwrap()["baseline_permission"], "plugins:execute");
+        assert_eq!(entries[0].after.as_ref().unwrap()["baseline_permission"], "plugins:run");
+        assert_eq!(trail.write_failures(), 0);
+    }
+
+    const BOUNDARY: &str = "rusty-ai-upload-boundary";
+
+    // POST /api/v1/plugins with the module in a multipart `file` field
+    async fn upload(router: &Router, token: &str, name: &str, module: &[u8]) -> axum::response::Response {
+        let mut body = format!(
+            "--{b}\r\nContent-Disposition: form-data; name=\"name\"\r\n\r\n{name}\r\n\
+             --{b}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{name}.wasm\"\r\n\
+             Content-Type: application/wasm\r\n\r\n",
+            b = BOUNDARY,
+        )
+        .into_bytes();
+        body.extend_from_slice(module);
+        body.extend_from_slice(format!("\r\n--{}--\r\n", BOUNDARY).as_bytes());
+
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/api/v1/plugins")
+            .header("content-type", format!("multipart/form-data; boundary={}", BOUNDARY))
+            .header("authorization", format!("Bearer {}", token))
+            .body(Body::from(body))
+            .unwrap();
+        router.clone().oneshot(request).await.unwrap()
+    }
+
+    async fn into_envelope(response: axum::response::Response) -> rusty_ai_common::ApiResponse<serde_json::Value> {
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_uploaded_plugin_can_be_run_configured_and_removed() {
+        let dir = tempfile::tempdir().unwrap();
+        let (router, demo_token) = app(&dir).await;
+        let admin = admin_token();
+        let echo = wat::parse_str(include_str!("../../../plugins/fixtures/echo.wat")).unwrap();
+
+        // Running code on the host takes the admin permission
+        let refused = upload(&router, &demo_token, "echo", &echo).await;
+        assert_eq!(refused.status(), StatusCode::FORBIDDEN);
+
+        let response = upload(&router, &admin, "echo", &echo).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let installed = into_envelope(response).await.data.unwrap();
+        assert_eq!(installed["name"], "echo");
+        assert_eq!(installed["source"], rusty_ai_plugins::UPLOAD_SOURCE);
+
+        let listed = envelope(&router, Some(&admin), Method::GET, "/api/v1/plugins", None).await;
+        let plugins = listed.data.unwrap()["plugins"].clone();
+        assert_eq!(plugins.as_array().unwrap().len(), 1);
+        assert_eq!(plugins[0]["id"], "echo");
+        assert_eq!(plugins[0]["config"]["enabled"], true);
+
+        let input = serde_json::json!({"greeting": "hello"});
+        let executed = envelope(
+            &router,
+            Some(&admin),
+            Method::POST,
+            "/api/v1/plugins/echo/execute",
+            Some(serde_json::json!({"function": "echo", "input": input})),
+        )
+        .await;
+        let executed = executed.data.unwrap();
+        assert_eq!(executed["output"], input);
+        assert_eq!(executed["function"], "echo");
+
+        // The demo user lacks the baseline plugin permission
+        let denied = envelope(
+            &router,
+            Some(&demo_token),
+            Method::POST,
+            "/api/v1/plugins/echo/execute",
+            Some(serde_json::json!({"function": "echo", "input": input})),
+        )
+        .await;
+        assert_eq!(denied.error_code, Some(ErrorCode::AuthorizationError));
+
+        let configured = envelope(
+            &router,
+            Some(&admin),
+            Method::PATCH,
+            "/api/v1/plugins/echo/config",
+            Some(serde_json::json!({"enabled": false, "settings": {"mode": "loud"}})),
+        )
+        .await;
+        let configured = configured.data.unwrap();
+        assert_eq!(configured["enabled"], false);
+        assert_eq!(configured["settings"]["mode"], "loud");
+        let fetched = envelope(&router, Some(&admin), Method::GET, "/api/v1/plugins/echo", None).await;
+        assert_eq!(fetched.data.unwrap()["config"]["enabled"], false);
+
+        // A quota of zero is a validation error, not a config that panics later
+        let zero_quota = envelope(
+            &router,
+            Some(&admin),
+            Method::PATCH,
+            "/api/v1/plugins/echo/config",
+            Some(serde_json::json!({"quota": {"max_calls_per_hour": 0, "max_concurrent": null}})),
+        )
+        .await;
+        assert_eq!(zero_quota.error_code, Some(ErrorCode::ValidationError));
+        assert_eq!(zero_quota.fields[0].field, "quota.max_calls_per_hour");
+
+        envelope(&router, Some(&admin), Method::DELETE, "/api/v1/plugins/echo", None).await;
+        let listed = envelope(&router, Some(&admin), Method::GET, "/api/v1/plugins", None).await;
+        assert!(listed.data.unwrap()["plugins"].as_array().unwrap().is_empty());
+        let missing = envelope(&router, Some(&admin), Method::GET, "/api/v1/plugins/echo", None).await;
+        assert_eq!(missing.error_code, Some(ErrorCode::NotFound));
+    }
+
+    #[tokio::test]
+    async fn test_oversized_upload_is_rejected_before_loading() {
+        let dir = tempfile::tempdir().unwrap();
+        let (router, _) = app(&dir).await;
+
+        let module = vec![0u8; MAX_PLUGIN_UPLOAD_BYTES + 1];
+        let response = upload(&router, &admin_token(), "huge", &module).await;
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        let rejected = into_envelope(response).await;
+        assert!(!rejected.success);
+        assert_eq!(rejected.error_code, Some(ErrorCode::RequestTooLarge));
+
+        assert!(!dir.path().join("plugins").join("huge.wasm").exists());
+        let listed = envelope(&router, Some(&admin_token()), Method::GET, "/api/v1/plugins", None).await;
+        assert!(listed.data.unwrap()["plugins"].as_array().unwrap().is_empty());
+    }
+}
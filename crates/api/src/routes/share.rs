@@ -0,0 +1,309 @@
+use crate::{
+    auth::{AuthService, AuthenticatedUser},
+    create_success_response,
+    error::{authz_error, validation_error, ApiResult},
+    middleware::{rate_limiting_middleware, RateLimiter},
+};
+use axum::{
+    extract::{Path, State},
+    http::{header, HeaderValue, StatusCode},
+    response::{Html, IntoResponse, Response},
+    routing::{delete, get},
+    Extension, Json, Router,
+};
+use rusty_ai_common::{
+    api::{CreateShareLinkRequest, MessageResponse, ShareLinkResponse},
+    AssistantError, DailyBriefing, Document,
+};
+use rusty_ai_core::{
+    response_processing::ResponseDestination,
+    sharing::{ShareLookup, SharedResource, DEFAULT_SHARE_TTL_HOURS},
+    AssistantCore,
+};
+use std::{sync::Arc, time::Duration};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+// Public share pages are unauthenticated, so they get a tighter budget than
+// the API as a whole
+const SHARE_REQUESTS_PER_MINUTE: u32 = 30;
+
+/// Public, unauthenticated routes mounted at `/share`
+pub fn routes(core: Arc<AssistantCore>) -> Router {
+    let rate_limiter = Arc::new(RateLimiter::new(SHARE_REQUESTS_PER_MINUTE, Duration::from_secs(60)));
+
+    Router::new()
+        .route("/:token", get(view_shared))
+        .with_state(core)
+        .layer(axum::middleware::from_fn_with_state(rate_limiter, rate_limiting_middleware))
+}
+
+/// Link management, mounted under the protected `/api/v1/share-links`
+pub fn management_routes(core: Arc<AssistantCore>) -> Router {
+    Router::new()
+        .route("/:id", delete(revoke_share_link))
+        .with_state(core)
+}
+
+// Shared by the briefing and knowledge routes. Resources carry no owner, so
+// whoever may modify them (write permission) owns them.
+pub(crate) async fn create_share_link(
+    core: &AssistantCore,
+    auth_service: &AuthService,
+    user: &AuthenticatedUser,
+    resource: SharedResource,
+    request: CreateShareLinkRequest,
+) -> ApiResult<Json<serde_json::Value>> {
+    if !auth_service.has_permission(&user.claims, "write") {
+        return Err(authz_error("Only the owner can share this resource"));
+    }
+
+    let exists = match &resource {
+        SharedResource::Briefing(id) => core.storage.get_briefing(*id).await?.is_some(),
+        SharedResource::Document(id) => core.storage.get_document(*id).await?.is_some(),
+    };
+    if !exists {
+        return Err(AssistantError::NotFound("Resource not found".to_string()).into());
+    }
+
+    let hours = request.expires_in_hours.unwrap_or(DEFAULT_SHARE_TTL_HOURS);
+    let (link, token) = core
+        .share_links
+        .create(resource, user.claims.user_id, chrono::Duration::hours(hours))
+        .map_err(|e| validation_error(&e.to_string()))?;
+
+    Ok(create_success_response(ShareLinkResponse {
+        id: link.id,
+        url: format!("/share/{}", token),
+        expires_at: link.expires_at,
+    }))
+}
+
+async fn revoke_share_link(
+    State(core): State<Arc<AssistantCore>>,
+    Extension(auth_service): Extension<Arc<AuthService>>,
+    Path(id): Path<Uuid>,
+    user: AuthenticatedUser,
+) -> ApiResult<Json<serde_json::Value>> {
+    if !auth_service.has_permission(&user.claims, "write") {
+        return Err(authz_error("Only the owner can revoke share links"));
+    }
+
+    if !core.share_links.revoke(id, user.claims.user_id)? {
+        return Err(AssistantError::NotFound("Share link not found".to_string()).into());
+    }
+
+    Ok(create_success_response(MessageResponse::new("Share link revoked")))
+}
+
+async fn view_shared(State(core): State<Arc<AssistantCore>>, Path(token): Path<String>) -> Response {
+    // Unknown and forged tokens get the same answer, so probing reveals nothing
+    let link = match core.share_links.resolve(&token) {
+        ShareLookup::Active(link) => link,
+        ShareLookup::Expired | ShareLookup::Revoked => return gone(),
+        ShareLookup::NotFound => return not_found(),
+    };
+
+    let body = match &link.resource {
+        SharedResource::Briefing(id) => core
+            .storage
+            .get_briefing(*id)
+            .await
+            .map(|b| b.map(|b| render_briefing(&scrub_briefing(&core, b)))),
+        SharedResource::Document(id) => core.storage.get_document(*id).await.map(|d| d.map(|d| render_document(&d))),
+    };
+
+    match body {
+        Ok(Some(body)) => {
+            info!("Served share link {}", link.id);
+            page(StatusCode::OK, body)
+        }
+        // The shared resource was deleted after the link was created
+        Ok(None) => gone(),
+        Err(e) => {
+            warn!("Failed to load shared resource for link {}: {}", link.id, e);
+            page(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                notice("Something went wrong", "This page could not be loaded. Please try again later."),
+            )
+        }
+    }
+}
+
+fn gone() -> Response {
+    page(
+        StatusCode::GONE,
+        notice(
+            "This link is no longer available",
+            "The share link has expired or was revoked by its owner.",
+        ),
+    )
+}
+
+fn not_found() -> Response {
+    page(
+        StatusCode::NOT_FOUND,
+        notice("Link not found", "Check that you copied the whole link."),
+    )
+}
+
+fn notice(title: &str, message: &str) -> String {
+    format!("<h1>{}</h1>\n<p class=\"notice\">{}</p>", escape_html(title), escape_html(message))
+}
+
+// Briefings are assistant output, so the copy leaving through the link goes
+// through the response pipeline, PII scrubbing included
+fn scrub_briefing(core: &AssistantCore, mut briefing: DailyBriefing) -> DailyBriefing {
+    for section in &mut briefing.sections {
+        section.content = core
+            .response_processor
+            .process(&section.content, None, ResponseDestination::ShareLink)
+            .text;
+    }
+    briefing
+}
+
+fn render_briefing(briefing: &DailyBriefing) -> String {
+    let mut body = format!("<h1>Daily briefing &middot; {}</h1>\n", briefing.date.format("%A, %B %-d, %Y"));
+    for section in &briefing.sections {
+        body.push_str(&format!(
+            "<section>\n<h2>{}</h2>\n<p>{}</p>\n</section>\n",
+            escape_html(&section.title),
+            escape_html(&section.content).replace('\n', "<br>"),
+        ));
+    }
+    body
+}
+
+fn render_document(document: &Document) -> String {
+    format!(
+        "<h1>{}</h1>\n<pre>{}</pre>",
+        escape_html(&document.title),
+        escape_html(&document.content),
+    )
+}
+
+fn page(status: StatusCode, body: String) -> Response {
+    let html = format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+         <meta name=\"robots\" content=\"noindex\">\n<title>Rusty AI</title>\n\
+         <style>body{{font-family:system-ui,sans-serif;max-width:48rem;margin:2rem auto;padding:0 1rem;color:#222}}\
+         header{{color:#b7410e;font-weight:600;margin-bottom:2rem}}pre{{white-space:pre-wrap}}.notice{{color:#666}}</style>\n\
+         </head>\n<body>\n<header>Rusty AI &middot; shared read-only</header>\n<main>\n{}\n</main>\n</body>\n</html>\n",
+        body
+    );
+
+    let mut response = (status, Html(html)).into_response();
+    let headers = response.headers_mut();
+    // The token is in the URL; keep it out of caches and referrers
+    headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+    headers.insert(header::REFERRER_POLICY, HeaderValue::from_static("no-referrer"));
+    headers.insert("x-robots-tag", HeaderValue::from_static("noindex"));
+    response
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::AuthConfig;
+    use crate::routes::tests::user;
+    use axum::body::Body;
+    use rusty_ai_core::CoreConfig;
+    use tower::ServiceExt;
+
+    async fn create_test_core() -> Arc<AssistantCore> {
+        Arc::new(AssistantCore::new(CoreConfig::default()).await.unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_only_owner_can_create_links() {
+        let core = create_test_core().await;
+        let auth_service = AuthService::new(AuthConfig::default());
+
+        let result = create_share_link(
+            &core,
+            &auth_service,
+            &user(Uuid::new_v4(), &["read"]),
+            SharedResource::Briefing(Uuid::new_v4()),
+            CreateShareLinkRequest::default(),
+        )
+        .await;
+        assert!(matches!(result, Err(crate::error::ApiError::Authorization(_))));
+    }
+
+    #[tokio::test]
+    async fn test_revoked_link_is_gone() {
+        let core = create_test_core().await;
+        let owner = Uuid::new_v4();
+        let (link, token) = core
+            .share_links
+            .create(SharedResource::Document(Uuid::new_v4()), owner, chrono::Duration::hours(1))
+            .unwrap();
+        core.share_links.revoke(link.id, owner).unwrap();
+
+        let (status, body) = get_status(core, &format!("/{}", token)).await;
+        assert_eq!(status, StatusCode::GONE);
+        assert!(body.contains("no longer available"));
+    }
+
+    #[tokio::test]
+    async fn test_only_the_creator_can_revoke_a_link() {
+        let core = create_test_core().await;
+        let auth_service = Arc::new(AuthService::new(AuthConfig::default()));
+        let owner = user(Uuid::new_v4(), &["read", "write"]);
+        let other = user(Uuid::new_v4(), &["read", "write"]);
+        let (link, token) = core
+            .share_links
+            .create(SharedResource::Document(Uuid::new_v4()), owner.claims.user_id, chrono::Duration::hours(1))
+            .unwrap();
+
+        let result =
+            revoke_share_link(State(core.clone()), Extension(auth_service.clone()), Path(link.id), other).await;
+        assert!(matches!(
+            result,
+            Err(crate::error::ApiError::CoreService(AssistantError::NotFound(_)))
+        ));
+        assert!(matches!(core.share_links.resolve(&token), ShareLookup::Active(_)));
+
+        revoke_share_link(State(core.clone()), Extension(auth_service), Path(link.id), owner)
+            .await
+            .unwrap();
+        assert!(matches!(core.share_links.resolve(&token), ShareLookup::Revoked));
+    }
+
+    #[tokio::test]
+    async fn test_unknown_and_forged_tokens_are_indistinguishable() {
+        let core = create_test_core().await;
+        let (_, token) = core
+            .share_links
+            .create(SharedResource::Document(Uuid::new_v4()), Uuid::new_v4(), chrono::Duration::hours(1))
+            .unwrap();
+        let (nonce, _) = token.split_once('.').unwrap();
+        let forged = format!("{}.{}", nonce, "0".repeat(32));
+
+        let unknown = get_status(core.clone(), "/deadbeef.cafebabe").await;
+        let tampered = get_status(core, &format!("/{}", forged)).await;
+        assert_eq!(unknown.0, StatusCode::NOT_FOUND);
+        assert_eq!(unknown, tampered);
+    }
+
+    #[test]
+    fn test_shared_content_is_escaped() {
+        assert_eq!(escape_html("<script>alert('x')</script>"), "&lt;script&gt;alert(&#39;x&#39;)&lt;/script&gt;");
+    }
+}
@@ -0,0 +1,50 @@
+use crate::{
+    auth::AuthenticatedUser,
+    create_paginated_response,
+    error::{validation_error, ApiResult},
+};
+use axum::{
+    extract::{Query, State},
+    routing::get,
+    Json, Router,
+};
+use rusty_ai_common::Pagination;
+use rusty_ai_core::sync::{SyncCursor, SyncResponse, DEFAULT_SYNC_PAGE_SIZE};
+use rusty_ai_core::AssistantCore;
+use serde::Deserialize;
+use std::sync::Arc;
+
+pub fn routes(core: Arc<AssistantCore>) -> Router {
+    Router::new()
+        .route("/", get(get_changes))
+        .with_state(core)
+}
+
+#[derive(Debug, Deserialize)]
+struct SyncQuery {
+    since: Option<String>,
+    limit: Option<usize>,
+}
+
+// Everything that changed for the caller since `since`, grouped by record
+// type. Clients keep requesting with the returned cursor while `has_more`
+async fn get_changes(
+    State(core): State<Arc<AssistantCore>>,
+    user: AuthenticatedUser,
+    Query(query): Query<SyncQuery>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let since = match query.since.as_deref() {
+        Some(cursor) => SyncCursor::decode(cursor).ok_or_else(|| validation_error("Invalid sync cursor"))?,
+        None => SyncCursor::START,
+    };
+
+    let batch = core
+        .storage
+        .get_changes_since(user.claims.user_id, since, query.limit.unwrap_or(DEFAULT_SYNC_PAGE_SIZE))
+        .await
+        .map_err(|e| crate::error::ApiError::CoreService(e))?;
+
+    let changes = SyncResponse::from(batch);
+    let next_cursor = changes.has_more.then(|| changes.cursor.clone());
+    Ok(create_paginated_response(changes, Pagination::new(None, next_cursor)))
+}
@@ -1,41 +1,62 @@
-use crate::{auth::AuthenticatedUser, create_success_response, error::ApiResult};
-use axum::{extract::{Path, State}, routing::{get, post, put}, Json, Router};
-use rusty_ai_core::AssistantCore;
+use crate::{auth::AuthenticatedUser, create_success_response, error::{validation_error, ApiResult}, validation::ValidJson};
+use axum::{extract::{Path, Query, State}, routing::{get, post, put}, Json, Router};
+use rusty_ai_core::time_tracking::{self, ReportPeriod};
+use rusty_ai_core::{events::AssistantEvent, AssistantCore};
+use rusty_ai_common::api::{CreateTaskRequest, MessageResponse, TaskTimerRequest};
+use rusty_ai_common::Task;
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use uuid::Uuid;
 
-#[derive(Serialize, Deserialize)]
-pub struct CreateTaskRequest {
-    pub name: String,
-    pub description: String,
-    pub priority: String,
-    pub due_date: Option<chrono::DateTime<chrono::Utc>>,
-    pub tags: Vec<String>,
-}
-
 pub fn routes(core: Arc<AssistantCore>) -> Router {
     Router::new()
         .route("/", get(list_tasks).post(create_task))
+        .route("/time-report", get(time_report))
         .route("/:id", get(get_task).put(update_task).delete(delete_task))
         .route("/:id/complete", post(complete_task))
+        .route("/:id/timer/start", post(start_timer))
+        .route("/:id/timer/stop", post(stop_timer))
         .with_state(core)
 }
 
+// A task with the time tracked on it so far
+#[derive(Debug, Serialize)]
+struct TrackedTask {
+    #[serde(flatten)]
+    task: Task,
+    tracked_seconds: i64,
+    timer_running: bool,
+}
+
+async fn with_tracked_time(core: &AssistantCore, task: Task) -> ApiResult<TrackedTask> {
+    let entries = core.storage.get_task_time_entries(task.id).await
+        .map_err(|e| crate::error::ApiError::CoreService(e))?;
+
+    Ok(TrackedTask {
+        tracked_seconds: time_tracking::tracked_seconds(&entries, chrono::Utc::now()),
+        timer_running: entries.iter().any(|entry| entry.is_running()),
+        task,
+    })
+}
+
 async fn list_tasks(
     State(core): State<Arc<AssistantCore>>,
     _user: AuthenticatedUser,
 ) -> ApiResult<Json<serde_json::Value>> {
     let pending_tasks = core.storage.get_pending_tasks().await
         .map_err(|e| crate::error::ApiError::CoreService(e))?;
-    
-    Ok(create_success_response(pending_tasks))
+
+    let mut tasks = Vec::with_capacity(pending_tasks.len());
+    for task in pending_tasks {
+        tasks.push(with_tracked_time(&core, task).await?);
+    }
+    Ok(create_success_response(tasks))
 }
 
 async fn create_task(
     State(core): State<Arc<AssistantCore>>,
-    _user: AuthenticatedUser,
-    Json(request): Json<CreateTaskRequest>,
+    user: AuthenticatedUser,
+    ValidJson(request): ValidJson<CreateTaskRequest>,
 ) -> ApiResult<Json<serde_json::Value>> {
     let priority = match request.priority.as_str() {
         "critical" => rusty_ai_common::TaskPriority::Critical,
@@ -59,6 +80,7 @@ async fn create_task(
     
     core.storage.store_task(&task).await
         .map_err(|e| crate::error::ApiError::CoreService(e))?;
+    core.events.publish(AssistantEvent::TaskStatusChanged { user_id: Some(user.claims.user_id), task: task.clone() });
     
     Ok(create_success_response(task))
 }
@@ -72,7 +94,7 @@ async fn get_task(
         .map_err(|e| crate::error::ApiError::CoreService(e))?;
     
     match task {
-        Some(task) => Ok(create_success_response(task)),
+        Some(task) => Ok(create_success_response(with_tracked_time(&core, task).await?)),
         None => Err(crate::error::ApiError::CoreService(
             rusty_ai_common::AssistantError::NotFound("Task not found".to_string())
         ))
@@ -83,9 +105,9 @@ async fn update_task(
     State(_core): State<Arc<AssistantCore>>,
     Path(_id): Path<Uuid>,
     _user: AuthenticatedUser,
-    Json(_request): Json<CreateTaskRequest>,
+    ValidJson(_request): ValidJson<CreateTaskRequest>,
 ) -> ApiResult<Json<serde_json::Value>> {
-    Ok(create_success_response(serde_json::json!({"message": "Task updated"})))
+    Ok(create_success_response(MessageResponse::new("Task updated")))
 }
 
 async fn delete_task(
@@ -93,16 +115,118 @@ async fn delete_task(
     Path(_id): Path<Uuid>,
     _user: AuthenticatedUser,
 ) -> ApiResult<Json<serde_json::Value>> {
-    Ok(create_success_response(serde_json::json!({"message": "Task deleted"})))
+    Ok(create_success_response(MessageResponse::new("Task deleted")))
 }
 
 async fn complete_task(
     State(core): State<Arc<AssistantCore>>,
     Path(id): Path<Uuid>,
-    _user: AuthenticatedUser,
+    user: AuthenticatedUser,
 ) -> ApiResult<Json<serde_json::Value>> {
     core.storage.update_task_status(id, rusty_ai_common::TaskStatus::Completed).await
         .map_err(|e| crate::error::ApiError::CoreService(e))?;
+    if let Some(task) = core.storage.get_task(id).await.map_err(|e| crate::error::ApiError::CoreService(e))? {
+        core.events.publish(AssistantEvent::TaskStatusChanged { user_id: Some(user.claims.user_id), task });
+    }
     
-    Ok(create_success_response(serde_json::json!({"message": "Task completed"})))
-}
\ No newline at end of file
+    Ok(create_success_response(MessageResponse::new("Task completed")))
+}
+
+// Starting a timer stops the caller's timer on any other task
+async fn start_timer(
+    State(core): State<Arc<AssistantCore>>,
+    Path(id): Path<Uuid>,
+    user: AuthenticatedUser,
+    request: Option<Json<TaskTimerRequest>>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let request = request.map(|Json(r)| r).unwrap_or_default();
+    if core.storage.get_task(id).await.map_err(|e| crate::error::ApiError::CoreService(e))?.is_none() {
+        return Err(crate::error::ApiError::CoreService(
+            rusty_ai_common::AssistantError::NotFound("Task not found".to_string())
+        ));
+    }
+
+    let started = core.storage.start_task_timer(user.claims.user_id, id, request.note).await
+        .map_err(|e| crate::error::ApiError::CoreService(e))?;
+
+    Ok(create_success_response(started))
+}
+
+async fn stop_timer(
+    State(core): State<Arc<AssistantCore>>,
+    Path(id): Path<Uuid>,
+    user: AuthenticatedUser,
+    request: Option<Json<TaskTimerRequest>>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let request = request.map(|Json(r)| r).unwrap_or_default();
+    let stopped = core.storage.stop_task_timer(user.claims.user_id, Some(id), request.note).await
+        .map_err(|e| crate::error::ApiError::CoreService(e))?;
+
+    match stopped {
+        Some(entry) => Ok(create_success_response(entry)),
+        None => Err(crate::error::ApiError::CoreService(
+            rusty_ai_common::AssistantError::NotFound("No timer is running on this task".to_string())
+        ))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct TimeReportQuery {
+    period: Option<String>,
+    /// IANA timezone the period's days start in; UTC when omitted
+    tz: Option<String>,
+}
+
+async fn time_report(
+    State(core): State<Arc<AssistantCore>>,
+    user: AuthenticatedUser,
+    Query(query): Query<TimeReportQuery>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let period = match query.period.as_deref() {
+        Some(period) => ReportPeriod::parse(period)
+            .ok_or_else(|| validation_error("period must be one of day, week, month"))?,
+        None => ReportPeriod::Week,
+    };
+    let timezone = time_tracking::parse_timezone(query.tz.as_deref().unwrap_or("UTC"))
+        .ok_or_else(|| validation_error("Unknown timezone"))?;
+
+    let report = time_tracking::time_report(core.storage.as_ref(), user.claims.user_id, period, timezone, chrono::Utc::now()).await
+        .map_err(|e| crate::error::ApiError::CoreService(e))?;
+
+    Ok(create_success_response(report))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validation::tests::{send, violations};
+    use axum::http::StatusCode;
+    use rusty_ai_core::CoreConfig;
+
+    async fn create_test_router() -> Router {
+        routes(Arc::new(AssistantCore::new(CoreConfig::default()).await.unwrap()))
+    }
+
+    #[tokio::test]
+    async fn test_create_task_rejects_invalid_payloads() {
+        let yesterday = chrono::Utc::now() - chrono::Duration::days(1);
+        let body = serde_json::json!({ "name": " ", "description": "", "priority": "urgent", "due_date": yesterday, "tags": [] });
+        let (status, body) = send(create_test_router().await, "POST", "/", Some(body)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let fields = violations(&body);
+        assert!(fields.contains(&("name".to_string(), "required".to_string())));
+        assert!(fields.contains(&("priority".to_string(), "one_of".to_string())));
+        assert!(fields.contains(&("due_date".to_string(), "not_in_past".to_string())));
+
+        // A back-dated task is fine once the caller opts in
+        let body = serde_json::json!({
+            "name": "File expenses", "description": "", "priority": "low", "due_date": yesterday, "tags": [], "allow_past_due": true
+        });
+        let (status, _) = send(create_test_router().await, "POST", "/", Some(body)).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, body) = send(create_test_router().await, "POST", "/", Some(serde_json::json!({ "description": "", "priority": "low", "tags": [] }))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(violations(&body), vec![("name".to_string(), "required".to_string())]);
+    }
+}
@@ -1,24 +1,9 @@
 use crate::{auth::AuthenticatedUser, create_success_response, error::ApiResult};
 use axum::{extract::State, routing::post, Json, Router};
-use rusty_ai_core::AssistantCore;
-use serde::{Deserialize, Serialize};
+use rusty_ai_core::{events::{AssistantEvent, UserAction}, AssistantCore};
+use rusty_ai_common::api::{SynthesizeRequest, VoiceRequest, VoiceResponse};
 use std::sync::Arc;
 
-#[derive(Deserialize)]
-pub struct VoiceRequest {
-    pub audio_data: String, // Base64 encoded audio
-    pub format: String,     // "wav", "mp3", etc.
-    pub session_id: Option<uuid::Uuid>,
-}
-
-#[derive(Serialize)]
-pub struct VoiceResponse {
-    pub transcript: String,
-    pub response: String,
-    pub audio_url: Option<String>,
-    pub processing_time_ms: u64,
-}
-
 pub fn routes(core: Arc<AssistantCore>) -> Router {
     Router::new()
         .route("/process", post(process_voice))
@@ -27,8 +12,8 @@ pub fn routes(core: Arc<AssistantCore>) -> Router {
 }
 
 async fn process_voice(
-    State(_core): State<Arc<AssistantCore>>,
-    _user: AuthenticatedUser,
+    State(core): State<Arc<AssistantCore>>,
+    user: AuthenticatedUser,
     Json(_request): Json<VoiceRequest>,
 ) -> ApiResult<Json<serde_json::Value>> {
     // TODO: Implement voice processing pipeline
@@ -38,6 +23,7 @@ async fn process_voice(
     // 4. Generate response
     // 5. Synthesize response through TTS
     
+    core.events.publish(AssistantEvent::UserAction { user_id: user.claims.user_id, action: UserAction::VoiceInteraction });
     let response = VoiceResponse {
         transcript: "Voice processing not yet implemented".to_string(),
         response: "Voice processing is coming soon!".to_string(),
@@ -51,7 +37,7 @@ async fn process_voice(
 async fn synthesize_speech(
     State(_core): State<Arc<AssistantCore>>,
     _user: AuthenticatedUser,
-    Json(request): Json<serde_json::Value>,
+    Json(_request): Json<SynthesizeRequest>,
 ) -> ApiResult<Json<serde_json::Value>> {
     // TODO: Implement text-to-speech
     // 1. Extract text from request
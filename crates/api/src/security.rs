@@ -0,0 +1,265 @@
+use axum::http::{HeaderMap, HeaderValue};
+use rusty_ai_common::{AssistantError, Result};
+use std::collections::HashSet;
+
+/// Policy for JSON API responses, which never need to load anything
+pub const DEFAULT_API_CSP: &str = "default-src 'none'; frame-ancestors 'none'; base-uri 'none'; form-action 'none'";
+
+/// Policy for the public share pages: server-rendered HTML with an inline
+/// stylesheet and nothing else
+pub const DEFAULT_SHARE_CSP: &str =
+    "default-src 'none'; style-src 'unsafe-inline'; img-src 'self' data:; frame-ancestors 'none'; base-uri 'none'; form-action 'none'";
+
+const DIRECTIVES: &[&str] = &[
+    "default-src", "script-src", "script-src-elem", "script-src-attr", "style-src", "style-src-elem",
+    "style-src-attr", "img-src", "font-src", "connect-src", "media-src", "object-src", "frame-src",
+    "child-src", "worker-src", "manifest-src", "base-uri", "form-action", "frame-ancestors", "sandbox",
+    "report-uri", "report-to", "upgrade-insecure-requests", "block-all-mixed-content",
+];
+
+// Directives that take no value
+const FLAG_DIRECTIVES: &[&str] = &["upgrade-insecure-requests", "block-all-mixed-content"];
+
+// Directives whose values are not source lists
+const FREEFORM_DIRECTIVES: &[&str] = &["sandbox", "report-uri", "report-to"];
+
+const KEYWORDS: &[&str] = &[
+    "'self'", "'none'", "'unsafe-inline'", "'unsafe-eval'", "'strict-dynamic'", "'unsafe-hashes'",
+    "'report-sample'", "'wasm-unsafe-eval'",
+];
+
+const REFERRER_POLICIES: &[&str] = &[
+    "no-referrer", "no-referrer-when-downgrade", "origin", "origin-when-cross-origin", "same-origin",
+    "strict-origin", "strict-origin-when-cross-origin", "unsafe-url",
+];
+
+#[derive(Debug, Clone)]
+pub struct SecurityHeadersConfig {
+    pub content_security_policy: String,
+    /// Path prefix and the policy used instead of the default under it; the
+    /// longest matching prefix wins
+    pub route_policies: Vec<(String, String)>,
+    pub referrer_policy: String,
+    /// Sent only when the request arrived over TLS, either directly or via a
+    /// proxy that sets `X-Forwarded-Proto: https`
+    pub hsts_max_age_secs: u64,
+    /// How long browsers may cache a CORS preflight response
+    pub cors_max_age_secs: u64,
+}
+
+impl Default for SecurityHeadersConfig {
+    fn default() -> Self {
+        Self {
+            content_security_policy: DEFAULT_API_CSP.to_string(),
+            route_policies: vec![("/share".to_string(), DEFAULT_SHARE_CSP.to_string())],
+            referrer_policy: "strict-origin-when-cross-origin".to_string(),
+            hsts_max_age_secs: 31_536_000,
+            cors_max_age_secs: 3600,
+        }
+    }
+}
+
+impl SecurityHeadersConfig {
+    pub fn validate(&self) -> Result<()> {
+        validate_csp(&self.content_security_policy)?;
+        for (prefix, policy) in &self.route_policies {
+            if !prefix.starts_with('/') {
+                return Err(AssistantError::Configuration(format!(
+                    "CSP override path '{}' must start with '/'",
+                    prefix
+                )));
+            }
+            validate_csp(policy)
+                .map_err(|e| AssistantError::Configuration(format!("CSP override for {}: {}", prefix, e)))?;
+        }
+
+        if !REFERRER_POLICIES.contains(&self.referrer_policy.as_str()) {
+            return Err(AssistantError::Configuration(format!(
+                "Unknown Referrer-Policy '{}'",
+                self.referrer_policy
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Check that a Content-Security-Policy is well formed: known directives,
+/// each at most once, with valid source expressions
+pub fn validate_csp(policy: &str) -> Result<()> {
+    let invalid = |reason: String| AssistantError::Configuration(format!("Invalid Content-Security-Policy: {}", reason));
+
+    if HeaderValue::from_str(policy).is_err() {
+        return Err(invalid("contains characters not allowed in a header".to_string()));
+    }
+
+    let mut seen = HashSet::new();
+    for directive in policy.split(';').map(str::trim).filter(|d| !d.is_empty()) {
+        let mut parts = directive.split_whitespace();
+        let name = parts.next().unwrap_or_default().to_ascii_lowercase();
+        let values: Vec<&str> = parts.collect();
+
+        if !DIRECTIVES.contains(&name.as_str()) {
+            return Err(invalid(format!("unknown directive '{}'", name)));
+        }
+        if !seen.insert(name.clone()) {
+            return Err(invalid(format!("'{}' appears more than once", name)));
+        }
+
+        if FLAG_DIRECTIVES.contains(&name.as_str()) {
+            if !values.is_empty() {
+                return Err(invalid(format!("'{}' takes no value", name)));
+            }
+            continue;
+        }
+        if values.is_empty() && name != "sandbox" {
+            return Err(invalid(format!("'{}' needs a value", name)));
+        }
+        if FREEFORM_DIRECTIVES.contains(&name.as_str()) {
+            continue;
+        }
+
+        if values.contains(&"'none'") && values.len() > 1 {
+            return Err(invalid(format!("'none' cannot be combined with other sources in '{}'", name)));
+        }
+        for value in values {
+            if !is_source_expression(value) {
+                return Err(invalid(format!("'{}' is not a valid source in '{}'", value, name)));
+            }
+        }
+    }
+
+    if seen.is_empty() {
+        return Err(invalid("policy is empty".to_string()));
+    }
+    Ok(())
+}
+
+fn is_source_expression(value: &str) -> bool {
+    if value.starts_with('\'') {
+        let lower = value.to_ascii_lowercase();
+        return KEYWORDS.contains(&lower.as_str())
+            || ["'nonce-", "'sha256-", "'sha384-", "'sha512-"]
+                .iter()
+                .any(|prefix| lower.starts_with(prefix) && lower.len() > prefix.len() + 1 && lower.ends_with('\''));
+    }
+
+    // Scheme sources ("https:", "data:") and host sources ("*.example.com",
+    // "https://cdn.example.com:443/path")
+    value == "*"
+        || value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-.:/*_~%+=".contains(c))
+}
+
+/// Header values prepared once at startup from a validated config
+#[derive(Debug, Clone)]
+pub struct SecurityHeaders {
+    default_csp: HeaderValue,
+    route_policies: Vec<(String, HeaderValue)>,
+    referrer_policy: HeaderValue,
+    hsts: HeaderValue,
+}
+
+impl SecurityHeaders {
+    pub fn new(config: &SecurityHeadersConfig) -> Result<Self> {
+        config.validate()?;
+
+        let header = |value: &str| {
+            HeaderValue::from_str(value).map_err(|e| AssistantError::Configuration(format!("Invalid header value: {}", e)))
+        };
+
+        let mut route_policies = config
+            .route_policies
+            .iter()
+            .map(|(prefix, policy)| Ok((prefix.trim_end_matches('/').to_string(), header(policy)?)))
+            .collect::<Result<Vec<_>>>()?;
+        route_policies.sort_by(|a, b| b.0.len().cmp(&a.0.len()));
+
+        Ok(Self {
+            default_csp: header(&config.content_security_policy)?,
+            route_policies,
+            referrer_policy: header(&config.referrer_policy)?,
+            hsts: header(&format!("max-age={}; includeSubDomains", config.hsts_max_age_secs))?,
+        })
+    }
+
+    pub fn csp_for(&self, path: &str) -> &HeaderValue {
+        self.route_policies
+            .iter()
+            .find(|(prefix, _)| {
+                path.strip_prefix(prefix.as_str())
+                    .map_or(false, |rest| rest.is_empty() || rest.starts_with('/'))
+            })
+            .map(|(_, policy)| policy)
+            .unwrap_or(&self.default_csp)
+    }
+
+    pub fn referrer_policy(&self) -> &HeaderValue {
+        &self.referrer_policy
+    }
+
+    pub fn hsts(&self) -> &HeaderValue {
+        &self.hsts
+    }
+}
+
+// This server does not terminate TLS itself, so HTTPS shows up either as an
+// absolute https URI or as the header a TLS-terminating proxy adds
+pub fn is_tls_request(uri: &axum::http::Uri, headers: &HeaderMap) -> bool {
+    uri.scheme_str() == Some("https")
+        || headers
+            .get("x-forwarded-proto")
+            .and_then(|v| v.to_str().ok())
+            .map_or(false, |proto| proto.split(',').next().unwrap_or("").trim().eq_ignore_ascii_case("https"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_policies_are_valid() {
+        assert!(SecurityHeadersConfig::default().validate().is_ok());
+        assert!(validate_csp("script-src 'self' 'nonce-abc123' https://cdn.example.com; upgrade-insecure-requests").is_ok());
+    }
+
+    #[test]
+    fn test_malformed_policies_are_rejected() {
+        for policy in [
+            "",
+            "default-src 'self'; default-src 'none'",
+            "script-src 'unsafe-inlin'",
+            "img-src",
+            "bogus-src 'self'",
+            "script-src 'none' https:",
+            "upgrade-insecure-requests https:",
+            "default-src 'self'\r\nX-Injected: 1",
+            "style-src \"self\"",
+        ] {
+            assert!(validate_csp(policy).is_err(), "accepted {:?}", policy);
+        }
+    }
+
+    #[test]
+    fn test_route_overrides_use_longest_prefix() {
+        let config = SecurityHeadersConfig {
+            route_policies: vec![
+                ("/share".to_string(), DEFAULT_SHARE_CSP.to_string()),
+                ("/share/embed/".to_string(), "default-src 'self'".to_string()),
+            ],
+            ..Default::default()
+        };
+        let headers = SecurityHeaders::new(&config).unwrap();
+
+        assert_eq!(headers.csp_for("/share/abc"), DEFAULT_SHARE_CSP);
+        assert_eq!(headers.csp_for("/share/embed/abc"), "default-src 'self'");
+        assert_eq!(headers.csp_for("/shared-things"), DEFAULT_API_CSP);
+        assert_eq!(headers.csp_for("/api/v1/tasks"), DEFAULT_API_CSP);
+
+        let bad = SecurityHeadersConfig {
+            route_policies: vec![("share".to_string(), DEFAULT_SHARE_CSP.to_string())],
+            ..Default::default()
+        };
+        assert!(SecurityHeaders::new(&bad).is_err());
+    }
+}
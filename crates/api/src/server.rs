@@ -1,24 +1,33 @@
 use crate::{
     auth::AuthService,
     middleware::{
-        auth_middleware, compression_layer, cors_layer, error_handling_middleware,
+        admission_middleware, auth_middleware, compression_layer, cors_layer, error_handling_middleware,
         rate_limiting_middleware, request_id_middleware, request_logging_middleware,
         request_size_middleware, security_headers_middleware, timeout_layer, RateLimiter,
     },
     routes::{create_routes, not_found_handler},
-    websocket::{websocket_handler, WebSocketManager},
+    security::SecurityHeaders,
+    websocket::{websocket_handler, WebSocketManager, WebSocketResources},
     ApiConfig,
 };
 use axum::{
     routing::get,
     Router,
 };
-use rusty_ai_core::AssistantCore;
+use rusty_ai_core::{events::AssistantEvent, health::ComponentId, intent_handlers::PRIORITY_PLUGIN, AssistantCore};
+use rusty_ai_plugins::scheduler::PluginScheduler;
+use rusty_ai_plugins::trust::TrustStore;
+use rusty_ai_plugins::events::PluginEventDispatcher;
+use rusty_ai_plugins::{MarketplaceConfig, PluginCommunication, PluginMarketplace, SecurityPolicy, WasmPluginManager};
 use std::{net::SocketAddr, sync::Arc, time::Duration};
 use tokio::signal;
+use tokio::sync::broadcast::error::RecvError;
 use tower::ServiceBuilder;
 use tower_http::trace::TraceLayer;
-use tracing::{error, info};
+use tracing::{error, info, warn};
+
+/// How long shutdown waits for running plugin calls, across all plugins
+const PLUGIN_DRAIN_DEADLINE: Duration = Duration::from_secs(10);
 
 pub struct ApiServer {
     config: ApiConfig,
@@ -26,6 +35,12 @@ pub struct ApiServer {
     auth_service: Arc<AuthService>,
     rate_limiter: Arc<RateLimiter>,
     websocket_manager: Arc<WebSocketManager>,
+    plugin_manager: Arc<WasmPluginManager>,
+    marketplace: Arc<PluginMarketplace>,
+    plugin_scheduler: Arc<PluginScheduler>,
+    /// Delivers assistant events to the plugins whose metadata asks for them
+    plugin_events: Arc<PluginEventDispatcher>,
+    security_headers: Arc<SecurityHeaders>,
 }
 
 impl ApiServer {
@@ -33,21 +48,59 @@ impl ApiServer {
         config: ApiConfig,
         core: Arc<AssistantCore>,
         auth_service: Arc<AuthService>,
-    ) -> Self {
+    ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        config.validate()?;
+        let security_headers = Arc::new(SecurityHeaders::new(&config.security_headers)?);
+
         let rate_limiter = Arc::new(RateLimiter::new(
             config.rate_limit_requests_per_minute,
             Duration::from_secs(60),
         ));
         
         let websocket_manager = Arc::new(WebSocketManager::new(core.clone()));
+        core.resources.register("websocket_buffers", Arc::new(WebSocketResources(websocket_manager.clone())));
+
+        // Plugins load under the trust level kept in the plugin directory,
+        // untrusted unless an admin raised it
+        let trust_store = Arc::new(TrustStore::in_plugin_directory(&config.plugin_directory)?);
+        let plugin_manager = Arc::new(
+            WasmPluginManager::new(&config.plugin_directory)?
+                .with_trust_store(trust_store)
+                .with_module_cache_key(module_cache_key(&config).as_ref())
+                .with_scratchpads(core.scratchpads.clone())
+                .with_host_services(crate::plugin_host::host_services(core.clone(), plugin_policy(&config)))
+                .with_audit_sink(Arc::new(crate::plugin_host::StoragePluginAudit(core.clone())))
+                .with_config_store(Arc::new(crate::plugin_host::StoragePluginConfigs(core.clone()))),
+        );
+        core.orchestrator.handlers().register(
+            PRIORITY_PLUGIN,
+            Arc::new(crate::plugin_host::CapabilityDispatchHandler::new(plugin_manager.clone())),
+        );
+        let plugin_scheduler = Arc::new(PluginScheduler::new(
+            plugin_manager.clone(),
+            Arc::new(crate::plugin_host::StoragePluginSchedules(core.clone())),
+        ));
+        let marketplace = Arc::new(PluginMarketplace::new(
+            MarketplaceConfig {
+                index_urls: config.plugin_index_urls.clone(),
+                plugin_directory: config.plugin_directory.clone().into(),
+            },
+            plugin_manager.clone(),
+        )?);
+        let plugin_events = Arc::new(PluginEventDispatcher::new(plugin_manager.clone(), Arc::new(PluginCommunication::new())));
 
-        Self {
+        Ok(Self {
             config,
             core,
             auth_service,
             rate_limiter,
             websocket_manager,
-        }
+            plugin_manager,
+            marketplace,
+            plugin_scheduler,
+            plugin_events,
+            security_headers,
+        })
     }
 
     pub async fn start(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
@@ -70,6 +123,14 @@ impl ApiServer {
             .await?;
 
         info!("API server stopped");
+        
+        // Plugin calls still running get to finish before the core goes away
+        if let Err(e) = self.plugin_manager.unload_all(PLUGIN_DRAIN_DEADLINE).await {
+            error!("Plugins did not drain cleanly: {}", e);
+        }
+        if let Err(e) = self.core.shutdown().await {
+            error!("Core shutdown failed: {}", e);
+        }
         Ok(())
     }
 
@@ -78,7 +139,7 @@ impl ApiServer {
             // WebSocket endpoint (if enabled)
             .route("/ws", get(websocket_handler))
             // Main API routes
-            .merge(create_routes(self.core.clone(), self.auth_service.clone()))
+            .merge(create_routes(self.core.clone(), self.auth_service.clone(), self.marketplace.clone()))
             // Fallback for unmatched routes
             .fallback(not_found_handler);
 
@@ -95,9 +156,15 @@ impl ApiServer {
                 .layer(timeout_layer())
                 .layer(compression_layer())
                 .layer(cors_layer(&self.config))
+                // Ahead of the layers that can reject, so their errors carry
+                // the request id too
+                .layer(axum::middleware::from_fn(request_id_middleware))
                 
                 // Security and validation layers
-                .layer(axum::middleware::from_fn(security_headers_middleware))
+                .layer(axum::middleware::from_fn_with_state(
+                    self.security_headers.clone(),
+                    security_headers_middleware,
+                ))
                 .layer(axum::middleware::from_fn_with_state(
                     self.config.max_request_size,
                     request_size_middleware,
@@ -106,9 +173,13 @@ impl ApiServer {
                     self.rate_limiter.clone(),
                     rate_limiting_middleware,
                 ))
+                // Inside rate limiting, so rejected requests never take a slot
+                .layer(axum::middleware::from_fn_with_state(
+                    self.core.admission.clone(),
+                    admission_middleware,
+                ))
                 
-                // Logging and request tracking
-                .layer(axum::middleware::from_fn(request_id_middleware))
+                // Logging
                 .layer(axum::middleware::from_fn(request_logging_middleware))
                 .layer(axum::middleware::from_fn(error_handling_middleware))
                 
@@ -148,12 +219,185 @@ impl ApiServer {
 
         // Task execution background service
         let orchestrator = self.core.orchestrator.clone();
+        let health = self.core.health.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(60)); // 1 minute
+            loop {
+                interval.tick().await;
+                match orchestrator.execute_pending_tasks().await {
+                    Ok(()) => health.report_success(ComponentId::JobQueue),
+                    Err(e) => {
+                        error!("Error executing pending tasks: {}", e);
+                        health.report_failure(ComponentId::JobQueue, e);
+                    }
+                }
+            }
+        });
+
+        // Onboarding steps complete as users do what they ask
+        self.core.onboarding.listen(&self.core.events);
+
+        // Deliver notifications held back during quiet hours
+        let notification_router = self.core.notification_router.clone();
+        let health = self.core.health.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(60)); // 1 minute
+            loop {
+                interval.tick().await;
+                match notification_router.flush_due().await {
+                    Ok(_) => health.report_success(ComponentId::Schedulers),
+                    Err(e) => {
+                        error!("Error flushing deferred notifications: {}", e);
+                        health.report_failure(ComponentId::Schedulers, e);
+                    }
+                }
+            }
+        });
+
+        // Weekly knowledge digests, each sent at the user's preferred day and time
+        let knowledge_digests = self.core.knowledge_digests.clone();
+        let context_manager = self.core.context_manager.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(900)); // 15 minutes
+            loop {
+                interval.tick().await;
+                let users = context_manager.read().await.user_preferences().await;
+                let generated = knowledge_digests.run_due(&users).await;
+                if generated > 0 {
+                    info!("Sent {} knowledge digests", generated);
+                }
+            }
+        });
+
+        // Due reminders, overdue-task suggestions and plugin health changes
+        let alerts = self.core.alerts.clone();
+        let context_manager = self.core.context_manager.clone();
+        let health = self.core.health.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(60)); // 1 minute
+            loop {
+                interval.tick().await;
+                let users = context_manager.read().await.user_preferences().await;
+                match alerts.run_due(&users).await {
+                    Ok(_) => health.report_success(ComponentId::Schedulers),
+                    Err(e) => {
+                        error!("Error raising alerts: {}", e);
+                        health.report_failure(ComponentId::Schedulers, e);
+                    }
+                }
+            }
+        });
+
+        // Daily briefing, plus retries of one that was generated but not stored
+        let briefing_generator = self.core.briefing_generator.clone();
+        let notification_router = self.core.notification_router.clone();
+        let context_manager = self.core.context_manager.clone();
+        let health = self.core.health.clone();
+        let events = self.core.events.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(600)); // 10 minutes
+            loop {
+                interval.tick().await;
+                let users = context_manager.read().await.user_preferences().await;
+                match briefing_generator.run_scheduled(chrono::Utc::now(), &users, &notification_router).await {
+                    Ok(Some(briefing)) => {
+                        info!("Stored daily briefing {}", briefing.id);
+                        events.publish(AssistantEvent::BriefingGenerated { user_id: None, briefing: Arc::new(briefing) });
+                        health.report_success(ComponentId::Schedulers);
+                    }
+                    Ok(None) => health.report_success(ComponentId::Schedulers),
+                    Err(e) => {
+                        error!("Daily briefing generation failed: {}", e);
+                        health.report_failure(ComponentId::Schedulers, e);
+                    }
+                }
+            }
+        });
+
+        // Assistant events, passed on to the plugins subscribed to them. The
+        // subscriptions follow plugins as they load and unload
+        let plugin_events = self.plugin_events.clone();
+        plugin_events.clone().follow().await;
+        let mut events = self.core.events.subscribe();
+        tokio::spawn(async move {
+            loop {
+                match events.recv().await {
+                    Ok(event) => {
+                        let Some(event) = event.plugin_event() else { continue };
+                        if let Err(e) = plugin_events.publish(&event).await {
+                            warn!("Failed to broadcast {} to plugins: {}", event.event_type(), e);
+                        }
+                    }
+                    Err(RecvError::Lagged(missed)) => warn!("Plugins missed {} assistant events", missed),
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        });
+
+        // Scheduled plugin jobs, taken up again from storage after a restart
+        match self.plugin_scheduler.load().await {
+            Ok(jobs) => info!("Loaded {} scheduled plugin jobs", jobs),
+            Err(e) => error!("Error loading scheduled plugin jobs: {}", e),
+        }
+        let plugin_scheduler = self.plugin_scheduler.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(30)); // 30 seconds
+            loop {
+                interval.tick().await;
+                plugin_scheduler.run_due(chrono::Utc::now()).await;
+            }
+        });
+
+        // Sample memory usage so the admin view can show how it changes
+        let resources = self.core.resources.clone();
         tokio::spawn(async move {
             let mut interval = tokio::time::interval(Duration::from_secs(60)); // 1 minute
             loop {
                 interval.tick().await;
-                if let Err(e) = orchestrator.execute_pending_tasks().await {
-                    error!("Error executing pending tasks: {}", e);
+                resources.record().await;
+            }
+        });
+
+        // Forget share links a week after they expire; until then they still
+        // answer with "gone" rather than "not found"
+        let share_links = self.core.share_links.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(3600)); // 1 hour
+            loop {
+                interval.tick().await;
+                if let Err(e) = share_links.purge_expired(chrono::Duration::days(7)) {
+                    error!("Error purging expired share links: {}", e);
+                }
+            }
+        });
+
+        // Data cleanup under the retention policy, previewed a day ahead
+        let maintenance = self.core.maintenance.clone();
+        let context_manager = self.core.context_manager.clone();
+        let health = self.core.health.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(3600)); // 1 hour
+            loop {
+                interval.tick().await;
+                let users = context_manager.read().await.user_preferences().await;
+                match maintenance.run_due(chrono::Utc::now(), &users).await {
+                    Ok(_) => health.report_success(ComponentId::Schedulers),
+                    Err(e) => {
+                        error!("Data cleanup failed: {}", e);
+                        health.report_failure(ComponentId::Schedulers, e);
+                    }
+                }
+            }
+        });
+
+        // Admin audit retention runs on its own schedule, apart from data cleanup
+        let audit = self.core.audit.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(86400)); // 1 day
+            loop {
+                interval.tick().await;
+                if let Err(e) = audit.enforce_retention().await {
+                    error!("Error pruning admin audit entries: {}", e);
                 }
             }
         });
@@ -162,17 +406,11 @@ impl ApiServer {
     }
 
     pub async fn health_check(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        // Check core services
-        let storage_health = self.core.storage.health_check().await?;
-        
-        match storage_health.status {
-            rusty_ai_core::storage::StorageStatus::Healthy => {
-                info!("Storage health check passed");
-            }
-            _ => {
-                error!("Storage health check failed: {:?}", storage_health);
-                return Err("Storage health check failed".into());
-            }
+        let report = self.core.health.check_all().await;
+        if !report.ready {
+            let failing: Vec<String> = report.not_ready.iter().map(|id| id.to_string()).collect();
+            error!("Health check failed for: {}", failing.join(", "));
+            return Err(format!("Critical components unavailable: {}", failing.join(", ")).into());
         }
 
         // Check WebSocket connections if enabled
@@ -189,16 +427,11 @@ impl ApiServer {
         &self.config
     }
 
+    // Admin overview; component state comes from the shared health registry
+    // rather than probing storage separately
     pub async fn get_metrics(&self) -> serde_json::Value {
-        let storage_health = self.core.storage.health_check().await.unwrap_or_else(|_| {
-            rusty_ai_core::storage::StorageHealth {
-                status: rusty_ai_core::storage::StorageStatus::Unhealthy,
-                connection_pool_size: None,
-                pending_migrations: None,
-                disk_usage_mb: None,
-                last_backup: None,
-            }
-        });
+        let report = self.core.health.check_all().await;
+        let storage = report.components.get(&ComponentId::Storage);
 
         serde_json::json!({
             "timestamp": chrono::Utc::now(),
@@ -208,16 +441,38 @@ impl ApiServer {
                 "websockets_enabled": self.config.enable_websockets
             },
             "storage": {
-                "status": format!("{:?}", storage_health.status),
-                "connection_pool_size": storage_health.connection_pool_size.unwrap_or(0)
+                "status": storage.map(|h| h.status),
+                "latency_ms": storage.map(|h| h.latency_ms)
             },
+            "health": report,
             "sessions": {
                 "active_count": self.core.context_manager.read().await.get_active_session_count().await
+            },
+            "audit": {
+                "write_failures": self.core.audit.write_failures()
             }
         })
     }
 }
 
+// The host's plugin policy: each configured key signs for its own author,
+// whichever way the plugin was installed
+fn plugin_policy(config: &ApiConfig) -> SecurityPolicy {
+    SecurityPolicy {
+        trusted_authors: config.plugin_trusted_keys.values().map(|key| key.author.clone()).collect(),
+        trusted_keys: config.plugin_trusted_keys.clone(),
+        ..SecurityPolicy::default()
+    }
+}
+
+// Derived from the server secret, which the plugin directory never sees,
+// so compiled modules survive a restart without being forgeable by anyone
+// who can write plugins
+fn module_cache_key(config: &ApiConfig) -> ring::hmac::Tag {
+    let secret = ring::hmac::Key::new(ring::hmac::HMAC_SHA256, config.jwt_secret.as_bytes());
+    ring::hmac::sign(&secret, b"plugin module cache")
+}
+
 async fn shutdown_signal() {
     let ctrl_c = async {
         signal::ctrl_c()
@@ -261,7 +516,7 @@ mod tests {
         
         let api_config = ApiConfig::default();
         
-        ApiServer::new(api_config, core, auth_service)
+        ApiServer::new(api_config, core, auth_service).unwrap()
     }
 
     #[tokio::test]
@@ -271,6 +526,19 @@ mod tests {
         assert!(server.config.enable_websockets);
     }
 
+    #[tokio::test]
+    async fn test_plugin_manager_trusts_the_configured_keys() {
+        let core = Arc::new(AssistantCore::new(CoreConfig::default()).await.unwrap());
+        let auth_service = Arc::new(AuthService::new(AuthConfig::default()));
+        let key = rusty_ai_plugins::TrustedKey { author: "acme".to_string(), public_key: "07".repeat(32) };
+        let config = ApiConfig { plugin_trusted_keys: [("acme-release".to_string(), key.clone())].into(), ..ApiConfig::default() };
+
+        let server = ApiServer::new(config, core, auth_service).unwrap();
+        let policy = server.plugin_manager.security_policy();
+        assert_eq!(policy.trusted_keys.get("acme-release"), Some(&key));
+        assert!(policy.trusted_authors.contains("acme"));
+    }
+
     #[tokio::test]
     async fn test_health_check() {
         let server = create_test_server().await;
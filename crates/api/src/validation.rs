@@ -0,0 +1,575 @@
+//! Request validation. Handlers take [`ValidJson`] and [`ValidQuery`] in
+//! place of axum's `Json` and `Query`; both report malformed input and
+//! broken rules as a `VALIDATION_ERROR` whose `fields` name every offending
+//! field and the constraint it broke.
+use crate::error::ApiError;
+use axum::{
+    async_trait,
+    body::Bytes,
+    extract::{FromRequest, FromRequestParts, Query, Request},
+    http::{request::Parts, StatusCode},
+};
+use chrono::Utc;
+use rusty_ai_common::api::{
+    ChatRequest, CreateSessionRequest, CreateTaskRequest, DocumentUpload, ExecuteCommandRequest, FieldError,
+    HistoryQuery, SearchQuery, SuggestQuery, UpdatePluginConfigRequest,
+};
+use rusty_ai_common::net::is_public_ip;
+use rusty_ai_common::UserPreferences;
+use rusty_ai_core::{
+    activity::{ActivityFilter, MAX_ACTIVITY_PAGE},
+    context_manager::MAX_HISTORY_PAGE,
+    entities::parse_iso_duration,
+    flags::FlagOverride,
+    focus::{StartFocusRequest, MAX_FOCUS_MINUTES},
+    time_tracking,
+};
+use serde::de::DeserializeOwned;
+use std::net::IpAddr;
+use std::ops::RangeInclusive;
+
+pub const MAX_MESSAGE_LENGTH: usize = 10_000;
+pub const MAX_QUERY_LENGTH: usize = 1_000;
+pub const MAX_SEARCH_LIMIT: usize = 100;
+pub const MAX_TASK_NAME_LENGTH: usize = 200;
+pub const MAX_TITLE_LENGTH: usize = 500;
+// Multipliers of the voice's natural speed and pitch
+pub const VOICE_ADJUSTMENT_RANGE: RangeInclusive<f32> = 0.5..=2.0;
+pub const TASK_PRIORITIES: [&str; 4] = ["critical", "high", "medium", "low"];
+// Bounds of a plugin quota; a limit is left out rather than set to zero
+pub const MAX_PLUGIN_CALLS_PER_HOUR: usize = 1_000_000;
+pub const MAX_PLUGIN_CONCURRENCY: usize = 1_024;
+
+/// Rules a request must satisfy beyond deserializing
+pub trait Validate {
+    fn validate(&self, errors: &mut FieldErrors);
+}
+
+/// Violations collected while validating one request
+#[derive(Debug, Default)]
+pub struct FieldErrors {
+    errors: Vec<FieldError>,
+}
+
+impl FieldErrors {
+    pub fn add(&mut self, field: &str, constraint: &str, message: impl Into<String>) {
+        self.errors.push(FieldError::new(field, constraint, message));
+    }
+
+    /// Validate a nested value, reporting its fields under `prefix`
+    pub fn nested(&mut self, prefix: &str, value: &impl Validate) {
+        let mut nested = FieldErrors::default();
+        value.validate(&mut nested);
+        self.errors.extend(nested.errors.into_iter().map(|mut e| {
+            e.field = format!("{}.{}", prefix, e.field);
+            e
+        }));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    pub fn into_result(self) -> Result<(), ApiError> {
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ApiError::InvalidFields(self.errors))
+        }
+    }
+
+    fn required_text(&mut self, field: &str, value: &str, max_length: usize) {
+        if value.trim().is_empty() {
+            self.add(field, "required", format!("{} must not be empty", field));
+        } else if value.chars().count() > max_length {
+            self.add(field, "max_length", format!("{} must be at most {} characters", field, max_length));
+        }
+    }
+
+    fn limit(&mut self, field: &str, value: Option<usize>, max: usize) {
+        if value.is_some_and(|limit| limit == 0 || limit > max) {
+            self.add(field, "range", format!("{} must be between 1 and {}", field, max));
+        }
+    }
+}
+
+pub fn validate(value: &impl Validate) -> Result<(), ApiError> {
+    let mut errors = FieldErrors::default();
+    value.validate(&mut errors);
+    errors.into_result()
+}
+
+/// JSON body that is deserialized and then validated
+pub struct ValidJson<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for ValidJson<T>
+where
+    T: DeserializeOwned + Validate,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let bytes = Bytes::from_request(req, state).await.map_err(|e| match e.status() {
+            StatusCode::PAYLOAD_TOO_LARGE => ApiError::RequestTooLarge,
+            _ => ApiError::Validation(e.body_text()),
+        })?;
+        let value = parse_json::<T>(&bytes)?;
+        validate(&value)?;
+        Ok(Self(value))
+    }
+}
+
+/// Query string that is deserialized and then validated
+pub struct ValidQuery<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequestParts<S> for ValidQuery<T>
+where
+    T: DeserializeOwned + Validate,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let Query(value) = Query::<T>::try_from_uri(&parts.uri).map_err(|e| {
+            let message = e.body_text();
+            let message = message.strip_prefix("Failed to deserialize query string: ").unwrap_or(&message);
+            ApiError::InvalidFields(vec![deserialize_error("query", message)])
+        })?;
+        validate(&value)?;
+        Ok(Self(value))
+    }
+}
+
+fn parse_json<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, ApiError> {
+    let mut deserializer = serde_json::Deserializer::from_slice(bytes);
+    let value = serde_path_to_error::deserialize(&mut deserializer).map_err(|e| {
+        let path = e.path().to_string();
+        json_error(if path == "." { "body" } else { &path }, e.inner())
+    })?;
+    deserializer.end().map_err(|e| json_error("body", &e))?;
+    Ok(value)
+}
+
+fn json_error(field: &str, error: &serde_json::Error) -> ApiError {
+    let message = error.to_string();
+    // Drop serde_json's " at line 1 column 9"
+    let message = message.rsplit_once(" at line ").map_or(message.as_str(), |(m, _)| m);
+    let error = if error.is_data() {
+        deserialize_error(field, message)
+    } else {
+        FieldError::new("body", "json", format!("Body is not valid JSON: {}", message))
+    };
+    ApiError::InvalidFields(vec![error])
+}
+
+// A missing field is reported under its own name rather than its parent's
+fn deserialize_error(field: &str, message: &str) -> FieldError {
+    if let Some(missing) = message
+        .strip_prefix("missing field `")
+        .and_then(|rest| rest.split_once('`'))
+        .map(|(name, _)| name)
+    {
+        let field = match field {
+            "body" | "query" => missing.to_string(),
+            parent => format!("{}.{}", parent, missing),
+        };
+        return FieldError::new(field.clone(), "required", format!("{} is required", field));
+    }
+    FieldError::new(field, "type", message)
+}
+
+/// Where webhooks may be delivered: http(s) URLs on public hosts, so a
+/// webhook cannot be pointed at the server's own network
+pub fn check_webhook_url(errors: &mut FieldErrors, field: &str, url: &str) {
+    let Ok(parsed) = reqwest::Url::parse(url) else {
+        errors.add(field, "format", format!("{} is not a valid URL", field));
+        return;
+    };
+    if !matches!(parsed.scheme(), "http" | "https") {
+        errors.add(field, "scheme", format!("{} must use http or https", field));
+    }
+    let host = parsed.host_str().unwrap_or_default().trim_matches(|c| c == '[' || c == ']').to_ascii_lowercase();
+    let private = match host.parse::<IpAddr>() {
+        Ok(ip) => !is_public_ip(ip),
+        Err(_) => host.is_empty() || host == "localhost" || host.ends_with(".localhost") || host.ends_with(".local"),
+    };
+    if private {
+        errors.add(field, "public_host", format!("{} must point to a public host", field));
+    }
+}
+
+/// Palette command parameters against the command's JSON schema. Only the
+/// parts commands use are checked: required keys, `additionalProperties`,
+/// the value type, and the `time` (`HH:MM`) and `duration` (ISO 8601) formats
+pub fn check_command_parameters(
+    errors: &mut FieldErrors,
+    schema: &serde_json::Value,
+    parameters: &serde_json::Map<String, serde_json::Value>,
+) {
+    let properties = schema["properties"].as_object();
+    for name in schema["required"].as_array().into_iter().flatten().filter_map(|n| n.as_str()) {
+        let present = parameters.get(name).is_some_and(|v| !v.is_null() && v.as_str().map_or(true, |s| !s.trim().is_empty()));
+        if !present {
+            let field = format!("parameters.{}", name);
+            errors.add(&field, "required", format!("{} must not be empty", field));
+        }
+    }
+
+    for (name, value) in parameters {
+        let field = format!("parameters.{}", name);
+        let Some(property) = properties.and_then(|p| p.get(name)) else {
+            if schema["additionalProperties"] == serde_json::Value::Bool(false) {
+                errors.add(&field, "unknown", format!("{} is not a parameter of this command", field));
+            }
+            continue;
+        };
+        if value.is_null() {
+            continue;
+        }
+        let expected = property["type"].as_str().unwrap_or("any");
+        let matches = match expected {
+            "string" => value.is_string(),
+            "number" => value.is_number(),
+            "integer" => value.is_i64() || value.is_u64(),
+            "boolean" => value.is_boolean(),
+            "array" => value.is_array(),
+            "object" => value.is_object(),
+            _ => true,
+        };
+        if !matches {
+            errors.add(&field, "type", format!("{} must be of type {}", field, expected));
+            continue;
+        }
+        let Some(text) = value.as_str().filter(|s| !s.trim().is_empty()) else {
+            continue;
+        };
+        match property["format"].as_str() {
+            Some("time") if chrono::NaiveTime::parse_from_str(text.trim(), "%H:%M").is_err() => {
+                errors.add(&field, "format", format!("{} must be a time of day as HH:MM", field));
+            }
+            Some("duration") if parse_iso_duration(text.trim()).is_none() => {
+                errors.add(&field, "format", format!("{} must be an ISO 8601 duration such as PT30M", field));
+            }
+            _ => {}
+        }
+        if text.chars().count() > MAX_MESSAGE_LENGTH {
+            errors.add(&field, "max_length", format!("{} must be at most {} characters", field, MAX_MESSAGE_LENGTH));
+        }
+    }
+}
+
+impl Validate for ChatRequest {
+    fn validate(&self, errors: &mut FieldErrors) {
+        errors.required_text("message", &self.message, MAX_MESSAGE_LENGTH);
+    }
+}
+
+impl Validate for CreateSessionRequest {
+    fn validate(&self, errors: &mut FieldErrors) {
+        if let Some(preferences) = &self.preferences {
+            errors.nested("preferences", preferences);
+        }
+    }
+}
+
+impl Validate for UserPreferences {
+    fn validate(&self, errors: &mut FieldErrors) {
+        if self.language.trim().is_empty() {
+            errors.add("language", "required", "language must not be empty");
+        }
+        if time_tracking::parse_timezone(&self.timezone).is_none() {
+            errors.add("timezone", "format", format!("Unknown timezone: '{}'", self.timezone));
+        }
+        for (field, value) in [("voice_settings.speed", self.voice_settings.speed), ("voice_settings.pitch", self.voice_settings.pitch)] {
+            if !VOICE_ADJUSTMENT_RANGE.contains(&value) {
+                errors.add(
+                    field,
+                    "range",
+                    format!("{} must be between {} and {}", field, VOICE_ADJUSTMENT_RANGE.start(), VOICE_ADJUSTMENT_RANGE.end()),
+                );
+            }
+        }
+        if let Err(e) = self.notification_settings.validate() {
+            errors.add("notification_settings", "format", e.to_string());
+        }
+    }
+}
+
+impl Validate for HistoryQuery {
+    fn validate(&self, errors: &mut FieldErrors) {
+        errors.limit("limit", self.limit, MAX_HISTORY_PAGE);
+    }
+}
+
+impl Validate for SearchQuery {
+    fn validate(&self, errors: &mut FieldErrors) {
+        if self.q.chars().count() > MAX_QUERY_LENGTH {
+            errors.add("q", "max_length", format!("q must be at most {} characters", MAX_QUERY_LENGTH));
+        }
+        errors.limit("limit", self.limit, MAX_SEARCH_LIMIT);
+    }
+}
+
+impl Validate for SuggestQuery {
+    fn validate(&self, errors: &mut FieldErrors) {
+        if self.q.chars().count() > MAX_QUERY_LENGTH {
+            errors.add("q", "max_length", format!("q must be at most {} characters", MAX_QUERY_LENGTH));
+        }
+    }
+}
+
+impl Validate for ActivityFilter {
+    fn validate(&self, errors: &mut FieldErrors) {
+        errors.limit("limit", self.limit, MAX_ACTIVITY_PAGE);
+        if let Some(source) = self.source.as_deref().filter(|s| !["chat", "scheduler"].contains(s)) {
+            errors.add("source", "one_of", format!("source must be chat or scheduler, not '{}'", source));
+        }
+        if let (Some(from), Some(to)) = (self.from, self.to) {
+            if from >= to {
+                errors.add("from", "range", "from must be before to");
+            }
+        }
+    }
+}
+
+impl Validate for FlagOverride {
+    fn validate(&self, errors: &mut FieldErrors) {
+        if self.rollout_percent.is_some_and(|percent| percent > 100) {
+            errors.add("rollout_percent", "range", "rollout_percent must be at most 100");
+        }
+    }
+}
+
+impl Validate for StartFocusRequest {
+    fn validate(&self, errors: &mut FieldErrors) {
+        if self.duration_minutes.is_some_and(|minutes| minutes == 0 || minutes > MAX_FOCUS_MINUTES) {
+            errors.add(
+                "duration_minutes",
+                "range",
+                format!("duration_minutes must be between 1 and {}; leave it out to focus until turned off", MAX_FOCUS_MINUTES),
+            );
+        }
+    }
+}
+
+impl Validate for DocumentUpload {
+    fn validate(&self, errors: &mut FieldErrors) {
+        errors.required_text("title", &self.title, MAX_TITLE_LENGTH);
+        if self.content.trim().is_empty() {
+            errors.add("content", "required", "content must not be empty");
+        }
+    }
+}
+
+impl Validate for CreateTaskRequest {
+    fn validate(&self, errors: &mut FieldErrors) {
+        errors.required_text("name", &self.name, MAX_TASK_NAME_LENGTH);
+        if !TASK_PRIORITIES.contains(&self.priority.as_str()) {
+            errors.add("priority", "one_of", format!("priority must be one of {}", TASK_PRIORITIES.join(", ")));
+        }
+        if self.due_date.is_some_and(|due| due < Utc::now()) && !self.allow_past_due {
+            errors.add("due_date", "not_in_past", "due_date is in the past; set allow_past_due to keep it");
+        }
+    }
+}
+
+impl Validate for UpdatePluginConfigRequest {
+    fn validate(&self, errors: &mut FieldErrors) {
+        if let Some(quota) = &self.quota {
+            let calls = quota.max_calls_per_hour.map(|limit| limit as usize);
+            errors.limit("quota.max_calls_per_hour", calls, MAX_PLUGIN_CALLS_PER_HOUR);
+            errors.limit("quota.max_concurrent", quota.max_concurrent, MAX_PLUGIN_CONCURRENCY);
+        }
+    }
+}
+
+impl Validate for ExecuteCommandRequest {
+    // The parameters are checked against the command's schema once the
+    // command is known; see `check_command_parameters`
+    fn validate(&self, _errors: &mut FieldErrors) {}
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::auth::{AuthConfig, AuthService};
+    use axum::{body::Body, routing::post, Extension, Json, Router};
+    use rusty_ai_common::api::LoginRequest;
+    use rusty_ai_common::{NotificationSettings, QuietHours, VoiceSettings};
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    /// Send a request with a valid bearer token to `router` and return the
+    /// status and JSON body
+    pub(crate) async fn send(router: Router, method: &str, uri: &str, body: Option<serde_json::Value>) -> (StatusCode, serde_json::Value) {
+        let auth_service = Arc::new(AuthService::new(AuthConfig::default()));
+        let login = auth_service
+            .authenticate(LoginRequest { email: "demo@example.com".to_string(), password: "password".to_string() })
+            .await
+            .unwrap();
+
+        let request = axum::http::Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("authorization", format!("Bearer {}", login.access_token))
+            .header("content-type", "application/json")
+            .body(body.map_or_else(Body::empty, |b| Body::from(b.to_string())))
+            .unwrap();
+        let response = router.layer(Extension(auth_service)).oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap_or(serde_json::Value::Null))
+    }
+
+    /// (field, constraint) of each reported violation
+    pub(crate) fn violations(body: &serde_json::Value) -> Vec<(String, String)> {
+        assert_eq!(body["error_code"], "VALIDATION_ERROR");
+        body["fields"]
+            .as_array()
+            .expect("validation errors list their fields")
+            .iter()
+            .map(|f| (f["field"].as_str().unwrap().to_string(), f["constraint"].as_str().unwrap().to_string()))
+            .collect()
+    }
+
+    fn pair(field: &str, constraint: &str) -> (String, String) {
+        (field.to_string(), constraint.to_string())
+    }
+
+    fn errors_of(value: &impl Validate) -> Vec<(String, String)> {
+        let mut errors = FieldErrors::default();
+        value.validate(&mut errors);
+        errors.errors.into_iter().map(|e| (e.field, e.constraint)).collect()
+    }
+
+    fn preferences() -> UserPreferences {
+        UserPreferences {
+            language: "en".to_string(),
+            timezone: "Europe/Vienna".to_string(),
+            voice_settings: VoiceSettings { enabled: true, voice_id: "default".to_string(), speed: 1.0, pitch: 1.0 },
+            notification_settings: NotificationSettings {
+                enabled: true,
+                channels: vec![],
+                quiet_hours: None,
+                routing: Default::default(),
+                knowledge_digest: Default::default(),
+            },
+        }
+    }
+
+    async fn echo_task(ValidJson(task): ValidJson<CreateTaskRequest>) -> Json<CreateTaskRequest> {
+        Json(task)
+    }
+
+    #[tokio::test]
+    async fn test_parse_failures_name_the_field() {
+        let app = || Router::new().route("/", post(echo_task));
+
+        let (status, body) = send(app(), "POST", "/", Some(serde_json::json!({ "description": "", "priority": "low", "tags": [] }))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(violations(&body), [pair("name", "required")]);
+
+        let (_, body) = send(
+            app(),
+            "POST",
+            "/",
+            Some(serde_json::json!({ "name": "Taxes", "description": "", "priority": "low", "tags": "finance" })),
+        )
+        .await;
+        assert_eq!(violations(&body), [pair("tags", "type")]);
+        assert!(body["fields"][0]["message"].as_str().unwrap().contains("expected a sequence"));
+
+        let request = axum::http::Request::builder()
+            .method("POST")
+            .uri("/")
+            .body(Body::from("{\"name\": "))
+            .unwrap();
+        let error = ValidJson::<CreateTaskRequest>::from_request(request, &()).await.err().unwrap();
+        assert!(matches!(error, ApiError::InvalidFields(ref fields) if fields[0].constraint == "json"));
+    }
+
+    #[test]
+    fn test_task_rules() {
+        let mut task = CreateTaskRequest {
+            name: " ".to_string(),
+            description: String::new(),
+            priority: "urgent".to_string(),
+            due_date: Some(Utc::now() - chrono::Duration::days(1)),
+            tags: vec![],
+            allow_past_due: false,
+        };
+        assert_eq!(
+            errors_of(&task),
+            [pair("name", "required"), pair("priority", "one_of"), pair("due_date", "not_in_past")]
+        );
+
+        task.name = "File taxes".to_string();
+        task.priority = "high".to_string();
+        task.allow_past_due = true;
+        assert!(errors_of(&task).is_empty());
+    }
+
+    #[test]
+    fn test_preference_rules_report_nested_fields() {
+        let mut prefs = preferences();
+        prefs.timezone = "Mars/Olympus".to_string();
+        prefs.voice_settings.speed = 3.0;
+        prefs.notification_settings.quiet_hours = Some(QuietHours { start: "25:00".to_string(), end: "07:00".to_string() });
+
+        let request = CreateSessionRequest { preferences: Some(prefs) };
+        assert_eq!(
+            errors_of(&request),
+            [
+                pair("preferences.timezone", "format"),
+                pair("preferences.voice_settings.speed", "range"),
+                pair("preferences.notification_settings", "format"),
+            ]
+        );
+        assert!(errors_of(&CreateSessionRequest { preferences: Some(preferences()) }).is_empty());
+    }
+
+    #[test]
+    fn test_plugin_quotas_must_be_positive_and_bounded() {
+        let config = |max_calls_per_hour, max_concurrent| UpdatePluginConfigRequest {
+            quota: Some(rusty_ai_common::PluginQuota { max_calls_per_hour, max_concurrent }),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            errors_of(&config(Some(0), Some(0))),
+            [pair("quota.max_calls_per_hour", "range"), pair("quota.max_concurrent", "range")]
+        );
+        assert_eq!(errors_of(&config(None, Some(MAX_PLUGIN_CONCURRENCY + 1))), [pair("quota.max_concurrent", "range")]);
+        assert!(errors_of(&config(Some(100), Some(4))).is_empty());
+        assert!(errors_of(&config(None, None)).is_empty());
+        assert!(errors_of(&UpdatePluginConfigRequest::default()).is_empty());
+    }
+
+    #[test]
+    fn test_webhook_urls_must_be_public_http() {
+        let check = |url: &str| {
+            let mut errors = FieldErrors::default();
+            check_webhook_url(&mut errors, "url", url);
+            errors.errors.into_iter().map(|e| e.constraint).collect::<Vec<_>>()
+        };
+
+        assert!(check("https://hooks.example.com/notify").is_empty());
+        assert_eq!(check("ftp://hooks.example.com/notify"), ["scheme"]);
+        assert_eq!(check("not a url"), ["format"]);
+        for private in [
+            "http://localhost:8080/hook",
+            "http://127.0.0.1/hook",
+            "http://10.0.0.5/hook",
+            "http://192.168.1.10/hook",
+            "http://169.254.169.254/latest/meta-data",
+            "http://[::1]/hook",
+            "http://[fd00::1]/hook",
+            "http://[::ffff:10.0.0.1]/hook",
+        ] {
+            assert_eq!(check(private), ["public_host"], "{}", private);
+        }
+    }
+}
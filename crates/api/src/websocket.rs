@@ -1,39 +1,55 @@
 use axum::{
     extract::{
         ws::{Message, WebSocket, WebSocketUpgrade},
-        State,
+        Query, State,
     },
+    async_trait,
+    http::{header, HeaderMap},
     response::Response,
+    Extension,
 };
 use futures::{sink::SinkExt, stream::StreamExt};
-use serde::{Deserialize, Serialize};
+use serde::Deserialize;
 use std::{
-    collections::HashMap,
-    sync::Arc,
+    collections::{BTreeSet, HashMap},
+    sync::{Arc, Mutex as StdMutex, RwLock as StdRwLock},
+};
+use tokio::{
+    sync::{
+        broadcast::{self, error::RecvError},
+        mpsc::{self, error::TrySendError},
+        RwLock,
+    },
+    task::JoinHandle,
 };
-use tokio::sync::{broadcast, RwLock};
 use tracing::{debug, error, info, warn};
 use uuid::Uuid;
-use rusty_ai_core::AssistantCore;
+use rusty_ai_common::{api::ChatResponse, TurnKind};
+use rusty_ai_core::{
+    events::AssistantEvent,
+    intent_handlers::IntentRequest,
+    resources::{ResourceReporter, ResourceUsage},
+    AssistantCore,
+};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct WebSocketMessage {
-    pub message_type: MessageType,
-    pub session_id: Option<Uuid>,
-    pub user_id: Option<Uuid>,
-    pub data: serde_json::Value,
-    pub timestamp: chrono::DateTime<chrono::Utc>,
-}
+use crate::auth::{AuthService, API_KEY_HEADER};
+use crate::error::{ApiError, ApiResult};
+use crate::routes::conversation::complete_turn;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub enum MessageType {
-    Chat,
-    VoiceData,
-    StatusUpdate,
-    Error,
-    Ping,
-    Pong,
-}
+pub use rusty_ai_common::api::{LiveCommand, LiveFrame, LiveTopic, MessageType, WebSocketMessage};
+
+// Messages each connection's broadcast channel can hold
+const CONNECTION_BUFFER: usize = 100;
+
+// Live frames queued per connection. Once it is full, task and briefing
+// updates are dropped and a snapshot follows when there is room again;
+// notifications wait for room instead
+const LIVE_BUFFER: usize = 64;
+
+type Subscriptions = Arc<StdRwLock<BTreeSet<LiveTopic>>>;
+
+// Conversation sessions a connection follows, each forwarded by its own task
+type Followed = Arc<StdMutex<HashMap<Uuid, JoinHandle<()>>>>;
 
 #[derive(Debug)]
 pub struct WebSocketConnection {
@@ -64,7 +80,7 @@ impl WebSocketManager {
     ) {
         info!("WebSocket connection established for user {}", user_id);
 
-        let (tx, _rx) = broadcast::channel(100);
+        let (tx, _rx) = broadcast::channel(CONNECTION_BUFFER);
         let connection_id = Uuid::new_v4();
 
         // Store connection
@@ -82,12 +98,27 @@ impl WebSocketManager {
         let connections_ref = self.connections.clone();
         let core_ref = self.core.clone();
 
+        // Live updates reach the socket through their own queue, so a burst
+        // of events cannot crowd out chat replies
+        let subscriptions: Subscriptions = Arc::default();
+        let followed: Followed = Arc::default();
+        let (live_tx, mut live_rx) = mpsc::channel(LIVE_BUFFER);
+        let events = self.core.events.subscribe();
+        let forward_task = tokio::spawn(forward_events(self.core.clone(), events, user_id, subscriptions.clone(), live_tx.clone()));
+        let followed_ref = followed.clone();
+
         // Spawn task to handle incoming messages
         let recv_task = tokio::spawn(async move {
             while let Some(msg) = receiver.next().await {
                 match msg {
                     Ok(Message::Text(text)) => {
                         debug!("Received WebSocket message: {}", text);
+
+                        if let Ok(command) = serde_json::from_str::<LiveCommand>(&text) {
+                            let live = LiveConnection { subscriptions: &subscriptions, followed: &followed_ref, live_tx: &live_tx };
+                            handle_live_command(command, &core_ref, &live, user_id, session_id).await;
+                            continue;
+                        }
                         
                         match serde_json::from_str::<WebSocketMessage>(&text) {
                             Ok(ws_msg) => {
@@ -95,6 +126,7 @@ impl WebSocketManager {
                                     ws_msg,
                                     &core_ref,
                                     &tx,
+                                    &followed_ref,
                                     user_id,
                                     session_id,
                                 ).await {
@@ -129,8 +161,18 @@ impl WebSocketManager {
         // Spawn task to handle outgoing messages
         let mut rx = tx.subscribe();
         let send_task = tokio::spawn(async move {
-            while let Ok(msg) = rx.recv().await {
-                let json_msg = match serde_json::to_string(&msg) {
+            loop {
+                let json_msg = tokio::select! {
+                    msg = rx.recv() => match msg {
+                        Ok(msg) => serde_json::to_string(&msg),
+                        Err(_) => break,
+                    },
+                    frame = live_rx.recv() => match frame {
+                        Some(frame) => serde_json::to_string(&frame),
+                        None => break,
+                    },
+                };
+                let json_msg = match json_msg {
                     Ok(json) => json,
                     Err(e) => {
                         error!("Failed to serialize WebSocket message: {}", e);
@@ -149,6 +191,11 @@ impl WebSocketManager {
             _ = recv_task => {},
             _ = send_task => {},
         }
+        forward_task.abort();
+        let sessions: Vec<_> = followed.lock().unwrap().drain().collect();
+        for (followed_session, task) in sessions {
+            stop_following(&self.core, followed_session, task).await;
+        }
 
         // Clean up connection
         {
@@ -175,10 +222,27 @@ impl WebSocketManager {
     }
 }
 
+// Reports connection buffers to the resource registry. Every connection
+// allocates its full broadcast ring up front, so the estimate counts slots
+// rather than queued messages.
+pub struct WebSocketResources(pub Arc<WebSocketManager>);
+
+#[async_trait]
+impl ResourceReporter for WebSocketResources {
+    async fn usage(&self) -> ResourceUsage {
+        let connections = self.0.get_active_connections().await;
+        ResourceUsage {
+            entries: connections,
+            approx_bytes: (connections * CONNECTION_BUFFER * std::mem::size_of::<WebSocketMessage>()) as u64,
+        }
+    }
+}
+
 async fn handle_websocket_message(
     message: WebSocketMessage,
     core: &Arc<AssistantCore>,
     tx: &broadcast::Sender<WebSocketMessage>,
+    followed: &Followed,
     user_id: Uuid,
     session_id: Uuid,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
@@ -186,21 +250,28 @@ async fn handle_websocket_message(
         MessageType::Chat => {
             // Handle chat message
             if let Some(text) = message.data.as_str() {
-                let context_manager = core.context_manager.read().await;
-                let user_context = context_manager.get_user_context(session_id).await?;
-                
-                let classification = core.intent_classifier.classify(text, Some(user_context));
-                let response = core.orchestrator.process_intent(classification.intent.clone(), user_context).await?;
-                
-                // Send response back
+                let session_id = message.session_id.unwrap_or(session_id);
+                if !owns_session(core, user_id, session_id).await {
+                    return Err(format!("Session {} is not one of the user's", session_id).into());
+                }
+                let (response, confidence) = stream_chat(core, session_id, text).await?;
+
+                // A connection following the session already saw the reply
+                // streamed; it gets no second copy
+                if followed.lock().unwrap().contains_key(&session_id) {
+                    return Ok(());
+                }
                 let response_msg = WebSocketMessage {
                     message_type: MessageType::Chat,
                     session_id: Some(session_id),
                     user_id: Some(user_id),
                     data: serde_json::json!({
-                        "response": response,
-                        "intent": classification.intent,
-                        "confidence": classification.confidence
+                        "response": response.response,
+                        "intent": response.intent,
+                        "confidence": confidence,
+                        "suggested_actions": response.suggested_actions,
+                        "sources": response.sources,
+                        "action_ids": response.action_ids,
                     }),
                     timestamp: chrono::Utc::now(),
                 };
@@ -227,6 +298,303 @@ async fn handle_websocket_message(
     Ok(())
 }
 
+// Answers a chat message as a reply streamed to every connection following
+// the session. Returns the response and the intent's confidence
+async fn stream_chat(
+    core: &Arc<AssistantCore>,
+    session_id: Uuid,
+    text: &str,
+) -> Result<(ChatResponse, f32), Box<dyn std::error::Error + Send + Sync>> {
+    let start_time = std::time::Instant::now();
+    let reply = core.session_streams.begin_reply(session_id, text);
+    let user_context = core.context_manager.read().await.get_user_context(session_id).await?.clone();
+
+    let classification = core.intent_classifier.classify(text, Some(&user_context));
+    let request = IntentRequest::from_classification(&classification).with_message(text);
+    let outcome = core.orchestrator.handle(&request, &user_context).await?;
+    let response = complete_turn(
+        core,
+        &user_context,
+        text.to_string(),
+        TurnKind::Message,
+        classification.intent.clone(),
+        outcome,
+        start_time,
+        Some(&reply),
+    )
+    .await?;
+    reply.finish(response.clone());
+    Ok((response, classification.confidence))
+}
+
+// The parts of a connection that live commands change
+struct LiveConnection<'a> {
+    subscriptions: &'a Subscriptions,
+    followed: &'a Followed,
+    live_tx: &'a mpsc::Sender<LiveFrame>,
+}
+
+// Applies a subscribe or unsubscribe request. A request naming an unknown
+// topic changes nothing. Subscribing answers with a snapshot of each topic
+// so the client starts from the current state. Joining, leaving and typing
+// act on one of the user's conversation sessions
+async fn handle_live_command(
+    command: LiveCommand,
+    core: &Arc<AssistantCore>,
+    live: &LiveConnection<'_>,
+    user_id: Uuid,
+    session_id: Uuid,
+) {
+    let (subscriptions, live_tx) = (live.subscriptions, live.live_tx);
+    let (subscribe, names) = match command {
+        LiveCommand::Subscribe { topics } => (true, topics),
+        LiveCommand::Unsubscribe { topics } => (false, topics),
+        LiveCommand::Join { session_id } => return join_session(core, live, user_id, session_id).await,
+        LiveCommand::Leave { session_id } => {
+            let task = live.followed.lock().unwrap().remove(&session_id);
+            if let Some(task) = task {
+                stop_following(core, session_id, task).await;
+            }
+            let _ = live_tx.send(LiveFrame::SessionLeft { session_id }).await;
+            return;
+        }
+        LiveCommand::Typing { session_id, active } => {
+            if owns_session(core, user_id, session_id).await {
+                core.session_streams.typing(session_id, active);
+            }
+            return;
+        }
+    };
+    let topics: Option<Vec<LiveTopic>> = names.iter().map(|name| LiveTopic::parse(name)).collect();
+    let topics = match topics {
+        Some(topics) if !topics.is_empty() => topics,
+        _ => {
+            let message = match names.iter().find(|name| LiveTopic::parse(name).is_none()) {
+                Some(name) => format!("Unknown topic '{}'; expected tasks, briefing or notifications", name),
+                None => "No topics given".to_string(),
+            };
+            let _ = live_tx.send(LiveFrame::Error { message }).await;
+            return;
+        }
+    };
+
+    if !subscribe {
+        subscriptions.write().unwrap().retain(|topic| !topics.contains(topic));
+        let _ = live_tx.send(LiveFrame::Unsubscribed { topics }).await;
+        return;
+    }
+
+    let first_subscribe = {
+        let mut subscriptions = subscriptions.write().unwrap();
+        let first_subscribe = subscriptions.is_empty();
+        subscriptions.extend(topics.iter().copied());
+        first_subscribe
+    };
+    // A connection that starts listening may be the first sight of a new session
+    if first_subscribe {
+        core.warmup.start(user_id, session_id);
+    }
+    let _ = live_tx.send(LiveFrame::Subscribed { topics: topics.clone() }).await;
+    for topic in topics {
+        if let Some(frame) = snapshot(core, topic).await {
+            let _ = live_tx.send(frame).await;
+        }
+    }
+}
+
+async fn owns_session(core: &AssistantCore, user_id: Uuid, session_id: Uuid) -> bool {
+    let context_manager = core.context_manager.read().await;
+    context_manager.get_session(session_id).await.is_ok_and(|session| session.user_id == user_id)
+}
+
+// Sends the session's snapshot, then keeps forwarding its frames. Joining
+// a session the connection already follows starts over with a new snapshot
+async fn join_session(core: &Arc<AssistantCore>, live: &LiveConnection<'_>, user_id: Uuid, session_id: Uuid) {
+    if !owns_session(core, user_id, session_id).await {
+        let message = format!("Unknown session {}", session_id);
+        let _ = live.live_tx.send(LiveFrame::Error { message }).await;
+        return;
+    }
+    let previous = live.followed.lock().unwrap().remove(&session_id);
+    if let Some(task) = previous {
+        stop_following(core, session_id, task).await;
+    }
+    let Some(frames) = send_session_snapshot(core, session_id, live.live_tx).await else { return };
+    let task = tokio::spawn(forward_session(core.clone(), session_id, frames, live.live_tx.clone()));
+    live.followed.lock().unwrap().insert(session_id, task);
+}
+
+// Queues a snapshot of the session and returns its frames from there on;
+// None once the connection is gone. The history is read and the stream
+// subscribed under one context manager lock, which turns are stored under
+async fn send_session_snapshot(
+    core: &AssistantCore,
+    session_id: Uuid,
+    live_tx: &mpsc::Sender<LiveFrame>,
+) -> Option<broadcast::Receiver<LiveFrame>> {
+    let (snapshot, frames) = {
+        let context_manager = core.context_manager.read().await;
+        let turns = match context_manager.get_conversation_history(session_id, None).await {
+            Ok(turns) => turns,
+            Err(e) => {
+                error!("Failed to load session {} for a live snapshot: {}", session_id, e);
+                let message = format!("Could not load session {}", session_id);
+                let _ = live_tx.send(LiveFrame::Error { message }).await;
+                return None;
+            }
+        };
+        core.session_streams.subscribe(session_id, turns)
+    };
+    live_tx.send(snapshot).await.ok()?;
+    Some(frames)
+}
+
+// Moves a followed session's frames into the live queue. They are never
+// dropped; a connection that fell behind the session's channel starts over
+// from a new snapshot
+async fn forward_session(
+    core: Arc<AssistantCore>,
+    session_id: Uuid,
+    mut frames: broadcast::Receiver<LiveFrame>,
+    live_tx: mpsc::Sender<LiveFrame>,
+) {
+    loop {
+        match frames.recv().await {
+            Ok(frame) => {
+                if live_tx.send(frame).await.is_err() {
+                    return;
+                }
+            }
+            Err(RecvError::Lagged(missed)) => {
+                warn!("Live session {} missed {} frames; resending its snapshot", session_id, missed);
+                drop(frames);
+                match send_session_snapshot(&core, session_id, &live_tx).await {
+                    Some(resumed) => frames = resumed,
+                    None => return,
+                }
+            }
+            Err(RecvError::Closed) => return,
+        }
+    }
+}
+
+// Waits for the forwarding task to go, so the session's channel can be
+// released once nobody follows it
+async fn stop_following(core: &AssistantCore, session_id: Uuid, task: JoinHandle<()>) {
+    task.abort();
+    let _ = task.await;
+    core.session_streams.release(session_id);
+}
+
+// Current state of a topic; notifications are not stored, so they have none
+async fn snapshot(core: &AssistantCore, topic: LiveTopic) -> Option<LiveFrame> {
+    let frame = match topic {
+        LiveTopic::Tasks => core.storage.get_pending_tasks().await.map(|tasks| LiveFrame::TaskSnapshot {
+            tasks: tasks.iter().map(Into::into).collect(),
+        }),
+        LiveTopic::Briefing => core.storage.get_latest_briefing().await.map(|briefing| LiveFrame::BriefingSnapshot {
+            briefing: briefing.as_ref().map(Into::into),
+        }),
+        LiveTopic::Notifications => return None,
+    };
+    Some(frame.unwrap_or_else(|e| {
+        error!("Failed to load {:?} snapshot: {}", topic, e);
+        LiveFrame::Error { message: format!("Could not load the current {} state", topic.as_str()) }
+    }))
+}
+
+// The frame an event becomes on a connection of `user_id`, if the event
+// concerns that user and the connection subscribed to its topic
+fn live_frame(event: &AssistantEvent, user_id: Uuid, subscriptions: &BTreeSet<LiveTopic>) -> Option<LiveFrame> {
+    if event.user_id().is_some_and(|owner| owner != user_id) {
+        return None;
+    }
+    let (topic, frame) = match event {
+        AssistantEvent::TaskStatusChanged { task, .. } => (LiveTopic::Tasks, LiveFrame::TaskUpdated { task: task.into() }),
+        AssistantEvent::BriefingGenerated { briefing, .. } => {
+            (LiveTopic::Briefing, LiveFrame::BriefingGenerated { briefing: briefing.as_ref().into() })
+        }
+        AssistantEvent::Notification(notification) => (
+            LiveTopic::Notifications,
+            LiveFrame::Notification {
+                id: notification.id,
+                category: notification.category,
+                title: notification.title.clone(),
+                body: notification.body.clone(),
+                created_at: notification.created_at,
+            },
+        ),
+        // Opening messages belong to the user's session, not to a topic
+        AssistantEvent::SessionGreeting { greeting, .. } => {
+            return Some(LiveFrame::SessionGreeting {
+                session_id: greeting.session_id,
+                turn_id: greeting.turn_id,
+                text: greeting.text.clone(),
+                created_at: greeting.created_at,
+            })
+        }
+        // Tracked for onboarding and plugins, not shown live
+        AssistantEvent::UserAction { .. } | AssistantEvent::DocumentAdded { .. } => return None,
+    };
+    subscriptions.contains(&topic).then_some(frame)
+}
+
+// Moves events for one connection from the bus into its live queue until
+// the connection goes away
+async fn forward_events(
+    core: Arc<AssistantCore>,
+    mut events: broadcast::Receiver<AssistantEvent>,
+    user_id: Uuid,
+    subscriptions: Subscriptions,
+    live_tx: mpsc::Sender<LiveFrame>,
+) {
+    // Topics with dropped frames, resent as snapshots once the queue drains
+    let mut stale = BTreeSet::new();
+    loop {
+        let frame = match events.recv().await {
+            Ok(event) => {
+                let subscriptions = subscriptions.read().unwrap();
+                live_frame(&event, user_id, &subscriptions)
+            }
+            Err(RecvError::Lagged(missed)) => {
+                warn!("Live updates for user {} missed {} events; resending snapshots", user_id, missed);
+                stale.extend(subscriptions.read().unwrap().iter().copied());
+                None
+            }
+            Err(RecvError::Closed) => break,
+        };
+
+        if !stale.is_empty() && live_tx.capacity() > LIVE_BUFFER / 2 {
+            for topic in std::mem::take(&mut stale) {
+                if let Some(snapshot) = snapshot(&core, topic).await {
+                    if live_tx.send(snapshot).await.is_err() {
+                        return;
+                    }
+                }
+            }
+        }
+
+        let Some(frame) = frame else { continue };
+        if !frame.is_droppable() {
+            if live_tx.send(frame).await.is_err() {
+                break;
+            }
+            continue;
+        }
+        match live_tx.try_send(frame) {
+            Ok(()) => {}
+            Err(TrySendError::Full(frame)) => {
+                debug!("Live queue for user {} is full; dropping {:?}", user_id, frame);
+                stale.insert(match frame {
+                    LiveFrame::BriefingGenerated { .. } => LiveTopic::Briefing,
+                    _ => LiveTopic::Tasks,
+                });
+            }
+            Err(TrySendError::Closed(_)) => break,
+        }
+    }
+}
+
 async fn handle_binary_data(
     _data: Vec<u8>,
     _core: &Arc<AssistantCore>,
@@ -238,13 +606,262 @@ async fn handle_binary_data(
     Ok(())
 }
 
+#[derive(Debug, Default, Deserialize)]
+pub struct WebSocketParams {
+    token: Option<String>,
+}
+
+// Browsers cannot set headers on the handshake, so the access token may
+// also come as `?token=`. Other clients may send an API key instead
 pub async fn websocket_handler(
     ws: WebSocketUpgrade,
     State(manager): State<Arc<WebSocketManager>>,
-) -> Response {
-    // In a real implementation, you would extract user info from headers/query params
-    let user_id = Uuid::new_v4(); // Placeholder
-    let session_id = Uuid::new_v4(); // Placeholder
+    Extension(auth_service): Extension<Arc<AuthService>>,
+    headers: HeaderMap,
+    Query(params): Query<WebSocketParams>,
+) -> ApiResult<Response> {
+    let claims = match headers.get(API_KEY_HEADER) {
+        Some(key) => {
+            let key = key.to_str().map_err(|_| ApiError::Authentication("Invalid API key".to_string()))?;
+            auth_service.verify_api_key(key)?
+        }
+        None => {
+            let bearer = headers
+                .get(header::AUTHORIZATION)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.strip_prefix("Bearer "))
+                .map(str::to_string);
+            let token = bearer
+                .or(params.token)
+                .ok_or_else(|| ApiError::Authentication("Missing access token".to_string()))?;
+            auth_service.verify_token(&token)?
+        }
+    };
+
+    Ok(ws.on_upgrade(move |socket| manager.handle_socket(socket, claims.user_id, claims.session_id)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::AuthConfig;
+    use axum::{routing::get, Router};
+    use rusty_ai_common::api::LoginRequest;
+    use rusty_ai_core::CoreConfig;
+    use std::time::Duration;
+    use tokio_tungstenite::{connect_async, tungstenite::Message as ClientMessage, MaybeTlsStream, WebSocketStream};
 
-    ws.on_upgrade(move |socket| manager.handle_socket(socket, user_id, session_id))
-}
\ No newline at end of file
+    type ClientSocket = WebSocketStream<MaybeTlsStream<tokio::net::TcpStream>>;
+
+    async fn next_frame(socket: &mut ClientSocket) -> serde_json::Value {
+        loop {
+            let message = tokio::time::timeout(Duration::from_secs(5), socket.next())
+                .await
+                .expect("a frame within five seconds")
+                .unwrap()
+                .unwrap();
+            if let ClientMessage::Text(text) = message {
+                return serde_json::from_str(&text).unwrap();
+            }
+        }
+    }
+
+    async fn send_command(socket: &mut ClientSocket, command: serde_json::Value) {
+        socket.send(ClientMessage::Text(command.to_string())).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_subscribed_connection_receives_task_updates_from_rest() {
+        let core = Arc::new(AssistantCore::new(CoreConfig::default()).await.unwrap());
+        let auth_service = Arc::new(AuthService::new(AuthConfig::default()));
+        let token = auth_service
+            .authenticate(LoginRequest { email: "demo@example.com".to_string(), password: "password".to_string() })
+            .await
+            .unwrap()
+            .access_token;
+        let app = Router::new()
+            .route("/ws", get(websocket_handler))
+            .with_state(Arc::new(WebSocketManager::new(core.clone())))
+            .nest("/api/v1/tasks", crate::routes::tasks::routes(core))
+            .layer(Extension(auth_service));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        // The handshake needs a valid token
+        assert!(connect_async(format!("ws://{}/ws", addr)).await.is_err());
+        assert!(connect_async(format!("ws://{}/ws?token=forged", addr)).await.is_err());
+        let (mut socket, _) = connect_async(format!("ws://{}/ws?token={}", addr, token)).await.unwrap();
+
+        send_command(&mut socket, serde_json::json!({ "type": "subscribe", "topics": ["tasks", "weather"] })).await;
+        let frame = next_frame(&mut socket).await;
+        assert_eq!(frame["type"], "error");
+        assert!(frame["message"].as_str().unwrap().contains("weather"));
+
+        send_command(&mut socket, serde_json::json!({ "type": "subscribe", "topics": ["tasks"] })).await;
+        assert_eq!(next_frame(&mut socket).await, serde_json::json!({ "type": "subscribed", "topics": ["tasks"] }));
+        let snapshot = next_frame(&mut socket).await;
+        assert_eq!(snapshot["type"], "task_snapshot");
+        assert!(snapshot["tasks"].is_array());
+
+        let created: serde_json::Value = reqwest::Client::new()
+            .post(format!("http://{}/api/v1/tasks", addr))
+            .bearer_auth(&token)
+            .json(&serde_json::json!({ "name": "Water the plants", "description": "All of them", "priority": "high", "tags": [] }))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+
+        let frame = next_frame(&mut socket).await;
+        assert_eq!(frame["type"], "task_updated");
+        let task = frame["task"].as_object().unwrap();
+        let mut fields: Vec<_> = task.keys().map(String::as_str).collect();
+        fields.sort_unstable();
+        assert_eq!(fields, vec!["due_date", "id", "name", "priority", "status", "updated_at"]);
+        assert_eq!(task["id"], created["data"]["id"]);
+        assert_eq!(task["name"], "Water the plants");
+        assert_eq!(task["status"], "Pending");
+        assert_eq!(task["priority"], "High");
+
+        send_command(&mut socket, serde_json::json!({ "type": "unsubscribe", "topics": ["tasks"] })).await;
+        assert_eq!(next_frame(&mut socket).await, serde_json::json!({ "type": "unsubscribed", "topics": ["tasks"] }));
+    }
+
+    // Frames of a followed session up to the end of the reply it streams
+    async fn session_frames_until_idle(socket: &mut ClientSocket) -> Vec<serde_json::Value> {
+        let mut frames = Vec::new();
+        loop {
+            let frame = next_frame(socket).await;
+            assert_eq!(frame["type"], "session");
+            let idle = frame["event"] == "presence" && frame["state"] == "idle";
+            frames.push(frame);
+            if idle {
+                return frames;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reply_streams_to_every_connection_following_the_session() {
+        let core = Arc::new(AssistantCore::new(CoreConfig::default()).await.unwrap());
+        let auth_service = Arc::new(AuthService::new(AuthConfig::default()));
+        let token = auth_service
+            .authenticate(LoginRequest { email: "demo@example.com".to_string(), password: "password".to_string() })
+            .await
+            .unwrap()
+            .access_token;
+        let user_id = auth_service.verify_token(&token).unwrap().user_id;
+        let session_id = core
+            .context_manager
+            .write()
+            .await
+            .create_session(user_id, crate::routes::conversation::default_preferences())
+            .await
+            .unwrap();
+        let app = Router::new()
+            .route("/ws", get(websocket_handler))
+            .with_state(Arc::new(WebSocketManager::new(core.clone())))
+            .layer(Extension(auth_service));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        let url = format!("ws://{}/ws?token={}", addr, token);
+
+        let (mut phone, _) = connect_async(&url).await.unwrap();
+        let (mut desktop, _) = connect_async(&url).await.unwrap();
+        send_command(&mut desktop, serde_json::json!({ "type": "join", "session_id": Uuid::new_v4() })).await;
+        assert_eq!(next_frame(&mut desktop).await["type"], "error");
+        for socket in [&mut phone, &mut desktop] {
+            send_command(socket, serde_json::json!({ "type": "join", "session_id": session_id })).await;
+            let snapshot = next_frame(socket).await;
+            assert_eq!(snapshot["type"], "session_snapshot");
+            assert_eq!(snapshot["seq"], 0);
+            assert_eq!(snapshot["turns"], serde_json::json!([]));
+        }
+
+        send_command(
+            &mut phone,
+            serde_json::json!({
+                "message_type": "Chat",
+                "session_id": session_id,
+                "user_id": null,
+                "data": "What can you do?",
+                "timestamp": chrono::Utc::now(),
+            }),
+        )
+        .await;
+
+        let seen_by_desktop = session_frames_until_idle(&mut desktop).await;
+        assert_eq!(session_frames_until_idle(&mut phone).await, seen_by_desktop);
+        let seqs: Vec<u64> = seen_by_desktop.iter().map(|frame| frame["seq"].as_u64().unwrap()).collect();
+        assert_eq!(seqs, (1..=seqs.len() as u64).collect::<Vec<_>>());
+
+        let events: Vec<&str> = seen_by_desktop.iter().map(|frame| frame["event"].as_str().unwrap()).collect();
+        assert_eq!(events[..2], ["presence", "chat_started"]);
+        assert_eq!(seen_by_desktop[0]["state"], "generating");
+        assert_eq!(seen_by_desktop[1]["user_input"], "What can you do?");
+        assert_eq!(events[events.len() - 3..], ["turn_appended", "chat_done", "presence"]);
+        let streamed: String = seen_by_desktop
+            .iter()
+            .filter(|frame| frame["event"] == "chat_delta")
+            .map(|frame| frame["delta"].as_str().unwrap())
+            .collect();
+        let done = &seen_by_desktop[events.len() - 2];
+        assert!(!streamed.is_empty());
+        assert_eq!(streamed, done["response"]["response"].as_str().unwrap());
+        let appended = &seen_by_desktop[events.len() - 3];
+        assert_eq!(appended["turn"]["assistant_response"], done["response"]["response"]);
+        assert_eq!(appended["reply_id"], done["reply_id"]);
+
+        // A device joining afterwards starts from the stored turn
+        let (mut laptop, _) = connect_async(&url).await.unwrap();
+        send_command(&mut laptop, serde_json::json!({ "type": "join", "session_id": session_id })).await;
+        let snapshot = next_frame(&mut laptop).await;
+        assert_eq!(snapshot["seq"], *seqs.last().unwrap());
+        assert_eq!(snapshot["turns"][0]["id"], appended["turn"]["id"]);
+        assert_eq!(snapshot["presence"], "idle");
+        assert!(snapshot["reply"].is_null());
+
+        send_command(&mut laptop, serde_json::json!({ "type": "typing", "session_id": session_id, "active": true })).await;
+        for socket in [&mut desktop, &mut laptop] {
+            let typing = next_frame(socket).await;
+            assert_eq!(typing["event"], "presence");
+            assert_eq!(typing["state"], "typing");
+            assert_eq!(typing["seq"], seqs.len() as u64 + 1);
+        }
+        send_command(&mut laptop, serde_json::json!({ "type": "leave", "session_id": session_id })).await;
+        assert_eq!(next_frame(&mut laptop).await, serde_json::json!({ "type": "session_left", "session_id": session_id }));
+    }
+
+    #[test]
+    fn test_events_reach_only_the_affected_subscribed_user() {
+        let user_id = Uuid::new_v4();
+        let now = chrono::Utc::now();
+        let task = rusty_ai_common::Task {
+            id: Uuid::new_v4(),
+            name: "Pay rent".to_string(),
+            description: String::new(),
+            status: rusty_ai_common::TaskStatus::Completed,
+            priority: rusty_ai_common::TaskPriority::Medium,
+            due_date: None,
+            tags: vec![],
+            created_at: now,
+            updated_at: now,
+        };
+        let tasks = BTreeSet::from([LiveTopic::Tasks]);
+
+        let own = AssistantEvent::TaskStatusChanged { user_id: Some(user_id), task: task.clone() };
+        assert!(matches!(live_frame(&own, user_id, &tasks), Some(LiveFrame::TaskUpdated { .. })));
+        assert!(live_frame(&own, user_id, &BTreeSet::from([LiveTopic::Briefing])).is_none());
+
+        let other = AssistantEvent::TaskStatusChanged { user_id: Some(Uuid::new_v4()), task: task.clone() };
+        assert!(live_frame(&other, user_id, &tasks).is_none());
+
+        // Background work belongs to nobody in particular
+        let background = AssistantEvent::TaskStatusChanged { user_id: None, task };
+        assert!(live_frame(&background, user_id, &tasks).is_some());
+    }
+}
@@ -0,0 +1,601 @@
+pub mod error;
+pub mod retry;
+pub mod websocket;
+
+use reqwest::{Method, RequestBuilder};
+use rusty_ai_common::api::{
+    ChatRequest, ChatResponse, ConversationHistory, CreateSessionRequest, CreateSessionResponse,
+    CreateShareLinkRequest, CreateTaskRequest, DocumentSearchResponse, DocumentUpload, ErrorResponse, HistoryQuery,
+    LoginRequest, LoginResponse, LogoutRequest, LogoutResponse, MessageResponse, RefreshRequest,
+    SearchQuery, ShareLinkResponse, SynthesizeRequest, ValidateTokenRequest, ValidateTokenResponse, VoiceRequest,
+    VoiceResponse,
+};
+use rusty_ai_common::{ApiResponse, DailyBriefing, Document, Task, UserContext, UserPreferences};
+use serde::de::DeserializeOwned;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+pub use error::{ClientError, ClientResult};
+pub use retry::RetryPolicy;
+pub use websocket::ChatSocket;
+
+// Re-export the shared DTOs so callers only need this crate
+pub use rusty_ai_common::api;
+
+#[derive(Debug, Clone)]
+pub enum Auth {
+    None,
+    // JWT obtained from /auth/login; `login` sets this automatically
+    Bearer(String),
+    // Long-lived key sent as X-API-Key
+    ApiKey(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct ClientConfig {
+    pub base_url: String,
+    pub auth: Auth,
+    pub retry: RetryPolicy,
+    pub timeout: Duration,
+}
+
+impl ClientConfig {
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            auth: Auth::None,
+            retry: RetryPolicy::default(),
+            timeout: Duration::from_secs(30),
+        }
+    }
+
+    pub fn with_auth(mut self, auth: Auth) -> Self {
+        self.auth = auth;
+        self
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+// Typed client for the public HTTP API. Streaming goes over the /ws
+// WebSocket, see `RustyAiClient::connect_websocket`.
+#[derive(Clone)]
+pub struct RustyAiClient {
+    http: reqwest::Client,
+    config: ClientConfig,
+    token: Arc<RwLock<Option<String>>>,
+}
+
+impl RustyAiClient {
+    pub fn new(config: ClientConfig) -> ClientResult<Self> {
+        let http = reqwest::Client::builder().timeout(config.timeout).build()?;
+        let token = match &config.auth {
+            Auth::Bearer(token) => Some(token.clone()),
+            _ => None,
+        };
+
+        Ok(Self {
+            http,
+            config,
+            token: Arc::new(RwLock::new(token)),
+        })
+    }
+
+    pub fn config(&self) -> &ClientConfig {
+        &self.config
+    }
+
+    pub fn set_token(&self, token: Option<String>) {
+        *self.token.write().unwrap() = token;
+    }
+
+    pub fn token(&self) -> Option<String> {
+        self.token.read().unwrap().clone()
+    }
+
+    // Authentication
+
+    pub async fn login(&self, email: &str, password: &str) -> ClientResult<LoginResponse> {
+        let request = LoginRequest {
+            email: email.to_string(),
+            password: password.to_string(),
+        };
+        let response: LoginResponse = self
+            .execute(self.request(Method::POST, "/auth/login").json(&request))
+            .await?;
+        self.set_token(Some(response.access_token.clone()));
+        Ok(response)
+    }
+
+    pub async fn refresh(&self, refresh_token: &str) -> ClientResult<LoginResponse> {
+        let request = RefreshRequest {
+            refresh_token: refresh_token.to_string(),
+        };
+        let response: LoginResponse = self
+            .execute(self.request(Method::POST, "/auth/refresh").json(&request))
+            .await?;
+        self.set_token(Some(response.access_token.clone()));
+        Ok(response)
+    }
+
+    pub async fn logout(&self) -> ClientResult<LogoutResponse> {
+        let token = self
+            .token()
+            .ok_or_else(|| ClientError::Configuration("not logged in".to_string()))?;
+        let response = self
+            .execute(self.request(Method::POST, "/auth/logout").json(&LogoutRequest { token }))
+            .await?;
+        self.set_token(None);
+        Ok(response)
+    }
+
+    pub async fn validate_token(&self, token: &str) -> ClientResult<ValidateTokenResponse> {
+        let request = ValidateTokenRequest {
+            token: token.to_string(),
+        };
+        self.execute(self.request(Method::POST, "/auth/validate").json(&request)).await
+    }
+
+    // Conversation
+
+    pub async fn chat(&self, request: &ChatRequest) -> ClientResult<ChatResponse> {
+        self.execute(self.request(Method::POST, "/api/v1/conversation/chat").json(request)).await
+    }
+
+    pub async fn create_session(&self, request: &CreateSessionRequest) -> ClientResult<CreateSessionResponse> {
+        self.execute(self.request(Method::POST, "/api/v1/conversation/sessions").json(request)).await
+    }
+
+    pub async fn get_session(&self, session_id: Uuid) -> ClientResult<serde_json::Value> {
+        let path = format!("/api/v1/conversation/sessions/{}", session_id);
+        self.execute(self.request(Method::GET, &path)).await
+    }
+
+    pub async fn delete_session(&self, session_id: Uuid) -> ClientResult<serde_json::Value> {
+        let path = format!("/api/v1/conversation/sessions/{}", session_id);
+        self.execute(self.request(Method::DELETE, &path)).await
+    }
+
+    pub async fn session_history(&self, session_id: Uuid, query: &HistoryQuery) -> ClientResult<ConversationHistory> {
+        let path = format!("/api/v1/conversation/sessions/{}/history", session_id);
+        self.execute(self.request(Method::GET, &path).query(query)).await
+    }
+
+    pub async fn session_context(&self, session_id: Uuid) -> ClientResult<UserContext> {
+        let path = format!("/api/v1/conversation/sessions/{}/context", session_id);
+        self.execute(self.request(Method::GET, &path)).await
+    }
+
+    pub async fn update_preferences(&self, session_id: Uuid, preferences: &UserPreferences) -> ClientResult<MessageResponse> {
+        let path = format!("/api/v1/conversation/sessions/{}/preferences", session_id);
+        self.execute(self.request(Method::PUT, &path).json(preferences)).await
+    }
+
+    pub async fn active_sessions(&self) -> ClientResult<serde_json::Value> {
+        self.execute(self.request(Method::GET, "/api/v1/conversation/active")).await
+    }
+
+    // Knowledge base
+
+    pub async fn search_documents(&self, query: &SearchQuery) -> ClientResult<DocumentSearchResponse> {
+        self.execute(self.request(Method::GET, "/api/v1/knowledge/search").query(query)).await
+    }
+
+    pub async fn upload_document(&self, upload: &DocumentUpload) -> ClientResult<Document> {
+        self.execute(self.request(Method::POST, "/api/v1/knowledge/documents").json(upload)).await
+    }
+
+    pub async fn list_documents(&self) -> ClientResult<Vec<Document>> {
+        self.execute(self.request(Method::GET, "/api/v1/knowledge/documents")).await
+    }
+
+    pub async fn get_document(&self, id: Uuid) -> ClientResult<Document> {
+        let path = format!("/api/v1/knowledge/documents/{}", id);
+        self.execute(self.request(Method::GET, &path)).await
+    }
+
+    pub async fn delete_document(&self, id: Uuid) -> ClientResult<MessageResponse> {
+        let path = format!("/api/v1/knowledge/documents/{}", id);
+        self.execute(self.request(Method::DELETE, &path)).await
+    }
+
+    pub async fn share_document(&self, id: Uuid, request: &CreateShareLinkRequest) -> ClientResult<ShareLinkResponse> {
+        let path = format!("/api/v1/knowledge/documents/{}/share-link", id);
+        self.execute(self.request(Method::POST, &path).json(request)).await
+    }
+
+    // Tasks
+
+    pub async fn list_tasks(&self) -> ClientResult<Vec<Task>> {
+        self.execute(self.request(Method::GET, "/api/v1/tasks/")).await
+    }
+
+    pub async fn create_task(&self, request: &CreateTaskRequest) -> ClientResult<Task> {
+        self.execute(self.request(Method::POST, "/api/v1/tasks/").json(request)).await
+    }
+
+    pub async fn get_task(&self, id: Uuid) -> ClientResult<Task> {
+        let path = format!("/api/v1/tasks/{}", id);
+        self.execute(self.request(Method::GET, &path)).await
+    }
+
+    pub async fn update_task(&self, id: Uuid, request: &CreateTaskRequest) -> ClientResult<MessageResponse> {
+        let path = format!("/api/v1/tasks/{}", id);
+        self.execute(self.request(Method::PUT, &path).json(request)).await
+    }
+
+    pub async fn delete_task(&self, id: Uuid) -> ClientResult<MessageResponse> {
+        let path = format!("/api/v1/tasks/{}", id);
+        self.execute(self.request(Method::DELETE, &path)).await
+    }
+
+    pub async fn complete_task(&self, id: Uuid) -> ClientResult<MessageResponse> {
+        let path = format!("/api/v1/tasks/{}/complete", id);
+        self.execute(self.request(Method::POST, &path)).await
+    }
+
+    // Voice
+
+    pub async fn process_voice(&self, request: &VoiceRequest) -> ClientResult<VoiceResponse> {
+        self.execute(self.request(Method::POST, "/api/v1/voice/process").json(request)).await
+    }
+
+    pub async fn synthesize(&self, request: &SynthesizeRequest) -> ClientResult<serde_json::Value> {
+        self.execute(self.request(Method::POST, "/api/v1/voice/synthesize").json(request)).await
+    }
+
+    // Briefings
+
+    pub async fn today_briefing(&self) -> ClientResult<Option<DailyBriefing>> {
+        self.execute_optional(self.request(Method::GET, "/api/v1/briefing/today")).await
+    }
+
+    pub async fn generate_briefing(&self) -> ClientResult<DailyBriefing> {
+        self.execute(self.request(Method::POST, "/api/v1/briefing/generate")).await
+    }
+
+    pub async fn get_briefing(&self, id: Uuid) -> ClientResult<DailyBriefing> {
+        let path = format!("/api/v1/briefing/{}", id);
+        self.execute(self.request(Method::GET, &path)).await
+    }
+
+    pub async fn share_briefing(&self, id: Uuid, request: &CreateShareLinkRequest) -> ClientResult<ShareLinkResponse> {
+        let path = format!("/api/v1/briefing/{}/share", id);
+        self.execute(self.request(Method::POST, &path).json(request)).await
+    }
+
+    pub async fn revoke_share_link(&self, id: Uuid) -> ClientResult<MessageResponse> {
+        let path = format!("/api/v1/share-links/{}", id);
+        self.execute(self.request(Method::DELETE, &path)).await
+    }
+
+    pub async fn briefing_history(&self) -> ClientResult<Vec<DailyBriefing>> {
+        self.execute(self.request(Method::GET, "/api/v1/briefing/history")).await
+    }
+
+    // Streaming
+
+    pub async fn connect_websocket(&self) -> ClientResult<ChatSocket> {
+        let ws_base = if let Some(rest) = self.config.base_url.strip_prefix("https://") {
+            format!("wss://{}", rest)
+        } else if let Some(rest) = self.config.base_url.strip_prefix("http://") {
+            format!("ws://{}", rest)
+        } else {
+            return Err(ClientError::Configuration(format!(
+                "base URL must start with http:// or https://: {}",
+                self.config.base_url
+            )));
+        };
+
+        let api_key = match &self.config.auth {
+            Auth::ApiKey(key) => Some(key.as_str()),
+            _ => None,
+        };
+        ChatSocket::connect(&format!("{}/ws", ws_base), self.token().as_deref(), api_key).await
+    }
+
+    // Request plumbing
+
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        let mut builder = self
+            .http
+            .request(method, format!("{}{}", self.config.base_url, path));
+
+        if let Some(token) = self.token() {
+            builder = builder.bearer_auth(token);
+        }
+        if let Auth::ApiKey(key) = &self.config.auth {
+            builder = builder.header("X-API-Key", key);
+        }
+        builder
+    }
+
+    async fn execute<T: DeserializeOwned>(&self, request: RequestBuilder) -> ClientResult<T> {
+        self.execute_optional(request)
+            .await?
+            .ok_or_else(|| ClientError::Decode("response has no data".to_string()))
+    }
+
+    // Sends the request, retrying per the retry policy, and unwraps the
+    // ApiResponse envelope
+    async fn execute_optional<T: DeserializeOwned>(&self, request: RequestBuilder) -> ClientResult<Option<T>> {
+        let policy = &self.config.retry;
+        let idempotent = request
+            .try_clone()
+            .and_then(|r| r.build().ok())
+            .map(|r| matches!(*r.method(), Method::GET | Method::PUT | Method::DELETE))
+            .unwrap_or(false);
+        let may_retry = idempotent || policy.retry_non_idempotent;
+
+        let mut attempt = 0;
+        let response = loop {
+            let Some(current) = request.try_clone() else {
+                // Streaming bodies cannot be cloned; send once without retries
+                break request.send().await?;
+            };
+
+            let can_retry = may_retry && attempt < policy.max_retries;
+            match current.send().await {
+                Ok(response) if can_retry && policy.should_retry_status(response.status()) => {
+                    debug!("Retrying after HTTP {}", response.status());
+                }
+                Ok(response) => break response,
+                Err(e) if can_retry && policy.should_retry_error(&e) => {
+                    warn!("Retrying after request error: {}", e);
+                }
+                Err(e) => return Err(e.into()),
+            }
+
+            tokio::time::sleep(policy.backoff(attempt)).await;
+            attempt += 1;
+        };
+
+        let status = response.status();
+        let bytes = response.bytes().await?;
+
+        if !status.is_success() {
+            let message = serde_json::from_slice::<ErrorResponse>(&bytes)
+                .ok()
+                .map(|body| (body.error, body.error_code));
+            let (message, code) = match message {
+                Some((Some(message), code)) => (message, code),
+                Some((None, code)) => (status.to_string(), code),
+                None => (String::from_utf8_lossy(&bytes).to_string(), None),
+            };
+            return Err(ClientError::Api {
+                status: status.as_u16(),
+                code,
+                message,
+            });
+        }
+
+        let envelope: ApiResponse<T> =
+            serde_json::from_slice(&bytes).map_err(|e| ClientError::Decode(e.to_string()))?;
+        if !envelope.success {
+            return Err(ClientError::Api {
+                status: status.as_u16(),
+                code: None,
+                message: envelope.error.unwrap_or_default(),
+            });
+        }
+        Ok(envelope.data)
+    }
+}
+
+// Contract tests: run the real api router in-process and drive it through
+// the client, so a server/client schema change fails here.
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::routing::get;
+    use rusty_ai_api::auth::{ApiKey, AuthConfig, AuthService};
+    use rusty_ai_api::middleware::auth_middleware;
+    use rusty_ai_api::routes::create_routes;
+    use rusty_ai_api::websocket::{websocket_handler, WebSocketManager};
+    use rusty_ai_common::api::MessageType;
+    use rusty_ai_core::{AssistantCore, CoreConfig};
+    use rusty_ai_plugins::{MarketplaceConfig, PluginMarketplace, WasmPluginManager};
+
+    const API_KEY: &str = "rk_test_contract";
+
+    async fn spawn_server() -> (String, tempfile::TempDir) {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = CoreConfig::default();
+        config.storage_config.database_url =
+            format!("sqlite:{}?mode=rwc", dir.path().join("contract.db").display());
+        config.share_store_path = dir.path().join("share_links.json").display().to_string();
+        let core = Arc::new(AssistantCore::new(config).await.unwrap());
+        let auth_service = Arc::new(AuthService::new(AuthConfig {
+            api_keys: vec![ApiKey::new(
+                API_KEY,
+                Uuid::new_v4(),
+                "contract tests",
+                vec!["read".to_string(), "write".to_string()],
+            )],
+            ..AuthConfig::default()
+        }));
+        let marketplace = Arc::new(
+            PluginMarketplace::new(
+                MarketplaceConfig {
+                    plugin_directory: dir.path().join("plugins"),
+                    ..Default::default()
+                },
+                Arc::new(WasmPluginManager::new(dir.path().join("plugins")).unwrap()),
+            )
+            .unwrap(),
+        );
+
+        let app = axum::Router::new()
+            .route("/ws", get(websocket_handler))
+            .merge(create_routes(core.clone(), auth_service.clone(), marketplace))
+            .with_state(Arc::new(WebSocketManager::new(core)))
+            .layer(axum::middleware::from_fn_with_state(auth_service, auth_middleware));
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        (format!("http://{}", addr), dir)
+    }
+
+    async fn logged_in_client(base_url: &str) -> RustyAiClient {
+        let client = RustyAiClient::new(ClientConfig::new(base_url).with_retry(RetryPolicy::none())).unwrap();
+        client.login("demo@example.com", "password").await.unwrap();
+        client
+    }
+
+    #[tokio::test]
+    async fn test_contract_auth() {
+        let (base_url, _dir) = spawn_server().await;
+        let client = RustyAiClient::new(ClientConfig::new(&base_url)).unwrap();
+
+        let login = client.login("demo@example.com", "password").await.unwrap();
+        assert_eq!(login.user.email, "demo@example.com");
+
+        let validation = client.validate_token(&login.access_token).await.unwrap();
+        assert!(validation.valid);
+
+        let error = client.login("demo@example.com", "wrong").await.unwrap_err();
+        assert_eq!(error.status(), Some(401));
+    }
+
+    #[tokio::test]
+    async fn test_contract_sessions_and_chat() {
+        let (base_url, _dir) = spawn_server().await;
+        let client = logged_in_client(&base_url).await;
+
+        let session = client.create_session(&CreateSessionRequest::default()).await.unwrap();
+        let response = client
+            .chat(&ChatRequest {
+                message: "What is on my schedule today?".to_string(),
+                session_id: Some(session.session_id),
+                context: None,
+            })
+            .await
+            .unwrap();
+        assert_eq!(response.session_id, session.session_id);
+
+        let history = client
+            .session_history(session.session_id, &HistoryQuery::default())
+            .await
+            .unwrap();
+        assert_eq!(history.session_id, session.session_id);
+    }
+
+    #[tokio::test]
+    async fn test_contract_knowledge_and_tasks() {
+        let (base_url, _dir) = spawn_server().await;
+        let client = logged_in_client(&base_url).await;
+
+        let document = client
+            .upload_document(&DocumentUpload {
+                title: "Contract".to_string(),
+                content: "Client and server share one schema".to_string(),
+                tags: vec!["test".to_string()],
+            })
+            .await
+            .unwrap();
+        let fetched = client.get_document(document.id).await.unwrap();
+        assert_eq!(fetched.title, "Contract");
+
+        let results = client
+            .search_documents(&SearchQuery { q: "schema".to_string(), limit: Some(5) })
+            .await
+            .unwrap();
+        assert_eq!(results.total, results.documents.len());
+
+        let task = client
+            .create_task(&CreateTaskRequest {
+                name: "Write client".to_string(),
+                description: "Typed SDK".to_string(),
+                priority: "high".to_string(),
+                due_date: None,
+                tags: vec![],
+                allow_past_due: false,
+            })
+            .await
+            .unwrap();
+        assert_eq!(client.get_task(task.id).await.unwrap().name, "Write client");
+        assert!(client.list_tasks().await.unwrap().iter().any(|t| t.id == task.id));
+        client.complete_task(task.id).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_contract_share_links() {
+        let (base_url, _dir) = spawn_server().await;
+        let client = logged_in_client(&base_url).await;
+
+        let document = client
+            .upload_document(&DocumentUpload {
+                title: "Shared <notes>".to_string(),
+                content: "Read-only for guests".to_string(),
+                tags: vec![],
+            })
+            .await
+            .unwrap();
+        let link = client
+            .share_document(document.id, &CreateShareLinkRequest { expires_in_hours: Some(1) })
+            .await
+            .unwrap();
+
+        // The public page needs no credentials
+        let page = reqwest::get(format!("{}{}", base_url, link.url)).await.unwrap();
+        assert_eq!(page.status(), reqwest::StatusCode::OK);
+        assert!(page.text().await.unwrap().contains("Shared &lt;notes&gt;"));
+
+        client.revoke_share_link(link.id).await.unwrap();
+        let page = reqwest::get(format!("{}{}", base_url, link.url)).await.unwrap();
+        assert_eq!(page.status(), reqwest::StatusCode::GONE);
+    }
+
+    #[tokio::test]
+    async fn test_contract_requires_auth() {
+        let (base_url, _dir) = spawn_server().await;
+        let client = RustyAiClient::new(ClientConfig::new(&base_url)).unwrap();
+
+        let error = client.list_tasks().await.unwrap_err();
+        assert_eq!(error.status(), Some(401));
+    }
+
+    #[tokio::test]
+    async fn test_contract_api_key() {
+        let (base_url, _dir) = spawn_server().await;
+        let client =
+            RustyAiClient::new(ClientConfig::new(&base_url).with_auth(Auth::ApiKey(API_KEY.to_string()))).unwrap();
+
+        client.list_tasks().await.unwrap();
+
+        let mut socket = client.connect_websocket().await.unwrap();
+        socket.ping().await.unwrap();
+        let reply = socket.next_message().await.unwrap().unwrap();
+        assert!(matches!(reply.message_type, MessageType::Pong));
+        socket.close().await.unwrap();
+
+        let guessed =
+            RustyAiClient::new(ClientConfig::new(&base_url).with_auth(Auth::ApiKey("rk_test_guess".to_string())))
+                .unwrap();
+        assert_eq!(guessed.list_tasks().await.unwrap_err().status(), Some(401));
+    }
+
+    #[tokio::test]
+    async fn test_contract_websocket_ping() {
+        let (base_url, _dir) = spawn_server().await;
+        let client = logged_in_client(&base_url).await;
+
+        let mut socket = client.connect_websocket().await.unwrap();
+        socket.ping().await.unwrap();
+        let reply = socket.next_message().await.unwrap().unwrap();
+        assert!(matches!(reply.message_type, MessageType::Pong));
+        socket.close().await.unwrap();
+    }
+}
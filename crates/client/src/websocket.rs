@@ -0,0 +1,99 @@
+use crate::error::{ClientError, ClientResult};
+use futures::{SinkExt, StreamExt};
+use rusty_ai_common::api::{MessageType, WebSocketMessage};
+use tokio::net::TcpStream;
+use tokio_tungstenite::{
+    tungstenite::{client::IntoClientRequest, http::HeaderValue, Message},
+    MaybeTlsStream, WebSocketStream,
+};
+use tracing::debug;
+use uuid::Uuid;
+
+// Streaming connection to the server's /ws endpoint
+pub struct ChatSocket {
+    stream: WebSocketStream<MaybeTlsStream<TcpStream>>,
+    session_id: Option<Uuid>,
+}
+
+impl ChatSocket {
+    pub(crate) async fn connect(url: &str, token: Option<&str>, api_key: Option<&str>) -> ClientResult<Self> {
+        let mut request = url.into_client_request()?;
+
+        if let Some(token) = token {
+            let value = HeaderValue::from_str(&format!("Bearer {}", token))
+                .map_err(|e| ClientError::Configuration(e.to_string()))?;
+            request.headers_mut().insert("Authorization", value);
+        }
+        if let Some(key) = api_key {
+            let value = HeaderValue::from_str(key).map_err(|e| ClientError::Configuration(e.to_string()))?;
+            request.headers_mut().insert("X-API-Key", value);
+        }
+
+        let (stream, _) = tokio_tungstenite::connect_async(request).await?;
+        debug!("WebSocket connected to {}", url);
+
+        Ok(Self {
+            stream,
+            session_id: None,
+        })
+    }
+
+    pub fn session_id(&self) -> Option<Uuid> {
+        self.session_id
+    }
+
+    pub async fn send(&mut self, message: &WebSocketMessage) -> ClientResult<()> {
+        let text = serde_json::to_string(message).map_err(|e| ClientError::Decode(e.to_string()))?;
+        self.stream.send(Message::Text(text)).await?;
+        Ok(())
+    }
+
+    pub async fn send_chat(&mut self, text: &str) -> ClientResult<()> {
+        self.send(&WebSocketMessage {
+            message_type: MessageType::Chat,
+            session_id: self.session_id,
+            user_id: None,
+            data: serde_json::Value::String(text.to_string()),
+            timestamp: chrono::Utc::now(),
+        })
+        .await
+    }
+
+    pub async fn ping(&mut self) -> ClientResult<()> {
+        self.send(&WebSocketMessage {
+            message_type: MessageType::Ping,
+            session_id: self.session_id,
+            user_id: None,
+            data: serde_json::json!({}),
+            timestamp: chrono::Utc::now(),
+        })
+        .await
+    }
+
+    // Next server message; None once the connection is closed
+    pub async fn next_message(&mut self) -> Option<ClientResult<WebSocketMessage>> {
+        while let Some(frame) = self.stream.next().await {
+            match frame {
+                Ok(Message::Text(text)) => {
+                    let parsed = serde_json::from_str::<WebSocketMessage>(&text)
+                        .map_err(|e| ClientError::Decode(e.to_string()));
+                    if let Ok(message) = &parsed {
+                        if message.session_id.is_some() {
+                            self.session_id = message.session_id;
+                        }
+                    }
+                    return Some(parsed);
+                }
+                Ok(Message::Close(_)) => return None,
+                Ok(_) => continue,
+                Err(e) => return Some(Err(e.into())),
+            }
+        }
+        None
+    }
+
+    pub async fn close(mut self) -> ClientResult<()> {
+        self.stream.close(None).await?;
+        Ok(())
+    }
+}
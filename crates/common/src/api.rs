@@ -0,0 +1,616 @@
+// Request and response bodies of the public HTTP API. The server and the
+// client crate both use these definitions, so they cannot drift apart.
+use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use crate::{
+    ConversationTurn, DailyBriefing, Document, Intent, NotificationCategory, Task, TaskPriority, TaskStatus, UserPreferences,
+};
+
+// Body of endpoints that only acknowledge an action
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MessageResponse {
+    pub message: String,
+}
+
+impl MessageResponse {
+    pub fn new(message: impl Into<String>) -> Self {
+        Self { message: message.into() }
+    }
+}
+
+// Body of every non-2xx response
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ErrorResponse {
+    pub success: bool,
+    pub error: Option<String>,
+    pub error_code: Option<String>,
+    // Set on validation errors, one per violated constraint
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<FieldError>,
+}
+
+// A request field that failed validation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldError {
+    // Dotted path into the body or query, e.g. "voice_settings.speed"
+    pub field: String,
+    // The rule it broke: "required", "type", "json", "max_length", "range",
+    // "one_of", "format", "not_in_past", "scheme" or "public_host"
+    pub constraint: String,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(field: impl Into<String>, constraint: impl Into<String>, message: impl Into<String>) -> Self {
+        Self { field: field.into(), constraint: constraint.into(), message: message.into() }
+    }
+}
+
+// Authentication
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoginRequest {
+    pub email: String,
+    pub password: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoginResponse {
+    pub access_token: String,
+    pub refresh_token: String,
+    pub token_type: String,
+    pub expires_in: i64,
+    pub user: UserInfo,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RefreshRequest {
+    pub refresh_token: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserInfo {
+    pub id: Uuid,
+    pub name: String,
+    pub email: String,
+    pub permissions: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogoutRequest {
+    pub token: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogoutResponse {
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ValidateTokenRequest {
+    pub token: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ValidateTokenResponse {
+    pub valid: bool,
+    pub user_id: Uuid,
+    pub email: String,
+    pub expires_at: i64,
+    pub permissions: Vec<String>,
+}
+
+// Conversation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatRequest {
+    pub message: String,
+    pub session_id: Option<Uuid>,
+    pub context: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatResponse {
+    pub response: String,
+    pub session_id: Uuid,
+    pub intent: Intent,
+    pub conversation_id: Uuid,
+    pub processing_time_ms: u64,
+    pub suggested_actions: Vec<SuggestedAction>,
+    #[serde(default)]
+    pub sources: Vec<SourceRef>,
+    // Post-processing stages run on `response`
+    #[serde(default)]
+    pub processing: Vec<StageReport>,
+    // Activity log entries for what handling the message did on the user's behalf
+    #[serde(default)]
+    pub action_ids: Vec<Uuid>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProcessingStage {
+    Markdown,
+    LocaleFormatting,
+    PiiScrubbing,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StageStatus {
+    Applied,
+    // Enabled, but not applicable to this response or destination
+    Skipped,
+    Disabled,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StageReport {
+    pub stage: ProcessingStage,
+    pub status: StageStatus,
+    // How many rewrites or masks the stage made
+    pub changes: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SuggestedAction {
+    pub action_type: String,
+    pub label: String,
+    pub description: String,
+    pub parameters: serde_json::Value,
+}
+
+// A document a response drew on, for citations
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SourceRef {
+    pub document_id: Uuid,
+    pub title: String,
+    pub snippet: Option<String>,
+}
+
+// Command palette: commands that run a handler or plugin capability
+// directly, skipping classification and the model
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommandDescriptor {
+    // Without the leading slash
+    pub name: String,
+    pub description: String,
+    // JSON schema of the parameters object
+    pub parameters: serde_json::Value,
+    pub required_permission: Option<String>,
+    // Set for plugin capabilities
+    pub plugin_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ExecuteCommandRequest {
+    // A new session is started when absent, as for chat
+    pub session_id: Option<Uuid>,
+    #[serde(default)]
+    pub parameters: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConversationHistory {
+    pub session_id: Uuid,
+    pub turns: Vec<ConversationTurn>,
+    pub total_turns: usize,
+    pub created_at: DateTime<Utc>,
+    pub last_activity: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HistoryQuery {
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CreateSessionRequest {
+    pub preferences: Option<UserPreferences>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateSessionResponse {
+    pub session_id: Uuid,
+    pub created_at: DateTime<Utc>,
+}
+
+// Knowledge base
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchQuery {
+    pub q: String,
+    pub limit: Option<usize>,
+}
+
+/// Search-as-you-type: `q` is what has been typed so far
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SuggestQuery {
+    pub q: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DocumentSearchResponse {
+    pub documents: Vec<Document>,
+    pub total: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DocumentUpload {
+    pub title: String,
+    pub content: String,
+    pub tags: Vec<String>,
+}
+
+// Tasks
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateTaskRequest {
+    pub name: String,
+    pub description: String,
+    pub priority: String,
+    pub due_date: Option<DateTime<Utc>>,
+    pub tags: Vec<String>,
+    /// Accept a due date in the past, e.g. when logging overdue work
+    #[serde(default)]
+    pub allow_past_due: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TaskTimerRequest {
+    /// What the time was spent on; on stop it replaces the note given at start
+    pub note: Option<String>,
+}
+
+// Plugins
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstallPluginRequest {
+    pub name: String,
+    /// Newest release across the configured indexes when omitted
+    pub version: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PluginCutoverRequest {
+    /// A version already loaded next to the active one
+    pub version: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExecutePluginRequest {
+    pub function: String,
+    /// Passed to the plugin as JSON
+    #[serde(default)]
+    pub input: serde_json::Value,
+    pub session_id: Option<Uuid>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExecutePluginResponse {
+    pub plugin_id: String,
+    pub function: String,
+    /// The plugin's output as JSON, or as a string when it is not JSON
+    pub output: serde_json::Value,
+    pub stdout: String,
+    pub stderr: String,
+    pub duration_ms: u64,
+}
+
+// Fields left out keep their current value; `null` in `settings` removes
+// that setting
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UpdatePluginConfigRequest {
+    pub enabled: Option<bool>,
+    pub priority: Option<i32>,
+    #[serde(default)]
+    pub settings: std::collections::HashMap<String, serde_json::Value>,
+    pub quota: Option<crate::PluginQuota>,
+}
+
+// Voice
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VoiceRequest {
+    pub audio_data: String, // Base64 encoded audio
+    pub format: String,     // "wav", "mp3", etc.
+    pub session_id: Option<Uuid>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VoiceResponse {
+    pub transcript: String,
+    pub response: String,
+    pub audio_url: Option<String>,
+    pub processing_time_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SynthesizeRequest {
+    pub text: String,
+}
+
+// Share links
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CreateShareLinkRequest {
+    /// Hours until the link expires; defaults to 24, at most 720
+    pub expires_in_hours: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShareLinkResponse {
+    pub id: Uuid,
+    /// Public URL path; the token inside it is only returned once
+    pub url: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+// WebSocket frames exchanged on /ws
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebSocketMessage {
+    pub message_type: MessageType,
+    pub session_id: Option<Uuid>,
+    pub user_id: Option<Uuid>,
+    pub data: serde_json::Value,
+    pub timestamp: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum MessageType {
+    Chat,
+    VoiceData,
+    StatusUpdate,
+    Error,
+    Ping,
+    Pong,
+}
+
+// Live updates on /ws. Clients send {"type":"subscribe","topics":[...]} or
+// "unsubscribe"; the server answers with typed frames for the topics the
+// connection is subscribed to, only ever about the authenticated user.
+// {"type":"join","session_id":...} follows one of the user's conversation
+// sessions: a snapshot first, then every step of the session in order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LiveTopic {
+    Tasks,
+    Briefing,
+    Notifications,
+}
+
+impl LiveTopic {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "tasks" => Some(LiveTopic::Tasks),
+            "briefing" => Some(LiveTopic::Briefing),
+            "notifications" => Some(LiveTopic::Notifications),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LiveTopic::Tasks => "tasks",
+            LiveTopic::Briefing => "briefing",
+            LiveTopic::Notifications => "notifications",
+        }
+    }
+}
+
+/// Topics are kept as strings so unknown ones can be reported back by name
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LiveCommand {
+    Subscribe { topics: Vec<String> },
+    Unsubscribe { topics: Vec<String> },
+    Join { session_id: Uuid },
+    Leave { session_id: Uuid },
+    /// The user started or stopped typing in a session on this device
+    Typing { session_id: Uuid, active: bool },
+}
+
+// The fields of a task a live client needs to update a list in place
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LiveTask {
+    pub id: Uuid,
+    pub name: String,
+    pub status: TaskStatus,
+    pub priority: TaskPriority,
+    pub due_date: Option<DateTime<Utc>>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl From<&Task> for LiveTask {
+    fn from(task: &Task) -> Self {
+        Self {
+            id: task.id,
+            name: task.name.clone(),
+            status: task.status.clone(),
+            priority: task.priority.clone(),
+            due_date: task.due_date,
+            updated_at: task.updated_at,
+        }
+    }
+}
+
+// A briefing by its section titles; the full text is one GET away
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LiveBriefing {
+    pub id: Uuid,
+    pub date: DateTime<Utc>,
+    pub generated_at: DateTime<Utc>,
+    pub sections: Vec<String>,
+}
+
+impl From<&DailyBriefing> for LiveBriefing {
+    fn from(briefing: &DailyBriefing) -> Self {
+        Self {
+            id: briefing.id,
+            date: briefing.date,
+            generated_at: briefing.generated_at,
+            sections: briefing.sections.iter().map(|section| section.title.clone()).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LiveFrame {
+    Subscribed { topics: Vec<LiveTopic> },
+    Unsubscribed { topics: Vec<LiveTopic> },
+    /// Current state of a topic, sent on subscribe and after the
+    /// connection fell behind and frames were dropped
+    TaskSnapshot { tasks: Vec<LiveTask> },
+    BriefingSnapshot { briefing: Option<LiveBriefing> },
+    TaskUpdated { task: LiveTask },
+    BriefingGenerated { briefing: LiveBriefing },
+    Notification {
+        id: Uuid,
+        category: NotificationCategory,
+        title: String,
+        body: String,
+        created_at: DateTime<Utc>,
+    },
+    /// The assistant's opening message in a new conversation session
+    SessionGreeting {
+        session_id: Uuid,
+        turn_id: Uuid,
+        text: String,
+        created_at: DateTime<Utc>,
+    },
+    /// A conversation session as of `seq`, sent on join and after the
+    /// connection fell behind; the session's frames from `seq + 1` follow
+    SessionSnapshot {
+        session_id: Uuid,
+        seq: u64,
+        turns: Vec<ConversationTurn>,
+        presence: Presence,
+        reply: Option<PartialReply>,
+    },
+    SessionLeft { session_id: Uuid },
+    /// One step of a conversation session. `seq` counts up by one per
+    /// session, and every connection following it sees the same order
+    Session {
+        session_id: Uuid,
+        seq: u64,
+        #[serde(flatten)]
+        event: SessionEvent,
+    },
+    Error { message: String },
+}
+
+/// What every device following a session sees happen in it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum SessionEvent {
+    Presence { state: Presence },
+    /// A reply to `user_input` is being generated on some device
+    ChatStarted { reply_id: Uuid, user_input: String },
+    ChatDelta { reply_id: Uuid, delta: String },
+    /// The reply as the requesting device received it; its turn was
+    /// appended just before
+    ChatDone { reply_id: Uuid, response: ChatResponse },
+    /// The reply ended without an answer
+    ChatFailed { reply_id: Uuid },
+    /// A turn was stored. `reply_id` links it to the streamed reply it
+    /// completes; turns from REST requests have none
+    TurnAppended { turn: ConversationTurn, reply_id: Option<Uuid> },
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Presence {
+    #[default]
+    Idle,
+    Typing,
+    Generating,
+}
+
+/// A reply still streaming when a connection joined; later deltas append to `text`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PartialReply {
+    pub reply_id: Uuid,
+    pub user_input: String,
+    pub text: String,
+}
+
+impl LiveFrame {
+    /// Frames superseded by the next snapshot may be dropped when a client
+    /// reads too slowly; notifications and control frames never are
+    pub fn is_droppable(&self) -> bool {
+        matches!(self, LiveFrame::TaskUpdated { .. } | LiveFrame::BriefingGenerated { .. })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chat_request_round_trip() {
+        let request = ChatRequest {
+            message: "hello".to_string(),
+            session_id: Some(Uuid::new_v4()),
+            context: None,
+        };
+
+        let json = serde_json::to_string(&request).unwrap();
+        let parsed: ChatRequest = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.message, "hello");
+        assert_eq!(parsed.session_id, request.session_id);
+    }
+
+    #[test]
+    fn test_websocket_message_schema_is_stable() {
+        let message = WebSocketMessage {
+            message_type: MessageType::Ping,
+            session_id: None,
+            user_id: None,
+            data: serde_json::Value::Null,
+            timestamp: Utc::now(),
+        };
+
+        let value = serde_json::to_value(&message).unwrap();
+        assert_eq!(value["message_type"], "Ping");
+    }
+
+    #[test]
+    fn test_live_frames_are_tagged_by_type() {
+        let command: LiveCommand = serde_json::from_str(r#"{"type":"subscribe","topics":["tasks","weather"]}"#).unwrap();
+        match command {
+            LiveCommand::Subscribe { topics } => {
+                let parsed: Vec<_> = topics.iter().map(|topic| LiveTopic::parse(topic)).collect();
+                assert_eq!(parsed, vec![Some(LiveTopic::Tasks), None]);
+            }
+            other => panic!("unexpected command {:?}", other),
+        }
+
+        let frame = serde_json::to_value(LiveFrame::Subscribed { topics: vec![LiveTopic::Tasks, LiveTopic::Notifications] }).unwrap();
+        assert_eq!(frame, serde_json::json!({ "type": "subscribed", "topics": ["tasks", "notifications"] }));
+        assert!(!LiveFrame::Error { message: String::new() }.is_droppable());
+
+        let command: LiveCommand =
+            serde_json::from_str(&format!(r#"{{"type":"typing","session_id":"{}","active":true}}"#, Uuid::nil())).unwrap();
+        assert!(matches!(command, LiveCommand::Typing { active: true, .. }));
+    }
+
+    #[test]
+    fn test_session_frames_carry_their_event_inline() {
+        let session_id = Uuid::new_v4();
+        let reply_id = Uuid::new_v4();
+        let frame = LiveFrame::Session {
+            session_id,
+            seq: 7,
+            event: SessionEvent::ChatDelta { reply_id, delta: "Hel".to_string() },
+        };
+        let json = serde_json::to_value(&frame).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({ "type": "session", "session_id": session_id, "seq": 7, "event": "chat_delta", "reply_id": reply_id, "delta": "Hel" })
+        );
+
+        match serde_json::from_value(json).unwrap() {
+            LiveFrame::Session { seq: 7, event: SessionEvent::ChatDelta { delta, .. }, .. } => assert_eq!(delta, "Hel"),
+            other => panic!("unexpected frame {:?}", other),
+        }
+        let presence = serde_json::to_value(SessionEvent::Presence { state: Presence::Generating }).unwrap();
+        assert_eq!(presence, serde_json::json!({ "event": "presence", "state": "generating" }));
+    }
+}
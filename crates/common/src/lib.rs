@@ -1,7 +1,13 @@
+pub mod api;
+pub mod net;
+pub mod query_metrics;
+pub mod scratchpad;
+
 use serde::{Deserialize, Serialize};
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveTime, Utc, Weekday};
 use uuid::Uuid;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
 
 // Document types for knowledge base
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -51,6 +57,9 @@ pub struct DailyBriefing {
     pub date: DateTime<Utc>,
     pub sections: Vec<BriefingSection>,
     pub generated_at: DateTime<Utc>,
+    /// How each section fared; absent on briefings stored before reports
+    #[serde(default)]
+    pub generation_report: Option<GenerationReport>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -61,6 +70,36 @@ pub struct BriefingSection {
     pub source_documents: Vec<Uuid>,
 }
 
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct GenerationReport {
+    pub sections: Vec<SectionOutcome>,
+    /// Attempts needed to store the briefing, including the successful one
+    pub store_attempts: u32,
+}
+
+impl GenerationReport {
+    pub fn failed_sections(&self) -> impl Iterator<Item = &SectionOutcome> {
+        self.sections.iter().filter(|s| matches!(s.status, SectionStatus::Failed { .. }))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SectionOutcome {
+    pub section: String,
+    #[serde(flatten)]
+    pub status: SectionStatus,
+    pub attempts: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum SectionStatus {
+    Generated,
+    /// Nothing to report, e.g. no high-priority tasks
+    SkippedEmpty,
+    Failed { error: String },
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum BriefingPriority {
     Critical,
@@ -86,6 +125,120 @@ pub struct PluginConfig {
     pub enabled: bool,
     pub priority: i32,
     pub settings: HashMap<String, serde_json::Value>,
+    #[serde(default)]
+    pub quota: PluginQuota,
+}
+
+/// How much one plugin may be used; unset limits do not apply
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct PluginQuota {
+    /// Calls each user may make within any hour
+    pub max_calls_per_hour: Option<u32>,
+    /// Calls running at once, across all users
+    pub max_concurrent: Option<usize>,
+}
+
+impl PluginQuota {
+    /// A limit of zero would refuse every call; leave it unset to not limit
+    pub fn validate(&self) -> Result<()> {
+        if self.max_calls_per_hour == Some(0) {
+            return Err(AssistantError::Configuration("max_calls_per_hour must be at least 1".to_string()));
+        }
+        if self.max_concurrent == Some(0) {
+            return Err(AssistantError::Configuration("max_concurrent must be at least 1".to_string()));
+        }
+        Ok(())
+    }
+}
+
+// One call into a plugin, as kept for debugging and compliance. Sizes are
+// in bytes; fuel is unknown for plugins that are not metered
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PluginAuditRecord {
+    pub id: Uuid,
+    pub plugin_id: String,
+    pub function: String,
+    pub user_id: String,
+    pub request_id: String,
+    pub input_size: u64,
+    pub output_size: u64,
+    pub duration_ms: u64,
+    pub success: bool,
+    pub error: Option<String>,
+    pub fuel_consumed: Option<u64>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// What a scheduled plugin job does about runs missed while the process was down
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CatchUpPolicy {
+    /// Drop the missed runs and wait for the next one
+    #[default]
+    Skip,
+    /// Run once to make up for any number of missed runs
+    RunOnce,
+}
+
+impl CatchUpPolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CatchUpPolicy::Skip => "skip",
+            CatchUpPolicy::RunOnce => "run_once",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        [CatchUpPolicy::Skip, CatchUpPolicy::RunOnce].into_iter().find(|policy| policy.as_str() == value)
+    }
+}
+
+/// A plugin function run on a cron schedule, in UTC, with the input it is
+/// called with each time
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PluginSchedule {
+    pub id: Uuid,
+    pub plugin_id: String,
+    pub function: String,
+    pub input: serde_json::Value,
+    pub cron: String,
+    pub catch_up: CatchUpPolicy,
+    pub next_run_at: DateTime<Utc>,
+    pub last_run_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Plugin calls to look up: one plugin's or all, within `[from, to)`, newest first
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PluginAuditQuery {
+    pub plugin_id: Option<String>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub limit: Option<usize>,
+}
+
+/// An assistant event as plugins receive it. The assistant's own events
+/// (`rusty_ai_core::events::AssistantEvent`) carry more than plugins may
+/// see; `AssistantEvent::plugin_event` is the one place they become these
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PluginEvent {
+    DocumentUploaded { user_id: Uuid, document_id: Uuid, title: String },
+    /// `user_id` is None for tasks completed by background work
+    TaskCompleted { user_id: Option<Uuid>, task_id: Uuid, name: String },
+    /// `user_id` is None for briefings the scheduler produced
+    BriefingGenerated { user_id: Option<Uuid>, briefing_id: Uuid, date: DateTime<Utc> },
+}
+
+impl PluginEvent {
+    /// The name subscriptions filter on; the same as the serialized `type`
+    pub fn event_type(&self) -> &'static str {
+        match self {
+            PluginEvent::DocumentUploaded { .. } => "document_uploaded",
+            PluginEvent::TaskCompleted { .. } => "task_completed",
+            PluginEvent::BriefingGenerated { .. } => "briefing_generated",
+        }
+    }
 }
 
 // Error types
@@ -112,19 +265,47 @@ pub enum AssistantError {
     #[error("Unauthorized")]
     Unauthorized,
     
+    #[error("Security violation: {0}")]
+    Security(String),
+    
+    #[error("Rate limited: {message}")]
+    RateLimited { message: String, retry_after_secs: u64 },
+    
+    /// Briefly unable to serve, e.g. while a plugin is being unloaded
+    #[error("Unavailable: {message}")]
+    Unavailable { message: String, retry_after_secs: u64 },
+    
+    #[error("Timed out: {0}")]
+    Timeout(String),
+    
     #[error("Internal error: {0}")]
     Internal(String),
 }
 
 pub type Result<T> = std::result::Result<T, AssistantError>;
 
-// API response types
+// API response types. Every endpoint answers with this envelope; fields
+// added after the first version are left out when unset, so older clients
+// see the same shape as before
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ApiResponse<T> {
     pub success: bool,
     pub data: Option<T>,
     pub error: Option<String>,
     pub timestamp: DateTime<Utc>,
+    /// Matches the `x-request-id` response header
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pagination: Option<Pagination>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<ErrorCode>,
+    /// Request fields that failed validation
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<api::FieldError>,
+    /// How the response was produced, e.g. pipeline timings
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub meta: BTreeMap<String, serde_json::Value>,
 }
 
 impl<T> ApiResponse<T> {
@@ -134,6 +315,11 @@ impl<T> ApiResponse<T> {
             data: Some(data),
             error: None,
             timestamp: Utc::now(),
+            request_id: None,
+            pagination: None,
+            error_code: None,
+            fields: Vec::new(),
+            meta: BTreeMap::new(),
         }
     }
     
@@ -143,8 +329,139 @@ impl<T> ApiResponse<T> {
             data: None,
             error: Some(message),
             timestamp: Utc::now(),
+            request_id: None,
+            pagination: None,
+            error_code: None,
+            fields: Vec::new(),
+            meta: BTreeMap::new(),
+        }
+    }
+
+    pub fn error_with_code(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            error_code: Some(code),
+            ..Self::error(message.into())
+        }
+    }
+
+    /// One page of a listing; `data` holds the items of this page
+    pub fn paginated(data: T, pagination: Pagination) -> Self {
+        Self {
+            pagination: Some(pagination),
+            ..Self::success(data)
         }
     }
+
+    pub fn with_request_id(mut self, request_id: impl Into<String>) -> Self {
+        self.request_id = Some(request_id.into());
+        self
+    }
+
+    pub fn with_fields(mut self, fields: Vec<api::FieldError>) -> Self {
+        self.fields = fields;
+        self
+    }
+
+    pub fn with_meta(mut self, key: impl Into<String>, value: impl Serialize) -> Self {
+        // Serializing plain data into a Value does not fail
+        if let Ok(value) = serde_json::to_value(value) {
+            self.meta.insert(key.into(), value);
+        }
+        self
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Pagination {
+    /// Items across all pages, when the endpoint knows it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total: Option<u64>,
+    /// Pass back to fetch the next page; absent on the last page
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+}
+
+impl Pagination {
+    pub fn new(total: Option<u64>, next_cursor: Option<String>) -> Self {
+        Self { total, next_cursor }
+    }
+
+    pub fn has_more(&self) -> bool {
+        self.next_cursor.is_some()
+    }
+}
+
+/// Machine-readable reason for a failed request, stable across message
+/// wording changes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ErrorCode {
+    ValidationError,
+    BadRequest,
+    AuthenticationError,
+    AuthorizationError,
+    Unauthorized,
+    NotFound,
+    Conflict,
+    RateLimit,
+    RequestTooLarge,
+    InvalidContentType,
+    SerializationError,
+    WebsocketError,
+    DatabaseError,
+    VoiceProcessingError,
+    PluginUnavailable,
+    ConfigurationError,
+    ServiceUnavailable,
+    InternalError,
+}
+
+impl ErrorCode {
+    /// The code for an error that only has an HTTP status to go by
+    pub fn for_status(status: u16) -> Self {
+        match status {
+            401 => ErrorCode::Unauthorized,
+            403 => ErrorCode::AuthorizationError,
+            404 => ErrorCode::NotFound,
+            409 => ErrorCode::Conflict,
+            413 => ErrorCode::RequestTooLarge,
+            415 => ErrorCode::InvalidContentType,
+            422 => ErrorCode::ValidationError,
+            429 => ErrorCode::RateLimit,
+            503 => ErrorCode::ServiceUnavailable,
+            500..=599 => ErrorCode::InternalError,
+            _ => ErrorCode::BadRequest,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorCode::ValidationError => "VALIDATION_ERROR",
+            ErrorCode::BadRequest => "BAD_REQUEST",
+            ErrorCode::AuthenticationError => "AUTHENTICATION_ERROR",
+            ErrorCode::AuthorizationError => "AUTHORIZATION_ERROR",
+            ErrorCode::Unauthorized => "UNAUTHORIZED",
+            ErrorCode::NotFound => "NOT_FOUND",
+            ErrorCode::Conflict => "CONFLICT",
+            ErrorCode::RateLimit => "RATE_LIMIT",
+            ErrorCode::RequestTooLarge => "REQUEST_TOO_LARGE",
+            ErrorCode::InvalidContentType => "INVALID_CONTENT_TYPE",
+            ErrorCode::SerializationError => "SERIALIZATION_ERROR",
+            ErrorCode::WebsocketError => "WEBSOCKET_ERROR",
+            ErrorCode::DatabaseError => "DATABASE_ERROR",
+            ErrorCode::VoiceProcessingError => "VOICE_PROCESSING_ERROR",
+            ErrorCode::PluginUnavailable => "PLUGIN_UNAVAILABLE",
+            ErrorCode::ConfigurationError => "CONFIGURATION_ERROR",
+            ErrorCode::ServiceUnavailable => "SERVICE_UNAVAILABLE",
+            ErrorCode::InternalError => "INTERNAL_ERROR",
+        }
+    }
+}
+
+impl std::fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
 }
 
 // Task types for orchestration
@@ -185,7 +502,8 @@ pub struct UserContext {
     pub session_id: Uuid,
     pub preferences: UserPreferences,
     pub active_plugins: Vec<String>,
-    pub conversation_history: Vec<ConversationTurn>,
+    // Only the most recent turns; the full history lives in storage
+    pub conversation_history: ConversationWindow,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -207,11 +525,83 @@ pub struct VoiceSettings {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NotificationSettings {
     pub enabled: bool,
+    // Channels the user has enabled
     pub channels: Vec<NotificationChannel>,
-    pub quiet_hours: Option<(String, String)>,
+    pub quiet_hours: Option<QuietHours>,
+    // Per-category channel overrides; categories not listed use the defaults
+    #[serde(default)]
+    pub routing: HashMap<NotificationCategory, Vec<NotificationChannel>>,
+    #[serde(default)]
+    pub knowledge_digest: KnowledgeDigestSchedule,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl NotificationSettings {
+    pub fn validate(&self) -> Result<()> {
+        if let Some(quiet_hours) = &self.quiet_hours {
+            quiet_hours.parse()?;
+        }
+        self.knowledge_digest.parse_time()?;
+        Ok(())
+    }
+}
+
+// When the weekly knowledge digest goes out: a weekday and local "HH:MM" in
+// the user's timezone. Disabling it opts the user out
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KnowledgeDigestSchedule {
+    pub enabled: bool,
+    pub day: Weekday,
+    pub time: String,
+}
+
+impl Default for KnowledgeDigestSchedule {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            day: Weekday::Mon,
+            time: "08:00".to_string(),
+        }
+    }
+}
+
+impl KnowledgeDigestSchedule {
+    pub fn parse_time(&self) -> Result<NaiveTime> {
+        NaiveTime::parse_from_str(self.time.trim(), "%H:%M").map_err(|_| {
+            AssistantError::Api(format!("Invalid knowledge digest time: '{}' (expected HH:MM)", self.time))
+        })
+    }
+}
+
+// Local wall-clock window ("HH:MM", in the user's timezone) during which
+// non-urgent notifications are held back. A window whose end is before its
+// start spans midnight, e.g. 22:00-07:00.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuietHours {
+    pub start: String,
+    pub end: String,
+}
+
+impl QuietHours {
+    pub fn parse(&self) -> Result<(NaiveTime, NaiveTime)> {
+        let parse_time = |value: &str, field: &str| {
+            NaiveTime::parse_from_str(value.trim(), "%H:%M").map_err(|_| {
+                AssistantError::Api(format!("Invalid quiet hours {}: '{}' (expected HH:MM)", field, value))
+            })
+        };
+        Ok((parse_time(&self.start, "start")?, parse_time(&self.end, "end")?))
+    }
+
+    pub fn contains(&self, local_time: NaiveTime) -> Result<bool> {
+        let (start, end) = self.parse()?;
+        Ok(if start <= end {
+            start <= local_time && local_time < end
+        } else {
+            local_time >= start || local_time < end
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum NotificationChannel {
     Email,
     Sms,
@@ -219,6 +609,20 @@ pub enum NotificationChannel {
     InApp,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum NotificationCategory {
+    Reminder,
+    Briefing,
+    SecurityAlert,
+    PluginHealth,
+    Proactive,
+    KnowledgeDigest,
+    /// What focus mode held back, delivered when focus ends
+    FocusDigest,
+    /// Notice of upcoming data cleanup and what it will delete
+    Maintenance,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ConversationTurn {
     pub id: Uuid,
@@ -226,6 +630,77 @@ pub struct ConversationTurn {
     pub assistant_response: String,
     pub intent: Intent,
     pub timestamp: DateTime<Utc>,
+    #[serde(default)]
+    pub kind: TurnKind,
+}
+
+/// How a turn entered the conversation
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TurnKind {
+    /// A chat message, classified before it reached the handlers
+    #[default]
+    Message,
+    /// A command run from the command palette, without classification
+    Command,
+}
+
+impl TurnKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TurnKind::Message => "message",
+            TurnKind::Command => "command",
+        }
+    }
+
+    /// Unknown values read as a message, the kind every older turn has
+    pub fn parse(value: &str) -> Self {
+        match value {
+            "command" => TurnKind::Command,
+            _ => TurnKind::Message,
+        }
+    }
+}
+
+// The most recent turns of a session. Clones share the turns, so copying a
+// UserContext does not copy the conversation
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(from = "Vec<ConversationTurn>")]
+pub struct ConversationWindow {
+    turns: Arc<Vec<ConversationTurn>>,
+}
+
+impl ConversationWindow {
+    // Append a turn, dropping the oldest ones beyond `capacity`. Copies the
+    // turns only when a clone still shares them
+    pub fn push_bounded(&mut self, turn: ConversationTurn, capacity: usize) {
+        let turns = Arc::make_mut(&mut self.turns);
+        turns.push(turn);
+        if turns.len() > capacity {
+            let excess = turns.len() - capacity;
+            turns.drain(0..excess);
+        }
+    }
+}
+
+impl std::ops::Deref for ConversationWindow {
+    type Target = [ConversationTurn];
+
+    fn deref(&self) -> &Self::Target {
+        &self.turns
+    }
+}
+
+impl From<Vec<ConversationTurn>> for ConversationWindow {
+    fn from(turns: Vec<ConversationTurn>) -> Self {
+        Self { turns: Arc::new(turns) }
+    }
+}
+
+impl Serialize for ConversationWindow {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        self.turns.as_slice().serialize(serializer)
+    }
 }
 
 #[cfg(test)]
@@ -254,6 +729,26 @@ mod tests {
         assert_eq!(doc.metadata.source, "test");
     }
     
+    #[test]
+    fn test_quiet_hours_across_midnight() {
+        let quiet_hours = QuietHours { start: "22:00".to_string(), end: "07:00".to_string() };
+        let at = |h, m| NaiveTime::from_hms_opt(h, m, 0).unwrap();
+
+        assert!(quiet_hours.contains(at(23, 30)).unwrap());
+        assert!(quiet_hours.contains(at(6, 59)).unwrap());
+        assert!(!quiet_hours.contains(at(7, 0)).unwrap());
+        assert!(!quiet_hours.contains(at(12, 0)).unwrap());
+    }
+
+    #[test]
+    fn test_quiet_hours_rejects_malformed_times() {
+        let quiet_hours = QuietHours { start: "10pm".to_string(), end: "07:00".to_string() };
+        assert!(quiet_hours.parse().is_err());
+
+        let quiet_hours = QuietHours { start: "22:00".to_string(), end: "25:00".to_string() };
+        assert!(quiet_hours.parse().is_err());
+    }
+
     #[test]
     fn test_api_response() {
         let response = ApiResponse::success("data");
@@ -264,4 +759,61 @@ mod tests {
         assert!(!error_response.success);
         assert_eq!(error_response.error, Some("error".to_string()));
     }
+
+    #[test]
+    fn test_api_response_envelope_is_backward_compatible() {
+        // Unset additions are left out, so the original four fields remain
+        let body = serde_json::to_value(ApiResponse::success(1)).unwrap();
+        let mut keys: Vec<&str> = body.as_object().unwrap().keys().map(String::as_str).collect();
+        keys.sort();
+        assert_eq!(keys, ["data", "error", "success", "timestamp"]);
+
+        // And a body from before the additions still deserializes
+        let old: ApiResponse<u32> = serde_json::from_value(serde_json::json!({
+            "success": true, "data": 3, "error": null, "timestamp": "2024-01-01T00:00:00Z"
+        }))
+        .unwrap();
+        assert_eq!(old.data, Some(3));
+        assert!(old.request_id.is_none() && old.pagination.is_none() && old.meta.is_empty());
+    }
+
+    #[test]
+    fn test_api_response_envelope_additions() {
+        let page = ApiResponse::paginated(vec!["a", "b"], Pagination::new(Some(5), Some("2".to_string())))
+            .with_request_id("req-1")
+            .with_meta("mode", "streaming");
+        let body = serde_json::to_value(&page).unwrap();
+        assert_eq!(body["request_id"], "req-1");
+        assert_eq!(body["pagination"], serde_json::json!({ "total": 5, "next_cursor": "2" }));
+        assert_eq!(body["meta"]["mode"], "streaming");
+        assert!(page.pagination.unwrap().has_more());
+
+        let error: ApiResponse<()> = ApiResponse::error_with_code(ErrorCode::NotFound, "Session not found")
+            .with_fields(vec![api::FieldError::new("id", "format", "not a uuid")]);
+        let body = serde_json::to_value(&error).unwrap();
+        assert_eq!(body["error_code"], ErrorCode::NotFound.as_str());
+        assert_eq!(body["fields"][0]["field"], "id");
+
+        assert_eq!(ErrorCode::for_status(422), ErrorCode::ValidationError);
+        assert_eq!(ErrorCode::for_status(502), ErrorCode::InternalError);
+        assert_eq!(ErrorCode::for_status(405), ErrorCode::BadRequest);
+
+        let parsed: ApiResponse<()> = serde_json::from_value(body).unwrap();
+        assert_eq!(parsed.error_code, Some(ErrorCode::NotFound));
+        assert_eq!(parsed.error.as_deref(), Some("Session not found"));
+    }
+
+    #[test]
+    fn test_plugin_event_type_is_its_serialized_tag() {
+        let events = [
+            PluginEvent::DocumentUploaded { user_id: Uuid::new_v4(), document_id: Uuid::new_v4(), title: "Lease".to_string() },
+            PluginEvent::TaskCompleted { user_id: None, task_id: Uuid::new_v4(), name: "Pay rent".to_string() },
+            PluginEvent::BriefingGenerated { user_id: None, briefing_id: Uuid::new_v4(), date: Utc::now() },
+        ];
+        for event in events {
+            let json = serde_json::to_value(&event).unwrap();
+            assert_eq!(json["type"], event.event_type());
+            assert_eq!(serde_json::from_value::<PluginEvent>(json).unwrap(), event);
+        }
+    }
 }
\ No newline at end of file
@@ -0,0 +1,51 @@
+// Address checks shared by everything that sends requests to URLs users
+// choose (webhooks, crawls), so none of them can be pointed at the server's
+// own network.
+use std::net::IpAddr;
+
+// Whether `ip` is reachable on the public internet: not private, loopback,
+// link-local, unspecified, broadcast or carrier-grade NAT. IPv4-mapped IPv6
+// addresses are judged as the IPv4 address they carry
+pub fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => {
+            !(v4.is_private()
+                || v4.is_loopback()
+                || v4.is_link_local()
+                || v4.is_unspecified()
+                || v4.is_broadcast()
+                // Carrier-grade NAT, 100.64.0.0/10
+                || (v4.octets()[0] == 100 && (v4.octets()[1] & 0xc0) == 64))
+        }
+        IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+            Some(v4) => is_public_ip(IpAddr::V4(v4)),
+            None => {
+                let first = v6.segments()[0];
+                !(v6.is_loopback()
+                    || v6.is_unspecified()
+                    // Unique local fc00::/7 and link-local fe80::/10
+                    || (first & 0xfe00) == 0xfc00
+                    || (first & 0xffc0) == 0xfe80)
+            }
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_internal_addresses_are_not_public() {
+        let internal = [
+            "127.0.0.1", "10.0.0.5", "172.16.0.1", "192.168.1.1", "169.254.169.254", "0.0.0.0",
+            "255.255.255.255", "100.64.0.1", "::1", "::", "fd00::1", "fe80::1", "::ffff:10.0.0.1",
+        ];
+        for address in internal {
+            assert!(!is_public_ip(address.parse().unwrap()), "{}", address);
+        }
+        for address in ["93.184.216.34", "100.128.0.1", "2606:2800:220:1::", "::ffff:93.184.216.34"] {
+            assert!(is_public_ip(address.parse().unwrap()), "{}", address);
+        }
+    }
+}
@@ -0,0 +1,199 @@
+//! Query timing for the stores: a histogram per operation, the slowest
+//! queries with their parameters redacted, and a warning for any query over
+//! the threshold. The core storage and the server's conversation store both
+//! use it.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+// Upper bounds (ms) of the per-operation histogram buckets
+const HISTOGRAM_BUCKETS_MS: [u64; 9] = [1, 5, 10, 25, 50, 100, 250, 1000, 5000];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OperationHistogram {
+    pub buckets: Vec<(u64, u64)>,
+    pub count: u64,
+    pub sum_ms: f64,
+    pub max_ms: f64,
+}
+
+impl Default for OperationHistogram {
+    fn default() -> Self {
+        Self {
+            buckets: HISTOGRAM_BUCKETS_MS.iter().map(|b| (*b, 0)).collect(),
+            count: 0,
+            sum_ms: 0.0,
+            max_ms: 0.0,
+        }
+    }
+}
+
+/// One of the slowest queries seen. Parameters are reduced to their length so
+/// no user data ends up in the log
+#[derive(Debug, Clone, Serialize)]
+pub struct SlowQuery {
+    pub operation: String,
+    pub duration_ms: f64,
+    pub rows: u64,
+    pub parameters: Vec<String>,
+    pub at: DateTime<Utc>,
+}
+
+/// Per-operation query timings of a store, and the slowest queries it ran
+pub struct QueryMetrics {
+    slow_threshold: Duration,
+    capacity: usize,
+    operations: Mutex<HashMap<String, OperationHistogram>>,
+    // Sorted slowest first, at most `capacity` entries
+    slowest: Mutex<Vec<SlowQuery>>,
+}
+
+impl QueryMetrics {
+    pub fn new(slow_threshold: Duration, capacity: usize) -> Self {
+        Self {
+            slow_threshold,
+            capacity,
+            operations: Mutex::new(HashMap::new()),
+            slowest: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Start timing a query; it is recorded when the timer is dropped, so
+    /// failed queries are counted too
+    pub fn time(&self, operation: &'static str) -> QueryTimer<'_> {
+        QueryTimer {
+            metrics: self,
+            operation,
+            parameters: Vec::new(),
+            rows: 0,
+            started: Instant::now(),
+        }
+    }
+
+    pub fn record(&self, operation: &str, duration: Duration, rows: u64, parameters: Vec<String>) {
+        let duration_ms = duration.as_secs_f64() * 1000.0;
+        {
+            let mut operations = self.operations.lock().unwrap();
+            let histogram = operations.entry(operation.to_string()).or_default();
+            histogram.count += 1;
+            histogram.sum_ms += duration_ms;
+            histogram.max_ms = histogram.max_ms.max(duration_ms);
+            for (upper, count) in histogram.buckets.iter_mut() {
+                if duration_ms <= *upper as f64 {
+                    *count += 1;
+                }
+            }
+        }
+
+        if duration >= self.slow_threshold {
+            warn!(
+                "Slow storage operation '{}': {:.1}ms ({} rows, threshold {:?})",
+                operation, duration_ms, rows, self.slow_threshold
+            );
+        }
+
+        let mut slowest = self.slowest.lock().unwrap();
+        if slowest.len() >= self.capacity
+            && slowest.last().is_none_or(|fastest| fastest.duration_ms >= duration_ms)
+        {
+            return;
+        }
+        let position = slowest.partition_point(|q| q.duration_ms >= duration_ms);
+        slowest.insert(
+            position,
+            SlowQuery {
+                operation: operation.to_string(),
+                duration_ms,
+                rows,
+                parameters,
+                at: Utc::now(),
+            },
+        );
+        slowest.truncate(self.capacity);
+    }
+
+    pub fn operations(&self) -> HashMap<String, OperationHistogram> {
+        self.operations.lock().unwrap().clone()
+    }
+
+    /// Slowest first
+    pub fn slow_queries(&self) -> Vec<SlowQuery> {
+        self.slowest.lock().unwrap().clone()
+    }
+}
+
+pub struct QueryTimer<'a> {
+    metrics: &'a QueryMetrics,
+    operation: &'static str,
+    parameters: Vec<String>,
+    rows: u64,
+    started: Instant,
+}
+
+impl QueryTimer<'_> {
+    /// Note a bound parameter; only its length is kept
+    pub fn param(mut self, value: impl std::fmt::Display) -> Self {
+        self.parameters.push(redact(&value.to_string()));
+        self
+    }
+
+    pub fn rows(&mut self, rows: usize) {
+        self.rows = rows as u64;
+    }
+}
+
+impl Drop for QueryTimer<'_> {
+    fn drop(&mut self) {
+        self.metrics.record(
+            self.operation,
+            self.started.elapsed(),
+            self.rows,
+            std::mem::take(&mut self.parameters),
+        );
+    }
+}
+
+fn redact(value: &str) -> String {
+    format!("<redacted, {} chars>", value.chars().count())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keeps_the_slowest_queries() {
+        let metrics = QueryMetrics::new(Duration::from_secs(60), 2);
+        for (operation, ms) in [("a", 5), ("b", 50), ("c", 20), ("d", 1)] {
+            metrics.record(operation, Duration::from_millis(ms), 1, vec![]);
+        }
+
+        let slowest: Vec<String> = metrics.slow_queries().into_iter().map(|q| q.operation).collect();
+        assert_eq!(slowest, vec!["b", "c"]);
+        assert_eq!(metrics.operations()["a"].count, 1);
+        assert_eq!(metrics.operations().len(), 4);
+    }
+
+    #[test]
+    fn test_timer_records_on_drop_with_redacted_parameters() {
+        let metrics = QueryMetrics::new(Duration::from_secs(60), 10);
+        {
+            let mut timer = metrics.time("search_documents").param("secret project");
+            timer.rows(3);
+        }
+
+        let query = &metrics.slow_queries()[0];
+        assert_eq!(query.operation, "search_documents");
+        assert_eq!(query.rows, 3);
+        assert_eq!(query.parameters, vec!["<redacted, 14 chars>"]);
+        assert!(!format!("{:?}", query).contains("secret"));
+
+        let histogram = &metrics.operations()["search_documents"];
+        assert_eq!(histogram.count, 1);
+        assert_eq!(histogram.buckets.last().unwrap().1, 1);
+    }
+}
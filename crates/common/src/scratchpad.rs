@@ -0,0 +1,196 @@
+// Working state of a conversation for multi-step tasks ("plan my trip":
+// gather constraints over several turns, then book). A scratchpad is a JSON
+// object with free-form keys that lives as long as its session. Built-in
+// handlers and plugins read and write it; the prompt sent to the model
+// carries it between `<scratchpad>` delimiters.
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::RwLock;
+use uuid::Uuid;
+
+use crate::{AssistantError, Result};
+
+// Largest serialized scratchpad a session may hold
+pub const MAX_SCRATCHPAD_BYTES: usize = 16 * 1024;
+// Past this size the prompt gets a summary instead of the whole document
+pub const PROMPT_SCRATCHPAD_BYTES: usize = 2 * 1024;
+// Longest value shown per key in a summary
+const SUMMARY_VALUE_CHARS: usize = 80;
+
+pub const SCRATCHPAD_OPEN: &str = "<scratchpad>";
+pub const SCRATCHPAD_CLOSE: &str = "</scratchpad>";
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Scratchpad(BTreeMap<String, Value>);
+
+impl Scratchpad {
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.0.get(key)
+    }
+
+    /// Store `value` under `key`; null removes the key. A write that would
+    /// take the scratchpad past `MAX_SCRATCHPAD_BYTES` is refused and
+    /// changes nothing
+    pub fn set(&mut self, key: impl Into<String>, value: Value) -> Result<()> {
+        let key = key.into();
+        if key.trim().is_empty() {
+            return Err(AssistantError::Api("Scratchpad keys must not be empty".to_string()));
+        }
+        if value.is_null() {
+            self.0.remove(&key);
+            return Ok(());
+        }
+
+        let previous = self.0.insert(key.clone(), value);
+        let size = self.size_bytes();
+        if size > MAX_SCRATCHPAD_BYTES {
+            match previous {
+                Some(previous) => self.0.insert(key, previous),
+                None => self.0.remove(&key),
+            };
+            return Err(AssistantError::Api(format!(
+                "Scratchpad would grow to {} bytes; at most {} are allowed",
+                size, MAX_SCRATCHPAD_BYTES
+            )));
+        }
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn size_bytes(&self) -> usize {
+        serde_json::to_vec(&self.0).map_or(0, |json| json.len())
+    }
+
+    /// The scratchpad as a prompt section, or None when it is empty. A
+    /// scratchpad over `PROMPT_SCRATCHPAD_BYTES` is summarized one line per
+    /// key with values cut short, as many keys as fit
+    pub fn prompt_section(&self) -> Option<String> {
+        if self.is_empty() {
+            return None;
+        }
+
+        let json = serde_json::to_string_pretty(&self.0).unwrap_or_default();
+        let body = if json.len() <= PROMPT_SCRATCHPAD_BYTES {
+            json
+        } else {
+            let mut lines = vec![format!("(summary of {} keys, {} bytes)", self.len(), self.size_bytes())];
+            let mut used = lines[0].len();
+            for (shown, (key, value)) in self.0.iter().enumerate() {
+                let line = format!("- {}: {}", key, preview(value));
+                if used + line.len() > PROMPT_SCRATCHPAD_BYTES {
+                    lines.push(format!("- ... {} more keys", self.len() - shown));
+                    break;
+                }
+                used += line.len() + 1;
+                lines.push(line);
+            }
+            lines.join("\n")
+        };
+        Some(format!("{}\n{}\n{}", SCRATCHPAD_OPEN, body, SCRATCHPAD_CLOSE))
+    }
+}
+
+fn preview(value: &Value) -> String {
+    let text = value.to_string();
+    if text.chars().count() <= SUMMARY_VALUE_CHARS {
+        return text;
+    }
+    let cut: String = text.chars().take(SUMMARY_VALUE_CHARS).collect();
+    format!("{}...", cut)
+}
+
+// Scratchpads of the live sessions, shared by the session store, the
+// intent handlers and the plugin host
+#[derive(Debug, Default)]
+pub struct Scratchpads {
+    pads: RwLock<HashMap<Uuid, Scratchpad>>,
+}
+
+impl Scratchpads {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A copy of the session's scratchpad; empty if nothing was written
+    pub fn get(&self, session_id: Uuid) -> Scratchpad {
+        self.pads.read().unwrap().get(&session_id).cloned().unwrap_or_default()
+    }
+
+    pub fn set(&self, session_id: Uuid, key: impl Into<String>, value: Value) -> Result<()> {
+        let mut pads = self.pads.write().unwrap();
+        let pad = pads.entry(session_id).or_default();
+        let result = pad.set(key, value);
+        if pad.is_empty() {
+            pads.remove(&session_id);
+        }
+        result
+    }
+
+    /// Drop the session's scratchpad, returning what it held
+    pub fn clear(&self, session_id: Uuid) -> Scratchpad {
+        self.pads.write().unwrap().remove(&session_id).unwrap_or_default()
+    }
+
+    pub fn approximate_size_bytes(&self) -> u64 {
+        self.pads.read().unwrap().values().map(|pad| pad.size_bytes() as u64).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_writes_past_the_cap_change_nothing() {
+        let mut pad = Scratchpad::default();
+        pad.set("destination", json!("Lisbon")).unwrap();
+        assert!(pad.set("notes", json!("x".repeat(MAX_SCRATCHPAD_BYTES))).is_err());
+        assert!(pad.get("notes").is_none());
+        assert!(pad.set("destination", json!("y".repeat(MAX_SCRATCHPAD_BYTES))).is_err());
+        assert_eq!(pad.get("destination"), Some(&json!("Lisbon")));
+
+        pad.set("destination", Value::Null).unwrap();
+        assert!(pad.is_empty());
+        assert!(pad.prompt_section().is_none());
+    }
+
+    #[test]
+    fn test_large_scratchpads_are_summarized_in_the_prompt() {
+        let mut pad = Scratchpad::default();
+        pad.set("trip", json!({ "destination": "Lisbon", "nights": 3 })).unwrap();
+        let section = pad.prompt_section().unwrap();
+        assert!(section.starts_with(SCRATCHPAD_OPEN) && section.ends_with(SCRATCHPAD_CLOSE));
+        assert!(section.contains("\"nights\": 3"));
+
+        for day in 0..40 {
+            pad.set(format!("day_{:02}", day), json!("a long itinerary entry ".repeat(10))).unwrap();
+        }
+        let section = pad.prompt_section().unwrap();
+        assert!(section.len() < PROMPT_SCRATCHPAD_BYTES + 200);
+        assert!(section.contains("(summary of 41 keys"));
+        assert!(section.contains("- day_00: \"a long itinerary entry"));
+        assert!(section.contains("more keys"));
+    }
+
+    #[test]
+    fn test_scratchpads_are_per_session() {
+        let pads = Scratchpads::new();
+        let (planning, other) = (Uuid::new_v4(), Uuid::new_v4());
+        pads.set(planning, "budget", json!(1200)).unwrap();
+
+        assert_eq!(pads.get(planning).get("budget"), Some(&json!(1200)));
+        assert!(pads.get(other).is_empty());
+        assert_eq!(pads.clear(planning).len(), 1);
+        assert!(pads.get(planning).is_empty());
+    }
+}
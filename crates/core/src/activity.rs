@@ -0,0 +1,369 @@
+use chrono::{DateTime, Utc};
+use rusty_ai_common::{AssistantError, Result, UserPreferences};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+use crate::context_manager::ContextManager;
+use crate::storage::Storage;
+
+pub const DEFAULT_ACTIVITY_PAGE: usize = 50;
+pub const MAX_ACTIVITY_PAGE: usize = 200;
+
+/// Side effects the assistant can cause without being asked step by step
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ActionKind {
+    TaskCreated,
+    TimerStarted,
+    TimerStopped,
+    PreferencesChanged,
+    PluginCommand,
+    NotificationSent,
+    MemoryWritten,
+    DocumentIngested,
+}
+
+impl ActionKind {
+    pub const ALL: [ActionKind; 8] = [
+        ActionKind::TaskCreated,
+        ActionKind::TimerStarted,
+        ActionKind::TimerStopped,
+        ActionKind::PreferencesChanged,
+        ActionKind::PluginCommand,
+        ActionKind::NotificationSent,
+        ActionKind::MemoryWritten,
+        ActionKind::DocumentIngested,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ActionKind::TaskCreated => "task_created",
+            ActionKind::TimerStarted => "timer_started",
+            ActionKind::TimerStopped => "timer_stopped",
+            ActionKind::PreferencesChanged => "preferences_changed",
+            ActionKind::PluginCommand => "plugin_command",
+            ActionKind::NotificationSent => "notification_sent",
+            ActionKind::MemoryWritten => "memory_written",
+            ActionKind::DocumentIngested => "document_ingested",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.as_str() == value)
+    }
+}
+
+/// What set an action off: a chat message, or a scheduled job
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "source", rename_all = "snake_case")]
+pub enum ActionTrigger {
+    Chat { session_id: Uuid, message_id: Uuid },
+    Scheduler { job: String },
+}
+
+impl ActionTrigger {
+    pub fn scheduler(job: impl Into<String>) -> Self {
+        ActionTrigger::Scheduler { job: job.into() }
+    }
+
+    pub fn source(&self) -> &'static str {
+        match self {
+            ActionTrigger::Chat { .. } => "chat",
+            ActionTrigger::Scheduler { .. } => "scheduler",
+        }
+    }
+
+    pub fn session_id(&self) -> Option<Uuid> {
+        match self {
+            ActionTrigger::Chat { session_id, .. } => Some(*session_id),
+            ActionTrigger::Scheduler { .. } => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ActionStatus {
+    Succeeded,
+    Failed,
+    Undone,
+}
+
+impl ActionStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ActionStatus::Succeeded => "succeeded",
+            ActionStatus::Failed => "failed",
+            ActionStatus::Undone => "undone",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "succeeded" => Some(ActionStatus::Succeeded),
+            "failed" => Some(ActionStatus::Failed),
+            "undone" => Some(ActionStatus::Undone),
+            _ => None,
+        }
+    }
+}
+
+/// How an action is taken back
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum UndoStep {
+    DeleteTask { task_id: Uuid },
+    /// Put back the preferences the session had before the change
+    RestorePreferences { session_id: Uuid, preferences: UserPreferences },
+}
+
+/// A side effect as reported by whoever caused it, before it is recorded
+#[derive(Debug, Clone, Serialize)]
+pub struct PerformedAction {
+    pub kind: ActionKind,
+    pub description: String,
+    pub resource_id: Option<String>,
+    #[serde(skip)]
+    pub undo: Option<UndoStep>,
+    pub error: Option<String>,
+}
+
+impl PerformedAction {
+    pub fn new(kind: ActionKind, description: impl Into<String>) -> Self {
+        Self {
+            kind,
+            description: description.into(),
+            resource_id: None,
+            undo: None,
+            error: None,
+        }
+    }
+
+    pub fn resource(mut self, id: impl ToString) -> Self {
+        self.resource_id = Some(id.to_string());
+        self
+    }
+
+    pub fn undo(mut self, step: UndoStep) -> Self {
+        self.undo = Some(step);
+        self
+    }
+
+    pub fn failed(mut self, error: impl Into<String>) -> Self {
+        self.error = Some(error.into());
+        self
+    }
+}
+
+/// One entry of a user's activity feed
+#[derive(Debug, Clone, Serialize)]
+pub struct AssistantAction {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub kind: ActionKind,
+    pub trigger: ActionTrigger,
+    pub status: ActionStatus,
+    /// Readable summary, e.g. "Created the reminder 'call Anna'"
+    pub description: String,
+    /// Id of the task, entry or document the action touched
+    pub resource_id: Option<String>,
+    #[serde(skip)]
+    pub undo: Option<UndoStep>,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub undone_at: Option<DateTime<Utc>>,
+}
+
+impl AssistantAction {
+    pub fn new(user_id: Uuid, trigger: ActionTrigger, performed: PerformedAction) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            user_id,
+            kind: performed.kind,
+            trigger,
+            status: if performed.error.is_some() { ActionStatus::Failed } else { ActionStatus::Succeeded },
+            description: performed.description,
+            resource_id: performed.resource_id,
+            undo: performed.undo,
+            error: performed.error,
+            created_at: Utc::now(),
+            undone_at: None,
+        }
+    }
+
+    pub fn can_undo(&self) -> bool {
+        self.status == ActionStatus::Succeeded && self.undo.is_some()
+    }
+}
+
+/// An action as listed in the feed
+#[derive(Debug, Clone, Serialize)]
+pub struct ActivityEntry {
+    #[serde(flatten)]
+    pub action: AssistantAction,
+    pub undoable: bool,
+}
+
+impl From<AssistantAction> for ActivityEntry {
+    fn from(action: AssistantAction) -> Self {
+        Self { undoable: action.can_undo(), action }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ActivityFilter {
+    pub kind: Option<ActionKind>,
+    /// `chat` or `scheduler`
+    pub source: Option<String>,
+    pub status: Option<ActionStatus>,
+    pub session_id: Option<Uuid>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ActivityPage {
+    pub actions: Vec<ActivityEntry>,
+    pub offset: usize,
+    pub has_more: bool,
+}
+
+/// Record an action, logging rather than failing when it cannot be written:
+/// the action itself has already happened
+pub async fn record_action(storage: &dyn Storage, action: &AssistantAction) -> Option<Uuid> {
+    match storage.store_assistant_action(action).await {
+        Ok(()) => {
+            debug!("Recorded {} for {}: {}", action.kind.as_str(), action.user_id, action.description);
+            Some(action.id)
+        }
+        Err(e) => {
+            warn!("Failed to record {} for {}: {}", action.kind.as_str(), action.user_id, e);
+            None
+        }
+    }
+}
+
+// What the assistant did on each user's behalf: tasks it created, settings
+// it changed, plugins it ran and notifications it sent, with a way to take
+// back the ones that can be
+pub struct ActivityLog {
+    storage: Arc<dyn Storage + Send + Sync>,
+    context_manager: Arc<RwLock<ContextManager>>,
+}
+
+impl ActivityLog {
+    pub fn new(storage: Arc<dyn Storage + Send + Sync>, context_manager: Arc<RwLock<ContextManager>>) -> Self {
+        Self { storage, context_manager }
+    }
+
+    /// Ids of the actions that were recorded, in the order given
+    pub async fn record(&self, user_id: Uuid, trigger: &ActionTrigger, performed: Vec<PerformedAction>) -> Vec<Uuid> {
+        let mut ids = Vec::with_capacity(performed.len());
+        for performed in performed {
+            let action = AssistantAction::new(user_id, trigger.clone(), performed);
+            ids.extend(record_action(self.storage.as_ref(), &action).await);
+        }
+        ids
+    }
+
+    /// Newest first
+    pub async fn list(&self, user_id: Uuid, filter: &ActivityFilter) -> Result<ActivityPage> {
+        let limit = filter.limit.unwrap_or(DEFAULT_ACTIVITY_PAGE).clamp(1, MAX_ACTIVITY_PAGE);
+        let offset = filter.offset.unwrap_or(0);
+        // One extra row tells whether another page follows
+        let page = ActivityFilter { limit: Some(limit + 1), offset: Some(offset), ..filter.clone() };
+
+        let mut actions = self.storage.get_assistant_actions(user_id, &page).await?;
+        let has_more = actions.len() > limit;
+        actions.truncate(limit);
+        Ok(ActivityPage {
+            actions: actions.into_iter().map(ActivityEntry::from).collect(),
+            offset,
+            has_more,
+        })
+    }
+
+    /// The user's action; another user's reads as missing
+    pub async fn get(&self, user_id: Uuid, id: Uuid) -> Result<AssistantAction> {
+        match self.storage.get_assistant_action(id).await? {
+            Some(action) if action.user_id == user_id => Ok(action),
+            _ => Err(AssistantError::NotFound(format!("Action not found: {}", id))),
+        }
+    }
+
+    /// Take back an action that `can_undo`. A task the user already deleted
+    /// counts as taken back
+    pub async fn undo(&self, mut action: AssistantAction) -> Result<AssistantAction> {
+        let step = match (&action.undo, action.status) {
+            (Some(step), ActionStatus::Succeeded) => step.clone(),
+            _ => return Err(AssistantError::Api(format!("Action {} cannot be undone", action.id))),
+        };
+
+        match step {
+            UndoStep::DeleteTask { task_id } => match self.storage.delete_task(task_id).await {
+                Ok(()) | Err(AssistantError::NotFound(_)) => {}
+                Err(e) => return Err(e),
+            },
+            UndoStep::RestorePreferences { session_id, preferences } => {
+                self.context_manager
+                    .write()
+                    .await
+                    .update_user_preferences(session_id, preferences)
+                    .await?;
+            }
+        }
+
+        let now = Utc::now();
+        self.storage.mark_assistant_action_undone(action.id, now).await?;
+        action.status = ActionStatus::Undone;
+        action.undone_at = Some(now);
+        debug!("Undid {} {} for {}", action.kind.as_str(), action.id, action.user_id);
+        Ok(action)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kinds_round_trip() {
+        for kind in ActionKind::ALL {
+            assert_eq!(ActionKind::parse(kind.as_str()), Some(kind));
+            assert_eq!(serde_json::to_value(kind).unwrap(), kind.as_str());
+        }
+        assert_eq!(ActionKind::parse("task_deleted"), None);
+    }
+
+    #[test]
+    fn test_only_succeeded_actions_with_an_undo_step_can_be_undone() {
+        let user_id = Uuid::new_v4();
+        let trigger = ActionTrigger::Chat { session_id: Uuid::new_v4(), message_id: Uuid::new_v4() };
+        let task_id = Uuid::new_v4();
+        let created = PerformedAction::new(ActionKind::TaskCreated, "Created the task 'file taxes'")
+            .resource(task_id)
+            .undo(UndoStep::DeleteTask { task_id });
+
+        let action = AssistantAction::new(user_id, trigger.clone(), created.clone());
+        assert!(action.can_undo());
+        assert_eq!(action.resource_id, Some(task_id.to_string()));
+
+        let failed = AssistantAction::new(user_id, trigger.clone(), created.failed("storage is read-only"));
+        assert_eq!(failed.status, ActionStatus::Failed);
+        assert!(!failed.can_undo());
+
+        let plugin = AssistantAction::new(user_id, trigger, PerformedAction::new(ActionKind::PluginCommand, "Ran weather"));
+        assert!(!plugin.can_undo());
+
+        // The feed says whether an entry can be undone without exposing how
+        let entry = serde_json::to_value(ActivityEntry::from(action)).unwrap();
+        assert_eq!(entry["undoable"], true);
+        assert_eq!(entry["trigger"]["source"], "chat");
+        assert!(entry.get("undo").is_none());
+    }
+}
@@ -0,0 +1,363 @@
+use rusty_ai_common::{AssistantError, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tracing::debug;
+
+/// What a request costs and how urgently it has to run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RequestClass {
+    /// Chat and the rest of the interactive API
+    InteractiveChat,
+    /// Speech recognition and synthesis
+    Voice,
+    /// Bulk work nobody is waiting on: ingest, sync, plugin installs, queued tasks
+    Background,
+    Admin,
+}
+
+impl RequestClass {
+    pub const ALL: [RequestClass; 4] =
+        [RequestClass::InteractiveChat, RequestClass::Voice, RequestClass::Background, RequestClass::Admin];
+
+    /// Background work is shed at once when there is no slot; the others
+    /// wait for one up to the queue timeout
+    pub fn waits_for_slot(&self) -> bool {
+        *self != RequestClass::Background
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RequestClass::InteractiveChat => "interactive_chat",
+            RequestClass::Voice => "voice",
+            RequestClass::Background => "background",
+            RequestClass::Admin => "admin",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AdmissionConfig {
+    /// Slots shared by all requests running at once
+    pub max_concurrent: u32,
+    /// Slots only one class may use, on top of its share of `max_concurrent`.
+    /// They keep chat responsive while background work fills the shared slots
+    pub reserved: BTreeMap<RequestClass, u32>,
+    /// Slots one request of the class takes; voice synthesis costs more than a chat turn
+    pub weights: BTreeMap<RequestClass, u32>,
+    /// How long a waiting class queues before it is turned away
+    pub queue_timeout_ms: u64,
+    /// Sent as Retry-After with every shed request
+    pub retry_after_secs: u64,
+}
+
+impl Default for AdmissionConfig {
+    fn default() -> Self {
+        Self {
+            max_concurrent: 32,
+            reserved: BTreeMap::from([
+                (RequestClass::InteractiveChat, 8),
+                (RequestClass::Voice, 4),
+                (RequestClass::Admin, 2),
+            ]),
+            weights: BTreeMap::from([(RequestClass::Voice, 2)]),
+            queue_timeout_ms: 2_000,
+            retry_after_secs: 5,
+        }
+    }
+}
+
+impl AdmissionConfig {
+    pub fn weight(&self, class: RequestClass) -> u32 {
+        self.weights.get(&class).copied().unwrap_or(1).max(1)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.max_concurrent == 0 {
+            return Err(AssistantError::Configuration("admission max_concurrent must be at least 1".to_string()));
+        }
+        // A request that fits neither its reserve nor the shared pool would
+        // queue forever
+        for class in RequestClass::ALL {
+            let weight = self.weight(class);
+            let reserved = self.reserved.get(&class).copied().unwrap_or(0);
+            if weight > self.max_concurrent && weight > reserved {
+                return Err(AssistantError::Configuration(format!(
+                    "admission weight {} of {} exceeds every pool it can use",
+                    weight,
+                    class.as_str()
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Why a request was turned away
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rejection {
+    /// Background work found no free slot
+    Shed,
+    /// An interactive request queued past its deadline
+    TimedOut,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Overloaded {
+    pub class: RequestClass,
+    pub rejection: Rejection,
+    pub retry_after: Duration,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClassUtilization {
+    pub in_flight: u64,
+    pub reserved: u32,
+    pub weight: u32,
+    pub admitted: u64,
+    /// Admitted after waiting for a slot
+    pub queued: u64,
+    pub shed: u64,
+    pub timed_out: u64,
+    /// Queued jobs put back to run later instead of being dropped
+    pub requeued: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AdmissionSnapshot {
+    pub max_concurrent: u32,
+    /// Shared slots currently taken
+    pub shared_in_use: u32,
+    pub classes: BTreeMap<RequestClass, ClassUtilization>,
+}
+
+#[derive(Default)]
+struct ClassCounters {
+    in_flight: AtomicU64,
+    admitted: AtomicU64,
+    queued: AtomicU64,
+    shed: AtomicU64,
+    timed_out: AtomicU64,
+    requeued: AtomicU64,
+}
+
+struct ClassPool {
+    reserved: Arc<Semaphore>,
+    reserved_slots: u32,
+    weight: u32,
+    counters: Arc<ClassCounters>,
+}
+
+// Global concurrency budget with per-class reservations. A request takes
+// `weight` slots from its class's reserve if it can, else from the shared
+// pool, and holds them until its permit is dropped
+pub struct AdmissionController {
+    shared: Arc<Semaphore>,
+    classes: BTreeMap<RequestClass, ClassPool>,
+    config: AdmissionConfig,
+}
+
+/// Slots held by an admitted request
+pub struct AdmissionPermit {
+    _permit: OwnedSemaphorePermit,
+    counters: Arc<ClassCounters>,
+}
+
+impl Drop for AdmissionPermit {
+    fn drop(&mut self) {
+        self.counters.in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl AdmissionController {
+    pub fn new(config: AdmissionConfig) -> Result<Self> {
+        config.validate()?;
+        let classes = RequestClass::ALL
+            .into_iter()
+            .map(|class| {
+                let reserved_slots = config.reserved.get(&class).copied().unwrap_or(0);
+                let pool = ClassPool {
+                    reserved: Arc::new(Semaphore::new(reserved_slots as usize)),
+                    reserved_slots,
+                    weight: config.weight(class),
+                    counters: Arc::new(ClassCounters::default()),
+                };
+                (class, pool)
+            })
+            .collect();
+        Ok(Self {
+            shared: Arc::new(Semaphore::new(config.max_concurrent as usize)),
+            classes,
+            config,
+        })
+    }
+
+    pub fn config(&self) -> &AdmissionConfig {
+        &self.config
+    }
+
+    fn pool(&self, class: RequestClass) -> &ClassPool {
+        &self.classes[&class]
+    }
+
+    fn try_take(&self, pool: &ClassPool) -> Option<OwnedSemaphorePermit> {
+        pool.reserved
+            .clone()
+            .try_acquire_many_owned(pool.weight)
+            .or_else(|_| self.shared.clone().try_acquire_many_owned(pool.weight))
+            .ok()
+    }
+
+    fn admitted(&self, pool: &ClassPool, permit: OwnedSemaphorePermit) -> AdmissionPermit {
+        pool.counters.in_flight.fetch_add(1, Ordering::Relaxed);
+        pool.counters.admitted.fetch_add(1, Ordering::Relaxed);
+        AdmissionPermit { _permit: permit, counters: pool.counters.clone() }
+    }
+
+    fn overloaded(&self, class: RequestClass, rejection: Rejection) -> Overloaded {
+        let counters = &self.pool(class).counters;
+        match rejection {
+            Rejection::Shed => counters.shed.fetch_add(1, Ordering::Relaxed),
+            Rejection::TimedOut => counters.timed_out.fetch_add(1, Ordering::Relaxed),
+        };
+        debug!("Turned away {} request ({:?})", class.as_str(), rejection);
+        Overloaded { class, rejection, retry_after: Duration::from_secs(self.config.retry_after_secs) }
+    }
+
+    /// Admit without waiting
+    pub fn try_admit(&self, class: RequestClass) -> std::result::Result<AdmissionPermit, Overloaded> {
+        let pool = self.pool(class);
+        match self.try_take(pool) {
+            Some(permit) => Ok(self.admitted(pool, permit)),
+            None => Err(self.overloaded(class, Rejection::Shed)),
+        }
+    }
+
+    /// Admit a request of the class: background work is shed when no slot
+    /// is free, other classes queue for one until the queue timeout
+    pub async fn admit(&self, class: RequestClass) -> std::result::Result<AdmissionPermit, Overloaded> {
+        if !class.waits_for_slot() {
+            return self.try_admit(class);
+        }
+        let pool = self.pool(class);
+        if let Some(permit) = self.try_take(pool) {
+            return Ok(self.admitted(pool, permit));
+        }
+
+        // Whichever pool frees enough slots first
+        let reserved = pool.reserved.clone().acquire_many_owned(pool.weight);
+        let shared = self.shared.clone().acquire_many_owned(pool.weight);
+        let wait = async {
+            tokio::select! {
+                permit = reserved => permit,
+                permit = shared => permit,
+            }
+        };
+        match tokio::time::timeout(Duration::from_millis(self.config.queue_timeout_ms), wait).await {
+            Ok(Ok(permit)) => {
+                pool.counters.queued.fetch_add(1, Ordering::Relaxed);
+                Ok(self.admitted(pool, permit))
+            }
+            // The semaphores are never closed
+            Ok(Err(_)) | Err(_) => Err(self.overloaded(class, Rejection::TimedOut)),
+        }
+    }
+
+    /// Jobs the queue put back after being shed
+    pub fn record_requeued(&self, class: RequestClass, jobs: u64) {
+        self.pool(class).counters.requeued.fetch_add(jobs, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> AdmissionSnapshot {
+        let classes = self
+            .classes
+            .iter()
+            .map(|(class, pool)| {
+                let counters = &pool.counters;
+                let utilization = ClassUtilization {
+                    in_flight: counters.in_flight.load(Ordering::Relaxed),
+                    reserved: pool.reserved_slots,
+                    weight: pool.weight,
+                    admitted: counters.admitted.load(Ordering::Relaxed),
+                    queued: counters.queued.load(Ordering::Relaxed),
+                    shed: counters.shed.load(Ordering::Relaxed),
+                    timed_out: counters.timed_out.load(Ordering::Relaxed),
+                    requeued: counters.requeued.load(Ordering::Relaxed),
+                };
+                (*class, utilization)
+            })
+            .collect();
+        AdmissionSnapshot {
+            max_concurrent: self.config.max_concurrent,
+            shared_in_use: self.config.max_concurrent - self.shared.available_permits() as u32,
+            classes,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn controller(max_concurrent: u32, reserved: &[(RequestClass, u32)]) -> AdmissionController {
+        AdmissionController::new(AdmissionConfig {
+            max_concurrent,
+            reserved: reserved.iter().copied().collect(),
+            weights: BTreeMap::new(),
+            queue_timeout_ms: 50,
+            retry_after_secs: 7,
+        })
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_background_is_shed_while_chat_uses_its_reserve() {
+        let admission = controller(2, &[(RequestClass::InteractiveChat, 1)]);
+
+        let _first = admission.admit(RequestClass::Background).await.unwrap();
+        let _second = admission.admit(RequestClass::Background).await.unwrap();
+        let shed = admission.admit(RequestClass::Background).await.err().unwrap();
+        assert_eq!(shed.rejection, Rejection::Shed);
+        assert_eq!(shed.retry_after, Duration::from_secs(7));
+
+        let chat = admission.admit(RequestClass::InteractiveChat).await.unwrap();
+        let timed_out = admission.admit(RequestClass::InteractiveChat).await.err().unwrap();
+        assert_eq!(timed_out.rejection, Rejection::TimedOut);
+        drop(chat);
+        assert!(admission.admit(RequestClass::InteractiveChat).await.is_ok());
+
+        let snapshot = admission.snapshot();
+        assert_eq!(snapshot.shared_in_use, 2);
+        let background = &snapshot.classes[&RequestClass::Background];
+        assert_eq!((background.in_flight, background.admitted, background.shed), (2, 2, 1));
+        assert_eq!(snapshot.classes[&RequestClass::InteractiveChat].timed_out, 1);
+    }
+
+    #[tokio::test]
+    async fn test_queued_requests_get_the_next_free_slot() {
+        let admission = Arc::new(controller(1, &[]));
+        let held = admission.admit(RequestClass::Voice).await.unwrap();
+
+        let waiting = tokio::spawn({
+            let admission = admission.clone();
+            async move { admission.admit(RequestClass::InteractiveChat).await.is_ok() }
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        drop(held);
+
+        assert!(waiting.await.unwrap());
+        assert_eq!(admission.snapshot().classes[&RequestClass::InteractiveChat].queued, 1);
+    }
+
+    #[test]
+    fn test_weights_must_fit_a_pool() {
+        let mut config = AdmissionConfig::default();
+        assert!(config.validate().is_ok());
+        config.weights.insert(RequestClass::Voice, 40);
+        assert!(config.validate().is_err());
+    }
+}
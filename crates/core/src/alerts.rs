@@ -0,0 +1,423 @@
+use rusty_ai_common::{NotificationCategory, Result, Task, TaskStatus, UserPreferences};
+use chrono::NaiveDate;
+use chrono_tz::Tz;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::flags::{FeatureFlags, Flag};
+use crate::notifications::{Clock, Notification, NotificationRouter, SystemClock};
+use crate::plugin_manager::{HealthStatus, PluginManager};
+use crate::storage::Storage;
+
+/// Tag the task handler puts on reminders
+pub const REMINDER_TAG: &str = "reminder";
+
+// Overdue tasks named in one suggestion
+const SUGGESTED_TASKS: usize = 5;
+
+// Notifications the assistant raises on its own: reminders that came due,
+// a daily suggestion about overdue tasks, and plugins whose health changed.
+// All go through the router, so they follow each user's channels, quiet
+// hours and focus. Tasks carry no owner, so like the briefing they go to
+// every user.
+pub struct AlertScheduler {
+    storage: Arc<dyn Storage + Send + Sync>,
+    plugin_manager: Arc<PluginManager>,
+    router: Arc<NotificationRouter>,
+    clock: Arc<dyn Clock>,
+    flags: Option<Arc<FeatureFlags>>,
+    // Last status seen per plugin, so only changes are announced
+    plugin_status: Mutex<HashMap<String, HealthStatus>>,
+    // Local date of each user's last overdue-task suggestion
+    suggested_on: Mutex<HashMap<Uuid, NaiveDate>>,
+}
+
+impl AlertScheduler {
+    pub fn new(
+        storage: Arc<dyn Storage + Send + Sync>,
+        plugin_manager: Arc<PluginManager>,
+        router: Arc<NotificationRouter>,
+    ) -> Self {
+        Self::new_with_clock(storage, plugin_manager, router, Arc::new(SystemClock))
+    }
+
+    pub fn new_with_clock(
+        storage: Arc<dyn Storage + Send + Sync>,
+        plugin_manager: Arc<PluginManager>,
+        router: Arc<NotificationRouter>,
+        clock: Arc<dyn Clock>,
+    ) -> Self {
+        Self {
+            storage,
+            plugin_manager,
+            router,
+            clock,
+            flags: None,
+            plugin_status: Mutex::new(HashMap::new()),
+            suggested_on: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Skip suggestions for users who switched proactive messages off
+    pub fn with_flags(mut self, flags: Arc<FeatureFlags>) -> Self {
+        self.flags = Some(flags);
+        self
+    }
+
+    /// Called periodically by the server. Returns how many notifications
+    /// were routed
+    pub async fn run_due(&self, users: &[(Uuid, UserPreferences)]) -> Result<usize> {
+        let pending = self.storage.get_tasks_by_status(TaskStatus::Pending).await?;
+        let mut routed = self.fire_reminders(&pending, users).await?;
+        routed += self.suggest_overdue(&pending, users).await;
+        routed += self.check_plugins(users).await;
+        Ok(routed)
+    }
+
+    // Route each reminder that came due, then complete it so it fires once.
+    // A reminder no user's router took stays pending and fires again on the
+    // next run
+    async fn fire_reminders(&self, pending: &[Task], users: &[(Uuid, UserPreferences)]) -> Result<usize> {
+        let now = self.clock.now();
+        let mut routed = 0;
+        for task in pending.iter().filter(|task| is_reminder(task)) {
+            let Some(due) = task.due_date.filter(|due| *due <= now) else { continue };
+            let mut accepted = false;
+            for (user_id, preferences) in users {
+                let notification = Notification::new(
+                    *user_id,
+                    NotificationCategory::Reminder,
+                    task.name.clone(),
+                    format!("Due {}", due.format("%Y-%m-%d %H:%M UTC")),
+                )
+                .with_priority(task.priority.clone());
+                if self.route(preferences, notification).await {
+                    routed += 1;
+                    accepted = true;
+                }
+            }
+            if !accepted {
+                warn!("Reminder '{}' reached no one; retrying on the next run", task.name);
+                continue;
+            }
+            self.storage.update_task_status(task.id, TaskStatus::Completed).await?;
+            info!("Reminder '{}' fired", task.name);
+        }
+        Ok(routed)
+    }
+
+    // At most one suggestion per user and local day to deal with tasks past
+    // their due date
+    async fn suggest_overdue(&self, pending: &[Task], users: &[(Uuid, UserPreferences)]) -> usize {
+        let now = self.clock.now();
+        let overdue: Vec<&Task> = pending
+            .iter()
+            .filter(|task| !is_reminder(task) && task.due_date.map_or(false, |due| due < now))
+            .collect();
+        if overdue.is_empty() {
+            return 0;
+        }
+
+        let mut names: Vec<String> = overdue.iter().take(SUGGESTED_TASKS).map(|task| format!("- {}", task.name)).collect();
+        if overdue.len() > SUGGESTED_TASKS {
+            names.push(format!("- and {} more", overdue.len() - SUGGESTED_TASKS));
+        }
+        let body = format!("Want to reschedule or drop them?\n{}", names.join("\n"));
+
+        let mut routed = 0;
+        for (user_id, preferences) in users {
+            if let Some(flags) = &self.flags {
+                if !flags.enabled(Flag::ProactiveMessages, *user_id) {
+                    continue;
+                }
+            }
+            let tz: Tz = preferences.timezone.parse().unwrap_or(Tz::UTC);
+            let today = now.with_timezone(&tz).date_naive();
+            if self.suggested_on.lock().unwrap().insert(*user_id, today) == Some(today) {
+                continue;
+            }
+
+            let notification = Notification::new(
+                *user_id,
+                NotificationCategory::Proactive,
+                format!("{} task(s) are overdue", overdue.len()),
+                body.clone(),
+            );
+            if self.route(preferences, notification).await {
+                routed += 1;
+            }
+        }
+        routed
+    }
+
+    // Tell every user when a plugin turns degraded or unhealthy, and when it
+    // recovers. Plugins seen for the first time count as healthy before
+    async fn check_plugins(&self, users: &[(Uuid, UserPreferences)]) -> usize {
+        let health = self.plugin_manager.health_check_all().await;
+        let mut changes = Vec::new();
+        {
+            let mut seen = self.plugin_status.lock().unwrap();
+            seen.retain(|id, _| health.contains_key(id));
+            for (id, plugin) in &health {
+                let previous = seen.insert(id.clone(), plugin.status).unwrap_or(HealthStatus::Healthy);
+                if previous != plugin.status {
+                    changes.push((id.clone(), plugin.status, plugin.message.clone()));
+                }
+            }
+        }
+        changes.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let mut routed = 0;
+        for (id, status, message) in changes {
+            let title = match status {
+                HealthStatus::Healthy => format!("Plugin '{}' recovered", id),
+                HealthStatus::Degraded => format!("Plugin '{}' is degraded", id),
+                HealthStatus::Unhealthy => format!("Plugin '{}' is unhealthy", id),
+            };
+            let body = message.unwrap_or_default();
+            for (user_id, preferences) in users {
+                let notification = Notification::new(*user_id, NotificationCategory::PluginHealth, title.clone(), body.clone());
+                if self.route(preferences, notification).await {
+                    routed += 1;
+                }
+            }
+        }
+        routed
+    }
+
+    // Whether the router took the notification, now or for later
+    async fn route(&self, preferences: &UserPreferences, notification: Notification) -> bool {
+        let id = notification.id;
+        match self.router.route(preferences, notification).await {
+            Ok(decision) => decision.is_accepted(),
+            Err(e) => {
+                warn!("Failed to route notification {}: {}", id, e);
+                false
+            }
+        }
+    }
+}
+
+fn is_reminder(task: &Task) -> bool {
+    task.tags.iter().any(|tag| tag == REMINDER_TAG)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flags::FlagOverride;
+    use crate::plugin_manager::{AssistantPlugin, PluginHealth};
+    use crate::storage::{SqliteStorage, StorageConfig};
+    use crate::test_support::{at, RecordingSink, TestClock};
+    use async_trait::async_trait;
+    use chrono::Utc;
+    use rusty_ai_common::{
+        Intent, NotificationChannel, NotificationSettings, PluginConfig, PluginMetadata, TaskPriority, UserContext,
+        VoiceSettings,
+    };
+
+    // Reports whatever status the test sets
+    struct StatusPlugin(Arc<Mutex<HealthStatus>>);
+
+    #[async_trait]
+    impl AssistantPlugin for StatusPlugin {
+        fn metadata(&self) -> PluginMetadata {
+            PluginMetadata {
+                id: "weather".to_string(),
+                name: "Weather".to_string(),
+                version: "0.1.0".to_string(),
+                description: String::new(),
+                author: String::new(),
+                capabilities: vec![],
+                dependencies: vec![],
+            }
+        }
+
+        async fn initialize(&mut self, _config: PluginConfig) -> Result<()> {
+            Ok(())
+        }
+
+        async fn handle_intent(&self, _intent: Intent, _context: &UserContext) -> Result<String> {
+            Ok(String::new())
+        }
+
+        async fn health_check(&self) -> PluginHealth {
+            PluginHealth { status: *self.0.lock().unwrap(), message: Some("API timeouts".to_string()), last_check: Utc::now() }
+        }
+
+        fn can_handle_query(&self, _query: &str) -> bool {
+            false
+        }
+
+        fn can_handle_task(&self, _task_name: &str) -> bool {
+            false
+        }
+
+        async fn process_query(&self, _query: String, _context: &UserContext) -> Result<String> {
+            Ok(String::new())
+        }
+
+        async fn execute_task(&self, _task: &Task) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    fn preferences() -> UserPreferences {
+        UserPreferences {
+            language: "en".to_string(),
+            timezone: "UTC".to_string(),
+            voice_settings: VoiceSettings { enabled: false, voice_id: "default".to_string(), speed: 1.0, pitch: 1.0 },
+            notification_settings: NotificationSettings {
+                enabled: true,
+                channels: vec![NotificationChannel::InApp, NotificationChannel::Push],
+                quiet_hours: None,
+                routing: Default::default(),
+                knowledge_digest: Default::default(),
+            },
+        }
+    }
+
+    fn task(name: &str, tag: &str, due: &str) -> Task {
+        Task {
+            id: Uuid::new_v4(),
+            name: name.to_string(),
+            description: String::new(),
+            status: TaskStatus::Pending,
+            priority: TaskPriority::High,
+            due_date: Some(at(due)),
+            tags: vec![tag.to_string()],
+            created_at: at("2024-03-01T09:00:00Z"),
+            updated_at: at("2024-03-01T09:00:00Z"),
+        }
+    }
+
+    struct Fixture {
+        alerts: AlertScheduler,
+        storage: Arc<dyn Storage + Send + Sync>,
+        clock: Arc<TestClock>,
+        sink: Arc<RecordingSink>,
+        plugin_status: Arc<Mutex<HealthStatus>>,
+        flags: Arc<FeatureFlags>,
+    }
+
+    async fn fixture(now: &str) -> Fixture {
+        let config =
+            StorageConfig { database_url: "sqlite::memory:".to_string(), max_connections: 1, ..Default::default() };
+        let storage: Arc<dyn Storage + Send + Sync> = Arc::new(SqliteStorage::new(&config).await.unwrap());
+        let clock = TestClock::at(now);
+        let sink = Arc::new(RecordingSink::default());
+        let router = Arc::new(NotificationRouter::new_with_clock(clock.clone(), sink.clone(), None));
+
+        let plugin_status = Arc::new(Mutex::new(HealthStatus::Healthy));
+        let plugin_manager = Arc::new(PluginManager::new());
+        plugin_manager.register_plugin(Box::new(StatusPlugin(plugin_status.clone()))).await.unwrap();
+
+        let flags = Arc::new(FeatureFlags::default());
+        let alerts = AlertScheduler::new_with_clock(storage.clone(), plugin_manager, router, clock.clone())
+            .with_flags(flags.clone());
+        Fixture { alerts, storage, clock, sink, plugin_status, flags }
+    }
+
+    fn delivered(fixture: &Fixture) -> Vec<(NotificationCategory, String)> {
+        let delivered = fixture.sink.notifications().into_iter().map(|n| (n.category, n.title)).collect();
+        fixture.sink.clear();
+        delivered
+    }
+
+    #[tokio::test]
+    async fn test_due_reminders_fire_once_through_the_router() {
+        let fixture = fixture("2024-03-12T09:00:00Z").await;
+        let due = task("Call the dentist", REMINDER_TAG, "2024-03-12T08:55:00Z");
+        let later = task("Water plants", REMINDER_TAG, "2024-03-12T18:00:00Z");
+        for task in [&due, &later] {
+            fixture.storage.store_task(task).await.unwrap();
+        }
+        let users = [(Uuid::new_v4(), preferences())];
+
+        // Reminders go to push by default
+        assert_eq!(fixture.alerts.run_due(&users).await.unwrap(), 1);
+        let sent = fixture.sink.notifications();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].category, NotificationCategory::Reminder);
+        assert_eq!(sent[0].title, "Call the dentist");
+        assert_eq!(sent[0].priority, Some(TaskPriority::High));
+        assert_eq!(fixture.storage.get_task(due.id).await.unwrap().unwrap().status, TaskStatus::Completed);
+        fixture.sink.clear();
+
+        assert_eq!(fixture.alerts.run_due(&users).await.unwrap(), 0);
+
+        fixture.clock.set("2024-03-12T18:00:00Z");
+        assert_eq!(fixture.alerts.run_due(&users).await.unwrap(), 1);
+        assert_eq!(delivered(&fixture), vec![(NotificationCategory::Reminder, "Water plants".to_string())]);
+    }
+
+    #[tokio::test]
+    async fn test_reminders_stay_pending_until_delivered() {
+        let fixture = fixture("2024-03-12T09:00:00Z").await;
+        let due = task("Call the dentist", REMINDER_TAG, "2024-03-12T08:55:00Z");
+        fixture.storage.store_task(&due).await.unwrap();
+        let users = [(Uuid::new_v4(), preferences())];
+
+        fixture.sink.set_down(true);
+        assert_eq!(fixture.alerts.run_due(&users).await.unwrap(), 0);
+        assert_eq!(fixture.storage.get_task(due.id).await.unwrap().unwrap().status, TaskStatus::Pending);
+
+        fixture.sink.set_down(false);
+        assert_eq!(fixture.alerts.run_due(&users).await.unwrap(), 1);
+        assert_eq!(delivered(&fixture), vec![(NotificationCategory::Reminder, "Call the dentist".to_string())]);
+        assert_eq!(fixture.storage.get_task(due.id).await.unwrap().unwrap().status, TaskStatus::Completed);
+    }
+
+    #[tokio::test]
+    async fn test_overdue_tasks_are_suggested_once_a_day_unless_proactive_messages_are_off() {
+        let fixture = fixture("2024-03-12T09:00:00Z").await;
+        fixture.storage.store_task(&task("Renew passport", "task", "2024-03-11T09:00:00Z")).await.unwrap();
+        let (user, paused) = (Uuid::new_v4(), Uuid::new_v4());
+        fixture
+            .flags
+            .set(Flag::ProactiveMessages, Some(FlagOverride { users: [(paused, false)].into(), ..Default::default() }))
+            .await
+            .unwrap();
+        let users = [(user, preferences()), (paused, preferences())];
+
+        assert_eq!(fixture.alerts.run_due(&users).await.unwrap(), 1);
+        let sent = fixture.sink.notifications();
+        assert_eq!(sent[0].user_id, user);
+        assert_eq!(sent[0].category, NotificationCategory::Proactive);
+        assert_eq!(sent[0].body, "Want to reschedule or drop them?\n- Renew passport");
+        fixture.sink.clear();
+
+        fixture.clock.set("2024-03-12T17:00:00Z");
+        assert_eq!(fixture.alerts.run_due(&users).await.unwrap(), 0);
+
+        fixture.clock.set("2024-03-13T09:00:00Z");
+        assert_eq!(fixture.alerts.run_due(&users).await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_plugin_health_changes_are_announced_once() {
+        let fixture = fixture("2024-03-12T09:00:00Z").await;
+        let users = [(Uuid::new_v4(), preferences())];
+
+        assert_eq!(fixture.alerts.run_due(&users).await.unwrap(), 0);
+
+        *fixture.plugin_status.lock().unwrap() = HealthStatus::Degraded;
+        assert_eq!(fixture.alerts.run_due(&users).await.unwrap(), 1);
+        let sent = fixture.sink.notifications();
+        assert_eq!(sent[0].category, NotificationCategory::PluginHealth);
+        assert_eq!(sent[0].title, "Plugin 'weather' is degraded");
+        assert_eq!(sent[0].body, "API timeouts");
+        fixture.sink.clear();
+
+        assert_eq!(fixture.alerts.run_due(&users).await.unwrap(), 0);
+
+        *fixture.plugin_status.lock().unwrap() = HealthStatus::Healthy;
+        assert_eq!(fixture.alerts.run_due(&users).await.unwrap(), 1);
+        assert_eq!(
+            delivered(&fixture),
+            vec![(NotificationCategory::PluginHealth, "Plugin 'weather' recovered".to_string())]
+        );
+    }
+}
@@ -0,0 +1,205 @@
+use chrono::{DateTime, Utc};
+use rusty_ai_common::Result;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::{mpsc, oneshot};
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+use crate::storage::Storage;
+
+/// Entries waiting to be written; further entries are dropped and counted
+/// as failures while the queue is full
+const AUDIT_QUEUE_CAPACITY: usize = 1024;
+
+pub const DEFAULT_AUDIT_RETENTION_DAYS: i64 = 365;
+
+/// One admin-scope mutation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub id: Uuid,
+    /// User id of the admin who made the change
+    pub actor: String,
+    /// Dotted action name, e.g. `plugin.uninstall`
+    pub action: String,
+    pub target: String,
+    pub before: Option<serde_json::Value>,
+    pub after: Option<serde_json::Value>,
+    pub request_id: Option<String>,
+    /// False when the handler returned without describing the change, e.g.
+    /// on an error; `action` is then the route
+    pub complete: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+impl AuditEntry {
+    pub fn new(actor: impl Into<String>, action: impl Into<String>, target: impl Into<String>) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            actor: actor.into(),
+            action: action.into(),
+            target: target.into(),
+            before: None,
+            after: None,
+            request_id: None,
+            complete: true,
+            created_at: Utc::now(),
+        }
+    }
+
+    pub fn before(mut self, state: impl Serialize) -> Self {
+        self.before = serde_json::to_value(state).ok();
+        self
+    }
+
+    pub fn after(mut self, state: impl Serialize) -> Self {
+        self.after = serde_json::to_value(state).ok();
+        self
+    }
+
+    pub fn request_id(mut self, request_id: Option<String>) -> Self {
+        self.request_id = request_id;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct AuditFilter {
+    pub actor: Option<String>,
+    pub action: Option<String>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub limit: Option<usize>,
+}
+
+enum Command {
+    Write(AuditEntry),
+    Flush(oneshot::Sender<()>),
+}
+
+// Records admin actions without holding up the request: entries are queued
+// and written by a background task. Failed or dropped writes are counted so
+// a broken audit trail shows up in the metrics.
+pub struct AuditTrail {
+    storage: Arc<dyn Storage + Send + Sync>,
+    sender: mpsc::Sender<Command>,
+    write_failures: Arc<AtomicU64>,
+    retention: chrono::Duration,
+}
+
+impl AuditTrail {
+    /// Must be called within a Tokio runtime, which runs the writer
+    pub fn new(storage: Arc<dyn Storage + Send + Sync>, retention_days: i64) -> Self {
+        let (sender, mut receiver) = mpsc::channel(AUDIT_QUEUE_CAPACITY);
+        let write_failures = Arc::new(AtomicU64::new(0));
+
+        let writer_storage = storage.clone();
+        let failures = write_failures.clone();
+        tokio::spawn(async move {
+            while let Some(command) = receiver.recv().await {
+                match command {
+                    Command::Write(entry) => match writer_storage.store_audit_entry(&entry).await {
+                        Ok(()) => debug!("Audited {} on {} by {}", entry.action, entry.target, entry.actor),
+                        Err(e) => {
+                            failures.fetch_add(1, Ordering::Relaxed);
+                            warn!("Failed to write audit entry for {} on {}: {}", entry.action, entry.target, e);
+                        }
+                    },
+                    Command::Flush(done) => {
+                        let _ = done.send(());
+                    }
+                }
+            }
+        });
+
+        Self {
+            storage,
+            sender,
+            write_failures,
+            retention: chrono::Duration::days(retention_days),
+        }
+    }
+
+    /// Queue an entry; never waits for the write
+    pub fn record(&self, entry: AuditEntry) {
+        if let Err(e) = self.sender.try_send(Command::Write(entry)) {
+            self.write_failures.fetch_add(1, Ordering::Relaxed);
+            warn!("Dropping audit entry: {}", e);
+        }
+    }
+
+    /// Wait until every entry queued so far has been written or has failed
+    pub async fn flush(&self) {
+        let (done, written) = oneshot::channel();
+        if self.sender.send(Command::Flush(done)).await.is_ok() {
+            let _ = written.await;
+        }
+    }
+
+    pub fn write_failures(&self) -> u64 {
+        self.write_failures.load(Ordering::Relaxed)
+    }
+
+    /// Newest first
+    pub async fn query(&self, filter: &AuditFilter) -> Result<Vec<AuditEntry>> {
+        self.storage.get_audit_entries(filter).await
+    }
+
+    /// Delete entries older than the audit retention, which is independent of
+    /// the general data retention
+    pub async fn enforce_retention(&self) -> Result<usize> {
+        self.storage.prune_audit_entries(Utc::now() - self.retention).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{SqliteStorage, StorageConfig};
+
+    async fn trail() -> AuditTrail {
+        let storage = SqliteStorage::new(&StorageConfig {
+            database_url: "sqlite::memory:".to_string(),
+            max_connections: 1,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        AuditTrail::new(Arc::new(storage), 30)
+    }
+
+    #[tokio::test]
+    async fn test_entries_are_filtered_by_actor_action_and_date() {
+        let trail = trail().await;
+        let mut old = AuditEntry::new("alice", "plugin.uninstall", "weather");
+        old.created_at = Utc::now() - chrono::Duration::days(40);
+        trail.record(old);
+        trail.record(AuditEntry::new("alice", "plugin.install", "weather").after(serde_json::json!({"version": "1.2.0"})));
+        trail.record(AuditEntry::new("bob", "plugin.uninstall", "notes").before(serde_json::json!({"version": "0.3.0"})));
+        trail.flush().await;
+
+        let by_action = trail
+            .query(&AuditFilter { action: Some("plugin.uninstall".to_string()), ..Default::default() })
+            .await
+            .unwrap();
+        assert_eq!(by_action.iter().map(|e| e.actor.as_str()).collect::<Vec<_>>(), vec!["bob", "alice"]);
+        assert_eq!(by_action[0].before, Some(serde_json::json!({"version": "0.3.0"})));
+
+        let recent_alice = trail
+            .query(&AuditFilter {
+                actor: Some("alice".to_string()),
+                from: Some(Utc::now() - chrono::Duration::days(1)),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(recent_alice.len(), 1);
+        assert_eq!(recent_alice[0].action, "plugin.install");
+
+        // Only the 40 day old entry is past the 30 day retention
+        assert_eq!(trail.enforce_retention().await.unwrap(), 1);
+        assert_eq!(trail.query(&AuditFilter::default()).await.unwrap().len(), 2);
+        assert_eq!(trail.write_failures(), 0);
+    }
+}
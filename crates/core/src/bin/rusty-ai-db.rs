@@ -0,0 +1,69 @@
+// Offline database maintenance: `rusty-ai-db backup <path>` / `rusty-ai-db restore <path>`.
+// Stop the API server before running a restore.
+use rusty_ai_core::database::{DatabaseConfig, DatabaseManager};
+use std::path::PathBuf;
+
+fn usage() -> ! {
+    eprintln!("usage: rusty-ai-db [--database-url <url>] <backup|restore> <path>");
+    std::process::exit(2);
+}
+
+#[tokio::main]
+async fn main() {
+    let mut config = DatabaseConfig::default();
+    if let Ok(url) = std::env::var("DATABASE_URL") {
+        config.database_url = url;
+    }
+
+    let mut args = std::env::args().skip(1).collect::<Vec<_>>();
+    if args.first().map(String::as_str) == Some("--database-url") {
+        if args.len() < 2 {
+            usage();
+        }
+        config.database_url = args.remove(1);
+        args.remove(0);
+    }
+
+    let (command, path) = match args.as_slice() {
+        [command, path] => (command.as_str(), PathBuf::from(path)),
+        _ => usage(),
+    };
+
+    // A restore must work even when the live database is corrupt,
+    // so it does not open (or migrate) the live file up front
+    let opened = if command == "restore" {
+        DatabaseManager::new_unchecked(config)
+    } else {
+        DatabaseManager::new(config).await
+    };
+
+    let manager = match opened {
+        Ok(manager) => manager,
+        Err(e) => {
+            eprintln!("error: failed to open database: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let result = match command {
+        "backup" => manager.backup(&path).await.map(|manifest| {
+            println!("Backup written to {}", path.display());
+            for (table, rows) in &manifest.table_row_counts {
+                println!("  {:<32} {:>10}", table, rows);
+            }
+        }),
+        "restore" => manager.restore(&path).await.map(|(manager, summary)| {
+            println!("{}", summary);
+            if !summary.is_consistent() {
+                eprintln!("warning: restored row counts differ from the backup manifest");
+            }
+            drop(manager);
+        }),
+        _ => usage(),
+    };
+
+    if let Err(e) = result {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
+}
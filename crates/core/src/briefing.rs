@@ -1,13 +1,22 @@
-use rusty_ai_common::{Result, AssistantError, DailyBriefing, BriefingSection, BriefingPriority, Document, Task, TaskStatus, UserContext};
-use std::sync::Arc;
+use rusty_ai_common::{
+    Result, AssistantError, DailyBriefing, BriefingSection, BriefingPriority, Document, GenerationReport,
+    NotificationCategory, SectionOutcome, SectionStatus, Task, TaskStatus, UserContext, UserPreferences,
+};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use uuid::Uuid;
-use chrono::{DateTime, Utc, TimeZone};
-use tracing::{info, debug, error};
+use chrono::{DateTime, NaiveDate, Utc, TimeZone};
+use tracing::{info, debug, warn, error};
+use super::notifications::{Notification, NotificationRouter};
 use super::storage::Storage;
 
 pub struct BriefingGenerator {
     storage: Arc<dyn Storage + Send + Sync>,
     config: BriefingConfig,
+    // The latest briefing whose store failed, kept for the scheduler to retry
+    pending: Mutex<Option<DailyBriefing>>,
+    // The day a failed scheduled generation was last alerted for
+    alerted_on: Mutex<Option<NaiveDate>>,
 }
 
 #[derive(Debug, Clone)]
@@ -18,6 +27,11 @@ pub struct BriefingConfig {
     pub include_completed_tasks: bool,
     pub task_lookback_days: i64,
     pub document_lookback_days: i64,
+    /// Extra attempts for a section whose storage lookups failed
+    pub section_retries: u32,
+    /// Extra attempts to store the finished briefing, with doubling delays
+    pub store_retries: u32,
+    pub store_backoff: Duration,
 }
 
 impl Default for BriefingConfig {
@@ -29,53 +43,71 @@ impl Default for BriefingConfig {
             include_completed_tasks: true,
             task_lookback_days: 7,
             document_lookback_days: 30,
+            section_retries: 1,
+            store_retries: 3,
+            store_backoff: Duration::from_millis(500),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum SectionKind {
+    Tasks,
+    Documents,
+    Priority,
+    Upcoming,
+    Insights,
+}
+
+impl SectionKind {
+    const ALL: [SectionKind; 5] = [
+        SectionKind::Tasks,
+        SectionKind::Documents,
+        SectionKind::Priority,
+        SectionKind::Upcoming,
+        SectionKind::Insights,
+    ];
+
+    fn title(self) -> &'static str {
+        match self {
+            SectionKind::Tasks => "Task Overview",
+            SectionKind::Documents => "Recent Documents",
+            SectionKind::Priority => "Priority Items",
+            SectionKind::Upcoming => "Upcoming Items",
+            SectionKind::Insights => "Knowledge Insights",
         }
     }
 }
 
 impl BriefingGenerator {
     pub fn new(storage: Arc<dyn Storage + Send + Sync>) -> Self {
-        Self {
-            storage,
-            config: BriefingConfig::default(),
-        }
+        Self::new_with_config(storage, BriefingConfig::default())
     }
 
     pub fn new_with_config(storage: Arc<dyn Storage + Send + Sync>, config: BriefingConfig) -> Self {
         Self {
             storage,
             config,
+            pending: Mutex::new(None),
+            alerted_on: Mutex::new(None),
         }
     }
 
-    pub async fn generate_daily_briefing(&self, date: DateTime<Utc>, user_context: &UserContext) -> Result<DailyBriefing> {
+    pub async fn generate_daily_briefing(&self, date: DateTime<Utc>, _user_context: &UserContext) -> Result<DailyBriefing> {
+        self.generate(date).await
+    }
+
+    // Sections that fail are retried and then left out, so a briefing is
+    // produced as long as it can be stored; the report says what is missing
+    async fn generate(&self, date: DateTime<Utc>) -> Result<DailyBriefing> {
         info!("Generating daily briefing for {}", date.format("%Y-%m-%d"));
 
         let mut sections = Vec::new();
-
-        // Add task overview section
-        if let Ok(task_section) = self.generate_task_section(date).await {
-            sections.push(task_section);
-        }
-
-        // Add recent documents section
-        if let Ok(docs_section) = self.generate_documents_section(date).await {
-            sections.push(docs_section);
-        }
-
-        // Add priority items section
-        if let Ok(priority_section) = self.generate_priority_section(date).await {
-            sections.push(priority_section);
-        }
-
-        // Add upcoming items section
-        if let Ok(upcoming_section) = self.generate_upcoming_section(date).await {
-            sections.push(upcoming_section);
-        }
-
-        // Add knowledge insights section
-        if let Ok(insights_section) = self.generate_insights_section(date).await {
-            sections.push(insights_section);
+        let mut report = GenerationReport::default();
+        for kind in SectionKind::ALL {
+            let (section, outcome) = self.run_section(kind, date).await;
+            sections.extend(section);
+            report.sections.push(outcome);
         }
 
         // Sort sections by priority
@@ -86,21 +118,152 @@ impl BriefingGenerator {
             sections.truncate(self.config.max_sections);
         }
 
-        let briefing = DailyBriefing {
+        let mut briefing = DailyBriefing {
             id: Uuid::new_v4(),
             date,
             sections,
             generated_at: Utc::now(),
+            generation_report: Some(report),
         };
 
-        // Store the briefing
-        self.storage.store_briefing(&briefing).await?;
+        if let Err(e) = self.store_with_retry(&mut briefing).await {
+            error!("Keeping briefing {} in memory after failing to store it: {}", briefing.id, e);
+            *self.pending.lock().unwrap() = Some(briefing);
+            return Err(e);
+        }
 
         info!("Generated daily briefing with {} sections", briefing.sections.len());
         Ok(briefing)
     }
 
-    async fn generate_task_section(&self, date: DateTime<Utc>) -> Result<BriefingSection> {
+    async fn run_section(&self, kind: SectionKind, date: DateTime<Utc>) -> (Option<BriefingSection>, SectionOutcome) {
+        let mut attempts = 0;
+        let (section, status) = loop {
+            attempts += 1;
+            let result = match kind {
+                SectionKind::Tasks => self.generate_task_section(date).await,
+                SectionKind::Documents => self.generate_documents_section(date).await,
+                SectionKind::Priority => self.generate_priority_section(date).await,
+                SectionKind::Upcoming => self.generate_upcoming_section(date).await,
+                SectionKind::Insights => self.generate_insights_section(date).await,
+            };
+            match result {
+                Ok(Some(section)) => break (Some(section), SectionStatus::Generated),
+                Ok(None) => break (None, SectionStatus::SkippedEmpty),
+                Err(e) if attempts <= self.config.section_retries => {
+                    warn!("Briefing section '{}' failed, retrying: {}", kind.title(), e);
+                }
+                Err(e) => {
+                    error!("Briefing section '{}' failed after {} attempts: {}", kind.title(), attempts, e);
+                    break (None, SectionStatus::Failed { error: e.to_string() });
+                }
+            }
+        };
+
+        let outcome = SectionOutcome {
+            section: kind.title().to_string(),
+            status,
+            attempts,
+        };
+        (section, outcome)
+    }
+
+    async fn store_with_retry(&self, briefing: &mut DailyBriefing) -> Result<()> {
+        let mut delay = self.config.store_backoff;
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            if let Some(report) = briefing.generation_report.as_mut() {
+                report.store_attempts = attempts;
+            }
+            match self.storage.store_briefing(briefing).await {
+                Ok(()) => return Ok(()),
+                Err(e) if attempts <= self.config.store_retries => {
+                    warn!("Failed to store briefing {} (attempt {}), retrying in {:?}: {}", briefing.id, attempts, delay, e);
+                    tokio::time::sleep(delay).await;
+                    delay *= 2;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// The briefing waiting to be stored, if the last store failed
+    pub fn pending_briefing(&self) -> Option<DailyBriefing> {
+        self.pending.lock().unwrap().clone()
+    }
+
+    /// Try again to store the briefing kept after a failed store
+    pub async fn retry_pending(&self) -> Result<Option<DailyBriefing>> {
+        let Some(mut briefing) = self.pending.lock().unwrap().take() else {
+            return Ok(None);
+        };
+
+        match self.store_with_retry(&mut briefing).await {
+            Ok(()) => {
+                info!("Stored briefing {} on retry", briefing.id);
+                Ok(Some(briefing))
+            }
+            Err(e) => {
+                // A briefing generated in the meantime supersedes this one
+                self.pending.lock().unwrap().get_or_insert(briefing);
+                Err(e)
+            }
+        }
+    }
+
+    /// Called periodically by the server: finishes storing a kept briefing,
+    /// or generates the day's briefing if none is stored yet. When that
+    /// fails every user is alerted, at most once per day
+    pub async fn run_scheduled(
+        &self,
+        now: DateTime<Utc>,
+        users: &[(Uuid, UserPreferences)],
+        router: &NotificationRouter,
+    ) -> Result<Option<DailyBriefing>> {
+        let result = self.scheduled_briefing(now).await;
+        if let Err(e) = &result {
+            self.alert_failure(now, users, router, e).await;
+        }
+        result
+    }
+
+    async fn scheduled_briefing(&self, now: DateTime<Utc>) -> Result<Option<DailyBriefing>> {
+        if self.pending.lock().unwrap().is_some() {
+            return self.retry_pending().await;
+        }
+
+        let latest = self.storage.get_latest_briefing().await?;
+        if latest.map_or(false, |briefing| briefing.date.date_naive() == now.date_naive()) {
+            return Ok(None);
+        }
+        self.generate(now).await.map(Some)
+    }
+
+    async fn alert_failure(&self, now: DateTime<Utc>, users: &[(Uuid, UserPreferences)], router: &NotificationRouter, e: &AssistantError) {
+        let today = now.date_naive();
+        {
+            let mut alerted_on = self.alerted_on.lock().unwrap();
+            if *alerted_on == Some(today) {
+                return;
+            }
+            *alerted_on = Some(today);
+        }
+
+        for (user_id, preferences) in users {
+            let notification = Notification::new(
+                *user_id,
+                NotificationCategory::Briefing,
+                "Your daily briefing could not be generated",
+                format!("It will be retried automatically. Error: {}", e),
+            );
+            if let Err(e) = router.route(preferences, notification).await {
+                warn!("Failed to route briefing failure alert: {}", e);
+            }
+        }
+    }
+
+    async fn generate_task_section(&self, date: DateTime<Utc>) -> Result<Option<BriefingSection>> {
         let start_date = date - chrono::Duration::days(self.config.task_lookback_days);
         
         // Get pending tasks
@@ -116,7 +279,15 @@ impl BriefingGenerator {
             Vec::new()
         };
 
-        let content = self.format_task_overview(&pending_tasks, &completed_tasks);
+        // Time tracked on the previous UTC day, by everyone the briefing is for
+        let day_start = Utc.from_utc_datetime(&date.date_naive().and_hms_opt(0, 0, 0).unwrap_or_default());
+        let yesterday = day_start - chrono::Duration::days(1);
+        let tracked_yesterday: i64 = self.storage.get_time_entries_between(None, yesterday, day_start).await?
+            .iter()
+            .map(|entry| entry.seconds_within(yesterday, day_start, date))
+            .sum();
+
+        let content = self.format_task_overview(&pending_tasks, &completed_tasks, tracked_yesterday);
         let priority = if pending_tasks.is_empty() {
             BriefingPriority::Low
         } else if pending_tasks.len() > 10 {
@@ -129,15 +300,15 @@ impl BriefingGenerator {
             .map(|_| Uuid::new_v4()) // Placeholder - would link to task documents
             .collect();
 
-        Ok(BriefingSection {
+        Ok(Some(BriefingSection {
             title: "Task Overview".to_string(),
             content,
             priority,
             source_documents,
-        })
+        }))
     }
 
-    async fn generate_documents_section(&self, date: DateTime<Utc>) -> Result<BriefingSection> {
+    async fn generate_documents_section(&self, date: DateTime<Utc>) -> Result<Option<BriefingSection>> {
         let start_date = date - chrono::Duration::days(self.config.document_lookback_days);
         
         // Search for recent documents
@@ -149,7 +320,7 @@ impl BriefingGenerator {
             .collect::<Vec<_>>();
 
         if recent_docs.is_empty() {
-            return Err(AssistantError::NotFound("No recent documents found".to_string()));
+            return Ok(None);
         }
 
         let content = self.format_documents_overview(&recent_docs);
@@ -161,15 +332,15 @@ impl BriefingGenerator {
 
         let source_documents = recent_docs.iter().map(|doc| doc.id).collect();
 
-        Ok(BriefingSection {
-            title: "Recent Documents".to_string(),
+        Ok(Some(BriefingSection {
+            title: SectionKind::Documents.title().to_string(),
             content,
             priority,
             source_documents,
-        })
+        }))
     }
 
-    async fn generate_priority_section(&self, _date: DateTime<Utc>) -> Result<BriefingSection> {
+    async fn generate_priority_section(&self, _date: DateTime<Utc>) -> Result<Option<BriefingSection>> {
         // Get high-priority pending tasks
         let high_priority_tasks = self.storage.get_tasks_by_status(TaskStatus::Pending).await?
             .into_iter()
@@ -177,7 +348,7 @@ impl BriefingGenerator {
             .collect::<Vec<_>>();
 
         if high_priority_tasks.is_empty() {
-            return Err(AssistantError::NotFound("No high-priority items found".to_string()));
+            return Ok(None);
         }
 
         let content = self.format_priority_items(&high_priority_tasks);
@@ -187,15 +358,15 @@ impl BriefingGenerator {
             .map(|_| Uuid::new_v4()) // Placeholder
             .collect();
 
-        Ok(BriefingSection {
-            title: "Priority Items".to_string(),
+        Ok(Some(BriefingSection {
+            title: SectionKind::Priority.title().to_string(),
             content,
             priority,
             source_documents,
-        })
+        }))
     }
 
-    async fn generate_upcoming_section(&self, date: DateTime<Utc>) -> Result<BriefingSection> {
+    async fn generate_upcoming_section(&self, date: DateTime<Utc>) -> Result<Option<BriefingSection>> {
         let end_date = date + chrono::Duration::days(7);
         
         let upcoming_tasks = self.storage.get_tasks_by_status(TaskStatus::Pending).await?
@@ -206,7 +377,7 @@ impl BriefingGenerator {
             .collect::<Vec<_>>();
 
         if upcoming_tasks.is_empty() {
-            return Err(AssistantError::NotFound("No upcoming items found".to_string()));
+            return Ok(None);
         }
 
         let content = self.format_upcoming_items(&upcoming_tasks);
@@ -216,15 +387,15 @@ impl BriefingGenerator {
             .map(|_| Uuid::new_v4()) // Placeholder
             .collect();
 
-        Ok(BriefingSection {
-            title: "Upcoming Items".to_string(),
+        Ok(Some(BriefingSection {
+            title: SectionKind::Upcoming.title().to_string(),
             content,
             priority,
             source_documents,
-        })
+        }))
     }
 
-    async fn generate_insights_section(&self, date: DateTime<Utc>) -> Result<BriefingSection> {
+    async fn generate_insights_section(&self, date: DateTime<Utc>) -> Result<Option<BriefingSection>> {
         let start_date = date - chrono::Duration::days(7);
         
         // Get recent documents for analysis
@@ -234,7 +405,7 @@ impl BriefingGenerator {
             .collect::<Vec<_>>();
 
         if recent_docs.is_empty() {
-            return Err(AssistantError::NotFound("No recent documents for insights".to_string()));
+            return Ok(None);
         }
 
         let content = self.generate_knowledge_insights(&recent_docs);
@@ -242,15 +413,15 @@ impl BriefingGenerator {
 
         let source_documents = recent_docs.iter().map(|doc| doc.id).collect();
 
-        Ok(BriefingSection {
-            title: "Knowledge Insights".to_string(),
+        Ok(Some(BriefingSection {
+            title: SectionKind::Insights.title().to_string(),
             content,
             priority,
             source_documents,
-        })
+        }))
     }
 
-    fn format_task_overview(&self, pending_tasks: &[Task], completed_tasks: &[Task]) -> String {
+    fn format_task_overview(&self, pending_tasks: &[Task], completed_tasks: &[Task], tracked_yesterday: i64) -> String {
         let mut content = String::new();
 
         if !pending_tasks.is_empty() {
@@ -280,6 +451,13 @@ impl BriefingGenerator {
             }
         }
 
+        if tracked_yesterday >= 60 {
+            if !content.is_empty() && !content.ends_with("\n\n") {
+                content.push('\n');
+            }
+            content.push_str(&format!("**Tracked yesterday:** {}\n", crate::time_tracking::format_duration(tracked_yesterday)));
+        }
+
         content
     }
 
@@ -454,7 +632,9 @@ impl Ord for BriefingPriority {
 mod tests {
     use super::*;
     use std::sync::Arc;
-    use rusty_ai_common::{DocumentMetadata, UserPreferences, VoiceSettings, NotificationSettings};
+    use rusty_ai_common::{DocumentMetadata, NotificationChannel, UserPreferences, VoiceSettings, NotificationSettings};
+    use crate::test_support::RecordingSink;
+    use crate::retention::{CleanupReport, RetentionPolicy};
 
     // Mock storage for testing
     struct MockStorage;
@@ -476,7 +656,7 @@ mod tests {
         async fn get_briefing(&self, _id: Uuid) -> Result<Option<DailyBriefing>> { Ok(None) }
         async fn get_latest_briefing(&self) -> Result<Option<DailyBriefing>> { Ok(None) }
         async fn get_briefings_by_date_range(&self, _start: DateTime<Utc>, _end: DateTime<Utc>) -> Result<Vec<DailyBriefing>> { Ok(Vec::new()) }
-        async fn cleanup_old_data(&self, _retention_days: i64) -> Result<usize> { Ok(0) }
+        async fn cleanup_old_data(&self, _policy: &RetentionPolicy, dry_run: bool) -> Result<CleanupReport> { Ok(CleanupReport::new(dry_run)) }
         async fn health_check(&self) -> Result<super::storage::StorageHealth> { 
             Ok(super::storage::StorageHealth {
                 status: super::storage::StorageStatus::Healthy,
@@ -509,13 +689,233 @@ mod tests {
                     enabled: true,
                     channels: vec![],
                     quiet_hours: None,
+                    routing: Default::default(),
+                    knowledge_digest: Default::default(),
                 },
             },
             active_plugins: vec![],
-            conversation_history: vec![],
+            conversation_history: Default::default(),
         };
 
         let briefing = generator.generate_daily_briefing(Utc::now(), &user_context).await.unwrap();
         assert!(!briefing.sections.is_empty());
     }
-}
\ No newline at end of file
+
+    // Storage whose lookups fail a set number of times. Calls are keyed by
+    // what they read: the documents section searches 20 documents and the
+    // insights section 10
+    #[derive(Default)]
+    struct FlakyStorage {
+        tasks: Vec<Task>,
+        documents: Vec<Document>,
+        failures: Mutex<std::collections::HashMap<&'static str, u32>>,
+        briefings: Mutex<Vec<DailyBriefing>>,
+    }
+
+    impl FlakyStorage {
+        fn fail(&self, call: &'static str, times: u32) {
+            self.failures.lock().unwrap().insert(call, times);
+        }
+
+        fn check(&self, call: &'static str) -> Result<()> {
+            let mut failures = self.failures.lock().unwrap();
+            match failures.get_mut(call) {
+                Some(remaining) if *remaining > 0 => {
+                    *remaining -= 1;
+                    Err(AssistantError::Database(format!("injected failure in {}", call)))
+                }
+                _ => Ok(()),
+            }
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl Storage for FlakyStorage {
+        async fn store_document(&self, _document: &Document) -> Result<()> { Ok(()) }
+        async fn get_document(&self, _id: Uuid) -> Result<Option<Document>> { Ok(None) }
+        async fn update_document(&self, _document: &Document) -> Result<()> { Ok(()) }
+        async fn delete_document(&self, _id: Uuid) -> Result<()> { Ok(()) }
+        async fn search_documents(&self, _query: &str, limit: usize) -> Result<Vec<Document>> {
+            self.check(if limit == 20 { "recent_documents" } else { "insight_documents" })?;
+            Ok(self.documents.clone())
+        }
+        async fn get_documents_by_tags(&self, _tags: &[String], _limit: usize) -> Result<Vec<Document>> { Ok(Vec::new()) }
+        async fn store_task(&self, _task: &Task) -> Result<()> { Ok(()) }
+        async fn get_task(&self, _id: Uuid) -> Result<Option<Task>> { Ok(None) }
+        async fn update_task_status(&self, _id: Uuid, _status: TaskStatus) -> Result<()> { Ok(()) }
+        async fn get_pending_tasks(&self) -> Result<Vec<Task>> { Ok(Vec::new()) }
+        async fn get_tasks_by_status(&self, status: TaskStatus) -> Result<Vec<Task>> {
+            self.check(if status == TaskStatus::Pending { "pending_tasks" } else { "other_tasks" })?;
+            Ok(self.tasks.iter().filter(|task| task.status == status).cloned().collect())
+        }
+        async fn store_briefing(&self, briefing: &DailyBriefing) -> Result<()> {
+            self.check("store_briefing")?;
+            self.briefings.lock().unwrap().push(briefing.clone());
+            Ok(())
+        }
+        async fn get_briefing(&self, _id: Uuid) -> Result<Option<DailyBriefing>> { Ok(None) }
+        async fn get_latest_briefing(&self) -> Result<Option<DailyBriefing>> {
+            Ok(self.briefings.lock().unwrap().iter().max_by_key(|b| b.date).cloned())
+        }
+        async fn get_briefings_by_date_range(&self, _start: DateTime<Utc>, _end: DateTime<Utc>) -> Result<Vec<DailyBriefing>> { Ok(Vec::new()) }
+        async fn cleanup_old_data(&self, _policy: &RetentionPolicy, dry_run: bool) -> Result<CleanupReport> { Ok(CleanupReport::new(dry_run)) }
+        async fn health_check(&self) -> Result<super::storage::StorageHealth> {
+            Ok(super::storage::StorageHealth {
+                status: super::storage::StorageStatus::Healthy,
+                connection_pool_size: None,
+                pending_migrations: None,
+                disk_usage_mb: None,
+                last_backup: None,
+            })
+        }
+    }
+
+    // One medium-priority task without a due date and one recent document
+    fn seeded_storage() -> FlakyStorage {
+        let now = Utc::now();
+        FlakyStorage {
+            tasks: vec![Task {
+                id: Uuid::new_v4(),
+                name: "Renew passport".to_string(),
+                description: String::new(),
+                status: TaskStatus::Pending,
+                priority: rusty_ai_common::TaskPriority::Medium,
+                due_date: None,
+                tags: vec![],
+                created_at: now,
+                updated_at: now,
+            }],
+            documents: vec![Document {
+                id: Uuid::new_v4(),
+                title: "Travel policy".to_string(),
+                content: "Per diem and booking rules".to_string(),
+                metadata: DocumentMetadata {
+                    source: "upload".to_string(),
+                    file_type: "text".to_string(),
+                    tags: vec!["travel".to_string()],
+                    summary: None,
+                    importance_score: 0.5,
+                    embeddings: None,
+                },
+                created_at: now - chrono::Duration::days(1),
+                updated_at: now - chrono::Duration::days(1),
+            }],
+            ..Default::default()
+        }
+    }
+
+    fn quick_retries() -> BriefingConfig {
+        BriefingConfig {
+            store_retries: 2,
+            store_backoff: Duration::from_millis(1),
+            ..Default::default()
+        }
+    }
+
+    fn preferences() -> UserPreferences {
+        UserPreferences {
+            language: "en".to_string(),
+            timezone: "UTC".to_string(),
+            voice_settings: VoiceSettings {
+                enabled: false,
+                voice_id: "default".to_string(),
+                speed: 1.0,
+                pitch: 1.0,
+            },
+            notification_settings: NotificationSettings {
+                enabled: true,
+                channels: vec![NotificationChannel::Email],
+                quiet_hours: None,
+                routing: Default::default(),
+                knowledge_digest: Default::default(),
+            },
+        }
+    }
+
+    fn status_of<'a>(report: &'a GenerationReport, section: &str) -> (&'a SectionStatus, u32) {
+        let outcome = report.sections.iter().find(|o| o.section == section).unwrap();
+        (&outcome.status, outcome.attempts)
+    }
+
+    #[tokio::test]
+    async fn test_section_failures_are_retried_and_reported() {
+        let storage = Arc::new(seeded_storage());
+        // Documents recover on the retry, insights never do
+        storage.fail("recent_documents", 1);
+        storage.fail("insight_documents", u32::MAX);
+        let generator = BriefingGenerator::new_with_config(storage.clone(), quick_retries());
+
+        let briefing = generator.generate(Utc::now()).await.unwrap();
+        let report = briefing.generation_report.clone().unwrap();
+
+        assert_eq!(status_of(&report, "Task Overview"), (&SectionStatus::Generated, 1));
+        assert_eq!(status_of(&report, "Recent Documents"), (&SectionStatus::Generated, 2));
+        assert_eq!(status_of(&report, "Priority Items"), (&SectionStatus::SkippedEmpty, 1));
+        assert_eq!(status_of(&report, "Upcoming Items"), (&SectionStatus::SkippedEmpty, 1));
+        match status_of(&report, "Knowledge Insights") {
+            (SectionStatus::Failed { error }, 2) => assert!(error.contains("injected failure in insight_documents")),
+            other => panic!("unexpected insights outcome: {:?}", other),
+        }
+        assert_eq!(report.failed_sections().count(), 1);
+        assert_eq!(report.store_attempts, 1);
+
+        let mut titles: Vec<&str> = briefing.sections.iter().map(|s| s.title.as_str()).collect();
+        titles.sort();
+        assert_eq!(titles, vec!["Recent Documents", "Task Overview"]);
+
+        // The report is stored with the briefing
+        let stored = storage.briefings.lock().unwrap()[0].clone();
+        assert_eq!(stored.generation_report, Some(report));
+    }
+
+    #[tokio::test]
+    async fn test_failing_task_lookups_fail_every_task_section() {
+        let storage = Arc::new(seeded_storage());
+        storage.fail("pending_tasks", u32::MAX);
+        let generator = BriefingGenerator::new_with_config(storage.clone(), quick_retries());
+
+        let briefing = generator.generate(Utc::now()).await.unwrap();
+        let report = briefing.generation_report.unwrap();
+
+        let failed: Vec<&str> = report.failed_sections().map(|o| o.section.as_str()).collect();
+        assert_eq!(failed, vec!["Task Overview", "Priority Items", "Upcoming Items"]);
+        assert_eq!(status_of(&report, "Knowledge Insights"), (&SectionStatus::Generated, 1));
+        assert_eq!(briefing.sections.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_failed_store_is_kept_for_the_scheduler_and_alerted_once() {
+        let storage = Arc::new(seeded_storage());
+        let generator = BriefingGenerator::new_with_config(storage.clone(), quick_retries());
+        let sink = Arc::new(RecordingSink::default());
+        let router = NotificationRouter::new(sink.clone(), None);
+        let users = vec![(Uuid::new_v4(), preferences())];
+
+        // Two failures are absorbed by the retries
+        storage.fail("store_briefing", 2);
+        let briefing = generator.generate(Utc::now() - chrono::Duration::days(1)).await.unwrap();
+        assert_eq!(briefing.generation_report.unwrap().store_attempts, 3);
+        assert!(generator.pending_briefing().is_none());
+
+        // Three attempts per run: the first run fails and alerts, the second
+        // fails quietly, the third stores the briefing kept in memory
+        storage.fail("store_briefing", 7);
+        let now = Utc::now();
+        assert!(generator.run_scheduled(now, &users, &router).await.is_err());
+        let kept = generator.pending_briefing().unwrap();
+        assert_eq!(kept.generation_report.as_ref().unwrap().failed_sections().count(), 0);
+
+        assert!(generator.run_scheduled(now, &users, &router).await.is_err());
+        assert_eq!(sink.count(), 1);
+        assert_eq!(sink.notifications()[0].category, NotificationCategory::Briefing);
+
+        let stored = generator.run_scheduled(now, &users, &router).await.unwrap().unwrap();
+        assert_eq!(stored.id, kept.id);
+        assert_eq!(stored.generation_report.unwrap().store_attempts, 2);
+        assert!(generator.pending_briefing().is_none());
+        assert_eq!(storage.briefings.lock().unwrap().len(), 2);
+
+        // Today's briefing exists, so the scheduler has nothing to do
+        assert!(generator.run_scheduled(now, &users, &router).await.unwrap().is_none());
+    }
+}
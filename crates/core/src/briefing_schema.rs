@@ -0,0 +1,233 @@
+use rusty_ai_common::{BriefingPriority, BriefingSection, GenerationReport};
+use serde::{Deserialize, Deserializer, Serialize};
+use uuid::Uuid;
+
+/// Version written by `store_briefing`; rows carrying an older version are
+/// rewritten the first time they are read
+pub const CURRENT_BRIEFING_SCHEMA: i64 = 2;
+
+/// Rows written before the `schema_version` column existed
+pub const LEGACY_BRIEFING_SCHEMA: i64 = 1;
+
+// Version 1: the sections column held the bare `Vec<BriefingSection>` array
+#[derive(Debug, Deserialize)]
+pub struct BriefingSectionV1 {
+    pub title: String,
+    pub content: String,
+    pub priority: BriefingPriority,
+    #[serde(default)]
+    pub source_documents: Vec<Uuid>,
+}
+
+// Version 2: the sections are wrapped in an object so briefing-level fields
+// can be added later without another format change. Everything except the
+// section title is optional so rows from newer builds still read
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StoredBriefingV2 {
+    pub sections: Vec<BriefingSectionV2>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub generation_report: Option<GenerationReport>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BriefingSectionV2 {
+    pub title: String,
+    #[serde(default)]
+    pub content: String,
+    #[serde(default = "default_priority", deserialize_with = "lenient_priority")]
+    pub priority: BriefingPriority,
+    #[serde(default)]
+    pub source_documents: Vec<Uuid>,
+}
+
+fn default_priority() -> BriefingPriority {
+    BriefingPriority::Medium
+}
+
+// A priority this build does not know is read as Medium
+fn lenient_priority<'de, D: Deserializer<'de>>(deserializer: D) -> std::result::Result<BriefingPriority, D::Error> {
+    let value = serde_json::Value::deserialize(deserializer)?;
+    Ok(serde_json::from_value(value).unwrap_or_else(|_| default_priority()))
+}
+
+impl From<BriefingSectionV1> for BriefingSectionV2 {
+    fn from(section: BriefingSectionV1) -> Self {
+        Self {
+            title: section.title,
+            content: section.content,
+            priority: section.priority,
+            source_documents: section.source_documents,
+        }
+    }
+}
+
+impl From<Vec<BriefingSectionV1>> for StoredBriefingV2 {
+    fn from(sections: Vec<BriefingSectionV1>) -> Self {
+        Self {
+            sections: sections.into_iter().map(Into::into).collect(),
+            generation_report: None,
+        }
+    }
+}
+
+impl From<BriefingSectionV2> for BriefingSection {
+    fn from(section: BriefingSectionV2) -> Self {
+        Self {
+            title: section.title,
+            content: section.content,
+            priority: section.priority,
+            source_documents: section.source_documents,
+        }
+    }
+}
+
+impl From<&BriefingSection> for BriefingSectionV2 {
+    fn from(section: &BriefingSection) -> Self {
+        Self {
+            title: section.title.clone(),
+            content: section.content.clone(),
+            priority: section.priority.clone(),
+            source_documents: section.source_documents.clone(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct DecodedSections {
+    pub sections: Vec<BriefingSection>,
+    pub generation_report: Option<GenerationReport>,
+    /// The version the JSON actually parsed as, which can differ from the
+    /// row's declared version if an older build overwrote it
+    pub version: i64,
+}
+
+impl DecodedSections {
+    pub fn needs_upgrade(&self, declared_version: i64) -> bool {
+        self.version != CURRENT_BRIEFING_SCHEMA || declared_version != CURRENT_BRIEFING_SCHEMA
+    }
+}
+
+/// Serialize sections in the current schema version
+pub fn encode_sections(sections: &[BriefingSection]) -> serde_json::Result<String> {
+    encode_briefing(sections, None)
+}
+
+/// Serialize sections and the generation report in the current schema version
+pub fn encode_briefing(sections: &[BriefingSection], report: Option<&GenerationReport>) -> serde_json::Result<String> {
+    serde_json::to_string(&StoredBriefingV2 {
+        sections: sections.iter().map(Into::into).collect(),
+        generation_report: report.cloned(),
+    })
+}
+
+/// Decode a stored sections column, trying the declared version first and
+/// then every other known version. The error lists each version's failure
+pub fn decode_sections(declared_version: i64, json: &str) -> std::result::Result<DecodedSections, String> {
+    let mut versions = vec![declared_version];
+    versions.extend((LEGACY_BRIEFING_SCHEMA..=CURRENT_BRIEFING_SCHEMA).rev().filter(|v| *v != declared_version));
+
+    let mut errors = Vec::new();
+    for version in versions {
+        match decode_version(version, json) {
+            Ok(Some(stored)) => {
+                return Ok(DecodedSections {
+                    sections: stored.sections.into_iter().map(Into::into).collect(),
+                    generation_report: stored.generation_report,
+                    version,
+                })
+            }
+            Ok(None) => {}
+            Err(e) => errors.push(format!("v{}: {}", version, e)),
+        }
+    }
+
+    if errors.is_empty() {
+        errors.push(format!("unknown schema version {}", declared_version));
+    }
+    Err(errors.join("; "))
+}
+
+// Ok(None) for a version this build does not know
+fn decode_version(version: i64, json: &str) -> serde_json::Result<Option<StoredBriefingV2>> {
+    match version {
+        1 => serde_json::from_str::<Vec<BriefingSectionV1>>(json).map(|v1| Some(v1.into())),
+        2 => serde_json::from_str::<StoredBriefingV2>(json).map(Some),
+        _ => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const V1_FIXTURE: &str = r#"[
+        {"title": "Tasks", "content": "3 pending tasks", "priority": "High", "source_documents": []},
+        {"title": "Documents", "content": "2 new documents", "priority": "Low",
+         "source_documents": ["6f1c1b2e-3c4d-4e5f-8a9b-0c1d2e3f4a5b"]}
+    ]"#;
+
+    #[test]
+    fn test_v1_rows_convert_to_current_model() {
+        let decoded = decode_sections(LEGACY_BRIEFING_SCHEMA, V1_FIXTURE).unwrap();
+        assert_eq!(decoded.version, 1);
+        assert!(decoded.needs_upgrade(LEGACY_BRIEFING_SCHEMA));
+        assert_eq!(decoded.sections.len(), 2);
+        assert_eq!(decoded.sections[0].priority, BriefingPriority::High);
+        assert_eq!(decoded.sections[1].source_documents.len(), 1);
+
+        // The rewritten form reads back as the current version
+        let upgraded = encode_sections(&decoded.sections).unwrap();
+        let reread = decode_sections(CURRENT_BRIEFING_SCHEMA, &upgraded).unwrap();
+        assert!(!reread.needs_upgrade(CURRENT_BRIEFING_SCHEMA));
+        assert_eq!(reread.sections[1].title, "Documents");
+    }
+
+    #[test]
+    fn test_current_version_ignores_unknown_and_defaults_missing_fields() {
+        let json = r#"{"revision": 4, "sections": [
+            {"title": "Weather", "priority": "Informational", "provider": "met"},
+            {"title": "Calendar", "content": "Standup at 9"}
+        ]}"#;
+
+        let decoded = decode_sections(CURRENT_BRIEFING_SCHEMA, json).unwrap();
+        assert_eq!(decoded.sections[0].content, "");
+        assert_eq!(decoded.sections[0].priority, BriefingPriority::Medium);
+        assert!(decoded.sections[1].source_documents.is_empty());
+    }
+
+    #[test]
+    fn test_generation_report_round_trips() {
+        let report = GenerationReport {
+            sections: vec![rusty_ai_common::SectionOutcome {
+                section: "Priority Items".to_string(),
+                status: rusty_ai_common::SectionStatus::Failed { error: "database is locked".to_string() },
+                attempts: 2,
+            }],
+            store_attempts: 1,
+        };
+        let json = encode_briefing(&[], Some(&report)).unwrap();
+        assert!(json.contains(r#""status":"failed""#));
+
+        let decoded = decode_sections(CURRENT_BRIEFING_SCHEMA, &json).unwrap();
+        assert_eq!(decoded.generation_report, Some(report));
+        assert!(decode_sections(LEGACY_BRIEFING_SCHEMA, V1_FIXTURE).unwrap().generation_report.is_none());
+    }
+
+    #[test]
+    fn test_mislabelled_row_falls_back_to_matching_version() {
+        // An old build wrote a v1 array into a row already marked v2
+        let decoded = decode_sections(CURRENT_BRIEFING_SCHEMA, V1_FIXTURE).unwrap();
+        assert_eq!(decoded.version, 1);
+        assert!(decoded.needs_upgrade(CURRENT_BRIEFING_SCHEMA));
+    }
+
+    #[test]
+    fn test_corrupted_row_reports_every_version() {
+        let error = decode_sections(LEGACY_BRIEFING_SCHEMA, r#"{"sections": [{"content": 1"#).unwrap_err();
+        assert!(error.contains("v1:"));
+        assert!(error.contains("v2:"));
+
+        let error = decode_sections(9, "{}").unwrap_err();
+        assert!(error.contains("v2:"));
+    }
+}
@@ -1,13 +1,30 @@
-use rusty_ai_common::{UserContext, ConversationTurn, Intent, UserPreferences, Result, AssistantError};
+use rusty_ai_common::{UserContext, ConversationTurn, Intent, TurnKind, UserPreferences, Result, AssistantError};
+use rusty_ai_common::scratchpad::Scratchpads;
+use async_trait::async_trait;
 use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::RwLock;
 use uuid::Uuid;
 use chrono::{DateTime, Utc};
 use tracing::{info, debug, warn};
 
+use crate::resources::{ResourceReporter, ResourceUsage};
+use crate::storage::Storage;
+
+// Turns a session keeps in memory by default
+pub const DEFAULT_HISTORY_WINDOW: usize = 20;
+// Most turns `get_full_history` returns at once
+pub const MAX_HISTORY_PAGE: usize = 500;
+
 pub struct ContextManager {
     active_sessions: HashMap<Uuid, UserSession>,
-    max_conversation_length: usize,
+    // Most recent turns kept in each session's context; older turns are only
+    // in storage
+    history_window: usize,
     context_retention_hours: i64,
+    storage: Option<Arc<dyn Storage + Send + Sync>>,
+    // Working state of each session; it goes when the session does
+    scratchpads: Arc<Scratchpads>,
 }
 
 #[derive(Debug, Clone)]
@@ -17,26 +34,41 @@ pub struct UserSession {
     pub context: UserContext,
     pub created_at: DateTime<Utc>,
     pub last_activity: DateTime<Utc>,
-    pub conversation_turns: Vec<ConversationTurn>,
+    // Turns over the session's lifetime, including those no longer in memory
+    pub turn_count: usize,
 }
 
 impl ContextManager {
     pub fn new() -> Self {
-        Self {
-            active_sessions: HashMap::new(),
-            max_conversation_length: 100,
-            context_retention_hours: 24,
-        }
+        Self::new_with_config(DEFAULT_HISTORY_WINDOW, 24)
     }
 
-    pub fn new_with_config(max_conversation_length: usize, context_retention_hours: i64) -> Self {
+    pub fn new_with_config(history_window: usize, context_retention_hours: i64) -> Self {
         Self {
             active_sessions: HashMap::new(),
-            max_conversation_length,
+            history_window,
             context_retention_hours,
+            storage: None,
+            scratchpads: Arc::default(),
         }
     }
 
+    // Keeps every turn in storage, so the full history can be paged back
+    pub fn with_storage(mut self, storage: Arc<dyn Storage + Send + Sync>) -> Self {
+        self.storage = Some(storage);
+        self
+    }
+
+    // Shares the scratchpads with the handlers and plugins that use them
+    pub fn with_scratchpads(mut self, scratchpads: Arc<Scratchpads>) -> Self {
+        self.scratchpads = scratchpads;
+        self
+    }
+
+    pub fn scratchpads(&self) -> &Arc<Scratchpads> {
+        &self.scratchpads
+    }
+
     pub async fn create_session(&mut self, user_id: Uuid, preferences: UserPreferences) -> Result<Uuid> {
         let session_id = Uuid::new_v4();
         let now = Utc::now();
@@ -46,7 +78,7 @@ impl ContextManager {
             session_id,
             preferences,
             active_plugins: Vec::new(),
-            conversation_history: Vec::new(),
+            conversation_history: Default::default(),
         };
 
         let session = UserSession {
@@ -55,7 +87,7 @@ impl ContextManager {
             context,
             created_at: now,
             last_activity: now,
-            conversation_turns: Vec::new(),
+            turn_count: 0,
         };
 
         self.active_sessions.insert(session_id, session);
@@ -76,6 +108,7 @@ impl ContextManager {
             .ok_or_else(|| AssistantError::NotFound(format!("Session not found: {}", session_id)))
     }
 
+    // Cloning the context is cheap: clones share the conversation window
     pub async fn get_user_context(&self, session_id: Uuid) -> Result<&UserContext> {
         let session = self.get_session(session_id).await?;
         Ok(&session.context)
@@ -95,7 +128,7 @@ impl ContextManager {
         assistant_response: String,
         intent: Intent,
     ) -> Result<()> {
-        let session = self.get_session_mut(session_id).await?;
+        self.get_session(session_id).await?;
         
         let turn = ConversationTurn {
             id: Uuid::new_v4(),
@@ -103,20 +136,69 @@ impl ContextManager {
             assistant_response,
             intent,
             timestamp: Utc::now(),
+            kind: TurnKind::Message,
         };
+        self.push_turn(session_id, turn).await
+    }
 
-        session.conversation_turns.push(turn.clone());
-        session.context.conversation_history.push(turn);
-        session.last_activity = Utc::now();
+    // A command run from the command palette; `command_line` is what the
+    // user picked, e.g. `/task name="buy milk"`
+    pub async fn add_command_turn(
+        &mut self,
+        session_id: Uuid,
+        command_line: String,
+        assistant_response: String,
+        intent: Intent,
+    ) -> Result<()> {
+        self.get_session(session_id).await?;
 
-        // Trim conversation history if it exceeds max length
-        if session.conversation_turns.len() > self.max_conversation_length {
-            let excess = session.conversation_turns.len() - self.max_conversation_length;
-            session.conversation_turns.drain(0..excess);
-            session.context.conversation_history.drain(0..excess);
-            debug!("Trimmed {} old conversation turns from session {}", excess, session_id);
+        let turn = ConversationTurn {
+            id: Uuid::new_v4(),
+            user_input: command_line,
+            assistant_response,
+            intent,
+            timestamp: Utc::now(),
+            kind: TurnKind::Command,
+        };
+        self.push_turn(session_id, turn).await
+    }
+
+    // An assistant message that opens the session, stored as a turn without
+    // user input. Returns the turn's id, or None when the session already has
+    // turns and the message would no longer come first
+    pub async fn add_opening_message(&mut self, session_id: Uuid, text: String) -> Result<Option<Uuid>> {
+        if self.get_session(session_id).await?.turn_count > 0 {
+            return Ok(None);
+        }
+
+        let turn = ConversationTurn {
+            id: Uuid::new_v4(),
+            user_input: String::new(),
+            assistant_response: text,
+            intent: Intent::Unknown,
+            timestamp: Utc::now(),
+            kind: TurnKind::Message,
+        };
+        let turn_id = turn.id;
+        self.push_turn(session_id, turn).await?;
+        Ok(Some(turn_id))
+    }
+
+    async fn push_turn(&mut self, session_id: Uuid, turn: ConversationTurn) -> Result<()> {
+        // A turn storage misses is still answered; it only drops out of the
+        // full history once it leaves the window
+        if let Some(storage) = &self.storage {
+            if let Err(e) = storage.store_conversation_turn(session_id, &turn).await {
+                warn!("Failed to store conversation turn for session {}: {}", session_id, e);
+            }
         }
 
+        let history_window = self.history_window;
+        let session = self.get_session_mut(session_id).await?;
+        session.context.conversation_history.push_bounded(turn, history_window);
+        session.turn_count += 1;
+        session.last_activity = Utc::now();
+
         debug!("Added conversation turn to session {}", session_id);
         Ok(())
     }
@@ -151,17 +233,35 @@ impl ContextManager {
         Ok(())
     }
 
+    // The most recent turns, from memory; at most the history window
     pub async fn get_conversation_history(&self, session_id: Uuid, limit: Option<usize>) -> Result<Vec<ConversationTurn>> {
         let session = self.get_session(session_id).await?;
-        let history = &session.conversation_turns;
+        let history = &session.context.conversation_history;
         
         match limit {
             Some(n) => {
-                let start = if history.len() > n { history.len() - n } else { 0 };
+                let start = history.len().saturating_sub(n);
                 Ok(history[start..].to_vec())
             }
-            None => Ok(history.clone()),
+            None => Ok(history.to_vec()),
+        }
+    }
+
+    // A page of the whole history, oldest first, for the few callers that
+    // need more than the window. Read from storage when the manager has
+    // one; otherwise only the turns still in memory can be returned
+    pub async fn get_full_history(&self, session_id: Uuid, offset: usize, limit: Option<usize>) -> Result<Vec<ConversationTurn>> {
+        let session = self.get_session(session_id).await?;
+        let limit = limit.unwrap_or(MAX_HISTORY_PAGE).min(MAX_HISTORY_PAGE);
+
+        if let Some(storage) = &self.storage {
+            return storage.get_conversation_turns(session_id, offset, limit).await;
         }
+
+        let history = &session.context.conversation_history;
+        let first_in_memory = session.turn_count - history.len();
+        let start = offset.saturating_sub(first_in_memory).min(history.len());
+        Ok(history[start..].iter().take(limit).cloned().collect())
     }
 
     pub async fn get_recent_context(&self, session_id: Uuid, turns: usize) -> Result<String> {
@@ -180,7 +280,14 @@ impl ContextManager {
         let cutoff = Utc::now() - chrono::Duration::hours(self.context_retention_hours);
         let initial_count = self.active_sessions.len();
         
-        self.active_sessions.retain(|_, session| session.last_activity > cutoff);
+        let scratchpads = &self.scratchpads;
+        self.active_sessions.retain(|session_id, session| {
+            let keep = session.last_activity > cutoff;
+            if !keep {
+                scratchpads.clear(*session_id);
+            }
+            keep
+        });
         
         let removed_count = initial_count - self.active_sessions.len();
         if removed_count > 0 {
@@ -201,9 +308,27 @@ impl ContextManager {
             .collect()
     }
 
+    // Preferences of every user with an active session, taken from their
+    // most recently used one
+    pub async fn user_preferences(&self) -> Vec<(Uuid, UserPreferences)> {
+        let mut latest: HashMap<Uuid, &UserSession> = HashMap::new();
+        for session in self.active_sessions.values() {
+            let entry = latest.entry(session.user_id).or_insert(session);
+            if session.last_activity > entry.last_activity {
+                *entry = session;
+            }
+        }
+
+        latest
+            .into_iter()
+            .map(|(user_id, session)| (user_id, session.context.preferences.clone()))
+            .collect()
+    }
+
     pub async fn destroy_session(&mut self, session_id: Uuid) -> Result<()> {
         match self.active_sessions.remove(&session_id) {
             Some(_) => {
+                self.scratchpads.clear(session_id);
                 info!("Destroyed session {}", session_id);
                 Ok(())
             }
@@ -226,6 +351,7 @@ impl ContextManager {
         let count = session_ids.len();
         for session_id in session_ids {
             self.active_sessions.remove(&session_id);
+            self.scratchpads.clear(session_id);
         }
 
         if count > 0 {
@@ -243,7 +369,7 @@ impl ContextManager {
             user_id: session.user_id,
             created_at: session.created_at,
             last_activity: session.last_activity,
-            turn_count: session.conversation_turns.len(),
+            turn_count: session.turn_count,
             active_plugins: session.context.active_plugins.clone(),
         })
     }
@@ -256,11 +382,53 @@ impl ContextManager {
                 user_id: session.user_id,
                 created_at: session.created_at,
                 last_activity: session.last_activity,
-                turn_count: session.conversation_turns.len(),
+                turn_count: session.turn_count,
                 active_plugins: session.context.active_plugins.clone(),
             })
             .collect()
     }
+
+    // Session structs, the text of the turns they keep in memory and their
+    // scratchpads
+    pub fn approximate_size_bytes(&self) -> u64 {
+        self.scratchpads.approximate_size_bytes() + self.active_sessions
+            .values()
+            .map(|session| {
+                let turns: usize = session
+                    .context
+                    .conversation_history
+                    .iter()
+                    .map(|turn| {
+                        std::mem::size_of::<ConversationTurn>() + turn.user_input.len() + turn.assistant_response.len()
+                    })
+                    .sum();
+                (std::mem::size_of::<UserSession>() + turns) as u64
+            })
+            .sum::<u64>()
+    }
+}
+
+// Reports the in-memory session store to the resource registry; trimming
+// drops sessions past the retention window
+pub struct SessionStoreResources(pub Arc<RwLock<ContextManager>>);
+
+#[async_trait]
+impl ResourceReporter for SessionStoreResources {
+    async fn usage(&self) -> ResourceUsage {
+        let manager = self.0.read().await;
+        ResourceUsage {
+            entries: manager.active_sessions.len(),
+            approx_bytes: manager.approximate_size_bytes(),
+        }
+    }
+
+    fn trimmable(&self) -> bool {
+        true
+    }
+
+    async fn trim(&self) -> Result<usize> {
+        self.0.write().await.cleanup_expired_sessions().await
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -292,6 +460,8 @@ mod tests {
                 enabled: true,
                 channels: vec![],
                 quiet_hours: None,
+                routing: Default::default(),
+                knowledge_digest: Default::default(),
             },
         }
     }
@@ -339,11 +509,88 @@ mod tests {
 
         let session_id = manager.create_session(user_id, preferences).await.unwrap();
         assert_eq!(manager.get_active_session_count().await, 1);
+        manager.scratchpads().set(session_id, "destination", serde_json::json!("Lisbon")).unwrap();
 
         // Wait a bit and cleanup
         tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
         let removed = manager.cleanup_expired_sessions().await.unwrap();
         assert_eq!(removed, 1);
         assert_eq!(manager.get_active_session_count().await, 0);
+        assert!(manager.scratchpads().get(session_id).is_empty());
+    }
+
+    async fn add_turns(manager: &mut ContextManager, session_id: Uuid, range: std::ops::Range<usize>) {
+        for i in range {
+            manager.add_conversation_turn(
+                session_id,
+                format!("turn {:04}", i),
+                "a reply long enough to make copies of the history noticeable".repeat(4),
+                Intent::Query { query: format!("turn {:04}", i) },
+            ).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_history_is_bounded_and_paged_from_storage() {
+        let storage = crate::storage::SqliteStorage::new(&crate::storage::StorageConfig {
+            database_url: "sqlite::memory:".to_string(),
+            max_connections: 1,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let mut manager = ContextManager::new_with_config(5, 24).with_storage(Arc::new(storage));
+        let session_id = manager.create_session(Uuid::new_v4(), create_test_preferences()).await.unwrap();
+        add_turns(&mut manager, session_id, 0..12).await;
+
+        let context = manager.get_user_context(session_id).await.unwrap();
+        let inputs: Vec<&str> = context.conversation_history.iter().map(|t| t.user_input.as_str()).collect();
+        assert_eq!(inputs, ["turn 0007", "turn 0008", "turn 0009", "turn 0010", "turn 0011"]);
+        assert_eq!(manager.get_session(session_id).await.unwrap().turn_count, 12);
+        assert_eq!(manager.get_conversation_history(session_id, Some(2)).await.unwrap()[0].user_input, "turn 0010");
+
+        // Older turns are paged back from storage
+        let all = manager.get_full_history(session_id, 0, None).await.unwrap();
+        assert_eq!(all.len(), 12);
+        assert_eq!(all[0].user_input, "turn 0000");
+        let page = manager.get_full_history(session_id, 10, Some(5)).await.unwrap();
+        let inputs: Vec<&str> = page.iter().map(|t| t.user_input.as_str()).collect();
+        assert_eq!(inputs, ["turn 0010", "turn 0011"]);
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_context_cost_stays_flat_as_the_session_grows() {
+        let mut manager = ContextManager::new();
+        let session_id = manager.create_session(Uuid::new_v4(), create_test_preferences()).await.unwrap();
+
+        // Turns and words, as a stand-in for tokens, in the context a request gets
+        async fn context_cost(manager: &ContextManager, session_id: Uuid) -> (usize, usize) {
+            let context = manager.get_user_context(session_id).await.unwrap();
+            let words = context
+                .conversation_history
+                .iter()
+                .map(|turn| turn.user_input.split_whitespace().count() + turn.assistant_response.split_whitespace().count())
+                .sum();
+            (context.conversation_history.len(), words)
+        }
+
+        add_turns(&mut manager, session_id, 0..100).await;
+        let early_bytes = manager.approximate_size_bytes();
+        let early = context_cost(&manager, session_id).await;
+
+        add_turns(&mut manager, session_id, 100..5_000).await;
+        let late_bytes = manager.approximate_size_bytes();
+        let late = context_cost(&manager, session_id).await;
+
+        assert_eq!(manager.get_session(session_id).await.unwrap().turn_count, 5_000);
+        assert_eq!(late_bytes, early_bytes);
+        assert_eq!(early.0, DEFAULT_HISTORY_WINDOW);
+        assert_eq!(late, early);
+
+        // Clones share the turns instead of copying them
+        let a = manager.get_user_context(session_id).await.unwrap().clone();
+        let b = manager.get_user_context(session_id).await.unwrap().clone();
+        assert_eq!(a.conversation_history.len(), DEFAULT_HISTORY_WINDOW);
+        assert!(std::ptr::eq(a.conversation_history.as_ptr(), b.conversation_history.as_ptr()));
+    }
+}
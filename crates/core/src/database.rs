@@ -3,7 +3,10 @@ use sqlx::{
     sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions, SqliteSynchronous},
     Pool, Sqlite, SqlitePool,
 };
-use std::path::Path;
+use serde::{Deserialize, Serialize};
+use sqlx::Row;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::time::Duration;
 use tracing::{info, warn, error, debug, instrument};
@@ -115,6 +118,19 @@ impl DatabaseManager {
         Ok(manager)
     }
     
+    /// Create a manager without connecting, migrating or health checking.
+    /// Used by maintenance tooling that must work on a damaged database.
+    pub fn new_unchecked(config: DatabaseConfig) -> Result<Self> {
+        let connect_options = SqliteConnectOptions::from_str(&config.database_url)
+            .map_err(|e| AssistantError::Database(format!("Invalid database URL: {}", e)))?;
+        
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect_lazy_with(connect_options);
+        
+        Ok(Self { pool, config })
+    }
+    
     /// Get a reference to the connection pool
     pub fn pool(&self) -> &SqlitePool {
         &self.pool
@@ -218,7 +234,7 @@ impl DatabaseManager {
     
     /// Create a backup of the database
     #[instrument(skip(self))]
-    pub async fn backup(&self, backup_path: &Path) -> Result<()> {
+    pub async fn backup(&self, backup_path: &Path) -> Result<BackupManifest> {
         info!("Creating database backup to: {:?}", backup_path);
         
         // Ensure backup directory exists
@@ -236,8 +252,199 @@ impl DatabaseManager {
             .await
             .map_err(|e| AssistantError::Database(format!("Backup failed: {}", e)))?;
         
+        // Record row counts next to the backup so a restore can be verified.
+        // They are read from the backup itself: the live database may have
+        // been written to since `VACUUM INTO` finished
+        let manifest = BackupManifest {
+            created_at: chrono::Utc::now(),
+            source_database: self.config.database_url.clone(),
+            table_row_counts: Self::verify_backup(backup_path).await?,
+        };
+        manifest.write(backup_path).await?;
+        
         info!("Database backup completed successfully");
-        Ok(())
+        Ok(manifest)
+    }
+    
+    /// Restore the database from a backup created by `backup`.
+    ///
+    /// Consumes the manager so its pool is closed before the files are
+    /// swapped; other processes writing to the database must be stopped.
+    /// The previous live file is kept next to it as a safety copy.
+    #[instrument(skip(self))]
+    pub async fn restore(self, backup_path: &Path) -> Result<(Self, RestoreSummary)> {
+        info!("Restoring database from backup: {:?}", backup_path);
+        
+        let backup_counts = Self::verify_backup(backup_path).await?;
+        
+        let manifest = BackupManifest::read(backup_path).await?;
+        if let Some(manifest) = &manifest {
+            for (table, expected) in &manifest.table_row_counts {
+                let actual = backup_counts.get(table).copied().unwrap_or(0);
+                if actual != *expected {
+                    return Err(AssistantError::Database(format!(
+                        "Backup does not match its manifest: table {} has {} rows, expected {}",
+                        table, actual, expected
+                    )));
+                }
+            }
+        } else {
+            warn!("No manifest found for backup {:?}; skipping manifest comparison", backup_path);
+        }
+        
+        // Quiesce writers from this process before touching the files
+        let config = self.config.clone();
+        self.pool.close().await;
+        
+        let live_path = Self::database_file_path(&config.database_url);
+        let safety_copy = Self::swap_in_backup(&live_path, backup_path).await?;
+        
+        // Reopen; post-restore migrations run here when auto_migrate is enabled
+        let manager = Self::new(config).await?;
+        let restored_counts = Self::count_rows(&manager.pool).await?;
+        
+        let expected_counts = manifest
+            .map(|m| m.table_row_counts)
+            .unwrap_or(backup_counts);
+        let tables = restored_counts
+            .iter()
+            .map(|(table, restored)| TableRestoreCount {
+                table: table.clone(),
+                expected_rows: expected_counts.get(table).copied(),
+                restored_rows: *restored,
+            })
+            .collect();
+        
+        let summary = RestoreSummary {
+            backup_path: backup_path.to_path_buf(),
+            safety_copy,
+            tables,
+        };
+        
+        info!("Database restore completed:\n{}", summary);
+        Ok((manager, summary))
+    }
+    
+    /// Open a backup read-only, run an integrity check and count its rows
+    async fn verify_backup(backup_path: &Path) -> Result<BTreeMap<String, i64>> {
+        if !backup_path.exists() {
+            return Err(AssistantError::NotFound(format!("Backup not found: {}", backup_path.display())));
+        }
+        
+        let options = SqliteConnectOptions::new()
+            .filename(backup_path)
+            .read_only(true);
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect_with(options)
+            .await
+            .map_err(|e| AssistantError::Database(format!("Failed to open backup: {}", e)))?;
+        
+        let integrity: String = sqlx::query_scalar("PRAGMA integrity_check")
+            .fetch_one(&pool)
+            .await
+            .map_err(|e| AssistantError::Database(format!("Backup integrity check failed: {}", e)))?;
+        
+        if integrity != "ok" {
+            pool.close().await;
+            return Err(AssistantError::Database(format!("Backup failed integrity check: {}", integrity)));
+        }
+        
+        let counts = Self::count_rows(&pool).await;
+        pool.close().await;
+        counts
+    }
+    
+    /// Stage the backup next to the live database, fsync it and rename it
+    /// over the live file. The live file stays in place until the rename
+    /// succeeds; its previous contents are kept as a safety copy
+    async fn swap_in_backup(live_path: &Path, backup_path: &Path) -> Result<Option<PathBuf>> {
+        let timestamp = chrono::Utc::now().format("%Y%m%d%H%M%S");
+        let staging_path = live_path.with_extension("restore-tmp");
+        let side_file = |path: &Path, suffix: &str| PathBuf::from(format!("{}{}", path.display(), suffix));
+        
+        // Stage a copy on the same filesystem so the final rename is atomic,
+        // and flush it so the rename cannot expose a partly written file
+        tokio::fs::copy(backup_path, &staging_path).await
+            .map_err(|e| AssistantError::Database(format!("Failed to stage backup: {}", e)))?;
+        let staged = tokio::fs::File::open(&staging_path).await
+            .map_err(|e| AssistantError::Database(format!("Failed to open staged backup: {}", e)))?;
+        if let Err(e) = staged.sync_all().await {
+            let _ = tokio::fs::remove_file(&staging_path).await;
+            return Err(AssistantError::Database(format!("Failed to flush staged backup: {}", e)));
+        }
+        
+        // A second link to the live file keeps its contents once the rename
+        // replaces it; copied where links are not supported
+        let safety_copy = if live_path.exists() {
+            let safety_path = PathBuf::from(format!("{}.pre-restore-{}", live_path.display(), timestamp));
+            if tokio::fs::hard_link(live_path, &safety_path).await.is_err() {
+                if let Err(e) = tokio::fs::copy(live_path, &safety_path).await {
+                    let _ = tokio::fs::remove_file(&staging_path).await;
+                    return Err(AssistantError::Database(format!("Failed to preserve live database: {}", e)));
+                }
+            }
+            Some(safety_path)
+        } else {
+            None
+        };
+        
+        // Stale WAL/SHM files belong to the old database and must not be
+        // replayed; they go back if the swap fails
+        let mut moved = Vec::new();
+        for suffix in ["-wal", "-shm"] {
+            let side = side_file(live_path, suffix);
+            if side.exists() {
+                let target = PathBuf::from(format!("{}.pre-restore-{}{}", live_path.display(), timestamp, suffix));
+                tokio::fs::rename(&side, &target).await
+                    .map_err(|e| AssistantError::Database(format!("Failed to move {}: {}", side.display(), e)))?;
+                moved.push((side, target));
+            }
+        }
+        
+        if let Err(e) = tokio::fs::rename(&staging_path, live_path).await {
+            for (side, target) in moved {
+                let _ = tokio::fs::rename(&target, &side).await;
+            }
+            let _ = tokio::fs::remove_file(&staging_path).await;
+            return Err(AssistantError::Database(format!("Failed to swap in backup: {}", e)));
+        }
+        
+        // Persist the rename itself; directories cannot be opened on every
+        // platform, so this is best effort
+        if let Some(parent) = live_path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            if let Ok(dir) = tokio::fs::File::open(parent).await {
+                let _ = dir.sync_all().await;
+            }
+        }
+        
+        Ok(safety_copy)
+    }
+    
+    /// Count rows in every user table
+    async fn count_rows(pool: &SqlitePool) -> Result<BTreeMap<String, i64>> {
+        let tables: Vec<String> = sqlx::query_scalar(
+            "SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%' ORDER BY name"
+        )
+        .fetch_all(pool)
+        .await
+        .map_err(|e| AssistantError::Database(format!("Failed to list tables: {}", e)))?;
+        
+        let mut counts = BTreeMap::new();
+        for table in tables {
+            let row = sqlx::query(&format!("SELECT COUNT(*) FROM \"{}\"", table.replace('"', "\"\"")))
+                .fetch_one(pool)
+                .await
+                .map_err(|e| AssistantError::Database(format!("Failed to count rows in {}: {}", table, e)))?;
+            counts.insert(table, row.get::<i64, _>(0));
+        }
+        
+        Ok(counts)
+    }
+    
+    fn database_file_path(database_url: &str) -> PathBuf {
+        let path = database_url.trim_start_matches("sqlite://").trim_start_matches("sqlite:");
+        PathBuf::from(path.split('?').next().unwrap_or(path))
     }
     
     /// Get database size information
@@ -342,6 +549,84 @@ impl DatabaseSizeInfo {
     }
 }
 
+/// Manifest written next to each backup
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupManifest {
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub source_database: String,
+    pub table_row_counts: BTreeMap<String, i64>,
+}
+
+impl BackupManifest {
+    /// Manifest location for a given backup file
+    pub fn path_for(backup_path: &Path) -> PathBuf {
+        PathBuf::from(format!("{}.manifest.json", backup_path.display()))
+    }
+    
+    async fn write(&self, backup_path: &Path) -> Result<()> {
+        let json = serde_json::to_vec_pretty(self)
+            .map_err(|e| AssistantError::Database(format!("Failed to serialize backup manifest: {}", e)))?;
+        tokio::fs::write(Self::path_for(backup_path), json).await
+            .map_err(|e| AssistantError::Database(format!("Failed to write backup manifest: {}", e)))
+    }
+    
+    async fn read(backup_path: &Path) -> Result<Option<Self>> {
+        let path = Self::path_for(backup_path);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let bytes = tokio::fs::read(&path).await
+            .map_err(|e| AssistantError::Database(format!("Failed to read backup manifest: {}", e)))?;
+        serde_json::from_slice(&bytes)
+            .map(Some)
+            .map_err(|e| AssistantError::Database(format!("Invalid backup manifest: {}", e)))
+    }
+}
+
+/// Row counts for one table after a restore
+#[derive(Debug, Clone, Serialize)]
+pub struct TableRestoreCount {
+    pub table: String,
+    pub expected_rows: Option<i64>,
+    pub restored_rows: i64,
+}
+
+/// Result of a database restore
+#[derive(Debug, Clone, Serialize)]
+pub struct RestoreSummary {
+    pub backup_path: PathBuf,
+    pub safety_copy: Option<PathBuf>,
+    pub tables: Vec<TableRestoreCount>,
+}
+
+impl RestoreSummary {
+    /// Whether every table matches the row count recorded at backup time
+    pub fn is_consistent(&self) -> bool {
+        self.tables
+            .iter()
+            .all(|t| t.expected_rows.map_or(true, |expected| expected == t.restored_rows))
+    }
+}
+
+impl std::fmt::Display for RestoreSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Restored from {}", self.backup_path.display())?;
+        if let Some(safety_copy) = &self.safety_copy {
+            writeln!(f, "Previous database kept at {}", safety_copy.display())?;
+        }
+        writeln!(f, "{:<32} {:>10} {:>10}", "table", "backup", "restored")?;
+        for table in &self.tables {
+            let expected = table.expected_rows.map(|n| n.to_string()).unwrap_or_else(|| "-".to_string());
+            let marker = match table.expected_rows {
+                Some(expected) if expected != table.restored_rows => " MISMATCH",
+                _ => "",
+            };
+            writeln!(f, "{:<32} {:>10} {:>10}{}", table.table, expected, table.restored_rows, marker)?;
+        }
+        write!(f, "Vector store contents are not restored; run a reindex to reconcile them")
+    }
+}
+
 /// Database utility functions
 pub struct DatabaseUtils;
 
@@ -446,6 +731,65 @@ mod tests {
         assert_eq!(fts_query, "\"hello\" AND \"world\"");
     }
     
+    #[tokio::test]
+    async fn test_backup_and_restore_after_corruption() {
+        let temp_dir = tempdir().unwrap();
+        let db_path = temp_dir.path().join("live.db");
+        let backup_path = temp_dir.path().join("backups").join("live.bak");
+        
+        let config = DatabaseConfig {
+            database_url: format!("sqlite:{}", db_path.display()),
+            max_connections: 2,
+            min_connections: 1,
+            auto_migrate: false,
+            ..DatabaseConfig::default()
+        };
+        
+        let db = DatabaseManager::new(config.clone()).await.unwrap();
+        sqlx::query("CREATE TABLE notes (id INTEGER PRIMARY KEY, body TEXT NOT NULL)")
+            .execute(db.pool())
+            .await
+            .unwrap();
+        for body in ["first", "second", "third"] {
+            sqlx::query("INSERT INTO notes (body) VALUES (?)")
+                .bind(body)
+                .execute(db.pool())
+                .await
+                .unwrap();
+        }
+        
+        let manifest = db.backup(&backup_path).await.unwrap();
+        assert_eq!(manifest.table_row_counts.get("notes"), Some(&3));
+        db.close().await;
+        
+        // Corrupt the live database
+        tokio::fs::write(&db_path, b"definitely not a sqlite database").await.unwrap();
+        
+        let db = DatabaseManager::new_unchecked(config).unwrap();
+        let (db, summary) = db.restore(&backup_path).await.unwrap();
+        
+        assert!(summary.is_consistent());
+        let safety_copy = tokio::fs::read(summary.safety_copy.as_ref().unwrap()).await.unwrap();
+        assert_eq!(safety_copy, b"definitely not a sqlite database");
+        assert!(!db_path.with_extension("restore-tmp").exists());
+        
+        let bodies: Vec<String> = sqlx::query_scalar("SELECT body FROM notes ORDER BY id")
+            .fetch_all(db.pool())
+            .await
+            .unwrap();
+        assert_eq!(bodies, vec!["first", "second", "third"]);
+    }
+    
+    #[tokio::test]
+    async fn test_restore_rejects_corrupt_backup() {
+        let temp_dir = tempdir().unwrap();
+        let backup_path = temp_dir.path().join("broken.bak");
+        tokio::fs::write(&backup_path, b"garbage").await.unwrap();
+        
+        let db = create_test_database().await.unwrap();
+        assert!(db.restore(&backup_path).await.is_err());
+    }
+    
     #[tokio::test]
     async fn test_size_info() {
         let db = create_test_database().await.unwrap();
@@ -0,0 +1,562 @@
+use chrono::{DateTime, Duration, NaiveTime, Utc};
+use chrono_tz::Tz;
+use regex::Regex;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Entity keys written into `ClassificationResult::extracted_entities`
+pub const DURATION: &str = "duration";
+pub const DURATION_TYPE: &str = "duration_type";
+pub const TIME_OF_DAY: &str = "time_of_day";
+pub const QUANTITY: &str = "quantity";
+pub const QUANTITY_UNIT: &str = "quantity_unit";
+pub const LOCATION: &str = "location";
+
+/// "in 45 minutes": the duration is an offset from now
+pub const DURATION_OFFSET: &str = "offset";
+/// "for 2 hours": the duration is how long something lasts
+pub const DURATION_SPAN: &str = "span";
+
+const NUMBER_WORDS: &[(&str, f64)] = &[
+    ("a", 1.0), ("an", 1.0), ("one", 1.0), ("two", 2.0), ("three", 3.0), ("four", 4.0),
+    ("five", 5.0), ("six", 6.0), ("seven", 7.0), ("eight", 8.0), ("nine", 9.0), ("ten", 10.0),
+    ("eleven", 11.0), ("twelve", 12.0), ("fifteen", 15.0), ("twenty", 20.0), ("thirty", 30.0),
+    ("forty-five", 45.0), ("ninety", 90.0), ("a dozen", 12.0), ("half a", 0.5), ("half an", 0.5),
+];
+
+/// Words that follow a number without being its unit
+const NON_UNIT_WORDS: &[&str] = &[
+    "to", "and", "or", "of", "the", "at", "in", "on", "for", "by", "from", "with", "more", "times",
+];
+
+const NON_LOCATION_WORDS: &[&str] = &[
+    "I", "Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday", "Sunday",
+    "January", "February", "March", "April", "May", "June", "July", "August", "September",
+    "October", "November", "December", "Today", "Tomorrow",
+];
+
+const DEFAULT_PLACES: &[&str] = &[
+    "Amsterdam", "Barcelona", "Berlin", "Chicago", "Hamburg", "London", "Los Angeles", "Madrid",
+    "Munich", "New York", "Paris", "Rome", "San Francisco", "Tokyo", "Vienna", "Zurich",
+];
+
+/// Words that may follow a bare hour ("at 6 tonight")
+const TIME_FOLLOWERS: &[&str] = &["for", "to", "and", "on", "in", "tonight", "today", "tomorrow", "sharp", "o'clock"];
+
+/// Locales that write times of day on a 12-hour clock
+const TWELVE_HOUR_LOCALES: &[&str] = &["en", "en-us", "en-ca", "en-au", "en-nz", "en-in", "en-ph", "hi"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeFormat {
+    TwelveHour,
+    TwentyFourHour,
+}
+
+impl TimeFormat {
+    pub fn for_locale(locale: &str) -> Self {
+        let locale = locale.to_lowercase().replace('_', "-");
+        if TWELVE_HOUR_LOCALES.contains(&locale.as_str()) {
+            TimeFormat::TwelveHour
+        } else {
+            TimeFormat::TwentyFourHour
+        }
+    }
+}
+
+/// Resolves proper nouns to known places; the default knows a handful of cities
+pub trait Gazetteer: Send + Sync {
+    /// Canonical place name for `name`, if it is a known place
+    fn lookup(&self, name: &str) -> Option<String>;
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct StaticGazetteer {
+    places: HashMap<String, String>,
+}
+
+impl StaticGazetteer {
+    pub fn new<I, S>(places: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            places: places
+                .into_iter()
+                .map(|p| {
+                    let place = p.into();
+                    (place.to_lowercase(), place)
+                })
+                .collect(),
+        }
+    }
+}
+
+impl Gazetteer for StaticGazetteer {
+    fn lookup(&self, name: &str) -> Option<String> {
+        self.places.get(&name.to_lowercase()).cloned()
+    }
+}
+
+/// Extracts durations, times of day, quantities and locations, normalized to
+/// ISO 8601 durations, 24h `HH:MM` times, plain numbers and place names
+pub struct EntityExtractor {
+    gazetteer: Arc<dyn Gazetteer>,
+    duration: Regex,
+    duration_component: Regex,
+    time_with_meridiem: Regex,
+    time_after_preposition: Regex,
+    clock_time: Regex,
+    named_time: Regex,
+    temporal_phrase: Regex,
+    date: Regex,
+    quantity: Regex,
+}
+
+impl EntityExtractor {
+    pub fn new() -> Self {
+        Self::with_gazetteer(Arc::new(StaticGazetteer::new(DEFAULT_PLACES.iter().copied())))
+    }
+
+    pub fn with_gazetteer(gazetteer: Arc<dyn Gazetteer>) -> Self {
+        let words = r"half an?|a dozen|forty-five|an?|one|two|three|four|five|six|seven|eight|nine|ten|eleven|twelve|fifteen|twenty|thirty|ninety";
+        let number = format!(r"\d+(?:\.\d+)?|{words}");
+        let unit = r"seconds?|secs?|minutes?|mins?|hours?|hrs?|days?|weeks?|wks?";
+        // Single-letter units only directly follow digits ("10m"), so "and" is never "an d"
+        let component = format!(r"\b(?:\d+(?:\.\d+)?\s*(?:{unit}|[smhdw])|(?:{words})\s+(?:{unit}))\b");
+
+        Self {
+            gazetteer,
+            duration: Regex::new(&format!(
+                r"\b(in|for|after)\s+({component}(?:\s*(?:,|and)?\s*{component})*)"
+            ))
+            .unwrap(),
+            duration_component: Regex::new(&format!(
+                r"\b(?:(\d+(?:\.\d+)?)\s*({unit}|[smhdw])|({words})\s+({unit}))\b"
+            ))
+            .unwrap(),
+            time_with_meridiem: Regex::new(r"\b(\d{1,2})(?:[:.](\d{2}))?\s*(a\.?m\.?|p\.?m\.?)(?:\s|$|[,!?])").unwrap(),
+            time_after_preposition: Regex::new(r"\b(?:at|by|around|um)\s+(\d{1,2})(?:[:.](\d{2}))?(\s*(?:h|uhr)\b)?").unwrap(),
+            clock_time: Regex::new(r"\b([01]?\d|2[0-3]):([0-5]\d)\b").unwrap(),
+            named_time: Regex::new(r"\b(noon|midday|midnight)\b").unwrap(),
+            temporal_phrase: Regex::new(
+                r"(?:\b(?:at|by|around|um)\s+)?(?:\b\d{1,2}(?:[:.]\d{2})?\s*(?:a\.?m\.?|p\.?m\.?)(?:\s|$|[,!?])|\b\d{1,2}[:.]\d{2}\b|\b(?:noon|midday|midnight)\b)",
+            )
+            .unwrap(),
+            date: Regex::new(r"\d{1,2}/\d{1,2}/\d{4}|\d{4}-\d{2}-\d{2}").unwrap(),
+            quantity: Regex::new(&format!(r"\b({number})\s+(?:x\s+)?([a-z][a-z-]*)")).unwrap(),
+        }
+    }
+
+    /// Extract every entity found in `input`, using `locale` to read times of day
+    pub fn extract(&self, input: &str, locale: &str) -> HashMap<String, String> {
+        let mut entities = HashMap::new();
+        let lower = input.to_lowercase();
+
+        if let Some((kind, duration)) = self.extract_duration(&lower) {
+            entities.insert(DURATION.to_string(), format_iso_duration(duration));
+            entities.insert(DURATION_TYPE.to_string(), kind.to_string());
+        }
+        if let Some(time) = self.extract_time_of_day(&lower, TimeFormat::for_locale(locale)) {
+            entities.insert(TIME_OF_DAY.to_string(), time.format("%H:%M").to_string());
+        }
+        if let Some((amount, unit)) = self.extract_quantity(&lower) {
+            entities.insert(QUANTITY.to_string(), format_number(amount));
+            if let Some(unit) = unit {
+                entities.insert(QUANTITY_UNIT.to_string(), unit);
+            }
+        }
+        if let Some(location) = self.extract_location(input) {
+            entities.insert(LOCATION.to_string(), location);
+        }
+
+        entities
+    }
+
+    /// Remove duration and time-of-day phrases, e.g. to clean up a task name
+    pub fn strip_temporal_phrases(&self, text: &str) -> String {
+        let mut stripped = text.to_string();
+        for regex in [&self.duration, &self.temporal_phrase, &self.time_after_preposition] {
+            stripped = regex.replace_all(&stripped, " ").to_string();
+        }
+        stripped.split_whitespace().collect::<Vec<_>>().join(" ")
+    }
+
+    fn extract_duration(&self, lower: &str) -> Option<(&'static str, Duration)> {
+        let captures = self.duration.captures(lower)?;
+        let kind = match &captures[1] {
+            "for" => DURATION_SPAN,
+            _ => DURATION_OFFSET,
+        };
+
+        let mut seconds = 0.0;
+        for component in self.duration_component.captures_iter(&captures[2]) {
+            let amount = parse_number(component.get(1).or(component.get(3))?.as_str())?;
+            let unit_seconds = match component.get(2).or(component.get(4))?.as_str() {
+                u if u.starts_with('s') => 1.0,
+                u if u.starts_with('m') => 60.0,
+                u if u.starts_with('h') => 3600.0,
+                u if u.starts_with('d') => 86400.0,
+                _ => 604800.0,
+            };
+            seconds += amount * unit_seconds;
+        }
+
+        (seconds > 0.0).then(|| (kind, Duration::seconds(seconds.round() as i64)))
+    }
+
+    fn extract_time_of_day(&self, lower: &str, format: TimeFormat) -> Option<NaiveTime> {
+        if let Some(c) = self.time_with_meridiem.captures(lower) {
+            let hour: u32 = c[1].parse().ok()?;
+            let minute: u32 = c.get(2).map_or(Some(0), |m| m.as_str().parse().ok())?;
+            if !(1..=12).contains(&hour) {
+                return None;
+            }
+            let pm = c[3].starts_with('p');
+            return NaiveTime::from_hms_opt(hour % 12 + if pm { 12 } else { 0 }, minute, 0);
+        }
+
+        if let Some(c) = self.named_time.captures(lower) {
+            return match &c[1] {
+                "midnight" => NaiveTime::from_hms_opt(0, 0, 0),
+                _ => NaiveTime::from_hms_opt(12, 0, 0),
+            };
+        }
+
+        if let Some(c) = self.time_after_preposition.captures(lower) {
+            // "at 6 tonight" is a time; "at 3 apples" is not
+            let next_word = lower[c.get(0)?.end()..].split_whitespace().next().unwrap_or("");
+            let explicit_24h = c.get(3).is_some();
+            if explicit_24h
+                || c.get(2).is_some()
+                || !next_word.starts_with(|ch: char| ch.is_ascii_alphabetic())
+                || TIME_FOLLOWERS.contains(&next_word.trim_end_matches(|ch: char| !ch.is_alphabetic()))
+            {
+                let hour: u32 = c[1].parse().ok()?;
+                let minute: u32 = c.get(2).map_or(Some(0), |m| m.as_str().parse().ok())?;
+                return NaiveTime::from_hms_opt(resolve_hour(hour, format, explicit_24h)?, minute, 0);
+            }
+        }
+
+        let c = self.clock_time.captures(lower)?;
+        let hour: u32 = c[1].parse().ok()?;
+        let minute: u32 = c[2].parse().ok()?;
+        NaiveTime::from_hms_opt(resolve_hour(hour, format, false)?, minute, 0)
+    }
+
+    fn extract_quantity(&self, lower: &str) -> Option<(f64, Option<String>)> {
+        // Numbers that belong to a duration, time or date are not quantities
+        let mut remaining = lower.to_string();
+        for regex in [&self.duration, &self.time_with_meridiem, &self.time_after_preposition, &self.clock_time, &self.date] {
+            remaining = regex.replace_all(&remaining, " ").to_string();
+        }
+
+        for c in self.quantity.captures_iter(&remaining) {
+            let unit = c[2].to_string();
+            if NON_UNIT_WORDS.contains(&unit.as_str()) {
+                // A bare digit still counts ("buy 3 of them"); a bare "one" does not
+                if c[1].chars().all(|ch| ch.is_ascii_digit() || ch == '.') {
+                    return Some((parse_number(&c[1])?, None));
+                }
+                continue;
+            }
+            // Articles are only quantities with a unit that is clearly countable
+            if matches!(&c[1], "a" | "an") {
+                continue;
+            }
+            return Some((parse_number(&c[1])?, Some(unit)));
+        }
+
+        None
+    }
+
+    fn extract_location(&self, input: &str) -> Option<String> {
+        let words: Vec<&str> = input.split_whitespace().collect();
+        let mut index = 0;
+
+        while index < words.len() {
+            let start = index;
+            let mut span = Vec::new();
+            while index < words.len() {
+                let word = words[index].trim_matches(|c: char| !c.is_alphanumeric() && c != '-');
+                let capitalized = word.chars().next().map_or(false, |c| c.is_uppercase())
+                    && !NON_LOCATION_WORDS.contains(&word);
+                if !capitalized {
+                    break;
+                }
+                span.push(word);
+                index += 1;
+                // Punctuation ends a name ("Berlin, then Paris")
+                if words[index - 1].ends_with(|c: char| matches!(c, ',' | '.' | '!' | '?' | ';')) {
+                    break;
+                }
+            }
+
+            if span.is_empty() {
+                index += 1;
+                continue;
+            }
+
+            let name = span.join(" ");
+            if let Some(place) = self.gazetteer.lookup(&name) {
+                return Some(place);
+            }
+
+            // Unknown names count when introduced by a place preposition,
+            // e.g. "notes from the Berlin trip"; the first word of a sentence
+            // is capitalized anyway and is not a signal on its own
+            let preposition = match start {
+                0 => None,
+                1 => Some(words[0]),
+                _ if words[start - 1].eq_ignore_ascii_case("the") => Some(words[start - 2]),
+                _ => Some(words[start - 1]),
+            };
+            if start > 0
+                && preposition
+                    .map(|p| ["in", "at", "from", "to", "near", "around"].contains(&p.to_lowercase().as_str()))
+                    .unwrap_or(false)
+            {
+                return Some(name);
+            }
+        }
+
+        None
+    }
+}
+
+impl Default for EntityExtractor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 12-hour locales read an hour without am/pm as the likelier half of the
+/// day ("at 6" is evening, "at 9" is morning); 24-hour locales take it as written
+fn resolve_hour(hour: u32, format: TimeFormat, explicit_24h: bool) -> Option<u32> {
+    if hour > 23 {
+        return None;
+    }
+    match format {
+        TimeFormat::TwelveHour if !explicit_24h && (1..=6).contains(&hour) => Some(hour + 12),
+        _ => Some(hour),
+    }
+}
+
+fn parse_number(text: &str) -> Option<f64> {
+    text.parse::<f64>().ok().or_else(|| {
+        NUMBER_WORDS
+            .iter()
+            .find(|(word, _)| *word == text)
+            .map(|(_, value)| *value)
+    })
+}
+
+fn format_number(value: f64) -> String {
+    if value.fract() == 0.0 {
+        format!("{}", value as i64)
+    } else {
+        format!("{}", value)
+    }
+}
+
+/// Format a duration as ISO 8601, e.g. `PT1H30M` or `P3D`
+pub fn format_iso_duration(duration: Duration) -> String {
+    let mut seconds = duration.num_seconds().max(0);
+    let days = seconds / 86400;
+    seconds %= 86400;
+    let hours = seconds / 3600;
+    seconds %= 3600;
+    let minutes = seconds / 60;
+    seconds %= 60;
+
+    let mut iso = String::from("P");
+    if days > 0 {
+        iso.push_str(&format!("{}D", days));
+    }
+    if hours > 0 || minutes > 0 || seconds > 0 || days == 0 {
+        iso.push('T');
+        if hours > 0 {
+            iso.push_str(&format!("{}H", hours));
+        }
+        if minutes > 0 {
+            iso.push_str(&format!("{}M", minutes));
+        }
+        if seconds > 0 || (hours == 0 && minutes == 0) {
+            iso.push_str(&format!("{}S", seconds));
+        }
+    }
+    iso
+}
+
+/// Parse the ISO 8601 durations produced by [`format_iso_duration`]
+pub fn parse_iso_duration(iso: &str) -> Option<Duration> {
+    let rest = iso.strip_prefix('P')?;
+    let (date_part, time_part) = rest.split_once('T').unwrap_or((rest, ""));
+    let mut total = Duration::zero();
+
+    for (part, units) in [(date_part, [('W', 604800), ('D', 86400), ('\0', 0)]), (time_part, [('H', 3600), ('M', 60), ('S', 1)])] {
+        let mut number = String::new();
+        for ch in part.chars() {
+            if ch.is_ascii_digit() {
+                number.push(ch);
+                continue;
+            }
+            let (_, seconds) = units.iter().find(|(unit, _)| *unit == ch)?;
+            total = total + Duration::seconds(number.parse::<i64>().ok()? * seconds);
+            number.clear();
+        }
+        if !number.is_empty() {
+            return None;
+        }
+    }
+
+    Some(total)
+}
+
+/// When a task or reminder described by `entities` is due: an offset duration
+/// counts from `now`, a time of day is its next occurrence in `timezone`
+pub fn resolve_due_date(entities: &HashMap<String, String>, timezone: &str, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    if entities.get(DURATION_TYPE).map(String::as_str) == Some(DURATION_OFFSET) {
+        if let Some(duration) = entities.get(DURATION).and_then(|d| parse_iso_duration(d)) {
+            return Some(now + duration);
+        }
+    }
+
+    let time = NaiveTime::parse_from_str(entities.get(TIME_OF_DAY)?, "%H:%M").ok()?;
+    let tz: Tz = timezone.parse().unwrap_or(chrono_tz::UTC);
+    let local_now = now.with_timezone(&tz);
+
+    let mut date = local_now.date_naive();
+    if entities.get("due_date").map(String::as_str) == Some("tomorrow") {
+        date = date.succ_opt()?;
+    } else if local_now.time() >= time {
+        date = date.succ_opt()?;
+    }
+
+    Some(crate::notifications::resolve_local(&tz, date.and_time(time)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn extract(input: &str) -> HashMap<String, String> {
+        EntityExtractor::new().extract(input, "en-US")
+    }
+
+    #[test]
+    fn test_durations_are_iso_normalized() {
+        let cases = [
+            ("remind me in 45 minutes", "PT45M", DURATION_OFFSET),
+            ("remind me in 45 mins to stretch", "PT45M", DURATION_OFFSET),
+            ("block focus time for 2 hours", "PT2H", DURATION_SPAN),
+            ("call me back in an hour", "PT1H", DURATION_OFFSET),
+            ("check the oven in half an hour", "PT30M", DURATION_OFFSET),
+            ("meeting for 1.5 hours", "PT1H30M", DURATION_SPAN),
+            ("in 2 hours and 15 minutes", "PT2H15M", DURATION_OFFSET),
+            ("follow up in 3 days", "P3D", DURATION_OFFSET),
+            ("ping me after 90 seconds", "PT1M30S", DURATION_OFFSET),
+            ("pause for 10m", "PT10M", DURATION_SPAN),
+        ];
+
+        for (input, expected, kind) in cases {
+            let entities = extract(input);
+            assert_eq!(entities.get(DURATION).map(String::as_str), Some(expected), "{}", input);
+            assert_eq!(entities.get(DURATION_TYPE).map(String::as_str), Some(kind), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_times_of_day_respect_locale() {
+        let extractor = EntityExtractor::new();
+        let cases = [
+            ("call mom at 6pm", "en-US", "18:00"),
+            ("call mom at 6 PM", "de-DE", "18:00"),
+            ("standup at 9:30am", "en-US", "09:30"),
+            ("dinner at 6", "en-US", "18:00"),
+            ("dinner at 6", "de-DE", "06:00"),
+            ("train at 6:30", "en", "18:30"),
+            ("train at 6:30", "fr-FR", "06:30"),
+            ("zug um 18 uhr", "de-DE", "18:00"),
+            ("deploy at 18:45", "en-US", "18:45"),
+            ("lunch at noon", "en-GB", "12:00"),
+            ("backup at 14.30", "de", "14:30"),
+        ];
+
+        for (input, locale, expected) in cases {
+            let entities = extractor.extract(input, locale);
+            assert_eq!(entities.get(TIME_OF_DAY).map(String::as_str), Some(expected), "{} ({})", input, locale);
+        }
+    }
+
+    #[test]
+    fn test_quantities() {
+        let cases = [
+            ("buy 3 bottles of milk", Some(("3", Some("bottles")))),
+            ("order twelve eggs", Some(("12", Some("eggs")))),
+            ("add 2.5 kg flour", Some(("2.5", Some("kg")))),
+            ("remind me in 45 minutes", None),
+            ("call mom at 6pm", None),
+            ("one of these days", None),
+        ];
+
+        for (input, expected) in cases {
+            let entities = extract(input);
+            let actual = entities
+                .get(QUANTITY)
+                .map(|q| (q.as_str(), entities.get(QUANTITY_UNIT).map(String::as_str)));
+            assert_eq!(actual, expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_locations() {
+        let cases = [
+            ("find notes from the Berlin trip", Some("Berlin")),
+            ("Meeting in New York next week", Some("New York")),
+            ("flights to Reykjavik", Some("Reykjavik")),
+            ("Paris notes", Some("Paris")),
+            ("remind me to call Anna on Monday", None),
+            ("Find my notes", None),
+        ];
+
+        for (input, expected) in cases {
+            assert_eq!(extract(input).get(LOCATION).map(String::as_str), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_custom_gazetteer() {
+        let extractor = EntityExtractor::with_gazetteer(Arc::new(StaticGazetteer::new(["Lake Como"])));
+        let entities = extractor.extract("pictures of lake como and the Lake Como villa", "en");
+        assert_eq!(entities.get(LOCATION).map(String::as_str), Some("Lake Como"));
+    }
+
+    #[test]
+    fn test_iso_duration_round_trip() {
+        for seconds in [30, 60, 2700, 5400, 86400, 90061] {
+            let duration = Duration::seconds(seconds);
+            assert_eq!(parse_iso_duration(&format_iso_duration(duration)), Some(duration));
+        }
+        assert_eq!(parse_iso_duration("P2W"), Some(Duration::days(14)));
+        assert_eq!(parse_iso_duration("PT5"), None);
+    }
+
+    #[test]
+    fn test_resolve_due_date() {
+        let now = Utc.with_ymd_and_hms(2024, 3, 1, 20, 0, 0).unwrap();
+
+        let offset = extract("remind me in 45 minutes");
+        assert_eq!(resolve_due_date(&offset, "UTC", now), Some(now + Duration::minutes(45)));
+
+        // 18:00 in Berlin (UTC+1) has already passed at 20:00 UTC, so it is tomorrow
+        let evening = extract("call mom at 6pm");
+        assert_eq!(
+            resolve_due_date(&evening, "Europe/Berlin", now),
+            Some(Utc.with_ymd_and_hms(2024, 3, 2, 17, 0, 0).unwrap())
+        );
+
+        assert_eq!(resolve_due_date(&extract("block time for 2 hours"), "UTC", now), None);
+    }
+}
@@ -0,0 +1,165 @@
+// In-process event bus for state changes that live clients want to hear
+// about. Publishers fire and forget: an event with nobody listening is
+// dropped, and a listener that falls behind loses the oldest events (it is
+// told how many via `RecvError::Lagged` and should resync from storage).
+use rusty_ai_common::{DailyBriefing, NotificationChannel, Result, Task, TaskStatus};
+use async_trait::async_trait;
+use std::sync::Arc;
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+use crate::notifications::{Notification, NotificationSink};
+use crate::warmup::SessionGreeting;
+
+// Events held for the slowest listener before it starts lagging
+pub const EVENT_BUS_CAPACITY: usize = 1024;
+
+#[derive(Debug, Clone)]
+pub enum AssistantEvent {
+    /// A task was created or its status changed. `user_id` is None for
+    /// tasks changed by background work on behalf of no particular user
+    TaskStatusChanged { user_id: Option<Uuid>, task: Task },
+    /// A briefing was generated; None when the scheduler produced it
+    BriefingGenerated { user_id: Option<Uuid>, briefing: Arc<DailyBriefing> },
+    /// A user added a document to the knowledge base
+    DocumentAdded { user_id: Uuid, document_id: Uuid, title: String },
+    /// An in-app notification was delivered
+    Notification(Notification),
+    /// The user did something first-run onboarding keeps track of
+    UserAction { user_id: Uuid, action: UserAction },
+    /// A new session was opened with a greeting
+    SessionGreeting { user_id: Uuid, greeting: SessionGreeting },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UserAction {
+    PreferencesUpdated,
+    /// Enabled or configured a plugin that connects an outside service
+    ProviderConnected,
+    DocumentUploaded,
+    VoiceInteraction,
+    /// Looked at what the assistant keeps about a session
+    MemoryReviewed,
+}
+
+impl AssistantEvent {
+    /// The user the event concerns; None means every user
+    pub fn user_id(&self) -> Option<Uuid> {
+        match self {
+            AssistantEvent::TaskStatusChanged { user_id, .. } => *user_id,
+            AssistantEvent::BriefingGenerated { user_id, .. } => *user_id,
+            AssistantEvent::DocumentAdded { user_id, .. } => Some(*user_id),
+            AssistantEvent::Notification(notification) => Some(notification.user_id),
+            AssistantEvent::UserAction { user_id, .. } => Some(*user_id),
+            AssistantEvent::SessionGreeting { user_id, .. } => Some(*user_id),
+        }
+    }
+
+    /// What plugins subscribed to broadcasts hear of the event, if anything
+    pub fn plugin_event(&self) -> Option<rusty_ai_common::PluginEvent> {
+        match self {
+            AssistantEvent::DocumentAdded { user_id, document_id, title } => {
+                Some(rusty_ai_common::PluginEvent::DocumentUploaded {
+                    user_id: *user_id,
+                    document_id: *document_id,
+                    title: title.clone(),
+                })
+            }
+            AssistantEvent::TaskStatusChanged { user_id, task } if task.status == TaskStatus::Completed => {
+                Some(rusty_ai_common::PluginEvent::TaskCompleted {
+                    user_id: *user_id,
+                    task_id: task.id,
+                    name: task.name.clone(),
+                })
+            }
+            AssistantEvent::BriefingGenerated { user_id, briefing } => {
+                Some(rusty_ai_common::PluginEvent::BriefingGenerated {
+                    user_id: *user_id,
+                    briefing_id: briefing.id,
+                    date: briefing.date,
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+pub struct EventBus {
+    tx: broadcast::Sender<AssistantEvent>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new(EVENT_BUS_CAPACITY)
+    }
+}
+
+impl EventBus {
+    pub fn new(capacity: usize) -> Self {
+        let (tx, _rx) = broadcast::channel(capacity);
+        Self { tx }
+    }
+
+    pub fn publish(&self, event: AssistantEvent) {
+        // Failing only means nobody is subscribed right now
+        let _ = self.tx.send(event);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<AssistantEvent> {
+        self.tx.subscribe()
+    }
+
+    pub fn subscriber_count(&self) -> usize {
+        self.tx.receiver_count()
+    }
+}
+
+// Publishes in-app notifications on the bus on top of delivering them
+// through the wrapped sink, so connected clients see them immediately
+pub struct InAppEventSink {
+    inner: Arc<dyn NotificationSink>,
+    events: Arc<EventBus>,
+}
+
+impl InAppEventSink {
+    pub fn new(inner: Arc<dyn NotificationSink>, events: Arc<EventBus>) -> Self {
+        Self { inner, events }
+    }
+}
+
+#[async_trait]
+impl NotificationSink for InAppEventSink {
+    async fn deliver(&self, channel: &NotificationChannel, notification: &Notification) -> Result<()> {
+        self.inner.deliver(channel, notification).await?;
+        if *channel == NotificationChannel::InApp {
+            self.events.publish(AssistantEvent::Notification(notification.clone()));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notifications::LoggingSink;
+    use rusty_ai_common::NotificationCategory;
+
+    #[tokio::test]
+    async fn test_only_in_app_notifications_are_published() {
+        let events = Arc::new(EventBus::default());
+        let mut rx = events.subscribe();
+        let sink = InAppEventSink::new(Arc::new(LoggingSink), events.clone());
+        let user_id = Uuid::new_v4();
+
+        let email = Notification::new(user_id, NotificationCategory::Reminder, "By mail", "");
+        sink.deliver(&NotificationChannel::Email, &email).await.unwrap();
+        let in_app = Notification::new(user_id, NotificationCategory::Reminder, "In the app", "");
+        sink.deliver(&NotificationChannel::InApp, &in_app).await.unwrap();
+
+        match rx.try_recv().unwrap() {
+            AssistantEvent::Notification(notification) => assert_eq!(notification.title, "In the app"),
+            other => panic!("unexpected event {:?}", other),
+        }
+        assert!(rx.try_recv().is_err());
+    }
+}
@@ -0,0 +1,354 @@
+use chrono::{DateTime, Utc};
+use rusty_ai_common::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock as StdRwLock};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::storage::Storage;
+
+/// Experimental behavior that can be switched per user at runtime
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Flag {
+    /// Document search also matches tags, not only title and content
+    HybridSearch,
+    /// Search results are reordered by how many query terms they contain
+    Reranking,
+    /// Scheduled messages such as the weekly knowledge digest
+    ProactiveMessages,
+    /// New sessions open with a greeting about the day's tasks, briefing
+    /// and waiting notifications
+    SessionWarmup,
+}
+
+impl Flag {
+    pub const ALL: [Flag; 4] = [Flag::HybridSearch, Flag::Reranking, Flag::ProactiveMessages, Flag::SessionWarmup];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Flag::HybridSearch => "hybrid_search",
+            Flag::Reranking => "reranking",
+            Flag::ProactiveMessages => "proactive_messages",
+            Flag::SessionWarmup => "session_warmup",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|flag| flag.as_str() == value)
+    }
+}
+
+/// Default state of each flag, part of the core configuration
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlagConfig {
+    pub defaults: HashMap<Flag, bool>,
+}
+
+impl Default for FlagConfig {
+    fn default() -> Self {
+        Self {
+            defaults: HashMap::from([
+                (Flag::HybridSearch, false),
+                (Flag::Reranking, false),
+                // Digests went out before the flag existed
+                (Flag::ProactiveMessages, true),
+                (Flag::SessionWarmup, false),
+            ]),
+        }
+    }
+}
+
+impl FlagConfig {
+    pub fn default_for(&self, flag: Flag) -> bool {
+        self.defaults.get(&flag).copied().unwrap_or(false)
+    }
+}
+
+/// Runtime changes to a flag, kept in storage. A user listed in `users`
+/// gets that state; everyone else gets `enabled` when set, otherwise the
+/// `rollout_percent` share of users, otherwise the configured default
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FlagOverride {
+    pub enabled: Option<bool>,
+    pub rollout_percent: Option<u8>,
+    #[serde(default)]
+    pub users: HashMap<Uuid, bool>,
+    pub updated_by: Option<String>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+impl FlagOverride {
+    fn evaluate(&self, flag: Flag, user_id: Option<Uuid>, default: bool) -> bool {
+        if let Some(enabled) = user_id.and_then(|id| self.users.get(&id)) {
+            return *enabled;
+        }
+        if let Some(enabled) = self.enabled {
+            return enabled;
+        }
+        match (self.rollout_percent, user_id) {
+            (Some(percent), Some(user_id)) => rollout_bucket(flag, user_id) < percent.min(100),
+            // Without a user there is nothing to bucket
+            (Some(_), None) => default,
+            (None, _) => default,
+        }
+    }
+}
+
+/// The user's bucket in 0..100 for `flag`. Stable across restarts and
+/// builds, and independent between flags so one 10% rollout does not pick
+/// the same users as another
+pub fn rollout_bucket(flag: Flag, user_id: Uuid) -> u8 {
+    // FNV-1a: std's hasher may change between Rust releases
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in flag.as_str().as_bytes().iter().chain(b":").chain(user_id.as_bytes()) {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    (hash % 100) as u8
+}
+
+/// A flag's configured default, override and state for everyone
+#[derive(Debug, Clone, Serialize)]
+pub struct FlagStatus {
+    pub flag: Flag,
+    pub default: bool,
+    #[serde(rename = "override")]
+    pub flag_override: Option<FlagOverride>,
+}
+
+// Flags are evaluated on every message, so evaluation reads an in-memory
+// snapshot; changes go to storage first and then replace the snapshot
+pub struct FeatureFlags {
+    config: FlagConfig,
+    storage: Option<Arc<dyn Storage + Send + Sync>>,
+    snapshot: StdRwLock<Arc<HashMap<Flag, FlagOverride>>>,
+}
+
+impl FeatureFlags {
+    pub fn new(config: FlagConfig) -> Self {
+        Self {
+            config,
+            storage: None,
+            snapshot: StdRwLock::new(Arc::new(HashMap::new())),
+        }
+    }
+
+    /// Keep overrides in `storage`; call [`FeatureFlags::load`] to read them
+    pub fn with_storage(mut self, storage: Arc<dyn Storage + Send + Sync>) -> Self {
+        self.storage = Some(storage);
+        self
+    }
+
+    /// Replace the snapshot with the overrides in storage. Unknown flag names,
+    /// e.g. of a removed flag, are skipped
+    pub async fn load(&self) -> Result<()> {
+        let Some(storage) = &self.storage else {
+            return Ok(());
+        };
+        let stored = storage.get_flag_overrides().await?;
+        let overrides: HashMap<Flag, FlagOverride> = stored
+            .into_iter()
+            .filter_map(|(name, flag_override)| match Flag::parse(&name) {
+                Some(flag) => Some((flag, flag_override)),
+                None => {
+                    warn!("Ignoring override of unknown feature flag '{}'", name);
+                    None
+                }
+            })
+            .collect();
+        info!("Loaded {} feature flag overrides", overrides.len());
+        *self.snapshot.write().unwrap() = Arc::new(overrides);
+        Ok(())
+    }
+
+    pub fn enabled(&self, flag: Flag, user_id: Uuid) -> bool {
+        self.evaluate(flag, Some(user_id))
+    }
+
+    /// For work not done for a particular user
+    pub fn enabled_globally(&self, flag: Flag) -> bool {
+        self.evaluate(flag, None)
+    }
+
+    fn evaluate(&self, flag: Flag, user_id: Option<Uuid>) -> bool {
+        let default = self.config.default_for(flag);
+        let snapshot = self.snapshot.read().unwrap();
+        match snapshot.get(&flag) {
+            Some(flag_override) => flag_override.evaluate(flag, user_id, default),
+            None => default,
+        }
+    }
+
+    pub fn statuses(&self) -> Vec<FlagStatus> {
+        let snapshot = self.snapshot.read().unwrap().clone();
+        Flag::ALL
+            .into_iter()
+            .map(|flag| FlagStatus {
+                flag,
+                default: self.config.default_for(flag),
+                flag_override: snapshot.get(&flag).cloned(),
+            })
+            .collect()
+    }
+
+    pub fn status(&self, flag: Flag) -> FlagStatus {
+        FlagStatus {
+            flag,
+            default: self.config.default_for(flag),
+            flag_override: self.snapshot.read().unwrap().get(&flag).cloned(),
+        }
+    }
+
+    /// Store `flag_override` and start evaluating with it; `None` goes back
+    /// to the configured default
+    pub async fn set(&self, flag: Flag, flag_override: Option<FlagOverride>) -> Result<FlagStatus> {
+        if let Some(storage) = &self.storage {
+            storage.store_flag_override(flag.as_str(), flag_override.as_ref()).await?;
+        }
+
+        {
+            let mut snapshot = self.snapshot.write().unwrap();
+            let mut next = HashMap::clone(&snapshot);
+            match flag_override {
+                Some(flag_override) => next.insert(flag, flag_override),
+                None => next.remove(&flag),
+            };
+            *snapshot = Arc::new(next);
+        }
+        info!("Feature flag {} changed", flag.as_str());
+        Ok(self.status(flag))
+    }
+}
+
+impl Default for FeatureFlags {
+    fn default() -> Self {
+        Self::new(FlagConfig::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{SqliteStorage, StorageConfig};
+
+    #[test]
+    fn test_unset_flags_use_the_configured_default() {
+        let flags = FeatureFlags::default();
+        let user = Uuid::new_v4();
+        assert!(!flags.enabled(Flag::HybridSearch, user));
+        assert!(flags.enabled(Flag::ProactiveMessages, user));
+
+        let mut config = FlagConfig::default();
+        config.defaults.insert(Flag::HybridSearch, true);
+        config.defaults.remove(&Flag::ProactiveMessages);
+        let flags = FeatureFlags::new(config);
+        assert!(flags.enabled(Flag::HybridSearch, user));
+        assert!(!flags.enabled(Flag::ProactiveMessages, user));
+    }
+
+    #[tokio::test]
+    async fn test_user_overrides_win_over_global_state() {
+        let flags = FeatureFlags::default();
+        let tester = Uuid::new_v4();
+        let opted_out = Uuid::new_v4();
+        let someone = Uuid::new_v4();
+
+        flags
+            .set(
+                Flag::Reranking,
+                Some(FlagOverride {
+                    users: HashMap::from([(tester, true)]),
+                    ..Default::default()
+                }),
+            )
+            .await
+            .unwrap();
+        assert!(flags.enabled(Flag::Reranking, tester));
+        assert!(!flags.enabled(Flag::Reranking, someone));
+
+        flags
+            .set(
+                Flag::Reranking,
+                Some(FlagOverride {
+                    enabled: Some(true),
+                    users: HashMap::from([(opted_out, false)]),
+                    ..Default::default()
+                }),
+            )
+            .await
+            .unwrap();
+        assert!(flags.enabled(Flag::Reranking, someone));
+        assert!(!flags.enabled(Flag::Reranking, opted_out));
+        assert!(flags.enabled_globally(Flag::Reranking));
+
+        // Runtime flips apply to the next evaluation
+        flags.set(Flag::Reranking, None).await.unwrap();
+        assert!(!flags.enabled(Flag::Reranking, someone));
+        assert!(flags.status(Flag::Reranking).flag_override.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_percentage_rollout_is_deterministic() {
+        let users: Vec<Uuid> = (0..2000u128).map(|i| Uuid::from_u128(i * 7919 + 17)).collect();
+        let flags = FeatureFlags::default();
+        flags
+            .set(Flag::HybridSearch, Some(FlagOverride { rollout_percent: Some(25), ..Default::default() }))
+            .await
+            .unwrap();
+
+        let enabled: Vec<Uuid> = users.iter().copied().filter(|u| flags.enabled(Flag::HybridSearch, *u)).collect();
+        let share = enabled.len() as f64 / users.len() as f64;
+        assert!((0.2..0.3).contains(&share), "{} of users enabled", share);
+
+        // Same users every time, and widening the rollout keeps them in
+        assert!(enabled.iter().all(|u| flags.enabled(Flag::HybridSearch, *u)));
+        flags
+            .set(Flag::HybridSearch, Some(FlagOverride { rollout_percent: Some(50), ..Default::default() }))
+            .await
+            .unwrap();
+        assert!(enabled.iter().all(|u| flags.enabled(Flag::HybridSearch, *u)));
+
+        // A known user lands in the same bucket on every build
+        assert_eq!(rollout_bucket(Flag::HybridSearch, Uuid::nil()), rollout_bucket(Flag::HybridSearch, Uuid::nil()));
+        assert!(users.iter().any(|u| rollout_bucket(Flag::HybridSearch, *u) != rollout_bucket(Flag::Reranking, *u)));
+
+        flags
+            .set(Flag::HybridSearch, Some(FlagOverride { rollout_percent: Some(0), ..Default::default() }))
+            .await
+            .unwrap();
+        assert!(users.iter().all(|u| !flags.enabled(Flag::HybridSearch, *u)));
+    }
+
+    #[tokio::test]
+    async fn test_overrides_survive_a_restart() {
+        let config = StorageConfig {
+            database_url: "sqlite::memory:".to_string(),
+            max_connections: 1,
+            ..Default::default()
+        };
+        let storage: Arc<dyn Storage + Send + Sync> = Arc::new(SqliteStorage::new(&config).await.unwrap());
+        let user = Uuid::new_v4();
+
+        let flags = FeatureFlags::default().with_storage(storage.clone());
+        flags
+            .set(Flag::ProactiveMessages, Some(FlagOverride { enabled: Some(false), ..Default::default() }))
+            .await
+            .unwrap();
+        flags
+            .set(
+                Flag::Reranking,
+                Some(FlagOverride { users: HashMap::from([(user, true)]), ..Default::default() }),
+            )
+            .await
+            .unwrap();
+        flags.set(Flag::Reranking, None).await.unwrap();
+
+        let restarted = FeatureFlags::default().with_storage(storage);
+        restarted.load().await.unwrap();
+        assert!(!restarted.enabled(Flag::ProactiveMessages, user));
+        assert!(!restarted.enabled(Flag::Reranking, user));
+        assert!(restarted.status(Flag::Reranking).flag_override.is_none());
+    }
+}
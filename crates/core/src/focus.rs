@@ -0,0 +1,316 @@
+// Focus mode. While a user is focused the notification router holds back
+// everything but security alerts and critical notifications; what it held
+// goes out as one digest when focus ends, by hand or when its time is up.
+// Sessions are persisted with what they hold, so a restart loses neither.
+use rusty_ai_common::{AssistantError, NotificationCategory, Result, UserPreferences};
+use chrono::{DateTime, Duration, Utc};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::intent_handlers::{mentions, padded_words};
+use crate::notifications::Notification;
+
+/// Longest focus session with an end time
+pub const MAX_FOCUS_MINUTES: u32 = 24 * 60;
+
+// Held notifications listed by name in the digest; the rest are counted
+const DIGEST_LISTED: usize = 20;
+
+/// A user's focus session and the notifications it held back
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FocusSession {
+    pub started_at: DateTime<Utc>,
+    /// None keeps focus on until the user turns it off
+    pub until: Option<DateTime<Utc>>,
+    /// Held notifications, oldest first
+    pub held: Vec<Notification>,
+    /// The preferences the last held notification was routed with; the
+    /// digest goes out with them
+    #[serde(default)]
+    pub preferences: Option<UserPreferences>,
+}
+
+impl FocusSession {
+    pub fn is_over(&self, now: DateTime<Utc>) -> bool {
+        self.until.is_some_and(|until| until <= now)
+    }
+
+    /// The notification the held ones are delivered as, None if nothing was held
+    pub fn digest(&self, user_id: Uuid) -> Option<Notification> {
+        if self.held.is_empty() {
+            return None;
+        }
+
+        let mut lines = vec![format!("{} notification(s) arrived while you were focused:", self.held.len())];
+        lines.extend(
+            self.held
+                .iter()
+                .take(DIGEST_LISTED)
+                .map(|n| format!("- {}: {}", category_label(n.category), n.title)),
+        );
+        if self.held.len() > DIGEST_LISTED {
+            lines.push(format!("- ... and {} more", self.held.len() - DIGEST_LISTED));
+        }
+        Some(Notification::new(
+            user_id,
+            NotificationCategory::FocusDigest,
+            "While you were focused",
+            lines.join("\n"),
+        ))
+    }
+}
+
+/// Focus state as the API and the profile report it
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FocusStatus {
+    pub active: bool,
+    pub started_at: Option<DateTime<Utc>>,
+    /// When focus ends by itself; None while inactive or open-ended
+    pub until: Option<DateTime<Utc>>,
+    /// Notifications held for the end-of-focus digest
+    pub held: usize,
+}
+
+impl FocusStatus {
+    pub fn inactive() -> Self {
+        Self { active: false, started_at: None, until: None, held: 0 }
+    }
+
+    fn of(session: &FocusSession) -> Self {
+        Self {
+            active: true,
+            started_at: Some(session.started_at),
+            until: session.until,
+            held: session.held.len(),
+        }
+    }
+}
+
+/// Body of `POST /me/focus`; without a duration focus lasts until turned off
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct StartFocusRequest {
+    #[serde(default)]
+    pub duration_minutes: Option<u32>,
+}
+
+/// What a chat message asks of focus mode
+#[derive(Debug, Clone, PartialEq)]
+pub enum FocusCommand {
+    /// Start, or change the end of a running session; None is open-ended
+    Start(Option<Duration>),
+    End,
+    Status,
+}
+
+impl FocusCommand {
+    /// Read "focus for two hours", "focus mode until I turn it off", "stop
+    /// focusing" or "am I in focus mode". None when focus is not mentioned
+    pub fn parse(text: &str) -> Option<Self> {
+        let padded = padded_words(text);
+        if !padded.contains(" focus") {
+            return None;
+        }
+
+        const END: &[&str] = &["stop", "end", "exit", "leave", "quit", "turn off", "disable", "cancel", "done"];
+        const STATUS: &[&str] = &["am i", "status", "how long"];
+        if END.iter().any(|phrase| mentions(&padded, phrase)) {
+            return Some(FocusCommand::End);
+        }
+        if STATUS.iter().any(|phrase| mentions(&padded, phrase)) {
+            return Some(FocusCommand::Status);
+        }
+        Some(FocusCommand::Start(parse_duration(&padded)))
+    }
+}
+
+// Durations such as "2 hours", "ninety minutes", "1 hour 30 minutes", "an
+// hour and a half" or "half an hour", added up
+fn parse_duration(padded: &str) -> Option<Duration> {
+    static AMOUNT: OnceLock<Regex> = OnceLock::new();
+    let amount = AMOUNT.get_or_init(|| {
+        Regex::new(r"\b(\d+|an?|one|two|three|four|five|six|seven|eight|nine|ten|eleven|twelve|fifteen|twenty|thirty|forty five|forty|sixty|ninety)\s*(hours?|hrs?|minutes?|mins?)\b")
+            .unwrap()
+    });
+
+    let padded = padded
+        .replace(" half an hour ", " 30 minutes ")
+        .replace(" half hour ", " 30 minutes ")
+        .replace(" and a half ", " 30 minutes ");
+
+    let mut minutes = 0i64;
+    for captures in amount.captures_iter(&padded) {
+        let count: i64 = match &captures[1] {
+            digits if digits.chars().all(|c| c.is_ascii_digit()) => digits.parse().ok()?,
+            word => number_word(word)?,
+        };
+        minutes += if captures[2].starts_with('h') { count * 60 } else { count };
+    }
+
+    (minutes > 0).then(|| Duration::minutes(minutes))
+}
+
+fn number_word(word: &str) -> Option<i64> {
+    const WORDS: &[(&str, i64)] = &[
+        ("a", 1), ("an", 1), ("one", 1), ("two", 2), ("three", 3), ("four", 4), ("five", 5), ("six", 6),
+        ("seven", 7), ("eight", 8), ("nine", 9), ("ten", 10), ("eleven", 11), ("twelve", 12),
+        ("fifteen", 15), ("twenty", 20), ("thirty", 30), ("forty", 40), ("forty five", 45),
+        ("sixty", 60), ("ninety", 90),
+    ];
+    WORDS.iter().find(|(name, _)| *name == word).map(|(_, value)| *value)
+}
+
+fn category_label(category: NotificationCategory) -> &'static str {
+    match category {
+        NotificationCategory::Reminder => "Reminder",
+        NotificationCategory::Briefing => "Briefing",
+        NotificationCategory::SecurityAlert => "Security alert",
+        NotificationCategory::PluginHealth => "Plugin health",
+        NotificationCategory::Proactive => "Suggestion",
+        NotificationCategory::KnowledgeDigest => "Knowledge digest",
+        NotificationCategory::FocusDigest => "Focus digest",
+        NotificationCategory::Maintenance => "Maintenance",
+    }
+}
+
+// Focus sessions by user, kept by the notification router
+pub(crate) struct FocusSessions {
+    sessions: Mutex<HashMap<Uuid, FocusSession>>,
+    store_path: Option<PathBuf>,
+}
+
+impl FocusSessions {
+    pub(crate) fn new(store_path: Option<PathBuf>) -> Self {
+        let sessions = store_path.as_deref().map(load_sessions).unwrap_or_default();
+        Self { sessions: Mutex::new(sessions), store_path }
+    }
+
+    /// Start focus, or move the end of the running session; what it already
+    /// holds stays held
+    pub(crate) fn start(&self, user_id: Uuid, now: DateTime<Utc>, until: Option<DateTime<Utc>>) -> Result<FocusStatus> {
+        let status = {
+            let mut sessions = self.sessions.lock().unwrap();
+            let session = sessions.entry(user_id).or_insert_with(|| FocusSession {
+                started_at: now,
+                until,
+                held: Vec::new(),
+                preferences: None,
+            });
+            session.until = until;
+            FocusStatus::of(session)
+        };
+        self.persist()?;
+        Ok(status)
+    }
+
+    /// Hold a notification if its user is focused. None when they are not,
+    /// including sessions that are over but not yet ended
+    pub(crate) fn hold(
+        &self,
+        notification: &Notification,
+        preferences: &UserPreferences,
+        now: DateTime<Utc>,
+    ) -> Result<Option<FocusStatus>> {
+        let status = {
+            let mut sessions = self.sessions.lock().unwrap();
+            match sessions.get_mut(&notification.user_id) {
+                Some(session) if !session.is_over(now) => {
+                    session.held.push(notification.clone());
+                    session.preferences = Some(preferences.clone());
+                    FocusStatus::of(session)
+                }
+                _ => return Ok(None),
+            }
+        };
+        self.persist()?;
+        Ok(Some(status))
+    }
+
+    pub(crate) fn end(&self, user_id: Uuid) -> Result<Option<FocusSession>> {
+        let session = self.sessions.lock().unwrap().remove(&user_id);
+        if session.is_some() {
+            self.persist()?;
+        }
+        Ok(session)
+    }
+
+    /// Remove and return the sessions whose time is up
+    pub(crate) fn take_over(&self, now: DateTime<Utc>) -> Result<Vec<(Uuid, FocusSession)>> {
+        let over: Vec<(Uuid, FocusSession)> = {
+            let mut sessions = self.sessions.lock().unwrap();
+            let ids: Vec<Uuid> = sessions.iter().filter(|(_, s)| s.is_over(now)).map(|(id, _)| *id).collect();
+            ids.into_iter().filter_map(|id| sessions.remove(&id).map(|s| (id, s))).collect()
+        };
+        if !over.is_empty() {
+            self.persist()?;
+        }
+        Ok(over)
+    }
+
+    pub(crate) fn status(&self, user_id: Uuid, now: DateTime<Utc>) -> FocusStatus {
+        match self.sessions.lock().unwrap().get(&user_id) {
+            Some(session) if !session.is_over(now) => FocusStatus::of(session),
+            _ => FocusStatus::inactive(),
+        }
+    }
+
+    fn persist(&self) -> Result<()> {
+        let path = match &self.store_path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+
+        let json = serde_json::to_vec_pretty(&*self.sessions.lock().unwrap())
+            .map_err(|e| AssistantError::Internal(format!("Failed to serialize focus sessions: {}", e)))?;
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| AssistantError::Internal(format!("Failed to create focus store: {}", e)))?;
+        }
+        std::fs::write(path, json).map_err(|e| AssistantError::Internal(format!("Failed to persist focus sessions: {}", e)))
+    }
+}
+
+fn load_sessions(path: &Path) -> HashMap<Uuid, FocusSession> {
+    match std::fs::read(path) {
+        Ok(bytes) => {
+            let sessions: HashMap<Uuid, FocusSession> = serde_json::from_slice(&bytes).unwrap_or_else(|e| {
+                warn!("Ignoring unreadable focus store {:?}: {}", path, e);
+                HashMap::new()
+            });
+            if !sessions.is_empty() {
+                info!("Loaded {} focus sessions", sessions.len());
+            }
+            sessions
+        }
+        Err(_) => HashMap::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_focus_commands_from_chat() {
+        assert_eq!(FocusCommand::parse("focus for two hours"), Some(FocusCommand::Start(Some(Duration::hours(2)))));
+        assert_eq!(
+            FocusCommand::parse("Focus mode for 1 hour 30 minutes please"),
+            Some(FocusCommand::Start(Some(Duration::minutes(90))))
+        );
+        assert_eq!(FocusCommand::parse("let me focus for half an hour"), Some(FocusCommand::Start(Some(Duration::minutes(30)))));
+        assert_eq!(
+            FocusCommand::parse("focus for an hour and a half"),
+            Some(FocusCommand::Start(Some(Duration::minutes(90))))
+        );
+        assert_eq!(FocusCommand::parse("focus mode until I turn it off"), Some(FocusCommand::Start(None)));
+        assert_eq!(FocusCommand::parse("stop focusing"), Some(FocusCommand::End));
+        assert_eq!(FocusCommand::parse("turn off focus mode"), Some(FocusCommand::End));
+        assert_eq!(FocusCommand::parse("am I in focus mode?"), Some(FocusCommand::Status));
+        assert_eq!(FocusCommand::parse("turn off notifications after 10pm"), None);
+    }
+}
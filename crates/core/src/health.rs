@@ -0,0 +1,495 @@
+use rusty_ai_common::{AssistantError, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::future::join_all;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::future::Future;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+use crate::services::ServiceStatus;
+
+pub const DEFAULT_PROBE_TIMEOUT_MS: u64 = 2_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ComponentId {
+    Storage,
+    VectorStore,
+    LlmProvider,
+    Tts,
+    Stt,
+    Plugins,
+    JobQueue,
+    Schedulers,
+}
+
+impl ComponentId {
+    pub const ALL: [ComponentId; 8] = [
+        ComponentId::Storage,
+        ComponentId::VectorStore,
+        ComponentId::LlmProvider,
+        ComponentId::Tts,
+        ComponentId::Stt,
+        ComponentId::Plugins,
+        ComponentId::JobQueue,
+        ComponentId::Schedulers,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ComponentId::Storage => "storage",
+            ComponentId::VectorStore => "vector_store",
+            ComponentId::LlmProvider => "llm_provider",
+            ComponentId::Tts => "tts",
+            ComponentId::Stt => "stt",
+            ComponentId::Plugins => "plugins",
+            ComponentId::JobQueue => "job_queue",
+            ComponentId::Schedulers => "schedulers",
+        }
+    }
+}
+
+impl fmt::Display for ComponentId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ComponentId {
+    type Err = AssistantError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        ComponentId::ALL
+            .into_iter()
+            .find(|id| id.as_str() == s.trim())
+            .ok_or_else(|| AssistantError::Configuration(format!("Unknown health component: {}", s)))
+    }
+}
+
+/// Ordered from best to worst, so the overall status is the maximum
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthStatus {
+    Healthy,
+    Degraded,
+    Unhealthy,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ComponentHealth {
+    pub status: HealthStatus,
+    pub latency_ms: u64,
+    pub last_error: Option<String>,
+    pub last_success_at: Option<DateTime<Utc>>,
+    pub dependencies: Vec<ComponentId>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HealthReport {
+    pub status: HealthStatus,
+    pub ready: bool,
+    /// Critical components that are unhealthy, missing, or depend on
+    /// something unhealthy
+    pub not_ready: Vec<ComponentId>,
+    pub components: BTreeMap<ComponentId, ComponentHealth>,
+    /// How each core service came up; anything short of ready at startup
+    /// keeps the overall status at least degraded until the next restart
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub startup: Vec<ServiceStatus>,
+    pub checked_at: DateTime<Utc>,
+}
+
+#[async_trait]
+pub trait HealthProbe: Send + Sync {
+    /// `Ok(Degraded)` for a component that works but is impaired; an error
+    /// marks it unhealthy and becomes its `last_error`
+    async fn probe(&self) -> Result<HealthStatus>;
+}
+
+/// Adapts an async closure into a probe
+pub struct FnProbe<F>(pub F);
+
+#[async_trait]
+impl<F, Fut> HealthProbe for FnProbe<F>
+where
+    F: Fn() -> Fut + Send + Sync,
+    Fut: Future<Output = Result<HealthStatus>> + Send,
+{
+    async fn probe(&self) -> Result<HealthStatus> {
+        (self.0)().await
+    }
+}
+
+#[derive(Clone)]
+enum Probe {
+    Active(Arc<dyn HealthProbe>),
+    // Background loops have nothing to call; they report in, and go
+    // unhealthy when they stop doing so
+    Heartbeat(Duration),
+}
+
+#[derive(Clone)]
+struct Registration {
+    id: ComponentId,
+    dependencies: Vec<ComponentId>,
+    probe: Probe,
+}
+
+#[derive(Debug, Default, Clone)]
+struct Outcome {
+    last_success_at: Option<DateTime<Utc>>,
+    last_error: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct HealthConfig {
+    pub probe_timeout: Duration,
+    /// Components that must be available for the service to be ready
+    pub critical: Vec<ComponentId>,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        Self {
+            probe_timeout: Duration::from_millis(DEFAULT_PROBE_TIMEOUT_MS),
+            critical: vec![ComponentId::Storage],
+        }
+    }
+}
+
+// Components register probes here during startup; every health surface
+// (/health, /health/ready, the admin overview) reads the same graph
+pub struct HealthRegistry {
+    config: HealthConfig,
+    registrations: RwLock<Vec<Registration>>,
+    outcomes: Mutex<HashMap<ComponentId, Outcome>>,
+    startup: RwLock<Vec<ServiceStatus>>,
+}
+
+impl HealthRegistry {
+    pub fn new(config: HealthConfig) -> Self {
+        Self {
+            config,
+            registrations: RwLock::new(Vec::new()),
+            outcomes: Mutex::new(HashMap::new()),
+            startup: RwLock::new(Vec::new()),
+        }
+    }
+
+    /// Register (or replace) the probe for a component
+    pub fn register(&self, id: ComponentId, dependencies: &[ComponentId], probe: impl HealthProbe + 'static) {
+        self.insert(Registration {
+            id,
+            dependencies: dependencies.to_vec(),
+            probe: Probe::Active(Arc::new(probe)),
+        });
+    }
+
+    /// Register a component that reports in through `report_success` and is
+    /// unhealthy once it has been silent for longer than `max_age`
+    pub fn register_heartbeat(&self, id: ComponentId, dependencies: &[ComponentId], max_age: Duration) {
+        self.insert(Registration {
+            id,
+            dependencies: dependencies.to_vec(),
+            probe: Probe::Heartbeat(max_age),
+        });
+    }
+
+    fn insert(&self, registration: Registration) {
+        let mut registrations = self.registrations.write().unwrap();
+        registrations.retain(|r| r.id != registration.id);
+        registrations.push(registration);
+    }
+
+    pub fn report_success(&self, id: ComponentId) {
+        self.outcomes.lock().unwrap().entry(id).or_default().last_success_at = Some(Utc::now());
+    }
+
+    pub fn report_failure(&self, id: ComponentId, error: impl ToString) {
+        self.outcomes.lock().unwrap().entry(id).or_default().last_error = Some(error.to_string());
+    }
+
+    /// Record how the core's services came up, shown with every report
+    pub fn set_startup(&self, statuses: &[ServiceStatus]) {
+        *self.startup.write().unwrap() = statuses.to_vec();
+    }
+
+    /// Run every probe concurrently, each under its own timeout
+    pub async fn check_all(&self) -> HealthReport {
+        let registrations = self.registrations.read().unwrap().clone();
+        let results = join_all(registrations.iter().map(|r| self.run_probe(r))).await;
+
+        let mut components = BTreeMap::new();
+        for (registration, (status, latency_ms)) in registrations.iter().zip(results) {
+            let outcome = self.outcomes.lock().unwrap().get(&registration.id).cloned().unwrap_or_default();
+            components.insert(
+                registration.id,
+                ComponentHealth {
+                    status,
+                    latency_ms,
+                    last_error: outcome.last_error,
+                    last_success_at: outcome.last_success_at,
+                    dependencies: registration.dependencies.clone(),
+                },
+            );
+        }
+
+        // A working component on top of a failed one is only partly working
+        let unhealthy: Vec<ComponentId> = components
+            .iter()
+            .filter(|(_, health)| health.status == HealthStatus::Unhealthy)
+            .map(|(id, _)| *id)
+            .collect();
+        for health in components.values_mut() {
+            if health.status == HealthStatus::Healthy && health.dependencies.iter().any(|d| unhealthy.contains(d)) {
+                health.status = HealthStatus::Degraded;
+            }
+        }
+
+        let not_ready: Vec<ComponentId> = self
+            .config
+            .critical
+            .iter()
+            .copied()
+            .filter(|id| !is_available(*id, &components, &mut Vec::new()))
+            .collect();
+
+        let startup = self.startup.read().unwrap().clone();
+        let startup_status = if startup.iter().all(ServiceStatus::is_ready) {
+            HealthStatus::Healthy
+        } else {
+            HealthStatus::Degraded
+        };
+
+        HealthReport {
+            status: components
+                .values()
+                .map(|h| h.status)
+                .chain([startup_status])
+                .max()
+                .unwrap_or(HealthStatus::Healthy),
+            ready: not_ready.is_empty(),
+            not_ready,
+            components,
+            startup,
+            checked_at: Utc::now(),
+        }
+    }
+
+    async fn run_probe(&self, registration: &Registration) -> (HealthStatus, u64) {
+        let started = Instant::now();
+        let status = match &registration.probe {
+            Probe::Active(probe) => match tokio::time::timeout(self.config.probe_timeout, probe.probe()).await {
+                Ok(Ok(status)) => {
+                    self.report_success(registration.id);
+                    status
+                }
+                Ok(Err(e)) => {
+                    warn!("Health probe for {} failed: {}", registration.id, e);
+                    self.report_failure(registration.id, e);
+                    HealthStatus::Unhealthy
+                }
+                Err(_) => {
+                    warn!("Health probe for {} timed out", registration.id);
+                    self.report_failure(
+                        registration.id,
+                        format!("Probe timed out after {}ms", self.config.probe_timeout.as_millis()),
+                    );
+                    HealthStatus::Unhealthy
+                }
+            },
+            Probe::Heartbeat(max_age) => {
+                let last_success = self
+                    .outcomes
+                    .lock()
+                    .unwrap()
+                    .get(&registration.id)
+                    .and_then(|o| o.last_success_at);
+                let max_age = chrono::Duration::from_std(*max_age).unwrap_or_else(|_| chrono::Duration::days(365));
+                match last_success {
+                    // Not started yet; not a failure
+                    None => HealthStatus::Degraded,
+                    Some(at) if Utc::now() - at <= max_age => HealthStatus::Healthy,
+                    Some(at) => {
+                        self.report_failure(
+                            registration.id,
+                            format!("No heartbeat since {}", at.to_rfc3339()),
+                        );
+                        HealthStatus::Unhealthy
+                    }
+                }
+            }
+        };
+
+        (status, started.elapsed().as_millis() as u64)
+    }
+}
+
+// Available means registered, not unhealthy, and the same for everything it
+// depends on. `visiting` guards against dependency cycles.
+fn is_available(id: ComponentId, components: &BTreeMap<ComponentId, ComponentHealth>, visiting: &mut Vec<ComponentId>) -> bool {
+    if visiting.contains(&id) {
+        return true;
+    }
+    let Some(health) = components.get(&id) else {
+        return false;
+    };
+    if health.status == HealthStatus::Unhealthy {
+        return false;
+    }
+
+    visiting.push(id);
+    let available = health
+        .dependencies
+        .iter()
+        .all(|dep| !components.contains_key(dep) || is_available(*dep, components, visiting));
+    visiting.pop();
+    available
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixed(status: HealthStatus) -> impl HealthProbe {
+        FnProbe(move || async move { Ok(status) })
+    }
+
+    fn failing(message: &'static str) -> impl HealthProbe {
+        FnProbe(move || async move { Err(AssistantError::Internal(message.to_string())) })
+    }
+
+    fn registry(critical: &[ComponentId]) -> HealthRegistry {
+        HealthRegistry::new(HealthConfig {
+            probe_timeout: Duration::from_millis(50),
+            critical: critical.to_vec(),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_ready_when_critical_components_are_up() {
+        let registry = registry(&[ComponentId::Storage]);
+        registry.register(ComponentId::Storage, &[], fixed(HealthStatus::Healthy));
+        registry.register(ComponentId::Tts, &[], failing("ElevenLabs unreachable"));
+
+        let report = registry.check_all().await;
+        assert!(report.ready);
+        // Non-critical failures still show in the overall status
+        assert_eq!(report.status, HealthStatus::Unhealthy);
+        let tts = &report.components[&ComponentId::Tts];
+        assert_eq!(tts.last_error.as_deref(), Some("Internal error: ElevenLabs unreachable"));
+        assert!(tts.last_success_at.is_none());
+        assert!(report.components[&ComponentId::Storage].last_success_at.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_unhealthy_dependency_makes_critical_component_not_ready() {
+        let registry = registry(&[ComponentId::JobQueue]);
+        registry.register(ComponentId::Storage, &[], failing("database is locked"));
+        registry.register(ComponentId::JobQueue, &[ComponentId::Storage], fixed(HealthStatus::Healthy));
+
+        let report = registry.check_all().await;
+        assert!(!report.ready);
+        assert_eq!(report.not_ready, vec![ComponentId::JobQueue]);
+        assert_eq!(report.components[&ComponentId::JobQueue].status, HealthStatus::Degraded);
+    }
+
+    #[tokio::test]
+    async fn test_degraded_critical_component_is_still_ready() {
+        let registry = registry(&[ComponentId::Storage, ComponentId::LlmProvider]);
+        registry.register(ComponentId::Storage, &[], fixed(HealthStatus::Healthy));
+        registry.register(ComponentId::LlmProvider, &[], fixed(HealthStatus::Degraded));
+
+        let report = registry.check_all().await;
+        assert!(report.ready);
+        assert_eq!(report.status, HealthStatus::Degraded);
+    }
+
+    #[tokio::test]
+    async fn test_missing_critical_component_is_not_ready() {
+        let registry = registry(&[ComponentId::Storage, ComponentId::VectorStore]);
+        registry.register(ComponentId::Storage, &[], fixed(HealthStatus::Healthy));
+
+        let report = registry.check_all().await;
+        assert_eq!(report.not_ready, vec![ComponentId::VectorStore]);
+    }
+
+    #[tokio::test]
+    async fn test_probes_run_concurrently_with_individual_timeouts() {
+        let registry = registry(&[ComponentId::Storage]);
+        registry.register(ComponentId::Storage, &[], fixed(HealthStatus::Healthy));
+        for id in [ComponentId::VectorStore, ComponentId::Stt, ComponentId::Tts] {
+            registry.register(
+                id,
+                &[],
+                FnProbe(|| async {
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                    Ok(HealthStatus::Healthy)
+                }),
+            );
+        }
+
+        let started = Instant::now();
+        let report = registry.check_all().await;
+        // Three hung probes cost one timeout, not three
+        assert!(started.elapsed() < Duration::from_millis(500));
+        assert!(report.ready);
+        assert!(report.components[&ComponentId::Stt]
+            .last_error
+            .as_deref()
+            .unwrap()
+            .contains("timed out"));
+    }
+
+    #[tokio::test]
+    async fn test_heartbeat_components() {
+        let registry = registry(&[ComponentId::Schedulers]);
+        registry.register_heartbeat(ComponentId::Schedulers, &[], Duration::from_secs(60));
+
+        let report = registry.check_all().await;
+        assert_eq!(report.components[&ComponentId::Schedulers].status, HealthStatus::Degraded);
+        assert!(report.ready);
+
+        registry.report_success(ComponentId::Schedulers);
+        let report = registry.check_all().await;
+        assert_eq!(report.components[&ComponentId::Schedulers].status, HealthStatus::Healthy);
+    }
+
+    #[tokio::test]
+    async fn test_startup_failures_degrade_the_report() {
+        use crate::services::{ServiceState, ServiceStatus};
+
+        let registry = registry(&[ComponentId::Storage]);
+        registry.register(ComponentId::Storage, &[], fixed(HealthStatus::Healthy));
+        let status = |name: &str, state| ServiceStatus {
+            name: name.to_string(),
+            state,
+            optional: true,
+            dependencies: vec![],
+            duration_ms: 3,
+        };
+        registry.set_startup(&[
+            status("storage", ServiceState::Ready),
+            status("knowledge_digests", ServiceState::Failed { error: "no store".to_string() }),
+        ]);
+
+        let report = registry.check_all().await;
+        assert!(report.ready);
+        assert_eq!(report.status, HealthStatus::Degraded);
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["startup"][1]["state"], "failed");
+        assert_eq!(json["startup"][1]["error"], "no store");
+    }
+
+    #[test]
+    fn test_component_ids_parse() {
+        assert_eq!("job_queue".parse::<ComponentId>().unwrap(), ComponentId::JobQueue);
+        assert!("database".parse::<ComponentId>().is_err());
+        assert_eq!(serde_json::to_string(&ComponentId::LlmProvider).unwrap(), "\"llm_provider\"");
+    }
+}
@@ -1,11 +1,14 @@
 use rusty_ai_common::{Result, AssistantError, Intent, UserContext};
 use std::collections::HashMap;
+use std::sync::{Arc, OnceLock};
 use regex::Regex;
 use tracing::{debug, info};
+use crate::entities::{self, EntityExtractor, Gazetteer};
 
 pub struct IntentClassifier {
     patterns: Vec<IntentPattern>,
     fallback_confidence_threshold: f32,
+    entity_extractor: EntityExtractor,
 }
 
 #[derive(Debug, Clone)]
@@ -39,11 +42,38 @@ pub struct ClassificationResult {
     pub extracted_entities: HashMap<String, String>,
 }
 
+impl ClassificationResult {
+    /// Duration entity, e.g. "in 45 minutes"
+    pub fn duration(&self) -> Option<chrono::Duration> {
+        self.extracted_entities
+            .get(entities::DURATION)
+            .and_then(|d| entities::parse_iso_duration(d))
+    }
+
+    /// Time of day entity in 24h form, e.g. "at 6pm"
+    pub fn time_of_day(&self) -> Option<chrono::NaiveTime> {
+        self.extracted_entities
+            .get(entities::TIME_OF_DAY)
+            .and_then(|t| chrono::NaiveTime::parse_from_str(t, "%H:%M").ok())
+    }
+
+    pub fn quantity(&self) -> Option<f64> {
+        self.extracted_entities
+            .get(entities::QUANTITY)
+            .and_then(|q| q.parse().ok())
+    }
+
+    pub fn location(&self) -> Option<&str> {
+        self.extracted_entities.get(entities::LOCATION).map(String::as_str)
+    }
+}
+
 impl IntentClassifier {
     pub fn new() -> Self {
         let mut classifier = Self {
             patterns: Vec::new(),
             fallback_confidence_threshold: 0.3,
+            entity_extractor: EntityExtractor::new(),
         };
         
         classifier.initialize_default_patterns();
@@ -54,6 +84,7 @@ impl IntentClassifier {
         let mut classifier = Self {
             patterns: Vec::new(),
             fallback_confidence_threshold: threshold,
+            entity_extractor: EntityExtractor::new(),
         };
         
         classifier.initialize_default_patterns();
@@ -102,9 +133,11 @@ impl IntentClassifier {
             intent_type: IntentType::TaskManagement,
             patterns: vec![
                 Regex::new(r"(create|add|new) .* (task|todo|reminder)").unwrap(),
+                Regex::new(r"^remind me\b").unwrap(),
                 Regex::new(r"(complete|finish|done) .* (task|todo)").unwrap(),
                 Regex::new(r"(list|show|display) .* (tasks|todos|reminders)").unwrap(),
                 Regex::new(r"(schedule|plan|organize)").unwrap(),
+                Regex::new(r"^(start|begin|resume|stop|pause) (working|work|the timer|timer|tracking)\b").unwrap(),
             ],
             keywords: vec!["task", "todo", "reminder", "schedule", "complete", "finish", "list"]
                 .iter().map(|s| s.to_string()).collect(),
@@ -131,6 +164,10 @@ impl IntentClassifier {
                 Regex::new(r"(change|update|modify|set) .* (settings|preferences|configuration)").unwrap(),
                 Regex::new(r"(configure|setup|adjust)").unwrap(),
                 Regex::new(r"(enable|disable|turn on|turn off)").unwrap(),
+                Regex::new(r"\b(switch|change|set|make|use)\b.*\b(voice|language|timezone|time zone|speed|quiet hours|notifications?)\b").unwrap(),
+                Regex::new(r"^(speak|talk) (faster|slower|more slowly|more quickly)\b").unwrap(),
+                Regex::new(r"\b(quiet hours|do not disturb)\b").unwrap(),
+                Regex::new(r"^(let me |i need to |i want to |time to |start |turn on )?focus\b.*\b(for|until)\b|\bfocus mode\b|\b(stop|end|exit|leave|quit|cancel) focus(ing)?\b").unwrap(),
             ],
             keywords: vec!["settings", "preferences", "configure", "enable", "disable"]
                 .iter().map(|s| s.to_string()).collect(),
@@ -198,7 +235,27 @@ impl IntentClassifier {
         self.patterns.sort_by(|a, b| b.priority.cmp(&a.priority));
     }
 
+    /// Replace the gazetteer used to recognize place names
+    pub fn set_gazetteer(&mut self, gazetteer: Arc<dyn Gazetteer>) {
+        self.entity_extractor = EntityExtractor::with_gazetteer(gazetteer);
+    }
+
     pub fn classify(&self, input: &str, context: Option<&UserContext>) -> ClassificationResult {
+        let mut result = self.classify_intent(input, context);
+        
+        // Durations, times, quantities and locations apply to every intent;
+        // time formats follow the user's locale
+        let locale = context.map(|c| c.preferences.language.as_str()).unwrap_or("en");
+        let typed_entities = self.entity_extractor.extract(input, locale);
+        if let Some(task_name) = result.extracted_entities.get_mut("task_name") {
+            *task_name = self.entity_extractor.strip_temporal_phrases(task_name);
+        }
+        result.extracted_entities.extend(typed_entities);
+        
+        result
+    }
+
+    fn classify_intent(&self, input: &str, context: Option<&UserContext>) -> ClassificationResult {
         let normalized_input = input.to_lowercase().trim().to_string();
         debug!("Classifying input: '{}'", input);
 
@@ -390,9 +447,18 @@ impl IntentClassifier {
                 if let Some(task_name) = self.extract_task_name(input) {
                     entities.insert("task_name".to_string(), task_name);
                 }
+                if input.starts_with("remind me") {
+                    entities.insert("task_type".to_string(), "reminder".to_string());
+                }
                 if let Some(due_date) = self.extract_date(input) {
                     entities.insert("due_date".to_string(), due_date);
                 }
+                if let Some((action, task)) = self.extract_timer_request(input) {
+                    entities.insert("timer".to_string(), action.to_string());
+                    if let Some(task) = task {
+                        entities.insert("timer_task".to_string(), task);
+                    }
+                }
             },
             IntentType::DocumentSearch => {
                 if let Some(search_term) = self.extract_search_term(input) {
@@ -443,6 +509,7 @@ impl IntentClassifier {
     fn extract_task_name(&self, input: &str) -> Option<String> {
         // Look for patterns like "create task [name]" or "add todo [name]"
         let patterns = [
+            Regex::new(r"^remind me\s+(?:.*?\s)?to\s+(.+)").unwrap(),
             Regex::new(r"(?:create|add|new)\s+(?:task|todo|reminder)\s+(.+)").unwrap(),
             Regex::new(r"(?:task|todo|reminder):\s*(.+)").unwrap(),
         ];
@@ -458,6 +525,25 @@ impl IntentClassifier {
         None
     }
 
+    // "start working on the tax return" -> ("start", Some("the tax return"));
+    // "stop the timer" names no task
+    fn extract_timer_request(&self, input: &str) -> Option<(&'static str, Option<String>)> {
+        static TIMER_REQUEST: OnceLock<Regex> = OnceLock::new();
+        let pattern = TIMER_REQUEST.get_or_init(|| {
+            Regex::new(
+                r"^(start|begin|resume|stop|pause)\s+(?:(?:working|work)(?:\s+on)?|(?:the\s+)?(?:timer|tracking)(?:\s+(?:on|for))?)(?:\s+(.+?))?[.!]?$",
+            )
+            .unwrap()
+        });
+        let captures = pattern.captures(input.trim())?;
+        let action = match &captures[1] {
+            "stop" | "pause" => "stop",
+            _ => "start",
+        };
+        let task = captures.get(2).map(|m| m.as_str().trim().to_string()).filter(|t| !t.is_empty());
+        Some((action, task))
+    }
+
     fn extract_search_term(&self, input: &str) -> Option<String> {
         let patterns = [
             Regex::new(r"(?:find|search|look for)\s+(.+)").unwrap(),
@@ -555,10 +641,12 @@ mod tests {
                     enabled: true,
                     channels: vec![],
                     quiet_hours: None,
+                    routing: Default::default(),
+                    knowledge_digest: Default::default(),
                 },
             },
             active_plugins: vec![],
-            conversation_history: vec![],
+            conversation_history: Default::default(),
         }
     }
 
@@ -597,6 +685,24 @@ mod tests {
         assert!(result.extracted_entities.contains_key("task_name"));
     }
 
+    #[test]
+    fn test_timer_command_classification() {
+        let classifier = IntentClassifier::new();
+        let result = classifier.classify("Start working on the tax return", None);
+
+        match result.intent {
+            Intent::Command { action, .. } => assert_eq!(action, "task"),
+            _ => panic!("Expected command intent"),
+        }
+        assert_eq!(result.extracted_entities.get("timer").map(String::as_str), Some("start"));
+        assert_eq!(result.extracted_entities.get("timer_task").map(String::as_str), Some("the tax return"));
+        assert!(!result.extracted_entities.contains_key("task_name"));
+
+        let result = classifier.classify("stop the timer", None);
+        assert_eq!(result.extracted_entities.get("timer").map(String::as_str), Some("stop"));
+        assert!(!result.extracted_entities.contains_key("timer_task"));
+    }
+
     #[test]
     fn test_document_search_classification() {
         let classifier = IntentClassifier::new();
@@ -630,6 +736,63 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_reminder_entities_are_normalized() {
+        let classifier = IntentClassifier::new();
+        let cases = [
+            ("remind me in 45 minutes to stretch", "stretch", Some("PT45M"), None),
+            ("Remind me to call mom at 6pm", "call mom", None, Some("18:00")),
+            ("remind me to water the plants in 2 hours", "water the plants", Some("PT2H"), None),
+        ];
+
+        for (input, task_name, duration, time) in cases {
+            let result = classifier.classify(input, None);
+            assert!(matches!(result.intent, Intent::Command { ref action, .. } if action == "task"), "{}", input);
+            assert_eq!(result.extracted_entities.get("task_name").map(String::as_str), Some(task_name), "{}", input);
+            assert_eq!(result.extracted_entities.get("task_type").map(String::as_str), Some("reminder"));
+            assert_eq!(result.extracted_entities.get(entities::DURATION).map(String::as_str), duration, "{}", input);
+            assert_eq!(result.extracted_entities.get(entities::TIME_OF_DAY).map(String::as_str), time, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_time_entities_follow_user_locale() {
+        let classifier = IntentClassifier::new();
+        let mut context = create_test_context();
+
+        let result = classifier.classify("remind me to leave at 6", Some(&context));
+        assert_eq!(result.time_of_day(), chrono::NaiveTime::from_hms_opt(18, 0, 0));
+
+        context.preferences.language = "de-DE".to_string();
+        let result = classifier.classify("remind me to leave at 6", Some(&context));
+        assert_eq!(result.time_of_day(), chrono::NaiveTime::from_hms_opt(6, 0, 0));
+    }
+
+    #[test]
+    fn test_location_entity_keeps_original_case() {
+        let classifier = IntentClassifier::new();
+        let result = classifier.classify("search my notes from the Berlin trip", None);
+
+        assert_eq!(result.location(), Some("Berlin"));
+        assert!(result.extracted_entities.contains_key("search_term"));
+    }
+
+    #[test]
+    fn test_setting_changes_classify_as_settings() {
+        let classifier = IntentClassifier::new();
+        for input in [
+            "Switch to the female voice",
+            "speak faster",
+            "disable notifications after 10pm",
+            "set quiet hours from 10pm to 7am",
+            "focus for two hours",
+            "stop focusing",
+        ] {
+            let result = classifier.classify(input, None);
+            assert!(matches!(result.intent, Intent::Command { ref action, .. } if action == "settings"), "{}", input);
+        }
+    }
+
     #[test]
     fn test_entity_extraction() {
         let classifier = IntentClassifier::new();
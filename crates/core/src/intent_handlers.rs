@@ -0,0 +1,1965 @@
+use rusty_ai_common::api::{SourceRef, SuggestedAction};
+use rusty_ai_common::scratchpad::Scratchpads;
+use rusty_ai_common::{
+    Document, Intent, NotificationChannel, QuietHours, Result, Task, TaskStatus, UserContext, UserPreferences,
+};
+use async_trait::async_trait;
+use chrono::NaiveTime;
+use regex::Regex;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock, RwLock as StdRwLock};
+use tokio::sync::RwLock;
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+use crate::activity::{ActionKind, PerformedAction, UndoStep};
+use crate::context_manager::ContextManager;
+use crate::entities;
+use crate::events::{AssistantEvent, EventBus};
+use crate::events::UserAction;
+use crate::flags::{FeatureFlags, Flag};
+use crate::focus::{FocusCommand, MAX_FOCUS_MINUTES};
+use crate::intent::ClassificationResult;
+use crate::notifications::NotificationRouter;
+use crate::onboarding::OnboardingTracker;
+use crate::plugin_manager::PluginManager;
+use crate::storage::Storage;
+use crate::time_tracking::{self, TimerStart};
+
+// Built-ins that act on an explicit command run before plugins; plugins run
+// before the generic document search so a specialised plugin wins a query
+pub const PRIORITY_COMMAND: i32 = 100;
+pub const PRIORITY_CONVERSATIONAL: i32 = 90;
+pub const PRIORITY_PLUGIN: i32 = 60;
+pub const PRIORITY_SEARCH: i32 = 50;
+
+const SEARCH_LIMIT: usize = 5;
+const SNIPPET_CHARS: usize = 100;
+
+// Information topics answered by the conversational handler, never searched
+const CONVERSATIONAL_TOPICS: &[&str] = &["greeting", "goodbye", "help"];
+
+/// A classified message as seen by the handlers
+#[derive(Debug, Clone)]
+pub struct IntentRequest {
+    pub intent: Intent,
+    pub entities: HashMap<String, String>,
+    /// The user's original wording, when the caller has it
+    pub message: Option<String>,
+}
+
+impl IntentRequest {
+    pub fn from_intent(intent: Intent) -> Self {
+        Self {
+            intent,
+            entities: HashMap::new(),
+            message: None,
+        }
+    }
+
+    pub fn from_classification(classification: &ClassificationResult) -> Self {
+        Self {
+            intent: classification.intent.clone(),
+            entities: classification.extracted_entities.clone(),
+            message: None,
+        }
+    }
+
+    pub fn with_message(mut self, message: impl Into<String>) -> Self {
+        self.message = Some(message.into());
+        self
+    }
+
+    pub fn entity(&self, key: &str) -> Option<&str> {
+        self.entities.get(key).map(String::as_str)
+    }
+
+    pub fn command_action(&self) -> Option<&str> {
+        match &self.intent {
+            Intent::Command { action, .. } => Some(action),
+            _ => None,
+        }
+    }
+
+    pub fn information_topic(&self) -> Option<&str> {
+        match &self.intent {
+            Intent::Information { topic } => Some(topic),
+            _ => None,
+        }
+    }
+
+    /// The text to act on: the original message, or what the intent carries
+    pub fn text(&self) -> String {
+        if let Some(message) = &self.message {
+            return message.clone();
+        }
+        match &self.intent {
+            Intent::Query { query } => query.clone(),
+            Intent::Command { action, parameters } => parameters.first().cloned().unwrap_or_else(|| action.clone()),
+            Intent::Information { topic } => topic.clone(),
+            Intent::Unknown => String::new(),
+        }
+    }
+}
+
+/// What a handler produced; every entry point returns the actions and
+/// sources alongside the text
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct HandlerOutcome {
+    pub response_text: String,
+    pub actions: Vec<SuggestedAction>,
+    pub sources: Vec<SourceRef>,
+    /// Side effects of handling the request, for the activity log
+    pub performed: Vec<PerformedAction>,
+}
+
+impl HandlerOutcome {
+    pub fn text(response_text: impl Into<String>) -> Self {
+        Self {
+            response_text: response_text.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_action(mut self, action_type: &str, label: &str, description: &str) -> Self {
+        self.actions.push(SuggestedAction {
+            action_type: action_type.to_string(),
+            label: label.to_string(),
+            description: description.to_string(),
+            parameters: serde_json::json!({}),
+        });
+        self
+    }
+
+    pub fn with_performed(mut self, action: PerformedAction) -> Self {
+        self.performed.push(action);
+        self
+    }
+}
+
+/// A command the palette runs on a handler directly, skipping classification
+#[derive(Debug, Clone, Serialize)]
+pub struct CommandSpec {
+    /// Without the leading slash
+    pub name: String,
+    pub description: String,
+    pub parameters: Vec<ParameterSpec>,
+    pub required_permission: Option<String>,
+}
+
+impl CommandSpec {
+    pub fn new(name: &str, description: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            description: description.to_string(),
+            parameters: Vec::new(),
+            required_permission: None,
+        }
+    }
+
+    pub fn parameter(mut self, name: &str, kind: ParameterKind, required: bool, description: &str) -> Self {
+        self.parameters.push(ParameterSpec {
+            name: name.to_string(),
+            description: description.to_string(),
+            kind,
+            required,
+        });
+        self
+    }
+
+    pub fn permission(mut self, permission: &str) -> Self {
+        self.required_permission = Some(permission.to_string());
+        self
+    }
+
+    /// JSON schema of the parameters object, in the form plugins declare
+    /// their function inputs in
+    pub fn input_schema(&self) -> serde_json::Value {
+        let properties: serde_json::Map<String, serde_json::Value> = self
+            .parameters
+            .iter()
+            .map(|p| (p.name.clone(), p.schema()))
+            .collect();
+        let required: Vec<&str> = self.parameters.iter().filter(|p| p.required).map(|p| p.name.as_str()).collect();
+        serde_json::json!({
+            "type": "object",
+            "properties": properties,
+            "required": required,
+            "additionalProperties": false,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ParameterSpec {
+    pub name: String,
+    pub description: String,
+    pub kind: ParameterKind,
+    pub required: bool,
+}
+
+impl ParameterSpec {
+    fn schema(&self) -> serde_json::Value {
+        let mut schema = serde_json::json!({ "type": "string", "description": self.description });
+        match self.kind {
+            ParameterKind::Text => {}
+            ParameterKind::TimeOfDay => schema["format"] = "time".into(),
+            ParameterKind::Duration => schema["format"] = "duration".into(),
+        }
+        schema
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ParameterKind {
+    Text,
+    /// 24-hour `HH:MM` in the user's timezone
+    TimeOfDay,
+    /// ISO 8601, e.g. `PT45M`
+    Duration,
+}
+
+#[async_trait]
+pub trait IntentHandler: Send + Sync {
+    fn name(&self) -> &str;
+
+    fn can_handle(&self, request: &IntentRequest) -> bool;
+
+    /// `Ok(None)` passes the request on to the next handler
+    async fn handle(&self, request: &IntentRequest, context: &UserContext) -> Result<Option<HandlerOutcome>>;
+
+    /// Commands the palette may run on this handler directly
+    fn commands(&self) -> Vec<CommandSpec> {
+        Vec::new()
+    }
+
+    /// The request a palette run of `command` stands for, given its
+    /// validated parameters. By default the parameters become the entities
+    /// of a command intent of the same name
+    fn command_request(&self, command: &str, parameters: HashMap<String, String>) -> IntentRequest {
+        IntentRequest {
+            intent: Intent::Command { action: command.to_string(), parameters: Vec::new() },
+            entities: parameters,
+            message: None,
+        }
+    }
+}
+
+/// Text generation used for intents no handler answered
+#[async_trait]
+pub trait CompletionProvider: Send + Sync {
+    async fn complete(&self, prompt: &str, context: &UserContext) -> Result<String>;
+}
+
+struct RegisteredHandler {
+    priority: i32,
+    handler: Arc<dyn IntentHandler>,
+}
+
+// Handlers are tried highest priority first, in registration order within a
+// priority; the first to return an outcome answers. The fallback answers
+// whatever is left
+pub struct IntentHandlerRegistry {
+    handlers: StdRwLock<Vec<RegisteredHandler>>,
+    fallback: StdRwLock<Arc<dyn IntentHandler>>,
+    scratchpads: Arc<Scratchpads>,
+    onboarding: Option<Arc<OnboardingTracker>>,
+    focus: Option<Arc<NotificationRouter>>,
+}
+
+impl IntentHandlerRegistry {
+    pub fn new() -> Self {
+        Self::with_scratchpads(Arc::default())
+    }
+
+    pub fn with_scratchpads(scratchpads: Arc<Scratchpads>) -> Self {
+        Self {
+            handlers: StdRwLock::new(Vec::new()),
+            fallback: StdRwLock::new(Arc::new(ClarifyFallback)),
+            scratchpads,
+            onboarding: None,
+            focus: None,
+        }
+    }
+
+    /// Hint at the user's remaining setup steps in the model prompt
+    pub fn with_onboarding(mut self, onboarding: Arc<OnboardingTracker>) -> Self {
+        self.onboarding = Some(onboarding);
+        self
+    }
+
+    /// Ask the model for brief answers while the user is in focus mode
+    pub fn with_focus(mut self, router: Arc<NotificationRouter>) -> Self {
+        self.focus = Some(router);
+        self
+    }
+
+    /// Per-session working state; handlers that keep state across turns
+    /// read and write it through this
+    pub fn scratchpads(&self) -> &Arc<Scratchpads> {
+        &self.scratchpads
+    }
+
+    pub fn register(&self, priority: i32, handler: Arc<dyn IntentHandler>) {
+        let mut handlers = self.handlers.write().unwrap();
+        let position = handlers.iter().position(|h| h.priority < priority).unwrap_or(handlers.len());
+        debug!("Registered intent handler {} (priority {})", handler.name(), priority);
+        handlers.insert(position, RegisteredHandler { priority, handler });
+    }
+
+    pub fn set_fallback(&self, handler: Arc<dyn IntentHandler>) {
+        *self.fallback.write().unwrap() = handler;
+    }
+
+    /// Route unhandled intents to a language model
+    pub fn set_completion_provider(&self, provider: Arc<dyn CompletionProvider>) {
+        self.set_fallback(Arc::new(LlmFallbackHandler {
+            provider,
+            scratchpads: self.scratchpads.clone(),
+            onboarding: self.onboarding.clone(),
+            focus: self.focus.clone(),
+        }));
+    }
+
+    /// Handler names with their priorities, in dispatch order
+    pub fn handler_names(&self) -> Vec<(String, i32)> {
+        self.handlers
+            .read()
+            .unwrap()
+            .iter()
+            .map(|h| (h.handler.name().to_string(), h.priority))
+            .collect()
+    }
+
+    /// Commands the registered handlers run directly, in dispatch order; a
+    /// name declared twice goes to the first handler
+    pub fn commands(&self) -> Vec<CommandSpec> {
+        let mut commands: Vec<CommandSpec> = Vec::new();
+        for registered in self.handlers.read().unwrap().iter() {
+            for command in registered.handler.commands() {
+                if !commands.iter().any(|c| c.name == command.name) {
+                    commands.push(command);
+                }
+            }
+        }
+        commands
+    }
+
+    /// Run a palette command on the handler that declares it, with
+    /// parameters already checked against its spec. If that handler passes,
+    /// the request is dispatched as a chat message would be, so the answer
+    /// matches what chat gives. None when no handler declares the command
+    pub async fn run_command(
+        &self,
+        command: &str,
+        parameters: HashMap<String, String>,
+        context: &UserContext,
+    ) -> Result<Option<(IntentRequest, HandlerOutcome)>> {
+        let handler = self
+            .handlers
+            .read()
+            .unwrap()
+            .iter()
+            .find(|h| h.handler.commands().iter().any(|c| c.name == command))
+            .map(|h| h.handler.clone());
+        let Some(handler) = handler else {
+            return Ok(None);
+        };
+
+        let request = handler.command_request(command, parameters);
+        let outcome = match handler.handle(&request, context).await? {
+            Some(outcome) => {
+                debug!("Command /{} handled by {}", command, handler.name());
+                outcome
+            }
+            None => self.dispatch(&request, context).await?,
+        };
+        Ok(Some((request, outcome)))
+    }
+
+    pub async fn dispatch(&self, request: &IntentRequest, context: &UserContext) -> Result<HandlerOutcome> {
+        let handlers: Vec<Arc<dyn IntentHandler>> =
+            self.handlers.read().unwrap().iter().map(|h| h.handler.clone()).collect();
+
+        for handler in handlers {
+            if !handler.can_handle(request) {
+                continue;
+            }
+            if let Some(outcome) = handler.handle(request, context).await? {
+                debug!("Intent {:?} handled by {}", request.intent, handler.name());
+                return Ok(outcome);
+            }
+        }
+
+        let fallback = self.fallback.read().unwrap().clone();
+        debug!("Intent {:?} handled by fallback {}", request.intent, fallback.name());
+        Ok(fallback
+            .handle(request, context)
+            .await?
+            .unwrap_or_else(|| HandlerOutcome::text(ClarifyFallback::MESSAGE)))
+    }
+}
+
+impl Default for IntentHandlerRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub fn register_builtin_handlers(
+    registry: &IntentHandlerRegistry,
+    storage: Arc<dyn Storage + Send + Sync>,
+    context_manager: Arc<RwLock<ContextManager>>,
+    plugin_manager: Arc<PluginManager>,
+    flags: Arc<FeatureFlags>,
+    events: Arc<EventBus>,
+    notification_router: Arc<NotificationRouter>,
+) {
+    registry.register(PRIORITY_COMMAND, Arc::new(TimeTrackingHandler { storage: storage.clone() }));
+    registry.register(PRIORITY_COMMAND, Arc::new(TaskHandler { storage: storage.clone(), events: events.clone() }));
+    registry.register(PRIORITY_COMMAND, Arc::new(FocusHandler { router: notification_router }));
+    registry.register(PRIORITY_COMMAND, Arc::new(SettingsHandler { context_manager, events }));
+    registry.register(PRIORITY_CONVERSATIONAL, Arc::new(ConversationalHandler));
+    registry.register(PRIORITY_PLUGIN, Arc::new(PluginBridgeHandler { plugin_manager }));
+    registry.register(PRIORITY_SEARCH, Arc::new(DocumentSearchHandler { storage, flags }));
+}
+
+// Creates tasks and reminders from the normalized entities, and lists the
+// pending ones when no task was named
+pub struct TaskHandler {
+    storage: Arc<dyn Storage + Send + Sync>,
+    events: Arc<EventBus>,
+}
+
+impl TaskHandler {
+    async fn create_task(&self, request: &IntentRequest, context: &UserContext) -> Result<HandlerOutcome> {
+        let entities = &request.entities;
+        let now = chrono::Utc::now();
+        let name = entities.get("task_name").cloned().unwrap_or_default();
+        let is_reminder = request.entity("task_type") == Some("reminder");
+        let due_date = entities::resolve_due_date(entities, &context.preferences.timezone, now);
+
+        let mut tags = vec![if is_reminder { "reminder" } else { "task" }.to_string()];
+        if let Some(location) = entities.get(entities::LOCATION) {
+            tags.push(format!("location:{}", location));
+        }
+
+        let mut description = name.clone();
+        if let Some(quantity) = entities.get(entities::QUANTITY) {
+            let quantity = match entities.get(entities::QUANTITY_UNIT) {
+                Some(unit) => format!("{} {}", quantity, unit),
+                None => quantity.clone(),
+            };
+            description = format!("{} (quantity: {})", description, quantity);
+        }
+        if let (Some(duration), Some(entities::DURATION_SPAN)) =
+            (entities.get(entities::DURATION), request.entity(entities::DURATION_TYPE))
+        {
+            description = format!("{} (duration: {})", description, duration);
+        }
+
+        let task = Task {
+            id: Uuid::new_v4(),
+            name: name.clone(),
+            description,
+            status: TaskStatus::Pending,
+            priority: rusty_ai_common::TaskPriority::Medium,
+            due_date,
+            tags,
+            created_at: now,
+            updated_at: now,
+        };
+        self.storage.store_task(&task).await?;
+        self.events.publish(AssistantEvent::TaskStatusChanged { user_id: Some(context.user_id), task: task.clone() });
+
+        let kind = if is_reminder { "Reminder" } else { "Task" };
+        let text = match due_date {
+            Some(due) => format!("{} '{}' set for {}", kind, name, due.format("%Y-%m-%d %H:%M UTC")),
+            None => format!("{} '{}' created", kind, name),
+        };
+        Ok(HandlerOutcome::text(text)
+            .with_action("view_tasks", "View all tasks", "See your current task list")
+            .with_action("schedule", "Schedule task", "Set due date and reminders")
+            .with_performed(
+                PerformedAction::new(ActionKind::TaskCreated, format!("Created the {} '{}'", kind.to_lowercase(), name))
+                    .resource(task.id)
+                    .undo(UndoStep::DeleteTask { task_id: task.id }),
+            ))
+    }
+
+    async fn list_pending(&self) -> Result<HandlerOutcome> {
+        let tasks = self.storage.get_pending_tasks().await?;
+        let text = if tasks.is_empty() {
+            "You have no pending tasks.".to_string()
+        } else {
+            let list = tasks
+                .iter()
+                .map(|t| match t.due_date {
+                    Some(due) => format!("- {} (due {})", t.name, due.format("%Y-%m-%d %H:%M UTC")),
+                    None => format!("- {}", t.name),
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+            format!("You have {} pending task(s):\n{}", tasks.len(), list)
+        };
+        Ok(HandlerOutcome::text(text).with_action("create_task", "Add a task", "Create a new task or reminder"))
+    }
+}
+
+#[async_trait]
+impl IntentHandler for TaskHandler {
+    fn name(&self) -> &str {
+        "tasks"
+    }
+
+    fn can_handle(&self, request: &IntentRequest) -> bool {
+        matches!(request.command_action(), Some("task") | Some("task_operation"))
+    }
+
+    async fn handle(&self, request: &IntentRequest, context: &UserContext) -> Result<Option<HandlerOutcome>> {
+        let outcome = if request.entities.contains_key("task_name") {
+            self.create_task(request, context).await?
+        } else {
+            self.list_pending().await?
+        };
+        Ok(Some(outcome))
+    }
+
+    fn commands(&self) -> Vec<CommandSpec> {
+        let due = |command: CommandSpec| {
+            command
+                .parameter("at", ParameterKind::TimeOfDay, false, "Time of day it is due, HH:MM")
+                .parameter("in", ParameterKind::Duration, false, "How long from now it is due, e.g. PT2H")
+                .permission("write")
+        };
+        vec![
+            due(CommandSpec::new("task", "Create a task").parameter("name", ParameterKind::Text, true, "What to do")),
+            due(CommandSpec::new("remind", "Set a reminder").parameter("name", ParameterKind::Text, true, "What to be reminded of")),
+        ]
+    }
+
+    // The entities the classifier extracts from "remind me in 2 hours to ..."
+    fn command_request(&self, command: &str, parameters: HashMap<String, String>) -> IntentRequest {
+        let mut entities = HashMap::new();
+        for (name, value) in parameters {
+            match name.as_str() {
+                "name" => entities.insert("task_name".to_string(), value),
+                // Chat extracts zero-padded times; "9:30" is stored as "09:30"
+                "at" => {
+                    let time = NaiveTime::parse_from_str(value.trim(), "%H:%M")
+                        .map(|t| t.format("%H:%M").to_string())
+                        .unwrap_or(value);
+                    entities.insert(entities::TIME_OF_DAY.to_string(), time)
+                }
+                "in" => {
+                    entities.insert(entities::DURATION_TYPE.to_string(), entities::DURATION_OFFSET.to_string());
+                    entities.insert(entities::DURATION.to_string(), value)
+                }
+                _ => None,
+            };
+        }
+        if command == "remind" {
+            entities.insert("task_type".to_string(), "reminder".to_string());
+        }
+        IntentRequest {
+            intent: Intent::Command { action: "task".to_string(), parameters: Vec::new() },
+            entities,
+            message: None,
+        }
+    }
+}
+
+// Starts and stops task timers: "start working on the tax return" finds the
+// open task by name, "stop the timer" stops whatever is running
+pub struct TimeTrackingHandler {
+    storage: Arc<dyn Storage + Send + Sync>,
+}
+
+impl TimeTrackingHandler {
+    async fn open_tasks(&self) -> Result<Vec<Task>> {
+        let mut tasks = self.storage.get_tasks_by_status(TaskStatus::InProgress).await?;
+        tasks.extend(self.storage.get_tasks_by_status(TaskStatus::Pending).await?);
+        Ok(tasks)
+    }
+
+    async fn task_name(&self, id: Uuid) -> String {
+        match self.storage.get_task(id).await {
+            Ok(Some(task)) => task.name,
+            _ => "your last task".to_string(),
+        }
+    }
+
+    async fn start(&self, query: &str, context: &UserContext) -> Result<HandlerOutcome> {
+        let open_tasks = self.open_tasks().await?;
+        let Some(task) = time_tracking::find_task_by_name(query, &open_tasks) else {
+            return Ok(HandlerOutcome::text(format!("I couldn't find an open task matching '{}'.", query))
+                .with_action("create_task", "Add a task", "Create a new task or reminder"));
+        };
+
+        let TimerStart { entry, stopped } = self.storage.start_task_timer(context.user_id, task.id, None).await?;
+        let mut text = format!("Started the timer on '{}'.", task.name);
+        let mut outcome = HandlerOutcome::default();
+        if let Some(stopped) = stopped {
+            let stopped_name = self.task_name(stopped.task_id).await;
+            text.push_str(&format!(
+                " Stopped '{}' after {}.",
+                stopped_name,
+                time_tracking::format_duration(stopped.seconds(chrono::Utc::now()))
+            ));
+            outcome = outcome.with_performed(
+                PerformedAction::new(ActionKind::TimerStopped, format!("Stopped the timer on '{}'", stopped_name))
+                    .resource(stopped.id),
+            );
+        }
+        outcome.response_text = text;
+        Ok(outcome
+            .with_action("stop_timer", "Stop timer", "Stop tracking time on this task")
+            .with_performed(
+                PerformedAction::new(ActionKind::TimerStarted, format!("Started the timer on '{}'", task.name)).resource(entry.id),
+            ))
+    }
+
+    async fn stop(&self, query: Option<&str>, context: &UserContext) -> Result<HandlerOutcome> {
+        // "stop working on the slides" leaves a timer on another task running
+        let task_id = match query {
+            Some(query) => time_tracking::find_task_by_name(query, &self.open_tasks().await?).map(|task| task.id),
+            None => None,
+        };
+
+        let Some(entry) = self.storage.stop_task_timer(context.user_id, task_id, None).await? else {
+            return Ok(HandlerOutcome::text("No timer is running."));
+        };
+        let name = self.task_name(entry.task_id).await;
+        Ok(HandlerOutcome::text(format!(
+            "Stopped the timer on '{}' after {}.",
+            name,
+            time_tracking::format_duration(entry.seconds(chrono::Utc::now()))
+        ))
+        .with_performed(PerformedAction::new(ActionKind::TimerStopped, format!("Stopped the timer on '{}'", name)).resource(entry.id)))
+    }
+}
+
+#[async_trait]
+impl IntentHandler for TimeTrackingHandler {
+    fn name(&self) -> &str {
+        "time_tracking"
+    }
+
+    fn can_handle(&self, request: &IntentRequest) -> bool {
+        matches!(request.command_action(), Some("task") | Some("task_operation")) && request.entity("timer").is_some()
+    }
+
+    async fn handle(&self, request: &IntentRequest, context: &UserContext) -> Result<Option<HandlerOutcome>> {
+        let outcome = match (request.entity("timer"), request.entity("timer_task")) {
+            (Some("start"), Some(query)) => self.start(query, context).await?,
+            (Some("start"), None) => HandlerOutcome::text("Which task are you starting on?")
+                .with_action("view_tasks", "View all tasks", "See your current task list"),
+            (_, query) => self.stop(query, context).await?,
+        };
+        Ok(Some(outcome))
+    }
+}
+
+// Changes preferences from what the user says: "switch to the female voice",
+// "speak faster", "disable notifications after 10pm", "set my timezone to
+// Europe/Vienna". Asks back when the setting or the value is unclear rather
+// than guessing
+pub struct SettingsHandler {
+    context_manager: Arc<RwLock<ContextManager>>,
+    events: Arc<EventBus>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Setting {
+    Language,
+    Timezone,
+    Voice,
+    VoiceSpeed,
+    QuietHours,
+    Notifications,
+    NotificationChannels,
+}
+
+// The words users call each setting by
+const SETTING_SYNONYMS: &[(&str, Setting)] = &[
+    ("language", Setting::Language),
+    ("speak in", Setting::Language),
+    ("timezone", Setting::Timezone),
+    ("time zone", Setting::Timezone),
+    ("voice", Setting::Voice),
+    ("speed", Setting::VoiceSpeed),
+    ("speaking rate", Setting::VoiceSpeed),
+    ("faster", Setting::VoiceSpeed),
+    ("slower", Setting::VoiceSpeed),
+    ("more slowly", Setting::VoiceSpeed),
+    ("quiet hours", Setting::QuietHours),
+    ("do not disturb", Setting::QuietHours),
+    ("notification", Setting::Notifications),
+    ("notifications", Setting::Notifications),
+    ("alerts", Setting::Notifications),
+    ("email", Setting::NotificationChannels),
+    ("sms", Setting::NotificationChannels),
+    ("text message", Setting::NotificationChannels),
+    ("text messages", Setting::NotificationChannels),
+    ("push", Setting::NotificationChannels),
+    ("in-app", Setting::NotificationChannels),
+    ("in app", Setting::NotificationChannels),
+];
+
+// Things people expect to configure that preferences do not have
+const UNSUPPORTED_SETTINGS: &[&str] = &["volume", "theme", "dark mode", "light mode", "font", "brightness", "wake word", "password"];
+
+const LANGUAGE_NAMES: &[(&str, &str)] = &[
+    ("english", "en"),
+    ("german", "de"),
+    ("french", "fr"),
+    ("spanish", "es"),
+    ("italian", "it"),
+    ("portuguese", "pt"),
+    ("dutch", "nl"),
+];
+
+// ElevenLabs voices by what users call them; Rachel is the default voice of
+// the voice pipeline
+const VOICES: &[(&str, &str, &str)] = &[
+    ("female", "Rachel", "21m00Tcm4TlvDq8ikWAM"),
+    ("rachel", "Rachel", "21m00Tcm4TlvDq8ikWAM"),
+    ("bella", "Bella", "EXAVITQu4vr4xnxxsQCs"),
+    ("male", "Adam", "pNInz6obpgDQGcFmaJgB"),
+    ("adam", "Adam", "pNInz6obpgDQGcFmaJgB"),
+    ("antoni", "Antoni", "ErXwobaYiN019PkySvjV"),
+];
+
+const CHANNEL_NAMES: &[(&str, NotificationChannel)] = &[
+    ("email", NotificationChannel::Email),
+    ("sms", NotificationChannel::Sms),
+    ("text message", NotificationChannel::Sms),
+    ("push", NotificationChannel::Push),
+    ("in-app", NotificationChannel::InApp),
+    ("in app", NotificationChannel::InApp),
+];
+
+// Same bounds the preferences endpoint enforces
+const VOICE_SPEED_RANGE: std::ops::RangeInclusive<f32> = 0.5..=2.0;
+const VOICE_SPEED_STEP: f32 = 0.25;
+const DEFAULT_QUIET_START: &str = "22:00";
+const DEFAULT_QUIET_END: &str = "07:00";
+
+const SUPPORTED_SETTINGS: &str = "your language, timezone, voice, voice speed, quiet hours and notification channels";
+
+/// A validated new value for one setting
+#[derive(Debug, Clone, PartialEq)]
+enum SettingChange {
+    Language(String),
+    Timezone(String),
+    Voice { name: &'static str, voice_id: &'static str },
+    VoiceEnabled(bool),
+    VoiceSpeed(f32),
+    QuietHours(Option<QuietHours>),
+    NotificationsEnabled(bool),
+    Channels(Vec<NotificationChannel>),
+}
+
+impl SettingChange {
+    fn apply(&self, preferences: &mut UserPreferences) {
+        match self {
+            SettingChange::Language(code) => preferences.language = code.clone(),
+            SettingChange::Timezone(tz) => preferences.timezone = tz.clone(),
+            SettingChange::Voice { voice_id, .. } => preferences.voice_settings.voice_id = voice_id.to_string(),
+            SettingChange::VoiceEnabled(enabled) => preferences.voice_settings.enabled = *enabled,
+            SettingChange::VoiceSpeed(speed) => preferences.voice_settings.speed = *speed,
+            SettingChange::QuietHours(hours) => preferences.notification_settings.quiet_hours = hours.clone(),
+            SettingChange::NotificationsEnabled(enabled) => preferences.notification_settings.enabled = *enabled,
+            SettingChange::Channels(channels) => {
+                preferences.notification_settings.enabled = !channels.is_empty();
+                preferences.notification_settings.channels = channels.clone();
+            }
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            SettingChange::Language(code) => format!("your language is {}", code),
+            SettingChange::Timezone(tz) => format!("your timezone is {}", tz),
+            SettingChange::Voice { name, .. } => format!("I speak with {}'s voice", name),
+            SettingChange::VoiceEnabled(true) => "voice replies are on".to_string(),
+            SettingChange::VoiceEnabled(false) => "voice replies are off".to_string(),
+            SettingChange::VoiceSpeed(speed) => format!("your voice speed is {}x", speed),
+            SettingChange::QuietHours(Some(hours)) => format!("quiet hours are {}–{}", hours.start, hours.end),
+            SettingChange::QuietHours(None) => "quiet hours are off".to_string(),
+            SettingChange::NotificationsEnabled(true) => "notifications are on".to_string(),
+            SettingChange::NotificationsEnabled(false) => "notifications are off".to_string(),
+            SettingChange::Channels(channels) if channels.is_empty() => "notifications are off".to_string(),
+            SettingChange::Channels(channels) => format!(
+                "notifications go to {}",
+                channels.iter().map(channel_label).collect::<Vec<_>>().join(", ")
+            ),
+        }
+    }
+
+    /// One interpretation offered back to the user; `parameters` names the
+    /// preferences field and the value it would get
+    fn suggestion(&self) -> SuggestedAction {
+        let on_off = |enabled: bool| if enabled { "on" } else { "off" };
+        let (setting, value, label) = match self {
+            SettingChange::Language(code) => ("language", serde_json::json!(code), format!("Language {}", code)),
+            SettingChange::Timezone(tz) => ("timezone", serde_json::json!(tz), format!("Timezone {}", tz)),
+            SettingChange::Voice { name, voice_id } => {
+                ("voice_settings.voice_id", serde_json::json!(voice_id), format!("{}'s voice", name))
+            }
+            SettingChange::VoiceEnabled(enabled) => {
+                ("voice_settings.enabled", serde_json::json!(enabled), format!("Voice replies {}", on_off(*enabled)))
+            }
+            SettingChange::VoiceSpeed(speed) => ("voice_settings.speed", serde_json::json!(speed), format!("Speed {}x", speed)),
+            SettingChange::QuietHours(hours) => (
+                "notification_settings.quiet_hours",
+                serde_json::json!(hours),
+                hours.as_ref().map_or("No quiet hours".to_string(), |h| format!("Quiet {}–{}", h.start, h.end)),
+            ),
+            SettingChange::NotificationsEnabled(enabled) => (
+                "notification_settings.enabled",
+                serde_json::json!(enabled),
+                format!("Notifications {}", on_off(*enabled)),
+            ),
+            SettingChange::Channels(channels) => (
+                "notification_settings.channels",
+                serde_json::json!(channels),
+                format!("Notify via {}", channels.iter().map(channel_label).collect::<Vec<_>>().join(", ")),
+            ),
+        };
+        SuggestedAction {
+            action_type: "change_setting".to_string(),
+            label,
+            description: format!("Change it so {}", self.describe()),
+            parameters: serde_json::json!({ "setting": setting, "value": value }),
+        }
+    }
+}
+
+fn channel_label(channel: &NotificationChannel) -> &'static str {
+    match channel {
+        NotificationChannel::Email => "email",
+        NotificationChannel::Sms => "SMS",
+        NotificationChannel::Push => "push",
+        NotificationChannel::InApp => "in-app",
+    }
+}
+
+// Lowercased words separated by single spaces and padded, so phrases match
+// on word boundaries: " push " is not found in "pushed"
+pub(crate) fn padded_words(text: &str) -> String {
+    let words: Vec<String> = text
+        .split_whitespace()
+        .map(|w| w.trim_matches(|c: char| matches!(c, ',' | '.' | '!' | '?' | '"' | '\'')).to_lowercase())
+        .filter(|w| !w.is_empty())
+        .collect();
+    format!(" {} ", words.join(" "))
+}
+
+pub(crate) fn mentions(padded: &str, phrase: &str) -> bool {
+    padded.contains(&format!(" {} ", phrase))
+}
+
+// Some(true) for "turn on", Some(false) for "turn off"
+fn toggle(padded: &str) -> Option<bool> {
+    const OFF: &[&str] = &["disable", "turn off", "switch off", "mute", "deactivate", "stop"];
+    const ON: &[&str] = &["enable", "turn on", "switch on", "unmute", "activate"];
+    if OFF.iter().any(|word| mentions(padded, word)) {
+        Some(false)
+    } else if ON.iter().any(|word| mentions(padded, word)) {
+        Some(true)
+    } else {
+        None
+    }
+}
+
+impl SettingsHandler {
+    fn requested_value(text: &str) -> Option<String> {
+        let index = text.rfind(" to ")?;
+        let value = text[index + 4..].trim().trim_end_matches(['.', '!']);
+        (!value.is_empty()).then(|| value.to_string())
+    }
+
+    // The settings the text is about, after the more specific readings
+    // absorb the general ones: "make the voice faster" is about speed
+    fn targets(padded: &str, has_time: bool) -> Vec<Setting> {
+        let mut targets: Vec<Setting> = Vec::new();
+        for (phrase, setting) in SETTING_SYNONYMS {
+            if mentions(padded, phrase) && !targets.contains(setting) {
+                targets.push(*setting);
+            }
+        }
+        if LANGUAGE_NAMES.iter().any(|(name, _)| mentions(padded, name)) && !targets.contains(&Setting::Language) {
+            targets.push(Setting::Language);
+        }
+
+        let has = |targets: &[Setting], setting| targets.contains(&setting);
+        if has(&targets, Setting::Notifications) && has_time && !has(&targets, Setting::QuietHours) {
+            targets.push(Setting::QuietHours);
+        }
+        if has(&targets, Setting::VoiceSpeed) {
+            targets.retain(|s| *s != Setting::Voice);
+        }
+        if has(&targets, Setting::QuietHours) || has(&targets, Setting::NotificationChannels) {
+            targets.retain(|s| *s != Setting::Notifications);
+        }
+        // "switch to german" names a language, "the german voice" does not exist
+        if has(&targets, Setting::Voice) && !mentions(padded, "language") && !mentions(padded, "speak in") {
+            targets.retain(|s| *s != Setting::Language);
+        }
+        targets
+    }
+
+    // Times of day in the order they appear, each with every reading: a bare
+    // "10" could be 10:00 or 22:00
+    fn clock_times(lower: &str) -> Vec<Vec<NaiveTime>> {
+        static CLOCK_TIME: OnceLock<Regex> = OnceLock::new();
+        let pattern = CLOCK_TIME.get_or_init(|| Regex::new(r"\b(\d{1,2})(?::(\d{2}))?\s*(am|pm)?\b").unwrap());
+        let mut times = Vec::new();
+        for c in pattern.captures_iter(lower) {
+            let Ok(hour) = c[1].parse::<u32>() else { continue };
+            let minute: u32 = c.get(2).map_or(Ok(0), |m| m.as_str().parse()).unwrap_or(60);
+            let readings: Vec<u32> = match c.get(3).map(|m| m.as_str()) {
+                Some("am") if (1..=12).contains(&hour) => vec![hour % 12],
+                Some(_) if (1..=12).contains(&hour) => vec![hour % 12 + 12],
+                Some(_) => vec![],
+                None if c.get(2).is_some() || hour == 0 || hour > 12 => vec![hour],
+                None if hour == 12 => vec![12, 0],
+                None => vec![hour, hour + 12],
+            };
+            let readings: Vec<NaiveTime> = readings.into_iter().filter_map(|h| NaiveTime::from_hms_opt(h, minute, 0)).collect();
+            if !readings.is_empty() {
+                times.push(readings);
+            }
+        }
+        times
+    }
+
+    fn parse(
+        setting: Setting,
+        text: &str,
+        padded: &str,
+        preferences: &UserPreferences,
+    ) -> std::result::Result<Vec<SettingChange>, String> {
+        match setting {
+            Setting::Language => {
+                if let Some((_, code)) = LANGUAGE_NAMES.iter().find(|(name, _)| mentions(padded, name)) {
+                    return Ok(vec![SettingChange::Language(code.to_string())]);
+                }
+                let value = Self::requested_value(text).ok_or("What should I set your language to?")?;
+                let code = value.to_lowercase();
+                if code.len() != 2 || !code.chars().all(|c| c.is_ascii_alphabetic()) {
+                    return Err(format!(
+                        "'{}' is not a language code I know. Use a two-letter code such as 'de'.",
+                        value
+                    ));
+                }
+                Ok(vec![SettingChange::Language(code)])
+            }
+            Setting::Timezone => {
+                let value = Self::requested_value(text).ok_or("What should I set your timezone to?")?;
+                if value.parse::<chrono_tz::Tz>().is_err() {
+                    return Err(format!("'{}' is not a timezone I know. Try a name like Europe/Vienna.", value));
+                }
+                Ok(vec![SettingChange::Timezone(value)])
+            }
+            Setting::Voice => {
+                if let Some((_, name, voice_id)) = VOICES.iter().find(|(word, _, _)| mentions(padded, word)) {
+                    return Ok(vec![SettingChange::Voice { name: *name, voice_id: *voice_id }]);
+                }
+                if let Some(enabled) = toggle(padded) {
+                    return Ok(vec![SettingChange::VoiceEnabled(enabled)]);
+                }
+                // Every voice once, as the choices to offer
+                let mut choices: Vec<SettingChange> = Vec::new();
+                for (_, name, voice_id) in VOICES {
+                    let choice = SettingChange::Voice { name: *name, voice_id: *voice_id };
+                    if !choices.contains(&choice) {
+                        choices.push(choice);
+                    }
+                }
+                Ok(choices)
+            }
+            Setting::VoiceSpeed => {
+                let current = preferences.voice_settings.speed;
+                static SPEED: OnceLock<Regex> = OnceLock::new();
+                let number = SPEED.get_or_init(|| Regex::new(r"(\d+(?:\.\d+)?)\s*x?\b").unwrap());
+                let speed = if let Some(c) = number.captures(padded) {
+                    c[1].parse::<f32>().map_err(|_| "What speed would you like, between 0.5x and 2x?".to_string())?
+                } else if ["faster", "quicker", "speed up"].iter().any(|w| mentions(padded, w)) {
+                    current + VOICE_SPEED_STEP
+                } else if ["slower", "more slowly", "slow down"].iter().any(|w| mentions(padded, w)) {
+                    current - VOICE_SPEED_STEP
+                } else if ["normal", "default", "reset"].iter().any(|w| mentions(padded, w)) {
+                    1.0
+                } else {
+                    return Err("What speed would you like, between 0.5x and 2x?".to_string());
+                };
+                if !VOICE_SPEED_RANGE.contains(&speed) {
+                    return Err(format!(
+                        "Voice speed must be between {}x and {}x, so I can't set it to {}x.",
+                        VOICE_SPEED_RANGE.start(),
+                        VOICE_SPEED_RANGE.end(),
+                        speed
+                    ));
+                }
+                Ok(vec![SettingChange::VoiceSpeed(speed)])
+            }
+            Setting::QuietHours => {
+                let lower = padded.trim();
+                let times = Self::clock_times(lower);
+                let current = preferences.notification_settings.quiet_hours.clone();
+                if times.is_empty() {
+                    return match toggle(padded) {
+                        Some(false) if mentions(padded, "quiet hours") || mentions(padded, "do not disturb") => {
+                            Ok(vec![SettingChange::QuietHours(None)])
+                        }
+                        _ => Err("When should quiet hours start and end? For example \"quiet hours from 10pm to 7am\"."
+                            .to_string()),
+                    };
+                }
+
+                let keep_start = current.as_ref().map_or(DEFAULT_QUIET_START.to_string(), |h| h.start.clone());
+                let keep_end = current.as_ref().map_or(DEFAULT_QUIET_END.to_string(), |h| h.end.clone());
+                // "until 7am", and "enable notifications after 7am", end quiet hours
+                let ends_only = times.len() == 1
+                    && (["until", "till", "before"].iter().any(|w| mentions(padded, w))
+                        || (toggle(padded) == Some(true) && !mentions(padded, "quiet hours")));
+                let hhmm = |t: &NaiveTime| t.format("%H:%M").to_string();
+
+                let mut choices = Vec::new();
+                match times.as_slice() {
+                    [only] if ends_only => {
+                        for end in only {
+                            choices.push(QuietHours { start: keep_start.clone(), end: hhmm(end) });
+                        }
+                    }
+                    [only] => {
+                        for start in only {
+                            choices.push(QuietHours { start: hhmm(start), end: keep_end.clone() });
+                        }
+                    }
+                    [starts, ends, ..] => {
+                        for start in starts {
+                            for end in ends {
+                                choices.push(QuietHours { start: hhmm(start), end: hhmm(end) });
+                            }
+                        }
+                    }
+                    [] => {}
+                }
+                Ok(choices.into_iter().map(|hours| SettingChange::QuietHours(Some(hours))).collect())
+            }
+            Setting::Notifications => match toggle(padded) {
+                Some(enabled) => Ok(vec![SettingChange::NotificationsEnabled(enabled)]),
+                None => Ok(vec![SettingChange::NotificationsEnabled(true), SettingChange::NotificationsEnabled(false)]),
+            },
+            Setting::NotificationChannels => {
+                let named: Vec<NotificationChannel> = CHANNEL_NAMES
+                    .iter()
+                    .filter(|(name, _)| mentions(padded, name) || mentions(padded, &format!("{}s", name)))
+                    .map(|(_, channel)| channel.clone())
+                    .fold(Vec::new(), |mut acc, channel| {
+                        if !acc.contains(&channel) {
+                            acc.push(channel);
+                        }
+                        acc
+                    });
+                let mut channels = preferences.notification_settings.channels.clone();
+                if mentions(padded, "only") {
+                    channels = named;
+                } else if toggle(padded) == Some(false) {
+                    channels.retain(|channel| !named.contains(channel));
+                } else {
+                    for channel in named {
+                        if !channels.contains(&channel) {
+                            channels.push(channel);
+                        }
+                    }
+                }
+                Ok(vec![SettingChange::Channels(channels)])
+            }
+        }
+    }
+
+    fn clarify(targets: &[Setting], choices: &[SettingChange]) -> HandlerOutcome {
+        let question = match targets {
+            [Setting::Voice] => "Which voice would you like?",
+            [Setting::QuietHours] => "Did you mean morning or evening? Pick the quiet hours you want.",
+            [Setting::Notifications] => "Should I turn notifications on or off?",
+            _ => "That could change more than one setting. Which did you mean?",
+        };
+        let mut outcome = HandlerOutcome::text(question);
+        outcome.actions.extend(choices.iter().map(SettingChange::suggestion));
+        outcome
+    }
+}
+
+#[async_trait]
+impl IntentHandler for SettingsHandler {
+    fn name(&self) -> &str {
+        "settings"
+    }
+
+    fn can_handle(&self, request: &IntentRequest) -> bool {
+        request.command_action() == Some("settings")
+    }
+
+    async fn handle(&self, request: &IntentRequest, context: &UserContext) -> Result<Option<HandlerOutcome>> {
+        let text = request.text();
+        let padded = padded_words(&text);
+
+        if let Some(unsupported) = UNSUPPORTED_SETTINGS.iter().find(|name| mentions(&padded, name)) {
+            return Ok(Some(
+                HandlerOutcome::text(format!(
+                    "I can't change the {} setting. I can change {}.",
+                    unsupported, SUPPORTED_SETTINGS
+                ))
+                .with_action("open_settings", "Open settings", "Review all of your preferences"),
+            ));
+        }
+
+        let has_time = !Self::clock_times(padded.trim()).is_empty();
+        let targets = Self::targets(&padded, has_time);
+        if targets.is_empty() {
+            return Ok(Some(
+                HandlerOutcome::text(format!("Which setting would you like to change? I can change {}.", SUPPORTED_SETTINGS))
+                    .with_action("open_settings", "Open settings", "Review all of your preferences"),
+            ));
+        }
+
+        // Readings of the request that make sense; several settings are only
+        // ambiguous when the value fits more than one of them
+        let mut choices = Vec::new();
+        let mut problems = Vec::new();
+        for setting in &targets {
+            match Self::parse(*setting, &text, &padded, &context.preferences) {
+                Ok(changes) => choices.extend(changes),
+                Err(problem) => problems.push(problem),
+            }
+        }
+
+        let change = match choices.as_slice() {
+            [] => return Ok(Some(HandlerOutcome::text(problems.join(" ")))),
+            [change] => change.clone(),
+            _ => return Ok(Some(Self::clarify(&targets, &choices))),
+        };
+
+        let mut preferences = context.preferences.clone();
+        change.apply(&mut preferences);
+        if let Err(e) = preferences.notification_settings.validate() {
+            return Ok(Some(HandlerOutcome::text(format!("I can't make that change: {}", e))));
+        }
+
+        self.context_manager
+            .write()
+            .await
+            .update_user_preferences(context.session_id, preferences)
+            .await?;
+        self.events.publish(AssistantEvent::UserAction { user_id: context.user_id, action: UserAction::PreferencesUpdated });
+        let description = change.describe();
+        Ok(Some(
+            HandlerOutcome::text(format!("Done, {} now.", description)).with_performed(
+                PerformedAction::new(ActionKind::PreferencesChanged, format!("Changed settings: {}", description))
+                    .resource(context.session_id)
+                    .undo(UndoStep::RestorePreferences {
+                        session_id: context.session_id,
+                        preferences: context.preferences.clone(),
+                    }),
+            ),
+        ))
+    }
+}
+
+// Turns focus mode on and off from what the user says: "focus for two
+// hours", "focus mode until I turn it off", "stop focusing". Settings-like
+// messages that do not mention focus pass on to the settings handler
+pub struct FocusHandler {
+    router: Arc<NotificationRouter>,
+}
+
+impl FocusHandler {
+    fn local_time(time: chrono::DateTime<chrono::Utc>, preferences: &UserPreferences) -> String {
+        match preferences.timezone.parse::<chrono_tz::Tz>() {
+            Ok(tz) => time.with_timezone(&tz).format("%H:%M").to_string(),
+            Err(_) => format!("{} UTC", time.format("%H:%M")),
+        }
+    }
+}
+
+#[async_trait]
+impl IntentHandler for FocusHandler {
+    fn name(&self) -> &str {
+        "focus"
+    }
+
+    fn can_handle(&self, request: &IntentRequest) -> bool {
+        request.command_action() == Some("settings")
+    }
+
+    async fn handle(&self, request: &IntentRequest, context: &UserContext) -> Result<Option<HandlerOutcome>> {
+        let Some(command) = FocusCommand::parse(&request.text()) else {
+            return Ok(None);
+        };
+
+        let text = match command {
+            FocusCommand::Start(Some(duration)) if duration.num_minutes() > MAX_FOCUS_MINUTES as i64 => {
+                format!("I can hold focus for at most {} hours, or until you turn it off.", MAX_FOCUS_MINUTES / 60)
+            }
+            FocusCommand::Start(duration) => match self.router.start_focus(context.user_id, duration)?.until {
+                Some(until) => format!(
+                    "Focus mode is on until {}. I'll hold reminders and briefings until then.",
+                    Self::local_time(until, &context.preferences)
+                ),
+                None => "Focus mode is on until you turn it off. I'll hold reminders and briefings until then.".to_string(),
+            },
+            FocusCommand::End => match self.router.end_focus(context.user_id).await? {
+                Some(0) => "Focus mode is off. Nothing came in while you were focused.".to_string(),
+                Some(held) => format!("Focus mode is off. Sending the {} notification(s) I held as one digest.", held),
+                None => "You're not in focus mode.".to_string(),
+            },
+            FocusCommand::Status => {
+                let status = self.router.focus_status(context.user_id);
+                match (status.active, status.until) {
+                    (false, _) => "You're not in focus mode.".to_string(),
+                    (true, Some(until)) => format!(
+                        "You're in focus mode until {}, with {} notification(s) held.",
+                        Self::local_time(until, &context.preferences),
+                        status.held
+                    ),
+                    (true, None) => format!("You're in focus mode until you turn it off, with {} notification(s) held.", status.held),
+                }
+            }
+        };
+        Ok(Some(HandlerOutcome::text(text)))
+    }
+}
+
+pub struct ConversationalHandler;
+
+#[async_trait]
+impl IntentHandler for ConversationalHandler {
+    fn name(&self) -> &str {
+        "conversational"
+    }
+
+    fn can_handle(&self, request: &IntentRequest) -> bool {
+        request.information_topic().map_or(false, |topic| CONVERSATIONAL_TOPICS.contains(&topic))
+    }
+
+    async fn handle(&self, request: &IntentRequest, _context: &UserContext) -> Result<Option<HandlerOutcome>> {
+        let outcome = match request.information_topic() {
+            Some("greeting") => HandlerOutcome::text("Hello! How can I help you today?"),
+            Some("goodbye") => HandlerOutcome::text("Goodbye! Talk to you soon."),
+            _ => HandlerOutcome::text(
+                "I can manage your tasks and reminders, search your documents, change settings like \
+                 your voice or quiet hours, and answer questions. Try \"remind me to call Anna tomorrow\".",
+            )
+            .with_action("create_task", "Add a task", "Create a new task or reminder")
+            .with_action("search", "Search documents", "Search the knowledge base"),
+        };
+        Ok(Some(outcome))
+    }
+}
+
+// Forwards to plugins: commands by declared capability, queries to plugins
+// that claim them. A failing plugin passes the request on
+pub struct PluginBridgeHandler {
+    plugin_manager: Arc<PluginManager>,
+}
+
+#[async_trait]
+impl IntentHandler for PluginBridgeHandler {
+    fn name(&self) -> &str {
+        "plugins"
+    }
+
+    fn can_handle(&self, request: &IntentRequest) -> bool {
+        matches!(request.intent, Intent::Command { .. } | Intent::Query { .. })
+    }
+
+    async fn handle(&self, request: &IntentRequest, context: &UserContext) -> Result<Option<HandlerOutcome>> {
+        for plugin in self.plugin_manager.get_active_plugins().await {
+            let metadata = plugin.metadata();
+            // Commands may change things on the user's behalf; queries only read
+            let (result, command) = match &request.intent {
+                Intent::Command { action, .. } if metadata.capabilities.iter().any(|c| c == action) => {
+                    (plugin.handle_intent(request.intent.clone(), context).await, Some(action))
+                }
+                Intent::Query { query } if plugin.can_handle_query(query) => {
+                    (plugin.process_query(query.clone(), context).await, None)
+                }
+                _ => continue,
+            };
+
+            match result {
+                Ok(text) => {
+                    let mut outcome = HandlerOutcome::text(text);
+                    if let Some(action) = command {
+                        outcome = outcome.with_performed(
+                            PerformedAction::new(ActionKind::PluginCommand, format!("Ran '{}' with {}", action, metadata.name))
+                                .resource(&metadata.id),
+                        );
+                    }
+                    return Ok(Some(outcome));
+                }
+                Err(e) => warn!("Plugin {} failed to handle {:?}: {}", metadata.id, request.intent, e),
+            }
+        }
+        Ok(None)
+    }
+}
+
+// Answers queries and information requests from stored documents, citing
+// them; with no matches the request falls through
+pub struct DocumentSearchHandler {
+    storage: Arc<dyn Storage + Send + Sync>,
+    flags: Arc<FeatureFlags>,
+}
+
+impl DocumentSearchHandler {
+    // Text matches, plus documents tagged with one of the query's words when
+    // hybrid search is on; reranked by how many query words each contains
+    async fn search(&self, term: &str, user_id: Uuid) -> Result<Vec<Document>> {
+        let mut documents = self.storage.search_documents(term, SEARCH_LIMIT).await?;
+        let words: Vec<String> = term.split_whitespace().map(str::to_lowercase).collect();
+
+        if self.flags.enabled(Flag::HybridSearch, user_id) && !words.is_empty() {
+            for document in self.storage.get_documents_by_tags(&words, SEARCH_LIMIT).await? {
+                if !documents.iter().any(|d| d.id == document.id) {
+                    documents.push(document);
+                }
+            }
+        }
+
+        if self.flags.enabled(Flag::Reranking, user_id) {
+            let matched = |document: &Document| {
+                let text = format!("{} {}", document.title, document.content).to_lowercase();
+                words.iter().filter(|word| text.contains(word.as_str())).count()
+            };
+            // Stable, so equally good matches keep the storage order
+            documents.sort_by_key(|document| std::cmp::Reverse(matched(document)));
+        }
+
+        documents.truncate(SEARCH_LIMIT);
+        Ok(documents)
+    }
+}
+
+#[async_trait]
+impl IntentHandler for DocumentSearchHandler {
+    fn name(&self) -> &str {
+        "document_search"
+    }
+
+    fn can_handle(&self, request: &IntentRequest) -> bool {
+        match &request.intent {
+            Intent::Query { .. } => true,
+            Intent::Information { topic } => !CONVERSATIONAL_TOPICS.contains(&topic.as_str()),
+            _ => false,
+        }
+    }
+
+    async fn handle(&self, request: &IntentRequest, context: &UserContext) -> Result<Option<HandlerOutcome>> {
+        let term = match (request.entity("search_term"), &request.intent) {
+            (Some(term), _) => term.to_string(),
+            (None, Intent::Query { query }) => query.clone(),
+            (None, Intent::Information { topic }) => topic.clone(),
+            _ => return Ok(None),
+        };
+
+        let documents = self.search(&term, context.user_id).await?;
+        if documents.is_empty() {
+            return Ok(None);
+        }
+
+        let sources: Vec<SourceRef> = documents
+            .iter()
+            .map(|d| SourceRef {
+                document_id: d.id,
+                title: d.title.clone(),
+                snippet: Some(
+                    d.metadata
+                        .summary
+                        .clone()
+                        .unwrap_or_else(|| d.content.chars().take(SNIPPET_CHARS).collect()),
+                ),
+            })
+            .collect();
+        let summary = sources
+            .iter()
+            .map(|s| format!("- {}: {}", s.title, s.snippet.as_deref().unwrap_or_default()))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let mut outcome = HandlerOutcome::text(format!("Here's what I know about {}:\n{}", term, summary))
+            .with_action("related", "Show related topics", "Find related information and documents");
+        outcome.sources = sources;
+        Ok(Some(outcome))
+    }
+
+    fn commands(&self) -> Vec<CommandSpec> {
+        vec![CommandSpec::new("search", "Search your documents")
+            .parameter("query", ParameterKind::Text, true, "What to look for")
+            .permission("read")]
+    }
+
+    fn command_request(&self, _command: &str, mut parameters: HashMap<String, String>) -> IntentRequest {
+        let query = parameters.remove("query").unwrap_or_default();
+        IntentRequest {
+            intent: Intent::Query { query: query.clone() },
+            entities: HashMap::from([("search_term".to_string(), query)]),
+            message: None,
+        }
+    }
+}
+
+// The session's scratchpad goes ahead of the message, so the model sees the
+// state gathered in earlier turns; for new users a note on the next setup
+// step goes ahead of that, and in focus mode a request for brief answers
+// goes first
+pub struct LlmFallbackHandler {
+    provider: Arc<dyn CompletionProvider>,
+    scratchpads: Arc<Scratchpads>,
+    onboarding: Option<Arc<OnboardingTracker>>,
+    focus: Option<Arc<NotificationRouter>>,
+}
+
+#[async_trait]
+impl IntentHandler for LlmFallbackHandler {
+    fn name(&self) -> &str {
+        "llm"
+    }
+
+    fn can_handle(&self, _request: &IntentRequest) -> bool {
+        true
+    }
+
+    async fn handle(&self, request: &IntentRequest, context: &UserContext) -> Result<Option<HandlerOutcome>> {
+        let message = request.text();
+        if message.is_empty() {
+            return Ok(None);
+        }
+        let focus = self.focus.as_ref().and_then(|router| router.focus_prompt_hint(context.user_id));
+        let onboarding = self.onboarding.as_ref().and_then(|onboarding| onboarding.prompt_hint(context.user_id));
+        let prompt = focus
+            .into_iter()
+            .chain(onboarding)
+            .chain(self.scratchpads.get(context.session_id).prompt_section())
+            .chain([message])
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        let text = self.provider.complete(&prompt, context).await?;
+        Ok(Some(HandlerOutcome::text(text)))
+    }
+}
+
+// Used until a completion provider is installed
+pub struct ClarifyFallback;
+
+impl ClarifyFallback {
+    const MESSAGE: &'static str = "I'm not sure I understand. Could you please rephrase?";
+}
+
+#[async_trait]
+impl IntentHandler for ClarifyFallback {
+    fn name(&self) -> &str {
+        "clarify"
+    }
+
+    fn can_handle(&self, _request: &IntentRequest) -> bool {
+        true
+    }
+
+    async fn handle(&self, request: &IntentRequest, _context: &UserContext) -> Result<Option<HandlerOutcome>> {
+        let outcome = match &request.intent {
+            Intent::Query { query } => HandlerOutcome::text(format!("I don't have information about {} yet.", query)),
+            Intent::Information { topic } => HandlerOutcome::text(format!(
+                "I don't have information about {} yet. Would you like me to research it?",
+                topic
+            )),
+            _ => HandlerOutcome::text(Self::MESSAGE)
+                .with_action("help", "Get help", "Learn about available commands and features"),
+        };
+        Ok(Some(outcome))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rusty_ai_common::{NotificationSettings, UserPreferences, VoiceSettings};
+    use std::sync::Mutex;
+
+    fn test_context() -> UserContext {
+        UserContext {
+            user_id: Uuid::new_v4(),
+            session_id: Uuid::new_v4(),
+            preferences: UserPreferences {
+                language: "en".to_string(),
+                timezone: "UTC".to_string(),
+                voice_settings: VoiceSettings {
+                    enabled: false,
+                    voice_id: "default".to_string(),
+                    speed: 1.0,
+                    pitch: 1.0,
+                },
+                notification_settings: NotificationSettings {
+                    enabled: false,
+                    channels: vec![],
+                    quiet_hours: None,
+                    routing: Default::default(),
+                    knowledge_digest: Default::default(),
+                },
+            },
+            active_plugins: vec![],
+            conversation_history: Default::default(),
+        }
+    }
+
+    // Answers commands with the given action, or declines them
+    struct MockHandler {
+        name: &'static str,
+        action: &'static str,
+        decline: bool,
+        calls: Mutex<usize>,
+    }
+
+    impl MockHandler {
+        fn new(name: &'static str, action: &'static str, decline: bool) -> Arc<Self> {
+            Arc::new(Self { name, action, decline, calls: Mutex::new(0) })
+        }
+    }
+
+    #[async_trait]
+    impl IntentHandler for MockHandler {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        fn can_handle(&self, request: &IntentRequest) -> bool {
+            request.command_action() == Some(self.action)
+        }
+
+        async fn handle(&self, _request: &IntentRequest, _context: &UserContext) -> Result<Option<HandlerOutcome>> {
+            *self.calls.lock().unwrap() += 1;
+            if self.decline {
+                return Ok(None);
+            }
+            Ok(Some(HandlerOutcome::text(format!("handled by {}", self.name))))
+        }
+    }
+
+    struct EchoModel;
+
+    #[async_trait]
+    impl CompletionProvider for EchoModel {
+        async fn complete(&self, prompt: &str, _context: &UserContext) -> Result<String> {
+            Ok(format!("model: {}", prompt))
+        }
+    }
+
+    fn command(action: &str) -> IntentRequest {
+        IntentRequest::from_intent(Intent::Command {
+            action: action.to_string(),
+            parameters: vec![format!("please {}", action)],
+        })
+    }
+
+    #[tokio::test]
+    async fn test_higher_priority_handler_wins() {
+        let registry = IntentHandlerRegistry::new();
+        let low = MockHandler::new("low", "play", false);
+        let high = MockHandler::new("high", "play", false);
+        registry.register(10, low.clone());
+        registry.register(20, high.clone());
+
+        let outcome = registry.dispatch(&command("play"), &test_context()).await.unwrap();
+        assert_eq!(outcome.response_text, "handled by high");
+        assert_eq!(*low.calls.lock().unwrap(), 0);
+        assert_eq!(
+            registry.handler_names(),
+            vec![("high".to_string(), 20), ("low".to_string(), 10)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_equal_priority_keeps_registration_order_and_declines_pass_on() {
+        let registry = IntentHandlerRegistry::new();
+        let first = MockHandler::new("first", "play", true);
+        let second = MockHandler::new("second", "play", false);
+        registry.register(10, first.clone());
+        registry.register(10, second.clone());
+
+        let outcome = registry.dispatch(&command("play"), &test_context()).await.unwrap();
+        assert_eq!(outcome.response_text, "handled by second");
+        assert_eq!(*first.calls.lock().unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_unhandled_intents_reach_the_fallback() {
+        let registry = IntentHandlerRegistry::new();
+        registry.register(10, MockHandler::new("music", "play", false));
+
+        let outcome = registry.dispatch(&command("book"), &test_context()).await.unwrap();
+        assert_eq!(outcome.response_text, ClarifyFallback::MESSAGE);
+        assert_eq!(outcome.actions[0].action_type, "help");
+
+        registry.set_completion_provider(Arc::new(EchoModel));
+        let request = command("book").with_message("book a table for two");
+        let outcome = registry.dispatch(&request, &test_context()).await.unwrap();
+        assert_eq!(outcome.response_text, "model: book a table for two");
+    }
+
+    // Declares /play but declines every request
+    struct PaletteHandler;
+
+    #[async_trait]
+    impl IntentHandler for PaletteHandler {
+        fn name(&self) -> &str {
+            "palette"
+        }
+
+        fn can_handle(&self, _request: &IntentRequest) -> bool {
+            false
+        }
+
+        async fn handle(&self, _request: &IntentRequest, _context: &UserContext) -> Result<Option<HandlerOutcome>> {
+            Ok(None)
+        }
+
+        fn commands(&self) -> Vec<CommandSpec> {
+            vec![CommandSpec::new("play", "Play something").parameter("title", ParameterKind::Text, true, "What to play")]
+        }
+    }
+
+    #[tokio::test]
+    async fn test_commands_run_on_their_handler_and_fall_back_like_chat() {
+        let registry = IntentHandlerRegistry::new();
+        registry.register(10, Arc::new(PaletteHandler));
+        let music = MockHandler::new("music", "play", false);
+        registry.register(5, music.clone());
+
+        let commands = registry.commands();
+        assert_eq!(commands.len(), 1);
+        assert_eq!(commands[0].input_schema()["required"], serde_json::json!(["title"]));
+
+        let parameters = HashMap::from([("title".to_string(), "jazz".to_string())]);
+        let (request, outcome) = registry.run_command("play", parameters, &test_context()).await.unwrap().unwrap();
+        assert_eq!(request.entity("title"), Some("jazz"));
+        assert_eq!(outcome.response_text, "handled by music");
+        assert!(registry.run_command("stop", HashMap::new(), &test_context()).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_task_commands_carry_the_entities_chat_extracts() {
+        let config = crate::storage::StorageConfig {
+            database_url: "sqlite::memory:".to_string(),
+            max_connections: 1,
+            ..Default::default()
+        };
+        let storage = Arc::new(crate::storage::SqliteStorage::new(&config).await.unwrap());
+        let handler = TaskHandler { storage, events: Arc::new(EventBus::default()) };
+
+        let chat = crate::intent::IntentClassifier::new().classify("remind me to water the plants in 2 hours", None);
+        let parameters = HashMap::from([
+            ("name".to_string(), "water the plants".to_string()),
+            ("in".to_string(), "PT2H".to_string()),
+        ]);
+        let request = handler.command_request("remind", parameters);
+        assert_eq!(request.command_action(), Some("task"));
+        for (entity, value) in &request.entities {
+            assert_eq!(chat.extracted_entities.get(entity), Some(value), "{}", entity);
+        }
+    }
+
+    // Keeps the trip being planned in the scratchpad, like a planning tool
+    struct TripTool {
+        scratchpads: Arc<Scratchpads>,
+    }
+
+    #[async_trait]
+    impl IntentHandler for TripTool {
+        fn name(&self) -> &str {
+            "trip_tool"
+        }
+
+        fn can_handle(&self, request: &IntentRequest) -> bool {
+            request.command_action() == Some("plan")
+        }
+
+        async fn handle(&self, _request: &IntentRequest, context: &UserContext) -> Result<Option<HandlerOutcome>> {
+            let trip = serde_json::json!({ "destination": "Lisbon", "nights": 3 });
+            self.scratchpads.set(context.session_id, "trip", trip)?;
+            Ok(Some(HandlerOutcome::text("Noted")))
+        }
+    }
+
+    // Answers from the scratchpad a tool wrote in an earlier turn
+    struct TripPlugin {
+        scratchpads: Arc<Scratchpads>,
+    }
+
+    #[async_trait]
+    impl crate::plugin_manager::AssistantPlugin for TripPlugin {
+        fn metadata(&self) -> rusty_ai_common::PluginMetadata {
+            rusty_ai_common::PluginMetadata {
+                id: "trips".to_string(),
+                name: "Trips".to_string(),
+                version: "0.1.0".to_string(),
+                description: String::new(),
+                author: String::new(),
+                capabilities: vec![],
+                dependencies: vec![],
+            }
+        }
+
+        async fn initialize(&mut self, _config: rusty_ai_common::PluginConfig) -> Result<()> {
+            Ok(())
+        }
+
+        async fn handle_intent(&self, _intent: Intent, _context: &UserContext) -> Result<String> {
+            Ok(String::new())
+        }
+
+        async fn health_check(&self) -> crate::plugin_manager::PluginHealth {
+            crate::plugin_manager::PluginHealth {
+                status: crate::plugin_manager::HealthStatus::Healthy,
+                message: None,
+                last_check: chrono::Utc::now(),
+            }
+        }
+
+        fn can_handle_query(&self, query: &str) -> bool {
+            query.contains("trip")
+        }
+
+        fn can_handle_task(&self, _task_name: &str) -> bool {
+            false
+        }
+
+        async fn process_query(&self, _query: String, context: &UserContext) -> Result<String> {
+            let scratchpad = self.scratchpads.get(context.session_id);
+            let destination = scratchpad.get("trip").and_then(|trip| trip["destination"].as_str()).unwrap_or("nowhere yet");
+            Ok(format!("Your trip goes to {}", destination))
+        }
+
+        async fn execute_task(&self, _task: &Task) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[derive(Default)]
+    struct RecordingModel {
+        prompts: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl CompletionProvider for RecordingModel {
+        async fn complete(&self, prompt: &str, _context: &UserContext) -> Result<String> {
+            self.prompts.lock().unwrap().push(prompt.to_string());
+            Ok("Booked".to_string())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_scratchpad_carries_state_between_turns() {
+        let scratchpads = Arc::new(Scratchpads::new());
+        let registry = IntentHandlerRegistry::with_scratchpads(scratchpads.clone());
+        registry.register(PRIORITY_COMMAND, Arc::new(TripTool { scratchpads: registry.scratchpads().clone() }));
+        let plugin_manager = Arc::new(PluginManager::new());
+        plugin_manager.register_plugin(Box::new(TripPlugin { scratchpads: scratchpads.clone() })).await.unwrap();
+        registry.register(PRIORITY_PLUGIN, Arc::new(PluginBridgeHandler { plugin_manager }));
+        let model = Arc::new(RecordingModel::default());
+        registry.set_completion_provider(model.clone());
+        let context = test_context();
+
+        let outcome = registry.dispatch(&command("plan"), &context).await.unwrap();
+        assert_eq!(outcome.response_text, "Noted");
+
+        let query = IntentRequest::from_intent(Intent::Query { query: "where does my trip go".to_string() });
+        let outcome = registry.dispatch(&query, &context).await.unwrap();
+        assert_eq!(outcome.response_text, "Your trip goes to Lisbon");
+
+        registry.dispatch(&command("book").with_message("book the hotel"), &context).await.unwrap();
+        let prompt = model.prompts.lock().unwrap().pop().unwrap();
+        let scratchpad = &prompt[prompt.find(rusty_ai_common::scratchpad::SCRATCHPAD_OPEN).unwrap()
+            ..prompt.find(rusty_ai_common::scratchpad::SCRATCHPAD_CLOSE).unwrap()];
+        assert!(scratchpad.contains("\"destination\": \"Lisbon\""));
+        assert!(prompt.ends_with("book the hotel"));
+
+        // Another session starts from an empty scratchpad
+        registry.dispatch(&command("book").with_message("book the hotel"), &test_context()).await.unwrap();
+        assert_eq!(model.prompts.lock().unwrap().pop().unwrap(), "book the hotel");
+    }
+
+    #[tokio::test]
+    async fn test_new_users_get_an_onboarding_hint_in_the_prompt() {
+        let onboarding = Arc::new(OnboardingTracker::new(None));
+        let registry = IntentHandlerRegistry::new().with_onboarding(onboarding.clone());
+        let model = Arc::new(RecordingModel::default());
+        registry.set_completion_provider(model.clone());
+        let context = test_context();
+        let query = IntentRequest::from_intent(Intent::Query { query: "what can you do".to_string() });
+
+        registry.dispatch(&query, &context).await.unwrap();
+        let prompt = model.prompts.lock().unwrap().pop().unwrap();
+        assert!(prompt.starts_with("<onboarding>"));
+        assert!(prompt.contains("Set your preferences"));
+        assert!(prompt.ends_with("what can you do"));
+
+        for step in crate::onboarding::OnboardingStep::ALL {
+            onboarding.skip(context.user_id, step).unwrap();
+        }
+        registry.dispatch(&query, &context).await.unwrap();
+        assert_eq!(model.prompts.lock().unwrap().pop().unwrap(), "what can you do");
+    }
+
+    #[tokio::test]
+    async fn test_focus_from_chat_shortens_prompts_until_it_ends() {
+        let router = Arc::new(NotificationRouter::new(Arc::new(crate::notifications::LoggingSink), None));
+        let registry = IntentHandlerRegistry::new().with_focus(router.clone());
+        registry.register(PRIORITY_COMMAND, Arc::new(FocusHandler { router: router.clone() }));
+        let model = Arc::new(RecordingModel::default());
+        registry.set_completion_provider(model.clone());
+        let context = test_context();
+
+        let outcome = registry.dispatch(&command("settings").with_message("focus for two hours"), &context).await.unwrap();
+        assert!(outcome.response_text.starts_with("Focus mode is on until"), "{}", outcome.response_text);
+        assert!(router.in_focus(context.user_id));
+
+        let query = IntentRequest::from_intent(Intent::Query { query: "summarize my notes".to_string() });
+        registry.dispatch(&query, &context).await.unwrap();
+        let prompt = model.prompts.lock().unwrap().pop().unwrap();
+        assert!(prompt.starts_with("<focus>"));
+        assert!(prompt.ends_with("summarize my notes"));
+
+        let outcome = registry.dispatch(&command("settings").with_message("stop focusing"), &context).await.unwrap();
+        assert_eq!(outcome.response_text, "Focus mode is off. Nothing came in while you were focused.");
+        registry.dispatch(&query, &context).await.unwrap();
+        assert_eq!(model.prompts.lock().unwrap().pop().unwrap(), "summarize my notes");
+    }
+
+    #[tokio::test]
+    async fn test_conversational_topics_are_not_searched() {
+        let request = IntentRequest::from_intent(Intent::Information { topic: "help".to_string() });
+        assert!(ConversationalHandler.can_handle(&request));
+
+        let outcome = ConversationalHandler.handle(&request, &test_context()).await.unwrap().unwrap();
+        assert!(!outcome.actions.is_empty());
+    }
+
+    #[test]
+    fn test_settings_value_extraction() {
+        assert_eq!(
+            SettingsHandler::requested_value("Set my timezone to Europe/Vienna."),
+            Some("Europe/Vienna".to_string())
+        );
+        assert_eq!(SettingsHandler::requested_value("change the language"), None);
+    }
+
+    async fn settings_session() -> (SettingsHandler, Arc<RwLock<ContextManager>>, Uuid) {
+        let context_manager = Arc::new(RwLock::new(ContextManager::new()));
+        let session_id = context_manager
+            .write()
+            .await
+            .create_session(Uuid::new_v4(), test_context().preferences)
+            .await
+            .unwrap();
+        let handler = SettingsHandler { context_manager: context_manager.clone(), events: Arc::new(EventBus::default()) };
+        (handler, context_manager, session_id)
+    }
+
+    async fn change_setting(
+        handler: &SettingsHandler,
+        context_manager: &RwLock<ContextManager>,
+        session_id: Uuid,
+        text: &str,
+    ) -> (HandlerOutcome, UserPreferences) {
+        let context = context_manager.read().await.get_user_context(session_id).await.unwrap().clone();
+        let request = IntentRequest::from_intent(Intent::Command {
+            action: "settings".to_string(),
+            parameters: vec![text.to_string()],
+        });
+        let outcome = handler.handle(&request, &context).await.unwrap().unwrap();
+        let preferences = context_manager.read().await.get_user_context(session_id).await.unwrap().preferences.clone();
+        (outcome, preferences)
+    }
+
+    #[tokio::test]
+    async fn test_settings_changes_are_persisted() {
+        let (handler, context_manager, session_id) = settings_session().await;
+
+        let (outcome, preferences) = change_setting(&handler, &context_manager, session_id, "Switch to the female voice").await;
+        assert_eq!(preferences.voice_settings.voice_id, "21m00Tcm4TlvDq8ikWAM");
+        assert_eq!(outcome.response_text, "Done, I speak with Rachel's voice now.");
+        assert_eq!(outcome.performed.len(), 1);
+
+        let (_, preferences) = change_setting(&handler, &context_manager, session_id, "speak faster").await;
+        assert_eq!(preferences.voice_settings.speed, 1.25);
+
+        let (outcome, preferences) =
+            change_setting(&handler, &context_manager, session_id, "disable notifications after 10pm").await;
+        let quiet_hours = preferences.notification_settings.quiet_hours.unwrap();
+        assert_eq!((quiet_hours.start.as_str(), quiet_hours.end.as_str()), ("22:00", "07:00"));
+        assert!(outcome.response_text.contains("22:00–07:00"));
+
+        let (_, preferences) = change_setting(&handler, &context_manager, session_id, "enable email notifications").await;
+        assert_eq!(preferences.notification_settings.channels, vec![NotificationChannel::Email]);
+        assert!(preferences.notification_settings.enabled);
+
+        let (_, preferences) = change_setting(&handler, &context_manager, session_id, "switch to German").await;
+        assert_eq!(preferences.language, "de");
+        let (_, preferences) =
+            change_setting(&handler, &context_manager, session_id, "set my timezone to Europe/Vienna").await;
+        assert_eq!(preferences.timezone, "Europe/Vienna");
+        // Earlier changes are kept
+        assert_eq!(preferences.voice_settings.speed, 1.25);
+    }
+
+    #[tokio::test]
+    async fn test_ambiguous_settings_ask_with_the_candidates() {
+        let (handler, context_manager, session_id) = settings_session().await;
+
+        let (outcome, preferences) = change_setting(&handler, &context_manager, session_id, "quiet hours after 10").await;
+        assert!(preferences.notification_settings.quiet_hours.is_none());
+        assert!(outcome.performed.is_empty());
+        let starts: Vec<&str> = outcome
+            .actions
+            .iter()
+            .map(|action| action.parameters["value"]["start"].as_str().unwrap())
+            .collect();
+        assert_eq!(starts, vec!["10:00", "22:00"]);
+        assert_eq!(outcome.actions[0].parameters["setting"], "notification_settings.quiet_hours");
+
+        let (outcome, _) = change_setting(&handler, &context_manager, session_id, "change the voice").await;
+        assert_eq!(outcome.response_text, "Which voice would you like?");
+        assert_eq!(outcome.actions.len(), 4);
+        assert!(outcome.actions.iter().all(|action| action.action_type == "change_setting"));
+
+        let (outcome, _) = change_setting(&handler, &context_manager, session_id, "change my notifications").await;
+        assert_eq!(outcome.actions.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_and_unsupported_settings_change_nothing() {
+        let (handler, context_manager, session_id) = settings_session().await;
+        let before = test_context().preferences;
+
+        for (text, expected) in [
+            ("set the voice speed to 3", "between 0.5x and 2x"),
+            ("set my timezone to Mars/Olympus", "not a timezone I know"),
+            ("set the language to klingon", "not a language code I know"),
+            ("turn up the volume", "I can't change the volume setting"),
+        ] {
+            let (outcome, preferences) = change_setting(&handler, &context_manager, session_id, text).await;
+            assert!(outcome.response_text.contains(expected), "{}: {}", text, outcome.response_text);
+            assert!(outcome.performed.is_empty(), "{}", text);
+            assert_eq!(preferences.voice_settings.speed, before.voice_settings.speed);
+            assert_eq!(preferences.timezone, before.timezone);
+            assert_eq!(preferences.language, before.language);
+        }
+    }
+}
@@ -0,0 +1,646 @@
+use rusty_ai_common::{
+    AssistantError, Document, KnowledgeDigestSchedule, NotificationCategory, Result, UserPreferences,
+};
+use chrono::{DateTime, Datelike, Duration, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::activity::{self, ActionKind, ActionTrigger, AssistantAction, PerformedAction};
+use crate::flags::{FeatureFlags, Flag};
+use crate::notifications::{resolve_local, Clock, Notification, NotificationRouter, SystemClock};
+use crate::storage::Storage;
+
+/// Tag on documents holding facts the assistant extracted from conversations;
+/// the digest lists these for review instead of grouping them with documents
+pub const MEMORY_FACT_TAG: &str = "extracted";
+
+#[derive(Debug, Clone)]
+pub struct DigestConfig {
+    pub max_documents_per_group: usize,
+    pub most_referenced: usize,
+    /// Period covered by a user's first digest
+    pub first_period_days: i64,
+}
+
+impl Default for DigestConfig {
+    fn default() -> Self {
+        Self {
+            max_documents_per_group: 10,
+            most_referenced: 5,
+            first_period_days: 7,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KnowledgeDigest {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub period_start: DateTime<Utc>,
+    pub period_end: DateTime<Utc>,
+    pub generated_at: DateTime<Utc>,
+    /// Documents ingested during the period, memory facts excluded
+    pub document_count: usize,
+    pub groups: Vec<DigestGroup>,
+    pub tags: Vec<TagCount>,
+    pub most_referenced: Vec<ReferencedDocument>,
+    pub memory_facts: Vec<DigestFact>,
+}
+
+/// New documents from one source
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DigestGroup {
+    pub source: String,
+    pub documents: Vec<DigestDocument>,
+    /// Documents from this source left out of `documents`
+    pub more: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DigestDocument {
+    pub id: Uuid,
+    pub title: String,
+    pub summary: Option<String>,
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TagCount {
+    pub tag: String,
+    pub documents: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReferencedDocument {
+    pub id: Uuid,
+    pub title: String,
+    pub references: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DigestFact {
+    pub id: Uuid,
+    pub text: String,
+    pub learned_at: DateTime<Utc>,
+}
+
+impl KnowledgeDigest {
+    pub fn is_empty(&self) -> bool {
+        self.document_count == 0 && self.most_referenced.is_empty() && self.memory_facts.is_empty()
+    }
+
+    // Plain-text body for email and in-app delivery
+    pub fn render(&self) -> String {
+        let mut content = format!(
+            "Your knowledge base from {} to {}\n\n",
+            self.period_start.format("%Y-%m-%d"),
+            self.period_end.format("%Y-%m-%d")
+        );
+
+        if self.document_count > 0 {
+            content.push_str(&format!("**New Documents ({}):**\n", self.document_count));
+            for group in &self.groups {
+                content.push_str(&format!("{}:\n", group.source));
+                for doc in &group.documents {
+                    match &doc.summary {
+                        Some(summary) => content.push_str(&format!("- **{}**: {}\n", doc.title, summary)),
+                        None => content.push_str(&format!("- **{}**\n", doc.title)),
+                    }
+                }
+                if group.more > 0 {
+                    content.push_str(&format!("... and {} more\n", group.more));
+                }
+            }
+            content.push('\n');
+        }
+
+        if !self.tags.is_empty() {
+            let tags: Vec<String> = self.tags.iter().map(|t| format!("{} ({})", t.tag, t.documents)).collect();
+            content.push_str(&format!("**Topics:** {}\n\n", tags.join(", ")));
+        }
+
+        if !self.most_referenced.is_empty() {
+            content.push_str("**Most Referenced:**\n");
+            for doc in &self.most_referenced {
+                content.push_str(&format!("- {} ({} times)\n", doc.title, doc.references));
+            }
+            content.push('\n');
+        }
+
+        if !self.memory_facts.is_empty() {
+            content.push_str("**Things I Learned About You (please review):**\n");
+            for fact in &self.memory_facts {
+                content.push_str(&format!("- {}\n", fact.text));
+            }
+        }
+
+        content
+    }
+}
+
+// Weekly summary of what entered the knowledge base, separate from the daily
+// briefing. Each user's digest covers everything since their previous one
+// and goes out at the weekday and time in their preferences.
+pub struct KnowledgeDigestGenerator {
+    storage: Arc<dyn Storage + Send + Sync>,
+    router: Arc<NotificationRouter>,
+    clock: Arc<dyn Clock>,
+    config: DigestConfig,
+    flags: Option<Arc<FeatureFlags>>,
+}
+
+impl KnowledgeDigestGenerator {
+    pub fn new(storage: Arc<dyn Storage + Send + Sync>, router: Arc<NotificationRouter>) -> Self {
+        Self::new_with_clock(storage, router, Arc::new(SystemClock), DigestConfig::default())
+    }
+
+    pub fn new_with_clock(
+        storage: Arc<dyn Storage + Send + Sync>,
+        router: Arc<NotificationRouter>,
+        clock: Arc<dyn Clock>,
+        config: DigestConfig,
+    ) -> Self {
+        Self {
+            storage,
+            router,
+            clock,
+            config,
+            flags: None,
+        }
+    }
+
+    /// Skip users for whom proactive messages are switched off
+    pub fn with_flags(mut self, flags: Arc<FeatureFlags>) -> Self {
+        self.flags = Some(flags);
+        self
+    }
+
+    // The latest scheduled slot at or before `now`
+    pub fn last_scheduled_slot(
+        schedule: &KnowledgeDigestSchedule,
+        timezone: &str,
+        now: DateTime<Utc>,
+    ) -> Result<DateTime<Utc>> {
+        let time = schedule.parse_time()?;
+        let tz: Tz = timezone.parse().unwrap_or_else(|_| {
+            warn!("Unknown timezone '{}', using UTC for the knowledge digest", timezone);
+            Tz::UTC
+        });
+
+        let local_now = now.with_timezone(&tz);
+        let days_back = (local_now.weekday().num_days_from_monday() + 7 - schedule.day.num_days_from_monday()) % 7;
+        let date = local_now.date_naive() - Duration::days(days_back as i64);
+
+        let slot = resolve_local(&tz, date.and_time(time));
+        if slot > now {
+            Ok(resolve_local(&tz, (date - Duration::days(7)).and_time(time)))
+        } else {
+            Ok(slot)
+        }
+    }
+
+    pub async fn is_due(&self, user_id: Uuid, preferences: &UserPreferences) -> Result<bool> {
+        let schedule = &preferences.notification_settings.knowledge_digest;
+        if !schedule.enabled {
+            return Ok(false);
+        }
+
+        let now = self.clock.now();
+        let slot = Self::last_scheduled_slot(schedule, &preferences.timezone, now)?;
+        let last = self.storage.get_knowledge_digests(user_id, 1).await?;
+        Ok(last.first().map_or(true, |digest| digest.period_end < slot))
+    }
+
+    // Generate and deliver digests for every user whose slot has passed.
+    // One user's failure does not stop the others
+    pub async fn run_due(&self, users: &[(Uuid, UserPreferences)]) -> usize {
+        let mut generated = 0;
+        for (user_id, preferences) in users {
+            if let Some(flags) = &self.flags {
+                if !flags.enabled(Flag::ProactiveMessages, *user_id) {
+                    continue;
+                }
+            }
+            match self.is_due(*user_id, preferences).await {
+                Ok(true) => {}
+                Ok(false) => continue,
+                Err(e) => {
+                    warn!("Cannot schedule knowledge digest for {}: {}", user_id, e);
+                    continue;
+                }
+            }
+
+            match self.generate(*user_id, preferences).await {
+                Ok(_) => generated += 1,
+                Err(e) => error!("Failed to generate knowledge digest for {}: {}", user_id, e),
+            }
+        }
+        generated
+    }
+
+    // Build, store and deliver a digest covering everything since the user's
+    // previous digest. An empty week is stored but not sent
+    pub async fn generate(&self, user_id: Uuid, preferences: &UserPreferences) -> Result<KnowledgeDigest> {
+        let now = self.clock.now();
+        let period_start = self
+            .storage
+            .get_knowledge_digests(user_id, 1)
+            .await?
+            .first()
+            .map(|digest| digest.period_end)
+            .unwrap_or_else(|| now - Duration::days(self.config.first_period_days));
+
+        let added = self.storage.get_documents_created_between(period_start, now).await?;
+        let (facts, documents): (Vec<Document>, Vec<Document>) = added
+            .into_iter()
+            .partition(|doc| doc.metadata.tags.iter().any(|tag| tag == MEMORY_FACT_TAG));
+
+        let digest = KnowledgeDigest {
+            id: Uuid::new_v4(),
+            user_id,
+            period_start,
+            period_end: now,
+            generated_at: now,
+            document_count: documents.len(),
+            groups: self.group_by_source(&documents),
+            tags: count_tags(&documents),
+            most_referenced: self.most_referenced(period_start, now).await?,
+            memory_facts: facts
+                .into_iter()
+                .map(|fact| DigestFact {
+                    id: fact.id,
+                    text: fact.content,
+                    learned_at: fact.created_at,
+                })
+                .collect(),
+        };
+
+        self.storage.store_knowledge_digest(&digest).await?;
+
+        if digest.is_empty() {
+            info!("Nothing entered the knowledge base for {}; digest not sent", user_id);
+            return Ok(digest);
+        }
+
+        let notification = Notification::new(
+            user_id,
+            NotificationCategory::KnowledgeDigest,
+            "Your weekly knowledge digest",
+            digest.render(),
+        );
+        let mut sent = PerformedAction::new(
+            ActionKind::NotificationSent,
+            format!("Sent your weekly knowledge digest covering {} documents", digest.document_count),
+        )
+        .resource(digest.id);
+        if let Err(e) = self.router.route(preferences, notification).await {
+            warn!("Failed to route knowledge digest {}: {}", digest.id, e);
+            sent = sent.failed(e.to_string());
+        }
+        let action = AssistantAction::new(user_id, ActionTrigger::scheduler("knowledge_digest"), sent);
+        activity::record_action(self.storage.as_ref(), &action).await;
+
+        info!(
+            "Generated knowledge digest for {} with {} documents and {} memory facts",
+            user_id,
+            digest.document_count,
+            digest.memory_facts.len()
+        );
+        Ok(digest)
+    }
+
+    pub async fn history(&self, user_id: Uuid, limit: usize) -> Result<Vec<KnowledgeDigest>> {
+        self.storage.get_knowledge_digests(user_id, limit).await
+    }
+
+    fn group_by_source(&self, documents: &[Document]) -> Vec<DigestGroup> {
+        let mut by_source: BTreeMap<&str, Vec<&Document>> = BTreeMap::new();
+        for doc in documents {
+            by_source.entry(doc.metadata.source.as_str()).or_default().push(doc);
+        }
+
+        let mut groups: Vec<DigestGroup> = by_source
+            .into_iter()
+            .map(|(source, docs)| DigestGroup {
+                source: source.to_string(),
+                more: docs.len().saturating_sub(self.config.max_documents_per_group),
+                documents: docs
+                    .into_iter()
+                    .take(self.config.max_documents_per_group)
+                    .map(|doc| DigestDocument {
+                        id: doc.id,
+                        title: doc.title.clone(),
+                        summary: doc.metadata.summary.clone(),
+                        tags: doc.metadata.tags.clone(),
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        // Busiest sources first; the map already ordered ties by name
+        groups.sort_by(|a, b| (b.documents.len() + b.more).cmp(&(a.documents.len() + a.more)));
+        groups
+    }
+
+    // Documents looked up most often during the period; ones deleted since
+    // are left out
+    async fn most_referenced(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Result<Vec<ReferencedDocument>> {
+        let counts = self
+            .storage
+            .get_document_access_counts(start, end, self.config.most_referenced)
+            .await?;
+
+        let mut referenced = Vec::with_capacity(counts.len());
+        for (id, references) in counts {
+            match self.storage.get_document(id).await {
+                Ok(Some(doc)) => referenced.push(ReferencedDocument {
+                    id,
+                    title: doc.title,
+                    references,
+                }),
+                Ok(None) => {}
+                Err(AssistantError::NotFound(_)) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(referenced)
+    }
+}
+
+fn count_tags(documents: &[Document]) -> Vec<TagCount> {
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for doc in documents {
+        for tag in &doc.metadata.tags {
+            *counts.entry(tag.as_str()).or_insert(0) += 1;
+        }
+    }
+
+    let mut tags: Vec<TagCount> = counts
+        .into_iter()
+        .map(|(tag, documents)| TagCount {
+            tag: tag.to_string(),
+            documents,
+        })
+        .collect();
+    tags.sort_by(|a, b| b.documents.cmp(&a.documents));
+    tags
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::retention::{CleanupReport, RetentionPolicy};
+    use crate::storage::{StorageHealth, StorageStatus};
+    use crate::test_support::{at, RecordingSink, TestClock};
+    use async_trait::async_trait;
+    use chrono::Weekday;
+    use rusty_ai_common::{
+        DailyBriefing, DocumentMetadata, NotificationChannel, NotificationSettings, Task, TaskStatus, VoiceSettings,
+    };
+    use std::sync::Mutex;
+
+    // A week of documents, lookups and stored digests
+    #[derive(Default)]
+    struct FixtureStorage {
+        documents: Vec<Document>,
+        accesses: Vec<(Uuid, DateTime<Utc>)>,
+        digests: Mutex<Vec<KnowledgeDigest>>,
+    }
+
+    #[async_trait]
+    impl Storage for FixtureStorage {
+        async fn store_document(&self, _document: &Document) -> Result<()> { Ok(()) }
+        async fn get_document(&self, id: Uuid) -> Result<Option<Document>> {
+            Ok(self.documents.iter().find(|doc| doc.id == id).cloned())
+        }
+        async fn update_document(&self, _document: &Document) -> Result<()> { Ok(()) }
+        async fn delete_document(&self, _id: Uuid) -> Result<()> { Ok(()) }
+        async fn search_documents(&self, _query: &str, _limit: usize) -> Result<Vec<Document>> { Ok(self.documents.clone()) }
+        async fn get_documents_by_tags(&self, _tags: &[String], _limit: usize) -> Result<Vec<Document>> { Ok(Vec::new()) }
+        async fn store_task(&self, _task: &Task) -> Result<()> { Ok(()) }
+        async fn get_task(&self, _id: Uuid) -> Result<Option<Task>> { Ok(None) }
+        async fn update_task_status(&self, _id: Uuid, _status: TaskStatus) -> Result<()> { Ok(()) }
+        async fn get_pending_tasks(&self) -> Result<Vec<Task>> { Ok(Vec::new()) }
+        async fn get_tasks_by_status(&self, _status: TaskStatus) -> Result<Vec<Task>> { Ok(Vec::new()) }
+        async fn store_briefing(&self, _briefing: &DailyBriefing) -> Result<()> { Ok(()) }
+        async fn get_briefing(&self, _id: Uuid) -> Result<Option<DailyBriefing>> { Ok(None) }
+        async fn get_latest_briefing(&self) -> Result<Option<DailyBriefing>> { Ok(None) }
+        async fn get_briefings_by_date_range(&self, _start: DateTime<Utc>, _end: DateTime<Utc>) -> Result<Vec<DailyBriefing>> { Ok(Vec::new()) }
+        async fn get_document_access_counts(&self, start: DateTime<Utc>, end: DateTime<Utc>, limit: usize) -> Result<Vec<(Uuid, u64)>> {
+            let mut counts: BTreeMap<Uuid, u64> = BTreeMap::new();
+            for (id, when) in &self.accesses {
+                if *when >= start && *when < end {
+                    *counts.entry(*id).or_insert(0) += 1;
+                }
+            }
+            let mut counts: Vec<(Uuid, u64)> = counts.into_iter().collect();
+            counts.sort_by(|a, b| b.1.cmp(&a.1));
+            counts.truncate(limit);
+            Ok(counts)
+        }
+        async fn store_knowledge_digest(&self, digest: &KnowledgeDigest) -> Result<()> {
+            self.digests.lock().unwrap().push(digest.clone());
+            Ok(())
+        }
+        async fn get_knowledge_digests(&self, _user_id: Uuid, limit: usize) -> Result<Vec<KnowledgeDigest>> {
+            let mut digests = self.digests.lock().unwrap().clone();
+            digests.sort_by(|a, b| b.period_end.cmp(&a.period_end));
+            digests.truncate(limit);
+            Ok(digests)
+        }
+        async fn cleanup_old_data(&self, _policy: &RetentionPolicy, dry_run: bool) -> Result<CleanupReport> { Ok(CleanupReport::new(dry_run)) }
+        async fn health_check(&self) -> Result<StorageHealth> {
+            Ok(StorageHealth {
+                status: StorageStatus::Healthy,
+                connection_pool_size: None,
+                pending_migrations: None,
+                disk_usage_mb: None,
+                last_backup: None,
+            })
+        }
+    }
+
+    fn document(title: &str, source: &str, tags: &[&str], summary: Option<&str>, created_at: &str) -> Document {
+        Document {
+            id: Uuid::new_v4(),
+            title: title.to_string(),
+            content: title.to_string(),
+            metadata: DocumentMetadata {
+                source: source.to_string(),
+                file_type: "text".to_string(),
+                tags: tags.iter().map(|t| t.to_string()).collect(),
+                summary: summary.map(|s| s.to_string()),
+                importance_score: 0.5,
+                embeddings: None,
+            },
+            created_at: at(created_at),
+            updated_at: at(created_at),
+        }
+    }
+
+    fn preferences(day: Weekday, time: &str) -> UserPreferences {
+        UserPreferences {
+            language: "en".to_string(),
+            timezone: "Europe/Berlin".to_string(),
+            voice_settings: VoiceSettings {
+                enabled: false,
+                voice_id: "default".to_string(),
+                speed: 1.0,
+                pitch: 1.0,
+            },
+            notification_settings: NotificationSettings {
+                enabled: true,
+                channels: vec![NotificationChannel::Email, NotificationChannel::InApp],
+                quiet_hours: None,
+                routing: Default::default(),
+                knowledge_digest: KnowledgeDigestSchedule {
+                    enabled: true,
+                    day,
+                    time: time.to_string(),
+                },
+            },
+        }
+    }
+
+    // Monday 2024-06-03 to Sunday 2024-06-09, plus one document from the
+    // week before
+    fn seeded_week() -> FixtureStorage {
+        let old = document("Old notes", "upload", &["misc"], None, "2024-05-30T09:00:00Z");
+        let policy = document("Travel policy", "upload", &["work", "travel"], Some("Per diem and booking rules"), "2024-06-03T10:00:00Z");
+        let handbook = document("Team handbook", "upload", &["work"], Some("How the team works"), "2024-06-05T15:00:00Z");
+        let article = document("Rust async book", "crawler:rust-lang.org", &["rust"], None, "2024-06-06T08:00:00Z");
+        let fact = document("User prefers window seats", "memory", &[MEMORY_FACT_TAG], None, "2024-06-07T19:00:00Z");
+
+        let accesses = vec![
+            (policy.id, at("2024-06-04T09:00:00Z")),
+            (policy.id, at("2024-06-06T09:00:00Z")),
+            (policy.id, at("2024-06-08T09:00:00Z")),
+            (old.id, at("2024-06-05T09:00:00Z")),
+            // Before the period
+            (old.id, at("2024-05-31T09:00:00Z")),
+            (old.id, at("2024-05-31T10:00:00Z")),
+            (old.id, at("2024-05-31T11:00:00Z")),
+        ];
+
+        FixtureStorage {
+            documents: vec![old, policy, handbook, article, fact],
+            accesses,
+            digests: Mutex::new(Vec::new()),
+        }
+    }
+
+    fn generator(storage: Arc<FixtureStorage>, clock: Arc<TestClock>, sink: Arc<RecordingSink>) -> KnowledgeDigestGenerator {
+        let router = Arc::new(NotificationRouter::new_with_clock(clock.clone(), sink, None));
+        KnowledgeDigestGenerator::new_with_clock(storage, router, clock, DigestConfig::default())
+    }
+
+    #[tokio::test]
+    async fn test_weekly_digest_groups_new_documents_and_lists_facts() {
+        // Monday 2024-06-10, 08:00 in Berlin
+        let clock = TestClock::at("2024-06-10T06:00:00Z");
+        let storage = Arc::new(seeded_week());
+        let sink = Arc::new(RecordingSink::default());
+        let generator = generator(storage.clone(), clock, sink.clone());
+        let user = Uuid::new_v4();
+
+        let generated = generator.run_due(&[(user, preferences(Weekday::Mon, "08:00"))]).await;
+        assert_eq!(generated, 1);
+
+        let digest = &generator.history(user, 10).await.unwrap()[0];
+        assert_eq!(digest.period_start, at("2024-06-03T06:00:00Z"));
+        assert_eq!(digest.document_count, 3);
+        assert_eq!(digest.groups[0].source, "upload");
+        assert_eq!(
+            digest.groups[0].documents.iter().map(|d| d.title.as_str()).collect::<Vec<_>>(),
+            vec!["Travel policy", "Team handbook"]
+        );
+        assert_eq!(digest.groups[0].documents[0].summary.as_deref(), Some("Per diem and booking rules"));
+        assert_eq!(digest.groups[1].source, "crawler:rust-lang.org");
+        assert_eq!(digest.tags[0], TagCount { tag: "work".to_string(), documents: 2 });
+
+        // Only lookups inside the period count
+        let referenced: Vec<(&str, u64)> = digest.most_referenced.iter().map(|d| (d.title.as_str(), d.references)).collect();
+        assert_eq!(referenced, vec![("Travel policy", 3), ("Old notes", 1)]);
+
+        assert_eq!(digest.memory_facts.len(), 1);
+        assert_eq!(digest.memory_facts[0].text, "User prefers window seats");
+
+        // Delivered by email and in-app, per the digest's default routing
+        let delivered = sink.bodies();
+        assert_eq!(
+            delivered.iter().map(|(channel, _)| channel.clone()).collect::<Vec<_>>(),
+            vec![NotificationChannel::Email, NotificationChannel::InApp]
+        );
+        assert!(delivered[0].1.contains("Things I Learned About You"));
+    }
+
+    #[tokio::test]
+    async fn test_schedule_follows_preferred_day_and_opt_out() {
+        // Sunday 2024-06-09, 20:00 in Berlin
+        let clock = TestClock::at("2024-06-09T18:00:00Z");
+        let storage = Arc::new(seeded_week());
+        let sink = Arc::new(RecordingSink::default());
+        let generator = generator(storage.clone(), clock.clone(), sink.clone());
+        let user = Uuid::new_v4();
+
+        let mut opted_out = preferences(Weekday::Sun, "09:00");
+        opted_out.notification_settings.knowledge_digest.enabled = false;
+        assert_eq!(generator.run_due(&[(user, opted_out)]).await, 0);
+
+        // Moving the day to Sunday makes it due today
+        let sunday = preferences(Weekday::Sun, "09:00");
+        assert_eq!(generator.run_due(&[(user, sunday.clone())]).await, 1);
+        // Not again until next Sunday
+        assert_eq!(generator.run_due(&[(user, sunday.clone())]).await, 0);
+        clock.set("2024-06-16T06:59:00Z");
+        assert_eq!(generator.run_due(&[(user, sunday.clone())]).await, 0);
+        clock.set("2024-06-16T07:00:00Z");
+        assert_eq!(generator.run_due(&[(user, sunday)]).await, 1);
+
+        // The second digest starts where the first ended and nothing new
+        // arrived, so it is stored but not sent
+        let history = generator.history(user, 10).await.unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].period_start, at("2024-06-09T18:00:00Z"));
+        assert!(history[0].is_empty());
+        assert_eq!(sink.count(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_proactive_messages_flag_pauses_digests() {
+        let clock = TestClock::at("2024-06-09T18:00:00Z");
+        let sink = Arc::new(RecordingSink::default());
+        let flags = Arc::new(FeatureFlags::default());
+        let generator = generator(Arc::new(seeded_week()), clock, sink.clone()).with_flags(flags.clone());
+        let user = Uuid::new_v4();
+        let sunday = preferences(Weekday::Sun, "09:00");
+
+        let paused = crate::flags::FlagOverride { users: [(user, false)].into(), ..Default::default() };
+        flags.set(Flag::ProactiveMessages, Some(paused)).await.unwrap();
+        assert_eq!(generator.run_due(&[(user, sunday.clone())]).await, 0);
+        assert_eq!(sink.count(), 0);
+
+        flags.set(Flag::ProactiveMessages, None).await.unwrap();
+        assert_eq!(generator.run_due(&[(user, sunday)]).await, 1);
+    }
+
+    #[test]
+    fn test_last_slot_uses_local_time() {
+        let schedule = KnowledgeDigestSchedule {
+            enabled: true,
+            day: Weekday::Fri,
+            time: "17:30".to_string(),
+        };
+
+        // Friday 17:29 in New York is still before this week's slot
+        let slot = KnowledgeDigestGenerator::last_scheduled_slot(&schedule, "America/New_York", at("2024-06-14T21:29:00Z")).unwrap();
+        assert_eq!(slot, at("2024-06-07T21:30:00Z"));
+
+        let slot = KnowledgeDigestGenerator::last_scheduled_slot(&schedule, "America/New_York", at("2024-06-14T21:30:00Z")).unwrap();
+        assert_eq!(slot, at("2024-06-14T21:30:00Z"));
+    }
+}
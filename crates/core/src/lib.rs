@@ -3,10 +3,40 @@ pub mod plugin_manager;
 pub mod context_manager;
 pub mod storage;
 pub mod briefing;
+pub mod briefing_schema;
 pub mod intent;
+pub mod intent_handlers;
+pub mod entities;
 pub mod database;
+pub mod notifications;
+pub mod focus;
+pub mod knowledge_digest;
+pub mod alerts;
+pub mod sharing;
+pub mod health;
+pub mod resources;
+pub mod response_processing;
+pub mod audit;
+pub mod sync;
+pub mod time_tracking;
+pub mod activity;
+pub mod suggest;
+pub mod flags;
+pub mod admission;
+pub mod events;
+pub mod onboarding;
+pub mod services;
+pub mod warmup;
+pub mod retention;
+pub mod session_stream;
+#[cfg(test)]
+pub(crate) mod test_support;
+
+// Shared with the server's conversation store
+pub use rusty_ai_common::query_metrics;
 
 use rusty_ai_common::{Result, AssistantError};
+use rusty_ai_common::scratchpad::Scratchpads;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
@@ -17,50 +47,355 @@ pub struct AssistantCore {
     pub storage: Arc<dyn storage::Storage + Send + Sync>,
     pub intent_classifier: Arc<intent::IntentClassifier>,
     pub briefing_generator: Arc<briefing::BriefingGenerator>,
+    pub notification_router: Arc<notifications::NotificationRouter>,
+    pub knowledge_digests: Arc<knowledge_digest::KnowledgeDigestGenerator>,
+    pub alerts: Arc<alerts::AlertScheduler>,
+    pub share_links: Arc<sharing::ShareLinkStore>,
+    pub health: Arc<health::HealthRegistry>,
+    pub resources: Arc<resources::ResourceRegistry>,
+    pub response_processor: Arc<response_processing::ResponseProcessor>,
+    pub audit: Arc<audit::AuditTrail>,
+    pub activity: Arc<activity::ActivityLog>,
+    pub suggestions: Arc<suggest::SuggestionIndex>,
+    pub flags: Arc<flags::FeatureFlags>,
+    pub admission: Arc<admission::AdmissionController>,
+    pub events: Arc<events::EventBus>,
+    pub scratchpads: Arc<Scratchpads>,
+    pub onboarding: Arc<onboarding::OnboardingTracker>,
+    pub warmup: Arc<warmup::SessionWarmup>,
+    pub maintenance: Arc<retention::DataMaintenance>,
+    pub session_streams: Arc<session_stream::SessionStreams>,
+    running: services::RunningServices,
 }
 
 impl AssistantCore {
     pub async fn new(config: CoreConfig) -> Result<Self> {
-        let storage = storage::create_storage(&config.storage_config).await?;
-        let plugin_manager = Arc::new(plugin_manager::PluginManager::new());
-        let context_manager = Arc::new(RwLock::new(context_manager::ContextManager::new()));
-        let intent_classifier = Arc::new(intent::IntentClassifier::new());
-        let briefing_generator = Arc::new(briefing::BriefingGenerator::new(storage.clone()));
-        let orchestrator = Arc::new(orchestrator::Orchestrator::new(
-            plugin_manager.clone(),
-            context_manager.clone(),
-            storage.clone(),
-        ));
-        
+        let running = Self::service_registry(&config).start().await?;
+        let health = Arc::new(health::HealthRegistry::new(config.health_config()));
+        health.set_startup(running.statuses());
+
+        let storage: Arc<SharedStorage> = running.get("storage")?;
+        let plugin_manager = running.get("plugin_manager")?;
+        register_core_probes(&health, &storage, &plugin_manager);
+
         Ok(Self {
-            orchestrator,
+            orchestrator: running.get("orchestrator")?,
             plugin_manager,
-            context_manager,
+            context_manager: running.get("context_manager")?,
             storage,
-            intent_classifier,
-            briefing_generator,
+            intent_classifier: running.get("intent_classifier")?,
+            briefing_generator: running.get("briefing_generator")?,
+            notification_router: running.get("notification_router")?,
+            knowledge_digests: running.get("knowledge_digests")?,
+            alerts: running.get("alerts")?,
+            share_links: running.get("share_links")?,
+            health,
+            resources: running.get("resources")?,
+            response_processor: running.get("response_processor")?,
+            audit: running.get("audit")?,
+            activity: running.get("activity")?,
+            suggestions: running.get("suggestions")?,
+            flags: running.get("flags")?,
+            admission: running.get("admission")?,
+            events: running.get("events")?,
+            scratchpads: running.get("scratchpads")?,
+            onboarding: running.get("onboarding")?,
+            warmup: running.get("warmup")?,
+            maintenance: running.get("maintenance")?,
+            session_streams: running.get("session_streams")?,
+            running,
         })
     }
-    
+
+    /// Every service the core runs, with its dependencies. Tests can narrow
+    /// this with `ServiceRegistry::only` and start just what they exercise
+    pub fn service_registry(config: &CoreConfig) -> services::ServiceRegistry {
+        use services::{ServiceDef, Services};
+
+        let config = Arc::new(config.clone());
+        let mut registry = services::ServiceRegistry::new(std::time::Duration::from_millis(
+            config.service_init_timeout_ms,
+        ));
+
+        let cfg = config.clone();
+        registry.register(ServiceDef::<SharedStorage>::new("storage", move |_| {
+            let config = cfg.clone();
+            async move { storage::create_storage(&config.storage_config).await }
+        }));
+        registry.register(
+            ServiceDef::new("plugin_manager", |_| async { Ok(Arc::new(plugin_manager::PluginManager::new())) })
+                .on_shutdown(|plugin_manager| async move { plugin_manager.unload_all().await }),
+        );
+        registry.register(ServiceDef::new("scratchpads", |_| async { Ok(Arc::new(Scratchpads::new())) }));
+        registry.register(
+            ServiceDef::new("context_manager", |s: Services| async move {
+                Ok(Arc::new(RwLock::new(
+                    context_manager::ContextManager::new()
+                        .with_storage(s.get::<SharedStorage>("storage")?)
+                        .with_scratchpads(s.get("scratchpads")?),
+                )))
+            })
+            .depends_on(&["storage", "scratchpads"]),
+        );
+
+        // Flags that fail to load fall back to the configured defaults rather
+        // than keeping the assistant down
+        let cfg = config.clone();
+        let fallback_cfg = config.clone();
+        registry.register(
+            ServiceDef::new("flags", move |s: Services| {
+                let config = cfg.clone();
+                async move {
+                    let flags = Arc::new(
+                        flags::FeatureFlags::new(config.feature_flags.clone())
+                            .with_storage(s.get::<SharedStorage>("storage")?),
+                    );
+                    flags.load().await?;
+                    Ok(flags)
+                }
+            })
+            .depends_on(&["storage"])
+            .fallback(move |_| Arc::new(flags::FeatureFlags::new(fallback_cfg.feature_flags.clone()))),
+        );
+
+        let cfg = config.clone();
+        registry.register(ServiceDef::new("admission", move |_| {
+            let config = cfg.clone();
+            async move { Ok(Arc::new(admission::AdmissionController::new(config.admission.clone())?)) }
+        }));
+        registry.register(ServiceDef::new("events", |_| async { Ok(Arc::new(events::EventBus::default())) }));
+        registry.register(ServiceDef::new("session_streams", |_| async {
+            Ok(Arc::new(session_stream::SessionStreams::new()))
+        }));
+        let cfg = config.clone();
+        registry.register(ServiceDef::new("onboarding", move |_| {
+            let config = cfg.clone();
+            async move {
+                Ok(Arc::new(onboarding::OnboardingTracker::new(Some(
+                    config.onboarding_store_path.clone().into(),
+                ))))
+            }
+        }));
+        registry.register(ServiceDef::new("intent_classifier", |_| async {
+            Ok(Arc::new(intent::IntentClassifier::new()))
+        }));
+        registry.register(
+            ServiceDef::new("briefing_generator", |s: Services| async move {
+                Ok(Arc::new(briefing::BriefingGenerator::new(s.get::<SharedStorage>("storage")?)))
+            })
+            .depends_on(&["storage"]),
+        );
+        registry.register(
+            ServiceDef::new("orchestrator", |s: Services| async move {
+                Ok(Arc::new(orchestrator::Orchestrator::new(
+                    s.get("plugin_manager")?,
+                    s.get("context_manager")?,
+                    s.get::<SharedStorage>("storage")?,
+                    s.get("flags")?,
+                    s.get("admission")?,
+                    s.get("events")?,
+                    s.get("scratchpads")?,
+                    s.get("onboarding")?,
+                    s.get("notification_router")?,
+                )))
+            })
+            .depends_on(&[
+                "plugin_manager",
+                "context_manager",
+                "storage",
+                "flags",
+                "admission",
+                "events",
+                "scratchpads",
+                "onboarding",
+                "notification_router",
+            ])
+            .on_shutdown(|orchestrator| async move { orchestrator.shutdown().await }),
+        );
+
+        let cfg = config.clone();
+        registry.register(
+            ServiceDef::new("notification_router", move |s: Services| {
+                let config = cfg.clone();
+                async move {
+                    Ok(Arc::new(
+                        notifications::NotificationRouter::new(
+                            Arc::new(events::InAppEventSink::new(
+                                Arc::new(notifications::LoggingSink),
+                                s.get("events")?,
+                            )),
+                            Some(config.notification_store_path.clone().into()),
+                        )
+                        .with_focus_store(config.focus_store_path.clone().into()),
+                    ))
+                }
+            })
+            .depends_on(&["events"]),
+        );
+        registry.register(
+            ServiceDef::new("knowledge_digests", |s: Services| async move {
+                Ok(Arc::new(
+                    knowledge_digest::KnowledgeDigestGenerator::new(
+                        s.get::<SharedStorage>("storage")?,
+                        s.get("notification_router")?,
+                    )
+                    .with_flags(s.get("flags")?),
+                ))
+            })
+            .depends_on(&["storage", "notification_router", "flags"]),
+        );
+        registry.register(
+            ServiceDef::new("alerts", |s: Services| async move {
+                Ok(Arc::new(
+                    alerts::AlertScheduler::new(
+                        s.get::<SharedStorage>("storage")?,
+                        s.get("plugin_manager")?,
+                        s.get("notification_router")?,
+                    )
+                    .with_flags(s.get("flags")?),
+                ))
+            })
+            .depends_on(&["storage", "plugin_manager", "notification_router", "flags"]),
+        );
+
+        let cfg = config.clone();
+        registry.register(ServiceDef::new("share_links", move |_| {
+            let config = cfg.clone();
+            async move {
+                Ok(Arc::new(sharing::ShareLinkStore::new(
+                    &share_link_secret(&config)?,
+                    Some(config.share_store_path.clone().into()),
+                )))
+            }
+        }));
+        let cfg = config.clone();
+        registry.register(ServiceDef::new("response_processor", move |_| {
+            let config = cfg.clone();
+            async move {
+                Ok(Arc::new(response_processing::ResponseProcessor::new(
+                    config.response_processing.clone(),
+                )?))
+            }
+        }));
+        let cfg = config.clone();
+        registry.register(
+            ServiceDef::new("audit", move |s: Services| {
+                let config = cfg.clone();
+                async move {
+                    Ok(Arc::new(audit::AuditTrail::new(
+                        s.get::<SharedStorage>("storage")?,
+                        config.audit_retention_days,
+                    )))
+                }
+            })
+            .depends_on(&["storage"]),
+        );
+        registry.register(
+            ServiceDef::new("activity", |s: Services| async move {
+                Ok(Arc::new(activity::ActivityLog::new(
+                    s.get::<SharedStorage>("storage")?,
+                    s.get("context_manager")?,
+                )))
+            })
+            .depends_on(&["storage", "context_manager"]),
+        );
+        registry.register(
+            ServiceDef::new("suggestions", |s: Services| async move {
+                Ok(Arc::new(suggest::SuggestionIndex::new(s.get::<SharedStorage>("storage")?)))
+            })
+            .depends_on(&["storage"]),
+        );
+        registry.register(
+            ServiceDef::new("warmup", |s: Services| async move {
+                Ok(Arc::new(warmup::SessionWarmup::new(
+                    s.get::<SharedStorage>("storage")?,
+                    s.get("context_manager")?,
+                    s.get("notification_router")?,
+                    s.get("flags")?,
+                    s.get("events")?,
+                )))
+            })
+            .depends_on(&["storage", "context_manager", "notification_router", "flags", "events"]),
+        );
+        let cfg = config.clone();
+        registry.register(
+            ServiceDef::new("maintenance", move |s: Services| {
+                let config = cfg.clone();
+                async move {
+                    Ok(Arc::new(retention::DataMaintenance::new(
+                        s.get::<SharedStorage>("storage")?,
+                        s.get("notification_router")?,
+                        config.retention.clone(),
+                        config.cleanup_interval_days,
+                    )))
+                }
+            })
+            .depends_on(&["storage", "notification_router"]),
+        );
+        registry.register(
+            ServiceDef::new("resources", |s: Services| async move {
+                let resources = Arc::new(resources::ResourceRegistry::default());
+                resources.register(
+                    "session_store",
+                    Arc::new(context_manager::SessionStoreResources(s.get("context_manager")?)),
+                );
+                Ok(resources)
+            })
+            .depends_on(&["context_manager"]),
+        );
+
+        registry
+    }
+
+    /// How each service came up, in startup order
+    pub fn startup_statuses(&self) -> &[services::ServiceStatus] {
+        self.running.statuses()
+    }
+
     pub async fn initialize(&self) -> Result<()> {
         self.plugin_manager.load_plugins().await?;
         self.orchestrator.initialize().await?;
         Ok(())
     }
     
+    /// Stops services in reverse startup order, so nothing outlives what it
+    /// depends on
     pub async fn shutdown(&self) -> Result<()> {
-        self.orchestrator.shutdown().await?;
-        self.plugin_manager.unload_all().await?;
-        Ok(())
+        self.running.shutdown().await
     }
 }
 
+type SharedStorage = dyn storage::Storage + Send + Sync;
+
 #[derive(Debug, Clone)]
 pub struct CoreConfig {
     pub storage_config: storage::StorageConfig,
     pub database_config: database::DatabaseConfig,
     pub plugin_directory: String,
     pub max_concurrent_tasks: usize,
+    pub notification_store_path: String,
+    /// Focus sessions and the notifications they hold
+    pub focus_store_path: String,
+    pub share_store_path: String,
+    pub onboarding_store_path: String,
+    /// Signing secret for share links; a random per-process key is used when unset
+    pub share_link_secret: Option<String>,
+    /// Components that must be available for `/health/ready` to pass
+    pub health_critical_components: Vec<health::ComponentId>,
+    pub health_probe_timeout_ms: u64,
+    pub response_processing: response_processing::ResponseProcessingConfig,
+    /// How long admin audit entries are kept, independent of data cleanup
+    pub audit_retention_days: i64,
+    /// Flag states used until an admin overrides them at runtime
+    pub feature_flags: flags::FlagConfig,
+    /// Concurrency budget and per-class reservations for expensive requests
+    pub admission: admission::AdmissionConfig,
+    /// How long a service may take to initialize before startup gives up on
+    /// it (or, for optional services, carries on without it)
+    pub service_init_timeout_ms: u64,
+    /// How long each kind of data is kept before cleanup deletes it
+    pub retention: retention::RetentionPolicy,
+    pub cleanup_interval_days: i64,
 }
 
 impl Default for CoreConfig {
@@ -70,6 +405,94 @@ impl Default for CoreConfig {
             database_config: database::DatabaseConfig::default(),
             plugin_directory: "./plugins".to_string(),
             max_concurrent_tasks: 10,
+            notification_store_path: "./data/deferred_notifications.json".to_string(),
+            focus_store_path: "./data/focus.json".to_string(),
+            share_store_path: "./data/share_links.json".to_string(),
+            onboarding_store_path: "./data/onboarding.json".to_string(),
+            share_link_secret: None,
+            health_critical_components: vec![health::ComponentId::Storage],
+            health_probe_timeout_ms: health::DEFAULT_PROBE_TIMEOUT_MS,
+            response_processing: response_processing::ResponseProcessingConfig::default(),
+            audit_retention_days: audit::DEFAULT_AUDIT_RETENTION_DAYS,
+            feature_flags: flags::FlagConfig::default(),
+            admission: admission::AdmissionConfig::default(),
+            service_init_timeout_ms: services::DEFAULT_INIT_TIMEOUT_MS,
+            retention: retention::RetentionPolicy::default(),
+            cleanup_interval_days: retention::DEFAULT_CLEANUP_INTERVAL_DAYS,
+        }
+    }
+}
+
+impl CoreConfig {
+    pub fn health_config(&self) -> health::HealthConfig {
+        health::HealthConfig {
+            probe_timeout: std::time::Duration::from_millis(self.health_probe_timeout_ms),
+            critical: self.health_critical_components.clone(),
         }
     }
+}
+
+// Probes for what the core owns. Job execution and the schedulers are
+// background loops run by the server, which reports their heartbeats.
+fn register_core_probes(
+    registry: &health::HealthRegistry,
+    storage: &Arc<dyn storage::Storage + Send + Sync>,
+    plugin_manager: &Arc<plugin_manager::PluginManager>,
+) {
+    use health::{ComponentId, FnProbe, HealthStatus};
+
+    let storage = storage.clone();
+    registry.register(
+        ComponentId::Storage,
+        &[],
+        FnProbe(move || {
+            let storage = storage.clone();
+            async move {
+                match storage.health_check().await?.status {
+                    storage::StorageStatus::Healthy => Ok(HealthStatus::Healthy),
+                    storage::StorageStatus::Degraded => Ok(HealthStatus::Degraded),
+                    storage::StorageStatus::Unhealthy => {
+                        Err(AssistantError::Database("Storage is not responding".to_string()))
+                    }
+                }
+            }
+        }),
+    );
+
+    // One failing plugin degrades the plugin system rather than failing it
+    let plugin_manager = plugin_manager.clone();
+    registry.register(
+        ComponentId::Plugins,
+        &[],
+        FnProbe(move || {
+            let plugin_manager = plugin_manager.clone();
+            async move {
+                let worst = plugin_manager
+                    .health_check_all()
+                    .await
+                    .into_values()
+                    .map(|h| h.status)
+                    .max()
+                    .unwrap_or(HealthStatus::Healthy);
+                Ok(worst.min(HealthStatus::Degraded))
+            }
+        }),
+    );
+
+    let heartbeat = std::time::Duration::from_secs(5 * 60);
+    registry.register_heartbeat(ComponentId::JobQueue, &[ComponentId::Storage], heartbeat);
+    registry.register_heartbeat(ComponentId::Schedulers, &[ComponentId::Storage], heartbeat);
+}
+
+fn share_link_secret(config: &CoreConfig) -> Result<Vec<u8>> {
+    if let Some(secret) = &config.share_link_secret {
+        return Ok(secret.as_bytes().to_vec());
+    }
+
+    // Links signed with an ephemeral key stop resolving after a restart
+    tracing::warn!("No share link secret configured; existing share links will not survive a restart");
+    let mut secret = vec![0u8; 32];
+    ring::rand::SecureRandom::fill(&ring::rand::SystemRandom::new(), &mut secret)
+        .map_err(|_| AssistantError::Internal("Failed to generate share link secret".to_string()))?;
+    Ok(secret)
 }
\ No newline at end of file
@@ -0,0 +1,729 @@
+use rusty_ai_common::{
+    AssistantError, NotificationCategory, NotificationChannel, NotificationSettings, Result, TaskPriority,
+    UserPreferences,
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use crate::focus::{FocusSession, FocusSessions, FocusStatus};
+
+const ALL_CHANNELS: [NotificationChannel; 4] = [
+    NotificationChannel::Email,
+    NotificationChannel::Sms,
+    NotificationChannel::Push,
+    NotificationChannel::InApp,
+];
+
+// Source of "now", injectable so quiet-hour logic can be tested
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+#[async_trait]
+pub trait NotificationSink: Send + Sync {
+    async fn deliver(&self, channel: &NotificationChannel, notification: &Notification) -> Result<()>;
+}
+
+// Default sink until real channel integrations exist
+pub struct LoggingSink;
+
+#[async_trait]
+impl NotificationSink for LoggingSink {
+    async fn deliver(&self, channel: &NotificationChannel, notification: &Notification) -> Result<()> {
+        info!("Notification via {:?} to {}: {}", channel, notification.user_id, notification.title);
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Notification {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub category: NotificationCategory,
+    pub title: String,
+    pub body: String,
+    pub created_at: DateTime<Utc>,
+    /// Priority of what the notification is about, e.g. the reminded task
+    #[serde(default)]
+    pub priority: Option<TaskPriority>,
+}
+
+impl Notification {
+    pub fn new(user_id: Uuid, category: NotificationCategory, title: impl Into<String>, body: impl Into<String>) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            user_id,
+            category,
+            title: title.into(),
+            body: body.into(),
+            created_at: Utc::now(),
+            priority: None,
+        }
+    }
+
+    pub fn with_priority(mut self, priority: TaskPriority) -> Self {
+        self.priority = Some(priority);
+        self
+    }
+
+    // Security alerts go out immediately, on every channel
+    pub fn is_urgent(&self) -> bool {
+        self.category == NotificationCategory::SecurityAlert
+    }
+
+    // Focus mode holds everything but urgent and critical notifications
+    pub fn holds_for_focus(&self) -> bool {
+        !self.is_urgent() && self.priority != Some(TaskPriority::Critical)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RoutingDecision {
+    Delivered(Vec<NotificationChannel>),
+    Deferred {
+        until: DateTime<Utc>,
+        channels: Vec<NotificationChannel>,
+    },
+    /// Held for the digest sent when the user's focus ends
+    HeldForFocus { until: Option<DateTime<Utc>> },
+    Suppressed(String),
+    /// No channel took the notification
+    Failed(Vec<NotificationChannel>),
+}
+
+impl RoutingDecision {
+    // Delivered on some channel, or held to be delivered later
+    pub fn is_accepted(&self) -> bool {
+        matches!(self, Self::Delivered(_) | Self::Deferred { .. } | Self::HeldForFocus { .. })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct DeferredNotification {
+    notification: Notification,
+    channels: Vec<NotificationChannel>,
+    deliver_at: DateTime<Utc>,
+}
+
+// Decides which channels a notification goes to and holds non-urgent
+// notifications back during the user's quiet hours. Deferred notifications
+// are persisted so a restart does not lose them. Users in focus mode get
+// what was held back as one digest when focus ends.
+pub struct NotificationRouter {
+    clock: Arc<dyn Clock>,
+    sink: Arc<dyn NotificationSink>,
+    deferred: Mutex<Vec<DeferredNotification>>,
+    store_path: Option<PathBuf>,
+    focus: FocusSessions,
+}
+
+impl NotificationRouter {
+    pub fn new(sink: Arc<dyn NotificationSink>, store_path: Option<PathBuf>) -> Self {
+        Self::new_with_clock(Arc::new(SystemClock), sink, store_path)
+    }
+
+    pub fn new_with_clock(clock: Arc<dyn Clock>, sink: Arc<dyn NotificationSink>, store_path: Option<PathBuf>) -> Self {
+        let deferred = store_path.as_deref().map(load_deferred).unwrap_or_default();
+        if !deferred.is_empty() {
+            info!("Loaded {} deferred notifications", deferred.len());
+        }
+
+        Self {
+            clock,
+            sink,
+            deferred: Mutex::new(deferred),
+            store_path,
+            focus: FocusSessions::new(None),
+        }
+    }
+
+    /// Persist focus sessions at `path`, loading the ones stored there
+    pub fn with_focus_store(mut self, path: PathBuf) -> Self {
+        self.focus = FocusSessions::new(Some(path));
+        self
+    }
+
+    // Channels for a category: the user's routing override if any, otherwise
+    // the default, limited to channels the user has enabled
+    pub fn channels_for(settings: &NotificationSettings, category: NotificationCategory) -> Vec<NotificationChannel> {
+        if category == NotificationCategory::SecurityAlert {
+            return ALL_CHANNELS.to_vec();
+        }
+
+        let routed = settings.routing.get(&category).cloned().unwrap_or_else(|| match category {
+            NotificationCategory::Reminder => vec![NotificationChannel::Push],
+            NotificationCategory::Briefing => vec![NotificationChannel::Email],
+            NotificationCategory::KnowledgeDigest => vec![NotificationChannel::Email, NotificationChannel::InApp],
+            NotificationCategory::FocusDigest => vec![NotificationChannel::Push, NotificationChannel::InApp],
+            _ => vec![NotificationChannel::InApp],
+        });
+
+        routed
+            .into_iter()
+            .filter(|channel| settings.channels.contains(channel))
+            .collect()
+    }
+
+    pub async fn route(&self, preferences: &UserPreferences, notification: Notification) -> Result<RoutingDecision> {
+        let settings = &preferences.notification_settings;
+        settings.validate()?;
+
+        if !settings.enabled && !notification.is_urgent() {
+            return Ok(RoutingDecision::Suppressed("notifications disabled".to_string()));
+        }
+
+        let channels = Self::channels_for(settings, notification.category);
+        if channels.is_empty() {
+            return Ok(RoutingDecision::Suppressed(format!(
+                "no enabled channel for {:?}",
+                notification.category
+            )));
+        }
+
+        if notification.holds_for_focus() {
+            if let Some(focus) = self.focus.hold(&notification, preferences, self.clock.now())? {
+                debug!("Holding notification {} until focus ends", notification.id);
+                return Ok(RoutingDecision::HeldForFocus { until: focus.until });
+            }
+        }
+
+        if !notification.is_urgent() {
+            if let Some(until) = self.quiet_hours_end(preferences)? {
+                debug!("Deferring notification {} until {}", notification.id, until);
+                self.defer(DeferredNotification {
+                    notification,
+                    channels: channels.clone(),
+                    deliver_at: until,
+                })?;
+                return Ok(RoutingDecision::Deferred { until, channels });
+            }
+        }
+
+        let failed = self.deliver(&channels, &notification).await;
+        if failed.len() == channels.len() {
+            return Ok(RoutingDecision::Failed(failed));
+        }
+        Ok(RoutingDecision::Delivered(
+            channels.into_iter().filter(|channel| !failed.contains(channel)).collect(),
+        ))
+    }
+
+    // End focus sessions whose time is up, then deliver deferred
+    // notifications whose quiet hours have ended. A notification stays
+    // deferred, with just the channels that failed, until every channel
+    // took it; the next flush retries those
+    pub async fn flush_due(&self) -> Result<usize> {
+        let now = self.clock.now();
+        for (user_id, session) in self.focus.take_over(now)? {
+            info!("Focus of user {} ended", user_id);
+            if let Err(e) = self.send_focus_digest(user_id, session).await {
+                warn!("Failed to send the focus digest of user {}: {}", user_id, e);
+            }
+        }
+
+        let due: Vec<DeferredNotification> = self
+            .deferred
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|d| d.deliver_at <= now)
+            .cloned()
+            .collect();
+
+        if due.is_empty() {
+            return Ok(0);
+        }
+
+        let mut delivered = 0;
+        for entry in due {
+            let failed = self.deliver(&entry.channels, &entry.notification).await;
+            let mut deferred = self.deferred.lock().unwrap();
+            let Some(index) = deferred.iter().position(|d| d.notification.id == entry.notification.id) else {
+                continue;
+            };
+            if failed.is_empty() {
+                deferred.remove(index);
+                delivered += 1;
+            } else {
+                deferred[index].channels = failed;
+            }
+        }
+        self.persist()?;
+
+        info!("Delivered {} deferred notifications", delivered);
+        Ok(delivered)
+    }
+
+    /// Start focus for `duration`, or until `end_focus` when None. Starting
+    /// again while focused moves the end and keeps what was held
+    pub fn start_focus(&self, user_id: Uuid, duration: Option<Duration>) -> Result<FocusStatus> {
+        let now = self.clock.now();
+        let status = self.focus.start(user_id, now, duration.map(|duration| now + duration))?;
+        info!("User {} is focused until {:?}", user_id, status.until);
+        Ok(status)
+    }
+
+    /// End focus now and send what it held as one digest. Returns how many
+    /// notifications were held, None when the user was not focused
+    pub async fn end_focus(&self, user_id: Uuid) -> Result<Option<usize>> {
+        let Some(session) = self.focus.end(user_id)? else {
+            return Ok(None);
+        };
+        let held = session.held.len();
+        self.send_focus_digest(user_id, session).await?;
+        Ok(Some(held))
+    }
+
+    pub fn focus_status(&self, user_id: Uuid) -> FocusStatus {
+        self.focus.status(user_id, self.clock.now())
+    }
+
+    pub fn in_focus(&self, user_id: Uuid) -> bool {
+        self.focus_status(user_id).active
+    }
+
+    /// A note for the model prompt asking for brief answers, while the user
+    /// is focused
+    pub fn focus_prompt_hint(&self, user_id: Uuid) -> Option<String> {
+        let status = self.focus_status(user_id);
+        if !status.active {
+            return None;
+        }
+        let until = match status.until {
+            Some(until) => format!("until {} UTC", until.format("%H:%M")),
+            None => "until they turn it off".to_string(),
+        };
+        Some(format!(
+            "<focus>\nThe user is in focus mode {}. Keep answers short and to the point: no small talk, no \
+             follow-up suggestions, no unrequested detail.\n</focus>",
+            until
+        ))
+    }
+
+    pub fn pending_count(&self) -> usize {
+        self.deferred.lock().unwrap().len()
+    }
+
+    // Notifications held back for one user until their quiet hours end
+    pub fn pending_for(&self, user_id: Uuid) -> usize {
+        self.deferred
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|entry| entry.notification.user_id == user_id)
+            .count()
+    }
+
+    // Route what a focus session held, with the preferences it was held under
+    async fn send_focus_digest(&self, user_id: Uuid, session: FocusSession) -> Result<()> {
+        let (Some(digest), Some(preferences)) = (session.digest(user_id), session.preferences) else {
+            return Ok(());
+        };
+        let decision = self.route(&preferences, digest).await?;
+        debug!("Focus digest of user {} with {} notifications: {:?}", user_id, session.held.len(), decision);
+        Ok(())
+    }
+
+    // Send on every channel, returning the channels that failed
+    async fn deliver(&self, channels: &[NotificationChannel], notification: &Notification) -> Vec<NotificationChannel> {
+        let mut failed = Vec::new();
+        for channel in channels {
+            if let Err(e) = self.sink.deliver(channel, notification).await {
+                warn!("Failed to deliver notification {} via {:?}: {}", notification.id, channel, e);
+                failed.push(channel.clone());
+            }
+        }
+        failed
+    }
+
+    // When the current quiet-hours window ends, or None outside quiet hours
+    fn quiet_hours_end(&self, preferences: &UserPreferences) -> Result<Option<DateTime<Utc>>> {
+        let quiet_hours = match &preferences.notification_settings.quiet_hours {
+            Some(quiet_hours) => quiet_hours,
+            None => return Ok(None),
+        };
+
+        let tz: Tz = preferences.timezone.parse().unwrap_or_else(|_| {
+            warn!("Unknown timezone '{}', using UTC for quiet hours", preferences.timezone);
+            Tz::UTC
+        });
+        let local_now = self.clock.now().with_timezone(&tz);
+
+        if !quiet_hours.contains(local_now.time())? {
+            return Ok(None);
+        }
+
+        let (_, end) = quiet_hours.parse()?;
+        let mut end_local = local_now.date_naive().and_time(end);
+        if end_local <= local_now.naive_local() {
+            end_local += Duration::days(1);
+        }
+
+        Ok(Some(resolve_local(&tz, end_local)))
+    }
+
+    fn defer(&self, entry: DeferredNotification) -> Result<()> {
+        self.deferred.lock().unwrap().push(entry);
+        self.persist()
+    }
+
+    fn persist(&self) -> Result<()> {
+        let path = match &self.store_path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+
+        let deferred = self.deferred.lock().unwrap().clone();
+        let json = serde_json::to_vec_pretty(&deferred)
+            .map_err(|e| AssistantError::Internal(format!("Failed to serialize deferred notifications: {}", e)))?;
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| AssistantError::Internal(format!("Failed to create notification store: {}", e)))?;
+        }
+        std::fs::write(path, json)
+            .map_err(|e| AssistantError::Internal(format!("Failed to persist deferred notifications: {}", e)))
+    }
+}
+
+// Validate the notification-related parts of user preferences
+pub fn validate_preferences(preferences: &UserPreferences) -> Result<()> {
+    preferences.notification_settings.validate()?;
+    preferences
+        .timezone
+        .parse::<Tz>()
+        .map_err(|_| AssistantError::Api(format!("Unknown timezone: '{}'", preferences.timezone)))?;
+    Ok(())
+}
+
+// Map a local wall-clock time to UTC; a time skipped by a DST jump resolves
+// to the first valid instant after it
+pub(crate) fn resolve_local(tz: &Tz, local: NaiveDateTime) -> DateTime<Utc> {
+    let mut candidate = local;
+    for _ in 0..4 {
+        if let Some(resolved) = tz.from_local_datetime(&candidate).earliest() {
+            return resolved.with_timezone(&Utc);
+        }
+        candidate += Duration::minutes(30);
+    }
+    Utc.from_utc_datetime(&local)
+}
+
+fn load_deferred(path: &Path) -> Vec<DeferredNotification> {
+    match std::fs::read(path) {
+        Ok(bytes) => serde_json::from_slice(&bytes).unwrap_or_else(|e| {
+            warn!("Ignoring unreadable deferred notification store {:?}: {}", path, e);
+            Vec::new()
+        }),
+        Err(_) => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{at, RecordingSink, TestClock};
+    use rusty_ai_common::{QuietHours, VoiceSettings};
+
+    // Fails on email until told otherwise
+    #[derive(Default)]
+    struct FlakyEmailSink {
+        email_down: std::sync::atomic::AtomicBool,
+        delivered: Mutex<Vec<(NotificationChannel, String)>>,
+    }
+
+    #[async_trait]
+    impl NotificationSink for FlakyEmailSink {
+        async fn deliver(&self, channel: &NotificationChannel, notification: &Notification) -> Result<()> {
+            if *channel == NotificationChannel::Email && self.email_down.load(std::sync::atomic::Ordering::SeqCst) {
+                return Err(AssistantError::Internal("mail server unreachable".to_string()));
+            }
+            self.delivered.lock().unwrap().push((channel.clone(), notification.title.clone()));
+            Ok(())
+        }
+    }
+
+    fn preferences(timezone: &str) -> UserPreferences {
+        UserPreferences {
+            language: "en".to_string(),
+            timezone: timezone.to_string(),
+            voice_settings: VoiceSettings {
+                enabled: false,
+                voice_id: "default".to_string(),
+                speed: 1.0,
+                pitch: 1.0,
+            },
+            notification_settings: NotificationSettings {
+                enabled: true,
+                channels: vec![NotificationChannel::Push, NotificationChannel::Email],
+                quiet_hours: Some(QuietHours { start: "22:00".to_string(), end: "07:00".to_string() }),
+                routing: Default::default(),
+                knowledge_digest: Default::default(),
+            },
+        }
+    }
+
+    fn temp_store() -> PathBuf {
+        std::env::temp_dir().join(format!("rusty-ai-notifications-{}.json", Uuid::new_v4()))
+    }
+
+    #[tokio::test]
+    async fn test_defers_across_midnight_and_flushes() {
+        // 23:30 in Berlin (UTC+2 in summer)
+        let clock = TestClock::at("2024-06-10T21:30:00Z");
+        let sink = Arc::new(RecordingSink::default());
+        let router = NotificationRouter::new_with_clock(clock.clone(), sink.clone(), Some(temp_store()));
+        let prefs = preferences("Europe/Berlin");
+
+        let reminder = Notification::new(Uuid::new_v4(), NotificationCategory::Reminder, "Water plants", "");
+        let decision = router.route(&prefs, reminder).await.unwrap();
+
+        // Held until 07:00 Berlin the next morning
+        let expected = at("2024-06-11T05:00:00Z");
+        assert_eq!(
+            decision,
+            RoutingDecision::Deferred { until: expected, channels: vec![NotificationChannel::Push] }
+        );
+        assert_eq!(sink.count(), 0);
+
+        // Still quiet at 06:59 local
+        clock.set("2024-06-11T04:59:00Z");
+        assert_eq!(router.flush_due().await.unwrap(), 0);
+
+        clock.set("2024-06-11T05:00:00Z");
+        assert_eq!(router.flush_due().await.unwrap(), 1);
+        assert_eq!(sink.titles(), vec![(NotificationChannel::Push, "Water plants".to_string())]);
+        assert_eq!(router.pending_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_deferred_notifications_survive_restart() {
+        let store = temp_store();
+        let clock = TestClock::at("2024-01-10T23:00:00Z");
+        let prefs = preferences("UTC");
+
+        let router = NotificationRouter::new_with_clock(clock.clone(), Arc::new(RecordingSink::default()), Some(store.clone()));
+        let briefing = Notification::new(Uuid::new_v4(), NotificationCategory::Briefing, "Daily briefing", "");
+        router.route(&prefs, briefing).await.unwrap();
+        drop(router);
+
+        let sink = Arc::new(RecordingSink::default());
+        let router = NotificationRouter::new_with_clock(clock.clone(), sink.clone(), Some(store));
+        assert_eq!(router.pending_count(), 1);
+
+        clock.set("2024-01-11T07:00:00Z");
+        assert_eq!(router.flush_due().await.unwrap(), 1);
+        assert_eq!(sink.titles()[0].0, NotificationChannel::Email);
+    }
+
+    #[tokio::test]
+    async fn test_failed_channels_stay_deferred_until_they_succeed() {
+        let store = temp_store();
+        let clock = TestClock::at("2024-01-10T23:00:00Z");
+        let sink = Arc::new(FlakyEmailSink::default());
+        sink.email_down.store(true, std::sync::atomic::Ordering::SeqCst);
+        let router = NotificationRouter::new_with_clock(clock.clone(), sink.clone(), Some(store.clone()));
+        let mut prefs = preferences("UTC");
+        prefs
+            .notification_settings
+            .routing
+            .insert(NotificationCategory::Reminder, vec![NotificationChannel::Push, NotificationChannel::Email]);
+
+        let reminder = Notification::new(Uuid::new_v4(), NotificationCategory::Reminder, "Water plants", "");
+        router.route(&prefs, reminder).await.unwrap();
+
+        clock.set("2024-01-11T07:00:00Z");
+        assert_eq!(router.flush_due().await.unwrap(), 0);
+        assert_eq!(
+            sink.delivered.lock().unwrap().as_slice(),
+            &[(NotificationChannel::Push, "Water plants".to_string())]
+        );
+        assert_eq!(router.pending_count(), 1);
+
+        // Kept on disk too, with only the channel that failed
+        drop(router);
+        let router = NotificationRouter::new_with_clock(clock.clone(), sink.clone(), Some(store.clone()));
+        assert_eq!(router.pending_count(), 1);
+
+        sink.email_down.store(false, std::sync::atomic::Ordering::SeqCst);
+        assert_eq!(router.flush_due().await.unwrap(), 1);
+        assert_eq!(
+            sink.delivered.lock().unwrap().as_slice(),
+            &[
+                (NotificationChannel::Push, "Water plants".to_string()),
+                (NotificationChannel::Email, "Water plants".to_string()),
+            ]
+        );
+        assert_eq!(router.pending_count(), 0);
+        let _ = std::fs::remove_file(store);
+    }
+
+    #[tokio::test]
+    async fn test_delivers_outside_quiet_hours() {
+        let clock = TestClock::at("2024-01-10T12:00:00Z");
+        let sink = Arc::new(RecordingSink::default());
+        let router = NotificationRouter::new_with_clock(clock, sink.clone(), None);
+
+        let reminder = Notification::new(Uuid::new_v4(), NotificationCategory::Reminder, "Standup", "");
+        let decision = router.route(&preferences("UTC"), reminder).await.unwrap();
+
+        assert_eq!(decision, RoutingDecision::Delivered(vec![NotificationChannel::Push]));
+        assert_eq!(sink.count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_reports_the_channels_that_took_it_and_fails_when_none_did() {
+        let clock = TestClock::at("2024-01-10T12:00:00Z");
+        let sink = Arc::new(FlakyEmailSink::default());
+        sink.email_down.store(true, std::sync::atomic::Ordering::SeqCst);
+        let router = NotificationRouter::new_with_clock(clock, sink.clone(), None);
+        let mut prefs = preferences("UTC");
+        prefs
+            .notification_settings
+            .routing
+            .insert(NotificationCategory::Reminder, vec![NotificationChannel::Push, NotificationChannel::Email]);
+
+        let reminder = Notification::new(Uuid::new_v4(), NotificationCategory::Reminder, "Standup", "");
+        let decision = router.route(&prefs, reminder).await.unwrap();
+        assert_eq!(decision, RoutingDecision::Delivered(vec![NotificationChannel::Push]));
+        assert!(decision.is_accepted());
+
+        let briefing = Notification::new(Uuid::new_v4(), NotificationCategory::Briefing, "Daily briefing", "");
+        let decision = router.route(&prefs, briefing).await.unwrap();
+        assert_eq!(decision, RoutingDecision::Failed(vec![NotificationChannel::Email]));
+        assert!(!decision.is_accepted());
+    }
+
+    #[tokio::test]
+    async fn test_security_alerts_bypass_quiet_hours_and_preferences() {
+        let clock = TestClock::at("2024-01-10T23:00:00Z");
+        let sink = Arc::new(RecordingSink::default());
+        let router = NotificationRouter::new_with_clock(clock, sink.clone(), None);
+        let mut prefs = preferences("UTC");
+        prefs.notification_settings.enabled = false;
+
+        let alert = Notification::new(Uuid::new_v4(), NotificationCategory::SecurityAlert, "New login", "");
+        let decision = router.route(&prefs, alert).await.unwrap();
+
+        assert_eq!(decision, RoutingDecision::Delivered(ALL_CHANNELS.to_vec()));
+        assert_eq!(sink.count(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_focus_holds_all_but_critical_and_sends_a_digest_when_time_is_up() {
+        let clock = TestClock::at("2024-01-10T12:00:00Z");
+        let sink = Arc::new(RecordingSink::default());
+        let router = NotificationRouter::new_with_clock(clock.clone(), sink.clone(), None);
+        let prefs = preferences("UTC");
+        let user_id = Uuid::new_v4();
+
+        let status = router.start_focus(user_id, Some(Duration::hours(2))).unwrap();
+        let until = at("2024-01-10T14:00:00Z");
+        assert_eq!(status.until, Some(until));
+        assert!(router.focus_prompt_hint(user_id).unwrap().contains("until 14:00 UTC"));
+
+        for notification in [
+            Notification::new(user_id, NotificationCategory::Reminder, "Water plants", ""),
+            Notification::new(user_id, NotificationCategory::Briefing, "Daily briefing", ""),
+            Notification::new(user_id, NotificationCategory::KnowledgeDigest, "Weekly digest", ""),
+        ] {
+            let decision = router.route(&prefs, notification).await.unwrap();
+            assert_eq!(decision, RoutingDecision::HeldForFocus { until: Some(until) });
+        }
+        assert_eq!(sink.count(), 0);
+
+        // Critical reminders and security alerts still go out
+        let critical = Notification::new(user_id, NotificationCategory::Reminder, "Pick up the kids", "")
+            .with_priority(TaskPriority::Critical);
+        assert_eq!(router.route(&prefs, critical).await.unwrap(), RoutingDecision::Delivered(vec![NotificationChannel::Push]));
+        let alert = Notification::new(user_id, NotificationCategory::SecurityAlert, "New login", "");
+        assert!(matches!(router.route(&prefs, alert).await.unwrap(), RoutingDecision::Delivered(_)));
+        assert_eq!(sink.count(), 5);
+        assert_eq!(router.focus_status(user_id).held, 3);
+
+        // Another user is not affected
+        let other = Notification::new(Uuid::new_v4(), NotificationCategory::Reminder, "Standup", "");
+        assert!(matches!(router.route(&prefs, other).await.unwrap(), RoutingDecision::Delivered(_)));
+        sink.clear();
+
+        clock.set("2024-01-10T13:59:00Z");
+        router.flush_due().await.unwrap();
+        assert_eq!(sink.count(), 0);
+
+        clock.set("2024-01-10T14:00:00Z");
+        router.flush_due().await.unwrap();
+        assert_eq!(sink.titles(), vec![(NotificationChannel::Push, "While you were focused".to_string())]);
+        let digest = sink.last().unwrap();
+        assert_eq!(digest.category, NotificationCategory::FocusDigest);
+        assert_eq!(
+            digest.body,
+            "3 notification(s) arrived while you were focused:\n\
+             - Reminder: Water plants\n\
+             - Briefing: Daily briefing\n\
+             - Knowledge digest: Weekly digest"
+        );
+        assert!(!router.in_focus(user_id));
+        assert!(router.focus_prompt_hint(user_id).is_none());
+
+        // Focus is over, so the next reminder goes straight out
+        let reminder = Notification::new(user_id, NotificationCategory::Reminder, "Stretch", "");
+        assert_eq!(router.route(&prefs, reminder).await.unwrap(), RoutingDecision::Delivered(vec![NotificationChannel::Push]));
+    }
+
+    #[tokio::test]
+    async fn test_open_ended_focus_survives_restart_and_ends_by_hand() {
+        let store = temp_store().with_extension("focus.json");
+        let clock = TestClock::at("2024-01-10T09:00:00Z");
+        let prefs = preferences("UTC");
+        let user_id = Uuid::new_v4();
+
+        let router = NotificationRouter::new_with_clock(clock.clone(), Arc::new(RecordingSink::default()), None)
+            .with_focus_store(store.clone());
+        assert_eq!(router.start_focus(user_id, None).unwrap().until, None);
+        let reminder = Notification::new(user_id, NotificationCategory::Reminder, "Call the bank", "");
+        router.route(&prefs, reminder).await.unwrap();
+        drop(router);
+
+        let sink = Arc::new(RecordingSink::default());
+        let router = NotificationRouter::new_with_clock(clock.clone(), sink.clone(), None).with_focus_store(store);
+
+        // Without an end time, focus lasts until it is turned off
+        clock.set("2024-01-11T09:00:00Z");
+        router.flush_due().await.unwrap();
+        let status = router.focus_status(user_id);
+        assert!(status.active);
+        assert_eq!(status.held, 1);
+        assert_eq!(sink.count(), 0);
+
+        assert_eq!(router.end_focus(user_id).await.unwrap(), Some(1));
+        let digest = sink.last().unwrap();
+        assert!(digest.body.contains("- Reminder: Call the bank"));
+        assert_eq!(router.end_focus(user_id).await.unwrap(), None);
+    }
+
+    #[test]
+    fn test_routing_override_respects_enabled_channels() {
+        let mut settings = preferences("UTC").notification_settings;
+        settings.routing.insert(
+            NotificationCategory::Reminder,
+            vec![NotificationChannel::Sms, NotificationChannel::Email],
+        );
+
+        let channels = NotificationRouter::channels_for(&settings, NotificationCategory::Reminder);
+        assert_eq!(channels, vec![NotificationChannel::Email]);
+    }
+}
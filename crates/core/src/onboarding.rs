@@ -0,0 +1,425 @@
+// First-run guidance. Each user works through a short list of setup steps;
+// a step completes by itself when the user does what it asks (seen on the
+// event bus) or can be skipped. While steps remain, the model prompt carries
+// a short hint so the assistant can point the user at the next one.
+use rusty_ai_common::{
+    AssistantError, Document, DocumentMetadata, Result, Task, TaskPriority, TaskStatus,
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use crate::events::{AssistantEvent, EventBus, UserAction};
+
+/// Tag carried by the sample documents and tasks, so users can find and
+/// remove them
+pub const SAMPLE_TAG: &str = "sample";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OnboardingStep {
+    SetPreferences,
+    ConnectProvider,
+    UploadDocument,
+    TryVoice,
+    ReviewMemory,
+}
+
+impl OnboardingStep {
+    pub const ALL: [OnboardingStep; 5] = [
+        OnboardingStep::SetPreferences,
+        OnboardingStep::ConnectProvider,
+        OnboardingStep::UploadDocument,
+        OnboardingStep::TryVoice,
+        OnboardingStep::ReviewMemory,
+    ];
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|step| step.as_str() == name)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OnboardingStep::SetPreferences => "set_preferences",
+            OnboardingStep::ConnectProvider => "connect_provider",
+            OnboardingStep::UploadDocument => "upload_document",
+            OnboardingStep::TryVoice => "try_voice",
+            OnboardingStep::ReviewMemory => "review_memory",
+        }
+    }
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            OnboardingStep::SetPreferences => "Set your preferences",
+            OnboardingStep::ConnectProvider => "Connect a provider",
+            OnboardingStep::UploadDocument => "Upload your first document",
+            OnboardingStep::TryVoice => "Try a voice interaction",
+            OnboardingStep::ReviewMemory => "Review memory settings",
+        }
+    }
+
+    /// How the user completes the step, phrased for the model to pass on
+    pub fn hint(&self) -> &'static str {
+        match self {
+            OnboardingStep::SetPreferences => "tell me your language and timezone, or change them in settings",
+            OnboardingStep::ConnectProvider => "enable or configure a plugin such as a calendar or mail provider",
+            OnboardingStep::UploadDocument => "upload a document so I can answer questions about it",
+            OnboardingStep::TryVoice => "send me a voice message",
+            OnboardingStep::ReviewMemory => "look at what I keep about our conversations in the session view",
+        }
+    }
+
+    fn completed_by(action: UserAction) -> Self {
+        match action {
+            UserAction::PreferencesUpdated => OnboardingStep::SetPreferences,
+            UserAction::ProviderConnected => OnboardingStep::ConnectProvider,
+            UserAction::DocumentUploaded => OnboardingStep::UploadDocument,
+            UserAction::VoiceInteraction => OnboardingStep::TryVoice,
+            UserAction::MemoryReviewed => OnboardingStep::ReviewMemory,
+        }
+    }
+}
+
+/// Pending until the user does the step or skips it. Doing a skipped step
+/// still completes it; a completed step stays completed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum StepState {
+    Pending,
+    Completed { at: DateTime<Utc> },
+    Skipped { at: DateTime<Utc> },
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct UserOnboarding {
+    // Steps not listed are pending
+    steps: BTreeMap<OnboardingStep, StepState>,
+    seeded_at: Option<DateTime<Utc>>,
+}
+
+impl UserOnboarding {
+    fn state(&self, step: OnboardingStep) -> StepState {
+        self.steps.get(&step).cloned().unwrap_or(StepState::Pending)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StepStatus {
+    pub step: OnboardingStep,
+    pub title: &'static str,
+    pub hint: &'static str,
+    #[serde(flatten)]
+    pub state: StepState,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OnboardingStatus {
+    pub complete: bool,
+    /// Steps neither completed nor skipped, in order
+    pub remaining: Vec<OnboardingStep>,
+    pub steps: Vec<StepStatus>,
+    pub seeded_at: Option<DateTime<Utc>>,
+}
+
+pub struct OnboardingTracker {
+    users: Mutex<HashMap<Uuid, UserOnboarding>>,
+    store_path: Option<PathBuf>,
+}
+
+impl OnboardingTracker {
+    pub fn new(store_path: Option<PathBuf>) -> Self {
+        let users = store_path.as_deref().map(load_users).unwrap_or_default();
+        Self { users: Mutex::new(users), store_path }
+    }
+
+    pub fn status(&self, user_id: Uuid) -> OnboardingStatus {
+        let users = self.users.lock().unwrap();
+        let onboarding = users.get(&user_id).cloned().unwrap_or_default();
+        let steps: Vec<StepStatus> = OnboardingStep::ALL
+            .into_iter()
+            .map(|step| StepStatus { step, title: step.title(), hint: step.hint(), state: onboarding.state(step) })
+            .collect();
+        let remaining: Vec<OnboardingStep> =
+            steps.iter().filter(|s| s.state == StepState::Pending).map(|s| s.step).collect();
+
+        OnboardingStatus { complete: remaining.is_empty(), remaining, steps, seeded_at: onboarding.seeded_at }
+    }
+
+    /// Mark the step done; false when it already was
+    pub fn complete(&self, user_id: Uuid, step: OnboardingStep) -> Result<bool> {
+        let changed = {
+            let mut users = self.users.lock().unwrap();
+            let onboarding = users.entry(user_id).or_default();
+            if matches!(onboarding.state(step), StepState::Completed { .. }) {
+                false
+            } else {
+                onboarding.steps.insert(step, StepState::Completed { at: Utc::now() });
+                true
+            }
+        };
+        if changed {
+            debug!("User {} completed onboarding step {}", user_id, step.as_str());
+            self.persist()?;
+        }
+        Ok(changed)
+    }
+
+    /// Skip a pending step; completed and already skipped steps stay as they are
+    pub fn skip(&self, user_id: Uuid, step: OnboardingStep) -> Result<OnboardingStatus> {
+        let changed = {
+            let mut users = self.users.lock().unwrap();
+            let onboarding = users.entry(user_id).or_default();
+            if onboarding.state(step) == StepState::Pending {
+                onboarding.steps.insert(step, StepState::Skipped { at: Utc::now() });
+                true
+            } else {
+                false
+            }
+        };
+        if changed {
+            self.persist()?;
+        }
+        Ok(self.status(user_id))
+    }
+
+    /// Record that the user's sample content was created; false when it
+    /// already had been, so it is seeded at most once
+    pub fn mark_seeded(&self, user_id: Uuid) -> Result<bool> {
+        {
+            let mut users = self.users.lock().unwrap();
+            let onboarding = users.entry(user_id).or_default();
+            if onboarding.seeded_at.is_some() {
+                return Ok(false);
+            }
+            onboarding.seeded_at = Some(Utc::now());
+        }
+        self.persist()?;
+        Ok(true)
+    }
+
+    /// A short note for the model prompt, None once nothing remains
+    pub fn prompt_hint(&self, user_id: Uuid) -> Option<String> {
+        let status = self.status(user_id);
+        let next = status.remaining.first()?;
+        Some(format!(
+            "<onboarding>\nThe user is new and has {} setup step(s) left. If it fits the conversation, gently \
+             suggest the next one: {} ({}). Do not insist; they can skip it.\n</onboarding>",
+            status.remaining.len(),
+            next.title(),
+            next.hint()
+        ))
+    }
+
+    /// Complete steps from user actions published on the bus, until the bus
+    /// goes away
+    pub fn listen(self: &Arc<Self>, events: &EventBus) -> JoinHandle<()> {
+        let tracker = self.clone();
+        let mut rx = events.subscribe();
+        tokio::spawn(async move {
+            loop {
+                match rx.recv().await {
+                    Ok(AssistantEvent::UserAction { user_id, action }) => {
+                        if let Err(e) = tracker.complete(user_id, OnboardingStep::completed_by(action)) {
+                            warn!("Failed to record onboarding progress for {}: {}", user_id, e);
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(RecvError::Lagged(missed)) => {
+                        warn!("Onboarding missed {} events; those steps complete the next time", missed);
+                    }
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        })
+    }
+
+    fn persist(&self) -> Result<()> {
+        let path = match &self.store_path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+
+        let json = serde_json::to_vec_pretty(&*self.users.lock().unwrap())
+            .map_err(|e| AssistantError::Internal(format!("Failed to serialize onboarding state: {}", e)))?;
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| AssistantError::Internal(format!("Failed to create onboarding store: {}", e)))?;
+        }
+        std::fs::write(path, json)
+            .map_err(|e| AssistantError::Internal(format!("Failed to persist onboarding state: {}", e)))
+    }
+}
+
+fn load_users(path: &Path) -> HashMap<Uuid, UserOnboarding> {
+    match std::fs::read(path) {
+        Ok(bytes) => {
+            let users: HashMap<Uuid, UserOnboarding> = serde_json::from_slice(&bytes).unwrap_or_else(|e| {
+                warn!("Ignoring unreadable onboarding store {:?}: {}", path, e);
+                HashMap::new()
+            });
+            if !users.is_empty() {
+                info!("Loaded onboarding state of {} users", users.len());
+            }
+            users
+        }
+        Err(_) => HashMap::new(),
+    }
+}
+
+// Sample documents: (title, tags, content). Short enough to read in the
+// response of a search, with facts the user can ask about
+const SAMPLE_DOCUMENTS: &[(&str, &[&str], &str)] = &[
+    (
+        "Welcome to Rusty AI",
+        &["guide"],
+        "Rusty AI is your personal assistant. Ask it questions about your documents, let it keep track of \
+         tasks and reminders, and read the daily briefing each morning for what is due. Everything you \
+         upload stays in your knowledge base and can be deleted at any time.",
+    ),
+    (
+        "Sample: Apartment lease summary",
+        &["home", "finance"],
+        "The lease for the apartment at 12 Linden Street runs until 31 August. Rent is 1,150 EUR, due on \
+         the 1st of each month. Notice must be given three months ahead in writing. The landlord, \
+         Mrs. Berger, can be reached at the property office on weekdays.",
+    ),
+    (
+        "Sample: Team offsite notes",
+        &["work", "meeting"],
+        "The team offsite is planned for the second week of next month in Graz. Agenda: roadmap review on \
+         day one, hiring plan and budget on day two. Travel is booked by each person; the budget is 300 EUR \
+         per night. Open question: who presents the customer feedback summary.",
+    ),
+];
+
+// Example tasks: (name, description, priority, due in days)
+const SAMPLE_TASKS: &[(&str, &str, TaskPriority, i64)] = &[
+    ("Explore your briefing", "Open today's briefing to see tasks that are due", TaskPriority::Medium, 0),
+    ("Ask about the lease", "Try asking: when is the rent due?", TaskPriority::Low, 1),
+    ("Plan the offsite presentation", "Decide who presents the customer feedback", TaskPriority::High, 3),
+];
+
+/// The bundled sample documents, tagged `sample`
+pub fn sample_documents() -> Vec<Document> {
+    let now = Utc::now();
+    SAMPLE_DOCUMENTS
+        .iter()
+        .map(|(title, tags, content)| Document {
+            id: Uuid::new_v4(),
+            title: title.to_string(),
+            content: content.to_string(),
+            metadata: DocumentMetadata {
+                source: "onboarding_sample".to_string(),
+                file_type: "text".to_string(),
+                tags: tags.iter().map(|t| t.to_string()).chain([SAMPLE_TAG.to_string()]).collect(),
+                summary: None,
+                importance_score: 0.5,
+                embeddings: None,
+            },
+            created_at: now,
+            updated_at: now,
+        })
+        .collect()
+}
+
+/// Example tasks due over the next days, so the briefing has something to show
+pub fn sample_tasks() -> Vec<Task> {
+    let now = Utc::now();
+    SAMPLE_TASKS
+        .iter()
+        .map(|(name, description, priority, due_in_days)| Task {
+            id: Uuid::new_v4(),
+            name: name.to_string(),
+            description: description.to_string(),
+            status: TaskStatus::Pending,
+            priority: priority.clone(),
+            due_date: Some(now + Duration::days(*due_in_days)),
+            tags: vec![SAMPLE_TAG.to_string()],
+            created_at: now,
+            updated_at: now,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn wait_for(tracker: &OnboardingTracker, user_id: Uuid, remaining: usize) {
+        for _ in 0..100 {
+            if tracker.status(user_id).remaining.len() == remaining {
+                return;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        panic!("onboarding did not reach {} remaining steps: {:?}", remaining, tracker.status(user_id));
+    }
+
+    #[tokio::test]
+    async fn test_steps_complete_from_user_actions() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("onboarding.json");
+        let events = EventBus::default();
+        let tracker = Arc::new(OnboardingTracker::new(Some(path.clone())));
+        let listener = tracker.listen(&events);
+        let (user_id, other) = (Uuid::new_v4(), Uuid::new_v4());
+
+        let status = tracker.status(user_id);
+        assert!(!status.complete);
+        assert_eq!(status.remaining, OnboardingStep::ALL.to_vec());
+        assert!(tracker.prompt_hint(user_id).unwrap().contains("Set your preferences"));
+
+        events.publish(AssistantEvent::UserAction { user_id, action: UserAction::PreferencesUpdated });
+        events.publish(AssistantEvent::UserAction { user_id, action: UserAction::DocumentUploaded });
+        wait_for(&tracker, user_id, 3).await;
+        assert_eq!(tracker.status(other).remaining.len(), 5);
+        assert!(tracker.prompt_hint(user_id).unwrap().contains("Connect a provider"));
+
+        // Skipped steps no longer count as remaining but can still be done
+        tracker.skip(user_id, OnboardingStep::ConnectProvider).unwrap();
+        let status = tracker.skip(user_id, OnboardingStep::TryVoice).unwrap();
+        assert_eq!(status.remaining, vec![OnboardingStep::ReviewMemory]);
+        events.publish(AssistantEvent::UserAction { user_id, action: UserAction::VoiceInteraction });
+        events.publish(AssistantEvent::UserAction { user_id, action: UserAction::MemoryReviewed });
+        wait_for(&tracker, user_id, 0).await;
+
+        let status = tracker.status(user_id);
+        assert!(status.complete);
+        let state = |step| status.steps.iter().find(|s| s.step == step).unwrap().state.clone();
+        assert!(matches!(state(OnboardingStep::TryVoice), StepState::Completed { .. }));
+        assert!(matches!(state(OnboardingStep::ConnectProvider), StepState::Skipped { .. }));
+        assert!(tracker.prompt_hint(user_id).is_none());
+        // Skipping a completed step changes nothing
+        tracker.skip(user_id, OnboardingStep::SetPreferences).unwrap();
+        assert!(tracker.status(user_id).complete);
+
+        // Progress survives a restart
+        listener.abort();
+        let restarted = OnboardingTracker::new(Some(path));
+        assert!(restarted.status(user_id).complete);
+        assert_eq!(restarted.status(other).remaining.len(), 5);
+    }
+
+    #[test]
+    fn test_sample_content_is_seeded_once() {
+        let tracker = OnboardingTracker::new(None);
+        let user_id = Uuid::new_v4();
+        assert!(tracker.mark_seeded(user_id).unwrap());
+        assert!(!tracker.mark_seeded(user_id).unwrap());
+        assert!(tracker.status(user_id).seeded_at.is_some());
+
+        let documents = sample_documents();
+        assert!(documents.iter().all(|d| d.metadata.tags.contains(&SAMPLE_TAG.to_string())));
+        let tasks = sample_tasks();
+        assert!(tasks.iter().all(|t| t.due_date.is_some() && t.status == TaskStatus::Pending));
+        assert_eq!(OnboardingStep::parse("try_voice"), Some(OnboardingStep::TryVoice));
+        assert_eq!(OnboardingStep::parse("nope"), None);
+    }
+}
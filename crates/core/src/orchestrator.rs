@@ -1,15 +1,26 @@
 use rusty_ai_common::{Result, Intent, Task, TaskStatus, UserContext};
+use rusty_ai_common::scratchpad::Scratchpads;
+use std::collections::VecDeque;
 use std::sync::Arc;
 use tokio::sync::{RwLock, mpsc};
-use uuid::Uuid;
 use tracing::{info, error, debug};
 use super::{plugin_manager::PluginManager, context_manager::ContextManager, storage::Storage};
+use super::admission::{AdmissionController, RequestClass};
+use super::events::{AssistantEvent, EventBus};
+use super::flags::FeatureFlags;
+use super::notifications::NotificationRouter;
+use super::onboarding::OnboardingTracker;
+use super::intent::ClassificationResult;
+use super::intent_handlers::{register_builtin_handlers, HandlerOutcome, IntentHandlerRegistry, IntentRequest};
 
 pub struct Orchestrator {
     plugin_manager: Arc<PluginManager>,
     context_manager: Arc<RwLock<ContextManager>>,
     storage: Arc<dyn Storage + Send + Sync>,
     task_queue: Arc<RwLock<Vec<Task>>>,
+    handlers: Arc<IntentHandlerRegistry>,
+    admission: Arc<AdmissionController>,
+    events: Arc<EventBus>,
     shutdown_tx: Option<mpsc::Sender<()>>,
 }
 
@@ -18,12 +29,36 @@ impl Orchestrator {
         plugin_manager: Arc<PluginManager>,
         context_manager: Arc<RwLock<ContextManager>>,
         storage: Arc<dyn Storage + Send + Sync>,
+        flags: Arc<FeatureFlags>,
+        admission: Arc<AdmissionController>,
+        events: Arc<EventBus>,
+        scratchpads: Arc<Scratchpads>,
+        onboarding: Arc<OnboardingTracker>,
+        notification_router: Arc<NotificationRouter>,
     ) -> Self {
+        let handlers = Arc::new(
+            IntentHandlerRegistry::with_scratchpads(scratchpads)
+                .with_onboarding(onboarding)
+                .with_focus(notification_router.clone()),
+        );
+        register_builtin_handlers(
+            &handlers,
+            storage.clone(),
+            context_manager.clone(),
+            plugin_manager.clone(),
+            flags,
+            events.clone(),
+            notification_router,
+        );
+        
         Self {
             plugin_manager,
             context_manager,
             storage,
             task_queue: Arc::new(RwLock::new(Vec::new())),
+            handlers,
+            admission,
+            events,
             shutdown_tx: None,
         }
     }
@@ -40,98 +75,54 @@ impl Orchestrator {
         Ok(())
     }
     
-    pub async fn process_intent(&self, intent: Intent, context: &UserContext) -> Result<String> {
-        debug!("Processing intent: {:?}", intent);
-        
-        match intent {
-            Intent::Query { query } => {
-                self.handle_query(query, context).await
-            },
-            Intent::Command { action, parameters } => {
-                self.handle_command(action, parameters, context).await
-            },
-            Intent::Information { topic } => {
-                self.handle_information_request(topic, context).await
-            },
-            Intent::Unknown => {
-                Ok("I'm not sure I understand. Could you please rephrase?".to_string())
-            }
-        }
+    /// Handlers tried for each message; plugins and a completion provider
+    /// can be added after construction
+    pub fn handlers(&self) -> &Arc<IntentHandlerRegistry> {
+        &self.handlers
     }
     
-    async fn handle_query(&self, query: String, context: &UserContext) -> Result<String> {
-        // Route to appropriate plugin based on query content
-        let plugins = self.plugin_manager.get_active_plugins().await;
-        
-        for plugin in plugins {
-            if plugin.can_handle_query(&query) {
-                return plugin.process_query(query, context).await;
-            }
-        }
-        
-        // Fallback to knowledge base search
-        Ok(format!("Searching for information about: {}", query))
+    pub async fn handle(&self, request: &IntentRequest, context: &UserContext) -> Result<HandlerOutcome> {
+        debug!("Processing intent: {:?}", request.intent);
+        self.handlers.dispatch(request, context).await
     }
     
-    async fn handle_command(&self, action: String, parameters: Vec<String>, context: &UserContext) -> Result<String> {
-        // Create a task for the command
-        let task = Task {
-            id: Uuid::new_v4(),
-            name: action.clone(),
-            description: format!("Execute {} with params: {:?}", action, parameters),
-            status: TaskStatus::Pending,
-            priority: rusty_ai_common::TaskPriority::Medium,
-            due_date: None,
-            tags: vec![action.clone()],
-            created_at: chrono::Utc::now(),
-            updated_at: chrono::Utc::now(),
-        };
-        
-        // Add to queue
-        let mut queue = self.task_queue.write().await;
-        queue.push(task.clone());
-        
-        // Persist task
-        self.storage.store_task(&task).await?;
-        
-        Ok(format!("Command '{}' has been queued for execution", action))
+    pub async fn process_intent(&self, intent: Intent, context: &UserContext) -> Result<String> {
+        let outcome = self.handle(&IntentRequest::from_intent(intent), context).await?;
+        Ok(outcome.response_text)
     }
     
-    async fn handle_information_request(&self, topic: String, context: &UserContext) -> Result<String> {
-        // Search knowledge base for topic
-        let documents = self.storage.search_documents(&topic, 5).await?;
-        
-        if documents.is_empty() {
-            Ok(format!("I don't have information about {} yet. Would you like me to research it?", topic))
-        } else {
-            let summary = documents.iter()
-                .map(|d| format!("- {}: {}", d.title, d.metadata.summary.as_ref().unwrap_or(&d.content[..100.min(d.content.len())].to_string())))
-                .collect::<Vec<_>>()
-                .join("\n");
-            
-            Ok(format!("Here's what I know about {}:\n{}", topic, summary))
-        }
+    /// Process a classified message; handlers see the normalized entities
+    /// as well as the intent
+    pub async fn process_classification(&self, classification: &ClassificationResult, context: &UserContext) -> Result<String> {
+        let outcome = self.handle(&IntentRequest::from_classification(classification), context).await?;
+        Ok(outcome.response_text)
     }
     
+    /// Runs queued tasks as background work. When the server is saturated
+    /// the remaining tasks go back on the queue for the next run
     pub async fn execute_pending_tasks(&self) -> Result<()> {
         let mut queue = self.task_queue.write().await;
-        let pending_tasks: Vec<Task> = queue.drain(..).filter(|t| t.status == TaskStatus::Pending).collect();
+        let mut pending_tasks: VecDeque<Task> = queue.drain(..).filter(|t| t.status == TaskStatus::Pending).collect();
         
-        for mut task in pending_tasks {
+        while let Some(mut task) = pending_tasks.pop_front() {
+            let Ok(_permit) = self.admission.try_admit(RequestClass::Background) else {
+                queue.push(task);
+                queue.extend(pending_tasks);
+                self.admission.record_requeued(RequestClass::Background, queue.len() as u64);
+                info!("Server is saturated; requeued {} tasks", queue.len());
+                break;
+            };
             info!("Executing task: {}", task.name);
-            task.status = TaskStatus::InProgress;
-            self.storage.update_task_status(task.id, TaskStatus::InProgress).await?;
+            self.set_status(&mut task, TaskStatus::InProgress).await?;
             
             // Execute task through appropriate plugin
             match self.execute_task(&task).await {
                 Ok(_) => {
-                    task.status = TaskStatus::Completed;
-                    self.storage.update_task_status(task.id, TaskStatus::Completed).await?;
+                    self.set_status(&mut task, TaskStatus::Completed).await?;
                     info!("Task {} completed successfully", task.name);
                 },
                 Err(e) => {
-                    task.status = TaskStatus::Failed;
-                    self.storage.update_task_status(task.id, TaskStatus::Failed).await?;
+                    self.set_status(&mut task, TaskStatus::Failed).await?;
                     error!("Task {} failed: {}", task.name, e);
                 }
             }
@@ -140,6 +131,15 @@ impl Orchestrator {
         Ok(())
     }
     
+    // Persists the new status and tells live clients about it
+    async fn set_status(&self, task: &mut Task, status: TaskStatus) -> Result<()> {
+        self.storage.update_task_status(task.id, status.clone()).await?;
+        task.status = status;
+        task.updated_at = chrono::Utc::now();
+        self.events.publish(AssistantEvent::TaskStatusChanged { user_id: None, task: task.clone() });
+        Ok(())
+    }
+    
     async fn execute_task(&self, task: &Task) -> Result<()> {
         // Route task to appropriate plugin
         let plugins = self.plugin_manager.get_active_plugins().await;
@@ -3,7 +3,9 @@ use async_trait::async_trait;
 use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::RwLock;
-use tracing::{info, warn, error};
+use tracing::{info, error};
+
+pub use crate::health::HealthStatus;
 
 #[async_trait]
 pub trait AssistantPlugin: Send + Sync {
@@ -24,15 +26,8 @@ pub struct PluginHealth {
     pub last_check: chrono::DateTime<chrono::Utc>,
 }
 
-#[derive(Debug, Clone, PartialEq)]
-pub enum HealthStatus {
-    Healthy,
-    Degraded,
-    Unhealthy,
-}
-
 pub struct PluginManager {
-    plugins: Arc<RwLock<HashMap<String, Box<dyn AssistantPlugin>>>>,
+    plugins: Arc<RwLock<HashMap<String, Arc<dyn AssistantPlugin>>>>,
     configs: Arc<RwLock<HashMap<String, PluginConfig>>>,
 }
 
@@ -58,6 +53,7 @@ impl PluginManager {
                 enabled: true,
                 priority: 0,
                 settings: HashMap::new(),
+                quota: Default::default(),
             });
         
         // Initialize plugin
@@ -65,7 +61,7 @@ impl PluginManager {
         
         // Store plugin
         let mut plugins = self.plugins.write().await;
-        plugins.insert(plugin_id.clone(), plugin);
+        plugins.insert(plugin_id.clone(), Arc::from(plugin));
         
         // Store config
         let mut configs = self.configs.write().await;
@@ -90,32 +86,26 @@ impl PluginManager {
         Ok(())
     }
     
-    pub async fn get_plugin(&self, plugin_id: &str) -> Option<Box<dyn AssistantPlugin>> {
+    pub async fn get_plugin(&self, plugin_id: &str) -> Option<Arc<dyn AssistantPlugin>> {
         let plugins = self.plugins.read().await;
-        plugins.get(plugin_id).map(|p| {
-            // This is a simplified approach - in production, you'd need proper cloning
-            // or Arc-based sharing of plugins
-            unimplemented!("Plugin cloning not implemented")
-        })
+        plugins.get(plugin_id).cloned()
     }
     
+    /// Enabled plugins, highest configured priority first
     pub async fn get_active_plugins(&self) -> Vec<Arc<dyn AssistantPlugin>> {
         let plugins = self.plugins.read().await;
         let configs = self.configs.read().await;
         
-        let mut active_plugins = Vec::new();
-        
-        for (id, _plugin) in plugins.iter() {
-            if let Some(config) = configs.get(id) {
-                if config.enabled {
-                    // In production, you'd return Arc references to the plugins
-                    // For now, we'll skip this
-                    warn!("Plugin sharing not fully implemented");
-                }
-            }
-        }
+        let mut active_plugins: Vec<(i32, Arc<dyn AssistantPlugin>)> = plugins
+            .iter()
+            .filter_map(|(id, plugin)| {
+                let config = configs.get(id)?;
+                config.enabled.then(|| (config.priority, plugin.clone()))
+            })
+            .collect();
+        active_plugins.sort_by(|a, b| b.0.cmp(&a.0));
         
-        active_plugins
+        active_plugins.into_iter().map(|(_, plugin)| plugin).collect()
     }
     
     pub async fn load_plugins(&self) -> Result<()> {
@@ -0,0 +1,301 @@
+use rusty_ai_common::{AssistantError, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::{Arc, Mutex, RwLock};
+use tracing::info;
+
+/// Snapshots kept for the gauges; one a minute covers the last hour
+pub const DEFAULT_HISTORY_LEN: usize = 60;
+
+/// What a component is holding in memory
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResourceUsage {
+    pub entries: usize,
+    /// Estimate of heap held by the entries, not an allocator measurement
+    pub approx_bytes: u64,
+}
+
+#[async_trait]
+pub trait ResourceReporter: Send + Sync {
+    async fn usage(&self) -> ResourceUsage;
+
+    fn trimmable(&self) -> bool {
+        false
+    }
+
+    /// Run the component's eviction routine; returns the entries removed
+    async fn trim(&self) -> Result<usize> {
+        Err(AssistantError::Api("This component cannot be trimmed".to_string()))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ComponentResources {
+    #[serde(flatten)]
+    pub usage: ResourceUsage,
+    pub trimmable: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AllocatorStats {
+    pub allocator: String,
+    pub allocated_bytes: u64,
+    pub resident_bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResourceSnapshot {
+    pub taken_at: DateTime<Utc>,
+    /// Resident set size of the whole process, where the OS exposes it
+    pub rss_bytes: Option<u64>,
+    /// Only with the `jemalloc` feature and jemalloc as global allocator
+    pub allocator: Option<AllocatorStats>,
+    pub components: BTreeMap<String, ComponentResources>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrimResult {
+    pub target: String,
+    pub entries_removed: usize,
+    pub before: ResourceUsage,
+    pub after: ResourceUsage,
+}
+
+// Components with caches register a reporter at startup; the admin API reads
+// the breakdown and a background task samples it into a bounded history
+pub struct ResourceRegistry {
+    reporters: RwLock<BTreeMap<String, Arc<dyn ResourceReporter>>>,
+    history: Mutex<VecDeque<ResourceSnapshot>>,
+    history_len: usize,
+}
+
+impl ResourceRegistry {
+    pub fn new(history_len: usize) -> Self {
+        Self {
+            reporters: RwLock::new(BTreeMap::new()),
+            history: Mutex::new(VecDeque::with_capacity(history_len)),
+            history_len,
+        }
+    }
+
+    pub fn register(&self, name: impl Into<String>, reporter: Arc<dyn ResourceReporter>) {
+        self.reporters.write().unwrap().insert(name.into(), reporter);
+    }
+
+    fn reporter(&self, name: &str) -> Option<Arc<dyn ResourceReporter>> {
+        self.reporters.read().unwrap().get(name).cloned()
+    }
+
+    pub async fn snapshot(&self) -> ResourceSnapshot {
+        let reporters: Vec<(String, Arc<dyn ResourceReporter>)> = self
+            .reporters
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(name, reporter)| (name.clone(), reporter.clone()))
+            .collect();
+
+        let mut components = BTreeMap::new();
+        for (name, reporter) in reporters {
+            let usage = reporter.usage().await;
+            components.insert(
+                name,
+                ComponentResources {
+                    usage,
+                    trimmable: reporter.trimmable(),
+                },
+            );
+        }
+
+        ResourceSnapshot {
+            taken_at: Utc::now(),
+            rss_bytes: process_rss_bytes(),
+            allocator: allocator_stats(),
+            components,
+        }
+    }
+
+    /// Take a snapshot and append it to the history
+    pub async fn record(&self) -> ResourceSnapshot {
+        let snapshot = self.snapshot().await;
+        let mut history = self.history.lock().unwrap();
+        if history.len() == self.history_len {
+            history.pop_front();
+        }
+        history.push_back(snapshot.clone());
+        snapshot
+    }
+
+    /// Recorded snapshots, oldest first
+    pub fn history(&self) -> Vec<ResourceSnapshot> {
+        self.history.lock().unwrap().iter().cloned().collect()
+    }
+
+    pub async fn trim(&self, target: &str) -> Result<TrimResult> {
+        let reporter = self
+            .reporter(target)
+            .ok_or_else(|| AssistantError::NotFound(format!("Unknown resource target: {}", target)))?;
+        if !reporter.trimmable() {
+            return Err(AssistantError::Api(format!("{} cannot be trimmed", target)));
+        }
+
+        let before = reporter.usage().await;
+        let entries_removed = reporter.trim().await?;
+        let after = reporter.usage().await;
+        info!(
+            "Trimmed {}: {} entries removed, ~{} bytes freed",
+            target,
+            entries_removed,
+            before.approx_bytes.saturating_sub(after.approx_bytes)
+        );
+
+        Ok(TrimResult {
+            target: target.to_string(),
+            entries_removed,
+            before,
+            after,
+        })
+    }
+}
+
+impl Default for ResourceRegistry {
+    fn default() -> Self {
+        Self::new(DEFAULT_HISTORY_LEN)
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub fn process_rss_bytes() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kb: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kb * 1024)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn process_rss_bytes() -> Option<u64> {
+    None
+}
+
+// The stats are only meaningful when the binary installs jemalloc as its
+// global allocator
+#[cfg(feature = "jemalloc")]
+fn allocator_stats() -> Option<AllocatorStats> {
+    use tikv_jemalloc_ctl::{epoch, stats};
+
+    // jemalloc caches its statistics until the epoch is advanced
+    epoch::advance().ok()?;
+    Some(AllocatorStats {
+        allocator: "jemalloc".to_string(),
+        allocated_bytes: stats::allocated::read().ok()? as u64,
+        resident_bytes: stats::resident::read().ok()? as u64,
+    })
+}
+
+#[cfg(not(feature = "jemalloc"))]
+fn allocator_stats() -> Option<AllocatorStats> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A cache holding fixed-size entries
+    struct FakeCache {
+        entries: Mutex<Vec<Vec<u8>>>,
+    }
+
+    impl FakeCache {
+        fn with_entries(count: usize) -> Self {
+            Self {
+                entries: Mutex::new((0..count).map(|_| vec![0u8; 1024]).collect()),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl ResourceReporter for FakeCache {
+        async fn usage(&self) -> ResourceUsage {
+            let entries = self.entries.lock().unwrap();
+            ResourceUsage {
+                entries: entries.len(),
+                approx_bytes: entries.iter().map(|e| e.len() as u64).sum(),
+            }
+        }
+
+        fn trimmable(&self) -> bool {
+            true
+        }
+
+        async fn trim(&self) -> Result<usize> {
+            let mut entries = self.entries.lock().unwrap();
+            let removed = entries.len() / 2;
+            entries.truncate(entries.len() - removed);
+            Ok(removed)
+        }
+    }
+
+    struct FixedBuffers;
+
+    #[async_trait]
+    impl ResourceReporter for FixedBuffers {
+        async fn usage(&self) -> ResourceUsage {
+            ResourceUsage { entries: 2, approx_bytes: 512 }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_report_lists_registered_components() {
+        let registry = ResourceRegistry::default();
+        registry.register("embedding_cache", Arc::new(FakeCache::with_entries(4)));
+        registry.register("websocket_buffers", Arc::new(FixedBuffers));
+
+        let snapshot = registry.snapshot().await;
+        let cache = &snapshot.components["embedding_cache"];
+        assert_eq!(cache.usage, ResourceUsage { entries: 4, approx_bytes: 4096 });
+        assert!(cache.trimmable);
+        assert!(!snapshot.components["websocket_buffers"].trimmable);
+        #[cfg(target_os = "linux")]
+        assert!(snapshot.rss_bytes.unwrap() > 0);
+    }
+
+    #[tokio::test]
+    async fn test_trim_calls_eviction_routine() {
+        let registry = ResourceRegistry::default();
+        registry.register("embedding_cache", Arc::new(FakeCache::with_entries(4)));
+
+        let result = registry.trim("embedding_cache").await.unwrap();
+        assert_eq!(result.entries_removed, 2);
+        assert_eq!(result.before.approx_bytes, 4096);
+        assert_eq!(result.after, ResourceUsage { entries: 2, approx_bytes: 2048 });
+    }
+
+    #[tokio::test]
+    async fn test_trim_rejects_unknown_and_untrimmable_targets() {
+        let registry = ResourceRegistry::default();
+        registry.register("websocket_buffers", Arc::new(FixedBuffers));
+
+        assert!(matches!(registry.trim("module_cache").await, Err(AssistantError::NotFound(_))));
+        assert!(matches!(registry.trim("websocket_buffers").await, Err(AssistantError::Api(_))));
+    }
+
+    #[tokio::test]
+    async fn test_history_is_bounded() {
+        let registry = ResourceRegistry::new(3);
+        let cache = Arc::new(FakeCache::with_entries(8));
+        registry.register("embedding_cache", cache.clone());
+
+        for _ in 0..5 {
+            registry.record().await;
+            cache.trim().await.unwrap();
+        }
+
+        let history = registry.history();
+        assert_eq!(history.len(), 3);
+        let entries: Vec<usize> = history.iter().map(|s| s.components["embedding_cache"].usage.entries).collect();
+        assert_eq!(entries, vec![2, 1, 1]);
+    }
+}
@@ -0,0 +1,539 @@
+use regex::Regex;
+use std::sync::OnceLock;
+use rusty_ai_common::api::{ProcessingStage, StageReport, StageStatus};
+use rusty_ai_common::{AssistantError, Result, UserPreferences};
+
+/// What replaces scrubbed text
+pub const PII_MASK: &str = "[redacted]";
+
+// E-mail addresses, payment card numbers, IBANs and international phone numbers
+const DEFAULT_PII_PATTERNS: [&str; 4] = [
+    r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}",
+    r"\b(?:\d[ -]?){12,18}\d\b",
+    r"\b[A-Z]{2}\d{2}(?: ?[A-Z0-9]{4}){2,7}(?: ?[A-Z0-9]{1,3})?\b",
+    r"\+\d{1,3}[ (]?\d[\d ()-]{6,}\d",
+];
+
+/// Which post-processing stages a deployment runs on assistant responses
+#[derive(Debug, Clone)]
+pub struct ResponseProcessingConfig {
+    pub normalize_markdown: bool,
+    /// Rewrites dates and numbers in the user's locale. Off by default since
+    /// it changes the wording the model produced
+    pub locale_formatting: bool,
+    /// Masks `pii_patterns` in copies that leave the user's session
+    pub pii_scrubbing: bool,
+    pub pii_patterns: Vec<String>,
+}
+
+impl Default for ResponseProcessingConfig {
+    fn default() -> Self {
+        Self {
+            normalize_markdown: true,
+            locale_formatting: false,
+            pii_scrubbing: false,
+            pii_patterns: DEFAULT_PII_PATTERNS.iter().map(|p| p.to_string()).collect(),
+        }
+    }
+}
+
+/// Where a processed response is going. Only the user's own session keeps
+/// the unscrubbed text
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResponseDestination {
+    Session,
+    ShareLink,
+    Webhook,
+}
+
+#[derive(Debug, Clone)]
+pub struct ProcessedResponse {
+    pub text: String,
+    pub stages: Vec<StageReport>,
+}
+
+pub struct ResponseProcessor {
+    config: ResponseProcessingConfig,
+    pii_patterns: Vec<Regex>,
+}
+
+impl ResponseProcessor {
+    pub fn new(config: ResponseProcessingConfig) -> Result<Self> {
+        let pii_patterns = config
+            .pii_patterns
+            .iter()
+            .map(|pattern| {
+                Regex::new(pattern).map_err(|e| {
+                    AssistantError::Configuration(format!("Invalid PII pattern '{}': {}", pattern, e))
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self { config, pii_patterns })
+    }
+
+    /// Run every stage on `text`, in order. Locale formatting needs the
+    /// user's preferences and is skipped without them
+    pub fn process(
+        &self,
+        text: &str,
+        preferences: Option<&UserPreferences>,
+        destination: ResponseDestination,
+    ) -> ProcessedResponse {
+        let mut text = text.to_string();
+        let mut stages = Vec::with_capacity(3);
+
+        stages.push(if !self.config.normalize_markdown {
+            report(ProcessingStage::Markdown, StageStatus::Disabled, 0)
+        } else {
+            let (normalized, changes) = normalize_markdown(&text);
+            text = normalized;
+            report(ProcessingStage::Markdown, StageStatus::Applied, changes)
+        });
+
+        let locale = preferences.and_then(|p| Locale::from_language(&p.language));
+        stages.push(match (self.config.locale_formatting, locale) {
+            (false, _) => report(ProcessingStage::LocaleFormatting, StageStatus::Disabled, 0),
+            (true, None) => report(ProcessingStage::LocaleFormatting, StageStatus::Skipped, 0),
+            (true, Some(locale)) => {
+                let (localized, changes) = localize(&text, &locale);
+                text = localized;
+                report(ProcessingStage::LocaleFormatting, StageStatus::Applied, changes)
+            }
+        });
+
+        stages.push(if !self.config.pii_scrubbing {
+            report(ProcessingStage::PiiScrubbing, StageStatus::Disabled, 0)
+        } else if destination == ResponseDestination::Session {
+            report(ProcessingStage::PiiScrubbing, StageStatus::Skipped, 0)
+        } else {
+            let (scrubbed, changes) = self.scrub(&text);
+            text = scrubbed;
+            report(ProcessingStage::PiiScrubbing, StageStatus::Applied, changes)
+        });
+
+        ProcessedResponse { text, stages }
+    }
+
+    fn scrub(&self, text: &str) -> (String, usize) {
+        let mut text = text.to_string();
+        let mut changes = 0;
+        for pattern in &self.pii_patterns {
+            changes += pattern.find_iter(&text).count();
+            text = pattern.replace_all(&text, PII_MASK).into_owned();
+        }
+        (text, changes)
+    }
+}
+
+fn report(stage: ProcessingStage, status: StageStatus, changes: usize) -> StageReport {
+    StageReport { stage, status, changes }
+}
+
+// Markdown normalization: closes an unterminated code fence, uses `-` for
+// bullets and `1.` for numbered items, and repairs tables whose separator
+// row is missing or whose rows have too few cells. Fenced code is left alone.
+fn normalize_markdown(text: &str) -> (String, usize) {
+    static BULLET: OnceLock<Regex> = OnceLock::new();
+    static NUMBERED: OnceLock<Regex> = OnceLock::new();
+    let bullet = BULLET.get_or_init(|| Regex::new(r"^(\s*)[*+]\s+(\S.*)$").unwrap());
+    let numbered = NUMBERED.get_or_init(|| Regex::new(r"^(\s*)(\d+)\)\s+(\S.*)$").unwrap());
+
+    let mut lines: Vec<String> = Vec::new();
+    let mut changes = 0;
+    let mut open_fence: Option<String> = None;
+    let mut table: Vec<String> = Vec::new();
+
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+        if open_fence.is_none() && trimmed.starts_with('|') {
+            table.push(line.to_string());
+            continue;
+        }
+        changes += flush_table(&mut table, &mut lines);
+
+        if let Some(fence) = &open_fence {
+            if trimmed.starts_with(fence.as_str()) {
+                open_fence = None;
+            }
+            lines.push(line.to_string());
+        } else if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            open_fence = Some(trimmed[..3].to_string());
+            lines.push(line.to_string());
+        } else if let Some(caps) = bullet.captures(line) {
+            lines.push(format!("{}- {}", &caps[1], &caps[2]));
+            changes += 1;
+        } else if let Some(caps) = numbered.captures(line) {
+            lines.push(format!("{}{}. {}", &caps[1], &caps[2], &caps[3]));
+            changes += 1;
+        } else {
+            lines.push(line.to_string());
+        }
+    }
+    changes += flush_table(&mut table, &mut lines);
+
+    if let Some(fence) = open_fence {
+        lines.push(fence);
+        changes += 1;
+    }
+
+    let mut normalized = lines.join("\n");
+    if text.ends_with('\n') {
+        normalized.push('\n');
+    }
+    (normalized, changes)
+}
+
+fn table_cells(row: &str) -> Vec<String> {
+    let row = row.trim();
+    let row = row.strip_prefix('|').unwrap_or(row);
+    let row = row.strip_suffix('|').unwrap_or(row);
+    row.split('|').map(|cell| cell.trim().to_string()).collect()
+}
+
+fn table_row(cells: &[String]) -> String {
+    format!("| {} |", cells.join(" | "))
+}
+
+fn is_separator_row(row: &str) -> bool {
+    let cells = table_cells(row);
+    !cells.is_empty()
+        && cells.iter().all(|cell| {
+            let dashes = cell.trim_matches(':');
+            !dashes.is_empty() && dashes.chars().all(|c| c == '-')
+        })
+}
+
+// Writes a buffered table out, repaired; returns the number of fixed rows
+fn flush_table(table: &mut Vec<String>, lines: &mut Vec<String>) -> usize {
+    if table.is_empty() {
+        return 0;
+    }
+    let rows = std::mem::take(table);
+    let columns = rows
+        .iter()
+        .filter(|row| !is_separator_row(row))
+        .map(|row| table_cells(row).len())
+        .max()
+        .unwrap_or(1);
+    let mut changes = 0;
+    for (index, row) in rows.iter().enumerate() {
+        if index == 1 && !is_separator_row(row) {
+            lines.push(table_row(&vec!["---".to_string(); columns]));
+            changes += 1;
+        }
+        if is_separator_row(row) {
+            let mut cells = table_cells(row);
+            if cells.len() < columns {
+                cells.resize(columns, "---".to_string());
+                lines.push(table_row(&cells));
+                changes += 1;
+            } else {
+                lines.push(row.clone());
+            }
+            continue;
+        }
+        let mut cells = table_cells(row);
+        if cells.len() < columns || !row.trim_end().ends_with('|') {
+            cells.resize(columns, String::new());
+            lines.push(table_row(&cells));
+            changes += 1;
+        } else {
+            lines.push(row.clone());
+        }
+    }
+    // A lone header row still needs its separator
+    if rows.len() == 1 {
+        lines.push(table_row(&vec!["---".to_string(); columns]));
+        changes += 1;
+    }
+    changes
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum DateOrder {
+    // Models write US month/day/year; locales that use it are left alone
+    MonthDayYear,
+    DayMonthYear(char),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Locale {
+    dates: DateOrder,
+    decimal: char,
+    group: char,
+}
+
+impl Locale {
+    // From a language tag such as "de", "de-AT" or "en_GB". None for locales
+    // that already read the way models write (en-US)
+    fn from_language(language: &str) -> Option<Self> {
+        let language = language.trim().to_ascii_lowercase().replace('_', "-");
+        let (primary, region) = language.split_once('-').unwrap_or((language.as_str(), ""));
+        let european = |dates: DateOrder, decimal: char, group: char| Some(Self { dates, decimal, group });
+        match (primary, region) {
+            ("en", "" | "us") => None,
+            ("en", _) => european(DateOrder::DayMonthYear('/'), '.', ','),
+            ("de", _) => european(DateOrder::DayMonthYear('.'), ',', '.'),
+            ("fr", _) => european(DateOrder::DayMonthYear('/'), ',', '\u{a0}'),
+            ("nl", _) => european(DateOrder::DayMonthYear('-'), ',', '.'),
+            ("es" | "it" | "pt", _) => european(DateOrder::DayMonthYear('/'), ',', '.'),
+            _ => None,
+        }
+    }
+}
+
+// Locale formatting: rewrites US-style dates (MM/DD/YYYY) and numbers
+// (1,234.5) outside code blocks and inline code
+fn localize(text: &str, locale: &Locale) -> (String, usize) {
+    let mut changes = 0;
+    let localized = map_prose(text, |prose| {
+        let (prose, number_changes) = localize_numbers(prose, locale);
+        let (prose, date_changes) = localize_dates(&prose, locale);
+        changes += number_changes + date_changes;
+        prose
+    });
+    (localized, changes)
+}
+
+// Applies `f` to the parts of `text` that are neither fenced nor inline code
+fn map_prose(text: &str, mut f: impl FnMut(&str) -> String) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut prose = String::new();
+    let mut in_fence = false;
+
+    for line in text.split_inclusive('\n') {
+        let is_fence = line.trim_start().starts_with("```") || line.trim_start().starts_with("~~~");
+        if in_fence || is_fence {
+            output.push_str(&map_inline(&std::mem::take(&mut prose), &mut f));
+            output.push_str(line);
+            if is_fence {
+                in_fence = !in_fence;
+            }
+        } else {
+            prose.push_str(line);
+        }
+    }
+    output.push_str(&map_inline(&prose, &mut f));
+    output
+}
+
+fn map_inline(text: &str, f: &mut impl FnMut(&str) -> String) -> String {
+    text.split('`')
+        .enumerate()
+        .map(|(index, part)| if index % 2 == 0 { f(part) } else { part.to_string() })
+        .collect::<Vec<_>>()
+        .join("`")
+}
+
+fn localize_numbers(text: &str, locale: &Locale) -> (String, usize) {
+    static NUMBER: OnceLock<Regex> = OnceLock::new();
+    let number = NUMBER.get_or_init(|| Regex::new(r"\d{1,3}(?:,\d{3})+(?:\.\d+)?|\d+\.\d+").unwrap());
+    let mut output = String::with_capacity(text.len());
+    let mut last = 0;
+    let mut changes = 0;
+
+    for found in number.find_iter(text) {
+        let before = text[..found.start()].chars().next_back();
+        let mut after = text[found.end()..].chars();
+        let next = after.next();
+        // Versions, IP addresses and identifiers are not numbers to reformat
+        let standalone = !before.is_some_and(|c| c.is_alphanumeric() || c == '.' || c == ',')
+            && !next.is_some_and(|c| c.is_alphanumeric())
+            && !(matches!(next, Some('.' | ',')) && after.next().is_some_and(|c| c.is_ascii_digit()));
+        if !standalone {
+            continue;
+        }
+
+        let value = found.as_str();
+        let (integer, fraction) = value.split_once('.').unwrap_or((value, ""));
+        let mut formatted = if integer.contains(',') {
+            integer.replace(',', &locale.group.to_string())
+        } else {
+            integer.to_string()
+        };
+        if !fraction.is_empty() {
+            formatted.push(locale.decimal);
+            formatted.push_str(fraction);
+        }
+        if formatted != value {
+            output.push_str(&text[last..found.start()]);
+            output.push_str(&formatted);
+            last = found.end();
+            changes += 1;
+        }
+    }
+    output.push_str(&text[last..]);
+    (output, changes)
+}
+
+fn localize_dates(text: &str, locale: &Locale) -> (String, usize) {
+    let DateOrder::DayMonthYear(separator) = locale.dates else {
+        return (text.to_string(), 0);
+    };
+    static DATE: OnceLock<Regex> = OnceLock::new();
+    let date = DATE.get_or_init(|| Regex::new(r"\b(\d{1,2})/(\d{1,2})/(\d{4})\b").unwrap());
+    let mut changes = 0;
+    let localized = date.replace_all(text, |caps: &regex::Captures| {
+        let month: u32 = caps[1].parse().unwrap_or(0);
+        let day: u32 = caps[2].parse().unwrap_or(0);
+        if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+            return caps[0].to_string();
+        }
+        changes += 1;
+        format!("{:02}{sep}{:02}{sep}{}", day, month, &caps[3], sep = separator)
+    });
+    (localized.into_owned(), changes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rusty_ai_common::{NotificationSettings, VoiceSettings};
+
+    fn preferences(language: &str) -> UserPreferences {
+        UserPreferences {
+            language: language.to_string(),
+            timezone: "Europe/Berlin".to_string(),
+            voice_settings: VoiceSettings {
+                enabled: false,
+                voice_id: "default".to_string(),
+                speed: 1.0,
+                pitch: 1.0,
+            },
+            notification_settings: NotificationSettings {
+                enabled: false,
+                channels: vec![],
+                quiet_hours: None,
+                routing: Default::default(),
+                knowledge_digest: Default::default(),
+            },
+        }
+    }
+
+    fn all_stages() -> ResponseProcessor {
+        ResponseProcessor::new(ResponseProcessingConfig {
+            locale_formatting: true,
+            pii_scrubbing: true,
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    fn status(response: &ProcessedResponse, stage: ProcessingStage) -> (StageStatus, usize) {
+        let report = response.stages.iter().find(|r| r.stage == stage).unwrap();
+        (report.status, report.changes)
+    }
+
+    #[test]
+    fn test_markdown_closes_fences_and_normalizes_lists() {
+        let (text, changes) = normalize_markdown("Steps:\n* one\n+ two\n1) three\n```rust\nlet x = 1;\n* not a list\n");
+        assert_eq!(text, "Steps:\n- one\n- two\n1. three\n```rust\nlet x = 1;\n* not a list\n```\n");
+        assert_eq!(changes, 4);
+
+        let (text, changes) = normalize_markdown("**Bold** stays\n- fine");
+        assert_eq!((text.as_str(), changes), ("**Bold** stays\n- fine", 0));
+    }
+
+    #[test]
+    fn test_markdown_repairs_tables() {
+        let (text, changes) = normalize_markdown("| City | Temp | Rain |\n| Berlin | 12 |\n| Paris | 15 | no |");
+        assert_eq!(
+            text,
+            "| City | Temp | Rain |\n| --- | --- | --- |\n| Berlin | 12 |  |\n| Paris | 15 | no |"
+        );
+        assert_eq!(changes, 2);
+    }
+
+    #[test]
+    fn test_locale_rewrites_dates_and_numbers_outside_code() {
+        let german = Locale::from_language("de-DE").unwrap();
+        let (text, changes) = localize("Due 03/14/2024, costs 1,234.50 EUR, v1.2.3 and `1,000.5`", &german);
+        assert_eq!(text, "Due 14.03.2024, costs 1.234,50 EUR, v1.2.3 and `1,000.5`");
+        assert_eq!(changes, 2);
+
+        let british = Locale::from_language("en_GB").unwrap();
+        assert_eq!(localize("On 12/01/2024 at 10.5 km", &british).0, "On 01/12/2024 at 10.5 km");
+        // Not a valid US date, and 192.168.0.1 is not a number
+        assert_eq!(localize("13/40/2024 192.168.0.1", &german).0, "13/40/2024 192.168.0.1");
+        assert!(Locale::from_language("en-US").is_none());
+    }
+
+    #[test]
+    fn test_pii_is_scrubbed_only_outside_the_session() {
+        let processor = all_stages();
+        let reply = "Card 4111 1111 1111 1111 and mail jane.doe@example.com";
+
+        let session = processor.process(reply, None, ResponseDestination::Session);
+        assert_eq!(session.text, reply);
+        assert_eq!(status(&session, ProcessingStage::PiiScrubbing), (StageStatus::Skipped, 0));
+
+        let shared = processor.process(reply, None, ResponseDestination::ShareLink);
+        assert_eq!(shared.text, "Card [redacted] and mail [redacted]");
+        assert_eq!(status(&shared, ProcessingStage::PiiScrubbing), (StageStatus::Applied, 2));
+    }
+
+    #[test]
+    fn test_stages_are_toggled_independently() {
+        let processor = ResponseProcessor::new(ResponseProcessingConfig {
+            normalize_markdown: false,
+            pii_scrubbing: true,
+            pii_patterns: vec![r"ACME-\d+".to_string()],
+            ..Default::default()
+        })
+        .unwrap();
+
+        let response = processor.process("* ticket ACME-42 on 03/14/2024", Some(&preferences("de")), ResponseDestination::Webhook);
+        assert_eq!(response.text, "* ticket [redacted] on 03/14/2024");
+        assert_eq!(status(&response, ProcessingStage::Markdown), (StageStatus::Disabled, 0));
+        assert_eq!(status(&response, ProcessingStage::LocaleFormatting), (StageStatus::Disabled, 0));
+
+        let invalid = ResponseProcessor::new(ResponseProcessingConfig {
+            pii_patterns: vec!["(unclosed".to_string()],
+            ..Default::default()
+        });
+        assert!(matches!(invalid, Err(AssistantError::Configuration(_))));
+    }
+
+    #[test]
+    fn test_messy_response_end_to_end() {
+        let messy = "Here is your summary:\n\
+                     * Meeting on 03/14/2024 with budget 12,500.75\n\
+                     + Call +49 30 1234567 to confirm\n\
+                     \n\
+                     | Item | Cost |\n\
+                     | Hotel | 1,200.00 |\n\
+                     | Train |\n\
+                     \n\
+                     ```bash\n\
+                     curl -d amount=1,000.5 https://api.example.com\n";
+        let processor = all_stages();
+
+        let private = processor.process(messy, Some(&preferences("de")), ResponseDestination::Session);
+        assert_eq!(
+            private.text,
+            "Here is your summary:\n\
+             - Meeting on 14.03.2024 with budget 12.500,75\n\
+             - Call +49 30 1234567 to confirm\n\
+             \n\
+             | Item | Cost |\n\
+             | --- | --- |\n\
+             | Hotel | 1.200,00 |\n\
+             | Train |  |\n\
+             \n\
+             ```bash\n\
+             curl -d amount=1,000.5 https://api.example.com\n\
+             ```\n"
+        );
+        assert_eq!(status(&private, ProcessingStage::Markdown), (StageStatus::Applied, 5));
+        assert_eq!(status(&private, ProcessingStage::LocaleFormatting), (StageStatus::Applied, 3));
+
+        let shared = processor.process(messy, Some(&preferences("de")), ResponseDestination::ShareLink);
+        assert!(shared.text.contains("- Call [redacted] to confirm"));
+        assert!(!shared.text.contains("1234567"));
+        assert_eq!(status(&shared, ProcessingStage::PiiScrubbing), (StageStatus::Applied, 1));
+
+        // Without preferences there is no locale to format for
+        let anonymous = processor.process(messy, None, ResponseDestination::Session);
+        assert_eq!(status(&anonymous, ProcessingStage::LocaleFormatting), (StageStatus::Skipped, 0));
+    }
+}
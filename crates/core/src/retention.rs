@@ -0,0 +1,282 @@
+// Data retention. Each kind of data has its own age limit; documents are
+// kept forever unless deleting them is explicitly turned on. Cleanup is
+// previewed with a dry run a day before it deletes anything, and the preview
+// goes out as a notification so there is time to change the policy.
+use rusty_ai_common::{NotificationCategory, UserPreferences, Result};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::notifications::{Notification, NotificationRouter};
+use crate::storage::Storage;
+
+/// Days between destructive cleanups
+pub const DEFAULT_CLEANUP_INTERVAL_DAYS: i64 = 7;
+
+/// How long a cleanup preview comes before the cleanup itself
+pub const PREVIEW_LEAD_HOURS: i64 = 24;
+
+/// Kinds of data with their own retention
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RetentionEntity {
+    Documents,
+    CompletedTasks,
+    Briefings,
+    VoiceInteractions,
+    Notifications,
+    LlmTraces,
+    PluginAudits,
+}
+
+/// Age limits per kind of data, in days; None keeps it forever
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RetentionPolicy {
+    /// Only applies with `delete_documents` on
+    pub documents_days: Option<i64>,
+    /// Documents are the knowledge base, so deleting them by age is opt-in
+    pub delete_documents: bool,
+    /// Completed, cancelled and failed tasks; open tasks are never deleted
+    pub completed_tasks_days: Option<i64>,
+    pub briefings_days: Option<i64>,
+    pub voice_interactions_days: Option<i64>,
+    pub notifications_days: Option<i64>,
+    pub llm_traces_days: Option<i64>,
+    /// The record of plugin calls
+    pub plugin_audits_days: Option<i64>,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self {
+            documents_days: None,
+            delete_documents: false,
+            completed_tasks_days: Some(90),
+            briefings_days: Some(365),
+            voice_interactions_days: Some(30),
+            notifications_days: Some(14),
+            llm_traces_days: Some(7),
+            plugin_audits_days: Some(90),
+        }
+    }
+}
+
+impl RetentionPolicy {
+    /// Data of this kind older than the cutoff is deleted; None keeps all of it
+    pub fn cutoff(&self, entity: RetentionEntity, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let days = match entity {
+            RetentionEntity::Documents if !self.delete_documents => None,
+            RetentionEntity::Documents => self.documents_days,
+            RetentionEntity::CompletedTasks => self.completed_tasks_days,
+            RetentionEntity::Briefings => self.briefings_days,
+            RetentionEntity::VoiceInteractions => self.voice_interactions_days,
+            RetentionEntity::Notifications => self.notifications_days,
+            RetentionEntity::LlmTraces => self.llm_traces_days,
+            RetentionEntity::PluginAudits => self.plugin_audits_days,
+        };
+        days.map(|days| now - Duration::days(days.max(0)))
+    }
+}
+
+/// Rows a cleanup deleted, or for a dry run would delete, by table
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct CleanupReport {
+    pub dry_run: bool,
+    pub tables: BTreeMap<String, usize>,
+}
+
+impl CleanupReport {
+    pub fn new(dry_run: bool) -> Self {
+        Self { dry_run, tables: BTreeMap::new() }
+    }
+
+    pub fn record(&mut self, table: &str, rows: usize) {
+        *self.tables.entry(table.to_string()).or_default() += rows;
+    }
+
+    pub fn total(&self) -> usize {
+        self.tables.values().sum()
+    }
+
+    /// One line such as "tasks: 12, daily_briefings: 3 (15 rows)"
+    pub fn summary(&self) -> String {
+        let tables: Vec<String> = self
+            .tables
+            .iter()
+            .filter(|(_, rows)| **rows > 0)
+            .map(|(table, rows)| format!("{}: {}", table, rows))
+            .collect();
+        if tables.is_empty() {
+            return "nothing to delete".to_string();
+        }
+        format!("{} ({} rows)", tables.join(", "), self.total())
+    }
+}
+
+/// What the maintenance schedule calls for at a given time
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MaintenanceStep {
+    Preview,
+    Cleanup,
+}
+
+// When the next cleanup runs and when it was previewed. A cleanup never runs
+// less than the preview lead after its preview, even if the preview was late
+#[derive(Debug, Clone)]
+struct Schedule {
+    next_cleanup: Option<DateTime<Utc>>,
+    previewed_at: Option<DateTime<Utc>>,
+    interval: Duration,
+}
+
+impl Schedule {
+    fn due(&mut self, now: DateTime<Utc>) -> Option<MaintenanceStep> {
+        let lead = Duration::hours(PREVIEW_LEAD_HOURS);
+        let next_cleanup = *self.next_cleanup.get_or_insert(now + lead);
+
+        match self.previewed_at {
+            None if now >= next_cleanup - lead => Some(MaintenanceStep::Preview),
+            Some(previewed_at) if now >= next_cleanup.max(previewed_at + lead) => Some(MaintenanceStep::Cleanup),
+            _ => None,
+        }
+    }
+
+    fn complete(&mut self, step: MaintenanceStep, now: DateTime<Utc>) {
+        match step {
+            MaintenanceStep::Preview => self.previewed_at = Some(now),
+            MaintenanceStep::Cleanup => {
+                self.previewed_at = None;
+                self.next_cleanup = Some(now + self.interval);
+            }
+        }
+    }
+}
+
+/// Runs cleanup on a fixed interval, each run preceded by a dry run whose
+/// summary is logged and sent to users as a maintenance notification
+pub struct DataMaintenance {
+    storage: Arc<dyn Storage + Send + Sync>,
+    router: Arc<NotificationRouter>,
+    policy: RetentionPolicy,
+    schedule: Mutex<Schedule>,
+}
+
+impl DataMaintenance {
+    pub fn new(
+        storage: Arc<dyn Storage + Send + Sync>,
+        router: Arc<NotificationRouter>,
+        policy: RetentionPolicy,
+        interval_days: i64,
+    ) -> Self {
+        Self {
+            storage,
+            router,
+            policy,
+            schedule: Mutex::new(Schedule {
+                next_cleanup: None,
+                previewed_at: None,
+                interval: Duration::days(interval_days.max(1)),
+            }),
+        }
+    }
+
+    pub fn policy(&self) -> &RetentionPolicy {
+        &self.policy
+    }
+
+    /// What cleanup would delete right now, without deleting it
+    pub async fn preview(&self) -> Result<CleanupReport> {
+        self.storage.cleanup_old_data(&self.policy, true).await
+    }
+
+    /// Preview or clean up if either is due. The first call only schedules:
+    /// the preview runs then and the cleanup a day later
+    pub async fn run_due(&self, now: DateTime<Utc>, users: &[(Uuid, UserPreferences)]) -> Result<Option<CleanupReport>> {
+        let step = match self.schedule.lock().unwrap().due(now) {
+            Some(step) => step,
+            None => return Ok(None),
+        };
+
+        let report = self.storage.cleanup_old_data(&self.policy, step == MaintenanceStep::Preview).await?;
+        self.schedule.lock().unwrap().complete(step, now);
+
+        match step {
+            MaintenanceStep::Preview => {
+                info!("Data cleanup in {} hours will delete {}", PREVIEW_LEAD_HOURS, report.summary());
+                if report.total() > 0 {
+                    self.notify(users, &report).await;
+                }
+            }
+            MaintenanceStep::Cleanup => info!("Data cleanup deleted {}", report.summary()),
+        }
+        Ok(Some(report))
+    }
+
+    async fn notify(&self, users: &[(Uuid, UserPreferences)], report: &CleanupReport) {
+        for (user_id, preferences) in users {
+            let notification = Notification::new(
+                *user_id,
+                NotificationCategory::Maintenance,
+                "Scheduled data cleanup",
+                format!(
+                    "In {} hours old data will be deleted under the retention policy: {}",
+                    PREVIEW_LEAD_HOURS,
+                    report.summary()
+                ),
+            );
+            if let Err(e) = self.router.route(preferences, notification).await {
+                warn!("Failed to route cleanup preview: {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_documents_need_explicit_opt_in() {
+        let now = Utc::now();
+        let mut policy = RetentionPolicy { documents_days: Some(30), ..Default::default() };
+        assert_eq!(policy.cutoff(RetentionEntity::Documents, now), None);
+
+        policy.delete_documents = true;
+        assert_eq!(policy.cutoff(RetentionEntity::Documents, now), Some(now - Duration::days(30)));
+        assert_eq!(policy.cutoff(RetentionEntity::Notifications, now), Some(now - Duration::days(14)));
+    }
+
+    #[test]
+    fn test_cleanup_runs_a_day_after_its_preview() {
+        let start = Utc::now();
+        let mut schedule = Schedule { next_cleanup: None, previewed_at: None, interval: Duration::days(7) };
+
+        assert_eq!(schedule.due(start), Some(MaintenanceStep::Preview));
+        schedule.complete(MaintenanceStep::Preview, start);
+        assert_eq!(schedule.due(start + Duration::hours(23)), None);
+
+        let cleanup_at = start + Duration::hours(24);
+        assert_eq!(schedule.due(cleanup_at), Some(MaintenanceStep::Cleanup));
+        schedule.complete(MaintenanceStep::Cleanup, cleanup_at);
+
+        // The next preview comes a day before the next cleanup
+        assert_eq!(schedule.due(cleanup_at + Duration::days(5)), None);
+        assert_eq!(schedule.due(cleanup_at + Duration::days(6)), Some(MaintenanceStep::Preview));
+    }
+
+    #[test]
+    fn test_late_preview_still_leads_cleanup_by_a_day() {
+        let start = Utc::now();
+        let mut schedule = Schedule { next_cleanup: Some(start), previewed_at: None, interval: Duration::days(7) };
+
+        // The cleanup is overdue but was never previewed
+        let previewed = start + Duration::hours(30);
+        assert_eq!(schedule.due(previewed), Some(MaintenanceStep::Preview));
+        schedule.complete(MaintenanceStep::Preview, previewed);
+        assert_eq!(schedule.due(previewed + Duration::hours(1)), None);
+        assert_eq!(schedule.due(previewed + Duration::hours(24)), Some(MaintenanceStep::Cleanup));
+    }
+}
@@ -0,0 +1,544 @@
+//! Startup ordering for the core's services.
+//!
+//! Each service declares the services it depends on and an async init
+//! function that receives the ones already started. The registry starts them
+//! in dependency order under a per-service timeout and stops them in reverse.
+//! A required service that fails aborts startup; an optional one is recorded
+//! as failed (or replaced by its fallback) and startup carries on without it.
+
+use futures::future::BoxFuture;
+use rusty_ai_common::{AssistantError, Result};
+use serde::{Deserialize, Serialize};
+use std::any::Any;
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::marker::PhantomData;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+pub const DEFAULT_INIT_TIMEOUT_MS: u64 = 30_000;
+
+type AnyService = Arc<dyn Any + Send + Sync>;
+type InitFn = Box<dyn Fn(Services) -> BoxFuture<'static, Result<AnyService>> + Send + Sync>;
+type FallbackFn = Box<dyn Fn(&Services) -> AnyService + Send + Sync>;
+type ShutdownFn = Box<dyn Fn(AnyService) -> BoxFuture<'static, Result<()>> + Send + Sync>;
+
+/// The services started so far, by name. Values are stored as `Arc<T>`, so
+/// trait objects such as `dyn Storage` are looked up with the same type they
+/// were declared with
+#[derive(Clone, Default)]
+pub struct Services {
+    values: HashMap<&'static str, AnyService>,
+}
+
+impl Services {
+    /// A started service; an error when it is missing or has another type
+    pub fn get<T: ?Sized + Send + Sync + 'static>(&self, name: &str) -> Result<Arc<T>> {
+        let value = self
+            .values
+            .get(name)
+            .ok_or_else(|| AssistantError::Internal(format!("Service `{}` is not available", name)))?;
+        value.downcast_ref::<Arc<T>>().cloned().ok_or_else(|| {
+            AssistantError::Internal(format!(
+                "Service `{}` is not a {}",
+                name,
+                std::any::type_name::<T>()
+            ))
+        })
+    }
+
+    /// A started service, or None when it is missing, failed or was skipped
+    pub fn try_get<T: ?Sized + Send + Sync + 'static>(&self, name: &str) -> Option<Arc<T>> {
+        self.get(name).ok()
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.values.contains_key(name)
+    }
+}
+
+/// Declaration of one service; `T` is the type dependents look it up as
+pub struct ServiceDef<T: ?Sized> {
+    name: &'static str,
+    dependencies: Vec<&'static str>,
+    optional: bool,
+    timeout: Option<Duration>,
+    init: InitFn,
+    fallback: Option<FallbackFn>,
+    on_shutdown: Option<ShutdownFn>,
+    _type: PhantomData<fn() -> Arc<T>>,
+}
+
+impl<T: ?Sized + Send + Sync + 'static> ServiceDef<T> {
+    pub fn new<F, Fut>(name: &'static str, init: F) -> Self
+    where
+        F: Fn(Services) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Arc<T>>> + Send + 'static,
+    {
+        Self {
+            name,
+            dependencies: Vec::new(),
+            optional: false,
+            timeout: None,
+            init: Box::new(move |services| {
+                let init = init(services);
+                Box::pin(async move { init.await.map(|value| Arc::new(value) as AnyService) })
+            }),
+            fallback: None,
+            on_shutdown: None,
+            _type: PhantomData,
+        }
+    }
+
+    pub fn depends_on(mut self, dependencies: &[&'static str]) -> Self {
+        self.dependencies.extend_from_slice(dependencies);
+        self
+    }
+
+    /// Startup continues when this service fails; dependents that are
+    /// themselves optional are skipped, required ones fail startup
+    pub fn optional(mut self) -> Self {
+        self.optional = true;
+        self
+    }
+
+    /// Optional, and when init fails the fallback stands in for it so
+    /// dependents still start. The service reports as degraded
+    pub fn fallback<F>(mut self, fallback: F) -> Self
+    where
+        F: Fn(&Services) -> Arc<T> + Send + Sync + 'static,
+    {
+        self.optional = true;
+        self.fallback = Some(Box::new(move |services| Arc::new(fallback(services)) as AnyService));
+        self
+    }
+
+    /// Overrides the registry's default init timeout
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn on_shutdown<F, Fut>(mut self, on_shutdown: F) -> Self
+    where
+        F: Fn(Arc<T>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        self.on_shutdown = Some(Box::new(move |value| {
+            match value.downcast_ref::<Arc<T>>() {
+                Some(service) => Box::pin(on_shutdown(service.clone())),
+                // Values only ever come from this definition's init or fallback
+                None => Box::pin(async { Ok(()) }),
+            }
+        }));
+        self
+    }
+}
+
+// A definition with its type erased so the registry can hold them together
+struct Entry {
+    name: &'static str,
+    dependencies: Vec<&'static str>,
+    optional: bool,
+    timeout: Option<Duration>,
+    init: InitFn,
+    fallback: Option<FallbackFn>,
+    on_shutdown: Option<ShutdownFn>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum ServiceState {
+    Ready,
+    /// Init failed and the service's fallback is standing in for it
+    Degraded { error: String },
+    Failed { error: String },
+    /// Not started because a dependency is unavailable
+    Skipped { reason: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServiceStatus {
+    pub name: String,
+    #[serde(flatten)]
+    pub state: ServiceState,
+    pub optional: bool,
+    pub dependencies: Vec<String>,
+    pub duration_ms: u64,
+}
+
+impl ServiceStatus {
+    pub fn is_ready(&self) -> bool {
+        self.state == ServiceState::Ready
+    }
+}
+
+pub struct ServiceRegistry {
+    entries: Vec<Entry>,
+    default_timeout: Duration,
+}
+
+impl Default for ServiceRegistry {
+    fn default() -> Self {
+        Self::new(Duration::from_millis(DEFAULT_INIT_TIMEOUT_MS))
+    }
+}
+
+impl ServiceRegistry {
+    pub fn new(default_timeout: Duration) -> Self {
+        Self {
+            entries: Vec::new(),
+            default_timeout,
+        }
+    }
+
+    /// Register (or replace) a service
+    pub fn register<T: ?Sized + Send + Sync + 'static>(&mut self, def: ServiceDef<T>) -> &mut Self {
+        self.entries.retain(|e| e.name != def.name);
+        self.entries.push(Entry {
+            name: def.name,
+            dependencies: def.dependencies,
+            optional: def.optional,
+            timeout: def.timeout,
+            init: def.init,
+            fallback: def.fallback,
+            on_shutdown: def.on_shutdown,
+        });
+        self
+    }
+
+    pub fn with<T: ?Sized + Send + Sync + 'static>(mut self, def: ServiceDef<T>) -> Self {
+        self.register(def);
+        self
+    }
+
+    /// Keep only the named services and what they transitively depend on, so
+    /// tests can start the part of the core they exercise
+    pub fn only(mut self, names: &[&str]) -> Self {
+        let mut keep: HashSet<&'static str> = HashSet::new();
+        let mut pending: Vec<&str> = names.to_vec();
+        while let Some(name) = pending.pop() {
+            if let Some(entry) = self.entries.iter().find(|e| e.name == name) {
+                if keep.insert(entry.name) {
+                    pending.extend(entry.dependencies.iter().copied());
+                }
+            }
+        }
+        self.entries.retain(|e| keep.contains(e.name));
+        self
+    }
+
+    pub fn names(&self) -> Vec<&'static str> {
+        self.entries.iter().map(|e| e.name).collect()
+    }
+
+    /// Dependencies before dependents; otherwise services keep their
+    /// registration order so startup logs read predictably
+    pub fn startup_order(&self) -> Result<Vec<&'static str>> {
+        let known: HashSet<&str> = self.entries.iter().map(|e| e.name).collect();
+        for entry in &self.entries {
+            if let Some(missing) = entry.dependencies.iter().find(|d| !known.contains(*d)) {
+                return Err(AssistantError::Configuration(format!(
+                    "Service `{}` depends on unknown service `{}`",
+                    entry.name, missing
+                )));
+            }
+        }
+
+        let mut order: Vec<&'static str> = Vec::with_capacity(self.entries.len());
+        while order.len() < self.entries.len() {
+            let next = self.entries.iter().find(|e| {
+                !order.contains(&e.name) && e.dependencies.iter().all(|d| order.contains(d))
+            });
+            match next {
+                Some(entry) => order.push(entry.name),
+                None => {
+                    let stuck: Vec<&str> = self
+                        .entries
+                        .iter()
+                        .map(|e| e.name)
+                        .filter(|name| !order.contains(name))
+                        .collect();
+                    return Err(AssistantError::Configuration(format!(
+                        "Service dependency cycle among: {}",
+                        stuck.join(", ")
+                    )));
+                }
+            }
+        }
+        Ok(order)
+    }
+
+    /// Start every service in dependency order. When a required service
+    /// cannot start, the ones already running are shut down before the error
+    /// is returned
+    pub async fn start(self) -> Result<RunningServices> {
+        let order = self.startup_order()?;
+        let mut entries: HashMap<&'static str, Entry> = self.entries.into_iter().map(|e| (e.name, e)).collect();
+
+        let mut services = Services::default();
+        let mut statuses = Vec::with_capacity(order.len());
+        let mut started: Vec<(&'static str, ShutdownFn)> = Vec::new();
+
+        for name in order {
+            let entry = entries.remove(name).expect("ordered services are registered");
+            let timeout = entry.timeout.unwrap_or(self.default_timeout);
+            let started_at = Instant::now();
+
+            let state = match entry.dependencies.iter().find(|d| !services.contains(d)) {
+                Some(dependency) => {
+                    let reason = format!("dependency `{}` is unavailable", dependency);
+                    if !entry.optional {
+                        let error = format!("Service `{}` cannot start: {}", name, reason);
+                        // Best effort; the startup error is what gets reported
+                        let _ = run_shutdown_hooks(&services, started).await;
+                        return Err(AssistantError::Internal(error));
+                    }
+                    warn!("Skipping optional service {}: {}", name, reason);
+                    ServiceState::Skipped { reason }
+                }
+                None => {
+                    let error = match tokio::time::timeout(timeout, (entry.init)(services.clone())).await {
+                        Ok(Ok(value)) => {
+                            services.values.insert(name, value);
+                            None
+                        }
+                        Ok(Err(e)) => Some(e.to_string()),
+                        Err(_) => Some(format!("Init timed out after {}ms", timeout.as_millis())),
+                    };
+                    match (error, &entry.fallback) {
+                        (None, _) => ServiceState::Ready,
+                        (Some(error), Some(fallback)) => {
+                            warn!("Service {} failed to start, using its fallback: {}", name, error);
+                            let value = fallback(&services);
+                            services.values.insert(name, value);
+                            ServiceState::Degraded { error }
+                        }
+                        (Some(error), None) if entry.optional => {
+                            warn!("Optional service {} failed to start: {}", name, error);
+                            ServiceState::Failed { error }
+                        }
+                        (Some(error), None) => {
+                            // Best effort; the startup error is what gets reported
+                        let _ = run_shutdown_hooks(&services, started).await;
+                            return Err(AssistantError::Internal(format!(
+                                "Service `{}` failed to start: {}",
+                                name, error
+                            )));
+                        }
+                    }
+                }
+            };
+
+            if let (Some(hook), true) = (entry.on_shutdown, services.contains(name)) {
+                started.push((name, hook));
+            }
+            statuses.push(ServiceStatus {
+                name: name.to_string(),
+                state,
+                optional: entry.optional,
+                dependencies: entry.dependencies.iter().map(|d| d.to_string()).collect(),
+                duration_ms: started_at.elapsed().as_millis() as u64,
+            });
+        }
+
+        info!(
+            "Started {} of {} services",
+            statuses.iter().filter(|s| s.is_ready()).count(),
+            statuses.len()
+        );
+        Ok(RunningServices {
+            services,
+            statuses,
+            shutdown: Mutex::new(started),
+        })
+    }
+}
+
+pub struct RunningServices {
+    services: Services,
+    statuses: Vec<ServiceStatus>,
+    shutdown: Mutex<Vec<(&'static str, ShutdownFn)>>,
+}
+
+impl RunningServices {
+    pub fn services(&self) -> &Services {
+        &self.services
+    }
+
+    pub fn get<T: ?Sized + Send + Sync + 'static>(&self, name: &str) -> Result<Arc<T>> {
+        self.services.get(name)
+    }
+
+    /// Per-service outcome, in startup order
+    pub fn statuses(&self) -> &[ServiceStatus] {
+        &self.statuses
+    }
+
+    /// Run shutdown hooks in reverse startup order. Later calls do nothing
+    pub async fn shutdown(&self) -> Result<()> {
+        let hooks = std::mem::take(&mut *self.shutdown.lock().unwrap());
+        run_shutdown_hooks(&self.services, hooks).await
+    }
+}
+
+// Every hook runs even when an earlier one fails; the first error is returned
+async fn run_shutdown_hooks(services: &Services, hooks: Vec<(&'static str, ShutdownFn)>) -> Result<()> {
+    let mut first_error = None;
+    for (name, hook) in hooks.into_iter().rev() {
+        let Some(value) = services.values.get(name).cloned() else {
+            continue;
+        };
+        if let Err(e) = hook(value).await {
+            warn!("Service {} failed to shut down: {}", name, e);
+            first_error.get_or_insert(e);
+        }
+    }
+    first_error.map_or(Ok(()), Err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Log = Arc<Mutex<Vec<String>>>;
+
+    fn logged(log: &Log, name: &'static str) -> ServiceDef<String> {
+        let init_log = log.clone();
+        let shutdown_log = log.clone();
+        ServiceDef::new(name, move |_| {
+            let log = init_log.clone();
+            async move {
+                log.lock().unwrap().push(format!("start {}", name));
+                Ok(Arc::new(name.to_string()))
+            }
+        })
+        .on_shutdown(move |_| {
+            let log = shutdown_log.clone();
+            async move {
+                log.lock().unwrap().push(format!("stop {}", name));
+                Ok(())
+            }
+        })
+    }
+
+    fn failing(name: &'static str) -> ServiceDef<String> {
+        ServiceDef::new(name, |_| async { Err(AssistantError::Database("connection refused".to_string())) })
+    }
+
+    #[tokio::test]
+    async fn test_services_start_after_their_dependencies() {
+        let log = Log::default();
+        let registry = ServiceRegistry::default()
+            .with(logged(&log, "orchestrator").depends_on(&["context", "plugins"]))
+            .with(logged(&log, "context").depends_on(&["storage"]))
+            .with(logged(&log, "plugins"))
+            .with(logged(&log, "storage"));
+        assert_eq!(
+            registry.startup_order().unwrap(),
+            vec!["plugins", "storage", "context", "orchestrator"]
+        );
+
+        let running = registry.start().await.unwrap();
+        assert_eq!(
+            log.lock().unwrap().clone(),
+            vec!["start plugins", "start storage", "start context", "start orchestrator"]
+        );
+        assert!(running.statuses().iter().all(ServiceStatus::is_ready));
+        assert_eq!(*running.get::<String>("context").unwrap(), "context");
+
+        let cyclic = ServiceRegistry::default()
+            .with(logged(&log, "a").depends_on(&["b"]))
+            .with(logged(&log, "b").depends_on(&["a"]));
+        assert!(cyclic.startup_order().unwrap_err().to_string().contains("cycle among: a, b"));
+        let unknown = ServiceRegistry::default().with(logged(&log, "a").depends_on(&["missing"]));
+        assert!(unknown.startup_order().is_err());
+
+        // A subset brings its dependencies along and nothing else
+        let log = Log::default();
+        let subset = ServiceRegistry::default()
+            .with(logged(&log, "storage"))
+            .with(logged(&log, "context").depends_on(&["storage"]))
+            .with(logged(&log, "plugins"))
+            .only(&["context"]);
+        assert_eq!(subset.names(), vec!["storage", "context"]);
+    }
+
+    #[tokio::test]
+    async fn test_optional_failures_degrade_instead_of_aborting() {
+        let log = Log::default();
+        let running = ServiceRegistry::new(Duration::from_millis(50))
+            .with(logged(&log, "storage"))
+            .with(failing("digests").depends_on(&["storage"]).optional())
+            .with(logged(&log, "digest_schedule").depends_on(&["digests"]).optional())
+            .with(
+                ServiceDef::new("flags", |_| async {
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                    Ok(Arc::new("loaded".to_string()))
+                })
+                .fallback(|_| Arc::new("defaults".to_string())),
+            )
+            .with(logged(&log, "orchestrator").depends_on(&["storage", "flags"]))
+            .start()
+            .await
+            .unwrap();
+
+        let states: HashMap<&str, &ServiceState> =
+            running.statuses().iter().map(|s| (s.name.as_str(), &s.state)).collect();
+        assert_eq!(states["storage"], &ServiceState::Ready);
+        assert!(matches!(states["digests"], ServiceState::Failed { error } if error.contains("connection refused")));
+        assert!(matches!(states["digest_schedule"], ServiceState::Skipped { reason } if reason.contains("`digests`")));
+        assert!(matches!(states["flags"], ServiceState::Degraded { error } if error.contains("timed out after 50ms")));
+        assert_eq!(states["orchestrator"], &ServiceState::Ready);
+        assert_eq!(*running.get::<String>("flags").unwrap(), "defaults");
+        assert!(running.services().try_get::<String>("digests").is_none());
+
+        // A required service on top of a failed one stops startup, and what
+        // had started is shut down again
+        let log = Log::default();
+        let error = ServiceRegistry::default()
+            .with(logged(&log, "storage"))
+            .with(failing("digests").optional())
+            .with(logged(&log, "briefings").depends_on(&["digests"]))
+            .start()
+            .await
+            .err()
+            .unwrap();
+        assert!(error.to_string().contains("Service `briefings` cannot start"));
+        assert_eq!(log.lock().unwrap().clone(), vec!["start storage", "stop storage"]);
+
+        let error = ServiceRegistry::default().with(failing("storage")).start().await.err().unwrap();
+        assert!(error.to_string().contains("Service `storage` failed to start"));
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_runs_in_reverse_startup_order() {
+        let log = Log::default();
+        let running = ServiceRegistry::default()
+            .with(logged(&log, "orchestrator").depends_on(&["plugins", "storage"]))
+            .with(logged(&log, "storage"))
+            .with(logged(&log, "plugins"))
+            .with(failing("digests").optional().on_shutdown(|_| async {
+                panic!("a service that never started is not shut down")
+            }))
+            .with(ServiceDef::new("events", |_| async { Ok(Arc::new(0u8)) }).on_shutdown(|_| async {
+                Err(AssistantError::Internal("subscribers still attached".to_string()))
+            }))
+            .start()
+            .await
+            .unwrap();
+        log.lock().unwrap().clear();
+
+        // A failing hook does not stop the rest
+        let error = running.shutdown().await.unwrap_err();
+        assert!(error.to_string().contains("subscribers still attached"));
+        assert_eq!(
+            log.lock().unwrap().clone(),
+            vec!["stop orchestrator", "stop plugins", "stop storage"]
+        );
+
+        running.shutdown().await.unwrap();
+        assert_eq!(log.lock().unwrap().len(), 3);
+    }
+}
@@ -0,0 +1,268 @@
+// Conversation sessions as live streams. Every device following a session
+// gets the same frames in the same order: each session has one channel, and
+// frames are numbered and sent while its lock is held. A device joining
+// late gets a snapshot numbered like the frames, so it knows which come
+// after it. Callers take the snapshot's turns and append turns under the
+// context manager lock, which keeps a turn from being both in a snapshot
+// and in a frame after it.
+use rusty_ai_common::api::{ChatResponse, LiveFrame, PartialReply, Presence, SessionEvent};
+use rusty_ai_common::ConversationTurn;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+/// Frames a follower of a session can fall behind by before it needs a new snapshot
+pub const SESSION_STREAM_CAPACITY: usize = 256;
+
+struct SessionChannel {
+    tx: broadcast::Sender<LiveFrame>,
+    seq: u64,
+    presence: Presence,
+    reply: Option<PartialReply>,
+}
+
+impl SessionChannel {
+    fn new() -> Self {
+        let (tx, _rx) = broadcast::channel(SESSION_STREAM_CAPACITY);
+        Self { tx, seq: 0, presence: Presence::Idle, reply: None }
+    }
+
+    fn send(&mut self, session_id: Uuid, event: SessionEvent) {
+        match &event {
+            SessionEvent::Presence { state } => self.presence = *state,
+            SessionEvent::ChatStarted { reply_id, user_input } => {
+                self.reply = Some(PartialReply { reply_id: *reply_id, user_input: user_input.clone(), text: String::new() });
+            }
+            SessionEvent::ChatDelta { reply_id, delta } => {
+                if let Some(reply) = self.reply.as_mut().filter(|reply| reply.reply_id == *reply_id) {
+                    reply.text.push_str(delta);
+                }
+            }
+            // Once its turn is stored the reply is part of the history
+            SessionEvent::TurnAppended { reply_id: Some(reply_id), .. } | SessionEvent::ChatFailed { reply_id } => {
+                if self.reply.as_ref().is_some_and(|reply| reply.reply_id == *reply_id) {
+                    self.reply = None;
+                }
+            }
+            SessionEvent::TurnAppended { reply_id: None, .. } | SessionEvent::ChatDone { .. } => {}
+        }
+        self.seq += 1;
+        // Failing only means nobody follows the session right now
+        let _ = self.tx.send(LiveFrame::Session { session_id, seq: self.seq, event });
+    }
+
+    fn unused(&self) -> bool {
+        self.tx.receiver_count() == 0 && self.reply.is_none()
+    }
+}
+
+#[derive(Default)]
+pub struct SessionStreams {
+    sessions: Mutex<HashMap<Uuid, SessionChannel>>,
+}
+
+impl SessionStreams {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start following `session_id`. `turns` is its history, read under the
+    /// context manager lock that must still be held; the snapshot is
+    /// returned with the receiver for every frame after it
+    pub fn subscribe(&self, session_id: Uuid, turns: Vec<ConversationTurn>) -> (LiveFrame, broadcast::Receiver<LiveFrame>) {
+        let mut sessions = self.sessions.lock().unwrap();
+        let channel = sessions.entry(session_id).or_insert_with(SessionChannel::new);
+        let snapshot = LiveFrame::SessionSnapshot {
+            session_id,
+            seq: channel.seq,
+            turns,
+            presence: channel.presence,
+            reply: channel.reply.clone(),
+        };
+        (snapshot, channel.tx.subscribe())
+    }
+
+    /// Send `event` to everyone following the session; nobody following
+    /// means it is dropped
+    pub fn publish(&self, session_id: Uuid, event: SessionEvent) {
+        let mut sessions = self.sessions.lock().unwrap();
+        if let Some(channel) = sessions.get_mut(&session_id) {
+            channel.send(session_id, event);
+            if channel.unused() {
+                sessions.remove(&session_id);
+            }
+        }
+    }
+
+    /// A turn was stored; call with the context manager lock still held
+    pub fn append(&self, session_id: Uuid, turn: ConversationTurn, reply_id: Option<Uuid>) {
+        self.publish(session_id, SessionEvent::TurnAppended { turn, reply_id });
+    }
+
+    /// Show the user typing, unless a reply is already being generated
+    pub fn typing(&self, session_id: Uuid, active: bool) {
+        let mut sessions = self.sessions.lock().unwrap();
+        if let Some(channel) = sessions.get_mut(&session_id).filter(|channel| channel.reply.is_none()) {
+            let state = if active { Presence::Typing } else { Presence::Idle };
+            channel.send(session_id, SessionEvent::Presence { state });
+        }
+    }
+
+    /// Start streaming a reply to `user_input`. The session shows as
+    /// generating until the reply is finished or dropped
+    pub fn begin_reply(self: &Arc<Self>, session_id: Uuid, user_input: &str) -> ReplyStream {
+        let reply_id = Uuid::new_v4();
+        {
+            // Kept even with nobody following, so a device joining mid-reply sees it
+            let mut sessions = self.sessions.lock().unwrap();
+            let channel = sessions.entry(session_id).or_insert_with(SessionChannel::new);
+            channel.send(session_id, SessionEvent::Presence { state: Presence::Generating });
+            channel.send(session_id, SessionEvent::ChatStarted { reply_id, user_input: user_input.to_string() });
+        }
+        ReplyStream { streams: self.clone(), session_id, reply_id, finished: false }
+    }
+
+    /// Forget a session nobody follows any more
+    pub fn release(&self, session_id: Uuid) {
+        let mut sessions = self.sessions.lock().unwrap();
+        if sessions.get(&session_id).is_some_and(SessionChannel::unused) {
+            sessions.remove(&session_id);
+        }
+    }
+
+    /// Sessions with a channel, for tests and resource reports
+    pub fn active_sessions(&self) -> usize {
+        self.sessions.lock().unwrap().len()
+    }
+}
+
+/// A reply being streamed into a session
+pub struct ReplyStream {
+    streams: Arc<SessionStreams>,
+    session_id: Uuid,
+    reply_id: Uuid,
+    finished: bool,
+}
+
+impl ReplyStream {
+    pub fn reply_id(&self) -> Uuid {
+        self.reply_id
+    }
+
+    pub fn delta(&self, delta: &str) {
+        self.streams.publish(
+            self.session_id,
+            SessionEvent::ChatDelta { reply_id: self.reply_id, delta: delta.to_string() },
+        );
+    }
+
+    /// End the reply with the response the requesting device got
+    pub fn finish(mut self, response: ChatResponse) {
+        self.finished = true;
+        self.streams.publish(self.session_id, SessionEvent::ChatDone { reply_id: self.reply_id, response });
+        self.streams.publish(self.session_id, SessionEvent::Presence { state: Presence::Idle });
+    }
+}
+
+impl Drop for ReplyStream {
+    // A reply dropped unfinished failed somewhere on the way
+    fn drop(&mut self) {
+        if !self.finished {
+            self.streams.publish(self.session_id, SessionEvent::ChatFailed { reply_id: self.reply_id });
+            self.streams.publish(self.session_id, SessionEvent::Presence { state: Presence::Idle });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rusty_ai_common::{Intent, TurnKind};
+
+    fn turn(text: &str) -> ConversationTurn {
+        ConversationTurn {
+            id: Uuid::new_v4(),
+            user_input: "hi".to_string(),
+            assistant_response: text.to_string(),
+            intent: Intent::Unknown,
+            timestamp: chrono::Utc::now(),
+            kind: TurnKind::Message,
+        }
+    }
+
+    fn event(frame: LiveFrame) -> (u64, SessionEvent) {
+        match frame {
+            LiveFrame::Session { seq, event, .. } => (seq, event),
+            other => panic!("expected a session frame, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_late_subscriber_resumes_after_its_snapshot() {
+        let streams = Arc::new(SessionStreams::new());
+        let session_id = Uuid::new_v4();
+        let (_, mut early) = streams.subscribe(session_id, vec![]);
+
+        let reply = streams.begin_reply(session_id, "hi");
+        reply.delta("Hel");
+        let (snapshot, mut late) = streams.subscribe(session_id, vec![]);
+        match snapshot {
+            LiveFrame::SessionSnapshot { seq, presence, reply: Some(partial), .. } => {
+                assert_eq!(seq, 3);
+                assert_eq!(presence, Presence::Generating);
+                assert_eq!(partial.text, "Hel");
+            }
+            other => panic!("unexpected snapshot {:?}", other),
+        }
+
+        reply.delta("lo");
+        let reply_id = reply.reply_id();
+        streams.append(session_id, turn("Hello"), Some(reply_id));
+        reply.finish(ChatResponse {
+            response: "Hello".to_string(),
+            session_id,
+            intent: Intent::Unknown,
+            conversation_id: Uuid::new_v4(),
+            processing_time_ms: 1,
+            suggested_actions: vec![],
+            sources: vec![],
+            processing: vec![],
+            action_ids: vec![],
+        });
+
+        let early_seqs: Vec<u64> = std::iter::from_fn(|| early.try_recv().ok()).map(|frame| event(frame).0).collect();
+        assert_eq!(early_seqs, (1..=7).collect::<Vec<_>>());
+        let late: Vec<_> = std::iter::from_fn(|| late.try_recv().ok()).map(event).collect();
+        assert_eq!(late.iter().map(|(seq, _)| *seq).collect::<Vec<_>>(), vec![4, 5, 6, 7]);
+        assert!(matches!(&late[0].1, SessionEvent::ChatDelta { delta, .. } if delta == "lo"));
+        assert!(matches!(late[1].1, SessionEvent::TurnAppended { reply_id: Some(id), .. } if id == reply_id));
+        assert!(matches!(&late[2].1, SessionEvent::ChatDone { response, .. } if response.response == "Hello"));
+        assert!(matches!(late[3].1, SessionEvent::Presence { state: Presence::Idle }));
+
+        let (snapshot, _) = streams.subscribe(session_id, vec![]);
+        assert!(matches!(snapshot, LiveFrame::SessionSnapshot { seq: 7, reply: None, .. }));
+    }
+
+    #[tokio::test]
+    async fn test_unfollowed_sessions_are_not_kept() {
+        let streams = Arc::new(SessionStreams::new());
+        let session_id = Uuid::new_v4();
+
+        // Nobody is listening, so nothing to keep
+        streams.append(session_id, turn("Hello"), None);
+        assert_eq!(streams.active_sessions(), 0);
+
+        let reply = streams.begin_reply(session_id, "hi");
+        assert_eq!(streams.active_sessions(), 1);
+        streams.typing(session_id, true);
+        // An unfinished reply ends as failed, which lets the session go
+        drop(reply);
+        assert_eq!(streams.active_sessions(), 0);
+
+        let (_, rx) = streams.subscribe(session_id, vec![]);
+        drop(rx);
+        streams.release(session_id);
+        assert_eq!(streams.active_sessions(), 0);
+    }
+}
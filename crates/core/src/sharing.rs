@@ -0,0 +1,332 @@
+use rusty_ai_common::{AssistantError, Result};
+use chrono::{DateTime, Duration, Utc};
+use ring::{digest, hmac, rand::{SecureRandom, SystemRandom}};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::notifications::{Clock, SystemClock};
+
+/// Longest lifetime a share link may be created with
+pub const MAX_SHARE_TTL_HOURS: i64 = 24 * 30;
+pub const DEFAULT_SHARE_TTL_HOURS: i64 = 24;
+
+/// The single resource a share link grants read access to
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "id", rename_all = "snake_case")]
+pub enum SharedResource {
+    Briefing(Uuid),
+    Document(Uuid),
+}
+
+/// A share link as stored; only the hash of its token is kept
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShareLink {
+    pub id: Uuid,
+    token_hash: String,
+    pub resource: SharedResource,
+    pub created_by: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone)]
+pub enum ShareLookup {
+    Active(ShareLink),
+    Expired,
+    Revoked,
+    NotFound,
+}
+
+// Issues and resolves read-only share links. A token is a random nonce plus
+// its HMAC, so forged tokens are rejected before any lookup; the store keeps
+// only SHA-256 hashes of issued tokens.
+pub struct ShareLinkStore {
+    clock: Arc<dyn Clock>,
+    key: hmac::Key,
+    rng: SystemRandom,
+    links: Mutex<HashMap<String, ShareLink>>,
+    store_path: Option<PathBuf>,
+}
+
+impl ShareLinkStore {
+    pub fn new(secret: &[u8], store_path: Option<PathBuf>) -> Self {
+        Self::new_with_clock(Arc::new(SystemClock), secret, store_path)
+    }
+
+    pub fn new_with_clock(clock: Arc<dyn Clock>, secret: &[u8], store_path: Option<PathBuf>) -> Self {
+        let links: HashMap<String, ShareLink> = store_path
+            .as_deref()
+            .map(load_links)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|link| (link.token_hash.clone(), link))
+            .collect();
+        if !links.is_empty() {
+            info!("Loaded {} share links", links.len());
+        }
+
+        Self {
+            clock,
+            key: hmac::Key::new(hmac::HMAC_SHA256, secret),
+            rng: SystemRandom::new(),
+            links: Mutex::new(links),
+            store_path,
+        }
+    }
+
+    /// Create a link for `resource`; returns the link and the token, which is
+    /// only available now
+    pub fn create(&self, resource: SharedResource, created_by: Uuid, ttl: Duration) -> Result<(ShareLink, String)> {
+        if ttl <= Duration::zero() || ttl > Duration::hours(MAX_SHARE_TTL_HOURS) {
+            return Err(AssistantError::Api(format!(
+                "Share links must expire within {} hours",
+                MAX_SHARE_TTL_HOURS
+            )));
+        }
+
+        let mut nonce = [0u8; 24];
+        self.rng
+            .fill(&mut nonce)
+            .map_err(|_| AssistantError::Internal("Failed to generate share token".to_string()))?;
+        let signature = hmac::sign(&self.key, &nonce);
+        let token = format!("{}.{}", to_hex(&nonce), to_hex(&signature.as_ref()[..16]));
+
+        let now = self.clock.now();
+        let link = ShareLink {
+            id: Uuid::new_v4(),
+            token_hash: hash_token(&token),
+            resource,
+            created_by,
+            created_at: now,
+            expires_at: now + ttl,
+            revoked_at: None,
+        };
+
+        self.links.lock().unwrap().insert(link.token_hash.clone(), link.clone());
+        self.persist()?;
+
+        info!("Created share link {} for {:?}", link.id, link.resource);
+        Ok((link, token))
+    }
+
+    pub fn resolve(&self, token: &str) -> ShareLookup {
+        if !self.verify_signature(token) {
+            return ShareLookup::NotFound;
+        }
+
+        let links = self.links.lock().unwrap();
+        match links.get(&hash_token(token)) {
+            None => ShareLookup::NotFound,
+            Some(link) if link.revoked_at.is_some() => ShareLookup::Revoked,
+            Some(link) if link.expires_at <= self.clock.now() => ShareLookup::Expired,
+            Some(link) => ShareLookup::Active(link.clone()),
+        }
+    }
+
+    /// Revoke a link on behalf of `revoked_by`; returns false if that user
+    /// created no such link, so other users can't learn that it exists
+    pub fn revoke(&self, link_id: Uuid, revoked_by: Uuid) -> Result<bool> {
+        let revoked = {
+            let mut links = self.links.lock().unwrap();
+            match links
+                .values_mut()
+                .find(|link| link.id == link_id && link.created_by == revoked_by)
+            {
+                Some(link) => {
+                    link.revoked_at.get_or_insert(self.clock.now());
+                    true
+                }
+                None => false,
+            }
+        };
+
+        if revoked {
+            self.persist()?;
+            info!("Revoked share link {}", link_id);
+        }
+        Ok(revoked)
+    }
+
+    /// Links for a resource, newest first
+    pub fn links_for(&self, resource: &SharedResource) -> Vec<ShareLink> {
+        let mut links: Vec<ShareLink> = self
+            .links
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|link| &link.resource == resource)
+            .cloned()
+            .collect();
+        links.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+        links
+    }
+
+    /// Drop links that expired more than `grace` ago
+    pub fn purge_expired(&self, grace: Duration) -> Result<usize> {
+        let cutoff = self.clock.now() - grace;
+        let removed = {
+            let mut links = self.links.lock().unwrap();
+            let before = links.len();
+            links.retain(|_, link| link.expires_at > cutoff);
+            before - links.len()
+        };
+
+        if removed > 0 {
+            self.persist()?;
+        }
+        Ok(removed)
+    }
+
+    fn verify_signature(&self, token: &str) -> bool {
+        let Some((nonce, signature)) = token.split_once('.') else {
+            return false;
+        };
+        let (Some(nonce), Some(signature)) = (from_hex(nonce), from_hex(signature)) else {
+            return false;
+        };
+
+        // The token carries a truncated tag, so compare against the same prefix
+        let expected = hmac::sign(&self.key, &nonce);
+        signature.len() == 16 && ring::constant_time::verify_slices_are_equal(&expected.as_ref()[..16], &signature).is_ok()
+    }
+
+    fn persist(&self) -> Result<()> {
+        let path = match &self.store_path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+
+        let links: Vec<ShareLink> = self.links.lock().unwrap().values().cloned().collect();
+        let json = serde_json::to_vec_pretty(&links)
+            .map_err(|e| AssistantError::Internal(format!("Failed to serialize share links: {}", e)))?;
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| AssistantError::Internal(format!("Failed to create share link store: {}", e)))?;
+        }
+        std::fs::write(path, json)
+            .map_err(|e| AssistantError::Internal(format!("Failed to persist share links: {}", e)))
+    }
+}
+
+fn hash_token(token: &str) -> String {
+    to_hex(digest::digest(&digest::SHA256, token.as_bytes()).as_ref())
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn load_links(path: &Path) -> Vec<ShareLink> {
+    match std::fs::read(path) {
+        Ok(bytes) => serde_json::from_slice(&bytes).unwrap_or_else(|e| {
+            warn!("Ignoring unreadable share link store {:?}: {}", path, e);
+            Vec::new()
+        }),
+        Err(_) => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestClock;
+
+    fn store_at(now: DateTime<Utc>) -> (Arc<TestClock>, ShareLinkStore) {
+        let clock = TestClock::new(now);
+        let store = ShareLinkStore::new_with_clock(clock.clone(), b"test-secret", None);
+        (clock, store)
+    }
+
+    #[test]
+    fn test_link_expires() {
+        let now = Utc::now();
+        let (clock, store) = store_at(now);
+        let resource = SharedResource::Briefing(Uuid::new_v4());
+
+        let (_, token) = store.create(resource.clone(), Uuid::new_v4(), Duration::hours(1)).unwrap();
+        assert!(matches!(store.resolve(&token), ShareLookup::Active(link) if link.resource == resource));
+
+        clock.set_to(now + Duration::hours(2));
+        assert!(matches!(store.resolve(&token), ShareLookup::Expired));
+    }
+
+    #[test]
+    fn test_link_revocation() {
+        let (_, store) = store_at(Utc::now());
+        let owner = Uuid::new_v4();
+        let (link, token) = store
+            .create(SharedResource::Document(Uuid::new_v4()), owner, Duration::hours(1))
+            .unwrap();
+
+        // Someone else's link looks the same as a missing one
+        assert!(!store.revoke(link.id, Uuid::new_v4()).unwrap());
+        assert!(matches!(store.resolve(&token), ShareLookup::Active(_)));
+
+        assert!(store.revoke(link.id, owner).unwrap());
+        assert!(matches!(store.resolve(&token), ShareLookup::Revoked));
+        assert!(!store.revoke(Uuid::new_v4(), owner).unwrap());
+    }
+
+    #[test]
+    fn test_forged_and_foreign_tokens_are_unknown() {
+        let (_, store) = store_at(Utc::now());
+        let (_, token) = store
+            .create(SharedResource::Document(Uuid::new_v4()), Uuid::new_v4(), Duration::hours(1))
+            .unwrap();
+
+        // Flipping a nonce character breaks the signature
+        let mut forged = token.clone();
+        let first = if forged.starts_with('0') { "1" } else { "0" };
+        forged.replace_range(0..1, first);
+        assert!(matches!(store.resolve(&forged), ShareLookup::NotFound));
+
+        // A validly signed token from another deployment is not ours either
+        let (_, other) = store_at(Utc::now());
+        let (_, foreign) = other
+            .create(SharedResource::Document(Uuid::new_v4()), Uuid::new_v4(), Duration::hours(1))
+            .unwrap();
+        assert!(matches!(store.resolve(&foreign), ShareLookup::NotFound));
+        assert!(matches!(store.resolve("not-a-token"), ShareLookup::NotFound));
+    }
+
+    #[test]
+    fn test_ttl_is_bounded() {
+        let (_, store) = store_at(Utc::now());
+        let resource = SharedResource::Briefing(Uuid::new_v4());
+
+        assert!(store.create(resource.clone(), Uuid::new_v4(), Duration::hours(MAX_SHARE_TTL_HOURS + 1)).is_err());
+        assert!(store.create(resource, Uuid::new_v4(), Duration::zero()).is_err());
+    }
+
+    #[test]
+    fn test_links_survive_restart_without_plaintext_tokens() {
+        let path = std::env::temp_dir().join(format!("rusty-ai-shares-{}.json", Uuid::new_v4()));
+        let store = ShareLinkStore::new(b"secret", Some(path.clone()));
+        let (_, token) = store
+            .create(SharedResource::Briefing(Uuid::new_v4()), Uuid::new_v4(), Duration::hours(1))
+            .unwrap();
+
+        let persisted = std::fs::read_to_string(&path).unwrap();
+        assert!(!persisted.contains(&token));
+
+        let reloaded = ShareLinkStore::new(b"secret", Some(path.clone()));
+        assert!(matches!(reloaded.resolve(&token), ShareLookup::Active(_)));
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -1,12 +1,22 @@
-use rusty_ai_common::{Result, AssistantError, Document, Task, TaskStatus, DailyBriefing};
+use rusty_ai_common::{Result, AssistantError, Document, Task, TaskStatus, DailyBriefing, BriefingSection, GenerationReport, ConversationTurn, TurnKind, PluginAuditRecord, PluginAuditQuery, PluginSchedule, CatchUpPolicy, PluginConfig};
 use async_trait::async_trait;
-use sqlx::{SqlitePool, Postgres, Pool, migrate::MigrateDatabase, Sqlite, Row};
+use sqlx::{SqlitePool, Postgres, Pool, migrate::MigrateDatabase, Sqlite, Row, sqlite::SqliteRow};
 use std::sync::Arc;
 use uuid::Uuid;
 use chrono::{DateTime, Utc};
-use tracing::{info, error, debug};
+use tracing::{info, error, debug, warn};
 use serde_json;
 
+use crate::activity::{ActionKind, ActionStatus, ActivityFilter, AssistantAction};
+use crate::audit::{AuditEntry, AuditFilter};
+use crate::briefing_schema::{decode_sections, encode_briefing, CURRENT_BRIEFING_SCHEMA};
+use crate::flags::FlagOverride;
+use crate::knowledge_digest::KnowledgeDigest;
+use crate::query_metrics::QueryMetrics;
+use crate::retention::{CleanupReport, RetentionEntity, RetentionPolicy};
+use crate::sync::{ChangeBatch, RecordChange, SyncCursor, SyncEntity, MAX_SYNC_PAGE_SIZE};
+use crate::time_tracking::{TimeEntry, TimerStart};
+
 #[async_trait]
 pub trait Storage: Send + Sync {
     // Document operations
@@ -23,6 +33,9 @@ pub trait Storage: Send + Sync {
     async fn update_task_status(&self, id: Uuid, status: TaskStatus) -> Result<()>;
     async fn get_pending_tasks(&self) -> Result<Vec<Task>>;
     async fn get_tasks_by_status(&self, status: TaskStatus) -> Result<Vec<Task>>;
+    async fn delete_task(&self, _id: Uuid) -> Result<()> {
+        Err(AssistantError::Internal("This storage does not delete tasks".to_string()))
+    }
 
     // Daily briefing operations
     async fn store_briefing(&self, briefing: &DailyBriefing) -> Result<()>;
@@ -30,9 +43,163 @@ pub trait Storage: Send + Sync {
     async fn get_latest_briefing(&self) -> Result<Option<DailyBriefing>>;
     async fn get_briefings_by_date_range(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Result<Vec<DailyBriefing>>;
 
+    // Knowledge digest operations. The defaults suit storage without an
+    // access log or digest table: nothing counts as referenced and digests
+    // cannot be kept
+    async fn get_documents_created_between(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Result<Vec<Document>> {
+        let mut documents: Vec<Document> = self.search_documents("", 1000).await?
+            .into_iter()
+            .filter(|doc| doc.created_at >= start && doc.created_at < end)
+            .collect();
+        documents.sort_by_key(|doc| doc.created_at);
+        Ok(documents)
+    }
+    async fn record_document_access(&self, _id: Uuid) -> Result<()> {
+        Ok(())
+    }
+    async fn get_document_access_counts(&self, _start: DateTime<Utc>, _end: DateTime<Utc>, _limit: usize) -> Result<Vec<(Uuid, u64)>> {
+        Ok(Vec::new())
+    }
+    async fn store_knowledge_digest(&self, _digest: &KnowledgeDigest) -> Result<()> {
+        Err(AssistantError::Internal("This storage does not keep knowledge digests".to_string()))
+    }
+    async fn get_knowledge_digests(&self, _user_id: Uuid, _limit: usize) -> Result<Vec<KnowledgeDigest>> {
+        Ok(Vec::new())
+    }
+
+    // Admin audit trail. Kept apart from the data `cleanup_old_data` prunes;
+    // the defaults suit storage without an audit table
+    async fn store_audit_entry(&self, _entry: &AuditEntry) -> Result<()> {
+        Err(AssistantError::Internal("This storage does not keep an audit trail".to_string()))
+    }
+    async fn get_audit_entries(&self, _filter: &AuditFilter) -> Result<Vec<AuditEntry>> {
+        Ok(Vec::new())
+    }
+    async fn prune_audit_entries(&self, _before: DateTime<Utc>) -> Result<usize> {
+        Ok(0)
+    }
+
+    // Differential sync: the latest change per record of the user's synced
+    // entities after `since`, at most `limit` of them
+    async fn get_changes_since(&self, _user_id: Uuid, since: SyncCursor, _limit: usize) -> Result<ChangeBatch> {
+        Ok(ChangeBatch::empty(since))
+    }
+
+    // Task time tracking. A user has at most one running timer: starting one
+    // stops the other. The defaults suit storage without a time log
+    async fn start_task_timer(&self, _user_id: Uuid, _task_id: Uuid, _note: Option<String>) -> Result<TimerStart> {
+        Err(AssistantError::Internal("This storage does not track time".to_string()))
+    }
+    /// Stop the user's running timer, only if it is on `task_id` when given
+    async fn stop_task_timer(&self, _user_id: Uuid, _task_id: Option<Uuid>, _note: Option<String>) -> Result<Option<TimeEntry>> {
+        Ok(None)
+    }
+    async fn get_task_time_entries(&self, _task_id: Uuid) -> Result<Vec<TimeEntry>> {
+        Ok(Vec::new())
+    }
+    /// Entries overlapping `[start, end)`, of one user or of everyone
+    async fn get_time_entries_between(&self, _user_id: Option<Uuid>, _start: DateTime<Utc>, _end: DateTime<Utc>) -> Result<Vec<TimeEntry>> {
+        Ok(Vec::new())
+    }
+
+    // Conversation turns, beyond the recent ones sessions keep in memory.
+    // The defaults suit storage without a turn log: older turns are dropped
+    async fn store_conversation_turn(&self, _session_id: Uuid, _turn: &ConversationTurn) -> Result<()> {
+        Ok(())
+    }
+    /// Turns of the session oldest first, skipping `offset`
+    async fn get_conversation_turns(&self, _session_id: Uuid, _offset: usize, _limit: usize) -> Result<Vec<ConversationTurn>> {
+        Ok(Vec::new())
+    }
+    async fn count_conversation_turns(&self, _session_id: Uuid) -> Result<usize> {
+        Ok(0)
+    }
+
+    // Searches that found something, counted per user for suggestions. The
+    // defaults suit storage without a search history
+    async fn record_search_query(&self, _user_id: Uuid, _query: &str) -> Result<()> {
+        Ok(())
+    }
+    /// The user's past queries with their counts, most frequent first
+    async fn get_search_queries(&self, _user_id: Uuid, _limit: usize) -> Result<Vec<(String, u64)>> {
+        Ok(Vec::new())
+    }
+
+    // Runtime feature flag changes, by flag name. The defaults suit storage
+    // without them: changes last until restart
+    /// Replace the flag's override; `None` removes it
+    async fn store_flag_override(&self, _flag: &str, _flag_override: Option<&FlagOverride>) -> Result<()> {
+        Ok(())
+    }
+    async fn get_flag_overrides(&self) -> Result<Vec<(String, FlagOverride)>> {
+        Ok(Vec::new())
+    }
+
+    // Key-value data plugins keep through the `kv_*` host imports, by plugin
+    // id. The defaults suit storage without it: plugins cannot persist data
+    async fn get_plugin_value(&self, _plugin_id: &str, _key: &str) -> Result<Option<Vec<u8>>> {
+        Ok(None)
+    }
+    /// Replace the value under the plugin's key; `None` removes it
+    async fn store_plugin_value(&self, _plugin_id: &str, _key: &str, _value: Option<&[u8]>) -> Result<()> {
+        Err(AssistantError::Internal("This storage does not keep plugin data".to_string()))
+    }
+
+    // A record of every plugin call, pruned by `cleanup_old_data`. The
+    // defaults suit storage without one: calls are not recorded
+    async fn store_plugin_audit(&self, _record: &PluginAuditRecord) -> Result<()> {
+        Ok(())
+    }
+    async fn query_plugin_audits(&self, _query: &PluginAuditQuery) -> Result<Vec<PluginAuditRecord>> {
+        Ok(Vec::new())
+    }
+
+    // Scheduled plugin jobs. Storing a job replaces the one with the same id
+    async fn store_plugin_schedule(&self, _schedule: &PluginSchedule) -> Result<()> {
+        Err(AssistantError::Internal("This storage does not keep plugin schedules".to_string()))
+    }
+    async fn list_plugin_schedules(&self) -> Result<Vec<PluginSchedule>> {
+        Ok(Vec::new())
+    }
+    // Whether there was a job to delete
+    async fn delete_plugin_schedule(&self, _id: Uuid) -> Result<bool> {
+        Ok(false)
+    }
+
+    // Plugin configuration by plugin name. Storing a config replaces the
+    // plugin's previous one
+    async fn store_plugin_config(&self, _plugin_id: &str, _config: &PluginConfig) -> Result<()> {
+        Err(AssistantError::Internal("This storage does not keep plugin configs".to_string()))
+    }
+    async fn get_plugin_config(&self, _plugin_id: &str) -> Result<Option<PluginConfig>> {
+        Ok(None)
+    }
+
+    // Actions the assistant took on a user's behalf. The defaults suit
+    // storage without an activity log
+    async fn store_assistant_action(&self, _action: &AssistantAction) -> Result<()> {
+        Err(AssistantError::Internal("This storage does not keep an activity log".to_string()))
+    }
+    /// The user's actions newest first, paged by the filter's offset and limit
+    async fn get_assistant_actions(&self, _user_id: Uuid, _filter: &ActivityFilter) -> Result<Vec<AssistantAction>> {
+        Ok(Vec::new())
+    }
+    async fn get_assistant_action(&self, _id: Uuid) -> Result<Option<AssistantAction>> {
+        Ok(None)
+    }
+    async fn mark_assistant_action_undone(&self, _id: Uuid, _at: DateTime<Utc>) -> Result<()> {
+        Err(AssistantError::Internal("This storage does not keep an activity log".to_string()))
+    }
+
     // Maintenance operations
-    async fn cleanup_old_data(&self, retention_days: i64) -> Result<usize>;
+    /// Delete what the policy no longer keeps; a dry run only counts it
+    async fn cleanup_old_data(&self, policy: &RetentionPolicy, dry_run: bool) -> Result<CleanupReport>;
     async fn health_check(&self) -> Result<StorageHealth>;
+
+    /// Query timings, for storage that records them
+    fn query_metrics(&self) -> Option<Arc<QueryMetrics>> {
+        None
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -57,6 +224,10 @@ pub struct StorageConfig {
     pub max_connections: u32,
     pub connection_timeout_secs: u64,
     pub enable_wal_mode: bool,
+    /// Operations slower than this are logged as warnings
+    pub slow_query_threshold_ms: u64,
+    /// How many of the slowest queries are kept for inspection
+    pub slow_query_log_size: usize,
 }
 
 impl Default for StorageConfig {
@@ -66,12 +237,15 @@ impl Default for StorageConfig {
             max_connections: 10,
             connection_timeout_secs: 30,
             enable_wal_mode: true,
+            slow_query_threshold_ms: 100,
+            slow_query_log_size: 50,
         }
     }
 }
 
 pub struct SqliteStorage {
     pool: SqlitePool,
+    metrics: Arc<QueryMetrics>,
 }
 
 impl SqliteStorage {
@@ -93,7 +267,7 @@ impl SqliteStorage {
             .map_err(|e| AssistantError::Database(format!("Failed to connect to database: {}", e)))?;
 
         // Run migrations
-        sqlx::migrate!("./migrations")
+        sqlx::migrate!("../../migrations")
             .run(&pool)
             .await
             .map_err(|e| AssistantError::Database(format!("Failed to run migrations: {}", e)))?;
@@ -106,17 +280,325 @@ impl SqliteStorage {
                 .map_err(|e| AssistantError::Database(format!("Failed to enable WAL mode: {}", e)))?;
         }
 
+        let metrics = Arc::new(QueryMetrics::new(
+            std::time::Duration::from_millis(config.slow_query_threshold_ms),
+            config.slow_query_log_size,
+        ));
+
         info!("SQLite storage initialized successfully");
-        Ok(Self { pool })
+        Ok(Self { pool, metrics })
+    }
+
+    /// Briefings whose stored sections could not be read by any schema version
+    pub async fn quarantined_briefings(&self) -> Result<Vec<QuarantinedBriefing>> {
+        let mut timer = self.metrics.time("quarantined_briefings");
+        let rows = sqlx::query(
+            "SELECT id, schema_version, quarantine_reason FROM daily_briefings WHERE quarantined = 1 ORDER BY date DESC",
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| AssistantError::Database(format!("Failed to list quarantined briefings: {}", e)))?;
+        timer.rows(rows.len());
+        drop(timer);
+
+        rows.iter()
+            .map(|row| {
+                Ok(QuarantinedBriefing {
+                    id: row.try_get("id").map_err(row_error)?,
+                    schema_version: row.try_get("schema_version").map_err(row_error)?,
+                    reason: row.try_get::<Option<String>, _>("quarantine_reason").map_err(row_error)?.unwrap_or_default(),
+                })
+            })
+            .collect()
+    }
+
+    // Decodes a briefing row, rewriting it in the current schema version if
+    // it is older. Rows no version can read are quarantined and skipped
+    async fn read_briefing_row(&self, row: &SqliteRow) -> Result<Option<DailyBriefing>> {
+        let id: String = row.try_get("id").map_err(row_error)?;
+        let declared_version: i64 = row.try_get("schema_version").map_err(row_error)?;
+        let sections_json: String = row.try_get("sections").map_err(row_error)?;
+
+        let decoded = match decode_sections(declared_version, &sections_json) {
+            Ok(decoded) => decoded,
+            Err(reason) => {
+                warn!("Quarantining briefing {} (schema v{}): {}", id, declared_version, reason);
+                let _timer = self.metrics.time("quarantine_briefing").param(&id);
+                sqlx::query("UPDATE daily_briefings SET quarantined = 1, quarantine_reason = ? WHERE id = ?")
+                    .bind(&reason)
+                    .bind(&id)
+                    .execute(&self.pool)
+                    .await
+                    .map_err(|e| AssistantError::Database(format!("Failed to quarantine briefing: {}", e)))?;
+                return Ok(None);
+            }
+        };
+
+        if decoded.needs_upgrade(declared_version) {
+            // The briefing is still returned if the rewrite fails; it is
+            // retried on the next read
+            match self.upgrade_briefing_row(&id, &decoded.sections, decoded.generation_report.as_ref()).await {
+                Ok(()) => debug!("Upgraded briefing {} from schema v{} to v{}", id, decoded.version, CURRENT_BRIEFING_SCHEMA),
+                Err(e) => warn!("Failed to upgrade briefing {}: {}", id, e),
+            }
+        }
+
+        Ok(Some(DailyBriefing {
+            id: Uuid::parse_str(&id)
+                .map_err(|e| AssistantError::Internal(format!("Invalid UUID: {}", e)))?,
+            date: row.try_get("date").map_err(row_error)?,
+            sections: decoded.sections,
+            generated_at: row.try_get("generated_at").map_err(row_error)?,
+            generation_report: decoded.generation_report,
+        }))
+    }
+
+    // Deletes the table's rows matching the condition, or on a dry run
+    // counts them. The cutoff, if any, binds the condition's one placeholder
+    async fn expire(&self, table: &str, condition: &str, cutoff: Option<DateTime<Utc>>, dry_run: bool) -> Result<usize> {
+        let sql = if dry_run {
+            format!("SELECT COUNT(*) FROM {} WHERE {}", table, condition)
+        } else {
+            format!("DELETE FROM {} WHERE {}", table, condition)
+        };
+        let query = sqlx::query(&sql);
+        let query = match cutoff {
+            Some(cutoff) => query.bind(cutoff),
+            None => query,
+        };
+
+        let failed = |e: sqlx::Error| AssistantError::Database(format!("Failed to clean up {}: {}", table, e));
+        if dry_run {
+            let row = query.fetch_one(&self.pool).await.map_err(failed)?;
+            Ok(row.try_get::<i64, _>(0).map_err(row_error)? as usize)
+        } else {
+            Ok(query.execute(&self.pool).await.map_err(failed)?.rows_affected() as usize)
+        }
+    }
+
+    async fn upgrade_briefing_row(&self, id: &str, sections: &[BriefingSection], report: Option<&GenerationReport>) -> Result<()> {
+        let sections_json = encode_briefing(sections, report)
+            .map_err(|e| AssistantError::Internal(format!("Failed to serialize sections: {}", e)))?;
+
+        let _timer = self.metrics.time("upgrade_briefing").param(id);
+        sqlx::query("UPDATE daily_briefings SET sections = ?, schema_version = ? WHERE id = ?")
+            .bind(sections_json)
+            .bind(CURRENT_BRIEFING_SCHEMA)
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| AssistantError::Database(format!("Failed to upgrade briefing: {}", e)))?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct QuarantinedBriefing {
+    pub id: String,
+    pub schema_version: i64,
+    pub reason: String,
+}
+
+fn row_error(e: sqlx::Error) -> AssistantError {
+    AssistantError::Database(format!("Failed to read briefing row: {}", e))
+}
+
+fn assistant_action_from_row(row: &SqliteRow) -> Result<AssistantAction> {
+    let uuid = |column: &str| -> Result<Uuid> {
+        let value: String = row.try_get(column).map_err(row_error)?;
+        Uuid::parse_str(&value).map_err(|e| AssistantError::Internal(format!("Invalid UUID: {}", e)))
+    };
+    let kind: String = row.try_get("kind").map_err(row_error)?;
+    let status: String = row.try_get("status").map_err(row_error)?;
+    let trigger: String = row.try_get("trigger").map_err(row_error)?;
+    let undo: Option<String> = row.try_get("undo").map_err(row_error)?;
+
+    Ok(AssistantAction {
+        id: uuid("id")?,
+        user_id: uuid("user_id")?,
+        kind: ActionKind::parse(&kind)
+            .ok_or_else(|| AssistantError::Database(format!("Unknown action kind '{}'", kind)))?,
+        trigger: serde_json::from_str(&trigger)
+            .map_err(|e| AssistantError::Internal(format!("Failed to parse action trigger: {}", e)))?,
+        status: ActionStatus::parse(&status)
+            .ok_or_else(|| AssistantError::Database(format!("Unknown action status '{}'", status)))?,
+        description: row.try_get("description").map_err(row_error)?,
+        resource_id: row.try_get("resource_id").map_err(row_error)?,
+        // An undo step this version cannot read leaves the action in the feed,
+        // just not undoable
+        undo: undo.and_then(|u| serde_json::from_str(&u).ok()),
+        error: row.try_get("error").map_err(row_error)?,
+        created_at: row.try_get("created_at").map_err(row_error)?,
+        undone_at: row.try_get("undone_at").map_err(row_error)?,
+    })
+}
+
+fn conversation_turn_from_row(row: &SqliteRow) -> Result<ConversationTurn> {
+    let id: String = row.try_get("id").map_err(row_error)?;
+    let intent: String = row.try_get("intent").map_err(row_error)?;
+
+    Ok(ConversationTurn {
+        id: Uuid::parse_str(&id).map_err(|e| AssistantError::Internal(format!("Invalid UUID: {}", e)))?,
+        user_input: row.try_get("user_input").map_err(row_error)?,
+        assistant_response: row.try_get("assistant_response").map_err(row_error)?,
+        intent: serde_json::from_str(&intent)
+            .map_err(|e| AssistantError::Internal(format!("Failed to parse turn intent: {}", e)))?,
+        timestamp: row.try_get("timestamp").map_err(row_error)?,
+        kind: TurnKind::parse(&row.try_get::<String, _>("kind").map_err(row_error)?),
+    })
+}
+
+fn time_entry_from_row(row: &SqliteRow) -> Result<TimeEntry> {
+    let uuid = |column: &str| -> Result<Uuid> {
+        let value: String = row.try_get(column).map_err(row_error)?;
+        Uuid::parse_str(&value).map_err(|e| AssistantError::Internal(format!("Invalid UUID: {}", e)))
+    };
+
+    Ok(TimeEntry {
+        id: uuid("id")?,
+        task_id: uuid("task_id")?,
+        user_id: uuid("user_id")?,
+        started_at: row.try_get("started_at").map_err(row_error)?,
+        ended_at: row.try_get("ended_at").map_err(row_error)?,
+        note: row.try_get("note").map_err(row_error)?,
+    })
+}
+
+fn audit_entry_from_row(row: &SqliteRow) -> Result<AuditEntry> {
+    let id: String = row.try_get("id").map_err(row_error)?;
+    let state = |column: &str| -> Result<Option<serde_json::Value>> {
+        let value: Option<String> = row.try_get(column).map_err(row_error)?;
+        Ok(value.and_then(|v| serde_json::from_str(&v).ok()))
+    };
+
+    Ok(AuditEntry {
+        id: Uuid::parse_str(&id)
+            .map_err(|e| AssistantError::Internal(format!("Invalid UUID: {}", e)))?,
+        actor: row.try_get("actor").map_err(row_error)?,
+        action: row.try_get("action").map_err(row_error)?,
+        target: row.try_get("target").map_err(row_error)?,
+        before: state("before_state")?,
+        after: state("after_state")?,
+        request_id: row.try_get("request_id").map_err(row_error)?,
+        complete: row.try_get("complete").map_err(row_error)?,
+        created_at: row.try_get("created_at").map_err(row_error)?,
+    })
+}
+
+fn plugin_audit_from_row(row: &SqliteRow) -> Result<PluginAuditRecord> {
+    let id: String = row.try_get("id").map_err(row_error)?;
+    let count = |column: &str| -> Result<u64> { Ok(row.try_get::<i64, _>(column).map_err(row_error)?.max(0) as u64) };
+    let fuel_consumed: Option<i64> = row.try_get("fuel_consumed").map_err(row_error)?;
+
+    Ok(PluginAuditRecord {
+        id: Uuid::parse_str(&id)
+            .map_err(|e| AssistantError::Internal(format!("Invalid UUID: {}", e)))?,
+        plugin_id: row.try_get("plugin_id").map_err(row_error)?,
+        function: row.try_get("function").map_err(row_error)?,
+        user_id: row.try_get("user_id").map_err(row_error)?,
+        request_id: row.try_get("request_id").map_err(row_error)?,
+        input_size: count("input_size")?,
+        output_size: count("output_size")?,
+        duration_ms: count("duration_ms")?,
+        success: row.try_get("success").map_err(row_error)?,
+        error: row.try_get("error").map_err(row_error)?,
+        fuel_consumed: fuel_consumed.map(|fuel| fuel.max(0) as u64),
+        created_at: row.try_get("created_at").map_err(row_error)?,
+    })
+}
+
+fn plugin_schedule_from_row(row: &SqliteRow) -> Result<PluginSchedule> {
+    let id: String = row.try_get("id").map_err(row_error)?;
+    let input: String = row.try_get("input").map_err(row_error)?;
+    let catch_up: String = row.try_get("catch_up").map_err(row_error)?;
+
+    Ok(PluginSchedule {
+        id: Uuid::parse_str(&id)
+            .map_err(|e| AssistantError::Internal(format!("Invalid UUID: {}", e)))?,
+        plugin_id: row.try_get("plugin_id").map_err(row_error)?,
+        function: row.try_get("function").map_err(row_error)?,
+        input: serde_json::from_str(&input)
+            .map_err(|e| AssistantError::Internal(format!("Failed to parse plugin schedule input: {}", e)))?,
+        cron: row.try_get("cron").map_err(row_error)?,
+        catch_up: CatchUpPolicy::parse(&catch_up)
+            .ok_or_else(|| AssistantError::Database(format!("Unknown catch-up policy '{}'", catch_up)))?,
+        next_run_at: row.try_get("next_run_at").map_err(row_error)?,
+        last_run_at: row.try_get("last_run_at").map_err(row_error)?,
+        created_at: row.try_get("created_at").map_err(row_error)?,
+    })
+}
+
+fn plugin_config_from_row(row: &SqliteRow) -> Result<PluginConfig> {
+    let settings: String = row.try_get("settings").map_err(row_error)?;
+    let quota: String = row.try_get("quota").map_err(row_error)?;
+
+    Ok(PluginConfig {
+        enabled: row.try_get("enabled").map_err(row_error)?,
+        priority: row.try_get("priority").map_err(row_error)?,
+        settings: serde_json::from_str(&settings)
+            .map_err(|e| AssistantError::Internal(format!("Failed to parse plugin settings: {}", e)))?,
+        quota: serde_json::from_str(&quota)
+            .map_err(|e| AssistantError::Internal(format!("Failed to parse plugin quota: {}", e)))?,
+    })
+}
+
+// Table and JSON projection of each synced entity. Documents are sent
+// without their content
+fn sync_projection(entity: SyncEntity) -> (&'static str, &'static str) {
+    match entity {
+        SyncEntity::Sessions => (
+            "conversations",
+            "json_object('id', id, 'title', title, 'message_count', message_count, 'last_message_at', last_message_at, \
+             'is_archived', is_archived, 'tags', json(COALESCE(tags, '[]')), 'created_at', created_at, 'updated_at', updated_at)",
+        ),
+        SyncEntity::Messages => (
+            "messages",
+            "json_object('id', id, 'session_id', conversation_id, 'role', role, 'content', content, \
+             'content_type', content_type, 'created_at', created_at, 'updated_at', updated_at)",
+        ),
+        SyncEntity::Tasks => (
+            "tasks",
+            "json_object('id', id, 'title', title, 'description', description, 'status', status, 'priority', priority, \
+             'category', category, 'due_date', due_date, 'tags', json(COALESCE(tags, '[]')), 'created_at', created_at, \
+             'updated_at', updated_at, 'completed_at', completed_at)",
+        ),
+        SyncEntity::Notifications => (
+            "notifications",
+            "json_object('id', id, 'notification_type', notification_type, 'title', title, 'message', message, \
+             'action_url', action_url, 'priority', priority, 'is_read', is_read, 'is_dismissed', is_dismissed, \
+             'expires_at', expires_at, 'created_at', created_at, 'read_at', read_at)",
+        ),
+        SyncEntity::Documents => (
+            "documents",
+            "json_object('id', id, 'title', title, 'content_type', content_type, 'file_size', file_size, \
+             'tags', json(COALESCE(tags, '[]')), 'created_at', created_at, 'updated_at', updated_at)",
+        ),
     }
 }
 
+fn document_from_row(row: &SqliteRow) -> Result<Document> {
+    let id: String = row.try_get("id").map_err(row_error)?;
+    let metadata: String = row.try_get("metadata").map_err(row_error)?;
+
+    Ok(Document {
+        id: Uuid::parse_str(&id)
+            .map_err(|e| AssistantError::Internal(format!("Invalid UUID: {}", e)))?,
+        title: row.try_get("title").map_err(row_error)?,
+        content: row.try_get("content").map_err(row_error)?,
+        metadata: serde_json::from_str(&metadata)
+            .map_err(|e| AssistantError::Internal(format!("Failed to deserialize metadata: {}", e)))?,
+        created_at: row.try_get("created_at").map_err(row_error)?,
+        updated_at: row.try_get("updated_at").map_err(row_error)?,
+    })
+}
+
 #[async_trait]
 impl Storage for SqliteStorage {
     async fn store_document(&self, document: &Document) -> Result<()> {
         let metadata_json = serde_json::to_string(&document.metadata)
             .map_err(|e| AssistantError::Internal(format!("Failed to serialize metadata: {}", e)))?;
 
+        let _timer = self.metrics.time("store_document").param(document.id);
         sqlx::query!(
             r#"
             INSERT INTO documents (id, title, content, metadata, created_at, updated_at)
@@ -138,6 +620,7 @@ impl Storage for SqliteStorage {
     }
 
     async fn get_document(&self, id: Uuid) -> Result<Option<Document>> {
+        let mut timer = self.metrics.time("get_document").param(id);
         let row = sqlx::query!(
             "SELECT * FROM documents WHERE id = ?",
             id.to_string()
@@ -145,6 +628,8 @@ impl Storage for SqliteStorage {
         .fetch_optional(&self.pool)
         .await
         .map_err(|e| AssistantError::Database(format!("Failed to get document: {}", e)))?;
+        timer.rows(row.iter().count());
+        drop(timer);
 
         match row {
             Some(row) => {
@@ -169,6 +654,7 @@ impl Storage for SqliteStorage {
         let metadata_json = serde_json::to_string(&document.metadata)
             .map_err(|e| AssistantError::Internal(format!("Failed to serialize metadata: {}", e)))?;
 
+        let mut timer = self.metrics.time("update_document").param(document.id);
         let result = sqlx::query!(
             r#"
             UPDATE documents 
@@ -184,6 +670,8 @@ impl Storage for SqliteStorage {
         .execute(&self.pool)
         .await
         .map_err(|e| AssistantError::Database(format!("Failed to update document: {}", e)))?;
+        timer.rows(result.rows_affected() as usize);
+        drop(timer);
 
         if result.rows_affected() == 0 {
             return Err(AssistantError::NotFound(format!("Document not found: {}", document.id)));
@@ -194,6 +682,7 @@ impl Storage for SqliteStorage {
     }
 
     async fn delete_document(&self, id: Uuid) -> Result<()> {
+        let mut timer = self.metrics.time("delete_document").param(id);
         let result = sqlx::query!(
             "DELETE FROM documents WHERE id = ?",
             id.to_string()
@@ -201,6 +690,8 @@ impl Storage for SqliteStorage {
         .execute(&self.pool)
         .await
         .map_err(|e| AssistantError::Database(format!("Failed to delete document: {}", e)))?;
+        timer.rows(result.rows_affected() as usize);
+        drop(timer);
 
         if result.rows_affected() == 0 {
             return Err(AssistantError::NotFound(format!("Document not found: {}", id)));
@@ -211,6 +702,7 @@ impl Storage for SqliteStorage {
     }
 
     async fn search_documents(&self, query: &str, limit: usize) -> Result<Vec<Document>> {
+        let mut timer = self.metrics.time("search_documents").param(query).param(limit);
         let rows = sqlx::query!(
             r#"
             SELECT * FROM documents 
@@ -225,6 +717,8 @@ impl Storage for SqliteStorage {
         .fetch_all(&self.pool)
         .await
         .map_err(|e| AssistantError::Database(format!("Failed to search documents: {}", e)))?;
+        timer.rows(rows.len());
+        drop(timer);
 
         let mut documents = Vec::new();
         for row in rows {
@@ -263,10 +757,13 @@ impl Storage for SqliteStorage {
         }
         query = query.bind(limit as i32);
 
+        let mut timer = tags.iter().fold(self.metrics.time("get_documents_by_tags"), |timer, tag| timer.param(tag));
         let rows = query
             .fetch_all(&self.pool)
             .await
             .map_err(|e| AssistantError::Database(format!("Failed to get documents by tags: {}", e)))?;
+        timer.rows(rows.len());
+        drop(timer);
 
         let mut documents = Vec::new();
         for row in rows {
@@ -298,6 +795,7 @@ impl Storage for SqliteStorage {
         let tags_json = serde_json::to_string(&task.tags)
             .map_err(|e| AssistantError::Internal(format!("Failed to serialize tags: {}", e)))?;
 
+        let _timer = self.metrics.time("store_task").param(task.id);
         sqlx::query!(
             r#"
             INSERT INTO tasks (id, name, description, status, priority, due_date, tags, created_at, updated_at)
@@ -322,6 +820,7 @@ impl Storage for SqliteStorage {
     }
 
     async fn get_task(&self, id: Uuid) -> Result<Option<Task>> {
+        let mut timer = self.metrics.time("get_task").param(id);
         let row = sqlx::query!(
             "SELECT * FROM tasks WHERE id = ?",
             id.to_string()
@@ -329,6 +828,8 @@ impl Storage for SqliteStorage {
         .fetch_optional(&self.pool)
         .await
         .map_err(|e| AssistantError::Database(format!("Failed to get task: {}", e)))?;
+        timer.rows(row.iter().count());
+        drop(timer);
 
         match row {
             Some(row) => {
@@ -355,6 +856,7 @@ impl Storage for SqliteStorage {
     }
 
     async fn update_task_status(&self, id: Uuid, status: TaskStatus) -> Result<()> {
+        let mut timer = self.metrics.time("update_task_status").param(id).param(&status);
         let result = sqlx::query!(
             "UPDATE tasks SET status = ?, updated_at = ? WHERE id = ?",
             status.to_string(),
@@ -364,6 +866,8 @@ impl Storage for SqliteStorage {
         .execute(&self.pool)
         .await
         .map_err(|e| AssistantError::Database(format!("Failed to update task status: {}", e)))?;
+        timer.rows(result.rows_affected() as usize);
+        drop(timer);
 
         if result.rows_affected() == 0 {
             return Err(AssistantError::NotFound(format!("Task not found: {}", id)));
@@ -377,7 +881,26 @@ impl Storage for SqliteStorage {
         self.get_tasks_by_status(TaskStatus::Pending).await
     }
 
+    async fn delete_task(&self, id: Uuid) -> Result<()> {
+        let mut timer = self.metrics.time("delete_task").param(id);
+        let result = sqlx::query("DELETE FROM tasks WHERE id = ?")
+            .bind(id.to_string())
+            .execute(&self.pool)
+            .await
+            .map_err(|e| AssistantError::Database(format!("Failed to delete task: {}", e)))?;
+        timer.rows(result.rows_affected() as usize);
+        drop(timer);
+
+        if result.rows_affected() == 0 {
+            return Err(AssistantError::NotFound(format!("Task not found: {}", id)));
+        }
+
+        debug!("Deleted task: {}", id);
+        Ok(())
+    }
+
     async fn get_tasks_by_status(&self, status: TaskStatus) -> Result<Vec<Task>> {
+        let mut timer = self.metrics.time("get_tasks_by_status").param(&status);
         let rows = sqlx::query!(
             "SELECT * FROM tasks WHERE status = ? ORDER BY created_at ASC",
             status.to_string()
@@ -385,6 +908,8 @@ impl Storage for SqliteStorage {
         .fetch_all(&self.pool)
         .await
         .map_err(|e| AssistantError::Database(format!("Failed to get tasks by status: {}", e)))?;
+        timer.rows(rows.len());
+        drop(timer);
 
         let mut tasks = Vec::new();
         for row in rows {
@@ -411,19 +936,21 @@ impl Storage for SqliteStorage {
     }
 
     async fn store_briefing(&self, briefing: &DailyBriefing) -> Result<()> {
-        let sections_json = serde_json::to_string(&briefing.sections)
+        let sections_json = encode_briefing(&briefing.sections, briefing.generation_report.as_ref())
             .map_err(|e| AssistantError::Internal(format!("Failed to serialize sections: {}", e)))?;
 
-        sqlx::query!(
+        let _timer = self.metrics.time("store_briefing").param(briefing.id);
+        sqlx::query(
             r#"
-            INSERT INTO daily_briefings (id, date, sections, generated_at)
-            VALUES (?, ?, ?, ?)
+            INSERT INTO daily_briefings (id, date, sections, generated_at, schema_version)
+            VALUES (?, ?, ?, ?, ?)
             "#,
-            briefing.id.to_string(),
-            briefing.date,
-            sections_json,
-            briefing.generated_at
         )
+        .bind(briefing.id.to_string())
+        .bind(briefing.date)
+        .bind(sections_json)
+        .bind(briefing.generated_at)
+        .bind(CURRENT_BRIEFING_SCHEMA)
         .execute(&self.pool)
         .await
         .map_err(|e| AssistantError::Database(format!("Failed to store briefing: {}", e)))?;
@@ -433,185 +960,994 @@ impl Storage for SqliteStorage {
     }
 
     async fn get_briefing(&self, id: Uuid) -> Result<Option<DailyBriefing>> {
-        let row = sqlx::query!(
-            "SELECT * FROM daily_briefings WHERE id = ?",
-            id.to_string()
-        )
-        .fetch_optional(&self.pool)
-        .await
-        .map_err(|e| AssistantError::Database(format!("Failed to get briefing: {}", e)))?;
+        let mut timer = self.metrics.time("get_briefing").param(id);
+        let row = sqlx::query("SELECT * FROM daily_briefings WHERE id = ? AND quarantined = 0")
+            .bind(id.to_string())
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| AssistantError::Database(format!("Failed to get briefing: {}", e)))?;
+        timer.rows(row.iter().count());
+        drop(timer);
 
         match row {
-            Some(row) => {
-                let sections = serde_json::from_str(&row.sections)
-                    .map_err(|e| AssistantError::Internal(format!("Failed to deserialize sections: {}", e)))?;
-
-                Ok(Some(DailyBriefing {
-                    id: Uuid::parse_str(&row.id)
-                        .map_err(|e| AssistantError::Internal(format!("Invalid UUID: {}", e)))?,
-                    date: row.date,
-                    sections,
-                    generated_at: row.generated_at,
-                }))
-            }
+            Some(row) => self.read_briefing_row(&row).await,
             None => Ok(None),
         }
     }
 
     async fn get_latest_briefing(&self) -> Result<Option<DailyBriefing>> {
-        let row = sqlx::query!(
-            "SELECT * FROM daily_briefings ORDER BY date DESC LIMIT 1"
-        )
-        .fetch_optional(&self.pool)
-        .await
-        .map_err(|e| AssistantError::Database(format!("Failed to get latest briefing: {}", e)))?;
-
-        match row {
-            Some(row) => {
-                let sections = serde_json::from_str(&row.sections)
-                    .map_err(|e| AssistantError::Internal(format!("Failed to deserialize sections: {}", e)))?;
+        // A row that turns out to be corrupted is quarantined by the read, so
+        // the next query returns the one before it
+        loop {
+            let mut timer = self.metrics.time("get_latest_briefing");
+            let row = sqlx::query("SELECT * FROM daily_briefings WHERE quarantined = 0 ORDER BY date DESC LIMIT 1")
+                .fetch_optional(&self.pool)
+                .await
+                .map_err(|e| AssistantError::Database(format!("Failed to get latest briefing: {}", e)))?;
+            timer.rows(row.iter().count());
+            drop(timer);
 
-                Ok(Some(DailyBriefing {
-                    id: Uuid::parse_str(&row.id)
-                        .map_err(|e| AssistantError::Internal(format!("Invalid UUID: {}", e)))?,
-                    date: row.date,
-                    sections,
-                    generated_at: row.generated_at,
-                }))
+            let Some(row) = row else { return Ok(None) };
+            if let Some(briefing) = self.read_briefing_row(&row).await? {
+                return Ok(Some(briefing));
             }
-            None => Ok(None),
         }
     }
 
     async fn get_briefings_by_date_range(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Result<Vec<DailyBriefing>> {
-        let rows = sqlx::query!(
-            "SELECT * FROM daily_briefings WHERE date BETWEEN ? AND ? ORDER BY date DESC",
-            start,
-            end
+        let mut timer = self.metrics.time("get_briefings_by_date_range").param(start).param(end);
+        let rows = sqlx::query(
+            "SELECT * FROM daily_briefings WHERE date BETWEEN ? AND ? AND quarantined = 0 ORDER BY date DESC",
         )
+        .bind(start)
+        .bind(end)
         .fetch_all(&self.pool)
         .await
         .map_err(|e| AssistantError::Database(format!("Failed to get briefings by date range: {}", e)))?;
+        timer.rows(rows.len());
+        drop(timer);
 
         let mut briefings = Vec::new();
         for row in rows {
-            let sections = serde_json::from_str(&row.sections)
-                .map_err(|e| AssistantError::Internal(format!("Failed to deserialize sections: {}", e)))?;
-
-            briefings.push(DailyBriefing {
-                id: Uuid::parse_str(&row.id)
-                    .map_err(|e| AssistantError::Internal(format!("Invalid UUID: {}", e)))?,
-                date: row.date,
-                sections,
-                generated_at: row.generated_at,
-            });
+            if let Some(briefing) = self.read_briefing_row(&row).await? {
+                briefings.push(briefing);
+            }
         }
 
         Ok(briefings)
     }
 
-    async fn cleanup_old_data(&self, retention_days: i64) -> Result<usize> {
-        let cutoff = Utc::now() - chrono::Duration::days(retention_days);
-        
-        let documents_result = sqlx::query!(
-            "DELETE FROM documents WHERE updated_at < ?",
-            cutoff
+    async fn get_documents_created_between(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Result<Vec<Document>> {
+        let mut timer = self.metrics.time("get_documents_created_between").param(start).param(end);
+        let rows = sqlx::query("SELECT * FROM documents WHERE created_at >= ? AND created_at < ? ORDER BY created_at ASC")
+            .bind(start)
+            .bind(end)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| AssistantError::Database(format!("Failed to list new documents: {}", e)))?;
+        timer.rows(rows.len());
+        drop(timer);
+
+        rows.iter().map(document_from_row).collect()
+    }
+
+    async fn record_document_access(&self, id: Uuid) -> Result<()> {
+        let _timer = self.metrics.time("record_document_access").param(id);
+        sqlx::query("INSERT INTO document_access (document_id, accessed_at) VALUES (?, ?)")
+            .bind(id.to_string())
+            .bind(Utc::now())
+            .execute(&self.pool)
+            .await
+            .map_err(|e| AssistantError::Database(format!("Failed to record document access: {}", e)))?;
+        Ok(())
+    }
+
+    async fn get_document_access_counts(&self, start: DateTime<Utc>, end: DateTime<Utc>, limit: usize) -> Result<Vec<(Uuid, u64)>> {
+        let mut timer = self.metrics.time("get_document_access_counts").param(start).param(end).param(limit);
+        let rows = sqlx::query(
+            r#"
+            SELECT document_id, COUNT(*) AS accesses FROM document_access
+            WHERE accessed_at >= ? AND accessed_at < ?
+            GROUP BY document_id
+            ORDER BY accesses DESC, document_id ASC
+            LIMIT ?
+            "#,
         )
-        .execute(&self.pool)
+        .bind(start)
+        .bind(end)
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
         .await
-        .map_err(|e| AssistantError::Database(format!("Failed to cleanup old documents: {}", e)))?;
+        .map_err(|e| AssistantError::Database(format!("Failed to count document accesses: {}", e)))?;
+        timer.rows(rows.len());
+        drop(timer);
+
+        let mut counts = Vec::with_capacity(rows.len());
+        for row in rows {
+            let id: String = row.try_get("document_id").map_err(row_error)?;
+            let accesses: i64 = row.try_get("accesses").map_err(row_error)?;
+            // Ids written by something other than this storage are skipped
+            if let Ok(id) = Uuid::parse_str(&id) {
+                counts.push((id, accesses as u64));
+            }
+        }
+        Ok(counts)
+    }
 
-        let tasks_result = sqlx::query!(
-            "DELETE FROM tasks WHERE updated_at < ? AND status IN ('Completed', 'Cancelled', 'Failed')",
-            cutoff
+    async fn store_knowledge_digest(&self, digest: &KnowledgeDigest) -> Result<()> {
+        let content = serde_json::to_string(digest)
+            .map_err(|e| AssistantError::Internal(format!("Failed to serialize digest: {}", e)))?;
+
+        let _timer = self.metrics.time("store_knowledge_digest").param(digest.id);
+        sqlx::query(
+            r#"
+            INSERT INTO knowledge_digests (id, user_id, period_start, period_end, generated_at, content)
+            VALUES (?, ?, ?, ?, ?, ?)
+            "#,
         )
+        .bind(digest.id.to_string())
+        .bind(digest.user_id.to_string())
+        .bind(digest.period_start)
+        .bind(digest.period_end)
+        .bind(digest.generated_at)
+        .bind(content)
         .execute(&self.pool)
         .await
-        .map_err(|e| AssistantError::Database(format!("Failed to cleanup old tasks: {}", e)))?;
+        .map_err(|e| AssistantError::Database(format!("Failed to store digest: {}", e)))?;
+
+        debug!("Stored knowledge digest {}", digest.id);
+        Ok(())
+    }
+
+    async fn get_knowledge_digests(&self, user_id: Uuid, limit: usize) -> Result<Vec<KnowledgeDigest>> {
+        let mut timer = self.metrics.time("get_knowledge_digests").param(user_id).param(limit);
+        let rows = sqlx::query("SELECT content FROM knowledge_digests WHERE user_id = ? ORDER BY period_end DESC LIMIT ?")
+            .bind(user_id.to_string())
+            .bind(limit as i64)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| AssistantError::Database(format!("Failed to get digests: {}", e)))?;
+        timer.rows(rows.len());
+        drop(timer);
+
+        let mut digests = Vec::with_capacity(rows.len());
+        for row in rows {
+            let content: String = row.try_get("content").map_err(row_error)?;
+            match serde_json::from_str(&content) {
+                Ok(digest) => digests.push(digest),
+                Err(e) => warn!("Skipping unreadable knowledge digest: {}", e),
+            }
+        }
+        Ok(digests)
+    }
 
-        let briefings_result = sqlx::query!(
-            "DELETE FROM daily_briefings WHERE date < ?",
-            cutoff
+    async fn store_audit_entry(&self, entry: &AuditEntry) -> Result<()> {
+        let _timer = self.metrics.time("store_audit_entry").param(&entry.action);
+        sqlx::query(
+            r#"
+            INSERT INTO admin_audit (id, actor, action, target, before_state, after_state, request_id, complete, created_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
         )
+        .bind(entry.id.to_string())
+        .bind(&entry.actor)
+        .bind(&entry.action)
+        .bind(&entry.target)
+        .bind(entry.before.as_ref().map(|v| v.to_string()))
+        .bind(entry.after.as_ref().map(|v| v.to_string()))
+        .bind(&entry.request_id)
+        .bind(entry.complete)
+        .bind(entry.created_at)
         .execute(&self.pool)
         .await
-        .map_err(|e| AssistantError::Database(format!("Failed to cleanup old briefings: {}", e)))?;
+        .map_err(|e| AssistantError::Database(format!("Failed to store audit entry: {}", e)))?;
+        Ok(())
+    }
 
-        let total_deleted = documents_result.rows_affected() + 
-                           tasks_result.rows_affected() + 
-                           briefings_result.rows_affected();
+    async fn get_audit_entries(&self, filter: &AuditFilter) -> Result<Vec<AuditEntry>> {
+        let limit = filter.limit.unwrap_or(100).min(1000);
+        let mut timer = self.metrics.time("get_audit_entries").param(limit);
+        let rows = sqlx::query(
+            r#"
+            SELECT * FROM admin_audit
+            WHERE (?1 IS NULL OR actor = ?1)
+              AND (?2 IS NULL OR action = ?2)
+              AND (?3 IS NULL OR created_at >= ?3)
+              AND (?4 IS NULL OR created_at < ?4)
+            ORDER BY created_at DESC
+            LIMIT ?5
+            "#,
+        )
+        .bind(&filter.actor)
+        .bind(&filter.action)
+        .bind(filter.from)
+        .bind(filter.to)
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| AssistantError::Database(format!("Failed to get audit entries: {}", e)))?;
+        timer.rows(rows.len());
+        drop(timer);
 
-        info!("Cleaned up {} old records", total_deleted);
-        Ok(total_deleted as usize)
+        rows.iter().map(audit_entry_from_row).collect()
     }
 
-    async fn health_check(&self) -> Result<StorageHealth> {
-        // Test database connectivity
-        let pool_status = sqlx::query("SELECT 1")
-            .fetch_one(&self.pool)
-            .await;
-
-        let status = match pool_status {
-            Ok(_) => StorageStatus::Healthy,
-            Err(_) => StorageStatus::Unhealthy,
-        };
+    async fn prune_audit_entries(&self, before: DateTime<Utc>) -> Result<usize> {
+        let mut timer = self.metrics.time("prune_audit_entries").param(before);
+        let result = sqlx::query("DELETE FROM admin_audit WHERE created_at < ?")
+            .bind(before)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| AssistantError::Database(format!("Failed to prune audit entries: {}", e)))?;
+        timer.rows(result.rows_affected() as usize);
+        drop(timer);
 
-        Ok(StorageHealth {
-            status,
-            connection_pool_size: Some(self.pool.size() as usize),
-            pending_migrations: None,
-            disk_usage_mb: None,
-            last_backup: None,
-        })
+        info!("Pruned {} audit entries older than {}", result.rows_affected(), before);
+        Ok(result.rows_affected() as usize)
     }
-}
 
-// Helper function to create storage instance
-pub async fn create_storage(config: &StorageConfig) -> Result<Arc<dyn Storage + Send + Sync>> {
-    let storage = SqliteStorage::new(config).await?;
-    Ok(Arc::new(storage))
-}
+    async fn get_changes_since(&self, user_id: Uuid, since: SyncCursor, limit: usize) -> Result<ChangeBatch> {
+        let limit = limit.clamp(1, MAX_SYNC_PAGE_SIZE);
+        let user_id = user_id.to_string();
 
-// Implement Display for enums to support string conversion
-impl std::fmt::Display for TaskStatus {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            TaskStatus::Pending => write!(f, "Pending"),
-            TaskStatus::InProgress => write!(f, "InProgress"),
-            TaskStatus::Completed => write!(f, "Completed"),
-            TaskStatus::Cancelled => write!(f, "Cancelled"),
-            TaskStatus::Failed => write!(f, "Failed"),
+        // A record changed several times since the cursor is listed once, at
+        // its latest change; one extra row tells whether more pages follow
+        let mut timer = self.metrics.time("get_changes_since").param(since.seq());
+        let rows = sqlx::query(
+            r#"
+            SELECT c.seq, c.entity, c.record_id, c.deleted
+            FROM sync_changes c
+            WHERE c.user_id = ?1 AND c.seq > ?2
+              AND c.seq = (SELECT MAX(l.seq) FROM sync_changes l WHERE l.entity = c.entity AND l.record_id = c.record_id)
+            ORDER BY c.seq
+            LIMIT ?3
+            "#,
+        )
+        .bind(&user_id)
+        .bind(since.seq())
+        .bind(limit as i64 + 1)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| AssistantError::Database(format!("Failed to read sync changes: {}", e)))?;
+        timer.rows(rows.len());
+        drop(timer);
+
+        let has_more = rows.len() > limit;
+        let mut latest = Vec::with_capacity(rows.len().min(limit));
+        for row in rows.iter().take(limit) {
+            let entity: String = row.try_get("entity").map_err(row_error)?;
+            let entity = SyncEntity::parse(&entity)
+                .ok_or_else(|| AssistantError::Database(format!("Unknown sync entity '{}'", entity)))?;
+            latest.push((
+                row.try_get::<i64, _>("seq").map_err(row_error)?,
+                entity,
+                row.try_get::<String, _>("record_id").map_err(row_error)?,
+                row.try_get::<bool, _>("deleted").map_err(row_error)?,
+            ));
         }
-    }
-}
 
-impl std::str::FromStr for TaskStatus {
-    type Err = String;
+        // Current state of the changed records, one query per entity
+        let mut records = std::collections::HashMap::new();
+        for entity in SyncEntity::ALL {
+            let ids: Vec<&str> = latest
+                .iter()
+                .filter(|(_, e, _, deleted)| *e == entity && !deleted)
+                .map(|(_, _, id, _)| id.as_str())
+                .collect();
+            if ids.is_empty() {
+                continue;
+            }
 
-    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
-        match s {
-            "Pending" => Ok(TaskStatus::Pending),
-            "InProgress" => Ok(TaskStatus::InProgress),
-            "Completed" => Ok(TaskStatus::Completed),
-            "Cancelled" => Ok(TaskStatus::Cancelled),
-            "Failed" => Ok(TaskStatus::Failed),
-            _ => Err(format!("Invalid task status: {}", s)),
-        }
-    }
-}
+            let (table, projection) = sync_projection(entity);
+            let sql = format!(
+                "SELECT id, {} AS record FROM {} WHERE user_id = ? AND id IN ({})",
+                projection,
+                table,
+                vec!["?"; ids.len()].join(", ")
+            );
+            let mut query = sqlx::query(&sql).bind(&user_id);
+            for id in &ids {
+                query = query.bind(*id);
+            }
 
-impl std::fmt::Display for rusty_ai_common::TaskPriority {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            rusty_ai_common::TaskPriority::Critical => write!(f, "Critical"),
-            rusty_ai_common::TaskPriority::High => write!(f, "High"),
-            rusty_ai_common::TaskPriority::Medium => write!(f, "Medium"),
-            rusty_ai_common::TaskPriority::Low => write!(f, "Low"),
+            let mut timer = self.metrics.time("get_sync_records").param(entity.as_str());
+            let rows = query
+                .fetch_all(&self.pool)
+                .await
+                .map_err(|e| AssistantError::Database(format!("Failed to read synced {}: {}", entity.as_str(), e)))?;
+            timer.rows(rows.len());
+            drop(timer);
+
+            for row in rows {
+                let id: String = row.try_get("id").map_err(row_error)?;
+                let record: String = row.try_get("record").map_err(row_error)?;
+                let record: serde_json::Value = serde_json::from_str(&record)
+                    .map_err(|e| AssistantError::Internal(format!("Invalid sync record: {}", e)))?;
+                records.insert((entity, id), record);
+            }
         }
+
+        let cursor = latest.last().map(|(seq, ..)| SyncCursor::new(*seq)).unwrap_or(since);
+        let changes = latest
+            .into_iter()
+            .filter_map(|(_, entity, id, deleted)| {
+                if deleted {
+                    return Some(RecordChange { entity, id, record: None });
+                }
+                // Deleted after the change was read; its tombstone follows
+                let record = records.remove(&(entity, id.clone()))?;
+                Some(RecordChange { entity, id, record: Some(record) })
+            })
+            .collect();
+
+        Ok(ChangeBatch { changes, cursor, has_more })
     }
-}
 
-impl std::str::FromStr for rusty_ai_common::TaskPriority {
+    async fn start_task_timer(&self, user_id: Uuid, task_id: Uuid, note: Option<String>) -> Result<TimerStart> {
+        let now = Utc::now();
+        let _timer = self.metrics.time("start_task_timer").param(task_id);
+        let mut tx = self.pool.begin().await
+            .map_err(|e| AssistantError::Database(format!("Failed to start timer: {}", e)))?;
+
+        let running = sqlx::query("SELECT * FROM task_time_entries WHERE user_id = ? AND ended_at IS NULL")
+            .bind(user_id.to_string())
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(|e| AssistantError::Database(format!("Failed to read running timer: {}", e)))?
+            .map(|row| time_entry_from_row(&row))
+            .transpose()?;
+
+        // Starting the timer that is already running keeps it going
+        if let Some(entry) = running.as_ref().filter(|entry| entry.task_id == task_id) {
+            return Ok(TimerStart { entry: entry.clone(), stopped: None });
+        }
+
+        let stopped = match running {
+            Some(mut entry) => {
+                sqlx::query("UPDATE task_time_entries SET ended_at = ? WHERE id = ?")
+                    .bind(now)
+                    .bind(entry.id.to_string())
+                    .execute(&mut *tx)
+                    .await
+                    .map_err(|e| AssistantError::Database(format!("Failed to stop running timer: {}", e)))?;
+                entry.ended_at = Some(now);
+                Some(entry)
+            }
+            None => None,
+        };
+
+        let entry = TimeEntry::start(user_id, task_id, note, now);
+        sqlx::query(
+            r#"
+            INSERT INTO task_time_entries (id, task_id, user_id, started_at, ended_at, note)
+            VALUES (?, ?, ?, ?, NULL, ?)
+            "#,
+        )
+        .bind(entry.id.to_string())
+        .bind(task_id.to_string())
+        .bind(user_id.to_string())
+        .bind(entry.started_at)
+        .bind(&entry.note)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| AssistantError::Database(format!("Failed to start timer: {}", e)))?;
+
+        tx.commit().await
+            .map_err(|e| AssistantError::Database(format!("Failed to start timer: {}", e)))?;
+
+        debug!("Started timer on task {} for user {}", task_id, user_id);
+        Ok(TimerStart { entry, stopped })
+    }
+
+    async fn stop_task_timer(&self, user_id: Uuid, task_id: Option<Uuid>, note: Option<String>) -> Result<Option<TimeEntry>> {
+        let now = Utc::now();
+        let mut timer = self.metrics.time("stop_task_timer").param(user_id);
+        let mut tx = self.pool.begin().await
+            .map_err(|e| AssistantError::Database(format!("Failed to stop timer: {}", e)))?;
+
+        let running = sqlx::query("SELECT * FROM task_time_entries WHERE user_id = ?1 AND ended_at IS NULL AND (?2 IS NULL OR task_id = ?2)")
+            .bind(user_id.to_string())
+            .bind(task_id.map(|id| id.to_string()))
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(|e| AssistantError::Database(format!("Failed to read running timer: {}", e)))?;
+        let Some(row) = running else {
+            return Ok(None);
+        };
+        let mut entry = time_entry_from_row(&row)?;
+
+        sqlx::query("UPDATE task_time_entries SET ended_at = ?, note = COALESCE(?, note) WHERE id = ?")
+            .bind(now)
+            .bind(&note)
+            .bind(entry.id.to_string())
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| AssistantError::Database(format!("Failed to stop timer: {}", e)))?;
+        tx.commit().await
+            .map_err(|e| AssistantError::Database(format!("Failed to stop timer: {}", e)))?;
+        timer.rows(1);
+        drop(timer);
+
+        entry.ended_at = Some(now);
+        entry.note = note.or(entry.note);
+        debug!("Stopped timer on task {} for user {}", entry.task_id, user_id);
+        Ok(Some(entry))
+    }
+
+    async fn get_task_time_entries(&self, task_id: Uuid) -> Result<Vec<TimeEntry>> {
+        let mut timer = self.metrics.time("get_task_time_entries").param(task_id);
+        let rows = sqlx::query("SELECT * FROM task_time_entries WHERE task_id = ? ORDER BY started_at")
+            .bind(task_id.to_string())
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| AssistantError::Database(format!("Failed to get time entries: {}", e)))?;
+        timer.rows(rows.len());
+        drop(timer);
+
+        rows.iter().map(time_entry_from_row).collect()
+    }
+
+    async fn get_time_entries_between(&self, user_id: Option<Uuid>, start: DateTime<Utc>, end: DateTime<Utc>) -> Result<Vec<TimeEntry>> {
+        let mut timer = self.metrics.time("get_time_entries_between").param(start);
+        let rows = sqlx::query(
+            r#"
+            SELECT * FROM task_time_entries
+            WHERE (?1 IS NULL OR user_id = ?1)
+              AND started_at < ?3
+              AND (ended_at IS NULL OR ended_at > ?2)
+            ORDER BY started_at
+            "#,
+        )
+        .bind(user_id.map(|id| id.to_string()))
+        .bind(start)
+        .bind(end)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| AssistantError::Database(format!("Failed to get time entries: {}", e)))?;
+        timer.rows(rows.len());
+        drop(timer);
+
+        rows.iter().map(time_entry_from_row).collect()
+    }
+
+    async fn store_conversation_turn(&self, session_id: Uuid, turn: &ConversationTurn) -> Result<()> {
+        let intent_json = serde_json::to_string(&turn.intent)
+            .map_err(|e| AssistantError::Internal(format!("Failed to serialize turn intent: {}", e)))?;
+
+        let _timer = self.metrics.time("store_conversation_turn").param(session_id);
+        sqlx::query(
+            r#"
+            INSERT INTO conversation_turns (id, session_id, user_input, assistant_response, intent, timestamp, kind)
+            VALUES (?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(turn.id.to_string())
+        .bind(session_id.to_string())
+        .bind(&turn.user_input)
+        .bind(&turn.assistant_response)
+        .bind(intent_json)
+        .bind(turn.timestamp)
+        .bind(turn.kind.as_str())
+        .execute(&self.pool)
+        .await
+        .map_err(|e| AssistantError::Database(format!("Failed to store conversation turn: {}", e)))?;
+        Ok(())
+    }
+
+    async fn get_conversation_turns(&self, session_id: Uuid, offset: usize, limit: usize) -> Result<Vec<ConversationTurn>> {
+        let mut timer = self.metrics.time("get_conversation_turns").param(session_id);
+        let rows = sqlx::query(
+            "SELECT * FROM conversation_turns WHERE session_id = ? ORDER BY timestamp, rowid LIMIT ? OFFSET ?",
+        )
+        .bind(session_id.to_string())
+        .bind(limit as i64)
+        .bind(offset as i64)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| AssistantError::Database(format!("Failed to get conversation turns: {}", e)))?;
+        timer.rows(rows.len());
+        drop(timer);
+
+        rows.iter().map(conversation_turn_from_row).collect()
+    }
+
+    async fn count_conversation_turns(&self, session_id: Uuid) -> Result<usize> {
+        let _timer = self.metrics.time("count_conversation_turns").param(session_id);
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM conversation_turns WHERE session_id = ?")
+            .bind(session_id.to_string())
+            .fetch_one(&self.pool)
+            .await
+            .map_err(|e| AssistantError::Database(format!("Failed to count conversation turns: {}", e)))?;
+        Ok(count as usize)
+    }
+
+    async fn record_search_query(&self, user_id: Uuid, query: &str) -> Result<()> {
+        let _timer = self.metrics.time("record_search_query").param(user_id);
+        sqlx::query(
+            r#"
+            INSERT INTO search_history (user_id, normalized, query, count, last_searched_at)
+            VALUES (?, ?, ?, 1, ?)
+            ON CONFLICT(user_id, normalized) DO UPDATE SET
+                query = excluded.query,
+                count = count + 1,
+                last_searched_at = excluded.last_searched_at
+            "#,
+        )
+        .bind(user_id.to_string())
+        .bind(query.to_lowercase())
+        .bind(query)
+        .bind(Utc::now())
+        .execute(&self.pool)
+        .await
+        .map_err(|e| AssistantError::Database(format!("Failed to record search query: {}", e)))?;
+        Ok(())
+    }
+
+    async fn get_search_queries(&self, user_id: Uuid, limit: usize) -> Result<Vec<(String, u64)>> {
+        let mut timer = self.metrics.time("get_search_queries").param(user_id).param(limit);
+        let rows = sqlx::query(
+            "SELECT query, count FROM search_history WHERE user_id = ? ORDER BY count DESC, last_searched_at DESC LIMIT ?",
+        )
+        .bind(user_id.to_string())
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| AssistantError::Database(format!("Failed to get search queries: {}", e)))?;
+        timer.rows(rows.len());
+        drop(timer);
+
+        rows.iter()
+            .map(|row| {
+                let count: i64 = row.try_get("count").map_err(row_error)?;
+                Ok((row.try_get("query").map_err(row_error)?, count.max(0) as u64))
+            })
+            .collect()
+    }
+
+    async fn store_flag_override(&self, flag: &str, flag_override: Option<&FlagOverride>) -> Result<()> {
+        let _timer = self.metrics.time("store_flag_override").param(flag);
+        let Some(flag_override) = flag_override else {
+            sqlx::query("DELETE FROM feature_flags WHERE flag = ?")
+                .bind(flag)
+                .execute(&self.pool)
+                .await
+                .map_err(|e| AssistantError::Database(format!("Failed to remove flag override: {}", e)))?;
+            return Ok(());
+        };
+
+        let value = serde_json::to_string(flag_override)
+            .map_err(|e| AssistantError::Internal(format!("Failed to serialize flag override: {}", e)))?;
+        sqlx::query(
+            r#"
+            INSERT INTO feature_flags (flag, override, updated_at) VALUES (?, ?, ?)
+            ON CONFLICT(flag) DO UPDATE SET override = excluded.override, updated_at = excluded.updated_at
+            "#,
+        )
+        .bind(flag)
+        .bind(value)
+        .bind(Utc::now())
+        .execute(&self.pool)
+        .await
+        .map_err(|e| AssistantError::Database(format!("Failed to store flag override: {}", e)))?;
+        Ok(())
+    }
+
+    async fn get_flag_overrides(&self) -> Result<Vec<(String, FlagOverride)>> {
+        let mut timer = self.metrics.time("get_flag_overrides");
+        let rows = sqlx::query("SELECT flag, override FROM feature_flags")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| AssistantError::Database(format!("Failed to get flag overrides: {}", e)))?;
+        timer.rows(rows.len());
+        drop(timer);
+
+        rows.iter()
+            .map(|row| {
+                let flag: String = row.try_get("flag").map_err(row_error)?;
+                let value: String = row.try_get("override").map_err(row_error)?;
+                let flag_override = serde_json::from_str(&value)
+                    .map_err(|e| AssistantError::Internal(format!("Invalid override of flag {}: {}", flag, e)))?;
+                Ok((flag, flag_override))
+            })
+            .collect()
+    }
+
+    async fn get_plugin_value(&self, plugin_id: &str, key: &str) -> Result<Option<Vec<u8>>> {
+        let _timer = self.metrics.time("get_plugin_value").param(plugin_id);
+        sqlx::query_scalar("SELECT value FROM plugin_data WHERE plugin_id = ? AND key = ?")
+            .bind(plugin_id)
+            .bind(key)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| AssistantError::Database(format!("Failed to get plugin value: {}", e)))
+    }
+
+    async fn store_plugin_value(&self, plugin_id: &str, key: &str, value: Option<&[u8]>) -> Result<()> {
+        let _timer = self.metrics.time("store_plugin_value").param(plugin_id);
+        let Some(value) = value else {
+            sqlx::query("DELETE FROM plugin_data WHERE plugin_id = ? AND key = ?")
+                .bind(plugin_id)
+                .bind(key)
+                .execute(&self.pool)
+                .await
+                .map_err(|e| AssistantError::Database(format!("Failed to remove plugin value: {}", e)))?;
+            return Ok(());
+        };
+
+        sqlx::query(
+            r#"
+            INSERT INTO plugin_data (plugin_id, key, value, updated_at) VALUES (?, ?, ?, ?)
+            ON CONFLICT(plugin_id, key) DO UPDATE SET value = excluded.value, updated_at = excluded.updated_at
+            "#,
+        )
+        .bind(plugin_id)
+        .bind(key)
+        .bind(value)
+        .bind(Utc::now())
+        .execute(&self.pool)
+        .await
+        .map_err(|e| AssistantError::Database(format!("Failed to store plugin value: {}", e)))?;
+        Ok(())
+    }
+
+    async fn store_plugin_audit(&self, record: &PluginAuditRecord) -> Result<()> {
+        let _timer = self.metrics.time("store_plugin_audit").param(&record.plugin_id);
+        sqlx::query(
+            r#"
+            INSERT INTO plugin_audit (id, plugin_id, function, user_id, request_id, input_size, output_size,
+                                      duration_ms, success, error, fuel_consumed, created_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(record.id.to_string())
+        .bind(&record.plugin_id)
+        .bind(&record.function)
+        .bind(&record.user_id)
+        .bind(&record.request_id)
+        .bind(record.input_size as i64)
+        .bind(record.output_size as i64)
+        .bind(record.duration_ms as i64)
+        .bind(record.success)
+        .bind(&record.error)
+        .bind(record.fuel_consumed.map(|fuel| fuel as i64))
+        .bind(record.created_at)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| AssistantError::Database(format!("Failed to store plugin audit record: {}", e)))?;
+        Ok(())
+    }
+
+    async fn query_plugin_audits(&self, query: &PluginAuditQuery) -> Result<Vec<PluginAuditRecord>> {
+        let limit = query.limit.unwrap_or(100).min(1000);
+        let mut timer = self.metrics.time("query_plugin_audits").param(limit);
+        let rows = sqlx::query(
+            r#"
+            SELECT * FROM plugin_audit
+            WHERE (?1 IS NULL OR plugin_id = ?1)
+              AND (?2 IS NULL OR created_at >= ?2)
+              AND (?3 IS NULL OR created_at < ?3)
+            ORDER BY created_at DESC
+            LIMIT ?4
+            "#,
+        )
+        .bind(&query.plugin_id)
+        .bind(query.from)
+        .bind(query.to)
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| AssistantError::Database(format!("Failed to query plugin audit records: {}", e)))?;
+        timer.rows(rows.len());
+        drop(timer);
+
+        rows.iter().map(plugin_audit_from_row).collect()
+    }
+
+    async fn store_plugin_schedule(&self, schedule: &PluginSchedule) -> Result<()> {
+        let _timer = self.metrics.time("store_plugin_schedule").param(&schedule.plugin_id);
+        sqlx::query(
+            r#"
+            INSERT INTO plugin_schedules (id, plugin_id, function, input, cron, catch_up, next_run_at, last_run_at, created_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT(id) DO UPDATE SET
+                plugin_id = excluded.plugin_id, function = excluded.function, input = excluded.input,
+                cron = excluded.cron, catch_up = excluded.catch_up, next_run_at = excluded.next_run_at,
+                last_run_at = excluded.last_run_at
+            "#,
+        )
+        .bind(schedule.id.to_string())
+        .bind(&schedule.plugin_id)
+        .bind(&schedule.function)
+        .bind(schedule.input.to_string())
+        .bind(&schedule.cron)
+        .bind(schedule.catch_up.as_str())
+        .bind(schedule.next_run_at)
+        .bind(schedule.last_run_at)
+        .bind(schedule.created_at)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| AssistantError::Database(format!("Failed to store plugin schedule: {}", e)))?;
+        Ok(())
+    }
+
+    async fn list_plugin_schedules(&self) -> Result<Vec<PluginSchedule>> {
+        let mut timer = self.metrics.time("list_plugin_schedules");
+        let rows = sqlx::query("SELECT * FROM plugin_schedules ORDER BY created_at")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| AssistantError::Database(format!("Failed to list plugin schedules: {}", e)))?;
+        timer.rows(rows.len());
+        drop(timer);
+
+        rows.iter().map(plugin_schedule_from_row).collect()
+    }
+
+    async fn delete_plugin_schedule(&self, id: Uuid) -> Result<bool> {
+        let _timer = self.metrics.time("delete_plugin_schedule");
+        let result = sqlx::query("DELETE FROM plugin_schedules WHERE id = ?")
+            .bind(id.to_string())
+            .execute(&self.pool)
+            .await
+            .map_err(|e| AssistantError::Database(format!("Failed to delete plugin schedule: {}", e)))?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn store_plugin_config(&self, plugin_id: &str, config: &PluginConfig) -> Result<()> {
+        let _timer = self.metrics.time("store_plugin_config").param(plugin_id);
+        let settings = serde_json::to_string(&config.settings)
+            .map_err(|e| AssistantError::Internal(format!("Failed to serialize plugin settings: {}", e)))?;
+        let quota = serde_json::to_string(&config.quota)
+            .map_err(|e| AssistantError::Internal(format!("Failed to serialize plugin quota: {}", e)))?;
+        sqlx::query(
+            r#"
+            INSERT INTO plugin_configs (plugin_id, enabled, priority, settings, quota, updated_at)
+            VALUES (?, ?, ?, ?, ?, ?)
+            ON CONFLICT(plugin_id) DO UPDATE SET
+                enabled = excluded.enabled, priority = excluded.priority, settings = excluded.settings,
+                quota = excluded.quota, updated_at = excluded.updated_at
+            "#,
+        )
+        .bind(plugin_id)
+        .bind(config.enabled)
+        .bind(config.priority)
+        .bind(settings)
+        .bind(quota)
+        .bind(Utc::now())
+        .execute(&self.pool)
+        .await
+        .map_err(|e| AssistantError::Database(format!("Failed to store plugin config: {}", e)))?;
+        Ok(())
+    }
+
+    async fn get_plugin_config(&self, plugin_id: &str) -> Result<Option<PluginConfig>> {
+        let _timer = self.metrics.time("get_plugin_config").param(plugin_id);
+        let row = sqlx::query("SELECT * FROM plugin_configs WHERE plugin_id = ?")
+            .bind(plugin_id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| AssistantError::Database(format!("Failed to get plugin config: {}", e)))?;
+        row.as_ref().map(plugin_config_from_row).transpose()
+    }
+
+    async fn store_assistant_action(&self, action: &AssistantAction) -> Result<()> {
+        let _timer = self.metrics.time("store_assistant_action").param(action.kind.as_str());
+        let trigger = serde_json::to_string(&action.trigger)
+            .map_err(|e| AssistantError::Internal(format!("Failed to serialize action trigger: {}", e)))?;
+        let undo = action
+            .undo
+            .as_ref()
+            .map(serde_json::to_string)
+            .transpose()
+            .map_err(|e| AssistantError::Internal(format!("Failed to serialize undo step: {}", e)))?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO assistant_actions
+                (id, user_id, kind, source, session_id, trigger, status, description, resource_id, undo, error, created_at, undone_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(action.id.to_string())
+        .bind(action.user_id.to_string())
+        .bind(action.kind.as_str())
+        .bind(action.trigger.source())
+        .bind(action.trigger.session_id().map(|id| id.to_string()))
+        .bind(trigger)
+        .bind(action.status.as_str())
+        .bind(&action.description)
+        .bind(&action.resource_id)
+        .bind(undo)
+        .bind(&action.error)
+        .bind(action.created_at)
+        .bind(action.undone_at)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| AssistantError::Database(format!("Failed to store assistant action: {}", e)))?;
+        Ok(())
+    }
+
+    async fn get_assistant_actions(&self, user_id: Uuid, filter: &ActivityFilter) -> Result<Vec<AssistantAction>> {
+        let limit = filter.limit.unwrap_or(100).min(1000);
+        let mut timer = self.metrics.time("get_assistant_actions").param(user_id).param(limit);
+        let rows = sqlx::query(
+            r#"
+            SELECT * FROM assistant_actions
+            WHERE user_id = ?1
+              AND (?2 IS NULL OR kind = ?2)
+              AND (?3 IS NULL OR source = ?3)
+              AND (?4 IS NULL OR status = ?4)
+              AND (?5 IS NULL OR session_id = ?5)
+              AND (?6 IS NULL OR created_at >= ?6)
+              AND (?7 IS NULL OR created_at < ?7)
+            ORDER BY created_at DESC, rowid DESC
+            LIMIT ?8 OFFSET ?9
+            "#,
+        )
+        .bind(user_id.to_string())
+        .bind(filter.kind.map(|kind| kind.as_str()))
+        .bind(&filter.source)
+        .bind(filter.status.map(|status| status.as_str()))
+        .bind(filter.session_id.map(|id| id.to_string()))
+        .bind(filter.from)
+        .bind(filter.to)
+        .bind(limit as i64)
+        .bind(filter.offset.unwrap_or(0) as i64)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| AssistantError::Database(format!("Failed to get assistant actions: {}", e)))?;
+        timer.rows(rows.len());
+        drop(timer);
+
+        rows.iter().map(assistant_action_from_row).collect()
+    }
+
+    async fn get_assistant_action(&self, id: Uuid) -> Result<Option<AssistantAction>> {
+        let _timer = self.metrics.time("get_assistant_action").param(id);
+        let row = sqlx::query("SELECT * FROM assistant_actions WHERE id = ?")
+            .bind(id.to_string())
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| AssistantError::Database(format!("Failed to get assistant action: {}", e)))?;
+        row.as_ref().map(assistant_action_from_row).transpose()
+    }
+
+    async fn mark_assistant_action_undone(&self, id: Uuid, at: DateTime<Utc>) -> Result<()> {
+        let mut timer = self.metrics.time("mark_assistant_action_undone").param(id);
+        let result = sqlx::query("UPDATE assistant_actions SET status = 'undone', undone_at = ? WHERE id = ? AND status = 'succeeded'")
+            .bind(at)
+            .bind(id.to_string())
+            .execute(&self.pool)
+            .await
+            .map_err(|e| AssistantError::Database(format!("Failed to update assistant action: {}", e)))?;
+        timer.rows(result.rows_affected() as usize);
+        drop(timer);
+
+        if result.rows_affected() == 0 {
+            return Err(AssistantError::NotFound(format!("No undoable action {}", id)));
+        }
+        Ok(())
+    }
+
+    // This storage keeps no voice interactions or LLM traces, so only
+    // documents, tasks, briefings, notifications and plugin calls have
+    // limits to apply
+    async fn cleanup_old_data(&self, policy: &RetentionPolicy, dry_run: bool) -> Result<CleanupReport> {
+        let now = Utc::now();
+        let mut timer = self.metrics.time("cleanup_old_data").param(if dry_run { "dry run" } else { "delete" });
+        let mut report = CleanupReport::new(dry_run);
+
+        let limits = [
+            (RetentionEntity::Documents, "documents", "updated_at < ?"),
+            (
+                RetentionEntity::CompletedTasks,
+                "tasks",
+                "updated_at < ? AND LOWER(status) IN ('completed', 'cancelled', 'failed')",
+            ),
+            (RetentionEntity::Briefings, "daily_briefings", "date < ?"),
+            (RetentionEntity::Notifications, "notifications", "created_at < ?"),
+            (RetentionEntity::PluginAudits, "plugin_audit", "created_at < ?"),
+        ];
+        for (entity, table, condition) in limits {
+            if let Some(cutoff) = policy.cutoff(entity, now) {
+                report.record(table, self.expire(table, condition, Some(cutoff), dry_run).await?);
+            }
+        }
+
+        // Sync reads only the latest change per record, so older ones are
+        // dropped without affecting any client's cursor
+        let superseded = self
+            .expire(
+                "sync_changes",
+                "seq < (SELECT MAX(l.seq) FROM sync_changes l \
+                 WHERE l.entity = sync_changes.entity AND l.record_id = sync_changes.record_id)",
+                None,
+                dry_run,
+            )
+            .await?;
+        report.record("sync_changes", superseded);
+
+        timer.rows(report.total());
+        drop(timer);
+
+        if dry_run {
+            info!("Cleanup would delete {}", report.summary());
+        } else {
+            info!("Cleaned up {}", report.summary());
+        }
+        Ok(report)
+    }
+
+    async fn health_check(&self) -> Result<StorageHealth> {
+        // Test database connectivity
+        let pool_status = sqlx::query("SELECT 1")
+            .fetch_one(&self.pool)
+            .await;
+
+        let status = match pool_status {
+            Ok(_) => StorageStatus::Healthy,
+            Err(_) => StorageStatus::Unhealthy,
+        };
+
+        Ok(StorageHealth {
+            status,
+            connection_pool_size: Some(self.pool.size() as usize),
+            pending_migrations: None,
+            disk_usage_mb: None,
+            last_backup: None,
+        })
+    }
+
+    fn query_metrics(&self) -> Option<Arc<QueryMetrics>> {
+        Some(self.metrics.clone())
+    }
+}
+
+// Helper function to create storage instance
+pub async fn create_storage(config: &StorageConfig) -> Result<Arc<dyn Storage + Send + Sync>> {
+    let storage = SqliteStorage::new(config).await?;
+    Ok(Arc::new(storage))
+}
+
+// Implement Display for enums to support string conversion
+impl std::fmt::Display for TaskStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TaskStatus::Pending => write!(f, "Pending"),
+            TaskStatus::InProgress => write!(f, "InProgress"),
+            TaskStatus::Completed => write!(f, "Completed"),
+            TaskStatus::Cancelled => write!(f, "Cancelled"),
+            TaskStatus::Failed => write!(f, "Failed"),
+        }
+    }
+}
+
+impl std::str::FromStr for TaskStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "Pending" => Ok(TaskStatus::Pending),
+            "InProgress" => Ok(TaskStatus::InProgress),
+            "Completed" => Ok(TaskStatus::Completed),
+            "Cancelled" => Ok(TaskStatus::Cancelled),
+            "Failed" => Ok(TaskStatus::Failed),
+            _ => Err(format!("Invalid task status: {}", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for rusty_ai_common::TaskPriority {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            rusty_ai_common::TaskPriority::Critical => write!(f, "Critical"),
+            rusty_ai_common::TaskPriority::High => write!(f, "High"),
+            rusty_ai_common::TaskPriority::Medium => write!(f, "Medium"),
+            rusty_ai_common::TaskPriority::Low => write!(f, "Low"),
+        }
+    }
+}
+
+impl std::str::FromStr for rusty_ai_common::TaskPriority {
     type Err = String;
 
     fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
@@ -629,6 +1965,7 @@ impl std::str::FromStr for rusty_ai_common::TaskPriority {
 mod tests {
     use super::*;
     use rusty_ai_common::DocumentMetadata;
+    use sqlx::Executor;
 
     #[tokio::test]
     async fn test_storage_operations() {
@@ -661,4 +1998,547 @@ mod tests {
         assert!(retrieved.is_some());
         assert_eq!(retrieved.unwrap().title, doc.title);
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_plugin_values_are_scoped_by_plugin() {
+        let config = StorageConfig {
+            database_url: "sqlite::memory:".to_string(),
+            max_connections: 1,
+            ..Default::default()
+        };
+        let storage = SqliteStorage::new(&config).await.unwrap();
+
+        storage.store_plugin_value("notes", "note", Some(b"buy oat milk")).await.unwrap();
+        storage.store_plugin_value("notes", "note", Some(b"buy rye bread")).await.unwrap();
+        assert_eq!(storage.get_plugin_value("notes", "note").await.unwrap().as_deref(), Some(&b"buy rye bread"[..]));
+        assert_eq!(storage.get_plugin_value("weather", "note").await.unwrap(), None);
+
+        storage.store_plugin_value("notes", "note", None).await.unwrap();
+        assert_eq!(storage.get_plugin_value("notes", "note").await.unwrap(), None);
+    }
+
+    fn plugin_call(plugin_id: &str, created_at: DateTime<Utc>, error: Option<&str>) -> PluginAuditRecord {
+        PluginAuditRecord {
+            id: Uuid::new_v4(),
+            plugin_id: plugin_id.to_string(),
+            function: "run".to_string(),
+            user_id: "u1".to_string(),
+            request_id: "req-1".to_string(),
+            input_size: 12,
+            output_size: if error.is_some() { 0 } else { 40 },
+            duration_ms: 3,
+            success: error.is_none(),
+            error: error.map(str::to_string),
+            fuel_consumed: Some(1_500),
+            created_at,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_plugin_audits_filter_by_plugin_and_time() {
+        let storage = sync_storage().await;
+        let now = Utc::now();
+        let hours_ago = |hours: i64| now - chrono::Duration::hours(hours);
+
+        let failed = plugin_call("notes", hours_ago(1), Some("notes: out of fuel"));
+        for record in [plugin_call("notes", hours_ago(30), None), failed.clone(), plugin_call("weather", hours_ago(2), None)] {
+            storage.store_plugin_audit(&record).await.unwrap();
+        }
+
+        let all = storage.query_plugin_audits(&PluginAuditQuery::default()).await.unwrap();
+        assert_eq!(all.len(), 3);
+        assert_eq!(all[0], failed);
+
+        let recent_notes = storage
+            .query_plugin_audits(&PluginAuditQuery {
+                plugin_id: Some("notes".to_string()),
+                from: Some(hours_ago(24)),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(recent_notes, vec![failed]);
+
+        let before = PluginAuditQuery { to: Some(hours_ago(2)), ..Default::default() };
+        assert_eq!(storage.query_plugin_audits(&before).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_plugin_schedules_are_replaced_by_id_and_deleted() {
+        let storage = sync_storage().await;
+        let created_at = Utc::now();
+        let mut schedule = PluginSchedule {
+            id: Uuid::new_v4(),
+            plugin_id: "rss".to_string(),
+            function: "fetch".to_string(),
+            input: serde_json::json!({"feed": "https://example.com/feed.xml"}),
+            cron: "*/30 * * * *".to_string(),
+            catch_up: CatchUpPolicy::RunOnce,
+            next_run_at: created_at + chrono::Duration::minutes(30),
+            last_run_at: None,
+            created_at,
+        };
+        storage.store_plugin_schedule(&schedule).await.unwrap();
+
+        schedule.last_run_at = Some(schedule.next_run_at);
+        schedule.next_run_at += chrono::Duration::minutes(30);
+        storage.store_plugin_schedule(&schedule).await.unwrap();
+        assert_eq!(storage.list_plugin_schedules().await.unwrap(), vec![schedule.clone()]);
+
+        assert!(storage.delete_plugin_schedule(schedule.id).await.unwrap());
+        assert!(!storage.delete_plugin_schedule(schedule.id).await.unwrap());
+        assert!(storage.list_plugin_schedules().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_plugin_configs_are_replaced_by_plugin_name() {
+        let storage = sync_storage().await;
+        assert!(storage.get_plugin_config("rss").await.unwrap().is_none());
+
+        let mut config = PluginConfig {
+            enabled: true,
+            priority: 5,
+            settings: std::collections::HashMap::from([("feed".to_string(), serde_json::json!("https://example.com/feed.xml"))]),
+            quota: rusty_ai_common::PluginQuota { max_calls_per_hour: Some(60), max_concurrent: None },
+        };
+        storage.store_plugin_config("rss", &config).await.unwrap();
+        config.enabled = false;
+        config.settings.insert("interval".to_string(), serde_json::json!(30));
+        storage.store_plugin_config("rss", &config).await.unwrap();
+
+        let stored = storage.get_plugin_config("rss").await.unwrap().unwrap();
+        assert!(!stored.enabled);
+        assert_eq!(stored.priority, 5);
+        assert_eq!(stored.settings, config.settings);
+        assert_eq!(stored.quota, config.quota);
+    }
+
+    async fn insert_raw_briefing(storage: &SqliteStorage, date: DateTime<Utc>, sections: &str) -> Uuid {
+        let id = Uuid::new_v4();
+        // Written the way rows were before schema_version existed
+        sqlx::query("INSERT INTO daily_briefings (id, date, sections, generated_at) VALUES (?, ?, ?, ?)")
+            .bind(id.to_string())
+            .bind(date)
+            .bind(sections)
+            .bind(date)
+            .execute(&storage.pool)
+            .await
+            .unwrap();
+        id
+    }
+
+    #[tokio::test]
+    async fn test_legacy_briefings_upgrade_and_corrupted_rows_are_quarantined() {
+        let config = StorageConfig {
+            database_url: "sqlite::memory:".to_string(),
+            // Every pooled connection would otherwise get its own database
+            max_connections: 1,
+            ..Default::default()
+        };
+        let storage = SqliteStorage::new(&config).await.unwrap();
+
+        let now = Utc::now();
+        let v1 = insert_raw_briefing(
+            &storage,
+            now - chrono::Duration::days(1),
+            r#"[{"title": "Tasks", "content": "3 pending", "priority": "High", "source_documents": []}]"#,
+        )
+        .await;
+        let corrupted = insert_raw_briefing(&storage, now, r#"{"sections": [{"title": 7"#).await;
+
+        // The newest row is unreadable, so the latest readable one is returned
+        let latest = storage.get_latest_briefing().await.unwrap().unwrap();
+        assert_eq!(latest.id, v1);
+        assert_eq!(latest.sections[0].title, "Tasks");
+
+        let quarantined = storage.quarantined_briefings().await.unwrap();
+        assert_eq!(quarantined.len(), 1);
+        assert_eq!(quarantined[0].id, corrupted.to_string());
+        assert!(quarantined[0].reason.contains("v1:"));
+
+        // The v1 row was rewritten in the current format on read
+        let row = sqlx::query("SELECT schema_version, sections FROM daily_briefings WHERE id = ?")
+            .bind(v1.to_string())
+            .fetch_one(&storage.pool)
+            .await
+            .unwrap();
+        assert_eq!(row.get::<i64, _>("schema_version"), CURRENT_BRIEFING_SCHEMA);
+        assert!(row.get::<String, _>("sections").starts_with(r#"{"sections""#));
+
+        let listed = storage
+            .get_briefings_by_date_range(now - chrono::Duration::days(7), now + chrono::Duration::days(1))
+            .await
+            .unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(storage.get_briefing(corrupted).await.unwrap().map(|b| b.id), None);
+    }
+
+    const SEARCH_QUERY: &str =
+        "SELECT * FROM documents WHERE title LIKE '%note%' OR content LIKE '%note%' ORDER BY updated_at DESC LIMIT 10";
+    const STATUS_QUERY: &str = "SELECT * FROM tasks WHERE status = 'failed' ORDER BY created_at ASC";
+
+    async fn query_plan(pool: &SqlitePool, query: &str) -> String {
+        sqlx::query(&format!("EXPLAIN QUERY PLAN {}", query))
+            .fetch_all(pool)
+            .await
+            .unwrap()
+            .iter()
+            .map(|row| row.get::<String, _>("detail"))
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+
+    // The plans SQLite picks, rather than timings, so the test does not
+    // depend on how fast the machine is
+    #[tokio::test]
+    async fn test_query_index_migration_indexes_hot_queries() {
+        let pool = sqlx::sqlite::SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        pool.execute(
+            r#"
+            CREATE TABLE documents (id TEXT PRIMARY KEY, title TEXT, content TEXT, created_at DATETIME, updated_at DATETIME);
+            CREATE TABLE tasks (id TEXT PRIMARY KEY, title TEXT, status TEXT, created_at DATETIME);
+            CREATE TABLE messages (id TEXT PRIMARY KEY, conversation_id TEXT, content TEXT, created_at DATETIME);
+            "#,
+        )
+        .await
+        .unwrap();
+
+        let start = Utc::now() - chrono::Duration::days(365);
+        let mut tx = pool.begin().await.unwrap();
+        for i in 0..500i64 {
+            let at = start + chrono::Duration::minutes(i * 7);
+            sqlx::query("INSERT INTO documents VALUES (?, ?, ?, ?, ?)")
+                .bind(Uuid::new_v4().to_string())
+                .bind(format!("note {}", i))
+                .bind("Body text of a note that matches nothing in particular")
+                .bind(at)
+                .bind(at)
+                .execute(&mut *tx)
+                .await
+                .unwrap();
+            let status = if i % 100 == 0 { "failed" } else { "completed" };
+            sqlx::query("INSERT INTO tasks VALUES (?, ?, ?, ?)")
+                .bind(Uuid::new_v4().to_string())
+                .bind(format!("task {}", i))
+                .bind(status)
+                .bind(at)
+                .execute(&mut *tx)
+                .await
+                .unwrap();
+        }
+        tx.commit().await.unwrap();
+
+        assert!(query_plan(&pool, SEARCH_QUERY).await.contains("TEMP B-TREE FOR ORDER BY"));
+        assert!(query_plan(&pool, STATUS_QUERY).await.contains("SCAN tasks"));
+
+        pool.execute(include_str!("../../../migrations/000003_query_indexes.up.sql"))
+            .await
+            .unwrap();
+
+        assert!(query_plan(&pool, SEARCH_QUERY).await.contains("USING INDEX idx_documents_updated_at"));
+        assert!(query_plan(&pool, STATUS_QUERY).await.contains("USING INDEX idx_tasks_status"));
+        assert!(!query_plan(&pool, SEARCH_QUERY).await.contains("TEMP B-TREE"));
+    }
+
+    #[tokio::test]
+    async fn test_starting_a_timer_stops_the_running_one() {
+        let config = StorageConfig {
+            database_url: "sqlite::memory:".to_string(),
+            max_connections: 1,
+            ..Default::default()
+        };
+        let storage = SqliteStorage::new(&config).await.unwrap();
+        let (user, other) = (Uuid::new_v4(), Uuid::new_v4());
+        let (taxes, slides) = (Uuid::new_v4(), Uuid::new_v4());
+
+        let first = storage.start_task_timer(user, taxes, Some("receipts".to_string())).await.unwrap();
+        assert!(first.stopped.is_none());
+        // Other users' timers are their own
+        storage.start_task_timer(other, slides, None).await.unwrap();
+
+        // Starting the running timer again keeps it going
+        let again = storage.start_task_timer(user, taxes, None).await.unwrap();
+        assert_eq!((again.entry.id, again.stopped.is_none()), (first.entry.id, true));
+
+        let second = storage.start_task_timer(user, slides, None).await.unwrap();
+        let stopped = second.stopped.unwrap();
+        assert_eq!(stopped.id, first.entry.id);
+        assert!(stopped.ended_at.unwrap() <= second.entry.started_at);
+
+        let now = Utc::now();
+        let entries = storage
+            .get_time_entries_between(Some(user), now - chrono::Duration::hours(1), now + chrono::Duration::hours(1))
+            .await
+            .unwrap();
+        let running: Vec<_> = entries.iter().filter(|entry| entry.is_running()).collect();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(running.len(), 1);
+        assert_eq!(running[0].task_id, slides);
+
+        // Not even a write around start_task_timer gets a second timer running
+        let duplicate = sqlx::query("INSERT INTO task_time_entries (id, task_id, user_id, started_at) VALUES (?, ?, ?, ?)")
+            .bind(Uuid::new_v4().to_string())
+            .bind(taxes.to_string())
+            .bind(user.to_string())
+            .bind(now)
+            .execute(&storage.pool)
+            .await;
+        assert!(duplicate.is_err());
+
+        // Only the timer on the named task is stopped
+        assert!(storage.stop_task_timer(user, Some(taxes), None).await.unwrap().is_none());
+        let stopped = storage.stop_task_timer(user, None, Some("first draft".to_string())).await.unwrap().unwrap();
+        assert_eq!((stopped.task_id, stopped.note.as_deref()), (slides, Some("first draft")));
+        assert!(storage.stop_task_timer(user, None, None).await.unwrap().is_none());
+
+        let taxes_entries = storage.get_task_time_entries(taxes).await.unwrap();
+        assert_eq!(taxes_entries.len(), 1);
+        assert_eq!(taxes_entries[0].note.as_deref(), Some("receipts"));
+    }
+
+    async fn sync_storage() -> SqliteStorage {
+        let pool = sqlx::sqlite::SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        for migration in [
+            include_str!("../../../migrations/000001_initial_schema.up.sql"),
+            include_str!("../../../migrations/000002_search_and_logging.up.sql"),
+            include_str!("../../../migrations/000004_sync_changes.up.sql"),
+            include_str!("../../../migrations/000005_plugin_audit.up.sql"),
+            include_str!("../../../migrations/000006_plugin_schedules.up.sql"),
+            include_str!("../../../migrations/000007_plugin_configs.up.sql"),
+            include_str!("../../../migrations/000008_daily_briefings.up.sql"),
+            include_str!("../../../migrations/000009_knowledge_digests.up.sql"),
+            include_str!("../../../migrations/000010_admin_audit.up.sql"),
+            include_str!("../../../migrations/000011_task_time_entries.up.sql"),
+            include_str!("../../../migrations/000012_conversation_turns.up.sql"),
+            include_str!("../../../migrations/000013_assistant_actions.up.sql"),
+            include_str!("../../../migrations/000014_search_history.up.sql"),
+            include_str!("../../../migrations/000015_feature_flags.up.sql"),
+            include_str!("../../../migrations/000016_plugin_data.up.sql"),
+        ] {
+            pool.execute(migration).await.unwrap();
+        }
+        SqliteStorage {
+            pool,
+            metrics: Arc::new(QueryMetrics::new(std::time::Duration::from_secs(60), 10)),
+        }
+    }
+
+    type ClientState = std::collections::HashMap<(SyncEntity, String), serde_json::Value>;
+
+    // Every synced record of the user as the server has it now
+    async fn server_state(storage: &SqliteStorage, user_id: Uuid) -> ClientState {
+        let mut state = ClientState::new();
+        for entity in SyncEntity::ALL {
+            let (table, projection) = sync_projection(entity);
+            let rows = sqlx::query(&format!("SELECT id, {} AS record FROM {} WHERE user_id = ?", projection, table))
+                .bind(user_id.to_string())
+                .fetch_all(&storage.pool)
+                .await
+                .unwrap();
+            for row in rows {
+                let record: String = row.get("record");
+                state.insert((entity, row.get("id")), serde_json::from_str(&record).unwrap());
+            }
+        }
+        state
+    }
+
+    // Apply one sync response the way a client would; returns its cursor and
+    // whether more pages follow
+    async fn sync_page(
+        storage: &SqliteStorage,
+        user_id: Uuid,
+        cursor: &str,
+        page_size: usize,
+        state: &mut ClientState,
+    ) -> (String, bool) {
+        let since = SyncCursor::decode(cursor).unwrap();
+        let batch = storage.get_changes_since(user_id, since, page_size).await.unwrap();
+        assert!(batch.changes.len() <= page_size);
+        let response = crate::sync::SyncResponse::from(batch);
+
+        for entity in SyncEntity::ALL {
+            let changes = response.entity(entity);
+            for record in &changes.upserted {
+                state.insert((entity, record["id"].as_str().unwrap().to_string()), record.clone());
+            }
+            for id in &changes.deleted {
+                state.remove(&(entity, id.clone()));
+            }
+        }
+        (response.cursor, response.has_more)
+    }
+
+    async fn sync_to_end(storage: &SqliteStorage, user_id: Uuid, mut cursor: String, state: &mut ClientState) -> String {
+        loop {
+            let (next, has_more) = sync_page(storage, user_id, &cursor, 3, state).await;
+            cursor = next;
+            if !has_more {
+                return cursor;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sync_stream_reconstructs_final_state() {
+        let storage = sync_storage().await;
+        let (user, other) = (Uuid::new_v4(), Uuid::new_v4());
+        let run = |sql: String| {
+            let pool = storage.pool.clone();
+            async move { pool.execute(sql.as_str()).await.unwrap() }
+        };
+
+        run(format!(
+            r#"
+            INSERT INTO users (id, email, password_hash, full_name) VALUES ('{user}', 'a@example.com', 'x', 'A'), ('{other}', 'b@example.com', 'x', 'B');
+            INSERT INTO conversations (id, user_id, title) VALUES ('c1', '{user}', 'Trip'), ('c2', '{user}', 'Groceries'), ('c3', '{other}', 'Not mine');
+            INSERT INTO messages (id, conversation_id, user_id, role, content) VALUES
+                ('m1', 'c1', '{user}', 'user', 'Book a flight'), ('m2', 'c1', '{user}', 'assistant', 'Done'),
+                ('m3', 'c2', '{user}', 'user', 'Milk'), ('m9', 'c3', '{other}', 'user', 'Hidden');
+            INSERT INTO tasks (id, user_id, title) VALUES ('t1', '{user}', 'Pack'), ('t2', '{user}', 'Water plants');
+            INSERT INTO notifications (id, user_id, title, message) VALUES ('n1', '{user}', 'Reminder', 'Flight tomorrow');
+            INSERT INTO documents (id, user_id, title, content) VALUES ('d1', '{user}', 'Itinerary', 'Seat 12A');
+            "#
+        ))
+        .await;
+
+        // A client that stopped after its first page
+        let mut interrupted = ClientState::new();
+        let (cursor, has_more) = sync_page(&storage, user, &SyncCursor::START.encode(), 3, &mut interrupted).await;
+        assert!(has_more);
+
+        run(format!(
+            r#"
+            UPDATE tasks SET status = 'completed', completed_at = CURRENT_TIMESTAMP WHERE id = 't1';
+            UPDATE conversations SET title = 'Trip to Rome' WHERE id = 'c1';
+            DELETE FROM conversations WHERE id = 'c2';
+            UPDATE notifications SET is_read = TRUE WHERE id = 'n1';
+            DELETE FROM tasks WHERE id = 't2';
+            UPDATE documents SET title = 'Rome itinerary' WHERE id = 'd1';
+            INSERT INTO messages (id, conversation_id, user_id, role, content) VALUES ('m4', 'c1', '{user}', 'user', 'Thanks');
+            UPDATE tasks SET title = 'Not mine either' WHERE user_id = '{other}';
+            "#
+        ))
+        .await;
+
+        let expected = server_state(&storage, user).await;
+        assert!(expected.contains_key(&(SyncEntity::Messages, "m4".to_string())));
+        assert!(!expected.contains_key(&(SyncEntity::Messages, "m3".to_string())));
+        assert_eq!(expected[&(SyncEntity::Tasks, "t1".to_string())]["status"], "completed");
+
+        let cursor = sync_to_end(&storage, user, cursor, &mut interrupted).await;
+        assert_eq!(interrupted, expected);
+
+        let mut fresh = ClientState::new();
+        let fresh_cursor = sync_to_end(&storage, user, SyncCursor::START.encode(), &mut fresh).await;
+        assert_eq!(fresh, expected);
+        assert_eq!(fresh_cursor, cursor);
+        assert!(fresh.get(&(SyncEntity::Documents, "d1".to_string())).unwrap().get("content").is_none());
+
+        // Nothing new: an empty page that keeps the cursor
+        let (unchanged, has_more) = sync_page(&storage, user, &cursor, 3, &mut fresh).await;
+        assert_eq!((unchanged.as_str(), has_more), (cursor.as_str(), false));
+    }
+
+    async fn retention_storage() -> SqliteStorage {
+        let storage = sync_storage().await;
+
+        let now = Utc::now();
+        let days_ago = |days: i64| now - chrono::Duration::days(days);
+        storage
+            .pool
+            .execute("INSERT INTO users (id, email, password_hash, full_name) VALUES ('u1', 'a@example.com', 'x', 'A')")
+            .await
+            .unwrap();
+        for (id, age) in [("old-doc", 400), ("new-doc", 1)] {
+            sqlx::query("INSERT INTO documents (id, user_id, title, updated_at) VALUES (?, 'u1', 'Doc', ?)")
+                .bind(id)
+                .bind(days_ago(age))
+                .execute(&storage.pool)
+                .await
+                .unwrap();
+        }
+        // Only the completed task past 90 days goes; open tasks stay at any age
+        for (id, status, age) in [("done-old", "completed", 100), ("done-new", "completed", 80), ("open-old", "pending", 200)] {
+            sqlx::query("INSERT INTO tasks (id, user_id, title, status, updated_at) VALUES (?, 'u1', 'Task', ?, ?)")
+                .bind(id)
+                .bind(status)
+                .bind(days_ago(age))
+                .execute(&storage.pool)
+                .await
+                .unwrap();
+        }
+        for age in [400, 300] {
+            insert_raw_briefing(&storage, days_ago(age), "[]").await;
+        }
+        for age in [20, 10] {
+            sqlx::query("INSERT INTO notifications (user_id, title, message, created_at) VALUES ('u1', 'Hi', '', ?)")
+                .bind(days_ago(age))
+                .execute(&storage.pool)
+                .await
+                .unwrap();
+        }
+        for age in [100, 10] {
+            storage.store_plugin_audit(&plugin_call("notes", days_ago(age), None)).await.unwrap();
+        }
+        storage
+    }
+
+    async fn count(storage: &SqliteStorage, table: &str) -> i64 {
+        sqlx::query(&format!("SELECT COUNT(*) FROM {}", table))
+            .fetch_one(&storage.pool)
+            .await
+            .unwrap()
+            .get(0)
+    }
+
+    #[tokio::test]
+    async fn test_cleanup_applies_each_entity_threshold() {
+        let storage = retention_storage().await;
+        let policy = RetentionPolicy::default();
+
+        let preview = storage.cleanup_old_data(&policy, true).await.unwrap();
+        assert!(preview.dry_run);
+        assert_eq!(preview.tables.get("tasks"), Some(&1));
+        assert_eq!(preview.tables.get("daily_briefings"), Some(&1));
+        assert_eq!(preview.tables.get("notifications"), Some(&1));
+        assert_eq!(preview.tables.get("plugin_audit"), Some(&1));
+        // The dry run deleted nothing
+        assert_eq!(count(&storage, "tasks").await, 3);
+        assert_eq!(count(&storage, "notifications").await, 2);
+
+        let report = storage.cleanup_old_data(&policy, false).await.unwrap();
+        assert_eq!(report.tables, preview.tables);
+        assert_eq!(count(&storage, "tasks").await, 2);
+        assert_eq!(count(&storage, "daily_briefings").await, 1);
+        assert_eq!(count(&storage, "notifications").await, 1);
+        assert_eq!(count(&storage, "plugin_audit").await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_documents_are_kept_unless_deletion_is_opted_into() {
+        let storage = retention_storage().await;
+
+        let report = storage.cleanup_old_data(&RetentionPolicy::default(), false).await.unwrap();
+        assert_eq!(report.tables.get("documents"), None);
+        assert_eq!(count(&storage, "documents").await, 2);
+
+        // A limit alone is not enough
+        let limited = RetentionPolicy { documents_days: Some(365), ..Default::default() };
+        storage.cleanup_old_data(&limited, false).await.unwrap();
+        assert_eq!(count(&storage, "documents").await, 2);
+
+        let opted_in = RetentionPolicy { delete_documents: true, ..limited };
+        let report = storage.cleanup_old_data(&opted_in, false).await.unwrap();
+        assert_eq!(report.tables.get("documents"), Some(&1));
+        assert_eq!(count(&storage, "documents").await, 1);
+    }
+}
@@ -0,0 +1,412 @@
+use rusty_ai_common::{Document, Result};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::{Arc, Mutex, RwLock as StdRwLock};
+use tokio::sync::OnceCell;
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+use crate::storage::Storage;
+
+pub const MAX_SUGGESTIONS: usize = 10;
+
+// Documents read from storage when the index is first used
+const INITIAL_LOAD_LIMIT: usize = 100_000;
+// Past searches loaded per user, most frequent first
+const HISTORY_LOAD_LIMIT: usize = 500;
+
+/// Where a suggestion came from, in ranking order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SuggestionKind {
+    Title,
+    Tag,
+    History,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Suggestion {
+    pub text: String,
+    pub kind: SuggestionKind,
+    /// What to pass as `q` to the regular search when the suggestion is picked
+    pub query: String,
+    /// The document a title suggestion names
+    pub document_id: Option<Uuid>,
+}
+
+fn normalize(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+}
+
+struct IndexedDocument {
+    title: String,
+    tags: Vec<String>,
+}
+
+// Titles as a sorted Vec searched by binary search, tags in a BTreeMap with
+// the number of documents carrying each. Both are updated per document
+#[derive(Default)]
+pub struct DocumentIndex {
+    titles: Vec<(String, Uuid)>,
+    documents: HashMap<Uuid, IndexedDocument>,
+    tags: BTreeMap<String, (String, usize)>,
+}
+
+impl DocumentIndex {
+    /// Add a document, replacing what was indexed for it before
+    pub fn insert(&mut self, document: &Document) {
+        self.remove(document.id);
+
+        let key = (normalize(&document.title), document.id);
+        if !key.0.is_empty() {
+            let position = self.titles.binary_search(&key).unwrap_or_else(|p| p);
+            self.titles.insert(position, key);
+        }
+
+        let mut tags: Vec<String> = Vec::new();
+        for tag in &document.metadata.tags {
+            let normalized = normalize(tag);
+            if normalized.is_empty() || tags.iter().any(|t| normalize(t) == normalized) {
+                continue;
+            }
+            self.tags.entry(normalized).or_insert_with(|| (tag.trim().to_string(), 0)).1 += 1;
+            tags.push(tag.trim().to_string());
+        }
+
+        self.documents.insert(
+            document.id,
+            IndexedDocument {
+                title: document.title.trim().to_string(),
+                tags,
+            },
+        );
+    }
+
+    pub fn remove(&mut self, id: Uuid) -> bool {
+        let Some(document) = self.documents.remove(&id) else {
+            return false;
+        };
+
+        if let Ok(position) = self.titles.binary_search(&(normalize(&document.title), id)) {
+            self.titles.remove(position);
+        }
+        for tag in document.tags {
+            let normalized = normalize(&tag);
+            if let Some((_, count)) = self.tags.get_mut(&normalized) {
+                *count -= 1;
+                if *count == 0 {
+                    self.tags.remove(&normalized);
+                }
+            }
+        }
+        true
+    }
+
+    pub fn contains(&self, id: Uuid) -> bool {
+        self.documents.contains_key(&id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.documents.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.documents.is_empty()
+    }
+
+    // Alphabetical, so a title equal to the prefix comes first
+    fn titles_starting_with<'a>(&'a self, prefix: &'a str) -> impl Iterator<Item = (&'a str, Uuid)> + 'a {
+        let start = self.titles.partition_point(|(title, _)| title.as_str() < prefix);
+        self.titles[start..]
+            .iter()
+            .take_while(move |(title, _)| title.starts_with(prefix))
+            .filter_map(|(_, id)| self.documents.get(id).map(|doc| (doc.title.as_str(), *id)))
+    }
+
+    // Most used first
+    fn tags_starting_with(&self, prefix: &str) -> Vec<&str> {
+        let mut tags: Vec<(&str, usize)> = self
+            .tags
+            .range(prefix.to_string()..)
+            .take_while(|(tag, _)| tag.starts_with(prefix))
+            .map(|(_, (display, count))| (display.as_str(), *count))
+            .collect();
+        tags.sort_by(|a, b| b.1.cmp(&a.1));
+        tags.into_iter().map(|(tag, _)| tag).collect()
+    }
+}
+
+/// A user's successful searches with how often each was run
+#[derive(Debug, Default)]
+pub struct SearchHistory {
+    queries: HashMap<String, (String, u64)>,
+}
+
+impl SearchHistory {
+    pub fn record(&mut self, query: &str) {
+        self.add(query, 1);
+    }
+
+    fn add(&mut self, query: &str, count: u64) {
+        let normalized = normalize(query);
+        if normalized.is_empty() {
+            return;
+        }
+        let entry = self.queries.entry(normalized).or_insert_with(|| (query.trim().to_string(), 0));
+        entry.0 = query.trim().to_string();
+        entry.1 += count;
+    }
+
+    // Most frequent first
+    fn starting_with(&self, prefix: &str) -> Vec<&str> {
+        let mut queries: Vec<(&str, u64)> = self
+            .queries
+            .iter()
+            .filter(|(normalized, _)| normalized.starts_with(prefix))
+            .map(|(_, (display, count))| (display.as_str(), *count))
+            .collect();
+        queries.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+        queries.into_iter().map(|(query, _)| query).collect()
+    }
+}
+
+/// Title matches first, then tags, then past searches; each text once
+pub fn rank(prefix: &str, documents: &DocumentIndex, history: Option<&SearchHistory>) -> Vec<Suggestion> {
+    let prefix = normalize(prefix);
+    if prefix.is_empty() {
+        return Vec::new();
+    }
+
+    let mut seen = HashSet::new();
+    let mut suggestions = Vec::with_capacity(MAX_SUGGESTIONS);
+    let mut push = |text: &str, kind: SuggestionKind, document_id: Option<Uuid>| {
+        // A title shared by several documents is suggested once per
+        // document; a tag or past search already suggested is skipped
+        let unseen = seen.insert(normalize(text));
+        if suggestions.len() < MAX_SUGGESTIONS && (unseen || document_id.is_some()) {
+            suggestions.push(Suggestion {
+                text: text.to_string(),
+                kind,
+                query: text.to_string(),
+                document_id,
+            });
+        }
+    };
+
+    for (title, id) in documents.titles_starting_with(&prefix).take(MAX_SUGGESTIONS) {
+        push(title, SuggestionKind::Title, Some(id));
+    }
+    for tag in documents.tags_starting_with(&prefix) {
+        push(tag, SuggestionKind::Tag, None);
+    }
+    if let Some(history) = history {
+        for query in history.starting_with(&prefix) {
+            push(query, SuggestionKind::History, None);
+        }
+    }
+
+    suggestions
+}
+
+// Search-as-you-type suggestions answered from memory. The knowledge base is
+// shared, so titles and tags are too; search history is per user. Documents
+// in storage are read once, on first use; after that the knowledge routes
+// report each ingest and delete
+pub struct SuggestionIndex {
+    storage: Arc<dyn Storage + Send + Sync>,
+    documents: StdRwLock<DocumentIndex>,
+    loaded: OnceCell<()>,
+    // Deleted while the first load ran, so the load does not bring them back
+    removed_before_load: Mutex<HashSet<Uuid>>,
+    history: StdRwLock<HashMap<Uuid, SearchHistory>>,
+}
+
+impl SuggestionIndex {
+    pub fn new(storage: Arc<dyn Storage + Send + Sync>) -> Self {
+        Self {
+            storage,
+            documents: StdRwLock::new(DocumentIndex::default()),
+            loaded: OnceCell::new(),
+            removed_before_load: Mutex::new(HashSet::new()),
+            history: StdRwLock::new(HashMap::new()),
+        }
+    }
+
+    pub fn document_added(&self, document: &Document) {
+        self.documents.write().unwrap().insert(document);
+    }
+
+    pub fn document_removed(&self, id: Uuid) {
+        if !self.loaded.initialized() {
+            self.removed_before_load.lock().unwrap().insert(id);
+        }
+        self.documents.write().unwrap().remove(id);
+    }
+
+    /// Remember a search that found something. A failure to store it only
+    /// costs the suggestion
+    pub async fn record_search(&self, user_id: Uuid, query: &str) {
+        if normalize(query).is_empty() {
+            return;
+        }
+        if let Some(history) = self.history.write().unwrap().get_mut(&user_id) {
+            history.record(query);
+        }
+        if let Err(e) = self.storage.record_search_query(user_id, query.trim()).await {
+            warn!("Failed to record search for {}: {}", user_id, e);
+        }
+    }
+
+    pub async fn suggest(&self, user_id: Uuid, prefix: &str) -> Result<Vec<Suggestion>> {
+        self.ensure_documents_loaded().await?;
+        self.ensure_history_loaded(user_id).await?;
+
+        let documents = self.documents.read().unwrap();
+        let history = self.history.read().unwrap();
+        Ok(rank(prefix, &documents, history.get(&user_id)))
+    }
+
+    async fn ensure_documents_loaded(&self) -> Result<()> {
+        self.loaded
+            .get_or_try_init(|| async {
+                let stored = self.storage.search_documents("", INITIAL_LOAD_LIMIT).await?;
+                let mut removed = self.removed_before_load.lock().unwrap();
+                let mut documents = self.documents.write().unwrap();
+                // Anything reported while the load ran is newer than storage's copy
+                for document in &stored {
+                    if !documents.contains(document.id) && !removed.contains(&document.id) {
+                        documents.insert(document);
+                    }
+                }
+                removed.clear();
+                debug!("Indexed {} documents for suggestions", documents.len());
+                Ok(())
+            })
+            .await
+            .map(|_| ())
+    }
+
+    async fn ensure_history_loaded(&self, user_id: Uuid) -> Result<()> {
+        if self.history.read().unwrap().contains_key(&user_id) {
+            return Ok(());
+        }
+
+        let stored = self.storage.get_search_queries(user_id, HISTORY_LOAD_LIMIT).await?;
+        let mut history = self.history.write().unwrap();
+        if !history.contains_key(&user_id) {
+            let mut loaded = SearchHistory::default();
+            for (query, count) in stored {
+                loaded.add(&query, count);
+            }
+            history.insert(user_id, loaded);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rusty_ai_common::DocumentMetadata;
+
+    fn document(title: &str, tags: &[&str]) -> Document {
+        Document {
+            id: Uuid::new_v4(),
+            title: title.to_string(),
+            content: String::new(),
+            metadata: DocumentMetadata {
+                source: "test".to_string(),
+                file_type: "text".to_string(),
+                tags: tags.iter().map(|t| t.to_string()).collect(),
+                summary: None,
+                importance_score: 0.5,
+                embeddings: None,
+            },
+            created_at: chrono::Utc::now(),
+            updated_at: chrono::Utc::now(),
+        }
+    }
+
+    fn texts(suggestions: &[Suggestion]) -> Vec<(&str, SuggestionKind)> {
+        suggestions.iter().map(|s| (s.text.as_str(), s.kind)).collect()
+    }
+
+    #[test]
+    fn test_titles_rank_before_tags_before_history() {
+        let mut index = DocumentIndex::default();
+        index.insert(&document("Tax return 2024", &["finance"]));
+        index.insert(&document("Taxi receipts", &["travel", "Finance"]));
+        index.insert(&document("Holiday plans", &["taxes", "travel"]));
+        index.insert(&document("Tax", &[]));
+
+        let mut history = SearchHistory::default();
+        history.record("tax deadline");
+        history.record("tax deadline");
+        history.record("taxonomy");
+        history.record("Tax");
+
+        let suggestions = rank("  TAX", &index, Some(&history));
+        assert_eq!(
+            texts(&suggestions),
+            vec![
+                ("Tax", SuggestionKind::Title),
+                ("Tax return 2024", SuggestionKind::Title),
+                ("Taxi receipts", SuggestionKind::Title),
+                ("taxes", SuggestionKind::Tag),
+                ("tax deadline", SuggestionKind::History),
+                ("taxonomy", SuggestionKind::History),
+            ]
+        );
+        assert!(suggestions[0].document_id.is_some());
+        assert_eq!(suggestions[4].query, "tax deadline");
+
+        // Tags shared by more documents come first; case does not split them
+        let suggestions = rank("f", &index, None);
+        assert_eq!(texts(&suggestions), vec![("finance", SuggestionKind::Tag)]);
+        assert!(rank(" ", &index, Some(&history)).is_empty());
+    }
+
+    #[test]
+    fn test_index_follows_inserts_and_deletes() {
+        let mut index = DocumentIndex::default();
+        let report = document("Quarterly report", &["work"]);
+        let review = document("Quarterly review", &["work", "reviews"]);
+        index.insert(&report);
+        index.insert(&review);
+
+        assert!(index.remove(report.id));
+        assert!(!index.remove(report.id));
+        let suggestions = rank("quarterly", &index, None);
+        assert_eq!(texts(&suggestions), vec![("Quarterly review", SuggestionKind::Title)]);
+        assert_eq!(texts(&rank("wo", &index, None)), vec![("work", SuggestionKind::Tag)]);
+
+        // Re-indexing a renamed document drops its old title and tags
+        let mut renamed = review.clone();
+        renamed.title = "Annual review".to_string();
+        renamed.metadata.tags = vec!["archive".to_string()];
+        index.insert(&renamed);
+        assert_eq!(index.len(), 1);
+        assert!(rank("quarterly", &index, None).is_empty());
+        assert!(rank("work", &index, None).is_empty());
+        assert_eq!(texts(&rank("ann", &index, None)), vec![("Annual review", SuggestionKind::Title)]);
+    }
+
+    #[test]
+    fn test_suggestions_stay_fast_on_a_large_index() {
+        let mut index = DocumentIndex::default();
+        for i in 0..50_000 {
+            index.insert(&document(&format!("Meeting notes {:05}", i), &[&format!("project-{}", i % 500)]));
+        }
+        let mut history = SearchHistory::default();
+        for i in 0..HISTORY_LOAD_LIMIT {
+            history.record(&format!("meeting {}", i));
+        }
+
+        let started = std::time::Instant::now();
+        for prefix in ["m", "meeting notes 4", "project-4", "zzz"] {
+            rank(prefix, &index, Some(&history));
+        }
+        assert!(started.elapsed() < std::time::Duration::from_millis(40), "took {:?}", started.elapsed());
+        assert_eq!(rank("meeting notes 4", &index, None).len(), MAX_SUGGESTIONS);
+    }
+}
@@ -0,0 +1,188 @@
+use serde::{Deserialize, Serialize};
+
+/// Changes returned per sync request unless the client asks for fewer
+pub const DEFAULT_SYNC_PAGE_SIZE: usize = 500;
+pub const MAX_SYNC_PAGE_SIZE: usize = 1000;
+
+/// Record types kept in step on clients through the sync API
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncEntity {
+    Sessions,
+    Messages,
+    Tasks,
+    Notifications,
+    Documents,
+}
+
+impl SyncEntity {
+    pub const ALL: [SyncEntity; 5] = [
+        SyncEntity::Sessions,
+        SyncEntity::Messages,
+        SyncEntity::Tasks,
+        SyncEntity::Notifications,
+        SyncEntity::Documents,
+    ];
+
+    /// The name the change log and clients use
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SyncEntity::Sessions => "sessions",
+            SyncEntity::Messages => "messages",
+            SyncEntity::Tasks => "tasks",
+            SyncEntity::Notifications => "notifications",
+            SyncEntity::Documents => "documents",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|e| e.as_str() == name)
+    }
+}
+
+/// Position in the change sequence. Clients treat the encoded form as opaque
+/// and send back the one from their last response.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SyncCursor(i64);
+
+impl SyncCursor {
+    /// Before the first change; a client without a cursor gets everything
+    pub const START: SyncCursor = SyncCursor(0);
+
+    pub fn new(seq: i64) -> Self {
+        Self(seq)
+    }
+
+    pub fn seq(&self) -> i64 {
+        self.0
+    }
+
+    pub fn encode(&self) -> String {
+        format!("c{:x}", self.0)
+    }
+
+    pub fn decode(cursor: &str) -> Option<Self> {
+        let seq = i64::from_str_radix(cursor.strip_prefix('c')?, 16).ok()?;
+        (seq >= 0).then_some(Self(seq))
+    }
+}
+
+/// The latest change to one record: its current state, or `None` once it
+/// has been deleted
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordChange {
+    pub entity: SyncEntity,
+    pub id: String,
+    pub record: Option<serde_json::Value>,
+}
+
+/// One page of changes, in change order. Requesting again from `cursor`
+/// continues where this page stopped.
+#[derive(Debug, Clone, Default)]
+pub struct ChangeBatch {
+    pub changes: Vec<RecordChange>,
+    pub cursor: SyncCursor,
+    pub has_more: bool,
+}
+
+impl ChangeBatch {
+    pub fn empty(since: SyncCursor) -> Self {
+        Self { changes: Vec::new(), cursor: since, has_more: false }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct EntityChanges {
+    /// Created or updated records, in their current state
+    pub upserted: Vec<serde_json::Value>,
+    pub deleted: Vec<String>,
+}
+
+/// A change page grouped by record type, as sent to clients
+#[derive(Debug, Clone, Serialize)]
+pub struct SyncResponse {
+    pub cursor: String,
+    pub has_more: bool,
+    pub sessions: EntityChanges,
+    pub messages: EntityChanges,
+    pub tasks: EntityChanges,
+    pub notifications: EntityChanges,
+    pub documents: EntityChanges,
+}
+
+impl SyncResponse {
+    pub fn entity(&self, entity: SyncEntity) -> &EntityChanges {
+        match entity {
+            SyncEntity::Sessions => &self.sessions,
+            SyncEntity::Messages => &self.messages,
+            SyncEntity::Tasks => &self.tasks,
+            SyncEntity::Notifications => &self.notifications,
+            SyncEntity::Documents => &self.documents,
+        }
+    }
+
+    fn entity_mut(&mut self, entity: SyncEntity) -> &mut EntityChanges {
+        match entity {
+            SyncEntity::Sessions => &mut self.sessions,
+            SyncEntity::Messages => &mut self.messages,
+            SyncEntity::Tasks => &mut self.tasks,
+            SyncEntity::Notifications => &mut self.notifications,
+            SyncEntity::Documents => &mut self.documents,
+        }
+    }
+}
+
+impl From<ChangeBatch> for SyncResponse {
+    fn from(batch: ChangeBatch) -> Self {
+        let mut response = SyncResponse {
+            cursor: batch.cursor.encode(),
+            has_more: batch.has_more,
+            sessions: EntityChanges::default(),
+            messages: EntityChanges::default(),
+            tasks: EntityChanges::default(),
+            notifications: EntityChanges::default(),
+            documents: EntityChanges::default(),
+        };
+        for change in batch.changes {
+            let changes = response.entity_mut(change.entity);
+            match change.record {
+                Some(record) => changes.upserted.push(record),
+                None => changes.deleted.push(change.id),
+            }
+        }
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cursor_round_trip_and_grouping() {
+        let cursor = SyncCursor::new(4711);
+        assert_eq!(SyncCursor::decode(&cursor.encode()), Some(cursor));
+        assert_eq!(SyncCursor::decode("c0"), Some(SyncCursor::START));
+        assert_eq!(SyncCursor::decode("4711"), None);
+        assert_eq!(SyncCursor::decode("czz"), None);
+
+        let batch = ChangeBatch {
+            changes: vec![
+                RecordChange {
+                    entity: SyncEntity::Tasks,
+                    id: "t1".to_string(),
+                    record: Some(serde_json::json!({"id": "t1"})),
+                },
+                RecordChange { entity: SyncEntity::Messages, id: "m1".to_string(), record: None },
+            ],
+            cursor,
+            has_more: true,
+        };
+        let response = SyncResponse::from(batch);
+        assert_eq!(response.cursor, cursor.encode());
+        assert!(response.has_more);
+        assert_eq!(response.tasks.upserted, vec![serde_json::json!({"id": "t1"})]);
+        assert_eq!(response.messages.deleted, vec!["m1".to_string()]);
+        assert_eq!(response.entity(SyncEntity::Documents), &EntityChanges::default());
+    }
+}
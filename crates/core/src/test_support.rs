@@ -0,0 +1,99 @@
+// Helpers shared by the unit tests of this crate
+
+use rusty_ai_common::{AssistantError, NotificationChannel, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::notifications::{Clock, Notification, NotificationSink};
+
+pub(crate) fn at(rfc3339: &str) -> DateTime<Utc> {
+    DateTime::parse_from_rfc3339(rfc3339).unwrap().with_timezone(&Utc)
+}
+
+// A clock that stands still until a test moves it
+pub(crate) struct TestClock(Mutex<DateTime<Utc>>);
+
+impl TestClock {
+    pub(crate) fn new(now: DateTime<Utc>) -> Arc<Self> {
+        Arc::new(Self(Mutex::new(now)))
+    }
+
+    pub(crate) fn at(rfc3339: &str) -> Arc<Self> {
+        Self::new(at(rfc3339))
+    }
+
+    pub(crate) fn set(&self, rfc3339: &str) {
+        self.set_to(at(rfc3339));
+    }
+
+    pub(crate) fn set_to(&self, now: DateTime<Utc>) {
+        *self.0.lock().unwrap() = now;
+    }
+}
+
+impl Clock for TestClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.0.lock().unwrap()
+    }
+}
+
+// Keeps every delivery, in order, with the channel it went out on, and
+// fails them all while set down
+#[derive(Default)]
+pub(crate) struct RecordingSink {
+    delivered: Mutex<Vec<(NotificationChannel, Notification)>>,
+    down: AtomicBool,
+}
+
+impl RecordingSink {
+    pub(crate) fn set_down(&self, down: bool) {
+        self.down.store(down, Ordering::SeqCst);
+    }
+
+    pub(crate) fn notifications(&self) -> Vec<Notification> {
+        self.delivered.lock().unwrap().iter().map(|(_, notification)| notification.clone()).collect()
+    }
+
+    pub(crate) fn titles(&self) -> Vec<(NotificationChannel, String)> {
+        self.delivered
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(channel, notification)| (channel.clone(), notification.title.clone()))
+            .collect()
+    }
+
+    pub(crate) fn bodies(&self) -> Vec<(NotificationChannel, String)> {
+        self.delivered
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(channel, notification)| (channel.clone(), notification.body.clone()))
+            .collect()
+    }
+
+    pub(crate) fn last(&self) -> Option<Notification> {
+        self.delivered.lock().unwrap().last().map(|(_, notification)| notification.clone())
+    }
+
+    pub(crate) fn count(&self) -> usize {
+        self.delivered.lock().unwrap().len()
+    }
+
+    pub(crate) fn clear(&self) {
+        self.delivered.lock().unwrap().clear();
+    }
+}
+
+#[async_trait]
+impl NotificationSink for RecordingSink {
+    async fn deliver(&self, channel: &NotificationChannel, notification: &Notification) -> Result<()> {
+        if self.down.load(Ordering::SeqCst) {
+            return Err(AssistantError::Internal("sink is down".to_string()));
+        }
+        self.delivered.lock().unwrap().push((channel.clone(), notification.clone()));
+        Ok(())
+    }
+}
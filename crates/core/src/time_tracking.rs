@@ -0,0 +1,372 @@
+use chrono::{DateTime, Datelike, Duration, NaiveDate, TimeZone, Utc};
+use chrono_tz::Tz;
+use rusty_ai_common::{Result, Task};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use uuid::Uuid;
+
+use crate::storage::Storage;
+
+/// Tag under which time on untagged tasks is reported
+pub const UNTAGGED: &str = "untagged";
+
+// Words that say nothing about which task is meant
+const FILLER_WORDS: &[&str] = &["a", "an", "the", "my", "on", "for", "to", "of", "with", "task"];
+
+/// A stretch of time spent on a task; `ended_at` is unset while the timer
+/// is running
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TimeEntry {
+    pub id: Uuid,
+    pub task_id: Uuid,
+    pub user_id: Uuid,
+    pub started_at: DateTime<Utc>,
+    pub ended_at: Option<DateTime<Utc>>,
+    pub note: Option<String>,
+}
+
+impl TimeEntry {
+    pub fn start(user_id: Uuid, task_id: Uuid, note: Option<String>, now: DateTime<Utc>) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            task_id,
+            user_id,
+            started_at: now,
+            ended_at: None,
+            note,
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.ended_at.is_none()
+    }
+
+    /// Seconds of this entry inside `[start, end)`, counting a running timer
+    /// up to `now`
+    pub fn seconds_within(&self, start: DateTime<Utc>, end: DateTime<Utc>, now: DateTime<Utc>) -> i64 {
+        let from = self.started_at.max(start);
+        let to = self.ended_at.unwrap_or(now).min(end);
+        (to - from).num_seconds().max(0)
+    }
+
+    pub fn seconds(&self, now: DateTime<Utc>) -> i64 {
+        (self.ended_at.unwrap_or(now) - self.started_at).num_seconds().max(0)
+    }
+}
+
+/// Result of starting a timer: the new entry, and the user's timer it
+/// stopped, if another one was running
+#[derive(Debug, Clone, Serialize)]
+pub struct TimerStart {
+    pub entry: TimeEntry,
+    pub stopped: Option<TimeEntry>,
+}
+
+/// Total tracked on a task across `entries`, a running timer up to `now`
+pub fn tracked_seconds(entries: &[TimeEntry], now: DateTime<Utc>) -> i64 {
+    entries.iter().map(|entry| entry.seconds(now)).sum()
+}
+
+/// "1h 5m", "25m"; a started minute is not counted
+pub fn format_duration(seconds: i64) -> String {
+    let minutes = seconds.max(0) / 60;
+    match (minutes / 60, minutes % 60) {
+        (0, minutes) => format!("{}m", minutes),
+        (hours, minutes) => format!("{}h {}m", hours, minutes),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportPeriod {
+    Day,
+    Week,
+    Month,
+}
+
+impl ReportPeriod {
+    pub fn parse(period: &str) -> Option<Self> {
+        match period.to_ascii_lowercase().as_str() {
+            "day" | "today" => Some(ReportPeriod::Day),
+            "week" => Some(ReportPeriod::Week),
+            "month" => Some(ReportPeriod::Month),
+            _ => None,
+        }
+    }
+
+    /// The period containing `now` as `[start, end)`, starting at local
+    /// midnight in `timezone`. Weeks start on Monday
+    pub fn bounds(self, now: DateTime<Utc>, timezone: Tz) -> (DateTime<Utc>, DateTime<Utc>) {
+        let today = now.with_timezone(&timezone).date_naive();
+        let (first, next) = match self {
+            ReportPeriod::Day => (today, today + Duration::days(1)),
+            ReportPeriod::Week => {
+                let first = today - Duration::days(today.weekday().num_days_from_monday() as i64);
+                (first, first + Duration::days(7))
+            }
+            ReportPeriod::Month => {
+                let first = today.with_day(1).unwrap_or(today);
+                let next = if first.month() == 12 {
+                    NaiveDate::from_ymd_opt(first.year() + 1, 1, 1)
+                } else {
+                    NaiveDate::from_ymd_opt(first.year(), first.month() + 1, 1)
+                };
+                (first, next.unwrap_or(first + Duration::days(31)))
+            }
+        };
+        (local_midnight(first, timezone), local_midnight(next, timezone))
+    }
+}
+
+/// Midnight of `date` in `timezone`; where a DST change skips midnight, the
+/// first hour that exists
+pub fn local_midnight(date: NaiveDate, timezone: Tz) -> DateTime<Utc> {
+    let midnight = date.and_hms_opt(0, 0, 0).unwrap_or_default();
+    timezone
+        .from_local_datetime(&midnight)
+        .earliest()
+        .or_else(|| timezone.from_local_datetime(&(midnight + Duration::hours(1))).earliest())
+        .map(|local| local.with_timezone(&Utc))
+        .unwrap_or_else(|| Utc.from_utc_datetime(&midnight))
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TaskTime {
+    pub task_id: Uuid,
+    /// Unset for tasks deleted since the time was tracked
+    pub name: Option<String>,
+    pub seconds: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TagTime {
+    pub tag: String,
+    pub seconds: i64,
+}
+
+/// Tracked time in one period, by task and by tag. A task with several tags
+/// counts towards each of them, so tag totals can add up to more than
+/// `total_seconds`
+#[derive(Debug, Clone, Serialize)]
+pub struct TimeReport {
+    pub period: ReportPeriod,
+    pub timezone: String,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub total_seconds: i64,
+    pub by_task: Vec<TaskTime>,
+    pub by_tag: Vec<TagTime>,
+}
+
+impl TimeReport {
+    /// Sum the parts of `entries` inside the period containing `now`
+    pub fn build(
+        period: ReportPeriod,
+        timezone: Tz,
+        now: DateTime<Utc>,
+        entries: &[TimeEntry],
+        tasks: &HashMap<Uuid, Task>,
+    ) -> Self {
+        let (start, end) = period.bounds(now, timezone);
+
+        let mut per_task: HashMap<Uuid, i64> = HashMap::new();
+        for entry in entries {
+            let seconds = entry.seconds_within(start, end, now);
+            if seconds > 0 {
+                *per_task.entry(entry.task_id).or_default() += seconds;
+            }
+        }
+
+        let mut per_tag: BTreeMap<String, i64> = BTreeMap::new();
+        for (task_id, seconds) in &per_task {
+            match tasks.get(task_id).map(|task| &task.tags) {
+                Some(tags) if !tags.is_empty() => {
+                    for tag in tags {
+                        *per_tag.entry(tag.clone()).or_default() += seconds;
+                    }
+                }
+                _ => *per_tag.entry(UNTAGGED.to_string()).or_default() += seconds,
+            }
+        }
+
+        let mut by_task: Vec<TaskTime> = per_task
+            .into_iter()
+            .map(|(task_id, seconds)| TaskTime {
+                task_id,
+                name: tasks.get(&task_id).map(|task| task.name.clone()),
+                seconds,
+            })
+            .collect();
+        by_task.sort_by(|a, b| b.seconds.cmp(&a.seconds).then_with(|| a.name.cmp(&b.name)));
+
+        let mut by_tag: Vec<TagTime> = per_tag.into_iter().map(|(tag, seconds)| TagTime { tag, seconds }).collect();
+        by_tag.sort_by(|a, b| b.seconds.cmp(&a.seconds).then_with(|| a.tag.cmp(&b.tag)));
+
+        Self {
+            period,
+            timezone: timezone.name().to_string(),
+            start,
+            end,
+            total_seconds: by_task.iter().map(|t| t.seconds).sum(),
+            by_task,
+            by_tag,
+        }
+    }
+}
+
+/// An IANA timezone name such as "Europe/Vienna"
+pub fn parse_timezone(name: &str) -> Option<Tz> {
+    name.parse().ok()
+}
+
+/// The user's tracked time in the period containing `now`
+pub async fn time_report(
+    storage: &(dyn Storage + Send + Sync),
+    user_id: Uuid,
+    period: ReportPeriod,
+    timezone: Tz,
+    now: DateTime<Utc>,
+) -> Result<TimeReport> {
+    let (start, end) = period.bounds(now, timezone);
+    let entries = storage.get_time_entries_between(Some(user_id), start, end).await?;
+
+    let mut tasks = HashMap::new();
+    for entry in &entries {
+        if !tasks.contains_key(&entry.task_id) {
+            if let Some(task) = storage.get_task(entry.task_id).await? {
+                tasks.insert(task.id, task);
+            }
+        }
+    }
+    Ok(TimeReport::build(period, timezone, now, &entries, &tasks))
+}
+
+fn name_words(text: &str) -> Vec<String> {
+    text.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty() && !FILLER_WORDS.contains(word))
+        .map(str::to_string)
+        .collect()
+}
+
+// "return" matches "returns", "tax" matches "taxes"
+fn words_match(a: &str, b: &str) -> bool {
+    let (short, long) = if a.len() <= b.len() { (a, b) } else { (b, a) };
+    short == long || (short.len() >= 3 && long.starts_with(short))
+}
+
+/// The task `query` most likely refers to: most of the query's words must
+/// appear in the name, and among those the name with the fewest other words
+/// wins. Filler words like "the" are ignored
+pub fn find_task_by_name<'a>(query: &str, tasks: &'a [Task]) -> Option<&'a Task> {
+    let query = name_words(query);
+    if query.is_empty() {
+        return None;
+    }
+
+    tasks
+        .iter()
+        .filter_map(|task| {
+            let name = name_words(&task.name);
+            let matched = query.iter().filter(|q| name.iter().any(|n| words_match(q, n))).count();
+            let coverage = matched as f64 / query.len() as f64;
+            let extra = name.len().saturating_sub(matched);
+            (coverage >= 0.5).then_some((task, coverage, extra))
+        })
+        .max_by(|a, b| a.1.total_cmp(&b.1).then_with(|| b.2.cmp(&a.2)))
+        .map(|(task, ..)| task)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rusty_ai_common::{TaskPriority, TaskStatus};
+
+    fn task(name: &str, tags: &[&str]) -> Task {
+        Task {
+            id: Uuid::new_v4(),
+            name: name.to_string(),
+            description: String::new(),
+            status: TaskStatus::Pending,
+            priority: TaskPriority::Medium,
+            due_date: None,
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    fn at(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    fn entry(task: &Task, start: &str, end: Option<&str>) -> TimeEntry {
+        TimeEntry {
+            ended_at: end.map(at),
+            ..TimeEntry::start(Uuid::nil(), task.id, None, at(start))
+        }
+    }
+
+    #[test]
+    fn test_report_aggregates_by_local_week() {
+        let taxes = task("File the tax return", &["finance", "home"]);
+        let slides = task("Quarterly slides", &["work"]);
+        let loose = task("Sort the garage", &[]);
+        let tasks: HashMap<Uuid, Task> = [&taxes, &slides, &loose].into_iter().map(|t| (t.id, t.clone())).collect();
+
+        // Wednesday afternoon in Vienna (UTC+2); the local week began at
+        // 2026-10-11 22:00 UTC, two hours before the UTC one
+        let now = at("2026-10-14T12:00:00Z");
+        let entries = vec![
+            // Sunday night local time, entirely before the week
+            entry(&loose, "2026-10-11T20:00:00Z", Some("2026-10-11T21:30:00Z")),
+            // Straddles local midnight: only the hour after it counts
+            entry(&taxes, "2026-10-11T21:00:00Z", Some("2026-10-11T23:00:00Z")),
+            entry(&slides, "2026-10-13T08:00:00Z", Some("2026-10-13T09:30:00Z")),
+            // Still running
+            entry(&taxes, "2026-10-14T11:30:00Z", None),
+        ];
+
+        let report = TimeReport::build(ReportPeriod::Week, chrono_tz::Europe::Vienna, now, &entries, &tasks);
+        assert_eq!(report.start, at("2026-10-11T22:00:00Z"));
+        assert_eq!(report.end, at("2026-10-18T22:00:00Z"));
+        assert_eq!(report.total_seconds, 3 * 3600);
+
+        let by_task: Vec<_> = report.by_task.iter().map(|t| (t.name.as_deref().unwrap(), t.seconds)).collect();
+        assert_eq!(by_task, vec![("File the tax return", 5400), ("Quarterly slides", 5400)]);
+        let by_tag: Vec<_> = report.by_tag.iter().map(|t| (t.tag.as_str(), t.seconds)).collect();
+        assert_eq!(by_tag, vec![("finance", 5400), ("home", 5400), ("work", 5400)]);
+
+        // The same entries over the UTC week: everything tracked on Sunday
+        // evening, UTC, falls before it
+        let report = TimeReport::build(ReportPeriod::Week, chrono_tz::UTC, now, &entries, &tasks);
+        assert_eq!(report.start, at("2026-10-12T00:00:00Z"));
+        assert_eq!(report.total_seconds, 2 * 3600);
+        assert!(report.by_tag.iter().all(|t| t.tag != UNTAGGED));
+    }
+
+    #[test]
+    fn test_month_and_day_bounds() {
+        let now = at("2026-12-31T23:30:00Z");
+        // Already January 1st in Vienna
+        assert_eq!(ReportPeriod::Month.bounds(now, chrono_tz::Europe::Vienna), (at("2026-12-31T23:00:00Z"), at("2027-01-31T23:00:00Z")));
+        assert_eq!(ReportPeriod::Day.bounds(now, chrono_tz::UTC), (at("2026-12-31T00:00:00Z"), at("2027-01-01T00:00:00Z")));
+        assert_eq!(ReportPeriod::parse("Week"), Some(ReportPeriod::Week));
+        assert_eq!(ReportPeriod::parse("fortnight"), None);
+    }
+
+    #[test]
+    fn test_fuzzy_task_lookup() {
+        let tasks = vec![
+            task("Prepare tax return documents", &[]),
+            task("File the tax return", &[]),
+            task("Return library books", &[]),
+        ];
+
+        assert_eq!(find_task_by_name("the tax return", &tasks).unwrap().name, "File the tax return");
+        assert_eq!(find_task_by_name("taxes", &tasks).unwrap().name, "File the tax return");
+        assert_eq!(find_task_by_name("library", &tasks).unwrap().name, "Return library books");
+        assert!(find_task_by_name("the garden", &tasks).is_none());
+        assert!(find_task_by_name("the", &tasks).is_none());
+    }
+}
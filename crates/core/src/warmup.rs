@@ -0,0 +1,475 @@
+use rusty_ai_common::{Result, UserContext};
+use chrono::{DateTime, Timelike, Utc};
+use chrono_tz::Tz;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex, RwLock as StdRwLock};
+use std::time::Duration;
+use tokio::sync::RwLock;
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use crate::context_manager::ContextManager;
+use crate::events::{AssistantEvent, EventBus};
+use crate::flags::{FeatureFlags, Flag};
+use crate::intent_handlers::CompletionProvider;
+use crate::notifications::{Clock, NotificationRouter, SystemClock};
+use crate::storage::Storage;
+
+/// How long the optional model polish may take before the plain greeting is used
+pub const POLISH_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// The assistant message a new session opens with
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionGreeting {
+    pub session_id: Uuid,
+    /// The conversation turn the greeting is stored as
+    pub turn_id: Uuid,
+    pub text: String,
+    pub tasks_due_today: usize,
+    pub tasks_overdue: usize,
+    /// Notifications held back during quiet hours that have not gone out yet
+    pub notifications_waiting: usize,
+    /// Today's briefing, when one has been generated
+    pub briefing_id: Option<Uuid>,
+    /// Composed during the user's quiet hours, so it mentions none of the above
+    pub quiet: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+// What the greeting is about, counted in the user's timezone
+#[derive(Debug, Clone, Default, PartialEq)]
+struct GreetingFacts {
+    tasks_due_today: usize,
+    tasks_overdue: usize,
+    notifications_waiting: usize,
+    briefing_id: Option<Uuid>,
+}
+
+// Opens new sessions with an assistant message about the user's day, built
+// from pending tasks, today's briefing and held-back notifications. The text
+// comes from a template; a completion provider, when set, may rephrase it.
+// Greeting runs in the background so session creation never waits for it,
+// and a session is greeted at most once: only while it has no turns yet.
+pub struct SessionWarmup {
+    storage: Arc<dyn Storage + Send + Sync>,
+    context_manager: Arc<RwLock<ContextManager>>,
+    router: Arc<NotificationRouter>,
+    flags: Arc<FeatureFlags>,
+    events: Arc<EventBus>,
+    clock: Arc<dyn Clock>,
+    polisher: StdRwLock<Option<Arc<dyn CompletionProvider>>>,
+    // Sessions being greeted right now, so overlapping triggers greet once
+    in_progress: Mutex<HashSet<Uuid>>,
+}
+
+impl SessionWarmup {
+    pub fn new(
+        storage: Arc<dyn Storage + Send + Sync>,
+        context_manager: Arc<RwLock<ContextManager>>,
+        router: Arc<NotificationRouter>,
+        flags: Arc<FeatureFlags>,
+        events: Arc<EventBus>,
+    ) -> Self {
+        Self::new_with_clock(storage, context_manager, router, flags, events, Arc::new(SystemClock))
+    }
+
+    pub fn new_with_clock(
+        storage: Arc<dyn Storage + Send + Sync>,
+        context_manager: Arc<RwLock<ContextManager>>,
+        router: Arc<NotificationRouter>,
+        flags: Arc<FeatureFlags>,
+        events: Arc<EventBus>,
+        clock: Arc<dyn Clock>,
+    ) -> Self {
+        Self {
+            storage,
+            context_manager,
+            router,
+            flags,
+            events,
+            clock,
+            polisher: StdRwLock::new(None),
+            in_progress: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Let a model rephrase greetings. Without one the template is used as is
+    pub fn set_polisher(&self, provider: Arc<dyn CompletionProvider>) {
+        *self.polisher.write().unwrap() = Some(provider);
+    }
+
+    /// Greet the session in the background if warm-up is on for the user
+    /// and they are not in focus mode
+    pub fn start(self: &Arc<Self>, user_id: Uuid, session_id: Uuid) {
+        if !self.flags.enabled(Flag::SessionWarmup, user_id) || self.router.in_focus(user_id) {
+            return;
+        }
+
+        let warmup = self.clone();
+        tokio::spawn(async move {
+            if let Err(e) = warmup.greet(session_id).await {
+                warn!("Failed to greet session {}: {}", session_id, e);
+            }
+        });
+    }
+
+    /// Compose, store and announce the opening message. None when the
+    /// session is unknown, already has turns or is being greeted already
+    pub async fn greet(&self, session_id: Uuid) -> Result<Option<SessionGreeting>> {
+        if !self.in_progress.lock().unwrap().insert(session_id) {
+            return Ok(None);
+        }
+        let greeting = self.compose_and_store(session_id).await;
+        self.in_progress.lock().unwrap().remove(&session_id);
+        greeting
+    }
+
+    async fn compose_and_store(&self, session_id: Uuid) -> Result<Option<SessionGreeting>> {
+        let (user_id, context) = {
+            let context_manager = self.context_manager.read().await;
+            let Ok(session) = context_manager.get_session(session_id).await else {
+                debug!("Not greeting unknown session {}", session_id);
+                return Ok(None);
+            };
+            if session.turn_count > 0 {
+                return Ok(None);
+            }
+            (session.user_id, session.context.clone())
+        };
+
+        let preferences = &context.preferences;
+        let tz: Tz = preferences.timezone.parse().unwrap_or_else(|_| {
+            warn!("Unknown timezone '{}', using UTC for the session greeting", preferences.timezone);
+            Tz::UTC
+        });
+        let local_now = self.clock.now().with_timezone(&tz);
+        let quiet = match &preferences.notification_settings.quiet_hours {
+            Some(quiet_hours) => quiet_hours.contains(local_now.time())?,
+            None => false,
+        };
+
+        let today = local_now.date_naive();
+        let mut facts = GreetingFacts {
+            notifications_waiting: self.router.pending_for(user_id),
+            ..GreetingFacts::default()
+        };
+        for task in self.storage.get_pending_tasks().await? {
+            match task.due_date.map(|due| due.with_timezone(&tz).date_naive()) {
+                Some(due) if due == today => facts.tasks_due_today += 1,
+                Some(due) if due < today => facts.tasks_overdue += 1,
+                _ => {}
+            }
+        }
+        facts.briefing_id = self
+            .storage
+            .get_latest_briefing()
+            .await?
+            .filter(|briefing| briefing.date.with_timezone(&tz).date_naive() == today)
+            .map(|briefing| briefing.id);
+
+        let template = compose(&facts, local_now.hour(), quiet);
+        // Quiet hours get the plain template; there is nothing to dress up
+        let text = if quiet { template } else { self.polish(template, &context).await };
+
+        let turn_id = self
+            .context_manager
+            .write()
+            .await
+            .add_opening_message(session_id, text.clone())
+            .await?;
+        // The user spoke first while the greeting was being composed
+        let Some(turn_id) = turn_id else {
+            return Ok(None);
+        };
+
+        let greeting = SessionGreeting {
+            session_id,
+            turn_id,
+            text,
+            tasks_due_today: facts.tasks_due_today,
+            tasks_overdue: facts.tasks_overdue,
+            notifications_waiting: facts.notifications_waiting,
+            briefing_id: facts.briefing_id,
+            quiet,
+            created_at: self.clock.now(),
+        };
+        self.events.publish(AssistantEvent::SessionGreeting { user_id, greeting: greeting.clone() });
+        info!("Greeted session {} of user {}", session_id, user_id);
+        Ok(Some(greeting))
+    }
+
+    // The model's rewording when it arrives in time and keeps every count,
+    // otherwise the template
+    async fn polish(&self, template: String, context: &UserContext) -> String {
+        let Some(provider) = self.polisher.read().unwrap().clone() else {
+            return template;
+        };
+        let prompt = format!(
+            "Rephrase this greeting as one or two friendly sentences in the same language. \
+             Keep every number as it is and add no new information:\n{}",
+            template
+        );
+
+        match tokio::time::timeout(POLISH_TIMEOUT, provider.complete(&prompt, context)).await {
+            Ok(Ok(polished)) if keeps_numbers(&template, polished.trim()) => polished.trim().to_string(),
+            Ok(Ok(_)) => {
+                debug!("Polished greeting changed its counts; using the template");
+                template
+            }
+            Ok(Err(e)) => {
+                warn!("Failed to polish session greeting: {}", e);
+                template
+            }
+            Err(_) => {
+                warn!("Polishing the session greeting timed out after {:?}", POLISH_TIMEOUT);
+                template
+            }
+        }
+    }
+}
+
+// The template greeting. During quiet hours it makes no suggestions
+fn compose(facts: &GreetingFacts, local_hour: u32, quiet: bool) -> String {
+    if quiet {
+        return "Hello. I'm here if you need anything.".to_string();
+    }
+
+    let salutation = match local_hour {
+        5..=11 => "Good morning",
+        12..=17 => "Good afternoon",
+        _ => "Good evening",
+    };
+    let mut items = Vec::new();
+    if facts.tasks_due_today > 0 {
+        items.push(count(facts.tasks_due_today, "task", "tasks") + " due today");
+    }
+    if facts.tasks_overdue > 0 {
+        items.push(format!("{} overdue", facts.tasks_overdue));
+    }
+    if facts.notifications_waiting > 0 {
+        items.push(count(facts.notifications_waiting, "notification", "notifications") + " waiting");
+    }
+
+    let mut text = match items.len() {
+        0 => format!("{} — nothing is due today.", salutation),
+        _ => format!("{} — you have {}.", salutation, join_items(&items)),
+    };
+    if facts.briefing_id.is_some() {
+        text.push_str(" Today's briefing is ready.");
+    }
+    text
+}
+
+fn count(n: usize, one: &str, many: &str) -> String {
+    format!("{} {}", n, if n == 1 { one } else { many })
+}
+
+// "a", "a and b", "a, b and c"
+fn join_items(items: &[String]) -> String {
+    match items.split_last() {
+        Some((last, [])) => last.clone(),
+        Some((last, rest)) => format!("{} and {}", rest.join(", "), last),
+        None => String::new(),
+    }
+}
+
+fn keeps_numbers(template: &str, polished: &str) -> bool {
+    let numbers = |text: &str| {
+        let mut numbers: Vec<String> = text
+            .split(|c: char| !c.is_ascii_digit())
+            .filter(|part| !part.is_empty())
+            .map(str::to_string)
+            .collect();
+        numbers.sort();
+        numbers
+    };
+    !polished.is_empty() && numbers(template) == numbers(polished)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flags::FlagOverride;
+    use crate::notifications::{LoggingSink, Notification};
+    use crate::storage::{SqliteStorage, StorageConfig};
+    use crate::test_support::{at, TestClock};
+    use async_trait::async_trait;
+    use rusty_ai_common::{
+        DailyBriefing, NotificationCategory, NotificationChannel, NotificationSettings, QuietHours, Task, TaskPriority,
+        TaskStatus, UserPreferences, VoiceSettings,
+    };
+
+    struct ShoutingModel;
+
+    #[async_trait]
+    impl CompletionProvider for ShoutingModel {
+        async fn complete(&self, prompt: &str, _context: &UserContext) -> Result<String> {
+            Ok(prompt.lines().last().unwrap_or_default().to_uppercase())
+        }
+    }
+
+    fn preferences() -> UserPreferences {
+        UserPreferences {
+            language: "en".to_string(),
+            timezone: "Europe/Berlin".to_string(),
+            voice_settings: VoiceSettings { enabled: false, voice_id: "default".to_string(), speed: 1.0, pitch: 1.0 },
+            notification_settings: NotificationSettings {
+                enabled: true,
+                channels: vec![NotificationChannel::InApp],
+                quiet_hours: Some(QuietHours { start: "22:00".to_string(), end: "07:00".to_string() }),
+                routing: Default::default(),
+                knowledge_digest: Default::default(),
+            },
+        }
+    }
+
+    fn task(name: &str, status: TaskStatus, due: Option<&str>) -> Task {
+        let now = Utc::now();
+        Task {
+            id: Uuid::new_v4(),
+            name: name.to_string(),
+            description: String::new(),
+            status,
+            priority: TaskPriority::Medium,
+            due_date: due.map(at),
+            tags: vec![],
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    struct Fixture {
+        warmup: Arc<SessionWarmup>,
+        context_manager: Arc<RwLock<ContextManager>>,
+        router: Arc<NotificationRouter>,
+        events: Arc<EventBus>,
+    }
+
+    // Tasks and a briefing for Tuesday 2024-03-12 in Berlin
+    async fn fixture(now: &str) -> Fixture {
+        let config =
+            StorageConfig { database_url: "sqlite::memory:".to_string(), max_connections: 1, ..Default::default() };
+        let storage: Arc<dyn Storage + Send + Sync> = Arc::new(SqliteStorage::new(&config).await.unwrap());
+        for task in [
+            task("Pay rent", TaskStatus::Pending, Some("2024-03-12T09:00:00Z")),
+            // 23:30 in Berlin, still today
+            task("Call the bank", TaskStatus::Pending, Some("2024-03-12T22:30:00Z")),
+            task("Renew passport", TaskStatus::Pending, Some("2024-03-11T09:00:00Z")),
+            task("Plan holidays", TaskStatus::Pending, Some("2024-03-20T09:00:00Z")),
+            task("Buy milk", TaskStatus::Pending, None),
+            task("File taxes", TaskStatus::Completed, Some("2024-03-12T09:00:00Z")),
+        ] {
+            storage.store_task(&task).await.unwrap();
+        }
+        storage
+            .store_briefing(&DailyBriefing {
+                id: Uuid::new_v4(),
+                date: at("2024-03-12T05:00:00Z"),
+                sections: vec![],
+                generated_at: at("2024-03-12T05:00:00Z"),
+                generation_report: None,
+            })
+            .await
+            .unwrap();
+
+        let clock = TestClock::at(now);
+        let context_manager = Arc::new(RwLock::new(ContextManager::new()));
+        let router = Arc::new(NotificationRouter::new_with_clock(clock.clone(), Arc::new(LoggingSink), None));
+        let flags = Arc::new(FeatureFlags::default());
+        flags.set(Flag::SessionWarmup, Some(FlagOverride { enabled: Some(true), ..Default::default() })).await.unwrap();
+        let events = Arc::new(EventBus::default());
+        let warmup = Arc::new(SessionWarmup::new_with_clock(
+            storage,
+            context_manager.clone(),
+            router.clone(),
+            flags,
+            events.clone(),
+            clock,
+        ));
+        Fixture { warmup, context_manager, router, events }
+    }
+
+    async fn new_session(fixture: &Fixture) -> (Uuid, Uuid) {
+        let user_id = Uuid::new_v4();
+        let session_id = fixture.context_manager.write().await.create_session(user_id, preferences()).await.unwrap();
+        (user_id, session_id)
+    }
+
+    #[tokio::test]
+    async fn test_new_session_opens_with_one_greeting_from_the_fixture_counts() {
+        // 08:30 in Berlin
+        let fixture = fixture("2024-03-12T07:30:00Z").await;
+        let mut events = fixture.events.subscribe();
+        let (user_id, session_id) = new_session(&fixture).await;
+
+        let greeting = fixture.warmup.greet(session_id).await.unwrap().unwrap();
+        assert_eq!((greeting.tasks_due_today, greeting.tasks_overdue), (2, 1));
+        assert!(greeting.briefing_id.is_some());
+        assert!(!greeting.quiet);
+        assert_eq!(greeting.text, "Good morning — you have 2 tasks due today and 1 overdue. Today's briefing is ready.");
+
+        let history = fixture.context_manager.read().await.get_conversation_history(session_id, None).await.unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].id, greeting.turn_id);
+        assert!(history[0].user_input.is_empty());
+        assert_eq!(history[0].assistant_response, greeting.text);
+        match events.try_recv().unwrap() {
+            AssistantEvent::SessionGreeting { user_id: owner, greeting: sent } => {
+                assert_eq!(owner, user_id);
+                assert_eq!(sent.turn_id, greeting.turn_id);
+            }
+            other => panic!("unexpected event {:?}", other),
+        }
+
+        // Later triggers find the greeting already there
+        assert!(fixture.warmup.greet(session_id).await.unwrap().is_none());
+        fixture.warmup.start(user_id, session_id);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let history = fixture.context_manager.read().await.get_conversation_history(session_id, None).await.unwrap();
+        assert_eq!(history.len(), 1);
+        assert!(events.try_recv().is_err());
+
+        // A session the user already spoke in is not greeted
+        let (_, session_id) = new_session(&fixture).await;
+        fixture
+            .context_manager
+            .write()
+            .await
+            .add_conversation_turn(session_id, "hi".to_string(), "Hello!".to_string(), rusty_ai_common::Intent::Unknown)
+            .await
+            .unwrap();
+        assert!(fixture.warmup.greet(session_id).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_quiet_hours_greeting_makes_no_suggestions() {
+        // 03:00 in Berlin, with a notification held back until 07:00
+        let fixture = fixture("2024-03-12T02:00:00Z").await;
+        fixture.warmup.set_polisher(Arc::new(ShoutingModel));
+        let (user_id, session_id) = new_session(&fixture).await;
+        let decision = fixture
+            .router
+            .route(&preferences(), Notification::new(user_id, NotificationCategory::Reminder, "Rent", "Due today"))
+            .await
+            .unwrap();
+        assert!(matches!(decision, crate::notifications::RoutingDecision::Deferred { .. }));
+
+        let greeting = fixture.warmup.greet(session_id).await.unwrap().unwrap();
+        assert!(greeting.quiet);
+        assert_eq!(greeting.notifications_waiting, 1);
+        assert_eq!(greeting.text, "Hello. I'm here if you need anything.");
+    }
+
+    #[test]
+    fn test_template_and_polish_guard() {
+        let facts = GreetingFacts { tasks_due_today: 1, notifications_waiting: 3, ..GreetingFacts::default() };
+        assert_eq!(compose(&facts, 14, false), "Good afternoon — you have 1 task due today and 3 notifications waiting.");
+        assert_eq!(compose(&GreetingFacts::default(), 20, false), "Good evening — nothing is due today.");
+
+        let template = compose(&facts, 14, false);
+        assert!(keeps_numbers(&template, "Afternoon! 3 notifications and 1 task for today."));
+        assert!(!keeps_numbers(&template, "Afternoon! 2 notifications and 1 task for today."));
+        assert!(!keeps_numbers(&template, ""));
+    }
+}
@@ -4,6 +4,6 @@ pub mod document_processor;
 pub mod semantic_search;
 pub mod vector_store;
 
-pub use document_processor::DocumentProcessor;
+pub use document_processor::{DocumentProcessor, DocumentType, ExtractionError, ProcessedDocument};
 pub use semantic_search::SemanticSearch;
 pub use vector_store::VectorStore;
\ No newline at end of file
@@ -0,0 +1,75 @@
+;; Plugin that prints, used by the output capture tests: `say` writes its
+;; input to stdout and "done" to stderr, `fail` writes its input and traps.
+;; Same layout and allocator as echo.wat.
+(module
+  (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+  (memory (export "memory") 1)
+  (global $next (mut i32) (i32.const 1024))
+
+  (data (i32.const 0) "{\"functions\":[{\"name\":\"say\",\"description\":\"Prints its input to stdout and returns it\"},{\"name\":\"fail\",\"description\":\"Prints its input to stdout and traps\"}]}")
+  (data (i32.const 256) "{\"id\":\"chatty\",\"name\":\"Chatty\",\"version\":\"0.1.0\",\"description\":\"Prints what it is given\",\"author\":\"RUSTY-AI\",\"license\":\"MIT\"}")
+  (data (i32.const 512) "done\n")
+
+  (func (export "alloc") (param $len i32) (result i32)
+    (local $ptr i32)
+    global.get $next
+    local.set $ptr
+    global.get $next
+    local.get $len
+    i32.add
+    global.set $next
+    local.get $ptr)
+
+  (func $pack (param $ptr i32) (param $len i32) (result i64)
+    local.get $ptr
+    i64.extend_i32_u
+    i64.const 32
+    i64.shl
+    local.get $len
+    i64.extend_i32_u
+    i64.or)
+
+  ;; Writes the bytes to the descriptor through a single iovec at 768
+  (func $write (param $fd i32) (param $ptr i32) (param $len i32)
+    i32.const 768
+    local.get $ptr
+    i32.store
+    i32.const 772
+    local.get $len
+    i32.store
+    local.get $fd
+    i32.const 768
+    i32.const 1
+    i32.const 776
+    call $fd_write
+    drop)
+
+  (func (export "get_metadata") (result i64)
+    i32.const 256
+    i32.const 125
+    call $pack)
+
+  (func (export "list_functions") (param i32 i32) (result i64)
+    i32.const 0
+    i32.const 157
+    call $pack)
+
+  (func (export "say") (param $ptr i32) (param $len i32) (result i64)
+    i32.const 1
+    local.get $ptr
+    local.get $len
+    call $write
+    i32.const 2
+    i32.const 512
+    i32.const 5
+    call $write
+    local.get $ptr
+    local.get $len
+    call $pack)
+
+  (func (export "fail") (param $ptr i32) (param $len i32) (result i64)
+    i32.const 1
+    local.get $ptr
+    local.get $len
+    call $write
+    unreachable))
@@ -0,0 +1,68 @@
+;; Minimal plugin following the host ABI, used by the plugin tests.
+;;
+;; The JSON documents live in data segments; `alloc` is a bump allocator
+;; above them that never frees, which is enough for short test sessions.
+;; Metadata comes from the `get_metadata` export, the fallback for modules
+;; without a `rusty-ai-plugin-metadata` section.
+(module
+  (memory (export "memory") 1)
+  (global $next (mut i32) (i32.const 1024))
+
+  (data (i32.const 0) "{\"functions\":[{\"name\":\"echo\",\"description\":\"Returns its input unchanged\",\"input_schema\":{\"type\":\"object\"},\"public\":true},{\"name\":\"version\",\"description\":\"Reports which build of the fixture is loaded\"}]}")
+  (data (i32.const 256) "{\"id\":\"echo\",\"name\":\"Echo\",\"version\":\"0.1.0\",\"description\":\"Returns its input unchanged\",\"author\":\"RUSTY-AI\",\"license\":\"MIT\"}")
+  (data (i32.const 512) "{\"version\":1}")
+
+  (func (export "alloc") (param $len i32) (result i32)
+    (local $ptr i32)
+    global.get $next
+    local.set $ptr
+    global.get $next
+    local.get $len
+    i32.add
+    global.set $next
+    local.get $ptr)
+
+  ;; Output location: pointer in the high 32 bits, length in the low 32
+  (func $pack (param $ptr i32) (param $len i32) (result i64)
+    local.get $ptr
+    i64.extend_i32_u
+    i64.const 32
+    i64.shl
+    local.get $len
+    i64.extend_i32_u
+    i64.or)
+
+  (func (export "get_metadata") (result i64)
+    i32.const 256
+    i32.const 125
+    call $pack)
+
+  (func (export "list_functions") (param i32 i32) (result i64)
+    i32.const 0
+    i32.const 202
+    call $pack)
+
+  (func (export "echo") (param $ptr i32) (param $len i32) (result i64)
+    local.get $ptr
+    local.get $len
+    call $pack)
+
+  (func (export "version") (param i32 i32) (result i64)
+    i32.const 512
+    i32.const 13
+    call $pack)
+
+  ;; Asks for 2048 more pages (128MB); for the memory limit tests
+  (func (export "grow") (param i32 i32) (result i64)
+    i32.const 2048
+    memory.grow
+    drop
+    i32.const 0
+    i32.const 0
+    call $pack)
+
+  ;; Never returns; for the CPU limit tests
+  (func (export "spin") (param i32 i32) (result i64)
+    (loop $forever
+      br $forever)
+    unreachable))
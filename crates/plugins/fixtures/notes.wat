@@ -0,0 +1,67 @@
+;; Plugin using the `rusty_ai` host imports, used by the host function
+;; tests: it keeps one note in its key-value storage and proxies searches
+;; to the knowledge base. Same layout and allocator as echo.wat.
+(module
+  (import "rusty_ai" "kv_get" (func $kv_get (param i32 i32) (result i64)))
+  (import "rusty_ai" "kv_set" (func $kv_set (param i32 i32 i32 i32) (result i32)))
+  (import "rusty_ai" "knowledge_search" (func $knowledge_search (param i32 i32) (result i64)))
+  (memory (export "memory") 1)
+  (global $next (mut i32) (i32.const 1024))
+
+  (data (i32.const 0) "{\"functions\":[{\"name\":\"remember\",\"description\":\"Stores its input as the note\"},{\"name\":\"recall\",\"description\":\"Returns the stored note\"},{\"name\":\"search\",\"description\":\"Searches the knowledge base for its input\"}]}")
+  (data (i32.const 256) "{\"id\":\"notes\",\"name\":\"Notes\",\"version\":\"0.1.0\",\"description\":\"Keeps one note and searches the knowledge base\",\"author\":\"RUSTY-AI\",\"license\":\"MIT\",\"capabilities\":[\"knowledge_access\"]}")
+  (data (i32.const 512) "note")
+
+  (func (export "alloc") (param $len i32) (result i32)
+    (local $ptr i32)
+    global.get $next
+    local.set $ptr
+    global.get $next
+    local.get $len
+    i32.add
+    global.set $next
+    local.get $ptr)
+
+  (func $pack (param $ptr i32) (param $len i32) (result i64)
+    local.get $ptr
+    i64.extend_i32_u
+    i64.const 32
+    i64.shl
+    local.get $len
+    i64.extend_i32_u
+    i64.or)
+
+  (func (export "get_metadata") (result i64)
+    i32.const 256
+    i32.const 182
+    call $pack)
+
+  (func (export "list_functions") (param i32 i32) (result i64)
+    i32.const 0
+    i32.const 214
+    call $pack)
+
+  ;; Stores the input under "note"; traps unless the host reports KV_OK
+  (func (export "remember") (param $ptr i32) (param $len i32) (result i64)
+    i32.const 512
+    i32.const 4
+    local.get $ptr
+    local.get $len
+    call $kv_set
+    if
+      unreachable
+    end
+    i32.const 0
+    i32.const 0
+    call $pack)
+
+  ;; The stored note, or no output when there is none
+  (func (export "recall") (param i32 i32) (result i64)
+    i32.const 512
+    i32.const 4
+    call $kv_get)
+
+  (func (export "search") (param $ptr i32) (param $len i32) (result i64)
+    local.get $ptr
+    local.get $len
+    call $knowledge_search))
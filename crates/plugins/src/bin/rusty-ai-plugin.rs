@@ -0,0 +1,39 @@
+// Plugin development tools:
+//   `rusty-ai-plugin dev <plugin.wasm>` loads a plugin, watches it for rebuilds
+//   and reads `call <function> <json>` commands from stdin.
+//   `rusty-ai-plugin scaffold <name> [--dir <path>]` generates a Rust plugin project.
+use rusty_ai_plugins::{dev, scaffold};
+use std::path::PathBuf;
+
+fn usage() -> ! {
+    eprintln!("usage: rusty-ai-plugin dev <plugin.wasm>");
+    eprintln!("       rusty-ai-plugin scaffold <name> [--dir <path>]");
+    std::process::exit(2);
+}
+
+#[tokio::main]
+async fn main() {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+
+    let result = match args.as_slice() {
+        ["dev", path] => dev::run_dev(path).await,
+        ["scaffold", name] => scaffold::scaffold_plugin(name, PathBuf::from(".")).map(print_next_steps),
+        ["scaffold", name, "--dir", dir] => scaffold::scaffold_plugin(name, PathBuf::from(dir)).map(print_next_steps),
+        _ => usage(),
+    };
+
+    if let Err(e) = result {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+fn print_next_steps(root: PathBuf) {
+    let crate_name = root.file_name().unwrap_or_default().to_string_lossy().replace('-', "_");
+    println!("Created {}", root.display());
+    println!("  cd {}", root.display());
+    println!("  cargo test");
+    println!("  cargo build --release --target wasm32-unknown-unknown");
+    println!("  rusty-ai-plugin dev target/wasm32-unknown-unknown/release/{}.wasm", crate_name);
+}
@@ -0,0 +1,240 @@
+//! Circuit breaking for plugins that keep failing.
+//!
+//! Every loaded version counts its consecutive failed calls. Once the count
+//! reaches the failure threshold the circuit opens: calls fail at once,
+//! without reaching the plugin, until the cooldown has passed. Then the
+//! circuit is half-open and lets a single probe call through. A probe that
+//! succeeds closes the circuit again; one that fails, or is abandoned
+//! before it reports, reopens it for another cooldown.
+
+use rusty_ai_common::{AssistantError, Result};
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// Consecutive failures that open a plugin's circuit unless configured otherwise
+pub const DEFAULT_FAILURE_THRESHOLD: u32 = 5;
+
+/// How long an open circuit fails calls before letting a probe through
+pub const DEFAULT_COOLDOWN: Duration = Duration::from_secs(30);
+
+/// When circuits open and how long they stay open
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BreakerConfig {
+    pub failure_threshold: u32,
+    pub cooldown: Duration,
+}
+
+impl Default for BreakerConfig {
+    fn default() -> Self {
+        Self { failure_threshold: DEFAULT_FAILURE_THRESHOLD, cooldown: DEFAULT_COOLDOWN }
+    }
+}
+
+/// Whether calls reach the plugin
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitState {
+    /// Calls go through
+    #[default]
+    Closed,
+    /// Calls fail at once until the cooldown has passed
+    Open,
+    /// The cooldown has passed; the next call is a probe
+    HalfOpen,
+}
+
+/// The circuit of one plugin version
+#[derive(Debug, Default)]
+pub struct CircuitBreaker {
+    state: Mutex<BreakerState>,
+}
+
+#[derive(Debug, Default)]
+struct BreakerState {
+    consecutive_failures: u32,
+    /// Set while the circuit is open or half-open
+    opened_at: Option<Instant>,
+    /// A probe call is running in the half-open circuit
+    probing: bool,
+}
+
+/// A call `CircuitBreaker::admit` let through, whose outcome is counted
+/// with `record`. A probe dropped without a recorded outcome, e.g. because
+/// the caller stopped waiting for it, counts as failed, so the circuit never
+/// stays half-open with no probe running. Other calls dropped that way are
+/// not counted either way
+#[must_use = "the outcome of the call must be recorded"]
+pub struct BreakerPermit<'a> {
+    breaker: &'a CircuitBreaker,
+    plugin: &'a str,
+    config: BreakerConfig,
+    probe: bool,
+    recorded: bool,
+}
+
+impl BreakerPermit<'_> {
+    /// Count the outcome of the call
+    pub fn record(self, succeeded: bool) {
+        self.record_at(succeeded, Instant::now())
+    }
+
+    fn record_at(mut self, succeeded: bool, now: Instant) {
+        self.recorded = true;
+        self.breaker.record_at(self.plugin, succeeded, &self.config, now);
+    }
+}
+
+impl Drop for BreakerPermit<'_> {
+    fn drop(&mut self) {
+        if self.probe && !self.recorded {
+            warn!("Probe call to plugin {} was abandoned; counting it as failed", self.plugin);
+            self.breaker.record_at(self.plugin, false, &self.config, Instant::now());
+        }
+    }
+}
+
+impl CircuitBreaker {
+    /// Let a call to `plugin` through, or fail it while the circuit is open
+    /// or a probe is already running
+    pub fn admit<'a>(&'a self, plugin: &'a str, config: &BreakerConfig) -> Result<BreakerPermit<'a>> {
+        self.admit_at(plugin, config, Instant::now())
+    }
+
+    /// The circuit's state and the failures counted towards opening it
+    pub fn status(&self, config: &BreakerConfig) -> (CircuitState, u32) {
+        self.status_at(config, Instant::now())
+    }
+
+    fn admit_at<'a>(&'a self, plugin: &'a str, config: &BreakerConfig, now: Instant) -> Result<BreakerPermit<'a>> {
+        let permit = |probe| BreakerPermit { breaker: self, plugin, config: *config, probe, recorded: false };
+        let mut state = self.state.lock().unwrap();
+        let Some(opened_at) = state.opened_at else {
+            return Ok(permit(false));
+        };
+        let open_for = now.saturating_duration_since(opened_at);
+        if open_for < config.cooldown {
+            return Err(AssistantError::Plugin(format!(
+                "{} failed {} calls in a row; calls are refused for another {}s",
+                plugin,
+                state.consecutive_failures,
+                (config.cooldown - open_for).as_secs().max(1)
+            )));
+        }
+        if state.probing {
+            return Err(AssistantError::Plugin(format!(
+                "{} is being probed after {} failed calls in a row",
+                plugin, state.consecutive_failures
+            )));
+        }
+        info!("Circuit of plugin {} is half-open; letting a probe call through", plugin);
+        state.probing = true;
+        Ok(permit(true))
+    }
+
+    fn record_at(&self, plugin: &str, succeeded: bool, config: &BreakerConfig, now: Instant) {
+        let mut state = self.state.lock().unwrap();
+        if succeeded {
+            if state.opened_at.is_some() {
+                info!("Circuit of plugin {} is closed again after a successful probe", plugin);
+            }
+            *state = BreakerState::default();
+            return;
+        }
+
+        state.consecutive_failures += 1;
+        if state.probing {
+            warn!("Probe call to plugin {} failed; circuit is open for another {:?}", plugin, config.cooldown);
+            state.opened_at = Some(now);
+            state.probing = false;
+        } else if state.opened_at.is_none() && state.consecutive_failures >= config.failure_threshold {
+            warn!(
+                "Circuit of plugin {} is open after {} failed calls in a row; refusing calls for {:?}",
+                plugin, state.consecutive_failures, config.cooldown
+            );
+            state.opened_at = Some(now);
+        }
+    }
+
+    fn status_at(&self, config: &BreakerConfig, now: Instant) -> (CircuitState, u32) {
+        let state = self.state.lock().unwrap();
+        let circuit = match state.opened_at {
+            None => CircuitState::Closed,
+            Some(opened_at) if !state.probing && now.saturating_duration_since(opened_at) < config.cooldown => {
+                CircuitState::Open
+            }
+            Some(_) => CircuitState::HalfOpen,
+        };
+        (circuit, state.consecutive_failures)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: BreakerConfig = BreakerConfig { failure_threshold: 3, cooldown: Duration::from_secs(30) };
+
+    #[test]
+    fn test_circuit_opens_after_consecutive_failures_only() {
+        let breaker = CircuitBreaker::default();
+        let now = Instant::now();
+
+        breaker.record_at("flaky", false, &CONFIG, now);
+        breaker.record_at("flaky", false, &CONFIG, now);
+        breaker.record_at("flaky", true, &CONFIG, now);
+        breaker.record_at("flaky", false, &CONFIG, now);
+        assert_eq!(breaker.status_at(&CONFIG, now), (CircuitState::Closed, 1));
+
+        breaker.record_at("flaky", false, &CONFIG, now);
+        breaker.record_at("flaky", false, &CONFIG, now);
+        assert_eq!(breaker.status_at(&CONFIG, now), (CircuitState::Open, 3));
+        assert!(breaker.admit_at("flaky", &CONFIG, now + Duration::from_secs(29)).is_err());
+    }
+
+    #[test]
+    fn test_one_probe_at_a_time_decides_whether_the_circuit_closes() {
+        let breaker = CircuitBreaker::default();
+        let opened = Instant::now();
+        for _ in 0..3 {
+            breaker.record_at("flaky", false, &CONFIG, opened);
+        }
+
+        // Once the cooldown has passed a single probe goes through
+        let cooled = opened + CONFIG.cooldown;
+        assert_eq!(breaker.status_at(&CONFIG, cooled).0, CircuitState::HalfOpen);
+        let probe = breaker.admit_at("flaky", &CONFIG, cooled).unwrap();
+        assert!(breaker.admit_at("flaky", &CONFIG, cooled).is_err());
+
+        // A failed probe reopens the circuit for a full cooldown
+        probe.record_at(false, cooled);
+        assert_eq!(breaker.status_at(&CONFIG, cooled + Duration::from_secs(29)), (CircuitState::Open, 4));
+
+        let cooled = cooled + CONFIG.cooldown;
+        breaker.admit_at("flaky", &CONFIG, cooled).unwrap().record_at(true, cooled);
+        assert_eq!(breaker.status_at(&CONFIG, cooled), (CircuitState::Closed, 0));
+        breaker.admit_at("flaky", &CONFIG, cooled).unwrap().record_at(true, cooled);
+    }
+
+    #[test]
+    fn test_an_abandoned_probe_reopens_the_circuit() {
+        let breaker = CircuitBreaker::default();
+        let opened = Instant::now();
+        for _ in 0..3 {
+            breaker.record_at("flaky", false, &CONFIG, opened);
+        }
+
+        let cooled = opened + CONFIG.cooldown;
+        drop(breaker.admit_at("flaky", &CONFIG, cooled).unwrap());
+        let (state, failures) = breaker.status(&CONFIG);
+        assert_eq!((state, failures), (CircuitState::Open, 4));
+        assert!(!breaker.state.lock().unwrap().probing);
+
+        // Ordinary calls dropped before they finish count for nothing
+        let breaker = CircuitBreaker::default();
+        breaker.record_at("flaky", false, &CONFIG, opened);
+        drop(breaker.admit_at("flaky", &CONFIG, opened).unwrap());
+        assert_eq!(breaker.status_at(&CONFIG, opened), (CircuitState::Closed, 1));
+    }
+}
@@ -0,0 +1,273 @@
+//! Compiled plugin modules kept on disk between loads.
+//!
+//! Compiling a larger plugin takes hundreds of milliseconds, which every
+//! load and hot reload used to pay. [`ModuleCache`] stores the compiled
+//! artifact under the plugin's checksum, tagged with the engine's
+//! compatibility hash, so a change to either the plugin or the wasmtime
+//! version and configuration makes the old entry unreachable. Entries of
+//! other engines are removed when the cache is opened.
+//!
+//! Deserializing an artifact runs whatever machine code it holds, so every
+//! entry carries an HMAC under a key the host holds and the plugin
+//! directory does not. An entry whose tag does not verify is discarded and
+//! recompiled without being deserialized.
+
+use ring::hmac;
+use ring::rand::{SecureRandom, SystemRandom};
+use rusty_ai_common::{AssistantError, Result};
+use sha2::{Digest, Sha256};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+use tracing::{debug, warn};
+use wasmtime::{Engine, Module};
+
+/// Subdirectory of the plugin directory the cache lives in
+pub const MODULE_CACHE_DIR: &str = "cache";
+
+const ENTRY_EXTENSION: &str = "cwasm";
+
+const TAG_LEN: usize = 32;
+
+/// SHA-256 of plugin bytes, hex encoded; the key of their cache entry
+pub fn checksum(bytes: &[u8]) -> String {
+    hex::encode(Sha256::digest(bytes))
+}
+
+/// Key generated once per process, for hosts that configure none. Entries
+/// tagged with it are only readable until the process exits
+pub fn process_key() -> &'static [u8] {
+    static KEY: OnceLock<[u8; TAG_LEN]> = OnceLock::new();
+    KEY.get_or_init(|| {
+        let mut key = [0u8; TAG_LEN];
+        SystemRandom::new().fill(&mut key).expect("system randomness is unavailable");
+        key
+    })
+}
+
+/// Compiled modules for one engine configuration, keyed by plugin checksum
+pub struct ModuleCache {
+    dir: PathBuf,
+    engine_tag: String,
+    key: hmac::Key,
+    compilations: AtomicU64,
+}
+
+impl ModuleCache {
+    /// Cache in `dir` for modules compiled by `engine`, its entries
+    /// authenticated with `key`
+    pub fn open(dir: impl Into<PathBuf>, engine: &Engine, key: &[u8]) -> Self {
+        // Only needs to be stable for as long as the binary is: another
+        // build recompiles anyway, and its entries are pruned below
+        let mut hasher = DefaultHasher::new();
+        engine.precompile_compatibility_hash().hash(&mut hasher);
+        let cache = Self {
+            dir: dir.into(),
+            engine_tag: format!("{:016x}", hasher.finish()),
+            key: hmac::Key::new(hmac::HMAC_SHA256, key),
+            compilations: AtomicU64::new(0),
+        };
+        cache.prune_other_engines();
+        cache
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Modules compiled rather than read from the cache since it was opened
+    pub fn compilations(&self) -> u64 {
+        self.compilations.load(Ordering::Relaxed)
+    }
+
+    /// The module for `wasm_bytes`, read from the cache when it has an
+    /// authentic entry for `checksum` and compiled (and stored) otherwise.
+    /// The flag is true for a cache hit. Failing to read or write the cache
+    /// only costs the compilation; invalid WebAssembly is an error
+    pub fn load(&self, engine: &Engine, wasm_bytes: &[u8], checksum: &str) -> Result<(Module, bool)> {
+        let path = self.entry_path(checksum);
+        if let Some(compiled) = self.read_entry(&path, checksum) {
+            // SAFETY: the tag verified, so these bytes were written by
+            // `store` under this host's key, from `precompile_module`, for
+            // this checksum; deserializing also checks engine compatibility
+            match unsafe { Module::deserialize(engine, &compiled) } {
+                Ok(module) => {
+                    debug!("Module cache hit for {}", checksum);
+                    return Ok((module, true));
+                }
+                Err(e) => {
+                    warn!("Discarding unreadable module cache entry {:?}: {}", path, e);
+                    let _ = std::fs::remove_file(&path);
+                }
+            }
+        }
+
+        let compiled = engine
+            .precompile_module(wasm_bytes)
+            .map_err(|e| AssistantError::Plugin(format!("Failed to compile module: {}", e)))?;
+        self.compilations.fetch_add(1, Ordering::Relaxed);
+        // SAFETY: the bytes were produced by `precompile_module` on this engine
+        let module = unsafe { Module::deserialize(engine, &compiled) }
+            .map_err(|e| AssistantError::Plugin(format!("Failed to load compiled module: {}", e)))?;
+
+        if let Err(e) = self.store(&path, checksum, &compiled) {
+            warn!("Failed to cache compiled module {}: {}", checksum, e);
+        }
+        Ok((module, false))
+    }
+
+    /// Drop the entry for a checksum no longer in use, e.g. a replaced build
+    pub fn remove(&self, checksum: &str) {
+        let path = self.entry_path(checksum);
+        if path.exists() {
+            if let Err(e) = std::fs::remove_file(&path) {
+                warn!("Failed to remove module cache entry {:?}: {}", path, e);
+            }
+        }
+    }
+
+    fn entry_path(&self, checksum: &str) -> PathBuf {
+        self.dir.join(format!("{}-{}.{}", checksum, self.engine_tag, ENTRY_EXTENSION))
+    }
+
+    // The artifact of an entry whose tag verifies; anything else is removed
+    fn read_entry(&self, path: &Path, checksum: &str) -> Option<Vec<u8>> {
+        let mut entry = std::fs::read(path).ok()?;
+        if entry.len() > TAG_LEN
+            && hmac::verify(&self.key, &self.signed_bytes(checksum, &entry[TAG_LEN..]), &entry[..TAG_LEN]).is_ok()
+        {
+            return Some(entry.split_off(TAG_LEN));
+        }
+        warn!("Discarding module cache entry {:?} that failed authentication", path);
+        let _ = std::fs::remove_file(path);
+        None
+    }
+
+    // The tag covers the engine and checksum as well as the artifact, so an
+    // entry cannot be replayed under another plugin's name
+    fn signed_bytes(&self, checksum: &str, compiled: &[u8]) -> Vec<u8> {
+        [self.engine_tag.as_bytes(), checksum.as_bytes(), compiled].concat()
+    }
+
+    // Written next to the entry and renamed into place, so a load never
+    // sees a partly written artifact. The directory is the host's alone
+    fn store(&self, path: &Path, checksum: &str, compiled: &[u8]) -> std::io::Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&self.dir, std::fs::Permissions::from_mode(0o700))?;
+        }
+        let partial = path.with_extension("partial");
+        let tag = hmac::sign(&self.key, &self.signed_bytes(checksum, compiled));
+        std::fs::write(&partial, [tag.as_ref(), compiled].concat())?;
+        std::fs::rename(&partial, path)
+    }
+
+    fn prune_other_engines(&self) {
+        let entries = match std::fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(_) => return,
+        };
+        let suffix = format!("-{}.{}", self.engine_tag, ENTRY_EXTENSION);
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let is_entry = name.ends_with(&format!(".{}", ENTRY_EXTENSION)) || name.ends_with(".partial");
+            if is_entry && !name.ends_with(&suffix) {
+                debug!("Removing module cache entry of another engine: {}", name);
+                let _ = std::fs::remove_file(entry.path());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::create_plugin_engine;
+    use tempfile::tempdir;
+
+    const KEY: &[u8] = b"module cache key held by the host";
+
+    #[test]
+    fn test_second_load_skips_compilation() {
+        let dir = tempdir().unwrap();
+        let engine = create_plugin_engine().unwrap();
+        let fixture = include_str!("../fixtures/echo.wat").as_bytes();
+        let sum = checksum(fixture);
+
+        let cache = ModuleCache::open(dir.path(), &engine, KEY);
+        let (_, hit) = cache.load(&engine, fixture, &sum).unwrap();
+        assert!(!hit);
+        let (_, hit) = cache.load(&engine, fixture, &sum).unwrap();
+        assert!(hit);
+        assert_eq!(cache.compilations(), 1);
+
+        // A fresh cache over the same directory, as after a restart
+        let reopened = ModuleCache::open(dir.path(), &engine, KEY);
+        assert!(reopened.load(&engine, fixture, &sum).unwrap().1);
+        assert_eq!(reopened.compilations(), 0);
+    }
+
+    #[test]
+    fn test_stale_and_corrupt_entries_are_recompiled() {
+        let dir = tempdir().unwrap();
+        let engine = create_plugin_engine().unwrap();
+        let fixture = include_str!("../fixtures/echo.wat").as_bytes();
+        let sum = checksum(fixture);
+
+        // Left behind by another wasmtime version or configuration
+        let foreign = dir.path().join(format!("{}-0000000000000000.cwasm", sum));
+        std::fs::write(&foreign, b"old artifact").unwrap();
+        let cache = ModuleCache::open(dir.path(), &engine, KEY);
+        assert!(!foreign.exists());
+
+        std::fs::write(cache.entry_path(&sum), b"truncated").unwrap();
+        let (_, hit) = cache.load(&engine, fixture, &sum).unwrap();
+        assert!(!hit);
+        assert!(cache.load(&engine, fixture, &sum).unwrap().1);
+
+        cache.remove(&sum);
+        assert!(!cache.load(&engine, fixture, &sum).unwrap().1);
+        assert_eq!(cache.compilations(), 2);
+    }
+
+    #[test]
+    fn test_entries_without_a_valid_tag_are_never_deserialized() {
+        let dir = tempdir().unwrap();
+        let engine = create_plugin_engine().unwrap();
+        let fixture = include_str!("../fixtures/echo.wat").as_bytes();
+        let sum = checksum(fixture);
+
+        // Planted by someone who can write the plugin directory but does
+        // not hold the key: a genuine artifact, tagged with another key
+        let forger = ModuleCache::open(dir.path(), &engine, b"someone else's key");
+        forger.load(&engine, fixture, &sum).unwrap();
+        let cache = ModuleCache::open(dir.path(), &engine, KEY);
+        assert!(!cache.load(&engine, fixture, &sum).unwrap().1);
+        assert_eq!(cache.compilations(), 1);
+
+        // An authentic entry moved under another checksum
+        let other = include_str!("../fixtures/chatty.wat").as_bytes();
+        let other_sum = checksum(other);
+        std::fs::copy(cache.entry_path(&sum), cache.entry_path(&other_sum)).unwrap();
+        assert!(!cache.load(&engine, other, &other_sum).unwrap().1);
+        assert!(cache.load(&engine, fixture, &sum).unwrap().1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_cache_directory_is_private_to_the_host() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempdir().unwrap();
+        let engine = create_plugin_engine().unwrap();
+        let fixture = include_str!("../fixtures/echo.wat").as_bytes();
+        let cache = ModuleCache::open(dir.path().join(MODULE_CACHE_DIR), &engine, KEY);
+        cache.load(&engine, fixture, &checksum(fixture)).unwrap();
+        let mode = std::fs::metadata(cache.dir()).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o700);
+    }
+}
@@ -1,16 +1,22 @@
-use rusty_ai_common::{Result, AssistantError};
+use futures::future::BoxFuture;
+use rusty_ai_common::{Result, AssistantError, PluginEvent};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
-use tokio::sync::{mpsc, oneshot, RwLock};
+use tokio::sync::{mpsc, oneshot, Notify, RwLock};
 use tracing::{debug, warn, error, instrument};
 use uuid::Uuid;
 
+/// Broadcasts held for a subscriber that has not handled them yet
+pub const DEFAULT_SUBSCRIBER_BUFFER: usize = 256;
+
 /// Plugin communication interface for bidirectional messaging
 pub struct PluginCommunication {
     channels: Arc<RwLock<HashMap<String, PluginChannel>>>,
     message_router: MessageRouter,
     serializer: MessageSerializer,
+    subscriber_buffer: usize,
 }
 
 /// Communication channel for a specific plugin
@@ -25,7 +31,24 @@ pub struct PluginChannel {
 /// Message router for handling plugin-to-plugin and plugin-to-host communication
 pub struct MessageRouter {
     routes: Arc<RwLock<HashMap<String, Vec<String>>>>,
-    broadcast_subscribers: Arc<RwLock<Vec<String>>>,
+    broadcast_subscribers: Arc<RwLock<HashMap<String, Subscriber>>>,
+}
+
+/// A plugin subscribed to broadcasts. Each has its own queue and delivery
+/// task, so a slow or failing plugin holds up no one else
+struct Subscriber {
+    event_types: Vec<String>,
+    queue: Arc<SubscriberQueue>,
+    delivery: tokio::task::JoinHandle<()>,
+}
+
+/// Broadcasts waiting for one subscriber. A full queue drops its oldest
+/// message to make room, so a stuck plugin holds at most `capacity`
+struct SubscriberQueue {
+    messages: std::sync::Mutex<VecDeque<PluginMessage>>,
+    capacity: usize,
+    dropped: AtomicU64,
+    ready: Notify,
 }
 
 /// Message serializer for converting between different formats
@@ -93,12 +116,25 @@ pub struct PluginResponse {
     pub execution_time: std::time::Duration,
 }
 
+/// Hands a broadcast to the subscribed plugin
+pub type EventCallback = Arc<dyn Fn(PluginMessage) -> BoxFuture<'static, Result<()>> + Send + Sync>;
+
 /// Event subscription for asynchronous communication
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct EventSubscription {
     pub plugin_id: String,
+    /// `MessagePayload::Event` types to receive; empty for every broadcast
     pub event_types: Vec<String>,
-    pub callback: Arc<dyn Fn(PluginMessage) -> Result<()> + Send + Sync>,
+    pub callback: EventCallback,
+}
+
+impl std::fmt::Debug for EventSubscription {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EventSubscription")
+            .field("plugin_id", &self.plugin_id)
+            .field("event_types", &self.event_types)
+            .finish_non_exhaustive()
+    }
 }
 
 impl PluginCommunication {
@@ -108,9 +144,16 @@ impl PluginCommunication {
             channels: Arc::new(RwLock::new(HashMap::new())),
             message_router: MessageRouter::new(),
             serializer: MessageSerializer::new(),
+            subscriber_buffer: DEFAULT_SUBSCRIBER_BUFFER,
         }
     }
     
+    /// Hold up to `capacity` broadcasts per subscriber
+    pub fn with_subscriber_buffer(mut self, capacity: usize) -> Self {
+        self.subscriber_buffer = capacity.max(1);
+        self
+    }
+    
     /// Register a new plugin for communication
     #[instrument(skip(self))]
     pub async fn register_plugin(&self, plugin_id: &str) -> Result<PluginChannel> {
@@ -143,6 +186,7 @@ impl PluginCommunication {
         }
         
         self.message_router.remove_routes(plugin_id).await;
+        self.message_router.remove_broadcast_subscriber(plugin_id).await?;
         
         debug!("Plugin communication unregistered: {}", plugin_id);
         Ok(())
@@ -191,6 +235,15 @@ impl PluginCommunication {
         self.broadcast_to_subscribers(message).await
     }
     
+    /// Broadcast an assistant event as `MessagePayload::Event`, typed by
+    /// `PluginEvent::event_type`
+    pub async fn publish_event(&self, sender: &str, event: &PluginEvent) -> Result<()> {
+        let data = serde_json::to_value(event)
+            .map_err(|e| AssistantError::Plugin(format!("Failed to serialize event: {}", e)))?;
+        let payload = MessagePayload::Event { event_type: event.event_type().to_string(), data };
+        self.broadcast_message(sender, MessageType::Event, payload).await
+    }
+    
     /// Send a request and wait for response
     #[instrument(skip(self, args))]
     pub async fn send_request(
@@ -274,18 +327,17 @@ impl PluginCommunication {
         Ok(())
     }
     
-    /// Broadcast to subscribed plugins only
+    /// Queue a broadcast for every subscriber that wants it. Never waits on
+    /// a subscriber; full queues drop their oldest message instead
     async fn broadcast_to_subscribers(&self, message: PluginMessage) -> Result<()> {
-        let subscribers = self.message_router.get_broadcast_subscribers().await;
-        let channels = self.channels.read().await;
+        let subscribers = self.message_router.broadcast_subscribers.read().await;
         
-        for subscriber_id in subscribers {
-            if let Some(channel) = channels.get(&subscriber_id) {
-                if channel.active {
-                    if let Err(e) = channel.sender.send(message.clone()) {
-                        warn!("Failed to send to subscriber {}: {}", subscriber_id, e);
-                    }
-                }
+        for (subscriber_id, subscriber) in subscribers.iter() {
+            if subscriber_id == &message.sender || !subscriber.wants(&message) {
+                continue;
+            }
+            if subscriber.queue.push(message.clone()) {
+                debug!("Subscriber {} is behind; dropped its oldest broadcast", subscriber_id);
             }
         }
         
@@ -333,9 +385,15 @@ impl PluginCommunication {
         self.message_router.remove_route(from, to).await
     }
     
-    /// Subscribe plugin to broadcast messages
-    pub async fn subscribe_to_broadcasts(&self, plugin_id: &str) -> Result<()> {
-        self.message_router.add_broadcast_subscriber(plugin_id).await
+    /// Subscribe a plugin to broadcasts of the subscription's event types,
+    /// replacing its previous subscription
+    pub async fn subscribe_to_broadcasts(&self, subscription: EventSubscription) -> Result<()> {
+        self.message_router.add_broadcast_subscriber(subscription, self.subscriber_buffer).await
+    }
+    
+    /// Broadcasts a subscriber missed because it fell behind
+    pub async fn dropped_broadcasts(&self, plugin_id: &str) -> Option<u64> {
+        self.message_router.dropped_broadcasts(plugin_id).await
     }
     
     /// Unsubscribe plugin from broadcast messages
@@ -381,7 +439,7 @@ impl MessageRouter {
     pub fn new() -> Self {
         Self {
             routes: Arc::new(RwLock::new(HashMap::new())),
-            broadcast_subscribers: Arc::new(RwLock::new(Vec::new())),
+            broadcast_subscribers: Arc::new(RwLock::new(HashMap::new())),
         }
     }
     
@@ -420,26 +478,106 @@ impl MessageRouter {
         routes.get(from).cloned().unwrap_or_default()
     }
     
-    /// Add broadcast subscriber
-    pub async fn add_broadcast_subscriber(&self, plugin_id: &str) -> Result<()> {
+    /// Add broadcast subscriber, holding up to `capacity` broadcasts for it.
+    /// Replaces the plugin's previous subscription
+    pub async fn add_broadcast_subscriber(&self, subscription: EventSubscription, capacity: usize) -> Result<()> {
+        let queue = Arc::new(SubscriberQueue::new(capacity));
+        let delivery = tokio::spawn({
+            let queue = queue.clone();
+            let EventSubscription { plugin_id, callback, .. } = subscription.clone();
+            async move {
+                loop {
+                    let message = queue.pop().await;
+                    let message_id = message.id.clone();
+                    if let Err(e) = callback(message).await {
+                        warn!("Plugin {} failed to handle broadcast {}: {}", plugin_id, message_id, e);
+                    }
+                }
+            }
+        });
+        
+        let subscriber = Subscriber { event_types: subscription.event_types, queue, delivery };
         let mut subscribers = self.broadcast_subscribers.write().await;
-        if !subscribers.contains(&plugin_id.to_string()) {
-            subscribers.push(plugin_id.to_string());
-        }
+        subscribers.insert(subscription.plugin_id, subscriber);
         Ok(())
     }
     
     /// Remove broadcast subscriber
     pub async fn remove_broadcast_subscriber(&self, plugin_id: &str) -> Result<()> {
         let mut subscribers = self.broadcast_subscribers.write().await;
-        subscribers.retain(|id| id != plugin_id);
+        subscribers.remove(plugin_id);
         Ok(())
     }
     
     /// Get all broadcast subscribers
     pub async fn get_broadcast_subscribers(&self) -> Vec<String> {
         let subscribers = self.broadcast_subscribers.read().await;
-        subscribers.clone()
+        let mut ids: Vec<String> = subscribers.keys().cloned().collect();
+        ids.sort();
+        ids
+    }
+    
+    /// Broadcasts dropped for a subscriber that fell behind; None for
+    /// plugins not subscribed
+    pub async fn dropped_broadcasts(&self, plugin_id: &str) -> Option<u64> {
+        let subscribers = self.broadcast_subscribers.read().await;
+        subscribers.get(plugin_id).map(|subscriber| subscriber.queue.dropped.load(Ordering::Relaxed))
+    }
+}
+
+impl Subscriber {
+    /// Event broadcasts go to subscribers of their type; other broadcasts
+    /// only to subscribers of everything
+    fn wants(&self, message: &PluginMessage) -> bool {
+        if self.event_types.is_empty() {
+            return true;
+        }
+        match &message.payload {
+            MessagePayload::Event { event_type, .. } => self.event_types.contains(event_type),
+            _ => false,
+        }
+    }
+}
+
+impl Drop for Subscriber {
+    fn drop(&mut self) {
+        self.delivery.abort();
+    }
+}
+
+impl SubscriberQueue {
+    fn new(capacity: usize) -> Self {
+        Self {
+            messages: std::sync::Mutex::new(VecDeque::with_capacity(capacity)),
+            capacity: capacity.max(1),
+            dropped: AtomicU64::new(0),
+            ready: Notify::new(),
+        }
+    }
+    
+    /// Queue a message; true if the oldest one was dropped to make room
+    fn push(&self, message: PluginMessage) -> bool {
+        let dropped = {
+            let mut messages = self.messages.lock().unwrap();
+            let dropped = messages.len() >= self.capacity && messages.pop_front().is_some();
+            messages.push_back(message);
+            dropped
+        };
+        if dropped {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+        self.ready.notify_one();
+        dropped
+    }
+    
+    async fn pop(&self) -> PluginMessage {
+        loop {
+            let message = self.messages.lock().unwrap().pop_front();
+            if let Some(message) = message {
+                return message;
+            }
+            self.ready.notified().await;
+        }
     }
 }
 
@@ -484,7 +622,7 @@ impl Default for PluginCommunication {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use tokio::time::{sleep, Duration};
+    use tokio::time::{sleep, timeout, Duration};
     
     #[tokio::test]
     async fn test_plugin_communication_creation() {
@@ -505,16 +643,121 @@ mod tests {
         assert!(routes.contains(&"plugin2".to_string()));
     }
     
+    // A subscription that passes what it receives on to the returned channel
+    fn forwarding(plugin_id: &str, event_types: &[&str]) -> (EventSubscription, mpsc::UnboundedReceiver<PluginMessage>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let callback: EventCallback = Arc::new(move |message| {
+            let tx = tx.clone();
+            Box::pin(async move {
+                tx.send(message).map_err(|e| AssistantError::Plugin(e.to_string()))
+            })
+        });
+        let subscription = EventSubscription {
+            plugin_id: plugin_id.to_string(),
+            event_types: event_types.iter().map(|t| t.to_string()).collect(),
+            callback,
+        };
+        (subscription, rx)
+    }
+    
+    fn event_type(message: &PluginMessage) -> &str {
+        match &message.payload {
+            MessagePayload::Event { event_type, .. } => event_type,
+            other => panic!("not an event: {:?}", other),
+        }
+    }
+    
+    fn task_completed(name: &str) -> PluginEvent {
+        PluginEvent::TaskCompleted { user_id: None, task_id: Uuid::new_v4(), name: name.to_string() }
+    }
+    
     #[tokio::test]
     async fn test_broadcast_subscription() {
         let router = MessageRouter::new();
         
-        router.add_broadcast_subscriber("plugin1").await.unwrap();
+        router.add_broadcast_subscriber(forwarding("plugin1", &[]).0, 8).await.unwrap();
         let subscribers = router.get_broadcast_subscribers().await;
         
         assert!(subscribers.contains(&"plugin1".to_string()));
     }
     
+    #[tokio::test]
+    async fn test_events_reach_only_subscribers_of_their_type() {
+        let comm = PluginCommunication::new();
+        let (tasks, mut task_rx) = forwarding("tasks", &["task_completed"]);
+        let (everything, mut all_rx) = forwarding("everything", &[]);
+        let (briefings, mut briefing_rx) = forwarding("briefings", &["briefing_generated"]);
+        for subscription in [tasks, everything, briefings] {
+            comm.subscribe_to_broadcasts(subscription).await.unwrap();
+        }
+        
+        comm.publish_event("assistant", &task_completed("Pay rent")).await.unwrap();
+        let document = PluginEvent::DocumentUploaded { user_id: Uuid::new_v4(), document_id: Uuid::new_v4(), title: "Lease".to_string() };
+        comm.publish_event("assistant", &document).await.unwrap();
+        // A subscriber does not hear its own broadcasts
+        comm.broadcast_message("everything", MessageType::Broadcast, MessagePayload::Text("hi".to_string())).await.unwrap();
+        
+        let received = timeout(Duration::from_secs(1), task_rx.recv()).await.unwrap().unwrap();
+        assert_eq!(event_type(&received), "task_completed");
+        let MessagePayload::Event { data, .. } = received.payload else { unreachable!() };
+        let event: PluginEvent = serde_json::from_value(data).unwrap();
+        assert!(matches!(event, PluginEvent::TaskCompleted { name, .. } if name == "Pay rent"));
+        
+        for expected in ["task_completed", "document_uploaded"] {
+            let received = timeout(Duration::from_secs(1), all_rx.recv()).await.unwrap().unwrap();
+            assert_eq!(event_type(&received), expected);
+        }
+        sleep(Duration::from_millis(50)).await;
+        assert!(task_rx.try_recv().is_err());
+        assert!(all_rx.try_recv().is_err());
+        assert!(briefing_rx.try_recv().is_err());
+    }
+    
+    #[tokio::test]
+    async fn test_stuck_subscriber_drops_its_oldest_broadcasts_without_holding_up_others() {
+        let comm = PluginCommunication::new().with_subscriber_buffer(2);
+        let (healthy, mut healthy_rx) = forwarding("healthy", &["task_completed"]);
+        comm.subscribe_to_broadcasts(healthy).await.unwrap();
+        
+        // Hangs on every broadcast until released
+        let release = Arc::new(Notify::new());
+        let (started_tx, mut started_rx) = mpsc::unbounded_channel();
+        let callback: EventCallback = {
+            let release = release.clone();
+            Arc::new(move |message| {
+                let (release, started_tx) = (release.clone(), started_tx.clone());
+                Box::pin(async move {
+                    started_tx.send(message).unwrap();
+                    release.notified().await;
+                    Ok(())
+                })
+            })
+        };
+        let stuck = EventSubscription { plugin_id: "stuck".to_string(), event_types: vec![], callback };
+        comm.subscribe_to_broadcasts(stuck).await.unwrap();
+        
+        comm.publish_event("assistant", &task_completed("0")).await.unwrap();
+        timeout(Duration::from_secs(1), started_rx.recv()).await.unwrap().unwrap();
+        timeout(Duration::from_secs(1), healthy_rx.recv()).await.unwrap().unwrap();
+        // The healthy plugin keeps up while the stuck one's queue overflows
+        for name in ["1", "2", "3", "4"] {
+            comm.publish_event("assistant", &task_completed(name)).await.unwrap();
+            timeout(Duration::from_secs(1), healthy_rx.recv()).await.unwrap().unwrap();
+        }
+        assert_eq!(comm.dropped_broadcasts("healthy").await, Some(0));
+        assert_eq!(comm.dropped_broadcasts("stuck").await, Some(2));
+        
+        // Once released, the stuck plugin gets the two newest
+        let mut names = Vec::new();
+        for _ in 0..2 {
+            release.notify_one();
+            let message = timeout(Duration::from_secs(1), started_rx.recv()).await.unwrap().unwrap();
+            let MessagePayload::Event { data, .. } = message.payload else { unreachable!() };
+            names.push(data["name"].as_str().unwrap().to_string());
+        }
+        assert_eq!(names, vec!["3", "4"]);
+    }
+    
     #[tokio::test]
     async fn test_message_serialization() {
         let serializer = MessageSerializer::new();
@@ -0,0 +1,195 @@
+//! Cron expressions for scheduled plugin jobs.
+//!
+//! The usual five fields, `minute hour day-of-month month day-of-week`, in
+//! UTC. Each field takes `*`, a value, a range `a-b`, a step `*/n`, `a-b/n`
+//! or `a/n`, and comma-separated lists of those. Day of week runs from 0
+//! (Sunday) to 6, with 7 also meaning Sunday. As in classic cron, when both
+//! day fields are restricted a day matching either one fires. `@hourly`,
+//! `@daily`, `@weekly`, `@monthly` and `@yearly` stand for their usual
+//! expressions.
+
+use chrono::{DateTime, Datelike, Days, Duration, DurationRound, TimeZone, Timelike, Utc};
+use rusty_ai_common::{AssistantError, Result};
+use std::fmt;
+use std::str::FromStr;
+
+// Enough to find the next run of any expression that fires at all, Feb 29
+// included, without looping forever on one that never does
+const MAX_SEARCH_STEPS: usize = 50_000;
+
+/// A parsed cron expression
+#[derive(Debug, Clone, PartialEq)]
+pub struct CronSchedule {
+    expression: String,
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    // Whether each day field was `*`, for the either-day rule
+    any_day_of_month: bool,
+    any_day_of_week: bool,
+}
+
+impl CronSchedule {
+    /// The first time after `after`, to the minute, the schedule fires; None
+    /// for expressions that never fire, like the 30th of February
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let mut time = after.duration_trunc(Duration::minutes(1)).ok()? + Duration::minutes(1);
+        for _ in 0..MAX_SEARCH_STEPS {
+            if !has(self.months, time.month()) {
+                let (year, month) = if time.month() == 12 { (time.year() + 1, 1) } else { (time.year(), time.month() + 1) };
+                time = Utc.with_ymd_and_hms(year, month, 1, 0, 0, 0).single()?;
+            } else if !self.fires_on(time) {
+                time = (time.date_naive() + Days::new(1)).and_hms_opt(0, 0, 0)?.and_utc();
+            } else if !has(self.hours, time.hour()) {
+                time = time.duration_trunc(Duration::hours(1)).ok()? + Duration::hours(1);
+            } else if !has(self.minutes, time.minute()) {
+                time += Duration::minutes(1);
+            } else {
+                return Some(time);
+            }
+        }
+        None
+    }
+
+    fn fires_on(&self, time: DateTime<Utc>) -> bool {
+        let day_of_month = has(self.days_of_month, time.day());
+        let day_of_week = has(self.days_of_week, time.weekday().num_days_from_sunday());
+        match (self.any_day_of_month, self.any_day_of_week) {
+            (true, true) => true,
+            (false, true) => day_of_month,
+            (true, false) => day_of_week,
+            (false, false) => day_of_month || day_of_week,
+        }
+    }
+}
+
+impl FromStr for CronSchedule {
+    type Err = AssistantError;
+
+    fn from_str(expression: &str) -> Result<Self> {
+        let expanded = match expression.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            other => other,
+        };
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        let [minute, hour, day_of_month, month, day_of_week] = fields[..] else {
+            return Err(invalid(expression, "expected five fields: minute hour day-of-month month day-of-week"));
+        };
+
+        // Sunday may be written as 7; it is kept as 0
+        let mut days_of_week = field(expression, day_of_week, 0, 7)?;
+        if has(days_of_week, 7) {
+            days_of_week = (days_of_week & !(1 << 7)) | 1;
+        }
+        Ok(Self {
+            expression: expression.trim().to_string(),
+            minutes: field(expression, minute, 0, 59)?,
+            hours: field(expression, hour, 0, 23)?,
+            days_of_month: field(expression, day_of_month, 1, 31)?,
+            months: field(expression, month, 1, 12)?,
+            days_of_week,
+            any_day_of_month: day_of_month.starts_with('*'),
+            any_day_of_week: day_of_week.starts_with('*'),
+        })
+    }
+}
+
+impl fmt::Display for CronSchedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.expression)
+    }
+}
+
+fn has(set: u64, value: u32) -> bool {
+    set & (1 << value) != 0
+}
+
+// The values one field allows, as a bit per value
+fn field(expression: &str, spec: &str, min: u32, max: u32) -> Result<u64> {
+    let number = |text: &str| -> Result<u32> {
+        text.parse::<u32>()
+            .ok()
+            .filter(|value| (min..=max).contains(value))
+            .ok_or_else(|| invalid(expression, &format!("'{}' is not a value from {} to {}", text, min, max)))
+    };
+
+    let mut set = 0u64;
+    for part in spec.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step = step.parse::<u32>().ok().filter(|step| *step > 0);
+                (range, Some(step.ok_or_else(|| invalid(expression, &format!("'{}' has no valid step", part)))?))
+            }
+            None => (part, None),
+        };
+        let (first, last) = match range.split_once('-') {
+            _ if range == "*" => (min, max),
+            Some((first, last)) => (number(first)?, number(last)?),
+            // `a/n` runs from a to the end of the field
+            None if step.is_some() => (number(range)?, max),
+            None => {
+                let value = number(range)?;
+                (value, value)
+            }
+        };
+        if first > last {
+            return Err(invalid(expression, &format!("'{}' is a backwards range", range)));
+        }
+        for value in (first..=last).step_by(step.unwrap_or(1) as usize) {
+            set |= 1 << value;
+        }
+    }
+    Ok(set)
+}
+
+fn invalid(expression: &str, reason: &str) -> AssistantError {
+    AssistantError::Api(format!("Invalid cron expression '{}': {}", expression, reason))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(time: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(time).unwrap().with_timezone(&Utc)
+    }
+
+    fn next(expression: &str, after: &str) -> Option<DateTime<Utc>> {
+        expression.parse::<CronSchedule>().unwrap().next_after(at(after))
+    }
+
+    #[test]
+    fn test_next_run_of_common_expressions() {
+        assert_eq!(next("*/30 * * * *", "2024-06-14T10:00:00Z"), Some(at("2024-06-14T10:30:00Z")));
+        assert_eq!(next("*/30 * * * *", "2024-06-14T10:29:59Z"), Some(at("2024-06-14T10:30:00Z")));
+        assert_eq!(next("@hourly", "2024-12-31T23:15:00Z"), Some(at("2025-01-01T00:00:00Z")));
+        assert_eq!(next("15 9 * * 1-5", "2024-06-14T09:15:00Z"), Some(at("2024-06-17T09:15:00Z")));
+        assert_eq!(next("0 8,20 * * *", "2024-06-14T09:00:00Z"), Some(at("2024-06-14T20:00:00Z")));
+        assert_eq!(next("0 0 29 2 *", "2024-03-01T00:00:00Z"), Some(at("2028-02-29T00:00:00Z")));
+        assert_eq!(next("0 0 30 2 *", "2024-03-01T00:00:00Z"), None);
+    }
+
+    #[test]
+    fn test_either_restricted_day_field_fires() {
+        // The 1st of the month, or any Sunday (written as 7)
+        assert_eq!(next("0 12 1 * 7", "2024-06-01T12:00:00Z"), Some(at("2024-06-02T12:00:00Z")));
+        assert_eq!(next("0 12 1 * 7", "2024-06-30T12:00:00Z"), Some(at("2024-07-01T12:00:00Z")));
+        // A day of week alone ignores the day of month
+        assert_eq!(next("0 12 * * 0", "2024-06-01T12:00:00Z"), Some(at("2024-06-02T12:00:00Z")));
+    }
+
+    #[test]
+    fn test_invalid_expressions_are_rejected() {
+        for expression in ["", "* * * *", "60 * * * *", "* 24 * * *", "* * 0 * *", "*/0 * * * *", "5-1 * * * *", "a * * * *"] {
+            let error = expression.parse::<CronSchedule>().unwrap_err();
+            assert!(matches!(error, AssistantError::Api(_)), "{}: {:?}", expression, error);
+        }
+        assert_eq!("5/15 * * * *".parse::<CronSchedule>().unwrap().to_string(), "5/15 * * * *");
+    }
+}
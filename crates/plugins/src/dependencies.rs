@@ -0,0 +1,235 @@
+//! Dependencies between plugins.
+//!
+//! A plugin lists the plugins it needs in `WasmPluginMetadata.dependencies`,
+//! one `name@requirement` string each, where the requirement is a semver
+//! range such as `^1.2` or `>=1.2, <2`; a bare name takes any version.
+//! Before a plugin is loaded its dependencies are resolved against the
+//! plugins already loaded and those available to load, which gives the
+//! order to load the missing ones in.
+
+use crate::WasmPluginMetadata;
+use rusty_ai_common::{AssistantError, Result};
+use semver::{Version, VersionReq};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::str::FromStr;
+
+/// A plugin another one needs, and the versions of it that will do
+#[derive(Debug, Clone, PartialEq)]
+pub struct PluginDependency {
+    pub plugin_id: String,
+    pub requirement: VersionReq,
+}
+
+impl PluginDependency {
+    /// Whether `version` meets the requirement; versions that are not
+    /// semver never do
+    pub fn accepts(&self, version: &str) -> bool {
+        Version::parse(version).is_ok_and(|version| self.requirement.matches(&version))
+    }
+}
+
+impl FromStr for PluginDependency {
+    type Err = AssistantError;
+
+    fn from_str(spec: &str) -> Result<Self> {
+        let invalid = |reason: String| AssistantError::Plugin(format!("Invalid dependency '{}': {}", spec, reason));
+        let (plugin_id, requirement) = match spec.split_once('@') {
+            Some((plugin_id, requirement)) => {
+                (plugin_id.trim(), VersionReq::parse(requirement.trim()).map_err(|e| invalid(e.to_string()))?)
+            }
+            None => (spec.trim(), VersionReq::STAR),
+        };
+        if plugin_id.is_empty() {
+            return Err(invalid("no plugin name".to_string()));
+        }
+        Ok(Self { plugin_id: plugin_id.to_string(), requirement })
+    }
+}
+
+impl fmt::Display for PluginDependency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.requirement == VersionReq::STAR {
+            f.write_str(&self.plugin_id)
+        } else {
+            write!(f, "{}@{}", self.plugin_id, self.requirement)
+        }
+    }
+}
+
+/// The dependencies a plugin declares
+pub fn declared(metadata: &WasmPluginMetadata) -> Result<Vec<PluginDependency>> {
+    metadata.dependencies.iter().map(|spec| spec.parse()).collect()
+}
+
+/// The plugins to load, dependencies first and `plugin_id` last, so that
+/// every plugin loads after what it needs. `known` holds the metadata of
+/// every plugin loaded or available to load, by id; dependencies in
+/// `loaded` are checked but not loaded again.
+///
+/// Fails on a dependency cycle, or with every unmet requirement found: a
+/// plugin nobody provides, or one whose version is outside the range
+pub fn load_order(
+    plugin_id: &str,
+    known: &HashMap<&str, &WasmPluginMetadata>,
+    loaded: &HashSet<&str>,
+) -> Result<Vec<String>> {
+    let (&plugin_id, _) = known
+        .get_key_value(plugin_id)
+        .ok_or_else(|| AssistantError::NotFound(format!("Plugin not found: {}", plugin_id)))?;
+    let mut resolver = Resolver { known, loaded, path: Vec::new(), visited: HashSet::new(), order: Vec::new(), unmet: Vec::new() };
+    resolver.visit(plugin_id)?;
+    if !resolver.unmet.is_empty() {
+        return Err(AssistantError::Plugin(format!(
+            "Unmet dependencies of {}: {}",
+            plugin_id,
+            resolver.unmet.join("; ")
+        )));
+    }
+    Ok(resolver.order)
+}
+
+/// The plugins among `plugins` that declare a dependency on `plugin_id`,
+/// sorted by id
+pub fn dependents<'a>(plugin_id: &str, plugins: impl IntoIterator<Item = &'a WasmPluginMetadata>) -> Vec<String> {
+    let mut dependents: Vec<String> = plugins
+        .into_iter()
+        .filter(|metadata| metadata.id != plugin_id)
+        // A dependency that does not parse never let the plugin load
+        .filter(|metadata| declared(metadata).is_ok_and(|dependencies| dependencies.iter().any(|d| d.plugin_id == plugin_id)))
+        .map(|metadata| metadata.id.clone())
+        .collect();
+    dependents.sort();
+    dependents
+}
+
+// Depth-first walk of the dependency graph, adding each plugin to the order
+// after everything it needs
+struct Resolver<'a> {
+    known: &'a HashMap<&'a str, &'a WasmPluginMetadata>,
+    loaded: &'a HashSet<&'a str>,
+    /// The plugins being resolved, outermost first, to spot cycles
+    path: Vec<&'a str>,
+    visited: HashSet<&'a str>,
+    order: Vec<String>,
+    unmet: Vec<String>,
+}
+
+impl<'a> Resolver<'a> {
+    fn visit(&mut self, plugin_id: &'a str) -> Result<()> {
+        if self.visited.contains(plugin_id) {
+            return Ok(());
+        }
+        if let Some(start) = self.path.iter().position(|id| *id == plugin_id) {
+            let mut cycle = self.path[start..].to_vec();
+            cycle.push(plugin_id);
+            return Err(AssistantError::Plugin(format!("Dependency cycle: {}", cycle.join(" -> "))));
+        }
+
+        self.path.push(plugin_id);
+        for dependency in declared(self.known[plugin_id])? {
+            match self.known.get_key_value(dependency.plugin_id.as_str()) {
+                None => self.unmet.push(format!("{} needs {}, which is not available", plugin_id, dependency)),
+                Some((&id, metadata)) if !dependency.accepts(&metadata.version) => {
+                    let state = if self.loaded.contains(id) { "loaded" } else { "available" };
+                    self.unmet.push(format!(
+                        "{} needs {}, but {} {} is {}",
+                        plugin_id, dependency, id, metadata.version, state
+                    ));
+                }
+                Some((&id, _)) if self.loaded.contains(id) => {}
+                Some((&id, _)) => self.visit(id)?,
+            }
+        }
+        self.path.pop();
+
+        self.visited.insert(plugin_id);
+        self.order.push(plugin_id.to_string());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plugin(id: &str, version: &str, dependencies: &[&str]) -> WasmPluginMetadata {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "name": id,
+            "version": version,
+            "dependencies": dependencies,
+        }))
+        .unwrap()
+    }
+
+    fn order(plugins: &[WasmPluginMetadata], loaded: &[&str], plugin_id: &str) -> Result<Vec<String>> {
+        let known = plugins.iter().map(|metadata| (metadata.id.as_str(), metadata)).collect();
+        load_order(plugin_id, &known, &loaded.iter().copied().collect())
+    }
+
+    #[test]
+    fn test_dependency_specs_parse_as_name_and_semver_range() {
+        let dependency: PluginDependency = "kv_store@>=1.2".parse().unwrap();
+        assert_eq!(dependency.plugin_id, "kv_store");
+        assert!(dependency.accepts("1.2.0") && dependency.accepts("3.0.1"));
+        assert!(!dependency.accepts("1.1.9") && !dependency.accepts("latest"));
+        assert_eq!(dependency.to_string(), "kv_store@>=1.2");
+
+        let any: PluginDependency = " kv_store ".parse().unwrap();
+        assert!(any.accepts("0.0.1"));
+        assert_eq!(any.to_string(), "kv_store");
+
+        for spec in ["kv_store@banana", "@1.0", ""] {
+            assert!(matches!(spec.parse::<PluginDependency>(), Err(AssistantError::Plugin(_))), "{}", spec);
+        }
+    }
+
+    #[test]
+    fn test_diamond_dependencies_load_once_and_before_their_dependents() {
+        let plugins = [
+            plugin("app", "1.0.0", &["left", "right@^2"]),
+            plugin("left", "1.0.0", &["base@^1.2"]),
+            plugin("right", "2.3.0", &["base@>=1.0, <2"]),
+            plugin("base", "1.4.0", &[]),
+        ];
+        assert_eq!(order(&plugins, &[], "app").unwrap(), vec!["base", "left", "right", "app"]);
+        // Loaded plugins are only checked
+        assert_eq!(order(&plugins, &["base", "left"], "app").unwrap(), vec!["right", "app"]);
+    }
+
+    #[test]
+    fn test_conflicting_missing_and_cyclic_dependencies_are_refused() {
+        let plugins = [
+            plugin("app", "1.0.0", &["left", "right", "clock"]),
+            plugin("left", "1.0.0", &["base@^1"]),
+            plugin("right", "1.0.0", &["base@^2"]),
+            plugin("base", "1.4.0", &[]),
+        ];
+        let error = order(&plugins, &[], "app").unwrap_err().to_string();
+        assert!(error.contains("right needs base@^2, but base 1.4.0 is available"), "{}", error);
+        assert!(error.contains("app needs clock, which is not available"), "{}", error);
+        let error = order(&plugins, &["base"], "right").unwrap_err().to_string();
+        assert!(error.contains("but base 1.4.0 is loaded"), "{}", error);
+
+        let plugins = [
+            plugin("a", "1.0.0", &["b"]),
+            plugin("b", "1.0.0", &["c"]),
+            plugin("c", "1.0.0", &["a@^1"]),
+        ];
+        let error = order(&plugins, &[], "a").unwrap_err().to_string();
+        assert!(error.contains("Dependency cycle: a -> b -> c -> a"), "{}", error);
+    }
+
+    #[test]
+    fn test_dependents_are_the_plugins_naming_it() {
+        let plugins = [
+            plugin("right", "1.0.0", &["base@^1"]),
+            plugin("left", "1.0.0", &["base"]),
+            plugin("base", "1.4.0", &[]),
+            plugin("clock", "1.0.0", &["basement"]),
+        ];
+        assert_eq!(dependents("base", &plugins), vec!["left", "right"]);
+        assert!(dependents("clock", &plugins).is_empty());
+    }
+}
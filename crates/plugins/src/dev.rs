@@ -0,0 +1,431 @@
+use crate::limits::FunctionLimits;
+use crate::security::ExecutionStats;
+use crate::{
+    artifact_sha256, create_plugin_engine, parse_function_schemas, ExecutionReport, FunctionSchema, PluginSandbox,
+    PluginWasiCtx, ResourceLimits, SecurityConfig, SecurityPolicy, WasmPlugin, WasmPluginInstance, WasmPluginMetadata,
+};
+use rusty_ai_common::{AssistantError, Result};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::sync::Mutex;
+use tracing::debug;
+use wasmtime::{Engine, Module};
+
+/// Stdout/stderr kept per REPL call, more than managed plugins keep
+const CAPTURE_CAPACITY: usize = 1024 * 1024;
+
+/// How often the plugin file is checked for a rebuild
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// A plugin loaded from a file for interactive development: permissive
+/// trusted policy, captured output and reload on rebuild
+pub struct PluginDevSession {
+    path: PathBuf,
+    engine: Engine,
+    limits: ResourceLimits,
+    policy: SecurityPolicy,
+    plugin: WasmPluginInstance,
+    functions: Vec<FunctionSchema>,
+    checksum: String,
+    /// Validated the current build and accounts for its calls
+    sandbox: std::sync::Mutex<PluginSandbox>,
+}
+
+/// Result of a `call` typed at the REPL
+#[derive(Debug, Clone)]
+pub struct DevCallResult {
+    /// The output parsed as JSON, or as a string if it is not JSON
+    pub output: serde_json::Value,
+    pub fuel_consumed: u64,
+    pub duration: Duration,
+    pub peak_memory: u64,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+impl From<ExecutionReport> for DevCallResult {
+    fn from(report: ExecutionReport) -> Self {
+        let output = serde_json::from_slice(&report.output)
+            .unwrap_or_else(|_| serde_json::Value::String(String::from_utf8_lossy(&report.output).into_owned()));
+        Self {
+            output,
+            fuel_consumed: report.fuel_consumed,
+            duration: report.duration,
+            peak_memory: report.peak_memory,
+            stdout: String::from_utf8_lossy(&report.stdout).into_owned(),
+            stderr: String::from_utf8_lossy(&report.stderr).into_owned(),
+        }
+    }
+}
+
+impl PluginDevSession {
+    pub async fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let engine = create_plugin_engine()?;
+        let limits = ResourceLimits::default();
+        let policy = SecurityConfig::trusted();
+
+        let bytes = read_module(&path).await?;
+        let (plugin, functions, sandbox) = instantiate(&engine, &limits, &policy, &bytes).await?;
+
+        Ok(Self {
+            path,
+            engine,
+            limits,
+            policy,
+            plugin,
+            functions,
+            checksum: artifact_sha256(&bytes),
+            sandbox: std::sync::Mutex::new(sandbox),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn metadata(&self) -> &WasmPluginMetadata {
+        self.plugin.metadata()
+    }
+
+    /// Functions declared by the plugin's `list_functions` export, by name
+    pub fn functions(&self) -> &[FunctionSchema] {
+        &self.functions
+    }
+
+    /// Call `function` with a JSON input; an empty input means `{}`
+    pub async fn call(&self, function: &str, input: &str) -> Result<DevCallResult> {
+        let input = if input.trim().is_empty() { "{}" } else { input.trim() };
+        serde_json::from_str::<serde_json::Value>(input)
+            .map_err(|e| AssistantError::Plugin(format!("Input is not valid JSON: {}", e)))?;
+
+        let report = self.plugin.call(function, input.as_bytes()).await?;
+        self.sandbox.lock().unwrap().record_execution(&report);
+        Ok(report.into())
+    }
+
+    /// Security stats of the current build, including its peak memory
+    pub fn security_stats(&self) -> ExecutionStats {
+        self.sandbox.lock().unwrap().get_stats().clone()
+    }
+
+    /// Load the file again. If the new build fails to load, the previous
+    /// one stays in place and the error is returned
+    pub async fn reload(&mut self) -> Result<()> {
+        let bytes = read_module(&self.path).await?;
+        let (plugin, functions, sandbox) = instantiate(&self.engine, &self.limits, &self.policy, &bytes).await?;
+
+        self.plugin = plugin;
+        self.functions = functions;
+        self.checksum = artifact_sha256(&bytes);
+        self.sandbox = std::sync::Mutex::new(sandbox);
+        Ok(())
+    }
+
+    /// Reload if the file's contents changed since the last load. A build
+    /// that fails to load is not retried until the file changes again
+    pub async fn reload_if_changed(&mut self) -> Result<bool> {
+        let bytes = match tokio::fs::read(&self.path).await {
+            Ok(bytes) => bytes,
+            // Mid-rebuild the file can briefly be missing
+            Err(e) => {
+                debug!("Plugin file {} not readable: {}", self.path.display(), e);
+                return Ok(false);
+            }
+        };
+        let checksum = artifact_sha256(&bytes);
+        if checksum == self.checksum {
+            return Ok(false);
+        }
+
+        self.checksum = checksum;
+        let (plugin, functions, sandbox) = instantiate(&self.engine, &self.limits, &self.policy, &bytes).await?;
+        self.plugin = plugin;
+        self.functions = functions;
+        self.sandbox = std::sync::Mutex::new(sandbox);
+        Ok(true)
+    }
+}
+
+async fn read_module(path: &Path) -> Result<Vec<u8>> {
+    tokio::fs::read(path)
+        .await
+        .map_err(|e| AssistantError::Plugin(format!("Failed to read {}: {}", path.display(), e)))
+}
+
+async fn instantiate(
+    engine: &Engine,
+    limits: &ResourceLimits,
+    policy: &SecurityPolicy,
+    bytes: &[u8],
+) -> Result<(WasmPluginInstance, Vec<FunctionSchema>, PluginSandbox)> {
+    let module = Module::new(engine, bytes)
+        .map_err(|e| AssistantError::Plugin(format!("Failed to compile module: {}", e)))?;
+    let mut sandbox = PluginSandbox::new(policy.clone(), limits.clone());
+    // Dev builds are unsigned; the trusted policy does not require signatures
+    let status = sandbox.validate_module("dev", bytes, &module, None)?;
+    let context = PluginWasiCtx::with_captured_output(limits.clone(), CAPTURE_CAPACITY)?;
+    let plugin = WasmPluginInstance::from_module(engine, &module, bytes, limits.clone(), context).await?;
+    sandbox.validate_metadata(plugin.metadata(), status)?;
+
+    // Plugins without list_functions still load; every export is callable
+    let mut functions: Vec<FunctionSchema> = match plugin.call("list_functions", b"{}").await {
+        Ok(report) => serde_json::from_slice(&report.output)
+            .map(|value| parse_function_schemas(&value).into_values().collect())
+            .unwrap_or_default(),
+        Err(e) => {
+            debug!("No usable list_functions export: {}", e);
+            Vec::new()
+        }
+    };
+    for function in &mut functions {
+        function.limits = Some(FunctionLimits::effective(&plugin.metadata().limits_for(&function.name, limits)));
+    }
+    functions.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok((plugin, functions, sandbox))
+}
+
+/// A line typed at the dev REPL
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReplCommand {
+    Call { function: String, input: String },
+    Functions,
+    Reload,
+    Help,
+    Quit,
+    Empty,
+}
+
+impl ReplCommand {
+    pub fn parse(line: &str) -> std::result::Result<Self, String> {
+        let line = line.trim();
+        let (command, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        match command {
+            "" => Ok(ReplCommand::Empty),
+            "call" => {
+                let rest = rest.trim();
+                let (function, input) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+                if function.is_empty() {
+                    return Err("usage: call <function> [json input]".to_string());
+                }
+                Ok(ReplCommand::Call {
+                    function: function.to_string(),
+                    input: input.trim().to_string(),
+                })
+            }
+            "functions" | "ls" => Ok(ReplCommand::Functions),
+            "reload" => Ok(ReplCommand::Reload),
+            "help" | "?" => Ok(ReplCommand::Help),
+            "quit" | "exit" => Ok(ReplCommand::Quit),
+            other => Err(format!("unknown command '{}', try 'help'", other)),
+        }
+    }
+}
+
+const REPL_HELP: &str = "\
+  call <function> [json]   call an export, e.g. call analyze_text {\"text\":\"hi\"}
+  functions                list the declared functions and their schemas
+  reload                   load the file again now
+  help                     show this help
+  quit                     leave dev mode";
+
+/// The declared functions with their schemas, as printed by the REPL
+pub fn describe_functions(functions: &[FunctionSchema]) -> String {
+    if functions.is_empty() {
+        return "  (the plugin declares no functions; export list_functions to describe them)\n".to_string();
+    }
+
+    let mut out = String::new();
+    for function in functions {
+        out.push_str(&format!("  {}", function.name));
+        if !function.description.is_empty() {
+            out.push_str(&format!(" - {}", function.description));
+        }
+        out.push('\n');
+        if let Some(schema) = &function.input_schema {
+            out.push_str(&format!("      input:  {}\n", schema));
+        }
+        if let Some(schema) = &function.output_schema {
+            out.push_str(&format!("      output: {}\n", schema));
+        }
+        if let Some(permission) = &function.required_permission {
+            out.push_str(&format!("      requires {}\n", permission));
+        }
+        if function.public {
+            out.push_str("      callable from chat\n");
+        }
+        if let Some(FunctionLimits { timeout_ms: Some(timeout_ms), max_fuel: Some(max_fuel) }) = function.limits {
+            out.push_str(&format!("      limits: {}ms, {} fuel\n", timeout_ms, max_fuel));
+        }
+    }
+    out
+}
+
+fn print_call_result(result: &DevCallResult) {
+    let output = serde_json::to_string_pretty(&result.output).unwrap_or_else(|_| result.output.to_string());
+    println!("{}", output);
+    println!(
+        "  fuel: {}  time: {:?}  memory: {} KiB",
+        result.fuel_consumed,
+        result.duration,
+        result.peak_memory / 1024
+    );
+    if !result.stdout.is_empty() {
+        println!("  stdout:\n{}", indent(&result.stdout));
+    }
+    if !result.stderr.is_empty() {
+        println!("  stderr:\n{}", indent(&result.stderr));
+    }
+}
+
+fn indent(text: &str) -> String {
+    text.lines().map(|line| format!("    {}", line)).collect::<Vec<_>>().join("\n")
+}
+
+fn prompt() {
+    print!("> ");
+    let _ = std::io::stdout().flush();
+}
+
+/// Load the plugin, print what it declares and read commands from stdin
+/// until `quit` or end of input, reloading whenever the file is rebuilt
+pub async fn run_dev(path: impl AsRef<Path>) -> Result<()> {
+    let session = PluginDevSession::load(path).await?;
+    println!(
+        "Loaded {} ({} {}) from {}",
+        session.metadata().name,
+        session.metadata().id,
+        session.metadata().version,
+        session.path().display()
+    );
+    print!("{}", describe_functions(session.functions()));
+    println!("Watching for rebuilds. Type 'help' for commands.");
+
+    let session = Arc::new(Mutex::new(session));
+    let watcher = {
+        let session = session.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(WATCH_INTERVAL);
+            loop {
+                interval.tick().await;
+                let mut session = session.lock().await;
+                match session.reload_if_changed().await {
+                    Ok(true) => {
+                        println!("\nReloaded {}", session.path().display());
+                        print!("{}", describe_functions(session.functions()));
+                        prompt();
+                    }
+                    Ok(false) => {}
+                    Err(e) => {
+                        println!("\nRebuild failed to load, keeping the previous build: {}", e);
+                        prompt();
+                    }
+                }
+            }
+        })
+    };
+
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    prompt();
+    while let Some(line) = lines
+        .next_line()
+        .await
+        .map_err(|e| AssistantError::Internal(format!("Failed to read input: {}", e)))?
+    {
+        match ReplCommand::parse(&line) {
+            Ok(ReplCommand::Call { function, input }) => match session.lock().await.call(&function, &input).await {
+                Ok(result) => print_call_result(&result),
+                Err(e) => println!("error: {}", e),
+            },
+            Ok(ReplCommand::Functions) => print!("{}", describe_functions(session.lock().await.functions())),
+            Ok(ReplCommand::Reload) => match session.lock().await.reload().await {
+                Ok(()) => println!("Reloaded"),
+                Err(e) => println!("error: {}", e),
+            },
+            Ok(ReplCommand::Help) => println!("{}", REPL_HELP),
+            Ok(ReplCommand::Quit) => break,
+            Ok(ReplCommand::Empty) => {}
+            Err(message) => println!("{}", message),
+        }
+        prompt();
+    }
+
+    watcher.abort();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    const FIXTURE: &str = include_str!("../fixtures/echo.wat");
+
+    async fn session_from(fixture: &str) -> (tempfile::TempDir, PluginDevSession) {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("echo.wat");
+        std::fs::write(&path, fixture).unwrap();
+        let session = PluginDevSession::load(&path).await.unwrap();
+        (dir, session)
+    }
+
+    #[tokio::test]
+    async fn test_load_discovers_functions_and_calls_report_cost() {
+        let (_dir, session) = session_from(FIXTURE).await;
+
+        let names: Vec<&str> = session.functions().iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, vec!["echo", "version"]);
+        assert!(describe_functions(session.functions()).contains("input:  {\"type\":\"object\"}"));
+
+        let result = session.call("echo", r#"{"text":"hi"}"#).await.unwrap();
+        assert_eq!(result.output, serde_json::json!({"text": "hi"}));
+        assert!(result.fuel_consumed > 0);
+        assert_eq!(result.peak_memory, 64 * 1024);
+        assert_eq!(session.security_stats().peak_memory_usage, 64 * 1024);
+        assert!(result.stdout.is_empty());
+
+        assert!(session.call("missing", "{}").await.is_err());
+        assert!(session.call("echo", "{not json").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_rebuilt_file_is_reloaded_and_broken_builds_are_ignored() {
+        let (_dir, mut session) = session_from(FIXTURE).await;
+        assert_eq!(session.call("version", "").await.unwrap().output["version"], 1);
+        assert!(!session.reload_if_changed().await.unwrap());
+
+        let rebuilt = FIXTURE.replace(r#"{\"version\":1}"#, r#"{\"version\":2}"#);
+        std::fs::write(session.path(), rebuilt).unwrap();
+        assert!(session.reload_if_changed().await.unwrap());
+        assert_eq!(session.call("version", "").await.unwrap().output["version"], 2);
+
+        // A broken build is reported once and the previous one keeps serving
+        std::fs::write(session.path(), "(module (func").unwrap();
+        assert!(session.reload_if_changed().await.is_err());
+        assert!(!session.reload_if_changed().await.unwrap());
+        assert_eq!(session.call("version", "").await.unwrap().output["version"], 2);
+        assert!(session.reload().await.is_err());
+    }
+
+    #[test]
+    fn test_repl_command_parsing() {
+        assert_eq!(
+            ReplCommand::parse(r#"call analyze_text {"text": "hi there"}"#),
+            Ok(ReplCommand::Call {
+                function: "analyze_text".to_string(),
+                input: r#"{"text": "hi there"}"#.to_string(),
+            })
+        );
+        assert_eq!(
+            ReplCommand::parse("  call hello  "),
+            Ok(ReplCommand::Call { function: "hello".to_string(), input: String::new() })
+        );
+        assert!(ReplCommand::parse("call").is_err());
+        assert!(ReplCommand::parse("invoke hello").is_err());
+        assert_eq!(ReplCommand::parse(""), Ok(ReplCommand::Empty));
+        assert_eq!(ReplCommand::parse("exit"), Ok(ReplCommand::Quit));
+    }
+}
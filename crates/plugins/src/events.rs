@@ -0,0 +1,242 @@
+//! Assistant events delivered to the plugins that ask for them.
+//!
+//! A plugin lists the event types it wants in its metadata's `events` and
+//! exports `on_event`, which is called with the event as JSON, typed by
+//! [`PluginEvent::event_type`]. [`PluginEventDispatcher`] subscribes each
+//! plugin on [`PluginCommunication`] as it loads and drops the subscription
+//! when it unloads, so the communication layer's per-subscriber queues
+//! apply: a plugin that is slow or failing only loses its own oldest
+//! events. Calls are made through `WasmPluginManager::execute_plugin` as
+//! [`EVENTS_USER`] with the baseline plugin permission, so permissions,
+//! quotas, limits and the audit log apply as they do to any other call.
+
+use crate::{CallOrigin, EventCallback, EventSubscription, MessagePayload, PluginCommunication, PluginContext, PluginLifecycleEvent, WasmPluginManager};
+use rusty_ai_common::{AssistantError, PluginEvent, Result};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+/// The export events are delivered to
+pub const EVENT_HANDLER: &str = "on_event";
+
+/// The user event deliveries are made as
+pub const EVENTS_USER: &str = "events";
+
+/// Sender of the broadcasts the dispatcher publishes
+const EVENTS_SENDER: &str = "assistant";
+
+/// Keeps plugin event subscriptions in step with the loaded plugins and
+/// publishes assistant events to them
+pub struct PluginEventDispatcher {
+    plugins: Arc<WasmPluginManager>,
+    communication: Arc<PluginCommunication>,
+}
+
+impl PluginEventDispatcher {
+    pub fn new(plugins: Arc<WasmPluginManager>, communication: Arc<PluginCommunication>) -> Self {
+        Self { plugins, communication }
+    }
+
+    /// Broadcast `event` to the plugins subscribed to its type
+    pub async fn publish(&self, event: &PluginEvent) -> Result<()> {
+        self.communication.publish_event(EVENTS_SENDER, event).await
+    }
+
+    /// Subscribe the plugin `name` to the events its serving version's
+    /// metadata lists, or drop its subscription when it lists none or is
+    /// not loaded
+    pub async fn register(&self, name: &str) -> Result<()> {
+        let events = match self.plugins.get_plugin_metadata(name).await {
+            Ok(metadata) => metadata.events,
+            Err(AssistantError::NotFound(_)) => Vec::new(),
+            Err(e) => return Err(e),
+        };
+        if events.is_empty() {
+            return self.communication.unsubscribe_from_broadcasts(name).await;
+        }
+
+        debug!("Plugin {} receives {:?}", name, events);
+        self.communication
+            .subscribe_to_broadcasts(EventSubscription {
+                plugin_id: name.to_string(),
+                event_types: events,
+                callback: self.deliver_to(name),
+            })
+            .await
+    }
+
+    /// Register every loaded plugin, then follow the manager and register
+    /// each plugin again as it loads or unloads, until the returned task is
+    /// aborted
+    pub async fn follow(self: Arc<Self>) -> JoinHandle<()> {
+        // Subscribed before the sync, so no load falls in between
+        let mut lifecycle = self.plugins.subscribe();
+        self.sync().await;
+
+        tokio::spawn(async move {
+            loop {
+                let name = match lifecycle.recv().await {
+                    Ok(PluginLifecycleEvent::Loaded { plugin_id, .. }) => plugin_id,
+                    Ok(PluginLifecycleEvent::Unloaded { plugin_id }) => plugin_id,
+                    Ok(_) => continue,
+                    Err(RecvError::Lagged(missed)) => {
+                        warn!("Missed {} plugin lifecycle events; registering every plugin again", missed);
+                        self.sync().await;
+                        continue;
+                    }
+                    Err(RecvError::Closed) => break,
+                };
+                if let Err(e) = self.register(&name).await {
+                    warn!("Failed to subscribe plugin {} to events: {}", name, e);
+                }
+            }
+        })
+    }
+
+    async fn sync(&self) {
+        for name in self.plugins.list_plugins().await {
+            if let Err(e) = self.register(&name).await {
+                warn!("Failed to subscribe plugin {} to events: {}", name, e);
+            }
+        }
+    }
+
+    // Calls the plugin's `on_event` with the event's JSON
+    fn deliver_to(&self, name: &str) -> EventCallback {
+        let plugins = self.plugins.clone();
+        let name = name.to_string();
+        Arc::new(move |message| {
+            let plugins = plugins.clone();
+            let name = name.clone();
+            Box::pin(async move {
+                let MessagePayload::Event { event_type, data } = message.payload else {
+                    return Ok(());
+                };
+                let context = PluginContext {
+                    user_id: EVENTS_USER.to_string(),
+                    session_id: format!("event:{}", message.id),
+                    request_id: Uuid::new_v4().to_string(),
+                    metadata: HashMap::from([("event_type".to_string(), event_type)]),
+                    started_at: Instant::now(),
+                    permissions: vec![plugins.permission_policy().baseline_permission],
+                    origin: CallOrigin::Api,
+                };
+                plugins.execute_plugin(&name, EVENT_HANDLER, data.to_string().as_bytes(), context).await?;
+                Ok(())
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{PluginHealth, WasmPlugin, WasmPluginMetadata};
+    use async_trait::async_trait;
+    use tempfile::tempdir;
+    use tokio::sync::mpsc;
+    use tokio::time::{sleep, timeout, Duration};
+
+    // Passes on the function, caller and input of every call
+    struct RecordingPlugin {
+        metadata: WasmPluginMetadata,
+        calls: mpsc::UnboundedSender<(String, String, PluginEvent)>,
+    }
+
+    #[async_trait]
+    impl WasmPlugin for RecordingPlugin {
+        fn metadata(&self) -> &WasmPluginMetadata {
+            &self.metadata
+        }
+
+        async fn initialize(&mut self, _config: serde_json::Value) -> Result<()> {
+            Ok(())
+        }
+
+        async fn execute(&self, function: &str, input: &[u8], context: &PluginContext) -> Result<Vec<u8>> {
+            let event = serde_json::from_slice(input).unwrap();
+            let _ = self.calls.send((function.to_string(), context.user_id.clone(), event));
+            Ok(Vec::new())
+        }
+
+        fn can_handle(&self, _capability: &str) -> bool {
+            false
+        }
+
+        async fn health_check(&self) -> Result<PluginHealth> {
+            Err(AssistantError::Plugin("not checked in these tests".to_string()))
+        }
+
+        async fn cleanup(&mut self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    fn plugin(id: &str, events: &[&str], calls: &mpsc::UnboundedSender<(String, String, PluginEvent)>) -> Box<dyn WasmPlugin> {
+        let metadata = serde_json::from_value(serde_json::json!({
+            "id": id, "name": id, "version": "1.0.0", "events": events
+        }))
+        .unwrap();
+        Box::new(RecordingPlugin { metadata, calls: calls.clone() })
+    }
+
+    fn task_completed() -> PluginEvent {
+        PluginEvent::TaskCompleted { user_id: None, task_id: Uuid::new_v4(), name: "Pay rent".to_string() }
+    }
+
+    fn document_uploaded() -> PluginEvent {
+        PluginEvent::DocumentUploaded { user_id: Uuid::new_v4(), document_id: Uuid::new_v4(), title: "Lease".to_string() }
+    }
+
+    // Waits for the dispatcher to catch up with a load or unload
+    async fn wait_until_subscribed(communication: &PluginCommunication, plugin_id: &str, subscribed: bool) {
+        timeout(Duration::from_secs(1), async {
+            while communication.dropped_broadcasts(plugin_id).await.is_some() != subscribed {
+                sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_plugins_receive_the_events_their_metadata_lists_while_loaded() {
+        let dir = tempdir().unwrap();
+        let plugins = Arc::new(WasmPluginManager::new(dir.path()).unwrap());
+        let communication = Arc::new(PluginCommunication::new());
+        let (tx, mut calls) = mpsc::unbounded_channel();
+        plugins.register_plugin("journal", plugin("journal", &["task_completed"], &tx)).await.unwrap();
+        plugins.register_plugin("quiet", plugin("quiet", &[], &tx)).await.unwrap();
+
+        let dispatcher = Arc::new(PluginEventDispatcher::new(plugins.clone(), communication.clone()));
+        let following = dispatcher.clone().follow().await;
+        dispatcher.publish(&document_uploaded()).await.unwrap();
+        let completed = task_completed();
+        dispatcher.publish(&completed).await.unwrap();
+
+        let (function, user, event) = timeout(Duration::from_secs(1), calls.recv()).await.unwrap().unwrap();
+        assert_eq!((function.as_str(), user.as_str()), (EVENT_HANDLER, EVENTS_USER));
+        assert_eq!(event, completed);
+
+        // Loaded after the dispatcher started following the manager
+        plugins.register_plugin("library", plugin("library", &["document_uploaded"], &tx)).await.unwrap();
+        wait_until_subscribed(&communication, "library", true).await;
+        let uploaded = document_uploaded();
+        dispatcher.publish(&uploaded).await.unwrap();
+        let (_, _, event) = timeout(Duration::from_secs(1), calls.recv()).await.unwrap().unwrap();
+        assert_eq!(event, uploaded);
+
+        // Unloading drops the subscription
+        plugins.unload_plugin("journal").await.unwrap();
+        wait_until_subscribed(&communication, "journal", false).await;
+        dispatcher.publish(&task_completed()).await.unwrap();
+        sleep(Duration::from_millis(50)).await;
+        assert!(calls.try_recv().is_err());
+        assert_eq!(communication.dropped_broadcasts("quiet").await, None);
+        following.abort();
+    }
+}
@@ -1,4 +1,6 @@
 use crate::{WasmPlugin, WasmPluginMetadata, PluginContext, PluginHealth, HealthStatus, ResourceLimits};
+use crate::breaker::CircuitState;
+use crate::limits::FunctionLimits;
 use rusty_ai_common::{Result, AssistantError};
 use async_trait::async_trait;
 use std::collections::HashMap;
@@ -32,6 +34,10 @@ struct FunctionInfo {
     description: String,
     input_schema: Option<serde_json::Value>,
     output_schema: Option<serde_json::Value>,
+    /// Permission needed to call the function; `None` means the host's baseline permission
+    required_permission: Option<String>,
+    /// Whether conversation capability dispatch may call the function
+    public: bool,
     execution_count: u64,
     total_execution_time: Duration,
 }
@@ -65,6 +71,12 @@ impl ExampleWasmPlugin {
             dependencies: vec![],
             api_version: "1.0".to_string(),
             checksum: "".to_string(),
+            // Greeting and echoing are cheap; anything slower is a bug
+            function_limits: ["hello", "echo"]
+                .into_iter()
+                .map(|name| (name.to_string(), FunctionLimits { timeout_ms: Some(1_000), max_fuel: Some(100_000) }))
+                .collect(),
+            events: vec![],
         };
         
         let mut state = PluginState::default();
@@ -85,6 +97,8 @@ impl ExampleWasmPlugin {
                     "message": {"type": "string"}
                 }
             })),
+            required_permission: None,
+            public: true,
             execution_count: 0,
             total_execution_time: Duration::from_secs(0),
         });
@@ -104,6 +118,8 @@ impl ExampleWasmPlugin {
                     "echo": {"type": "string"}
                 }
             })),
+            required_permission: None,
+            public: true,
             execution_count: 0,
             total_execution_time: Duration::from_secs(0),
         });
@@ -125,6 +141,24 @@ impl ExampleWasmPlugin {
                     "lines": {"type": "number"}
                 }
             })),
+            required_permission: Some("text:analyze".to_string()),
+            public: true,
+            execution_count: 0,
+            total_execution_time: Duration::from_secs(0),
+        });
+        
+        state.function_registry.insert("reset_stats".to_string(), FunctionInfo {
+            name: "reset_stats".to_string(),
+            description: "Resets the plugin's execution statistics".to_string(),
+            input_schema: Some(serde_json::json!({"type": "object"})),
+            output_schema: Some(serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "reset": {"type": "boolean"}
+                }
+            })),
+            required_permission: Some("admin".to_string()),
+            public: false,
             execution_count: 0,
             total_execution_time: Duration::from_secs(0),
         });
@@ -153,6 +187,7 @@ impl ExampleWasmPlugin {
             "analyze_text" => self.handle_analyze_text(input_json).await?,
             "list_functions" => self.handle_list_functions().await?,
             "get_stats" => self.handle_get_stats().await?,
+            "reset_stats" => self.handle_reset_stats().await?,
             _ => return Err(AssistantError::Plugin(format!("Unknown function: {}", function))),
         };
         
@@ -218,6 +253,9 @@ impl ExampleWasmPlugin {
                 "description": func.description,
                 "input_schema": func.input_schema,
                 "output_schema": func.output_schema,
+                "required_permission": func.required_permission,
+                "public": func.public,
+                "limits": FunctionLimits::effective(&self.metadata.limits_for(&func.name, &self.limits)),
                 "execution_count": func.execution_count,
                 "average_execution_time": func.total_execution_time.as_millis() as f64 / func.execution_count.max(1) as f64
             }))
@@ -255,6 +293,21 @@ impl ExampleWasmPlugin {
         }))
     }
     
+    /// Handle reset stats request
+    async fn handle_reset_stats(&self) -> Result<serde_json::Value> {
+        *self.execution_stats.write().await = ExecutionStats::default();
+        
+        let mut state = self.state.write().await;
+        for func in state.function_registry.values_mut() {
+            func.execution_count = 0;
+            func.total_execution_time = Duration::from_secs(0);
+        }
+        
+        Ok(serde_json::json!({
+            "reset": true
+        }))
+    }
+    
     /// Update function execution statistics
     async fn update_function_stats(&self, function: &str, execution_time: Duration, success: bool) {
         // Update global stats
@@ -431,6 +484,7 @@ impl WasmPlugin for ExampleWasmPlugin {
             HealthStatus::Degraded => Some(format!("High failure rate: {}/{}", stats.failed_calls, stats.total_calls)),
             HealthStatus::Healthy => None,
             HealthStatus::Unknown => Some("Unknown status".to_string()),
+            HealthStatus::Disabled => Some("Plugin disabled".to_string()),
         };
         
         Ok(PluginHealth {
@@ -444,6 +498,9 @@ impl WasmPlugin for ExampleWasmPlugin {
             } else {
                 Duration::from_secs(0)
             },
+            pool: None,
+            circuit_state: CircuitState::Closed,
+            consecutive_failures: 0,
         })
     }
     
@@ -493,6 +550,7 @@ pub fn create_sample_wasm_plugin() -> Vec<u8> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::CallOrigin;
     
     #[tokio::test]
     async fn test_example_plugin_creation() {
@@ -528,6 +586,8 @@ mod tests {
             request_id: "test_request".to_string(),
             metadata: HashMap::new(),
             started_at: Instant::now(),
+            permissions: vec![],
+            origin: CallOrigin::Api,
         };
         
         let input = serde_json::json!({"name": "Test"});
@@ -550,6 +610,8 @@ mod tests {
             request_id: "test_request".to_string(),
             metadata: HashMap::new(),
             started_at: Instant::now(),
+            permissions: vec![],
+            origin: CallOrigin::Api,
         };
         
         let input = serde_json::json!({"text": "Hello world\nThis is a test"});
@@ -0,0 +1,591 @@
+//! Functions the host offers plugins under the `rusty_ai` import module.
+//!
+//! They follow the calling convention of `WasmPluginInstance::call`:
+//! strings are passed as pointer and length into the plugin's memory, and
+//! data handed back is written into a buffer from the plugin's `alloc`
+//! export and returned packed as `ptr << 32 | len`.
+//!
+//! - `scratchpad_get(key_ptr, key_len) -> i64` returns the JSON value stored
+//!   under the key, or 0 when there is none
+//! - `scratchpad_set(key_ptr, key_len, value_ptr, value_len) -> i32` stores a
+//!   JSON value (null removes the key) and returns one of the
+//!   `SCRATCHPAD_*` status codes
+//!
+//! Both act on the scratchpad of the session the current call serves, so a
+//! plugin can never reach another conversation's state.
+//!
+//! - `kv_get(key_ptr, key_len) -> i64` returns the bytes stored under the
+//!   key, or 0 when there are none
+//! - `kv_set(key_ptr, key_len, value_ptr, value_len) -> i32` stores the bytes
+//!   (an empty value removes the key) and returns one of the `KV_*` status
+//!   codes
+//!
+//! Key-value data outlives the instance and is scoped to the plugin's name,
+//! so every version of a plugin sees the same keys and no other plugin does.
+//!
+//! - `knowledge_search(query_ptr, query_len) -> i64` searches the knowledge
+//!   base for the user the current call serves and returns
+//!   `{"results": [{"id", "title", "content", "source", "tags"}]}`, or
+//!   `{"error": code, "message": ...}`: `permission_denied` for plugins
+//!   not granted [`KNOWLEDGE_ACCESS`], `unavailable`, `invalid` or `failed`
+//!
+//! While a call is recorded every reply is kept on the store's
+//! [`HostTape`]; while a recording is replayed the replies come from the
+//! tape and the live services are never reached.
+use crate::security::{SecurityPolicy, KNOWLEDGE_ACCESS};
+use async_trait::async_trait;
+use rusty_ai_common::scratchpad::{Scratchpads, MAX_SCRATCHPAD_BYTES};
+use rusty_ai_common::{AssistantError, Document, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::Arc;
+use tracing::{debug, warn};
+use uuid::Uuid;
+use wasmtime::{Caller, Extern, Linker};
+
+pub const HOST_MODULE: &str = "rusty_ai";
+
+pub const SCRATCHPAD_OK: i32 = 0;
+/// The call is not serving a session, or the host keeps no scratchpads
+pub const SCRATCHPAD_NO_SESSION: i32 = -1;
+/// The key is not UTF-8 or the value is not JSON
+pub const SCRATCHPAD_INVALID: i32 = -2;
+/// The write would take the scratchpad past its size cap
+pub const SCRATCHPAD_TOO_LARGE: i32 = -3;
+
+pub const KV_OK: i32 = 0;
+/// The host keeps no plugin data
+pub const KV_UNAVAILABLE: i32 = -1;
+/// The key is empty, too long or not UTF-8
+pub const KV_INVALID: i32 = -2;
+/// The value is over `MAX_KV_VALUE_BYTES`
+pub const KV_TOO_LARGE: i32 = -3;
+/// Storage refused the write
+pub const KV_FAILED: i32 = -4;
+
+pub const MAX_KV_KEY_BYTES: usize = 256;
+pub const MAX_KV_VALUE_BYTES: usize = 64 * 1024;
+/// Results returned by one `knowledge_search` call
+pub const KNOWLEDGE_SEARCH_LIMIT: usize = 5;
+/// Longest query `knowledge_search` accepts, in bytes
+const MAX_QUERY_BYTES: usize = 1024;
+
+/// Store data that knows which session's scratchpad the current call may use
+pub trait ScratchpadHost {
+    fn scratchpad(&self) -> Option<(Arc<Scratchpads>, Uuid)>;
+}
+
+/// Persistent storage behind `kv_get`/`kv_set`. The host passes the id of
+/// the calling plugin, so implementations only need to key by it
+#[async_trait]
+pub trait PluginKvStore: Send + Sync {
+    async fn get(&self, plugin_id: &str, key: &str) -> Result<Option<Vec<u8>>>;
+    /// Replace the value under the key; `None` removes it
+    async fn set(&self, plugin_id: &str, key: &str, value: Option<&[u8]>) -> Result<()>;
+}
+
+/// The knowledge base behind `knowledge_search`
+#[async_trait]
+pub trait KnowledgeSearch: Send + Sync {
+    /// Documents matching the query, searched on behalf of `user_id`
+    async fn search(&self, user_id: &str, query: &str, limit: usize) -> Result<Vec<Document>>;
+}
+
+/// What the host functions beyond the scratchpad reach, and the policy
+/// deciding which of them a plugin may use
+#[derive(Clone, Default)]
+pub struct HostServices {
+    pub kv: Option<Arc<dyn PluginKvStore>>,
+    pub knowledge: Option<Arc<dyn KnowledgeSearch>>,
+    pub policy: SecurityPolicy,
+}
+
+/// Why a plugin's knowledge search is not run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HostDenial {
+    PermissionDenied,
+    Unavailable,
+}
+
+impl HostDenial {
+    pub fn code(self) -> &'static str {
+        match self {
+            HostDenial::PermissionDenied => "permission_denied",
+            HostDenial::Unavailable => "unavailable",
+        }
+    }
+}
+
+/// Store data that knows which plugin it belongs to and which user the
+/// current call serves
+pub trait ServiceHost {
+    /// The key-value store and the plugin id it is scoped to
+    fn kv(&self) -> Option<(Arc<dyn PluginKvStore>, String)>;
+    /// The knowledge base and the user to search for
+    fn knowledge(&self) -> std::result::Result<(Arc<dyn KnowledgeSearch>, String), HostDenial>;
+}
+
+/// What a host function handed back to the plugin
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HostReply {
+    /// Bytes copied into the plugin's memory
+    Data(#[serde(with = "crate::replay::hex_bytes")] Vec<u8>),
+    /// Nothing to hand back; the plugin got 0
+    Empty,
+    Status(i32),
+}
+
+/// One host function call made by a recorded plugin call
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HostInteraction {
+    pub function: String,
+    /// The key or query the plugin passed, if it could be read
+    #[serde(default)]
+    pub request: Option<String>,
+    pub reply: HostReply,
+}
+
+/// The host function calls of one plugin call, as they are recorded or
+/// replayed
+#[derive(Debug)]
+pub enum HostTape {
+    Recording(Vec<HostInteraction>),
+    /// Replies are handed out in recorded order. A call that does not match
+    /// the next recorded one, or comes after the last, gets the function's
+    /// "unavailable" reply and is noted as a divergence
+    Replaying {
+        remaining: VecDeque<HostInteraction>,
+        divergences: Vec<String>,
+    },
+}
+
+/// A tape shared between the host and the store of the instance running
+/// the call
+pub type SharedHostTape = Arc<std::sync::Mutex<HostTape>>;
+
+impl HostTape {
+    pub fn recording() -> Self {
+        Self::Recording(Vec::new())
+    }
+
+    pub fn replaying(interactions: Vec<HostInteraction>) -> Self {
+        Self::Replaying { remaining: interactions.into(), divergences: Vec::new() }
+    }
+
+    pub fn shared(self) -> SharedHostTape {
+        Arc::new(std::sync::Mutex::new(self))
+    }
+
+    // The reply to hand back when replaying, None when the call runs live
+    fn replay(&mut self, function: &str, request: Option<&str>, unavailable: HostReply) -> Option<HostReply> {
+        let Self::Replaying { remaining, divergences } = self else {
+            return None;
+        };
+        match remaining.pop_front() {
+            Some(next) if next.function == function && next.request.as_deref() == request => Some(next.reply),
+            Some(next) => {
+                divergences.push(format!(
+                    "called {}({}) where the recording has {}({})",
+                    function,
+                    request.unwrap_or("?"),
+                    next.function,
+                    next.request.as_deref().unwrap_or("?")
+                ));
+                Some(unavailable)
+            }
+            None => {
+                divergences.push(format!("called {}({}) after the last recorded host call", function, request.unwrap_or("?")));
+                Some(unavailable)
+            }
+        }
+    }
+
+    fn record(&mut self, function: &str, request: Option<String>, reply: &HostReply) {
+        if let Self::Recording(interactions) = self {
+            interactions.push(HostInteraction { function: function.to_string(), request, reply: reply.clone() });
+        }
+    }
+
+    /// The recorded calls, or when replaying, the ones not made
+    pub fn interactions(&self) -> Vec<HostInteraction> {
+        match self {
+            Self::Recording(interactions) => interactions.clone(),
+            Self::Replaying { remaining, .. } => remaining.iter().cloned().collect(),
+        }
+    }
+
+    /// How a replay strayed from the recording, in order
+    pub fn divergences(&self) -> &[String] {
+        match self {
+            Self::Recording(_) => &[],
+            Self::Replaying { divergences, .. } => divergences,
+        }
+    }
+}
+
+/// Store data that may be recording or replaying the current call
+pub trait TapeHost {
+    fn tape(&self) -> Option<SharedHostTape>;
+}
+
+// The recorded reply when the store replays, None when the call runs live
+fn replayed<T: TapeHost>(caller: &Caller<'_, T>, function: &str, request: Option<&str>, unavailable: HostReply) -> Option<HostReply> {
+    caller.data().tape()?.lock().unwrap().replay(function, request, unavailable)
+}
+
+fn record<T: TapeHost>(caller: &Caller<'_, T>, function: &str, request: Option<String>, reply: &HostReply) {
+    if let Some(tape) = caller.data().tape() {
+        tape.lock().unwrap().record(function, request, reply);
+    }
+}
+
+// Hands a reply to a function returning packed output
+async fn deliver<T: Send>(caller: &mut Caller<'_, T>, reply: HostReply) -> anyhow::Result<i64> {
+    match reply {
+        HostReply::Data(bytes) => write_output(caller, &bytes).await,
+        HostReply::Empty | HostReply::Status(_) => Ok(0),
+    }
+}
+
+// Hands a reply to a function returning a status code
+fn status(reply: &HostReply, unavailable: i32) -> i32 {
+    match reply {
+        HostReply::Status(code) => *code,
+        HostReply::Data(_) | HostReply::Empty => unavailable,
+    }
+}
+
+pub fn add_to_linker<T: ScratchpadHost + ServiceHost + TapeHost + Send + 'static>(linker: &mut Linker<T>) -> Result<()> {
+    let fail = |e: anyhow::Error| AssistantError::Plugin(format!("Failed to add host functions to linker: {}", e));
+
+    linker
+        .func_wrap_async(HOST_MODULE, "scratchpad_get", |mut caller: Caller<'_, T>, (key_ptr, key_len): (i32, i32)| {
+            Box::new(async move {
+                let key = read_string(&mut caller, key_ptr, key_len);
+                let reply = match replayed(&caller, "scratchpad_get", key.as_deref(), HostReply::Empty) {
+                    Some(reply) => reply,
+                    None => match (caller.data().scratchpad(), &key) {
+                        (Some((scratchpads, session_id)), Some(key)) => match scratchpads.get(session_id).get(key) {
+                            Some(value) => HostReply::Data(value.to_string().into_bytes()),
+                            None => HostReply::Empty,
+                        },
+                        _ => HostReply::Empty,
+                    },
+                };
+                record(&caller, "scratchpad_get", key, &reply);
+                deliver(&mut caller, reply).await
+            })
+        })
+        .map_err(fail)?;
+
+    linker
+        .func_wrap_async(
+            HOST_MODULE,
+            "scratchpad_set",
+            |mut caller: Caller<'_, T>, (key_ptr, key_len, value_ptr, value_len): (i32, i32, i32, i32)| {
+                Box::new(async move {
+                    let key = read_string(&mut caller, key_ptr, key_len);
+                    let unavailable = HostReply::Status(SCRATCHPAD_NO_SESSION);
+                    let reply = match replayed(&caller, "scratchpad_set", key.as_deref(), unavailable) {
+                        Some(reply) => reply,
+                        None => HostReply::Status(scratchpad_set(&mut caller, key.clone(), value_ptr, value_len)),
+                    };
+                    record(&caller, "scratchpad_set", key, &reply);
+                    status(&reply, SCRATCHPAD_NO_SESSION)
+                })
+            },
+        )
+        .map_err(fail)?;
+
+    linker
+        .func_wrap_async(HOST_MODULE, "kv_get", |mut caller: Caller<'_, T>, (key_ptr, key_len): (i32, i32)| {
+            Box::new(async move {
+                let key = read_key(&mut caller, key_ptr, key_len);
+                let reply = match replayed(&caller, "kv_get", key.as_deref(), HostReply::Empty) {
+                    Some(reply) => reply,
+                    None => kv_get(caller.data().kv(), key.as_deref()).await,
+                };
+                record(&caller, "kv_get", key, &reply);
+                deliver(&mut caller, reply).await
+            })
+        })
+        .map_err(fail)?;
+
+    linker
+        .func_wrap_async(
+            HOST_MODULE,
+            "kv_set",
+            |mut caller: Caller<'_, T>, (key_ptr, key_len, value_ptr, value_len): (i32, i32, i32, i32)| {
+                Box::new(async move {
+                    let key = read_key(&mut caller, key_ptr, key_len);
+                    let reply = match replayed(&caller, "kv_set", key.as_deref(), HostReply::Status(KV_UNAVAILABLE)) {
+                        Some(reply) => reply,
+                        None => {
+                            let value = read_bytes(&mut caller, value_ptr, value_len, MAX_KV_VALUE_BYTES);
+                            HostReply::Status(kv_set(caller.data().kv(), key.as_deref(), value_len, value).await)
+                        }
+                    };
+                    record(&caller, "kv_set", key, &reply);
+                    status(&reply, KV_UNAVAILABLE)
+                })
+            },
+        )
+        .map_err(fail)?;
+
+    linker
+        .func_wrap_async(HOST_MODULE, "knowledge_search", |mut caller: Caller<'_, T>, (query_ptr, query_len): (i32, i32)| {
+            Box::new(async move {
+                let query = read_bytes(&mut caller, query_ptr, query_len, MAX_QUERY_BYTES)
+                    .and_then(|query| String::from_utf8(query).ok());
+                let unavailable = HostReply::Data(denied(HostDenial::Unavailable).to_string().into_bytes());
+                let reply = match replayed(&caller, "knowledge_search", query.as_deref(), unavailable) {
+                    Some(reply) => reply,
+                    None => {
+                        let response = knowledge_search(caller.data().knowledge(), query.as_deref()).await;
+                        HostReply::Data(response.to_string().into_bytes())
+                    }
+                };
+                record(&caller, "knowledge_search", query, &reply);
+                deliver(&mut caller, reply).await
+            })
+        })
+        .map_err(fail)?;
+    Ok(())
+}
+
+fn scratchpad_set<T: ScratchpadHost>(caller: &mut Caller<'_, T>, key: Option<String>, value_ptr: i32, value_len: i32) -> i32 {
+    let Some((scratchpads, session_id)) = caller.data().scratchpad() else {
+        return SCRATCHPAD_NO_SESSION;
+    };
+    if usize::try_from(value_len).map_or(true, |len| len > MAX_SCRATCHPAD_BYTES) {
+        return SCRATCHPAD_TOO_LARGE;
+    }
+    let value = read_string(caller, value_ptr, value_len)
+        .and_then(|value| serde_json::from_str::<serde_json::Value>(&value).ok());
+    let (Some(key), Some(value)) = (key, value) else {
+        return SCRATCHPAD_INVALID;
+    };
+    match scratchpads.set(session_id, key, value) {
+        Ok(()) => SCRATCHPAD_OK,
+        Err(e) => {
+            debug!("Plugin scratchpad write refused: {}", e);
+            SCRATCHPAD_TOO_LARGE
+        }
+    }
+}
+
+async fn kv_get(kv: Option<(Arc<dyn PluginKvStore>, String)>, key: Option<&str>) -> HostReply {
+    let (Some((kv, plugin_id)), Some(key)) = (kv, key) else {
+        return HostReply::Empty;
+    };
+    match kv.get(&plugin_id, key).await {
+        Ok(Some(value)) => HostReply::Data(value),
+        Ok(None) => HostReply::Empty,
+        Err(e) => {
+            warn!("Plugin {} failed to read key {}: {}", plugin_id, key, e);
+            HostReply::Empty
+        }
+    }
+}
+
+async fn kv_set(
+    kv: Option<(Arc<dyn PluginKvStore>, String)>,
+    key: Option<&str>,
+    value_len: i32,
+    value: Option<Vec<u8>>,
+) -> i32 {
+    let Some((kv, plugin_id)) = kv else {
+        return KV_UNAVAILABLE;
+    };
+    let Some(key) = key else {
+        return KV_INVALID;
+    };
+    if usize::try_from(value_len).is_ok_and(|len| len > MAX_KV_VALUE_BYTES) {
+        return KV_TOO_LARGE;
+    }
+    let Some(value) = value else {
+        return KV_INVALID;
+    };
+    let value = (!value.is_empty()).then_some(value.as_slice());
+    match kv.set(&plugin_id, key, value).await {
+        Ok(()) => KV_OK,
+        Err(e) => {
+            warn!("Plugin {} failed to write key {}: {}", plugin_id, key, e);
+            KV_FAILED
+        }
+    }
+}
+
+async fn knowledge_search(
+    access: std::result::Result<(Arc<dyn KnowledgeSearch>, String), HostDenial>,
+    query: Option<&str>,
+) -> serde_json::Value {
+    let (knowledge, user_id) = match access {
+        Ok(access) => access,
+        Err(denial) => return denied(denial),
+    };
+    let Some(query) = query else {
+        return error_response("invalid", "The query is not UTF-8 or is too long");
+    };
+    match knowledge.search(&user_id, query, KNOWLEDGE_SEARCH_LIMIT).await {
+        Ok(documents) => search_results(&documents),
+        Err(e) => {
+            warn!("Plugin knowledge search failed: {}", e);
+            error_response("failed", "The knowledge search failed")
+        }
+    }
+}
+
+fn denied(denial: HostDenial) -> serde_json::Value {
+    let message = match denial {
+        HostDenial::PermissionDenied => format!("The plugin is not granted the {} capability", KNOWLEDGE_ACCESS),
+        HostDenial::Unavailable => "The host has no knowledge base".to_string(),
+    };
+    error_response(denial.code(), &message)
+}
+
+fn error_response(code: &str, message: &str) -> serde_json::Value {
+    serde_json::json!({ "error": code, "message": message })
+}
+
+// Embeddings and scores stay on the host
+fn search_results(documents: &[Document]) -> serde_json::Value {
+    let results: Vec<serde_json::Value> = documents
+        .iter()
+        .map(|doc| {
+            serde_json::json!({
+                "id": doc.id,
+                "title": doc.title,
+                "content": doc.content,
+                "source": doc.metadata.source,
+                "tags": doc.metadata.tags,
+            })
+        })
+        .collect();
+    serde_json::json!({ "results": results })
+}
+
+fn read_string<T>(caller: &mut Caller<'_, T>, ptr: i32, len: i32) -> Option<String> {
+    String::from_utf8(read_bytes(caller, ptr, len, MAX_SCRATCHPAD_BYTES)?).ok()
+}
+
+fn read_key<T>(caller: &mut Caller<'_, T>, ptr: i32, len: i32) -> Option<String> {
+    let key = String::from_utf8(read_bytes(caller, ptr, len, MAX_KV_KEY_BYTES)?).ok()?;
+    (!key.is_empty()).then_some(key)
+}
+
+fn read_bytes<T>(caller: &mut Caller<'_, T>, ptr: i32, len: i32, max: usize) -> Option<Vec<u8>> {
+    let memory = caller.get_export("memory").and_then(Extern::into_memory)?;
+    let len = usize::try_from(len).ok().filter(|len| *len <= max)?;
+    let mut buffer = vec![0u8; len];
+    memory.read(&*caller, ptr as u32 as usize, &mut buffer).ok()?;
+    Some(buffer)
+}
+
+// Copies `bytes` into a buffer the plugin allocates and returns it packed
+async fn write_output<T: Send>(caller: &mut Caller<'_, T>, bytes: &[u8]) -> anyhow::Result<i64> {
+    let memory = caller
+        .get_export("memory")
+        .and_then(Extern::into_memory)
+        .ok_or_else(|| anyhow::anyhow!("plugin does not export its memory"))?;
+    let alloc = caller
+        .get_export("alloc")
+        .and_then(Extern::into_func)
+        .ok_or_else(|| anyhow::anyhow!("plugin has no alloc export"))?
+        .typed::<i32, i32>(&*caller)?;
+    let ptr = alloc.call_async(&mut *caller, i32::try_from(bytes.len())?).await?;
+    memory.write(&mut *caller, ptr as u32 as usize, bytes)?;
+    Ok(((ptr as u32 as i64) << 32) | bytes.len() as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasmtime::{Config, Engine, Module, Store};
+
+    struct Host {
+        scratchpads: Arc<Scratchpads>,
+        session_id: Option<Uuid>,
+    }
+
+    impl ScratchpadHost for Host {
+        fn scratchpad(&self) -> Option<(Arc<Scratchpads>, Uuid)> {
+            Some((self.scratchpads.clone(), self.session_id?))
+        }
+    }
+
+    impl ServiceHost for Host {
+        fn kv(&self) -> Option<(Arc<dyn PluginKvStore>, String)> {
+            None
+        }
+
+        fn knowledge(&self) -> std::result::Result<(Arc<dyn KnowledgeSearch>, String), HostDenial> {
+            Err(HostDenial::Unavailable)
+        }
+    }
+
+    impl TapeHost for Host {
+        fn tape(&self) -> Option<SharedHostTape> {
+            None
+        }
+    }
+
+    // Writes {"budget": 1200} in `remember` and hands back the stored
+    // "destination" from `recall`
+    const PLUGIN: &str = r#"
+        (module
+          (import "rusty_ai" "scratchpad_get" (func $get (param i32 i32) (result i64)))
+          (import "rusty_ai" "scratchpad_set" (func $set (param i32 i32 i32 i32) (result i32)))
+          (memory (export "memory") 1)
+          (data (i32.const 0) "destination")
+          (data (i32.const 16) "budget")
+          (data (i32.const 32) "1200")
+          (global $next (mut i32) (i32.const 1024))
+          (func (export "alloc") (param $len i32) (result i32)
+            (local $ptr i32)
+            (local.set $ptr (global.get $next))
+            (global.set $next (i32.add (global.get $next) (local.get $len)))
+            (local.get $ptr))
+          (func (export "remember") (result i32)
+            (call $set (i32.const 16) (i32.const 6) (i32.const 32) (i32.const 4)))
+          (func (export "recall") (result i64)
+            (call $get (i32.const 0) (i32.const 11))))
+    "#;
+
+    async fn instantiate(host: Host) -> (Store<Host>, wasmtime::Instance) {
+        let mut config = Config::new();
+        config.async_support(true);
+        let engine = Engine::new(&config).unwrap();
+        let module = Module::new(&engine, PLUGIN).unwrap();
+        let mut linker = Linker::new(&engine);
+        add_to_linker(&mut linker).unwrap();
+        let mut store = Store::new(&engine, host);
+        let instance = linker.instantiate_async(&mut store, &module).await.unwrap();
+        (store, instance)
+    }
+
+    #[tokio::test]
+    async fn test_plugins_share_the_session_scratchpad() {
+        let scratchpads = Arc::new(Scratchpads::new());
+        let session_id = Uuid::new_v4();
+        scratchpads.set(session_id, "destination", serde_json::json!("Lisbon")).unwrap();
+        let (mut store, instance) = instantiate(Host { scratchpads: scratchpads.clone(), session_id: Some(session_id) }).await;
+
+        let remember = instance.get_typed_func::<(), i32>(&mut store, "remember").unwrap();
+        assert_eq!(remember.call_async(&mut store, ()).await.unwrap(), SCRATCHPAD_OK);
+        assert_eq!(scratchpads.get(session_id).get("budget"), Some(&serde_json::json!(1200)));
+
+        let recall = instance.get_typed_func::<(), i64>(&mut store, "recall").unwrap();
+        let packed = recall.call_async(&mut store, ()).await.unwrap() as u64;
+        let (ptr, len) = ((packed >> 32) as usize, (packed & 0xffff_ffff) as usize);
+        let mut output = vec![0u8; len];
+        instance.get_memory(&mut store, "memory").unwrap().read(&store, ptr, &mut output).unwrap();
+        assert_eq!(output, br#""Lisbon""#);
+
+        // Outside a session there is nothing to read or write
+        store.data_mut().session_id = None;
+        assert_eq!(recall.call_async(&mut store, ()).await.unwrap(), 0);
+        assert_eq!(remember.call_async(&mut store, ()).await.unwrap(), SCRATCHPAD_NO_SESSION);
+    }
+}
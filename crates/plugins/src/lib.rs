@@ -1,30 +1,78 @@
-use rusty_ai_common::{Result, AssistantError};
+use rusty_ai_common::{Result, AssistantError, PluginAuditRecord, PluginConfig, PluginQuota};
+use rusty_ai_common::scratchpad::Scratchpads;
 use async_trait::async_trait;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
-use tokio::sync::{RwLock, Mutex};
+use tokio::sync::{broadcast, RwLock, Mutex};
 use tracing::{info, warn, error, debug, instrument};
 use wasmtime::*;
 use wasmtime_wasi::{WasiCtx, WasiCtxBuilder, WasiView};
 
+use breaker::{BreakerConfig, CircuitState};
+use limits::{ExecutionLimit, FunctionLimits};
+use pool::{InstanceFactory, InstancePool, PoolUtilization};
+
 pub mod runtime;
 pub mod loader;
 pub mod security;
 pub mod communication;
 pub mod example_plugin;
+pub mod permissions;
+pub mod marketplace;
+pub mod dev;
+pub mod scaffold;
+pub mod versions;
+pub mod host;
+pub mod metadata;
+pub mod limits;
+pub mod cache;
+pub mod pool;
+pub mod quota;
+pub mod breaker;
+pub mod cron;
+pub mod scheduler;
+pub mod output;
+pub mod dependencies;
+pub mod metrics;
+pub mod trust;
+pub mod replay;
+pub mod events;
 
 pub use runtime::*;
 pub use loader::*;
 pub use security::*;
 pub use communication::*;
+pub use permissions::*;
+pub use marketplace::*;
+pub use versions::*;
+
+/// Number of permission decisions kept for auditing
+const PERMISSION_AUDIT_CAPACITY: usize = 1000;
+
+/// How long one plugin's health check may take before it reports Unknown
+const DEFAULT_HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How long a successful health check is reused instead of calling the plugin
+const DEFAULT_HEALTH_CACHE_TTL: Duration = Duration::from_secs(10);
+
+/// How long unloading a plugin waits for its running calls to finish
+const DEFAULT_DRAIN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Retry hint for calls to a plugin that is being unloaded
+const UNLOADING_RETRY_AFTER_SECS: u64 = 1;
+
+/// Lifecycle events held for a subscriber that has not read them yet
+const LIFECYCLE_EVENT_BUFFER: usize = 64;
 
 /// Plugin execution limits and resource constraints
 #[derive(Debug, Clone)]
 pub struct ResourceLimits {
     /// Maximum memory allocation in bytes (default: 64MB)
     pub max_memory: u64,
+    /// Maximum elements in any one table (default: 10,000)
+    pub max_table_elements: u32,
     /// Maximum execution time for a single operation (default: 30s)
     pub max_execution_time: Duration,
     /// Maximum number of WASI file descriptors (default: 10)
@@ -35,44 +83,88 @@ pub struct ResourceLimits {
     pub cpu_time_limit: Duration,
     /// Maximum fuel units for Wasmtime execution (default: 1M)
     pub max_fuel: u64,
+    /// How often the manager advances the engine epoch; `cpu_time_limit` is
+    /// enforced in steps of this size (default: 10ms)
+    pub epoch_interval: Duration,
+    /// Bytes of stdout and of stderr kept per call; the rest is dropped
+    /// (default: 64KB)
+    pub max_output_bytes: usize,
 }
 
 impl Default for ResourceLimits {
     fn default() -> Self {
         Self {
             max_memory: 64 * 1024 * 1024, // 64MB
+            max_table_elements: 10_000,
             max_execution_time: Duration::from_secs(30),
             max_file_descriptors: 10,
             max_network_connections: 5,
             cpu_time_limit: Duration::from_secs(10),
             max_fuel: 1_000_000,
+            epoch_interval: Duration::from_millis(10),
+            max_output_bytes: output::DEFAULT_OUTPUT_CAPACITY,
         }
     }
 }
 
-/// Plugin metadata extracted from WASM module
+/// Plugin metadata extracted from WASM module (see [`metadata`]). Only the
+/// id, name and version are required
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct WasmPluginMetadata {
     pub id: String,
     pub name: String,
     pub version: String,
+    #[serde(default)]
     pub description: String,
+    #[serde(default)]
     pub author: String,
+    #[serde(default)]
     pub license: String,
+    #[serde(default)]
     pub capabilities: Vec<String>,
+    /// Plugins this one needs, as `name@semver-range` (see [`dependencies`])
+    #[serde(default)]
     pub dependencies: Vec<String>,
+    #[serde(default = "default_api_version")]
     pub api_version: String,
+    /// SHA-256 of the module, filled in by the host when it is loaded
+    #[serde(default)]
     pub checksum: String,
+    /// Tighter limits for single functions, by function name
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub function_limits: HashMap<String, FunctionLimits>,
+    /// Assistant events the plugin's `on_event` export receives, by
+    /// [`rusty_ai_common::PluginEvent::event_type`] (see [`events`])
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub events: Vec<String>,
+}
+
+fn default_api_version() -> String {
+    "1.0".to_string()
+}
+
+impl WasmPluginMetadata {
+    /// The limits a call to `function` runs under: `plugin_limits`, lowered
+    /// by the function's override if it declares one
+    pub fn limits_for(&self, function: &str, plugin_limits: &ResourceLimits) -> ResourceLimits {
+        match self.function_limits.get(function) {
+            Some(overrides) => overrides.apply(plugin_limits),
+            None => plugin_limits.clone(),
+        }
+    }
 }
 
 /// Plugin execution context
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct PluginContext {
     pub user_id: String,
     pub session_id: String,
     pub request_id: String,
     pub metadata: HashMap<String, String>,
     pub started_at: Instant,
+    /// Permissions of the caller, taken from their token claims
+    pub permissions: Vec<String>,
+    pub origin: CallOrigin,
 }
 
 /// WebAssembly plugin trait for sandboxed execution
@@ -95,6 +187,54 @@ pub trait WasmPlugin: Send + Sync {
     
     /// Cleanup resources
     async fn cleanup(&mut self) -> Result<()>;
+    
+    /// Fuel the last `execute` on this instance consumed; None for plugins
+    /// that are not fuel-metered
+    fn last_fuel_consumed(&self) -> Option<u64> {
+        None
+    }
+    
+    /// Stdout and stderr of the last `execute` on this instance; empty for
+    /// plugins that do not capture their output
+    fn last_output(&self) -> (String, String) {
+        (String::new(), String::new())
+    }
+    
+    /// `execute`, with the host functions it calls recorded on or answered
+    /// from `tape` (see [`replay`]). Plugins without host imports ignore it
+    async fn execute_with_tape(
+        &self,
+        function: &str,
+        input: &[u8],
+        context: &PluginContext,
+        _tape: host::SharedHostTape,
+    ) -> Result<Vec<u8>> {
+        self.execute(function, input, context).await
+    }
+}
+
+/// What a plugin call returned, with what the plugin printed while serving it
+#[derive(Debug, Clone)]
+pub struct PluginExecutionResult {
+    pub output: Vec<u8>,
+    /// Up to `max_output_bytes` of each stream, then a truncation marker
+    pub stdout: String,
+    pub stderr: String,
+    pub duration: Duration,
+}
+
+/// Where the manager records every plugin call. Records are written in the
+/// background, so a slow or failing sink never holds up the call
+#[async_trait]
+pub trait PluginAuditSink: Send + Sync {
+    async fn record(&self, record: PluginAuditRecord) -> Result<()>;
+}
+
+/// Where plugin configuration is kept across restarts, by plugin name
+#[async_trait]
+pub trait PluginConfigStore: Send + Sync {
+    async fn save(&self, plugin_id: &str, config: &PluginConfig) -> Result<()>;
+    async fn load(&self, plugin_id: &str) -> Result<Option<PluginConfig>>;
 }
 
 /// Plugin health status
@@ -106,6 +246,15 @@ pub struct PluginHealth {
     pub execution_count: u64,
     pub error_count: u64,
     pub average_execution_time: Duration,
+    /// Use of the plugin's instance pool, filled in by the manager
+    #[serde(default)]
+    pub pool: Option<PoolUtilization>,
+    /// Whether the manager lets calls through, and the failed calls in a
+    /// row counted towards refusing them; filled in by the manager
+    #[serde(default)]
+    pub circuit_state: CircuitState,
+    #[serde(default)]
+    pub consecutive_failures: u32,
 }
 
 #[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
@@ -114,21 +263,110 @@ pub enum HealthStatus {
     Degraded,
     Unhealthy,
     Unknown,
+    /// Turned off in the plugin's config; not checked
+    Disabled,
 }
 
 /// WASI context for plugin execution
 pub struct PluginWasiCtx {
     wasi: WasiCtx,
     limits: ResourceLimits,
+    limiter: limits::StoreLimiter,
+    /// The plugin's stdout and stderr, kept until the host takes them
+    stdout: output::CapturedStream,
+    stderr: output::CapturedStream,
+    scratchpads: Option<Arc<Scratchpads>>,
+    /// Session served by the call in progress, if any
+    session_id: Option<uuid::Uuid>,
+    services: host::HostServices,
+    /// Id and declared capabilities of the plugin, known once its metadata
+    /// is read
+    plugin_id: Option<String>,
+    capabilities: Vec<String>,
+    /// User served by the call in progress, if any
+    user_id: Option<String>,
+    /// Host calls of the call in progress, if it is recorded or replayed
+    tape: Option<host::SharedHostTape>,
 }
 
 impl PluginWasiCtx {
+    /// A context keeping up to `limits.max_output_bytes` of the plugin's
+    /// stdout and stderr per call
     pub fn new(limits: ResourceLimits) -> Result<Self> {
+        let capacity = limits.max_output_bytes;
+        Self::with_captured_output(limits, capacity)
+    }
+    
+    /// A context keeping up to `capacity` bytes of each stream per call
+    pub fn with_captured_output(limits: ResourceLimits, capacity: usize) -> Result<Self> {
+        let stdout = output::CapturedStream::new(capacity);
+        let stderr = output::CapturedStream::new(capacity);
         let wasi = WasiCtxBuilder::new()
-            .inherit_stdio()
+            .stdout(stdout.clone())
+            .stderr(stderr.clone())
             .build();
-            
-        Ok(Self { wasi, limits })
+        
+        Ok(Self {
+            wasi,
+            limiter: limits::StoreLimiter::new(&limits),
+            limits,
+            stdout,
+            stderr,
+            scratchpads: None,
+            session_id: None,
+            services: host::HostServices::default(),
+            plugin_id: None,
+            capabilities: Vec::new(),
+            user_id: None,
+            tape: None,
+        })
+    }
+    
+    /// Give the plugin the `scratchpad_get`/`scratchpad_set` host imports
+    /// over these scratchpads
+    pub fn with_scratchpads(mut self, scratchpads: Arc<Scratchpads>) -> Self {
+        self.scratchpads = Some(scratchpads);
+        self
+    }
+    
+    /// Give the plugin the `kv_*` and `knowledge_search` host imports over
+    /// these services
+    pub fn with_host_services(mut self, services: host::HostServices) -> Self {
+        self.services = services;
+        self
+    }
+    
+    /// Take what the plugin wrote to stdout and stderr since the last take
+    fn take_output(&self) -> (Vec<u8>, Vec<u8>) {
+        (self.stdout.take(), self.stderr.take())
+    }
+}
+
+impl host::ScratchpadHost for PluginWasiCtx {
+    fn scratchpad(&self) -> Option<(Arc<Scratchpads>, uuid::Uuid)> {
+        Some((self.scratchpads.clone()?, self.session_id?))
+    }
+}
+
+impl host::ServiceHost for PluginWasiCtx {
+    fn kv(&self) -> Option<(Arc<dyn host::PluginKvStore>, String)> {
+        Some((self.services.kv.clone()?, self.plugin_id.clone()?))
+    }
+    
+    fn knowledge(&self) -> std::result::Result<(Arc<dyn host::KnowledgeSearch>, String), host::HostDenial> {
+        if !self.services.policy.grants(&self.capabilities, KNOWLEDGE_ACCESS) {
+            return Err(host::HostDenial::PermissionDenied);
+        }
+        match (&self.services.knowledge, &self.user_id) {
+            (Some(knowledge), Some(user_id)) => Ok((knowledge.clone(), user_id.clone())),
+            _ => Err(host::HostDenial::Unavailable),
+        }
+    }
+}
+
+impl host::TapeHost for PluginWasiCtx {
+    fn tape(&self) -> Option<host::SharedHostTape> {
+        self.tape.clone()
     }
 }
 
@@ -138,76 +376,550 @@ impl WasiView for PluginWasiCtx {
     }
 }
 
+/// Engine configured the way every plugin is run: async, fuel-metered,
+/// interruptible by epoch and without the WebAssembly features the sandbox
+/// does not allow
+pub fn create_plugin_engine() -> Result<Engine> {
+    let mut config = Config::new();
+    
+    // Enable WebAssembly features
+    config.wasm_component_model(true);
+    config.async_support(true);
+    config.consume_fuel(true);
+    config.epoch_interruption(true);
+    
+    // Security settings
+    config.wasm_multi_memory(false);
+    config.wasm_threads(false);
+    config.wasm_reference_types(false);
+    config.wasm_simd(false);
+    config.wasm_bulk_memory(false);
+    
+    Engine::new(&config)
+        .map_err(|e| AssistantError::Plugin(format!("Failed to create Wasmtime engine: {}", e)))
+}
+
+// The JSON object `WasmPlugin::initialize` receives for a config
+fn config_settings(config: &PluginConfig) -> serde_json::Value {
+    serde_json::Value::Object(config.settings.clone().into_iter().collect())
+}
+
+// The context a managed plugin instance runs with
+fn plugin_wasi_ctx(
+    limits: &ResourceLimits,
+    host_services: host::HostServices,
+    scratchpads: Option<Arc<Scratchpads>>,
+) -> Result<PluginWasiCtx> {
+    let mut wasi_ctx = PluginWasiCtx::new(limits.clone())?.with_host_services(host_services);
+    if let Some(scratchpads) = scratchpads {
+        wasi_ctx = wasi_ctx.with_scratchpads(scratchpads);
+    }
+    Ok(wasi_ctx)
+}
+
 /// Main WebAssembly plugin manager
 pub struct WasmPluginManager {
     engine: Engine,
-    plugins: Arc<RwLock<HashMap<String, Arc<Mutex<Box<dyn WasmPlugin>>>>>>,
+    /// Loaded versions per plugin name
+    plugins: Arc<RwLock<HashMap<String, PluginSlot>>>,
     default_limits: ResourceLimits,
     plugin_directory: PathBuf,
+    permission_policy: std::sync::RwLock<PermissionPolicy>,
+    function_schemas: Arc<RwLock<HashMap<String, HashMap<String, FunctionSchema>>>>,
+    permission_audit: Arc<RwLock<VecDeque<PermissionDecision>>>,
+    /// Last successful health check per plugin name
+    health_cache: Arc<RwLock<HashMap<String, CachedHealth>>>,
+    health_check_timeout: Duration,
+    health_cache_ttl: Duration,
+    /// Session scratchpads loaded plugins can read and write
+    scratchpads: Option<Arc<Scratchpads>>,
+    /// Storage and knowledge base behind the other host imports
+    host_services: host::HostServices,
+    /// Drives the epoch deadlines that enforce `cpu_time_limit`
+    epoch_ticker: limits::EpochTicker,
+    /// Checks plugins against the host services' policy before they serve,
+    /// counting the ones it rejects
+    sandbox: std::sync::Mutex<PluginSandbox>,
+    /// Compiled modules under the plugin directory, so loading the same
+    /// bytes again skips compilation
+    module_cache: cache::ModuleCache,
+    /// Instances each loaded plugin version may run calls on at once
+    pool_size: usize,
+    /// Enablement and priority per plugin name, for capability dispatch
+    configs: Arc<RwLock<HashMap<String, PluginConfig>>>,
+    /// Receives a record of each call, if calls are audited
+    audit_sink: Option<Arc<dyn PluginAuditSink>>,
+    /// Keeps `configs` across restarts, if configured
+    config_store: Option<Arc<dyn PluginConfigStore>>,
+    /// Calls per user and plugin against `PluginConfig.quota`
+    quotas: quota::QuotaTracker,
+    /// When a failing plugin's calls are refused, and for how long
+    breaker_config: BreakerConfig,
+    /// Plugins, or `name@version`s, waiting for their running calls to
+    /// finish before they are cleaned up; new calls to them are turned away
+    unloading: std::sync::Mutex<HashSet<String>>,
+    drain_timeout: Duration,
+    /// Picks the policy and limits each plugin loads under, if configured
+    trust_store: Option<Arc<trust::TrustStore>>,
+    /// Module bytes and signature of each `name@version` loaded from WASM,
+    /// to load it again when its trust level changes
+    sources: std::sync::RwLock<HashMap<String, PluginSource>>,
+    /// Keeps the calls of the plugins it is enabled for, to replay them
+    recorder: Option<Arc<replay::PluginRecorder>>,
+    /// Plugins whose serving version changed or that were unloaded
+    lifecycle: broadcast::Sender<PluginLifecycleEvent>,
+}
+
+/// What a plugin was loaded from
+#[derive(Clone)]
+struct PluginSource {
+    wasm_bytes: Arc<[u8]>,
+    signature: Option<PluginSignature>,
+}
+
+/// The plugin that answered a capability dispatch, with the plugins tried
+/// before it that failed
+#[derive(Debug)]
+pub struct CapabilityDispatch {
+    pub plugin_id: String,
+    pub output: Vec<u8>,
+    /// Plugin names and errors, in the order they were tried
+    pub failed: Vec<(String, String)>,
+}
+
+/// A health check result and the version and time it was taken for
+#[derive(Debug, Clone)]
+struct CachedHealth {
+    version: String,
+    health: PluginHealth,
+    checked_at: Instant,
 }
 
 impl WasmPluginManager {
     /// Create a new WebAssembly plugin manager
     pub fn new(plugin_directory: impl AsRef<Path>) -> Result<Self> {
-        let mut config = Config::new();
-        
-        // Enable WebAssembly features
-        config.wasm_component_model(true);
-        config.async_support(true);
-        config.consume_fuel(true);
-        
-        // Security settings
-        config.wasm_multi_memory(false);
-        config.wasm_threads(false);
-        config.wasm_reference_types(false);
-        config.wasm_simd(false);
-        config.wasm_bulk_memory(false);
-        
-        let engine = Engine::new(&config)
-            .map_err(|e| AssistantError::Plugin(format!("Failed to create Wasmtime engine: {}", e)))?;
-        
+        let engine = create_plugin_engine()?;
+        let default_limits = ResourceLimits::default();
+        let epoch_ticker = limits::EpochTicker::start(engine.clone(), default_limits.epoch_interval);
+        let sandbox = PluginSandbox::new(SecurityPolicy::default(), default_limits.clone());
+        let module_cache = cache::ModuleCache::open(
+            plugin_directory.as_ref().join(cache::MODULE_CACHE_DIR),
+            &engine,
+            cache::process_key(),
+        );
         Ok(Self {
             engine,
             plugins: Arc::new(RwLock::new(HashMap::new())),
-            default_limits: ResourceLimits::default(),
+            default_limits,
             plugin_directory: plugin_directory.as_ref().to_path_buf(),
+            permission_policy: std::sync::RwLock::new(PermissionPolicy::default()),
+            function_schemas: Arc::new(RwLock::new(HashMap::new())),
+            permission_audit: Arc::new(RwLock::new(VecDeque::new())),
+            health_cache: Arc::new(RwLock::new(HashMap::new())),
+            health_check_timeout: DEFAULT_HEALTH_CHECK_TIMEOUT,
+            health_cache_ttl: DEFAULT_HEALTH_CACHE_TTL,
+            scratchpads: None,
+            host_services: host::HostServices::default(),
+            epoch_ticker,
+            sandbox: std::sync::Mutex::new(sandbox),
+            module_cache,
+            pool_size: pool::DEFAULT_POOL_SIZE,
+            configs: Arc::new(RwLock::new(HashMap::new())),
+            audit_sink: None,
+            config_store: None,
+            quotas: quota::QuotaTracker::new(),
+            breaker_config: BreakerConfig::default(),
+            unloading: std::sync::Mutex::new(HashSet::new()),
+            drain_timeout: DEFAULT_DRAIN_TIMEOUT,
+            trust_store: None,
+            sources: std::sync::RwLock::new(HashMap::new()),
+            recorder: None,
+            lifecycle: broadcast::channel(LIFECYCLE_EVENT_BUFFER).0,
         })
     }
     
-    /// Load a plugin from a WASM file
-    #[instrument(skip(self, wasm_bytes))]
+    /// Let plugins loaded from now on use the session scratchpads
+    pub fn with_scratchpads(mut self, scratchpads: Arc<Scratchpads>) -> Self {
+        self.scratchpads = Some(scratchpads);
+        self
+    }
+    
+    /// Let plugins loaded from now on use these host services, as far as
+    /// the services' policy grants them; the same policy decides which
+    /// plugins load at all
+    pub fn with_host_services(mut self, services: host::HostServices) -> Self {
+        self.sandbox.get_mut().unwrap().update_policy(services.policy.clone());
+        self.host_services = services;
+        self
+    }
+    
+    /// Keep plugin configs in `store`, and apply the stored config to every
+    /// plugin loaded from now on
+    pub fn with_config_store(mut self, store: Arc<dyn PluginConfigStore>) -> Self {
+        self.config_store = Some(store);
+        self
+    }
+    
+    /// Load plugins under the policy and limits of the trust level `store`
+    /// gives them, untrusted unless it names them. Without a store every
+    /// plugin loads under the host services' policy and the default limits
+    pub fn with_trust_store(mut self, store: Arc<trust::TrustStore>) -> Self {
+        self.trust_store = Some(store);
+        self
+    }
+    
+    /// Authenticate compiled modules with a key the host keeps, so they are
+    /// reused across restarts. Without one the cache only serves reloads
+    /// within this process
+    pub fn with_module_cache_key(mut self, key: &[u8]) -> Self {
+        self.module_cache = cache::ModuleCache::open(self.module_cache.dir().to_path_buf(), &self.engine, key);
+        self
+    }
+    
+    /// Record every call, allowed or not, with `sink`
+    pub fn with_audit_sink(mut self, sink: Arc<dyn PluginAuditSink>) -> Self {
+        self.audit_sink = Some(sink);
+        self
+    }
+    
+    /// Keep the calls that run in the plugins `recorder` is enabled for, to
+    /// be replayed with `replay`
+    pub fn with_recorder(mut self, recorder: Arc<replay::PluginRecorder>) -> Self {
+        self.recorder = Some(recorder);
+        self
+    }
+    
+    /// Plugins loaded, reloaded or unloaded from now on: `Loaded` whenever a
+    /// version starts serving plain-name calls, `Unloaded` when the whole
+    /// plugin goes. A subscriber that lags should resync from `list_plugins`
+    pub fn subscribe(&self) -> broadcast::Receiver<PluginLifecycleEvent> {
+        self.lifecycle.subscribe()
+    }
+    
+    // No subscribers is fine; nobody needs to hear about it
+    fn announce(&self, event: PluginLifecycleEvent) {
+        let _ = self.lifecycle.send(event);
+    }
+    
+    /// The compiled modules loads are served from
+    pub fn module_cache(&self) -> &cache::ModuleCache {
+        &self.module_cache
+    }
+    
+    /// The policy plugins are checked against before trust levels apply.
+    /// Its signing keys are the ones every way of installing a plugin
+    /// verifies against
+    pub fn security_policy(&self) -> SecurityPolicy {
+        self.host_services.policy.clone()
+    }
+    
+    /// Validation counts, including the plugins rejected by the security policy
+    pub fn security_stats(&self) -> ExecutionStats {
+        self.sandbox.lock().unwrap().get_stats().clone()
+    }
+    
+    /// Load an unsigned plugin from WASM bytes, as `load_signed_plugin`
     pub async fn load_plugin(&self, plugin_id: &str, wasm_bytes: &[u8]) -> Result<()> {
+        self.load_signed_plugin(plugin_id, wasm_bytes, None).await
+    }
+    
+    /// Load a plugin from WASM bytes and its detached signature, if it has
+    /// one; policies that require signatures reject plugins without one.
+    /// `name@version` loads that version next to the ones already serving,
+    /// to be activated with `cutover` or tried with `start_canary`; a bare
+    /// name replaces the active version.
+    #[instrument(skip(self, wasm_bytes, signature))]
+    pub async fn load_signed_plugin(
+        &self,
+        plugin_id: &str,
+        wasm_bytes: &[u8],
+        signature: Option<&PluginSignature>,
+    ) -> Result<()> {
         info!("Loading WebAssembly plugin: {}", plugin_id);
         
-        // Validate plugin before loading
-        self.validate_plugin(wasm_bytes).await?;
+        let (module, cache_hit) = self.module_cache.load(&self.engine, wasm_bytes, &cache::checksum(wasm_bytes))?;
+        debug!("Module for {} {}", plugin_id, if cache_hit { "read from cache" } else { "compiled" });
         
-        // Create plugin instance
-        let plugin = WasmPluginInstance::new(
-            &self.engine,
-            wasm_bytes,
-            self.default_limits.clone(),
-        ).await?;
+        let (name, version) = parse_plugin_ref(plugin_id);
+        let mut limits = self.default_limits.clone();
+        let mut host_services = self.host_services.clone();
+        let trust_level = self.trust_store.as_ref().map(|store| store.level(name));
+        if let Some(level) = trust_level {
+            debug!("Plugin {} loads as {:?}", name, level);
+            limits = level.limits(&limits);
+            host_services.policy = level.policy(&host_services.policy);
+        }
         
-        // Register plugin
-        let mut plugins = self.plugins.write().await;
-        plugins.insert(plugin_id.to_string(), Arc::new(Mutex::new(Box::new(plugin))));
+        // Checked before the module is instantiated, which already runs its
+        // start function
+        let policy = trust_level.map(|_| host_services.policy.clone());
+        let status = self.sandboxed(policy.clone(), |sandbox| sandbox.validate_module(name, wasm_bytes, &module, signature))?;
+        
+        // Further instances of the same module are created as calls need them
+        let wasm_bytes: Arc<[u8]> = Arc::from(wasm_bytes);
+        let pool_limits = limits.clone();
+        let factory: InstanceFactory = {
+            let engine = self.engine.clone();
+            let wasm_bytes = wasm_bytes.clone();
+            let host_services = host_services.clone();
+            let scratchpads = self.scratchpads.clone();
+            Arc::new(move || {
+                let (engine, module, wasm_bytes) = (engine.clone(), module.clone(), wasm_bytes.clone());
+                let (limits, host_services, scratchpads) = (limits.clone(), host_services.clone(), scratchpads.clone());
+                Box::pin(async move {
+                    let wasi_ctx = plugin_wasi_ctx(&limits, host_services, scratchpads)?;
+                    let instance = WasmPluginInstance::from_module(&engine, &module, &wasm_bytes, limits, wasi_ctx).await?;
+                    Ok(Box::new(instance) as Box<dyn WasmPlugin>)
+                })
+            })
+        };
+        let plugin = factory().await?;
+        self.sandboxed(policy, |sandbox| sandbox.validate_metadata(plugin.metadata(), status))?;
+        
+        let label = version.map(str::to_string).unwrap_or_else(|| plugin.metadata().version.clone());
+        let instances = InstancePool::new(plugin, factory, self.pool_size).with_limits(pool_limits);
+        self.register_instances(plugin_id, instances).await?;
+        let source = PluginSource { wasm_bytes, signature: signature.cloned() };
+        self.sources.write().unwrap().insert(format!("{}@{}", name, label), source);
         
         info!("Plugin loaded successfully: {}", plugin_id);
         Ok(())
     }
     
-    /// Load a plugin from file
+    /// Register an already constructed plugin and discover its function
+    /// schemas. Accepts `name@version` like `load_plugin`; without a version
+    /// the plugin's metadata version is used
+    pub async fn register_plugin(&self, plugin_id: &str, plugin: Box<dyn WasmPlugin>) -> Result<()> {
+        self.register_instances(plugin_id, InstancePool::single(plugin)).await
+    }
+    
+    /// Register a version served by a pool of instances
+    pub(crate) async fn register_instances(&self, plugin_id: &str, instances: InstancePool) -> Result<()> {
+        let (name, version) = parse_plugin_ref(plugin_id);
+        let label = version.map(str::to_string).unwrap_or_else(|| instances.metadata().version.clone());
+        let mut schemas = {
+            let plugin = instances.checkout().await?;
+            Self::discover_function_schemas(&*plugin).await
+        };
+        for schema in schemas.values_mut() {
+            let limits = instances.metadata().limits_for(&schema.name, self.pool_limits(&instances));
+            schema.limits = Some(FunctionLimits::effective(&limits));
+        }
+        debug!("Plugin {}@{} declares {} functions", name, label, schemas.len());
+        if let Some(config) = self.stored_config(name).await? {
+            instances.initialize_all(config_settings(&config)).await?;
+        }
+        
+        let loaded = Arc::new(LoadedVersion::new(label, instances, schemas.clone()));
+        let (retired, active) = {
+            let mut plugins = self.plugins.write().await;
+            match plugins.get_mut(name) {
+                Some(slot) => slot.add(loaded, version.is_none()),
+                None => {
+                    plugins.insert(name.to_string(), PluginSlot::new(loaded));
+                    (Vec::new(), true)
+                }
+            }
+        };
+        
+        if active {
+            self.function_schemas.write().await.insert(name.to_string(), schemas);
+            self.announce(PluginLifecycleEvent::Loaded { plugin_id: name.to_string(), version: label });
+        }
+        for version in retired {
+            self.retire(name, version);
+        }
+        Ok(())
+    }
+    
+    /// The limits calls to `instances` run under
+    fn pool_limits<'a>(&'a self, instances: &'a InstancePool) -> &'a ResourceLimits {
+        instances.limits().unwrap_or(&self.default_limits)
+    }
+    
+    /// Clean up a version taken out of service once its running calls finish
+    fn retire(&self, name: &str, version: Arc<LoadedVersion>) {
+        let name = name.to_string();
+        tokio::spawn(async move {
+            version.wait_drained().await;
+            match version.instances.cleanup_all().await {
+                Ok(()) => info!("Plugin unloaded: {}@{}", name, version.version),
+                Err(e) => warn!("Cleanup of {}@{} failed: {}", name, version.version, e),
+            }
+        });
+    }
+    
+    /// Switch the version serving calls to `name` in one step. Calls already
+    /// running in the previous version finish there before it is unloaded;
+    /// a running canary is stopped
+    pub async fn cutover(&self, name: &str, version: &str) -> Result<()> {
+        let previous = {
+            let mut plugins = self.plugins.write().await;
+            let slot = plugins
+                .get_mut(name)
+                .ok_or_else(|| AssistantError::NotFound(format!("Plugin not found: {}", name)))?;
+            let previous = slot.cutover(name, version)?;
+            self.function_schemas.write().await.insert(name.to_string(), slot.active_version().schemas.clone());
+            previous
+        };
+        
+        info!("Plugin {} cut over to {}", name, version);
+        self.announce(PluginLifecycleEvent::Loaded { plugin_id: name.to_string(), version: version.to_string() });
+        if let Some(previous) = previous {
+            self.retire(name, previous);
+        }
+        Ok(())
+    }
+    
+    /// Send a share of the calls to `name` to a standby version. It is
+    /// promoted or rolled back on its own once it has run enough calls
+    pub async fn start_canary(&self, name: &str, config: CanaryConfig) -> Result<()> {
+        let mut plugins = self.plugins.write().await;
+        let slot = plugins
+            .get_mut(name)
+            .ok_or_else(|| AssistantError::NotFound(format!("Plugin not found: {}", name)))?;
+        info!("Plugin {}: routing {}% of calls to {}", name, config.percent, config.version);
+        slot.start_canary(name, config)
+    }
+    
+    /// Stop routing calls to the canary; it stays loaded on standby
+    pub async fn stop_canary(&self, name: &str) -> Result<bool> {
+        let mut plugins = self.plugins.write().await;
+        let slot = plugins
+            .get_mut(name)
+            .ok_or_else(|| AssistantError::NotFound(format!("Plugin not found: {}", name)))?;
+        Ok(slot.stop_canary())
+    }
+    
+    /// Promote or roll back the canary of `name` if it has been judged
+    async fn settle_canary(&self, name: &str) {
+        let settled = {
+            let mut plugins = self.plugins.write().await;
+            let Some(slot) = plugins.get_mut(name) else { return };
+            let settled = slot.settle_canary();
+            if let Some((CanaryOutcome::Promoted, _)) = settled {
+                self.function_schemas.write().await.insert(name.to_string(), slot.active_version().schemas.clone());
+            }
+            settled
+        };
+        
+        if let Some((outcome, retired)) = settled {
+            info!("Plugin {} canary finished: {:?}, retiring {}", name, outcome, retired.version);
+            if outcome == CanaryOutcome::Promoted {
+                if let Some(version) = self.plugins.read().await.get(name).map(|slot| slot.active.clone()) {
+                    self.announce(PluginLifecycleEvent::Loaded { plugin_id: name.to_string(), version });
+                }
+            }
+            self.retire(name, retired);
+        }
+    }
+    
+    /// Loaded versions, active version and canary state of a plugin
+    pub async fn plugin_versions(&self, name: &str) -> Option<PluginVersions> {
+        self.plugins.read().await.get(name).map(|slot| slot.describe(name))
+    }
+    
+    /// Ask the plugin for its `list_functions` export; plugins without one get
+    /// no declared functions, so every call needs the baseline permission
+    async fn discover_function_schemas(plugin: &dyn WasmPlugin) -> HashMap<String, FunctionSchema> {
+        let context = PluginContext {
+            user_id: "system".to_string(),
+            session_id: String::new(),
+            request_id: uuid::Uuid::new_v4().to_string(),
+            metadata: HashMap::new(),
+            started_at: Instant::now(),
+            permissions: vec![ADMIN_PERMISSION.to_string()],
+            origin: CallOrigin::Api,
+        };
+        
+        match plugin.execute("list_functions", b"{}", &context).await {
+            Ok(output) => serde_json::from_slice(&output)
+                .map(|value| parse_function_schemas(&value))
+                .unwrap_or_default(),
+            Err(e) => {
+                debug!("Plugin has no usable list_functions export: {}", e);
+                HashMap::new()
+            }
+        }
+    }
+    
+    /// Replace the declared function schemas of a plugin
+    pub async fn register_function_schemas(&self, plugin_id: &str, schemas: Vec<FunctionSchema>) {
+        let schemas = schemas.into_iter().map(|s| (s.name.clone(), s)).collect();
+        self.function_schemas.write().await.insert(plugin_id.to_string(), schemas);
+    }
+    
+    /// Declared function schemas of a plugin
+    pub async fn get_function_schemas(&self, plugin_id: &str) -> Vec<FunctionSchema> {
+        self.function_schemas
+            .read()
+            .await
+            .get(plugin_id)
+            .map(|schemas| schemas.values().cloned().collect())
+            .unwrap_or_default()
+    }
+    
+    /// Check whether the caller in `context` may invoke `function`, recording the decision
+    pub async fn check_permission(&self, plugin_id: &str, function: &str, context: &PluginContext) -> PermissionDecision {
+        let decision = {
+            let schemas = self.function_schemas.read().await;
+            let schema = schemas.get(plugin_id).and_then(|s| s.get(function));
+            self.permission_policy.read().unwrap().check(
+                plugin_id,
+                function,
+                schema,
+                &context.user_id,
+                &context.permissions,
+                context.origin,
+            )
+        };
+        
+        info!(
+            target: "audit",
+            plugin_id,
+            function,
+            user_id = %context.user_id,
+            allowed = decision.allowed,
+            "Plugin permission decision: {}",
+            decision.reason
+        );
+        
+        let mut audit = self.permission_audit.write().await;
+        if audit.len() >= PERMISSION_AUDIT_CAPACITY {
+            audit.pop_front();
+        }
+        audit.push_back(decision.clone());
+        
+        decision
+    }
+    
+    /// Most recent permission decisions, newest last
+    pub async fn recent_permission_decisions(&self, limit: usize) -> Vec<PermissionDecision> {
+        let audit = self.permission_audit.read().await;
+        audit.iter().skip(audit.len().saturating_sub(limit)).cloned().collect()
+    }
+    
+    /// Set the permission policy applied to plugin function calls
+    pub fn set_permission_policy(&self, policy: PermissionPolicy) {
+        *self.permission_policy.write().unwrap() = policy;
+    }
+    
+    /// The permission policy currently applied
+    pub fn permission_policy(&self) -> PermissionPolicy {
+        self.permission_policy.read().unwrap().clone()
+    }
+    
+    /// Load a plugin from file, with the signature next to it if there is one
     #[instrument(skip(self))]
     pub async fn load_plugin_from_file(&self, plugin_id: &str, wasm_path: impl AsRef<Path>) -> Result<()> {
         let wasm_bytes = tokio::fs::read(wasm_path.as_ref()).await
             .map_err(|e| AssistantError::Plugin(format!("Failed to read plugin file: {}", e)))?;
+        let signature = PluginSignature::read_for(wasm_path.as_ref()).await?;
         
-        self.load_plugin(plugin_id, &wasm_bytes).await
+        self.load_signed_plugin(plugin_id, &wasm_bytes, signature.as_ref()).await
     }
     
     /// Execute a plugin function
-    #[instrument(skip(self, input))]
     pub async fn execute_plugin(
         &self,
         plugin_id: &str,
@@ -215,118 +927,734 @@ impl WasmPluginManager {
         input: &[u8],
         context: PluginContext,
     ) -> Result<Vec<u8>> {
-        let plugins = self.plugins.read().await;
+        self.execute_plugin_detailed(plugin_id, function, input, context)
+            .await
+            .map(|result| result.output)
+    }
+    
+    /// Execute a plugin function, returning what the plugin printed along
+    /// with its output
+    #[instrument(skip(self, input))]
+    pub async fn execute_plugin_detailed(
+        &self,
+        plugin_id: &str,
+        function: &str,
+        input: &[u8],
+        context: PluginContext,
+    ) -> Result<PluginExecutionResult> {
+        // A plain name resolves to the active version (or the canary) per
+        // call; `name@version` pins one
+        let (name, pinned) = parse_plugin_ref(plugin_id);
+        let (version, _in_flight) = {
+            let plugins = self.plugins.read().await;
+            let slot = plugins
+                .get(name)
+                .ok_or_else(|| self.not_loaded(plugin_id))?;
+            let version = match pinned {
+                Some(pinned) => slot
+                    .versions
+                    .get(pinned)
+                    .cloned()
+                    .ok_or_else(|| self.not_loaded(plugin_id))?,
+                None => slot.select(),
+            };
+            let in_flight = version.begin();
+            (version, in_flight)
+        };
         
-        let plugin = plugins
-            .get(plugin_id)
-            .ok_or_else(|| AssistantError::NotFound(format!("Plugin not found: {}", plugin_id)))?;
+        let started = Instant::now();
+        let decision = self.check_permission(name, function, &context).await;
+        if !decision.allowed {
+            let denied = AssistantError::Security(format!(
+                "Permission denied for {}::{}: {}",
+                name, function, decision.reason
+            ));
+            self.audit_call(name, function, input, &context, started, Err(&denied), None);
+            return Err(denied);
+        }
+        
+        // Counted per plugin name, so every version shares the quota
+        let quota = self.plugin_config(name).await.quota;
+        let _running = match self.quotas.admit(&context.user_id, name, &quota) {
+            Ok(permit) => permit,
+            Err(e) => {
+                self.audit_call(name, function, input, &context, started, Err(&e), None);
+                return Err(e);
+            }
+        };
+        
+        // A plugin that keeps failing is not called until its cooldown is
+        // over. Held until the outcome is known; a probe whose call is
+        // dropped on the way counts as failed
+        let breaker_permit = match version.breaker.admit(name, &self.breaker_config) {
+            Ok(permit) => permit,
+            Err(e) => {
+                self.audit_call(name, function, input, &context, started, Err(&e), None);
+                return Err(e);
+            }
+        };
+        
+        // Waits while every instance in the pool is busy
+        let plugin_guard = match version.instances.checkout().await {
+            Ok(guard) => guard,
+            Err(e) => {
+                breaker_permit.record(false);
+                self.audit_call(name, function, input, &context, started, Err(&e), None);
+                return Err(e);
+            }
+        };
         
-        let plugin_guard = plugin.lock().await;
+        // Fuel and the epoch deadline stop a guest that keeps the CPU; this
+        // timeout covers calls stuck waiting on the host. A function may
+        // declare a shorter one than the plugin's
+        let limits = plugin_guard.metadata().limits_for(function, self.pool_limits(&version.instances));
+        let tape = self
+            .recorder
+            .as_ref()
+            .filter(|recorder| recorder.is_enabled(name))
+            .map(|_| host::HostTape::recording().shared());
+        let execution_future = match &tape {
+            Some(tape) => plugin_guard.execute_with_tape(function, input, &context, tape.clone()),
+            None => plugin_guard.execute(function, input, &context),
+        };
+        let execution_started = Instant::now();
         
-        // Execute with timeout
-        let execution_future = plugin_guard.execute(function, input, &context);
+        // A call cut off by the timeout never reports its fuel or output
+        let (result, fuel_consumed, (stdout, stderr)) = match tokio::time::timeout(limits.max_execution_time, execution_future).await {
+            Ok(result) => (result, plugin_guard.last_fuel_consumed(), plugin_guard.last_output()),
+            Err(_) => (
+                Err(AssistantError::Plugin(format!(
+                    "Plugin {} {}",
+                    plugin_id,
+                    ExecutionLimit::WallClock.describe(&limits)
+                ))),
+                None,
+                Default::default(),
+            ),
+        };
+        let duration = execution_started.elapsed();
+        drop(plugin_guard);
+        let outcome = result.as_ref().map(Vec::as_slice);
+        self.audit_call(name, function, input, &context, started, outcome, fuel_consumed);
+        if let (Some(recorder), Some(tape)) = (&self.recorder, tape) {
+            let call = replay::RecordedCall {
+                format_version: replay::REPLAY_FORMAT_VERSION,
+                id: uuid::Uuid::new_v4(),
+                recorded_at: chrono::Utc::now(),
+                plugin_id: name.to_string(),
+                version: version.version.clone(),
+                function: function.to_string(),
+                input: input.to_vec(),
+                context: replay::RecordedContext::from(&context),
+                host_calls: tape.lock().unwrap().interactions(),
+                outcome: outcome.into(),
+            };
+            if let Err(e) = recorder.write(&call).await {
+                warn!("Failed to record call to {}::{}: {}", name, function, e);
+            }
+        }
         
-        match tokio::time::timeout(self.default_limits.max_execution_time, execution_future).await {
-            Ok(result) => result,
-            Err(_) => Err(AssistantError::Plugin(
-                format!("Plugin execution timeout: {}", plugin_id)
-            )),
+        version.record(result.is_ok());
+        breaker_permit.record(result.is_ok());
+        if let Some(limit) = result.as_ref().err().and_then(ExecutionLimit::of) {
+            warn!("Call to {}::{} was killed: {:?} limit", plugin_id, function, limit);
+            version.killed.record(limit);
+        }
+        if pinned.is_none() && self.plugins.read().await.get(name).is_some_and(PluginSlot::has_canary) {
+            self.settle_canary(name).await;
+        }
+        result.map(|output| PluginExecutionResult { output, stdout, stderr, duration })
+    }
+    
+    /// Run the calls recorded in a replay file again, in the active version
+    /// of their plugin, with every host function answered from the
+    /// recording. Permissions, quotas and the circuit breaker are not
+    /// consulted and the calls are not counted
+    pub async fn replay(&self, path: impl AsRef<Path>) -> Result<Vec<replay::ReplayReport>> {
+        let calls = replay::read_recording(path).await?;
+        let mut reports = Vec::with_capacity(calls.len());
+        for call in calls {
+            let version = self
+                .plugins
+                .read()
+                .await
+                .get(&call.plugin_id)
+                .map(|slot| slot.active_version().clone())
+                .ok_or_else(|| self.not_loaded(&call.plugin_id))?;
+            let plugin = version.instances.checkout().await?;
+            let limits = plugin.metadata().limits_for(&call.function, self.pool_limits(&version.instances));
+            let tape = host::HostTape::replaying(call.host_calls.clone()).shared();
+            let context = call.context.to_context();
+            let execution = plugin.execute_with_tape(&call.function, &call.input, &context, tape.clone());
+            let result = match tokio::time::timeout(limits.max_execution_time, execution).await {
+                Ok(result) => result,
+                Err(_) => Err(AssistantError::Plugin(format!(
+                    "Plugin {} {}",
+                    call.plugin_id,
+                    ExecutionLimit::WallClock.describe(&limits)
+                ))),
+            };
+            drop(plugin);
+            
+            let replayed = replay::CallOutcome::from(result.as_ref().map(Vec::as_slice));
+            let mut divergences = Vec::new();
+            {
+                let tape = tape.lock().unwrap();
+                divergences.extend(tape.divergences().iter().cloned());
+                divergences.extend(tape.interactions().into_iter().map(|unmade| {
+                    format!("never called {}({})", unmade.function, unmade.request.as_deref().unwrap_or("?"))
+                }));
+            }
+            reports.push(replay::ReplayReport {
+                call_id: call.id,
+                function: call.function,
+                recorded_version: call.version,
+                replayed_version: version.version.clone(),
+                difference: replay::describe_difference(&call.outcome, &replayed),
+                recorded: call.outcome,
+                replayed,
+                divergences,
+            });
+        }
+        Ok(reports)
+    }
+    
+    // Why a call found no plugin to run in: it is gone, or still finishing
+    // the calls it had when it was unloaded
+    fn not_loaded(&self, plugin_id: &str) -> AssistantError {
+        let (name, _) = parse_plugin_ref(plugin_id);
+        let unloading = self.unloading.lock().unwrap();
+        if unloading.contains(name) || unloading.contains(plugin_id) {
+            return AssistantError::Unavailable {
+                message: format!("Plugin {} is being unloaded", plugin_id),
+                retry_after_secs: UNLOADING_RETRY_AFTER_SECS,
+            };
         }
+        AssistantError::NotFound(format!("Plugin not found: {}", plugin_id))
+    }
+    
+    // Counts the call in the runtime metrics and hands its record to the
+    // audit sink without waiting for it
+    #[allow(clippy::too_many_arguments)]
+    fn audit_call(
+        &self,
+        name: &str,
+        function: &str,
+        input: &[u8],
+        context: &PluginContext,
+        started: Instant,
+        result: std::result::Result<&[u8], &AssistantError>,
+        fuel_consumed: Option<u64>,
+    ) {
+        metrics::global().record_call(name, function, input.len(), result, started.elapsed());
+        let Some(sink) = self.audit_sink.clone() else {
+            return;
+        };
+        let record = PluginAuditRecord {
+            id: uuid::Uuid::new_v4(),
+            plugin_id: name.to_string(),
+            function: function.to_string(),
+            user_id: context.user_id.clone(),
+            request_id: context.request_id.clone(),
+            input_size: input.len() as u64,
+            output_size: result.map_or(0, |output| output.len() as u64),
+            duration_ms: started.elapsed().as_millis() as u64,
+            success: result.is_ok(),
+            error: result.err().map(|e| e.to_string()),
+            fuel_consumed,
+            created_at: chrono::Utc::now(),
+        };
+        let call = format!("{}::{}", name, function);
+        tokio::spawn(async move {
+            if let Err(e) = sink.record(record).await {
+                warn!("Failed to record call to {} in the audit log: {}", call, e);
+            }
+        });
     }
     
     /// Get plugin metadata
     pub async fn get_plugin_metadata(&self, plugin_id: &str) -> Result<WasmPluginMetadata> {
-        let plugins = self.plugins.read().await;
-        
-        let plugin = plugins
-            .get(plugin_id)
-            .ok_or_else(|| AssistantError::NotFound(format!("Plugin not found: {}", plugin_id)))?;
+        let (name, pinned) = parse_plugin_ref(plugin_id);
+        let version = {
+            let plugins = self.plugins.read().await;
+            let slot = plugins.get(name);
+            match pinned {
+                Some(pinned) => slot.and_then(|s| s.versions.get(pinned)).cloned(),
+                None => slot.map(|s| Arc::clone(s.active_version())),
+            }
+            .ok_or_else(|| AssistantError::NotFound(format!("Plugin not found: {}", plugin_id)))?
+        };
         
-        let plugin_guard = plugin.lock().await;
-        Ok(plugin_guard.metadata().clone())
+        Ok(version.instances.metadata().clone())
+    }
+    
+    /// Initialize every instance of a plugin with `config`, waiting for
+    /// running calls to finish first. Instances created later get the same
+    /// config. Accepts `name@version`; a bare name is the active version
+    pub async fn initialize_plugin(&self, plugin_id: &str, config: serde_json::Value) -> Result<()> {
+        let (name, pinned) = parse_plugin_ref(plugin_id);
+        let version = {
+            let plugins = self.plugins.read().await;
+            let slot = plugins.get(name);
+            match pinned {
+                Some(pinned) => slot.and_then(|s| s.versions.get(pinned)).cloned(),
+                None => slot.map(|s| Arc::clone(s.active_version())),
+            }
+            .ok_or_else(|| AssistantError::NotFound(format!("Plugin not found: {}", plugin_id)))?
+        };
+        version.instances.initialize_all(config).await
     }
     
-    /// List all loaded plugins
+    /// List all loaded plugins by name
     pub async fn list_plugins(&self) -> Vec<String> {
         let plugins = self.plugins.read().await;
         plugins.keys().cloned().collect()
     }
     
-    /// Unload a plugin
-    #[instrument(skip(self))]
-    pub async fn unload_plugin(&self, plugin_id: &str) -> Result<()> {
-        info!("Unloading plugin: {}", plugin_id);
-        
-        let mut plugins = self.plugins.write().await;
+    /// Set whether a plugin takes part in capability dispatch and how it
+    /// ranks there. Kept by name, so it also applies to later versions
+    pub async fn set_plugin_config(&self, name: &str, config: PluginConfig) {
+        self.configs.write().await.insert(name.to_string(), config);
+    }
+    
+    /// Merge `config` into a plugin's current config, keep the result in the
+    /// config store and initialize every loaded version of the plugin with
+    /// the merged settings. Settings not named in `config` are kept, and a
+    /// setting given as `null` is removed; the other fields are replaced.
+    /// A plugin that is not loaded gets the config when it loads
+    #[instrument(skip(self, config))]
+    pub async fn configure_plugin(&self, plugin_id: &str, config: PluginConfig) -> Result<PluginConfig> {
+        config.quota.validate()?;
+        let (name, _) = parse_plugin_ref(plugin_id);
+        let mut merged = match self.stored_config(name).await? {
+            Some(current) => PluginConfig { settings: current.settings, ..config.clone() },
+            None => PluginConfig { settings: HashMap::new(), ..config.clone() },
+        };
+        for (key, value) in config.settings {
+            if value.is_null() {
+                merged.settings.remove(&key);
+            } else {
+                merged.settings.insert(key, value);
+            }
+        }
         
-        if let Some(plugin) = plugins.remove(plugin_id) {
-            let mut plugin_guard = plugin.lock().await;
-            plugin_guard.cleanup().await?;
-            info!("Plugin unloaded: {}", plugin_id);
+        if let Some(store) = &self.config_store {
+            store.save(name, &merged).await?;
         }
+        self.configs.write().await.insert(name.to_string(), merged.clone());
+        self.health_cache.write().await.remove(name);
         
-        Ok(())
+        let versions: Vec<Arc<LoadedVersion>> = match self.plugins.read().await.get(name) {
+            Some(slot) => slot.versions.values().cloned().collect(),
+            None => Vec::new(),
+        };
+        for version in versions {
+            version.instances.initialize_all(config_settings(&merged)).await?;
+        }
+        info!("Plugin {} configured (enabled: {}, priority: {})", name, merged.enabled, merged.priority);
+        Ok(merged)
     }
     
-    /// Perform health check on all plugins
-    pub async fn health_check_all(&self) -> HashMap<String, PluginHealth> {
-        let mut results = HashMap::new();
+    // The config set for a plugin, looked up in the config store the first
+    // time a plugin is asked about
+    async fn stored_config(&self, name: &str) -> Result<Option<PluginConfig>> {
+        if let Some(config) = self.configs.read().await.get(name) {
+            return Ok(Some(config.clone()));
+        }
+        let Some(store) = &self.config_store else {
+            return Ok(None);
+        };
+        let stored = store.load(name).await?;
+        if let Some(config) = &stored {
+            self.configs.write().await.entry(name.to_string()).or_insert_with(|| config.clone());
+        }
+        Ok(stored)
+    }
+    
+    /// A plugin's config; enabled with priority 0 unless one was set
+    pub async fn plugin_config(&self, name: &str) -> PluginConfig {
+        self.configs.read().await.get(name).cloned().unwrap_or_else(|| PluginConfig {
+            enabled: true,
+            priority: 0,
+            settings: HashMap::new(),
+            quota: PluginQuota::default(),
+        })
+    }
+    
+    /// Names of the enabled plugins whose active version declares
+    /// `capability`, highest `PluginConfig.priority` first. Equal priorities
+    /// are ordered by name, so the same plugin wins every time
+    pub async fn find_plugins_for_capability(&self, capability: &str) -> Vec<String> {
         let plugins = self.plugins.read().await;
+        let configs = self.configs.read().await;
         
-        for (id, plugin) in plugins.iter() {
-            let plugin_guard = plugin.lock().await;
-            match plugin_guard.health_check().await {
-                Ok(health) => {
-                    results.insert(id.clone(), health);
-                }
+        let mut matching: Vec<(i32, &String)> = plugins
+            .iter()
+            .filter(|(_, slot)| slot.active_version().instances.metadata().capabilities.iter().any(|c| c == capability))
+            .filter_map(|(name, _)| match configs.get(name) {
+                Some(config) => config.enabled.then_some((config.priority, name)),
+                None => Some((0, name)),
+            })
+            .collect();
+        matching.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(b.1)));
+        
+        matching.into_iter().map(|(_, name)| name.clone()).collect()
+    }
+    
+    /// Call the function named after `capability` on the best plugin for it.
+    /// A plugin that fails or refuses the call passes it on to the next in
+    /// `find_plugins_for_capability` order; the error lists every failure
+    /// once none is left. `NotFound` means no enabled plugin declares it
+    pub async fn dispatch_capability(&self, capability: &str, input: &[u8], context: PluginContext) -> Result<CapabilityDispatch> {
+        let candidates = self.find_plugins_for_capability(capability).await;
+        if candidates.is_empty() {
+            return Err(AssistantError::NotFound(format!("No plugin handles capability: {}", capability)));
+        }
+        
+        let mut failed = Vec::new();
+        for name in candidates {
+            match self.execute_plugin(&name, capability, input, context.clone()).await {
+                Ok(output) => return Ok(CapabilityDispatch { plugin_id: name, output, failed }),
                 Err(e) => {
-                    warn!("Health check failed for plugin {}: {}", id, e);
-                    results.insert(id.clone(), PluginHealth {
-                        status: HealthStatus::Unhealthy,
-                        message: Some(e.to_string()),
-                        last_check: chrono::Utc::now(),
-                        execution_count: 0,
-                        error_count: 1,
-                        average_execution_time: Duration::from_secs(0),
-                    });
+                    warn!("Plugin {} failed capability {}: {}", name, capability, e);
+                    failed.push((name, e.to_string()));
                 }
             }
         }
         
-        results
+        let failures: Vec<String> = failed.iter().map(|(name, error)| format!("{}: {}", name, error)).collect();
+        Err(AssistantError::Plugin(format!(
+            "Every plugin for capability {} failed ({})",
+            capability,
+            failures.join("; ")
+        )))
     }
     
-    /// Validate plugin security and structure
-    async fn validate_plugin(&self, wasm_bytes: &[u8]) -> Result<()> {
-        // Basic validation - check if it's valid WebAssembly
-        Module::new(&self.engine, wasm_bytes)
-            .map_err(|e| AssistantError::Plugin(format!("Invalid WebAssembly module: {}", e)))?;
+    /// Unload a plugin, or with `name@version` one standby version of it.
+    /// New calls to it are turned away at once as `Unavailable`; calls
+    /// already running get up to the drain timeout to finish before the
+    /// instances are cleaned up. Instances still busy then are left to be
+    /// dropped when their calls end, and the unload fails as timed out
+    #[instrument(skip(self))]
+    pub async fn unload_plugin(&self, plugin_id: &str) -> Result<()> {
+        info!("Unloading plugin: {}", plugin_id);
+        let (name, version) = parse_plugin_ref(plugin_id);
         
-        // Additional security validations can be added here
-        // - Check for prohibited imports
-        // - Validate exports
-        // - Scan for malicious patterns
+        let (unloading, removed): (String, Vec<Arc<LoadedVersion>>) = {
+            let mut plugins = self.plugins.write().await;
+            let whole_plugin = match (version, plugins.get(name)) {
+                (_, None) => return Ok(()),
+                (None, Some(_)) => true,
+                (Some(version), Some(slot)) if slot.active == version => {
+                    if slot.versions.len() > 1 {
+                        return Err(AssistantError::Plugin(format!(
+                            "{} is the active version of {}; cut over to another version first",
+                            version, name
+                        )));
+                    }
+                    true
+                }
+                (Some(_), Some(_)) => false,
+            };
+            
+            // Marked before the plugin leaves the map, so no call sees it missing
+            let unloading = if whole_plugin { name } else { plugin_id }.to_string();
+            self.unloading.lock().unwrap().insert(unloading.clone());
+            let removed = if whole_plugin {
+                self.sources.write().unwrap().retain(|reference, _| parse_plugin_ref(reference).0 != name);
+                self.function_schemas.write().await.remove(name);
+                self.health_cache.write().await.remove(name);
+                plugins.remove(name).map(|slot| slot.versions.into_values().collect()).unwrap_or_default()
+            } else {
+                let slot = plugins.get_mut(name).expect("checked above");
+                let version = version.expect("checked above");
+                if slot.canary_version() == Some(version) {
+                    slot.stop_canary();
+                }
+                self.sources.write().unwrap().remove(plugin_id);
+                slot.versions.remove(version).into_iter().collect()
+            };
+            (unloading, removed)
+        };
         
-        Ok(())
+        if unloading == name {
+            self.announce(PluginLifecycleEvent::Unloaded { plugin_id: name.to_string() });
+        }
+        let drained = self.drain(name, removed, tokio::time::Instant::now() + self.drain_timeout).await;
+        self.unloading.lock().unwrap().remove(&unloading);
+        drained
     }
     
-    /// Set resource limits for plugin execution
-    pub fn set_default_limits(&mut self, limits: ResourceLimits) {
-        self.default_limits = limits;
-    }
+    /// Give `name` a trust level in the trust store and load the versions
+    /// of it loaded from WASM again under that level's policy and limits.
+    /// A plugin the new policy rejects is unloaded, and the rejection
+    /// returned
+    pub async fn set_trust_level(&self, name: &str, level: trust::TrustLevel) -> Result<()> {
+        let store = self
+            .trust_store
+            .as_ref()
+            .ok_or_else(|| AssistantError::Plugin("No trust store is configured".to_string()))?;
+        store.set_level(name, level).await?;
+        
+        let loaded: Vec<String> = match self.plugins.read().await.get(name) {
+            Some(slot) => slot.versions.keys().map(|version| format!("{}@{}", name, version)).collect(),
+            None => return Ok(()),
+        };
+        let reloads: Vec<(String, PluginSource)> = {
+            let sources = self.sources.read().unwrap();
+            loaded.into_iter().filter_map(|reference| sources.get(&reference).cloned().map(|source| (reference, source))).collect()
+        };
+        for (reference, source) in reloads {
+            info!("Reloading plugin {} as {:?}", reference, level);
+            if let Err(e) = self.load_signed_plugin(&reference, &source.wasm_bytes, source.signature.as_ref()).await {
+                warn!("Plugin {} is rejected as {:?}, unloading it: {}", reference, level, e);
+                self.unload_plugin(name).await?;
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+    
+    /// Unload every plugin, draining them concurrently. Calls still running
+    /// at `deadline` are given up on as in `unload_plugin`
+    pub async fn unload_all(&self, timeout: Duration) -> Result<()> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        let removed: Vec<(String, Vec<Arc<LoadedVersion>>)> = {
+            let mut plugins = self.plugins.write().await;
+            self.unloading.lock().unwrap().extend(plugins.keys().cloned());
+            self.sources.write().unwrap().clear();
+            self.function_schemas.write().await.clear();
+            self.health_cache.write().await.clear();
+            plugins.drain().map(|(name, slot)| (name, slot.versions.into_values().collect())).collect()
+        };
+        info!("Unloading {} plugins", removed.len());
+        for (name, _) in &removed {
+            self.announce(PluginLifecycleEvent::Unloaded { plugin_id: name.clone() });
+        }
+        
+        let results = futures::future::join_all(removed.into_iter().map(|(name, versions)| async move {
+            let drained = self.drain(&name, versions, deadline).await;
+            self.unloading.lock().unwrap().remove(&name);
+            drained
+        }))
+        .await;
+        results.into_iter().collect()
+    }
+    
+    // Waits until `deadline` for the calls running in `versions` to finish,
+    // then cleans up their instances. Versions with calls still running are
+    // not cleaned up; the first error is returned once all have been tried
+    async fn drain(&self, name: &str, versions: Vec<Arc<LoadedVersion>>, deadline: tokio::time::Instant) -> Result<()> {
+        let mut result = Ok(());
+        for version in versions {
+            if tokio::time::timeout_at(deadline, version.wait_drained()).await.is_err() {
+                warn!(
+                    "Plugin {}@{} still has {} calls running; unloading it without cleanup",
+                    name,
+                    version.version,
+                    version.in_flight()
+                );
+                result = result.and(Err(AssistantError::Timeout(format!(
+                    "Calls to {}@{} did not finish before it was unloaded",
+                    name, version.version
+                ))));
+                continue;
+            }
+            match version.instances.cleanup_all().await {
+                Ok(()) => info!("Plugin unloaded: {}@{}", name, version.version),
+                Err(e) => result = result.and(Err(e)),
+            }
+        }
+        result
+    }
+    
+    /// Perform health check on all plugins. Plugins are checked concurrently,
+    /// each within the health check timeout; one that does not answer in time
+    /// reports Unknown. A successful result is reused for the cache TTL, so
+    /// frequent probes do not call into the plugins at all. A plugin with a
+    /// call killed by its resource limits recently reports at best Degraded.
+    /// Disabled plugins are not called and report Disabled
+    pub async fn health_check_all(&self) -> HashMap<String, PluginHealth> {
+        // Clone the handles first so no lock is held while plugins are checked
+        let plugins: Vec<(String, Arc<LoadedVersion>, bool)> = {
+            let plugins = self.plugins.read().await;
+            let configs = self.configs.read().await;
+            plugins
+                .iter()
+                .map(|(name, slot)| {
+                    let enabled = configs.get(name).map_or(true, |config| config.enabled);
+                    (name.clone(), Arc::clone(slot.active_version()), enabled)
+                })
+                .collect()
+        };
+        
+        let checks = plugins.into_iter().map(|(id, version, enabled)| async move {
+            if !enabled {
+                let (executions, errors) = version.counts();
+                let (circuit_state, consecutive_failures) = version.breaker.status(&self.breaker_config);
+                let health = PluginHealth {
+                    status: HealthStatus::Disabled,
+                    message: Some("Disabled in the plugin's config".to_string()),
+                    last_check: chrono::Utc::now(),
+                    execution_count: executions,
+                    error_count: errors,
+                    average_execution_time: Duration::from_secs(0),
+                    pool: Some(version.instances.utilization()),
+                    circuit_state,
+                    consecutive_failures,
+                };
+                return (id, health);
+            }
+            let mut health = self.check_plugin_health(&id, &version).await;
+            health.pool = Some(version.instances.utilization());
+            if let Some(message) = version.killed.recent(limits::KILLED_CALL_DEGRADED_FOR) {
+                if health.status == HealthStatus::Healthy {
+                    health.status = HealthStatus::Degraded;
+                }
+                health.message = Some(message);
+            }
+            (health.circuit_state, health.consecutive_failures) = version.breaker.status(&self.breaker_config);
+            if health.circuit_state != CircuitState::Closed {
+                health.status = HealthStatus::Unhealthy;
+                health.message = Some(format!("{} failed calls in a row; calls are refused", health.consecutive_failures));
+            }
+            (id, health)
+        });
+        futures::future::join_all(checks).await.into_iter().collect()
+    }
+    
+    /// The last successful health check of every plugin, however old,
+    /// without calling into any plugin
+    pub async fn cached_health(&self) -> HashMap<String, PluginHealth> {
+        self.health_cache
+            .read()
+            .await
+            .iter()
+            .map(|(id, cached)| (id.clone(), cached.health.clone()))
+            .collect()
+    }
+    
+    async fn check_plugin_health(&self, id: &str, version: &LoadedVersion) -> PluginHealth {
+        if let Some(cached) = self.health_cache.read().await.get(id) {
+            if cached.version == version.version && cached.checked_at.elapsed() < self.health_cache_ttl {
+                return cached.health.clone();
+            }
+        }
+        
+        // Checking out an instance is awaited within the timeout too: every
+        // instance may be stuck in a call
+        let check = async { version.instances.checkout().await?.health_check().await };
+        match tokio::time::timeout(self.health_check_timeout, check).await {
+            Ok(Ok(health)) => {
+                self.health_cache.write().await.insert(id.to_string(), CachedHealth {
+                    version: version.version.clone(),
+                    health: health.clone(),
+                    checked_at: Instant::now(),
+                });
+                health
+            }
+            Ok(Err(e)) => {
+                warn!("Health check failed for plugin {}: {}", id, e);
+                PluginHealth {
+                    status: HealthStatus::Unhealthy,
+                    message: Some(e.to_string()),
+                    last_check: chrono::Utc::now(),
+                    execution_count: 0,
+                    error_count: 1,
+                    average_execution_time: Duration::from_secs(0),
+                    pool: None,
+                    circuit_state: CircuitState::Closed,
+                    consecutive_failures: 0,
+                }
+            }
+            Err(_) => {
+                warn!("Health check for plugin {} did not finish within {:?}", id, self.health_check_timeout);
+                PluginHealth {
+                    status: HealthStatus::Unknown,
+                    message: Some(format!("Health check timed out after {:?}", self.health_check_timeout)),
+                    last_check: chrono::Utc::now(),
+                    execution_count: 0,
+                    error_count: 0,
+                    average_execution_time: Duration::from_secs(0),
+                    pool: None,
+                    circuit_state: CircuitState::Closed,
+                    consecutive_failures: 0,
+                }
+            }
+        }
+    }
+    
+    /// Run a sandbox check, against `policy` if given instead of the host
+    /// services' policy
+    fn sandboxed<T>(&self, policy: Option<SecurityPolicy>, check: impl FnOnce(&mut PluginSandbox) -> Result<T>) -> Result<T> {
+        let mut sandbox = self.sandbox.lock().unwrap();
+        match policy {
+            Some(policy) => sandbox.under(policy, check),
+            None => check(&mut *sandbox),
+        }
+    }
+    
+    /// Set resource limits for plugins loaded from now on
+    pub fn set_default_limits(&mut self, limits: ResourceLimits) {
+        self.epoch_ticker.set_interval(limits.epoch_interval);
+        self.default_limits = limits;
+    }
     
     /// Get current resource limits
     pub fn get_default_limits(&self) -> &ResourceLimits {
         &self.default_limits
     }
+    
+    /// Set the per-plugin health check timeout and how long a successful
+    /// result is cached
+    pub fn set_health_check_limits(&mut self, timeout: Duration, cache_ttl: Duration) {
+        self.health_check_timeout = timeout;
+        self.health_cache_ttl = cache_ttl;
+    }
+    
+    /// Set how many failed calls in a row make the manager refuse a
+    /// plugin's calls, and for how long before it probes the plugin again
+    pub fn set_circuit_breaker(&mut self, failure_threshold: u32, cooldown: Duration) {
+        self.breaker_config = BreakerConfig { failure_threshold: failure_threshold.max(1), cooldown };
+    }
+    
+    /// Set how long `unload_plugin` waits for running calls to finish
+    pub fn set_drain_timeout(&mut self, timeout: Duration) {
+        self.drain_timeout = timeout;
+    }
+    
+    /// Set how many instances of each plugin loaded from now on may run
+    /// calls at the same time; at least one
+    pub fn set_pool_size(&mut self, size: usize) {
+        self.pool_size = size.max(1);
+    }
 }
 
 /// Concrete WebAssembly plugin instance
 pub struct WasmPluginInstance {
-    store: Store<PluginWasiCtx>,
+    store: Mutex<Store<PluginWasiCtx>>,
     instance: Instance,
     metadata: WasmPluginMetadata,
+    limits: ResourceLimits,
     execution_stats: ExecutionStats,
+    /// Fuel used by the last `execute`, whether it succeeded or not
+    last_fuel: std::sync::Mutex<Option<u64>>,
+    /// Stdout and stderr of the last `execute`, whether it succeeded or not
+    last_output: std::sync::Mutex<(String, String)>,
+}
+
+/// What a single call into a plugin produced and cost
+#[derive(Debug, Clone)]
+pub struct ExecutionReport {
+    pub output: Vec<u8>,
+    pub fuel_consumed: u64,
+    pub duration: Duration,
+    /// Largest the plugin's linear memory has been, in bytes
+    pub peak_memory: u64,
+    /// What the plugin printed during the call, up to the context's
+    /// capacity and then a truncation marker
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
 }
 
 #[derive(Debug)]
@@ -342,66 +1670,242 @@ impl WasmPluginInstance {
         engine: &Engine,
         wasm_bytes: &[u8],
         limits: ResourceLimits,
+    ) -> Result<Self> {
+        let wasi_ctx = PluginWasiCtx::new(limits.clone())?;
+        Self::new_with_context(engine, wasm_bytes, limits, wasi_ctx).await
+    }
+    
+    /// Create an instance around a prepared WASI context, e.g. one that
+    /// captures the plugin's output
+    pub async fn new_with_context(
+        engine: &Engine,
+        wasm_bytes: &[u8],
+        limits: ResourceLimits,
+        wasi_ctx: PluginWasiCtx,
     ) -> Result<Self> {
         let module = Module::new(engine, wasm_bytes)
             .map_err(|e| AssistantError::Plugin(format!("Failed to compile module: {}", e)))?;
-        
-        let wasi_ctx = PluginWasiCtx::new(limits.clone())?;
+        Self::from_module(engine, &module, wasm_bytes, limits, wasi_ctx).await
+    }
+    
+    /// Create an instance of an already compiled module, e.g. one from the
+    /// [`cache::ModuleCache`]; `wasm_bytes` are the module's source, read for
+    /// its metadata section. Instantiating runs the module's start function,
+    /// so a module from outside is first passed through
+    /// [`PluginSandbox::validate_module`]
+    pub async fn from_module(
+        engine: &Engine,
+        module: &Module,
+        wasm_bytes: &[u8],
+        limits: ResourceLimits,
+        wasi_ctx: PluginWasiCtx,
+    ) -> Result<Self> {
         let mut store = Store::new(engine, wasi_ctx);
         
+        // Growing memory or a table past the context's limits traps
+        store.limiter(|ctx| &mut ctx.limiter);
+        
         // Set fuel limit for execution control
         store.set_fuel(limits.max_fuel)
             .map_err(|e| AssistantError::Plugin(format!("Failed to set fuel: {}", e)))?;
         
-        // Enable epoch interruption for timeout handling
-        store.set_epoch_deadline(1);
+        // Bounds initialization and metadata calls the same way as `call`
+        store.set_epoch_deadline(limits::epoch_deadline(&limits));
         
         let mut linker = Linker::new(engine);
         wasmtime_wasi::add_to_linker_async(&mut linker)
             .map_err(|e| AssistantError::Plugin(format!("Failed to add WASI to linker: {}", e)))?;
+        host::add_to_linker(&mut linker)?;
         
-        let instance = linker.instantiate_async(&mut store, &module).await
+        let instance = linker.instantiate_async(&mut store, module).await
             .map_err(|e| AssistantError::Plugin(format!("Failed to instantiate module: {}", e)))?;
         
         // Extract metadata from the plugin
-        let metadata = Self::extract_metadata(&mut store, &instance).await?;
+        let metadata = Self::extract_metadata(&mut store, &instance, wasm_bytes).await?;
+        
+        // Key-value data is scoped to the plugin id, and host capabilities
+        // are granted from what the plugin declares
+        store.data_mut().plugin_id = Some(metadata.id.clone());
+        store.data_mut().capabilities = metadata.capabilities.clone();
         
         Ok(Self {
-            store,
+            store: Mutex::new(store),
             instance,
             metadata,
+            limits,
             execution_stats: ExecutionStats {
                 execution_count: 0,
                 error_count: 0,
                 total_execution_time: Duration::from_secs(0),
             },
+            last_fuel: std::sync::Mutex::new(None),
+            last_output: std::sync::Mutex::new(Default::default()),
         })
     }
     
-    /// Extract metadata from the WebAssembly module
+    /// Call an exported plugin function.
+    ///
+    /// Plugins exchange JSON through linear memory: the host asks the
+    /// plugin's `alloc(len) -> ptr` export for space, copies the input in
+    /// and calls `function(ptr, len) -> i64`. The result packs the output's
+    /// pointer into the high 32 bits and its length into the low 32 bits.
+    /// A `dealloc(ptr, len)` export, if present, is given back both buffers.
+    /// Fuel and the epoch deadline are reset to the instance's limits, or
+    /// the function's override of them, before every call; running out of
+    /// either, or growing memory past `max_memory`, is reported as an
+    /// [`ExecutionLimit`] error
+    pub async fn call(&self, function: &str, input: &[u8]) -> Result<ExecutionReport> {
+        let limits = self.metadata.limits_for(function, &self.limits);
+        let fail = |what: String| AssistantError::Plugin(format!("{}: {}", function, what));
+        let trapped = |what: &str, e: anyhow::Error| {
+            // Names the size the plugin asked for, not just the limit
+            if let Some(denied) = e.downcast_ref::<limits::GrowthDenied>() {
+                return fail(denied.to_string());
+            }
+            match ExecutionLimit::from_trap(&e) {
+                Some(limit) => fail(limit.describe(&limits)),
+                None => fail(format!("{}: {}", what, e)),
+            }
+        };
+        
+        let mut store = self.store.lock().await;
+        store.set_fuel(limits.max_fuel)
+            .map_err(|e| fail(format!("failed to set fuel: {}", e)))?;
+        store.set_epoch_deadline(limits::epoch_deadline(&limits));
+        // Output from outside a call, e.g. `initialize`, is not the call's
+        store.data().take_output();
+        let start_time = Instant::now();
+        
+        let memory = self.instance.get_memory(&mut *store, "memory")
+            .ok_or_else(|| fail("plugin does not export its memory".to_string()))?;
+        let alloc = self.instance.get_typed_func::<i32, i32>(&mut *store, "alloc")
+            .map_err(|e| fail(format!("missing alloc export: {}", e)))?;
+        let func = self.instance.get_typed_func::<(i32, i32), i64>(&mut *store, function)
+            .map_err(|e| fail(format!("no such export: {}", e)))?;
+        
+        let input_len = i32::try_from(input.len())
+            .map_err(|_| fail(format!("input of {} bytes is too large", input.len())))?;
+        let input_ptr = alloc.call_async(&mut *store, input_len).await
+            .map_err(|e| trapped("alloc failed", e))?;
+        memory.write(&mut *store, input_ptr as u32 as usize, input)
+            .map_err(|e| fail(format!("input does not fit in plugin memory: {}", e)))?;
+        
+        let packed = func.call_async(&mut *store, (input_ptr, input_len)).await
+            .map_err(|e| trapped("execution failed", e))? as u64;
+        let (output_ptr, output_len) = ((packed >> 32) as usize, (packed & 0xffff_ffff) as usize);
+        
+        let mut output = vec![0u8; output_len];
+        memory.read(&*store, output_ptr, &mut output)
+            .map_err(|e| fail(format!("output at {}+{} is out of bounds: {}", output_ptr, output_len, e)))?;
+        
+        if let Ok(dealloc) = self.instance.get_typed_func::<(i32, i32), ()>(&mut *store, "dealloc") {
+            for (ptr, len) in [(input_ptr, input_len), (output_ptr as i32, output_len as i32)] {
+                if let Err(e) = dealloc.call_async(&mut *store, (ptr, len)).await {
+                    warn!("Plugin {} failed to free {} bytes: {}", function, len, e);
+                }
+            }
+        }
+        
+        let duration = start_time.elapsed();
+        let fuel_consumed = limits.max_fuel.saturating_sub(store.get_fuel().unwrap_or(0));
+        let peak_memory = store.data().limiter.peak_memory();
+        let (stdout, stderr) = store.data().take_output();
+        
+        debug!("Plugin function '{}' executed in {:?} using {} fuel", function, duration, fuel_consumed);
+        Ok(ExecutionReport { output, fuel_consumed, duration, peak_memory, stdout, stderr })
+    }
+    
+    /// Read the plugin's metadata from its custom section or, without one,
+    /// from its `get_metadata` export, and stamp it with the module checksum
     async fn extract_metadata(
         store: &mut Store<PluginWasiCtx>,
         instance: &Instance,
+        wasm_bytes: &[u8],
     ) -> Result<WasmPluginMetadata> {
-        // Try to call the metadata export function
-        if let Ok(metadata_func) = instance.get_typed_func::<(), i32>(store, "get_metadata") {
-            // This is a simplified approach - in production you'd have a more robust
-            // metadata extraction system using component model or memory exports
-            Ok(WasmPluginMetadata {
-                id: "unknown".to_string(),
-                name: "WebAssembly Plugin".to_string(),
-                version: "0.1.0".to_string(),
-                description: "A WebAssembly plugin".to_string(),
-                author: "Unknown".to_string(),
-                license: "Unknown".to_string(),
-                capabilities: vec![],
-                dependencies: vec![],
-                api_version: "1.0".to_string(),
-                checksum: "".to_string(),
-            })
-        } else {
-            Err(AssistantError::Plugin("Plugin missing metadata export".to_string()))
+        let mut found = match metadata::from_custom_section(wasm_bytes)? {
+            Some(found) => found,
+            None => Self::exported_metadata(store, instance).await?,
+        };
+        found.checksum = artifact_sha256(wasm_bytes);
+        Ok(found)
+    }
+    
+    async fn exported_metadata(
+        store: &mut Store<PluginWasiCtx>,
+        instance: &Instance,
+    ) -> Result<WasmPluginMetadata> {
+        let fail = |what: String| AssistantError::Plugin(format!("Plugin metadata unavailable: {}", what));
+        
+        let get_metadata = instance.get_typed_func::<(), i64>(&mut *store, "get_metadata")
+            .map_err(|e| fail(format!(
+                "no `{}` section and no `get_metadata() -> i64` export ({})",
+                metadata::METADATA_SECTION, e
+            )))?;
+        let memory = instance.get_memory(&mut *store, "memory")
+            .ok_or_else(|| fail("plugin does not export its memory".to_string()))?;
+        
+        let packed = get_metadata.call_async(&mut *store, ()).await
+            .map_err(|e| fail(format!("get_metadata failed: {}", e)))? as u64;
+        let (ptr, len) = ((packed >> 32) as usize, (packed & 0xffff_ffff) as usize);
+        let mut json = vec![0u8; len];
+        memory.read(&*store, ptr, &mut json)
+            .map_err(|e| fail(format!("get_metadata output at {}+{} is out of bounds: {}", ptr, len, e)))?;
+        
+        metadata::parse(&json, "`get_metadata` export")
+    }
+    
+    // `execute` on this instance. A call whose future was dropped never
+    // clears the store, so every call sets all of what it serves
+    async fn run(
+        &self,
+        function: &str,
+        input: &[u8],
+        context: &PluginContext,
+        tape: Option<host::SharedHostTape>,
+    ) -> Result<Vec<u8>> {
+        // Host imports act on the scratchpad of the session being served and
+        // search for the user being served
+        {
+            let mut store = self.store.lock().await;
+            let data = store.data_mut();
+            data.session_id = uuid::Uuid::parse_str(&context.session_id).ok();
+            data.user_id = Some(context.user_id.clone());
+            data.tape = tape;
         }
+        let (result, mut output) = match self.call(function, input).await {
+            Ok(report) => (Ok(report.output), (report.stdout, report.stderr)),
+            Err(e) => (Err(e), (Vec::new(), Vec::new())),
+        };
+        {
+            let mut store = self.store.lock().await;
+            // The fuel `call` set is left as the call ended, trapped or not
+            let remaining = store.get_fuel().unwrap_or(0);
+            let max_fuel = self.metadata.limits_for(function, &self.limits).max_fuel;
+            *self.last_fuel.lock().unwrap() = Some(max_fuel.saturating_sub(remaining));
+            // A failed call leaves its output in the context
+            if result.is_err() {
+                output = store.data().take_output();
+            }
+            let data = store.data_mut();
+            data.session_id = None;
+            data.user_id = None;
+            data.tape = None;
+        }
+        
+        let stdout = String::from_utf8_lossy(&output.0).into_owned();
+        let stderr = String::from_utf8_lossy(&output.1).into_owned();
+        for (stream, text) in [("stdout", &stdout), ("stderr", &stderr)] {
+            if !text.is_empty() {
+                debug!(
+                    plugin_id = %self.metadata.id,
+                    request_id = %context.request_id,
+                    "Plugin {} wrote to {} during {}: {}",
+                    self.metadata.id, stream, function, text.trim_end()
+                );
+            }
+        }
+        *self.last_output.lock().unwrap() = (stdout, stderr);
+        result
     }
 }
 
@@ -413,27 +1917,27 @@ impl WasmPlugin for WasmPluginInstance {
     
     async fn initialize(&mut self, _config: serde_json::Value) -> Result<()> {
         // Call plugin initialization function if available
-        if let Ok(init_func) = self.instance.get_typed_func::<(), ()>(&mut self.store, "initialize") {
-            init_func.call_async(&mut self.store, ()).await
+        let store = self.store.get_mut();
+        if let Ok(init_func) = self.instance.get_typed_func::<(), ()>(&mut *store, "initialize") {
+            init_func.call_async(&mut *store, ()).await
                 .map_err(|e| AssistantError::Plugin(format!("Plugin initialization failed: {}", e)))?;
         }
         Ok(())
     }
     
-    async fn execute(&self, function: &str, input: &[u8], _context: &PluginContext) -> Result<Vec<u8>> {
-        // This is a simplified implementation
-        // In production, you'd implement proper function calling with input/output handling
-        
-        let start_time = Instant::now();
-        
-        // For now, return empty success response
-        let execution_time = start_time.elapsed();
-        
-        debug!("Plugin function '{}' executed in {:?}", function, execution_time);
-        
-        Ok(vec![])
+    async fn execute(&self, function: &str, input: &[u8], context: &PluginContext) -> Result<Vec<u8>> {
+        self.run(function, input, context, None).await
     }
     
+    async fn execute_with_tape(
+        &self,
+        function: &str,
+        input: &[u8],
+        context: &PluginContext,
+        tape: host::SharedHostTape,
+    ) -> Result<Vec<u8>> {
+        self.run(function, input, context, Some(tape)).await
+    }
     fn can_handle(&self, capability: &str) -> bool {
         self.metadata.capabilities.contains(&capability.to_string())
     }
@@ -450,6 +1954,9 @@ impl WasmPlugin for WasmPluginInstance {
             } else {
                 Duration::from_secs(0)
             },
+            pool: None,
+            circuit_state: CircuitState::Closed,
+            consecutive_failures: 0,
         })
     }
     
@@ -457,6 +1964,14 @@ impl WasmPlugin for WasmPluginInstance {
         // Cleanup resources
         Ok(())
     }
+    
+    fn last_fuel_consumed(&self) -> Option<u64> {
+        *self.last_fuel.lock().unwrap()
+    }
+    
+    fn last_output(&self) -> (String, String) {
+        self.last_output.lock().unwrap().clone()
+    }
 }
 
 #[cfg(test)]
@@ -477,4 +1992,1367 @@ mod tests {
         assert_eq!(limits.max_memory, 64 * 1024 * 1024);
         assert_eq!(limits.max_execution_time, Duration::from_secs(30));
     }
-}
\ No newline at end of file
+
+    struct ChannelAuditSink(tokio::sync::mpsc::UnboundedSender<PluginAuditRecord>);
+
+    #[async_trait]
+    impl PluginAuditSink for ChannelAuditSink {
+        async fn record(&self, record: PluginAuditRecord) -> Result<()> {
+            self.0.send(record).map_err(|e| AssistantError::Internal(e.to_string()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_every_call_is_recorded_in_the_audit_sink() {
+        let temp_dir = tempdir().unwrap();
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let manager = WasmPluginManager::new(temp_dir.path()).unwrap().with_audit_sink(Arc::new(ChannelAuditSink(tx)));
+        manager.load_plugin("echo", ECHO_FIXTURE.as_bytes()).await.unwrap();
+        manager.set_permission_policy(PermissionPolicy::new("plugins:run"));
+
+        let allowed = context(&["plugins:run"], CallOrigin::Api);
+        manager.execute_plugin("echo", "echo", b"hello", allowed.clone()).await.unwrap();
+        manager.execute_plugin("echo", "missing", b"{}", allowed).await.unwrap_err();
+        manager.execute_plugin("echo", "echo", b"hello", context(&[], CallOrigin::Api)).await.unwrap_err();
+
+        let echoed = rx.recv().await.unwrap();
+        assert_eq!((echoed.plugin_id.as_str(), echoed.function.as_str()), ("echo", "echo"));
+        assert_eq!((echoed.user_id.as_str(), echoed.request_id.as_str()), ("user-1", "request-1"));
+        assert_eq!((echoed.input_size, echoed.output_size), (5, 5));
+        assert!(echoed.success && echoed.error.is_none());
+        assert!(echoed.fuel_consumed.unwrap() > 0);
+
+        let missing = rx.recv().await.unwrap();
+        assert!(!missing.success);
+        assert!(missing.error.unwrap().contains("missing: no such export"));
+
+        // Denied before it ran, so it used no fuel
+        let denied = rx.recv().await.unwrap();
+        assert!(denied.error.unwrap().contains("Permission denied"));
+        assert_eq!(denied.fuel_consumed, None);
+    }
+
+    #[tokio::test]
+    async fn test_calls_are_counted_in_the_metrics_registry() {
+        let temp_dir = tempdir().unwrap();
+        let mut manager = WasmPluginManager::new(temp_dir.path()).unwrap();
+        manager.set_default_limits(ResourceLimits { max_fuel: 100_000, ..ResourceLimits::default() });
+        // The registry is shared by every test, so this one has a plugin name of its own
+        manager.load_plugin("metered", ECHO_FIXTURE.as_bytes()).await.unwrap();
+        let allowed = context(&["plugins:execute"], CallOrigin::Api);
+        manager.execute_plugin("metered", "echo", b"hello", allowed.clone()).await.unwrap();
+        manager.execute_plugin("metered", "echo", b"again", allowed.clone()).await.unwrap();
+        manager.execute_plugin("metered", "spin", b"", allowed).await.unwrap_err();
+
+        let metrics = metrics::global();
+        let echo = ["metered", "echo"];
+        assert_eq!(metrics.executions.with_label_values(&echo).get(), 2);
+        assert_eq!(metrics.errors.with_label_values(&echo).get(), 0);
+        assert_eq!(metrics.input_bytes.with_label_values(&echo).get_sample_sum(), 10.0);
+        assert_eq!(metrics.output_bytes.with_label_values(&echo).get_sample_sum(), 10.0);
+        let spin = ["metered", "spin"];
+        assert_eq!(metrics.errors.with_label_values(&spin).get(), 1);
+        assert_eq!(metrics.fuel_exhausted.with_label_values(&spin).get(), 1);
+        assert_eq!(metrics.timeouts.with_label_values(&spin).get(), 0);
+
+        let text = metrics::gather();
+        assert!(text.contains(r#"rusty_ai_plugin_executions_total{function="echo",plugin_id="metered"} 2"#), "{}", text);
+        assert!(!text.contains("user_id"));
+    }
+
+    #[tokio::test]
+    async fn test_calls_over_the_hourly_quota_are_rate_limited() {
+        let temp_dir = tempdir().unwrap();
+        let manager = WasmPluginManager::new(temp_dir.path()).unwrap();
+        manager.load_plugin("echo", ECHO_FIXTURE.as_bytes()).await.unwrap();
+        let quota = PluginQuota { max_calls_per_hour: Some(100), max_concurrent: Some(1) };
+        manager.set_plugin_config("echo", PluginConfig { enabled: true, priority: 0, settings: HashMap::new(), quota }).await;
+
+        let caller = context(&["plugins:execute"], CallOrigin::Api);
+        for _ in 0..100 {
+            manager.execute_plugin("echo", "echo", b"hi", caller.clone()).await.unwrap();
+        }
+        match manager.execute_plugin("echo", "echo", b"hi", caller.clone()).await {
+            Err(AssistantError::RateLimited { retry_after_secs, .. }) => assert!(retry_after_secs > 0 && retry_after_secs <= 3600),
+            other => panic!("expected the 101st call to be rate limited, got {:?}", other),
+        }
+
+        // Another user's quota is untouched
+        let other = PluginContext { user_id: "user-2".to_string(), ..caller };
+        manager.execute_plugin("echo", "echo", b"hi", other).await.unwrap();
+
+        // A quota of zero is refused rather than kept
+        let zero = PluginQuota { max_calls_per_hour: Some(0), max_concurrent: None };
+        let config = PluginConfig { enabled: true, priority: 0, settings: HashMap::new(), quota: zero };
+        assert!(matches!(manager.configure_plugin("echo", config).await, Err(AssistantError::Configuration(_))));
+        assert_eq!(manager.plugin_config("echo").await.quota, quota);
+    }
+
+    async fn manager_with_example_plugin() -> (tempfile::TempDir, WasmPluginManager) {
+        let temp_dir = tempdir().unwrap();
+        let manager = WasmPluginManager::new(temp_dir.path()).unwrap();
+        
+        let mut plugin = example_plugin::ExampleWasmPlugin::new();
+        plugin.initialize(serde_json::json!({})).await.unwrap();
+        manager.register_plugin("example", Box::new(plugin)).await.unwrap();
+        
+        (temp_dir, manager)
+    }
+    
+    fn context(permissions: &[&str], origin: CallOrigin) -> PluginContext {
+        PluginContext {
+            user_id: "user-1".to_string(),
+            session_id: "session-1".to_string(),
+            request_id: "request-1".to_string(),
+            metadata: HashMap::new(),
+            started_at: Instant::now(),
+            permissions: permissions.iter().map(|p| p.to_string()).collect(),
+            origin,
+        }
+    }
+    
+    #[tokio::test]
+    async fn test_register_plugin_discovers_function_schemas() {
+        let (_dir, manager) = manager_with_example_plugin().await;
+        
+        let schemas = manager.get_function_schemas("example").await;
+        let reset = schemas.iter().find(|s| s.name == "reset_stats").unwrap();
+        assert_eq!(reset.required_permission.as_deref(), Some(ADMIN_PERMISSION));
+        assert!(!reset.public);
+    }
+    
+    #[tokio::test]
+    async fn test_execute_plugin_enforces_declared_permission() {
+        let (_dir, manager) = manager_with_example_plugin().await;
+        let input = br#"{"text": "hello world"}"#;
+        
+        let allowed = manager
+            .execute_plugin("example", "analyze_text", input, context(&["text:analyze"], CallOrigin::Api))
+            .await;
+        assert!(allowed.is_ok());
+        
+        let denied = manager
+            .execute_plugin("example", "reset_stats", b"{}", context(&["text:analyze"], CallOrigin::Api))
+            .await;
+        assert!(matches!(denied, Err(AssistantError::Security(_))));
+        
+        let decisions = manager.recent_permission_decisions(10).await;
+        assert_eq!(decisions.len(), 2);
+        assert!(decisions[0].allowed);
+        assert!(!decisions[1].allowed);
+        assert_eq!(decisions[1].required_permission, ADMIN_PERMISSION);
+    }
+    
+    #[tokio::test]
+    async fn test_execute_plugin_applies_baseline_permission() {
+        let (_dir, manager) = manager_with_example_plugin().await;
+        manager.set_permission_policy(PermissionPolicy::new("plugins:run"));
+        
+        // hello declares no permission of its own, so the baseline applies
+        let denied = manager
+            .execute_plugin("example", "hello", b"{}", context(&["plugins:execute"], CallOrigin::Api))
+            .await;
+        assert!(denied.is_err());
+        
+        let allowed = manager
+            .execute_plugin("example", "hello", b"{}", context(&["plugins:run"], CallOrigin::Api))
+            .await;
+        assert!(allowed.is_ok());
+    }
+    
+    #[tokio::test]
+    async fn test_conversation_dispatch_only_reaches_public_functions() {
+        let (_dir, manager) = manager_with_example_plugin().await;
+        
+        let public = manager
+            .execute_plugin("example", "hello", b"{}", context(&["plugins:execute"], CallOrigin::Conversation))
+            .await;
+        assert!(public.is_ok());
+        
+        let private = manager
+            .execute_plugin("example", "reset_stats", b"{}", context(&[ADMIN_PERMISSION], CallOrigin::Conversation))
+            .await;
+        assert!(private.is_err());
+    }
+    
+    const ECHO_FIXTURE: &str = include_str!("../fixtures/echo.wat");
+    
+    // The echo fixture with its `version` function reporting `build`, or
+    // failing every call when `build` is None
+    fn echo_build(build: Option<u32>) -> String {
+        match build {
+            Some(build) => ECHO_FIXTURE.replace(r#"{\"version\":1}"#, &format!(r#"{{\"version\":{}}}"#, build)),
+            None => ECHO_FIXTURE.replace("i32.const 512\n    i32.const 13", "i32.const 70000\n    i32.const 13"),
+        }
+    }
+    
+    async fn manager_with_builds(builds: &[(&str, Option<u32>)]) -> (tempfile::TempDir, WasmPluginManager) {
+        let temp_dir = tempdir().unwrap();
+        let manager = WasmPluginManager::new(temp_dir.path()).unwrap();
+        for (plugin_ref, build) in builds {
+            manager.load_plugin(plugin_ref, echo_build(*build).as_bytes()).await.unwrap();
+        }
+        (temp_dir, manager)
+    }
+    
+    async fn served_build(manager: &WasmPluginManager, plugin_id: &str) -> Result<u64> {
+        let output = manager
+            .execute_plugin(plugin_id, "version", b"", context(&["plugins:execute"], CallOrigin::Api))
+            .await?;
+        Ok(serde_json::from_slice::<serde_json::Value>(&output).unwrap()["version"].as_u64().unwrap())
+    }
+    
+    #[tokio::test]
+    async fn test_execute_round_trips_bytes_through_guest_memory() {
+        let (_dir, manager) = manager_with_builds(&[("echo", Some(1)), ("broken", None)]).await;
+        let call = |plugin_id: &'static str, function: &'static str, input: Vec<u8>| {
+            let manager = &manager;
+            async move {
+                manager
+                    .execute_plugin(plugin_id, function, &input, context(&["plugins:execute"], CallOrigin::Api))
+                    .await
+            }
+        };
+        
+        // Arbitrary bytes, not only JSON, and successive calls get fresh buffers
+        let input: Vec<u8> = (0..5000u32).map(|i| (i % 251) as u8).collect();
+        assert_eq!(call("echo", "echo", input.clone()).await.unwrap(), input);
+        assert_eq!(call("echo", "echo", b"\xff\x00second".to_vec()).await.unwrap(), b"\xff\x00second");
+        assert!(call("echo", "echo", Vec::new()).await.unwrap().is_empty());
+        
+        match call("echo", "missing", b"{}".to_vec()).await {
+            Err(AssistantError::Plugin(message)) => assert!(message.starts_with("missing: no such export"), "{}", message),
+            other => panic!("expected a plugin error, got {:?}", other),
+        }
+        // The broken build returns an output pointer past its one page of memory
+        match call("broken", "version", Vec::new()).await {
+            Err(AssistantError::Plugin(message)) => assert!(message.starts_with("version: output at 70000+13"), "{}", message),
+            other => panic!("expected a plugin error, got {:?}", other),
+        }
+    }
+        
+    #[tokio::test]
+    async fn test_runaway_calls_are_killed_by_cpu_limits() {
+        let spin = |limits: ResourceLimits| async move {
+            let temp_dir = tempdir().unwrap();
+            let mut manager = WasmPluginManager::new(temp_dir.path()).unwrap();
+            manager.set_default_limits(limits);
+            manager.load_plugin("echo", ECHO_FIXTURE.as_bytes()).await.unwrap();
+            
+            let started = Instant::now();
+            let result = manager
+                .execute_plugin("echo", "spin", b"", context(&["plugins:execute"], CallOrigin::Api))
+                .await;
+            (manager, started.elapsed(), result.unwrap_err())
+        };
+        
+        // Plenty of fuel: the epoch deadline stops the loop, long before the
+        // wall-clock timeout
+        let (manager, elapsed, error) = spin(ResourceLimits {
+            cpu_time_limit: Duration::from_millis(100),
+            max_fuel: 1_000_000_000_000,
+            ..ResourceLimits::default()
+        })
+        .await;
+        assert!(elapsed < Duration::from_secs(2), "took {:?}", elapsed);
+        assert_eq!(ExecutionLimit::of(&error), Some(ExecutionLimit::CpuTime), "{}", error);
+        assert!(error.to_string().contains("spin: killed: exceeded its CPU time limit of 100ms"), "{}", error);
+        
+        // The instance is still usable and the kill shows in its health
+        assert_eq!(
+            manager.execute_plugin("echo", "echo", b"ok", context(&["plugins:execute"], CallOrigin::Api)).await.unwrap(),
+            b"ok"
+        );
+        let health = &manager.health_check_all().await["echo"];
+        assert_eq!(health.status, HealthStatus::Degraded);
+        assert!(health.message.as_deref().unwrap().starts_with("1 calls killed (0 out of fuel, 1 over CPU time"));
+        assert_eq!(manager.plugin_versions("echo").await.unwrap().versions[0].killed, 1);
+        
+        let (_manager, elapsed, error) = spin(ResourceLimits {
+            max_fuel: 100_000,
+            ..ResourceLimits::default()
+        })
+        .await;
+        assert!(elapsed < Duration::from_secs(2), "took {:?}", elapsed);
+        assert_eq!(ExecutionLimit::of(&error), Some(ExecutionLimit::Fuel), "{}", error);
+    }
+    
+    #[tokio::test]
+    async fn test_function_limits_override_the_plugin_limits_downward_only() {
+        let metadata: WasmPluginMetadata = serde_json::from_value(serde_json::json!({
+            "id": "echo",
+            "name": "Echo",
+            "version": "0.1.0",
+            "function_limits": {
+                "spin": {"timeout_ms": 100},
+                "version": {"max_fuel": 5_000},
+                // Higher than the plugin's 30s, so the plugin's ceiling holds
+                "echo": {"timeout_ms": 60_000},
+            },
+        }))
+        .unwrap();
+        let wasm = metadata::embed(ECHO_FIXTURE.as_bytes(), &metadata).unwrap();
+
+        let temp_dir = tempdir().unwrap();
+        let mut manager = WasmPluginManager::new(temp_dir.path()).unwrap();
+        // Plenty of fuel, so only the override can stop the loop early
+        manager.set_default_limits(ResourceLimits { max_fuel: 1_000_000_000_000, ..ResourceLimits::default() });
+        manager.load_plugin("echo", &wasm).await.unwrap();
+
+        let started = Instant::now();
+        let error = manager
+            .execute_plugin("echo", "spin", b"", context(&["plugins:execute"], CallOrigin::Api))
+            .await
+            .unwrap_err();
+        assert!(started.elapsed() < Duration::from_secs(2), "took {:?}", started.elapsed());
+        assert_eq!(ExecutionLimit::of(&error), Some(ExecutionLimit::CpuTime), "{}", error);
+        assert!(error.to_string().contains("exceeded its CPU time limit of 100ms"), "{}", error);
+
+        let schemas: HashMap<String, FunctionSchema> = manager
+            .get_function_schemas("echo")
+            .await
+            .into_iter()
+            .map(|schema| (schema.name.clone(), schema))
+            .collect();
+        assert_eq!(
+            schemas["echo"].limits,
+            Some(FunctionLimits { timeout_ms: Some(30_000), max_fuel: Some(1_000_000_000_000) })
+        );
+        assert_eq!(schemas["version"].limits, Some(FunctionLimits { timeout_ms: Some(30_000), max_fuel: Some(5_000) }));
+        assert_eq!(
+            manager.execute_plugin("echo", "echo", b"ok", context(&["plugins:execute"], CallOrigin::Api)).await.unwrap(),
+            b"ok"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_memory_growth_past_the_limit_is_killed() {
+        let engine = create_plugin_engine().unwrap();
+        let limits = ResourceLimits { max_memory: 1024 * 1024, ..ResourceLimits::default() };
+        let plugin = WasmPluginInstance::new(&engine, ECHO_FIXTURE.as_bytes(), limits.clone()).await.unwrap();
+        assert_eq!(plugin.call("echo", b"{}").await.unwrap().peak_memory, 64 * 1024);
+        
+        let error = plugin.call("grow", b"").await.unwrap_err();
+        assert_eq!(ExecutionLimit::of(&error), Some(ExecutionLimit::Memory), "{}", error);
+        assert!(
+            error.to_string().contains(
+                "grow: killed: exceeded its memory limit: tried to grow memory to 134283264 bytes, the limit is 1048576 bytes"
+            ),
+            "{}",
+            error
+        );
+        // The denied growth never happened
+        assert_eq!(plugin.call("echo", b"ok").await.unwrap().peak_memory, 64 * 1024);
+        
+        // A module whose initial memory is already over the limit does not load
+        let oversized = ECHO_FIXTURE.replace("(memory (export \"memory\") 1)", "(memory (export \"memory\") 32)");
+        assert!(WasmPluginInstance::new(&engine, oversized.as_bytes(), limits.clone()).await.is_err());
+        
+        // Through the manager the kill is counted like the CPU limits
+        let temp_dir = tempdir().unwrap();
+        let mut manager = WasmPluginManager::new(temp_dir.path()).unwrap();
+        manager.set_default_limits(limits);
+        manager.load_plugin("echo", ECHO_FIXTURE.as_bytes()).await.unwrap();
+        let error = manager
+            .execute_plugin("echo", "grow", b"", context(&["plugins:execute"], CallOrigin::Api))
+            .await
+            .unwrap_err();
+        assert_eq!(ExecutionLimit::of(&error), Some(ExecutionLimit::Memory), "{}", error);
+        let health = &manager.health_check_all().await["echo"];
+        assert_eq!(health.status, HealthStatus::Degraded);
+        assert!(health.message.as_deref().unwrap().contains("1 over memory"));
+    }
+    
+    #[tokio::test]
+    async fn test_load_plugin_rejects_prohibited_wasi_imports() {
+        let temp_dir = tempdir().unwrap();
+        let manager = WasmPluginManager::new(temp_dir.path()).unwrap();
+        
+        // The default policy grants neither sockets nor the filesystem
+        let networked = ECHO_FIXTURE.replacen(
+            "(module\n",
+            "(module\n  (import \"wasi_snapshot_preview1\" \"sock_accept\" (func (param i32 i32 i32) (result i32)))\n",
+            1,
+        );
+        match manager.load_plugin("echo", networked.as_bytes()).await {
+            Err(AssistantError::Security(message)) => assert!(message.contains("sock_accept"), "{}", message),
+            other => panic!("expected a security error, got {:?}", other),
+        }
+        assert!(manager.list_plugins().await.is_empty());
+        assert_eq!(manager.security_stats().security_violations, 1);
+        
+        manager.load_plugin("echo", ECHO_FIXTURE.as_bytes()).await.unwrap();
+        assert_eq!(manager.list_plugins().await, vec!["echo".to_string()]);
+        assert_eq!(manager.security_stats().security_violations, 1);
+    }
+    
+    #[tokio::test]
+    async fn test_rejected_module_never_runs_its_start_function() {
+        let temp_dir = tempdir().unwrap();
+        let manager = WasmPluginManager::new(temp_dir.path()).unwrap();
+        
+        // The start function traps, so running it fails the load visibly
+        let trapping_start = |module: &str| {
+            let end = module.rfind(')').unwrap();
+            format!("{}\n  (func $boot unreachable)\n  (start $boot))\n", &module[..end])
+        };
+        match manager.load_plugin("echo", trapping_start(ECHO_FIXTURE).as_bytes()).await {
+            Err(AssistantError::Plugin(message)) => assert!(message.contains("Failed to instantiate"), "{}", message),
+            other => panic!("expected the start function to trap, got {:?}", other),
+        }
+        
+        let networked = ECHO_FIXTURE.replacen(
+            "(module\n",
+            "(module\n  (import \"wasi_snapshot_preview1\" \"sock_accept\" (func (param i32 i32 i32) (result i32)))\n",
+            1,
+        );
+        match manager.load_plugin("echo", trapping_start(&networked).as_bytes()).await {
+            Err(AssistantError::Security(message)) => assert!(message.contains("sock_accept"), "{}", message),
+            other => panic!("expected a security error before instantiation, got {:?}", other),
+        }
+        assert!(manager.list_plugins().await.is_empty());
+        assert_eq!(manager.security_stats().security_violations, 1);
+    }
+    
+    #[tokio::test]
+    async fn test_trust_level_decides_whether_a_networked_plugin_loads() {
+        let temp_dir = tempdir().unwrap();
+        let store = Arc::new(trust::TrustStore::in_plugin_directory(temp_dir.path()).unwrap());
+        store.set_level("echo", trust::TrustLevel::Trusted).await.unwrap();
+        let manager = WasmPluginManager::new(temp_dir.path()).unwrap().with_trust_store(store);
+        let networked = ECHO_FIXTURE.replacen(
+            "(module\n",
+            "(module\n  (import \"wasi_snapshot_preview1\" \"sock_accept\" (func (param i32 i32 i32) (result i32)))\n",
+            1,
+        );
+        
+        manager.load_plugin("echo", networked.as_bytes()).await.unwrap();
+        let output = manager.execute_plugin("echo", "echo", b"hi", context(&["plugins:execute"], CallOrigin::Api)).await;
+        assert_eq!(output.unwrap(), b"hi");
+        
+        // Semi-trusted plugins get no sockets, so it is reloaded, rejected and unloaded
+        match manager.set_trust_level("echo", trust::TrustLevel::SemiTrusted).await {
+            Err(AssistantError::Security(message)) => assert!(message.contains("Network"), "{}", message),
+            other => panic!("expected a security error, got {:?}", other),
+        }
+        assert!(manager.list_plugins().await.is_empty());
+        let reopened = trust::TrustStore::in_plugin_directory(temp_dir.path()).unwrap();
+        assert_eq!(reopened.level("echo"), trust::TrustLevel::SemiTrusted);
+        
+        // Plugins the store does not name are untrusted, which takes a signature
+        match manager.load_plugin("stranger", networked.as_bytes()).await {
+            Err(AssistantError::Security(message)) => assert!(message.contains("not signed"), "{}", message),
+            other => panic!("expected a security error, got {:?}", other),
+        }
+        assert_eq!(manager.security_stats().security_violations, 2);
+    }
+    
+    #[tokio::test]
+    async fn test_signed_untrusted_plugin_loads_with_capped_limits() {
+        let temp_dir = tempdir().unwrap();
+        let rng = ring::rand::SystemRandom::new();
+        let pkcs8 = ring::signature::Ed25519KeyPair::generate_pkcs8(&rng).unwrap();
+        let key = TrustedKey { author: "RUSTY-AI".to_string(), public_key: signing_public_key(pkcs8.as_ref()).unwrap() };
+        let policy = SecurityPolicy {
+            trusted_authors: ["RUSTY-AI".to_string()].into_iter().collect(),
+            trusted_keys: [("release".to_string(), key)].into_iter().collect(),
+            ..SecurityPolicy::default()
+        };
+        let store = Arc::new(trust::TrustStore::in_plugin_directory(temp_dir.path()).unwrap());
+        let manager = WasmPluginManager::new(temp_dir.path())
+            .unwrap()
+            .with_host_services(host::HostServices { policy, ..Default::default() })
+            .with_trust_store(store);
+        
+        // Laid out as the marketplace keeps an install, the signature next to the module
+        let path = temp_dir.path().join("echo.wat");
+        std::fs::write(&path, ECHO_FIXTURE).unwrap();
+        match manager.load_plugin_from_file("echo", &path).await {
+            Err(AssistantError::Security(message)) => assert!(message.contains("not signed"), "{}", message),
+            other => panic!("expected the unsigned plugin to be rejected, got {:?}", other),
+        }
+        sign_plugin(ECHO_FIXTURE.as_bytes(), "release", pkcs8.as_ref()).unwrap().write_for(&path).await.unwrap();
+        manager.load_plugin_from_file("echo", &path).await.unwrap();
+        let output = manager.execute_plugin("echo", "echo", b"hi", context(&["plugins:execute"], CallOrigin::Api)).await;
+        assert_eq!(output.unwrap(), b"hi");
+        
+        let echo_limits = |schemas: Vec<FunctionSchema>| schemas.into_iter().find(|s| s.name == "echo").unwrap().limits;
+        let untrusted = trust::TrustLevel::Untrusted.limits(&ResourceLimits::default());
+        assert!(untrusted.max_execution_time < ResourceLimits::default().max_execution_time);
+        assert_eq!(echo_limits(manager.get_function_schemas("echo").await), Some(FunctionLimits::effective(&untrusted)));
+        
+        // Reloaded at the new level with the signature it was loaded with
+        manager.set_trust_level("echo", trust::TrustLevel::Trusted).await.unwrap();
+        let trusted = FunctionLimits::effective(&ResourceLimits::default());
+        assert_eq!(echo_limits(manager.get_function_schemas("echo").await), Some(trusted));
+    }
+    
+    const CHATTY_FIXTURE: &str = include_str!("../fixtures/chatty.wat");
+    
+    #[tokio::test]
+    async fn test_plugin_output_is_captured_per_call() {
+        let temp_dir = tempdir().unwrap();
+        let manager = WasmPluginManager::new(temp_dir.path()).unwrap();
+        // Writing to stdout needs no filesystem access
+        manager.load_plugin("chatty", CHATTY_FIXTURE.as_bytes()).await.unwrap();
+        
+        let say = |input: &'static [u8]| {
+            manager.execute_plugin_detailed("chatty", "say", input, context(&["plugins:execute"], CallOrigin::Api))
+        };
+        let result = say(b"hello").await.unwrap();
+        assert_eq!(result.output, b"hello");
+        assert_eq!((result.stdout.as_str(), result.stderr.as_str()), ("hello", "done\n"));
+        assert!(result.duration > Duration::ZERO);
+        
+        // Each call only sees what it printed itself
+        let result = say(b"again").await.unwrap();
+        assert_eq!(result.stdout, "again");
+        
+        // So does a call that traps after printing
+        let engine = create_plugin_engine().unwrap();
+        let plugin = WasmPluginInstance::new(&engine, CHATTY_FIXTURE.as_bytes(), ResourceLimits::default()).await.unwrap();
+        let context = context(&["plugins:execute"], CallOrigin::Api);
+        assert!(plugin.execute("fail", b"last words", &context).await.is_err());
+        assert_eq!(plugin.last_output(), ("last words".to_string(), String::new()));
+    }
+    
+    #[tokio::test]
+    async fn test_plugin_output_past_the_cap_is_truncated() {
+        let temp_dir = tempdir().unwrap();
+        let mut manager = WasmPluginManager::new(temp_dir.path()).unwrap();
+        manager.set_default_limits(ResourceLimits { max_output_bytes: 16, ..ResourceLimits::default() });
+        manager.load_plugin("chatty", CHATTY_FIXTURE.as_bytes()).await.unwrap();
+        
+        let long = "x".repeat(100);
+        let result = manager
+            .execute_plugin_detailed("chatty", "say", long.as_bytes(), context(&["plugins:execute"], CallOrigin::Api))
+            .await
+            .unwrap();
+        // Only the printed copy is cut, not the output
+        assert_eq!(result.output, long.as_bytes());
+        assert_eq!(result.stdout, format!("{}{}", "x".repeat(16), output::truncation_marker(84)));
+        assert_eq!(result.stderr, "done\n");
+        
+        // The cap applies per call
+        let result = manager
+            .execute_plugin_detailed("chatty", "say", b"short", context(&["plugins:execute"], CallOrigin::Api))
+            .await
+            .unwrap();
+        assert_eq!(result.stdout, "short");
+    }
+    
+    const NOTES_FIXTURE: &str = include_str!("../fixtures/notes.wat");
+    
+    /// Plugin data by (plugin id, key), standing in for the core storage
+    #[derive(Default)]
+    struct MemoryKv(std::sync::Mutex<HashMap<(String, String), Vec<u8>>>);
+    
+    #[async_trait]
+    impl host::PluginKvStore for MemoryKv {
+        async fn get(&self, plugin_id: &str, key: &str) -> Result<Option<Vec<u8>>> {
+            Ok(self.0.lock().unwrap().get(&(plugin_id.to_string(), key.to_string())).cloned())
+        }
+        
+        async fn set(&self, plugin_id: &str, key: &str, value: Option<&[u8]>) -> Result<()> {
+            let mut values = self.0.lock().unwrap();
+            let entry = (plugin_id.to_string(), key.to_string());
+            match value {
+                Some(value) => values.insert(entry, value.to_vec()),
+                None => values.remove(&entry),
+            };
+            Ok(())
+        }
+    }
+    
+    /// Answers every search with one document naming the user and query
+    struct EchoKnowledge;
+    
+    #[async_trait]
+    impl host::KnowledgeSearch for EchoKnowledge {
+        async fn search(&self, user_id: &str, query: &str, _limit: usize) -> Result<Vec<rusty_ai_common::Document>> {
+            Ok(vec![rusty_ai_common::Document {
+                id: uuid::Uuid::nil(),
+                title: format!("{} for {}", query, user_id),
+                content: "Oat milk is in aisle 4".to_string(),
+                metadata: rusty_ai_common::DocumentMetadata {
+                    source: "notes.md".to_string(),
+                    file_type: "md".to_string(),
+                    tags: vec!["groceries".to_string()],
+                    summary: None,
+                    importance_score: 0.5,
+                    embeddings: Some(vec![0.1; 4]),
+                },
+                created_at: chrono::Utc::now(),
+                updated_at: chrono::Utc::now(),
+            }])
+        }
+    }
+    
+    async fn notes_manager(kv: Arc<MemoryKv>, policy: SecurityPolicy) -> (tempfile::TempDir, WasmPluginManager) {
+        let temp_dir = tempdir().unwrap();
+        let manager = WasmPluginManager::new(temp_dir.path()).unwrap().with_host_services(host::HostServices {
+            kv: Some(kv),
+            knowledge: Some(Arc::new(EchoKnowledge)),
+            policy,
+        });
+        manager.load_plugin("notes", NOTES_FIXTURE.as_bytes()).await.unwrap();
+        (temp_dir, manager)
+    }
+    
+    async fn call(manager: &WasmPluginManager, function: &str, input: &[u8]) -> Result<Vec<u8>> {
+        manager.execute_plugin("notes", function, input, context(&["plugins:execute"], CallOrigin::Api)).await
+    }
+    
+    #[tokio::test]
+    async fn test_host_imports_persist_plugin_data_and_gate_knowledge_search() {
+        let kv = Arc::new(MemoryKv::default());
+        let (_dir, manager) = notes_manager(kv.clone(), SecurityPolicy::default()).await;
+        
+        assert!(call(&manager, "recall", b"").await.unwrap().is_empty());
+        call(&manager, "remember", b"buy oat milk").await.unwrap();
+        assert_eq!(call(&manager, "recall", b"").await.unwrap(), b"buy oat milk");
+        // Stored under the plugin's id, where a fresh instance finds it
+        assert_eq!(kv.0.lock().unwrap().get(&("notes".to_string(), "note".to_string())).unwrap(), b"buy oat milk");
+        let (_dir, reloaded) = notes_manager(kv.clone(), SecurityPolicy::default()).await;
+        assert_eq!(call(&reloaded, "recall", b"").await.unwrap(), b"buy oat milk");
+        
+        let found: serde_json::Value = serde_json::from_slice(&call(&manager, "search", b"oat milk").await.unwrap()).unwrap();
+        assert_eq!(found["results"][0]["title"], "oat milk for user-1");
+        assert_eq!(found["results"][0]["tags"], serde_json::json!(["groceries"]));
+        assert!(found["results"][0].get("embeddings").is_none());
+        
+        // The plugin declares knowledge_access, but this policy does not grant it
+        let policy = SecurityPolicy { allowed_host_capabilities: Default::default(), ..SecurityPolicy::default() };
+        let (_dir, restricted) = notes_manager(kv, policy).await;
+        let denied: serde_json::Value = serde_json::from_slice(&call(&restricted, "search", b"oat milk").await.unwrap()).unwrap();
+        assert_eq!(denied["error"], "permission_denied");
+        assert!(denied.get("results").is_none());
+    }
+    
+    #[tokio::test]
+    async fn test_recorded_calls_replay_without_the_host_services() {
+        let (dir, production) = notes_manager(Arc::new(MemoryKv::default()), SecurityPolicy::default()).await;
+        let recorder = Arc::new(replay::PluginRecorder::new(dir.path().join("replays")));
+        recorder.enable("notes");
+        let production = production.with_recorder(recorder.clone());
+        call(&production, "remember", b"buy oat milk").await.unwrap();
+        let note = call(&production, "recall", b"").await.unwrap();
+        let found = call(&production, "search", b"oat milk").await.unwrap();
+
+        let recorded = replay::read_recording(recorder.path_for("notes")).await.unwrap();
+        assert_eq!(recorded.len(), 3);
+        assert_eq!(recorded[1].host_calls[0].function, "kv_get");
+        assert_eq!(recorded[1].host_calls[0].reply, host::HostReply::Data(b"buy oat milk".to_vec()));
+        assert_eq!(recorded[2].context.user_id, "user-1");
+
+        // No storage and no knowledge base: every reply comes from the file
+        let temp_dir = tempdir().unwrap();
+        let local = WasmPluginManager::new(temp_dir.path()).unwrap();
+        local.load_plugin("notes", NOTES_FIXTURE.as_bytes()).await.unwrap();
+        let reports = local.replay(recorder.path_for("notes")).await.unwrap();
+        assert_eq!(reports.len(), 3);
+        assert!(reports.iter().all(replay::ReplayReport::reproduced), "{:?}", reports);
+        assert_eq!(reports[1].replayed, replay::CallOutcome::Output(note));
+        assert_eq!(reports[2].replayed, replay::CallOutcome::Output(found));
+
+        // A build that asks for another key diverges, and its output differs
+        let other_key = NOTES_FIXTURE.replace("(data (i32.const 512) \"note\")", "(data (i32.const 512) \"todo\")");
+        local.load_plugin("notes", other_key.as_bytes()).await.unwrap();
+        let reports = local.replay(recorder.path_for("notes")).await.unwrap();
+        assert!(!reports[1].reproduced());
+        assert_eq!(reports[1].divergences, vec!["called kv_get(todo) where the recording has kv_get(note)".to_string()]);
+        assert!(reports[1].difference.as_deref().unwrap().starts_with("outputs differ from byte 0"), "{:?}", reports[1]);
+    }
+
+    #[tokio::test]
+    async fn test_example_plugin_call_replays_byte_identically() {
+        let (dir, manager) = manager_with_example_plugin().await;
+        let recorder = Arc::new(replay::PluginRecorder::new(dir.path().join("replays")));
+        let manager = manager.with_recorder(recorder.clone());
+        let input = br#"{"name": "Ada"}"#;
+
+        // Not enabled yet, so not recorded
+        manager.execute_plugin("example", "hello", input, context(&["plugins:execute"], CallOrigin::Api)).await.unwrap();
+        assert!(!recorder.path_for("example").exists());
+
+        recorder.enable("example");
+        let output = manager
+            .execute_plugin("example", "hello", input, context(&["plugins:execute"], CallOrigin::Api))
+            .await
+            .unwrap();
+        let reports = manager.replay(recorder.path_for("example")).await.unwrap();
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].recorded, replay::CallOutcome::Output(output.clone()));
+        assert_eq!(reports[0].replayed, replay::CallOutcome::Output(output));
+        assert!(reports[0].reproduced());
+        assert_eq!(reports[0].replayed_version, "1.0.0");
+    }
+
+    #[tokio::test]
+    async fn test_side_by_side_versions_with_canary_share_and_cutover() {
+        let (_dir, manager) = manager_with_builds(&[("echo@1.1.0", Some(1)), ("echo@1.2.0", Some(2))]).await;
+        assert_eq!(manager.list_plugins().await, vec!["echo".to_string()]);
+        assert_eq!(manager.plugin_versions("echo").await.unwrap().active, "1.1.0");
+        
+        // The new version is loaded but only reachable when pinned
+        for _ in 0..10 {
+            assert_eq!(served_build(&manager, "echo").await.unwrap(), 1);
+        }
+        assert_eq!(served_build(&manager, "echo@1.2.0").await.unwrap(), 2);
+        
+        manager.start_canary("echo", CanaryConfig::new("1.2.0", 25).min_executions(1000)).await.unwrap();
+        let mut canary_calls = 0;
+        for _ in 0..100 {
+            if served_build(&manager, "echo").await.unwrap() == 2 {
+                canary_calls += 1;
+            }
+        }
+        assert_eq!(canary_calls, 25);
+        let status = manager.plugin_versions("echo").await.unwrap().canary.unwrap();
+        assert_eq!((status.canary_executions, status.active_executions), (25, 75));
+        
+        manager.cutover("echo", "1.2.0").await.unwrap();
+        let versions = manager.plugin_versions("echo").await.unwrap();
+        assert_eq!(versions.active, "1.2.0");
+        assert!(versions.canary.is_none());
+        assert_eq!(versions.versions.iter().map(|v| v.version.as_str()).collect::<Vec<_>>(), vec!["1.2.0"]);
+        for _ in 0..10 {
+            assert_eq!(served_build(&manager, "echo").await.unwrap(), 2);
+        }
+        assert!(matches!(served_build(&manager, "echo@1.1.0").await, Err(AssistantError::NotFound(_))));
+        assert!(manager.cutover("echo", "1.1.0").await.is_err());
+    }
+    
+    #[tokio::test]
+    async fn test_canary_is_promoted_or_rolled_back_by_error_rate() {
+        let (_dir, manager) = manager_with_builds(&[("echo@1", Some(1)), ("echo@2", Some(2)), ("echo@3", None)]).await;
+        assert!(manager.start_canary("echo", CanaryConfig::new("1", 50)).await.is_err());
+        assert!(manager.start_canary("echo", CanaryConfig::new("2", 100)).await.is_err());
+        
+        // Half the calls go to 2 and none fail: promoted after its tenth
+        manager.start_canary("echo", CanaryConfig::new("2", 50).min_executions(10)).await.unwrap();
+        for _ in 0..20 {
+            served_build(&manager, "echo").await.unwrap();
+        }
+        let versions = manager.plugin_versions("echo").await.unwrap();
+        assert_eq!(versions.active, "2");
+        assert!(versions.canary.is_none());
+        
+        // Every call to 3 fails: rolled back and unloaded
+        manager.start_canary("echo", CanaryConfig::new("3", 50).min_executions(5)).await.unwrap();
+        let mut failures = 0;
+        for _ in 0..10 {
+            if served_build(&manager, "echo").await.is_err() {
+                failures += 1;
+            }
+        }
+        assert_eq!(failures, 5);
+        let versions = manager.plugin_versions("echo").await.unwrap();
+        assert_eq!(versions.active, "2");
+        assert!(versions.canary.is_none());
+        assert_eq!(versions.versions.iter().map(|v| v.version.as_str()).collect::<Vec<_>>(), vec!["2"]);
+        assert_eq!(served_build(&manager, "echo").await.unwrap(), 2);
+    }
+    
+    // Answers the functions named after its capabilities with its own id,
+    // or fails every call
+    struct CapabilityPlugin {
+        metadata: WasmPluginMetadata,
+        failing: bool,
+        /// Every config the plugin was initialized with
+        initialized: Arc<std::sync::Mutex<Vec<serde_json::Value>>>,
+    }
+    
+    impl CapabilityPlugin {
+        fn new(id: &str, capabilities: &[&str], failing: bool) -> Self {
+            let metadata = WasmPluginMetadata {
+                id: id.to_string(),
+                name: id.to_string(),
+                version: "1.0.0".to_string(),
+                description: String::new(),
+                author: String::new(),
+                license: String::new(),
+                capabilities: capabilities.iter().map(|c| c.to_string()).collect(),
+                dependencies: vec![],
+                api_version: "1.0".to_string(),
+                checksum: String::new(),
+                function_limits: HashMap::new(),
+                events: vec![],
+            };
+            Self { metadata, failing, initialized: Arc::default() }
+        }
+    }
+    
+    #[async_trait]
+    impl WasmPlugin for CapabilityPlugin {
+        fn metadata(&self) -> &WasmPluginMetadata {
+            &self.metadata
+        }
+        
+        async fn initialize(&mut self, config: serde_json::Value) -> Result<()> {
+            self.initialized.lock().unwrap().push(config);
+            Ok(())
+        }
+        
+        async fn execute(&self, function: &str, _input: &[u8], _context: &PluginContext) -> Result<Vec<u8>> {
+            if self.failing || !self.can_handle(function) {
+                return Err(AssistantError::Plugin(format!("{} cannot run {}", self.metadata.id, function)));
+            }
+            Ok(serde_json::to_vec(&serde_json::json!({ "response": self.metadata.id })).unwrap())
+        }
+        
+        fn can_handle(&self, capability: &str) -> bool {
+            self.metadata.capabilities.iter().any(|c| c == capability)
+        }
+        
+        async fn health_check(&self) -> Result<PluginHealth> {
+            Err(AssistantError::Plugin("not checked".to_string()))
+        }
+        
+        async fn cleanup(&mut self) -> Result<()> {
+            Ok(())
+        }
+    }
+    
+    #[tokio::test]
+    async fn test_capability_dispatch_follows_priority_and_falls_back_on_failure() {
+        let temp_dir = tempdir().unwrap();
+        let manager = WasmPluginManager::new(temp_dir.path()).unwrap();
+        for (id, capabilities, failing) in [
+            ("beta", &["weather_lookup"][..], false),
+            ("alpha", &["weather_lookup", "text_processing"][..], false),
+            ("gamma", &["weather_lookup"][..], true),
+        ] {
+            manager.register_plugin(id, Box::new(CapabilityPlugin::new(id, capabilities, failing))).await.unwrap();
+            let schema = FunctionSchema {
+                name: "weather_lookup".to_string(),
+                description: String::new(),
+                input_schema: None,
+                output_schema: None,
+                required_permission: None,
+                public: true,
+                limits: None,
+            };
+            manager.register_function_schemas(id, vec![schema]).await;
+        }
+        let config = |enabled, priority| PluginConfig { enabled, priority, settings: HashMap::new(), quota: PluginQuota::default() };
+        let ctx = || context(&[DEFAULT_BASELINE_PERMISSION], CallOrigin::Conversation);
+        let answered_by = |dispatch: CapabilityDispatch| serde_json::from_slice::<serde_json::Value>(&dispatch.output).unwrap()["response"].clone();
+        
+        // Equal priorities go by name
+        assert_eq!(manager.find_plugins_for_capability("weather_lookup").await, vec!["alpha", "beta", "gamma"]);
+        assert_eq!(manager.find_plugins_for_capability("text_processing").await, vec!["alpha"]);
+        assert!(manager.find_plugins_for_capability("translation").await.is_empty());
+        
+        manager.set_plugin_config("beta", config(true, 10)).await;
+        assert_eq!(manager.find_plugins_for_capability("weather_lookup").await, vec!["beta", "alpha", "gamma"]);
+        let dispatch = manager.dispatch_capability("weather_lookup", b"{}", ctx()).await.unwrap();
+        assert!(dispatch.failed.is_empty());
+        assert_eq!(answered_by(dispatch), "beta");
+        
+        // The highest priority fails, so the next one answers
+        manager.set_plugin_config("gamma", config(true, 20)).await;
+        let dispatch = manager.dispatch_capability("weather_lookup", b"{}", ctx()).await.unwrap();
+        assert_eq!(dispatch.failed.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>(), vec!["gamma"]);
+        assert_eq!(answered_by(dispatch), "beta");
+        
+        manager.set_plugin_config("beta", config(false, 10)).await;
+        assert_eq!(manager.find_plugins_for_capability("weather_lookup").await, vec!["gamma", "alpha"]);
+        assert_eq!(answered_by(manager.dispatch_capability("weather_lookup", b"{}", ctx()).await.unwrap()), "alpha");
+        
+        // text_processing has no public function schema, so chat may not call it
+        let refused = manager.dispatch_capability("text_processing", b"{}", ctx()).await.unwrap_err();
+        assert!(refused.to_string().contains("alpha: "));
+        assert!(matches!(
+            manager.dispatch_capability("translation", b"{}", ctx()).await,
+            Err(AssistantError::NotFound(_))
+        ));
+    }
+    
+    #[derive(Default)]
+    struct MemoryConfigStore(std::sync::Mutex<HashMap<String, PluginConfig>>);
+    
+    #[async_trait]
+    impl PluginConfigStore for MemoryConfigStore {
+        async fn save(&self, plugin_id: &str, config: &PluginConfig) -> Result<()> {
+            self.0.lock().unwrap().insert(plugin_id.to_string(), config.clone());
+            Ok(())
+        }
+        
+        async fn load(&self, plugin_id: &str) -> Result<Option<PluginConfig>> {
+            Ok(self.0.lock().unwrap().get(plugin_id).cloned())
+        }
+    }
+    
+    #[tokio::test]
+    async fn test_stored_config_is_reapplied_when_a_restarted_manager_loads_the_plugin() {
+        let temp_dir = tempdir().unwrap();
+        let store = Arc::new(MemoryConfigStore::default());
+        let config = |enabled, settings: serde_json::Value| PluginConfig {
+            enabled,
+            priority: 0,
+            settings: serde_json::from_value(settings).unwrap(),
+            quota: PluginQuota::default(),
+        };
+        
+        let manager = WasmPluginManager::new(temp_dir.path()).unwrap().with_config_store(store.clone());
+        let plugin = CapabilityPlugin::new("weather", &["weather_lookup"], false);
+        let initialized = plugin.initialized.clone();
+        manager.register_plugin("weather", Box::new(plugin)).await.unwrap();
+        manager
+            .configure_plugin("weather", config(true, serde_json::json!({"units": "metric", "city": "Berlin"})))
+            .await
+            .unwrap();
+        // Settings left out are kept, and null removes one
+        let merged = manager
+            .configure_plugin("weather", config(false, serde_json::json!({"units": null, "days": 3})))
+            .await
+            .unwrap();
+        assert!(!merged.enabled);
+        assert_eq!(
+            *initialized.lock().unwrap(),
+            vec![
+                serde_json::json!({"units": "metric", "city": "Berlin"}),
+                serde_json::json!({"city": "Berlin", "days": 3}),
+            ]
+        );
+        assert!(manager.find_plugins_for_capability("weather_lookup").await.is_empty());
+        assert_eq!(manager.health_check_all().await["weather"].status, HealthStatus::Disabled);
+        
+        // A new manager on the same store applies the config as the plugin loads
+        let restarted = WasmPluginManager::new(temp_dir.path()).unwrap().with_config_store(store);
+        let plugin = CapabilityPlugin::new("weather", &["weather_lookup"], false);
+        let initialized = plugin.initialized.clone();
+        restarted.register_plugin("weather", Box::new(plugin)).await.unwrap();
+        assert_eq!(*initialized.lock().unwrap(), vec![serde_json::json!({"city": "Berlin", "days": 3})]);
+        assert!(restarted.find_plugins_for_capability("weather_lookup").await.is_empty());
+        assert_eq!(restarted.health_check_all().await["weather"].status, HealthStatus::Disabled);
+        
+        restarted.configure_plugin("weather", config(true, serde_json::json!({}))).await.unwrap();
+        assert_eq!(restarted.find_plugins_for_capability("weather_lookup").await, vec!["weather"]);
+        assert_eq!(restarted.plugin_config("weather").await.settings.len(), 2);
+    }
+    
+    // Answers `work` after a delay with its version and records its cleanup
+    struct SlowPlugin {
+        metadata: WasmPluginMetadata,
+        cleaned_up: Arc<std::sync::atomic::AtomicBool>,
+    }
+    
+    impl SlowPlugin {
+        fn new(version: &str) -> (Self, Arc<std::sync::atomic::AtomicBool>) {
+            let cleaned_up = Arc::new(std::sync::atomic::AtomicBool::new(false));
+            let metadata = WasmPluginMetadata {
+                id: "slow".to_string(),
+                name: "slow".to_string(),
+                version: version.to_string(),
+                description: String::new(),
+                author: String::new(),
+                license: String::new(),
+                capabilities: vec![],
+                dependencies: vec![],
+                api_version: "1.0".to_string(),
+                checksum: String::new(),
+                function_limits: HashMap::new(),
+                events: vec![],
+            };
+            (Self { metadata, cleaned_up: cleaned_up.clone() }, cleaned_up)
+        }
+    }
+    
+    #[async_trait]
+    impl WasmPlugin for SlowPlugin {
+        fn metadata(&self) -> &WasmPluginMetadata {
+            &self.metadata
+        }
+        
+        async fn initialize(&mut self, _config: serde_json::Value) -> Result<()> {
+            Ok(())
+        }
+        
+        async fn execute(&self, function: &str, _input: &[u8], _context: &PluginContext) -> Result<Vec<u8>> {
+            if function != "work" {
+                return Err(AssistantError::Plugin(format!("no such function: {}", function)));
+            }
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            Ok(self.metadata.version.clone().into_bytes())
+        }
+        
+        fn can_handle(&self, _capability: &str) -> bool {
+            false
+        }
+        
+        async fn health_check(&self) -> Result<PluginHealth> {
+            Err(AssistantError::Plugin("not checked".to_string()))
+        }
+        
+        async fn cleanup(&mut self) -> Result<()> {
+            self.cleaned_up.store(true, std::sync::atomic::Ordering::SeqCst);
+            Ok(())
+        }
+    }
+    
+    #[tokio::test]
+    async fn test_cutover_unloads_old_version_after_running_calls_finish() {
+        let temp_dir = tempdir().unwrap();
+        let manager = Arc::new(WasmPluginManager::new(temp_dir.path()).unwrap());
+        let (old, old_cleaned_up) = SlowPlugin::new("1");
+        let (new, _) = SlowPlugin::new("2");
+        manager.register_plugin("slow", Box::new(old)).await.unwrap();
+        manager.register_plugin("slow@2", Box::new(new)).await.unwrap();
+        
+        let running = tokio::spawn({
+            let manager = manager.clone();
+            async move {
+                manager.execute_plugin("slow", "work", b"", context(&["plugins:execute"], CallOrigin::Api)).await
+            }
+        });
+        while manager.plugin_versions("slow").await.unwrap().versions[0].in_flight == 0 {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        
+        manager.cutover("slow", "2").await.unwrap();
+        assert!(!old_cleaned_up.load(std::sync::atomic::Ordering::SeqCst));
+        let output = manager
+            .execute_plugin("slow", "work", b"", context(&["plugins:execute"], CallOrigin::Api))
+            .await
+            .unwrap();
+        assert_eq!(output, b"2");
+        
+        // The call that started before the cutover finished on version 1
+        assert_eq!(running.await.unwrap().unwrap(), b"1");
+        for _ in 0..100 {
+            if old_cleaned_up.load(std::sync::atomic::Ordering::SeqCst) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        assert!(old_cleaned_up.load(std::sync::atomic::Ordering::SeqCst));
+    }
+    
+    #[tokio::test]
+    async fn test_unload_waits_for_running_calls_and_turns_new_ones_away() {
+        let temp_dir = tempdir().unwrap();
+        let manager = Arc::new(WasmPluginManager::new(temp_dir.path()).unwrap());
+        let (plugin, cleaned_up) = SlowPlugin::new("1");
+        manager.register_plugin("slow", Box::new(plugin)).await.unwrap();
+        
+        let running = tokio::spawn({
+            let manager = manager.clone();
+            async move {
+                manager.execute_plugin("slow", "work", b"", context(&["plugins:execute"], CallOrigin::Api)).await
+            }
+        });
+        while manager.plugin_versions("slow").await.unwrap().versions[0].in_flight == 0 {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        let unloading = tokio::spawn({
+            let manager = manager.clone();
+            async move { manager.unload_plugin("slow").await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        
+        let refused = manager
+            .execute_plugin("slow", "work", b"", context(&["plugins:execute"], CallOrigin::Api))
+            .await
+            .unwrap_err();
+        assert!(matches!(refused, AssistantError::Unavailable { retry_after_secs: 1, .. }), "{:?}", refused);
+        assert!(!cleaned_up.load(std::sync::atomic::Ordering::SeqCst));
+        
+        // The running call finishes before the plugin is cleaned up
+        assert_eq!(running.await.unwrap().unwrap(), b"1");
+        unloading.await.unwrap().unwrap();
+        assert!(cleaned_up.load(std::sync::atomic::Ordering::SeqCst));
+        assert!(matches!(
+            manager.execute_plugin("slow", "work", b"", context(&["plugins:execute"], CallOrigin::Api)).await,
+            Err(AssistantError::NotFound(_))
+        ));
+    }
+    
+    #[tokio::test]
+    async fn test_unload_all_drains_plugins_concurrently_within_the_deadline() {
+        let temp_dir = tempdir().unwrap();
+        let manager = Arc::new(WasmPluginManager::new(temp_dir.path()).unwrap());
+        let (first, first_cleaned_up) = SlowPlugin::new("1");
+        let (second, second_cleaned_up) = SlowPlugin::new("1");
+        manager.register_plugin("first", Box::new(first)).await.unwrap();
+        manager.register_plugin("second", Box::new(second)).await.unwrap();
+        
+        let start = |plugin_id: &'static str| {
+            let manager = manager.clone();
+            tokio::spawn(async move {
+                manager.execute_plugin(plugin_id, "work", b"", context(&["plugins:execute"], CallOrigin::Api)).await
+            })
+        };
+        let calls = [start("first"), start("second")];
+        for plugin_id in ["first", "second"] {
+            while manager.plugin_versions(plugin_id).await.unwrap().versions[0].in_flight == 0 {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        }
+        
+        // Both 200ms calls are waited for side by side
+        let started = Instant::now();
+        manager.unload_all(Duration::from_secs(5)).await.unwrap();
+        assert!(started.elapsed() < Duration::from_millis(350), "plugins drained one by one: {:?}", started.elapsed());
+        assert!(first_cleaned_up.load(std::sync::atomic::Ordering::SeqCst));
+        assert!(second_cleaned_up.load(std::sync::atomic::Ordering::SeqCst));
+        for call in calls {
+            assert_eq!(call.await.unwrap().unwrap(), b"1");
+        }
+        
+        // Past the deadline the plugin is dropped without cleanup
+        let (stuck, stuck_cleaned_up) = SlowPlugin::new("1");
+        manager.register_plugin("stuck", Box::new(stuck)).await.unwrap();
+        let call = start("stuck");
+        while manager.plugin_versions("stuck").await.unwrap().versions[0].in_flight == 0 {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        let error = manager.unload_all(Duration::from_millis(20)).await.unwrap_err();
+        assert!(matches!(error, AssistantError::Timeout(_)), "{:?}", error);
+        assert_eq!(call.await.unwrap().unwrap(), b"1");
+        assert!(!stuck_cleaned_up.load(std::sync::atomic::Ordering::SeqCst));
+    }
+    
+    #[tokio::test]
+    async fn test_pooled_instances_run_calls_to_one_plugin_concurrently() {
+        let temp_dir = tempdir().unwrap();
+        let manager = Arc::new(WasmPluginManager::new(temp_dir.path()).unwrap());
+        let factory: InstanceFactory =
+            Arc::new(|| Box::pin(async { Ok(Box::new(SlowPlugin::new("1").0) as Box<dyn WasmPlugin>) }));
+        let first = factory().await.unwrap();
+        manager.register_instances("slow", InstancePool::new(first, factory, 2)).await.unwrap();
+        
+        let call = || {
+            let manager = manager.clone();
+            async move { manager.execute_plugin("slow", "work", b"", context(&["plugins:execute"], CallOrigin::Api)).await }
+        };
+        let started = Instant::now();
+        let (first, second) = tokio::join!(call(), call());
+        assert_eq!(first.unwrap(), b"1");
+        assert_eq!(second.unwrap(), b"1");
+        // Each call takes 200ms; on one instance they would take 400ms
+        assert!(started.elapsed() < Duration::from_millis(350), "calls did not overlap: {:?}", started.elapsed());
+        
+        let health = manager.health_check_all().await;
+        assert_eq!(health["slow"].pool, Some(PoolUtilization { size: 2, instantiated: 2, busy: 0 }));
+        manager.initialize_plugin("slow", serde_json::json!({"units": "metric"})).await.unwrap();
+    }
+    
+    // Answers health checks after a delay and counts them
+    struct HealthProbePlugin {
+        metadata: WasmPluginMetadata,
+        delay: Duration,
+        checks: Arc<std::sync::atomic::AtomicUsize>,
+    }
+    
+    impl HealthProbePlugin {
+        fn new(id: &str, delay: Duration) -> (Self, Arc<std::sync::atomic::AtomicUsize>) {
+            let checks = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+            let metadata = WasmPluginMetadata {
+                id: id.to_string(),
+                name: id.to_string(),
+                version: "1".to_string(),
+                description: String::new(),
+                author: String::new(),
+                license: String::new(),
+                capabilities: vec![],
+                dependencies: vec![],
+                api_version: "1.0".to_string(),
+                checksum: String::new(),
+                function_limits: HashMap::new(),
+                events: vec![],
+            };
+            (Self { metadata, delay, checks: checks.clone() }, checks)
+        }
+    }
+    
+    #[async_trait]
+    impl WasmPlugin for HealthProbePlugin {
+        fn metadata(&self) -> &WasmPluginMetadata {
+            &self.metadata
+        }
+        
+        async fn initialize(&mut self, _config: serde_json::Value) -> Result<()> {
+            Ok(())
+        }
+        
+        async fn execute(&self, function: &str, _input: &[u8], _context: &PluginContext) -> Result<Vec<u8>> {
+            Err(AssistantError::Plugin(format!("no such function: {}", function)))
+        }
+        
+        fn can_handle(&self, _capability: &str) -> bool {
+            false
+        }
+        
+        async fn health_check(&self) -> Result<PluginHealth> {
+            self.checks.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            tokio::time::sleep(self.delay).await;
+            Ok(PluginHealth {
+                status: HealthStatus::Healthy,
+                message: None,
+                last_check: chrono::Utc::now(),
+                execution_count: 0,
+                error_count: 0,
+                average_execution_time: Duration::from_secs(0),
+                pool: None,
+                circuit_state: CircuitState::Closed,
+                consecutive_failures: 0,
+            })
+        }
+        
+        async fn cleanup(&mut self) -> Result<()> {
+            Ok(())
+        }
+    }
+    
+    // Fails every call while `failing` is set, counting the calls it gets
+    struct FlakyPlugin {
+        probe: HealthProbePlugin,
+        failing: Arc<std::sync::atomic::AtomicBool>,
+        calls: Arc<std::sync::atomic::AtomicUsize>,
+    }
+    
+    #[async_trait]
+    impl WasmPlugin for FlakyPlugin {
+        fn metadata(&self) -> &WasmPluginMetadata {
+            self.probe.metadata()
+        }
+        
+        async fn initialize(&mut self, _config: serde_json::Value) -> Result<()> {
+            Ok(())
+        }
+        
+        async fn execute(&self, function: &str, input: &[u8], _context: &PluginContext) -> Result<Vec<u8>> {
+            self.calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            if function == "hang" {
+                std::future::pending::<()>().await;
+            }
+            if self.failing.load(std::sync::atomic::Ordering::SeqCst) {
+                return Err(AssistantError::Plugin("upstream is down".to_string()));
+            }
+            Ok(input.to_vec())
+        }
+        
+        fn can_handle(&self, _capability: &str) -> bool {
+            false
+        }
+        
+        async fn health_check(&self) -> Result<PluginHealth> {
+            self.probe.health_check().await
+        }
+        
+        async fn cleanup(&mut self) -> Result<()> {
+            Ok(())
+        }
+    }
+    
+    #[tokio::test]
+    async fn test_circuit_opens_on_a_failing_plugin_and_closes_after_a_good_probe() {
+        let temp_dir = tempdir().unwrap();
+        let mut manager = WasmPluginManager::new(temp_dir.path()).unwrap();
+        let cooldown = Duration::from_millis(100);
+        manager.set_circuit_breaker(3, cooldown);
+        
+        let failing = Arc::new(std::sync::atomic::AtomicBool::new(true));
+        let calls = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let (probe, _) = HealthProbePlugin::new("flaky", Duration::ZERO);
+        let plugin = FlakyPlugin { probe, failing: failing.clone(), calls: calls.clone() };
+        manager.register_plugin("flaky", Box::new(plugin)).await.unwrap();
+        let call = || manager.execute_plugin("flaky", "fetch", b"ok", context(&["plugins:execute"], CallOrigin::Api));
+        let calls_made = || calls.load(std::sync::atomic::Ordering::SeqCst);
+        
+        for _ in 0..3 {
+            assert!(call().await.unwrap_err().to_string().contains("upstream is down"));
+        }
+        let health = manager.health_check_all().await;
+        assert_eq!(health["flaky"].status, HealthStatus::Unhealthy);
+        assert_eq!((health["flaky"].circuit_state, health["flaky"].consecutive_failures), (CircuitState::Open, 3));
+        
+        // Open: refused without reaching the plugin
+        assert!(call().await.unwrap_err().to_string().contains("calls are refused"));
+        assert_eq!(calls_made(), 3);
+        
+        // Half-open once the cooldown has passed; the probe goes through and
+        // closes the circuit
+        tokio::time::sleep(cooldown + Duration::from_millis(20)).await;
+        assert_eq!(manager.health_check_all().await["flaky"].circuit_state, CircuitState::HalfOpen);
+        failing.store(false, std::sync::atomic::Ordering::SeqCst);
+        assert_eq!(call().await.unwrap(), b"ok");
+        assert_eq!(calls_made(), 4);
+        
+        let health = manager.health_check_all().await;
+        assert_eq!(health["flaky"].status, HealthStatus::Healthy);
+        assert_eq!((health["flaky"].circuit_state, health["flaky"].consecutive_failures), (CircuitState::Closed, 0));
+        call().await.unwrap();
+    }
+    
+    #[tokio::test]
+    async fn test_a_probe_dropped_mid_call_reopens_the_circuit() {
+        let temp_dir = tempdir().unwrap();
+        let mut manager = WasmPluginManager::new(temp_dir.path()).unwrap();
+        let cooldown = Duration::from_millis(100);
+        manager.set_circuit_breaker(3, cooldown);
+        
+        let failing = Arc::new(std::sync::atomic::AtomicBool::new(true));
+        let (probe, _) = HealthProbePlugin::new("flaky", Duration::ZERO);
+        let plugin = FlakyPlugin { probe, failing: failing.clone(), calls: Arc::default() };
+        manager.register_plugin("flaky", Box::new(plugin)).await.unwrap();
+        let call = |function| manager.execute_plugin("flaky", function, b"ok", context(&["plugins:execute"], CallOrigin::Api));
+        for _ in 0..3 {
+            call("fetch").await.unwrap_err();
+        }
+        
+        // The probe hangs and its caller gives up, dropping the call
+        tokio::time::sleep(cooldown + Duration::from_millis(20)).await;
+        assert!(tokio::time::timeout(Duration::from_millis(50), call("hang")).await.is_err());
+        let health = manager.health_check_all().await;
+        assert_eq!((health["flaky"].circuit_state, health["flaky"].consecutive_failures), (CircuitState::Open, 4));
+        
+        // Not stuck half-open: the next cooldown lets another probe through
+        tokio::time::sleep(cooldown + Duration::from_millis(20)).await;
+        failing.store(false, std::sync::atomic::Ordering::SeqCst);
+        assert_eq!(call("fetch").await.unwrap(), b"ok");
+        assert_eq!(manager.health_check_all().await["flaky"].circuit_state, CircuitState::Closed);
+    }
+    
+    #[tokio::test]
+    async fn test_health_check_all_bounds_stuck_plugins_and_caches_results() {
+        let temp_dir = tempdir().unwrap();
+        let mut manager = WasmPluginManager::new(temp_dir.path()).unwrap();
+        let timeout = Duration::from_millis(200);
+        manager.set_health_check_limits(timeout, Duration::from_secs(60));
+        
+        let mut fast_checks = Vec::new();
+        for i in 0..5 {
+            let (plugin, checks) = HealthProbePlugin::new(&format!("fast-{}", i), Duration::from_millis(100));
+            manager.register_plugin(&format!("fast-{}", i), Box::new(plugin)).await.unwrap();
+            fast_checks.push(checks);
+        }
+        let (stuck, stuck_checks) = HealthProbePlugin::new("stuck", Duration::from_secs(60));
+        manager.register_plugin("stuck", Box::new(stuck)).await.unwrap();
+        
+        // Checked one after another this would take over a minute; checked
+        // concurrently the call is bounded by the per-plugin timeout
+        let started = Instant::now();
+        let health = manager.health_check_all().await;
+        assert!(started.elapsed() < timeout * 3, "took {:?}", started.elapsed());
+        
+        assert_eq!(health.len(), 6);
+        assert_eq!(health["stuck"].status, HealthStatus::Unknown);
+        assert!((0..5).all(|i| health[&format!("fast-{}", i)].status == HealthStatus::Healthy));
+        
+        // Healthy plugins are served from the cache; the stuck one had no
+        // successful result and is asked again
+        let started = Instant::now();
+        let health = manager.health_check_all().await;
+        assert!(started.elapsed() < timeout * 3, "took {:?}", started.elapsed());
+        assert_eq!(health["fast-0"].status, HealthStatus::Healthy);
+        assert!(fast_checks.iter().all(|checks| checks.load(std::sync::atomic::Ordering::SeqCst) == 1));
+        assert_eq!(stuck_checks.load(std::sync::atomic::Ordering::SeqCst), 2);
+        
+        let cached = manager.cached_health().await;
+        assert_eq!(cached.len(), 5);
+        assert!(!cached.contains_key("stuck"));
+    }
+}
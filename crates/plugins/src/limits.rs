@@ -0,0 +1,305 @@
+//! Enforcement of the per-call CPU limits in [`ResourceLimits`].
+//!
+//! Fuel bounds how many instructions a call may run and is refilled before
+//! every call. Epoch interruption bounds how long a call may keep the CPU:
+//! [`EpochTicker`] advances the engine epoch on a fixed interval and each
+//! call's deadline is its `cpu_time_limit` in ticks. Both trap inside the
+//! guest, so a busy-looping plugin is stopped even though it never yields
+//! to the async runtime and the manager's wall-clock timeout cannot fire.
+//!
+//! [`StoreLimiter`] bounds linear memory and tables to `max_memory` and
+//! `max_table_elements`; growing past them traps with [`GrowthDenied`].
+
+use crate::ResourceLimits;
+use rusty_ai_common::AssistantError;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use wasmtime::{Engine, ResourceLimiter, Trap};
+
+/// How long a plugin reports degraded after one of its calls was killed
+pub const KILLED_CALL_DEGRADED_FOR: Duration = Duration::from_secs(5 * 60);
+
+/// Which limit stopped a call before it finished
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExecutionLimit {
+    /// Fuel ran out: the call executed more instructions than allowed
+    Fuel,
+    /// The epoch deadline passed: the call held the CPU for too long
+    CpuTime,
+    /// The manager's timeout fired while the call was waiting on the host
+    WallClock,
+    /// The call tried to grow memory or a table past its limit
+    Memory,
+}
+
+impl ExecutionLimit {
+    /// The limit behind a wasmtime error, if it was one of ours
+    pub fn from_trap(error: &anyhow::Error) -> Option<Self> {
+        match error.downcast_ref::<Trap>() {
+            Some(Trap::OutOfFuel) => Some(Self::Fuel),
+            Some(Trap::Interrupt) => Some(Self::CpuTime),
+            _ => error.downcast_ref::<GrowthDenied>().map(|_| Self::Memory),
+        }
+    }
+
+    /// The limit named in an error message built by [`ExecutionLimit::describe`]
+    pub fn of(error: &AssistantError) -> Option<Self> {
+        let AssistantError::Plugin(message) = error else {
+            return None;
+        };
+        [Self::Fuel, Self::CpuTime, Self::WallClock, Self::Memory]
+            .into_iter()
+            .find(|limit| message.contains(limit.phrase()))
+    }
+
+    /// Error text for a call stopped by this limit
+    pub fn describe(self, limits: &ResourceLimits) -> String {
+        match self {
+            Self::Fuel => format!("{} ({} units)", self.phrase(), limits.max_fuel),
+            Self::CpuTime => format!("{} of {:?}", self.phrase(), limits.cpu_time_limit),
+            Self::WallClock => format!("{} of {:?}", self.phrase(), limits.max_execution_time),
+            Self::Memory => format!("{} of {} bytes", self.phrase(), limits.max_memory),
+        }
+    }
+
+    fn phrase(self) -> &'static str {
+        match self {
+            Self::Fuel => "killed: ran out of fuel",
+            Self::CpuTime => "killed: exceeded its CPU time limit",
+            Self::WallClock => "killed: exceeded its wall-clock limit",
+            Self::Memory => "killed: exceeded its memory limit",
+        }
+    }
+}
+
+/// Epoch ticks a call may run for before it is interrupted
+pub fn epoch_deadline(limits: &ResourceLimits) -> u64 {
+    let interval = limits.epoch_interval.as_micros().max(1);
+    limits.cpu_time_limit.as_micros().div_ceil(interval).max(1) as u64
+}
+
+/// Tighter limits for one function, declared in the plugin's metadata under
+/// `function_limits`. An override never raises a limit above the
+/// plugin's own: a cheap function can be cut off sooner, not an expensive
+/// one allowed longer
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FunctionLimits {
+    /// Caps both the wall-clock timeout and the CPU time of a call
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_ms: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_fuel: Option<u64>,
+}
+
+impl FunctionLimits {
+    /// `base` with this override applied
+    pub fn apply(&self, base: &ResourceLimits) -> ResourceLimits {
+        let mut limits = base.clone();
+        if let Some(timeout) = self.timeout_ms.map(Duration::from_millis) {
+            limits.max_execution_time = limits.max_execution_time.min(timeout);
+            limits.cpu_time_limit = limits.cpu_time_limit.min(timeout);
+        }
+        if let Some(max_fuel) = self.max_fuel {
+            limits.max_fuel = limits.max_fuel.min(max_fuel);
+        }
+        limits
+    }
+
+    /// The limits a call runs under, as reported by introspection
+    pub fn effective(limits: &ResourceLimits) -> Self {
+        Self {
+            timeout_ms: Some(limits.max_execution_time.as_millis() as u64),
+            max_fuel: Some(limits.max_fuel),
+        }
+    }
+}
+
+/// Calls of one plugin version stopped by each limit
+#[derive(Debug, Default)]
+pub struct KilledCalls {
+    fuel: AtomicU64,
+    cpu_time: AtomicU64,
+    wall_clock: AtomicU64,
+    memory: AtomicU64,
+    last_killed_at: std::sync::Mutex<Option<Instant>>,
+}
+
+impl KilledCalls {
+    pub fn record(&self, limit: ExecutionLimit) {
+        let counter = match limit {
+            ExecutionLimit::Fuel => &self.fuel,
+            ExecutionLimit::CpuTime => &self.cpu_time,
+            ExecutionLimit::WallClock => &self.wall_clock,
+            ExecutionLimit::Memory => &self.memory,
+        };
+        counter.fetch_add(1, Ordering::SeqCst);
+        *self.last_killed_at.lock().unwrap() = Some(Instant::now());
+    }
+
+    pub fn total(&self) -> u64 {
+        [&self.fuel, &self.cpu_time, &self.wall_clock, &self.memory]
+            .iter()
+            .map(|counter| counter.load(Ordering::SeqCst))
+            .sum()
+    }
+
+    /// Health message while a call was killed within `window`, None otherwise
+    pub fn recent(&self, window: Duration) -> Option<String> {
+        let last = (*self.last_killed_at.lock().unwrap())?;
+        if last.elapsed() > window {
+            return None;
+        }
+        Some(format!(
+            "{} calls killed ({} out of fuel, {} over CPU time, {} over wall-clock time, {} over memory), the last {}s ago",
+            self.total(),
+            self.fuel.load(Ordering::SeqCst),
+            self.cpu_time.load(Ordering::SeqCst),
+            self.wall_clock.load(Ordering::SeqCst),
+            self.memory.load(Ordering::SeqCst),
+            last.elapsed().as_secs()
+        ))
+    }
+}
+
+/// Why a store refused to grow a memory or table. Returned from the
+/// limiter, it traps the guest, and the call fails with this as its message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GrowthDenied {
+    Memory { desired: usize, limit: u64 },
+    Table { desired: usize, limit: u32 },
+}
+
+impl fmt::Display for GrowthDenied {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let phrase = ExecutionLimit::Memory.phrase();
+        match self {
+            Self::Memory { desired, limit } => {
+                write!(f, "{}: tried to grow memory to {} bytes, the limit is {} bytes", phrase, desired, limit)
+            }
+            Self::Table { desired, limit } => {
+                write!(f, "{}: tried to grow a table to {} elements, the limit is {}", phrase, desired, limit)
+            }
+        }
+    }
+}
+
+impl std::error::Error for GrowthDenied {}
+
+/// Caps one store's linear memory at `max_memory` bytes and its tables at
+/// `max_table_elements`, remembering the largest memory it allowed.
+/// Instantiation goes through the same checks, so a module that declares
+/// more initial memory than allowed fails to load
+#[derive(Debug)]
+pub struct StoreLimiter {
+    max_memory: u64,
+    max_table_elements: u32,
+    peak_memory: u64,
+}
+
+impl StoreLimiter {
+    pub fn new(limits: &ResourceLimits) -> Self {
+        Self { max_memory: limits.max_memory, max_table_elements: limits.max_table_elements, peak_memory: 0 }
+    }
+
+    /// Largest linear memory the store has held, in bytes. Memories never
+    /// shrink, so this is also the current size of the largest one
+    pub fn peak_memory(&self) -> u64 {
+        self.peak_memory
+    }
+}
+
+impl ResourceLimiter for StoreLimiter {
+    fn memory_growing(&mut self, _current: usize, desired: usize, _maximum: Option<usize>) -> anyhow::Result<bool> {
+        if desired as u64 > self.max_memory {
+            return Err(GrowthDenied::Memory { desired, limit: self.max_memory }.into());
+        }
+        self.peak_memory = self.peak_memory.max(desired as u64);
+        Ok(true)
+    }
+
+    fn table_growing(&mut self, _current: usize, desired: usize, _maximum: Option<usize>) -> anyhow::Result<bool> {
+        if desired > self.max_table_elements as usize {
+            return Err(GrowthDenied::Table { desired, limit: self.max_table_elements }.into());
+        }
+        Ok(true)
+    }
+}
+
+/// Advances an engine's epoch until dropped.
+///
+/// A plain thread rather than a tokio task: a guest stuck in a loop occupies
+/// a runtime worker, and on a single-threaded runtime a task-based ticker
+/// would never get to run and interrupt it.
+pub struct EpochTicker {
+    interval_micros: Arc<AtomicU64>,
+    stopped: Arc<AtomicBool>,
+}
+
+impl EpochTicker {
+    pub fn start(engine: Engine, interval: Duration) -> Self {
+        let interval_micros = Arc::new(AtomicU64::new(micros(interval)));
+        let stopped = Arc::new(AtomicBool::new(false));
+        let (thread_interval, thread_stopped) = (interval_micros.clone(), stopped.clone());
+        std::thread::Builder::new()
+            .name("plugin-epoch-ticker".to_string())
+            .spawn(move || {
+                while !thread_stopped.load(Ordering::Relaxed) {
+                    std::thread::sleep(Duration::from_micros(thread_interval.load(Ordering::Relaxed)));
+                    engine.increment_epoch();
+                }
+            })
+            .expect("failed to spawn the plugin epoch ticker");
+        Self { interval_micros, stopped }
+    }
+
+    /// Takes effect after the current tick
+    pub fn set_interval(&self, interval: Duration) {
+        self.interval_micros.store(micros(interval), Ordering::Relaxed);
+    }
+}
+
+impl Drop for EpochTicker {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::Relaxed);
+    }
+}
+
+fn micros(interval: Duration) -> u64 {
+    (interval.as_micros() as u64).max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_limit_errors_round_trip_through_their_message() {
+        let limits = ResourceLimits::default();
+        for limit in [ExecutionLimit::Fuel, ExecutionLimit::CpuTime, ExecutionLimit::WallClock, ExecutionLimit::Memory] {
+            let error = AssistantError::Plugin(format!("spin: {}", limit.describe(&limits)));
+            assert_eq!(ExecutionLimit::of(&error), Some(limit));
+        }
+        assert_eq!(ExecutionLimit::of(&AssistantError::Plugin("spin: no such export".to_string())), None);
+
+        let limits = ResourceLimits {
+            cpu_time_limit: Duration::from_millis(95),
+            epoch_interval: Duration::from_millis(10),
+            ..ResourceLimits::default()
+        };
+        assert_eq!(epoch_deadline(&limits), 10);
+
+        let killed = KilledCalls::default();
+        assert!(killed.recent(KILLED_CALL_DEGRADED_FOR).is_none());
+        killed.record(ExecutionLimit::CpuTime);
+        killed.record(ExecutionLimit::Fuel);
+        let message = killed.recent(KILLED_CALL_DEGRADED_FOR).unwrap();
+        assert!(
+            message.starts_with("2 calls killed (1 out of fuel, 1 over CPU time, 0 over wall-clock time, 0 over memory)"),
+            "{}",
+            message
+        );
+    }
+}
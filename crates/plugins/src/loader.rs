@@ -1,18 +1,62 @@
-use crate::{WasmPluginMetadata, ResourceLimits, WasmRuntime, RuntimeConfig};
+use crate::{cache, dependencies, metadata, metrics, create_plugin_engine, PluginSandbox, PluginSignature, SecurityPolicy, SignatureStatus, WasmPlugin, WasmPluginInstance, WasmPluginMetadata, ResourceLimits, WasmRuntime, RuntimeConfig};
+use notify::{RecursiveMode, Watcher};
 use rusty_ai_common::{Result, AssistantError};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
-use std::time::SystemTime;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
 use tokio::fs;
+use tokio::sync::{broadcast, mpsc, Mutex};
 use tracing::{info, warn, error, debug, instrument};
-use sha2::{Sha256, Digest};
+use wasmtime::Engine;
+
+/// How long the plugin directory must be quiet before a change is picked
+/// up; compilers and copies write a module in several steps
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(250);
+
+/// Lifecycle events kept for slow subscribers
+const LIFECYCLE_EVENT_BUFFER: usize = 64;
 
 /// Plugin loader for discovering and loading WebAssembly plugins
 pub struct PluginLoader {
     plugin_directory: PathBuf,
     runtime: WasmRuntime,
+    engine: Engine,
+    security_policy: SecurityPolicy,
     loaded_plugins: HashMap<String, LoadedPlugin>,
+    /// The instance serving each loaded plugin; replaced only once a new
+    /// build has been instantiated and validated
+    instances: HashMap<String, Arc<WasmPluginInstance>>,
     plugin_registry: PluginRegistry,
+    events: broadcast::Sender<PluginLifecycleEvent>,
+    /// Compiled modules from earlier loads; None when the runtime config
+    /// turns the compilation cache off
+    module_cache: Option<cache::ModuleCache>,
+}
+
+/// A change to the set of loaded plugins
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PluginLifecycleEvent {
+    Loaded { plugin_id: String, version: String },
+    /// A new build replaced the one serving calls
+    Reloaded { plugin_id: String, version: String, previous_version: String },
+    Unloaded { plugin_id: String },
+    /// A build could not be loaded. For a plugin that was already loaded,
+    /// the previous build keeps serving
+    Failed { plugin_id: Option<String>, path: PathBuf, error: String },
+}
+
+/// Watches the plugin directory for the loader until dropped
+pub struct PluginWatcher {
+    _watcher: notify::RecommendedWatcher,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl Drop for PluginWatcher {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
 }
 
 /// Information about a loaded plugin
@@ -24,6 +68,15 @@ pub struct LoadedPlugin {
     pub loaded_at: SystemTime,
     pub size: u64,
     pub limits: ResourceLimits,
+    /// Whether the compiled module came from the module cache
+    pub cache_hit: bool,
+}
+
+/// A build instantiated and validated, ready to serve
+struct PreparedBuild {
+    instance: WasmPluginInstance,
+    signature: SignatureStatus,
+    cache_hit: bool,
 }
 
 /// Plugin registry for tracking available plugins
@@ -43,6 +96,9 @@ pub struct PluginEntry {
     pub metadata: WasmPluginMetadata,
     pub status: PluginStatus,
     pub last_updated: SystemTime,
+    /// What checking the plugin's detached signature found
+    #[serde(default)]
+    pub signature: SignatureStatus,
 }
 
 /// Plugin status in the registry
@@ -63,9 +119,9 @@ pub struct DiscoveryConfig {
     pub extensions: Vec<String>,
     /// Maximum file size (in bytes)
     pub max_file_size: u64,
-    /// Verify plugin signatures
+    /// Check signatures of discovered plugins; loading always checks them
     pub verify_signatures: bool,
-    /// Auto-reload on file changes
+    /// Reload plugins when their files change (see `PluginLoader::watch`)
     pub auto_reload: bool,
 }
 
@@ -85,16 +141,61 @@ impl PluginLoader {
     /// Create a new plugin loader
     #[instrument]
     pub fn new(plugin_directory: impl AsRef<Path>, runtime_config: RuntimeConfig) -> Result<Self> {
+        let engine = create_plugin_engine()?;
+        let module_cache = runtime_config.compilation_cache.then(|| {
+            let dir = runtime_config.cache_directory.clone()
+                .unwrap_or_else(|| plugin_directory.as_ref().join(cache::MODULE_CACHE_DIR));
+            cache::ModuleCache::open(dir, &engine, cache::process_key())
+        });
         let runtime = WasmRuntime::new(runtime_config)?;
+        let (events, _) = broadcast::channel(LIFECYCLE_EVENT_BUFFER);
         
         Ok(Self {
             plugin_directory: plugin_directory.as_ref().to_path_buf(),
             runtime,
+            engine,
+            security_policy: SecurityPolicy::default(),
             loaded_plugins: HashMap::new(),
+            instances: HashMap::new(),
             plugin_registry: PluginRegistry::new(),
+            events,
+            module_cache,
         })
     }
     
+    /// Validate plugins against `policy` instead of the default policy
+    pub fn with_security_policy(mut self, policy: SecurityPolicy) -> Self {
+        self.security_policy = policy;
+        self
+    }
+    
+    /// Authenticate compiled modules with a key the host keeps, so they are
+    /// reused across restarts. Without one the cache only serves reloads
+    /// within this process
+    pub fn with_module_cache_key(mut self, key: &[u8]) -> Self {
+        if let Some(module_cache) = &self.module_cache {
+            self.module_cache = Some(cache::ModuleCache::open(module_cache.dir().to_path_buf(), &self.engine, key));
+        }
+        self
+    }
+    
+    /// The compiled modules loads are served from, if caching is on
+    pub fn module_cache(&self) -> Option<&cache::ModuleCache> {
+        self.module_cache.as_ref()
+    }
+    
+    /// Plugins loaded, reloaded, unloaded or failing to load from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<PluginLifecycleEvent> {
+        self.events.subscribe()
+    }
+    
+    fn emit(&self, event: PluginLifecycleEvent) {
+        debug!("Plugin lifecycle: {:?}", event);
+        metrics::global().record_lifecycle(&event);
+        // No subscribers is fine
+        let _ = self.events.send(event);
+    }
+    
     /// Discover all plugins in the plugin directory
     #[instrument(skip(self))]
     pub async fn discover_plugins(&mut self, config: DiscoveryConfig) -> Result<Vec<PluginEntry>> {
@@ -176,12 +277,7 @@ impl PluginLoader {
     
     /// Check if a file is a potential plugin file
     fn is_plugin_file(&self, path: &Path, config: &DiscoveryConfig) -> bool {
-        if let Some(extension) = path.extension() {
-            if let Some(ext_str) = extension.to_str() {
-                return config.extensions.iter().any(|e| e == ext_str);
-            }
-        }
-        false
+        is_plugin_path(path, config)
     }
     
     /// Analyze a plugin file and create a plugin entry
@@ -204,9 +300,17 @@ impl PluginLoader {
         // Calculate checksum
         let checksum = self.calculate_checksum(&wasm_bytes);
         
-        // Try to extract metadata (this is a simplified approach)
+        // The registry is keyed on the id the plugin declares
         let plugin_metadata = self.extract_plugin_metadata(&wasm_bytes).await?;
         
+        let signature = if config.verify_signatures {
+            let plugin_signature = PluginSignature::read_for(path).await?;
+            PluginSandbox::new(self.security_policy.clone(), ResourceLimits::default())
+                .check_signature(&wasm_bytes, &plugin_metadata, plugin_signature.as_ref())
+        } else {
+            SignatureStatus::Unchecked
+        };
+        
         let plugin_entry = PluginEntry {
             id: plugin_metadata.id.clone(),
             name: plugin_metadata.name.clone(),
@@ -216,50 +320,40 @@ impl PluginLoader {
             metadata: plugin_metadata,
             status: PluginStatus::Available,
             last_updated: metadata.modified().unwrap_or(SystemTime::now()),
+            signature,
         };
         
         Ok(Some(plugin_entry))
     }
     
-    /// Extract metadata from WebAssembly binary
+    /// Extract metadata from WebAssembly binary: its metadata section, or the
+    /// `get_metadata` export of an instance when it has none
     async fn extract_plugin_metadata(&self, wasm_bytes: &[u8]) -> Result<WasmPluginMetadata> {
-        // In a production system, you would:
-        // 1. Parse the WebAssembly binary
-        // 2. Look for custom sections with metadata
-        // 3. Use component model introspection
-        // 4. Call metadata export functions
-        
-        // For this implementation, we'll use defaults with basic validation
-        
-        // Validate it's a valid WebAssembly module
-        match wasmtime::Module::new(&wasmtime::Engine::default(), wasm_bytes) {
-            Ok(_) => {
-                // Create default metadata - in production this would be extracted from the module
-                Ok(WasmPluginMetadata {
-                    id: format!("plugin_{}", uuid::Uuid::new_v4()),
-                    name: "WebAssembly Plugin".to_string(),
-                    version: "0.1.0".to_string(),
-                    description: "A WebAssembly plugin".to_string(),
-                    author: "Unknown".to_string(),
-                    license: "Unknown".to_string(),
-                    capabilities: vec!["general".to_string()],
-                    dependencies: vec![],
-                    api_version: "1.0".to_string(),
-                    checksum: self.calculate_checksum(wasm_bytes),
-                })
+        let mut metadata = match metadata::from_custom_section(wasm_bytes)? {
+            Some(metadata) => {
+                wasmtime::Module::new(&wasmtime::Engine::default(), wasm_bytes)
+                    .map_err(|e| AssistantError::Plugin(format!("Invalid WebAssembly module: {}", e)))?;
+                metadata
             }
-            Err(e) => Err(AssistantError::Plugin(format!("Invalid WebAssembly module: {}", e))),
-        }
+            None => {
+                let engine = create_plugin_engine()?;
+                let instance = WasmPluginInstance::new(&engine, wasm_bytes, ResourceLimits::default()).await?;
+                instance.metadata().clone()
+            }
+        };
+        metadata.checksum = self.calculate_checksum(wasm_bytes);
+        Ok(metadata)
     }
     
     /// Calculate SHA-256 checksum of plugin bytes
     fn calculate_checksum(&self, bytes: &[u8]) -> String {
-        let mut hasher = Sha256::new();
-        hasher.update(bytes);
-        hex::encode(hasher.finalize())
+        cache::checksum(bytes)
     }
     
-    /// Load a specific plugin by ID
+    /// Load a specific plugin by ID, after loading the plugins it depends
+    /// on that are available but not loaded yet. Fails before loading
+    /// anything if a dependency is missing, has a version outside the
+    /// declared range or is part of a cycle
     #[instrument(skip(self))]
     pub async fn load_plugin(&mut self, plugin_id: &str, limits: Option<ResourceLimits>) -> Result<()> {
         info!("Loading plugin: {}", plugin_id);
@@ -273,50 +367,181 @@ impl PluginLoader {
             return Ok(());
         }
         
+        let order = match self.dependency_order(plugin_id) {
+            Ok(order) => order,
+            Err(e) => {
+                let path = plugin_entry.file_path.clone();
+                self.plugin_registry.update_status(plugin_id, PluginStatus::Error(e.to_string()));
+                self.emit(PluginLifecycleEvent::Failed {
+                    plugin_id: Some(plugin_id.to_string()),
+                    path,
+                    error: e.to_string(),
+                });
+                return Err(e);
+            }
+        };
+        for dependency in order.iter().filter(|id| *id != plugin_id) {
+            info!("Loading plugin {} first; {} depends on it", dependency, plugin_id);
+            self.load_resolved(dependency, None).await.map_err(|e| {
+                AssistantError::Plugin(format!("Failed to load {}, a dependency of {}: {}", dependency, plugin_id, e))
+            })?;
+        }
+        self.load_resolved(plugin_id, limits).await
+    }
+    
+    /// Where `plugin_id` comes in loading it and its missing dependencies.
+    /// A loaded build counts over what the registry has for the same id
+    fn dependency_order(&self, plugin_id: &str) -> Result<Vec<String>> {
+        let mut known: HashMap<&str, &WasmPluginMetadata> = self.plugin_registry.plugins.iter()
+            .filter(|(_, entry)| entry.status != PluginStatus::Disabled)
+            .map(|(id, entry)| (id.as_str(), &entry.metadata))
+            .collect();
+        known.extend(self.loaded_plugins.iter().map(|(id, plugin)| (id.as_str(), &plugin.metadata)));
+        let loaded: HashSet<&str> = self.loaded_plugins.keys().map(String::as_str).collect();
+        dependencies::load_order(plugin_id, &known, &loaded)
+    }
+    
+    /// Load one registered plugin whose dependencies are loaded
+    async fn load_resolved(&mut self, plugin_id: &str, limits: Option<ResourceLimits>) -> Result<()> {
+        let plugin_entry = self.plugin_registry.get_plugin(plugin_id)
+            .ok_or_else(|| AssistantError::NotFound(format!("Plugin not found: {}", plugin_id)))?;
+        
         // Read plugin file
         let wasm_bytes = fs::read(&plugin_entry.file_path).await
             .map_err(|e| AssistantError::Plugin(format!("Failed to read plugin file: {}", e)))?;
         
-        // Verify checksum
-        let current_checksum = self.calculate_checksum(&wasm_bytes);
-        if current_checksum != plugin_entry.checksum {
-            return Err(AssistantError::Plugin(
-                format!("Plugin checksum mismatch for {}: expected {}, got {}", 
-                    plugin_id, plugin_entry.checksum, current_checksum)
-            ));
-        }
-        
         // Use provided limits or defaults
         let resource_limits = limits.unwrap_or_default();
+        let file_path = plugin_entry.file_path.clone();
+        let current_checksum = plugin_entry.checksum.clone();
         
-        // Load plugin into runtime
-        let _instance = self.runtime.get_instance(plugin_id, &wasm_bytes, resource_limits.clone()).await?;
-        
-        // Create loaded plugin record
-        let loaded_plugin = LoadedPlugin {
-            metadata: plugin_entry.metadata.clone(),
-            file_path: plugin_entry.file_path.clone(),
-            checksum: current_checksum,
-            loaded_at: SystemTime::now(),
-            size: wasm_bytes.len() as u64,
-            limits: resource_limits,
+        let preparing = Instant::now();
+        let prepared = self.prepare(plugin_id, &file_path, &wasm_bytes, &current_checksum, &resource_limits).await;
+        metrics::global().record_load_time(plugin_id, preparing.elapsed());
+        let prepared = match prepared {
+            Ok(prepared) => prepared,
+            Err(e) => {
+                self.plugin_registry.update_status(plugin_id, PluginStatus::Error(e.to_string()));
+                self.emit(PluginLifecycleEvent::Failed {
+                    plugin_id: Some(plugin_id.to_string()),
+                    path: file_path,
+                    error: e.to_string(),
+                });
+                return Err(e);
+            }
         };
-        
-        self.loaded_plugins.insert(plugin_id.to_string(), loaded_plugin);
-        self.plugin_registry.update_status(plugin_id, PluginStatus::Loaded);
+        let version = prepared.instance.metadata().version.clone();
+        self.install(plugin_id, file_path, prepared, current_checksum, wasm_bytes.len() as u64, resource_limits);
         
         info!("Plugin loaded successfully: {}", plugin_id);
+        self.emit(PluginLifecycleEvent::Loaded { plugin_id: plugin_id.to_string(), version });
         Ok(())
     }
     
-    /// Unload a plugin
+    /// Check a build of `plugin_id` against the checksum it is expected to
+    /// have and, with the signature next to `file_path`, against the
+    /// security policy, then instantiate it, without touching what is
+    /// loaded. Nothing of the build runs before its bytes and compiled
+    /// module pass. The module comes from the cache when it has one
+    async fn prepare(
+        &self,
+        plugin_id: &str,
+        file_path: &Path,
+        wasm_bytes: &[u8],
+        checksum: &str,
+        limits: &ResourceLimits,
+    ) -> Result<PreparedBuild> {
+        let actual = self.calculate_checksum(wasm_bytes);
+        if actual != checksum {
+            return Err(AssistantError::Plugin(format!(
+                "Plugin checksum mismatch for {}: expected {}, got {}",
+                plugin_id, checksum, actual
+            )));
+        }
+        let signature = PluginSignature::read_for(file_path).await?;
+        
+        let (module, cache_hit) = match &self.module_cache {
+            Some(module_cache) => module_cache.load(&self.engine, wasm_bytes, checksum)?,
+            None => {
+                let module = wasmtime::Module::new(&self.engine, wasm_bytes)
+                    .map_err(|e| AssistantError::Plugin(format!("Failed to compile module: {}", e)))?;
+                (module, false)
+            }
+        };
+        let mut sandbox = PluginSandbox::new(self.security_policy.clone(), limits.clone());
+        let signature = sandbox.validate_module(plugin_id, wasm_bytes, &module, signature.as_ref())?;
+        
+        let wasi_ctx = crate::PluginWasiCtx::new(limits.clone())?;
+        let instance = WasmPluginInstance::from_module(&self.engine, &module, wasm_bytes, limits.clone(), wasi_ctx).await?;
+        let declared = &instance.metadata().id;
+        if declared != plugin_id {
+            return Err(AssistantError::Plugin(format!(
+                "Plugin file declares id {}, expected {}",
+                declared, plugin_id
+            )));
+        }
+        let signature = sandbox.validate_metadata(instance.metadata(), signature)?;
+        Ok(PreparedBuild { instance, signature, cache_hit })
+    }
+    
+    /// Make a prepared instance the one serving `plugin_id`
+    fn install(
+        &mut self,
+        plugin_id: &str,
+        file_path: PathBuf,
+        PreparedBuild { instance, signature, cache_hit }: PreparedBuild,
+        checksum: String,
+        size: u64,
+        limits: ResourceLimits,
+    ) {
+        let metadata = instance.metadata().clone();
+        self.instances.insert(plugin_id.to_string(), Arc::new(instance));
+        self.loaded_plugins.insert(plugin_id.to_string(), LoadedPlugin {
+            metadata: metadata.clone(),
+            file_path: file_path.clone(),
+            checksum: checksum.clone(),
+            loaded_at: SystemTime::now(),
+            size,
+            limits,
+            cache_hit,
+        });
+        self.plugin_registry.add_plugin(PluginEntry {
+            id: plugin_id.to_string(),
+            name: metadata.name.clone(),
+            version: metadata.version.clone(),
+            file_path,
+            checksum,
+            metadata,
+            status: PluginStatus::Loaded,
+            last_updated: SystemTime::now(),
+            signature,
+        });
+    }
+    
+    /// Unload a plugin. Refused while other loaded plugins depend on it,
+    /// unless `force` is set
     #[instrument(skip(self))]
-    pub async fn unload_plugin(&mut self, plugin_id: &str) -> Result<()> {
+    pub async fn unload_plugin(&mut self, plugin_id: &str, force: bool) -> Result<()> {
         info!("Unloading plugin: {}", plugin_id);
         
+        let dependents = dependencies::dependents(plugin_id, self.loaded_plugins.values().map(|p| &p.metadata));
+        if !dependents.is_empty() && self.loaded_plugins.contains_key(plugin_id) {
+            if !force {
+                return Err(AssistantError::Plugin(format!(
+                    "Plugin {} is needed by {}",
+                    plugin_id,
+                    dependents.join(", ")
+                )));
+            }
+            warn!("Unloading plugin {} although {} depend on it", plugin_id, dependents.join(", "));
+        }
+        
         if let Some(_loaded_plugin) = self.loaded_plugins.remove(plugin_id) {
+            // Calls holding the instance finish on it
+            self.instances.remove(plugin_id);
             self.plugin_registry.update_status(plugin_id, PluginStatus::Available);
             info!("Plugin unloaded successfully: {}", plugin_id);
+            self.emit(PluginLifecycleEvent::Unloaded { plugin_id: plugin_id.to_string() });
         } else {
             warn!("Plugin not loaded: {}", plugin_id);
         }
@@ -329,30 +554,246 @@ impl PluginLoader {
         self.loaded_plugins.values().collect()
     }
     
+    /// The instance serving a loaded plugin
+    pub fn instance(&self, plugin_id: &str) -> Option<Arc<WasmPluginInstance>> {
+        self.instances.get(plugin_id).cloned()
+    }
+    
     /// Get plugin registry
     pub fn get_registry(&self) -> &PluginRegistry {
         &self.plugin_registry
     }
     
-    /// Reload a plugin (unload and load again)
+    /// Load the plugin's file again. The current instance keeps serving
+    /// until the new build is validated and instantiated; if it fails, the
+    /// current instance stays and the registry entry is marked `Error`
     pub async fn reload_plugin(&mut self, plugin_id: &str) -> Result<()> {
         info!("Reloading plugin: {}", plugin_id);
         
-        let limits = self.loaded_plugins.get(plugin_id)
-            .map(|p| p.limits.clone());
+        let file_path = self.plugin_registry.get_plugin(plugin_id)
+            .map(|entry| entry.file_path.clone())
+            .ok_or_else(|| AssistantError::NotFound(format!("Plugin not found: {}", plugin_id)))?;
+        let wasm_bytes = fs::read(&file_path).await
+            .map_err(|e| AssistantError::Plugin(format!("Failed to read plugin file: {}", e)))?;
         
-        self.unload_plugin(plugin_id).await?;
-        self.load_plugin(plugin_id, limits).await?;
+        self.replace(plugin_id, file_path, &wasm_bytes).await
+    }
+    
+    async fn replace(&mut self, plugin_id: &str, file_path: PathBuf, wasm_bytes: &[u8]) -> Result<()> {
+        let checksum = self.calculate_checksum(wasm_bytes);
+        let previous = self.loaded_plugins.get(plugin_id)
+            .map(|p| (p.metadata.version.clone(), p.limits.clone(), p.checksum.clone()));
+        let limits = previous.as_ref().map(|(_, limits, _)| limits.clone()).unwrap_or_default();
         
+        let preparing = Instant::now();
+        let prepared = self.prepare(plugin_id, &file_path, wasm_bytes, &checksum, &limits).await;
+        metrics::global().record_load_time(plugin_id, preparing.elapsed());
+        let prepared = match prepared {
+            Ok(prepared) => prepared,
+            Err(e) => {
+                warn!("New build of plugin {} rejected, keeping the loaded one: {}", plugin_id, e);
+                // Remembering the checksum keeps a rescan from retrying the
+                // same broken build
+                if let Some(entry) = self.plugin_registry.plugins.get_mut(plugin_id) {
+                    entry.checksum = checksum;
+                    entry.status = PluginStatus::Error(e.to_string());
+                }
+                self.emit(PluginLifecycleEvent::Failed {
+                    plugin_id: Some(plugin_id.to_string()),
+                    path: file_path,
+                    error: e.to_string(),
+                });
+                return Err(e);
+            }
+        };
+        
+        let version = prepared.instance.metadata().version.clone();
+        self.install(plugin_id, file_path, prepared, checksum.clone(), wasm_bytes.len() as u64, limits);
+        info!("Plugin reloaded: {} {}", plugin_id, version);
+        // The replaced build's compiled module will not be asked for again
+        if let (Some(module_cache), Some((_, _, previous_checksum))) = (&self.module_cache, &previous) {
+            if *previous_checksum != checksum {
+                module_cache.remove(previous_checksum);
+            }
+        }
+        self.emit(match previous {
+            Some((previous_version, _, _)) => PluginLifecycleEvent::Reloaded {
+                plugin_id: plugin_id.to_string(),
+                version,
+                previous_version,
+            },
+            None => PluginLifecycleEvent::Loaded { plugin_id: plugin_id.to_string(), version },
+        });
         Ok(())
     }
     
+    /// Bring the loaded plugins in line with the plugin directory: load new
+    /// files, reload files whose checksum changed and unload plugins whose
+    /// file is gone. Problems with one file do not stop the others; they are
+    /// reported as `Failed` events, which are also returned
+    pub async fn rescan(&mut self, config: &DiscoveryConfig) -> Vec<PluginLifecycleEvent> {
+        let mut events = self.subscribe();
+        
+        let files = match self.plugin_files(config).await {
+            Ok(files) => files,
+            Err(e) => {
+                warn!("Failed to scan plugin directory {:?}: {}", self.plugin_directory, e);
+                return Vec::new();
+            }
+        };
+        
+        let removed: Vec<String> = self.plugin_registry.plugins.values()
+            .filter(|entry| !files.contains(&entry.file_path))
+            .map(|entry| entry.id.clone())
+            .collect();
+        for plugin_id in removed {
+            // Unloading announces itself; an entry that never loaded just
+            // goes. The file is gone, so dependents lose it either way
+            let _ = self.unload_plugin(&plugin_id, true).await;
+            self.plugin_registry.remove_plugin(&plugin_id);
+        }
+        
+        for path in files {
+            let wasm_bytes = match fs::read(&path).await {
+                Ok(bytes) => bytes,
+                // Removed or still being written; the next change event comes back to it
+                Err(e) => {
+                    debug!("Plugin file {:?} not readable: {}", path, e);
+                    continue;
+                }
+            };
+            if wasm_bytes.len() as u64 > config.max_file_size {
+                warn!("Plugin file too large: {:?} ({} bytes)", path, wasm_bytes.len());
+                continue;
+            }
+            
+            let checksum = self.calculate_checksum(&wasm_bytes);
+            let known = self.plugin_registry.plugins.values()
+                .find(|entry| entry.file_path == path)
+                .map(|entry| (entry.id.clone(), entry.checksum.clone()));
+            match known {
+                Some((_, known_checksum)) if known_checksum == checksum => {}
+                Some((plugin_id, _)) => {
+                    let _ = self.replace(&plugin_id, path, &wasm_bytes).await;
+                }
+                None => self.add_file(path, &wasm_bytes, config).await,
+            }
+        }
+        
+        let mut emitted = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            emitted.push(event);
+        }
+        emitted
+    }
+    
+    /// Register and load a plugin file seen for the first time
+    async fn add_file(&mut self, path: PathBuf, wasm_bytes: &[u8], config: &DiscoveryConfig) {
+        let failed = |error: String| PluginLifecycleEvent::Failed { plugin_id: None, path: path.clone(), error };
+        let entry = match self.analyze_plugin_file(&path, config).await {
+            Ok(Some(entry)) => entry,
+            Ok(None) => return,
+            Err(e) => return self.emit(failed(e.to_string())),
+        };
+        if entry.checksum != self.calculate_checksum(wasm_bytes) {
+            // Changed while being read; the next change event comes back to it
+            return;
+        }
+        if let Some(other) = self.plugin_registry.get_plugin(&entry.id) {
+            let error = format!("Plugin {} is already provided by {:?}", entry.id, other.file_path);
+            return self.emit(failed(error));
+        }
+        
+        let plugin_id = entry.id.clone();
+        self.plugin_registry.add_plugin(entry);
+        if let Err(e) = self.load_plugin(&plugin_id, None).await {
+            warn!("Failed to load new plugin {}: {}", plugin_id, e);
+        }
+    }
+    
+    /// Plugin files under the plugin directory, following `config`
+    async fn plugin_files(&self, config: &DiscoveryConfig) -> Result<Vec<PathBuf>> {
+        let mut files = Vec::new();
+        if !self.plugin_directory.exists() {
+            return Ok(files);
+        }
+        
+        let mut directories = vec![self.plugin_directory.clone()];
+        while let Some(dir) = directories.pop() {
+            let mut entries = fs::read_dir(&dir).await
+                .map_err(|e| AssistantError::Plugin(format!("Failed to read directory: {}", e)))?;
+            while let Some(entry) = entries.next_entry().await
+                .map_err(|e| AssistantError::Plugin(format!("Failed to read directory entry: {}", e)))? {
+                let path = entry.path();
+                if path.is_dir() {
+                    if config.recursive {
+                        directories.push(path);
+                    }
+                } else if self.is_plugin_file(&path, config) {
+                    files.push(path);
+                }
+            }
+        }
+        files.sort();
+        Ok(files)
+    }
+    
+    /// Watch the plugin directory and rescan it whenever plugin files
+    /// change, if `config.auto_reload` is set; `None` otherwise. Watching
+    /// stops when the returned watcher is dropped
+    pub async fn watch(loader: Arc<Mutex<PluginLoader>>, config: DiscoveryConfig) -> Result<Option<PluginWatcher>> {
+        if !config.auto_reload {
+            return Ok(None);
+        }
+        
+        let directory = loader.lock().await.plugin_directory.clone();
+        let (changes_tx, mut changes) = mpsc::unbounded_channel();
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| match event {
+            Ok(event) => {
+                let _ = changes_tx.send(event.paths);
+            }
+            Err(e) => warn!("Plugin directory watch error: {}", e),
+        })
+        .map_err(|e| AssistantError::Plugin(format!("Failed to watch plugin directory: {}", e)))?;
+        let mode = if config.recursive { RecursiveMode::Recursive } else { RecursiveMode::NonRecursive };
+        watcher.watch(&directory, mode)
+            .map_err(|e| AssistantError::Plugin(format!("Failed to watch {:?}: {}", directory, e)))?;
+        info!("Watching {:?} for plugin changes", directory);
+        
+        let task = tokio::spawn(async move {
+            while let Some(paths) = changes.recv().await {
+                let mut relevant = paths.iter().any(|path| is_plugin_path(path, &config));
+                loop {
+                    match tokio::time::timeout(WATCH_DEBOUNCE, changes.recv()).await {
+                        Ok(Some(paths)) => relevant |= paths.iter().any(|path| is_plugin_path(path, &config)),
+                        Ok(None) => return,
+                        Err(_) => break,
+                    }
+                }
+                if relevant {
+                    loader.lock().await.rescan(&config).await;
+                }
+            }
+        });
+        
+        Ok(Some(PluginWatcher { _watcher: watcher, task }))
+    }
+    
     /// Get runtime reference
     pub fn runtime(&self) -> &WasmRuntime {
         &self.runtime
     }
 }
 
+fn is_plugin_path(path: &Path, config: &DiscoveryConfig) -> bool {
+    if let Some(extension) = path.extension() {
+        if let Some(ext_str) = extension.to_str() {
+            return config.extensions.iter().any(|e| e == ext_str);
+        }
+    }
+    false
+}
+
 impl PluginRegistry {
     /// Create a new plugin registry
     pub fn new() -> Self {
@@ -431,6 +872,248 @@ mod tests {
         // Status update on non-existent plugin should not panic
     }
     
+    #[tokio::test]
+    async fn test_discovered_plugins_are_keyed_by_declared_id() {
+        let temp_dir = tempdir().unwrap();
+        let fixture = include_str!("../fixtures/echo.wat");
+        let weather: WasmPluginMetadata = serde_json::from_value(serde_json::json!({
+            "id": "weather",
+            "name": "Weather",
+            "version": "2.0.0",
+        }))
+        .unwrap();
+        // The section wins over the fixture's own `get_metadata` export
+        std::fs::write(temp_dir.path().join("weather.wasm"), metadata::embed(fixture.as_bytes(), &weather).unwrap()).unwrap();
+        std::fs::write(temp_dir.path().join("echo.wat"), fixture).unwrap();
+        
+        let mut loader = PluginLoader::new(temp_dir.path(), RuntimeConfig::default()).unwrap();
+        let mut entries = loader.discover_plugins(DiscoveryConfig::default()).await.unwrap();
+        entries.sort_by(|a, b| a.id.cmp(&b.id));
+        let ids: Vec<_> = entries.iter().map(|e| (e.id.as_str(), e.version.as_str())).collect();
+        assert_eq!(ids, vec![("echo", "0.1.0"), ("weather", "2.0.0")]);
+        assert!(entries.iter().all(|e| e.metadata.checksum == e.checksum));
+        assert!(loader.get_registry().get_plugin("weather").is_some());
+        
+        // No section and no export: refused instead of given a made-up id
+        std::fs::write(temp_dir.path().join("anonymous.wat"), r#"(module (memory (export "memory") 1))"#).unwrap();
+        match loader.discover_plugins(DiscoveryConfig::default()).await {
+            Err(AssistantError::Plugin(message)) => assert!(message.contains("rusty-ai-plugin-metadata"), "{}", message),
+            other => panic!("expected a plugin error, got {:?}", other),
+        }
+    }
+    
+    async fn served_version(loader: &PluginLoader) -> Vec<u8> {
+        loader.instance("echo").unwrap().call("version", b"").await.unwrap().output
+    }
+    
+    #[tokio::test]
+    async fn test_rescan_swaps_in_builds_only_once_they_validate() {
+        let temp_dir = tempdir().unwrap();
+        let fixture = include_str!("../fixtures/echo.wat");
+        let path = temp_dir.path().join("echo.wat");
+        std::fs::write(&path, fixture).unwrap();
+        
+        let config = DiscoveryConfig::default();
+        let mut loader = PluginLoader::new(temp_dir.path(), RuntimeConfig::default()).unwrap();
+        let mut events = loader.subscribe();
+        let loaded = PluginLifecycleEvent::Loaded { plugin_id: "echo".to_string(), version: "0.1.0".to_string() };
+        assert_eq!(loader.rescan(&config).await, vec![loaded.clone()]);
+        assert_eq!(events.try_recv().unwrap(), loaded);
+        assert!(loader.rescan(&config).await.is_empty());
+        
+        std::fs::write(&path, fixture.replace(r#"{\"version\":1}"#, r#"{\"version\":2}"#)).unwrap();
+        assert_eq!(
+            loader.rescan(&config).await,
+            vec![PluginLifecycleEvent::Reloaded {
+                plugin_id: "echo".to_string(),
+                version: "0.1.0".to_string(),
+                previous_version: "0.1.0".to_string(),
+            }]
+        );
+        assert_eq!(served_version(&loader).await, br#"{"version":2}"#);
+        
+        // A build the security policy rejects leaves the running one in place
+        let rejected: WasmPluginMetadata = serde_json::from_value(serde_json::json!({
+            "id": "echo",
+            "name": "Echo",
+            "version": "0.2.0",
+            "capabilities": ["system_admin"],
+        }))
+        .unwrap();
+        std::fs::write(&path, metadata::embed(fixture.as_bytes(), &rejected).unwrap()).unwrap();
+        match loader.rescan(&config).await.as_slice() {
+            [PluginLifecycleEvent::Failed { plugin_id: Some(plugin_id), error, .. }] => {
+                assert_eq!(plugin_id, "echo");
+                assert!(error.contains("Prohibited capability: system_admin"), "{}", error);
+            }
+            other => panic!("expected one failure, got {:?}", other),
+        }
+        assert!(matches!(loader.get_registry().get_plugin("echo").unwrap().status, PluginStatus::Error(_)));
+        assert_eq!(served_version(&loader).await, br#"{"version":2}"#);
+        // Not retried until the file changes again
+        assert!(loader.rescan(&config).await.is_empty());
+        
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loader.rescan(&config).await, vec![PluginLifecycleEvent::Unloaded { plugin_id: "echo".to_string() }]);
+        assert!(loader.instance("echo").is_none());
+        assert!(loader.get_registry().list_plugins().is_empty());
+    }
+    
+    #[tokio::test]
+    async fn test_required_signatures_are_checked_and_recorded() {
+        let temp_dir = tempdir().unwrap();
+        let fixture = include_str!("../fixtures/echo.wat");
+        let path = temp_dir.path().join("echo.wat");
+        std::fs::write(&path, fixture).unwrap();
+        
+        let rng = ring::rand::SystemRandom::new();
+        let pkcs8 = ring::signature::Ed25519KeyPair::generate_pkcs8(&rng).unwrap();
+        let policy = SecurityPolicy {
+            require_signature: true,
+            trusted_authors: ["RUSTY-AI".to_string()].into_iter().collect(),
+            trusted_keys: [(
+                "release".to_string(),
+                crate::TrustedKey { author: "RUSTY-AI".to_string(), public_key: crate::signing_public_key(pkcs8.as_ref()).unwrap() },
+            )]
+            .into_iter()
+            .collect(),
+            ..SecurityPolicy::default()
+        };
+        let config = DiscoveryConfig { verify_signatures: true, ..DiscoveryConfig::default() };
+        let mut loader = PluginLoader::new(temp_dir.path(), RuntimeConfig::default()).unwrap().with_security_policy(policy);
+        
+        match loader.rescan(&config).await.as_slice() {
+            [PluginLifecycleEvent::Failed { error, .. }] => assert!(error.contains("plugin is not signed"), "{}", error),
+            other => panic!("expected the unsigned plugin to fail, got {:?}", other),
+        }
+        assert_eq!(loader.get_registry().get_plugin("echo").unwrap().signature, SignatureStatus::Unsigned);
+        
+        crate::sign_plugin(fixture.as_bytes(), "release", pkcs8.as_ref()).unwrap().write_for(&path).await.unwrap();
+        loader.reload_plugin("echo").await.unwrap();
+        let entry = loader.get_registry().get_plugin("echo").unwrap();
+        assert_eq!(entry.status, PluginStatus::Loaded);
+        assert_eq!(
+            entry.signature,
+            SignatureStatus::Verified { key_id: "release".to_string(), author: "RUSTY-AI".to_string() }
+        );
+        
+        // A build changed after signing no longer verifies; the signed one
+        // keeps serving, and the new build's start function, which traps,
+        // never runs
+        let tampered = fixture.replace(r#"{\"version\":1}"#, r#"{\"version\":2}"#);
+        let end = tampered.rfind(')').unwrap();
+        std::fs::write(&path, format!("{}\n  (func $boot unreachable)\n  (start $boot))\n", &tampered[..end])).unwrap();
+        match loader.rescan(&config).await.as_slice() {
+            [PluginLifecycleEvent::Failed { error, .. }] => {
+                assert!(error.contains("signature does not match the module"), "{}", error)
+            }
+            other => panic!("expected the tampered build to fail, got {:?}", other),
+        }
+        assert_eq!(served_version(&loader).await, br#"{"version":1}"#);
+    }
+    
+    #[tokio::test]
+    async fn test_reloading_the_same_bytes_reads_the_module_cache() {
+        let temp_dir = tempdir().unwrap();
+        std::fs::write(temp_dir.path().join("echo.wat"), include_str!("../fixtures/echo.wat")).unwrap();
+        
+        let mut loader = PluginLoader::new(temp_dir.path(), RuntimeConfig::default())
+            .unwrap()
+            .with_module_cache_key(b"host key");
+        loader.discover_plugins(DiscoveryConfig::default()).await.unwrap();
+        loader.load_plugin("echo", None).await.unwrap();
+        assert!(!loader.get_loaded_plugins()[0].cache_hit);
+        assert_eq!(loader.module_cache().unwrap().compilations(), 1);
+        
+        // A restarted loader holding the same key finds the compiled module on disk
+        let mut restarted = PluginLoader::new(temp_dir.path(), RuntimeConfig::default())
+            .unwrap()
+            .with_module_cache_key(b"host key");
+        restarted.discover_plugins(DiscoveryConfig::default()).await.unwrap();
+        restarted.load_plugin("echo", None).await.unwrap();
+        assert!(restarted.get_loaded_plugins()[0].cache_hit);
+        assert_eq!(restarted.module_cache().unwrap().compilations(), 0);
+        assert_eq!(served_version(&restarted).await, br#"{"version":1}"#);
+        
+        // Without the cache every load compiles
+        let uncached = RuntimeConfig { compilation_cache: false, ..RuntimeConfig::default() };
+        let mut loader = PluginLoader::new(temp_dir.path(), uncached).unwrap();
+        loader.discover_plugins(DiscoveryConfig::default()).await.unwrap();
+        loader.load_plugin("echo", None).await.unwrap();
+        assert!(loader.module_cache().is_none());
+        assert!(!loader.get_loaded_plugins()[0].cache_hit);
+    }
+    
+    fn write_plugin(dir: &Path, id: &str, version: &str, dependencies: &[&str]) {
+        let metadata: WasmPluginMetadata = serde_json::from_value(serde_json::json!({
+            "id": id,
+            "name": id,
+            "version": version,
+            "dependencies": dependencies,
+        }))
+        .unwrap();
+        let bytes = metadata::embed(include_str!("../fixtures/echo.wat").as_bytes(), &metadata).unwrap();
+        std::fs::write(dir.join(format!("{}.wasm", id)), bytes).unwrap();
+    }
+    
+    #[tokio::test]
+    async fn test_dependencies_load_first_and_stay_while_needed() {
+        let temp_dir = tempdir().unwrap();
+        write_plugin(temp_dir.path(), "base", "1.4.0", &[]);
+        write_plugin(temp_dir.path(), "left", "1.0.0", &["base@^1.2"]);
+        write_plugin(temp_dir.path(), "right", "1.0.0", &["base"]);
+        write_plugin(temp_dir.path(), "app", "1.0.0", &["left", "right"]);
+        write_plugin(temp_dir.path(), "legacy", "1.0.0", &["base@^2", "clock"]);
+        
+        let mut loader = PluginLoader::new(temp_dir.path(), RuntimeConfig::default()).unwrap();
+        let mut events = loader.subscribe();
+        loader.discover_plugins(DiscoveryConfig::default()).await.unwrap();
+        
+        // The shared dependency of the diamond loads once, before both sides
+        loader.load_plugin("app", None).await.unwrap();
+        let mut loaded = Vec::new();
+        while let Ok(PluginLifecycleEvent::Loaded { plugin_id, .. }) = events.try_recv() {
+            loaded.push(plugin_id);
+        }
+        assert_eq!(loaded, vec!["base", "left", "right", "app"]);
+        
+        // Every unmet requirement is named and nothing is loaded
+        match loader.load_plugin("legacy", None).await {
+            Err(AssistantError::Plugin(message)) => {
+                assert!(message.contains("legacy needs base@^2, but base 1.4.0 is loaded"), "{}", message);
+                assert!(message.contains("legacy needs clock, which is not available"), "{}", message);
+            }
+            other => panic!("expected unmet dependencies, got {:?}", other),
+        }
+        assert!(matches!(loader.get_registry().get_plugin("legacy").unwrap().status, PluginStatus::Error(_)));
+        assert_eq!(loader.get_loaded_plugins().len(), 4);
+        
+        match loader.unload_plugin("base", false).await {
+            Err(AssistantError::Plugin(message)) => assert!(message.contains("needed by left, right"), "{}", message),
+            other => panic!("expected the unload to be refused, got {:?}", other),
+        }
+        assert!(loader.instance("base").is_some());
+        loader.unload_plugin("app", false).await.unwrap();
+        loader.unload_plugin("base", true).await.unwrap();
+        assert!(loader.instance("base").is_none());
+    }
+    
+    #[tokio::test]
+    async fn test_watcher_loads_plugins_written_to_the_directory() {
+        let temp_dir = tempdir().unwrap();
+        let loader = Arc::new(Mutex::new(PluginLoader::new(temp_dir.path(), RuntimeConfig::default()).unwrap()));
+        let mut events = loader.lock().await.subscribe();
+        assert!(PluginLoader::watch(loader.clone(), DiscoveryConfig::default()).await.unwrap().is_none());
+        
+        let config = DiscoveryConfig { auto_reload: true, ..DiscoveryConfig::default() };
+        let _watcher = PluginLoader::watch(loader.clone(), config).await.unwrap().unwrap();
+        std::fs::write(temp_dir.path().join("echo.wat"), include_str!("../fixtures/echo.wat")).unwrap();
+        
+        let event = tokio::time::timeout(Duration::from_secs(5), events.recv()).await.unwrap().unwrap();
+        assert_eq!(event, PluginLifecycleEvent::Loaded { plugin_id: "echo".to_string(), version: "0.1.0".to_string() });
+        assert!(loader.lock().await.instance("echo").is_some());
+    }
+    
     #[test]
     fn test_checksum_calculation() {
         let temp_dir = tempdir().unwrap();
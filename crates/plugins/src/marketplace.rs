@@ -0,0 +1,596 @@
+use crate::{security::{artifact_sha256, verify_artifact}, PluginSignature, WasmPluginManager};
+use rusty_ai_common::{AssistantError, Result};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{Mutex, RwLock};
+use tracing::{info, warn};
+
+/// Largest plugin artifact accepted from a remote index or an upload
+pub const MAX_ARTIFACT_BYTES: usize = 50 * 1024 * 1024;
+
+/// File in the plugin directory recording what was installed from an index
+const REGISTRY_FILE: &str = "installed.json";
+
+/// `InstalledPlugin.source` of plugins uploaded rather than installed from
+/// an index
+pub const UPLOAD_SOURCE: &str = "upload";
+
+/// A plugin release as published in a remote index
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexEntry {
+    pub name: String,
+    pub version: String,
+    pub download_url: String,
+    pub sha256: String,
+    /// Hex-encoded detached Ed25519 signature over the artifact bytes
+    pub signature: String,
+    #[serde(default)]
+    pub metadata: IndexMetadata,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct IndexMetadata {
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub author: String,
+    #[serde(default)]
+    pub license: String,
+    #[serde(default)]
+    pub capabilities: Vec<String>,
+}
+
+/// The JSON document served at an index URL
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PluginIndex {
+    pub plugins: Vec<IndexEntry>,
+}
+
+/// A plugin installed from an index, as recorded in the local registry
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstalledPlugin {
+    pub name: String,
+    pub version: String,
+    pub sha256: String,
+    pub source: String,
+    pub installed_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Local and remote view of one plugin
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AvailablePlugin {
+    pub name: String,
+    pub installed_version: Option<String>,
+    pub latest_version: Option<String>,
+    pub update_available: bool,
+    /// Every release found across the configured indexes, newest first
+    pub releases: Vec<IndexEntry>,
+}
+
+/// Artifacts are verified against the manager's `SecurityPolicy::trusted_keys`,
+/// the keys plugins are loaded against
+#[derive(Debug, Clone, Default)]
+pub struct MarketplaceConfig {
+    pub index_urls: Vec<String>,
+    pub plugin_directory: PathBuf,
+}
+
+/// Installs plugins published in remote indexes into the plugin directory
+pub struct PluginMarketplace {
+    config: MarketplaceConfig,
+    http: reqwest::Client,
+    manager: Arc<WasmPluginManager>,
+    installed: RwLock<BTreeMap<String, InstalledPlugin>>,
+    // Serializes installs so two upgrades of one plugin cannot interleave
+    install_lock: Mutex<()>,
+}
+
+impl PluginMarketplace {
+    pub fn new(config: MarketplaceConfig, manager: Arc<WasmPluginManager>) -> Result<Self> {
+        let http = reqwest::Client::builder()
+            .timeout(Duration::from_secs(60))
+            .build()
+            .map_err(|e| AssistantError::Plugin(format!("Failed to create HTTP client: {}", e)))?;
+        let installed = load_registry(&config.plugin_directory.join(REGISTRY_FILE));
+
+        Ok(Self {
+            config,
+            http,
+            manager,
+            installed: RwLock::new(installed),
+            install_lock: Mutex::new(()),
+        })
+    }
+
+    pub fn manager(&self) -> &Arc<WasmPluginManager> {
+        &self.manager
+    }
+
+    /// Fetch every configured index; unreachable indexes are skipped
+    pub async fn fetch_indexes(&self) -> Vec<(String, PluginIndex)> {
+        let mut indexes = Vec::new();
+        for url in &self.config.index_urls {
+            match self.fetch_index(url).await {
+                Ok(index) => indexes.push((url.clone(), index)),
+                Err(e) => warn!("Skipping plugin index {}: {}", url, e),
+            }
+        }
+        indexes
+    }
+
+    async fn fetch_index(&self, url: &str) -> Result<PluginIndex> {
+        self.http
+            .get(url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| AssistantError::Plugin(format!("Failed to fetch plugin index: {}", e)))?
+            .json()
+            .await
+            .map_err(|e| AssistantError::Plugin(format!("Invalid plugin index: {}", e)))
+    }
+
+    /// Merge the local registry with the releases of every reachable index
+    pub async fn available(&self) -> Vec<AvailablePlugin> {
+        let mut releases: BTreeMap<String, Vec<IndexEntry>> = BTreeMap::new();
+        for (_, index) in self.fetch_indexes().await {
+            for entry in index.plugins {
+                releases.entry(entry.name.clone()).or_default().push(entry);
+            }
+        }
+
+        let installed = self.installed.read().await;
+        for name in installed.keys() {
+            releases.entry(name.clone()).or_default();
+        }
+
+        releases
+            .into_iter()
+            .map(|(name, mut entries)| {
+                entries.sort_by(|a, b| compare_versions(&b.version, &a.version));
+                let installed_version = installed.get(&name).map(|p| p.version.clone());
+                let latest_version = entries.first().map(|e| e.version.clone());
+                let update_available = match (&installed_version, &latest_version) {
+                    (Some(current), Some(latest)) => compare_versions(latest, current) == Ordering::Greater,
+                    _ => false,
+                };
+
+                AvailablePlugin {
+                    name,
+                    installed_version,
+                    latest_version,
+                    update_available,
+                    releases: entries,
+                }
+            })
+            .collect()
+    }
+
+    pub async fn installed(&self) -> Vec<InstalledPlugin> {
+        self.installed.read().await.values().cloned().collect()
+    }
+
+    /// Download, verify and load a release; `version` defaults to the newest.
+    /// An existing installation is only replaced once the new artifact loads.
+    pub async fn install(&self, name: &str, version: Option<&str>) -> Result<InstalledPlugin> {
+        validate_plugin_name(name)?;
+        let _guard = self.install_lock.lock().await;
+
+        let (source, entry) = self
+            .fetch_indexes()
+            .await
+            .into_iter()
+            .flat_map(|(url, index)| index.plugins.into_iter().map(move |entry| (url.clone(), entry)))
+            .filter(|(_, entry)| entry.name == name && version.map_or(true, |v| entry.version == v))
+            .max_by(|(_, a), (_, b)| compare_versions(&a.version, &b.version))
+            .ok_or_else(|| {
+                AssistantError::NotFound(match version {
+                    Some(v) => format!("Plugin {} {} not found in any index", name, v),
+                    None => format!("Plugin {} not found in any index", name),
+                })
+            })?;
+
+        info!("Installing plugin {} {} from {}", name, entry.version, source);
+        let bytes = self.download(&entry.download_url).await?;
+        let trusted_keys = self.manager.security_policy().trusted_keys;
+        let signature = verify_artifact(&bytes, &entry.sha256, &entry.signature, &trusted_keys)?;
+        self.load_into_place(name, &entry.version, &bytes, Some(&signature)).await?;
+
+        let installed = InstalledPlugin {
+            name: name.to_string(),
+            version: entry.version.clone(),
+            sha256: entry.sha256.clone(),
+            source,
+            installed_at: chrono::Utc::now(),
+        };
+        self.record(installed).await
+    }
+
+    /// Validate and load an uploaded artifact with its detached signature,
+    /// if it has one, then keep both in the plugin directory as if they were
+    /// installed from an index. Its version is the one the plugin declares;
+    /// an existing installation is only replaced once the upload loads.
+    pub async fn install_upload(
+        &self,
+        name: &str,
+        bytes: &[u8],
+        signature: Option<&PluginSignature>,
+    ) -> Result<InstalledPlugin> {
+        validate_plugin_name(name)?;
+        if bytes.len() > MAX_ARTIFACT_BYTES {
+            return Err(AssistantError::Plugin(format!("Plugin artifact exceeds {} bytes", MAX_ARTIFACT_BYTES)));
+        }
+        let _guard = self.install_lock.lock().await;
+
+        info!("Installing uploaded plugin {} ({} bytes)", name, bytes.len());
+        self.load_into_place(name, UPLOAD_SOURCE, bytes, signature).await?;
+        let version = self.manager.plugin_versions(name).await.map(|versions| versions.active).unwrap_or_default();
+
+        let installed = InstalledPlugin {
+            name: name.to_string(),
+            version,
+            sha256: artifact_sha256(bytes),
+            source: UPLOAD_SOURCE.to_string(),
+            installed_at: chrono::Utc::now(),
+        };
+        self.record(installed).await
+    }
+
+    // Stages the artifact next to the current file, which stays in place
+    // until the new one has passed validation and loaded. The signature it
+    // loaded with is kept next to it, so it verifies again on the next load
+    async fn load_into_place(&self, name: &str, label: &str, bytes: &[u8], signature: Option<&PluginSignature>) -> Result<()> {
+        tokio::fs::create_dir_all(&self.config.plugin_directory)
+            .await
+            .map_err(|e| AssistantError::Plugin(format!("Failed to create plugin directory: {}", e)))?;
+        let target = self.config.plugin_directory.join(format!("{}.wasm", name));
+        let staged = self.config.plugin_directory.join(format!("{}-{}.wasm.download", name, label));
+        tokio::fs::write(&staged, bytes)
+            .await
+            .map_err(|e| AssistantError::Plugin(format!("Failed to write plugin artifact: {}", e)))?;
+
+        if let Err(e) = self.manager.load_signed_plugin(name, bytes, signature).await {
+            let _ = tokio::fs::remove_file(&staged).await;
+            warn!("Plugin {} {} failed to load, keeping the installed version: {}", name, label, e);
+            return Err(e);
+        }
+
+        tokio::fs::rename(&staged, &target)
+            .await
+            .map_err(|e| AssistantError::Plugin(format!("Failed to move plugin into place: {}", e)))?;
+        match signature {
+            Some(signature) => signature.write_for(&target).await,
+            None => remove_signature(&target).await,
+        }
+    }
+
+    async fn record(&self, installed: InstalledPlugin) -> Result<InstalledPlugin> {
+        self.installed.write().await.insert(installed.name.clone(), installed.clone());
+        self.persist().await?;
+
+        info!("Installed plugin {} {}", installed.name, installed.version);
+        Ok(installed)
+    }
+
+    /// Unload an installed plugin, delete its artifact and drop it from the
+    /// registry. Returns the record that was removed.
+    pub async fn uninstall(&self, name: &str) -> Result<InstalledPlugin> {
+        validate_plugin_name(name)?;
+        let _guard = self.install_lock.lock().await;
+
+        let installed = self
+            .installed
+            .read()
+            .await
+            .get(name)
+            .cloned()
+            .ok_or_else(|| AssistantError::NotFound(format!("Plugin {} is not installed", name)))?;
+
+        if self.manager.list_plugins().await.iter().any(|id| id == name) {
+            self.manager.unload_plugin(name).await?;
+        }
+        let target = self.config.plugin_directory.join(format!("{}.wasm", name));
+        match tokio::fs::remove_file(&target).await {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(AssistantError::Plugin(format!("Failed to remove plugin artifact: {}", e))),
+        }
+        remove_signature(&target).await?;
+
+        self.installed.write().await.remove(name);
+        self.persist().await?;
+
+        info!("Uninstalled plugin {} {}", name, installed.version);
+        Ok(installed)
+    }
+
+    async fn download(&self, url: &str) -> Result<Vec<u8>> {
+        let mut response = self
+            .http
+            .get(url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| AssistantError::Plugin(format!("Failed to download plugin: {}", e)))?;
+
+        let mut bytes = Vec::new();
+        while let Some(chunk) = response
+            .chunk()
+            .await
+            .map_err(|e| AssistantError::Plugin(format!("Failed to download plugin: {}", e)))?
+        {
+            if bytes.len() + chunk.len() > MAX_ARTIFACT_BYTES {
+                return Err(AssistantError::Plugin(format!(
+                    "Plugin artifact exceeds {} bytes",
+                    MAX_ARTIFACT_BYTES
+                )));
+            }
+            bytes.extend_from_slice(&chunk);
+        }
+        Ok(bytes)
+    }
+
+    async fn persist(&self) -> Result<()> {
+        let installed: Vec<InstalledPlugin> = self.installed.read().await.values().cloned().collect();
+        let json = serde_json::to_vec_pretty(&installed)
+            .map_err(|e| AssistantError::Internal(format!("Failed to serialize plugin registry: {}", e)))?;
+        tokio::fs::write(self.config.plugin_directory.join(REGISTRY_FILE), json)
+            .await
+            .map_err(|e| AssistantError::Internal(format!("Failed to persist plugin registry: {}", e)))
+    }
+}
+
+// A signature left from an earlier install would not match the new module
+async fn remove_signature(module_path: &Path) -> Result<()> {
+    match tokio::fs::remove_file(PluginSignature::path_for(module_path)).await {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(AssistantError::Plugin(format!("Failed to remove plugin signature: {}", e))),
+    }
+}
+
+// Names become file names, so keep them to a safe alphabet
+fn validate_plugin_name(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && name.len() <= 64
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(AssistantError::Plugin(format!("Invalid plugin name: {}", name)))
+    }
+}
+
+/// Compare dotted numeric versions ("1.10.0" > "1.9.2"); non-numeric parts
+/// compare as text
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    let mut left = a.split('.');
+    let mut right = b.split('.');
+    loop {
+        match (left.next(), right.next()) {
+            (None, None) => return Ordering::Equal,
+            (Some(_), None) => return Ordering::Greater,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(x), Some(y)) => {
+                let ordering = match (x.parse::<u64>(), y.parse::<u64>()) {
+                    (Ok(x), Ok(y)) => x.cmp(&y),
+                    _ => x.cmp(y),
+                };
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+        }
+    }
+}
+
+fn load_registry(path: &Path) -> BTreeMap<String, InstalledPlugin> {
+    let Ok(bytes) = std::fs::read(path) else {
+        return BTreeMap::new();
+    };
+    match serde_json::from_slice::<Vec<InstalledPlugin>>(&bytes) {
+        Ok(installed) => installed.into_iter().map(|p| (p.name.clone(), p)).collect(),
+        Err(e) => {
+            warn!("Ignoring unreadable plugin registry {:?}: {}", path, e);
+            BTreeMap::new()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{host::HostServices, SecurityPolicy, TrustedKey};
+    use axum::{routing::get, Router};
+    use ring::signature::{Ed25519KeyPair, KeyPair};
+
+    // (module (func (export "get_metadata") (result i32) i32.const 1)) with a
+    // metadata section
+    fn loadable_plugin() -> Vec<u8> {
+        let mut wasm = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // header
+            0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7f, // type section
+            0x03, 0x02, 0x01, 0x00, // function section
+            0x07, 0x10, 0x01, 0x0c, // export section, one 12 byte name
+        ];
+        wasm.extend_from_slice(b"get_metadata");
+        wasm.extend_from_slice(&[0x00, 0x00]);
+        wasm.extend_from_slice(&[0x0a, 0x06, 0x01, 0x04, 0x00, 0x41, 0x01, 0x0b]); // code section
+        let metadata: crate::WasmPluginMetadata = serde_json::from_value(serde_json::json!({
+            "id": "weather",
+            "name": "Weather",
+            "version": "1.0.0",
+            "author": "acme",
+        }))
+        .unwrap();
+        crate::metadata::embed(&wasm, &metadata).unwrap()
+    }
+
+    struct Fixture {
+        dir: tempfile::TempDir,
+        marketplace: PluginMarketplace,
+        pkcs8: Vec<u8>,
+    }
+
+    // The host's policy, trusting `public_key` to sign for acme
+    fn policy(key_id: &str, public_key: String, require_signature: bool) -> SecurityPolicy {
+        SecurityPolicy {
+            require_signature,
+            trusted_authors: ["acme".to_string()].into_iter().collect(),
+            trusted_keys: [(key_id.to_string(), TrustedKey { author: "acme".to_string(), public_key })].into_iter().collect(),
+            ..SecurityPolicy::default()
+        }
+    }
+
+    fn manager(dir: &Path, policy: SecurityPolicy) -> Arc<WasmPluginManager> {
+        Arc::new(WasmPluginManager::new(dir).unwrap().with_host_services(HostServices { policy, ..Default::default() }))
+    }
+
+    // Serve an index listing each (version, artifact) pair, all signed by one
+    // key the host trusts
+    async fn fixture(releases: Vec<(&str, Vec<u8>)>) -> Fixture {
+        let rng = ring::rand::SystemRandom::new();
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&rng).unwrap().as_ref().to_vec();
+        let key_pair = Ed25519KeyPair::from_pkcs8(&pkcs8).unwrap();
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+
+        let mut app = Router::new();
+        let mut index = PluginIndex::default();
+        for (version, artifact) in releases {
+            let path = format!("/artifacts/weather-{}.wasm", version);
+            index.plugins.push(IndexEntry {
+                name: "weather".to_string(),
+                version: version.to_string(),
+                download_url: format!("{}{}", base_url, path),
+                sha256: artifact_sha256(&artifact),
+                signature: hex::encode(key_pair.sign(&artifact).as_ref()),
+                metadata: IndexMetadata::default(),
+            });
+            app = app.route(&path, get(move || {
+                let artifact = artifact.clone();
+                async move { artifact }
+            }));
+        }
+        let index = serde_json::to_string(&index).unwrap();
+        app = app.route("/index.json", get(move || {
+            let index = index.clone();
+            async move { index }
+        }));
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let dir = tempfile::tempdir().unwrap();
+        let config = MarketplaceConfig {
+            index_urls: vec![format!("{}/index.json", base_url)],
+            plugin_directory: dir.path().to_path_buf(),
+        };
+        let public_key = hex::encode(key_pair.public_key().as_ref());
+        let marketplace = PluginMarketplace::new(config, manager(dir.path(), policy("acme-release", public_key, false))).unwrap();
+        Fixture { dir, marketplace, pkcs8 }
+    }
+
+    #[tokio::test]
+    async fn test_install_from_index() {
+        let f = fixture(vec![("1.0.0", loadable_plugin())]).await;
+
+        let available = f.marketplace.available().await;
+        assert_eq!(available.len(), 1);
+        assert_eq!(available[0].installed_version, None);
+
+        let installed = f.marketplace.install("weather", Some("1.0.0")).await.unwrap();
+        assert_eq!(installed.version, "1.0.0");
+        assert!(f.dir.path().join("weather.wasm").exists());
+        assert!(f.marketplace.manager().list_plugins().await.contains(&"weather".to_string()));
+
+        let available = f.marketplace.available().await;
+        assert_eq!(available[0].installed_version.as_deref(), Some("1.0.0"));
+
+        // The verified signature is kept next to the module, so a host that
+        // requires signatures loads it again from disk
+        let module_path = f.dir.path().join("weather.wasm");
+        let signature = PluginSignature::read_for(&module_path).await.unwrap().unwrap();
+        assert_eq!(signature.key_id, "acme-release");
+        let public_key = crate::signing_public_key(&f.pkcs8).unwrap();
+        let strict = manager(f.dir.path(), policy("acme-release", public_key, true));
+        strict.load_plugin_from_file("weather", &module_path).await.unwrap();
+        assert!(matches!(strict.load_plugin("weather", &loadable_plugin()).await, Err(AssistantError::Security(_))));
+    }
+
+    #[tokio::test]
+    async fn test_failed_upgrade_keeps_installed_version() {
+        // 2.0.0 is signed and intact but is not a loadable module
+        let f = fixture(vec![("1.0.0", loadable_plugin()), ("2.0.0", b"not wasm".to_vec())]).await;
+        f.marketplace.install("weather", Some("1.0.0")).await.unwrap();
+        assert!(f.marketplace.available().await[0].update_available);
+
+        assert!(f.marketplace.install("weather", None).await.is_err());
+        assert_eq!(std::fs::read(f.dir.path().join("weather.wasm")).unwrap(), loadable_plugin());
+        assert_eq!(f.marketplace.installed().await[0].version, "1.0.0");
+        assert!(!f.dir.path().join("weather-2.0.0.wasm.download").exists());
+    }
+
+    #[tokio::test]
+    async fn test_uninstall_removes_artifact_and_registry_entry() {
+        let f = fixture(vec![("1.0.0", loadable_plugin())]).await;
+        f.marketplace.install("weather", Some("1.0.0")).await.unwrap();
+
+        let removed = f.marketplace.uninstall("weather").await.unwrap();
+        assert_eq!(removed.version, "1.0.0");
+        assert!(!f.dir.path().join("weather.wasm").exists());
+        assert!(!f.dir.path().join("weather.sig").exists());
+        assert!(f.marketplace.installed().await.is_empty());
+        assert!(f.marketplace.manager().list_plugins().await.is_empty());
+        assert!(load_registry(&f.dir.path().join(REGISTRY_FILE)).is_empty());
+
+        assert!(matches!(f.marketplace.uninstall("weather").await, Err(AssistantError::NotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn test_uploaded_plugin_is_installed_with_its_declared_version() {
+        let f = fixture(Vec::new()).await;
+        assert!(f.marketplace.install_upload("weather", b"not wasm", None).await.is_err());
+        assert!(!f.dir.path().join("weather.wasm").exists());
+
+        let signature = crate::sign_plugin(&loadable_plugin(), "acme-release", &f.pkcs8).unwrap();
+        let installed = f.marketplace.install_upload("weather", &loadable_plugin(), Some(&signature)).await.unwrap();
+        assert_eq!(PluginSignature::read_for(&f.dir.path().join("weather.wasm")).await.unwrap(), Some(signature));
+        // Uploaded again unsigned, the earlier signature goes with the old module
+        let installed_unsigned = f.marketplace.install_upload("weather", &loadable_plugin(), None).await.unwrap();
+        assert_eq!(installed_unsigned.sha256, installed.sha256);
+        assert_eq!(PluginSignature::read_for(&f.dir.path().join("weather.wasm")).await.unwrap(), None);
+        assert_eq!(installed.source, UPLOAD_SOURCE);
+        assert_eq!(installed.sha256, artifact_sha256(&loadable_plugin()));
+        assert_eq!(installed.version, f.marketplace.manager().plugin_versions("weather").await.unwrap().active);
+        assert_eq!(std::fs::read(f.dir.path().join("weather.wasm")).unwrap(), loadable_plugin());
+        assert_eq!(load_registry(&f.dir.path().join(REGISTRY_FILE)).len(), 1);
+
+        f.marketplace.uninstall("weather").await.unwrap();
+        assert!(f.marketplace.manager().list_plugins().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_untrusted_signature_is_rejected() {
+        let f = fixture(vec![("1.0.0", loadable_plugin())]).await;
+        let stranger = manager(f.dir.path(), policy("other", hex::encode([7u8; 32]), false));
+        let marketplace = PluginMarketplace::new(f.marketplace.config.clone(), stranger).unwrap();
+
+        assert!(marketplace.install("weather", Some("1.0.0")).await.is_err());
+        assert!(!f.dir.path().join("weather.wasm").exists());
+        assert!(!f.dir.path().join("weather.sig").exists());
+    }
+
+    #[test]
+    fn test_compare_versions_and_names() {
+        assert_eq!(compare_versions("1.10.0", "1.9.2"), Ordering::Greater);
+        assert_eq!(compare_versions("1.0", "1.0.0"), Ordering::Less);
+        assert!(validate_plugin_name("weather_v2").is_ok());
+        assert!(validate_plugin_name("../etc/passwd").is_err());
+    }
+}
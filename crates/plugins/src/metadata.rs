@@ -0,0 +1,207 @@
+//! Plugin metadata carried inside the module.
+//!
+//! A plugin describes itself with a `rusty-ai-plugin-metadata` custom section
+//! holding [`WasmPluginMetadata`] as JSON. Modules without the section may
+//! export `get_metadata() -> i64` instead, returning the JSON's location in
+//! linear memory packed like a function output: pointer in the high 32 bits,
+//! length in the low 32. The loader keys its registry and checksum checks on
+//! the metadata id, so missing or malformed metadata is an error rather than
+//! an invented id.
+
+use crate::WasmPluginMetadata;
+use rusty_ai_common::{AssistantError, Result};
+use std::borrow::Cow;
+
+/// Name of the custom section holding the plugin's metadata JSON
+pub const METADATA_SECTION: &str = "rusty-ai-plugin-metadata";
+
+/// Section id of custom sections in the binary format
+const CUSTOM_SECTION_ID: u8 = 0;
+
+/// Magic number and version preceding the first section
+const WASM_HEADER_LEN: usize = 8;
+
+/// Metadata from the module's custom section, or None when it has none.
+/// Accepts the binary and the text format
+pub fn from_custom_section(wasm_bytes: &[u8]) -> Result<Option<WasmPluginMetadata>> {
+    let binary = to_binary(wasm_bytes)?;
+    match custom_section(&binary, METADATA_SECTION)? {
+        Some(json) => parse(json, &format!("`{}` section", METADATA_SECTION)).map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Parse and check metadata JSON; `origin` names where it came from in errors
+pub fn parse(json: &[u8], origin: &str) -> Result<WasmPluginMetadata> {
+    let metadata: WasmPluginMetadata = serde_json::from_slice(json)
+        .map_err(|e| AssistantError::Plugin(format!("Malformed plugin metadata in the {}: {}", origin, e)))?;
+    for (field, value) in [("id", &metadata.id), ("name", &metadata.name), ("version", &metadata.version)] {
+        if value.trim().is_empty() {
+            return Err(AssistantError::Plugin(format!(
+                "Plugin metadata in the {} has an empty {}",
+                origin, field
+            )));
+        }
+    }
+    // A zero limit would fail every call, which is never what was meant
+    for (function, limits) in &metadata.function_limits {
+        if limits.timeout_ms == Some(0) || limits.max_fuel == Some(0) {
+            return Err(AssistantError::Plugin(format!(
+                "Plugin metadata in the {} gives {} a zero limit",
+                origin, function
+            )));
+        }
+    }
+    Ok(metadata)
+}
+
+/// Append a metadata section to a module given in the binary or text format,
+/// returning the binary. Used to build plugin artifacts and test fixtures
+pub fn embed(wasm_bytes: &[u8], metadata: &WasmPluginMetadata) -> Result<Vec<u8>> {
+    let mut binary = to_binary(wasm_bytes)?.into_owned();
+    if custom_section(&binary, METADATA_SECTION)?.is_some() {
+        return Err(AssistantError::Plugin(format!(
+            "Module already has a `{}` section",
+            METADATA_SECTION
+        )));
+    }
+    let json = serde_json::to_vec(metadata)
+        .map_err(|e| AssistantError::Plugin(format!("Failed to encode plugin metadata: {}", e)))?;
+
+    let mut payload = Vec::with_capacity(METADATA_SECTION.len() + json.len() + 5);
+    write_leb128(&mut payload, METADATA_SECTION.len());
+    payload.extend_from_slice(METADATA_SECTION.as_bytes());
+    payload.extend_from_slice(&json);
+
+    binary.push(CUSTOM_SECTION_ID);
+    write_leb128(&mut binary, payload.len());
+    binary.extend_from_slice(&payload);
+    Ok(binary)
+}
+
+fn to_binary(wasm_bytes: &[u8]) -> Result<Cow<'_, [u8]>> {
+    wat::parse_bytes(wasm_bytes)
+        .map_err(|e| AssistantError::Plugin(format!("Invalid WebAssembly module: {}", e)))
+}
+
+// Walks the section headers only; section bodies other than the custom
+// section's name are not decoded
+fn custom_section<'a>(binary: &'a [u8], name: &str) -> Result<Option<&'a [u8]>> {
+    let truncated = || AssistantError::Plugin("Invalid WebAssembly module: truncated section".to_string());
+    if binary.len() < WASM_HEADER_LEN || !binary.starts_with(b"\0asm") {
+        return Err(AssistantError::Plugin("Invalid WebAssembly module: missing header".to_string()));
+    }
+
+    let mut rest = &binary[WASM_HEADER_LEN..];
+    while let Some((&id, tail)) = rest.split_first() {
+        let (size, tail) = read_leb128(tail).ok_or_else(truncated)?;
+        let body = tail.get(..size).ok_or_else(truncated)?;
+        rest = &tail[size..];
+        if id == CUSTOM_SECTION_ID {
+            let (name_len, body) = read_leb128(body).ok_or_else(truncated)?;
+            if body.get(..name_len) == Some(name.as_bytes()) {
+                return Ok(Some(&body[name_len..]));
+            }
+        }
+    }
+    Ok(None)
+}
+
+// Unsigned LEB128 of at most 32 bits, as used for section sizes
+fn read_leb128(bytes: &[u8]) -> Option<(usize, &[u8])> {
+    let mut value = 0usize;
+    for (i, &byte) in bytes.iter().enumerate().take(5) {
+        value |= ((byte & 0x7f) as usize) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Some((value, &bytes[i + 1..]));
+        }
+    }
+    None
+}
+
+fn write_leb128(out: &mut Vec<u8>, mut value: usize) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::limits::FunctionLimits;
+
+    const MODULE: &str = r#"(module (func (export "run") (result i32) i32.const 1))"#;
+
+    fn metadata(id: &str) -> WasmPluginMetadata {
+        WasmPluginMetadata {
+            id: id.to_string(),
+            name: "Weather".to_string(),
+            version: "1.2.0".to_string(),
+            // Long enough that the section size needs a multi-byte LEB128
+            description: "Forecasts for the places in your calendar".repeat(4),
+            author: "RUSTY-AI".to_string(),
+            license: "MIT".to_string(),
+            capabilities: vec!["weather".to_string()],
+            dependencies: vec![],
+            api_version: "1.0".to_string(),
+            checksum: String::new(),
+            function_limits: [("forecast".to_string(), FunctionLimits { timeout_ms: Some(100), max_fuel: None })].into(),
+            events: vec!["task_completed".to_string()],
+        }
+    }
+
+    #[test]
+    fn test_embedded_metadata_round_trips() {
+        assert!(from_custom_section(MODULE.as_bytes()).unwrap().is_none());
+
+        let wasm = embed(MODULE.as_bytes(), &metadata("weather")).unwrap();
+        assert!(wasm.starts_with(b"\0asm"));
+        let read = from_custom_section(&wasm).unwrap().unwrap();
+        assert_eq!(read.id, "weather");
+        assert_eq!(read.description, metadata("weather").description);
+        assert_eq!(read.function_limits, metadata("weather").function_limits);
+
+        // Other custom sections before it are skipped
+        let named = embed(r#"(module (@custom "producers" "x") (func))"#.as_bytes(), &metadata("named")).unwrap();
+        assert_eq!(from_custom_section(&named).unwrap().unwrap().id, "named");
+
+        assert!(embed(&wasm, &metadata("other")).is_err());
+    }
+
+    #[test]
+    fn test_malformed_metadata_is_a_descriptive_error() {
+        let errors = [
+            (r#"(module (@custom "rusty-ai-plugin-metadata" "{not json"))"#, "Malformed plugin metadata"),
+            (r#"(module (@custom "rusty-ai-plugin-metadata" "{\"id\":\"x\"}"))"#, "missing field"),
+        ];
+        for (module, expected) in errors {
+            match from_custom_section(module.as_bytes()) {
+                Err(AssistantError::Plugin(message)) => assert!(message.contains(expected), "{}", message),
+                other => panic!("expected a plugin error, got {:?}", other),
+            }
+        }
+
+        let wasm = embed(MODULE.as_bytes(), &metadata(" ")).unwrap();
+        match from_custom_section(&wasm) {
+            Err(AssistantError::Plugin(message)) => assert!(message.ends_with("has an empty id"), "{}", message),
+            other => panic!("expected a plugin error, got {:?}", other),
+        }
+
+        let mut zero = metadata("weather");
+        zero.function_limits.insert("ping".to_string(), FunctionLimits { timeout_ms: None, max_fuel: Some(0) });
+        match from_custom_section(&embed(MODULE.as_bytes(), &zero).unwrap()) {
+            Err(AssistantError::Plugin(message)) => assert!(message.ends_with("gives ping a zero limit"), "{}", message),
+            other => panic!("expected a plugin error, got {:?}", other),
+        }
+
+        let mut truncated = embed(MODULE.as_bytes(), &metadata("weather")).unwrap();
+        truncated.truncate(truncated.len() - 10);
+        assert!(from_custom_section(&truncated).is_err());
+    }
+}
@@ -0,0 +1,160 @@
+//! Prometheus metrics for the plugin runtime.
+//!
+//! Calls are counted and timed per plugin name and function; loads per
+//! plugin. Users are never a label, so the number of series stays bounded
+//! by the plugins installed. The metrics live in their own registry, shared
+//! by every manager and loader in the process; `gather` renders it in the
+//! text exposition format for a `/metrics` endpoint.
+
+use crate::limits::ExecutionLimit;
+use crate::loader::PluginLifecycleEvent;
+use prometheus::{
+    exponential_buckets, HistogramOpts, HistogramVec, IntCounterVec, Opts, Registry, TextEncoder,
+};
+use rusty_ai_common::AssistantError;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+const NAMESPACE: &str = "rusty_ai_plugin";
+
+/// The runtime's metrics
+pub struct PluginMetrics {
+    registry: Registry,
+    pub executions: IntCounterVec,
+    pub errors: IntCounterVec,
+    /// Calls stopped by the CPU time limit or the manager's timeout
+    pub timeouts: IntCounterVec,
+    pub fuel_exhausted: IntCounterVec,
+    pub execution_duration: HistogramVec,
+    pub input_bytes: HistogramVec,
+    pub output_bytes: HistogramVec,
+    /// Lifecycle events by outcome: loaded, reloaded, unloaded or failed
+    pub loads: IntCounterVec,
+    /// Time to instantiate and validate a build, whether it loaded or not
+    pub load_duration: HistogramVec,
+}
+
+/// The metrics every manager and loader in the process reports to
+pub fn global() -> &'static PluginMetrics {
+    static METRICS: OnceLock<PluginMetrics> = OnceLock::new();
+    METRICS.get_or_init(PluginMetrics::new)
+}
+
+/// Every plugin metric in the Prometheus text format
+pub fn gather() -> String {
+    global().gather()
+}
+
+impl PluginMetrics {
+    fn new() -> Self {
+        let registry = Registry::new();
+        let call = ["plugin_id", "function"];
+        let counter = |name: &str, help: &str, labels: &[&str]| {
+            let counter = IntCounterVec::new(Opts::new(name, help).namespace(NAMESPACE), labels)
+                .expect("valid counter definition");
+            registry.register(Box::new(counter.clone())).expect("counter registered once");
+            counter
+        };
+        let histogram = |name: &str, help: &str, labels: &[&str], buckets: Vec<f64>| {
+            let histogram = HistogramVec::new(HistogramOpts::new(name, help).namespace(NAMESPACE).buckets(buckets), labels)
+                .expect("valid histogram definition");
+            registry.register(Box::new(histogram.clone())).expect("histogram registered once");
+            histogram
+        };
+        // 1ms to about 33s, and 64 bytes to about 16MB
+        let seconds = exponential_buckets(0.001, 2.0, 16).expect("valid buckets");
+        let bytes = exponential_buckets(64.0, 4.0, 10).expect("valid buckets");
+
+        Self {
+            executions: counter("executions_total", "Plugin calls", &call),
+            errors: counter("errors_total", "Plugin calls that failed or were refused", &call),
+            timeouts: counter("timeouts_total", "Plugin calls stopped for taking too long", &call),
+            fuel_exhausted: counter("fuel_exhausted_total", "Plugin calls stopped for running out of fuel", &call),
+            execution_duration: histogram("execution_duration_seconds", "Time from a call's arrival to its result", &call, seconds.clone()),
+            input_bytes: histogram("input_bytes", "Size of the input of plugin calls", &call, bytes.clone()),
+            output_bytes: histogram("output_bytes", "Size of the output of successful plugin calls", &call, bytes),
+            loads: counter("loads_total", "Plugin lifecycle events by outcome", &["plugin_id", "outcome"]),
+            load_duration: histogram("load_duration_seconds", "Time to instantiate and validate a plugin build", &["plugin_id"], seconds),
+            registry,
+        }
+    }
+
+    /// Count a finished call, allowed or not
+    pub fn record_call(
+        &self,
+        plugin_id: &str,
+        function: &str,
+        input_size: usize,
+        result: std::result::Result<&[u8], &AssistantError>,
+        duration: Duration,
+    ) {
+        let labels = [plugin_id, function];
+        self.executions.with_label_values(&labels).inc();
+        self.execution_duration.with_label_values(&labels).observe(duration.as_secs_f64());
+        self.input_bytes.with_label_values(&labels).observe(input_size as f64);
+        match result {
+            Ok(output) => self.output_bytes.with_label_values(&labels).observe(output.len() as f64),
+            Err(e) => {
+                self.errors.with_label_values(&labels).inc();
+                match ExecutionLimit::of(e) {
+                    Some(ExecutionLimit::CpuTime | ExecutionLimit::WallClock) => self.timeouts.with_label_values(&labels).inc(),
+                    Some(ExecutionLimit::Fuel) => self.fuel_exhausted.with_label_values(&labels).inc(),
+                    Some(ExecutionLimit::Memory) | None => {}
+                }
+            }
+        }
+    }
+
+    /// Count a lifecycle event of the loader. Builds that fail before their
+    /// plugin is known are counted under `unknown`
+    pub fn record_lifecycle(&self, event: &PluginLifecycleEvent) {
+        let (plugin_id, outcome) = match event {
+            PluginLifecycleEvent::Loaded { plugin_id, .. } => (plugin_id.as_str(), "loaded"),
+            PluginLifecycleEvent::Reloaded { plugin_id, .. } => (plugin_id.as_str(), "reloaded"),
+            PluginLifecycleEvent::Unloaded { plugin_id } => (plugin_id.as_str(), "unloaded"),
+            PluginLifecycleEvent::Failed { plugin_id, .. } => (plugin_id.as_deref().unwrap_or("unknown"), "failed"),
+        };
+        self.loads.with_label_values(&[plugin_id, outcome]).inc();
+    }
+
+    pub fn record_load_time(&self, plugin_id: &str, duration: Duration) {
+        self.load_duration.with_label_values(&[plugin_id]).observe(duration.as_secs_f64());
+    }
+
+    pub fn gather(&self) -> String {
+        TextEncoder::new().encode_to_string(&self.registry.gather()).unwrap_or_else(|e| {
+            tracing::warn!("Failed to encode plugin metrics: {}", e);
+            String::new()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ResourceLimits;
+
+    #[test]
+    fn test_calls_are_counted_by_outcome_and_limit() {
+        let metrics = PluginMetrics::new();
+        let limits = ResourceLimits::default();
+        let out_of_fuel = AssistantError::Plugin(format!("Plugin spin {}", ExecutionLimit::Fuel.describe(&limits)));
+        let too_slow = AssistantError::Plugin(format!("Plugin spin {}", ExecutionLimit::WallClock.describe(&limits)));
+
+        metrics.record_call("spin", "run", 10, Ok(b"done"), Duration::from_millis(5));
+        metrics.record_call("spin", "run", 10, Err(&out_of_fuel), Duration::from_millis(5));
+        metrics.record_call("spin", "run", 10, Err(&too_slow), Duration::from_secs(30));
+        metrics.record_call("spin", "run", 10, Err(&AssistantError::NotFound("gone".to_string())), Duration::ZERO);
+
+        let labels = ["spin", "run"];
+        assert_eq!(metrics.executions.with_label_values(&labels).get(), 4);
+        assert_eq!(metrics.errors.with_label_values(&labels).get(), 3);
+        assert_eq!(metrics.fuel_exhausted.with_label_values(&labels).get(), 1);
+        assert_eq!(metrics.timeouts.with_label_values(&labels).get(), 1);
+        assert_eq!(metrics.output_bytes.with_label_values(&labels).get_sample_count(), 1);
+
+        let text = metrics.gather();
+        assert!(text.contains(r#"rusty_ai_plugin_executions_total{function="run",plugin_id="spin"} 4"#), "{}", text);
+        assert!(text.contains("rusty_ai_plugin_execution_duration_seconds_bucket"));
+    }
+}
@@ -0,0 +1,114 @@
+//! Capture of plugin stdout and stderr.
+//!
+//! What a plugin prints is kept in memory instead of reaching the host's
+//! stdio. Each stream keeps up to its capacity per call; anything written
+//! past that is counted and dropped without failing the write, and the
+//! output taken at the end of the call closes with a marker saying how many
+//! bytes were cut.
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use std::sync::{Arc, Mutex};
+use wasmtime_wasi::{HostOutputStream, StdoutStream, StreamResult, Subscribe};
+
+/// Bytes of each stream kept per call unless configured otherwise
+pub const DEFAULT_OUTPUT_CAPACITY: usize = 64 * 1024;
+
+// How much the plugin may write in one go. Writes beyond the capacity are
+// accepted too, so this only sets the chunk size
+const WRITE_BUDGET: usize = 64 * 1024;
+
+/// One captured stream, shared between the WASI context and the host
+#[derive(Clone)]
+pub struct CapturedStream {
+    buffer: Arc<Mutex<Buffer>>,
+}
+
+struct Buffer {
+    bytes: Vec<u8>,
+    capacity: usize,
+    dropped: usize,
+}
+
+impl CapturedStream {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            buffer: Arc::new(Mutex::new(Buffer { bytes: Vec::new(), capacity, dropped: 0 })),
+        }
+    }
+
+    /// Everything written since the last take, ending with a truncation
+    /// marker if any of it was dropped. The stream starts over empty
+    pub fn take(&self) -> Vec<u8> {
+        let mut buffer = self.buffer.lock().unwrap();
+        let mut bytes = std::mem::take(&mut buffer.bytes);
+        if buffer.dropped > 0 {
+            bytes.extend_from_slice(truncation_marker(buffer.dropped).as_bytes());
+            buffer.dropped = 0;
+        }
+        bytes
+    }
+
+    fn append(&self, bytes: &[u8]) {
+        let mut buffer = self.buffer.lock().unwrap();
+        let room = buffer.capacity.saturating_sub(buffer.bytes.len());
+        let kept = bytes.len().min(room);
+        buffer.bytes.extend_from_slice(&bytes[..kept]);
+        buffer.dropped += bytes.len() - kept;
+    }
+}
+
+/// Closes output that went over the capacity
+pub fn truncation_marker(dropped: usize) -> String {
+    format!("\n[truncated: {} more bytes]\n", dropped)
+}
+
+impl StdoutStream for CapturedStream {
+    fn stream(&self) -> Box<dyn HostOutputStream> {
+        Box::new(self.clone())
+    }
+
+    fn isatty(&self) -> bool {
+        false
+    }
+}
+
+#[async_trait]
+impl Subscribe for CapturedStream {
+    async fn ready(&mut self) {}
+}
+
+#[async_trait]
+impl HostOutputStream for CapturedStream {
+    fn write(&mut self, bytes: Bytes) -> StreamResult<()> {
+        self.append(&bytes);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> StreamResult<()> {
+        Ok(())
+    }
+
+    fn check_write(&mut self) -> StreamResult<usize> {
+        Ok(WRITE_BUDGET)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_output_past_the_capacity_is_dropped_until_the_next_take() {
+        let stream = CapturedStream::new(8);
+        let mut writer = stream.stream();
+        writer.write(Bytes::from_static(b"hello ")).unwrap();
+        writer.write(Bytes::from_static(b"world")).unwrap();
+        assert_eq!(stream.take(), [&b"hello wo"[..], truncation_marker(3).as_bytes()].concat());
+
+        // Each take starts a fresh buffer with the full capacity
+        writer.write(Bytes::from_static(b"again")).unwrap();
+        assert_eq!(stream.take(), b"again");
+        assert!(stream.take().is_empty());
+    }
+}
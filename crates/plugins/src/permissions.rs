@@ -0,0 +1,189 @@
+use crate::limits::FunctionLimits;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Permission that grants every plugin function
+pub const ADMIN_PERMISSION: &str = "admin";
+
+/// Permission required by functions that do not declare one (default: plugins:execute)
+pub const DEFAULT_BASELINE_PERMISSION: &str = "plugins:execute";
+
+/// A function as declared by a plugin's `list_functions` export
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FunctionSchema {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub input_schema: Option<serde_json::Value>,
+    #[serde(default)]
+    pub output_schema: Option<serde_json::Value>,
+    /// Permission the caller needs; `None` falls back to the baseline permission
+    #[serde(default)]
+    pub required_permission: Option<String>,
+    /// Whether the function may be invoked from chat capability dispatch
+    #[serde(default)]
+    pub public: bool,
+    /// Limits calls run under, after the plugin's per-function overrides;
+    /// filled in by the host when the plugin is loaded
+    #[serde(default)]
+    pub limits: Option<FunctionLimits>,
+}
+
+/// Parse the `{"functions": [...]}` document returned by `list_functions`
+pub fn parse_function_schemas(list_functions_output: &serde_json::Value) -> HashMap<String, FunctionSchema> {
+    list_functions_output
+        .get("functions")
+        .and_then(|f| f.as_array())
+        .map(|functions| {
+            functions
+                .iter()
+                .filter_map(|f| serde_json::from_value::<FunctionSchema>(f.clone()).ok())
+                .map(|schema| (schema.name.clone(), schema))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Where a plugin call originates
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CallOrigin {
+    /// Explicit call through the plugin API
+    #[default]
+    Api,
+    /// Capability dispatch from a conversation; only public functions are allowed
+    Conversation,
+}
+
+/// Outcome of a permission check, recorded in the audit log
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PermissionDecision {
+    pub plugin_id: String,
+    pub function: String,
+    pub user_id: String,
+    pub origin: CallOrigin,
+    pub required_permission: String,
+    pub allowed: bool,
+    pub reason: String,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+}
+
+/// Per-function execution permission policy
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PermissionPolicy {
+    /// Permission required by functions without a declared `required_permission`
+    pub baseline_permission: String,
+}
+
+impl Default for PermissionPolicy {
+    fn default() -> Self {
+        Self {
+            baseline_permission: DEFAULT_BASELINE_PERMISSION.to_string(),
+        }
+    }
+}
+
+impl PermissionPolicy {
+    pub fn new(baseline_permission: impl Into<String>) -> Self {
+        Self {
+            baseline_permission: baseline_permission.into(),
+        }
+    }
+
+    /// Decide whether a caller holding `permissions` may invoke `function`
+    pub fn check(
+        &self,
+        plugin_id: &str,
+        function: &str,
+        schema: Option<&FunctionSchema>,
+        user_id: &str,
+        permissions: &[String],
+        origin: CallOrigin,
+    ) -> PermissionDecision {
+        let required_permission = schema
+            .and_then(|s| s.required_permission.clone())
+            .unwrap_or_else(|| self.baseline_permission.clone());
+
+        let (allowed, reason) = if origin == CallOrigin::Conversation && !schema.map(|s| s.public).unwrap_or(false) {
+            (false, "function is not marked public for conversation dispatch".to_string())
+        } else if permissions.iter().any(|p| p == ADMIN_PERMISSION) {
+            (true, "caller is admin".to_string())
+        } else if permissions.iter().any(|p| *p == required_permission) {
+            (true, format!("caller has '{}'", required_permission))
+        } else if schema.is_none() {
+            (false, format!("undeclared function requires baseline permission '{}'", required_permission))
+        } else {
+            (false, format!("missing permission '{}'", required_permission))
+        };
+
+        PermissionDecision {
+            plugin_id: plugin_id.to_string(),
+            function: function.to_string(),
+            user_id: user_id.to_string(),
+            origin,
+            required_permission,
+            allowed,
+            reason,
+            timestamp: chrono::Utc::now(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schemas() -> HashMap<String, FunctionSchema> {
+        parse_function_schemas(&serde_json::json!({
+            "functions": [
+                {"name": "get_forecast", "description": "", "required_permission": "weather:read", "public": true},
+                {"name": "set_api_key", "description": "", "required_permission": "admin"}
+            ]
+        }))
+    }
+
+    fn perms(list: &[&str]) -> Vec<String> {
+        list.iter().map(|p| p.to_string()).collect()
+    }
+
+    #[test]
+    fn test_declared_permission_allows_and_denies() {
+        let policy = PermissionPolicy::default();
+        let schemas = schemas();
+
+        let decision = policy.check("weather", "get_forecast", schemas.get("get_forecast"), "u1", &perms(&["weather:read"]), CallOrigin::Api);
+        assert!(decision.allowed);
+
+        let decision = policy.check("weather", "set_api_key", schemas.get("set_api_key"), "u1", &perms(&["weather:read"]), CallOrigin::Api);
+        assert!(!decision.allowed);
+        assert_eq!(decision.required_permission, "admin");
+
+        let decision = policy.check("weather", "set_api_key", schemas.get("set_api_key"), "u1", &perms(&["admin"]), CallOrigin::Api);
+        assert!(decision.allowed);
+    }
+
+    #[test]
+    fn test_undeclared_function_uses_baseline() {
+        let policy = PermissionPolicy::new("plugins:run");
+
+        let denied = policy.check("weather", "debug_dump", None, "u1", &perms(&["weather:read"]), CallOrigin::Api);
+        assert!(!denied.allowed);
+        assert_eq!(denied.required_permission, "plugins:run");
+
+        let allowed = policy.check("weather", "debug_dump", None, "u1", &perms(&["plugins:run"]), CallOrigin::Api);
+        assert!(allowed.allowed);
+    }
+
+    #[test]
+    fn test_conversation_dispatch_requires_public_function() {
+        let policy = PermissionPolicy::default();
+        let schemas = schemas();
+
+        let public = policy.check("weather", "get_forecast", schemas.get("get_forecast"), "u1", &perms(&["weather:read"]), CallOrigin::Conversation);
+        assert!(public.allowed);
+
+        let private = policy.check("weather", "set_api_key", schemas.get("set_api_key"), "u1", &perms(&["admin"]), CallOrigin::Conversation);
+        assert!(!private.allowed);
+    }
+}
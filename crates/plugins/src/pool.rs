@@ -0,0 +1,198 @@
+//! Instances of one plugin version that calls share.
+//!
+//! A WebAssembly instance runs one call at a time, so a version backed by a
+//! single instance serializes every call to it. [`InstancePool`] keeps up to
+//! `size` instances of the same module: a call checks out an idle one,
+//! instantiating another lazily while the pool is below its size, and hands
+//! it back when done. Calls past the size wait for an instance to come back.
+//!
+//! Operations that change an instance's state, `initialize` and `cleanup`,
+//! wait until every instance is idle and are applied to all of them. The
+//! last `initialize` config is also applied to instances created later.
+
+use crate::{ResourceLimits, WasmPlugin, WasmPluginMetadata};
+use futures::future::BoxFuture;
+use rusty_ai_common::{AssistantError, Result};
+use serde::{Deserialize, Serialize};
+use std::ops::Deref;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::{Semaphore, SemaphorePermit};
+use tracing::debug;
+
+/// Instances kept per plugin version unless configured otherwise
+pub const DEFAULT_POOL_SIZE: usize = 2;
+
+/// Creates another instance of the same plugin build
+pub type InstanceFactory = Arc<dyn Fn() -> BoxFuture<'static, Result<Box<dyn WasmPlugin>>> + Send + Sync>;
+
+/// How much of a plugin's pool is in use, as health checks report it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PoolUtilization {
+    /// Most instances the pool will create
+    pub size: usize,
+    /// Instances created so far
+    pub instantiated: usize,
+    /// Instances running a call (or held by `initialize` or `cleanup`)
+    pub busy: usize,
+}
+
+/// The instances serving one plugin version
+pub struct InstancePool {
+    metadata: WasmPluginMetadata,
+    idle: Mutex<Vec<Box<dyn WasmPlugin>>>,
+    /// None for plugins registered as a single instance
+    factory: Option<InstanceFactory>,
+    size: usize,
+    // One permit per instance the pool may have checked out
+    permits: Semaphore,
+    instantiated: AtomicUsize,
+    config: Mutex<Option<serde_json::Value>>,
+    /// What the instances were created with, when it is not the manager's
+    /// default, e.g. limits capped by the plugin's trust level
+    limits: Option<ResourceLimits>,
+}
+
+impl InstancePool {
+    /// A pool starting with `first` that grows to `size` instances from `factory`
+    pub fn new(first: Box<dyn WasmPlugin>, factory: InstanceFactory, size: usize) -> Self {
+        let size = size.max(1);
+        Self::with_parts(first, Some(factory), size)
+    }
+
+    /// A pool of just this instance, for plugins that cannot be instantiated again
+    pub fn single(plugin: Box<dyn WasmPlugin>) -> Self {
+        Self::with_parts(plugin, None, 1)
+    }
+
+    fn with_parts(first: Box<dyn WasmPlugin>, factory: Option<InstanceFactory>, size: usize) -> Self {
+        Self {
+            metadata: first.metadata().clone(),
+            idle: Mutex::new(vec![first]),
+            factory,
+            size,
+            permits: Semaphore::new(size),
+            instantiated: AtomicUsize::new(1),
+            config: Mutex::new(None),
+            limits: None,
+        }
+    }
+
+    /// Record the limits the instances run under
+    pub fn with_limits(mut self, limits: ResourceLimits) -> Self {
+        self.limits = Some(limits);
+        self
+    }
+
+    pub fn limits(&self) -> Option<&ResourceLimits> {
+        self.limits.as_ref()
+    }
+
+    pub fn metadata(&self) -> &WasmPluginMetadata {
+        &self.metadata
+    }
+
+    pub fn utilization(&self) -> PoolUtilization {
+        PoolUtilization {
+            size: self.size,
+            instantiated: self.instantiated.load(Ordering::SeqCst),
+            busy: self.size - self.permits.available_permits(),
+        }
+    }
+
+    /// An instance for one call, waiting while all of them are busy. It goes
+    /// back to the pool when the returned guard is dropped
+    pub async fn checkout(&self) -> Result<PooledInstance<'_>> {
+        let permit = self
+            .permits
+            .acquire()
+            .await
+            .map_err(|_| AssistantError::Plugin("Plugin instance pool is closed".to_string()))?;
+        let idle = self.idle.lock().unwrap().pop();
+        let plugin = match idle {
+            Some(plugin) => plugin,
+            None => self.instantiate().await?,
+        };
+        Ok(PooledInstance { pool: self, plugin: Some(plugin), _permit: permit })
+    }
+
+    // Only reached with a permit and no idle instance, so fewer than `size`
+    // instances exist
+    async fn instantiate(&self) -> Result<Box<dyn WasmPlugin>> {
+        let factory = self
+            .factory
+            .as_ref()
+            .ok_or_else(|| AssistantError::Plugin(format!("No idle instance of {}", self.metadata.id)))?;
+        let mut plugin = factory().await?;
+        let config = self.config.lock().unwrap().clone();
+        if let Some(config) = config {
+            plugin.initialize(config).await?;
+        }
+        let instantiated = self.instantiated.fetch_add(1, Ordering::SeqCst) + 1;
+        debug!("Instantiated {} of {} instances of {}", instantiated, self.size, self.metadata.id);
+        Ok(plugin)
+    }
+
+    /// Initialize every instance, and the ones created from now on, with `config`
+    pub async fn initialize_all(&self, config: serde_json::Value) -> Result<()> {
+        *self.config.lock().unwrap() = Some(config.clone());
+        self.apply_to_all(|plugin| {
+            let config = config.clone();
+            Box::pin(async move { plugin.initialize(config).await })
+        })
+        .await
+    }
+
+    pub async fn cleanup_all(&self) -> Result<()> {
+        self.apply_to_all(|plugin| Box::pin(async move { plugin.cleanup().await })).await
+    }
+
+    // Waits until no instance is checked out, then runs `operation` on each;
+    // the first error is returned once all have been tried
+    async fn apply_to_all<F>(&self, operation: F) -> Result<()>
+    where
+        F: for<'p> Fn(&'p mut Box<dyn WasmPlugin>) -> BoxFuture<'p, Result<()>>,
+    {
+        let _all = self
+            .permits
+            .acquire_many(self.size as u32)
+            .await
+            .map_err(|_| AssistantError::Plugin("Plugin instance pool is closed".to_string()))?;
+        let mut instances = std::mem::take(&mut *self.idle.lock().unwrap());
+
+        let mut result = Ok(());
+        for plugin in instances.iter_mut() {
+            if let Err(e) = operation(plugin).await {
+                if result.is_ok() {
+                    result = Err(e);
+                }
+            }
+        }
+        self.idle.lock().unwrap().extend(instances);
+        result
+    }
+}
+
+/// An instance checked out of an [`InstancePool`]
+pub struct PooledInstance<'a> {
+    pool: &'a InstancePool,
+    plugin: Option<Box<dyn WasmPlugin>>,
+    _permit: SemaphorePermit<'a>,
+}
+
+impl Deref for PooledInstance<'_> {
+    type Target = dyn WasmPlugin;
+
+    fn deref(&self) -> &Self::Target {
+        self.plugin.as_deref().expect("present until dropped")
+    }
+}
+
+impl Drop for PooledInstance<'_> {
+    // Runs before the permit is released, so the next checkout finds it idle
+    fn drop(&mut self) {
+        if let Some(plugin) = self.plugin.take() {
+            self.pool.idle.lock().unwrap().push(plugin);
+        }
+    }
+}
@@ -0,0 +1,174 @@
+//! Per-user call quotas and per-plugin concurrency limits.
+//!
+//! A plugin's [`PluginQuota`] caps the calls each user may make to it in any
+//! hour, counted over a sliding window of call start times, and the calls it
+//! runs at once across all users, enforced with a semaphore per plugin.
+//! Calls over either limit are rejected with `AssistantError::RateLimited`,
+//! whose retry hint is when the oldest call in the window leaves it, or a
+//! second for a plugin that is merely busy.
+
+use rusty_ai_common::{AssistantError, PluginQuota, Result};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// The span `max_calls_per_hour` counts calls over
+pub const QUOTA_WINDOW: Duration = Duration::from_secs(60 * 60);
+
+/// Retry hint for calls rejected while a plugin runs its most calls at once
+const BUSY_RETRY_AFTER_SECS: u64 = 1;
+
+/// Usage of every plugin with a quota
+#[derive(Default)]
+pub struct QuotaTracker {
+    /// Start times of the calls within the window, per user and plugin
+    calls: Mutex<HashMap<(String, String), VecDeque<Instant>>>,
+    /// Concurrency permits per plugin, with the limit they were sized for
+    running: Mutex<HashMap<String, (usize, Arc<Semaphore>)>>,
+}
+
+impl QuotaTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count a call by `user_id` to `plugin_id`, or reject it if it is over
+    /// `quota`. The returned permit holds the call's concurrency slot until
+    /// it is dropped
+    pub fn admit(&self, user_id: &str, plugin_id: &str, quota: &PluginQuota) -> Result<Option<OwnedSemaphorePermit>> {
+        self.admit_at(user_id, plugin_id, quota, Instant::now())
+    }
+
+    fn admit_at(
+        &self,
+        user_id: &str,
+        plugin_id: &str,
+        quota: &PluginQuota,
+        now: Instant,
+    ) -> Result<Option<OwnedSemaphorePermit>> {
+        // Taken first, so a call turned away as busy does not use up quota
+        let permit = match quota.max_concurrent {
+            Some(limit) => Some(self.semaphore(plugin_id, limit).try_acquire_owned().map_err(|_| {
+                AssistantError::RateLimited {
+                    message: format!("{} is already running {} calls", plugin_id, limit),
+                    retry_after_secs: BUSY_RETRY_AFTER_SECS,
+                }
+            })?),
+            None => None,
+        };
+
+        if let Some(limit) = quota.max_calls_per_hour {
+            let mut calls = self.calls.lock().unwrap();
+            let window = calls.entry((user_id.to_string(), plugin_id.to_string())).or_default();
+            while window.front().is_some_and(|started| now.duration_since(*started) >= QUOTA_WINDOW) {
+                window.pop_front();
+            }
+            if limit == 0 {
+                return Err(AssistantError::RateLimited {
+                    message: format!("{} allows no calls", plugin_id),
+                    retry_after_secs: QUOTA_WINDOW.as_secs(),
+                });
+            }
+            if window.len() >= limit as usize {
+                let frees_at = window[window.len() - limit as usize] + QUOTA_WINDOW;
+                let wait = frees_at.saturating_duration_since(now);
+                return Err(AssistantError::RateLimited {
+                    message: format!("{} allows {} calls per hour", plugin_id, limit),
+                    retry_after_secs: wait.as_secs() + u64::from(wait.subsec_nanos() > 0),
+                });
+            }
+            window.push_back(now);
+        }
+
+        Ok(permit)
+    }
+
+    // The plugin's semaphore, replaced when its limit changed
+    fn semaphore(&self, plugin_id: &str, limit: usize) -> Arc<Semaphore> {
+        let mut running = self.running.lock().unwrap();
+        match running.get(plugin_id) {
+            Some((sized_for, semaphore)) if *sized_for == limit => semaphore.clone(),
+            _ => {
+                let semaphore = Arc::new(Semaphore::new(limit));
+                running.insert(plugin_id.to_string(), (limit, semaphore.clone()));
+                semaphore
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn retry_after(result: Result<Option<OwnedSemaphorePermit>>) -> u64 {
+        match result {
+            Err(AssistantError::RateLimited { retry_after_secs, .. }) => retry_after_secs,
+            other => panic!("expected a rate limit, got {:?}", other.map(|permit| permit.is_some())),
+        }
+    }
+
+    #[test]
+    fn test_the_101st_call_in_an_hour_waits_for_the_window_to_slide() {
+        let tracker = QuotaTracker::new();
+        let quota = PluginQuota { max_calls_per_hour: Some(100), max_concurrent: None };
+        let start = Instant::now();
+
+        for call in 0..100 {
+            let at = start + Duration::from_secs(call * 30);
+            tracker.admit_at("alice", "search", &quota, at).unwrap();
+        }
+        // Room frees up once the first call is an hour old
+        let last = start + Duration::from_secs(99 * 30);
+        assert_eq!(retry_after(tracker.admit_at("alice", "search", &quota, last)), 60 * 60 - 99 * 30);
+
+        // Other users and plugins have their own windows
+        tracker.admit_at("bob", "search", &quota, last).unwrap();
+        tracker.admit_at("alice", "weather", &quota, last).unwrap();
+
+        // Which makes room for exactly one more
+        let reset = start + QUOTA_WINDOW;
+        tracker.admit_at("alice", "search", &quota, reset).unwrap();
+        assert_eq!(retry_after(tracker.admit_at("alice", "search", &quota, reset)), 30);
+    }
+
+    #[test]
+    fn test_calls_beyond_the_concurrency_limit_are_rejected() {
+        let tracker = QuotaTracker::new();
+        let quota = PluginQuota { max_calls_per_hour: None, max_concurrent: Some(2) };
+
+        let first = tracker.admit("alice", "search", &quota).unwrap();
+        let _second = tracker.admit("bob", "search", &quota).unwrap();
+        assert_eq!(retry_after(tracker.admit("carol", "search", &quota)), BUSY_RETRY_AFTER_SECS);
+
+        drop(first);
+        assert!(tracker.admit("carol", "search", &quota).unwrap().is_some());
+        assert!(tracker.admit("carol", "search", &PluginQuota::default()).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_a_zero_quota_refuses_calls_without_poisoning_the_tracker() {
+        let tracker = QuotaTracker::new();
+        let zero = PluginQuota { max_calls_per_hour: Some(0), max_concurrent: None };
+        assert!(zero.validate().is_err());
+        assert!(PluginQuota { max_calls_per_hour: None, max_concurrent: Some(0) }.validate().is_err());
+
+        assert_eq!(retry_after(tracker.admit("alice", "search", &zero)), QUOTA_WINDOW.as_secs());
+        assert_eq!(retry_after(tracker.admit("alice", "search", &zero)), QUOTA_WINDOW.as_secs());
+        let one = PluginQuota { max_calls_per_hour: Some(1), max_concurrent: None };
+        tracker.admit("alice", "search", &one).unwrap();
+    }
+
+    #[test]
+    fn test_calls_turned_away_as_busy_use_no_quota() {
+        let tracker = QuotaTracker::new();
+        let quota = PluginQuota { max_calls_per_hour: Some(1), max_concurrent: Some(1) };
+        let now = Instant::now();
+
+        let running = tracker.admit_at("alice", "search", &quota, now).unwrap();
+        tracker.admit_at("bob", "search", &quota, now).unwrap_err();
+        drop(running);
+        tracker.admit_at("bob", "search", &quota, now).unwrap();
+    }
+}
@@ -0,0 +1,314 @@
+//! Recording plugin calls and replaying them against the current module.
+//!
+//! A [`PluginRecorder`] keeps the calls of the plugins it is enabled for in
+//! `<directory>/<plugin>.jsonl`, one [`RecordedCall`] per line: the function,
+//! input and caller, every reply the host functions gave, and the result.
+//! [`WasmPluginManager::replay`](crate::WasmPluginManager::replay) runs the
+//! calls in such a file again with the host functions answered from the
+//! recording, so a call that failed in production runs the same way on a
+//! developer's machine, without its storage or knowledge base.
+//!
+//! Lines carry a `format_version`; readers reject versions they do not know
+//! rather than guess. Byte fields (input, output, host replies) are hex.
+
+use crate::host::HostInteraction;
+use crate::{CallOrigin, PluginContext};
+use chrono::{DateTime, Utc};
+use rusty_ai_common::{AssistantError, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use std::time::Instant;
+use tokio::io::AsyncWriteExt;
+use uuid::Uuid;
+
+/// Version of the replay file format written by this build
+pub const REPLAY_FORMAT_VERSION: u32 = 1;
+
+/// The caller a recorded call ran for
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedContext {
+    pub user_id: String,
+    pub session_id: String,
+    pub request_id: String,
+    #[serde(default)]
+    pub metadata: HashMap<String, String>,
+    #[serde(default)]
+    pub permissions: Vec<String>,
+    #[serde(default)]
+    pub origin: CallOrigin,
+}
+
+impl From<&PluginContext> for RecordedContext {
+    fn from(context: &PluginContext) -> Self {
+        Self {
+            user_id: context.user_id.clone(),
+            session_id: context.session_id.clone(),
+            request_id: context.request_id.clone(),
+            metadata: context.metadata.clone(),
+            permissions: context.permissions.clone(),
+            origin: context.origin,
+        }
+    }
+}
+
+impl RecordedContext {
+    /// A context for running the call again, starting now
+    pub fn to_context(&self) -> PluginContext {
+        PluginContext {
+            user_id: self.user_id.clone(),
+            session_id: self.session_id.clone(),
+            request_id: self.request_id.clone(),
+            metadata: self.metadata.clone(),
+            started_at: Instant::now(),
+            permissions: self.permissions.clone(),
+            origin: self.origin,
+        }
+    }
+}
+
+/// What a call returned
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CallOutcome {
+    Output(#[serde(with = "hex_bytes")] Vec<u8>),
+    Error(String),
+}
+
+impl<'a> From<std::result::Result<&'a [u8], &'a AssistantError>> for CallOutcome {
+    fn from(result: std::result::Result<&'a [u8], &'a AssistantError>) -> Self {
+        match result {
+            Ok(output) => Self::Output(output.to_vec()),
+            Err(e) => Self::Error(e.to_string()),
+        }
+    }
+}
+
+/// One line of a replay file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedCall {
+    pub format_version: u32,
+    pub id: Uuid,
+    pub recorded_at: DateTime<Utc>,
+    /// Plugin name, without a version
+    pub plugin_id: String,
+    /// Version the call ran in
+    pub version: String,
+    pub function: String,
+    #[serde(with = "hex_bytes")]
+    pub input: Vec<u8>,
+    pub context: RecordedContext,
+    /// Host function calls, in the order the plugin made them
+    #[serde(default)]
+    pub host_calls: Vec<HostInteraction>,
+    pub outcome: CallOutcome,
+}
+
+/// How one recorded call went when run again
+#[derive(Debug, Clone, Serialize)]
+pub struct ReplayReport {
+    pub call_id: Uuid,
+    pub function: String,
+    /// Version the call was recorded in and the one it was replayed in
+    pub recorded_version: String,
+    pub replayed_version: String,
+    pub recorded: CallOutcome,
+    pub replayed: CallOutcome,
+    /// Where the replayed result first differs from the recorded one;
+    /// None when they are identical byte for byte
+    pub difference: Option<String>,
+    /// Host calls the replay made differently from the recording, and
+    /// recorded ones it never made
+    pub divergences: Vec<String>,
+}
+
+impl ReplayReport {
+    /// Whether the replay returned the recorded result and made the
+    /// recorded host calls
+    pub fn reproduced(&self) -> bool {
+        self.difference.is_none() && self.divergences.is_empty()
+    }
+}
+
+/// Where `replayed` first strays from `recorded`, None if it does not
+pub fn describe_difference(recorded: &CallOutcome, replayed: &CallOutcome) -> Option<String> {
+    match (recorded, replayed) {
+        (CallOutcome::Output(recorded), CallOutcome::Output(replayed)) => {
+            if recorded == replayed {
+                return None;
+            }
+            let at = recorded.iter().zip(replayed).take_while(|(a, b)| a == b).count();
+            Some(format!(
+                "outputs differ from byte {} (recorded {} bytes, replayed {} bytes): recorded {:?}, replayed {:?}",
+                at,
+                recorded.len(),
+                replayed.len(),
+                String::from_utf8_lossy(&recorded[at..]),
+                String::from_utf8_lossy(&replayed[at..])
+            ))
+        }
+        (CallOutcome::Error(recorded), CallOutcome::Error(replayed)) if recorded == replayed => None,
+        (CallOutcome::Error(recorded), CallOutcome::Error(replayed)) => {
+            Some(format!("recorded error {:?}, replay failed with {:?}", recorded, replayed))
+        }
+        (CallOutcome::Error(recorded), CallOutcome::Output(_)) => {
+            Some(format!("recorded error {:?}, replay returned output", recorded))
+        }
+        (CallOutcome::Output(_), CallOutcome::Error(replayed)) => {
+            Some(format!("recorded output, replay failed with {:?}", replayed))
+        }
+    }
+}
+
+/// Records the calls of the plugins it is enabled for, by plugin name
+pub struct PluginRecorder {
+    directory: PathBuf,
+    enabled: RwLock<HashSet<String>>,
+    /// Appends from concurrent calls never interleave within a line
+    write_lock: tokio::sync::Mutex<()>,
+}
+
+impl PluginRecorder {
+    /// A recorder writing under `directory`, enabled for no plugin yet
+    pub fn new(directory: impl AsRef<Path>) -> Self {
+        Self {
+            directory: directory.as_ref().to_path_buf(),
+            enabled: RwLock::new(HashSet::new()),
+            write_lock: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    pub fn enable(&self, plugin_id: &str) {
+        self.enabled.write().unwrap().insert(plugin_id.to_string());
+    }
+
+    pub fn disable(&self, plugin_id: &str) {
+        self.enabled.write().unwrap().remove(plugin_id);
+    }
+
+    pub fn is_enabled(&self, plugin_id: &str) -> bool {
+        self.enabled.read().unwrap().contains(plugin_id)
+    }
+
+    /// The replay file of a plugin
+    pub fn path_for(&self, plugin_id: &str) -> PathBuf {
+        self.directory.join(format!("{}.jsonl", plugin_id))
+    }
+
+    /// Append a call to its plugin's replay file
+    pub async fn write(&self, call: &RecordedCall) -> Result<PathBuf> {
+        let path = self.path_for(&call.plugin_id);
+        let fail = |e: std::io::Error| AssistantError::Plugin(format!("Failed to write {}: {}", path.display(), e));
+        let mut line = serde_json::to_vec(call)
+            .map_err(|e| AssistantError::Plugin(format!("Failed to serialize recorded call: {}", e)))?;
+        line.push(b'\n');
+
+        let _writing = self.write_lock.lock().await;
+        tokio::fs::create_dir_all(&self.directory).await.map_err(fail)?;
+        let mut file = tokio::fs::OpenOptions::new().create(true).append(true).open(&path).await.map_err(fail)?;
+        file.write_all(&line).await.map_err(fail)?;
+        file.flush().await.map_err(fail)?;
+        Ok(path)
+    }
+}
+
+/// The calls in a replay file, in recorded order
+pub async fn read_recording(path: impl AsRef<Path>) -> Result<Vec<RecordedCall>> {
+    let path = path.as_ref();
+    let text = tokio::fs::read_to_string(path)
+        .await
+        .map_err(|e| AssistantError::Plugin(format!("Failed to read {}: {}", path.display(), e)))?;
+    let mut calls = Vec::new();
+    for (number, line) in text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+        let call: RecordedCall = serde_json::from_str(line).map_err(|e| {
+            AssistantError::Plugin(format!("Malformed recorded call on line {} of {}: {}", number + 1, path.display(), e))
+        })?;
+        if call.format_version != REPLAY_FORMAT_VERSION {
+            return Err(AssistantError::Plugin(format!(
+                "Line {} of {} has replay format {}, this build reads {}",
+                number + 1,
+                path.display(),
+                call.format_version,
+                REPLAY_FORMAT_VERSION
+            )));
+        }
+        calls.push(call);
+    }
+    Ok(calls)
+}
+
+/// Serde for byte fields as lowercase hex
+pub(crate) mod hex_bytes {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&hex::encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        hex::decode(text).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::host::HostReply;
+    use tempfile::tempdir;
+
+    fn recorded_call(plugin_id: &str) -> RecordedCall {
+        RecordedCall {
+            format_version: REPLAY_FORMAT_VERSION,
+            id: Uuid::new_v4(),
+            recorded_at: Utc::now(),
+            plugin_id: plugin_id.to_string(),
+            version: "0.1.0".to_string(),
+            function: "recall".to_string(),
+            input: b"\x00\xffraw".to_vec(),
+            context: RecordedContext {
+                user_id: "user-1".to_string(),
+                session_id: String::new(),
+                request_id: "req-1".to_string(),
+                metadata: HashMap::new(),
+                permissions: vec!["plugins:execute".to_string()],
+                origin: CallOrigin::Api,
+            },
+            host_calls: vec![HostInteraction {
+                function: "kv_get".to_string(),
+                request: Some("note".to_string()),
+                reply: HostReply::Data(b"buy oat milk".to_vec()),
+            }],
+            outcome: CallOutcome::Output(b"buy oat milk".to_vec()),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_recorded_calls_round_trip_through_the_file() {
+        let dir = tempdir().unwrap();
+        let recorder = PluginRecorder::new(dir.path().join("replays"));
+        let (first, second) = (recorded_call("notes"), recorded_call("notes"));
+        let path = recorder.write(&first).await.unwrap();
+        recorder.write(&second).await.unwrap();
+
+        assert_eq!(path, dir.path().join("replays").join("notes.jsonl"));
+        assert_eq!(read_recording(&path).await.unwrap(), vec![first.clone(), second]);
+        let line = std::fs::read_to_string(&path).unwrap();
+        assert!(line.contains(r#""input":"00ff726177""#), "{}", line);
+        assert!(line.contains(r#""reply":{"data":"627579206f6174206d696c6b"}"#), "{}", line);
+
+        let newer = RecordedCall { format_version: REPLAY_FORMAT_VERSION + 1, ..first };
+        std::fs::write(&path, serde_json::to_string(&newer).unwrap()).unwrap();
+        assert!(read_recording(&path).await.unwrap_err().to_string().contains("has replay format 2"));
+    }
+
+    #[test]
+    fn test_differences_name_the_first_differing_byte() {
+        let output = |bytes: &[u8]| CallOutcome::Output(bytes.to_vec());
+        assert_eq!(describe_difference(&output(b"same"), &output(b"same")), None);
+        let difference = describe_difference(&output(b"total: 12"), &output(b"total: 13!")).unwrap();
+        assert!(difference.starts_with("outputs differ from byte 8 (recorded 9 bytes, replayed 10 bytes)"), "{}", difference);
+        assert!(describe_difference(&CallOutcome::Error("boom".to_string()), &output(b"ok")).is_some());
+    }
+}
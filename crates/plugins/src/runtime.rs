@@ -176,6 +176,7 @@ impl WasmRuntime {
         let mut linker = Linker::new(&self.engine);
         wasmtime_wasi::add_to_linker_async(&mut linker)
             .map_err(|e| AssistantError::Plugin(format!("Failed to add WASI to linker: {}", e)))?;
+        crate::host::add_to_linker(&mut linker)?;
         
         // Instantiate module
         let instance = linker.instantiate_async(&mut store, &module).await
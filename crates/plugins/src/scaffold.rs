@@ -0,0 +1,219 @@
+use rusty_ai_common::{AssistantError, Result};
+use std::path::{Path, PathBuf};
+
+// The plugin gets a `[workspace]` table of its own so generating it inside
+// another cargo workspace does not pull it into that workspace
+const CARGO_TOML: &str = r#"[package]
+name = "{{name}}"
+version = "0.1.0"
+edition = "2021"
+
+[lib]
+crate-type = ["cdylib", "rlib"]
+
+[dependencies]
+serde_json = "1"
+
+[profile.release]
+opt-level = "s"
+lto = true
+
+[workspace]
+"#;
+
+const LIB_RS: &str = r#"//! {{name}}: a rusty-ai plugin.
+//!
+//! Build:  cargo build --release --target wasm32-unknown-unknown
+//! Try it: rusty-ai-plugin dev target/wasm32-unknown-unknown/release/{{crate_name}}.wasm
+
+use serde_json::{json, Value};
+
+// ---------------------------------------------------------------------------
+// Host ABI helpers. The host places the JSON input in linear memory through
+// `alloc`, calls an exported function with (ptr, len) and reads the JSON
+// output from the returned i64: pointer in the high 32 bits, length in the
+// low 32. Both buffers are handed back through `dealloc` afterwards.
+// ---------------------------------------------------------------------------
+
+#[no_mangle]
+pub extern "C" fn alloc(len: i32) -> i32 {
+    let mut buffer = Vec::<u8>::with_capacity(len as usize);
+    let ptr = buffer.as_mut_ptr();
+    std::mem::forget(buffer);
+    ptr as i32
+}
+
+/// # Safety
+/// `ptr` and `len` must come from `alloc` or from a function's output.
+#[no_mangle]
+pub unsafe extern "C" fn dealloc(ptr: i32, len: i32) {
+    drop(Vec::from_raw_parts(ptr as *mut u8, 0, len as usize));
+}
+
+/// Identifies the plugin to the host, which keys its registry on the id.
+#[no_mangle]
+pub extern "C" fn get_metadata() -> i64 {
+    respond(&json!({
+        "id": "{{name}}",
+        "name": "{{name}}",
+        "version": env!("CARGO_PKG_VERSION"),
+        "description": "Counts the words and characters in a text",
+    }))
+}
+
+fn respond(value: &Value) -> i64 {
+    let bytes = serde_json::to_vec(value).unwrap_or_default().into_boxed_slice();
+    let len = bytes.len() as u32 as i64;
+    let ptr = Box::into_raw(bytes) as *mut u8 as u32 as i64;
+    (ptr << 32) | len
+}
+
+/// Decode the input, run `handler` and encode its result. Errors come back
+/// as `{"error": "..."}`.
+fn handle(ptr: i32, len: i32, handler: fn(Value) -> Result<Value, String>) -> i64 {
+    let input = unsafe { std::slice::from_raw_parts(ptr as *const u8, len as usize) };
+    let result = serde_json::from_slice(input)
+        .map_err(|e| format!("input is not valid JSON: {}", e))
+        .and_then(handler);
+    match result {
+        Ok(value) => respond(&value),
+        Err(error) => respond(&json!({ "error": error })),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Plugin functions. Declare each one in `list_functions` so the host can
+// show its schema and enforce its permission.
+// ---------------------------------------------------------------------------
+
+#[no_mangle]
+pub extern "C" fn list_functions(ptr: i32, len: i32) -> i64 {
+    handle(ptr, len, |_| {
+        Ok(json!({
+            "functions": [{
+                "name": "analyze_text",
+                "description": "Counts the words and characters in a text",
+                "input_schema": {
+                    "type": "object",
+                    "properties": { "text": { "type": "string" } },
+                    "required": ["text"]
+                },
+                "output_schema": {
+                    "type": "object",
+                    "properties": {
+                        "words": { "type": "integer" },
+                        "characters": { "type": "integer" }
+                    }
+                },
+                "public": true
+            }]
+        }))
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn analyze_text(ptr: i32, len: i32) -> i64 {
+    handle(ptr, len, analyze)
+}
+
+fn analyze(input: Value) -> Result<Value, String> {
+    let text = input
+        .get("text")
+        .and_then(Value::as_str)
+        .ok_or("expected a \"text\" string")?;
+    Ok(json!({
+        "words": text.split_whitespace().count(),
+        "characters": text.chars().count(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn analyze_counts_words_and_characters() {
+        let output = analyze(json!({ "text": "hello plugin world" })).unwrap();
+        assert_eq!(output, json!({ "words": 3, "characters": 18 }));
+        assert!(analyze(json!({})).is_err());
+    }
+}
+"#;
+
+const GITIGNORE: &str = "/target\nCargo.lock\n";
+
+/// Generate a minimal Rust plugin project named `name` under `parent`,
+/// returning the project directory
+pub fn scaffold_plugin(name: &str, parent: impl AsRef<Path>) -> Result<PathBuf> {
+    validate_name(name)?;
+
+    let root = parent.as_ref().join(name);
+    if root.exists() {
+        return Err(AssistantError::Plugin(format!("{} already exists", root.display())));
+    }
+
+    let crate_name = name.replace('-', "_");
+    let render = |template: &str| template.replace("{{name}}", name).replace("{{crate_name}}", &crate_name);
+    let write = |relative: &str, contents: String| {
+        let path = root.join(relative);
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .map_err(|e| AssistantError::Plugin(format!("Failed to create {}: {}", dir.display(), e)))?;
+        }
+        std::fs::write(&path, contents)
+            .map_err(|e| AssistantError::Plugin(format!("Failed to write {}: {}", path.display(), e)))
+    };
+
+    write("Cargo.toml", render(CARGO_TOML))?;
+    write("src/lib.rs", render(LIB_RS))?;
+    write(".gitignore", GITIGNORE.to_string())?;
+    Ok(root)
+}
+
+// Cargo package names: ASCII letters, digits, '-' and '_', starting with a letter
+fn validate_name(name: &str) -> Result<()> {
+    let valid = name.chars().next().map_or(false, |c| c.is_ascii_alphabetic())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(AssistantError::Plugin(format!(
+            "'{}' is not a valid plugin name: use letters, digits, '-' and '_', starting with a letter",
+            name
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_scaffold_writes_a_buildable_project() {
+        let dir = tempdir().unwrap();
+        let root = scaffold_plugin("word-counter", dir.path()).unwrap();
+
+        let manifest = std::fs::read_to_string(root.join("Cargo.toml")).unwrap();
+        assert!(manifest.contains(r#"name = "word-counter""#));
+        assert!(manifest.contains(r#"crate-type = ["cdylib", "rlib"]"#));
+
+        let lib = std::fs::read_to_string(root.join("src/lib.rs")).unwrap();
+        assert!(lib.contains("release/word_counter.wasm"));
+        for export in ["fn alloc", "fn dealloc", "fn get_metadata", "fn list_functions", "fn analyze_text"] {
+            assert!(lib.contains(export), "missing {}", export);
+        }
+        assert!(!lib.contains("{{"));
+
+        // Never overwrites an existing project
+        assert!(scaffold_plugin("word-counter", dir.path()).is_err());
+    }
+
+    #[test]
+    fn test_scaffold_rejects_invalid_names() {
+        let dir = tempdir().unwrap();
+        for name in ["", "1plugin", "../escape", "my plugin"] {
+            assert!(scaffold_plugin(name, dir.path()).is_err(), "accepted {:?}", name);
+        }
+    }
+}
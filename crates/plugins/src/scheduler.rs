@@ -0,0 +1,414 @@
+//! Plugin functions run on a cron schedule.
+//!
+//! [`PluginScheduler`] keeps the registered jobs in a [`PluginScheduleStore`]
+//! and, each time `run_due` is called, starts the jobs whose next run has
+//! come through `WasmPluginManager::execute_plugin`. Calls are made as
+//! [`SCHEDULER_USER`] with the baseline plugin permission, so the
+//! manager's permission checks, quotas and audit log apply as they do to
+//! any other call.
+//!
+//! A job is never started while its previous run is still going; that run
+//! is skipped. Runs that fell due while the process was down follow the
+//! job's [`CatchUpPolicy`]: `skip` waits for the next run, `run_once` runs
+//! once straight away however many were missed. Either way a run due within
+//! [`MISSED_RUN_GRACE`] of the check counts as on time, not as missed.
+
+use crate::cron::CronSchedule;
+use crate::{CallOrigin, PluginContext, WasmPluginManager};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use rusty_ai_common::{AssistantError, CatchUpPolicy, PluginSchedule, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+/// The user scheduled calls are made as
+pub const SCHEDULER_USER: &str = "scheduler";
+
+/// How late a run may start and still count as on time
+pub const MISSED_RUN_GRACE: chrono::Duration = chrono::Duration::minutes(2);
+
+/// Where the scheduler keeps its jobs, so they outlive the process
+#[async_trait]
+pub trait PluginScheduleStore: Send + Sync {
+    /// Add the job, or replace the one with the same id
+    async fn save(&self, schedule: &PluginSchedule) -> Result<()>;
+    async fn load(&self) -> Result<Vec<PluginSchedule>>;
+    /// Whether there was a job to remove
+    async fn remove(&self, id: Uuid) -> Result<bool>;
+}
+
+/// A job to schedule
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewPluginSchedule {
+    pub plugin_id: String,
+    pub function: String,
+    /// Sent to the function as JSON on every run
+    #[serde(default)]
+    pub input: serde_json::Value,
+    /// When to run, see [`crate::cron`]
+    pub cron: String,
+    #[serde(default)]
+    pub catch_up: CatchUpPolicy,
+}
+
+/// Runs plugin functions on their schedules
+pub struct PluginScheduler {
+    plugins: Arc<WasmPluginManager>,
+    store: Arc<dyn PluginScheduleStore>,
+    jobs: Mutex<HashMap<Uuid, ScheduledJob>>,
+}
+
+struct ScheduledJob {
+    schedule: PluginSchedule,
+    cron: CronSchedule,
+    /// Set while a run of the job is in progress
+    running: Arc<AtomicBool>,
+}
+
+impl PluginScheduler {
+    pub fn new(plugins: Arc<WasmPluginManager>, store: Arc<dyn PluginScheduleStore>) -> Self {
+        Self { plugins, store, jobs: Mutex::new(HashMap::new()) }
+    }
+
+    /// Take up the jobs kept in the store, e.g. after a restart. Jobs whose
+    /// cron expression no longer parses are left out. Returns how many were
+    /// taken up
+    pub async fn load(&self) -> Result<usize> {
+        let mut jobs = self.jobs.lock().await;
+        for schedule in self.store.load().await? {
+            match schedule.cron.parse::<CronSchedule>() {
+                Ok(cron) => {
+                    jobs.insert(schedule.id, ScheduledJob { schedule, cron, running: Arc::default() });
+                }
+                Err(e) => warn!("Not scheduling plugin job {}: {}", schedule.id, e),
+            }
+        }
+        Ok(jobs.len())
+    }
+
+    /// Schedule a job, first running at the next time its cron expression
+    /// matches
+    pub async fn add(&self, job: NewPluginSchedule) -> Result<PluginSchedule> {
+        let cron: CronSchedule = job.cron.parse()?;
+        let now = Utc::now();
+        let next_run_at = cron
+            .next_after(now)
+            .ok_or_else(|| AssistantError::Api(format!("Cron expression '{}' never fires", job.cron)))?;
+        let schedule = PluginSchedule {
+            id: Uuid::new_v4(),
+            plugin_id: job.plugin_id,
+            function: job.function,
+            input: job.input,
+            cron: cron.to_string(),
+            catch_up: job.catch_up,
+            next_run_at,
+            last_run_at: None,
+            created_at: now,
+        };
+        self.store.save(&schedule).await?;
+        info!("Scheduled {}::{} at '{}'", schedule.plugin_id, schedule.function, schedule.cron);
+
+        let scheduled = ScheduledJob { schedule: schedule.clone(), cron, running: Arc::default() };
+        self.jobs.lock().await.insert(schedule.id, scheduled);
+        Ok(schedule)
+    }
+
+    /// Every job, oldest first
+    pub async fn list(&self) -> Vec<PluginSchedule> {
+        let mut schedules: Vec<PluginSchedule> = self.jobs.lock().await.values().map(|job| job.schedule.clone()).collect();
+        schedules.sort_by_key(|schedule| schedule.created_at);
+        schedules
+    }
+
+    /// Unschedule a job. A run in progress finishes. Returns whether there
+    /// was such a job
+    pub async fn remove(&self, id: Uuid) -> Result<bool> {
+        let removed = self.store.remove(id).await?;
+        Ok(self.jobs.lock().await.remove(&id).is_some() || removed)
+    }
+
+    /// Start the jobs due at `now` and move every due job on to its next
+    /// run. Returns the runs started, which finish in the background
+    pub async fn run_due(&self, now: DateTime<Utc>) -> Vec<JoinHandle<()>> {
+        let mut started = Vec::new();
+        let mut jobs = self.jobs.lock().await;
+        for job in jobs.values_mut().filter(|job| job.schedule.next_run_at <= now) {
+            let planned = job.schedule.next_run_at;
+            // The latest run due within the grace period, if there is one
+            let on_time = job.cron.next_after(now - MISSED_RUN_GRACE).filter(|due| *due <= now);
+            let run = match (on_time, job.schedule.catch_up) {
+                (Some(_), _) => true,
+                (None, CatchUpPolicy::RunOnce) => {
+                    info!("Plugin job {} missed its runs since {}; running it once", job.schedule.id, planned);
+                    true
+                }
+                (None, CatchUpPolicy::Skip) => {
+                    info!("Plugin job {} missed its runs since {}; skipping them", job.schedule.id, planned);
+                    false
+                }
+            };
+
+            let Some(next_run_at) = job.cron.next_after(now) else {
+                warn!("Plugin job {} has no further runs", job.schedule.id);
+                continue;
+            };
+            job.schedule.next_run_at = next_run_at;
+
+            if run {
+                if job.running.swap(true, Ordering::SeqCst) {
+                    warn!("Plugin job {} is still running; skipping the run due at {}", job.schedule.id, planned);
+                } else {
+                    job.schedule.last_run_at = Some(now);
+                    started.push(self.start(&job.schedule, on_time.unwrap_or(planned), job.running.clone()));
+                }
+            }
+
+            if let Err(e) = self.store.save(&job.schedule).await {
+                warn!("Failed to save the next run of plugin job {}: {}", job.schedule.id, e);
+            }
+        }
+        started
+    }
+
+    fn start(&self, schedule: &PluginSchedule, due: DateTime<Utc>, running: Arc<AtomicBool>) -> JoinHandle<()> {
+        let plugins = self.plugins.clone();
+        let schedule = schedule.clone();
+        let context = PluginContext {
+            user_id: SCHEDULER_USER.to_string(),
+            session_id: format!("schedule:{}", schedule.id),
+            request_id: Uuid::new_v4().to_string(),
+            metadata: HashMap::from([
+                ("schedule_id".to_string(), schedule.id.to_string()),
+                ("scheduled_for".to_string(), due.to_rfc3339()),
+            ]),
+            started_at: Instant::now(),
+            permissions: vec![plugins.permission_policy().baseline_permission],
+            origin: CallOrigin::Api,
+        };
+
+        tokio::spawn(async move {
+            let input = schedule.input.to_string();
+            match plugins.execute_plugin(&schedule.plugin_id, &schedule.function, input.as_bytes(), context).await {
+                Ok(output) => debug!(
+                    "Plugin job {} ({}::{}) returned {} bytes",
+                    schedule.id,
+                    schedule.plugin_id,
+                    schedule.function,
+                    output.len()
+                ),
+                Err(e) => warn!("Plugin job {} ({}::{}) failed: {}", schedule.id, schedule.plugin_id, schedule.function, e),
+            }
+            running.store(false, Ordering::SeqCst);
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{PluginHealth, WasmPlugin, WasmPluginMetadata};
+    use tempfile::tempdir;
+    use tokio::sync::Semaphore;
+
+    #[derive(Default)]
+    struct MemoryScheduleStore(std::sync::Mutex<HashMap<Uuid, PluginSchedule>>);
+
+    #[async_trait]
+    impl PluginScheduleStore for MemoryScheduleStore {
+        async fn save(&self, schedule: &PluginSchedule) -> Result<()> {
+            self.0.lock().unwrap().insert(schedule.id, schedule.clone());
+            Ok(())
+        }
+
+        async fn load(&self) -> Result<Vec<PluginSchedule>> {
+            Ok(self.0.lock().unwrap().values().cloned().collect())
+        }
+
+        async fn remove(&self, id: Uuid) -> Result<bool> {
+            Ok(self.0.lock().unwrap().remove(&id).is_some())
+        }
+    }
+
+    // Records the inputs it is called with; each call waits for a permit
+    struct GatedPlugin {
+        metadata: WasmPluginMetadata,
+        gate: Arc<Semaphore>,
+        inputs: Arc<std::sync::Mutex<Vec<String>>>,
+    }
+
+    #[async_trait]
+    impl WasmPlugin for GatedPlugin {
+        fn metadata(&self) -> &WasmPluginMetadata {
+            &self.metadata
+        }
+
+        async fn initialize(&mut self, _config: serde_json::Value) -> Result<()> {
+            Ok(())
+        }
+
+        async fn execute(&self, _function: &str, input: &[u8], context: &PluginContext) -> Result<Vec<u8>> {
+            assert_eq!(context.user_id, SCHEDULER_USER);
+            self.inputs.lock().unwrap().push(String::from_utf8_lossy(input).into_owned());
+            self.gate.acquire().await.unwrap().forget();
+            Ok(Vec::new())
+        }
+
+        fn can_handle(&self, _capability: &str) -> bool {
+            false
+        }
+
+        async fn health_check(&self) -> Result<PluginHealth> {
+            Err(AssistantError::Plugin("not checked in these tests".to_string()))
+        }
+
+        async fn cleanup(&mut self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    struct Fixture {
+        _dir: tempfile::TempDir,
+        plugins: Arc<WasmPluginManager>,
+        store: Arc<MemoryScheduleStore>,
+        gate: Arc<Semaphore>,
+        inputs: Arc<std::sync::Mutex<Vec<String>>>,
+    }
+
+    impl Fixture {
+        async fn new() -> Self {
+            let dir = tempdir().unwrap();
+            let plugins = Arc::new(WasmPluginManager::new(dir.path()).unwrap());
+            let gate = Arc::new(Semaphore::new(0));
+            let inputs = Arc::new(std::sync::Mutex::new(Vec::new()));
+            let metadata = WasmPluginMetadata {
+                id: "rss".to_string(),
+                name: "rss".to_string(),
+                version: "1".to_string(),
+                description: String::new(),
+                author: String::new(),
+                license: String::new(),
+                capabilities: vec![],
+                dependencies: vec![],
+                api_version: "1.0".to_string(),
+                checksum: String::new(),
+                function_limits: HashMap::new(),
+                events: vec![],
+            };
+            let plugin = GatedPlugin { metadata, gate: gate.clone(), inputs: inputs.clone() };
+            plugins.register_plugin("rss", Box::new(plugin)).await.unwrap();
+            Self { _dir: dir, plugins, store: Arc::default(), gate, inputs }
+        }
+
+        fn scheduler(&self) -> PluginScheduler {
+            PluginScheduler::new(self.plugins.clone(), self.store.clone())
+        }
+
+        fn calls(&self) -> usize {
+            self.inputs.lock().unwrap().len()
+        }
+    }
+
+    fn job(catch_up: CatchUpPolicy) -> NewPluginSchedule {
+        NewPluginSchedule {
+            plugin_id: "rss".to_string(),
+            function: "fetch".to_string(),
+            input: serde_json::json!({"feed": "news"}),
+            cron: "*/30 * * * *".to_string(),
+            catch_up,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_due_jobs_run_without_stacking_overlapping_runs() {
+        let fixture = Fixture::new().await;
+        let scheduler = fixture.scheduler();
+        let schedule = scheduler.add(job(CatchUpPolicy::Skip)).await.unwrap();
+        let first = schedule.next_run_at;
+
+        assert!(scheduler.run_due(first - chrono::Duration::seconds(1)).await.is_empty());
+        let started = scheduler.run_due(first).await;
+        assert_eq!(started.len(), 1);
+
+        // The first run is still going when the next one is due
+        let second = first + chrono::Duration::minutes(30);
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        assert!(scheduler.run_due(second).await.is_empty());
+        assert_eq!(scheduler.list().await[0].next_run_at, second + chrono::Duration::minutes(30));
+
+        fixture.gate.add_permits(1);
+        for run in started {
+            run.await.unwrap();
+        }
+        fixture.gate.add_permits(1);
+        for run in scheduler.run_due(second + chrono::Duration::minutes(30)).await {
+            run.await.unwrap();
+        }
+        assert_eq!(*fixture.inputs.lock().unwrap(), vec![r#"{"feed":"news"}"#; 2]);
+
+        // Each run is saved, so a restart picks up where it left off
+        let saved = fixture.store.load().await.unwrap();
+        assert_eq!(saved[0].last_run_at, Some(second + chrono::Duration::minutes(30)));
+        assert_eq!(saved[0].next_run_at, second + chrono::Duration::minutes(60));
+    }
+
+    #[tokio::test]
+    async fn test_missed_runs_follow_the_catch_up_policy() {
+        let fixture = Fixture::new().await;
+        let before = fixture.scheduler();
+        let skipped = before.add(job(CatchUpPolicy::Skip)).await.unwrap();
+        let caught_up = before.add(job(CatchUpPolicy::RunOnce)).await.unwrap();
+
+        // Restarted a day later, ten minutes past a scheduled time
+        let scheduler = fixture.scheduler();
+        assert_eq!(scheduler.load().await.unwrap(), 2);
+        let now = skipped.next_run_at + chrono::Duration::days(1) + chrono::Duration::minutes(10);
+        fixture.gate.add_permits(1);
+        for run in scheduler.run_due(now).await {
+            run.await.unwrap();
+        }
+        assert_eq!(fixture.calls(), 1);
+
+        let schedules: HashMap<Uuid, PluginSchedule> =
+            scheduler.list().await.into_iter().map(|schedule| (schedule.id, schedule)).collect();
+        assert_eq!(schedules[&skipped.id].last_run_at, None);
+        assert_eq!(schedules[&caught_up.id].last_run_at, Some(now));
+        for schedule in schedules.values() {
+            assert_eq!(schedule.next_run_at, now + chrono::Duration::minutes(20));
+        }
+
+        // A run only just due is on time, whatever the policy
+        let on_time = now + chrono::Duration::minutes(21);
+        fixture.gate.add_permits(2);
+        for run in scheduler.run_due(on_time).await {
+            run.await.unwrap();
+        }
+        assert_eq!(fixture.calls(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_jobs_are_validated_persisted_and_removed() {
+        let fixture = Fixture::new().await;
+        let scheduler = fixture.scheduler();
+
+        let never = NewPluginSchedule { cron: "0 0 30 2 *".to_string(), ..job(CatchUpPolicy::Skip) };
+        assert!(matches!(scheduler.add(never).await, Err(AssistantError::Api(_))));
+        let invalid = NewPluginSchedule { cron: "every hour".to_string(), ..job(CatchUpPolicy::Skip) };
+        assert!(matches!(scheduler.add(invalid).await, Err(AssistantError::Api(_))));
+
+        let schedule = scheduler.add(job(CatchUpPolicy::Skip)).await.unwrap();
+        assert_eq!(scheduler.list().await, vec![schedule.clone()]);
+        assert_eq!(fixture.store.load().await.unwrap(), vec![schedule.clone()]);
+
+        assert!(scheduler.remove(schedule.id).await.unwrap());
+        assert!(!scheduler.remove(schedule.id).await.unwrap());
+        assert!(scheduler.list().await.is_empty());
+        assert!(fixture.store.load().await.unwrap().is_empty());
+    }
+}
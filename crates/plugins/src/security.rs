@@ -1,8 +1,11 @@
-use crate::ResourceLimits;
+use crate::{ExecutionReport, ResourceLimits};
 use rusty_ai_common::{Result, AssistantError};
 use std::collections::{HashMap, HashSet};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::time::{Duration, Instant};
+use ring::signature;
+use sha2::{Digest, Sha256};
+use serde::{Deserialize, Serialize};
 use tracing::{warn, error, debug, instrument};
 use wasmtime::*;
 
@@ -27,6 +30,108 @@ pub struct SecurityPolicy {
     pub require_signature: bool,
     /// Trusted plugin authors
     pub trusted_authors: HashSet<String>,
+    /// Public keys plugin signatures are checked against, by key id. A key
+    /// only vouches for plugins whose declared author is its author and is
+    /// in `trusted_authors`
+    pub trusted_keys: HashMap<String, TrustedKey>,
+    /// Host capabilities a plugin is granted when it also declares them,
+    /// e.g. [`KNOWLEDGE_ACCESS`]
+    pub allowed_host_capabilities: HashSet<String>,
+}
+
+/// Capability a plugin declares to use the `knowledge_search` host import
+pub const KNOWLEDGE_ACCESS: &str = "knowledge_access";
+
+impl SecurityPolicy {
+    /// Whether a plugin declaring `declared` may use the host capability
+    pub fn grants(&self, declared: &[String], capability: &str) -> bool {
+        self.allowed_host_capabilities.contains(capability) && declared.iter().any(|c| c == capability)
+    }
+}
+
+/// A public key allowed to sign plugins
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrustedKey {
+    pub author: String,
+    /// Hex-encoded Ed25519 public key
+    pub public_key: String,
+}
+
+/// Detached plugin signature, stored as JSON in a `.sig` file next to the
+/// module (`weather.wasm` is signed by `weather.sig`)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PluginSignature {
+    /// Id of the signing key in `SecurityPolicy::trusted_keys`
+    pub key_id: String,
+    /// Hex-encoded Ed25519 signature over the module bytes
+    pub signature: String,
+}
+
+impl PluginSignature {
+    /// Path of the signature file for the module at `module_path`
+    pub fn path_for(module_path: &Path) -> PathBuf {
+        module_path.with_extension(SIGNATURE_EXTENSION)
+    }
+
+    /// The signature next to the module at `module_path`, if there is one
+    pub async fn read_for(module_path: &Path) -> Result<Option<Self>> {
+        let path = Self::path_for(module_path);
+        let bytes = match tokio::fs::read(&path).await {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(AssistantError::Plugin(format!("Failed to read {}: {}", path.display(), e))),
+        };
+        serde_json::from_slice(&bytes)
+            .map(Some)
+            .map_err(|e| AssistantError::Security(format!("Malformed plugin signature {}: {}", path.display(), e)))
+    }
+
+    /// Write the signature next to the module at `module_path`
+    pub async fn write_for(&self, module_path: &Path) -> Result<()> {
+        let path = Self::path_for(module_path);
+        let json = serde_json::to_vec_pretty(self)
+            .map_err(|e| AssistantError::Plugin(format!("Failed to serialize plugin signature: {}", e)))?;
+        tokio::fs::write(&path, json)
+            .await
+            .map_err(|e| AssistantError::Plugin(format!("Failed to write {}: {}", path.display(), e)))
+    }
+}
+
+/// File extension of detached plugin signatures
+pub const SIGNATURE_EXTENSION: &str = "sig";
+
+/// Result of checking a plugin's signature, kept in the plugin registry
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum SignatureStatus {
+    /// Not checked, e.g. a discovered plugin that has not been loaded
+    #[default]
+    Unchecked,
+    Unsigned,
+    Verified { key_id: String, author: String },
+    /// Signed, but not by a trusted key or not over these bytes
+    Invalid { reason: String },
+}
+
+/// Sign `wasm_bytes` with the Ed25519 key in `pkcs8` (as produced by
+/// `ring::signature::Ed25519KeyPair::generate_pkcs8`). Write the result with
+/// `PluginSignature::write_for` and add the public key to the host's
+/// `trusted_keys` under `key_id`
+pub fn sign_plugin(wasm_bytes: &[u8], key_id: &str, pkcs8: &[u8]) -> Result<PluginSignature> {
+    let key_pair = signature::Ed25519KeyPair::from_pkcs8(pkcs8)
+        .map_err(|e| AssistantError::Security(format!("Invalid Ed25519 signing key: {}", e)))?;
+    Ok(PluginSignature {
+        key_id: key_id.to_string(),
+        signature: hex::encode(key_pair.sign(wasm_bytes).as_ref()),
+    })
+}
+
+/// Hex-encoded public key of the Ed25519 key in `pkcs8`, for `TrustedKey`
+pub fn signing_public_key(pkcs8: &[u8]) -> Result<String> {
+    use signature::KeyPair;
+    let key_pair = signature::Ed25519KeyPair::from_pkcs8(pkcs8)
+        .map_err(|e| AssistantError::Security(format!("Invalid Ed25519 signing key: {}", e)))?;
+    Ok(hex::encode(key_pair.public_key().as_ref()))
 }
 
 /// WASI capabilities that can be granted to plugins
@@ -56,6 +161,8 @@ impl Default for SecurityPolicy {
             disable_dangerous_features: true,
             require_signature: false,
             trusted_authors: HashSet::new(),
+            trusted_keys: HashMap::new(),
+            allowed_host_capabilities: [KNOWLEDGE_ACCESS.to_string()].into_iter().collect(),
         }
     }
 }
@@ -99,7 +206,24 @@ pub enum SecurityViolation {
     ProhibitedWasiCall(String),
     ResourceLimitExceeded(String),
     UntrustedPlugin(String),
-    InvalidSignature,
+    InvalidSignature(String),
+}
+
+impl SecurityViolation {
+    /// The error reported for this violation by plugin `plugin_id`
+    pub fn into_error(self, plugin_id: &str) -> AssistantError {
+        let detail = match self {
+            SecurityViolation::ExcessiveMemoryUsage(bytes) => format!("excessive memory usage: {} bytes", bytes),
+            SecurityViolation::ExcessiveCpuTime(time) => format!("excessive CPU time: {:?}", time),
+            SecurityViolation::UnauthorizedFileAccess(path) => format!("unauthorized file access: {:?}", path),
+            SecurityViolation::UnauthorizedNetworkAccess(host) => format!("unauthorized network access: {}", host),
+            SecurityViolation::ProhibitedWasiCall(call) => format!("prohibited WASI call: {}", call),
+            SecurityViolation::ResourceLimitExceeded(limit) => format!("resource limit exceeded: {}", limit),
+            SecurityViolation::UntrustedPlugin(reason) => format!("untrusted plugin: {}", reason),
+            SecurityViolation::InvalidSignature(reason) => format!("invalid signature: {}", reason),
+        };
+        AssistantError::Security(format!("Plugin {} rejected, {}", plugin_id, detail))
+    }
 }
 
 impl PluginSandbox {
@@ -112,42 +236,179 @@ impl PluginSandbox {
         }
     }
     
-    /// Validate plugin against security policy
-    #[instrument(skip(self, wasm_bytes))]
-    pub fn validate_plugin(&self, wasm_bytes: &[u8], metadata: &crate::WasmPluginMetadata) -> Result<()> {
-        debug!("Validating plugin security: {}", metadata.id);
-        
-        // Check if plugin author is trusted
-        if self.policy.require_signature && !self.policy.trusted_authors.contains(&metadata.author) {
-            return Err(AssistantError::Security(
-                format!("Plugin author not trusted: {}", metadata.author)
-            ));
+    /// Validate plugin against security policy. `signature` is the
+    /// plugin's detached signature, if it has one; the returned status is
+    /// what checking it found. A rejection counts as a security violation
+    #[instrument(skip(self, wasm_bytes, signature))]
+    pub fn validate_plugin(
+        &mut self,
+        wasm_bytes: &[u8],
+        metadata: &crate::WasmPluginMetadata,
+        signature: Option<&PluginSignature>,
+    ) -> Result<SignatureStatus> {
+        let module = Module::new(&Engine::default(), wasm_bytes)
+            .map_err(|e| AssistantError::Security(format!("Invalid WebAssembly module: {}", e)));
+        let result = module.and_then(|module| self.check_module(&metadata.id, wasm_bytes, &module, Some(metadata), signature));
+        self.record(&metadata.id, result)
+    }
+    
+    /// Validate an already compiled module before any of its code runs,
+    /// start function included, with the metadata in its custom section.
+    /// Without the section the signature is checked against the signing key
+    /// alone, and [`validate_metadata`](Self::validate_metadata) checks the
+    /// rest once the plugin's `get_metadata` export has answered
+    pub fn validate_module(
+        &mut self,
+        plugin_id: &str,
+        wasm_bytes: &[u8],
+        module: &Module,
+        signature: Option<&PluginSignature>,
+    ) -> Result<SignatureStatus> {
+        let metadata = crate::metadata::from_custom_section(wasm_bytes)?;
+        let result = self.check_module(plugin_id, wasm_bytes, module, metadata.as_ref(), signature);
+        self.record(plugin_id, result)
+    }
+    
+    /// Check the metadata of the instantiated plugin against the status
+    /// `validate_module` returned: the signing key must sign for the
+    /// declared author and the declared capabilities must be allowed
+    pub fn validate_metadata(
+        &mut self,
+        metadata: &crate::WasmPluginMetadata,
+        status: SignatureStatus,
+    ) -> Result<SignatureStatus> {
+        let status = match status {
+            SignatureStatus::Verified { key_id, author } if author != metadata.author => SignatureStatus::Invalid {
+                reason: format!("key {} signs for {}, but the plugin declares author {}", key_id, author, metadata.author),
+            },
+            status => status,
+        };
+        let result = self
+            .require_signature(&metadata.id, &status)
+            .and_then(|()| self.validate_capabilities(&metadata.capabilities))
+            .map(|()| status);
+        self.record(&metadata.id, result)
+    }
+    
+    /// Run `check` against `policy` instead of the sandbox's own, e.g. the
+    /// one a plugin's trust level calls for
+    pub fn under<T>(&mut self, policy: SecurityPolicy, check: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        let own = std::mem::replace(&mut self.policy, policy);
+        let result = check(self);
+        self.policy = own;
+        result
+    }
+    
+    /// Count a rejection as a security violation
+    fn record<T>(&mut self, plugin_id: &str, result: Result<T>) -> Result<T> {
+        if let Err(AssistantError::Security(reason)) = &result {
+            warn!("Plugin {} failed security validation: {}", plugin_id, reason);
+            self.execution_stats.security_violations += 1;
         }
+        result
+    }
+    
+    fn check_module(
+        &self,
+        plugin_id: &str,
+        wasm_bytes: &[u8],
+        module: &Module,
+        metadata: Option<&crate::WasmPluginMetadata>,
+        signature: Option<&PluginSignature>,
+    ) -> Result<SignatureStatus> {
+        debug!("Validating plugin security: {}", plugin_id);
+        
+        let status = self.verify_signature(wasm_bytes, metadata.map(|metadata| metadata.author.as_str()), signature);
+        self.require_signature(plugin_id, &status)?;
         
         // Validate WebAssembly module
-        self.validate_wasm_module(wasm_bytes)?;
+        self.validate_wasm_module(module)?;
         
         // Check plugin capabilities against policy
-        self.validate_capabilities(&metadata.capabilities)?;
+        if let Some(metadata) = metadata {
+            self.validate_capabilities(&metadata.capabilities)?;
+        }
         
-        debug!("Plugin validation successful: {}", metadata.id);
+        debug!("Plugin validation successful: {}", plugin_id);
+        Ok(status)
+    }
+    
+    /// Reject a plugin whose signature the policy requires and `status`
+    /// does not vouch for
+    fn require_signature(&self, plugin_id: &str, status: &SignatureStatus) -> Result<()> {
+        if self.policy.require_signature {
+            let reason = match status {
+                SignatureStatus::Verified { .. } => None,
+                SignatureStatus::Invalid { reason } => Some(reason.as_str()),
+                SignatureStatus::Unsigned | SignatureStatus::Unchecked => Some("plugin is not signed"),
+            };
+            if let Some(reason) = reason {
+                return Err(SecurityViolation::InvalidSignature(reason.to_string()).into_error(plugin_id));
+            }
+        } else if let SignatureStatus::Invalid { reason } = status {
+            warn!("Plugin {} has an invalid signature, loading it as signatures are not required: {}", plugin_id, reason);
+        }
         Ok(())
     }
     
-    /// Validate WebAssembly module structure
-    fn validate_wasm_module(&self, wasm_bytes: &[u8]) -> Result<()> {
-        let engine = Engine::default();
-        let module = Module::new(&engine, wasm_bytes)
-            .map_err(|e| AssistantError::Security(format!("Invalid WebAssembly module: {}", e)))?;
+    /// Check a detached signature against the trusted keys and authors
+    pub fn check_signature(
+        &self,
+        wasm_bytes: &[u8],
+        metadata: &crate::WasmPluginMetadata,
+        plugin_signature: Option<&PluginSignature>,
+    ) -> SignatureStatus {
+        self.verify_signature(wasm_bytes, Some(&metadata.author), plugin_signature)
+    }
+    
+    /// As `check_signature`; without the declared `author` the key's own
+    /// author is not compared against it
+    fn verify_signature(
+        &self,
+        wasm_bytes: &[u8],
+        author: Option<&str>,
+        plugin_signature: Option<&PluginSignature>,
+    ) -> SignatureStatus {
+        let Some(plugin_signature) = plugin_signature else {
+            return SignatureStatus::Unsigned;
+        };
+        let invalid = |reason: String| SignatureStatus::Invalid { reason };
+        
+        let Some(key) = self.policy.trusted_keys.get(&plugin_signature.key_id) else {
+            return invalid(format!("unknown signing key {}", plugin_signature.key_id));
+        };
+        if let Some(author) = author.filter(|author| *author != key.author) {
+            return invalid(format!(
+                "key {} signs for {}, but the plugin declares author {}",
+                plugin_signature.key_id, key.author, author
+            ));
+        }
+        if !self.policy.trusted_authors.contains(&key.author) {
+            return invalid(format!("plugin author not trusted: {}", key.author));
+        }
         
+        let (Ok(public_key), Ok(bytes)) = (hex::decode(&key.public_key), hex::decode(&plugin_signature.signature)) else {
+            return invalid("signature or public key is not valid hex".to_string());
+        };
+        match signature::UnparsedPublicKey::new(&signature::ED25519, public_key).verify(wasm_bytes, &bytes) {
+            Ok(()) => SignatureStatus::Verified {
+                key_id: plugin_signature.key_id.clone(),
+                author: key.author.clone(),
+            },
+            Err(_) => invalid("signature does not match the module".to_string()),
+        }
+    }
+    
+    /// Validate WebAssembly module structure
+    fn validate_wasm_module(&self, module: &Module) -> Result<()> {
         if self.policy.disable_dangerous_features {
             // Check for prohibited features
-            self.check_prohibited_features(&module)?;
+            self.check_prohibited_features(module)?;
         }
         
         // Validate imports and exports
-        self.validate_imports(&module)?;
-        self.validate_exports(&module)?;
+        self.validate_imports(module)?;
+        self.validate_exports(module)?;
         
         Ok(())
     }
@@ -191,7 +452,10 @@ impl PluginSandbox {
     /// Validate WASI import against security policy
     fn validate_wasi_import(&self, import_name: &str) -> Result<()> {
         let capability = match import_name {
-            "fd_read" | "fd_write" | "fd_close" | "path_open" => WasiCapability::FileSystem,
+            // Without `path_open` the only descriptors a plugin can write to
+            // are its stdout and stderr, which the host captures
+            "fd_write" => return Ok(()),
+            "fd_read" | "fd_close" | "path_open" => WasiCapability::FileSystem,
             "sock_accept" | "sock_recv" | "sock_send" => WasiCapability::Network,
             "environ_get" | "environ_sizes_get" => WasiCapability::EnvironmentVariables,
             "args_get" | "args_sizes_get" => WasiCapability::CommandLineArguments,
@@ -217,7 +481,7 @@ impl PluginSandbox {
     /// Check if import module is allowed
     fn is_allowed_import_module(&self, module_name: &str) -> bool {
         // Allow common safe modules
-        matches!(module_name, "wasi_snapshot_preview1" | "wasi_unstable")
+        matches!(module_name, "wasi_snapshot_preview1" | "wasi_unstable" | crate::host::HOST_MODULE)
     }
     
     /// Validate module imports
@@ -281,6 +545,8 @@ impl PluginSandbox {
         // Update execution stats
         self.execution_stats.total_executions += 1;
         self.execution_stats.total_cpu_time += execution_time;
+        self.execution_stats.peak_memory_usage =
+            self.execution_stats.peak_memory_usage.max(self.resource_monitor.memory_usage);
         
         // Calculate average execution time
         self.execution_stats.average_execution_time = 
@@ -335,6 +601,13 @@ impl PluginSandbox {
         Ok(())
     }
     
+    /// Account for a finished call: its peak memory goes to the resource
+    /// monitor and the security stats
+    pub fn record_execution(&mut self, report: &ExecutionReport) {
+        self.resource_monitor.record_memory_usage(report.peak_memory);
+        self.execution_stats.peak_memory_usage = self.execution_stats.peak_memory_usage.max(report.peak_memory);
+    }
+    
     /// Get execution statistics
     pub fn get_stats(&self) -> &ExecutionStats {
         &self.execution_stats
@@ -426,6 +699,8 @@ impl SecurityConfig {
             disable_dangerous_features: true,
             require_signature: true,
             trusted_authors: HashSet::new(),
+            trusted_keys: HashMap::new(),
+            allowed_host_capabilities: HashSet::new(),
         }
     }
     
@@ -448,6 +723,8 @@ impl SecurityConfig {
             disable_dangerous_features: true,
             require_signature: false,
             trusted_authors: HashSet::new(),
+            trusted_keys: HashMap::new(),
+            allowed_host_capabilities: [KNOWLEDGE_ACCESS.to_string()].into_iter().collect(),
         }
     }
     
@@ -473,10 +750,55 @@ impl SecurityConfig {
             disable_dangerous_features: false,
             require_signature: false,
             trusted_authors: HashSet::new(),
+            trusted_keys: HashMap::new(),
+            allowed_host_capabilities: [KNOWLEDGE_ACCESS.to_string()].into_iter().collect(),
         }
     }
 }
 
+/// Hex-encoded SHA-256 of a plugin artifact
+pub fn artifact_sha256(bytes: &[u8]) -> String {
+    hex::encode(Sha256::digest(bytes))
+}
+
+/// Check a downloaded artifact against its published checksum and detached
+/// Ed25519 signature. The signature covers the raw artifact bytes and must
+/// verify against one of `trusted_keys`, the same keys plugins are loaded
+/// against; it is returned with the id of that key, to be kept next to the
+/// module.
+pub fn verify_artifact(
+    bytes: &[u8],
+    expected_sha256: &str,
+    signature_hex: &str,
+    trusted_keys: &HashMap<String, TrustedKey>,
+) -> Result<PluginSignature> {
+    let actual = artifact_sha256(bytes);
+    if !actual.eq_ignore_ascii_case(expected_sha256) {
+        return Err(AssistantError::Plugin(format!(
+            "Checksum mismatch: expected {}, got {}",
+            expected_sha256, actual
+        )));
+    }
+
+    let signature = hex::decode(signature_hex)
+        .map_err(|_| AssistantError::Plugin("Artifact signature is not valid hex".to_string()))?;
+
+    let mut key_ids: Vec<&String> = trusted_keys.keys().collect();
+    key_ids.sort();
+    let key_id = key_ids.into_iter().find(|key_id| {
+        hex::decode(&trusted_keys[*key_id].public_key).is_ok_and(|key| {
+            signature::UnparsedPublicKey::new(&signature::ED25519, key)
+                .verify(bytes, &signature)
+                .is_ok()
+        })
+    });
+
+    match key_id {
+        Some(key_id) => Ok(PluginSignature { key_id: key_id.clone(), signature: signature_hex.to_ascii_lowercase() }),
+        None => Err(AssistantError::Plugin("Artifact signature does not match any trusted key".to_string())),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -502,6 +824,12 @@ mod tests {
         assert!(untrusted.require_signature);
         assert!(!semi_trusted.require_signature);
         assert!(!trusted.require_signature);
+        
+        // Knowledge access needs both the policy and the plugin's declaration
+        let declared = vec![KNOWLEDGE_ACCESS.to_string()];
+        assert!(!untrusted.grants(&declared, KNOWLEDGE_ACCESS));
+        assert!(trusted.grants(&declared, KNOWLEDGE_ACCESS));
+        assert!(!trusted.grants(&[], KNOWLEDGE_ACCESS));
     }
     
     #[test]
@@ -523,4 +851,102 @@ mod tests {
         assert_eq!(stats.total_executions, 0);
         assert_eq!(stats.security_violations, 0);
     }
-}
\ No newline at end of file
+    
+    #[test]
+    fn test_verify_artifact() {
+        use ring::signature::KeyPair;
+        
+        let rng = ring::rand::SystemRandom::new();
+        let pkcs8 = signature::Ed25519KeyPair::generate_pkcs8(&rng).unwrap();
+        let key_pair = signature::Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap();
+        let public_key = hex::encode(key_pair.public_key().as_ref());
+        
+        let artifact = b"\0asm plugin bytes";
+        let checksum = artifact_sha256(artifact);
+        let sig = hex::encode(key_pair.sign(artifact).as_ref());
+        
+        let stranger = TrustedKey { author: "stranger".to_string(), public_key: hex::encode([7u8; 32]) };
+        let keys: HashMap<String, TrustedKey> = [
+            ("acme-2026".to_string(), TrustedKey { author: "acme".to_string(), public_key }),
+            ("other".to_string(), stranger.clone()),
+        ]
+        .into_iter()
+        .collect();
+        
+        // Returned as the `.sig` the loader checks, naming the key that verified
+        let verified = verify_artifact(artifact, &checksum, &sig, &keys).unwrap();
+        assert_eq!(verified, PluginSignature { key_id: "acme-2026".to_string(), signature: sig.clone() });
+        assert!(verify_artifact(b"tampered", &checksum, &sig, &keys).is_err());
+        assert!(verify_artifact(artifact, &checksum, &sig, &HashMap::new()).is_err());
+        assert!(verify_artifact(artifact, &checksum, &sig, &[("other".to_string(), stranger)].into_iter().collect()).is_err());
+        
+        let other = artifact_sha256(b"tampered");
+        assert!(verify_artifact(b"tampered", &other, &sig, &keys).is_err());
+    }
+    
+    fn signed_policy(public_key: String) -> SecurityPolicy {
+        SecurityPolicy {
+            require_signature: true,
+            trusted_authors: ["acme".to_string()].into_iter().collect(),
+            trusted_keys: [("acme-2026".to_string(), TrustedKey { author: "acme".to_string(), public_key })]
+                .into_iter()
+                .collect(),
+            ..SecurityPolicy::default()
+        }
+    }
+    
+    fn plugin_metadata(author: &str) -> crate::WasmPluginMetadata {
+        serde_json::from_value(serde_json::json!({
+            "id": "weather", "name": "Weather", "version": "1.0.0", "author": author
+        }))
+        .unwrap()
+    }
+    
+    #[test]
+    fn test_plugin_signature_round_trip() {
+        let rng = ring::rand::SystemRandom::new();
+        let pkcs8 = signature::Ed25519KeyPair::generate_pkcs8(&rng).unwrap();
+        let mut sandbox = PluginSandbox::new(signed_policy(signing_public_key(pkcs8.as_ref()).unwrap()), ResourceLimits::default());
+        
+        let module = wat::parse_str(r#"(module $a (func (export "run")))"#).unwrap();
+        let signed = sign_plugin(&module, "acme-2026", pkcs8.as_ref()).unwrap();
+        let status = sandbox.validate_plugin(&module, &plugin_metadata("acme"), Some(&signed)).unwrap();
+        assert_eq!(status, SignatureStatus::Verified { key_id: "acme-2026".to_string(), author: "acme".to_string() });
+        
+        // Flipping a bit of the module name (the last byte) keeps it valid
+        let mut tampered = module.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        let error = sandbox.validate_plugin(&tampered, &plugin_metadata("acme"), Some(&signed)).unwrap_err();
+        assert!(error.to_string().contains("signature does not match the module"), "{}", error);
+        assert!(sandbox.validate_plugin(&module, &plugin_metadata("acme"), None).is_err());
+        
+        // A trusted key does not vouch for another author's plugins
+        let status = sandbox.check_signature(&module, &plugin_metadata("mallory"), Some(&signed));
+        assert!(matches!(status, SignatureStatus::Invalid { .. }));
+        let unknown = PluginSignature { key_id: "other".to_string(), ..signed.clone() };
+        assert!(sandbox.validate_plugin(&module, &plugin_metadata("acme"), Some(&unknown)).is_err());
+        assert_eq!(sandbox.get_stats().security_violations, 3);
+        
+        // Without require_signature a bad signature is recorded, not fatal
+        let mut lenient = PluginSandbox::new(
+            SecurityPolicy { require_signature: false, ..sandbox.get_policy().clone() },
+            ResourceLimits::default(),
+        );
+        let status = lenient.validate_plugin(&module, &plugin_metadata("acme"), Some(&unknown)).unwrap();
+        assert!(matches!(status, SignatureStatus::Invalid { .. }));
+    }
+    
+    #[tokio::test]
+    async fn test_signature_file_sits_next_to_the_module() {
+        let dir = tempfile::tempdir().unwrap();
+        let module_path = dir.path().join("weather.wasm");
+        assert_eq!(PluginSignature::path_for(&module_path), dir.path().join("weather.sig"));
+        assert_eq!(PluginSignature::read_for(&module_path).await.unwrap(), None);
+        
+        let rng = ring::rand::SystemRandom::new();
+        let pkcs8 = signature::Ed25519KeyPair::generate_pkcs8(&rng).unwrap();
+        let signed = sign_plugin(b"\0asm", "acme-2026", pkcs8.as_ref()).unwrap();
+        signed.write_for(&module_path).await.unwrap();
+        assert_eq!(PluginSignature::read_for(&module_path).await.unwrap(), Some(signed));
+    }
+}
@@ -0,0 +1,165 @@
+//! Trust levels assigned to plugins.
+//!
+//! A [`TrustStore`] records which [`TrustLevel`] each plugin is loaded at,
+//! kept as JSON in `trust.json` under the plugin directory. The level picks
+//! the [`SecurityConfig`] preset the plugin is validated against and caps
+//! the resource limits it runs with; plugins the store does not name are
+//! untrusted.
+
+use crate::security::{SecurityConfig, SecurityPolicy};
+use crate::ResourceLimits;
+use rusty_ai_common::{AssistantError, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use std::time::Duration;
+
+/// File under the plugin directory the trust store is kept in
+pub const TRUST_STORE_FILE: &str = "trust.json";
+
+/// How far a plugin is trusted, from least to most
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrustLevel {
+    #[default]
+    Untrusted,
+    SemiTrusted,
+    Trusted,
+}
+
+impl TrustLevel {
+    /// The preset for this level. Signing keys and authors are the host's,
+    /// taken from `base`, so signatures verify the same at every level
+    pub fn policy(self, base: &SecurityPolicy) -> SecurityPolicy {
+        let preset = match self {
+            Self::Untrusted => SecurityConfig::untrusted(),
+            Self::SemiTrusted => SecurityConfig::semi_trusted(),
+            Self::Trusted => SecurityConfig::trusted(),
+        };
+        SecurityPolicy {
+            trusted_authors: base.trusted_authors.clone(),
+            trusted_keys: base.trusted_keys.clone(),
+            ..preset
+        }
+    }
+
+    /// `base` capped for this level: descriptors and connections to what
+    /// the level's policy allows, and memory and CPU time below trusted
+    pub fn limits(self, base: &ResourceLimits) -> ResourceLimits {
+        let policy = self.policy(&SecurityPolicy::default());
+        let (max_memory, cpu_time_limit, max_execution_time) = match self {
+            Self::Untrusted => (16 * 1024 * 1024, Duration::from_secs(2), Duration::from_secs(10)),
+            Self::SemiTrusted => (32 * 1024 * 1024, Duration::from_secs(5), Duration::from_secs(20)),
+            Self::Trusted => (base.max_memory, base.cpu_time_limit, base.max_execution_time),
+        };
+        ResourceLimits {
+            max_memory: base.max_memory.min(max_memory),
+            cpu_time_limit: base.cpu_time_limit.min(cpu_time_limit),
+            max_execution_time: base.max_execution_time.min(max_execution_time),
+            max_file_descriptors: base.max_file_descriptors.min(policy.max_file_descriptors),
+            max_network_connections: base.max_network_connections.min(policy.max_network_connections),
+            ..base.clone()
+        }
+    }
+}
+
+// What `trust.json` holds
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct TrustFile {
+    #[serde(default)]
+    plugins: BTreeMap<String, TrustLevel>,
+}
+
+/// Trust levels by plugin name, saved to a file on every change
+pub struct TrustStore {
+    path: PathBuf,
+    levels: RwLock<BTreeMap<String, TrustLevel>>,
+}
+
+impl TrustStore {
+    /// The store kept in `path`, empty if the file does not exist yet
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let file: TrustFile = match std::fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .map_err(|e| AssistantError::Plugin(format!("Malformed trust store {}: {}", path.display(), e)))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => TrustFile::default(),
+            Err(e) => return Err(AssistantError::Plugin(format!("Failed to read {}: {}", path.display(), e))),
+        };
+        Ok(Self { path, levels: RwLock::new(file.plugins) })
+    }
+
+    /// The store in `trust.json` under `plugin_directory`
+    pub fn in_plugin_directory(plugin_directory: impl AsRef<Path>) -> Result<Self> {
+        Self::open(plugin_directory.as_ref().join(TRUST_STORE_FILE))
+    }
+
+    /// The level of `plugin_id`, untrusted unless the store names it
+    pub fn level(&self, plugin_id: &str) -> TrustLevel {
+        self.levels.read().unwrap().get(plugin_id).copied().unwrap_or_default()
+    }
+
+    /// Every plugin given a level, by name
+    pub fn levels(&self) -> BTreeMap<String, TrustLevel> {
+        self.levels.read().unwrap().clone()
+    }
+
+    /// Give `plugin_id` a level and save the store
+    pub async fn set_level(&self, plugin_id: &str, level: TrustLevel) -> Result<()> {
+        let file = {
+            let mut levels = self.levels.write().unwrap();
+            levels.insert(plugin_id.to_string(), level);
+            TrustFile { plugins: levels.clone() }
+        };
+        let json = serde_json::to_vec_pretty(&file)
+            .map_err(|e| AssistantError::Plugin(format!("Failed to serialize trust store: {}", e)))?;
+        tokio::fs::write(&self.path, json)
+            .await
+            .map_err(|e| AssistantError::Plugin(format!("Failed to write {}: {}", self.path.display(), e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_levels_persist_and_unknown_plugins_are_untrusted() {
+        let temp_dir = tempdir().unwrap();
+        let store = TrustStore::in_plugin_directory(temp_dir.path()).unwrap();
+        assert_eq!(store.level("weather"), TrustLevel::Untrusted);
+
+        store.set_level("weather", TrustLevel::Trusted).await.unwrap();
+        store.set_level("notes", TrustLevel::SemiTrusted).await.unwrap();
+
+        let reopened = TrustStore::in_plugin_directory(temp_dir.path()).unwrap();
+        assert_eq!(reopened.level("weather"), TrustLevel::Trusted);
+        assert_eq!(reopened.level("notes"), TrustLevel::SemiTrusted);
+        assert_eq!(reopened.level("clock"), TrustLevel::Untrusted);
+
+        std::fs::write(temp_dir.path().join(TRUST_STORE_FILE), "{ not json").unwrap();
+        assert!(TrustStore::in_plugin_directory(temp_dir.path()).is_err());
+    }
+
+    #[test]
+    fn test_levels_cap_limits_and_keep_the_hosts_signing_keys() {
+        let base = ResourceLimits::default();
+        let untrusted = TrustLevel::Untrusted.limits(&base);
+        assert_eq!(untrusted.max_memory, 16 * 1024 * 1024);
+        assert_eq!((untrusted.max_file_descriptors, untrusted.max_network_connections), (0, 0));
+        assert_eq!(untrusted.max_fuel, base.max_fuel);
+
+        let trusted = TrustLevel::Trusted.limits(&base);
+        assert_eq!((trusted.max_memory, trusted.cpu_time_limit), (base.max_memory, base.cpu_time_limit));
+        // Never raised above what the host configured
+        assert_eq!(trusted.max_file_descriptors, base.max_file_descriptors);
+
+        let mut host = SecurityPolicy::default();
+        host.trusted_authors.insert("acme".to_string());
+        let policy = TrustLevel::Untrusted.policy(&host);
+        assert!(policy.require_signature);
+        assert!(policy.trusted_authors.contains("acme"));
+    }
+}
@@ -0,0 +1,355 @@
+use crate::breaker::CircuitBreaker;
+use crate::limits::KilledCalls;
+use crate::pool::InstancePool;
+use crate::FunctionSchema;
+use rusty_ai_common::{AssistantError, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::sync::Notify;
+
+/// Split `name@version` into its parts; a bare name has no version
+pub fn parse_plugin_ref(plugin_ref: &str) -> (&str, Option<&str>) {
+    match plugin_ref.split_once('@') {
+        Some((name, version)) if !version.is_empty() => (name, Some(version)),
+        Some((name, _)) => (name, None),
+        None => (plugin_ref, None),
+    }
+}
+
+/// One loaded build of a plugin, with its execution counts and the calls
+/// currently running in it
+pub struct LoadedVersion {
+    pub version: String,
+    pub(crate) instances: InstancePool,
+    pub(crate) schemas: HashMap<String, FunctionSchema>,
+    in_flight: AtomicUsize,
+    drained: Notify,
+    executions: AtomicU64,
+    errors: AtomicU64,
+    /// Calls stopped by fuel, CPU time or wall-clock limits
+    pub(crate) killed: KilledCalls,
+    /// Refuses calls for a while once this version keeps failing
+    pub(crate) breaker: CircuitBreaker,
+}
+
+impl LoadedVersion {
+    pub(crate) fn new(version: String, instances: InstancePool, schemas: HashMap<String, FunctionSchema>) -> Self {
+        Self {
+            version,
+            instances,
+            schemas,
+            in_flight: AtomicUsize::new(0),
+            drained: Notify::new(),
+            executions: AtomicU64::new(0),
+            errors: AtomicU64::new(0),
+            killed: KilledCalls::default(),
+            breaker: CircuitBreaker::default(),
+        }
+    }
+
+    /// Count a call as running until the returned guard is dropped
+    pub(crate) fn begin(self: &Arc<Self>) -> InFlight {
+        self.in_flight.fetch_add(1, Ordering::SeqCst);
+        InFlight(Arc::clone(self))
+    }
+
+    pub(crate) fn record(&self, succeeded: bool) {
+        self.executions.fetch_add(1, Ordering::SeqCst);
+        if !succeeded {
+            self.errors.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    pub(crate) fn counts(&self) -> (u64, u64) {
+        (self.executions.load(Ordering::SeqCst), self.errors.load(Ordering::SeqCst))
+    }
+
+    /// Calls running in this version right now
+    pub(crate) fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::SeqCst)
+    }
+
+    /// Resolves once no call is running in this version. Only meaningful
+    /// after the version stopped receiving new calls
+    pub(crate) async fn wait_drained(&self) {
+        loop {
+            let drained = self.drained.notified();
+            if self.in_flight.load(Ordering::SeqCst) == 0 {
+                return;
+            }
+            drained.await;
+        }
+    }
+
+    fn info(&self) -> VersionInfo {
+        let (executions, errors) = self.counts();
+        VersionInfo {
+            version: self.version.clone(),
+            in_flight: self.in_flight(),
+            executions,
+            errors,
+            killed: self.killed.total(),
+        }
+    }
+}
+
+/// A running call into a plugin version
+pub(crate) struct InFlight(Arc<LoadedVersion>);
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        if self.0.in_flight.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.0.drained.notify_waiters();
+        }
+    }
+}
+
+fn default_min_executions() -> u64 {
+    100
+}
+
+fn default_max_error_rate_increase() -> f64 {
+    0.05
+}
+
+/// Route part of a plugin's traffic to a loaded standby version, then
+/// promote or roll it back by comparing error rates
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CanaryConfig {
+    pub version: String,
+    /// Share of executions sent to the canary, 1-99
+    pub percent: u8,
+    /// Canary executions needed before it is judged (default: 100)
+    #[serde(default = "default_min_executions")]
+    pub min_executions: u64,
+    /// How far the canary's error rate may exceed the active version's
+    /// before it is rolled back (default: 0.05)
+    #[serde(default = "default_max_error_rate_increase")]
+    pub max_error_rate_increase: f64,
+}
+
+impl CanaryConfig {
+    pub fn new(version: impl Into<String>, percent: u8) -> Self {
+        Self {
+            version: version.into(),
+            percent,
+            min_executions: default_min_executions(),
+            max_error_rate_increase: default_max_error_rate_increase(),
+        }
+    }
+
+    pub fn min_executions(mut self, min_executions: u64) -> Self {
+        self.min_executions = min_executions;
+        self
+    }
+
+    fn validate(&self) -> Result<()> {
+        if !(1..=99).contains(&self.percent) {
+            return Err(AssistantError::Plugin(format!("Canary percent must be 1-99, got {}", self.percent)));
+        }
+        if self.min_executions == 0 {
+            return Err(AssistantError::Plugin("Canary needs at least one execution to be judged".to_string()));
+        }
+        Ok(())
+    }
+}
+
+/// How a canary ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CanaryOutcome {
+    Promoted,
+    RolledBack,
+}
+
+struct Canary {
+    config: CanaryConfig,
+    routed: AtomicU64,
+    /// Counts of the active and canary versions when the canary started
+    active_start: (u64, u64),
+    canary_start: (u64, u64),
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct VersionInfo {
+    pub version: String,
+    pub in_flight: usize,
+    pub executions: u64,
+    pub errors: u64,
+    /// Calls stopped by a resource limit, counted in `errors` too
+    pub killed: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CanaryStatus {
+    pub version: String,
+    pub percent: u8,
+    pub min_executions: u64,
+    pub canary_executions: u64,
+    pub canary_errors: u64,
+    pub active_executions: u64,
+    pub active_errors: u64,
+}
+
+/// The loaded versions of a plugin and which one serves its calls
+#[derive(Debug, Clone, Serialize)]
+pub struct PluginVersions {
+    pub name: String,
+    pub active: String,
+    pub versions: Vec<VersionInfo>,
+    pub canary: Option<CanaryStatus>,
+}
+
+/// Versions of one plugin. Calls by plain name go to `active`, or to the
+/// canary for its share of them
+pub(crate) struct PluginSlot {
+    pub(crate) versions: BTreeMap<String, Arc<LoadedVersion>>,
+    pub(crate) active: String,
+    canary: Option<Canary>,
+}
+
+impl PluginSlot {
+    pub(crate) fn new(version: Arc<LoadedVersion>) -> Self {
+        let active = version.version.clone();
+        Self { versions: BTreeMap::from([(active.clone(), version)]), active, canary: None }
+    }
+
+    pub(crate) fn active_version(&self) -> &Arc<LoadedVersion> {
+        &self.versions[&self.active]
+    }
+
+    /// Add a version. With `activate` it takes over from the active version
+    /// at once; otherwise it is loaded on standby, unless it replaces the
+    /// active build of the same version. Returns the versions taken out of
+    /// service and whether the new one is active
+    pub(crate) fn add(&mut self, version: Arc<LoadedVersion>, activate: bool) -> (Vec<Arc<LoadedVersion>>, bool) {
+        let label = version.version.clone();
+        let mut retired: Vec<_> = self.versions.insert(label.clone(), version).into_iter().collect();
+        if self.canary.as_ref().is_some_and(|c| c.config.version == label) {
+            self.canary = None;
+        }
+
+        if activate && label != self.active {
+            retired.extend(self.versions.remove(&self.active));
+            self.active = label;
+            self.canary = None;
+        }
+        let is_active = self.active == label;
+        (retired, is_active)
+    }
+
+    /// The version the next plain-name call goes to. Canary routing is
+    /// spread evenly: exactly `percent` of every hundred calls
+    pub(crate) fn select(&self) -> Arc<LoadedVersion> {
+        if let Some(canary) = &self.canary {
+            let n = canary.routed.fetch_add(1, Ordering::SeqCst);
+            let percent = canary.config.percent as u64;
+            if (n + 1) * percent / 100 > n * percent / 100 {
+                if let Some(version) = self.versions.get(&canary.config.version) {
+                    return Arc::clone(version);
+                }
+            }
+        }
+        Arc::clone(self.active_version())
+    }
+
+    /// Make `version` the active one; the previous active version is
+    /// returned for draining
+    pub(crate) fn cutover(&mut self, name: &str, version: &str) -> Result<Option<Arc<LoadedVersion>>> {
+        if !self.versions.contains_key(version) {
+            return Err(AssistantError::NotFound(format!("Plugin not found: {}@{}", name, version)));
+        }
+        self.canary = None;
+        if self.active == version {
+            return Ok(None);
+        }
+        let previous = self.versions.remove(&self.active);
+        self.active = version.to_string();
+        Ok(previous)
+    }
+
+    pub(crate) fn start_canary(&mut self, name: &str, config: CanaryConfig) -> Result<()> {
+        config.validate()?;
+        let canary = self
+            .versions
+            .get(&config.version)
+            .ok_or_else(|| AssistantError::NotFound(format!("Plugin not found: {}@{}", name, config.version)))?;
+        if config.version == self.active {
+            return Err(AssistantError::Plugin(format!("{}@{} is already active", name, config.version)));
+        }
+
+        self.canary = Some(Canary {
+            active_start: self.active_version().counts(),
+            canary_start: canary.counts(),
+            routed: AtomicU64::new(0),
+            config,
+        });
+        Ok(())
+    }
+
+    pub(crate) fn stop_canary(&mut self) -> bool {
+        self.canary.take().is_some()
+    }
+
+    pub(crate) fn has_canary(&self) -> bool {
+        self.canary.is_some()
+    }
+
+    pub(crate) fn canary_version(&self) -> Option<&str> {
+        self.canary.as_ref().map(|c| c.config.version.as_str())
+    }
+
+    /// Judge the canary once it has enough executions: roll it back if its
+    /// error rate exceeds the active version's by more than allowed,
+    /// otherwise promote it. Returns the outcome and the version retired
+    pub(crate) fn settle_canary(&mut self) -> Option<(CanaryOutcome, Arc<LoadedVersion>)> {
+        let status = self.canary_status()?;
+        let canary = self.canary.as_ref()?;
+        if status.canary_executions < canary.config.min_executions {
+            return None;
+        }
+
+        let canary_rate = status.canary_errors as f64 / status.canary_executions as f64;
+        let active_rate = match status.active_executions {
+            0 => 0.0,
+            executions => status.active_errors as f64 / executions as f64,
+        };
+        let version = canary.config.version.clone();
+        let rolled_back = canary_rate - active_rate > canary.config.max_error_rate_increase;
+        self.canary = None;
+
+        if rolled_back {
+            Some((CanaryOutcome::RolledBack, self.versions.remove(&version)?))
+        } else {
+            let previous = self.versions.remove(&self.active)?;
+            self.active = version;
+            Some((CanaryOutcome::Promoted, previous))
+        }
+    }
+
+    fn canary_status(&self) -> Option<CanaryStatus> {
+        let canary = self.canary.as_ref()?;
+        let (canary_executions, canary_errors) = self.versions.get(&canary.config.version)?.counts();
+        let (active_executions, active_errors) = self.active_version().counts();
+        Some(CanaryStatus {
+            version: canary.config.version.clone(),
+            percent: canary.config.percent,
+            min_executions: canary.config.min_executions,
+            canary_executions: canary_executions - canary.canary_start.0,
+            canary_errors: canary_errors - canary.canary_start.1,
+            active_executions: active_executions - canary.active_start.0,
+            active_errors: active_errors - canary.active_start.1,
+        })
+    }
+
+    pub(crate) fn describe(&self, name: &str) -> PluginVersions {
+        PluginVersions {
+            name: name.to_string(),
+            active: self.active.clone(),
+            versions: self.versions.values().map(|v| v.info()).collect(),
+            canary: self.canary_status(),
+        }
+    }
+}
@@ -62,8 +62,9 @@ export function ChatInterface() {
         throw new Error(`HTTP error! status: ${response.status}`);
       }
 
-      const data = await response.json();
-      console.log("Response from backend:", data);
+      const body = await response.json();
+      console.log("Response from backend:", body);
+      const data = body.data ?? {};
 
       // Store session ID for future messages
       if (data.session_id) {
@@ -40,8 +40,8 @@ export function History() {
     try {
       const response = await fetch('/api/v1/conversation/sessions');
       if (response.ok) {
-        const data = await response.json();
-        setSessions(data.sessions || []);
+        const { data } = await response.json();
+        setSessions(data?.sessions || []);
       }
     } catch (error) {
       console.error("Failed to fetch sessions:", error);
@@ -56,7 +56,7 @@ export function History() {
     try {
       const response = await fetch(`/api/v1/conversation/session/${sessionId}`);
       if (response.ok) {
-        const data = await response.json();
+        const { data } = await response.json();
         setSelectedSession(data);
         setShowSessionDialog(true);
       }
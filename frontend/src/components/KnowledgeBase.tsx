@@ -75,6 +75,31 @@ export function KnowledgeBase() {
     }
   };
 
+  // Poll an accepted upload until it is indexed or fails
+  const waitForIndexing = async (uploadId: string): Promise<string> => {
+    for (;;) {
+      const response = await fetch(`/api/v1/knowledge/upload/${uploadId}/status`);
+      if (!response.ok) {
+        return "❌ Lost track of the upload";
+      }
+
+      const { status } = await response.json();
+      switch (status.stage) {
+        case "indexed":
+          return `✅ Document indexed! Created ${status.chunks} chunks.`;
+        case "failed":
+          return `❌ Indexing failed while ${status.failed_stage}: ${status.error}`;
+        case "embedding":
+          setUploadStatus(`Embedding ${status.done}/${status.total} chunks...`);
+          break;
+        default:
+          setUploadStatus(`${status.stage.charAt(0).toUpperCase()}${status.stage.slice(1)}...`);
+      }
+
+      await new Promise((resolve) => setTimeout(resolve, 1000));
+    }
+  };
+
   // Upload document
   const handleUpload = async () => {
     if (!uploadTitle || !uploadContent) {
@@ -99,7 +124,7 @@ export function KnowledgeBase() {
 
       if (response.ok) {
         const result = await response.json();
-        setUploadStatus(`✅ Document uploaded successfully! Created ${result.chunks_created} chunks.`);
+        setUploadStatus(await waitForIndexing(result.upload_id));
         setUploadTitle("");
         setUploadContent("");
         setUploadTags("");
@@ -143,7 +168,7 @@ export function KnowledgeBase() {
 
         if (response.ok) {
           const result = await response.json();
-          setUploadStatus(`✅ File uploaded successfully! Created ${result.chunks_created} chunks.`);
+          setUploadStatus(await waitForIndexing(result.upload_id));
           fetchStats();
         } else {
           setUploadStatus("❌ Failed to upload file");
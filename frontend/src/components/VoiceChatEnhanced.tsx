@@ -147,6 +147,7 @@ export function VoiceChatEnhanced() {
         body: JSON.stringify({
           message: text,
           session_id: sessionId,
+          language: navigator.language,
         }),
       });
 
@@ -154,7 +155,8 @@ export function VoiceChatEnhanced() {
         throw new Error(`HTTP error! status: ${response.status}`);
       }
 
-      const data = await response.json();
+      const body = await response.json();
+      const data = body.data ?? {};
       
       // Store session ID for future messages
       if (data.session_id) {
@@ -173,7 +175,7 @@ export function VoiceChatEnhanced() {
       
       // If it was a voice input, speak the response using TTS API
       if (type === "voice") {
-        await speakResponseWithAPI(data.response);
+        await speakResponseWithAPI(data.response, data.response_language);
       }
     } catch (error) {
       console.error("Error sending message:", error);
@@ -185,7 +187,7 @@ export function VoiceChatEnhanced() {
   };
 
   // Text-to-speech using backend API
-  const speakResponseWithAPI = async (text: string) => {
+  const speakResponseWithAPI = async (text: string, language?: string) => {
     try {
       setIsPlaying(true);
       
@@ -194,7 +196,7 @@ export function VoiceChatEnhanced() {
         headers: {
           'Content-Type': 'application/json',
         },
-        body: JSON.stringify({ text }),
+        body: JSON.stringify({ text, language }),
       });
       
       if (!response.ok) {
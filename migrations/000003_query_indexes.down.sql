@@ -0,0 +1,5 @@
+-- Rollback script for query index migration
+
+-- idx_tasks_status belongs to the initial schema and is left in place
+DROP INDEX IF EXISTS idx_messages_conversation_created;
+DROP INDEX IF EXISTS idx_documents_updated_at;
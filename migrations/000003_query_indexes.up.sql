@@ -0,0 +1,10 @@
+-- Third migration: indexes for the storage layer's most frequent queries
+
+-- Pending and in-progress task lookups filter on status
+CREATE INDEX IF NOT EXISTS idx_tasks_status ON tasks(status);
+
+-- Document search and tag listings are ordered by last update
+CREATE INDEX IF NOT EXISTS idx_documents_updated_at ON documents(updated_at);
+
+-- Conversation history is read per conversation in message order
+CREATE INDEX IF NOT EXISTS idx_messages_conversation_created ON messages(conversation_id, created_at);
@@ -0,0 +1,21 @@
+-- Rollback script for the sync change log
+
+DROP TRIGGER IF EXISTS sync_conversations_insert;
+DROP TRIGGER IF EXISTS sync_conversations_update;
+DROP TRIGGER IF EXISTS sync_conversations_delete;
+DROP TRIGGER IF EXISTS sync_messages_insert;
+DROP TRIGGER IF EXISTS sync_messages_update;
+DROP TRIGGER IF EXISTS sync_messages_delete;
+DROP TRIGGER IF EXISTS sync_tasks_insert;
+DROP TRIGGER IF EXISTS sync_tasks_update;
+DROP TRIGGER IF EXISTS sync_tasks_delete;
+DROP TRIGGER IF EXISTS sync_notifications_insert;
+DROP TRIGGER IF EXISTS sync_notifications_update;
+DROP TRIGGER IF EXISTS sync_notifications_delete;
+DROP TRIGGER IF EXISTS sync_documents_insert;
+DROP TRIGGER IF EXISTS sync_documents_update;
+DROP TRIGGER IF EXISTS sync_documents_delete;
+
+DROP INDEX IF EXISTS idx_sync_changes_record;
+DROP INDEX IF EXISTS idx_sync_changes_user_seq;
+DROP TABLE IF EXISTS sync_changes;
@@ -0,0 +1,126 @@
+-- Fourth migration: change log for differential sync
+
+-- Every insert, update and delete of a synced record appends a row here; the
+-- sequence number is the sync cursor. Only the latest change per record is
+-- read, so superseded rows can be pruned without invalidating cursors.
+CREATE TABLE IF NOT EXISTS sync_changes (
+    seq INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id TEXT NOT NULL,
+    entity TEXT NOT NULL CHECK (entity IN ('sessions', 'messages', 'tasks', 'notifications', 'documents')),
+    record_id TEXT NOT NULL,
+    deleted BOOLEAN NOT NULL DEFAULT FALSE,
+    changed_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_sync_changes_user_seq ON sync_changes(user_id, seq);
+CREATE INDEX IF NOT EXISTS idx_sync_changes_record ON sync_changes(entity, record_id, seq);
+
+-- conversations sync as 'sessions'
+CREATE TRIGGER IF NOT EXISTS sync_conversations_insert
+    AFTER INSERT ON conversations
+    FOR EACH ROW
+BEGIN
+    INSERT INTO sync_changes (user_id, entity, record_id) VALUES (NEW.user_id, 'sessions', NEW.id);
+END;
+
+CREATE TRIGGER IF NOT EXISTS sync_conversations_update
+    AFTER UPDATE ON conversations
+    FOR EACH ROW
+BEGIN
+    INSERT INTO sync_changes (user_id, entity, record_id) VALUES (NEW.user_id, 'sessions', NEW.id);
+END;
+
+CREATE TRIGGER IF NOT EXISTS sync_conversations_delete
+    AFTER DELETE ON conversations
+    FOR EACH ROW
+BEGIN
+    INSERT INTO sync_changes (user_id, entity, record_id, deleted) VALUES (OLD.user_id, 'sessions', OLD.id, TRUE);
+END;
+
+-- messages sync as 'messages'
+CREATE TRIGGER IF NOT EXISTS sync_messages_insert
+    AFTER INSERT ON messages
+    FOR EACH ROW
+BEGIN
+    INSERT INTO sync_changes (user_id, entity, record_id) VALUES (NEW.user_id, 'messages', NEW.id);
+END;
+
+CREATE TRIGGER IF NOT EXISTS sync_messages_update
+    AFTER UPDATE ON messages
+    FOR EACH ROW
+BEGIN
+    INSERT INTO sync_changes (user_id, entity, record_id) VALUES (NEW.user_id, 'messages', NEW.id);
+END;
+
+CREATE TRIGGER IF NOT EXISTS sync_messages_delete
+    AFTER DELETE ON messages
+    FOR EACH ROW
+BEGIN
+    INSERT INTO sync_changes (user_id, entity, record_id, deleted) VALUES (OLD.user_id, 'messages', OLD.id, TRUE);
+END;
+
+-- tasks sync as 'tasks'
+CREATE TRIGGER IF NOT EXISTS sync_tasks_insert
+    AFTER INSERT ON tasks
+    FOR EACH ROW
+BEGIN
+    INSERT INTO sync_changes (user_id, entity, record_id) VALUES (NEW.user_id, 'tasks', NEW.id);
+END;
+
+CREATE TRIGGER IF NOT EXISTS sync_tasks_update
+    AFTER UPDATE ON tasks
+    FOR EACH ROW
+BEGIN
+    INSERT INTO sync_changes (user_id, entity, record_id) VALUES (NEW.user_id, 'tasks', NEW.id);
+END;
+
+CREATE TRIGGER IF NOT EXISTS sync_tasks_delete
+    AFTER DELETE ON tasks
+    FOR EACH ROW
+BEGIN
+    INSERT INTO sync_changes (user_id, entity, record_id, deleted) VALUES (OLD.user_id, 'tasks', OLD.id, TRUE);
+END;
+
+-- notifications sync as 'notifications'
+CREATE TRIGGER IF NOT EXISTS sync_notifications_insert
+    AFTER INSERT ON notifications
+    FOR EACH ROW
+BEGIN
+    INSERT INTO sync_changes (user_id, entity, record_id) VALUES (NEW.user_id, 'notifications', NEW.id);
+END;
+
+CREATE TRIGGER IF NOT EXISTS sync_notifications_update
+    AFTER UPDATE ON notifications
+    FOR EACH ROW
+BEGIN
+    INSERT INTO sync_changes (user_id, entity, record_id) VALUES (NEW.user_id, 'notifications', NEW.id);
+END;
+
+CREATE TRIGGER IF NOT EXISTS sync_notifications_delete
+    AFTER DELETE ON notifications
+    FOR EACH ROW
+BEGIN
+    INSERT INTO sync_changes (user_id, entity, record_id, deleted) VALUES (OLD.user_id, 'notifications', OLD.id, TRUE);
+END;
+
+-- documents sync as 'documents'
+CREATE TRIGGER IF NOT EXISTS sync_documents_insert
+    AFTER INSERT ON documents
+    FOR EACH ROW
+BEGIN
+    INSERT INTO sync_changes (user_id, entity, record_id) VALUES (NEW.user_id, 'documents', NEW.id);
+END;
+
+CREATE TRIGGER IF NOT EXISTS sync_documents_update
+    AFTER UPDATE ON documents
+    FOR EACH ROW
+BEGIN
+    INSERT INTO sync_changes (user_id, entity, record_id) VALUES (NEW.user_id, 'documents', NEW.id);
+END;
+
+CREATE TRIGGER IF NOT EXISTS sync_documents_delete
+    AFTER DELETE ON documents
+    FOR EACH ROW
+BEGIN
+    INSERT INTO sync_changes (user_id, entity, record_id, deleted) VALUES (OLD.user_id, 'documents', OLD.id, TRUE);
+END;
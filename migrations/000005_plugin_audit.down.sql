@@ -0,0 +1,5 @@
+-- Rollback script for the plugin call record
+
+DROP INDEX IF EXISTS idx_plugin_audit_plugin;
+DROP INDEX IF EXISTS idx_plugin_audit_created;
+DROP TABLE IF EXISTS plugin_audit;
@@ -0,0 +1,21 @@
+-- Fifth migration: a record of every plugin call
+
+-- Written after each call without holding it up; rows past the retention
+-- limit are removed by the regular cleanup
+CREATE TABLE IF NOT EXISTS plugin_audit (
+    id TEXT PRIMARY KEY,
+    plugin_id TEXT NOT NULL,
+    function TEXT NOT NULL,
+    user_id TEXT NOT NULL,
+    request_id TEXT NOT NULL,
+    input_size INTEGER NOT NULL,
+    output_size INTEGER NOT NULL,
+    duration_ms INTEGER NOT NULL,
+    success BOOLEAN NOT NULL,
+    error TEXT,
+    fuel_consumed INTEGER,
+    created_at DATETIME NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_plugin_audit_created ON plugin_audit(created_at);
+CREATE INDEX IF NOT EXISTS idx_plugin_audit_plugin ON plugin_audit(plugin_id, created_at);
@@ -0,0 +1,3 @@
+-- Rollback script for scheduled plugin jobs
+
+DROP TABLE IF EXISTS plugin_schedules;
@@ -0,0 +1,15 @@
+-- Sixth migration: plugin functions run on a schedule
+
+-- One row per job; the scheduler moves next_run_at on after every due time,
+-- whether the job ran or its run was skipped
+CREATE TABLE IF NOT EXISTS plugin_schedules (
+    id TEXT PRIMARY KEY,
+    plugin_id TEXT NOT NULL,
+    function TEXT NOT NULL,
+    input TEXT NOT NULL,
+    cron TEXT NOT NULL,
+    catch_up TEXT NOT NULL,
+    next_run_at DATETIME NOT NULL,
+    last_run_at DATETIME,
+    created_at DATETIME NOT NULL
+);
@@ -0,0 +1,3 @@
+-- Rollback script for plugin configuration
+
+DROP TABLE IF EXISTS plugin_configs;
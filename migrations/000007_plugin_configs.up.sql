@@ -0,0 +1,15 @@
+-- Seventh migration: plugin configuration kept across restarts
+
+-- The initial schema's per-user plugin_configs table was never written;
+-- dropping it takes its indexes and trigger along
+DROP TABLE IF EXISTS plugin_configs;
+
+-- One row per plugin name; applied whenever a version of the plugin loads
+CREATE TABLE plugin_configs (
+    plugin_id TEXT PRIMARY KEY,
+    enabled BOOLEAN NOT NULL,
+    priority INTEGER NOT NULL,
+    settings TEXT NOT NULL,
+    quota TEXT NOT NULL,
+    updated_at DATETIME NOT NULL
+);
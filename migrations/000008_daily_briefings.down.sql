@@ -0,0 +1,3 @@
+-- Rollback script for daily briefings
+
+DROP TABLE IF EXISTS daily_briefings;
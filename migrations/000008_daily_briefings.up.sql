@@ -0,0 +1,14 @@
+-- Eighth migration: versioned daily briefings
+
+-- Sections are JSON in the layout of `schema_version` (1 is the legacy,
+-- unversioned layout); rows that can't be read are quarantined with the
+-- reason instead of failing every read
+CREATE TABLE IF NOT EXISTS daily_briefings (
+    id TEXT PRIMARY KEY,
+    date DATETIME NOT NULL,
+    sections TEXT NOT NULL,
+    generated_at DATETIME NOT NULL,
+    schema_version INTEGER NOT NULL DEFAULT 1,
+    quarantined INTEGER NOT NULL DEFAULT 0,
+    quarantine_reason TEXT
+);
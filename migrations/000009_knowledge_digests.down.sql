@@ -0,0 +1,6 @@
+-- Rollback script for knowledge digests
+
+DROP INDEX IF EXISTS idx_knowledge_digests_user;
+DROP TABLE IF EXISTS knowledge_digests;
+DROP INDEX IF EXISTS idx_document_access_time;
+DROP TABLE IF EXISTS document_access;
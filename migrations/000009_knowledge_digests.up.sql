@@ -0,0 +1,21 @@
+-- Ninth migration: weekly knowledge digests
+
+-- One row per document read, counted per digest period
+CREATE TABLE IF NOT EXISTS document_access (
+    document_id TEXT NOT NULL,
+    accessed_at DATETIME NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_document_access_time ON document_access(accessed_at);
+
+-- Digests as sent, listed per user newest first
+CREATE TABLE IF NOT EXISTS knowledge_digests (
+    id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL,
+    period_start DATETIME NOT NULL,
+    period_end DATETIME NOT NULL,
+    generated_at DATETIME NOT NULL,
+    content TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_knowledge_digests_user ON knowledge_digests(user_id, period_end);
@@ -0,0 +1,5 @@
+-- Rollback script for the admin audit trail
+
+DROP INDEX IF EXISTS idx_admin_audit_actor;
+DROP INDEX IF EXISTS idx_admin_audit_created;
+DROP TABLE IF EXISTS admin_audit;
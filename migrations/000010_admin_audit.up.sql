@@ -0,0 +1,18 @@
+-- Tenth migration: audit trail of admin mutations
+
+-- One row per admin request; `complete` is false when the handler returned
+-- without describing the change, and `action` is then the route
+CREATE TABLE IF NOT EXISTS admin_audit (
+    id TEXT PRIMARY KEY,
+    actor TEXT NOT NULL,
+    action TEXT NOT NULL,
+    target TEXT NOT NULL,
+    before_state TEXT,
+    after_state TEXT,
+    request_id TEXT,
+    complete BOOLEAN NOT NULL,
+    created_at DATETIME NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_admin_audit_created ON admin_audit(created_at);
+CREATE INDEX IF NOT EXISTS idx_admin_audit_actor ON admin_audit(actor, created_at);
@@ -0,0 +1,6 @@
+-- Rollback script for task time entries
+
+DROP INDEX IF EXISTS idx_task_time_entries_running;
+DROP INDEX IF EXISTS idx_task_time_entries_user;
+DROP INDEX IF EXISTS idx_task_time_entries_task;
+DROP TABLE IF EXISTS task_time_entries;
@@ -0,0 +1,17 @@
+-- Eleventh migration: time tracked on tasks
+
+-- An entry without ended_at is a running timer
+CREATE TABLE IF NOT EXISTS task_time_entries (
+    id TEXT PRIMARY KEY,
+    task_id TEXT NOT NULL,
+    user_id TEXT NOT NULL,
+    started_at DATETIME NOT NULL,
+    ended_at DATETIME,
+    note TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_task_time_entries_task ON task_time_entries(task_id);
+CREATE INDEX IF NOT EXISTS idx_task_time_entries_user ON task_time_entries(user_id, started_at);
+
+-- Keeps a user from having two timers running, even when two starts race
+CREATE UNIQUE INDEX IF NOT EXISTS idx_task_time_entries_running ON task_time_entries(user_id) WHERE ended_at IS NULL;
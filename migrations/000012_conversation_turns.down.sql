@@ -0,0 +1,4 @@
+-- Rollback script for conversation turns
+
+DROP INDEX IF EXISTS idx_conversation_turns_session;
+DROP TABLE IF EXISTS conversation_turns;
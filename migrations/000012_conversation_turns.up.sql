@@ -0,0 +1,15 @@
+-- Twelfth migration: conversation history kept out of memory
+
+-- Every turn of a session, paged back in when it scrolls out of the
+-- in-memory window; `kind` tells chat messages from palette commands
+CREATE TABLE IF NOT EXISTS conversation_turns (
+    id TEXT PRIMARY KEY,
+    session_id TEXT NOT NULL,
+    user_input TEXT NOT NULL,
+    assistant_response TEXT NOT NULL,
+    intent TEXT NOT NULL,
+    timestamp DATETIME NOT NULL,
+    kind TEXT NOT NULL DEFAULT 'message'
+);
+
+CREATE INDEX IF NOT EXISTS idx_conversation_turns_session ON conversation_turns(session_id, timestamp);
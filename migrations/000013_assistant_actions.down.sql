@@ -0,0 +1,4 @@
+-- Rollback script for the assistant activity log
+
+DROP INDEX IF EXISTS idx_assistant_actions_user;
+DROP TABLE IF EXISTS assistant_actions;
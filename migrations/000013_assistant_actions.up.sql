@@ -0,0 +1,20 @@
+-- Thirteenth migration: activity log of what the assistant did
+
+-- Side effects the assistant caused, with what it takes to undo them
+CREATE TABLE IF NOT EXISTS assistant_actions (
+    id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    source TEXT NOT NULL,
+    session_id TEXT,
+    trigger TEXT NOT NULL,
+    status TEXT NOT NULL,
+    description TEXT NOT NULL,
+    resource_id TEXT,
+    undo TEXT,
+    error TEXT,
+    created_at DATETIME NOT NULL,
+    undone_at DATETIME
+);
+
+CREATE INDEX IF NOT EXISTS idx_assistant_actions_user ON assistant_actions(user_id, created_at);
@@ -0,0 +1,3 @@
+-- Rollback script for search history
+
+DROP TABLE IF EXISTS search_history;
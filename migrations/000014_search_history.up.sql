@@ -0,0 +1,12 @@
+-- Fourteenth migration: search history for suggestions
+
+-- One row per user and query, keyed by the query in lower case so "Tax" and
+-- "tax" count together; `query` keeps the latest spelling
+CREATE TABLE IF NOT EXISTS search_history (
+    user_id TEXT NOT NULL,
+    normalized TEXT NOT NULL,
+    query TEXT NOT NULL,
+    count INTEGER NOT NULL,
+    last_searched_at DATETIME NOT NULL,
+    PRIMARY KEY (user_id, normalized)
+);
@@ -0,0 +1,3 @@
+-- Rollback script for feature flag overrides
+
+DROP TABLE IF EXISTS feature_flags;
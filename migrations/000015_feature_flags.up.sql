@@ -0,0 +1,9 @@
+-- Fifteenth migration: runtime feature flag overrides
+
+-- The override is kept as JSON; it is read once at startup and after that
+-- only written
+CREATE TABLE IF NOT EXISTS feature_flags (
+    flag TEXT PRIMARY KEY,
+    override TEXT NOT NULL,
+    updated_at DATETIME NOT NULL
+);
@@ -0,0 +1,3 @@
+-- Rollback script for plugin key-value storage
+
+DROP TABLE IF EXISTS plugin_data;
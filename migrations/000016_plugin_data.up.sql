@@ -0,0 +1,10 @@
+-- Sixteenth migration: plugin key-value storage
+
+-- What plugins keep with kv_set, one namespace per plugin name
+CREATE TABLE IF NOT EXISTS plugin_data (
+    plugin_id TEXT NOT NULL,
+    key TEXT NOT NULL,
+    value BLOB NOT NULL,
+    updated_at DATETIME NOT NULL,
+    PRIMARY KEY (plugin_id, key)
+);
@@ -1,11 +1,13 @@
 use anyhow::Result;
 use async_openai::{
-    config::OpenAIConfig,
+    config::{Config, OpenAIConfig},
     types::{
         ChatCompletionRequestAssistantMessageArgs,
         ChatCompletionRequestMessage,
         ChatCompletionRequestSystemMessageArgs,
         ChatCompletionRequestUserMessageArgs,
+        ChatCompletionResponseFormat,
+        ChatCompletionResponseFormatType,
         CreateChatCompletionRequestArgs,
     },
     Client,
@@ -15,6 +17,13 @@ use std::sync::Arc;
 use tokio::sync::RwLock;
 use tracing::{debug, error, info};
 
+use crate::data_residency::ProviderClass;
+use crate::http_client::{ClientKind, HttpClientFactory};
+use crate::query_metrics::{self, QueryMetrics};
+
+// Past messages sent to the model with each prompt
+pub const HISTORY_WINDOW: usize = 10;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ConversationContext {
     pub session_id: String,
@@ -29,8 +38,21 @@ pub struct ChatMessage {
     pub timestamp: chrono::DateTime<chrono::Utc>,
 }
 
+// One model reply with the usage the provider reported
+#[derive(Debug, Clone)]
+pub struct ChatReply {
+    pub text: String,
+    pub model: String,
+    pub prompt_tokens: Option<u32>,
+    pub completion_tokens: Option<u32>,
+    // The canned apology was returned because the provider call failed
+    pub fallback: bool,
+}
+
 pub struct AIService {
     client: Client<OpenAIConfig>,
+    // Decides which retrieved documents may be put in the prompt
+    provider_class: ProviderClass,
     model: String,
     max_tokens: u16,
     temperature: f32,
@@ -46,16 +68,27 @@ impl AIService {
             // This will use OPENAI_API_KEY environment variable
             OpenAIConfig::new()
         };
+        // An OpenAI-compatible server, e.g. a local model
+        let config = match std::env::var("CHAT_API_BASE") {
+            Ok(api_base) => config.with_api_base(api_base),
+            Err(_) => config,
+        };
+
+        Ok(Self::from_config(config))
+    }
 
+    pub fn from_config(config: OpenAIConfig) -> Self {
+        let provider_class = ProviderClass::of_endpoint(config.api_base());
         let client = Client::with_config(config);
         
-        Ok(Self {
+        Self {
             client,
+            provider_class,
             model: "gpt-3.5-turbo".to_string(), // Using fastest model for quick responses
             max_tokens: 800,
             temperature: 0.7,
             conversations: Arc::new(RwLock::new(std::collections::HashMap::new())),
-        })
+        }
     }
 
     pub fn with_model(mut self, model: String) -> Self {
@@ -63,6 +96,26 @@ impl AIService {
         self
     }
 
+    // Send requests through the deployment's proxy and CAs
+    pub fn with_http(mut self, http: &HttpClientFactory) -> Result<Self> {
+        self.client = self.client.with_http_client(http.client(ClientKind::OpenAi)?);
+        Ok(self)
+    }
+
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    pub fn provider_class(&self) -> ProviderClass {
+        self.provider_class
+    }
+
+    // Cheapest authenticated call: listing the models
+    pub async fn check_credentials(&self) -> Result<()> {
+        self.client.models().list().await?;
+        Ok(())
+    }
+
     pub fn with_max_tokens(mut self, max_tokens: u16) -> Self {
         self.max_tokens = max_tokens;
         self
@@ -77,7 +130,32 @@ impl AIService {
         &self,
         message: &str,
         session_id: &str,
+        response_language: &str,
     ) -> Result<String> {
+        Ok(self.process_message_with_usage(message, session_id, response_language).await?.text)
+    }
+
+    pub async fn process_message_with_usage(
+        &self,
+        message: &str,
+        session_id: &str,
+        response_language: &str,
+    ) -> Result<ChatReply> {
+        let system_prompt = format!(
+            "{}\n\n{}",
+            self.get_system_prompt(),
+            crate::language::response_language_instruction(response_language)
+        );
+        self.process_message_with_prompt(message, session_id, &system_prompt).await
+    }
+
+    // As above, with the whole system prompt assembled by the caller
+    pub async fn process_message_with_prompt(
+        &self,
+        message: &str,
+        session_id: &str,
+        system_prompt: &str,
+    ) -> Result<ChatReply> {
         debug!("Processing message for session: {}", session_id);
         
         // Get or create conversation context
@@ -90,6 +168,8 @@ impl AIService {
             }
         });
 
+        context.system_prompt = system_prompt.to_string();
+
         // Add user message to context
         context.messages.push(ChatMessage {
             role: "user".to_string(),
@@ -106,11 +186,11 @@ impl AIService {
             )
         ];
 
-        // Add conversation history (keep last 10 messages for context)
+        // Add conversation history (the last HISTORY_WINDOW messages)
         let history_messages: Vec<_> = context.messages
             .iter()
             .rev()
-            .take(10)
+            .take(HISTORY_WINDOW)
             .rev()
             .collect();
 
@@ -144,16 +224,23 @@ impl AIService {
             Ok(resp) => resp,
             Err(e) => {
                 error!("OpenAI API error: {}", e);
-                return Ok(self.get_fallback_response());
+                return Ok(ChatReply {
+                    text: self.get_fallback_response(),
+                    model: self.model.clone(),
+                    prompt_tokens: None,
+                    completion_tokens: None,
+                    fallback: true,
+                });
             }
         };
 
         // Extract response text
-        let response_text = response
+        let content = response
             .choices
             .first()
-            .and_then(|choice| choice.message.content.clone())
-            .unwrap_or_else(|| self.get_fallback_response());
+            .and_then(|choice| choice.message.content.clone());
+        let fallback = content.is_none();
+        let response_text = content.unwrap_or_else(|| self.get_fallback_response());
 
         // Add assistant response to context
         context.messages.push(ChatMessage {
@@ -168,7 +255,61 @@ impl AIService {
         }
 
         info!("Generated response for session: {}", session_id);
-        Ok(response_text)
+        Ok(ChatReply {
+            text: response_text,
+            model: response.model,
+            prompt_tokens: response.usage.as_ref().map(|u| u.prompt_tokens),
+            completion_tokens: response.usage.as_ref().map(|u| u.completion_tokens),
+            fallback,
+        })
+    }
+
+    // One request outside any conversation, e.g. a summary. Unlike chat
+    // replies, a failed call is an error rather than the canned apology
+    pub async fn complete(&self, system_prompt: &str, message: &str) -> Result<ChatReply> {
+        self.complete_as(system_prompt, message, ChatCompletionResponseFormatType::Text).await
+    }
+
+    // Like `complete`, with the model held to answering with a JSON object.
+    // The system prompt has to ask for JSON for the provider to accept it
+    pub async fn complete_json(&self, system_prompt: &str, message: &str) -> Result<ChatReply> {
+        self.complete_as(system_prompt, message, ChatCompletionResponseFormatType::JsonObject).await
+    }
+
+    async fn complete_as(
+        &self,
+        system_prompt: &str,
+        message: &str,
+        format: ChatCompletionResponseFormatType,
+    ) -> Result<ChatReply> {
+        let request = CreateChatCompletionRequestArgs::default()
+            .model(&self.model)
+            .messages(vec![
+                ChatCompletionRequestMessage::System(
+                    ChatCompletionRequestSystemMessageArgs::default().content(system_prompt).build()?,
+                ),
+                ChatCompletionRequestMessage::User(
+                    ChatCompletionRequestUserMessageArgs::default().content(message).build()?,
+                ),
+            ])
+            .max_tokens(self.max_tokens)
+            .temperature(self.temperature)
+            .response_format(ChatCompletionResponseFormat { r#type: format })
+            .build()?;
+        let response = self.client.chat().create(request).await?;
+        let text = response
+            .choices
+            .first()
+            .and_then(|choice| choice.message.content.clone())
+            .ok_or_else(|| anyhow::anyhow!("The model returned no text"))?;
+
+        Ok(ChatReply {
+            text,
+            model: response.model,
+            prompt_tokens: response.usage.as_ref().map(|u| u.prompt_tokens),
+            completion_tokens: response.usage.as_ref().map(|u| u.completion_tokens),
+            fallback: false,
+        })
     }
 
     pub async fn clear_session(&self, session_id: &str) -> Result<()> {
@@ -177,6 +318,16 @@ impl AIService {
         Ok(())
     }
 
+    // Start a session's context from stored messages, e.g. those copied into a fork
+    pub async fn seed_session(&self, session_id: &str, messages: Vec<ChatMessage>) {
+        let mut conversations = self.conversations.write().await;
+        conversations.insert(session_id.to_string(), ConversationContext {
+            session_id: session_id.to_string(),
+            messages,
+            system_prompt: self.get_system_prompt(),
+        });
+    }
+
     pub async fn get_session_history(&self, session_id: &str) -> Result<Vec<ChatMessage>> {
         let conversations = self.conversations.read().await;
         if let Some(context) = conversations.get(session_id) {
@@ -186,7 +337,12 @@ impl AIService {
         }
     }
 
-    fn get_system_prompt(&self) -> String {
+    // Identifies the base prompt where its text is not shown
+    pub fn system_prompt_name(&self) -> &'static str {
+        "personal-assistant"
+    }
+
+    pub fn get_system_prompt(&self) -> String {
         "You are a helpful personal AI assistant. You are knowledgeable, friendly, and professional. \
          You help users with various tasks including answering questions, providing information, \
          and assisting with productivity. Keep your responses concise and relevant. \
@@ -206,6 +362,10 @@ pub struct SessionRecord {
     pub created_at: chrono::DateTime<chrono::Utc>,
     pub updated_at: chrono::DateTime<chrono::Utc>,
     pub metadata: Option<String>,
+    // Set on a fork: the session it was forked from and the last message
+    // copied from it
+    pub parent_session_id: Option<String>,
+    pub forked_from_message_id: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
@@ -215,15 +375,105 @@ pub struct MessageRecord {
     pub role: String,
     pub content: String,
     pub created_at: chrono::DateTime<chrono::Utc>,
+    // ISO 639-1 code detected for user messages, the response language for
+    // assistant messages
+    pub language: Option<String>,
+    // Set when spoken playback of an assistant reply was cut off; the byte
+    // offset into the audio where it stopped
+    pub interrupted_at_byte: Option<i64>,
+    // Set on assistant replies built from documents the residency policy
+    // keeps off cloud providers; the tag that restricted them. Such replies
+    // are not sent to cloud TTS
+    pub restricted_by: Option<String>,
+    // Only assistant messages carry stats; rows saved before they were
+    // recorded read back as all nulls
+    #[sqlx(flatten)]
+    pub stats: MessageStats,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, sqlx::FromRow)]
+pub struct MessageStats {
+    pub processing_time_ms: Option<i64>,
+    pub prompt_tokens: Option<i64>,
+    pub completion_tokens: Option<i64>,
+    pub model: Option<String>,
+    // "rag" when retrieved documents were added to the prompt,
+    // "keyword_fallback" when some of them came from the keyword index while
+    // the vector search was unavailable, "direct" without them, "fallback"
+    // when the model call failed
+    pub pipeline_mode: Option<String>,
+    pub retrieval_count: Option<i64>,
+}
+
+impl MessageStats {
+    pub fn for_reply(reply: &ChatReply, processing_time_ms: u64, retrieval_count: usize, keyword_fallback: bool) -> Self {
+        let pipeline_mode = if reply.fallback {
+            "fallback"
+        } else if retrieval_count > 0 && keyword_fallback {
+            "keyword_fallback"
+        } else if retrieval_count > 0 {
+            "rag"
+        } else {
+            "direct"
+        };
+
+        Self {
+            processing_time_ms: Some(processing_time_ms as i64),
+            prompt_tokens: reply.prompt_tokens.map(i64::from),
+            completion_tokens: reply.completion_tokens.map(i64::from),
+            model: Some(reply.model.clone()),
+            pipeline_mode: Some(pipeline_mode.to_string()),
+            retrieval_count: Some(retrieval_count as i64),
+        }
+    }
+}
+
+// A session forked from another, as created by ConversationStore::fork_session
+#[derive(Debug)]
+pub struct ForkedSession {
+    pub session: SessionRecord,
+    pub messages: Vec<MessageRecord>,
+    // When the fork point was written; what the parent had by then is shared
+    pub forked_at: chrono::DateTime<chrono::Utc>,
+}
+
+// Totals over a session's assistant messages; sums are null when no message
+// recorded the value
+#[derive(Debug, Clone, Default, PartialEq, Serialize, sqlx::FromRow)]
+pub struct SessionStats {
+    pub assistant_messages: i64,
+    pub total_processing_time_ms: Option<i64>,
+    pub avg_processing_time_ms: Option<f64>,
+    pub total_prompt_tokens: Option<i64>,
+    pub total_completion_tokens: Option<i64>,
+    pub total_retrievals: Option<i64>,
 }
 
 pub struct ConversationStore {
     pool: sqlx::SqlitePool,
+    metrics: QueryMetrics,
+}
+
+// Open a pool for a SQLite URL. An in-memory database (`sqlite::memory:`)
+// exists only while a connection to it is open, so its pool keeps exactly
+// one connection for its whole life.
+pub async fn connect_sqlite(database_url: &str) -> Result<sqlx::SqlitePool> {
+    if !database_url.contains(":memory:") {
+        return Ok(sqlx::SqlitePool::connect(database_url).await?);
+    }
+    
+    Ok(sqlx::sqlite::SqlitePoolOptions::new()
+        .max_connections(1)
+        .min_connections(1)
+        .idle_timeout(None)
+        .max_lifetime(None)
+        .connect(database_url)
+        .await?)
 }
 
 impl ConversationStore {
     pub async fn new(database_url: &str) -> Result<Self> {
-        let pool = sqlx::SqlitePool::connect(database_url).await?;
+        let pool = connect_sqlite(database_url).await?;
         
         // Create tables if they don't exist
         sqlx::query(
@@ -232,12 +482,20 @@ impl ConversationStore {
                 id TEXT PRIMARY KEY,
                 created_at TIMESTAMP NOT NULL,
                 updated_at TIMESTAMP NOT NULL,
-                metadata TEXT
+                metadata TEXT,
+                parent_session_id TEXT,
+                forked_from_message_id TEXT
             )
             "#,
         )
         .execute(&pool)
         .await?;
+        // Databases created before sessions could be forked
+        for column in ["parent_session_id TEXT", "forked_from_message_id TEXT"] {
+            let _ = sqlx::query(&format!("ALTER TABLE sessions ADD COLUMN {}", column))
+                .execute(&pool)
+                .await;
+        }
 
         sqlx::query(
             r#"
@@ -247,6 +505,15 @@ impl ConversationStore {
                 role TEXT NOT NULL,
                 content TEXT NOT NULL,
                 created_at TIMESTAMP NOT NULL,
+                language TEXT,
+                interrupted_at_byte INTEGER,
+                restricted_by TEXT,
+                processing_time_ms INTEGER,
+                prompt_tokens INTEGER,
+                completion_tokens INTEGER,
+                model TEXT,
+                pipeline_mode TEXT,
+                retrieval_count INTEGER,
                 FOREIGN KEY (session_id) REFERENCES sessions(id)
             )
             "#,
@@ -254,48 +521,126 @@ impl ConversationStore {
         .execute(&pool)
         .await?;
 
-        Ok(Self { pool })
-    }
+        // Databases created before messages had a language; SQLite has no
+        // ADD COLUMN IF NOT EXISTS, so a duplicate-column error is expected
+        let _ = sqlx::query("ALTER TABLE messages ADD COLUMN language TEXT")
+            .execute(&pool)
+            .await;
+        for column in [
+            "interrupted_at_byte INTEGER",
+            "restricted_by TEXT",
+            "processing_time_ms INTEGER",
+            "prompt_tokens INTEGER",
+            "completion_tokens INTEGER",
+            "model TEXT",
+            "pipeline_mode TEXT",
+            "retrieval_count INTEGER",
+        ] {
+            let _ = sqlx::query(&format!("ALTER TABLE messages ADD COLUMN {}", column))
+                .execute(&pool)
+                .await;
+        }
 
-    pub async fn save_session(&self, session: &SessionRecord) -> Result<()> {
-        sqlx::query(
-            r#"
-            INSERT INTO sessions (id, created_at, updated_at, metadata)
-            VALUES (?, ?, ?, ?)
-            ON CONFLICT(id) DO UPDATE SET
-                updated_at = excluded.updated_at,
-                metadata = excluded.metadata
-            "#,
-        )
-        .bind(&session.id)
-        .bind(&session.created_at)
-        .bind(&session.updated_at)
-        .bind(&session.metadata)
-        .execute(&self.pool)
-        .await?;
+        Self::create_indexes(&pool).await?;
 
+        Ok(Self { pool, metrics: query_metrics::conversation_store_metrics() })
+    }
+
+    // History reads filter by session and order by time; the session list is
+    // ordered by last update. Also adds them to databases created before
+    async fn create_indexes(pool: &sqlx::SqlitePool) -> Result<()> {
+        for index in [
+            "CREATE INDEX IF NOT EXISTS idx_messages_session_created ON messages(session_id, created_at)",
+            "CREATE INDEX IF NOT EXISTS idx_sessions_updated_at ON sessions(updated_at)",
+        ] {
+            sqlx::query(index).execute(pool).await?;
+        }
         Ok(())
     }
 
+    pub fn query_metrics(&self) -> &QueryMetrics {
+        &self.metrics
+    }
+
+    pub async fn save_session(&self, session: &SessionRecord) -> Result<()> {
+        let _timer = self.metrics.time("save_session").param(&session.id);
+        insert_session(&self.pool, session).await
+    }
+
     pub async fn save_message(&self, message: &MessageRecord) -> Result<()> {
-        sqlx::query(
-            r#"
-            INSERT INTO messages (id, session_id, role, content, created_at)
-            VALUES (?, ?, ?, ?, ?)
-            "#,
-        )
-        .bind(&message.id)
-        .bind(&message.session_id)
-        .bind(&message.role)
-        .bind(&message.content)
-        .bind(&message.created_at)
-        .execute(&self.pool)
-        .await?;
+        let _timer = self.metrics.time("save_message").param(&message.id).param(&message.content);
+        insert_message(&self.pool, message).await
+    }
 
-        Ok(())
+    // Copy a session's messages up to and including `from_message_id` into a
+    // new session that keeps the parent's settings. None when the message is
+    // not part of the session. Derived memories stay with the parent.
+    pub async fn fork_session(&self, parent: &SessionRecord, from_message_id: &str) -> Result<Option<ForkedSession>> {
+        let history = self.get_session_messages(&parent.id).await?;
+        let Some(fork_point) = history.iter().position(|m| m.id == from_message_id) else {
+            return Ok(None);
+        };
+        let forked_at = history[fork_point].created_at;
+
+        let now = chrono::Utc::now();
+        let session = SessionRecord {
+            id: uuid::Uuid::new_v4().to_string(),
+            created_at: now,
+            updated_at: now,
+            metadata: parent.metadata.clone(),
+            parent_session_id: Some(parent.id.clone()),
+            forked_from_message_id: Some(from_message_id.to_string()),
+        };
+        let messages: Vec<MessageRecord> = history
+            .into_iter()
+            .take(fork_point + 1)
+            .map(|message| MessageRecord {
+                id: uuid::Uuid::new_v4().to_string(),
+                session_id: session.id.clone(),
+                ..message
+            })
+            .collect();
+
+        let mut timer = self.metrics.time("fork_session").param(&parent.id).param(from_message_id);
+        timer.rows(messages.len());
+        let mut tx = self.pool.begin().await?;
+        insert_session(&mut *tx, &session).await?;
+        for message in &messages {
+            insert_message(&mut *tx, message).await?;
+        }
+        tx.commit().await?;
+        drop(timer);
+
+        Ok(Some(ForkedSession { session, messages, forked_at }))
+    }
+
+    // Returns false when no message has this id
+    pub async fn mark_interrupted(&self, message_id: &str, byte_offset: u64) -> Result<bool> {
+        let mut timer = self.metrics.time("mark_interrupted").param(message_id).param(byte_offset);
+        let result = sqlx::query("UPDATE messages SET interrupted_at_byte = ? WHERE id = ?")
+            .bind(byte_offset as i64)
+            .bind(message_id)
+            .execute(&self.pool)
+            .await?;
+        timer.rows(result.rows_affected() as usize);
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    // The residency tag recorded on a message, None for unknown ids too
+    pub async fn message_restriction(&self, message_id: &str) -> Result<Option<String>> {
+        let mut timer = self.metrics.time("message_restriction").param(message_id);
+        let restricted_by: Option<Option<String>> = sqlx::query_scalar("SELECT restricted_by FROM messages WHERE id = ?")
+            .bind(message_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        timer.rows(restricted_by.iter().count());
+
+        Ok(restricted_by.flatten())
     }
 
     pub async fn get_session_messages(&self, session_id: &str) -> Result<Vec<MessageRecord>> {
+        let mut timer = self.metrics.time("get_session_messages").param(session_id);
         let messages = sqlx::query_as::<_, MessageRecord>(
             r#"
             SELECT * FROM messages
@@ -306,11 +651,36 @@ impl ConversationStore {
         .bind(session_id)
         .fetch_all(&self.pool)
         .await?;
+        timer.rows(messages.len());
 
         Ok(messages)
     }
 
+    pub async fn get_session_stats(&self, session_id: &str) -> Result<SessionStats> {
+        let mut timer = self.metrics.time("get_session_stats").param(session_id);
+        let stats = sqlx::query_as::<_, SessionStats>(
+            r#"
+            SELECT
+                COUNT(*) AS assistant_messages,
+                SUM(processing_time_ms) AS total_processing_time_ms,
+                AVG(processing_time_ms) AS avg_processing_time_ms,
+                SUM(prompt_tokens) AS total_prompt_tokens,
+                SUM(completion_tokens) AS total_completion_tokens,
+                SUM(retrieval_count) AS total_retrievals
+            FROM messages
+            WHERE session_id = ? AND role = 'assistant'
+            "#,
+        )
+        .bind(session_id)
+        .fetch_one(&self.pool)
+        .await?;
+        timer.rows(1);
+
+        Ok(stats)
+    }
+
     pub async fn get_session(&self, session_id: &str) -> Result<Option<SessionRecord>> {
+        let mut timer = self.metrics.time("get_session").param(session_id);
         let session = sqlx::query_as::<_, SessionRecord>(
             r#"
             SELECT * FROM sessions
@@ -320,14 +690,16 @@ impl ConversationStore {
         .bind(session_id)
         .fetch_optional(&self.pool)
         .await?;
+        timer.rows(session.iter().count());
 
         Ok(session)
     }
     
     pub async fn get_recent_sessions(&self, limit: i32) -> Result<Vec<SessionRecord>> {
+        let mut timer = self.metrics.time("get_recent_sessions").param(limit);
         let sessions = sqlx::query_as::<_, SessionRecord>(
             r#"
-            SELECT id, created_at, updated_at, metadata 
+            SELECT id, created_at, updated_at, metadata, parent_session_id, forked_from_message_id
             FROM sessions 
             ORDER BY updated_at DESC 
             LIMIT ?
@@ -336,8 +708,350 @@ impl ConversationStore {
         .bind(limit)
         .fetch_all(&self.pool)
         .await?;
+        timer.rows(sessions.len());
         
         Ok(sessions)
     }
     
-}
\ No newline at end of file
+}
+
+// Inserts run against the pool or inside a transaction
+async fn insert_session<'e, E: sqlx::SqliteExecutor<'e>>(executor: E, session: &SessionRecord) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO sessions (id, created_at, updated_at, metadata, parent_session_id, forked_from_message_id)
+        VALUES (?, ?, ?, ?, ?, ?)
+        ON CONFLICT(id) DO UPDATE SET
+            updated_at = excluded.updated_at,
+            metadata = excluded.metadata
+        "#,
+    )
+    .bind(&session.id)
+    .bind(&session.created_at)
+    .bind(&session.updated_at)
+    .bind(&session.metadata)
+    .bind(&session.parent_session_id)
+    .bind(&session.forked_from_message_id)
+    .execute(executor)
+    .await?;
+
+    Ok(())
+}
+
+async fn insert_message<'e, E: sqlx::SqliteExecutor<'e>>(executor: E, message: &MessageRecord) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO messages (
+            id, session_id, role, content, created_at, language, interrupted_at_byte, restricted_by,
+            processing_time_ms, prompt_tokens, completion_tokens, model, pipeline_mode, retrieval_count
+        )
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        "#,
+    )
+    .bind(&message.id)
+    .bind(&message.session_id)
+    .bind(&message.role)
+    .bind(&message.content)
+    .bind(&message.created_at)
+    .bind(&message.language)
+    .bind(message.interrupted_at_byte)
+    .bind(&message.restricted_by)
+    .bind(message.stats.processing_time_ms)
+    .bind(message.stats.prompt_tokens)
+    .bind(message.stats.completion_tokens)
+    .bind(&message.stats.model)
+    .bind(&message.stats.pipeline_mode)
+    .bind(message.stats.retrieval_count)
+    .execute(executor)
+    .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{routing::post, Json, Router};
+
+    // Answers every completion request the way the OpenAI API does,
+    // including the usage block
+    async fn mock_openai() -> String {
+        let app = Router::new().route(
+            "/chat/completions",
+            post(|| async {
+                Json(serde_json::json!({
+                    "id": "chatcmpl-test",
+                    "object": "chat.completion",
+                    "created": 1700000000,
+                    "model": "gpt-test-0125",
+                    "choices": [{
+                        "index": 0,
+                        "message": {"role": "assistant", "content": "Hello from the mock"},
+                        "finish_reason": "stop"
+                    }],
+                    "usage": {"prompt_tokens": 42, "completion_tokens": 7, "total_tokens": 49}
+                }))
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        format!("http://{}", addr)
+    }
+
+    async fn test_store() -> ConversationStore {
+        let path = std::env::temp_dir().join(format!("rusty-ai-conversations-{}.db", uuid::Uuid::new_v4()));
+        ConversationStore::new(&format!("sqlite://{}?mode=rwc", path.display())).await.unwrap()
+    }
+
+    fn message(session_id: &str, role: &str, stats: MessageStats) -> MessageRecord {
+        MessageRecord {
+            id: uuid::Uuid::new_v4().to_string(),
+            session_id: session_id.to_string(),
+            role: role.to_string(),
+            content: "content".to_string(),
+            created_at: chrono::Utc::now(),
+            language: None,
+            interrupted_at_byte: None,
+            restricted_by: None,
+            stats,
+        }
+    }
+
+    async fn save_session(store: &ConversationStore, session_id: &str) {
+        store.save_session(&SessionRecord {
+            id: session_id.to_string(),
+            created_at: chrono::Utc::now(),
+            updated_at: chrono::Utc::now(),
+            metadata: None,
+            parent_session_id: None,
+            forked_from_message_id: None,
+        }).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_chat_usage_is_persisted_with_the_reply() {
+        let base = mock_openai().await;
+        let service = AIService::from_config(OpenAIConfig::new().with_api_key("test").with_api_base(base));
+        let store = test_store().await;
+        save_session(&store, "s1").await;
+
+        let reply = service.process_message_with_usage("Hi", "s1", "en").await.unwrap();
+        assert_eq!(reply.text, "Hello from the mock");
+        assert!(!reply.fallback);
+
+        let stats = MessageStats::for_reply(&reply, 120, 2, false);
+        store.save_message(&message("s1", "user", MessageStats::default())).await.unwrap();
+        store.save_message(&message("s1", "assistant", stats)).await.unwrap();
+
+        let messages = store.get_session_messages("s1").await.unwrap();
+        let assistant = messages.iter().find(|m| m.role == "assistant").unwrap();
+        assert_eq!(assistant.stats, MessageStats {
+            processing_time_ms: Some(120),
+            prompt_tokens: Some(42),
+            completion_tokens: Some(7),
+            model: Some("gpt-test-0125".to_string()),
+            pipeline_mode: Some("rag".to_string()),
+            retrieval_count: Some(2),
+        });
+
+        let user = messages.iter().find(|m| m.role == "user").unwrap();
+        assert_eq!(user.stats, MessageStats::default());
+
+        // Context from the keyword index alone is marked as such
+        let stats = MessageStats::for_reply(&reply, 120, 2, true);
+        assert_eq!(stats.pipeline_mode.as_deref(), Some("keyword_fallback"));
+    }
+
+    #[tokio::test]
+    async fn test_rows_without_stats_serialize_as_nulls() {
+        let store = test_store().await;
+        save_session(&store, "old").await;
+        // A row written before the stats columns were populated
+        sqlx::query("INSERT INTO messages (id, session_id, role, content, created_at) VALUES (?, ?, ?, ?, ?)")
+            .bind("legacy")
+            .bind("old")
+            .bind("assistant")
+            .bind("an old reply")
+            .bind(chrono::Utc::now())
+            .execute(&store.pool)
+            .await
+            .unwrap();
+
+        let messages = store.get_session_messages("old").await.unwrap();
+        let json = serde_json::to_value(&messages[0].stats).unwrap();
+        for field in ["processing_time_ms", "prompt_tokens", "completion_tokens", "model", "pipeline_mode", "retrieval_count"] {
+            assert!(json[field].is_null(), "{} should be null", field);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_session_stats_aggregate_assistant_messages() {
+        let store = test_store().await;
+        save_session(&store, "s1").await;
+        save_session(&store, "s2").await;
+
+        let reply = |prompt, completion| ChatReply {
+            text: String::new(),
+            model: "gpt-test".to_string(),
+            prompt_tokens: prompt,
+            completion_tokens: completion,
+            fallback: false,
+        };
+        store.save_message(&message("s1", "user", MessageStats::default())).await.unwrap();
+        store.save_message(&message("s1", "assistant", MessageStats::for_reply(&reply(Some(100), Some(20)), 300, 3, false))).await.unwrap();
+        store.save_message(&message("s1", "assistant", MessageStats::for_reply(&reply(Some(50), Some(10)), 100, 0, false))).await.unwrap();
+        // Neither the unmetered reply nor another session's reply add tokens
+        store.save_message(&message("s1", "assistant", MessageStats::for_reply(&reply(None, None), 200, 1, false))).await.unwrap();
+        store.save_message(&message("s2", "assistant", MessageStats::for_reply(&reply(Some(999), Some(999)), 999, 9, false))).await.unwrap();
+
+        let stats = store.get_session_stats("s1").await.unwrap();
+        assert_eq!(stats, SessionStats {
+            assistant_messages: 3,
+            total_processing_time_ms: Some(600),
+            avg_processing_time_ms: Some(200.0),
+            total_prompt_tokens: Some(150),
+            total_completion_tokens: Some(30),
+            total_retrievals: Some(4),
+        });
+
+        let empty = store.get_session_stats("missing").await.unwrap();
+        assert_eq!(empty.assistant_messages, 0);
+        assert_eq!(empty.total_prompt_tokens, None);
+    }
+
+    #[tokio::test]
+    async fn test_restricted_replies_are_recorded() {
+        let local = AIService::from_config(OpenAIConfig::new().with_api_base(mock_openai().await));
+        assert_eq!(local.provider_class(), ProviderClass::Local);
+        assert_eq!(AIService::from_config(OpenAIConfig::new()).provider_class(), ProviderClass::Cloud);
+
+        let store = test_store().await;
+        save_session(&store, "s1").await;
+        let mut reply = message("s1", "assistant", MessageStats::default());
+        reply.restricted_by = Some("medical".to_string());
+        store.save_message(&reply).await.unwrap();
+        let plain = message("s1", "assistant", MessageStats::default());
+        store.save_message(&plain).await.unwrap();
+
+        assert_eq!(store.message_restriction(&reply.id).await.unwrap().as_deref(), Some("medical"));
+        assert_eq!(store.message_restriction(&plain.id).await.unwrap(), None);
+        assert_eq!(store.message_restriction("missing").await.unwrap(), None);
+    }
+
+    fn text_message(session_id: &str, role: &str, content: &str) -> MessageRecord {
+        MessageRecord {
+            content: content.to_string(),
+            ..message(session_id, role, MessageStats::default())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_forked_branches_diverge_after_the_shared_prefix() {
+        let store = test_store().await;
+        store.save_session(&SessionRecord {
+            id: "trip".to_string(),
+            created_at: chrono::Utc::now(),
+            updated_at: chrono::Utc::now(),
+            metadata: Some(r#"{"response_language":"de"}"#.to_string()),
+            parent_session_id: None,
+            forked_from_message_id: None,
+        }).await.unwrap();
+        let history = [
+            text_message("trip", "user", "Plan a trip to Lyon"),
+            text_message("trip", "assistant", "Train or car?"),
+            text_message("trip", "user", "Train"),
+            text_message("trip", "assistant", "Here are the trains"),
+        ];
+        for message in &history {
+            store.save_message(message).await.unwrap();
+        }
+
+        let parent = store.get_session("trip").await.unwrap().unwrap();
+        let fork = store.fork_session(&parent, &history[1].id).await.unwrap().unwrap();
+        assert_eq!(fork.messages.len(), 2);
+        assert_eq!(fork.forked_at, history[1].created_at);
+
+        // Continue both branches
+        store.save_message(&text_message(&fork.session.id, "user", "Car")).await.unwrap();
+        store.save_message(&text_message("trip", "user", "Book the 9:00")).await.unwrap();
+
+        let contents = |messages: Vec<MessageRecord>| messages.into_iter().map(|m| m.content).collect::<Vec<_>>();
+        let original = contents(store.get_session_messages("trip").await.unwrap());
+        let forked = contents(store.get_session_messages(&fork.session.id).await.unwrap());
+        assert_eq!(original[..2], forked[..2]);
+        assert_eq!(forked[2], "Car");
+        assert_eq!(original[2..], ["Train", "Here are the trains", "Book the 9:00"]);
+
+        // The fork keeps the settings and records where it came from
+        let stored = store.get_session(&fork.session.id).await.unwrap().unwrap();
+        assert_eq!(stored.metadata.as_deref(), Some(r#"{"response_language":"de"}"#));
+        assert_eq!(stored.parent_session_id.as_deref(), Some("trip"));
+        assert_eq!(stored.forked_from_message_id.as_deref(), Some(history[1].id.as_str()));
+        let listed = store.get_recent_sessions(10).await.unwrap();
+        assert!(listed.iter().any(|s| s.id == fork.session.id && s.parent_session_id.as_deref() == Some("trip")));
+
+        // A fork can itself be forked, and unknown messages are refused
+        let grandchild = store.fork_session(&stored, &fork.messages[0].id).await.unwrap().unwrap();
+        assert_eq!(grandchild.session.parent_session_id.as_deref(), Some(fork.session.id.as_str()));
+        assert!(store.fork_session(&parent, "missing").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_seeded_session_continues_from_the_copied_history() {
+        let service = AIService::from_config(OpenAIConfig::new().with_api_key("test").with_api_base(mock_openai().await));
+        let copied = vec![ChatMessage {
+            role: "user".to_string(),
+            content: "Plan a trip to Lyon".to_string(),
+            timestamp: chrono::Utc::now(),
+        }];
+        service.seed_session("fork", copied).await;
+
+        service.process_message("Car", "fork", "en").await.unwrap();
+
+        let history = service.get_session_history("fork").await.unwrap();
+        let contents: Vec<&str> = history.iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(contents, vec!["Plan a trip to Lyon", "Car", "Hello from the mock"]);
+    }
+
+    const HISTORY_QUERY: &str = "SELECT * FROM messages WHERE session_id = 'session-7' ORDER BY created_at ASC";
+
+    async fn history_plan(store: &ConversationStore) -> String {
+        sqlx::query(&format!("EXPLAIN QUERY PLAN {}", HISTORY_QUERY))
+            .fetch_all(&store.pool)
+            .await
+            .unwrap()
+            .iter()
+            .map(|row| sqlx::Row::get::<String, _>(row, "detail"))
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+
+    // Checks the plan SQLite picks rather than timings, so the result does
+    // not depend on how fast the machine is
+    #[tokio::test]
+    async fn test_history_index_serves_session_reads() {
+        let store = ConversationStore::new("sqlite::memory:").await.unwrap();
+
+        for i in 0..200 {
+            save_session(&store, &format!("session-{}", i)).await;
+        }
+        let start = chrono::Utc::now() - chrono::Duration::days(90);
+        let mut tx = store.pool.begin().await.unwrap();
+        for i in 0..2_000i64 {
+            let mut record = message(&format!("session-{}", i % 200), "user", MessageStats::default());
+            record.created_at = start + chrono::Duration::seconds(i * 30);
+            insert_message(&mut *tx, &record).await.unwrap();
+        }
+        tx.commit().await.unwrap();
+
+        // As on a database written before the index existed
+        sqlx::query("DROP INDEX idx_messages_session_created").execute(&store.pool).await.unwrap();
+        assert!(history_plan(&store).await.contains("SCAN messages"));
+
+        // What opening the store runs adds the index back
+        ConversationStore::create_indexes(&store.pool).await.unwrap();
+        assert!(history_plan(&store).await.contains("USING INDEX idx_messages_session_created"));
+        assert_eq!(store.get_session_messages("session-7").await.unwrap().len(), 10);
+    }
+}
@@ -0,0 +1,465 @@
+// Scheduled backups. Each run copies the SQLite database and snapshots every
+// vector collection into one directory, with a manifest linking the two, so a
+// disaster recovery brings back conversations and the knowledge base from the
+// same moment. `rusty-ai-api restore <dir>` puts a backup back in place.
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use qdrant_client::Payload;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+
+use crate::ai_service::connect_sqlite;
+use crate::http_client::HttpClientFactory;
+use crate::knowledge_service_simple::{self, EmbeddedCollection, KnowledgeService};
+use crate::vector_store::{VectorPoint, VectorStore};
+
+// Bumped when the layout of a backup directory changes
+pub const SCHEMA_VERSION: u32 = 1;
+const MANIFEST_FILE: &str = "manifest.json";
+const DATABASE_FILE: &str = "rusty_ai.db";
+// Points read from or written to the vector store at a time
+const SNAPSHOT_BATCH_SIZE: usize = 256;
+const DEFAULT_BACKUP_DIR: &str = "./backups";
+const DEFAULT_INTERVAL_HOURS: u64 = 24;
+const DEFAULT_RETENTION_DAYS: u64 = 30;
+
+#[derive(Debug, Clone)]
+pub struct BackupConfig {
+    pub dir: PathBuf,
+    // None turns scheduled backups off
+    pub interval: Option<Duration>,
+    // Older backups are removed after each run
+    pub retention: chrono::Duration,
+}
+
+impl BackupConfig {
+    // BACKUP_ENABLED, BACKUP_DIRECTORY, BACKUP_INTERVAL_HOURS and
+    // BACKUP_RETENTION_DAYS
+    pub fn from_env() -> Self {
+        let number = |name: &str| std::env::var(name).ok().and_then(|v| v.parse::<u64>().ok());
+        let enabled = std::env::var("BACKUP_ENABLED").map(|v| v != "false" && v != "0").unwrap_or(true);
+        Self {
+            dir: std::env::var("BACKUP_DIRECTORY").map(PathBuf::from).unwrap_or_else(|_| PathBuf::from(DEFAULT_BACKUP_DIR)),
+            interval: match number("BACKUP_INTERVAL_HOURS").unwrap_or(DEFAULT_INTERVAL_HOURS) {
+                hours if enabled && hours > 0 => Some(Duration::from_secs(hours * 3600)),
+                _ => None,
+            },
+            retention: chrono::Duration::days(number("BACKUP_RETENTION_DAYS").unwrap_or(DEFAULT_RETENTION_DAYS).max(1) as i64),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BackupManifest {
+    pub schema_version: u32,
+    pub created_at: DateTime<Utc>,
+    // File name of the database copy; None when the database was in memory
+    pub database: Option<String>,
+    pub collections: Vec<CollectionSnapshot>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CollectionSnapshot {
+    pub collection: String,
+    // JSON lines file, one point per line
+    pub file: String,
+    pub embedding_model: String,
+    pub dimension: u64,
+    pub point_count: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SnapshotPoint {
+    id: String,
+    vector: Vec<f32>,
+    payload: serde_json::Map<String, serde_json::Value>,
+}
+
+// Back up the database behind `database_url` and the given collections into
+// a new directory under `dir`. The directory only gets its final name once
+// everything is written, so an interrupted run never looks like a backup
+pub async fn create_backup(
+    dir: &Path,
+    database_url: &str,
+    store: &VectorStore,
+    collections: &[EmbeddedCollection],
+) -> Result<(PathBuf, BackupManifest)> {
+    let created_at = Utc::now();
+    let target = dir.join(created_at.format("%Y%m%dT%H%M%S%.3fZ").to_string());
+    let partial = target.with_extension("partial");
+    std::fs::create_dir_all(&partial).with_context(|| format!("Failed to create {}", partial.display()))?;
+
+    let database = match sqlite_path(database_url) {
+        Some(_) => {
+            let pool = connect_sqlite(database_url).await?;
+            let copy = partial.join(DATABASE_FILE);
+            sqlx::query("VACUUM INTO ?")
+                .bind(copy.to_string_lossy().into_owned())
+                .execute(&pool)
+                .await
+                .context("Failed to copy the database")?;
+            pool.close().await;
+            Some(DATABASE_FILE.to_string())
+        }
+        None => None,
+    };
+
+    let mut snapshots = Vec::with_capacity(collections.len());
+    for collection in collections {
+        let file = format!("{}.jsonl", collection.name);
+        let point_count = snapshot_collection(store, &collection.name, &partial.join(&file)).await?;
+        snapshots.push(CollectionSnapshot {
+            collection: collection.name.clone(),
+            file,
+            embedding_model: collection.embedding_model.clone(),
+            dimension: collection.dimension,
+            point_count,
+        });
+    }
+
+    let manifest = BackupManifest { schema_version: SCHEMA_VERSION, created_at, database, collections: snapshots };
+    std::fs::write(partial.join(MANIFEST_FILE), serde_json::to_vec_pretty(&manifest)?)?;
+    std::fs::rename(&partial, &target)?;
+    Ok((target, manifest))
+}
+
+async fn snapshot_collection(store: &VectorStore, collection: &str, path: &Path) -> Result<u64> {
+    let mut writer = BufWriter::new(std::fs::File::create(path)?);
+    let mut offset: Option<String> = None;
+    let mut written = 0;
+    loop {
+        let (points, next) = store.export_page(collection, offset.as_deref(), SNAPSHOT_BATCH_SIZE as u32).await?;
+        for point in points {
+            let point = SnapshotPoint { id: point.id, vector: point.vector, payload: point.payload.into() };
+            serde_json::to_writer(&mut writer, &point)?;
+            writer.write_all(b"\n")?;
+            written += 1;
+        }
+        match next {
+            Some(next) => offset = Some(next),
+            None => break,
+        }
+    }
+    writer.flush()?;
+    Ok(written)
+}
+
+pub fn read_manifest(backup: &Path) -> Result<BackupManifest> {
+    let path = backup.join(MANIFEST_FILE);
+    let text = std::fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let manifest: BackupManifest =
+        serde_json::from_str(&text).with_context(|| format!("Invalid backup manifest {}", path.display()))?;
+    if manifest.schema_version != SCHEMA_VERSION {
+        anyhow::bail!(
+            "Backup {} has schema version {}, this server reads version {}",
+            backup.display(),
+            manifest.schema_version,
+            SCHEMA_VERSION
+        );
+    }
+    Ok(manifest)
+}
+
+// Vectors from another embedding model are not comparable with the ones new
+// queries get, so such snapshots are refused rather than restored
+pub fn check_embedding_models(manifest: &BackupManifest, collections: &[EmbeddedCollection]) -> Result<()> {
+    for snapshot in &manifest.collections {
+        let Some(current) = collections.iter().find(|c| c.name == snapshot.collection) else {
+            anyhow::bail!(
+                "Backup has a snapshot of {}, which this server does not use (is the local embedding provider configured?). \
+                 Restore with --database-only and re-embed the documents instead",
+                snapshot.collection
+            );
+        };
+        if current.embedding_model != snapshot.embedding_model || current.dimension != snapshot.dimension {
+            anyhow::bail!(
+                "Snapshot of {} was embedded with {} ({} dimensions), but this server embeds with {} ({} dimensions). \
+                 Restore with --database-only and re-embed the documents with the current model instead",
+                snapshot.collection,
+                snapshot.embedding_model,
+                snapshot.dimension,
+                current.embedding_model,
+                current.dimension
+            );
+        }
+    }
+    Ok(())
+}
+
+// Replace the database file behind `database_url` with the backup copy. The
+// copy is staged and flushed next to the live file, then renamed over it, so
+// an interrupted restore leaves the old database in place. The server must
+// not be running
+pub fn restore_database(backup: &Path, manifest: &BackupManifest, database_url: &str) -> Result<bool> {
+    let Some(file) = &manifest.database else {
+        return Ok(false);
+    };
+    let path = sqlite_path(database_url)
+        .ok_or_else(|| anyhow::anyhow!("Cannot restore into {}; point DATABASE_URL at a database file", database_url))?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let staging = path.with_extension("restore-tmp");
+    std::fs::copy(backup.join(file), &staging)
+        .with_context(|| format!("Failed to stage the database at {}", staging.display()))?;
+    if let Err(e) = std::fs::File::open(&staging).and_then(|f| f.sync_all()) {
+        std::fs::remove_file(&staging).ok();
+        return Err(e).with_context(|| format!("Failed to flush {}", staging.display()));
+    }
+    // A write-ahead log left by the old database would be replayed over the
+    // restored one
+    for suffix in ["-wal", "-shm"] {
+        let stale = PathBuf::from(format!("{}{}", path.display(), suffix));
+        if stale.exists() {
+            std::fs::remove_file(&stale)?;
+        }
+    }
+    if let Err(e) = std::fs::rename(&staging, &path) {
+        std::fs::remove_file(&staging).ok();
+        return Err(e).with_context(|| format!("Failed to restore the database to {}", path.display()));
+    }
+    Ok(true)
+}
+
+// Re-create every snapshotted collection and load its points, then check the
+// point counts against the manifest
+pub async fn restore_vectors(
+    backup: &Path,
+    manifest: &BackupManifest,
+    store: &VectorStore,
+    collections: &[EmbeddedCollection],
+) -> Result<Vec<(String, u64)>> {
+    check_embedding_models(manifest, collections)?;
+
+    let mut restored = Vec::with_capacity(manifest.collections.len());
+    for snapshot in &manifest.collections {
+        store.recreate_collection(&snapshot.collection, snapshot.dimension).await?;
+        let path = backup.join(&snapshot.file);
+        let reader = BufReader::new(std::fs::File::open(&path).with_context(|| format!("Failed to open {}", path.display()))?);
+        let mut batch = Vec::with_capacity(SNAPSHOT_BATCH_SIZE);
+        for line in reader.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let point: SnapshotPoint =
+                serde_json::from_str(&line).with_context(|| format!("Invalid point in {}", path.display()))?;
+            batch.push(VectorPoint { id: point.id, vector: point.vector, payload: Payload::from(point.payload) });
+            if batch.len() == SNAPSHOT_BATCH_SIZE {
+                store.upsert(&snapshot.collection, std::mem::take(&mut batch)).await?;
+            }
+        }
+        if !batch.is_empty() {
+            store.upsert(&snapshot.collection, batch).await?;
+        }
+
+        let count = store.point_count(&snapshot.collection).await?;
+        if count != snapshot.point_count {
+            anyhow::bail!(
+                "Restored {} points into {}, the manifest lists {}",
+                count,
+                snapshot.collection,
+                snapshot.point_count
+            );
+        }
+        restored.push((snapshot.collection.clone(), count));
+    }
+    Ok(restored)
+}
+
+#[derive(Debug)]
+pub struct RestoreSummary {
+    pub created_at: DateTime<Utc>,
+    pub database: bool,
+    // Collections and their verified point counts; empty with --database-only
+    pub collections: Vec<(String, u64)>,
+}
+
+impl fmt::Display for RestoreSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Restored backup taken at {}", self.created_at.to_rfc3339())?;
+        writeln!(f, "  database: {}", if self.database { "restored" } else { "not in backup" })?;
+        if self.collections.is_empty() {
+            write!(f, "  vectors: not restored")?;
+        }
+        for (i, (collection, count)) in self.collections.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "  {}: {} points", collection, count)?;
+        }
+        Ok(())
+    }
+}
+
+// The restore command: the database named by DATABASE_URL and the Qdrant
+// collections of the configured embedding providers. Models are checked
+// before anything is overwritten
+pub async fn restore_from_env(backup: &Path, vectors: bool) -> Result<RestoreSummary> {
+    let manifest = read_manifest(backup)?;
+    let database_url = std::env::var("DATABASE_URL").unwrap_or_else(|_| "sqlite:./data/rusty_ai.db".to_string());
+
+    let store = if vectors {
+        let (store, collections) = knowledge_service_simple::configured_vector_store(&HttpClientFactory::from_env()?)?;
+        check_embedding_models(&manifest, &collections)?;
+        Some((store, collections))
+    } else {
+        None
+    };
+
+    let database = restore_database(backup, &manifest, &database_url)?;
+    let collections = match &store {
+        Some((store, collections)) => restore_vectors(backup, &manifest, store, collections).await?,
+        None => Vec::new(),
+    };
+    Ok(RestoreSummary { created_at: manifest.created_at, database, collections })
+}
+
+// Back up on the configured cadence, starting one interval after startup
+pub fn spawn_scheduler(config: BackupConfig, database_url: String, knowledge: Option<Arc<KnowledgeService>>) {
+    let Some(interval) = config.interval else {
+        info!("Scheduled backups are off");
+        return;
+    };
+    tokio::spawn(async move {
+        // Without a knowledge service only the database is backed up
+        let no_vectors = VectorStore::in_memory();
+        let mut ticker = tokio::time::interval(interval);
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            let (store, collections) = match &knowledge {
+                Some(ks) => (ks.vector_store(), ks.embedded_collections()),
+                None => (&no_vectors, Vec::new()),
+            };
+            match create_backup(&config.dir, &database_url, store, &collections).await {
+                Ok((path, manifest)) => {
+                    let points: u64 = manifest.collections.iter().map(|c| c.point_count).sum();
+                    info!("Backup written to {} ({} vector points)", path.display(), points);
+                    if let Err(e) = prune_backups(&config.dir, Utc::now() - config.retention) {
+                        warn!("Failed to remove old backups: {:#}", e);
+                    }
+                }
+                Err(e) => warn!("Scheduled backup failed: {:#}", e),
+            }
+        }
+    });
+}
+
+// Remove the backups taken before `cutoff`. Directories without a readable
+// manifest are left alone
+fn prune_backups(dir: &Path, cutoff: DateTime<Utc>) -> Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if read_manifest(&path).is_ok_and(|manifest| manifest.created_at < cutoff) {
+            std::fs::remove_dir_all(&path)?;
+        }
+    }
+    Ok(())
+}
+
+// The file behind a sqlite: URL; None for in-memory databases
+fn sqlite_path(database_url: &str) -> Option<PathBuf> {
+    let rest = database_url
+        .strip_prefix("sqlite://")
+        .or_else(|| database_url.strip_prefix("sqlite:"))?;
+    let path = rest.split('?').next().unwrap_or_default();
+    if path.is_empty() || path.contains(":memory:") {
+        return None;
+    }
+    Some(PathBuf::from(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn backup_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("rusty-ai-backup-{}", uuid::Uuid::new_v4()))
+    }
+
+    fn collection(model: &str) -> EmbeddedCollection {
+        EmbeddedCollection { name: "docs".to_string(), embedding_model: model.to_string(), dimension: 3 }
+    }
+
+    async fn ranked(store: &VectorStore, query: Vec<f32>) -> Vec<(String, f32)> {
+        store
+            .search("docs", query, 10, 0.0, None)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|p| (serde_json::Value::from(Payload::from(p.payload))["title"].to_string(), p.score))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_and_restore_of_the_memory_store_keeps_search_results() {
+        let store = VectorStore::in_memory();
+        store.ensure_collection("docs", 3).await.unwrap();
+        let points = (0..600)
+            .map(|i| VectorPoint {
+                id: uuid::Uuid::new_v4().to_string(),
+                vector: vec![1.0, i as f32 / 600.0, ((i * 37) % 600) as f32 / 600.0],
+                payload: serde_json::json!({"title": format!("doc {}", i), "tags": ["work"], "chunk_index": i})
+                    .try_into()
+                    .unwrap(),
+            })
+            .collect();
+        store.upsert("docs", points).await.unwrap();
+        let before = ranked(&store, vec![1.0, 2.0, 3.0]).await;
+
+        let dir = backup_dir();
+        let (backup, manifest) = create_backup(&dir, "sqlite::memory:", &store, &[collection("embed-a")]).await.unwrap();
+        assert_eq!(manifest.database, None);
+        assert_eq!(manifest.collections[0].point_count, 600);
+        assert_eq!(read_manifest(&backup).unwrap(), manifest);
+
+        let restored = VectorStore::in_memory();
+        let counts = restore_vectors(&backup, &manifest, &restored, &[collection("embed-a")]).await.unwrap();
+        assert_eq!(counts, vec![("docs".to_string(), 600)]);
+        assert_eq!(ranked(&restored, vec![1.0, 2.0, 3.0]).await, before);
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_restore_refuses_a_snapshot_from_another_embedding_model() {
+        let store = VectorStore::in_memory();
+        store.ensure_collection("docs", 3).await.unwrap();
+        let dir = backup_dir();
+        let (backup, manifest) = create_backup(&dir, "sqlite::memory:", &store, &[collection("embed-a")]).await.unwrap();
+
+        let target = VectorStore::in_memory();
+        let error = restore_vectors(&backup, &manifest, &target, &[collection("embed-b")]).await.unwrap_err();
+        assert!(error.to_string().contains("--database-only and re-embed"), "{}", error);
+        assert!(target.point_count("docs").await.is_err(), "nothing is re-created");
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_database_copy_is_restored_over_the_live_file() {
+        let dir = backup_dir();
+        std::fs::create_dir_all(&dir).unwrap();
+        let database_url = format!("sqlite://{}?mode=rwc", dir.join("live.db").display());
+        let pool = connect_sqlite(&database_url).await.unwrap();
+        sqlx::query("CREATE TABLE notes (text TEXT)").execute(&pool).await.unwrap();
+        sqlx::query("INSERT INTO notes VALUES ('before')").execute(&pool).await.unwrap();
+
+        let (backup, manifest) =
+            create_backup(&dir.join("backups"), &database_url, &VectorStore::in_memory(), &[]).await.unwrap();
+        sqlx::query("DELETE FROM notes").execute(&pool).await.unwrap();
+        pool.close().await;
+
+        assert!(restore_database(&backup, &manifest, &database_url).unwrap());
+        let pool = connect_sqlite(&database_url).await.unwrap();
+        let text: String = sqlx::query_scalar("SELECT text FROM notes").fetch_one(&pool).await.unwrap();
+        assert_eq!(text, "before");
+        assert!(!dir.join("live.restore-tmp").exists());
+        pool.close().await;
+        std::fs::remove_dir_all(dir).ok();
+    }
+}
@@ -0,0 +1,460 @@
+// Inbound callbacks from providers that finish work asynchronously, such as
+// speech-to-text services that transcribe a recording and post the result
+// back. Each integration signs its callbacks with a shared secret; the
+// operation waiting for a provider job registers it here, and the first
+// verified callback for that job completes it. Unknown and repeated
+// callbacks are answered `202` so providers do not keep retrying them.
+use anyhow::{bail, Context, Result};
+use axum::{
+    body::Bytes,
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use ring::hmac;
+use rusty_ai_common::ApiResponse;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path as FsPath, PathBuf};
+use std::sync::{Arc, Mutex};
+use tracing::{error, info, warn};
+
+use crate::envelope::{fail, ok, respond};
+use crate::transcription::{self, TranscriptSink, TranscriptionQueue};
+
+const DEFAULT_STATE_PATH: &str = "./data/callbacks.json";
+
+// `sha256=<hex>`, the HMAC-SHA256 of the raw body under the integration's secret
+pub const SIGNATURE_HEADER: &str = "x-callback-signature";
+
+// Provider job ids stay known this long after their callback, to recognize replays
+const PROCESSED_RETENTION_DAYS: i64 = 30;
+
+#[derive(Debug, Clone)]
+pub struct CallbackConfig {
+    // Shared secret by integration name
+    pub secrets: HashMap<String, String>,
+    pub state_path: PathBuf,
+}
+
+impl CallbackConfig {
+    // CALLBACK_SECRETS="speechco=s3cret,batch=0ther"
+    pub fn from_env() -> Result<Self> {
+        let secrets = match std::env::var("CALLBACK_SECRETS") {
+            Ok(spec) => Self::parse_secrets(&spec).map_err(|e| anyhow::anyhow!("Invalid CALLBACK_SECRETS: {}", e))?,
+            Err(_) => HashMap::new(),
+        };
+        Ok(Self {
+            secrets,
+            state_path: std::env::var("CALLBACK_STATE_PATH")
+                .map(PathBuf::from)
+                .unwrap_or_else(|_| PathBuf::from(DEFAULT_STATE_PATH)),
+        })
+    }
+
+    pub fn parse_secrets(spec: &str) -> Result<HashMap<String, String>> {
+        let mut secrets = HashMap::new();
+        for entry in spec.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let Some((integration, secret)) = entry.split_once('=') else {
+                bail!("entry '{}' is not integration=secret", entry);
+            };
+            let integration = integration.trim().to_ascii_lowercase();
+            if integration.is_empty() || secret.trim().is_empty() {
+                bail!("entry for '{}' needs both a name and a secret", integration);
+            }
+            secrets.insert(integration, secret.trim().to_string());
+        }
+        Ok(secrets)
+    }
+}
+
+// The operation a provider job completes
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum CallbackTarget {
+    Transcription { job_id: String },
+}
+
+// What providers post. Provider-specific formats are translated to this
+// shape before they reach the server.
+#[derive(Debug, Clone, Deserialize)]
+pub struct CallbackPayload {
+    pub job_id: String,
+    pub status: CallbackStatus,
+    #[serde(default)]
+    pub text: Option<String>,
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CallbackStatus {
+    Completed,
+    Failed,
+}
+
+impl CallbackPayload {
+    pub fn into_result(self) -> Result<String> {
+        match self.status {
+            CallbackStatus::Completed => Ok(self.text.unwrap_or_default()),
+            CallbackStatus::Failed => Err(anyhow::anyhow!(
+                "Provider reported failure: {}",
+                self.error.unwrap_or_else(|| "no reason given".to_string())
+            )),
+        }
+    }
+}
+
+// A verified callback for a job something was waiting on
+#[derive(Debug)]
+pub struct Delivery {
+    pub target: CallbackTarget,
+    pub payload: CallbackPayload,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Rejection {
+    UnknownIntegration,
+    BadSignature,
+    Malformed(String),
+    // No operation registered this provider job
+    Unknown { job_id: String },
+    // The provider job was already completed by an earlier callback
+    Replayed { job_id: String },
+}
+
+impl Rejection {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Rejection::UnknownIntegration => StatusCode::NOT_FOUND,
+            Rejection::BadSignature => StatusCode::UNAUTHORIZED,
+            Rejection::Malformed(_) => StatusCode::BAD_REQUEST,
+            Rejection::Unknown { .. } | Rejection::Replayed { .. } => StatusCode::ACCEPTED,
+        }
+    }
+}
+
+impl std::fmt::Display for Rejection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Rejection::UnknownIntegration => write!(f, "unknown integration"),
+            Rejection::BadSignature => write!(f, "signature does not match"),
+            Rejection::Malformed(reason) => write!(f, "malformed payload: {}", reason),
+            Rejection::Unknown { job_id } => write!(f, "no operation is waiting for job {}", job_id),
+            Rejection::Replayed { job_id } => write!(f, "job {} was already completed", job_id),
+        }
+    }
+}
+
+impl IntoResponse for Rejection {
+    fn into_response(self) -> Response {
+        let reason = match &self {
+            Rejection::Unknown { .. } => "unknown",
+            Rejection::Replayed { .. } => "replayed",
+            _ => return fail(self.status(), self.to_string()),
+        };
+        respond(
+            self.status(),
+            ApiResponse::success(serde_json::json!({ "accepted": false, "reason": reason })),
+        )
+    }
+}
+
+// Registered and completed provider jobs by `<integration>:<provider job id>`
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CallbackState {
+    waiting: HashMap<String, CallbackTarget>,
+    processed: HashMap<String, DateTime<Utc>>,
+}
+
+pub struct CallbackService {
+    config: CallbackConfig,
+    state: Mutex<CallbackState>,
+}
+
+impl CallbackService {
+    pub fn open(config: CallbackConfig) -> Result<Self> {
+        let state = load_state(&config.state_path)?;
+        if !state.waiting.is_empty() {
+            info!("{} operations are waiting for provider callbacks", state.waiting.len());
+        }
+        Ok(Self {
+            config,
+            state: Mutex::new(state),
+        })
+    }
+
+    pub fn has_integration(&self, integration: &str) -> bool {
+        self.config.secrets.contains_key(integration)
+    }
+
+    // Wait for the callback of `provider_job_id`
+    pub fn register(&self, integration: &str, provider_job_id: &str, target: CallbackTarget) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        state.waiting.insert(key(integration, provider_job_id), target);
+        self.persist(&state)
+    }
+
+    // Verify a callback and claim the operation waiting for its job. A job
+    // is claimed once; later callbacks for it are replays.
+    pub fn accept(&self, integration: &str, signature: Option<&str>, body: &[u8]) -> Result<Delivery, Rejection> {
+        let secret = self.config.secrets.get(integration).ok_or(Rejection::UnknownIntegration)?;
+        if !signature.is_some_and(|signature| verify_signature(secret, body, signature)) {
+            return Err(Rejection::BadSignature);
+        }
+        let payload: CallbackPayload =
+            serde_json::from_slice(body).map_err(|e| Rejection::Malformed(e.to_string()))?;
+
+        let key = key(integration, &payload.job_id);
+        let mut state = self.state.lock().unwrap();
+        if state.processed.contains_key(&key) {
+            return Err(Rejection::Replayed { job_id: payload.job_id });
+        }
+        let Some(target) = state.waiting.remove(&key) else {
+            return Err(Rejection::Unknown { job_id: payload.job_id });
+        };
+
+        let now = Utc::now();
+        state.processed.insert(key, now);
+        state
+            .processed
+            .retain(|_, at| *at > now - chrono::Duration::days(PROCESSED_RETENTION_DAYS));
+        // The callback is still delivered; a restart before the next write
+        // would let a replay of it through, which the operation ignores
+        if let Err(e) = self.persist(&state) {
+            warn!("Failed to persist callback state: {:#}", e);
+        }
+        Ok(Delivery { target, payload })
+    }
+
+    fn persist(&self, state: &CallbackState) -> Result<()> {
+        let path = &self.config.state_path;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).context("Failed to create callback state directory")?;
+        }
+        std::fs::write(path, serde_json::to_vec(state)?)
+            .with_context(|| format!("Failed to write callback state {}", path.display()))
+    }
+}
+
+fn key(integration: &str, provider_job_id: &str) -> String {
+    format!("{}:{}", integration, provider_job_id)
+}
+
+fn load_state(path: &FsPath) -> Result<CallbackState> {
+    match std::fs::read(path) {
+        Ok(bytes) => serde_json::from_slice(&bytes).with_context(|| format!("Unreadable callback state {}", path.display())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(CallbackState::default()),
+        Err(e) => Err(e).with_context(|| format!("Failed to read callback state {}", path.display())),
+    }
+}
+
+// Compared in constant time
+fn verify_signature(secret: &str, body: &[u8], signature: &str) -> bool {
+    let Some(tag) = signature.trim().strip_prefix("sha256=").and_then(decode_hex) else {
+        return false;
+    };
+    hmac::verify(&hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes()), body, &tag).is_ok()
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+// Verify a callback and complete the operation it is for
+pub async fn deliver<S: TranscriptSink>(
+    callbacks: &CallbackService,
+    transcriptions: &TranscriptionQueue,
+    sink: Option<&S>,
+    integration: &str,
+    headers: &HeaderMap,
+    body: &[u8],
+) -> Response {
+    let signature = headers.get(SIGNATURE_HEADER).and_then(|v| v.to_str().ok());
+    let delivery = match callbacks.accept(integration, signature, body) {
+        Ok(delivery) => delivery,
+        Err(rejection) => {
+            warn!("Rejected {} callback: {}", integration, rejection);
+            return rejection.into_response();
+        }
+    };
+
+    match delivery.target {
+        CallbackTarget::Transcription { job_id } => {
+            match transcription::complete_job(transcriptions, sink, &job_id, delivery.payload.into_result()).await {
+                Ok(job) => ok(serde_json::json!({
+                    "accepted": true,
+                    "transcription_id": job.id,
+                    "status": job.status,
+                })),
+                // Retrying would be a replay, so the provider is not asked to
+                Err(e) => {
+                    error!("Callback for transcription {} has nothing to complete: {:#}", job_id, e);
+                    respond(
+                        StatusCode::ACCEPTED,
+                        ApiResponse::success(serde_json::json!({ "accepted": false, "reason": "gone" })),
+                    )
+                }
+            }
+        }
+    }
+}
+
+// HTTP Handlers
+pub async fn callback_handler(
+    State(state): State<Arc<crate::AppState>>,
+    Path(integration): Path<String>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let manager = &state.transcription_manager;
+    deliver(
+        &state.callbacks,
+        manager.queue(),
+        manager.sink(),
+        &integration.to_ascii_lowercase(),
+        &headers,
+        &body,
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::knowledge_service_simple::KnowledgeService;
+    use crate::transcription::{submit_job, CallbackTranscriber, JobStatus, TranscriptionJob};
+    use axum::routing::post;
+    use axum::Router;
+    use uuid::Uuid;
+
+    const SECRET: &str = "s3cret";
+
+    fn sign(secret: &str, body: &[u8]) -> String {
+        let tag = hmac::sign(&hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes()), body);
+        let hex: String = tag.as_ref().iter().map(|b| format!("{:02x}", b)).collect();
+        format!("sha256={}", hex)
+    }
+
+    // Accepts every recording as provider job `prov-1`
+    struct FakeProvider;
+
+    impl CallbackTranscriber for FakeProvider {
+        fn integration(&self) -> &str {
+            "speechco"
+        }
+
+        async fn submit(&self, audio: Vec<u8>, filename: &str) -> Result<String> {
+            assert_eq!((audio.as_slice(), filename), (&b"RIFF memo"[..], "memo.wav"));
+            Ok("prov-1".to_string())
+        }
+    }
+
+    struct Fixture {
+        callbacks: CallbackService,
+        queue: TranscriptionQueue,
+    }
+
+    async fn handle(
+        State(fixture): State<Arc<Fixture>>,
+        Path(integration): Path<String>,
+        headers: HeaderMap,
+        body: Bytes,
+    ) -> Response {
+        deliver(&fixture.callbacks, &fixture.queue, None::<&KnowledgeService>, &integration, &headers, &body).await
+    }
+
+    fn service(dir: &FsPath) -> CallbackService {
+        CallbackService::open(CallbackConfig {
+            secrets: CallbackConfig::parse_secrets(&format!("SpeechCo={}", SECRET)).unwrap(),
+            state_path: dir.join("callbacks.json"),
+        })
+        .unwrap()
+    }
+
+    async fn post_callback(base: &str, body: &str, signature: &str) -> (u16, serde_json::Value) {
+        let response = reqwest::Client::new()
+            .post(format!("{}/api/v1/callbacks/speechco", base))
+            .header(SIGNATURE_HEADER, signature)
+            .body(body.to_string())
+            .send()
+            .await
+            .unwrap();
+        (response.status().as_u16(), response.json().await.unwrap())
+    }
+
+    #[test]
+    fn test_signatures_are_checked_against_the_body() {
+        let signature = sign(SECRET, b"{}");
+        assert!(verify_signature(SECRET, b"{}", &signature));
+        assert!(!verify_signature(SECRET, b"{ }", &signature));
+        assert!(!verify_signature("other", b"{}", &signature));
+        assert!(!verify_signature(SECRET, b"{}", signature.trim_start_matches("sha256=")));
+        assert!(CallbackConfig::parse_secrets("speechco").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_signed_callbacks_complete_transcriptions_once() {
+        let dir = std::env::temp_dir().join(format!("rusty-ai-callbacks-{}", Uuid::new_v4()));
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        let media_path = dir.join("memo.wav");
+        tokio::fs::write(&media_path, b"RIFF memo").await.unwrap();
+
+        let queue = TranscriptionQueue::open(dir.join("jobs")).await.unwrap();
+        queue
+            .insert(TranscriptionJob::new("job-1".to_string(), "memo.wav".to_string(), media_path, None, false))
+            .await;
+        let callbacks = service(&dir);
+        submit_job(&queue, &FakeProvider, &callbacks, "job-1").await.unwrap();
+        let job = queue.get("job-1").await.unwrap();
+        assert_eq!((job.status, job.provider_job_id.as_deref()), (JobStatus::Transcribing, Some("prov-1")));
+
+        let mut watcher = queue.subscribe();
+        let fixture = Arc::new(Fixture { callbacks, queue });
+        let app = Router::new()
+            .route("/api/v1/callbacks/:integration", post(handle))
+            .with_state(fixture.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let body = r#"{"job_id":"prov-1","status":"completed","text":"Call the plumber."}"#;
+        let tampered = body.replace("plumber", "landlord");
+        let (status, _) = post_callback(&base, &tampered, &sign(SECRET, body.as_bytes())).await;
+        assert_eq!(status, 401);
+        assert_eq!(fixture.queue.get("job-1").await.unwrap().status, JobStatus::Transcribing);
+
+        let (status, response) = post_callback(&base, body, &sign(SECRET, body.as_bytes())).await;
+        assert_eq!(status, 200);
+        assert_eq!(response["data"]["accepted"], true);
+        assert_eq!(response["data"]["status"]["state"], "completed");
+        let job = fixture.queue.get("job-1").await.unwrap();
+        assert_eq!(job.transcript.as_deref(), Some("Call the plumber."));
+        let event = watcher.recv().await.unwrap();
+        assert_eq!((event.id.as_str(), event.status), ("job-1", JobStatus::Completed));
+
+        // The same callback again, and one for a job nobody submitted
+        let (status, response) = post_callback(&base, body, &sign(SECRET, body.as_bytes())).await;
+        assert_eq!((status, response["data"]["reason"].as_str()), (202, Some("replayed")));
+        let unknown = r#"{"job_id":"prov-9","status":"failed"}"#;
+        let (status, response) = post_callback(&base, unknown, &sign(SECRET, unknown.as_bytes())).await;
+        assert_eq!((status, response["data"]["reason"].as_str()), (202, Some("unknown")));
+        assert!(watcher.try_recv().is_err());
+
+        // Replays are still recognized after a restart
+        let reopened = service(&dir);
+        assert_eq!(
+            reopened.accept("speechco", Some(&sign(SECRET, body.as_bytes())), body.as_bytes()).unwrap_err(),
+            Rejection::Replayed { job_id: "prov-1".to_string() }
+        );
+        let _ = tokio::fs::remove_dir_all(dir).await;
+    }
+}
@@ -0,0 +1,340 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::{debug, warn};
+
+use crate::knowledge_service_simple::DocumentMatch;
+
+const DEFAULT_LATENCY_BUDGET_MS: u64 = 6000;
+const DEFAULT_RETRIEVAL_TIMEOUT_MS: u64 = 2500;
+const DEFAULT_OPTIONAL_STAGE_RESERVE_MS: u64 = 3500;
+
+// Upper bounds (ms) of the histogram buckets recorded per stage
+const HISTOGRAM_BUCKETS_MS: [u64; 10] = [10, 25, 50, 100, 250, 500, 1000, 2500, 5000, 10000];
+
+#[derive(Debug, Clone)]
+pub struct PipelineConfig {
+    pub latency_budget: Duration,
+    pub retrieval_timeout: Duration,
+    // Optional stages only run while at least this much budget is left,
+    // so the LLM call always gets the bulk of the request time.
+    pub optional_stage_reserve: Duration,
+}
+
+impl Default for PipelineConfig {
+    fn default() -> Self {
+        Self {
+            latency_budget: Duration::from_millis(DEFAULT_LATENCY_BUDGET_MS),
+            retrieval_timeout: Duration::from_millis(DEFAULT_RETRIEVAL_TIMEOUT_MS),
+            optional_stage_reserve: Duration::from_millis(DEFAULT_OPTIONAL_STAGE_RESERVE_MS),
+        }
+    }
+}
+
+impl PipelineConfig {
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        let read_ms = |key: &str, default: Duration| {
+            std::env::var(key)
+                .ok()
+                .and_then(|v| v.parse::<u64>().ok())
+                .map(Duration::from_millis)
+                .unwrap_or(default)
+        };
+
+        Self {
+            latency_budget: read_ms("CHAT_LATENCY_BUDGET_MS", defaults.latency_budget),
+            retrieval_timeout: read_ms("CHAT_RETRIEVAL_TIMEOUT_MS", defaults.retrieval_timeout),
+            optional_stage_reserve: read_ms("CHAT_OPTIONAL_STAGE_RESERVE_MS", defaults.optional_stage_reserve),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StageStatus {
+    Completed,
+    Skipped,
+    TimedOut,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StageTiming {
+    pub stage: String,
+    pub status: StageStatus,
+    pub duration_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PipelineTimings {
+    pub budget_ms: u64,
+    pub total_ms: u64,
+    pub stages: Vec<StageTiming>,
+}
+
+// Tracks the time spent on one chat request against its latency budget
+pub struct LatencyBudget {
+    config: PipelineConfig,
+    started_at: Instant,
+    stages: Mutex<Vec<StageTiming>>,
+}
+
+impl LatencyBudget {
+    pub fn start(config: PipelineConfig) -> Self {
+        Self {
+            config,
+            started_at: Instant::now(),
+            stages: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.started_at.elapsed()
+    }
+
+    pub fn remaining(&self) -> Duration {
+        self.config.latency_budget.saturating_sub(self.elapsed())
+    }
+
+    // Run a stage whose result the request can live without. The stage is
+    // skipped outright when the remaining budget is below the reserve, and is
+    // cut off once its share of the budget is used up.
+    pub async fn run_optional<T, E, F>(&self, stage: &str, fut: F) -> Option<T>
+    where
+        F: Future<Output = Result<T, E>>,
+        E: std::fmt::Display,
+    {
+        if self.remaining() < self.config.optional_stage_reserve {
+            debug!("Skipping optional stage '{}': {:?} of budget left", stage, self.remaining());
+            self.record(stage, StageStatus::Skipped, Duration::ZERO);
+            return None;
+        }
+
+        let deadline = self.remaining().saturating_sub(self.config.optional_stage_reserve);
+        self.run_with_deadline(stage, deadline, fut).await
+    }
+
+    // Run a retrieval stage bounded by the retrieval timeout and the remaining budget
+    pub async fn run_retrieval<T, E, F>(&self, stage: &str, fut: F) -> Option<T>
+    where
+        F: Future<Output = Result<T, E>>,
+        E: std::fmt::Display,
+    {
+        let deadline = self.config.retrieval_timeout.min(self.remaining());
+        self.run_with_deadline(stage, deadline, fut).await
+    }
+
+    // Run a stage the request cannot skip; only timed, never cut off
+    pub async fn run_required<T, F>(&self, stage: &str, fut: F) -> T
+    where
+        F: Future<Output = T>,
+    {
+        let start = Instant::now();
+        let result = fut.await;
+        self.record(stage, StageStatus::Completed, start.elapsed());
+        result
+    }
+
+    async fn run_with_deadline<T, E, F>(&self, stage: &str, deadline: Duration, fut: F) -> Option<T>
+    where
+        F: Future<Output = Result<T, E>>,
+        E: std::fmt::Display,
+    {
+        let start = Instant::now();
+        match tokio::time::timeout(deadline, fut).await {
+            Ok(Ok(value)) => {
+                self.record(stage, StageStatus::Completed, start.elapsed());
+                Some(value)
+            }
+            Ok(Err(e)) => {
+                warn!("Chat pipeline stage '{}' failed: {}", stage, e);
+                self.record(stage, StageStatus::Failed, start.elapsed());
+                None
+            }
+            Err(_) => {
+                warn!("Chat pipeline stage '{}' exceeded its {:?} deadline", stage, deadline);
+                self.record(stage, StageStatus::TimedOut, start.elapsed());
+                None
+            }
+        }
+    }
+
+    fn record(&self, stage: &str, status: StageStatus, duration: Duration) {
+        self.stages.lock().unwrap().push(StageTiming {
+            stage: stage.to_string(),
+            status,
+            duration_ms: duration.as_millis() as u64,
+        });
+    }
+
+    pub fn timings(&self) -> PipelineTimings {
+        PipelineTimings {
+            budget_ms: self.config.latency_budget.as_millis() as u64,
+            total_ms: self.elapsed().as_millis() as u64,
+            stages: self.stages.lock().unwrap().clone(),
+        }
+    }
+}
+
+// Per-stage latency histograms, aggregated across requests
+#[derive(Default)]
+pub struct PipelineMetrics {
+    histograms: Mutex<HashMap<String, StageHistogram>>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct StageHistogram {
+    pub buckets: Vec<(u64, u64)>,
+    pub count: u64,
+    pub sum_ms: u64,
+    pub skipped: u64,
+    pub timed_out: u64,
+}
+
+impl PipelineMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn observe(&self, timings: &PipelineTimings) {
+        let mut histograms = self.histograms.lock().unwrap();
+
+        for timing in timings.stages.iter().chain(std::iter::once(&StageTiming {
+            stage: "total".to_string(),
+            status: StageStatus::Completed,
+            duration_ms: timings.total_ms,
+        })) {
+            let histogram = histograms.entry(timing.stage.clone()).or_insert_with(|| StageHistogram {
+                buckets: HISTOGRAM_BUCKETS_MS.iter().map(|b| (*b, 0)).collect(),
+                ..Default::default()
+            });
+
+            match timing.status {
+                StageStatus::Skipped => {
+                    histogram.skipped += 1;
+                    continue;
+                }
+                StageStatus::TimedOut => histogram.timed_out += 1,
+                _ => {}
+            }
+
+            histogram.count += 1;
+            histogram.sum_ms += timing.duration_ms;
+            for (upper, count) in histogram.buckets.iter_mut() {
+                if timing.duration_ms <= *upper {
+                    *count += 1;
+                }
+            }
+        }
+    }
+
+    pub fn snapshot(&self) -> HashMap<String, StageHistogram> {
+        self.histograms.lock().unwrap().clone()
+    }
+}
+
+// Cheap lexical rerank: blend the vector score with query term overlap
+pub fn rerank_by_overlap(query: &str, mut documents: Vec<DocumentMatch>) -> Vec<DocumentMatch> {
+    let terms: Vec<String> = query
+        .split_whitespace()
+        .map(|t| t.trim_matches(|c: char| !c.is_alphanumeric()).to_lowercase())
+        .filter(|t| t.len() > 2)
+        .collect();
+
+    if terms.is_empty() {
+        return documents;
+    }
+
+    let overlap = |doc: &DocumentMatch| {
+        let text = format!("{} {}", doc.title, doc.content).to_lowercase();
+        terms.iter().filter(|t| text.contains(t.as_str())).count() as f32 / terms.len() as f32
+    };
+
+    documents.sort_by(|a, b| {
+        let score_a = a.score * 0.7 + overlap(a) * 0.3;
+        let score_b = b.score * 0.7 + overlap(b) * 0.3;
+        score_b.partial_cmp(&score_a).unwrap_or(std::cmp::Ordering::Equal)
+    });
+    documents
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_config(budget_ms: u64, retrieval_ms: u64, reserve_ms: u64) -> PipelineConfig {
+        PipelineConfig {
+            latency_budget: Duration::from_millis(budget_ms),
+            retrieval_timeout: Duration::from_millis(retrieval_ms),
+            optional_stage_reserve: Duration::from_millis(reserve_ms),
+        }
+    }
+
+    async fn slow_stage(delay_ms: u64) -> Result<&'static str, String> {
+        tokio::time::sleep(Duration::from_millis(delay_ms)).await;
+        Ok("done")
+    }
+
+    #[tokio::test]
+    async fn test_slow_retrieval_is_cut_off() {
+        let budget = LatencyBudget::start(test_config(1000, 50, 200));
+
+        let result = budget.run_retrieval("knowledge_search", slow_stage(500)).await;
+
+        assert!(result.is_none());
+        assert!(budget.elapsed() < Duration::from_millis(400));
+        assert_eq!(budget.timings().stages[0].status, StageStatus::TimedOut);
+    }
+
+    #[tokio::test]
+    async fn test_optional_stage_skipped_when_budget_low() {
+        let budget = LatencyBudget::start(test_config(300, 250, 200));
+
+        // Consume most of the budget with parallel retrieval stages
+        let (docs, memories) = tokio::join!(
+            budget.run_retrieval("knowledge_search", slow_stage(150)),
+            budget.run_retrieval("memory_retrieval", slow_stage(150)),
+        );
+        assert!(docs.is_some());
+        assert!(memories.is_some());
+
+        let reranked = budget.run_optional("rerank", slow_stage(10)).await;
+        assert!(reranked.is_none());
+
+        let timings = budget.timings();
+        let rerank = timings.stages.iter().find(|s| s.stage == "rerank").unwrap();
+        assert_eq!(rerank.status, StageStatus::Skipped);
+        assert!(timings.total_ms < 300);
+    }
+
+    #[tokio::test]
+    async fn test_optional_stage_runs_with_budget_left() {
+        let budget = LatencyBudget::start(test_config(2000, 500, 200));
+
+        let result = budget.run_optional("rerank", slow_stage(10)).await;
+
+        assert_eq!(result, Some("done"));
+    }
+
+    #[test]
+    fn test_metrics_histograms() {
+        let metrics = PipelineMetrics::new();
+        metrics.observe(&PipelineTimings {
+            budget_ms: 6000,
+            total_ms: 120,
+            stages: vec![
+                StageTiming { stage: "knowledge_search".to_string(), status: StageStatus::Completed, duration_ms: 40 },
+                StageTiming { stage: "rerank".to_string(), status: StageStatus::Skipped, duration_ms: 0 },
+            ],
+        });
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot["knowledge_search"].count, 1);
+        assert_eq!(snapshot["rerank"].skipped, 1);
+        assert_eq!(snapshot["rerank"].count, 0);
+        assert_eq!(snapshot["total"].sum_ms, 120);
+    }
+}
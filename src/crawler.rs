@@ -0,0 +1,1114 @@
+use anyhow::{Context, Result};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use reqwest::Url;
+use rusty_ai_common::net::is_public_ip;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::future::Future;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+use tracing::{debug, error, info, warn};
+use uuid::Uuid;
+
+use crate::http_client::{ClientKind, HttpClientFactory};
+use crate::knowledge_service_simple::KnowledgeService;
+use crate::trust::TrustLevel;
+
+const DEFAULT_MAX_PAGES: usize = 200;
+const DEFAULT_POLITENESS_DELAY_MS: u64 = 500;
+const DEFAULT_STATE_DIR: &str = "./data/crawls";
+const USER_AGENT: &str = "rusty-ai-crawler/0.1";
+// The product token of USER_AGENT, which robots.txt groups name
+const ROBOTS_AGENT: &str = "rusty-ai-crawler";
+// Redirects are followed by hand, so every hop's host can be checked
+const MAX_REDIRECTS: usize = 5;
+
+// CRAWL_ALLOWED_HOSTS: comma-separated hosts that may be crawled although
+// they resolve to private addresses, e.g. an intranet wiki
+pub fn allowed_hosts_from_env() -> Vec<String> {
+    std::env::var("CRAWL_ALLOWED_HOSTS")
+        .map(|hosts| hosts.split(',').map(str::trim).filter(|h| !h.is_empty()).map(str::to_string).collect())
+        .unwrap_or_default()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrawlConfig {
+    pub seed_url: String,
+    #[serde(default = "default_max_pages")]
+    pub max_pages: usize,
+    #[serde(default = "default_politeness_delay_ms")]
+    pub politeness_delay_ms: u64,
+    #[serde(default = "default_true")]
+    pub respect_robots: bool,
+    // Trust level of the stored pages; crawled content is external unless
+    // the user vouches for the site
+    #[serde(default = "default_trust_level")]
+    pub trust_level: TrustLevel,
+}
+
+fn default_max_pages() -> usize {
+    DEFAULT_MAX_PAGES
+}
+
+fn default_politeness_delay_ms() -> u64 {
+    DEFAULT_POLITENESS_DELAY_MS
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_trust_level() -> TrustLevel {
+    TrustLevel::External
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CrawlStatus {
+    Running,
+    Completed,
+    Failed(String),
+}
+
+// What we know about a page from the previous crawl
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PageRecord {
+    pub content_hash: u64,
+    pub document_id: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CrawlProgress {
+    pub pages_fetched: usize,
+    pub pages_stored: usize,
+    pub pages_unchanged: usize,
+    pub pages_removed: usize,
+    pub pages_skipped: usize,
+}
+
+// A crawl and its frontier, persisted after every page so it can resume
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrawlJob {
+    pub id: String,
+    pub config: CrawlConfig,
+    pub status: CrawlStatus,
+    pub frontier: VecDeque<String>,
+    pub visited: HashSet<String>,
+    // Pages that returned content during the current run
+    #[serde(default)]
+    pub fetched: HashSet<String>,
+    pub pages: HashMap<String, PageRecord>,
+    // Content hashes seen during the current run, so a resumed run still
+    // skips aliases of pages it stored before the restart
+    #[serde(default)]
+    pub seen_hashes: HashSet<u64>,
+    pub progress: CrawlProgress,
+    pub started_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl CrawlJob {
+    pub fn new(config: CrawlConfig) -> Self {
+        let now = chrono::Utc::now();
+        Self {
+            id: Uuid::new_v4().to_string(),
+            frontier: VecDeque::from([config.seed_url.clone()]),
+            config,
+            status: CrawlStatus::Running,
+            visited: HashSet::new(),
+            fetched: HashSet::new(),
+            pages: HashMap::new(),
+            seen_hashes: HashSet::new(),
+            progress: CrawlProgress::default(),
+            started_at: now,
+            updated_at: now,
+        }
+    }
+
+    // Reset the frontier for a re-crawl, keeping the page records so
+    // unchanged pages are skipped and vanished ones can be removed
+    pub fn restart(&mut self) {
+        self.frontier = VecDeque::from([self.config.seed_url.clone()]);
+        self.visited.clear();
+        self.fetched.clear();
+        self.seen_hashes.clear();
+        self.progress = CrawlProgress::default();
+        self.status = CrawlStatus::Running;
+        self.started_at = chrono::Utc::now();
+    }
+}
+
+// A page ready to be stored in the knowledge base
+#[derive(Debug, Clone)]
+pub struct CrawledPage {
+    pub url: String,
+    pub title: String,
+    pub markdown: String,
+    pub source: String,
+    pub tags: Vec<String>,
+    pub trust_level: TrustLevel,
+}
+
+// Where crawled pages end up; the knowledge service in production
+pub trait PageStore {
+    fn store_page(&self, page: &CrawledPage) -> impl Future<Output = Result<String>> + Send;
+    // Replace a stored page with a new version, returning the id it is now
+    // stored under
+    fn update_page(&self, document_id: &str, page: &CrawledPage) -> impl Future<Output = Result<String>> + Send;
+    fn remove_page(&self, document_id: &str) -> impl Future<Output = Result<()>> + Send;
+}
+
+impl PageStore for KnowledgeService {
+    async fn store_page(&self, page: &CrawledPage) -> Result<String> {
+        let response = self
+            .store_document(
+                page.title.clone(),
+                page.markdown.clone(),
+                page.source.clone(),
+                page.tags.clone(),
+                page.trust_level,
+            )
+            .await?;
+        Ok(response.document_id)
+    }
+
+    // Only the changed chunks are embedded again, and the document keeps its
+    // id (and with it its annotations and revision history)
+    async fn update_page(&self, document_id: &str, page: &CrawledPage) -> Result<String> {
+        let reindexed = self
+            .reindex_document(document_id, &page.title, &page.markdown, &page.source, &page.tags, page.trust_level)
+            .await?;
+        match reindexed {
+            Some(_) => Ok(document_id.to_string()),
+            None => self.store_page(page).await,
+        }
+    }
+
+    async fn remove_page(&self, document_id: &str) -> Result<()> {
+        self.delete_document(document_id).await?;
+        Ok(())
+    }
+}
+
+pub struct Crawler {
+    client: reqwest::Client,
+    state_dir: PathBuf,
+    // Lowercase hosts exempt from the public-address check
+    allowed_hosts: HashSet<String>,
+}
+
+impl Crawler {
+    pub fn new(state_dir: impl Into<PathBuf>, http: &HttpClientFactory) -> Result<Self> {
+        let client = http
+            .builder(ClientKind::Crawler)?
+            .user_agent(USER_AGENT)
+            .redirect(reqwest::redirect::Policy::none())
+            .build()?;
+
+        Ok(Self {
+            client,
+            state_dir: state_dir.into(),
+            allowed_hosts: HashSet::new(),
+        })
+    }
+
+    // Let crawls reach these hosts even where they resolve to private
+    // addresses
+    pub fn with_allowed_hosts(mut self, hosts: impl IntoIterator<Item = String>) -> Self {
+        self.allowed_hosts = hosts.into_iter().map(|host| host.to_lowercase()).collect();
+        self
+    }
+
+    // Crawls reach the public web, never the server's own network, unless
+    // the host is allowed explicitly. Checked for the seed and for every
+    // request, redirects included
+    async fn check_host(&self, url: &Url) -> Result<()> {
+        let host = url.host_str().context("URL has no host")?;
+        // IP literals resolve to themselves, without the brackets of IPv6
+        let name = host.trim_start_matches('[').trim_end_matches(']');
+        if self.allowed_hosts.contains(&name.to_lowercase()) {
+            return Ok(());
+        }
+        let port = url.port_or_known_default().unwrap_or(80);
+        let addresses: Vec<IpAddr> = tokio::net::lookup_host((name, port))
+            .await
+            .with_context(|| format!("Could not resolve host {}", host))?
+            .map(|address| address.ip())
+            .collect();
+        if let Some(address) = addresses.iter().find(|address| !is_public_ip(**address)) {
+            anyhow::bail!("Host {} is not public ({}); list it in CRAWL_ALLOWED_HOSTS to crawl it", host, address);
+        }
+        Ok(())
+    }
+
+    // GET `url`, following redirects to hosts that pass the check
+    async fn get(&self, url: &Url) -> Result<reqwest::Response> {
+        let mut url = url.clone();
+        for _ in 0..=MAX_REDIRECTS {
+            self.check_host(&url).await?;
+            let response = self.client.get(url.clone()).send().await?;
+            if !response.status().is_redirection() {
+                return Ok(response);
+            }
+            let Some(location) = response.headers().get(reqwest::header::LOCATION) else {
+                return Ok(response);
+            };
+            let next = url.join(location.to_str().context("Redirect location is not text")?)?;
+            debug!("{} redirects to {}", url, next);
+            url = next;
+        }
+        anyhow::bail!("More than {} redirects", MAX_REDIRECTS)
+    }
+
+    pub async fn run<S: PageStore>(&self, job: &Arc<RwLock<CrawlJob>>, store: &S) -> Result<()> {
+        let (seed, config) = {
+            let job = job.read().await;
+            (Url::parse(&job.config.seed_url).context("Invalid seed URL")?, job.config.clone())
+        };
+
+        let robots = if config.respect_robots {
+            self.fetch_robots(&seed).await
+        } else {
+            RobotsRules::default()
+        };
+
+        loop {
+            let next = {
+                let mut job = job.write().await;
+                if job.progress.pages_fetched >= config.max_pages {
+                    None
+                } else {
+                    let mut next = None;
+                    while let Some(candidate) = job.frontier.pop_front() {
+                        if job.visited.insert(candidate.clone()) {
+                            next = Some(candidate);
+                            break;
+                        }
+                    }
+                    next
+                }
+            };
+
+            let Some(url) = next else { break };
+            let parsed = match Url::parse(&url) {
+                Ok(parsed) => parsed,
+                Err(_) => continue,
+            };
+
+            if !robots.allows(parsed.path()) {
+                debug!("robots.txt disallows {}", url);
+                job.write().await.progress.pages_skipped += 1;
+                continue;
+            }
+
+            match self.fetch_page(&parsed).await {
+                Ok(Some(html)) => {
+                    let links = extract_links(&html, &parsed, &seed);
+                    let page = build_page(&parsed, &html, config.trust_level);
+                    let hash = content_hash(&page.markdown);
+
+                    let previous = job.read().await.pages.get(&url).cloned();
+                    let duplicate = {
+                        let mut job = job.write().await;
+                        job.progress.pages_fetched += 1;
+                        job.fetched.insert(url.clone());
+                        for link in links {
+                            if !job.visited.contains(&link) && !job.frontier.contains(&link) {
+                                job.frontier.push_back(link);
+                            }
+                        }
+                        !job.seen_hashes.insert(hash)
+                    };
+
+                    // Identical content under another URL (e.g. index.html aliases)
+                    if duplicate && previous.is_none() {
+                        job.write().await.progress.pages_skipped += 1;
+                    } else if previous.as_ref().map(|p| p.content_hash) == Some(hash) {
+                        job.write().await.progress.pages_unchanged += 1;
+                    } else {
+                        let document_id = match &previous {
+                            Some(previous) => store.update_page(&previous.document_id, &page).await?,
+                            None => store.store_page(&page).await?,
+                        };
+                        let mut job = job.write().await;
+                        job.pages.insert(url.clone(), PageRecord { content_hash: hash, document_id });
+                        job.progress.pages_stored += 1;
+                    }
+                }
+                Ok(None) => {
+                    job.write().await.progress.pages_skipped += 1;
+                }
+                Err(e) => {
+                    warn!("Failed to fetch {}: {}", url, e);
+                    job.write().await.progress.pages_skipped += 1;
+                }
+            }
+
+            self.persist(job).await;
+            tokio::time::sleep(Duration::from_millis(config.politeness_delay_ms)).await;
+        }
+
+        // Pages from the previous crawl that no longer exist are removed, but
+        // only after a complete crawl: a capped crawl proves nothing about them
+        let vanished: Vec<(String, PageRecord)> = {
+            let job = job.read().await;
+            if job.frontier.is_empty() {
+                job.pages
+                    .iter()
+                    .filter(|(url, _)| !job.fetched.contains(*url))
+                    .map(|(url, record)| (url.clone(), record.clone()))
+                    .collect()
+            } else {
+                Vec::new()
+            }
+        };
+        for (url, record) in vanished {
+            store.remove_page(&record.document_id).await?;
+            let mut job = job.write().await;
+            job.pages.remove(&url);
+            job.progress.pages_removed += 1;
+        }
+
+        {
+            let mut job = job.write().await;
+            job.status = CrawlStatus::Completed;
+            job.updated_at = chrono::Utc::now();
+            info!("Crawl {} finished: {:?}", job.id, job.progress);
+        }
+        self.persist(job).await;
+        Ok(())
+    }
+
+    async fn fetch_page(&self, url: &Url) -> Result<Option<String>> {
+        let response = self.get(url).await?;
+        if !response.status().is_success() {
+            return Ok(None);
+        }
+
+        let is_html = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.contains("text/html"))
+            .unwrap_or(false);
+        if !is_html {
+            return Ok(None);
+        }
+
+        Ok(Some(response.text().await?))
+    }
+
+    async fn fetch_robots(&self, seed: &Url) -> RobotsRules {
+        let robots_url = match seed.join("/robots.txt") {
+            Ok(url) => url,
+            Err(_) => return RobotsRules::default(),
+        };
+
+        match self.get(&robots_url).await {
+            Ok(response) if response.status().is_success() => {
+                RobotsRules::parse(&response.text().await.unwrap_or_default())
+            }
+            _ => RobotsRules::default(),
+        }
+    }
+
+    async fn persist(&self, job: &Arc<RwLock<CrawlJob>>) {
+        let snapshot = {
+            let mut job = job.write().await;
+            job.updated_at = chrono::Utc::now();
+            job.clone()
+        };
+
+        let path = self.state_dir.join(format!("{}.json", snapshot.id));
+        let result = async {
+            tokio::fs::create_dir_all(&self.state_dir).await?;
+            tokio::fs::write(&path, serde_json::to_vec(&snapshot)?).await?;
+            Ok::<_, anyhow::Error>(())
+        }
+        .await;
+
+        if let Err(e) = result {
+            warn!("Failed to persist crawl state {:?}: {}", path, e);
+        }
+    }
+
+    pub async fn load_jobs(&self) -> Vec<CrawlJob> {
+        let mut jobs = Vec::new();
+        let mut entries = match tokio::fs::read_dir(&self.state_dir).await {
+            Ok(entries) => entries,
+            Err(_) => return jobs,
+        };
+
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            if let Ok(bytes) = tokio::fs::read(entry.path()).await {
+                match serde_json::from_slice::<CrawlJob>(&bytes) {
+                    Ok(job) => jobs.push(job),
+                    Err(e) => warn!("Ignoring unreadable crawl state {:?}: {}", entry.path(), e),
+                }
+            }
+        }
+
+        jobs
+    }
+}
+
+// Disallow rules that apply to every user agent
+#[derive(Debug, Default)]
+struct RobotsRules {
+    disallowed: Vec<String>,
+}
+
+impl RobotsRules {
+    fn parse(robots: &str) -> Self {
+        let mut disallowed = Vec::new();
+        let mut applies = false;
+
+        for line in robots.lines() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            let Some((key, value)) = line.split_once(':') else { continue };
+            let value = value.trim();
+
+            match key.trim().to_lowercase().as_str() {
+                "user-agent" => applies = value == "*" || value.eq_ignore_ascii_case(ROBOTS_AGENT),
+                "disallow" if applies && !value.is_empty() => disallowed.push(value.to_string()),
+                _ => {}
+            }
+        }
+
+        Self { disallowed }
+    }
+
+    fn allows(&self, path: &str) -> bool {
+        !self.disallowed.iter().any(|prefix| path.starts_with(prefix))
+    }
+}
+
+// Same-origin links found in the page, normalized without fragments
+fn extract_links(html: &str, base: &Url, seed: &Url) -> Vec<String> {
+    let mut links = Vec::new();
+    // ASCII-only lowercasing keeps byte offsets valid for `html`
+    let lower = html.to_ascii_lowercase();
+    let mut offset = 0;
+
+    while let Some(pos) = lower[offset..].find("href=") {
+        let start = offset + pos + 5;
+        offset = start;
+
+        let quote = match html[start..].chars().next() {
+            Some(q @ ('"' | '\'')) => q,
+            _ => continue,
+        };
+        let value_start = start + 1;
+        let Some(len) = html[value_start..].find(quote) else { break };
+        let href = &html[value_start..value_start + len];
+
+        if let Ok(mut url) = base.join(href) {
+            url.set_fragment(None);
+            if url.origin() == seed.origin() && url.path().starts_with(seed_scope(seed)) {
+                links.push(url.to_string());
+            }
+        }
+    }
+
+    links
+}
+
+// Crawls stay below the seed's directory
+fn seed_scope(seed: &Url) -> &str {
+    let path = seed.path();
+    match path.rfind('/') {
+        Some(idx) => &path[..=idx],
+        None => "/",
+    }
+}
+
+fn build_page(url: &Url, html: &str, trust_level: TrustLevel) -> CrawledPage {
+    let title = extract_tag_text(html, "title")
+        .or_else(|| extract_tag_text(html, "h1"))
+        .unwrap_or_else(|| url.path().to_string());
+
+    // Breadcrumb tags come from the directory segments of the URL path
+    let segments: Vec<&str> = url.path().split('/').filter(|s| !s.is_empty()).collect();
+    let mut tags: Vec<String> = segments
+        .iter()
+        .take(segments.len().saturating_sub(1))
+        .map(|s| s.to_lowercase())
+        .collect();
+    tags.push("crawl".to_string());
+
+    CrawledPage {
+        url: url.to_string(),
+        title,
+        markdown: html_to_markdown(html),
+        source: url.path().to_string(),
+        tags,
+        trust_level,
+    }
+}
+
+fn extract_tag_text(html: &str, tag: &str) -> Option<String> {
+    let lower = html.to_ascii_lowercase();
+    let open = lower.find(&format!("<{}", tag))?;
+    let content_start = open + lower[open..].find('>')? + 1;
+    let content_end = content_start + lower[content_start..].find(&format!("</{}", tag))?;
+    let text = strip_tags(&html[content_start..content_end]);
+    let text = text.trim();
+    (!text.is_empty()).then(|| text.to_string())
+}
+
+// Readability-lite: drop chrome (script/style/nav/header/footer) and render
+// headings, list items and paragraphs as markdown
+pub fn html_to_markdown(html: &str) -> String {
+    let mut body = html.to_string();
+    for tag in ["script", "style", "nav", "header", "footer", "head"] {
+        body = remove_elements(&body, tag);
+    }
+
+    let mut out = String::new();
+    let mut rest = body.as_str();
+    while let Some(open) = rest.find('<') {
+        out.push_str(&rest[..open]);
+        let Some(close) = rest[open..].find('>') else { break };
+        let tag = rest[open + 1..open + close].trim().to_lowercase();
+        let name = tag.trim_start_matches('/').split_whitespace().next().unwrap_or("");
+
+        match (tag.starts_with('/'), name) {
+            (false, "h1") => out.push_str("\n\n# "),
+            (false, "h2") => out.push_str("\n\n## "),
+            (false, "h3") => out.push_str("\n\n### "),
+            (false, "h4" | "h5" | "h6") => out.push_str("\n\n#### "),
+            (false, "li") => out.push_str("\n- "),
+            (_, "p" | "div" | "br" | "ul" | "ol" | "table" | "tr") => out.push('\n'),
+            (true, "h1" | "h2" | "h3" | "h4" | "h5" | "h6") => out.push('\n'),
+            _ => {}
+        }
+        rest = &rest[open + close + 1..];
+    }
+    out.push_str(rest);
+
+    let decoded = decode_entities(&out);
+    let mut lines: Vec<String> = Vec::new();
+    for line in decoded.lines() {
+        let line = line.split_whitespace().collect::<Vec<_>>().join(" ");
+        if line.is_empty() && lines.last().map(|l| l.is_empty()).unwrap_or(true) {
+            continue;
+        }
+        lines.push(line);
+    }
+    lines.join("\n").trim().to_string()
+}
+
+fn remove_elements(html: &str, tag: &str) -> String {
+    let mut result = String::with_capacity(html.len());
+    let lower = html.to_ascii_lowercase();
+    let open_pattern = format!("<{}", tag);
+    let close_pattern = format!("</{}>", tag);
+    let mut offset = 0;
+
+    while let Some(pos) = lower[offset..].find(&open_pattern) {
+        let start = offset + pos;
+        // Avoid matching a longer tag name, e.g. <header> when removing <head>
+        let boundary = lower[start + open_pattern.len()..].chars().next();
+        if !matches!(boundary, Some('>') | Some(' ') | Some('\t') | Some('\n') | Some('/')) {
+            result.push_str(&html[offset..start + open_pattern.len()]);
+            offset = start + open_pattern.len();
+            continue;
+        }
+        result.push_str(&html[offset..start]);
+        match lower[start..].find(&close_pattern) {
+            Some(end) => offset = start + end + close_pattern.len(),
+            None => return result,
+        }
+    }
+
+    result.push_str(&html[offset..]);
+    result
+}
+
+fn strip_tags(html: &str) -> String {
+    let mut out = String::new();
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' => in_tag = false,
+            _ if !in_tag => out.push(c),
+            _ => {}
+        }
+    }
+    decode_entities(&out)
+}
+
+fn decode_entities(text: &str) -> String {
+    text.replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
+
+// FNV-1a; stable across builds, unlike DefaultHasher, so persisted hashes stay valid
+fn content_hash(text: &str) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in text.as_bytes() {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+// Runs crawls in the background and keeps their progress for the connectors API
+pub struct CrawlManager {
+    crawler: Arc<Crawler>,
+    jobs: Arc<RwLock<HashMap<String, Arc<RwLock<CrawlJob>>>>>,
+}
+
+impl CrawlManager {
+    pub async fn new(knowledge_service: Option<Arc<KnowledgeService>>, http: &HttpClientFactory) -> Result<Self> {
+        Self::in_dir(knowledge_service, DEFAULT_STATE_DIR, http).await
+    }
+
+    // Persists crawl state under `state_dir`
+    pub async fn in_dir(
+        knowledge_service: Option<Arc<KnowledgeService>>,
+        state_dir: impl Into<PathBuf>,
+        http: &HttpClientFactory,
+    ) -> Result<Self> {
+        let crawler = Arc::new(Crawler::new(state_dir, http)?.with_allowed_hosts(allowed_hosts_from_env()));
+        let manager = Self {
+            crawler,
+            jobs: Arc::new(RwLock::new(HashMap::new())),
+        };
+
+        // Resume crawls interrupted by a restart from their persisted frontier
+        for job in manager.crawler.load_jobs().await {
+            let running = job.status == CrawlStatus::Running;
+            let id = job.id.clone();
+            let job = Arc::new(RwLock::new(job));
+            manager.jobs.write().await.insert(id.clone(), Arc::clone(&job));
+
+            if running {
+                if let Some(ks) = &knowledge_service {
+                    info!("Resuming crawl {}", id);
+                    manager.spawn(job, Arc::clone(ks));
+                }
+            }
+        }
+
+        Ok(manager)
+    }
+
+    pub async fn start(&self, config: CrawlConfig, store: Arc<KnowledgeService>) -> Result<String> {
+        let seed = Url::parse(&config.seed_url).context("Invalid seed URL")?;
+        self.crawler.check_host(&seed).await?;
+
+        // Re-crawling the same seed reuses the existing page records
+        let existing = {
+            let jobs = self.jobs.read().await;
+            let mut found = None;
+            for job in jobs.values() {
+                if job.read().await.config.seed_url == config.seed_url {
+                    found = Some(Arc::clone(job));
+                    break;
+                }
+            }
+            found
+        };
+
+        let job = match existing {
+            Some(job) => {
+                let mut guard = job.write().await;
+                if guard.status == CrawlStatus::Running {
+                    return Ok(guard.id.clone());
+                }
+                guard.config = config;
+                guard.restart();
+                drop(guard);
+                job
+            }
+            None => {
+                let job = CrawlJob::new(config);
+                let id = job.id.clone();
+                let job = Arc::new(RwLock::new(job));
+                self.jobs.write().await.insert(id, Arc::clone(&job));
+                job
+            }
+        };
+
+        let id = job.read().await.id.clone();
+        self.spawn(job, store);
+        Ok(id)
+    }
+
+    fn spawn(&self, job: Arc<RwLock<CrawlJob>>, store: Arc<KnowledgeService>) {
+        let crawler = Arc::clone(&self.crawler);
+        tokio::spawn(async move {
+            if let Err(e) = crawler.run(&job, store.as_ref()).await {
+                error!("Crawl failed: {}", e);
+                job.write().await.status = CrawlStatus::Failed(e.to_string());
+                crawler.persist(&job).await;
+            }
+        });
+    }
+
+    pub async fn get(&self, id: &str) -> Option<CrawlJob> {
+        let job = self.jobs.read().await.get(id).cloned()?;
+        let job = job.read().await.clone();
+        Some(job)
+    }
+
+    pub async fn list(&self) -> Vec<CrawlJob> {
+        let jobs = self.jobs.read().await;
+        let mut result = Vec::with_capacity(jobs.len());
+        for job in jobs.values() {
+            result.push(job.read().await.clone());
+        }
+        result
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct CrawlSummary {
+    id: String,
+    seed_url: String,
+    status: CrawlStatus,
+    progress: CrawlProgress,
+    frontier_size: usize,
+    started_at: chrono::DateTime<chrono::Utc>,
+    updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl From<CrawlJob> for CrawlSummary {
+    fn from(job: CrawlJob) -> Self {
+        Self {
+            id: job.id,
+            seed_url: job.config.seed_url,
+            status: job.status,
+            progress: job.progress,
+            frontier_size: job.frontier.len(),
+            started_at: job.started_at,
+            updated_at: job.updated_at,
+        }
+    }
+}
+
+// HTTP Handlers
+pub async fn start_crawl_handler(
+    State(state): State<Arc<crate::AppState>>,
+    Json(config): Json<CrawlConfig>,
+) -> impl IntoResponse {
+    let knowledge_service = match &state.knowledge_service {
+        Some(service) => Arc::clone(service),
+        None => {
+            return (StatusCode::SERVICE_UNAVAILABLE, "Knowledge base service is not available").into_response();
+        }
+    };
+
+    match state.crawl_manager.start(config, knowledge_service).await {
+        Ok(id) => (StatusCode::ACCEPTED, Json(serde_json::json!({ "crawl_id": id }))).into_response(),
+        Err(e) => (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    }
+}
+
+pub async fn crawl_status_handler(
+    State(state): State<Arc<crate::AppState>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match state.crawl_manager.get(&id).await {
+        Some(job) => Json(CrawlSummary::from(job)).into_response(),
+        None => (StatusCode::NOT_FOUND, "Crawl not found").into_response(),
+    }
+}
+
+pub async fn list_connectors_handler(
+    State(state): State<Arc<crate::AppState>>,
+) -> impl IntoResponse {
+    let crawls: Vec<CrawlSummary> = state
+        .crawl_manager
+        .list()
+        .await
+        .into_iter()
+        .map(CrawlSummary::from)
+        .collect();
+
+    Json(serde_json::json!({
+        "connectors": [{
+            "type": "url_crawl",
+            "crawls": crawls,
+        }]
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{http::header, Router};
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct MemoryStore {
+        pages: Mutex<HashMap<String, CrawledPage>>,
+    }
+
+    impl PageStore for MemoryStore {
+        async fn store_page(&self, page: &CrawledPage) -> Result<String> {
+            let id = Uuid::new_v4().to_string();
+            self.pages.lock().unwrap().insert(id.clone(), page.clone());
+            Ok(id)
+        }
+
+        async fn update_page(&self, document_id: &str, page: &CrawledPage) -> Result<String> {
+            self.pages.lock().unwrap().insert(document_id.to_string(), page.clone());
+            Ok(document_id.to_string())
+        }
+
+        async fn remove_page(&self, document_id: &str) -> Result<()> {
+            self.pages.lock().unwrap().remove(document_id);
+            Ok(())
+        }
+    }
+
+    impl MemoryStore {
+        fn sources(&self) -> Vec<String> {
+            let mut sources: Vec<String> = self.pages.lock().unwrap().values().map(|p| p.source.clone()).collect();
+            sources.sort();
+            sources
+        }
+    }
+
+    type Site = Arc<Mutex<HashMap<String, String>>>;
+
+    fn fixture_site() -> Site {
+        let pages = [
+            ("/robots.txt", "User-agent: *\nDisallow: /wiki/private/\n"),
+            ("/wiki/", r#"<html><head><title>Wiki Home</title></head><body>
+                <nav><a href="/wiki/nav-only">Menu</a></nav>
+                <h1>Home</h1><p>Welcome &amp; hello.</p>
+                <a href="setup/docker">Docker</a> <a href="/wiki/faq#top">FAQ</a>
+                <a href="/wiki/private/secrets">Secrets</a> <a href="https://example.com/">Elsewhere</a>
+                <a href="/blog/">Blog</a></body></html>"#),
+            ("/wiki/setup/docker", r#"<html><head><title>Docker</title></head><body>
+                <h2>Install</h2><ul><li>Pull image</li><li>Run it</li></ul>
+                <a href="/wiki/">Home</a></body></html>"#),
+            ("/wiki/faq", "<html><head><title>FAQ</title></head><body><p>Questions</p></body></html>"),
+            ("/wiki/private/secrets", "<html><body>secret</body></html>"),
+        ];
+        Arc::new(Mutex::new(pages.iter().map(|(p, b)| (p.to_string(), b.to_string())).collect()))
+    }
+
+    async fn serve(site: Site) -> String {
+        let app = Router::new().fallback(move |uri: axum::http::Uri| {
+            let site = Arc::clone(&site);
+            async move {
+                match site.lock().unwrap().get(uri.path()).cloned() {
+                    Some(body) if uri.path().ends_with(".txt") => ([(header::CONTENT_TYPE, "text/plain")], body).into_response(),
+                    Some(body) => ([(header::CONTENT_TYPE, "text/html")], body).into_response(),
+                    None => StatusCode::NOT_FOUND.into_response(),
+                }
+            }
+        });
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        format!("http://{}/wiki/", addr)
+    }
+
+    fn test_job(seed_url: String, max_pages: usize) -> Arc<RwLock<CrawlJob>> {
+        Arc::new(RwLock::new(CrawlJob::new(CrawlConfig {
+            seed_url,
+            max_pages,
+            politeness_delay_ms: 1,
+            respect_robots: true,
+            trust_level: TrustLevel::External,
+        })))
+    }
+
+    // The fixture sites listen on loopback, so it is allowed
+    fn test_crawler() -> Crawler {
+        Crawler::new(std::env::temp_dir().join(format!("rusty-ai-crawl-{}", Uuid::new_v4())), &HttpClientFactory::default())
+            .unwrap()
+            .with_allowed_hosts(["127.0.0.1".to_string()])
+    }
+
+    #[tokio::test]
+    async fn test_crawl_respects_scope_and_robots() {
+        let seed = serve(fixture_site()).await;
+        let crawler = test_crawler();
+        let store = MemoryStore::default();
+        let job = test_job(seed, 50);
+
+        crawler.run(&job, &store).await.unwrap();
+
+        assert_eq!(store.sources(), vec!["/wiki/", "/wiki/faq", "/wiki/setup/docker"]);
+        let job = job.read().await;
+        assert_eq!(job.status, CrawlStatus::Completed);
+        assert_eq!(job.progress.pages_stored, 3);
+
+        let docker = store.pages.lock().unwrap().values().find(|p| p.source == "/wiki/setup/docker").cloned().unwrap();
+        assert_eq!(docker.tags, vec!["wiki", "setup", "crawl"]);
+        assert_eq!(docker.trust_level, TrustLevel::External);
+        assert!(docker.markdown.contains("## Install"));
+        assert!(docker.markdown.contains("- Pull image"));
+    }
+
+    #[tokio::test]
+    async fn test_crawl_page_cap() {
+        let seed = serve(fixture_site()).await;
+        let crawler = test_crawler();
+        let store = MemoryStore::default();
+        let job = test_job(seed, 1);
+
+        crawler.run(&job, &store).await.unwrap();
+
+        assert_eq!(store.sources(), vec!["/wiki/"]);
+        assert!(!job.read().await.frontier.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_recrawl_updates_changed_and_removes_vanished_pages() {
+        let site = fixture_site();
+        let seed = serve(Arc::clone(&site)).await;
+        let crawler = test_crawler();
+        let store = MemoryStore::default();
+        let job = test_job(seed, 50);
+
+        crawler.run(&job, &store).await.unwrap();
+        let docker_id = |job: &CrawlJob| {
+            job.pages.iter().find(|(url, _)| url.ends_with("/wiki/setup/docker")).map(|(_, page)| page.document_id.clone())
+        };
+        let original_id = docker_id(&*job.read().await);
+
+        {
+            let mut site = site.lock().unwrap();
+            site.remove("/wiki/faq");
+            site.insert(
+                "/wiki/setup/docker".to_string(),
+                "<html><head><title>Docker</title></head><body><p>Use compose now</p></body></html>".to_string(),
+            );
+        }
+
+        job.write().await.restart();
+        crawler.run(&job, &store).await.unwrap();
+
+        assert_eq!(store.sources(), vec!["/wiki/", "/wiki/setup/docker"]);
+        let job = job.read().await;
+        assert_eq!(job.progress.pages_unchanged, 1);
+        assert_eq!(job.progress.pages_stored, 1);
+        assert_eq!(job.progress.pages_removed, 1);
+        // The changed page is updated in place rather than stored anew
+        assert!(original_id.is_some());
+        assert_eq!(docker_id(&job), original_id);
+        assert!(store.pages.lock().unwrap().values().any(|p| p.markdown.contains("compose")));
+    }
+
+    #[tokio::test]
+    async fn test_crawl_state_is_persisted() {
+        let seed = serve(fixture_site()).await;
+        let crawler = test_crawler();
+        let store = MemoryStore::default();
+        let job = test_job(seed, 1);
+
+        crawler.run(&job, &store).await.unwrap();
+
+        let jobs = crawler.load_jobs().await;
+        assert_eq!(jobs.len(), 1);
+        assert_eq!(jobs[0].frontier, job.read().await.frontier);
+        assert_eq!(jobs[0].seen_hashes, job.read().await.seen_hashes);
+        assert_eq!(jobs[0].seen_hashes.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_resumed_crawl_still_skips_aliases_of_stored_pages() {
+        let site = fixture_site();
+        let index = site.lock().unwrap()["/wiki/"].clone();
+        site.lock().unwrap().insert("/wiki/index.html".to_string(), index);
+        let seed = serve(site).await;
+        let crawler = test_crawler();
+        let store = MemoryStore::default();
+        let job = test_job(seed.clone(), 1);
+
+        crawler.run(&job, &store).await.unwrap();
+        assert_eq!(store.sources(), vec!["/wiki/"]);
+
+        // Picked up from the persisted state, as after a restart
+        let mut resumed = crawler.load_jobs().await.remove(0);
+        resumed.config.max_pages = 50;
+        resumed.frontier.push_front(format!("{}index.html", seed));
+        let resumed = Arc::new(RwLock::new(resumed));
+        crawler.run(&resumed, &store).await.unwrap();
+
+        assert!(!store.sources().contains(&"/wiki/index.html".to_string()));
+    }
+
+    #[test]
+    fn test_robots_rules() {
+        let rules = RobotsRules::parse("User-agent: googlebot\nDisallow: /\n\nUser-agent: *\nDisallow: /private\n");
+        assert!(rules.allows("/wiki"));
+        assert!(!rules.allows("/private/page"));
+
+        // Groups name the crawler by its exact token, in any case
+        let rules = RobotsRules::parse("User-agent: rusty\nDisallow: /a\n\nUser-agent: Rusty-AI-Crawler\nDisallow: /b\n");
+        assert!(rules.allows("/a"));
+        assert!(!rules.allows("/b"));
+    }
+
+    #[test]
+    fn test_non_ascii_text_before_a_tag() {
+        let seed = Url::parse("https://example.com/").unwrap();
+        // Unquoted hrefs are skipped, but must not throw the offsets off
+        assert!(extract_links("<p>\u{130}stanbul</p><a href=\u{fc}ber>x</a>", &seed, &seed).is_empty());
+
+        let html = "<p>\u{130}stanbul</p><a href=\"\u{fc}ber\">x</a><TITLE>\u{130}zmir</TITLE><script>x()</script>";
+        assert_eq!(extract_links(html, &seed, &seed), vec!["https://example.com/%C3%BCber".to_string()]);
+        assert_eq!(extract_tag_text(html, "title").as_deref(), Some("\u{130}zmir"));
+        assert!(!remove_elements(html, "script").contains("x()"));
+    }
+
+    #[tokio::test]
+    async fn test_private_and_loopback_seeds_are_rejected_unless_allowed() {
+        let crawler = test_crawler().with_allowed_hosts(["Wiki.Internal".to_string(), "10.0.0.5".to_string()]);
+        for seed in ["http://127.0.0.1/", "http://localhost:8080/", "http://10.0.0.6/", "http://[::1]/", "http://169.254.169.254/"] {
+            let error = crawler.check_host(&Url::parse(seed).unwrap()).await.unwrap_err();
+            assert!(error.to_string().contains("is not public"), "{}: {}", seed, error);
+        }
+        assert!(crawler.check_host(&Url::parse("http://93.184.216.34/").unwrap()).await.is_ok());
+        assert!(crawler.check_host(&Url::parse("http://10.0.0.5/").unwrap()).await.is_ok());
+        assert!(crawler.check_host(&Url::parse("http://wiki.internal/").unwrap()).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_redirects_to_hosts_that_are_not_allowed_are_refused() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let app = Router::new()
+            .route("/wiki/", axum::routing::get(|| async { axum::response::Redirect::temporary("/wiki/home") }))
+            .route(
+                "/wiki/home",
+                axum::routing::get(|| async { ([(header::CONTENT_TYPE, "text/html")], "<title>Home</title>") }),
+            )
+            .route(
+                "/wiki/metadata",
+                axum::routing::get(move || async move {
+                    axum::response::Redirect::temporary(&format!("http://localhost:{}/wiki/home", port))
+                }),
+            );
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        let crawler = test_crawler();
+
+        let home = crawler.fetch_page(&Url::parse(&format!("http://127.0.0.1:{}/wiki/", port)).unwrap()).await;
+        assert!(home.unwrap().unwrap().contains("Home"));
+
+        let url = Url::parse(&format!("http://127.0.0.1:{}/wiki/metadata", port)).unwrap();
+        let error = crawler.fetch_page(&url).await.unwrap_err();
+        assert!(error.to_string().contains("localhost is not public"), "{}", error);
+    }
+}
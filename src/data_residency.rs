@@ -0,0 +1,238 @@
+use anyhow::{bail, Result};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use tracing::warn;
+
+use crate::knowledge_service_simple::DocumentMatch;
+
+// Where a provider processes the data sent to it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProviderClass {
+    Cloud,
+    Local,
+}
+
+impl ProviderClass {
+    // Providers reached over the loopback interface run on this machine;
+    // everything else is treated as cloud
+    pub fn of_endpoint(url: &str) -> Self {
+        let host = reqwest::Url::parse(url)
+            .ok()
+            .and_then(|url| url.host_str().map(|h| h.trim_matches(|c| c == '[' || c == ']').to_string()));
+        match host.as_deref() {
+            Some("localhost") | Some("::1") => ProviderClass::Local,
+            Some(host) if host.starts_with("127.") => ProviderClass::Local,
+            _ => ProviderClass::Cloud,
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "cloud" => Some(ProviderClass::Cloud),
+            "local" => Some(ProviderClass::Local),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for ProviderClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProviderClass::Cloud => write!(f, "cloud"),
+            ProviderClass::Local => write!(f, "local"),
+        }
+    }
+}
+
+// Why a piece of data was kept away from a provider
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Restriction {
+    pub tag: String,
+    pub allowed: Vec<ProviderClass>,
+    pub provider: ProviderClass,
+}
+
+impl Restriction {
+    pub fn reason(&self) -> String {
+        format!(
+            "tagged '{}', which may only be processed by {} providers",
+            self.tag,
+            self.allowed.iter().map(|c| c.to_string()).collect::<Vec<_>>().join(" or ")
+        )
+    }
+}
+
+// A retrieved chunk left out of the chat context
+#[derive(Debug, Clone, Serialize)]
+pub struct WithheldSource {
+    pub document_id: String,
+    pub title: String,
+    pub chunk_index: usize,
+    pub tag: String,
+    pub reason: String,
+}
+
+// Which provider classes may see data carrying a given document tag or
+// memory category. Tags without a rule may go anywhere
+#[derive(Debug, Clone, Default)]
+pub struct ResidencyPolicy {
+    rules: BTreeMap<String, Vec<ProviderClass>>,
+}
+
+impl ResidencyPolicy {
+    pub fn new(rules: impl IntoIterator<Item = (String, Vec<ProviderClass>)>) -> Self {
+        Self {
+            rules: rules
+                .into_iter()
+                .map(|(tag, allowed)| (tag.to_ascii_lowercase(), allowed))
+                .collect(),
+        }
+    }
+
+    // DATA_RESIDENCY="medical=local,finance=local,work=cloud|local"
+    // A malformed policy is an error rather than an empty one, so the server
+    // refuses to start instead of sending restricted data out
+    pub fn from_env() -> Result<Self> {
+        match std::env::var("DATA_RESIDENCY") {
+            Ok(spec) => Self::parse(&spec).map_err(|e| anyhow::anyhow!("Invalid DATA_RESIDENCY: {}", e)),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+
+    pub fn parse(spec: &str) -> Result<Self> {
+        let mut rules = Vec::new();
+        for rule in spec.split(',').map(str::trim).filter(|r| !r.is_empty()) {
+            let Some((tag, classes)) = rule.split_once('=') else {
+                bail!("rule '{}' is not tag=classes", rule);
+            };
+            let allowed = classes
+                .split('|')
+                .map(|c| ProviderClass::parse(c).ok_or_else(|| anyhow::anyhow!("unknown provider class '{}'", c)))
+                .collect::<Result<Vec<_>>>()?;
+            if tag.trim().is_empty() {
+                bail!("rule '{}' has no tag", rule);
+            }
+            rules.push((tag.trim().to_string(), allowed));
+        }
+        Ok(Self::new(rules))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    // Each tag with a rule and the provider classes it allows
+    pub fn rules(&self) -> impl Iterator<Item = (&str, &[ProviderClass])> {
+        self.rules.iter().map(|(tag, allowed)| (tag.as_str(), allowed.as_slice()))
+    }
+
+    // The first of the tags that keeps the data away from the provider
+    pub fn check(&self, tags: &[String], provider: ProviderClass) -> Option<Restriction> {
+        tags.iter().find_map(|tag| {
+            let allowed = self.rules.get(&tag.to_ascii_lowercase())?;
+            (!allowed.contains(&provider)).then(|| Restriction {
+                tag: tag.clone(),
+                allowed: allowed.clone(),
+                provider,
+            })
+        })
+    }
+
+    // Split retrieved chunks into those the chat provider may see and those
+    // withheld from it
+    pub fn filter_context(
+        &self,
+        matches: Vec<DocumentMatch>,
+        chat_provider: ProviderClass,
+    ) -> (Vec<DocumentMatch>, Vec<WithheldSource>) {
+        let mut allowed = Vec::with_capacity(matches.len());
+        let mut withheld = Vec::new();
+        for candidate in matches {
+            match self.check(&candidate.tags, chat_provider) {
+                None => allowed.push(candidate),
+                Some(restriction) => {
+                    warn!(
+                        "Withholding '{}' (chunk {}) from the {} chat provider: {}",
+                        candidate.title,
+                        candidate.chunk_index,
+                        chat_provider,
+                        restriction.reason()
+                    );
+                    withheld.push(WithheldSource {
+                        document_id: candidate.id,
+                        title: candidate.title,
+                        chunk_index: candidate.chunk_index,
+                        reason: restriction.reason(),
+                        tag: restriction.tag,
+                    });
+                }
+            }
+        }
+        (allowed, withheld)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trust::TrustLevel;
+
+    fn chunk(id: &str, title: &str, tags: &[&str]) -> DocumentMatch {
+        DocumentMatch {
+            id: id.to_string(),
+            title: title.to_string(),
+            content: format!("{} content", title),
+            score: 0.8,
+            chunk_index: 0,
+            source: "upload".to_string(),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            trust_level: TrustLevel::Personal,
+            note: None,
+            manually_corrected: false,
+            page: None,
+        }
+    }
+
+    #[test]
+    fn test_restricted_document_follows_chat_provider() {
+        let policy = ResidencyPolicy::parse("medical=local, work=cloud|local").unwrap();
+        let retrieved = vec![
+            chunk("doc-1", "Blood test results", &["Medical", "2024"]),
+            chunk("doc-2", "Team handbook", &["work"]),
+            chunk("doc-3", "Recipes", &[]),
+        ];
+
+        let (included, withheld) = policy.filter_context(retrieved.clone(), ProviderClass::Cloud);
+        assert_eq!(included.iter().map(|m| m.id.as_str()).collect::<Vec<_>>(), vec!["doc-2", "doc-3"]);
+        assert_eq!(withheld.len(), 1);
+        assert_eq!(withheld[0].document_id, "doc-1");
+        assert_eq!(withheld[0].tag, "Medical");
+        assert!(withheld[0].reason.contains("local"));
+
+        let (included, withheld) = policy.filter_context(retrieved, ProviderClass::Local);
+        assert_eq!(included.len(), 3);
+        assert!(withheld.is_empty());
+    }
+
+    #[test]
+    fn test_policy_parsing() {
+        assert!(ResidencyPolicy::parse("").unwrap().is_empty());
+        assert!(ResidencyPolicy::parse("medical").is_err());
+        assert!(ResidencyPolicy::parse("medical=onprem").is_err());
+
+        let policy = ResidencyPolicy::parse("finance=local").unwrap();
+        let restriction = policy.check(&["finance".to_string()], ProviderClass::Cloud).unwrap();
+        assert_eq!(restriction.allowed, vec![ProviderClass::Local]);
+        assert!(policy.check(&["finance".to_string()], ProviderClass::Local).is_none());
+    }
+
+    #[test]
+    fn test_endpoint_classification() {
+        assert_eq!(ProviderClass::of_endpoint("https://api.openai.com/v1"), ProviderClass::Cloud);
+        assert_eq!(ProviderClass::of_endpoint("http://localhost:11434/v1"), ProviderClass::Local);
+        assert_eq!(ProviderClass::of_endpoint("http://127.0.0.1:8080/v1"), ProviderClass::Local);
+        assert_eq!(ProviderClass::of_endpoint("http://[::1]:8080/v1"), ProviderClass::Local);
+        assert_eq!(ProviderClass::of_endpoint("http://localhost.example.com/v1"), ProviderClass::Cloud);
+    }
+}
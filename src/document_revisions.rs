@@ -0,0 +1,451 @@
+// Revision history of re-ingested documents. When a connector brings in a
+// new version of a document it already indexed, the knowledge service diffs
+// the stored text against the new one, re-embeds only the chunks that
+// changed and records a revision here. Each revision is also broadcast as a
+// `document.updated` event for the WebSocket push channel.
+use anyhow::Result;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Response,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::sync::broadcast;
+use tracing::error;
+
+use crate::envelope::{fail, ok};
+
+// Changed lines considered per side once the common head and tail are
+// trimmed. A larger changed region is reported as replaced wholesale instead
+// of diffed line by line, which would need (old x new) cells
+pub const MAX_DIFF_LINES: usize = 1_000;
+// Changed lines listed in a summary; the counts always cover every change
+const MAX_LISTED_CHANGES: usize = 50;
+// Longer lines are cut in the listing
+const MAX_LINE_CHARS: usize = 200;
+const EVENT_CHANNEL_CAPACITY: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    Added,
+    Removed,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LineChange {
+    pub kind: ChangeKind,
+    // 1-based, in the old text for removed lines and the new text for added ones
+    pub line: usize,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DiffSummary {
+    pub lines_added: usize,
+    pub lines_removed: usize,
+    pub changes: Vec<LineChange>,
+    // Not every change is listed, or the changed region was over
+    // MAX_DIFF_LINES and is counted as replaced wholesale
+    pub truncated: bool,
+}
+
+impl DiffSummary {
+    pub fn is_empty(&self) -> bool {
+        self.lines_added == 0 && self.lines_removed == 0
+    }
+
+    pub fn headline(&self) -> String {
+        if self.is_empty() {
+            return "no text changes".to_string();
+        }
+        format!(
+            "{} line{} added, {} removed",
+            self.lines_added,
+            if self.lines_added == 1 { "" } else { "s" },
+            self.lines_removed
+        )
+    }
+
+    fn push(&mut self, kind: ChangeKind, line: usize, text: &str) {
+        match kind {
+            ChangeKind::Added => self.lines_added += 1,
+            ChangeKind::Removed => self.lines_removed += 1,
+        }
+        if self.changes.len() < MAX_LISTED_CHANGES {
+            self.changes.push(LineChange {
+                kind,
+                line: line + 1,
+                text: text.chars().take(MAX_LINE_CHARS).collect(),
+            });
+        } else {
+            self.truncated = true;
+        }
+    }
+}
+
+// Line-based diff of two versions of a document's text
+pub fn diff_lines(old: &str, new: &str) -> DiffSummary {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    let head = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let tail = old[head..]
+        .iter()
+        .rev()
+        .zip(new[head..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_changed = &old[head..old.len() - tail];
+    let new_changed = &new[head..new.len() - tail];
+
+    let mut summary = DiffSummary::default();
+    if old_changed.len() > MAX_DIFF_LINES || new_changed.len() > MAX_DIFF_LINES {
+        for (i, line) in old_changed.iter().enumerate() {
+            summary.push(ChangeKind::Removed, head + i, line);
+        }
+        for (j, line) in new_changed.iter().enumerate() {
+            summary.push(ChangeKind::Added, head + j, line);
+        }
+        summary.truncated = true;
+        return summary;
+    }
+
+    // lcs[i][j]: longest common subsequence of old_changed[i..] and new_changed[j..]
+    let width = new_changed.len() + 1;
+    let mut lcs = vec![0u32; (old_changed.len() + 1) * width];
+    for i in (0..old_changed.len()).rev() {
+        for j in (0..new_changed.len()).rev() {
+            lcs[i * width + j] = if old_changed[i] == new_changed[j] {
+                lcs[(i + 1) * width + j + 1] + 1
+            } else {
+                lcs[(i + 1) * width + j].max(lcs[i * width + j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    while i < old_changed.len() || j < new_changed.len() {
+        if i < old_changed.len() && j < new_changed.len() && old_changed[i] == new_changed[j] {
+            i += 1;
+            j += 1;
+        } else if j == new_changed.len() || (i < old_changed.len() && lcs[(i + 1) * width + j] >= lcs[i * width + j + 1]) {
+            summary.push(ChangeKind::Removed, head + i, old_changed[i]);
+            i += 1;
+        } else {
+            summary.push(ChangeKind::Added, head + j, new_changed[j]);
+            j += 1;
+        }
+    }
+    summary
+}
+
+// What re-indexing a new version of a document has to do with its chunks
+#[derive(Debug, Default, PartialEq)]
+pub struct ChunkPlan {
+    // (new chunk index, existing point id) for chunks whose text is unchanged
+    pub reused: Vec<(usize, String)>,
+    // New chunk indices that need an embedding
+    pub embed: Vec<usize>,
+    // Existing points that are no longer part of the document
+    pub stale: Vec<String>,
+}
+
+// `existing` are (point id, text) of the stored chunks, `chunks` the texts
+// of the new version. A chunk is reused when an existing point has the same
+// text, wherever it moved to; each point is reused at most once
+pub fn plan_chunks(existing: &[(String, String)], chunks: &[String]) -> ChunkPlan {
+    let mut available: Vec<Option<&(String, String)>> = existing.iter().map(Some).collect();
+    let mut plan = ChunkPlan::default();
+
+    for (index, chunk) in chunks.iter().enumerate() {
+        let found = available
+            .iter_mut()
+            .find(|point| point.is_some_and(|(_, text)| text == chunk))
+            .and_then(Option::take);
+        match found {
+            Some((point_id, _)) => plan.reused.push((index, point_id.clone())),
+            None => plan.embed.push(index),
+        }
+    }
+    plan.stale = available.into_iter().flatten().map(|(point_id, _)| point_id.clone()).collect();
+    plan
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DocumentRevision {
+    pub document_id: String,
+    // The original ingestion is revision 1, so the first recorded change is 2
+    pub revision: i64,
+    pub title: String,
+    pub summary: DiffSummary,
+    // Chunk indices of the new version that were re-embedded
+    pub changed_chunks: Vec<usize>,
+    pub total_chunks: usize,
+    pub reused_chunks: usize,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+// Pushed to WebSocket clients as `document.updated`
+#[derive(Debug, Clone, Serialize)]
+pub struct DocumentUpdated {
+    pub document_id: String,
+    pub title: String,
+    pub revision: i64,
+    pub headline: String,
+    pub summary: DiffSummary,
+}
+
+#[derive(sqlx::FromRow)]
+struct RevisionRow {
+    document_id: String,
+    revision: i64,
+    title: String,
+    summary: String,
+    changed_chunks: String,
+    total_chunks: i64,
+    reused_chunks: i64,
+    created_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl TryFrom<RevisionRow> for DocumentRevision {
+    type Error = anyhow::Error;
+
+    fn try_from(row: RevisionRow) -> Result<Self> {
+        Ok(Self {
+            document_id: row.document_id,
+            revision: row.revision,
+            title: row.title,
+            summary: serde_json::from_str(&row.summary)?,
+            changed_chunks: serde_json::from_str(&row.changed_chunks)?,
+            total_chunks: row.total_chunks as usize,
+            reused_chunks: row.reused_chunks as usize,
+            created_at: row.created_at,
+        })
+    }
+}
+
+pub struct RevisionStore {
+    pool: sqlx::SqlitePool,
+    events: broadcast::Sender<DocumentUpdated>,
+}
+
+impl RevisionStore {
+    pub async fn new(database_url: &str) -> Result<Self> {
+        let pool = crate::ai_service::connect_sqlite(database_url).await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS document_revisions (
+                document_id TEXT NOT NULL,
+                revision INTEGER NOT NULL,
+                title TEXT NOT NULL,
+                summary TEXT NOT NULL,
+                changed_chunks TEXT NOT NULL,
+                total_chunks INTEGER NOT NULL,
+                reused_chunks INTEGER NOT NULL,
+                created_at TIMESTAMP NOT NULL,
+                PRIMARY KEY (document_id, revision)
+            )
+            "#,
+        )
+        .execute(&pool)
+        .await?;
+
+        let (events, _) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
+        Ok(Self { pool, events })
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<DocumentUpdated> {
+        self.events.subscribe()
+    }
+
+    // Store the next revision of a document and announce it
+    pub async fn record(
+        &self,
+        document_id: &str,
+        title: &str,
+        summary: DiffSummary,
+        changed_chunks: Vec<usize>,
+        total_chunks: usize,
+    ) -> Result<DocumentRevision> {
+        let latest: Option<i64> = sqlx::query_scalar("SELECT MAX(revision) FROM document_revisions WHERE document_id = ?")
+            .bind(document_id)
+            .fetch_one(&self.pool)
+            .await?;
+        let revision = DocumentRevision {
+            document_id: document_id.to_string(),
+            revision: latest.unwrap_or(1) + 1,
+            title: title.to_string(),
+            reused_chunks: total_chunks - changed_chunks.len(),
+            summary,
+            changed_chunks,
+            total_chunks,
+            created_at: chrono::Utc::now(),
+        };
+
+        sqlx::query(
+            r#"
+            INSERT INTO document_revisions
+                (document_id, revision, title, summary, changed_chunks, total_chunks, reused_chunks, created_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&revision.document_id)
+        .bind(revision.revision)
+        .bind(&revision.title)
+        .bind(serde_json::to_string(&revision.summary)?)
+        .bind(serde_json::to_string(&revision.changed_chunks)?)
+        .bind(revision.total_chunks as i64)
+        .bind(revision.reused_chunks as i64)
+        .bind(revision.created_at)
+        .execute(&self.pool)
+        .await?;
+
+        // No subscribers is fine
+        let _ = self.events.send(DocumentUpdated {
+            document_id: revision.document_id.clone(),
+            title: revision.title.clone(),
+            revision: revision.revision,
+            headline: revision.summary.headline(),
+            summary: revision.summary.clone(),
+        });
+        Ok(revision)
+    }
+
+    // Oldest first
+    pub async fn list_for_document(&self, document_id: &str) -> Result<Vec<DocumentRevision>> {
+        let rows = sqlx::query_as::<_, RevisionRow>(
+            "SELECT * FROM document_revisions WHERE document_id = ? ORDER BY revision ASC",
+        )
+        .bind(document_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter().map(DocumentRevision::try_from).collect()
+    }
+
+    pub async fn delete_for_document(&self, document_id: &str) -> Result<u64> {
+        let result = sqlx::query("DELETE FROM document_revisions WHERE document_id = ?")
+            .bind(document_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+// HTTP Handlers
+pub async fn list_revisions_handler(
+    State(state): State<Arc<crate::AppState>>,
+    Path(document_id): Path<String>,
+) -> Response {
+    let revisions = match state.revision_store.list_for_document(&document_id).await {
+        Ok(revisions) => revisions,
+        Err(e) => {
+            error!("Failed to list revisions of {}: {}", document_id, e);
+            return fail(StatusCode::INTERNAL_SERVER_ERROR, "Failed to list document revisions");
+        }
+    };
+
+    // A document that was never changed has no revisions but still exists
+    if revisions.is_empty() {
+        let Some(knowledge_service) = &state.knowledge_service else {
+            return fail(StatusCode::SERVICE_UNAVAILABLE, "Knowledge base service is not available");
+        };
+        match knowledge_service.find_document(&document_id).await {
+            Ok(Some(_)) => {}
+            Ok(None) => return fail(StatusCode::NOT_FOUND, "Document not found"),
+            Err(e) => {
+                error!("Failed to look up document {}: {}", document_id, e);
+                return fail(StatusCode::INTERNAL_SERVER_ERROR, "Failed to look up document");
+            }
+        }
+    }
+
+    ok(serde_json::json!({
+        "document_id": document_id,
+        "total": revisions.len(),
+        "revisions": revisions,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_lists_changed_lines_in_order() {
+        let old = "# Lease\nRent is 1200 per month.\nDeposit is two months.\nPets allowed.";
+        let new = "# Lease\nRent is 1350 per month.\nDeposit is two months.\nNo smoking.\nPets allowed.";
+        let summary = diff_lines(old, new);
+
+        assert_eq!((summary.lines_added, summary.lines_removed), (2, 1));
+        assert!(!summary.truncated);
+        let listed: Vec<(ChangeKind, usize, &str)> =
+            summary.changes.iter().map(|c| (c.kind, c.line, c.text.as_str())).collect();
+        assert_eq!(
+            listed,
+            vec![
+                (ChangeKind::Removed, 2, "Rent is 1200 per month."),
+                (ChangeKind::Added, 2, "Rent is 1350 per month."),
+                (ChangeKind::Added, 4, "No smoking."),
+            ]
+        );
+        assert_eq!(summary.headline(), "2 lines added, 1 removed");
+        assert!(diff_lines(old, old).is_empty());
+    }
+
+    #[test]
+    fn test_large_changed_regions_are_capped() {
+        let old: String = (0..MAX_DIFF_LINES + 10).map(|i| format!("old {}\n", i)).collect();
+        let new: String = (0..MAX_DIFF_LINES + 10).map(|i| format!("new {}\n", i)).collect();
+        let summary = diff_lines(&format!("same\n{}", old), &format!("same\n{}", new));
+
+        assert_eq!(summary.lines_removed, MAX_DIFF_LINES + 10);
+        assert_eq!(summary.lines_added, MAX_DIFF_LINES + 10);
+        assert_eq!(summary.changes.len(), MAX_LISTED_CHANGES);
+        assert_eq!(summary.changes[0].line, 2);
+        assert!(summary.truncated);
+    }
+
+    #[test]
+    fn test_plan_reuses_unchanged_chunks_wherever_they_moved() {
+        let existing = vec![
+            ("p0".to_string(), "intro".to_string()),
+            ("p1".to_string(), "rent".to_string()),
+            ("p2".to_string(), "pets".to_string()),
+        ];
+        let chunks = vec!["intro".to_string(), "pets".to_string(), "rent v2".to_string()];
+        let plan = plan_chunks(&existing, &chunks);
+
+        assert_eq!(plan.reused, vec![(0, "p0".to_string()), (1, "p2".to_string())]);
+        assert_eq!(plan.embed, vec![2]);
+        assert_eq!(plan.stale, vec!["p1".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_revisions_are_numbered_per_document_and_announced() {
+        let path = std::env::temp_dir().join(format!("rusty-ai-revisions-{}.db", uuid::Uuid::new_v4()));
+        let store = RevisionStore::new(&format!("sqlite://{}?mode=rwc", path.display())).await.unwrap();
+        let mut events = store.subscribe();
+
+        let summary = diff_lines("a\nb", "a\nc");
+        let first = store.record("doc-1", "Lease", summary.clone(), vec![1], 3).await.unwrap();
+        let second = store.record("doc-1", "Lease", DiffSummary::default(), vec![], 3).await.unwrap();
+        store.record("doc-2", "Other", DiffSummary::default(), vec![0], 1).await.unwrap();
+        assert_eq!((first.revision, second.revision), (2, 3));
+        assert_eq!(first.reused_chunks, 2);
+
+        let listed = store.list_for_document("doc-1").await.unwrap();
+        assert_eq!(listed, vec![first, second]);
+
+        let event = events.recv().await.unwrap();
+        assert_eq!((event.document_id.as_str(), event.revision), ("doc-1", 2));
+        assert_eq!(event.summary, summary);
+
+        assert_eq!(store.delete_for_document("doc-1").await.unwrap(), 2);
+        assert!(store.list_for_document("doc-1").await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,93 @@
+// The response envelope of the multi-user API (`rusty_ai_common::ApiResponse`)
+// for this server's handlers, so clients parse one shape everywhere: the
+// payload under `data`, failures as `error` with a typed `error_code`, and
+// the request id that is also sent in the `x-request-id` header.
+use axum::{
+    extract::Request,
+    http::{HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use rusty_ai_common::{ApiResponse, ErrorCode};
+use serde::Serialize;
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+// Gives every request an id that responses built inside it carry
+pub async fn request_id_middleware(request: Request, next: Next) -> Response {
+    let request_id = uuid::Uuid::new_v4().to_string();
+    let mut response = REQUEST_ID.scope(request_id.clone(), next.run(request)).await;
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
+
+pub fn respond<T: Serialize>(status: StatusCode, response: ApiResponse<T>) -> Response {
+    let response = match REQUEST_ID.try_with(|id| id.clone()) {
+        Ok(request_id) => response.with_request_id(request_id),
+        Err(_) => response,
+    };
+    (status, Json(response)).into_response()
+}
+
+pub fn ok<T: Serialize>(data: T) -> Response {
+    respond(StatusCode::OK, ApiResponse::success(data))
+}
+
+pub fn created<T: Serialize>(data: T) -> Response {
+    respond(StatusCode::CREATED, ApiResponse::success(data))
+}
+
+// An error whose code follows from the status
+pub fn fail(status: StatusCode, message: impl Into<String>) -> Response {
+    respond(status, ApiResponse::<()>::error_with_code(ErrorCode::for_status(status.as_u16()), message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::routing::get;
+    use axum::Router;
+
+    async fn serve() -> String {
+        let app = Router::new()
+            .route("/ok", get(|| async { ok(serde_json::json!({ "answer": 42 })) }))
+            .route("/missing", get(|| async { fail(StatusCode::NOT_FOUND, "Session not found") }))
+            .layer(axum::middleware::from_fn(request_id_middleware));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        format!("http://{}", addr)
+    }
+
+    async fn call(url: String) -> (u16, String, ApiResponse<serde_json::Value>) {
+        let response = reqwest::get(url).await.unwrap();
+        let status = response.status().as_u16();
+        let request_id = response.headers()[REQUEST_ID_HEADER].to_str().unwrap().to_string();
+        (status, request_id, response.json().await.unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_responses_carry_the_request_id() {
+        let base = serve().await;
+
+        let (status, request_id, body) = call(format!("{}/ok", base)).await;
+        assert_eq!(status, 200);
+        assert!(body.success);
+        assert_eq!(body.data.unwrap()["answer"], 42);
+        assert_eq!(body.request_id, Some(request_id));
+
+        let (status, request_id, body) = call(format!("{}/missing", base)).await;
+        assert_eq!(status, 404);
+        assert!(!body.success);
+        assert_eq!(body.error.as_deref(), Some("Session not found"));
+        assert_eq!(body.error_code, Some(ErrorCode::NotFound));
+        assert_eq!(body.request_id, Some(request_id));
+    }
+}
@@ -0,0 +1,272 @@
+// Ephemeral profile: the whole stack in one process for integration tests and
+// demos. Storage is in memory, and the chat, embedding and audio providers are
+// replaced by a fake OpenAI-compatible server on a local port: embeddings are
+// hashed from the text, chat replies are scripted from fixture files, and
+// voice calls return empty results. Nothing outlives the process.
+use anyhow::{Context, Result};
+use async_openai::config::OpenAIConfig;
+use axum::{
+    extract::State,
+    routing::{get, post},
+    Json, Router,
+};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+pub const DEFAULT_FIXTURES_DIR: &str = "./tests/fixtures/ephemeral";
+const DEFAULT_REPLY: &str = "This is a scripted reply; no fixture matched the message.";
+const DEFAULT_EMBEDDING_DIMENSION: usize = 1536;
+
+// `--ephemeral` or RUSTY_AI_PROFILE=ephemeral
+pub fn requested(args: &[String]) -> bool {
+    args.iter().any(|a| a == "--ephemeral")
+        || std::env::var("RUSTY_AI_PROFILE").map(|p| p == "ephemeral").unwrap_or(false)
+}
+
+// One canned chat reply, used when the last user message contains every
+// `when` keyword (case-insensitive)
+#[derive(Debug, Clone, Deserialize)]
+pub struct ScriptedReply {
+    #[serde(default)]
+    pub when: Vec<String>,
+    pub reply: String,
+}
+
+impl ScriptedReply {
+    fn matches(&self, message: &str) -> bool {
+        let message = message.to_lowercase();
+        self.when.iter().all(|keyword| message.contains(&keyword.to_lowercase()))
+    }
+}
+
+// Replies loaded from `<fixtures>/chat/*.json`, tried in file name order
+#[derive(Debug, Default)]
+pub struct ScriptedChat {
+    replies: Vec<ScriptedReply>,
+}
+
+impl ScriptedChat {
+    pub fn load(fixtures_dir: &Path) -> Result<Self> {
+        let dir = fixtures_dir.join("chat");
+        if !dir.is_dir() {
+            warn!("No chat fixtures in {}; every reply will be the default", dir.display());
+            return Ok(Self::default());
+        }
+
+        let mut files: Vec<PathBuf> = std::fs::read_dir(&dir)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.extension().map(|e| e == "json").unwrap_or(false))
+            .collect();
+        files.sort();
+
+        let mut replies = Vec::with_capacity(files.len());
+        for path in files {
+            let text = std::fs::read_to_string(&path)?;
+            let reply = serde_json::from_str(&text)
+                .with_context(|| format!("Invalid chat fixture {}", path.display()))?;
+            replies.push(reply);
+        }
+        Ok(Self { replies })
+    }
+
+    pub fn reply(&self, message: &str) -> &str {
+        self.replies
+            .iter()
+            .find(|r| r.matches(message))
+            .map(|r| r.reply.as_str())
+            .unwrap_or(DEFAULT_REPLY)
+    }
+}
+
+// A bag-of-words vector: each word of two or more letters is hashed to a
+// signed dimension, and the result is L2-normalized. Texts sharing words get
+// a positive cosine similarity, and the same text always gets the same vector.
+pub fn hash_embedding(text: &str, dimension: usize) -> Vec<f32> {
+    let mut vector = vec![0.0f32; dimension];
+    for word in text.to_lowercase().split(|c: char| !c.is_ascii_lowercase()) {
+        if word.len() < 2 {
+            continue;
+        }
+        let hash = fnv1a(word.as_bytes());
+        let sign = if hash >> 63 == 0 { 1.0 } else { -1.0 };
+        vector[(hash % dimension as u64) as usize] += sign;
+    }
+
+    let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|x| *x /= norm);
+    }
+    vector
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+// The fake provider and the scratch directory of one ephemeral run; both go
+// away when this is dropped
+pub struct EphemeralStack {
+    api_base: String,
+    data_dir: PathBuf,
+    server: JoinHandle<()>,
+}
+
+impl EphemeralStack {
+    // Fixtures come from EPHEMERAL_FIXTURES, ./tests/fixtures/ephemeral by default
+    pub async fn from_env() -> Result<Self> {
+        let fixtures = std::env::var("EPHEMERAL_FIXTURES").unwrap_or_else(|_| DEFAULT_FIXTURES_DIR.to_string());
+        Self::start(Path::new(&fixtures)).await
+    }
+
+    pub async fn start(fixtures_dir: &Path) -> Result<Self> {
+        let chat = Arc::new(ScriptedChat::load(fixtures_dir)?);
+        let data_dir = std::env::temp_dir().join(format!("rusty-ai-ephemeral-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&data_dir).context("Failed to create ephemeral data directory")?;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let api_base = format!("http://{}", listener.local_addr()?);
+        let app = fake_provider(chat);
+        let server = tokio::spawn(async move {
+            if let Err(e) = axum::serve(listener, app).await {
+                warn!("Fake provider stopped: {}", e);
+            }
+        });
+
+        info!("Ephemeral profile: fake providers at {}, scratch data in {}", api_base, data_dir.display());
+        Ok(Self { api_base, data_dir, server })
+    }
+
+    // Client config for the chat, embedding and audio services
+    pub fn openai_config(&self) -> OpenAIConfig {
+        OpenAIConfig::new().with_api_base(&self.api_base).with_api_key("ephemeral")
+    }
+
+    // One shared in-memory database per store
+    pub fn database_url(&self) -> &'static str {
+        "sqlite::memory:"
+    }
+
+    // Scratch space for uploads, crawl state, media and startup state
+    pub fn data_dir(&self) -> &Path {
+        &self.data_dir
+    }
+}
+
+impl Drop for EphemeralStack {
+    fn drop(&mut self) {
+        self.server.abort();
+        let _ = std::fs::remove_dir_all(&self.data_dir);
+    }
+}
+
+fn fake_provider(chat: Arc<ScriptedChat>) -> Router {
+    Router::new()
+        .route("/chat/completions", post(fake_chat_completion))
+        .route("/embeddings", post(fake_embeddings))
+        .route("/audio/transcriptions", post(|| async { Json(serde_json::json!({"text": ""})) }))
+        .route("/audio/speech", post(|| async { Vec::<u8>::new() }))
+        .route("/models", get(|| async { Json(serde_json::json!({"object": "list", "data": []})) }))
+        .with_state(chat)
+}
+
+async fn fake_chat_completion(
+    State(chat): State<Arc<ScriptedChat>>,
+    Json(request): Json<serde_json::Value>,
+) -> Json<serde_json::Value> {
+    let message = request["messages"]
+        .as_array()
+        .and_then(|messages| messages.iter().rev().find(|m| m["role"] == "user"))
+        .and_then(|m| m["content"].as_str())
+        .unwrap_or_default();
+    let reply = chat.reply(message);
+    let prompt_tokens = message.split_whitespace().count();
+    let completion_tokens = reply.split_whitespace().count();
+
+    Json(serde_json::json!({
+        "id": format!("chatcmpl-{}", uuid::Uuid::new_v4()),
+        "object": "chat.completion",
+        "created": chrono::Utc::now().timestamp(),
+        "model": request["model"].as_str().unwrap_or("ephemeral"),
+        "choices": [{
+            "index": 0,
+            "message": {"role": "assistant", "content": reply},
+            "finish_reason": "stop"
+        }],
+        "usage": {
+            "prompt_tokens": prompt_tokens,
+            "completion_tokens": completion_tokens,
+            "total_tokens": prompt_tokens + completion_tokens
+        }
+    }))
+}
+
+async fn fake_embeddings(Json(request): Json<serde_json::Value>) -> Json<serde_json::Value> {
+    let inputs: Vec<String> = match &request["input"] {
+        serde_json::Value::String(text) => vec![text.clone()],
+        serde_json::Value::Array(items) => items.iter().filter_map(|i| i.as_str().map(str::to_string)).collect(),
+        _ => Vec::new(),
+    };
+    let dimension = request["dimensions"].as_u64().map(|d| d as usize).unwrap_or(DEFAULT_EMBEDDING_DIMENSION);
+    let tokens: usize = inputs.iter().map(|i| i.split_whitespace().count()).sum();
+
+    let data: Vec<serde_json::Value> = inputs
+        .iter()
+        .enumerate()
+        .map(|(index, text)| serde_json::json!({
+            "index": index,
+            "object": "embedding",
+            "embedding": hash_embedding(text, dimension),
+        }))
+        .collect();
+
+    Json(serde_json::json!({
+        "object": "list",
+        "model": request["model"].as_str().unwrap_or("ephemeral"),
+        "data": data,
+        "usage": {"prompt_tokens": tokens, "total_tokens": tokens}
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cosine(a: &[f32], b: &[f32]) -> f32 {
+        a.iter().zip(b).map(|(x, y)| x * y).sum()
+    }
+
+    #[test]
+    fn test_hash_embedding_is_deterministic_and_normalized() {
+        let a = hash_embedding("The office wifi password", 1536);
+        assert_eq!(a, hash_embedding("the OFFICE wifi password!", 1536));
+        assert!((cosine(&a, &a) - 1.0).abs() < 1e-5);
+        assert!(hash_embedding("", 8).iter().all(|x| *x == 0.0));
+
+        let related = hash_embedding("What is the wifi password?", 1536);
+        let unrelated = hash_embedding("Quarterly budget review", 1536);
+        assert!(cosine(&a, &related) > 0.4);
+        assert!(cosine(&a, &related) > cosine(&a, &unrelated));
+    }
+
+    #[test]
+    fn test_scripted_chat_uses_first_matching_fixture() {
+        let dir = std::env::temp_dir().join(format!("rusty-ai-fixtures-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("chat")).unwrap();
+        std::fs::write(dir.join("chat/10-wifi.json"), r#"{"when": ["wifi", "Password"], "reply": "It is on the wall."}"#).unwrap();
+        std::fs::write(dir.join("chat/20-any-wifi.json"), r#"{"when": ["wifi"], "reply": "Ask IT."}"#).unwrap();
+        std::fs::write(dir.join("chat/notes.txt"), "not a fixture").unwrap();
+
+        let chat = ScriptedChat::load(&dir).unwrap();
+        assert_eq!(chat.reply("what is the WiFi password?"), "It is on the wall.");
+        assert_eq!(chat.reply("is the wifi down?"), "Ask IT.");
+        assert_eq!(chat.reply("hello"), DEFAULT_REPLY);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(ScriptedChat::load(&dir).unwrap().reply("wifi"), DEFAULT_REPLY);
+    }
+}
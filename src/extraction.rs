@@ -0,0 +1,743 @@
+// Structured extraction from the knowledge base. The caller gives a JSON
+// schema and an instruction; the chunks most relevant to the instruction
+// are retrieved and the model fills the schema from them in JSON mode.
+// Like summaries, large result sets go map-reduce style: each group of
+// chunks yields a partial object and the partials are merged into one.
+// Every answer is checked against the schema and asked for again once,
+// with the errors, when it does not match. Served at
+// /api/v1/knowledge/extract; extractions that need more than one call run
+// as jobs polled at /api/v1/knowledge/extract/:id.
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tracing::{debug, warn};
+
+use crate::ai_service::{AIService, ChatReply};
+use crate::data_residency::WithheldSource;
+use crate::knowledge_service_simple::DocumentMatch;
+use crate::query_metrics::TokenUsage;
+use crate::summarization::batch_by_tokens;
+
+// Usage of extraction calls is recorded under this feature
+pub const EXTRACTION_FEATURE: &str = "extraction";
+
+const DEFAULT_MAP_INPUT_TOKENS: usize = 6000;
+const DEFAULT_MAX_CHUNKS: usize = 40;
+const DEFAULT_SCORE_THRESHOLD: f32 = 0.3;
+const EXCERPT_CHARS: usize = 160;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ExtractionRequest {
+    // JSON schema the result must match
+    pub schema: Value,
+    pub instruction: String,
+    // Only chunks carrying at least one of these tags
+    #[serde(default)]
+    pub tags: Vec<String>,
+    // Chunks retrieved; the configured maximum when absent
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+// A retrieved chunk a field's value came from
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FieldSource {
+    pub document_id: String,
+    pub title: String,
+    pub chunk_index: usize,
+    pub excerpt: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Extraction {
+    pub data: Value,
+    // Sources per field path as the model reported them, e.g. "tenant" or
+    // "payments[0].amount"
+    pub citations: BTreeMap<String, Vec<FieldSource>>,
+    pub chunks_used: usize,
+    pub model_calls: usize,
+    // Answers that did not match the schema and were asked for again
+    pub retries: usize,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    // Retrieved chunks the residency policy keeps from the chat provider
+    pub withheld: Vec<WithheldSource>,
+}
+
+// The model kept answering with data that does not match the schema
+#[derive(Debug, Clone, PartialEq)]
+pub struct SchemaMismatch(pub Vec<String>);
+
+impl std::fmt::Display for SchemaMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "the model's answer does not match the schema: {}", self.0.join("; "))
+    }
+}
+
+impl std::error::Error for SchemaMismatch {}
+
+#[derive(Debug, Clone)]
+pub struct ExtractionConfig {
+    // Estimated tokens of chunk text sent in one call
+    pub map_input_tokens: usize,
+    pub max_chunks: usize,
+    pub score_threshold: f32,
+}
+
+impl Default for ExtractionConfig {
+    fn default() -> Self {
+        Self {
+            map_input_tokens: DEFAULT_MAP_INPUT_TOKENS,
+            max_chunks: DEFAULT_MAX_CHUNKS,
+            score_threshold: DEFAULT_SCORE_THRESHOLD,
+        }
+    }
+}
+
+impl ExtractionConfig {
+    // EXTRACTION_MAP_INPUT_TOKENS, EXTRACTION_MAX_CHUNKS and
+    // EXTRACTION_SCORE_THRESHOLD
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        let var = |key: &str| std::env::var(key).ok();
+        Self {
+            map_input_tokens: var("EXTRACTION_MAP_INPUT_TOKENS")
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(defaults.map_input_tokens),
+            max_chunks: var("EXTRACTION_MAX_CHUNKS").and_then(|v| v.parse().ok()).unwrap_or(defaults.max_chunks),
+            score_threshold: var("EXTRACTION_SCORE_THRESHOLD")
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(defaults.score_threshold),
+        }
+    }
+}
+
+// The model calls an extraction makes; the chat service in JSON mode in
+// production, a mock in tests
+pub trait ExtractionModel {
+    fn complete_json(&self, system_prompt: &str, prompt: &str) -> impl Future<Output = Result<ChatReply>> + Send;
+}
+
+impl ExtractionModel for AIService {
+    async fn complete_json(&self, system_prompt: &str, prompt: &str) -> Result<ChatReply> {
+        AIService::complete_json(self, system_prompt, prompt).await
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum JobState {
+    Queued,
+    Running,
+    Done { result: Box<Extraction> },
+    Failed { error: String },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExtractionJob {
+    pub id: String,
+    pub instruction: String,
+    pub status: JobState,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+pub struct Extractor {
+    config: ExtractionConfig,
+    usage: Arc<TokenUsage>,
+    // Extractions run in the background since startup
+    jobs: RwLock<HashMap<String, ExtractionJob>>,
+}
+
+impl Extractor {
+    pub fn new(config: ExtractionConfig, usage: Arc<TokenUsage>) -> Self {
+        Self { config, usage, jobs: RwLock::new(HashMap::new()) }
+    }
+
+    pub fn config(&self) -> &ExtractionConfig {
+        &self.config
+    }
+
+    // More than one call's worth of chunks goes to the job queue
+    pub fn needs_job(&self, chunks: &[DocumentMatch]) -> bool {
+        batch_by_tokens(sections(chunks), self.config.map_input_tokens).len() > 1
+    }
+
+    // Fill `schema` from `chunks`, in the order they were retrieved
+    pub async fn extract<M: ExtractionModel>(
+        &self,
+        model: &M,
+        schema: &Value,
+        instruction: &str,
+        chunks: &[DocumentMatch],
+    ) -> Result<Extraction> {
+        if chunks.is_empty() {
+            anyhow::bail!("Nothing to extract from");
+        }
+        let mut run = Run { model, usage: &self.usage, schema, calls: 0, retries: 0, prompt_tokens: 0, completion_tokens: 0 };
+        let batches = batch_by_tokens(sections(chunks), self.config.map_input_tokens);
+        let answer = if batches.len() == 1 {
+            run.call(&final_instructions(schema, instruction), &batches[0], Check::Complete).await?
+        } else {
+            // Map: a partial object per group of chunks. Required fields may
+            // only be found in other groups, so they are not checked yet
+            let mut partials = Vec::with_capacity(batches.len());
+            for batch in &batches {
+                let partial = run.call(&map_instructions(schema, instruction), batch, Check::Partial).await?;
+                partials.push(partial.to_string());
+            }
+            // Reduce: merge partials until they fit one call, then merge
+            // those into the final object
+            let mut groups = batch_by_tokens(partials, self.config.map_input_tokens);
+            while groups.len() > 1 {
+                let mut merged = Vec::with_capacity(groups.len());
+                for group in &groups {
+                    merged.push(run.call(&merge_instructions(schema, instruction), group, Check::Partial).await?.to_string());
+                }
+                groups = batch_by_tokens(merged, self.config.map_input_tokens);
+            }
+            run.call(&merge_instructions(schema, instruction), &groups[0], Check::Complete).await?
+        };
+
+        Ok(Extraction {
+            citations: citations(answer.get("sources"), chunks),
+            data: answer.get("data").cloned().unwrap_or(Value::Null),
+            chunks_used: chunks.len(),
+            model_calls: run.calls,
+            retries: run.retries,
+            prompt_tokens: run.prompt_tokens,
+            completion_tokens: run.completion_tokens,
+            withheld: Vec::new(),
+        })
+    }
+
+    pub async fn enqueue(&self, instruction: &str) -> ExtractionJob {
+        let now = Utc::now();
+        let job = ExtractionJob {
+            id: uuid::Uuid::new_v4().to_string(),
+            instruction: instruction.to_string(),
+            status: JobState::Queued,
+            created_at: now,
+            updated_at: now,
+        };
+        self.jobs.write().await.insert(job.id.clone(), job.clone());
+        job
+    }
+
+    pub async fn job(&self, id: &str) -> Option<ExtractionJob> {
+        self.jobs.read().await.get(id).cloned()
+    }
+
+    // Run a queued job to its end; the outcome is kept for the results endpoint
+    pub async fn run_job<M: ExtractionModel>(
+        &self,
+        id: &str,
+        model: &M,
+        request: &ExtractionRequest,
+        chunks: &[DocumentMatch],
+        withheld: Vec<WithheldSource>,
+    ) {
+        self.set_state(id, JobState::Running).await;
+        let state = match self.extract(model, &request.schema, &request.instruction, chunks).await {
+            Ok(extraction) => JobState::Done { result: Box::new(Extraction { withheld, ..extraction }) },
+            Err(e) => {
+                warn!("Extraction {} failed: {}", id, e);
+                JobState::Failed { error: e.to_string() }
+            }
+        };
+        self.set_state(id, state).await;
+    }
+
+    async fn set_state(&self, id: &str, state: JobState) {
+        if let Some(job) = self.jobs.write().await.get_mut(id) {
+            debug!("Extraction {} is now {:?}", id, state);
+            job.status = state;
+            job.updated_at = Utc::now();
+        }
+    }
+}
+
+// How much of the schema an answer must satisfy
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Check {
+    // A merge step: fields may still be missing
+    Partial,
+    Complete,
+}
+
+// The calls of one extraction and what they used
+struct Run<'a, M> {
+    model: &'a M,
+    usage: &'a TokenUsage,
+    schema: &'a Value,
+    calls: usize,
+    retries: usize,
+    prompt_tokens: u64,
+    completion_tokens: u64,
+}
+
+impl<M: ExtractionModel> Run<'_, M> {
+    // One answer checked against the schema; a mismatch is asked for again
+    // once with the errors, and a second mismatch fails the extraction
+    async fn call(&mut self, instructions: &str, text: &str, check: Check) -> Result<Value> {
+        let errors = match self.answer(instructions, text, check).await? {
+            Ok(answer) => return Ok(answer),
+            Err(errors) => errors,
+        };
+        debug!("Extraction answer did not match the schema: {:?}", errors);
+        self.retries += 1;
+        let retry = format!(
+            "{}\n\nYour previous answer was rejected because it does not match the schema:\n- {}\nAnswer again \
+             with corrected JSON.",
+            text,
+            errors.join("\n- ")
+        );
+        match self.answer(instructions, &retry, check).await? {
+            Ok(answer) => Ok(answer),
+            Err(errors) => Err(SchemaMismatch(errors).into()),
+        }
+    }
+
+    async fn answer(&mut self, instructions: &str, text: &str, check: Check) -> Result<Result<Value, Vec<String>>> {
+        let reply = self.model.complete_json(instructions, text).await?;
+        self.usage.record(EXTRACTION_FEATURE, reply.prompt_tokens, reply.completion_tokens);
+        self.calls += 1;
+        self.prompt_tokens += u64::from(reply.prompt_tokens.unwrap_or(0));
+        self.completion_tokens += u64::from(reply.completion_tokens.unwrap_or(0));
+        Ok(check_answer(&reply.text, self.schema, check))
+    }
+}
+
+// The answer's {"data": ..., "sources": ...} envelope, with `data` validated
+fn check_answer(text: &str, schema: &Value, check: Check) -> Result<Value, Vec<String>> {
+    let answer: Value = serde_json::from_str(strip_fences(text))
+        .map_err(|e| vec![format!("the answer is not valid JSON: {}", e)])?;
+    let Some(data) = answer.get("data") else {
+        return Err(vec!["the answer has no \"data\" field".to_string()]);
+    };
+    let mut errors = Vec::new();
+    validate(data, schema, "$", check == Check::Partial, &mut errors);
+    if let Some(sources) = answer.get("sources").filter(|s| !s.is_object()) {
+        errors.push(format!("\"sources\" must be an object of field paths to section numbers, got {}", type_name(sources)));
+    }
+    if errors.is_empty() {
+        Ok(answer)
+    } else {
+        Err(errors)
+    }
+}
+
+// Models sometimes wrap JSON in a markdown code block even in JSON mode
+fn strip_fences(text: &str) -> &str {
+    let text = text.trim();
+    match text.strip_prefix("```") {
+        Some(rest) => {
+            let rest = rest.strip_prefix("json").unwrap_or(rest);
+            rest.strip_suffix("```").unwrap_or(rest).trim()
+        }
+        None => text,
+    }
+}
+
+// Errors in a schema the extraction could not check answers against
+pub fn check_schema(schema: &Value) -> Result<(), String> {
+    let Some(object) = schema.as_object() else {
+        return Err("The schema must be a JSON object".to_string());
+    };
+    if let Some(kind) = object.get("type") {
+        let names: Vec<&Value> = match kind {
+            Value::Array(names) => names.iter().collect(),
+            name => vec![name],
+        };
+        for name in names {
+            if !name.as_str().is_some_and(|name| TYPE_NAMES.contains(&name)) {
+                return Err(format!("Unknown schema type {}", name));
+            }
+        }
+    }
+    for property in object.get("properties").and_then(Value::as_object).into_iter().flat_map(|p| p.values()) {
+        check_schema(property)?;
+    }
+    if let Some(items) = object.get("items") {
+        check_schema(items)?;
+    }
+    Ok(())
+}
+
+const TYPE_NAMES: [&str; 7] = ["object", "array", "string", "number", "integer", "boolean", "null"];
+
+// The subset of JSON schema extractions use: type, properties, required,
+// additionalProperties: false, items and enum. Errors name the path, e.g.
+// "$.payments[1].amount: expected number, got string"
+pub fn validate(value: &Value, schema: &Value, path: &str, partial: bool, errors: &mut Vec<String>) {
+    if let Some(kind) = schema.get("type") {
+        let allowed: Vec<&str> = match kind {
+            Value::Array(names) => names.iter().filter_map(Value::as_str).collect(),
+            name => name.as_str().into_iter().collect(),
+        };
+        if !allowed.iter().any(|name| is_type(value, name)) {
+            errors.push(format!("{}: expected {}, got {}", path, allowed.join(" or "), type_name(value)));
+            return;
+        }
+    }
+    if let Some(options) = schema.get("enum").and_then(Value::as_array) {
+        if !options.contains(value) {
+            errors.push(format!("{}: {} is not one of {}", path, value, Value::Array(options.clone())));
+        }
+    }
+
+    match value {
+        Value::Object(object) => {
+            let properties = schema.get("properties").and_then(Value::as_object);
+            if !partial {
+                for name in schema.get("required").and_then(Value::as_array).into_iter().flatten().filter_map(Value::as_str) {
+                    if !object.contains_key(name) {
+                        errors.push(format!("{}: missing required field \"{}\"", path, name));
+                    }
+                }
+            }
+            let closed = schema.get("additionalProperties") == Some(&Value::Bool(false));
+            for (name, field) in object {
+                match properties.and_then(|p| p.get(name)) {
+                    Some(field_schema) => validate(field, field_schema, &format!("{}.{}", path, name), partial, errors),
+                    None if closed => errors.push(format!("{}: unexpected field \"{}\"", path, name)),
+                    None => {}
+                }
+            }
+        }
+        Value::Array(items) => {
+            if let Some(item_schema) = schema.get("items") {
+                for (index, item) in items.iter().enumerate() {
+                    validate(item, item_schema, &format!("{}[{}]", path, index), partial, errors);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+fn is_type(value: &Value, name: &str) -> bool {
+    match name {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        "integer" => value.is_i64() || value.is_u64() || value.as_f64().is_some_and(|n| n.fract() == 0.0),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        _ => false,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+// Chunks are numbered from 1 in retrieval order so the model can cite them
+fn sections(chunks: &[DocumentMatch]) -> Vec<String> {
+    chunks
+        .iter()
+        .enumerate()
+        .map(|(index, chunk)| format!("[{}] ({}) {}", index + 1, chunk.title, chunk.content.trim()))
+        .collect()
+}
+
+fn answer_format(schema: &Value) -> String {
+    format!(
+        "Respond with a JSON object of the form {{\"data\": <value matching the schema>, \"sources\": {{<field path>: \
+         [<section numbers>]}}}}, where field paths look like \"name\" or \"items[0].price\". The schema is:\n{}",
+        schema
+    )
+}
+
+fn final_instructions(schema: &Value, instruction: &str) -> String {
+    format!(
+        "Extract structured data from the numbered sections below. Task: {}\nUse only facts stated in the \
+         sections; use null for a value they do not give. {}",
+        instruction.trim(),
+        answer_format(schema)
+    )
+}
+
+fn map_instructions(schema: &Value, instruction: &str) -> String {
+    format!(
+        "You are extracting structured data from part of a larger set of numbered sections; other parts are \
+         handled separately. Task: {}\nFill in only what these sections state and leave out fields they do not \
+         mention. {}",
+        instruction.trim(),
+        answer_format(schema)
+    )
+}
+
+fn merge_instructions(schema: &Value, instruction: &str) -> String {
+    format!(
+        "Below are partial extraction results, one JSON object per part of the source material. Merge them into \
+         one result for the task: {}\nCombine list items, prefer the more specific value when parts disagree, and \
+         keep the section numbers of every value you keep. {}",
+        instruction.trim(),
+        answer_format(schema)
+    )
+}
+
+// The sections each field cites; numbers outside the retrieved set are dropped
+fn citations(sources: Option<&Value>, chunks: &[DocumentMatch]) -> BTreeMap<String, Vec<FieldSource>> {
+    let Some(sources) = sources.and_then(Value::as_object) else {
+        return BTreeMap::new();
+    };
+    sources
+        .iter()
+        .map(|(field, numbers)| {
+            let mut cited: Vec<usize> = Vec::new();
+            for number in numbers.as_array().into_iter().flatten().filter_map(Value::as_u64) {
+                let index = number as usize;
+                if index >= 1 && index <= chunks.len() && !cited.contains(&(index - 1)) {
+                    cited.push(index - 1);
+                }
+            }
+            let sources = cited
+                .into_iter()
+                .map(|index| {
+                    let chunk = &chunks[index];
+                    FieldSource {
+                        document_id: chunk.id.clone(),
+                        title: chunk.title.clone(),
+                        chunk_index: chunk.chunk_index,
+                        excerpt: excerpt(&chunk.content),
+                    }
+                })
+                .collect();
+            (field.clone(), sources)
+        })
+        .collect()
+}
+
+fn excerpt(content: &str) -> String {
+    let content = content.trim();
+    match content.char_indices().nth(EXCERPT_CHARS) {
+        Some((end, _)) => format!("{}…", &content[..end]),
+        None => content.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trust::TrustLevel;
+    use serde_json::json;
+    use std::collections::VecDeque;
+    use std::sync::Mutex;
+
+    // Answers with the scripted replies in order and records every call
+    #[derive(Default)]
+    struct ScriptedModel {
+        replies: Mutex<VecDeque<String>>,
+        calls: Mutex<Vec<(String, String)>>,
+    }
+
+    impl ScriptedModel {
+        fn new(replies: &[Value]) -> Self {
+            Self { replies: Mutex::new(replies.iter().map(Value::to_string).collect()), ..Self::default() }
+        }
+
+        fn calls(&self) -> Vec<(String, String)> {
+            self.calls.lock().unwrap().clone()
+        }
+    }
+
+    impl ExtractionModel for ScriptedModel {
+        async fn complete_json(&self, system_prompt: &str, prompt: &str) -> Result<ChatReply> {
+            self.calls.lock().unwrap().push((system_prompt.to_string(), prompt.to_string()));
+            let text = self.replies.lock().unwrap().pop_front().expect("no scripted reply left");
+            Ok(ChatReply {
+                text,
+                model: "mock".to_string(),
+                prompt_tokens: Some(100),
+                completion_tokens: Some(20),
+                fallback: false,
+            })
+        }
+    }
+
+    fn chunk(title: &str, chunk_index: usize, content: &str) -> DocumentMatch {
+        DocumentMatch {
+            id: title.to_lowercase().replace(' ', "-"),
+            title: title.to_string(),
+            content: content.to_string(),
+            score: 0.9,
+            chunk_index,
+            source: "upload".to_string(),
+            tags: vec![],
+            trust_level: TrustLevel::default(),
+            note: None,
+            manually_corrected: false,
+            page: None,
+        }
+    }
+
+    fn lease_schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "tenant": { "type": "string" },
+                "monthly_rent": { "type": "number" },
+                "payments": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": { "month": { "type": "string" }, "amount": { "type": "number" } },
+                        "required": ["month", "amount"]
+                    }
+                }
+            },
+            "required": ["tenant", "monthly_rent"],
+            "additionalProperties": false
+        })
+    }
+
+    fn extractor(map_input_tokens: usize) -> Extractor {
+        let config = ExtractionConfig { map_input_tokens, ..ExtractionConfig::default() };
+        Extractor::new(config, Arc::new(TokenUsage::new()))
+    }
+
+    #[tokio::test]
+    async fn test_invalid_answer_is_retried_once_with_the_errors() {
+        let chunks = vec![
+            chunk("Lease", 0, "The tenant is Ada Lovelace."),
+            chunk("Lease", 3, "Rent is 950 euros a month."),
+        ];
+        let model = ScriptedModel::new(&[
+            json!({ "data": { "tenant": "Ada Lovelace", "monthly_rent": "950 euros" } }),
+            json!({
+                "data": { "tenant": "Ada Lovelace", "monthly_rent": 950 },
+                "sources": { "tenant": [1], "monthly_rent": [2, 7] }
+            }),
+        ]);
+        let extractor = extractor(6000);
+
+        let extraction = extractor.extract(&model, &lease_schema(), "Who rents and for how much?", &chunks).await.unwrap();
+
+        let calls = model.calls();
+        assert_eq!(calls.len(), 2);
+        assert!(calls[0].0.starts_with("Extract structured data") && calls[0].0.contains("\"monthly_rent\""));
+        assert!(calls[0].1.starts_with("[1] (Lease) The tenant") && calls[0].1.contains("[2] (Lease) Rent"));
+        // The retry repeats the sections and says what was wrong
+        assert!(calls[1].1.starts_with(&calls[0].1));
+        assert!(calls[1].1.contains("$.monthly_rent: expected number, got string"));
+
+        assert_eq!(extraction.data, json!({ "tenant": "Ada Lovelace", "monthly_rent": 950 }));
+        assert_eq!(extraction.retries, 1);
+        assert_eq!(extraction.model_calls, 2);
+        assert_eq!(extraction.prompt_tokens, 200);
+        assert_eq!(extractor.usage.get(EXTRACTION_FEATURE).calls, 2);
+        // Section 7 was not retrieved, so only section 2 backs the rent
+        assert_eq!(extraction.citations["tenant"][0].excerpt, "The tenant is Ada Lovelace.");
+        let rent: Vec<usize> = extraction.citations["monthly_rent"].iter().map(|s| s.chunk_index).collect();
+        assert_eq!(rent, vec![3]);
+    }
+
+    #[tokio::test]
+    async fn test_second_invalid_answer_fails_the_extraction() {
+        let chunks = vec![chunk("Lease", 0, "The tenant is Ada Lovelace.")];
+        let model = ScriptedModel::new(&[
+            json!({ "data": { "tenant": "Ada Lovelace" } }),
+            json!({ "data": { "tenant": "Ada Lovelace", "monthly_rent": 950, "deposit": 1900 } }),
+        ]);
+
+        let error = extractor(6000).extract(&model, &lease_schema(), "Who rents?", &chunks).await.unwrap_err();
+
+        assert!(model.calls()[1].1.contains("$: missing required field \"monthly_rent\""));
+        let mismatch = error.downcast_ref::<SchemaMismatch>().expect("a schema mismatch");
+        assert_eq!(mismatch.0, vec!["$: unexpected field \"deposit\"".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_large_result_sets_run_as_jobs_and_are_merged() {
+        // Each chunk is ~250 tokens, so each one is a call of its own
+        let filler = "word ".repeat(200);
+        let chunks = vec![
+            chunk("Lease", 0, &format!("The tenant is Ada Lovelace. {}", filler)),
+            chunk("Ledger", 0, &format!("March rent of 950 was paid. {}", filler)),
+        ];
+        // Partial answers may leave out required fields; the merged one may not
+        let model = ScriptedModel::new(&[
+            json!({ "data": { "tenant": "Ada Lovelace" }, "sources": { "tenant": [1] } }),
+            json!({ "data": { "payments": [{ "month": "March", "amount": 950 }] }, "sources": { "payments[0].amount": [2] } }),
+            json!({ "data": { "tenant": "Ada Lovelace", "payments": [{ "month": "March", "amount": 950 }] } }),
+            json!({
+                "data": { "tenant": "Ada Lovelace", "monthly_rent": 950, "payments": [{ "month": "March", "amount": 950 }] },
+                "sources": { "tenant": [1], "payments[0].amount": [2] }
+            }),
+        ]);
+        let extractor = extractor(300);
+        assert!(extractor.needs_job(&chunks));
+        assert!(!extractor.needs_job(&chunks[..1]));
+
+        let request = ExtractionRequest {
+            schema: lease_schema(),
+            instruction: "Rent and payments".to_string(),
+            tags: vec![],
+            limit: None,
+        };
+        let job = extractor.enqueue(&request.instruction).await;
+        assert!(matches!(job.status, JobState::Queued));
+        extractor.run_job(&job.id, &model, &request, &chunks, vec![]).await;
+
+        let calls = model.calls();
+        assert_eq!(calls.len(), 4);
+        assert!(calls[0].0.starts_with("You are extracting") && calls[0].1.starts_with("[1] "));
+        assert!(calls[1].1.starts_with("[2] "));
+        assert!(calls[2].0.starts_with("Below are partial") && calls[2].1.contains("\"payments\""));
+        assert!(calls[3].1.contains("missing required field \"monthly_rent\""));
+
+        let Some(ExtractionJob { status: JobState::Done { result }, .. }) = extractor.job(&job.id).await else {
+            panic!("the job did not finish");
+        };
+        assert_eq!(result.data["monthly_rent"], json!(950));
+        assert_eq!(result.retries, 1);
+        assert_eq!(result.citations["payments[0].amount"][0].title, "Ledger");
+    }
+
+    #[test]
+    fn test_validation_reports_every_mismatch_by_path() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "status": { "enum": ["active", "ended"] },
+                "tags": { "type": "array", "items": { "type": "string" } },
+                "rooms": { "type": ["integer", "null"] }
+            },
+            "required": ["status"]
+        });
+        let mut errors = Vec::new();
+        validate(&json!({ "status": "paused", "tags": ["a", 2], "rooms": 2.5 }), &schema, "$", false, &mut errors);
+        assert_eq!(
+            errors,
+            vec![
+                "$.rooms: expected integer or null, got number".to_string(),
+                "$.status: \"paused\" is not one of [\"active\",\"ended\"]".to_string(),
+                "$.tags[1]: expected string, got number".to_string(),
+            ]
+        );
+
+        let mut errors = Vec::new();
+        validate(&json!({ "rooms": null }), &schema, "$", true, &mut errors);
+        assert!(errors.is_empty());
+
+        assert_eq!(strip_fences("```json\n{\"data\": 1}\n```"), "{\"data\": 1}");
+        assert!(check_schema(&json!({ "type": "object", "properties": { "a": { "type": "text" } } })).is_err());
+        assert!(check_schema(&json!("object")).is_err());
+        assert!(check_schema(&schema).is_ok());
+    }
+}
@@ -0,0 +1,287 @@
+use anyhow::{Context, Result};
+use qdrant_client::config::QdrantConfig;
+use qdrant_client::Qdrant;
+use reqwest::{Certificate, NoProxy, Proxy};
+use std::path::PathBuf;
+use std::time::Duration;
+
+// Sent by every outbound client except the crawler, which has its own for
+// robots.txt matching
+pub const USER_AGENT: &str = concat!("rusty-ai/", env!("CARGO_PKG_VERSION"));
+
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+// The external services the server talks to; each has its own request timeout
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClientKind {
+    OpenAi,
+    ElevenLabs,
+    Qdrant,
+    Webhook,
+    Crawler,
+}
+
+impl ClientKind {
+    fn env_name(&self) -> &'static str {
+        match self {
+            ClientKind::OpenAi => "OPENAI",
+            ClientKind::ElevenLabs => "ELEVENLABS",
+            ClientKind::Qdrant => "QDRANT",
+            ClientKind::Webhook => "WEBHOOK",
+            ClientKind::Crawler => "CRAWLER",
+        }
+    }
+
+    // Chat and speech responses are streamed, so they get the whole body's time
+    fn default_timeout(&self) -> Duration {
+        match self {
+            ClientKind::OpenAi | ClientKind::ElevenLabs => Duration::from_secs(120),
+            ClientKind::Crawler => Duration::from_secs(20),
+            ClientKind::Qdrant | ClientKind::Webhook => Duration::from_secs(10),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HttpClientConfig {
+    pub proxy_url: Option<String>,
+    // Comma-separated hosts, domains and IP ranges reached without the proxy
+    pub no_proxy: Option<String>,
+    // PEM files with root certificates trusted besides the system's, e.g. a
+    // corporate CA
+    pub ca_cert_paths: Vec<PathBuf>,
+    pub connect_timeout: Duration,
+    pub openai_timeout: Duration,
+    pub elevenlabs_timeout: Duration,
+    pub qdrant_timeout: Duration,
+    pub webhook_timeout: Duration,
+    pub crawler_timeout: Duration,
+}
+
+impl Default for HttpClientConfig {
+    fn default() -> Self {
+        Self {
+            proxy_url: None,
+            no_proxy: None,
+            ca_cert_paths: Vec::new(),
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            openai_timeout: ClientKind::OpenAi.default_timeout(),
+            elevenlabs_timeout: ClientKind::ElevenLabs.default_timeout(),
+            qdrant_timeout: ClientKind::Qdrant.default_timeout(),
+            webhook_timeout: ClientKind::Webhook.default_timeout(),
+            crawler_timeout: ClientKind::Crawler.default_timeout(),
+        }
+    }
+}
+
+impl HttpClientConfig {
+    // OUTBOUND_PROXY_URL (else HTTPS_PROXY / HTTP_PROXY), OUTBOUND_NO_PROXY
+    // (else NO_PROXY), OUTBOUND_CA_CERTS (comma-separated PEM paths),
+    // OUTBOUND_CONNECT_TIMEOUT_SECS and OUTBOUND_<SERVICE>_TIMEOUT_SECS for
+    // OPENAI, ELEVENLABS, QDRANT, WEBHOOK and CRAWLER
+    pub fn from_env() -> Self {
+        let var = |key: &str| std::env::var(key).ok().filter(|v| !v.trim().is_empty());
+        let first = |keys: &[&str]| keys.iter().find_map(|key| var(key));
+        let secs = |key: &str, default: Duration| {
+            var(key).and_then(|v| v.parse().ok()).map(Duration::from_secs).unwrap_or(default)
+        };
+        let timeout = |kind: ClientKind| {
+            secs(&format!("OUTBOUND_{}_TIMEOUT_SECS", kind.env_name()), kind.default_timeout())
+        };
+
+        Self {
+            proxy_url: first(&["OUTBOUND_PROXY_URL", "HTTPS_PROXY", "https_proxy", "HTTP_PROXY", "http_proxy"]),
+            no_proxy: first(&["OUTBOUND_NO_PROXY", "NO_PROXY", "no_proxy"]),
+            ca_cert_paths: var("OUTBOUND_CA_CERTS")
+                .map(|paths| paths.split(',').map(str::trim).filter(|p| !p.is_empty()).map(PathBuf::from).collect())
+                .unwrap_or_default(),
+            connect_timeout: secs("OUTBOUND_CONNECT_TIMEOUT_SECS", DEFAULT_CONNECT_TIMEOUT),
+            openai_timeout: timeout(ClientKind::OpenAi),
+            elevenlabs_timeout: timeout(ClientKind::ElevenLabs),
+            qdrant_timeout: timeout(ClientKind::Qdrant),
+            webhook_timeout: timeout(ClientKind::Webhook),
+            crawler_timeout: timeout(ClientKind::Crawler),
+        }
+    }
+
+    pub fn timeout(&self, kind: ClientKind) -> Duration {
+        match kind {
+            ClientKind::OpenAi => self.openai_timeout,
+            ClientKind::ElevenLabs => self.elevenlabs_timeout,
+            ClientKind::Qdrant => self.qdrant_timeout,
+            ClientKind::Webhook => self.webhook_timeout,
+            ClientKind::Crawler => self.crawler_timeout,
+        }
+    }
+}
+
+// Builds the clients of every outbound integration, so they all go through
+// the configured proxy and trust the configured CAs
+#[derive(Clone, Default)]
+pub struct HttpClientFactory {
+    config: HttpClientConfig,
+    root_certificates: Vec<Certificate>,
+}
+
+impl HttpClientFactory {
+    // Fails when a CA file cannot be read or the proxy URL is invalid
+    pub fn new(config: HttpClientConfig) -> Result<Self> {
+        let root_certificates = load_root_certificates(&config.ca_cert_paths)?;
+        if let Some(url) = &config.proxy_url {
+            Proxy::all(url.as_str()).with_context(|| format!("Invalid proxy URL {}", url))?;
+        }
+        Ok(Self { config, root_certificates })
+    }
+
+    pub fn from_env() -> Result<Self> {
+        Self::new(HttpClientConfig::from_env())
+    }
+
+    pub fn config(&self) -> &HttpClientConfig {
+        &self.config
+    }
+
+    // For callers that set more options before building
+    pub fn builder(&self, kind: ClientKind) -> Result<reqwest::ClientBuilder> {
+        let mut builder = reqwest::Client::builder()
+            .user_agent(USER_AGENT)
+            .connect_timeout(self.config.connect_timeout)
+            .timeout(self.config.timeout(kind));
+        if let Some(url) = &self.config.proxy_url {
+            let no_proxy = self.config.no_proxy.as_deref().and_then(NoProxy::from_string);
+            builder = builder.proxy(Proxy::all(url.as_str())?.no_proxy(no_proxy));
+        }
+        for certificate in &self.root_certificates {
+            builder = builder.add_root_certificate(certificate.clone());
+        }
+        Ok(builder)
+    }
+
+    pub fn client(&self, kind: ClientKind) -> Result<reqwest::Client> {
+        self.builder(kind)?.build().context("Failed to build HTTP client")
+    }
+
+    // The gRPC channel takes the timeouts only: it connects directly and
+    // trusts the system roots (or SSL_CERT_FILE) for https URLs
+    pub fn qdrant(&self, url: &str) -> QdrantConfig {
+        Qdrant::from_url(url)
+            .timeout(self.config.qdrant_timeout)
+            .connect_timeout(self.config.connect_timeout)
+    }
+}
+
+// Every certificate in each PEM file; a file without any is an error too
+pub fn load_root_certificates(paths: &[PathBuf]) -> Result<Vec<Certificate>> {
+    let mut certificates = Vec::new();
+    for path in paths {
+        let pem = std::fs::read(path).with_context(|| format!("Cannot read CA certificate {}", path.display()))?;
+        let bundle = Certificate::from_pem_bundle(&pem)
+            .with_context(|| format!("Invalid CA certificate {}", path.display()))?;
+        if bundle.is_empty() {
+            anyhow::bail!("No PEM certificates in {}", path.display());
+        }
+        certificates.extend(bundle);
+    }
+    Ok(certificates)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+    use std::sync::Arc;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio_rustls::rustls::pki_types::pem::PemObject;
+    use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+    use tokio_rustls::rustls::ServerConfig;
+    use tokio_rustls::TlsAcceptor;
+
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/tls").join(name)
+    }
+
+    // An HTTPS server on localhost whose certificate is signed by the test CA
+    // in tests/fixtures/tls; answers every request with its user agent
+    async fn tls_server() -> String {
+        let certs = CertificateDer::pem_file_iter(fixture("server.pem")).unwrap().collect::<Result<Vec<_>, _>>().unwrap();
+        let key = PrivateKeyDer::from_pem_file(fixture("server.key")).unwrap();
+        let config = ServerConfig::builder_with_provider(Arc::new(tokio_rustls::rustls::crypto::ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_no_client_auth()
+            .with_single_cert(certs, key)
+            .unwrap();
+        let acceptor = TlsAcceptor::from(Arc::new(config));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let acceptor = acceptor.clone();
+                tokio::spawn(async move {
+                    let Ok(mut stream) = acceptor.accept(stream).await else { return };
+                    let mut request = vec![0u8; 4096];
+                    let n = stream.read(&mut request).await.unwrap_or(0);
+                    let request = String::from_utf8_lossy(&request[..n]).to_lowercase();
+                    let agent = request
+                        .lines()
+                        .find_map(|line| line.strip_prefix("user-agent: "))
+                        .unwrap_or_default()
+                        .to_string();
+                    let response = format!("HTTP/1.1 200 OK\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}", agent.len(), agent);
+                    let _ = stream.write_all(response.as_bytes()).await;
+                    let _ = stream.shutdown().await;
+                });
+            }
+        });
+        format!("https://localhost:{}/", port)
+    }
+
+    #[tokio::test]
+    async fn test_connects_only_when_the_ca_is_configured() {
+        let url = tls_server().await;
+
+        let untrusted = HttpClientFactory::default().client(ClientKind::Webhook).unwrap();
+        assert!(untrusted.get(&url).send().await.is_err());
+
+        let factory = HttpClientFactory::new(HttpClientConfig {
+            ca_cert_paths: vec![fixture("ca.pem")],
+            ..HttpClientConfig::default()
+        })
+        .unwrap();
+        let response = factory.client(ClientKind::Webhook).unwrap().get(&url).send().await.unwrap();
+        assert!(response.status().is_success());
+        assert_eq!(response.text().await.unwrap(), USER_AGENT.to_lowercase());
+    }
+
+    #[tokio::test]
+    async fn test_no_proxy_hosts_bypass_the_proxy() {
+        let url = tls_server().await;
+        // Nothing listens on the proxy's port
+        let config = HttpClientConfig {
+            proxy_url: Some("http://127.0.0.1:9".to_string()),
+            ca_cert_paths: vec![fixture("ca.pem")],
+            ..HttpClientConfig::default()
+        };
+
+        let proxied = HttpClientFactory::new(config.clone()).unwrap().client(ClientKind::OpenAi).unwrap();
+        assert!(proxied.get(&url).send().await.is_err());
+
+        let direct = HttpClientFactory::new(HttpClientConfig { no_proxy: Some("localhost".to_string()), ..config })
+            .unwrap()
+            .client(ClientKind::OpenAi)
+            .unwrap();
+        assert!(direct.get(&url).send().await.unwrap().status().is_success());
+    }
+
+    #[test]
+    fn test_misconfigured_ca_paths_are_rejected() {
+        let missing = HttpClientConfig { ca_cert_paths: vec![fixture("missing.pem")], ..HttpClientConfig::default() };
+        let error = HttpClientFactory::new(missing).err().unwrap();
+        assert!(error.to_string().contains("missing.pem"));
+
+        // A private key is not a certificate
+        assert!(load_root_certificates(&[fixture("server.key")]).is_err());
+        assert_eq!(load_root_certificates(&[fixture("ca.pem"), fixture("server.pem")]).unwrap().len(), 2);
+    }
+}
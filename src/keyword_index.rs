@@ -0,0 +1,313 @@
+// Full-text index of knowledge chunks in SQLite. It backs retrieval up when
+// the vector search is slow or down: the federated search races both and
+// falls back to these keyword matches when the vector side misses its
+// deadline. Chunks whose text is encrypted in the vector store are not
+// indexed, so their plaintext never lands here.
+use anyhow::Result;
+use sqlx::Row;
+use std::collections::HashSet;
+
+use crate::knowledge_service_simple::{Document, DocumentMatch, TagFilter, TagMode};
+
+// Query words beyond this are ignored
+const MAX_QUERY_TERMS: usize = 16;
+
+pub struct KeywordIndex {
+    pool: sqlx::SqlitePool,
+}
+
+impl KeywordIndex {
+    pub async fn new(database_url: &str) -> Result<Self> {
+        let pool = crate::ai_service::connect_sqlite(database_url).await?;
+
+        // The FTS table only holds the searchable text; the triggers keep it
+        // in step with the chunk rows
+        for statement in [
+            r#"
+            CREATE TABLE IF NOT EXISTS keyword_chunks (
+                rowid INTEGER PRIMARY KEY,
+                document_id TEXT NOT NULL,
+                chunk_index INTEGER NOT NULL,
+                title TEXT NOT NULL,
+                content TEXT NOT NULL,
+                source TEXT NOT NULL,
+                tags TEXT NOT NULL,
+                trust_level TEXT NOT NULL,
+                manually_corrected BOOLEAN NOT NULL,
+                UNIQUE (document_id, chunk_index)
+            )
+            "#,
+            r#"
+            CREATE VIRTUAL TABLE IF NOT EXISTS keyword_chunks_fts
+            USING fts5(title, content, content='keyword_chunks', content_rowid='rowid')
+            "#,
+            r#"
+            CREATE TRIGGER IF NOT EXISTS keyword_chunks_insert AFTER INSERT ON keyword_chunks BEGIN
+                INSERT INTO keyword_chunks_fts (rowid, title, content) VALUES (new.rowid, new.title, new.content);
+            END
+            "#,
+            r#"
+            CREATE TRIGGER IF NOT EXISTS keyword_chunks_delete AFTER DELETE ON keyword_chunks BEGIN
+                INSERT INTO keyword_chunks_fts (keyword_chunks_fts, rowid, title, content)
+                VALUES ('delete', old.rowid, old.title, old.content);
+            END
+            "#,
+        ] {
+            sqlx::query(statement).execute(&pool).await?;
+        }
+
+        Ok(Self { pool })
+    }
+
+    // Replace everything indexed for the document with these chunks
+    pub async fn replace_document(&self, document_id: &str, chunks: &[Document]) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM keyword_chunks WHERE document_id = ?")
+            .bind(document_id)
+            .execute(&mut *tx)
+            .await?;
+        for chunk in chunks {
+            insert_chunk(&mut tx, chunk).await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    // Index the chunks that are not indexed yet, e.g. while filling the
+    // index from the vector store. Returns how many were added
+    pub async fn add_missing(&self, chunks: &[Document]) -> Result<usize> {
+        let mut tx = self.pool.begin().await?;
+        let mut added = 0;
+        for chunk in chunks {
+            let indexed: Option<i64> =
+                sqlx::query_scalar("SELECT rowid FROM keyword_chunks WHERE document_id = ? AND chunk_index = ?")
+                    .bind(&chunk.id)
+                    .bind(chunk.chunk_index as i64)
+                    .fetch_optional(&mut *tx)
+                    .await?;
+            if indexed.is_none() {
+                insert_chunk(&mut tx, chunk).await?;
+                added += 1;
+            }
+        }
+        tx.commit().await?;
+        Ok(added)
+    }
+
+    pub async fn remove_document(&self, document_id: &str) -> Result<()> {
+        sqlx::query("DELETE FROM keyword_chunks WHERE document_id = ?")
+            .bind(document_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    // Chunks containing any of the query's words, best match first, among
+    // those the tag filter admits. The score is the share of the query's
+    // words found in the chunk, so it is on the same 0 to 1 scale as a
+    // similarity
+    pub async fn search(&self, query: &str, limit: usize, tags_filter: Option<&TagFilter>) -> Result<Vec<DocumentMatch>> {
+        let terms = query_terms(query);
+        if terms.is_empty() || limit == 0 {
+            return Ok(Vec::new());
+        }
+        let expression = terms.iter().map(|term| format!("\"{}\"", term)).collect::<Vec<_>>().join(" OR ");
+        let (conditions, tags) = tags_filter.map(tag_conditions).unwrap_or_default();
+
+        let sql = format!(
+            r#"
+            SELECT c.document_id, c.chunk_index, c.title, c.content, c.source, c.tags, c.trust_level, c.manually_corrected
+            FROM keyword_chunks_fts f JOIN keyword_chunks c ON c.rowid = f.rowid
+            WHERE keyword_chunks_fts MATCH ?{}
+            ORDER BY f.rank
+            LIMIT ?
+            "#,
+            conditions
+        );
+        let mut rows = sqlx::query(&sql).bind(expression);
+        for tag in tags {
+            rows = rows.bind(tag);
+        }
+        let rows = rows.bind(limit as i64).fetch_all(&self.pool).await?;
+
+        let mut matches = rows
+            .iter()
+            .map(|row| {
+                let title: String = row.try_get("title")?;
+                let content: String = row.try_get("content")?;
+                let words: HashSet<String> = words(&format!("{} {}", title, content)).collect();
+                let found = terms.iter().filter(|term| words.contains(*term)).count();
+                let tags: String = row.try_get("tags")?;
+                let trust_level: String = row.try_get("trust_level")?;
+                Ok(DocumentMatch {
+                    id: row.try_get("document_id")?,
+                    title,
+                    content,
+                    score: found as f32 / terms.len() as f32,
+                    chunk_index: row.try_get::<i64, _>("chunk_index")? as usize,
+                    source: row.try_get("source")?,
+                    tags: serde_json::from_str(&tags).unwrap_or_default(),
+                    trust_level: trust_level.parse().unwrap_or_default(),
+                    note: None,
+                    manually_corrected: row.try_get("manually_corrected")?,
+                    // The keyword index does not record pages
+                    page: None,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        // Rank by the words matched; FTS rank breaks ties
+        matches.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
+        Ok(matches)
+    }
+}
+
+// SQL conditions on a chunk's JSON tags for the filter, and the tags to
+// bind to them in order
+fn tag_conditions(filter: &TagFilter) -> (String, Vec<String>) {
+    let carries = |count: usize| {
+        format!("EXISTS (SELECT 1 FROM json_each(c.tags) WHERE value IN ({}))", vec!["?"; count].join(", "))
+    };
+    let mut conditions = Vec::new();
+    let mut tags = Vec::new();
+    if !filter.tags.is_empty() {
+        match filter.mode {
+            TagMode::Any => conditions.push(carries(filter.tags.len())),
+            TagMode::All => conditions.extend(filter.tags.iter().map(|_| carries(1))),
+        }
+        tags.extend(filter.tags.iter().cloned());
+    }
+    if !filter.excluded.is_empty() {
+        conditions.push(format!("NOT {}", carries(filter.excluded.len())));
+        tags.extend(filter.excluded.iter().cloned());
+    }
+    (conditions.iter().map(|condition| format!(" AND {}", condition)).collect(), tags)
+}
+
+async fn insert_chunk(tx: &mut sqlx::Transaction<'_, sqlx::Sqlite>, chunk: &Document) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO keyword_chunks
+            (document_id, chunk_index, title, content, source, tags, trust_level, manually_corrected)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+        "#,
+    )
+    .bind(&chunk.id)
+    .bind(chunk.chunk_index as i64)
+    .bind(&chunk.title)
+    .bind(&chunk.content)
+    .bind(&chunk.source)
+    .bind(serde_json::to_string(&chunk.tags)?)
+    .bind(chunk.trust_level.as_str())
+    .bind(chunk.manually_corrected)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+// The distinct lowercase words of a query, quoted into the FTS expression,
+// so they are never read as FTS syntax
+fn query_terms(query: &str) -> Vec<String> {
+    let mut terms: Vec<String> = Vec::new();
+    for term in words(query) {
+        if !terms.contains(&term) {
+            terms.push(term);
+        }
+    }
+    terms.truncate(MAX_QUERY_TERMS);
+    terms
+}
+
+fn words(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trust::TrustLevel;
+
+    fn chunk(document_id: &str, chunk_index: usize, content: &str) -> Document {
+        Document {
+            id: document_id.to_string(),
+            title: "Lease".to_string(),
+            content: content.to_string(),
+            chunk_index,
+            total_chunks: 2,
+            source: "upload".to_string(),
+            tags: vec!["home".to_string()],
+            trust_level: TrustLevel::Verified,
+            created_at: chrono::Utc::now(),
+            offset: 0,
+            manually_corrected: false,
+            revision: 1,
+            updated_at: None,
+            page: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_search_scores_by_the_share_of_query_words() {
+        let index = KeywordIndex::new("sqlite::memory:").await.unwrap();
+        index
+            .replace_document("lease", &[chunk("lease", 0, "The monthly rent is 950 EUR"), chunk("lease", 1, "Pets are allowed")])
+            .await
+            .unwrap();
+        index.replace_document("notes", &[chunk("notes", 0, "Pay the rent on time")]).await.unwrap();
+
+        let matches = index.search("monthly rent?", 10, None).await.unwrap();
+        let found: Vec<(&str, usize, f32)> = matches.iter().map(|m| (m.id.as_str(), m.chunk_index, m.score)).collect();
+        assert_eq!(found, vec![("lease", 0, 1.0), ("notes", 0, 0.5)]);
+        assert_eq!(matches[0].trust_level, TrustLevel::Verified);
+        assert_eq!(matches[0].tags, vec!["home"]);
+
+        // Quotes and operators in the query are only words
+        assert!(index.search("\"rent\" OR NOT", 10, None).await.unwrap().len() == 2);
+        assert!(index.search("?!", 10, None).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_search_within_a_tag_filter() {
+        let index = KeywordIndex::new("sqlite::memory:").await.unwrap();
+        let tagged = |id: &str, tags: &[&str]| Document { tags: tags.iter().map(|t| t.to_string()).collect(), ..chunk(id, 0, "rent 950") };
+        index.replace_document("lease", &[tagged("lease", &["home", "contract"])]).await.unwrap();
+        index.replace_document("fact", &[tagged("fact", &["extracted"])]).await.unwrap();
+        index.replace_document("upload", &[tagged("upload", &["session:s1", "attachment"])]).await.unwrap();
+
+        let found = |filter: TagFilter| {
+            let index = &index;
+            async move {
+                let mut ids: Vec<String> = index.search("rent", 10, Some(&filter)).await.unwrap().into_iter().map(|m| m.id).collect();
+                ids.sort();
+                ids
+            }
+        };
+        assert_eq!(found(TagFilter::new(vec![], TagMode::Any).excluding(["extracted", "attachment"])).await, vec!["lease"]);
+        assert_eq!(found(TagFilter::new(vec!["home".into(), "extracted".into()], TagMode::Any)).await, vec!["fact", "lease"]);
+        assert_eq!(found(TagFilter::new(vec!["home".into(), "contract".into()], TagMode::All)).await, vec!["lease"]);
+        assert!(found(TagFilter::new(vec!["home".into(), "extracted".into()], TagMode::All)).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_replacing_and_removing_a_document() {
+        let index = KeywordIndex::new("sqlite::memory:").await.unwrap();
+        index
+            .replace_document("lease", &[chunk("lease", 0, "rent 950"), chunk("lease", 1, "rent due monthly")])
+            .await
+            .unwrap();
+        index.replace_document("lease", &[chunk("lease", 0, "rent 990")]).await.unwrap();
+
+        let matches = index.search("rent", 10, None).await.unwrap();
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].content, "rent 990");
+
+        // Only the chunks not indexed yet are added
+        let added = index.add_missing(&[chunk("lease", 0, "stale copy"), chunk("lease", 1, "rent due monthly")]).await.unwrap();
+        assert_eq!(added, 1);
+        assert_eq!(index.search("rent", 10, None).await.unwrap().len(), 2);
+
+        index.remove_document("lease").await.unwrap();
+        assert!(index.search("rent", 10, None).await.unwrap().is_empty());
+    }
+}
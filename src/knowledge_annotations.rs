@@ -0,0 +1,576 @@
+use anyhow::Result;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::Arc;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::knowledge_service_simple::{DocumentMatch, NoteRef};
+
+// A user's correction or note on a knowledge document, optionally pinned to
+// one chunk of it
+#[derive(Debug, Clone, PartialEq, Serialize, sqlx::FromRow)]
+pub struct Annotation {
+    pub id: String,
+    pub document_id: String,
+    // None for a note on the whole document
+    pub chunk_index: Option<i64>,
+    pub text: String,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl Annotation {
+    pub fn note_ref(&self) -> NoteRef {
+        NoteRef {
+            annotation_id: self.id.clone(),
+            chunk_index: self.chunk_index.map(|i| i as usize),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateAnnotationRequest {
+    #[serde(default)]
+    pub chunk_index: Option<usize>,
+    pub text: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateAnnotationRequest {
+    pub text: String,
+}
+
+pub struct AnnotationStore {
+    pool: sqlx::SqlitePool,
+}
+
+impl AnnotationStore {
+    pub async fn new(database_url: &str) -> Result<Self> {
+        let pool = crate::ai_service::connect_sqlite(database_url).await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS document_annotations (
+                id TEXT PRIMARY KEY,
+                document_id TEXT NOT NULL,
+                chunk_index INTEGER,
+                text TEXT NOT NULL,
+                created_at TIMESTAMP NOT NULL,
+                updated_at TIMESTAMP NOT NULL
+            )
+            "#,
+        )
+        .execute(&pool)
+        .await?;
+
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_document_annotations_document ON document_annotations(document_id)")
+            .execute(&pool)
+            .await?;
+
+        Ok(Self { pool })
+    }
+
+    pub async fn create(&self, document_id: &str, chunk_index: Option<usize>, text: &str) -> Result<Annotation> {
+        let now = chrono::Utc::now();
+        let annotation = Annotation {
+            id: Uuid::new_v4().to_string(),
+            document_id: document_id.to_string(),
+            chunk_index: chunk_index.map(|i| i as i64),
+            text: text.to_string(),
+            created_at: now,
+            updated_at: now,
+        };
+
+        sqlx::query(
+            r#"
+            INSERT INTO document_annotations (id, document_id, chunk_index, text, created_at, updated_at)
+            VALUES (?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&annotation.id)
+        .bind(&annotation.document_id)
+        .bind(annotation.chunk_index)
+        .bind(&annotation.text)
+        .bind(annotation.created_at)
+        .bind(annotation.updated_at)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(annotation)
+    }
+
+    pub async fn get(&self, annotation_id: &str) -> Result<Option<Annotation>> {
+        let annotation = sqlx::query_as::<_, Annotation>("SELECT * FROM document_annotations WHERE id = ?")
+            .bind(annotation_id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(annotation)
+    }
+
+    pub async fn list_for_document(&self, document_id: &str) -> Result<Vec<Annotation>> {
+        let annotations = sqlx::query_as::<_, Annotation>(
+            "SELECT * FROM document_annotations WHERE document_id = ? ORDER BY created_at ASC",
+        )
+        .bind(document_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(annotations)
+    }
+
+    pub async fn list_for_documents(&self, document_ids: &[String]) -> Result<Vec<Annotation>> {
+        if document_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let placeholders = vec!["?"; document_ids.len()].join(", ");
+        let sql = format!(
+            "SELECT * FROM document_annotations WHERE document_id IN ({}) ORDER BY created_at ASC",
+            placeholders
+        );
+        let mut query = sqlx::query_as::<_, Annotation>(&sql);
+        for document_id in document_ids {
+            query = query.bind(document_id);
+        }
+
+        Ok(query.fetch_all(&self.pool).await?)
+    }
+
+    // None when the annotation does not exist
+    pub async fn update_text(&self, annotation_id: &str, text: &str) -> Result<Option<Annotation>> {
+        let result = sqlx::query("UPDATE document_annotations SET text = ?, updated_at = ? WHERE id = ?")
+            .bind(text)
+            .bind(chrono::Utc::now())
+            .bind(annotation_id)
+            .execute(&self.pool)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Ok(None);
+        }
+        self.get(annotation_id).await
+    }
+
+    pub async fn delete(&self, annotation_id: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM document_annotations WHERE id = ?")
+            .bind(annotation_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn delete_for_document(&self, document_id: &str) -> Result<u64> {
+        let result = sqlx::query("DELETE FROM document_annotations WHERE document_id = ?")
+            .bind(document_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+struct PendingNote {
+    annotation_id: String,
+    document_id: String,
+    chunk_index: Option<usize>,
+    title: String,
+    text: String,
+}
+
+// Renders retrieved chunks as prompt context with each user note directly
+// under the chunk it annotates. A note that matched the query pulls its
+// parent chunk up to the note's rank; a note whose chunk was not retrieved
+// is listed on its own. `stored` are the saved annotations of the retrieved
+// documents, so a note accompanies its chunk even when it did not match.
+// Each chunk is marked with its document's trust level.
+pub fn assemble_context(matches: &[DocumentMatch], stored: &[Annotation]) -> String {
+    let mut notes: Vec<PendingNote> = Vec::new();
+    for doc in matches {
+        if let Some(note) = &doc.note {
+            if !notes.iter().any(|n| n.annotation_id == note.annotation_id) {
+                notes.push(PendingNote {
+                    annotation_id: note.annotation_id.clone(),
+                    document_id: doc.id.clone(),
+                    chunk_index: note.chunk_index,
+                    title: doc.title.clone(),
+                    text: doc.content.clone(),
+                });
+            }
+        }
+    }
+    for annotation in stored {
+        // The stored text is authoritative if the index lags behind an edit
+        match notes.iter_mut().find(|n| n.annotation_id == annotation.id) {
+            Some(note) => note.text = annotation.text.clone(),
+            None => notes.push(PendingNote {
+                annotation_id: annotation.id.clone(),
+                document_id: annotation.document_id.clone(),
+                chunk_index: annotation.chunk_index.map(|i| i as usize),
+                title: String::new(),
+                text: annotation.text.clone(),
+            }),
+        }
+    }
+
+    let chunks: Vec<&DocumentMatch> = matches.iter().filter(|doc| doc.note.is_none()).collect();
+    let mut assembler = ContextAssembler {
+        notes,
+        emitted_chunks: HashSet::new(),
+        emitted_notes: HashSet::new(),
+        lines: Vec::new(),
+    };
+
+    for doc in matches {
+        let Some(note) = &doc.note else {
+            assembler.emit_chunk(doc);
+            continue;
+        };
+        let parent = chunks.iter().find(|chunk| {
+            chunk.id == doc.id && note.chunk_index.map_or(true, |index| chunk.chunk_index == index)
+        });
+        match parent {
+            Some(parent) => assembler.emit_chunk(parent),
+            None => assembler.emit_orphan(&note.annotation_id),
+        }
+    }
+
+    assembler.lines.join("\n")
+}
+
+struct ContextAssembler {
+    notes: Vec<PendingNote>,
+    emitted_chunks: HashSet<(String, usize)>,
+    emitted_notes: HashSet<String>,
+    lines: Vec<String>,
+}
+
+impl ContextAssembler {
+    fn emit_chunk(&mut self, chunk: &DocumentMatch) {
+        if !self.emitted_chunks.insert((chunk.id.clone(), chunk.chunk_index)) {
+            return;
+        }
+        let page = chunk.page.map(|page| format!(", page {}", page)).unwrap_or_default();
+        self.lines.push(format!("- {}{} [{}]: {}", chunk.title, page, chunk.trust_level, chunk.content));
+
+        for note in &self.notes {
+            let applies = note.document_id == chunk.id
+                && note.chunk_index.map_or(true, |index| index == chunk.chunk_index);
+            if applies && self.emitted_notes.insert(note.annotation_id.clone()) {
+                self.lines.push(format!("  user note: {}", note.text));
+            }
+        }
+    }
+
+    fn emit_orphan(&mut self, annotation_id: &str) {
+        let Some(note) = self.notes.iter().find(|n| n.annotation_id == annotation_id) else {
+            return;
+        };
+        if self.emitted_notes.insert(note.annotation_id.clone()) {
+            self.lines.push(format!("- {} (user note): {}", note.title, note.text));
+        }
+    }
+}
+
+// HTTP Handlers
+pub async fn create_annotation_handler(
+    State(state): State<Arc<crate::AppState>>,
+    Path(document_id): Path<String>,
+    Json(request): Json<CreateAnnotationRequest>,
+) -> impl IntoResponse {
+    let Some(knowledge_service) = &state.knowledge_service else {
+        return (StatusCode::SERVICE_UNAVAILABLE, "Knowledge base service is not available").into_response();
+    };
+
+    let text = request.text.trim();
+    if text.is_empty() {
+        return (StatusCode::BAD_REQUEST, "Annotation text is required").into_response();
+    }
+
+    let document = match knowledge_service.find_document(&document_id).await {
+        Ok(Some(document)) => document,
+        Ok(None) => return (StatusCode::NOT_FOUND, "Document not found").into_response(),
+        Err(e) => {
+            error!("Failed to look up document {}: {}", document_id, e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to look up document").into_response();
+        }
+    };
+    if let Some(chunk_index) = request.chunk_index {
+        if chunk_index >= document.total_chunks {
+            return (
+                StatusCode::BAD_REQUEST,
+                format!("Document has {} chunks", document.total_chunks),
+            )
+                .into_response();
+        }
+    }
+
+    let embedding = match knowledge_service.embed_for(text, &document.tags).await {
+        Ok(embedding) => embedding,
+        Err(e) => {
+            error!("Failed to embed annotation: {}", e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to index annotation").into_response();
+        }
+    };
+
+    let annotation = match state.annotation_store.create(&document_id, request.chunk_index, text).await {
+        Ok(annotation) => annotation,
+        Err(e) => {
+            error!("Failed to save annotation: {}", e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to save annotation").into_response();
+        }
+    };
+
+    if let Err(e) = knowledge_service
+        .store_note(&document, &annotation.note_ref(), &annotation.text, embedding)
+        .await
+    {
+        error!("Failed to index annotation {}: {}", annotation.id, e);
+        if let Err(e) = state.annotation_store.delete(&annotation.id).await {
+            warn!("Failed to remove unindexed annotation {}: {}", annotation.id, e);
+        }
+        return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to index annotation").into_response();
+    }
+
+    info!("Added annotation {} to document {}", annotation.id, document_id);
+    (StatusCode::CREATED, Json(annotation)).into_response()
+}
+
+pub async fn list_annotations_handler(
+    State(state): State<Arc<crate::AppState>>,
+    Path(document_id): Path<String>,
+) -> impl IntoResponse {
+    match state.annotation_store.list_for_document(&document_id).await {
+        Ok(annotations) => Json(serde_json::json!({
+            "document_id": document_id,
+            "total": annotations.len(),
+            "annotations": annotations,
+        }))
+        .into_response(),
+        Err(e) => {
+            error!("Failed to list annotations for {}: {}", document_id, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to list annotations").into_response()
+        }
+    }
+}
+
+pub async fn update_annotation_handler(
+    State(state): State<Arc<crate::AppState>>,
+    Path((document_id, annotation_id)): Path<(String, String)>,
+    Json(request): Json<UpdateAnnotationRequest>,
+) -> impl IntoResponse {
+    let Some(knowledge_service) = &state.knowledge_service else {
+        return (StatusCode::SERVICE_UNAVAILABLE, "Knowledge base service is not available").into_response();
+    };
+
+    let text = request.text.trim();
+    if text.is_empty() {
+        return (StatusCode::BAD_REQUEST, "Annotation text is required").into_response();
+    }
+
+    match state.annotation_store.get(&annotation_id).await {
+        Ok(Some(annotation)) if annotation.document_id == document_id => {}
+        Ok(_) => return (StatusCode::NOT_FOUND, "Annotation not found").into_response(),
+        Err(e) => {
+            error!("Failed to load annotation {}: {}", annotation_id, e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to load annotation").into_response();
+        }
+    }
+
+    // The document's tags decide which provider may embed the note
+    let document = match knowledge_service.find_document(&document_id).await {
+        Ok(Some(document)) => document,
+        Ok(None) => return (StatusCode::NOT_FOUND, "Document not found").into_response(),
+        Err(e) => {
+            error!("Failed to re-index annotation {}: {}", annotation_id, e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to index annotation").into_response();
+        }
+    };
+    let embedding = match knowledge_service.embed_for(text, &document.tags).await {
+        Ok(embedding) => embedding,
+        Err(e) => {
+            error!("Failed to re-index annotation {}: {}", annotation_id, e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to index annotation").into_response();
+        }
+    };
+
+    let annotation = match state.annotation_store.update_text(&annotation_id, text).await {
+        Ok(Some(annotation)) => annotation,
+        Ok(None) => return (StatusCode::NOT_FOUND, "Annotation not found").into_response(),
+        Err(e) => {
+            error!("Failed to update annotation {}: {}", annotation_id, e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to update annotation").into_response();
+        }
+    };
+
+    // The note keeps its point id, so this replaces the old embedding
+    if let Err(e) = knowledge_service
+        .store_note(&document, &annotation.note_ref(), &annotation.text, embedding)
+        .await
+    {
+        error!("Failed to re-index annotation {}: {}", annotation_id, e);
+        return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to index annotation").into_response();
+    }
+
+    Json(annotation).into_response()
+}
+
+pub async fn delete_annotation_handler(
+    State(state): State<Arc<crate::AppState>>,
+    Path((document_id, annotation_id)): Path<(String, String)>,
+) -> impl IntoResponse {
+    match state.annotation_store.get(&annotation_id).await {
+        Ok(Some(annotation)) if annotation.document_id == document_id => {}
+        Ok(_) => return (StatusCode::NOT_FOUND, "Annotation not found").into_response(),
+        Err(e) => {
+            error!("Failed to load annotation {}: {}", annotation_id, e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to load annotation").into_response();
+        }
+    }
+
+    if let Some(knowledge_service) = &state.knowledge_service {
+        if let Err(e) = knowledge_service.delete_note(&annotation_id).await {
+            error!("Failed to remove annotation {} from the index: {}", annotation_id, e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to delete annotation").into_response();
+        }
+    }
+
+    match state.annotation_store.delete(&annotation_id).await {
+        Ok(_) => StatusCode::NO_CONTENT.into_response(),
+        Err(e) => {
+            error!("Failed to delete annotation {}: {}", annotation_id, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to delete annotation").into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trust::TrustLevel;
+
+    fn chunk(id: &str, title: &str, chunk_index: usize, content: &str, score: f32) -> DocumentMatch {
+        DocumentMatch {
+            id: id.to_string(),
+            title: title.to_string(),
+            content: content.to_string(),
+            score,
+            chunk_index,
+            source: "notes.md".to_string(),
+            tags: Vec::new(),
+            trust_level: TrustLevel::Personal,
+            note: None,
+            manually_corrected: false,
+            page: None,
+        }
+    }
+
+    fn note_match(document_id: &str, annotation_id: &str, chunk_index: Option<usize>, text: &str, score: f32) -> DocumentMatch {
+        DocumentMatch {
+            note: Some(NoteRef {
+                annotation_id: annotation_id.to_string(),
+                chunk_index,
+            }),
+            ..chunk(document_id, "Travel policy", chunk_index.unwrap_or(0), text, score)
+        }
+    }
+
+    fn stored(document_id: &str, annotation_id: &str, chunk_index: Option<i64>, text: &str) -> Annotation {
+        Annotation {
+            id: annotation_id.to_string(),
+            document_id: document_id.to_string(),
+            chunk_index,
+            text: text.to_string(),
+            created_at: chrono::Utc::now(),
+            updated_at: chrono::Utc::now(),
+        }
+    }
+
+    async fn test_store() -> AnnotationStore {
+        let path = std::env::temp_dir().join(format!("rusty-ai-annotations-{}.db", Uuid::new_v4()));
+        AnnotationStore::new(&format!("sqlite://{}?mode=rwc", path.display())).await.unwrap()
+    }
+
+    #[test]
+    fn test_matching_note_pulls_stale_chunk_up_and_sits_beside_it() {
+        // Search order: the boosted note outranks an unrelated chunk, which
+        // outranks the stale chunk the note corrects
+        let matches = vec![
+            note_match("policy", "n1", Some(2), "This figure is outdated, see 2024 policy", 0.95),
+            DocumentMatch { page: Some(12), ..chunk("budget", "Budget", 0, "Travel budget is set per team", 0.8) },
+            chunk("policy", "Travel policy", 2, "Per diem is 40 EUR", 0.7),
+        ];
+
+        let context = assemble_context(&matches, &[]);
+        assert_eq!(
+            context,
+            "- Travel policy [personal]: Per diem is 40 EUR\n  user note: This figure is outdated, see 2024 policy\n- Budget, page 12 [personal]: Travel budget is set per team"
+        );
+    }
+
+    #[test]
+    fn test_stored_note_accompanies_retrieved_chunk() {
+        let matches = vec![
+            chunk("policy", "Travel policy", 2, "Per diem is 40 EUR", 0.7),
+            chunk("policy", "Travel policy", 3, "Book trains over flights", 0.6),
+        ];
+        let annotations = vec![
+            stored("policy", "n1", Some(2), "This figure is outdated, see 2024 policy"),
+            stored("policy", "n2", None, "Superseded in March"),
+            stored("policy", "n3", Some(7), "Not retrieved, not shown"),
+        ];
+
+        let context = assemble_context(&matches, &annotations);
+        assert_eq!(
+            context,
+            "- Travel policy [personal]: Per diem is 40 EUR\n  user note: This figure is outdated, see 2024 policy\n  user note: Superseded in March\n- Travel policy [personal]: Book trains over flights"
+        );
+    }
+
+    #[test]
+    fn test_note_without_retrieved_parent_is_listed_alone() {
+        let matches = vec![
+            note_match("policy", "n1", Some(2), "Old text in the index", 0.9),
+            chunk("budget", "Budget", 0, "Travel budget is set per team", 0.8),
+        ];
+        // The edited text from the store replaces the indexed text
+        let annotations = vec![stored("policy", "n1", Some(2), "Per diem is 55 EUR since 2024")];
+
+        let context = assemble_context(&matches, &annotations);
+        assert_eq!(
+            context,
+            "- Travel policy (user note): Per diem is 55 EUR since 2024\n- Budget [personal]: Travel budget is set per team"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_store_edits_and_cascades_on_document_delete() {
+        let store = test_store().await;
+        let first = store.create("doc-1", Some(0), "first").await.unwrap();
+        store.create("doc-1", None, "second").await.unwrap();
+        let other = store.create("doc-2", None, "other").await.unwrap();
+
+        let edited = store.update_text(&first.id, "first, corrected").await.unwrap().unwrap();
+        assert_eq!(edited.text, "first, corrected");
+        assert!(store.update_text("missing", "x").await.unwrap().is_none());
+
+        let both = store.list_for_documents(&["doc-1".to_string(), "doc-2".to_string()]).await.unwrap();
+        assert_eq!(both.len(), 3);
+
+        assert_eq!(store.delete_for_document("doc-1").await.unwrap(), 2);
+        assert!(store.list_for_document("doc-1").await.unwrap().is_empty());
+        let remaining = store.list_for_document("doc-2").await.unwrap();
+        assert_eq!(remaining.iter().map(|a| a.id.as_str()).collect::<Vec<_>>(), vec![other.id.as_str()]);
+
+        assert!(store.delete(&other.id).await.unwrap());
+        assert!(!store.delete(&other.id).await.unwrap());
+    }
+}
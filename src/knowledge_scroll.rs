@@ -0,0 +1,351 @@
+// Paged walk over a knowledge collection for maintenance jobs that touch
+// every point. Batches are spaced out, and the walk pauses while searches are
+// slow so a background job never competes with the user's queries. After each
+// finished batch the job's position is written to a checkpoint, so a restart
+// picks up where the last run stopped instead of starting over.
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+use crate::query_metrics::RecentLatency;
+use crate::vector_store::{PayloadFilter, StoredPoint, VectorStore};
+
+const DEFAULT_STATE_DIR: &str = "./data/knowledge_jobs";
+const DEFAULT_BATCH_SIZE: u32 = 256;
+const DEFAULT_BATCH_DELAY_MS: u64 = 100;
+const DEFAULT_MAX_SEARCH_P95_MS: u64 = 250;
+// How long a throttled job waits before looking at the latency again
+const THROTTLE_PAUSE: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone)]
+pub struct ScrollConfig {
+    pub batch_size: u32,
+    // Pause between two batches
+    pub batch_delay: Duration,
+    // Wait while the p95 of recent searches is above this; None never waits
+    pub max_search_p95: Option<Duration>,
+    pub throttle_pause: Duration,
+    // Read and count only; jobs write nothing, checkpoints included
+    pub dry_run: bool,
+    pub state_dir: PathBuf,
+}
+
+impl Default for ScrollConfig {
+    fn default() -> Self {
+        Self {
+            batch_size: DEFAULT_BATCH_SIZE,
+            batch_delay: Duration::from_millis(DEFAULT_BATCH_DELAY_MS),
+            max_search_p95: Some(Duration::from_millis(DEFAULT_MAX_SEARCH_P95_MS)),
+            throttle_pause: THROTTLE_PAUSE,
+            dry_run: false,
+            state_dir: PathBuf::from(DEFAULT_STATE_DIR),
+        }
+    }
+}
+
+impl ScrollConfig {
+    // KNOWLEDGE_JOB_BATCH_SIZE, KNOWLEDGE_JOB_BATCH_DELAY_MS,
+    // KNOWLEDGE_JOB_MAX_SEARCH_P95_MS (0 disables throttling),
+    // KNOWLEDGE_JOB_DRY_RUN and KNOWLEDGE_JOB_STATE_DIR
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        let number = |name: &str| std::env::var(name).ok().and_then(|v| v.parse::<u64>().ok());
+        Self {
+            batch_size: number("KNOWLEDGE_JOB_BATCH_SIZE")
+                .map(|n| n.clamp(1, u32::MAX as u64) as u32)
+                .unwrap_or(defaults.batch_size),
+            batch_delay: number("KNOWLEDGE_JOB_BATCH_DELAY_MS")
+                .map(Duration::from_millis)
+                .unwrap_or(defaults.batch_delay),
+            max_search_p95: match number("KNOWLEDGE_JOB_MAX_SEARCH_P95_MS") {
+                Some(0) => None,
+                Some(ms) => Some(Duration::from_millis(ms)),
+                None => defaults.max_search_p95,
+            },
+            throttle_pause: defaults.throttle_pause,
+            dry_run: std::env::var("KNOWLEDGE_JOB_DRY_RUN").map(|v| v == "1" || v == "true").unwrap_or(false),
+            state_dir: std::env::var("KNOWLEDGE_JOB_STATE_DIR").map(PathBuf::from).unwrap_or(defaults.state_dir),
+        }
+    }
+}
+
+// What a walk did so far; in a dry run this is the whole outcome
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ScrollStats {
+    pub batches: u64,
+    pub points: u64,
+    // Number of pauses for slow searches, and their total length
+    pub throttled: u64,
+    pub throttled_for: Duration,
+    // The offset a checkpoint from an earlier run resumed at
+    pub resumed_from: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Checkpoint {
+    job: String,
+    collection: String,
+    scope: String,
+    // First point of the next batch to process
+    offset: String,
+    points: u64,
+    updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+pub struct ScrollIterator<'a> {
+    store: &'a VectorStore,
+    config: &'a ScrollConfig,
+    job: String,
+    collection: String,
+    filter: Option<PayloadFilter>,
+    scope: String,
+    latency: Option<Arc<RecentLatency>>,
+    offset: Option<String>,
+    started: bool,
+    finished: bool,
+    stats: ScrollStats,
+}
+
+impl<'a> ScrollIterator<'a> {
+    pub fn new(store: &'a VectorStore, config: &'a ScrollConfig, job: &str, collection: &str) -> Self {
+        Self {
+            store,
+            config,
+            job: job.to_string(),
+            collection: collection.to_string(),
+            filter: None,
+            scope: String::new(),
+            latency: None,
+            offset: None,
+            started: false,
+            finished: false,
+            stats: ScrollStats::default(),
+        }
+    }
+
+    pub fn with_filter(mut self, filter: PayloadFilter) -> Self {
+        self.filter = Some(filter);
+        self
+    }
+
+    // What the job's filter depends on, e.g. the key version a migration
+    // moves to. A checkpoint written under another scope is not resumed,
+    // since its offset may skip points the new run has to visit
+    pub fn with_scope(mut self, scope: impl Into<String>) -> Self {
+        self.scope = scope.into();
+        self
+    }
+
+    // Search latency to back off on
+    pub fn with_latency(mut self, latency: Arc<RecentLatency>) -> Self {
+        self.latency = Some(latency);
+        self
+    }
+
+    pub fn is_dry_run(&self) -> bool {
+        self.config.dry_run
+    }
+
+    pub fn stats(&self) -> &ScrollStats {
+        &self.stats
+    }
+
+    // The next batch, None once the collection is exhausted. Asking for the
+    // next batch marks the previous one as done
+    pub async fn next_batch(&mut self) -> Result<Option<Vec<StoredPoint>>> {
+        if self.finished {
+            return Ok(None);
+        }
+
+        if !self.started {
+            self.started = true;
+            self.offset = self.load_checkpoint().await;
+            self.stats.resumed_from = self.offset.clone();
+        } else {
+            if self.offset.is_none() {
+                self.finish().await;
+                return Ok(None);
+            }
+            self.save_checkpoint().await?;
+            tokio::time::sleep(self.config.batch_delay).await;
+        }
+
+        self.throttle().await;
+        let (points, next) = self
+            .store
+            .scroll_page(&self.collection, self.filter.as_ref(), self.offset.as_deref(), self.config.batch_size)
+            .await?;
+        if points.is_empty() {
+            self.finish().await;
+            return Ok(None);
+        }
+
+        self.offset = next;
+        self.stats.batches += 1;
+        self.stats.points += points.len() as u64;
+        Ok(Some(points))
+    }
+
+    async fn throttle(&mut self) {
+        let (Some(latency), Some(max)) = (&self.latency, self.config.max_search_p95) else {
+            return;
+        };
+        while let Some(p95) = latency.p95().filter(|p95| *p95 > max) {
+            debug!("Job {} waits: search p95 {:?} is above {:?}", self.job, p95, max);
+            tokio::time::sleep(self.config.throttle_pause).await;
+            self.stats.throttled += 1;
+            self.stats.throttled_for += self.config.throttle_pause;
+        }
+    }
+
+    fn checkpoint_path(&self) -> PathBuf {
+        self.config.state_dir.join(format!("{}.{}.json", self.job, self.collection))
+    }
+
+    async fn load_checkpoint(&self) -> Option<String> {
+        let bytes = tokio::fs::read(self.checkpoint_path()).await.ok()?;
+        match serde_json::from_slice::<Checkpoint>(&bytes) {
+            Ok(checkpoint) if checkpoint.scope == self.scope => {
+                info!("Job {} resumes on {} after {} points", self.job, self.collection, checkpoint.points);
+                Some(checkpoint.offset)
+            }
+            Ok(_) => None,
+            Err(e) => {
+                warn!("Ignoring unreadable checkpoint {:?}: {}", self.checkpoint_path(), e);
+                None
+            }
+        }
+    }
+
+    async fn save_checkpoint(&self) -> Result<()> {
+        let Some(offset) = self.offset.clone().filter(|_| !self.config.dry_run) else {
+            return Ok(());
+        };
+        let checkpoint = Checkpoint {
+            job: self.job.clone(),
+            collection: self.collection.clone(),
+            scope: self.scope.clone(),
+            offset,
+            points: self.stats.points,
+            updated_at: chrono::Utc::now(),
+        };
+        tokio::fs::create_dir_all(&self.config.state_dir).await?;
+        tokio::fs::write(self.checkpoint_path(), serde_json::to_vec(&checkpoint)?).await?;
+        Ok(())
+    }
+
+    async fn finish(&mut self) {
+        self.finished = true;
+        if !self.config.dry_run {
+            if let Err(e) = tokio::fs::remove_file(self.checkpoint_path()).await {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    warn!("Failed to remove checkpoint {:?}: {}", self.checkpoint_path(), e);
+                }
+            }
+        }
+        info!(
+            "Job {} went through {} points of {} in {} batches{}",
+            self.job,
+            self.stats.points,
+            self.collection,
+            self.stats.batches,
+            if self.config.dry_run { " (dry run)" } else { "" }
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vector_store::VectorPoint;
+
+    fn config(name: &str) -> ScrollConfig {
+        ScrollConfig {
+            batch_size: 2,
+            batch_delay: Duration::ZERO,
+            max_search_p95: Some(Duration::from_millis(50)),
+            throttle_pause: Duration::from_millis(20),
+            dry_run: false,
+            state_dir: std::env::temp_dir().join(format!("rusty-ai-scroll-{}-{}", name, uuid::Uuid::new_v4())),
+        }
+    }
+
+    async fn store(ids: &[&str]) -> VectorStore {
+        let store = VectorStore::in_memory();
+        store.ensure_collection("docs", 1).await.unwrap();
+        let points = ids
+            .iter()
+            .map(|id| VectorPoint { id: id.to_string(), vector: vec![1.0], payload: serde_json::json!({}).try_into().unwrap() })
+            .collect();
+        store.upsert("docs", points).await.unwrap();
+        store
+    }
+
+    fn ids(points: &[StoredPoint]) -> Vec<&str> {
+        points.iter().map(|p| p.id.as_str()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_slow_searches_pause_the_walk_until_they_recover() {
+        let store = store(&["a", "b", "c"]).await;
+        let config = config("throttle");
+        let latency = Arc::new(RecentLatency::new(Duration::from_millis(100)));
+        let mut scroll = ScrollIterator::new(&store, &config, "test", "docs").with_latency(latency.clone());
+
+        latency.observe(Duration::from_millis(10));
+        assert_eq!(ids(&scroll.next_batch().await.unwrap().unwrap()), vec!["a", "b"]);
+        assert_eq!(scroll.stats().throttled, 0);
+
+        // Searches got slow: the next batch waits until they age out
+        for _ in 0..20 {
+            latency.observe(Duration::from_millis(400));
+        }
+        let started = std::time::Instant::now();
+        assert_eq!(ids(&scroll.next_batch().await.unwrap().unwrap()), vec!["c"]);
+        assert!(scroll.stats().throttled >= 3);
+        assert!(started.elapsed() >= Duration::from_millis(60));
+        assert!(scroll.next_batch().await.unwrap().is_none());
+        assert_eq!(scroll.stats().points, 3);
+    }
+
+    #[tokio::test]
+    async fn test_restarted_jobs_resume_from_the_checkpoint() {
+        let store = store(&["a", "b", "c", "d", "e"]).await;
+        let config = config("resume");
+        {
+            let mut scroll = ScrollIterator::new(&store, &config, "test", "docs").with_scope("v2");
+            assert_eq!(ids(&scroll.next_batch().await.unwrap().unwrap()), vec!["a", "b"]);
+            // Interrupted while working on the second batch
+            assert_eq!(ids(&scroll.next_batch().await.unwrap().unwrap()), vec!["c", "d"]);
+        }
+
+        // Another scope starts over
+        let mut other = ScrollIterator::new(&store, &config, "test", "docs").with_scope("v3");
+        assert_eq!(ids(&other.next_batch().await.unwrap().unwrap()), vec!["a", "b"]);
+
+        let mut scroll = ScrollIterator::new(&store, &config, "test", "docs").with_scope("v2");
+        assert_eq!(ids(&scroll.next_batch().await.unwrap().unwrap()), vec!["c", "d"]);
+        assert_eq!(scroll.stats().resumed_from.as_deref(), Some("c"));
+        assert_eq!(ids(&scroll.next_batch().await.unwrap().unwrap()), vec!["e"]);
+        assert!(scroll.next_batch().await.unwrap().is_none());
+
+        // A finished job leaves no checkpoint behind
+        let mut scroll = ScrollIterator::new(&store, &config, "test", "docs").with_scope("v2");
+        assert_eq!(ids(&scroll.next_batch().await.unwrap().unwrap()), vec!["a", "b"]);
+    }
+
+    #[tokio::test]
+    async fn test_dry_runs_count_without_checkpoints() {
+        let store = store(&["a", "b", "c", "d", "e"]).await;
+        let config = ScrollConfig { dry_run: true, ..config("dry-run") };
+        let mut scroll = ScrollIterator::new(&store, &config, "test", "docs");
+        while scroll.next_batch().await.unwrap().is_some() {}
+
+        assert_eq!(scroll.stats().batches, 3);
+        assert_eq!(scroll.stats().points, 5);
+        assert!(!config.state_dir.exists());
+    }
+}
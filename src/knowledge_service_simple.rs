@@ -5,28 +5,83 @@ use async_openai::{
     Client,
 };
 use axum::{
-    extract::{Multipart, Query, State},
+    extract::{Path, Query, State},
     http::StatusCode,
     response::IntoResponse,
     Json,
 };
 use qdrant_client::{
     Qdrant, Payload,
-    qdrant::{
-        CreateCollectionBuilder, Distance, PointStruct, SearchPointsBuilder,
-        UpsertPointsBuilder, VectorParamsBuilder,
-    },
+    qdrant::Value,
 };
 use serde::{Deserialize, Serialize};
 use serde_json;
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::sync::Arc;
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 use uuid::Uuid;
 
+use crate::data_residency::{ProviderClass, ResidencyPolicy};
+use crate::document_revisions::{self, DiffSummary, DocumentRevision, RevisionStore};
+use crate::http_client::{ClientKind, HttpClientFactory};
+use crate::keyword_index::KeywordIndex;
+use crate::knowledge_scroll::{ScrollConfig, ScrollIterator};
+use crate::payload_crypto::{self, PayloadCipher, ENCRYPTED_CONTENT_FIELD, KEY_VERSION_FIELD, LOCAL_OWNER};
+use crate::query_metrics::RecentLatency;
+use crate::retrieval;
+use crate::trust::{self, TrustLevel};
+use crate::vector_store::{PayloadFilter, PointPayload, VectorPoint, VectorStore};
+
+const QDRANT_URL: &str = "http://localhost:6334";
 const COLLECTION_NAME: &str = "personal_knowledge";
-const EMBEDDING_MODEL: &str = "text-embedding-3-small";
+// Chunks of documents that may not leave the machine, embedded by the local
+// provider; its vectors are not comparable with the cloud ones
+const LOCAL_COLLECTION_NAME: &str = "personal_knowledge_local";
+const DEFAULT_LOCAL_EMBEDDING_DIMENSION: u64 = 768;
+pub(crate) const EMBEDDING_MODEL: &str = "text-embedding-3-small";
 const EMBEDDING_DIMENSION: u64 = 1536;
-const MAX_CHUNK_SIZE: usize = 2000; // Characters per chunk
+pub(crate) const MAX_CHUNK_SIZE: usize = 2000; // Characters per chunk
+// Upper bound on the chunks read back for one document
+const MAX_DOCUMENT_CHUNKS: u32 = 10_000;
+// Points read per page when listing documents
+const LIST_PAGE_SIZE: u32 = 1000;
+pub const DEFAULT_LIST_LIMIT: usize = 50;
+pub const MAX_LIST_LIMIT: usize = 200;
+// Searches with a minimum trust level drop matches afterwards, so ask for
+// this many times the limit
+const MIN_TRUST_OVERFETCH_FACTOR: usize = 4;
+// User notes are high-importance entries; their similarity is scaled up so a
+// correction ranks above the text it corrects
+const ANNOTATION_SCORE_BOOST: f32 = 1.25;
+// Searches maintenance jobs look at to decide whether to back off
+const SEARCH_LATENCY_WINDOW: std::time::Duration = std::time::Duration::from_secs(60);
+// Namespace of the UUIDv5 point ids derived from document id and chunk index
+const CHUNK_ID_NAMESPACE: Uuid = Uuid::from_u128(0x6f1c_2b8e_93d4_4a57_8c0e_5b2f_d17a_9e43);
+
+// How chunk points get their ids. Deterministic ids are derived from the
+// document id and chunk index, so storing a chunk again replaces its point
+// and a chunk can be read by id; random ids are what points got before
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PointIdStrategy {
+    #[default]
+    Deterministic,
+    Random,
+}
+
+impl PointIdStrategy {
+    // KNOWLEDGE_POINT_IDS=deterministic|random
+    pub fn from_env() -> Self {
+        match std::env::var("KNOWLEDGE_POINT_IDS").as_deref() {
+            Ok("random") => PointIdStrategy::Random,
+            _ => PointIdStrategy::Deterministic,
+        }
+    }
+}
+
+// The deterministic point id of a document's chunk
+pub fn chunk_point_id(document_id: &str, chunk_index: usize) -> String {
+    Uuid::new_v5(&CHUNK_ID_NAMESPACE, format!("{}/{}", document_id, chunk_index).as_bytes()).to_string()
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Document {
@@ -37,7 +92,71 @@ pub struct Document {
     pub total_chunks: usize,
     pub source: String,
     pub tags: Vec<String>,
+    #[serde(default)]
+    pub trust_level: TrustLevel,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    // Byte offset of the chunk in the document's text
+    #[serde(default)]
+    pub offset: usize,
+    // Set once the user corrected the chunk's text by hand
+    #[serde(default)]
+    pub manually_corrected: bool,
+    // The document's revision: 1 as uploaded, bumped by every change to its
+    // text. All of a document's chunks carry the same one
+    #[serde(default = "first_revision")]
+    pub revision: u64,
+    // When the text last changed, None while it is as uploaded
+    #[serde(default)]
+    pub updated_at: Option<chrono::DateTime<chrono::Utc>>,
+    // Page of the uploaded file the chunk was taken from, counted from 1;
+    // None for text without pages
+    #[serde(default)]
+    pub page: Option<usize>,
+}
+
+fn first_revision() -> u64 {
+    1
+}
+
+// One document in a listing, described by its first chunk
+#[derive(Debug, Clone, Serialize)]
+pub struct DocumentSummary {
+    pub id: String,
+    pub title: String,
+    pub source: String,
+    pub tags: Vec<String>,
+    pub trust_level: TrustLevel,
+    pub chunk_count: usize,
     pub created_at: chrono::DateTime<chrono::Utc>,
+    pub revision: u64,
+    pub updated_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl From<Document> for DocumentSummary {
+    fn from(document: Document) -> Self {
+        Self {
+            id: document.id,
+            title: document.title,
+            source: document.source,
+            tags: document.tags,
+            trust_level: document.trust_level,
+            chunk_count: document.total_chunks,
+            created_at: document.created_at,
+            revision: document.revision,
+            updated_at: document.updated_at,
+        }
+    }
+}
+
+// A page of documents, newest first
+#[derive(Debug, Serialize)]
+pub struct DocumentPage {
+    pub documents: Vec<DocumentSummary>,
+    // Documents matching the listing, across all pages
+    pub total: usize,
+    pub offset: usize,
+    pub limit: usize,
+    pub has_more: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -53,9 +172,97 @@ pub struct SearchQuery {
     pub query: String,
     pub limit: Option<usize>,
     pub threshold: Option<f32>,
+    // Comma separated, e.g. `?tags=work,finance`
+    #[serde(default, deserialize_with = "comma_separated")]
+    pub tags: Option<Vec<String>>,
+    // Whether a document needs any or all of `tags`; any by default
+    #[serde(default)]
+    pub tag_mode: TagMode,
+    // Only return documents at least this trusted
+    pub min_trust: Option<TrustLevel>,
+}
+
+// Whether a document must carry any one of the searched tags or all of them
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TagMode {
+    #[default]
+    Any,
+    All,
+}
+
+// Restricts a search to the documents carrying some tags, and none of the
+// excluded ones
+#[derive(Debug, Clone, PartialEq)]
+pub struct TagFilter {
+    pub tags: Vec<String>,
+    pub mode: TagMode,
+    pub excluded: Vec<String>,
+}
+
+impl TagFilter {
+    pub fn new(tags: Vec<String>, mode: TagMode) -> Self {
+        Self { tags, mode, excluded: Vec::new() }
+    }
+    
+    pub fn excluding(mut self, tags: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.excluded.extend(tags.into_iter().map(Into::into));
+        self
+    }
+    
+    // The condition on the `tags` payload field; None without any tags, so
+    // an empty filter searches everything
+    fn to_payload_filter(&self) -> Option<PayloadFilter> {
+        if self.tags.is_empty() && self.excluded.is_empty() {
+            return None;
+        }
+        let tags = self.tags.iter().cloned();
+        let filter = match self.mode {
+            TagMode::Any => PayloadFilter::default().and_any("tags", tags),
+            TagMode::All => tags.fold(PayloadFilter::default(), |filter, tag| filter.and("tags", tag)),
+        };
+        Some(self.excluded.iter().fold(filter, |filter, tag| filter.and_not("tags", tag.clone())))
+    }
+}
+
+fn comma_separated<'de, D: serde::Deserializer<'de>>(deserializer: D) -> std::result::Result<Option<Vec<String>>, D::Error> {
+    let value: Option<String> = Option::deserialize(deserializer)?;
+    Ok(value.map(|value| {
+        value.split(',').map(str::trim).filter(|tag| !tag.is_empty()).map(str::to_string).collect()
+    }))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListDocumentsQuery {
+    pub offset: Option<usize>,
+    pub limit: Option<usize>,
+    // Only documents with this tag
+    pub tag: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReplaceDocumentRequest {
+    pub content: String,
+    // Keeps the current tags when left out
+    #[serde(default)]
     pub tags: Option<Vec<String>>,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct DeleteBySourceQuery {
+    pub source: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateDocumentRequest {
+    pub trust_level: TrustLevel,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CorrectChunkRequest {
+    pub content: String,
+}
+
 #[derive(Debug, Serialize)]
 pub struct SearchResult {
     pub documents: Vec<DocumentMatch>,
@@ -63,7 +270,7 @@ pub struct SearchResult {
     pub total_results: usize,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 pub struct DocumentMatch {
     pub id: String,
     pub title: String,
@@ -71,63 +278,496 @@ pub struct DocumentMatch {
     pub score: f32,
     pub chunk_index: usize,
     pub source: String,
+    pub tags: Vec<String>,
+    pub trust_level: TrustLevel,
+    // Set when the match is a user note attached to the document rather than
+    // the document's own text
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<NoteRef>,
+    // The chunk's text was corrected by hand, which trust weighting favours
+    pub manually_corrected: bool,
+    // Page of the uploaded file the chunk came from, for citing it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub page: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NoteRef {
+    pub annotation_id: String,
+    // None for a note on the whole document
+    pub chunk_index: Option<usize>,
+}
+
+impl DocumentMatch {
+    // Whether both matches are the same indexed entry
+    pub fn same_entry(&self, other: &DocumentMatch) -> bool {
+        self.id == other.id
+            && self.chunk_index == other.chunk_index
+            && self.note.as_ref().map(|n| &n.annotation_id) == other.note.as_ref().map(|n| &n.annotation_id)
+    }
+}
+
+// An OpenAI-compatible embedding server on this machine
+pub struct LocalEmbeddings {
+    client: Client<OpenAIConfig>,
+    model: String,
+    dimension: u64,
+}
+
+impl LocalEmbeddings {
+    // LOCAL_EMBEDDING_API_BASE, LOCAL_EMBEDDING_MODEL and
+    // LOCAL_EMBEDDING_DIMENSION; None when no local provider is configured
+    pub fn from_env() -> Result<Option<Self>> {
+        let Ok(api_base) = std::env::var("LOCAL_EMBEDDING_API_BASE") else {
+            return Ok(None);
+        };
+        if ProviderClass::of_endpoint(&api_base) != ProviderClass::Local {
+            anyhow::bail!("LOCAL_EMBEDDING_API_BASE {} is not on this machine", api_base);
+        }
+
+        Ok(Some(Self {
+            client: Client::with_config(OpenAIConfig::new().with_api_base(api_base).with_api_key("local")),
+            model: std::env::var("LOCAL_EMBEDDING_MODEL").unwrap_or_else(|_| "nomic-embed-text".to_string()),
+            dimension: std::env::var("LOCAL_EMBEDDING_DIMENSION")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(DEFAULT_LOCAL_EMBEDDING_DIMENSION),
+        }))
+    }
+
+    async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        let request = CreateEmbeddingRequestArgs::default()
+            .model(&self.model)
+            .input([text])
+            .build()?;
+        let response = self.client.embeddings().create(request).await?;
+        
+        Ok(response
+            .data
+            .first()
+            .ok_or_else(|| anyhow::anyhow!("No embedding returned by the local provider"))?
+            .embedding
+            .clone())
+    }
 }
 
 pub struct KnowledgeService {
-    qdrant_client: Qdrant,
+    vector_store: VectorStore,
     openai_client: Client<OpenAIConfig>,
     collection_name: String,
+    local_embeddings: Option<LocalEmbeddings>,
+    residency: Arc<ResidencyPolicy>,
+    cipher: Option<PayloadCipher>,
+    search_latency: Arc<RecentLatency>,
+    // Pacing and checkpoints of the maintenance jobs
+    scroll_config: ScrollConfig,
+    // Where re-indexed documents record their revisions
+    revisions: Option<Arc<RevisionStore>>,
+    point_ids: PointIdStrategy,
+    // Full-text copy of the readable chunks, searched when the vector
+    // search is slow or down
+    keywords: Option<Arc<KeywordIndex>>,
+}
+
+// Outcome of re-indexing a new version of a stored document
+#[derive(Debug, Serialize)]
+pub struct Reindexed {
+    pub summary: DiffSummary,
+    // Chunk indices that were embedded again
+    pub changed_chunks: Vec<usize>,
+    pub total_chunks: usize,
+    // The revision every chunk of the document carries now
+    pub current_revision: u64,
+    pub updated_at: Option<chrono::DateTime<chrono::Utc>>,
+    // None when nothing changed or no revision store is configured
+    pub revision: Option<DocumentRevision>,
+}
+
+// Outcome of correcting the text of one chunk in place
+#[derive(Debug, Serialize)]
+pub struct ChunkCorrection {
+    pub document_id: String,
+    pub chunk_index: usize,
+    pub summary: DiffSummary,
+    // How far the offsets of the following chunks moved
+    pub offset_shift: i64,
+    // None when the text did not change or no revision store is configured
+    pub revision: Option<DocumentRevision>,
+}
+
+// Qdrant client using the gRPC port (6334)
+fn qdrant_client(http: &HttpClientFactory) -> Result<Qdrant> {
+    http.qdrant(QDRANT_URL)
+        .build()
+        .context("Failed to create Qdrant client")
+}
+
+// The collections the service writes to and the vector size of each, given
+// the configured embedding providers
+pub fn expected_collections() -> Result<Vec<(String, u64)>> {
+    let mut collections = vec![(COLLECTION_NAME.to_string(), EMBEDDING_DIMENSION)];
+    if let Some(local) = LocalEmbeddings::from_env()? {
+        collections.push((LOCAL_COLLECTION_NAME.to_string(), local.dimension));
+    }
+    Ok(collections)
+}
+
+// A collection and the embedding model and vector size of its points
+#[derive(Debug, Clone, PartialEq)]
+pub struct EmbeddedCollection {
+    pub name: String,
+    pub embedding_model: String,
+    pub dimension: u64,
+}
+
+fn embedded_collections(local: Option<&LocalEmbeddings>) -> Vec<EmbeddedCollection> {
+    let mut collections = vec![EmbeddedCollection {
+        name: COLLECTION_NAME.to_string(),
+        embedding_model: EMBEDDING_MODEL.to_string(),
+        dimension: EMBEDDING_DIMENSION,
+    }];
+    if let Some(local) = local {
+        collections.push(EmbeddedCollection {
+            name: LOCAL_COLLECTION_NAME.to_string(),
+            embedding_model: local.model.clone(),
+            dimension: local.dimension,
+        });
+    }
+    collections
+}
+
+// The collections a server with the configured providers writes to, and the
+// Qdrant store holding them; for maintenance commands run outside the server
+pub fn configured_vector_store(http: &HttpClientFactory) -> Result<(VectorStore, Vec<EmbeddedCollection>)> {
+    let local = LocalEmbeddings::from_env()?;
+    Ok((VectorStore::Qdrant(qdrant_client(http)?), embedded_collections(local.as_ref())))
+}
+
+// Vector size of an existing collection; None when it does not exist
+pub async fn collection_dimension(name: &str, http: &HttpClientFactory) -> Result<Option<u64>> {
+    use qdrant_client::qdrant::vectors_config::Config;
+
+    let client = qdrant_client(http)?;
+    let collections = client.list_collections().await?;
+    if !collections.collections.iter().any(|c| c.name == name) {
+        return Ok(None);
+    }
+
+    let info = client.collection_info(name).await?;
+    let params = info
+        .result
+        .and_then(|r| r.config)
+        .and_then(|c| c.params)
+        .and_then(|p| p.vectors_config)
+        .and_then(|v| v.config);
+    match params {
+        Some(Config::Params(params)) => Ok(Some(params.size)),
+        _ => anyhow::bail!("collection {} has no single unnamed vector", name),
+    }
 }
 
 impl KnowledgeService {
-    pub async fn new(openai_api_key: Option<String>) -> Result<Self> {
-        // Initialize Qdrant client using gRPC port (6334)
-        let qdrant_client = Qdrant::from_url("http://localhost:6334")
-            .timeout(std::time::Duration::from_secs(10))
-            .build()
-            .context("Failed to create Qdrant client")?;
-        
+    pub async fn new(
+        openai_api_key: Option<String>,
+        residency: Arc<ResidencyPolicy>,
+        http: &HttpClientFactory,
+    ) -> Result<Self> {
         // Initialize OpenAI client for embeddings
         let config = if let Some(key) = openai_api_key {
             OpenAIConfig::new().with_api_key(key)
         } else {
             OpenAIConfig::new() // Uses OPENAI_API_KEY env var
         };
-        let openai_client = Client::with_config(config);
         
+        let service = Self::with_backends(
+            VectorStore::Qdrant(qdrant_client(http)?),
+            config,
+            LocalEmbeddings::from_env()?,
+            residency,
+        )
+        .await?;
+        // Only the cloud client goes through the proxy; the local embedding
+        // server is on this machine
+        Ok(Self {
+            openai_client: service.openai_client.with_http_client(http.client(ClientKind::OpenAi)?),
+            ..service
+        })
+    }
+    
+    // A service over the given vector store and embedding endpoint, e.g. the
+    // in-memory store and fake provider of the ephemeral profile
+    pub async fn with_backends(
+        vector_store: VectorStore,
+        embedding_config: OpenAIConfig,
+        local_embeddings: Option<LocalEmbeddings>,
+        residency: Arc<ResidencyPolicy>,
+    ) -> Result<Self> {
         let service = Self {
-            qdrant_client,
-            openai_client,
+            vector_store,
+            openai_client: Client::with_config(embedding_config),
             collection_name: COLLECTION_NAME.to_string(),
+            local_embeddings,
+            residency,
+            cipher: None,
+            search_latency: Arc::new(RecentLatency::new(SEARCH_LATENCY_WINDOW)),
+            scroll_config: ScrollConfig::from_env(),
+            revisions: None,
+            point_ids: PointIdStrategy::from_env(),
+            keywords: None,
         };
         
-        // Ensure collection exists
-        service.ensure_collection().await?;
+        // Ensure collections exist
+        service.vector_store.ensure_collection(&service.collection_name, EMBEDDING_DIMENSION).await?;
+        if let Some(local) = &service.local_embeddings {
+            service.vector_store.ensure_collection(LOCAL_COLLECTION_NAME, local.dimension).await?;
+        }
+        for collection in service.collections() {
+            service.vector_store.ensure_keyword_index(collection, trust::TRUST_FIELD).await?;
+            service.vector_store.ensure_keyword_index(collection, "tags").await?;
+        }
         
         Ok(service)
     }
     
-    async fn ensure_collection(&self) -> Result<()> {
-        // Check if collection exists
-        let collections = self.qdrant_client.list_collections().await?;
-        let exists = collections.collections.iter()
-            .any(|c| c.name == self.collection_name);
+    // Encrypt the text of memory facts and sensitive documents before it is
+    // stored (see payload_crypto)
+    pub fn with_payload_cipher(mut self, cipher: Option<PayloadCipher>) -> Self {
+        self.cipher = cipher;
+        self
+    }
+    
+    pub fn with_scroll_config(mut self, config: ScrollConfig) -> Self {
+        self.scroll_config = config;
+        self
+    }
+    
+    pub fn with_revisions(mut self, revisions: Arc<RevisionStore>) -> Self {
+        self.revisions = Some(revisions);
+        self
+    }
+    
+    pub fn with_point_ids(mut self, point_ids: PointIdStrategy) -> Self {
+        self.point_ids = point_ids;
+        self
+    }
+    
+    pub fn with_keyword_index(mut self, keywords: Arc<KeywordIndex>) -> Self {
+        self.keywords = Some(keywords);
+        self
+    }
+    
+    // Chunks whose text is stored encrypted stay out of the keyword index
+    fn keyword_indexed(&self, tags: &[String]) -> bool {
+        self.cipher.is_none() || !payload_crypto::should_encrypt(tags)
+    }
+    
+    // Replace what the keyword index holds for the document. The index is
+    // only a fallback, so failing to update it does not fail the write
+    async fn index_keywords(&self, document_id: &str, chunks: Option<Vec<Document>>) {
+        let Some(keywords) = &self.keywords else {
+            return;
+        };
+        let result = async {
+            let chunks = match chunks {
+                Some(chunks) => chunks,
+                None => self.document_chunks(document_id).await?,
+            };
+            let readable: Vec<Document> = chunks.into_iter().filter(|chunk| self.keyword_indexed(&chunk.tags)).collect();
+            keywords.replace_document(document_id, &readable).await
+        }
+        .await;
+        if let Err(e) = result {
+            warn!("Failed to update the keyword index for document {}: {}", document_id, e);
+        }
+    }
+    
+    // Chunks containing the query's words, from the keyword index; nothing
+    // without one
+    pub async fn search_keywords(&self, query: &str, limit: usize, tags_filter: Option<&TagFilter>) -> Result<Vec<DocumentMatch>> {
+        match &self.keywords {
+            Some(keywords) => keywords.search(query, limit, tags_filter).await,
+            None => Ok(Vec::new()),
+        }
+    }
+    
+    // Add the chunks stored before the keyword index existed, or while it
+    // could not be written. Returns the number of chunks added, or in a dry
+    // run the number of readable chunks looked at
+    pub async fn fill_keyword_index(&self) -> Result<usize> {
+        let Some(keywords) = &self.keywords else {
+            return Ok(0);
+        };
+        let mut added = 0;
         
-        if !exists {
-            info!("Creating Qdrant collection: {}", self.collection_name);
+        for collection in self.collections() {
+            let mut scroll = ScrollIterator::new(&self.vector_store, &self.scroll_config, "fill_keyword_index", collection)
+                .with_filter(PayloadFilter::default().and_not("kind", "annotation"))
+                .with_latency(self.search_latency.clone());
             
-            // Create collection with proper vector configuration
-            let create_collection = CreateCollectionBuilder::new(&self.collection_name)
-                .vectors_config(VectorParamsBuilder::new(EMBEDDING_DIMENSION, Distance::Cosine));
+            while let Some(batch) = scroll.next_batch().await? {
+                let chunks: Vec<Document> = batch
+                    .into_iter()
+                    .filter(|point| !point.payload.contains_key(ENCRYPTED_CONTENT_FIELD))
+                    .map(|point| document_from_payload(&point.payload))
+                    .filter(|chunk| !chunk.id.is_empty() && self.keyword_indexed(&chunk.tags))
+                    .collect();
+                if scroll.is_dry_run() {
+                    added += chunks.len();
+                } else {
+                    added += keywords.add_missing(&chunks).await?;
+                }
+            }
+        }
+        
+        if added > 0 && self.scroll_config.dry_run {
+            info!("Dry run: looked at {} chunks for the keyword index", added);
+        } else if added > 0 {
+            info!("Added {} chunks to the keyword index", added);
+        }
+        Ok(added)
+    }
+    
+    // The id a new point for the chunk gets
+    fn new_point_id(&self, document_id: &str, chunk_index: usize) -> String {
+        match self.point_ids {
+            PointIdStrategy::Deterministic => chunk_point_id(document_id, chunk_index),
+            PointIdStrategy::Random => Uuid::new_v4().to_string(),
+        }
+    }
+    
+    // Replace `content` with its ciphertext when the tags call for it. Without
+    // a configured key the text is stored as is
+    fn seal_content(&self, payload: &mut serde_json::Value, tags: &[String]) -> Result<()> {
+        let Some(cipher) = self.cipher.as_ref().filter(|_| payload_crypto::should_encrypt(tags)) else {
+            return Ok(());
+        };
+        let content = payload["content"].as_str().unwrap_or_default();
+        let sealed = cipher.encrypt(LOCAL_OWNER, content)?;
+        payload["content"] = serde_json::json!("");
+        payload[ENCRYPTED_CONTENT_FIELD] = serde_json::json!(sealed);
+        payload[KEY_VERSION_FIELD] = serde_json::json!(payload_crypto::version_label(cipher.current_version()));
+        Ok(())
+    }
+    
+    // Put the plaintext back into `content`. Plaintext payloads pass through;
+    // false when the payload is encrypted and cannot be read with the
+    // configured keys
+    fn open_payload(&self, payload: &mut PointPayload) -> bool {
+        let Some(sealed) = payload.get(ENCRYPTED_CONTENT_FIELD).and_then(|v| v.as_str()).cloned() else {
+            return true;
+        };
+        let opened = match &self.cipher {
+            Some(cipher) => cipher.decrypt(LOCAL_OWNER, &sealed),
+            None => Err(anyhow::anyhow!("no payload encryption key is configured")),
+        };
+        match opened {
+            Ok(content) => {
+                payload.insert("content".to_string(), Value::from(content));
+                true
+            }
+            Err(e) => {
+                warn!("Cannot read encrypted chunk: {}", e);
+                false
+            }
+        }
+    }
+    
+    // Re-encrypt every payload sealed under an older master key with the
+    // current one, so retired keys can be removed from the config. Returns
+    // the number of points rewritten, or in a dry run the number that would be
+    pub async fn rotate_payload_keys(&self) -> Result<usize> {
+        let Some(cipher) = &self.cipher else {
+            return Ok(0);
+        };
+        let current = payload_crypto::version_label(cipher.current_version());
+        let mut rotated = 0;
+        
+        for collection in self.collections() {
+            // Rewritten points drop out of the filter, so the scroll only ever
+            // sees what is left to do
+            let mut scroll = ScrollIterator::new(&self.vector_store, &self.scroll_config, "rotate_payload_keys", collection)
+                .with_filter(PayloadFilter::default().and_not(KEY_VERSION_FIELD, current.clone()))
+                .with_scope(current.clone())
+                .with_latency(self.search_latency.clone());
             
-            self.qdrant_client
-                .create_collection(create_collection)
-                .await?;
+            while let Some(batch) = scroll.next_batch().await? {
+                for point in batch {
+                    let Some(sealed) = point.payload.get(ENCRYPTED_CONTENT_FIELD).and_then(|v| v.as_str()) else {
+                        continue;
+                    };
+                    let Some(resealed) = cipher.reencrypt(LOCAL_OWNER, sealed)? else {
+                        continue;
+                    };
+                    rotated += 1;
+                    if scroll.is_dry_run() {
+                        continue;
+                    }
+                    let payload: Payload = serde_json::json!({
+                        ENCRYPTED_CONTENT_FIELD: resealed,
+                        KEY_VERSION_FIELD: current,
+                    }).try_into()?;
+                    self.vector_store
+                        .set_payload(collection, &point.id, payload)
+                        .await?;
+                }
+            }
+        }
+        
+        if rotated > 0 && self.scroll_config.dry_run {
+            info!("Dry run: {} chunks would be re-encrypted under payload key {}", rotated, current);
+        } else if rotated > 0 {
+            info!("Re-encrypted {} chunks under payload key {}", rotated, current);
+        }
+        Ok(rotated)
+    }
+    
+    // Move chunks stored under random point ids to the deterministic id of
+    // their document and index, keeping vector and payload. A document
+    // stored twice under random ids had a point per copy; the copies end up
+    // as one. Returns the number of points moved, or in a dry run the number
+    // that would be
+    pub async fn migrate_point_ids(&self) -> Result<usize> {
+        if self.point_ids != PointIdStrategy::Deterministic {
+            return Ok(0);
+        }
+        let mut migrated = 0;
+        
+        for collection in self.collections() {
+            // Notes keep their annotation ids. Moved points either sort before
+            // the scroll position or are skipped when the scroll reaches them
+            let mut scroll = ScrollIterator::new(&self.vector_store, &self.scroll_config, "migrate_point_ids", collection)
+                .with_filter(PayloadFilter::default().and_not("kind", "annotation"))
+                .with_latency(self.search_latency.clone());
             
-            info!("Collection created successfully");
+            while let Some(batch) = scroll.next_batch().await? {
+                let moves: HashMap<String, String> = batch
+                    .iter()
+                    .map(|point| (point.id.clone(), document_from_payload(&point.payload)))
+                    .filter(|(_, chunk)| !chunk.id.is_empty())
+                    .map(|(id, chunk)| (id, chunk_point_id(&chunk.id, chunk.chunk_index)))
+                    .filter(|(id, target)| id != target)
+                    .collect();
+                migrated += moves.len();
+                if moves.is_empty() || scroll.is_dry_run() {
+                    continue;
+                }
+                
+                let old_ids: Vec<String> = moves.keys().cloned().collect();
+                let mut points = self.vector_store.get_points(collection, &old_ids).await?;
+                for point in &mut points {
+                    point.id = moves[&point.id].clone();
+                }
+                self.vector_store.upsert(collection, points).await?;
+                self.vector_store.delete_points(collection, &old_ids).await?;
+            }
         }
         
-        Ok(())
+        if migrated > 0 && self.scroll_config.dry_run {
+            info!("Dry run: {} chunks would move to deterministic point ids", migrated);
+        } else if migrated > 0 {
+            info!("Moved {} chunks to deterministic point ids", migrated);
+        }
+        Ok(migrated)
     }
     
     // Generate embeddings using OpenAI
@@ -149,8 +789,40 @@ impl KnowledgeService {
         Ok(embedding)
     }
     
+    // Embed text belonging to a document with these tags, using the local
+    // provider when the residency policy keeps them off the cloud
+    pub async fn embed_for(&self, text: &str, tags: &[String]) -> Result<Vec<f32>> {
+        let Some(restriction) = self.residency.check(tags, ProviderClass::Cloud) else {
+            return self.generate_embedding(text).await;
+        };
+        match &self.local_embeddings {
+            Some(local) => local.embed(text).await,
+            None => anyhow::bail!(
+                "Document is {}, but no local embedding provider is configured (set LOCAL_EMBEDDING_API_BASE)",
+                restriction.reason()
+            ),
+        }
+    }
+    
+    // The collection holding chunks of a document with these tags
+    fn collection_for(&self, tags: &[String]) -> &str {
+        if self.local_embeddings.is_some() && self.residency.check(tags, ProviderClass::Cloud).is_some() {
+            LOCAL_COLLECTION_NAME
+        } else {
+            &self.collection_name
+        }
+    }
+    
+    fn collections(&self) -> Vec<&str> {
+        let mut collections = vec![self.collection_name.as_str()];
+        if self.local_embeddings.is_some() {
+            collections.push(LOCAL_COLLECTION_NAME);
+        }
+        collections
+    }
+    
     // Simple text chunking
-    fn chunk_text(&self, text: &str, max_size: usize) -> Vec<String> {
+    pub(crate) fn chunk_text(&self, text: &str, max_size: usize) -> Vec<String> {
         let mut chunks = Vec::new();
         let mut current_chunk = String::new();
         
@@ -181,6 +853,7 @@ impl KnowledgeService {
         content: String,
         source: String,
         tags: Vec<String>,
+        trust_level: TrustLevel,
     ) -> Result<DocumentUploadResponse> {
         let document_id = Uuid::new_v4().to_string();
         let chunks = self.chunk_text(&content, MAX_CHUNK_SIZE);
@@ -188,54 +861,14 @@ impl KnowledgeService {
         
         info!("Storing document '{}' with {} chunks", title, total_chunks);
         
-        let mut points = Vec::new();
-        
-        for (index, chunk) in chunks.iter().enumerate() {
+        let mut embedded = Vec::with_capacity(total_chunks);
+        for chunk in chunks {
             // Generate embedding for chunk
-            let embedding = self.generate_embedding(chunk).await?;
-            
-            // Create document metadata
-            let document = Document {
-                id: document_id.clone(),
-                title: title.clone(),
-                content: chunk.clone(),
-                chunk_index: index,
-                total_chunks,
-                source: source.clone(),
-                tags: tags.clone(),
-                created_at: chrono::Utc::now(),
-            };
-            
-            // Create payload for Qdrant
-            let payload: Payload = serde_json::json!({
-                "id": document.id,
-                "title": document.title,
-                "content": document.content,
-                "chunk_index": document.chunk_index,
-                "total_chunks": document.total_chunks,
-                "source": document.source,
-                "created_at": document.created_at.to_rfc3339(),
-                "tags": document.tags,
-            }).try_into()?;
-            
-            // Create point for Qdrant - use a unique UUID for each chunk
-            let point_id = Uuid::new_v4().to_string();
-            
-            points.push(PointStruct::new(
-                point_id,
-                embedding,
-                payload,
-            ));
+            let embedding = self.embed_for(&chunk, &tags).await?;
+            embedded.push((chunk, embedding));
         }
         
-        // Upload points to Qdrant
-        let upsert_points = UpsertPointsBuilder::new(&self.collection_name, points);
-        
-        self.qdrant_client
-            .upsert_points(upsert_points)
-            .await?;
-        
-        info!("Successfully stored document '{}'", title);
+        self.store_chunks(&document_id, &title, &source, &tags, trust_level, embedded).await?;
         
         Ok(DocumentUploadResponse {
             document_id,
@@ -245,216 +878,925 @@ impl KnowledgeService {
         })
     }
     
-    // Search documents using semantic similarity
-    pub async fn search_documents(
+    // Store already embedded chunks under a caller-chosen document id
+    pub async fn store_chunks(
         &self,
-        query: &str,
-        limit: usize,
-        score_threshold: f32,
-        _tags_filter: Option<Vec<String>>,
-    ) -> Result<Vec<DocumentMatch>> {
-        debug!("Searching for: {}", query);
-        
-        // Generate embedding for query
-        let query_embedding = self.generate_embedding(query).await?;
+        document_id: &str,
+        title: &str,
+        source: &str,
+        tags: &[String],
+        trust_level: TrustLevel,
+        chunks: Vec<(String, Vec<f32>)>,
+    ) -> Result<()> {
+        let chunks = chunks.into_iter().map(|(chunk, embedding)| (chunk, embedding, None)).collect();
+        self.store_paged_chunks(document_id, title, source, tags, trust_level, chunks).await
+    }
+    
+    // Like store_chunks, with the page of the uploaded file each chunk was
+    // taken from
+    pub async fn store_paged_chunks(
+        &self,
+        document_id: &str,
+        title: &str,
+        source: &str,
+        tags: &[String],
+        trust_level: TrustLevel,
+        chunks: Vec<(String, Vec<f32>, Option<usize>)>,
+    ) -> Result<()> {
+        let total_chunks = chunks.len();
+        let created_at = chrono::Utc::now();
+        let target = self.collection_for(tags);
+        // With deterministic ids a document stored again overwrites its
+        // points; the ones past its new length, or in a collection its tags
+        // no longer lead to, are removed afterwards
+        let previous = match self.point_ids {
+            PointIdStrategy::Deterministic => self.stored_chunk_counts(document_id).await?,
+            PointIdStrategy::Random => Vec::new(),
+        };
+        let mut points = Vec::with_capacity(total_chunks);
+        let mut indexed = Vec::new();
+        let mut offset = 0;
         
-        // Search in Qdrant
-        let search_points = SearchPointsBuilder::new(
-            &self.collection_name,
-            query_embedding,
-            limit as u64,
-        )
-        .score_threshold(score_threshold)
-        .with_payload(true);
+        for (index, (chunk, embedding, page)) in chunks.into_iter().enumerate() {
+            let chunk_len = chunk.len();
+            // Create document metadata
+            let document = Document {
+                id: document_id.to_string(),
+                title: title.to_string(),
+                content: chunk,
+                chunk_index: index,
+                total_chunks,
+                source: source.to_string(),
+                tags: tags.to_vec(),
+                trust_level,
+                created_at,
+                offset,
+                manually_corrected: false,
+                revision: first_revision(),
+                updated_at: None,
+                page,
+            };
+            offset += chunk_len;
+            points.push(self.chunk_point(self.new_point_id(document_id, index), &document, embedding)?);
+            if self.keywords.is_some() {
+                indexed.push(document);
+            }
+        }
         
-        let search_result = self.qdrant_client
-            .search_points(search_points)
+        self.vector_store
+            .upsert(target, points)
             .await?;
+        for (collection, count) in previous {
+            let keep = if collection == target { total_chunks } else { 0 };
+            let leftover: Vec<String> = (keep..count).map(|index| chunk_point_id(document_id, index)).collect();
+            self.vector_store.delete_points(collection, &leftover).await?;
+        }
+        self.index_keywords(document_id, Some(indexed)).await;
         
-        // Convert results to DocumentMatch
-        let documents: Vec<DocumentMatch> = search_result
-            .result
-            .into_iter()
-            .filter_map(|point| {
-                let payload = point.payload;
-                let title = payload.get("title")
-                    .and_then(|v| v.as_str())
-                    .map(|s| s.to_string())
-                    .unwrap_or_else(|| "Unknown".to_string());
-                
-                let content = payload.get("content")
-                    .and_then(|v| v.as_str())
-                    .map(|s| s.to_string())
-                    .unwrap_or_else(|| String::new());
-                
-                let id = payload.get("id")
-                    .and_then(|v| v.as_str())
-                    .map(|s| s.to_string())
-                    .unwrap_or_else(|| String::new());
-                
-                let chunk_index = payload.get("chunk_index")
-                    .and_then(|v| v.as_integer())
-                    .map(|i| i as usize)
-                    .unwrap_or(0);
-                
-                let source = payload.get("source")
-                    .and_then(|v| v.as_str())
-                    .map(|s| s.to_string())
-                    .unwrap_or_else(|| "Unknown".to_string());
-                
-                Some(DocumentMatch {
-                    id,
-                    title,
-                    content,
-                    score: point.score,
-                    chunk_index,
-                    source,
-                })
-            })
-            .collect();
-        
-        info!("Found {} relevant documents", documents.len());
-        
-        Ok(documents)
+        info!("Successfully stored document '{}'", title);
+        Ok(())
     }
     
-    // Get collection statistics
-    pub async fn get_stats(&self) -> Result<serde_json::Value> {
-        let collection_info = self.qdrant_client
-            .collection_info(&self.collection_name)
-            .await?;
+    // The point for one chunk; a corrected chunk keeps its point id so the
+    // upsert replaces it
+    fn chunk_point(&self, id: String, document: &Document, embedding: Vec<f32>) -> Result<VectorPoint> {
+        let mut payload = serde_json::json!({
+            "id": document.id,
+            "title": document.title,
+            "content": document.content,
+            "chunk_index": document.chunk_index,
+            "total_chunks": document.total_chunks,
+            "source": document.source,
+            "created_at": document.created_at.to_rfc3339(),
+            "tags": document.tags,
+            "trust_level": document.trust_level,
+            "offset": document.offset,
+            "manually_corrected": document.manually_corrected,
+            "revision": document.revision,
+            "updated_at": document.updated_at.map(|at| at.to_rfc3339()),
+            "page": document.page,
+        });
+        self.seal_content(&mut payload, &document.tags)?;
         
-        Ok(serde_json::json!({
-            "collection": self.collection_name,
-            "vectors_count": collection_info.result.as_ref().and_then(|r| r.vectors_count).unwrap_or(0),
-            "indexed_vectors_count": collection_info.result.as_ref().and_then(|r| r.indexed_vectors_count).unwrap_or(0),
-        }))
+        Ok(VectorPoint {
+            id,
+            vector: embedding,
+            payload: payload.try_into()?,
+        })
     }
     
-    // List all documents in the knowledge base
-    pub async fn list_all_documents(&self) -> Result<Vec<Document>> {
-        use qdrant_client::qdrant::ScrollPointsBuilder;
-        
-        let scroll_points = ScrollPointsBuilder::new(&self.collection_name)
-            .limit(1000)
-            .with_payload(true)
-            .with_vectors(false)
-            .build();
-        
-        let scroll_result = self.qdrant_client
-            .scroll(scroll_points)
-            .await?;
-        
-        let documents: Vec<Document> = scroll_result
-            .result
+    // Replace the text of a stored document, and its tags when given,
+    // keeping its id, title, source and trust level. None when the document
+    // is unknown
+    pub async fn update_document(
+        &self,
+        document_id: &str,
+        content: &str,
+        tags: Option<Vec<String>>,
+    ) -> Result<Option<Reindexed>> {
+        let Some(current) = self.document_chunks(document_id).await?.into_iter().next() else {
+            return Ok(None);
+        };
+        let tags = tags.unwrap_or(current.tags);
+        self.reindex_document(document_id, &current.title, content, &current.source, &tags, current.trust_level)
+            .await
+    }
+    
+    // Re-index a new version of a stored document under the same id. Chunks
+    // whose text did not change keep their points and embeddings, so only
+    // the changed ones are embedded again; the line diff against the stored
+    // text is recorded as a revision, and every chunk moves to the
+    // document's next revision. None when the document is unknown
+    pub async fn reindex_document(
+        &self,
+        document_id: &str,
+        title: &str,
+        content: &str,
+        source: &str,
+        tags: &[String],
+        trust_level: TrustLevel,
+    ) -> Result<Option<Reindexed>> {
+        let stored = self.stored_chunks(document_id).await?;
+        if stored.is_empty() {
+            return Ok(None);
+        }
+        
+        let readable: Vec<&Document> = stored.iter().filter_map(|(_, _, chunk)| chunk.as_ref()).collect();
+        let previous_text: String = readable.iter().map(|chunk| chunk.content.as_str()).collect();
+        // Tags decide the collection, the embedding provider and whether the
+        // text is encrypted, so points are only reused while they stay the same
+        let target = self.collection_for(tags);
+        let reusable: Vec<(String, String)> = match readable.first() {
+            Some(first) if first.tags == tags => stored
+                .iter()
+                .filter(|(collection, _, _)| *collection == target)
+                .filter_map(|(_, id, chunk)| chunk.as_ref().map(|c| (id.clone(), c.content.clone())))
+                .collect(),
+            _ => Vec::new(),
+        };
+        
+        let chunks = self.chunk_text(content, MAX_CHUNK_SIZE);
+        let total_chunks = chunks.len();
+        let offsets = chunk_offsets(chunks.iter().map(|chunk| chunk.as_str()));
+        let plan = document_revisions::plan_chunks(&reusable, &chunks);
+        let summary = document_revisions::diff_lines(&previous_text, &chunks.concat());
+        let changed = !summary.is_empty() || !plan.embed.is_empty();
+        let latest = readable.iter().map(|chunk| chunk.revision).max().unwrap_or_else(first_revision);
+        let (revision, updated_at) = if changed {
+            (latest + 1, Some(chrono::Utc::now()))
+        } else {
+            (latest, readable.iter().filter_map(|chunk| chunk.updated_at).max())
+        };
+        
+        // Everything is embedded before the index changes, so a failed
+        // embedding leaves the previous version in place. New chunks keep the
+        // document's creation time; `updated_at` tells when it changed
+        let created_at = readable.iter().map(|chunk| chunk.created_at).min().unwrap_or_else(chrono::Utc::now);
+        let mut points = Vec::with_capacity(plan.embed.len());
+        for &index in &plan.embed {
+            let document = Document {
+                id: document_id.to_string(),
+                title: title.to_string(),
+                content: chunks[index].clone(),
+                chunk_index: index,
+                total_chunks,
+                source: source.to_string(),
+                tags: tags.to_vec(),
+                trust_level,
+                created_at,
+                offset: offsets[index],
+                manually_corrected: false,
+                revision,
+                updated_at,
+                page: None,
+            };
+            let embedding = self.embed_for(&document.content, tags).await?;
+            points.push(self.chunk_point(self.new_point_id(document_id, index), &document, embedding)?);
+        }
+        
+        // Reused chunks keep their embeddings but are written again, so that
+        // every chunk of the new revision lands in the same upsert and a
+        // failure leaves the previous revision whole. With deterministic ids a
+        // reused chunk that moved to another index moves to that index's
+        // point. Vectors are read before anything is written, as the point a
+        // chunk leaves may be overwritten by another one
+        let reused_ids: Vec<String> = plan.reused.iter().map(|(_, point_id)| point_id.clone()).collect();
+        let mut vectors: HashMap<String, Vec<f32>> = self
+            .vector_store
+            .get_points(target, &reused_ids)
+            .await?
+            .into_iter()
+            .map(|point| (point.id, point.vector))
+            .collect();
+        for (index, point_id) in &plan.reused {
+            let Some(chunk) = stored
+                .iter()
+                .find(|(collection, id, _)| *collection == target && id == point_id)
+                .and_then(|(_, _, chunk)| chunk.as_ref())
+            else {
+                continue;
+            };
+            let document = Document {
+                title: title.to_string(),
+                chunk_index: *index,
+                total_chunks,
+                source: source.to_string(),
+                trust_level,
+                offset: offsets[*index],
+                revision,
+                updated_at,
+                // The new text comes without pages
+                page: None,
+                ..chunk.clone()
+            };
+            let embedding = match vectors.remove(point_id) {
+                Some(vector) => vector,
+                None => self.embed_for(&document.content, tags).await?,
+            };
+            let id = match self.point_ids {
+                PointIdStrategy::Deterministic => chunk_point_id(document_id, *index),
+                PointIdStrategy::Random => point_id.clone(),
+            };
+            points.push(self.chunk_point(id, &document, embedding)?);
+        }
+        let kept: HashSet<String> = points.iter().map(|point| point.id.clone()).collect();
+        if !points.is_empty() {
+            self.vector_store.upsert(target, points).await?;
+        }
+        
+        // Old points go only once the new revision is in place
+        for collection in self.collections() {
+            let stale: Vec<String> = stored
+                .iter()
+                .filter(|(c, id, _)| *c == collection && !(*c == target && kept.contains(id)))
+                .map(|(_, id, _)| id.clone())
+                .collect();
+            self.vector_store.delete_points(collection, &stale).await?;
+        }
+        
+        self.index_keywords(document_id, None).await;
+        
+        let recorded = match &self.revisions {
+            Some(revisions) if changed => Some(
+                revisions
+                    .record(document_id, title, summary.clone(), plan.embed.clone(), total_chunks)
+                    .await?,
+            ),
+            _ => None,
+        };
+        
+        info!(
+            "Re-indexed document '{}': {} of {} chunks embedded, {}",
+            title,
+            plan.embed.len(),
+            total_chunks,
+            summary.headline()
+        );
+        Ok(Some(Reindexed {
+            summary,
+            changed_chunks: plan.embed,
+            total_chunks,
+            current_revision: revision,
+            updated_at,
+            revision: recorded,
+        }))
+    }
+    
+    // Replace the text of one chunk, e.g. to fix an OCR error, without
+    // uploading the document again. Only that chunk is embedded again; it
+    // keeps its point and is flagged as manually corrected, the offsets of
+    // the chunks follow the new text and the edit is recorded as a revision.
+    // None when the document has no readable chunk at that index
+    pub async fn correct_chunk(
+        &self,
+        document_id: &str,
+        chunk_index: usize,
+        content: &str,
+    ) -> Result<Option<ChunkCorrection>> {
+        let stored = self.stored_chunks(document_id).await?;
+        let Some((collection, point_id, Some(chunk))) = stored
+            .iter()
+            .find(|(_, _, chunk)| chunk.as_ref().map(|c| c.chunk_index) == Some(chunk_index))
+        else {
+            return Ok(None);
+        };
+        
+        let readable: Vec<&Document> = stored.iter().filter_map(|(_, _, chunk)| chunk.as_ref()).collect();
+        let previous_text: String = readable.iter().map(|c| c.content.as_str()).collect();
+        let corrected: Vec<&str> = readable
+            .iter()
+            .map(|c| if c.chunk_index == chunk_index { content } else { c.content.as_str() })
+            .collect();
+        let summary = document_revisions::diff_lines(&previous_text, &corrected.concat());
+        let offset_shift = content.len() as i64 - chunk.content.len() as i64;
+        if chunk.content == content {
+            return Ok(Some(ChunkCorrection {
+                document_id: document_id.to_string(),
+                chunk_index,
+                summary,
+                offset_shift,
+                revision: None,
+            }));
+        }
+        
+        let next_revision = readable.iter().map(|c| c.revision).max().unwrap_or_else(first_revision) + 1;
+        let updated_at = chrono::Utc::now();
+        let document = Document {
+            content: content.to_string(),
+            manually_corrected: true,
+            revision: next_revision,
+            updated_at: Some(updated_at),
+            ..chunk.clone()
+        };
+        let embedding = self.embed_for(content, &document.tags).await?;
+        let point = self.chunk_point(point_id.clone(), &document, embedding)?;
+        self.vector_store.upsert(collection, vec![point]).await?;
+        
+        // The other chunks move to the new revision with it. Offsets are
+        // recomputed rather than shifted, which also fills them in for chunks
+        // stored before offsets were recorded
+        let offsets = chunk_offsets(corrected.iter().copied());
+        for ((collection, point_id, chunk), offset) in stored.iter().filter(|(_, _, c)| c.is_some()).zip(offsets) {
+            if chunk.as_ref().is_some_and(|c| c.chunk_index != chunk_index) {
+                let payload: Payload = serde_json::json!({
+                    "offset": offset,
+                    "revision": next_revision,
+                    "updated_at": updated_at.to_rfc3339(),
+                })
+                .try_into()?;
+                self.vector_store.set_payload(collection, point_id, payload).await?;
+            }
+        }
+        self.index_keywords(document_id, None).await;
+        
+        let revision = match &self.revisions {
+            Some(revisions) => Some(
+                revisions
+                    .record(document_id, &chunk.title, summary.clone(), vec![chunk_index], chunk.total_chunks)
+                    .await?,
+            ),
+            None => None,
+        };
+        
+        info!("Corrected chunk {} of document '{}': {}", chunk_index, chunk.title, summary.headline());
+        Ok(Some(ChunkCorrection {
+            document_id: document_id.to_string(),
+            chunk_index,
+            summary,
+            offset_shift,
+            revision,
+        }))
+    }
+    
+    // Every point of a document's text in chunk order, as (collection, point
+    // id, chunk), without the chunk when it cannot be decrypted
+    async fn stored_chunks(&self, document_id: &str) -> Result<Vec<(&str, String, Option<Document>)>> {
+        let filter = PayloadFilter::matching("id", document_id).and_not("kind", "annotation");
+        let mut stored = Vec::new();
+        for collection in self.collections() {
+            let points = self.vector_store
+                .scroll(collection, Some(&filter), MAX_DOCUMENT_CHUNKS)
+                .await?;
+            for mut point in points {
+                let chunk = self.open_payload(&mut point.payload).then(|| document_from_payload(&point.payload));
+                stored.push((collection, point.id, chunk));
+            }
+        }
+        stored.sort_by_key(|(_, _, chunk)| chunk.as_ref().map_or(usize::MAX, |c| c.chunk_index));
+        Ok(stored)
+    }
+    
+    // Search documents using semantic similarity, among those carrying the
+    // filter's tags when one is given
+    pub async fn search_documents(
+        &self,
+        query: &str,
+        limit: usize,
+        score_threshold: f32,
+        tags_filter: Option<TagFilter>,
+    ) -> Result<Vec<DocumentMatch>> {
+        debug!("Searching for: {}", query);
+        let filter = tags_filter.and_then(|tags| tags.to_payload_filter());
+        
+        let query_embedding = self.generate_embedding(query).await?;
+        let mut documents = self
+            .search_collection(&self.collection_name, query_embedding, limit, score_threshold, filter.as_ref())
+            .await?;
+        // Restricted documents live in the local collection and are searched
+        // with a locally embedded query
+        if let Some(local) = &self.local_embeddings {
+            let query_embedding = local.embed(query).await?;
+            documents.extend(
+                self.search_collection(LOCAL_COLLECTION_NAME, query_embedding, limit, score_threshold, filter.as_ref())
+                    .await?,
+            );
+        }
+        documents.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
+        documents.truncate(limit);
+        
+        info!("Found {} relevant documents", documents.len());
+        
+        Ok(documents)
+    }
+    
+    async fn search_collection(
+        &self,
+        collection: &str,
+        query_embedding: Vec<f32>,
+        limit: usize,
+        score_threshold: f32,
+        filter: Option<&PayloadFilter>,
+    ) -> Result<Vec<DocumentMatch>> {
+        let started = std::time::Instant::now();
+        let search_result = self.vector_store
+            .search(collection, query_embedding, limit, score_threshold, filter)
+            .await;
+        self.search_latency.observe(started.elapsed());
+        let search_result = search_result?;
+        
+        // Convert results to DocumentMatch. Chunks that cannot be decrypted
+        // are left out rather than failing the whole search
+        Ok(search_result
             .into_iter()
+            .filter_map(|mut point| self.open_payload(&mut point.payload).then_some(point))
             .map(|point| {
-                let payload = point.payload;
-                Document {
-                    id: payload.get("id")
-                        .and_then(|v| v.as_str())
-                        .map(|s| s.to_string())
-                        .unwrap_or_else(|| String::new()),
-                    title: payload.get("title")
-                        .and_then(|v| v.as_str())
-                        .map(|s| s.to_string())
-                        .unwrap_or_else(|| "Unknown".to_string()),
-                    content: payload.get("content")
-                        .and_then(|v| v.as_str())
-                        .map(|s| s.to_string())
-                        .unwrap_or_else(|| String::new()),
-                    chunk_index: payload.get("chunk_index")
-                        .and_then(|v| v.as_integer())
-                        .map(|i| i as usize)
-                        .unwrap_or(0),
-                    total_chunks: payload.get("total_chunks")
-                        .and_then(|v| v.as_integer())
-                        .map(|i| i as usize)
-                        .unwrap_or(1),
-                    source: payload.get("source")
-                        .and_then(|v| v.as_str())
-                        .map(|s| s.to_string())
-                        .unwrap_or_else(|| "Unknown".to_string()),
-                    tags: payload.get("tags")
-                        .and_then(|v| {
-                            // Try to parse the JSON value as an array
-                            if let serde_json::Value::Array(arr) = serde_json::from_str(&v.to_string()).ok()? {
-                                Some(arr.iter()
-                                    .filter_map(|val| val.as_str())
-                                    .map(|s| s.to_string())
-                                    .collect())
-                            } else {
-                                None
-                            }
-                        })
-                        .unwrap_or_default(),
-                    created_at: payload.get("created_at")
-                        .and_then(|v| v.as_str())
-                        .and_then(|s| chrono::DateTime::parse_from_rfc3339(s).ok())
-                        .map(|dt| dt.with_timezone(&chrono::Utc))
-                        .unwrap_or_else(chrono::Utc::now),
+                let document = document_from_payload(&point.payload);
+                let note = note_from_payload(&point.payload);
+                let score = if note.is_some() {
+                    point.score * ANNOTATION_SCORE_BOOST
+                } else {
+                    point.score
+                };
+                
+                DocumentMatch {
+                    id: document.id,
+                    title: document.title,
+                    content: document.content,
+                    score,
+                    chunk_index: document.chunk_index,
+                    source: document.source,
+                    tags: document.tags,
+                    trust_level: document.trust_level,
+                    note,
+                    manually_corrected: document.manually_corrected,
+                    page: document.page,
                 }
             })
-            .collect();
+            .collect())
+    }
+    
+    // Get collection statistics
+    pub async fn get_stats(&self) -> Result<serde_json::Value> {
+        let (vectors_count, indexed_vectors_count) = self.vector_store
+            .count(&self.collection_name)
+            .await?;
+        
+        Ok(serde_json::json!({
+            "collection": self.collection_name,
+            "vectors_count": vectors_count,
+            "indexed_vectors_count": indexed_vectors_count,
+        }))
+    }
+    
+    pub fn vector_store(&self) -> &VectorStore {
+        &self.vector_store
+    }
+    
+    // Every collection the service writes to, with its embedding model
+    pub fn embedded_collections(&self) -> Vec<EmbeddedCollection> {
+        embedded_collections(self.local_embeddings.as_ref())
+    }
+    
+    // The local embedding model documents under local-only residency rules
+    // are embedded with, when one is configured
+    pub fn local_embedding_model(&self) -> Option<&str> {
+        self.local_embeddings.as_ref().map(|local| local.model.as_str())
+    }
+    
+    // A page of the stored documents, newest first and by id among those
+    // stored at the same time, optionally only those with a tag. Only the
+    // first chunk of each document is read, however many chunks it has, and
+    // it is not decrypted: the listing carries no text
+    pub async fn list_documents(&self, offset: usize, limit: usize, tag: Option<&str>) -> Result<DocumentPage> {
+        let mut filter = PayloadFilter::default().and_integer("chunk_index", 0).and_not("kind", "annotation");
+        if let Some(tag) = tag {
+            filter = filter.and("tags", tag);
+        }
+        
+        let mut documents: Vec<DocumentSummary> = Vec::new();
+        for collection in self.collections() {
+            let mut next = None;
+            loop {
+                let (points, next_page) = self.vector_store
+                    .scroll_page(collection, Some(&filter), next.as_deref(), LIST_PAGE_SIZE)
+                    .await?;
+                documents.extend(points.iter().map(|point| DocumentSummary::from(document_from_payload(&point.payload))));
+                match next_page {
+                    Some(next_page) => next = Some(next_page),
+                    None => break,
+                }
+            }
+        }
+        documents.sort_by(|a, b| b.created_at.cmp(&a.created_at).then_with(|| a.id.cmp(&b.id)));
+        // A document is listed once even if a stale first chunk survived in
+        // another collection
+        let mut seen = HashSet::new();
+        documents.retain(|document| seen.insert(document.id.clone()));
+        
+        let total = documents.len();
+        let documents: Vec<DocumentSummary> = documents.into_iter().skip(offset).take(limit).collect();
+        Ok(DocumentPage {
+            has_more: offset + documents.len() < total,
+            documents,
+            total,
+            offset,
+            limit,
+        })
+    }
+    
+    // List all documents in the knowledge base
+    pub async fn list_all_documents(&self) -> Result<Vec<Document>> {
+        let mut documents = Vec::new();
+        for collection in self.collections() {
+            let scroll_result = self.vector_store
+                .scroll(collection, None, 1000)
+                .await?;
+            
+            // User notes share the collection but are not documents
+            documents.extend(
+                scroll_result
+                    .into_iter()
+                    .filter(|point| note_from_payload(&point.payload).is_none())
+                    .map(|mut point| {
+                        self.open_payload(&mut point.payload);
+                        document_from_payload(&point.payload)
+                    }),
+            );
+        }
         
         info!("Listed {} documents from knowledge base", documents.len());
         Ok(documents)
     }
-}
 
-// HTTP Handlers
-pub async fn upload_document_handler(
-    State(state): State<Arc<crate::AppState>>,
-    mut multipart: Multipart,
-) -> impl IntoResponse {
-    // Check if knowledge service is available
-    let knowledge_service = match &state.knowledge_service {
-        Some(service) => service,
-        None => {
-            return (StatusCode::SERVICE_UNAVAILABLE, "Knowledge base service is not available").into_response();
+    // Make the attachments uploaded into one session up to `until` searchable
+    // in another session as well, by adding its tag to their chunks. Returns
+    // the number of documents shared
+    pub async fn share_attachments(
+        &self,
+        from_session: &str,
+        to_session: &str,
+        until: chrono::DateTime<chrono::Utc>,
+    ) -> Result<usize> {
+        let from_tag = retrieval::attachment_tag(from_session);
+        let to_tag = retrieval::attachment_tag(to_session);
+        let mut shared = std::collections::HashSet::new();
+        
+        for collection in self.collections() {
+            let scroll_result = self.vector_store
+                .scroll(collection, Some(&PayloadFilter::matching("tags", from_tag.clone())), 1000)
+                .await?;
+            
+            for point in scroll_result {
+                let document = document_from_payload(&point.payload);
+                if document.created_at > until {
+                    continue;
+                }
+                
+                let mut tags = document.tags;
+                if !tags.contains(&to_tag) {
+                    tags.push(to_tag.clone());
+                }
+                let payload: Payload = serde_json::json!({ "tags": tags }).try_into()?;
+                self.vector_store
+                    .set_payload(collection, &point.id, payload)
+                    .await?;
+                shared.insert(document.id);
+            }
         }
-    };
+        for document_id in &shared {
+            self.index_keywords(document_id, None).await;
+        }
+        
+        Ok(shared.len())
+    }
+
+    // The first chunk of a document, None when nothing carries the id
+    pub async fn find_document(&self, document_id: &str) -> Result<Option<Document>> {
+        let filter = PayloadFilter::matching("id", document_id).and_not("kind", "annotation");
+        
+        for collection in self.collections() {
+            let scroll_result = self.vector_store
+                .scroll(collection, Some(&filter), 1)
+                .await?;
+            
+            if let Some(mut point) = scroll_result.into_iter().next() {
+                self.open_payload(&mut point.payload);
+                return Ok(Some(document_from_payload(&point.payload)));
+            }
+        }
+        
+        Ok(None)
+    }
     
-    let mut title = String::new();
-    let mut content = String::new();
-    let mut source = String::new();
-    let mut tags = Vec::new();
-    
-    while let Some(mut field) = multipart.next_field().await.unwrap() {
-        let name = field.name().unwrap_or("").to_string();
-        let filename = field.file_name().map(|s| s.to_string());
-        let data = field.bytes().await.unwrap_or_default();
-        let value = String::from_utf8_lossy(&data).to_string();
-        
-        match name.as_str() {
-            "title" => title = value,
-            "content" => content = value,
-            "source" => source = value,
-            "tags" => tags = value.split(',').map(|s| s.trim().to_string()).collect(),
-            "file" => {
-                // Handle file upload
-                if let Some(fname) = filename {
-                    source = fname;
-                    content = value;
-                }
+    // Every chunk of a document in order, without notes. Chunks encrypted
+    // under keys that are not configured are left out
+    pub async fn document_chunks(&self, document_id: &str) -> Result<Vec<Document>> {
+        let filter = PayloadFilter::matching("id", document_id).and_not("kind", "annotation");
+        let mut chunks = Vec::new();
+        
+        for collection in self.collections() {
+            let points = self.vector_store
+                .scroll(collection, Some(&filter), MAX_DOCUMENT_CHUNKS)
+                .await?;
+            chunks.extend(points.into_iter().filter_map(|mut point| {
+                self.open_payload(&mut point.payload).then(|| document_from_payload(&point.payload))
+            }));
+        }
+        
+        chunks.sort_by_key(|chunk| chunk.chunk_index);
+        Ok(chunks)
+    }
+    
+    // One chunk of a document's text, read by its deterministic point id.
+    // Chunks still under random ids are found among the document's chunks
+    pub async fn chunk(&self, document_id: &str, chunk_index: usize) -> Result<Option<Document>> {
+        let id = chunk_point_id(document_id, chunk_index);
+        for collection in self.collections() {
+            if let Some(point) = self.vector_store.get_points(collection, &[id.clone()]).await?.pop() {
+                let mut payload = PointPayload::from(point.payload);
+                return Ok(self.open_payload(&mut payload).then(|| document_from_payload(&payload)));
             }
-            _ => {}
         }
+        Ok(self
+            .document_chunks(document_id)
+            .await?
+            .into_iter()
+            .find(|chunk| chunk.chunk_index == chunk_index))
     }
     
-    if title.is_empty() || content.is_empty() {
-        return (StatusCode::BAD_REQUEST, "Title and content are required").into_response();
+    // How many chunks a document has stored in each collection, as its first
+    // chunk's point says. Only points with deterministic ids are seen
+    async fn stored_chunk_counts(&self, document_id: &str) -> Result<Vec<(&str, usize)>> {
+        let first = [chunk_point_id(document_id, 0)];
+        let mut counts = Vec::new();
+        for collection in self.collections() {
+            if let Some(point) = self.vector_store.get_points(collection, &first).await?.pop() {
+                counts.push((collection, document_from_payload(&PointPayload::from(point.payload)).total_chunks));
+            }
+        }
+        Ok(counts)
     }
     
-    match knowledge_service.store_document(title, content, source, tags).await {
-        Ok(response) => Json(response).into_response(),
-        Err(e) => {
-            error!("Failed to store document: {}", e);
-            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to store document").into_response()
+    // Index a user note as its own point. It keeps the annotated document's id
+    // so deleting the document removes its notes as well, and re-indexing the
+    // same note replaces the previous point
+    pub async fn store_note(
+        &self,
+        document: &Document,
+        note: &NoteRef,
+        text: &str,
+        embedding: Vec<f32>,
+    ) -> Result<()> {
+        let mut payload = serde_json::json!({
+            "id": document.id,
+            "title": document.title,
+            "content": text,
+            "chunk_index": note.chunk_index.unwrap_or(0),
+            "total_chunks": document.total_chunks,
+            "source": document.source,
+            "created_at": chrono::Utc::now().to_rfc3339(),
+            "tags": document.tags,
+            "trust_level": document.trust_level,
+            "kind": "annotation",
+            "importance": "high",
+            "annotation_id": note.annotation_id,
+        });
+        if let Some(chunk_index) = note.chunk_index {
+            payload["note_chunk_index"] = serde_json::json!(chunk_index);
+        }
+        self.seal_content(&mut payload, &document.tags)?;
+        let payload: Payload = payload.try_into()?;
+        
+        // Notes are kept with the document's own chunks, and are embedded
+        // under the same residency rules (see `embed_for`)
+        let point = VectorPoint {
+            id: note.annotation_id.clone(),
+            vector: embedding,
+            payload,
+        };
+        
+        self.vector_store
+            .upsert(self.collection_for(&document.tags), vec![point])
+            .await?;
+        
+        debug!("Indexed note {} on document {}", note.annotation_id, document.id);
+        Ok(())
+    }
+    
+    pub async fn delete_note(&self, annotation_id: &str) -> Result<()> {
+        for collection in self.collections() {
+            self.vector_store
+                .delete(collection, &PayloadFilter::matching("annotation_id", annotation_id))
+                .await
+                .context("Failed to delete note point")?;
+        }
+        
+        Ok(())
+    }
+
+    // Set the trust level on every point of a document, its notes included.
+    // Returns the number of points updated; 0 when the document is unknown
+    pub async fn set_trust_level(&self, document_id: &str, trust_level: TrustLevel) -> Result<usize> {
+        let mut updated = 0;
+        
+        for collection in self.collections() {
+            let scroll_result = self.vector_store
+                .scroll(collection, Some(&PayloadFilter::matching("id", document_id)), 1000)
+                .await?;
+            
+            for point in scroll_result {
+                let payload: Payload = serde_json::json!({ "trust_level": trust_level }).try_into()?;
+                self.vector_store
+                    .set_payload(collection, &point.id, payload)
+                    .await?;
+                updated += 1;
+            }
+        }
+        
+        if updated > 0 {
+            self.index_keywords(document_id, None).await;
         }
+        info!("Set trust level of document {} to {}", document_id, trust_level);
+        Ok(updated)
+    }
+
+    // Delete every point of a document, its notes included. Returns the
+    // number of chunks removed; 0 when the document is unknown, in which case
+    // nothing is deleted
+    pub async fn delete_document(&self, document_id: &str) -> Result<usize> {
+        let chunks = PayloadFilter::matching("id", document_id).and_not("kind", "annotation");
+        let mut removed = 0;
+        for collection in self.collections() {
+            removed += self.vector_store
+                .scroll(collection, Some(&chunks), MAX_DOCUMENT_CHUNKS)
+                .await?
+                .len();
+        }
+        if removed == 0 {
+            return Ok(0);
+        }
+        
+        for collection in self.collections() {
+            self.vector_store
+                .delete(collection, &PayloadFilter::matching("id", document_id))
+                .await
+                .context("Failed to delete document points")?;
+        }
+        if let Some(keywords) = &self.keywords {
+            if let Err(e) = keywords.remove_document(document_id).await {
+                warn!("Failed to remove document {} from the keyword index: {}", document_id, e);
+            }
+        }
+
+        info!("Deleted document {} ({} chunks)", document_id, removed);
+        Ok(removed)
+    }
+    
+    // Delete every document stored from `source`, e.g. everything one import
+    // brought in. Returns the ids of the documents deleted
+    pub async fn delete_documents_by_source(&self, source: &str) -> Result<Vec<String>> {
+        let filter = PayloadFilter::matching("source", source).and_not("kind", "annotation");
+        let mut document_ids = BTreeSet::new();
+        for collection in self.collections() {
+            let mut offset = None;
+            loop {
+                let (points, next) = self.vector_store
+                    .scroll_page(collection, Some(&filter), offset.as_deref(), MAX_DOCUMENT_CHUNKS)
+                    .await?;
+                document_ids.extend(
+                    points
+                        .iter()
+                        .map(|point| document_from_payload(&point.payload).id)
+                        .filter(|id| !id.is_empty()),
+                );
+                match next {
+                    Some(next) => offset = Some(next),
+                    None => break,
+                }
+            }
+        }
+        
+        let mut deleted = Vec::with_capacity(document_ids.len());
+        for document_id in document_ids {
+            if self.delete_document(&document_id).await? > 0 {
+                deleted.push(document_id);
+            }
+        }
+        info!("Deleted {} documents from source {}", deleted.len(), source);
+        Ok(deleted)
+    }
+}
+
+fn document_from_payload(payload: &HashMap<String, Value>) -> Document {
+    Document {
+        id: payload.get("id")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string())
+            .unwrap_or_else(|| String::new()),
+        title: payload.get("title")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string())
+            .unwrap_or_else(|| "Unknown".to_string()),
+        content: payload.get("content")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string())
+            .unwrap_or_else(|| String::new()),
+        chunk_index: payload.get("chunk_index")
+            .and_then(|v| v.as_integer())
+            .map(|i| i as usize)
+            .unwrap_or(0),
+        total_chunks: payload.get("total_chunks")
+            .and_then(|v| v.as_integer())
+            .map(|i| i as usize)
+            .unwrap_or(1),
+        source: payload.get("source")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string())
+            .unwrap_or_else(|| "Unknown".to_string()),
+        tags: payload.get("tags")
+            .and_then(|v| {
+                // Try to parse the JSON value as an array
+                if let serde_json::Value::Array(arr) = serde_json::from_str(&v.to_string()).ok()? {
+                    Some(arr.iter()
+                        .filter_map(|val| val.as_str())
+                        .map(|s| s.to_string())
+                        .collect())
+                } else {
+                    None
+                }
+            })
+            .unwrap_or_default(),
+        trust_level: payload.get(trust::TRUST_FIELD)
+            .and_then(|v| v.as_str())
+            .and_then(|s| s.parse().ok())
+            .unwrap_or_default(),
+        created_at: payload.get("created_at")
+            .and_then(|v| v.as_str())
+            .and_then(|s| chrono::DateTime::parse_from_rfc3339(s).ok())
+            .map(|dt| dt.with_timezone(&chrono::Utc))
+            .unwrap_or_else(chrono::Utc::now),
+        offset: payload.get("offset")
+            .and_then(|v| v.as_integer())
+            .map(|i| i as usize)
+            .unwrap_or(0),
+        manually_corrected: payload.get("manually_corrected")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false),
+        revision: payload.get("revision")
+            .and_then(|v| v.as_integer())
+            .map(|i| i as u64)
+            .unwrap_or_else(first_revision),
+        updated_at: payload.get("updated_at")
+            .and_then(|v| v.as_str())
+            .and_then(|s| chrono::DateTime::parse_from_rfc3339(s).ok())
+            .map(|dt| dt.with_timezone(&chrono::Utc)),
+        page: payload.get("page")
+            .and_then(|v| v.as_integer())
+            .map(|i| i as usize),
     }
 }
 
+// Byte offset of each chunk in the concatenated text
+fn chunk_offsets<'a>(chunks: impl IntoIterator<Item = &'a str>) -> Vec<usize> {
+    let mut offset = 0;
+    chunks
+        .into_iter()
+        .map(|chunk| {
+            let start = offset;
+            offset += chunk.len();
+            start
+        })
+        .collect()
+}
+
+// The note reference carried by an annotation point, None for document chunks
+fn note_from_payload(payload: &HashMap<String, Value>) -> Option<NoteRef> {
+    if payload.get("kind").and_then(|v| v.as_str()).map(|s| s.as_str()) != Some("annotation") {
+        return None;
+    }
+    
+    Some(NoteRef {
+        annotation_id: payload.get("annotation_id")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string())
+            .unwrap_or_default(),
+        chunk_index: payload.get("note_chunk_index")
+            .and_then(|v| v.as_integer())
+            .map(|i| i as usize),
+    })
+}
+
+// HTTP Handlers
 pub async fn search_documents_handler(
     State(state): State<Arc<crate::AppState>>,
     Query(params): Query<SearchQuery>,
@@ -469,14 +1811,25 @@ pub async fn search_documents_handler(
     
     let limit = params.limit.unwrap_or(10);
     let threshold = params.threshold.unwrap_or(0.3);
+    // Trust is not a payload filter: documents indexed before trust levels
+    // existed have none and read as personal
+    let fetch_limit = match params.min_trust {
+        Some(_) => limit * MIN_TRUST_OVERFETCH_FACTOR,
+        None => limit,
+    };
     
     match knowledge_service.search_documents(
         &params.query,
-        limit,
+        fetch_limit,
         threshold,
-        params.tags,
+        params.tags.map(|tags| TagFilter::new(tags, params.tag_mode)),
     ).await {
-        Ok(documents) => {
+        Ok(mut documents) => {
+            if let Some(min_trust) = params.min_trust {
+                documents.retain(|doc| doc.trust_level.at_least(min_trust));
+                documents.truncate(limit);
+            }
+
             Json(SearchResult {
                 total_results: documents.len(),
                 documents,
@@ -517,6 +1870,7 @@ pub async fn knowledge_stats_handler(
 
 pub async fn list_documents_handler(
     State(state): State<Arc<crate::AppState>>,
+    Query(params): Query<ListDocumentsQuery>,
 ) -> impl IntoResponse {
     // Check if knowledge service is available
     let knowledge_service = match &state.knowledge_service {
@@ -529,16 +1883,689 @@ pub async fn list_documents_handler(
         }
     };
     
-    match knowledge_service.list_all_documents().await {
-        Ok(documents) => {
-            Json(serde_json::json!({
-                "documents": documents,
-                "total": documents.len()
-            })).into_response()
-        }
+    let offset = params.offset.unwrap_or(0);
+    let limit = params.limit.unwrap_or(DEFAULT_LIST_LIMIT).clamp(1, MAX_LIST_LIMIT);
+    let tag = params.tag.as_deref().map(str::trim).filter(|tag| !tag.is_empty());
+    match knowledge_service.list_documents(offset, limit, tag).await {
+        Ok(page) => Json(page).into_response(),
         Err(e) => {
             error!("Failed to list documents: {}", e);
             (StatusCode::INTERNAL_SERVER_ERROR, "Failed to list documents").into_response()
         }
     }
-}
\ No newline at end of file
+}
+
+// Changes a document's trust level
+pub async fn update_document_handler(
+    State(state): State<Arc<crate::AppState>>,
+    Path(document_id): Path<String>,
+    Json(request): Json<UpdateDocumentRequest>,
+) -> impl IntoResponse {
+    let knowledge_service = match &state.knowledge_service {
+        Some(service) => service,
+        None => {
+            return (StatusCode::SERVICE_UNAVAILABLE, "Knowledge base service is not available").into_response();
+        }
+    };
+    
+    match knowledge_service.set_trust_level(&document_id, request.trust_level).await {
+        Ok(0) => (StatusCode::NOT_FOUND, "Document not found").into_response(),
+        Ok(_) => Json(serde_json::json!({
+            "document_id": document_id,
+            "trust_level": request.trust_level,
+        })).into_response(),
+        Err(e) => {
+            error!("Failed to update document {}: {}", document_id, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to update document").into_response()
+        }
+    }
+}
+
+// One chunk of a document, e.g. to show it before correcting it
+pub async fn get_chunk_handler(
+    State(state): State<Arc<crate::AppState>>,
+    Path((document_id, chunk_index)): Path<(String, usize)>,
+) -> impl IntoResponse {
+    let knowledge_service = match &state.knowledge_service {
+        Some(service) => service,
+        None => {
+            return (StatusCode::SERVICE_UNAVAILABLE, "Knowledge base service is not available").into_response();
+        }
+    };
+    
+    match knowledge_service.chunk(&document_id, chunk_index).await {
+        Ok(Some(chunk)) => Json(chunk).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "Chunk not found").into_response(),
+        Err(e) => {
+            error!("Failed to read chunk {} of document {}: {}", chunk_index, document_id, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to read chunk").into_response()
+        }
+    }
+}
+
+// Replaces the text of one chunk, re-embedding only that chunk
+pub async fn correct_chunk_handler(
+    State(state): State<Arc<crate::AppState>>,
+    Path((document_id, chunk_index)): Path<(String, usize)>,
+    Json(request): Json<CorrectChunkRequest>,
+) -> impl IntoResponse {
+    let knowledge_service = match &state.knowledge_service {
+        Some(service) => service,
+        None => {
+            return (StatusCode::SERVICE_UNAVAILABLE, "Knowledge base service is not available").into_response();
+        }
+    };
+    
+    if request.content.trim().is_empty() {
+        return (StatusCode::BAD_REQUEST, "Chunk content is required").into_response();
+    }
+    
+    match knowledge_service.correct_chunk(&document_id, chunk_index, &request.content).await {
+        Ok(Some(correction)) => Json(correction).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "Chunk not found").into_response(),
+        Err(e) => {
+            error!("Failed to correct chunk {} of document {}: {}", chunk_index, document_id, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to correct chunk").into_response()
+        }
+    }
+}
+
+// Replaces a document's text, and optionally its tags, under the same id
+pub async fn replace_document_handler(
+    State(state): State<Arc<crate::AppState>>,
+    Path(document_id): Path<String>,
+    Json(request): Json<ReplaceDocumentRequest>,
+) -> impl IntoResponse {
+    let knowledge_service = match &state.knowledge_service {
+        Some(service) => service,
+        None => {
+            return (StatusCode::SERVICE_UNAVAILABLE, "Knowledge base service is not available").into_response();
+        }
+    };
+    
+    if request.content.trim().is_empty() {
+        return (StatusCode::BAD_REQUEST, "Document content is required").into_response();
+    }
+    
+    match knowledge_service.update_document(&document_id, &request.content, request.tags).await {
+        Ok(Some(reindexed)) => Json(serde_json::json!({
+            "document_id": document_id,
+            "reindexed": reindexed,
+        })).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "Document not found").into_response(),
+        Err(e) => {
+            error!("Failed to update document {}: {}", document_id, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to update document").into_response()
+        }
+    }
+}
+
+// Removes the document's chunks and its user notes
+pub async fn delete_document_handler(
+    State(state): State<Arc<crate::AppState>>,
+    Path(document_id): Path<String>,
+) -> impl IntoResponse {
+    let knowledge_service = match &state.knowledge_service {
+        Some(service) => service,
+        None => {
+            return (StatusCode::SERVICE_UNAVAILABLE, "Knowledge base service is not available").into_response();
+        }
+    };
+    
+    // Note points carry the document id, so this removes them from the index
+    let chunks = match knowledge_service.delete_document(&document_id).await {
+        Ok(0) => return (StatusCode::NOT_FOUND, "Document not found").into_response(),
+        Ok(chunks) => chunks,
+        Err(e) => {
+            error!("Failed to delete document {}: {}", document_id, e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to delete document").into_response();
+        }
+    };
+    
+    match forget_document(&state, &document_id).await {
+        Ok(annotations) => Json(serde_json::json!({
+            "document_id": document_id,
+            "chunks_deleted": chunks,
+            "annotations_deleted": annotations,
+        })).into_response(),
+        Err(e) => {
+            error!("Failed to delete annotations of document {}: {}", document_id, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to delete document annotations").into_response()
+        }
+    }
+}
+
+// Removes every document stored from one source, e.g. a finished import
+pub async fn delete_documents_by_source_handler(
+    State(state): State<Arc<crate::AppState>>,
+    Query(params): Query<DeleteBySourceQuery>,
+) -> impl IntoResponse {
+    let knowledge_service = match &state.knowledge_service {
+        Some(service) => service,
+        None => {
+            return (StatusCode::SERVICE_UNAVAILABLE, "Knowledge base service is not available").into_response();
+        }
+    };
+    
+    if params.source.trim().is_empty() {
+        return (StatusCode::BAD_REQUEST, "A source is required").into_response();
+    }
+    
+    let document_ids = match knowledge_service.delete_documents_by_source(&params.source).await {
+        Ok(document_ids) => document_ids,
+        Err(e) => {
+            error!("Failed to delete documents from {}: {}", params.source, e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to delete documents").into_response();
+        }
+    };
+    
+    let mut annotations = 0;
+    for document_id in &document_ids {
+        match forget_document(&state, document_id).await {
+            Ok(deleted) => annotations += deleted,
+            Err(e) => warn!("Failed to delete annotations of document {}: {}", document_id, e),
+        }
+    }
+    
+    Json(serde_json::json!({
+        "source": params.source,
+        "documents_deleted": document_ids,
+        "annotations_deleted": annotations,
+    })).into_response()
+}
+
+// Drop what is kept about a deleted document outside the vector store: its
+// revisions and its user notes. Returns the number of notes deleted
+async fn forget_document(state: &crate::AppState, document_id: &str) -> Result<u64> {
+    if let Err(e) = state.revision_store.delete_for_document(document_id).await {
+        warn!("Failed to delete revisions of document {}: {}", document_id, e);
+    }
+    state.annotation_store.delete_for_document(document_id).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cipher(keys: &[(u32, u8)]) -> PayloadCipher {
+        PayloadCipher::new(keys[0].0, keys.iter().map(|(version, byte)| (*version, [*byte; 32]))).unwrap()
+    }
+
+    fn scroll_config() -> ScrollConfig {
+        ScrollConfig {
+            batch_size: 1,
+            batch_delay: std::time::Duration::ZERO,
+            state_dir: std::env::temp_dir().join(format!("rusty-ai-knowledge-jobs-{}", Uuid::new_v4())),
+            ..ScrollConfig::default()
+        }
+    }
+
+    async fn service(cipher: Option<PayloadCipher>) -> KnowledgeService {
+        KnowledgeService::with_backends(VectorStore::in_memory(), OpenAIConfig::new(), None, Arc::new(ResidencyPolicy::default()))
+            .await
+            .unwrap()
+            .with_payload_cipher(cipher)
+            .with_scroll_config(scroll_config())
+    }
+
+    async fn store(service: &KnowledgeService, title: &str, text: &str, tags: &[&str], vector: Vec<f32>) {
+        let tags: Vec<String> = tags.iter().map(|t| t.to_string()).collect();
+        service
+            .store_chunks(&Uuid::new_v4().to_string(), title, "test", &tags, TrustLevel::Personal, vec![(text.to_string(), vector)])
+            .await
+            .unwrap();
+    }
+
+    fn query() -> Vec<f32> {
+        let mut vector = vec![0.0; EMBEDDING_DIMENSION as usize];
+        vector[0] = 1.0;
+        vector
+    }
+
+    // Everything stored, as read straight from the store
+    async fn raw_contents(service: &KnowledgeService) -> Vec<String> {
+        let points = service.vector_store.scroll(COLLECTION_NAME, None, 100).await.unwrap();
+        points
+            .iter()
+            .map(|point| {
+                let field = |name: &str| point.payload.get(name).and_then(|v| v.as_str()).cloned().unwrap_or_default();
+                format!("{}{}", field("content"), field(ENCRYPTED_CONTENT_FIELD))
+            })
+            .collect()
+    }
+
+    // (chunk index, point id) of a document's chunks
+    async fn chunk_points(service: &KnowledgeService, document_id: &str) -> Vec<(usize, String)> {
+        let points = service.vector_store
+            .scroll(COLLECTION_NAME, Some(&PayloadFilter::matching("id", document_id)), 100)
+            .await
+            .unwrap();
+        let mut points: Vec<(usize, String)> = points
+            .into_iter()
+            .map(|point| (document_from_payload(&point.payload).chunk_index, point.id))
+            .collect();
+        points.sort();
+        points
+    }
+
+    // A lease in three chunks, one per section
+    fn lease(rent: u32) -> String {
+        let section = |name: &str, fact: String| format!("{}\n{}\n{}", name, fact, vec!["Terms continue here"; 70].join("\n"));
+        [
+            section("Parties", "Between Ada and the landlord".to_string()),
+            section("Rent", format!("Rent is {} per month", rent)),
+            section("Pets", "One cat is allowed".to_string()),
+        ]
+        .join(". ")
+    }
+
+    #[tokio::test]
+    async fn test_sensitive_payloads_are_ciphertext_and_search_mixes_both() {
+        let service = service(Some(cipher(&[(1, 7)]))).await;
+        store(&service, "Lab results", "Cholesterol 5.2 mmol/L", &["sensitive"], query()).await;
+        store(&service, "[personal] Allergy", "User is allergic to penicillin", &[retrieval::MEMORY_TAG], query()).await;
+        store(&service, "Recipe", "Pancakes need two eggs", &["cooking"], query()).await;
+
+        let raw = raw_contents(&service).await;
+        assert!(raw.iter().any(|c| c == "Pancakes need two eggs"));
+        assert!(raw.iter().all(|c| !c.contains("Cholesterol") && !c.contains("penicillin")));
+        assert_eq!(raw.iter().filter(|c| c.starts_with("k1:")).count(), 2);
+
+        let matches = service.search_collection(COLLECTION_NAME, query(), 10, 0.0, None).await.unwrap();
+        let mut contents: Vec<&str> = matches.iter().map(|m| m.content.as_str()).collect();
+        contents.sort();
+        assert_eq!(contents, vec!["Cholesterol 5.2 mmol/L", "Pancakes need two eggs", "User is allergic to penicillin"]);
+
+        let listed = service.list_all_documents().await.unwrap();
+        assert!(listed.iter().any(|d| d.content == "Cholesterol 5.2 mmol/L"));
+
+        // Without the key the plaintext chunks are still found
+        let service = KnowledgeService { cipher: None, ..service };
+        let matches = service.search_collection(COLLECTION_NAME, query(), 10, 0.0, None).await.unwrap();
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].content, "Pancakes need two eggs");
+    }
+
+    #[tokio::test]
+    async fn test_rotation_moves_payloads_to_the_current_key() {
+        let mut service = service(Some(cipher(&[(1, 7)]))).await;
+        store(&service, "Lab results", "Cholesterol 5.2 mmol/L", &["sensitive"], query()).await;
+        store(&service, "Recipe", "Pancakes need two eggs", &["cooking"], query()).await;
+        store(&service, "Prescription", "Ibuprofen 400mg", &["sensitive"], query()).await;
+
+        service.cipher = Some(cipher(&[(2, 9), (1, 7)]));
+        store(&service, "Scan", "MRI shows no findings", &["Sensitive"], query()).await;
+        
+        // A dry run only counts
+        service.scroll_config.dry_run = true;
+        assert_eq!(service.rotate_payload_keys().await.unwrap(), 2);
+        assert_eq!(raw_contents(&service).await.iter().filter(|c| c.starts_with("k1:")).count(), 2);
+        
+        service.scroll_config.dry_run = false;
+        assert_eq!(service.rotate_payload_keys().await.unwrap(), 2);
+        assert_eq!(service.rotate_payload_keys().await.unwrap(), 0);
+        let raw = raw_contents(&service).await;
+        assert_eq!(raw.iter().filter(|c| c.starts_with("k2:")).count(), 3);
+
+        // The retired key is no longer needed
+        service.cipher = Some(cipher(&[(2, 9)]));
+        let matches = service.search_collection(COLLECTION_NAME, query(), 10, 0.0, None).await.unwrap();
+        assert_eq!(matches.len(), 4);
+        assert!(matches.iter().any(|m| m.content == "Cholesterol 5.2 mmol/L"));
+    }
+    // Titles of the stored chunks carrying the tags, sorted
+    async fn tagged(service: &KnowledgeService, tags: &[&str], mode: TagMode) -> Vec<String> {
+        let filter = TagFilter::new(tags.iter().map(|t| t.to_string()).collect(), mode).to_payload_filter();
+        let matches = service.search_collection(COLLECTION_NAME, query(), 10, 0.0, filter.as_ref()).await.unwrap();
+        let mut titles: Vec<String> = matches.into_iter().map(|m| m.title).collect();
+        titles.sort();
+        titles
+    }
+
+    #[tokio::test]
+    async fn test_tag_filters_match_any_or_all_of_the_tags() {
+        let service = service(None).await;
+        store(&service, "Budget", "Quarterly budget", &["work", "finance"], query()).await;
+        store(&service, "Standup", "Standup notes", &["work"], query()).await;
+        store(&service, "Groceries", "Oat milk", &["home"], query()).await;
+
+        assert_eq!(tagged(&service, &["finance", "home"], TagMode::Any).await, vec!["Budget", "Groceries"]);
+        assert_eq!(tagged(&service, &["work", "finance"], TagMode::All).await, vec!["Budget"]);
+        assert_eq!(tagged(&service, &["work"], TagMode::All).await, vec!["Budget", "Standup"]);
+        assert_eq!(tagged(&service, &[], TagMode::All).await.len(), 3);
+        // Filters that exclude everything find nothing rather than failing
+        assert!(tagged(&service, &["work", "home"], TagMode::All).await.is_empty());
+        assert!(tagged(&service, &["travel"], TagMode::Any).await.is_empty());
+        
+        let filter = TagFilter::new(vec!["work".to_string()], TagMode::Any).excluding(["finance"]).to_payload_filter();
+        let matches = service.search_collection(COLLECTION_NAME, query(), 10, 0.0, filter.as_ref()).await.unwrap();
+        assert_eq!(matches.iter().map(|m| m.title.as_str()).collect::<Vec<_>>(), vec!["Standup"]);
+
+        let uri: axum::http::Uri = "/search?query=budget&tags=work,%20finance&tag_mode=all".parse().unwrap();
+        let Query(params) = Query::<SearchQuery>::try_from_uri(&uri).unwrap();
+        assert_eq!(params.tags, Some(vec!["work".to_string(), "finance".to_string()]));
+        assert_eq!(params.tag_mode, TagMode::All);
+        let uri: axum::http::Uri = "/search?query=budget".parse().unwrap();
+        assert_eq!(Query::<SearchQuery>::try_from_uri(&uri).unwrap().0.tag_mode, TagMode::Any);
+    }
+
+    #[tokio::test]
+    async fn test_chunks_of_paged_files_are_found_with_their_page() {
+        let service = service(None).await;
+        let chunks = vec![
+            ("Warranty covers parts".to_string(), query(), Some(1)),
+            ("Refunds within 30 days".to_string(), query(), Some(12)),
+        ];
+        service
+            .store_paged_chunks("manual", "Manual", "manual.pdf", &[], TrustLevel::Personal, chunks)
+            .await
+            .unwrap();
+        store(&service, "Notes", "No pages here", &[], query()).await;
+
+        let matches = service.search_collection(COLLECTION_NAME, query(), 10, 0.0, None).await.unwrap();
+        let mut pages: Vec<(&str, Option<usize>)> = matches.iter().map(|m| (m.content.as_str(), m.page)).collect();
+        pages.sort();
+        assert_eq!(
+            pages,
+            vec![("No pages here", None), ("Refunds within 30 days", Some(12)), ("Warranty covers parts", Some(1))]
+        );
+    }
+
+    // Chunks that all have the query vector
+    fn chunks(texts: &[&str]) -> Vec<(String, Vec<f32>)> {
+        texts.iter().map(|text| (text.to_string(), query())).collect()
+    }
+
+    #[tokio::test]
+    async fn test_deleted_documents_are_gone_from_searches() {
+        let service = service(None).await;
+        let store = |id: &'static str, source: &'static str, texts: &'static [&'static str]| {
+            service.store_chunks(id, id, source, &[], TrustLevel::Personal, chunks(texts))
+        };
+        store("lease", "lease.txt", &["Parties", "Rent is 1200", "Pets"]).await.unwrap();
+        store("wiki-setup", "import:wiki", &["Install docker"]).await.unwrap();
+        store("wiki-deploy", "import:wiki", &["Deploy on Fridays", "Never"]).await.unwrap();
+        store("recipe", "notes", &["Pancakes need two eggs"]).await.unwrap();
+        let found = || {
+            let search = service.search_collection(COLLECTION_NAME, query(), 10, 0.0, None);
+            async move {
+                let mut ids: Vec<String> = search.await.unwrap().into_iter().map(|m| m.id).collect();
+                ids.sort();
+                ids.dedup();
+                ids
+            }
+        };
+
+        assert_eq!(service.delete_document("lease").await.unwrap(), 3);
+        assert_eq!(found().await, vec!["recipe", "wiki-deploy", "wiki-setup"]);
+        assert_eq!(service.delete_document("lease").await.unwrap(), 0);
+
+        let deleted = service.delete_documents_by_source("import:wiki").await.unwrap();
+        assert_eq!(deleted, vec!["wiki-deploy", "wiki-setup"]);
+        assert_eq!(found().await, vec!["recipe"]);
+        assert!(service.delete_documents_by_source("import:wiki").await.unwrap().is_empty());
+        assert_eq!(service.vector_store.point_count(COLLECTION_NAME).await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_documents_are_listed_once_newest_first_in_pages() {
+        let service = service(None).await;
+        let tags = |tags: &[&str]| tags.iter().map(|t| t.to_string()).collect::<Vec<String>>();
+        let sections: Vec<(String, Vec<f32>)> = (0..300).map(|i| (format!("Section {}", i), query())).collect();
+        // Stored oldest first; ids run the other way so ties order the same
+        service.store_chunks("c-manual", "Manual", "manual.pdf", &tags(&["work"]), TrustLevel::Verified, sections).await.unwrap();
+        service.store_chunks("b-notes", "Notes", "notes", &tags(&["home"]), TrustLevel::Personal, chunks(&["Call the plumber"])).await.unwrap();
+        service
+            .store_chunks("a-recipe", "Pancakes", "notes", &tags(&["cooking", "home"]), TrustLevel::Personal, chunks(&["Two eggs", "Milk"]))
+            .await
+            .unwrap();
+
+        let first = service.list_documents(0, 2, None).await.unwrap();
+        let ids: Vec<&str> = first.documents.iter().map(|d| d.id.as_str()).collect();
+        assert_eq!(ids, vec!["a-recipe", "b-notes"]);
+        assert_eq!((first.total, first.has_more), (3, true));
+        assert_eq!(first.documents[0].chunk_count, 2);
+
+        let second = service.list_documents(2, 2, None).await.unwrap();
+        assert_eq!(second.documents.len(), 1);
+        let manual = &second.documents[0];
+        assert_eq!((manual.id.as_str(), manual.title.as_str(), manual.source.as_str()), ("c-manual", "Manual", "manual.pdf"));
+        assert_eq!((manual.chunk_count, manual.tags.clone()), (300, vec!["work".to_string()]));
+        assert!(!second.has_more);
+
+        let home = service.list_documents(0, 10, Some("home")).await.unwrap();
+        assert_eq!(home.documents.iter().map(|d| d.id.as_str()).collect::<Vec<_>>(), vec!["a-recipe", "b-notes"]);
+        assert_eq!(home.total, 2);
+        assert!(service.list_documents(0, 10, Some("travel")).await.unwrap().documents.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_storing_a_document_again_replaces_its_points() {
+        let service = service(None).await;
+        let tags = vec!["home".to_string()];
+        let store = |texts: &'static [&'static str]| {
+            service.store_chunks("lease", "Lease", "test", &tags, TrustLevel::Personal, chunks(texts))
+        };
+
+        store(&["Parties", "Rent is 1200", "Pets"]).await.unwrap();
+        store(&["Parties", "Rent is 1350", "Pets"]).await.unwrap();
+        assert_eq!(service.vector_store.point_count(COLLECTION_NAME).await.unwrap(), 3);
+
+        // The rent chunk is read by its computed id, straight from the store
+        let id = chunk_point_id("lease", 1);
+        let points = service.vector_store.get_points(COLLECTION_NAME, &[id.clone()]).await.unwrap();
+        assert_eq!(points.len(), 1);
+        assert_eq!(service.chunk("lease", 1).await.unwrap().unwrap().content, "Rent is 1350");
+        assert!(service.chunk("lease", 3).await.unwrap().is_none());
+
+        // A shorter version drops the points past its end
+        store(&["Parties and rent"]).await.unwrap();
+        assert_eq!(chunk_points(&service, "lease").await, vec![(0, chunk_point_id("lease", 0))]);
+
+        // Random ids keep the old behaviour: every store adds points
+        let service = service.with_point_ids(PointIdStrategy::Random);
+        let store = || service.store_chunks("memo", "Memo", "test", &tags, TrustLevel::Personal, chunks(&["Call Ada"]));
+        store().await.unwrap();
+        store().await.unwrap();
+        assert_eq!(chunk_points(&service, "memo").await.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_migration_moves_random_ids_to_deterministic_ones() {
+        let mut service = service(None).await.with_point_ids(PointIdStrategy::Random);
+        let tags = vec!["home".to_string()];
+        service
+            .store_chunks("lease", "Lease", "test", &tags, TrustLevel::Personal, chunks(&["Parties", "Rent", "Pets"]))
+            .await
+            .unwrap();
+        service
+            .store_chunks("memo", "Memo", "test", &tags, TrustLevel::Personal, chunks(&["Call Ada"]))
+            .await
+            .unwrap();
+        let document = service.document_chunks("lease").await.unwrap().remove(0);
+        let note = NoteRef { annotation_id: Uuid::new_v4().to_string(), chunk_index: Some(0) };
+        service.store_note(&document, &note, "Ada moved out", query()).await.unwrap();
+
+        // Nothing to do while ids stay random
+        assert_eq!(service.migrate_point_ids().await.unwrap(), 0);
+
+        service.point_ids = PointIdStrategy::Deterministic;
+        service.scroll_config.dry_run = true;
+        assert_eq!(service.migrate_point_ids().await.unwrap(), 4);
+        assert_ne!(chunk_points(&service, "memo").await[0].1, chunk_point_id("memo", 0));
+
+        service.scroll_config.dry_run = false;
+        assert_eq!(service.migrate_point_ids().await.unwrap(), 4);
+        assert_eq!(service.migrate_point_ids().await.unwrap(), 0);
+
+        let lease: Vec<(usize, String)> = (0..3).map(|index| (index, chunk_point_id("lease", index))).collect();
+        let points = chunk_points(&service, "lease").await;
+        // The note keeps its own id next to the chunks
+        assert_eq!(points.len(), 4);
+        assert!(points.contains(&(0, note.annotation_id.clone())));
+        assert!(lease.iter().all(|point| points.contains(point)));
+        assert_eq!(service.vector_store.point_count(COLLECTION_NAME).await.unwrap(), 5);
+        assert_eq!(service.chunk("memo", 0).await.unwrap().unwrap().content, "Call Ada");
+        let matches = service.search_collection(COLLECTION_NAME, query(), 10, 0.0, None).await.unwrap();
+        assert_eq!(matches.len(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_reindexing_embeds_only_changed_chunks_and_records_the_diff() {
+        let fixtures = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/ephemeral");
+        let stack = crate::ephemeral::EphemeralStack::start(&fixtures).await.unwrap();
+        let revisions = Arc::new(RevisionStore::new(stack.database_url()).await.unwrap());
+        let mut events = revisions.subscribe();
+        let service = KnowledgeService::with_backends(VectorStore::in_memory(), stack.openai_config(), None, Arc::new(ResidencyPolicy::default()))
+            .await
+            .unwrap()
+            .with_revisions(revisions.clone());
+        let tags = vec!["home".to_string()];
+
+        let stored = service
+            .store_document("Lease".to_string(), lease(1200), "lease.txt".to_string(), tags.clone(), TrustLevel::Personal)
+            .await
+            .unwrap();
+        assert_eq!(stored.chunks_created, 3);
+        let document_id = stored.document_id;
+        let before = chunk_points(&service, &document_id).await;
+
+        let reindexed = service
+            .reindex_document(&document_id, "Lease", &lease(1350), "lease.txt", &tags, TrustLevel::Personal)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(reindexed.changed_chunks, vec![1]);
+        assert_eq!(reindexed.total_chunks, 3);
+
+        // Every chunk is still at its point; the rent chunk's was overwritten
+        let after = chunk_points(&service, &document_id).await;
+        assert_eq!(after, before);
+        assert_eq!(after[1].1, chunk_point_id(&document_id, 1));
+        let chunks = service.document_chunks(&document_id).await.unwrap();
+        assert!(chunks[1].content.contains("Rent is 1350 per month"));
+
+        let changes: Vec<(document_revisions::ChangeKind, usize, &str)> =
+            reindexed.summary.changes.iter().map(|c| (c.kind, c.line, c.text.as_str())).collect();
+        assert_eq!(
+            changes,
+            vec![
+                (document_revisions::ChangeKind::Removed, 73, "Rent is 1200 per month"),
+                (document_revisions::ChangeKind::Added, 73, "Rent is 1350 per month"),
+            ]
+        );
+        let revision = reindexed.revision.unwrap();
+        assert_eq!((revision.revision, revision.reused_chunks), (2, 2));
+        assert_eq!(revisions.list_for_document(&document_id).await.unwrap(), vec![revision]);
+        let event = events.recv().await.unwrap();
+        assert_eq!(event.document_id, document_id);
+        assert_eq!(event.headline, "1 line added, 1 removed");
+
+        // The same text again embeds nothing and records no revision
+        let unchanged = service
+            .reindex_document(&document_id, "Lease", &lease(1350), "lease.txt", &tags, TrustLevel::Personal)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(unchanged.changed_chunks.is_empty());
+        assert!(unchanged.revision.is_none());
+        assert_eq!(chunk_points(&service, &document_id).await, after);
+
+        let missing = service
+            .reindex_document("missing", "Lease", &lease(1350), "lease.txt", &tags, TrustLevel::Personal)
+            .await
+            .unwrap();
+        assert!(missing.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_updated_documents_keep_their_id_and_search_only_the_latest_revision() {
+        let fixtures = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/ephemeral");
+        let stack = crate::ephemeral::EphemeralStack::start(&fixtures).await.unwrap();
+        let service = KnowledgeService::with_backends(VectorStore::in_memory(), stack.openai_config(), None, Arc::new(ResidencyPolicy::default()))
+            .await
+            .unwrap();
+
+        let stored = service
+            .store_document("Lease".to_string(), lease(1200), "lease.txt".to_string(), vec!["home".to_string()], TrustLevel::Personal)
+            .await
+            .unwrap();
+        let document_id = stored.document_id;
+        assert!(service.document_chunks(&document_id).await.unwrap().iter().all(|c| c.revision == 1 && c.updated_at.is_none()));
+
+        let updated = service.update_document(&document_id, &lease(1350), None).await.unwrap().unwrap();
+        assert_eq!((updated.current_revision, updated.changed_chunks.clone()), (2, vec![1]));
+        let updated = service
+            .update_document(&document_id, &lease(1400), Some(vec!["home".to_string(), "contracts".to_string()]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(updated.current_revision, 3);
+
+        let chunks = service.document_chunks(&document_id).await.unwrap();
+        assert_eq!(chunks.len(), 3);
+        assert!(chunks.iter().all(|c| c.revision == 3 && c.updated_at == updated.updated_at));
+        assert!(chunks.iter().all(|c| c.title == "Lease" && c.tags == vec!["home", "contracts"]));
+        assert_eq!(service.vector_store.point_count(COLLECTION_NAME).await.unwrap(), 3);
+
+        let matches = service.search_documents("rent per month", 10, 0.0, None).await.unwrap();
+        assert!(matches.iter().all(|m| m.id == document_id));
+        assert!(matches.iter().any(|m| m.content.contains("Rent is 1400 per month")));
+        assert!(matches.iter().all(|m| !m.content.contains("Rent is 1200") && !m.content.contains("Rent is 1350")));
+
+        // The same text again is not a new revision
+        let unchanged = service.update_document(&document_id, &lease(1400), None).await.unwrap().unwrap();
+        assert_eq!((unchanged.current_revision, unchanged.updated_at), (3, updated.updated_at));
+        assert!(service.update_document("missing", "text", None).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_correcting_a_chunk_reembeds_it_in_place_and_moves_later_offsets() {
+        let fixtures = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/ephemeral");
+        let stack = crate::ephemeral::EphemeralStack::start(&fixtures).await.unwrap();
+        let revisions = Arc::new(RevisionStore::new(stack.database_url()).await.unwrap());
+        let service = KnowledgeService::with_backends(VectorStore::in_memory(), stack.openai_config(), None, Arc::new(ResidencyPolicy::default()))
+            .await
+            .unwrap()
+            .with_revisions(revisions.clone());
+
+        let stored = service
+            .store_document("Lease".to_string(), lease(1200), "lease.txt".to_string(), vec!["home".to_string()], TrustLevel::Personal)
+            .await
+            .unwrap();
+        let document_id = stored.document_id;
+        let before = service.document_chunks(&document_id).await.unwrap();
+        let points = chunk_points(&service, &document_id).await;
+        assert_eq!(before[1].offset, before[0].content.len());
+        assert_eq!(before[2].offset, before[0].content.len() + before[1].content.len());
+
+        // An OCR slip in the middle chunk
+        let text = before[1].content.replace("Rent is 1200 per month", "Rent is 1200 euros per month, due on the first");
+        let correction = service.correct_chunk(&document_id, 1, &text).await.unwrap().unwrap();
+        assert_eq!(correction.offset_shift, 24);
+        assert_eq!(correction.summary.headline(), "1 line added, 1 removed");
+
+        // Every chunk keeps its point; only the corrected one changed
+        assert_eq!(chunk_points(&service, &document_id).await, points);
+        let after = service.document_chunks(&document_id).await.unwrap();
+        assert_eq!(after[1].content, text);
+        let flags: Vec<bool> = after.iter().map(|c| c.manually_corrected).collect();
+        assert_eq!(flags, vec![false, true, false]);
+        let offsets: Vec<usize> = after.iter().map(|c| c.offset).collect();
+        assert_eq!(offsets, vec![0, before[1].offset, before[2].offset + 24]);
+        assert_eq!(after[0].content, before[0].content);
+
+        let matches = service.search_documents("rent euros due on the first", 1, 0.0, None).await.unwrap();
+        assert_eq!((matches[0].chunk_index, matches[0].manually_corrected), (1, true));
+        assert!(matches[0].content.contains("Rent is 1200 euros per month"));
+
+        let revision = correction.revision.unwrap();
+        assert_eq!((revision.revision, revision.changed_chunks.clone(), revision.reused_chunks), (2, vec![1], 2));
+        assert_eq!(revisions.list_for_document(&document_id).await.unwrap(), vec![revision]);
+
+        // The same text again records nothing
+        let unchanged = service.correct_chunk(&document_id, 1, &text).await.unwrap().unwrap();
+        assert!(unchanged.revision.is_none());
+        assert!(service.correct_chunk(&document_id, 3, "Out of range").await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,1052 @@
+use anyhow::{Context, Result};
+use axum::{
+    extract::{multipart::Field, Multipart, Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::{Path as FsPath, PathBuf};
+use std::sync::Arc;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::{broadcast, RwLock};
+use tracing::{debug, error, info, warn};
+use uuid::Uuid;
+
+use crate::knowledge_service_simple::{KnowledgeService, MAX_CHUNK_SIZE};
+use crate::retrieval;
+use crate::trust::TrustLevel;
+use rusty_ai_knowledge::{DocumentProcessor, ProcessedDocument};
+
+const DEFAULT_UPLOAD_DIR: &str = "./data/uploads";
+const EVENT_CHANNEL_CAPACITY: usize = 256;
+// Finished uploads stay pollable this long, and only this many are kept
+const FINISHED_UPLOAD_TTL_MINUTES: i64 = 60;
+const MAX_FINISHED_UPLOADS: usize = 500;
+
+// Where an upload is in the ingestion pipeline
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "stage", rename_all = "snake_case")]
+pub enum UploadStage {
+    Received { bytes: u64 },
+    Extracting,
+    Chunking,
+    Embedding { done: usize, total: usize },
+    Indexed { document_id: String, chunks: usize },
+    Failed { failed_stage: String, error: String },
+}
+
+impl UploadStage {
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, UploadStage::Indexed { .. } | UploadStage::Failed { .. })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UploadStatus {
+    pub upload_id: String,
+    pub title: String,
+    pub source: String,
+    pub status: UploadStage,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct UploadMetadata {
+    pub title: String,
+    pub source: String,
+    pub tags: Vec<String>,
+    // Uploads are the user's own documents unless the form says otherwise
+    pub trust_level: TrustLevel,
+}
+
+// The ingestion steps an upload goes through; KnowledgeService in production,
+// a mock in tests
+pub trait UploadIndexer {
+    fn chunk(&self, text: &str) -> Vec<String>;
+    // The metadata decides which embedding provider may see the chunk
+    fn embed(&self, chunk: &str, metadata: &UploadMetadata) -> impl Future<Output = Result<Vec<f32>>> + Send;
+    // Each chunk with its embedding and the page it came from
+    fn index(
+        &self,
+        document_id: &str,
+        metadata: &UploadMetadata,
+        chunks: Vec<(String, Vec<f32>, Option<usize>)>,
+    ) -> impl Future<Output = Result<()>> + Send;
+    fn remove(&self, document_id: &str) -> impl Future<Output = Result<()>> + Send;
+}
+
+impl UploadIndexer for KnowledgeService {
+    fn chunk(&self, text: &str) -> Vec<String> {
+        self.chunk_text(text, MAX_CHUNK_SIZE)
+    }
+
+    async fn embed(&self, chunk: &str, metadata: &UploadMetadata) -> Result<Vec<f32>> {
+        self.embed_for(chunk, &metadata.tags).await
+    }
+
+    async fn index(
+        &self,
+        document_id: &str,
+        metadata: &UploadMetadata,
+        chunks: Vec<(String, Vec<f32>, Option<usize>)>,
+    ) -> Result<()> {
+        self.store_paged_chunks(
+            document_id,
+            &metadata.title,
+            &metadata.source,
+            &metadata.tags,
+            metadata.trust_level,
+            chunks,
+        )
+        .await
+    }
+
+    async fn remove(&self, document_id: &str) -> Result<()> {
+        self.delete_document(document_id).await?;
+        Ok(())
+    }
+}
+
+// Status of every upload in flight and of recently finished ones, with a
+// broadcast of each change for the WebSocket push channel
+pub struct UploadTracker {
+    statuses: RwLock<HashMap<String, UploadStatus>>,
+    events: broadcast::Sender<UploadStatus>,
+    finished_ttl: chrono::Duration,
+    max_finished: usize,
+}
+
+impl UploadTracker {
+    pub fn new() -> Self {
+        Self::with_limits(
+            chrono::Duration::minutes(FINISHED_UPLOAD_TTL_MINUTES),
+            MAX_FINISHED_UPLOADS,
+        )
+    }
+
+    pub fn with_limits(finished_ttl: chrono::Duration, max_finished: usize) -> Self {
+        let (events, _) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
+        Self {
+            statuses: RwLock::new(HashMap::new()),
+            events,
+            finished_ttl,
+            max_finished,
+        }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<UploadStatus> {
+        self.events.subscribe()
+    }
+
+    pub async fn create(&self, upload_id: &str, metadata: &UploadMetadata, bytes: u64) -> UploadStatus {
+        let now = chrono::Utc::now();
+        let status = UploadStatus {
+            upload_id: upload_id.to_string(),
+            title: metadata.title.clone(),
+            source: metadata.source.clone(),
+            status: UploadStage::Received { bytes },
+            created_at: now,
+            updated_at: now,
+        };
+
+        {
+            let mut statuses = self.statuses.write().await;
+            self.prune(&mut statuses, now);
+            statuses.insert(upload_id.to_string(), status.clone());
+        }
+        // No subscribers is fine; the status stays pollable
+        let _ = self.events.send(status.clone());
+        status
+    }
+
+    pub async fn set_stage(&self, upload_id: &str, stage: UploadStage) {
+        let updated = {
+            let mut statuses = self.statuses.write().await;
+            match statuses.get_mut(upload_id) {
+                Some(status) => {
+                    status.status = stage;
+                    status.updated_at = chrono::Utc::now();
+                    status.clone()
+                }
+                None => return,
+            }
+        };
+
+        debug!("Upload {} is now {:?}", upload_id, updated.status);
+        let _ = self.events.send(updated);
+    }
+
+    pub async fn get(&self, upload_id: &str) -> Option<UploadStatus> {
+        self.statuses.read().await.get(upload_id).cloned()
+    }
+
+    // Drop finished uploads as of `now`; returns how many were dropped
+    pub async fn prune_finished(&self, now: chrono::DateTime<chrono::Utc>) -> usize {
+        let mut statuses = self.statuses.write().await;
+        self.prune(&mut statuses, now)
+    }
+
+    // Uploads still in flight are never dropped. Finished ones go once they
+    // are older than the TTL, then oldest first while over the cap
+    fn prune(&self, statuses: &mut HashMap<String, UploadStatus>, now: chrono::DateTime<chrono::Utc>) -> usize {
+        let before = statuses.len();
+        statuses.retain(|_, status| !status.status.is_terminal() || now - status.updated_at < self.finished_ttl);
+
+        let mut finished: Vec<(chrono::DateTime<chrono::Utc>, String)> = statuses
+            .values()
+            .filter(|status| status.status.is_terminal())
+            .map(|status| (status.updated_at, status.upload_id.clone()))
+            .collect();
+        if finished.len() > self.max_finished {
+            finished.sort();
+            let excess = finished.len() - self.max_finished;
+            for (_, upload_id) in finished.into_iter().take(excess) {
+                statuses.remove(&upload_id);
+            }
+        }
+
+        before - statuses.len()
+    }
+}
+
+impl Default for UploadTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// What the form says about a body's format. The "content" field is always
+// text; a file is what its content type, else its name, says it is
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BodyFormat {
+    pub content_type: Option<String>,
+    pub file_name: Option<String>,
+}
+
+impl BodyFormat {
+    pub fn text() -> Self {
+        Self {
+            content_type: Some("text/plain".to_string()),
+            file_name: None,
+        }
+    }
+}
+
+// Extract the text of an upload received into `path`. The temp file is
+// always removed; a file that cannot be read as its format fails the upload
+// at the extracting stage with an ExtractionError
+pub async fn extract_upload(
+    tracker: &UploadTracker,
+    upload_id: &str,
+    path: &FsPath,
+    format: &BodyFormat,
+) -> Result<ProcessedDocument> {
+    tracker.set_stage(upload_id, UploadStage::Extracting).await;
+    let result = extract_file(path, format).await;
+
+    if let Err(e) = tokio::fs::remove_file(path).await {
+        warn!("Failed to remove upload temp file {}: {}", path.display(), e);
+    }
+
+    match result {
+        Ok(document) => {
+            debug!("Upload {} read as {}", upload_id, document.detected_type);
+            Ok(document)
+        }
+        Err(e) => {
+            warn!("Upload {} failed while extracting: {}", upload_id, e);
+            tracker
+                .set_stage(
+                    upload_id,
+                    UploadStage::Failed {
+                        failed_stage: "extracting".to_string(),
+                        error: e.to_string(),
+                    },
+                )
+                .await;
+            Err(e)
+        }
+    }
+}
+
+async fn extract_file(path: &FsPath, format: &BodyFormat) -> Result<ProcessedDocument> {
+    let bytes = tokio::fs::read(path).await.context("Failed to read uploaded file")?;
+    let format = format.clone();
+    // Parsing a large PDF is CPU-bound
+    let document = tokio::task::spawn_blocking(move || {
+        DocumentProcessor::new().process(&bytes, format.content_type.as_deref(), format.file_name.as_deref())
+    })
+    .await
+    .context("Text extraction did not finish")??;
+    Ok(document)
+}
+
+// The whole background job of an accepted upload: extraction, then the
+// pipeline below. Every failure, an unreadable file included, ends in a
+// Failed stage the client polls for
+pub async fn ingest_upload<I: UploadIndexer>(
+    tracker: &UploadTracker,
+    indexer: &I,
+    upload_id: &str,
+    path: &FsPath,
+    format: &BodyFormat,
+    metadata: &UploadMetadata,
+) -> Result<String> {
+    let document = extract_upload(tracker, upload_id, path, format).await?;
+    process_upload(tracker, indexer, upload_id, &document, metadata).await
+}
+
+// Run an extracted upload through chunking, embedding and indexing. A
+// failure during indexing removes whatever part of the document already
+// reached the store.
+pub async fn process_upload<I: UploadIndexer>(
+    tracker: &UploadTracker,
+    indexer: &I,
+    upload_id: &str,
+    document: &ProcessedDocument,
+    metadata: &UploadMetadata,
+) -> Result<String> {
+    let result = run_pipeline(tracker, indexer, upload_id, document, metadata).await;
+
+    match result {
+        Ok((document_id, chunks)) => {
+            tracker
+                .set_stage(upload_id, UploadStage::Indexed { document_id: document_id.clone(), chunks })
+                .await;
+            info!("Upload {} indexed as document {} ({} chunks)", upload_id, document_id, chunks);
+            Ok(document_id)
+        }
+        Err((stage, e)) => {
+            error!("Upload {} failed while {}: {}", upload_id, stage, e);
+            tracker
+                .set_stage(
+                    upload_id,
+                    UploadStage::Failed {
+                        failed_stage: stage.to_string(),
+                        error: e.to_string(),
+                    },
+                )
+                .await;
+            Err(e)
+        }
+    }
+}
+
+async fn run_pipeline<I: UploadIndexer>(
+    tracker: &UploadTracker,
+    indexer: &I,
+    upload_id: &str,
+    document: &ProcessedDocument,
+    metadata: &UploadMetadata,
+) -> std::result::Result<(String, usize), (&'static str, anyhow::Error)> {
+    tracker.set_stage(upload_id, UploadStage::Chunking).await;
+    // Pages are chunked one at a time, so every chunk cites a single page
+    let chunks: Vec<(String, Option<usize>)> = document
+        .pages()
+        .into_iter()
+        .filter(|(_, text)| !text.trim().is_empty())
+        .flat_map(|(page, text)| indexer.chunk(text).into_iter().map(move |chunk| (chunk, page)))
+        .collect();
+    let total = chunks.len();
+
+    tracker.set_stage(upload_id, UploadStage::Embedding { done: 0, total }).await;
+    let mut embedded = Vec::with_capacity(total);
+    for (chunk, page) in chunks {
+        let embedding = indexer.embed(&chunk, metadata).await.map_err(|e| ("embedding", e))?;
+        embedded.push((chunk, embedding, page));
+        tracker
+            .set_stage(upload_id, UploadStage::Embedding { done: embedded.len(), total })
+            .await;
+    }
+
+    let document_id = Uuid::new_v4().to_string();
+    if let Err(e) = indexer.index(&document_id, metadata, embedded).await {
+        if let Err(cleanup) = indexer.remove(&document_id).await {
+            warn!("Failed to clean up partially indexed document {}: {}", document_id, cleanup);
+        }
+        return Err(("indexing", e));
+    }
+
+    Ok((document_id, total))
+}
+
+pub struct UploadManager {
+    tracker: Arc<UploadTracker>,
+    knowledge_service: Option<Arc<KnowledgeService>>,
+    upload_dir: PathBuf,
+}
+
+impl UploadManager {
+    pub async fn new(knowledge_service: Option<Arc<KnowledgeService>>) -> Result<Self> {
+        Self::in_dir(knowledge_service, DEFAULT_UPLOAD_DIR).await
+    }
+
+    // Keeps in-progress uploads under `upload_dir`
+    pub async fn in_dir(knowledge_service: Option<Arc<KnowledgeService>>, upload_dir: impl Into<PathBuf>) -> Result<Self> {
+        let upload_dir = upload_dir.into();
+        tokio::fs::create_dir_all(&upload_dir)
+            .await
+            .context("Failed to create upload directory")?;
+
+        // Temp files left by a crash mid-upload can never be resumed
+        let mut entries = tokio::fs::read_dir(&upload_dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            if entry.path().extension().map(|e| e == "part").unwrap_or(false) {
+                let _ = tokio::fs::remove_file(entry.path()).await;
+            }
+        }
+
+        Ok(Self {
+            tracker: Arc::new(UploadTracker::new()),
+            knowledge_service,
+            upload_dir,
+        })
+    }
+
+    pub fn tracker(&self) -> &Arc<UploadTracker> {
+        &self.tracker
+    }
+
+    fn temp_path(&self, upload_id: &str) -> PathBuf {
+        self.upload_dir.join(format!("{}.part", upload_id))
+    }
+
+    // Extraction runs here rather than in the request: parsing a large PDF
+    // takes a while, and a damaged one is reported through the status
+    fn spawn(&self, upload_id: String, path: PathBuf, format: BodyFormat, metadata: UploadMetadata) {
+        let Some(knowledge_service) = self.knowledge_service.clone() else {
+            return;
+        };
+        let tracker = self.tracker.clone();
+
+        tokio::spawn(async move {
+            let _ = ingest_upload(&tracker, knowledge_service.as_ref(), &upload_id, &path, &format, &metadata).await;
+        });
+    }
+}
+
+// Stream a multipart field to disk instead of buffering it in memory
+pub(crate) async fn write_field_to_file(field: &mut Field<'_>, path: &FsPath) -> Result<u64> {
+    let mut file = tokio::fs::File::create(path)
+        .await
+        .context("Failed to create upload temp file")?;
+    let mut written = 0u64;
+
+    while let Some(chunk) = field.chunk().await? {
+        file.write_all(&chunk).await?;
+        written += chunk.len() as u64;
+    }
+
+    file.flush().await?;
+    Ok(written)
+}
+
+// A form field that breaks the upload contract, reported as a 400 naming it
+#[derive(Debug, Clone, PartialEq)]
+pub struct UploadFieldError {
+    pub field: &'static str,
+    pub message: String,
+}
+
+impl UploadFieldError {
+    fn new(field: &'static str, message: impl Into<String>) -> Self {
+        Self { field, message: message.into() }
+    }
+
+    fn into_response(self) -> axum::response::Response {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": self.message, "field": self.field })),
+        )
+            .into_response()
+    }
+}
+
+// Which received body becomes the document
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UploadBody {
+    Content,
+    File,
+}
+
+// Everything the form sent, in whatever order it arrived. Bodies are
+// streamed to temp files; empty text fields count as not sent, so a blank
+// field cannot wipe out a value that came before it
+#[derive(Debug, Default)]
+struct UploadForm {
+    title: Option<String>,
+    source: Option<String>,
+    tags: Vec<String>,
+    trust_level: Option<String>,
+    session_id: Option<String>,
+    // Bytes of the "content" field
+    content: Option<u64>,
+    // Bytes of the "file" field and what its part header says about it
+    file: Option<(u64, BodyFormat)>,
+}
+
+fn non_empty(value: String) -> Option<String> {
+    let value = value.trim();
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+// Where a "content" body waits until the whole form has been read
+fn content_path(path: &FsPath) -> PathBuf {
+    path.with_extension("content.part")
+}
+
+// Streams a body field to `target`. An empty field leaves what an earlier
+// field of the same name wrote there
+async fn receive_body(field: &mut Field<'_>, target: &FsPath) -> Result<u64> {
+    let scratch = target.with_extension("next.part");
+    let bytes = write_field_to_file(field, &scratch).await?;
+    if bytes == 0 {
+        let _ = tokio::fs::remove_file(&scratch).await;
+    } else {
+        tokio::fs::rename(&scratch, target)
+            .await
+            .context("Failed to keep upload temp file")?;
+    }
+    Ok(bytes)
+}
+
+// Reads every form field; "file" goes to `path` and "content" next to it
+async fn receive_form(multipart: &mut Multipart, path: &FsPath) -> Result<UploadForm> {
+    let mut form = UploadForm::default();
+
+    while let Some(mut field) = multipart.next_field().await? {
+        let name = field.name().unwrap_or("").to_string();
+
+        match name.as_str() {
+            "file" => {
+                let format = BodyFormat {
+                    content_type: field.content_type().and_then(|t| non_empty(t.to_string())),
+                    file_name: field.file_name().and_then(|f| non_empty(f.to_string())),
+                };
+                let bytes = receive_body(&mut field, path).await?;
+                if bytes > 0 || form.file.is_none() {
+                    form.file = Some((bytes, format));
+                }
+            }
+            "content" => {
+                let bytes = receive_body(&mut field, &content_path(path)).await?;
+                if bytes > 0 || form.content.is_none() {
+                    form.content = Some(bytes);
+                }
+            }
+            "title" => form.title = non_empty(field.text().await?).or(form.title),
+            // Uploaded into a chat: an attachment searched only in that session
+            "session_id" => form.session_id = non_empty(field.text().await?).or(form.session_id),
+            "source" => form.source = non_empty(field.text().await?).or(form.source),
+            "tags" => {
+                form.tags = field
+                    .text()
+                    .await?
+                    .split(',')
+                    .map(|s| s.trim().to_string())
+                    .filter(|s| !s.is_empty())
+                    .collect()
+            }
+            "trust_level" => form.trust_level = non_empty(field.text().await?).or(form.trust_level),
+            // Drain fields we do not use so the stream can advance
+            _ => {
+                while field.chunk().await?.is_some() {}
+            }
+        }
+    }
+
+    Ok(form)
+}
+
+impl UploadForm {
+    // Applies the upload contract: a non-empty "content" or "file" is
+    // required, and "content" wins when both are sent. The source is the
+    // file's name, else the "source" field; a file with neither is refused.
+    // A missing title is taken from the file's name.
+    fn resolve(self) -> std::result::Result<(UploadMetadata, UploadBody, u64), UploadFieldError> {
+        let filename = self.file.as_ref().and_then(|(_, format)| format.file_name.clone());
+        let (body, bytes) = match (self.content, &self.file) {
+            (Some(bytes), _) if bytes > 0 => (UploadBody::Content, bytes),
+            (_, Some((bytes, _))) if *bytes > 0 => (UploadBody::File, *bytes),
+            (_, Some(_)) => return Err(UploadFieldError::new("file", "Uploaded file is empty")),
+            (Some(_), None) => return Err(UploadFieldError::new("content", "content must not be empty")),
+            (None, None) => return Err(UploadFieldError::new("content", "Either content or file is required")),
+        };
+
+        let source = match (filename.clone(), self.source) {
+            (Some(filename), _) => filename,
+            (None, Some(source)) => source,
+            (None, None) if body == UploadBody::File => {
+                return Err(UploadFieldError::new(
+                    "source",
+                    "source is required when the file has no filename",
+                ))
+            }
+            (None, None) => String::new(),
+        };
+
+        let title = self
+            .title
+            .or_else(|| {
+                let filename = filename?;
+                let stem = FsPath::new(&filename).file_stem()?.to_string_lossy().to_string();
+                non_empty(stem)
+            })
+            .ok_or_else(|| UploadFieldError::new("title", "title is required unless the file has a filename"))?;
+
+        let trust_level = match self.trust_level {
+            Some(level) => level.parse().map_err(|e: String| UploadFieldError::new("trust_level", e))?,
+            None => TrustLevel::default(),
+        };
+
+        let mut tags = self.tags;
+        if let Some(session_id) = self.session_id {
+            tags.push(retrieval::attachment_tag(&session_id));
+            tags.push(retrieval::ATTACHMENT_TAG.to_string());
+        }
+
+        Ok((UploadMetadata { title, source, tags, trust_level }, body, bytes))
+    }
+}
+
+// Leaves the chosen body at `path` and removes the other one
+async fn keep_body(path: &FsPath, body: UploadBody) -> Result<()> {
+    let content = content_path(path);
+    match body {
+        UploadBody::Content => tokio::fs::rename(&content, path)
+            .await
+            .context("Failed to move uploaded content")?,
+        UploadBody::File => {
+            let _ = tokio::fs::remove_file(&content).await;
+        }
+    }
+    Ok(())
+}
+
+async fn remove_received(path: &FsPath) {
+    let content = content_path(path);
+    for leftover in [path.to_path_buf(), path.with_extension("next.part"), content.with_extension("next.part"), content] {
+        let _ = tokio::fs::remove_file(leftover).await;
+    }
+}
+
+// HTTP Handlers
+pub async fn upload_document_handler(
+    State(state): State<Arc<crate::AppState>>,
+    mut multipart: Multipart,
+) -> impl IntoResponse {
+    if state.knowledge_service.is_none() {
+        return (StatusCode::SERVICE_UNAVAILABLE, "Knowledge base service is not available").into_response();
+    }
+
+    let uploads = &state.upload_manager;
+    let upload_id = Uuid::new_v4().to_string();
+    let path = uploads.temp_path(&upload_id);
+
+    let form = match receive_form(&mut multipart, &path).await {
+        Ok(form) => form,
+        Err(e) => {
+            warn!("Failed to receive upload: {}", e);
+            remove_received(&path).await;
+            return (StatusCode::BAD_REQUEST, format!("Failed to read upload: {}", e)).into_response();
+        }
+    };
+
+    let file_format = form.file.as_ref().map(|(_, format)| format.clone()).unwrap_or_default();
+    let (metadata, body, bytes) = match form.resolve() {
+        Ok(resolved) => resolved,
+        Err(e) => {
+            remove_received(&path).await;
+            return e.into_response();
+        }
+    };
+    if let Err(e) = keep_body(&path, body).await {
+        error!("Failed to keep upload {}: {}", upload_id, e);
+        remove_received(&path).await;
+        return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to store upload").into_response();
+    }
+    let format = match body {
+        UploadBody::Content => BodyFormat::text(),
+        UploadBody::File => file_format,
+    };
+
+    let status = uploads.tracker.create(&upload_id, &metadata, bytes).await;
+    uploads.spawn(upload_id.clone(), path, format, metadata);
+
+    (
+        StatusCode::ACCEPTED,
+        Json(serde_json::json!({
+            "upload_id": upload_id,
+            "status_url": format!("/api/v1/knowledge/upload/{}/status", upload_id),
+            "status": status.status,
+        })),
+    )
+        .into_response()
+}
+
+pub async fn upload_status_handler(
+    State(state): State<Arc<crate::AppState>>,
+    Path(upload_id): Path<String>,
+) -> impl IntoResponse {
+    match state.upload_manager.tracker.get(&upload_id).await {
+        Some(status) => Json(status).into_response(),
+        None => (StatusCode::NOT_FOUND, "Upload not found").into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rusty_ai_knowledge::ExtractionError;
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    #[derive(Default)]
+    struct SlowIndexer {
+        fail_embedding_at: Option<usize>,
+        fail_index: bool,
+        embedded: Mutex<usize>,
+        indexed: Mutex<HashMap<String, usize>>,
+        pages: Mutex<Vec<(String, Option<usize>)>>,
+        removed: Mutex<Vec<String>>,
+    }
+
+    impl UploadIndexer for SlowIndexer {
+        fn chunk(&self, text: &str) -> Vec<String> {
+            text.lines().map(|l| l.to_string()).collect()
+        }
+
+        async fn embed(&self, _chunk: &str, _metadata: &UploadMetadata) -> Result<Vec<f32>> {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            let mut embedded = self.embedded.lock().unwrap();
+            if Some(*embedded) == self.fail_embedding_at {
+                anyhow::bail!("embedding service unavailable");
+            }
+            *embedded += 1;
+            Ok(vec![0.0; 4])
+        }
+
+        async fn index(
+            &self,
+            document_id: &str,
+            _metadata: &UploadMetadata,
+            chunks: Vec<(String, Vec<f32>, Option<usize>)>,
+        ) -> Result<()> {
+            // Simulate a store that accepted some points before failing
+            self.indexed.lock().unwrap().insert(document_id.to_string(), chunks.len());
+            self.pages.lock().unwrap().extend(chunks.into_iter().map(|(chunk, _, page)| (chunk, page)));
+            if self.fail_index {
+                anyhow::bail!("vector store rejected upsert");
+            }
+            Ok(())
+        }
+
+        async fn remove(&self, document_id: &str) -> Result<()> {
+            self.indexed.lock().unwrap().remove(document_id);
+            self.removed.lock().unwrap().push(document_id.to_string());
+            Ok(())
+        }
+    }
+
+    async fn write_upload(content: impl AsRef<[u8]>) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("rusty-ai-upload-{}.part", Uuid::new_v4()));
+        tokio::fs::write(&path, content).await.unwrap();
+        path
+    }
+
+    // Extracts a text upload the way the handler does
+    async fn extracted(tracker: &UploadTracker, upload_id: &str, content: &str) -> ProcessedDocument {
+        let path = write_upload(content).await;
+        let document = extract_upload(tracker, upload_id, &path, &BodyFormat::text()).await.unwrap();
+        assert!(!path.exists());
+        document
+    }
+
+    fn metadata() -> UploadMetadata {
+        UploadMetadata {
+            title: "Notes".to_string(),
+            source: "notes.txt".to_string(),
+            tags: vec!["test".to_string()],
+            trust_level: TrustLevel::Personal,
+        }
+    }
+
+    fn drain(events: &mut broadcast::Receiver<UploadStatus>) -> Vec<UploadStage> {
+        let mut stages = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            stages.push(event.status);
+        }
+        stages
+    }
+
+    #[tokio::test]
+    async fn test_upload_progresses_through_every_stage() {
+        let tracker = UploadTracker::new();
+        let indexer = SlowIndexer::default();
+        let mut events = tracker.subscribe();
+
+        tracker.create("u1", &metadata(), 33).await;
+        let document = extracted(&tracker, "u1", "first line\nsecond line\nthird line").await;
+        let document_id = process_upload(&tracker, &indexer, "u1", &document, &metadata()).await.unwrap();
+
+        assert_eq!(
+            drain(&mut events),
+            vec![
+                UploadStage::Received { bytes: 33 },
+                UploadStage::Extracting,
+                UploadStage::Chunking,
+                UploadStage::Embedding { done: 0, total: 3 },
+                UploadStage::Embedding { done: 1, total: 3 },
+                UploadStage::Embedding { done: 2, total: 3 },
+                UploadStage::Embedding { done: 3, total: 3 },
+                UploadStage::Indexed { document_id: document_id.clone(), chunks: 3 },
+            ]
+        );
+        assert_eq!(indexer.indexed.lock().unwrap().get(&document_id), Some(&3));
+    }
+
+    #[tokio::test]
+    async fn test_finished_uploads_are_pruned_by_age_and_count() {
+        let tracker = UploadTracker::with_limits(chrono::Duration::minutes(10), 2);
+        for upload_id in ["u1", "u2", "u3"] {
+            tracker.create(upload_id, &metadata(), 10).await;
+            tracker
+                .set_stage(upload_id, UploadStage::Indexed { document_id: upload_id.to_string(), chunks: 1 })
+                .await;
+        }
+
+        // Over the cap: the oldest finished upload makes room
+        tracker.create("u4", &metadata(), 10).await;
+        assert!(tracker.get("u1").await.is_none());
+        assert!(tracker.get("u2").await.is_some());
+        assert!(tracker.get("u3").await.is_some());
+
+        // Past the TTL only the upload still in flight is kept
+        let later = chrono::Utc::now() + chrono::Duration::minutes(11);
+        assert_eq!(tracker.prune_finished(later).await, 2);
+        assert!(tracker.get("u4").await.is_some());
+        assert!(tracker.get("u3").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_embedding_failure_is_retrievable() {
+        let tracker = UploadTracker::new();
+        let indexer = SlowIndexer {
+            fail_embedding_at: Some(1),
+            ..Default::default()
+        };
+        tracker.create("u2", &metadata(), 22).await;
+        let document = extracted(&tracker, "u2", "first line\nsecond line").await;
+        assert!(process_upload(&tracker, &indexer, "u2", &document, &metadata()).await.is_err());
+
+        let status = tracker.get("u2").await.unwrap();
+        match status.status {
+            UploadStage::Failed { failed_stage, error } => {
+                assert_eq!(failed_stage, "embedding");
+                assert!(error.contains("embedding service unavailable"));
+            }
+            other => panic!("expected failure, got {:?}", other),
+        }
+        assert!(indexer.indexed.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_index_failure_removes_partial_document() {
+        let tracker = UploadTracker::new();
+        let indexer = SlowIndexer {
+            fail_index: true,
+            ..Default::default()
+        };
+        tracker.create("u3", &metadata(), 9).await;
+        let document = extracted(&tracker, "u3", "only line").await;
+        assert!(process_upload(&tracker, &indexer, "u3", &document, &metadata()).await.is_err());
+
+        let status = tracker.get("u3").await.unwrap();
+        assert!(matches!(status.status, UploadStage::Failed { ref failed_stage, .. } if failed_stage == "indexing"));
+        assert!(status.status.is_terminal());
+        assert!(indexer.indexed.lock().unwrap().is_empty());
+        assert_eq!(indexer.removed.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_pdf_chunks_carry_their_page_and_damaged_files_fail_in_the_background() {
+        const PDF: &[u8] = include_bytes!("../crates/knowledge/tests/fixtures/warranty.pdf");
+        let tracker = UploadTracker::new();
+        let indexer = SlowIndexer::default();
+        let format = BodyFormat {
+            content_type: Some("application/pdf".to_string()),
+            file_name: Some("warranty.pdf".to_string()),
+        };
+
+        tracker.create("u4", &metadata(), PDF.len() as u64).await;
+        ingest_upload(&tracker, &indexer, "u4", &write_upload(PDF).await, &format, &metadata()).await.unwrap();
+        assert_eq!(
+            *indexer.pages.lock().unwrap(),
+            vec![
+                ("Warranty covers parts for two years".to_string(), Some(1)),
+                ("Refunds are paid within 30 days".to_string(), Some(2)),
+            ]
+        );
+
+        // Accepted like any upload; the damage shows up in its status
+        tracker.create("u5", &metadata(), 100).await;
+        let path = write_upload(&PDF[..100]).await;
+        let error = ingest_upload(&tracker, &indexer, "u5", &path, &format, &metadata()).await.unwrap_err();
+        assert!(error.downcast_ref::<ExtractionError>().is_some(), "{}", error);
+        assert!(!path.exists());
+        match tracker.get("u5").await.unwrap().status {
+            UploadStage::Failed { failed_stage, error } => {
+                assert_eq!(failed_stage, "extracting");
+                assert!(error.contains("PDF"), "{}", error);
+            }
+            other => panic!("expected failure, got {:?}", other),
+        }
+        assert_eq!(indexer.pages.lock().unwrap().len(), 2);
+    }
+
+    const BOUNDARY: &str = "rusty-ai-upload-boundary";
+
+    // (name, file name, value) per form field
+    async fn multipart(fields: &[(&str, Option<&str>, &str)]) -> Multipart {
+        use axum::extract::FromRequest;
+
+        let mut body = String::new();
+        for (name, filename, value) in fields {
+            body.push_str(&format!("--{}\r\nContent-Disposition: form-data; name=\"{}\"", BOUNDARY, name));
+            if let Some(filename) = filename {
+                body.push_str(&format!("; filename=\"{}\"\r\nContent-Type: text/plain", filename));
+            }
+            body.push_str(&format!("\r\n\r\n{}\r\n", value));
+        }
+        body.push_str(&format!("--{}--\r\n", BOUNDARY));
+
+        let request = axum::http::Request::builder()
+            .header("content-type", format!("multipart/form-data; boundary={}", BOUNDARY))
+            .body(axum::body::Body::from(body))
+            .unwrap();
+        Multipart::from_request(request, &()).await.unwrap()
+    }
+
+    // Receives the form the way the handler does; returns the metadata and
+    // the document text that would be indexed
+    async fn receive(fields: &[(&str, Option<&str>, &str)]) -> std::result::Result<(UploadMetadata, String), UploadFieldError> {
+        let path = std::env::temp_dir().join(format!("rusty-ai-upload-{}.part", Uuid::new_v4()));
+        let form = receive_form(&mut multipart(fields).await, &path).await.unwrap();
+        let resolved = form.resolve();
+        let result = match resolved {
+            Ok((metadata, body, bytes)) => {
+                keep_body(&path, body).await.unwrap();
+                let text = tokio::fs::read_to_string(&path).await.unwrap();
+                assert_eq!(text.len() as u64, bytes);
+                Ok((metadata, text))
+            }
+            Err(e) => Err(e),
+        };
+        remove_received(&path).await;
+        assert!(!content_path(&path).exists());
+        result
+    }
+
+    fn permutations<T: Clone>(items: &[T]) -> Vec<Vec<T>> {
+        if items.len() <= 1 {
+            return vec![items.to_vec()];
+        }
+        let mut all = Vec::new();
+        for i in 0..items.len() {
+            let mut rest = items.to_vec();
+            let first = rest.remove(i);
+            for mut tail in permutations(&rest) {
+                tail.insert(0, first.clone());
+                all.push(tail);
+            }
+        }
+        all
+    }
+
+    #[tokio::test]
+    async fn test_fields_are_read_in_any_order() {
+        let fields = [
+            ("title", None, "Office handbook"),
+            ("tags", None, "work, hr"),
+            ("file", Some("handbook.txt"), "Visitors sign in at the front desk."),
+            ("content", None, ""),
+            ("trust_level", None, "verified"),
+        ];
+
+        for order in permutations(&fields) {
+            let (metadata, text) = receive(&order).await.unwrap();
+            assert_eq!(metadata.title, "Office handbook", "{:?}", order);
+            assert_eq!(metadata.tags, vec!["work", "hr"], "{:?}", order);
+            assert_eq!(metadata.source, "handbook.txt", "{:?}", order);
+            assert_eq!(metadata.trust_level, TrustLevel::Verified, "{:?}", order);
+            assert_eq!(text, "Visitors sign in at the front desk.", "{:?}", order);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_content_wins_over_file_and_filename_over_source() {
+        let fields = [
+            ("content", None, "Typed notes"),
+            ("file", Some("notes.txt"), "File notes"),
+            ("source", None, "clipboard"),
+        ];
+        for order in permutations(&fields) {
+            let (metadata, text) = receive(&order).await.unwrap();
+            assert_eq!(text, "Typed notes", "{:?}", order);
+            assert_eq!(metadata.source, "notes.txt", "{:?}", order);
+            assert_eq!(metadata.title, "notes", "{:?}", order);
+        }
+
+        // Without a filename the source field is used, and is then required
+        let (metadata, _) = receive(&[("title", None, "Scan"), ("file", None, "Page 1"), ("source", None, "scanner")])
+            .await
+            .unwrap();
+        assert_eq!(metadata.source, "scanner");
+        let error = receive(&[("title", None, "Scan"), ("file", None, "Page 1")]).await.unwrap_err();
+        assert_eq!(error.field, "source");
+    }
+
+    #[tokio::test]
+    async fn test_missing_fields_are_reported_by_name() {
+        // A missing title comes from the filename
+        let (metadata, _) = receive(&[("file", Some("meeting notes.md"), "Agenda")]).await.unwrap();
+        assert_eq!(metadata.title, "meeting notes");
+        assert!(metadata.tags.is_empty());
+
+        let (metadata, text) = receive(&[("title", None, "Idea"), ("content", None, "Solar kettle")]).await.unwrap();
+        assert_eq!((metadata.source.as_str(), text.as_str()), ("", "Solar kettle"));
+
+        let cases: [(&[(&str, Option<&str>, &str)], &str); 5] = [
+            (&[("content", None, "Solar kettle")], "title"),
+            (&[("title", None, "Idea")], "content"),
+            (&[("title", None, "Idea"), ("content", None, "")], "content"),
+            (&[("title", None, "Idea"), ("file", Some("idea.txt"), "")], "file"),
+            (&[("title", None, "Idea"), ("content", None, "x"), ("trust_level", None, "gospel")], "trust_level"),
+        ];
+        for (fields, field) in cases {
+            assert_eq!(receive(fields).await.unwrap_err().field, field, "{:?}", fields);
+        }
+    }
+
+    // The file used to be overwritten by blank fields that followed it
+    #[tokio::test]
+    async fn test_blank_fields_after_the_file_keep_it() {
+        let (metadata, text) = receive(&[
+            ("file", Some("report.txt"), "Q3 revenue grew 4%"),
+            ("title", None, "Q3 report"),
+            ("content", None, ""),
+            ("source", None, ""),
+            ("title", None, ""),
+            ("file", None, ""),
+        ])
+        .await
+        .unwrap();
+        assert_eq!(metadata.title, "Q3 report");
+        assert_eq!(metadata.source, "report.txt");
+        assert_eq!(text, "Q3 revenue grew 4%");
+    }
+}
@@ -0,0 +1,211 @@
+use serde::{Deserialize, Serialize};
+use whatlang::Lang;
+
+use crate::memory_policy::MemoryPolicyOverride;
+use crate::retrieval::SourceWeights;
+
+pub const DEFAULT_LANGUAGE: &str = "en";
+
+// Below this many characters whatlang guesses more than it detects
+const MIN_DETECTION_CHARS: usize = 12;
+
+// Languages we can both detect and speak: (ISO 639-1 code, whatlang language,
+// English name used in the prompt instruction)
+const SUPPORTED_LANGUAGES: &[(&str, Lang, &str)] = &[
+    ("en", Lang::Eng, "English"),
+    ("de", Lang::Deu, "German"),
+    ("fr", Lang::Fra, "French"),
+    ("es", Lang::Spa, "Spanish"),
+    ("it", Lang::Ita, "Italian"),
+    ("pt", Lang::Por, "Portuguese"),
+    ("nl", Lang::Nld, "Dutch"),
+    ("pl", Lang::Pol, "Polish"),
+];
+
+// Default voice per response language for each TTS provider:
+// (language, ElevenLabs voice id, OpenAI voice)
+const VOICE_TABLE: &[(&str, &str, &str)] = &[
+    ("en", "21m00Tcm4TlvDq8ikWAM", "alloy"),
+    ("de", "ErXwobaYiN019PkySvjV", "onyx"),
+    ("fr", "EXAVITQu4vr4xnSDxMaL", "nova"),
+    ("es", "AZnzlk1XvdvUeBnXmlld", "shimmer"),
+    ("it", "TxGEqnHWrfWFTfGW9XAT", "echo"),
+    ("pt", "pNInz6obpgDQGcFmaJgB", "fable"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TtsProvider {
+    ElevenLabs,
+    OpenAi,
+}
+
+// Per-session settings kept as JSON in the session's metadata column
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SessionSettings {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response_language: Option<String>,
+    // Overrides of the retrieval weight per source, e.g. to favour attachments
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_weights: Option<SourceWeights>,
+    // Who the assistant plays in this session, e.g. "a patient Spanish tutor"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub persona: Option<String>,
+    // The user's standing instructions, e.g. "use metric units"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instructions: Option<String>,
+    // How many and which memories the persona's prompts take
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory_policy: Option<MemoryPolicyOverride>,
+}
+
+impl SessionSettings {
+    pub fn from_metadata(metadata: Option<&str>) -> Self {
+        metadata
+            .and_then(|m| serde_json::from_str(m).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn to_metadata(&self) -> String {
+        serde_json::to_string(self).unwrap_or_else(|_| "{}".to_string())
+    }
+}
+
+// ISO 639-1 code of the message's language, if it is long enough to tell and
+// in a language we support
+pub fn detect_language(text: &str) -> Option<String> {
+    if text.trim().chars().count() < MIN_DETECTION_CHARS {
+        return None;
+    }
+
+    let info = whatlang::detect(text)?;
+    if !info.is_reliable() {
+        return None;
+    }
+
+    SUPPORTED_LANGUAGES
+        .iter()
+        .find(|(_, lang, _)| *lang == info.lang())
+        .map(|(code, _, _)| code.to_string())
+}
+
+// "de-DE" and "DE" both normalize to "de"; unsupported languages are dropped
+pub fn normalize_language(code: &str) -> Option<String> {
+    let primary = code.split(['-', '_']).next()?.trim().to_ascii_lowercase();
+    SUPPORTED_LANGUAGES
+        .iter()
+        .find(|(supported, _, _)| *supported == primary)
+        .map(|(supported, _, _)| supported.to_string())
+}
+
+// Language to answer in: what the message was written in, then the session
+// override, then the user's preference
+pub fn resolve_response_language(
+    detected: Option<&str>,
+    session_override: Option<&str>,
+    preference: Option<&str>,
+) -> String {
+    detected
+        .and_then(normalize_language)
+        .or_else(|| session_override.and_then(normalize_language))
+        .or_else(|| preference.and_then(normalize_language))
+        .unwrap_or_else(|| DEFAULT_LANGUAGE.to_string())
+}
+
+pub fn language_name(code: &str) -> &'static str {
+    SUPPORTED_LANGUAGES
+        .iter()
+        .find(|(supported, _, _)| *supported == code)
+        .map(|(_, _, name)| *name)
+        .unwrap_or("English")
+}
+
+// Appended to the system prompt so the model does not drift back to English
+// when retrieved context is in another language
+pub fn response_language_instruction(code: &str) -> String {
+    format!(
+        "Always respond in {} ({}), regardless of the language of any provided context.",
+        language_name(code),
+        code
+    )
+}
+
+// Default voice for a language, falling back to the provider's English voice
+pub fn default_voice(provider: TtsProvider, language: &str) -> &'static str {
+    let row = VOICE_TABLE
+        .iter()
+        .find(|(code, _, _)| *code == language)
+        .unwrap_or(&VOICE_TABLE[0]);
+    match provider {
+        TtsProvider::ElevenLabs => row.1,
+        TtsProvider::OpenAi => row.2,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Mixed-language messages as a user might send them in one session
+    const FIXTURES: &[(&str, Option<&str>)] = &[
+        ("Can you summarize what we discussed about the project budget yesterday?", Some("en")),
+        ("Kannst du mir bitte die wichtigsten Punkte aus dem Meeting zusammenfassen?", Some("de")),
+        ("Peux-tu me rappeler ce que nous avons décidé pour le voyage à Lyon ?", Some("fr")),
+        ("ok", None),
+    ];
+
+    #[test]
+    fn test_detects_mixed_language_messages() {
+        for (message, expected) in FIXTURES {
+            assert_eq!(detect_language(message).as_deref(), *expected, "message: {}", message);
+        }
+    }
+
+    #[test]
+    fn test_instruction_follows_each_message() {
+        let instructions: Vec<String> = FIXTURES
+            .iter()
+            .map(|(message, _)| {
+                let detected = detect_language(message);
+                response_language_instruction(&resolve_response_language(detected.as_deref(), None, Some("en-US")))
+            })
+            .collect();
+
+        assert!(instructions[0].contains("English (en)"));
+        assert!(instructions[1].contains("German (de)"));
+        assert!(instructions[2].contains("French (fr)"));
+        // Too short to detect, so the user's preference applies
+        assert!(instructions[3].contains("English (en)"));
+    }
+
+    #[test]
+    fn test_resolution_order() {
+        assert_eq!(resolve_response_language(Some("de"), Some("fr"), Some("en")), "de");
+        assert_eq!(resolve_response_language(None, Some("fr"), Some("en")), "fr");
+        assert_eq!(resolve_response_language(None, None, Some("de-AT")), "de");
+        assert_eq!(resolve_response_language(None, Some("xx"), None), DEFAULT_LANGUAGE);
+    }
+
+    #[test]
+    fn test_voice_follows_response_language() {
+        let german = resolve_response_language(detect_language(FIXTURES[1].0).as_deref(), None, None);
+        assert_eq!(default_voice(TtsProvider::OpenAi, &german), "onyx");
+        assert_eq!(default_voice(TtsProvider::ElevenLabs, &german), "ErXwobaYiN019PkySvjV");
+
+        assert_eq!(default_voice(TtsProvider::OpenAi, "en"), "alloy");
+        // Supported for detection but without a dedicated voice
+        assert_eq!(default_voice(TtsProvider::OpenAi, "pl"), "alloy");
+    }
+
+    #[test]
+    fn test_session_settings_round_trip() {
+        let settings = SessionSettings {
+            response_language: Some("de".to_string()),
+            source_weights: Some(SourceWeights { attachment: Some(2.0), ..Default::default() }),
+            persona: Some("A patient tutor".to_string()),
+            instructions: None,
+            memory_policy: Some(MemoryPolicyOverride { max_items: Some(1), ..Default::default() }),
+        };
+        assert_eq!(SessionSettings::from_metadata(Some(&settings.to_metadata())), settings);
+        assert_eq!(SessionSettings::from_metadata(Some("not json")), SessionSettings::default());
+    }
+}
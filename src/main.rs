@@ -1,7 +1,7 @@
 use anyhow::Result;
 use axum::{
-    extract::{State, Json, WebSocketUpgrade},
-    http::{Method, StatusCode},
+    extract::{DefaultBodyLimit, Path, State, Json, WebSocketUpgrade},
+    http::{HeaderMap, Method, StatusCode},
     response::{IntoResponse, Response},
     routing::{get, post},
     Router,
@@ -9,17 +9,71 @@ use axum::{
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use tower_http::cors::{CorsLayer, Any};
-use tracing::{info, error, debug};
+use tracing::{info, warn, error, debug};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 mod ai_service;
 mod voice_service;
 mod knowledge_service_simple;
 mod memory_service;
+mod chat_pipeline;
+mod startup;
+mod crawler;
+mod knowledge_upload;
+mod language;
+mod transcription;
+mod voice_playback;
+mod knowledge_annotations;
+mod knowledge_scroll;
+mod data_residency;
+mod retrieval;
+mod self_check;
+mod query_metrics;
+mod vector_store;
+mod ephemeral;
+mod guardrails;
+mod trust;
+mod profile_summary;
+mod payload_crypto;
+mod envelope;
+mod http_client;
+mod summarization;
+mod extraction;
+mod memory_policy;
+mod document_revisions;
+mod keyword_index;
+mod backup;
+mod callbacks;
 use ai_service::{AIService, ConversationStore};
 use voice_service::VoiceService;
-use knowledge_service_simple::{KnowledgeService, upload_document_handler, search_documents_handler, knowledge_stats_handler, list_documents_handler};
+use knowledge_service_simple::{KnowledgeService, TagFilter, TagMode, search_documents_handler, knowledge_stats_handler, list_documents_handler, update_document_handler, replace_document_handler, delete_document_handler, delete_documents_by_source_handler, get_chunk_handler, correct_chunk_handler};
+use knowledge_annotations::AnnotationStore;
+use document_revisions::RevisionStore;
+use keyword_index::KeywordIndex;
+use backup::BackupConfig;
+use callbacks::{CallbackConfig, CallbackService};
+use knowledge_scroll::ScrollConfig;
 use memory_service::MemoryService;
+use startup::{ComponentRegistry, StartupOptions};
+use crawler::CrawlManager;
+use payload_crypto::PayloadCipher;
+use knowledge_upload::{UploadManager, upload_document_handler, upload_status_handler};
+use transcription::{TranscriptionConfig, TranscriptionManager};
+use voice_playback::{InterruptReason, PlaybackEvent, PlaybackOutcome, PlaybackSummary, StreamingTts, VoiceCommand, VoiceSession};
+use chat_pipeline::{LatencyBudget, PipelineConfig, PipelineMetrics, PipelineTimings};
+use data_residency::{ProviderClass, ResidencyPolicy};
+use retrieval::{RetrievalConfig, SourceKind, SourceWeights};
+use memory_policy::{MemoryPolicy, MemoryPolicyOverride, MemoryTrace};
+use vector_store::VectorStore;
+use ephemeral::EphemeralStack;
+use guardrails::{Guardrails, PromptCustomization};
+use trust::TrustLevel;
+use envelope::{created, fail, ok, respond};
+use http_client::HttpClientFactory;
+use query_metrics::TokenUsage;
+use summarization::{Summarizer, Summary, SummaryConfig, SummaryOptions};
+use extraction::{ExtractionConfig, ExtractionRequest, Extractor, SchemaMismatch};
+use rusty_ai_common::ApiResponse;
 
 // Request/Response structures
 #[derive(Debug, Deserialize)]
@@ -27,12 +81,119 @@ struct ChatRequest {
     message: String,
     #[serde(default)]
     session_id: Option<String>,
+    // The user's preferred language, used when the message itself is too
+    // short to detect and the session has no override
+    #[serde(default)]
+    language: Option<String>,
+    // Adds the retrieval explain trace to the response
+    #[serde(default)]
+    explain: bool,
+}
+
+// Non-voice messages a WebSocket client can send
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ClientMessage {
+    Chat(ChatRequest),
+}
+
+#[derive(Debug, Deserialize)]
+struct SessionMessagesQuery {
+    // Comma-separated extras; "stats" adds per-message and session totals
+    #[serde(default)]
+    include: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
 struct ChatResponse {
     response: String,
+    // Id of the stored assistant message, passed back in tts_start
+    message_id: String,
     session_id: String,
+    response_language: String,
+    // Reported in the envelope's meta over HTTP
+    #[serde(skip)]
+    timings: PipelineTimings,
+    #[serde(skip)]
+    pipeline_mode: String,
+    // Retrieved documents, including those withheld from the model
+    sources: Vec<ChatSource>,
+    // Trust level most of the context came from; absent without context
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dominant_trust: Option<TrustLevel>,
+    // How retrieval chose the context; only when the request asked to explain
+    #[serde(skip_serializing_if = "Option::is_none")]
+    explain: Option<RetrievalExplain>,
+}
+
+#[derive(Debug, Serialize)]
+struct RetrievalExplain {
+    // Memories admitted to the prompt and the close ones left out
+    memories: MemoryTrace,
+}
+
+#[derive(Debug, Serialize)]
+struct ChatSource {
+    document_id: String,
+    title: String,
+    chunk_index: usize,
+    // Document, memory or attachment
+    origin: SourceKind,
+    trust_level: TrustLevel,
+    // Why the chunk was left out of the prompt; absent when it was used
+    #[serde(skip_serializing_if = "Option::is_none")]
+    withheld: Option<String>,
+    // Found by the keyword fallback while the vector search was unavailable
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    partial: bool,
+    // Page of the uploaded file the chunk came from
+    #[serde(skip_serializing_if = "Option::is_none")]
+    page: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SessionSettingsUpdate {
+    // null clears the override
+    response_language: Option<String>,
+    // Per-source retrieval weights for the session; null restores the defaults
+    #[serde(default)]
+    source_weights: Option<SourceWeights>,
+    #[serde(default)]
+    persona: Option<String>,
+    #[serde(default)]
+    instructions: Option<String>,
+    // Memory cap and floors for the persona; null restores the defaults
+    #[serde(default)]
+    memory_policy: Option<MemoryPolicyOverride>,
+}
+
+impl SessionSettingsUpdate {
+    // Names of the settings this update gives a value
+    fn fields_set(&self) -> Vec<&'static str> {
+        [
+            ("response_language", self.response_language.is_some()),
+            ("source_weights", self.source_weights.is_some()),
+            ("persona", self.persona.is_some()),
+            ("instructions", self.instructions.is_some()),
+            ("memory_policy", self.memory_policy.is_some()),
+        ]
+        .into_iter()
+        .filter_map(|(field, set)| set.then_some(field))
+        .collect()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ProfileSummaryQuery {
+    // Reports the persona and instructions of this session
+    #[serde(default)]
+    session_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ForkSessionRequest {
+    // The last message copied into the fork
+    from_message_id: String,
 }
 
 #[derive(Debug, Serialize)]
@@ -40,6 +201,7 @@ struct HealthResponse {
     status: String,
     service: String,
     version: String,
+    disabled_components: Vec<String>,
 }
 
 // Application state
@@ -47,13 +209,67 @@ struct HealthResponse {
 pub struct AppState {
     pub ai_service: Arc<AIService>,
     pub conversation_store: Arc<ConversationStore>,
-    pub voice_service: Arc<VoiceService>,
+    pub voice_service: Option<Arc<VoiceService>>,
     pub knowledge_service: Option<Arc<KnowledgeService>>,
+    // User notes on knowledge documents; also indexed next to the chunks
+    pub annotation_store: Arc<AnnotationStore>,
+    // Diffs recorded when a connector re-ingests a changed document
+    pub revision_store: Arc<RevisionStore>,
     pub memory_service: Option<Arc<MemoryService>>,
+    pub pipeline_config: PipelineConfig,
+    pub pipeline_metrics: Arc<PipelineMetrics>,
+    // Per-source weights, caps and thresholds for chat retrieval
+    pub retrieval_config: RetrievalConfig,
+    // How many and which memories a prompt takes
+    pub memory_policy: MemoryPolicy,
+    pub components: Arc<ComponentRegistry>,
+    pub crawl_manager: Arc<CrawlManager>,
+    pub upload_manager: Arc<UploadManager>,
+    pub transcription_manager: Arc<TranscriptionManager>,
+    // Operations waiting for asynchronous providers to call back
+    pub callbacks: Arc<CallbackService>,
+    // Which provider classes may see documents with a given tag
+    pub residency: Arc<ResidencyPolicy>,
+    // Deployment instructions that lead every system prompt and the session
+    // settings users may not change
+    pub guardrails: Arc<Guardrails>,
+    // Model calls and tokens per feature (chat, summarization)
+    pub token_usage: Arc<TokenUsage>,
+    // Whole-document summaries, cached per document version and options
+    pub summarizer: Arc<Summarizer>,
+    // Schema-constrained extraction, and the extractions running as jobs
+    pub extractor: Arc<Extractor>,
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    // `rusty-ai-api check [--json]` validates the configured integrations and
+    // exits; `serve --check [--force]` runs the same checks before starting
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("check") {
+        dotenv::dotenv().ok();
+        let report = self_check::run_live().await?;
+        if args.iter().any(|a| a == "--json") {
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        } else {
+            report.print_table();
+        }
+        std::process::exit(if report.passed() { 0 } else { 1 });
+    }
+    
+    // `rusty-ai-api restore <backup-dir> [--database-only]` puts a backup
+    // back in place; run it while the server is stopped
+    if args.get(1).map(String::as_str) == Some("restore") {
+        dotenv::dotenv().ok();
+        let Some(backup) = args.get(2).filter(|a| !a.starts_with("--")) else {
+            anyhow::bail!("usage: rusty-ai-api restore <backup-dir> [--database-only]");
+        };
+        let vectors = !args.iter().any(|a| a == "--database-only");
+        let summary = backup::restore_from_env(std::path::Path::new(backup), vectors).await?;
+        println!("{}", summary);
+        return Ok(());
+    }
+    
     // Initialize tracing
     tracing_subscriber::registry()
         .with(
@@ -68,83 +284,321 @@ async fn main() -> Result<()> {
     // Load environment variables
     dotenv::dotenv().ok();
     
+    if args.iter().any(|a| a == "--check") {
+        let report = self_check::run_live().await?;
+        report.print_table();
+        if !report.passed() {
+            if !args.iter().any(|a| a == "--force") {
+                anyhow::bail!("Startup self-check found {} critical failure(s); pass --force to start anyway", report.critical_failures);
+            }
+            warn!("Starting despite {} failed startup check(s) (--force)", report.critical_failures);
+        }
+    }
+    
+    // Everything in memory with fake providers; kept alive until shutdown
+    let ephemeral = if ephemeral::requested(&args) {
+        warn!("Ephemeral profile: nothing is persisted and external providers are replaced by fakes");
+        Some(EphemeralStack::from_env().await?)
+    } else {
+        None
+    };
+    
+    let state = build_state(&args, ephemeral.as_ref()).await?;
+    let app = build_router(state);
+    
+    // Get the port from environment or use default
+    let port = std::env::var("PORT").unwrap_or_else(|_| "8081".to_string());
+    let addr = format!("0.0.0.0:{}", port);
+    
+    info!("Server starting on http://{}", addr);
+    
+    // Parse the address
+    let listener = tokio::net::TcpListener::bind(&addr).await?;
+    info!("Server listening on http://{}", addr);
+    
+    // Start the server
+    axum::serve(listener, app)
+        .await
+        .map_err(|e| anyhow::anyhow!("Server error: {}", e))?;
+    
+    Ok(())
+}
+
+// Services and stores, built once at startup. With an ephemeral stack
+// everything is in memory and talks to its fake providers.
+async fn build_state(args: &[String], ephemeral: Option<&EphemeralStack>) -> Result<Arc<AppState>> {
+    // Proxy, extra CAs and timeouts of every outbound client; an unreadable
+    // CA file stops the server here
+    let http = HttpClientFactory::from_env()?;
+    
     // Initialize AI service
-    let ai_service = AIService::new(None)?; // Will use OPENAI_API_KEY env var
+    let ai_service = match ephemeral {
+        Some(stack) => AIService::from_config(stack.openai_config()),
+        None => AIService::new(None)?.with_http(&http)?, // Will use OPENAI_API_KEY env var
+    };
+    let ai_service = match std::env::var("CHAT_MODEL") {
+        Ok(model) => ai_service.with_model(model),
+        Err(_) => ai_service,
+    };
+    
+    let residency = Arc::new(ResidencyPolicy::from_env()?);
+    if !residency.is_empty() {
+        info!("Data residency policy active; chat provider is {}", ai_service.provider_class());
+    }
+    
+    let guardrails = Arc::new(Guardrails::from_env()?);
+    guardrails.spawn_reloader();
     
     // Initialize conversation store
-    let database_url = std::env::var("DATABASE_URL")
-        .unwrap_or_else(|_| "sqlite:./data/rusty_ai.db".to_string());
+    let database_url = match ephemeral {
+        Some(stack) => stack.database_url().to_string(),
+        None => std::env::var("DATABASE_URL")
+            .unwrap_or_else(|_| "sqlite:./data/rusty_ai.db".to_string()),
+    };
     let conversation_store = ConversationStore::new(&database_url).await?;
+    let annotation_store = AnnotationStore::new(&database_url).await?;
+    let revision_store = Arc::new(RevisionStore::new(&database_url).await?);
+    let keyword_index = Arc::new(KeywordIndex::new(&database_url).await?);
+    
+    // Components listed in --disable are skipped, and components that keep
+    // failing to initialize are started in safe mode instead of crash-looping
+    let mut startup_options = StartupOptions::from_env_and_args(args.iter().cloned());
+    if let Some(stack) = ephemeral {
+        startup_options.state_file = stack.data_dir().join("startup_state.json");
+    }
+    let components = Arc::new(ComponentRegistry::new(startup_options));
     
     // Initialize voice service
     let elevenlabs_api_key = std::env::var("ELEVENLABS_API_KEY").ok();
-    let voice_service = VoiceService::new(None, elevenlabs_api_key)?;
+    let voice_service = components
+        .initialize("voice", async {
+            match ephemeral {
+                Some(stack) => Ok(VoiceService::from_config(stack.openai_config(), None)),
+                None => VoiceService::new(None, elevenlabs_api_key)?.with_http(&http),
+            }
+        })
+        .await
+        .map(Arc::new);
     
     // Initialize knowledge service (optional - if Qdrant is not available, backend can still run)
-    let knowledge_service = match KnowledgeService::new(None).await {
-        Ok(service) => {
+    let payload_cipher = PayloadCipher::from_env()?;
+    let knowledge_service = components
+        .initialize("knowledge", async {
+            let service = match ephemeral {
+                Some(stack) => {
+                    KnowledgeService::with_backends(VectorStore::in_memory(), stack.openai_config(), None, residency.clone())
+                        .await
+                        .map(|service| {
+                            service.with_scroll_config(ScrollConfig {
+                                state_dir: stack.data_dir().join("knowledge_jobs"),
+                                ..ScrollConfig::from_env()
+                            })
+                        })
+                }
+                None => KnowledgeService::new(None, residency.clone(), &http).await,
+            };
+            service.map(|service| {
+                service
+                    .with_payload_cipher(payload_cipher)
+                    .with_revisions(revision_store.clone())
+                    .with_keyword_index(keyword_index.clone())
+            })
+        })
+        .await
+        .map(|service| {
             info!("Knowledge service initialized successfully");
-            Some(Arc::new(service))
-        }
-        Err(e) => {
-            error!("Failed to initialize knowledge service (Qdrant may not be running): {}", e);
-            info!("Starting without knowledge base features - chat and voice will still work");
-            None
-        }
-    };
+            Arc::new(service)
+        });
+    if knowledge_service.is_none() {
+        info!("Starting without knowledge base features - chat and voice will still work");
+    }
+    
+    // Chunks still encrypted under a retired payload key are moved to the
+    // current one in the background, and chunks stored under random point
+    // ids to their deterministic ones; searches read both meanwhile
+    if let Some(ks) = &knowledge_service {
+        let ks = Arc::clone(ks);
+        tokio::spawn(async move {
+            if let Err(e) = ks.rotate_payload_keys().await {
+                warn!("Payload key rotation failed: {}", e);
+            }
+            if let Err(e) = ks.migrate_point_ids().await {
+                warn!("Point id migration failed: {}", e);
+            }
+            if let Err(e) = ks.fill_keyword_index().await {
+                warn!("Filling the keyword index failed: {}", e);
+            }
+        });
+    }
     
     // Initialize memory service (requires knowledge service)
     let memory_service = match &knowledge_service {
         Some(ks) => {
-            match MemoryService::new(None, Arc::clone(ks)) {
-                Ok(service) => {
+            let ks = Arc::clone(ks);
+            let http = http.clone();
+            components
+                .initialize("memory", async move {
+                    match ephemeral {
+                        Some(stack) => Ok(MemoryService::from_config(stack.openai_config(), ks)),
+                        None => MemoryService::new(None, ks)?.with_http(&http),
+                    }
+                })
+                .await
+                .map(|service| {
                     info!("Memory service initialized successfully");
-                    Some(Arc::new(service))
-                }
-                Err(e) => {
-                    error!("Failed to initialize memory service: {}", e);
-                    None
-                }
-            }
+                    Arc::new(service)
+                })
         }
         None => {
             info!("Memory service disabled (requires knowledge service)");
+            components.mark_unavailable("memory", "requires knowledge service");
             None
         }
     };
     
+    let disabled = components.disabled_components();
+    if !disabled.is_empty() {
+        warn!("Running with disabled components: {}", disabled.join(", "));
+    }
+    
+    // Crawl connector; resumes crawls interrupted by the last shutdown
+    let crawl_manager = Arc::new(match ephemeral {
+        Some(stack) => CrawlManager::in_dir(knowledge_service.clone(), stack.data_dir().join("crawls"), &http).await?,
+        None => CrawlManager::new(knowledge_service.clone(), &http).await?,
+    });
+    
+    // Knowledge uploads are indexed in the background and report progress
+    let upload_manager = Arc::new(match ephemeral {
+        Some(stack) => UploadManager::in_dir(knowledge_service.clone(), stack.data_dir().join("uploads")).await?,
+        None => UploadManager::new(knowledge_service.clone()).await?,
+    });
+    
+    // Providers that answer later post to /api/v1/callbacks/:integration
+    let mut callback_config = CallbackConfig::from_env()?;
+    if let Some(stack) = ephemeral {
+        callback_config.state_path = stack.data_dir().join("callbacks.json");
+    }
+    let callbacks = Arc::new(CallbackService::open(callback_config)?);
+    
+    // Uploaded recordings are transcribed in the background, segment by
+    // segment or by a provider that calls back; unfinished jobs resume from
+    // the persisted queue
+    let mut transcription_config = TranscriptionConfig::from_env();
+    if let Some(stack) = ephemeral {
+        transcription_config.media_dir = stack.data_dir().join("media");
+    }
+    let transcription_manager = Arc::new(
+        TranscriptionManager::new(
+            transcription_config,
+            voice_service.clone(),
+            knowledge_service.clone(),
+            callbacks.clone(),
+            &http,
+        )
+        .await?,
+    );
+    
+    // The database and a snapshot of the vector collections on one cadence;
+    // the ephemeral profile has nothing worth keeping
+    if ephemeral.is_none() {
+        backup::spawn_scheduler(BackupConfig::from_env(), database_url.clone(), knowledge_service.clone());
+    }
+    
+    let token_usage = Arc::new(TokenUsage::new());
+    let summarizer = Arc::new(Summarizer::new(SummaryConfig::from_env(), token_usage.clone()));
+    let extractor = Arc::new(Extractor::new(ExtractionConfig::from_env(), token_usage.clone()));
+    
     // Create application state
-    let state = Arc::new(AppState {
+    Ok(Arc::new(AppState {
         ai_service: Arc::new(ai_service),
         conversation_store: Arc::new(conversation_store),
-        voice_service: Arc::new(voice_service),
+        voice_service,
         knowledge_service,
+        annotation_store: Arc::new(annotation_store),
+        revision_store,
         memory_service,
-    });
-    
-    // Build the router
-    let app = Router::new()
+        pipeline_config: PipelineConfig::from_env(),
+        pipeline_metrics: Arc::new(PipelineMetrics::new()),
+        retrieval_config: RetrievalConfig::from_env(),
+        memory_policy: MemoryPolicy::from_env(),
+        components,
+        crawl_manager,
+        upload_manager,
+        transcription_manager,
+        callbacks,
+        residency,
+        guardrails,
+        token_usage,
+        summarizer,
+        extractor,
+    }))
+}
+
+fn build_router(state: Arc<AppState>) -> Router {
+    Router::new()
         // Health check endpoints
         .route("/health", get(health_check))
         .route("/health/ready", get(health_ready))
         .route("/health/live", get(health_live))
+        .route("/metrics/pipeline", get(pipeline_metrics_handler))
         
         // API v1 routes
+        // Admin endpoints
+        .route("/api/v1/admin/components", get(components_handler))
+        .route("/api/v1/admin/components/:name/enable", post(enable_component_handler))
+        .route("/api/v1/admin/storage/slow-queries", get(slow_queries_handler))
+        
         .route("/api/v1/conversation/send", post(chat_handler))
         .route("/api/v1/conversation/history", get(get_history))
         .route("/api/v1/conversation/sessions", get(get_sessions))
         .route("/api/v1/conversation/session/:id", get(get_session_messages))
+        .route("/api/v1/conversation/session/:id/settings", get(get_session_settings).put(update_session_settings))
+        .route("/api/v1/conversation/session/:id/fork", post(fork_session))
+        .route("/api/v1/me/profile-summary", get(profile_summary_handler))
         
         // Voice endpoints
         .route("/api/v1/voice/transcribe", post(voice_service::transcribe_handler))
         .route("/api/v1/voice/synthesize", post(voice_service::synthesize_handler))
         .route("/api/v1/voice/health", get(voice_service::voice_health))
+        .route(
+            "/api/v1/voice/transcriptions",
+            post(transcription::create_transcription_handler)
+                .layer(DefaultBodyLimit::max(transcription::MAX_RECORDING_BYTES)),
+        )
+        .route("/api/v1/voice/transcriptions/:id", get(transcription::transcription_status_handler))
+        
+        // Results posted back by asynchronous providers
+        .route("/api/v1/callbacks/:integration", post(callbacks::callback_handler))
         
         // Knowledge base endpoints
         .route("/api/v1/knowledge/upload", post(upload_document_handler))
+        .route("/api/v1/knowledge/upload/:id/status", get(upload_status_handler))
         .route("/api/v1/knowledge/search", get(search_documents_handler))
         .route("/api/v1/knowledge/stats", get(knowledge_stats_handler))
-        .route("/api/v1/knowledge/documents", get(list_documents_handler))
+        .route("/api/v1/knowledge/extract", post(extract_handler))
+        .route("/api/v1/knowledge/extract/:id", get(extraction_job_handler))
+        .route("/api/v1/knowledge/documents", get(list_documents_handler).delete(delete_documents_by_source_handler))
+        .route(
+            "/api/v1/knowledge/documents/:id",
+            axum::routing::delete(delete_document_handler).patch(update_document_handler).put(replace_document_handler),
+        )
+        .route("/api/v1/knowledge/documents/:id/chunks/:index", get(get_chunk_handler).patch(correct_chunk_handler))
+        .route("/api/v1/knowledge/documents/:id/summarize", post(summarize_document_handler))
+        .route("/api/v1/knowledge/documents/:id/revisions", get(document_revisions::list_revisions_handler))
+        .route(
+            "/api/v1/knowledge/documents/:id/annotations",
+            get(knowledge_annotations::list_annotations_handler).post(knowledge_annotations::create_annotation_handler),
+        )
+        .route(
+            "/api/v1/knowledge/documents/:id/annotations/:annotation_id",
+            axum::routing::put(knowledge_annotations::update_annotation_handler)
+                .delete(knowledge_annotations::delete_annotation_handler),
+        )
+        
+        // Connector endpoints
+        .route("/api/v1/connectors", get(crawler::list_connectors_handler))
+        .route("/api/v1/connectors/crawl", post(crawler::start_crawl_handler))
+        .route("/api/v1/connectors/crawl/:id", get(crawler::crawl_status_handler))
         
         // WebSocket endpoint
         .route("/ws", get(websocket_handler))
@@ -152,90 +606,277 @@ async fn main() -> Result<()> {
         // Add state
         .with_state(state)
         
+        // Every response carries the id of its request
+        .layer(axum::middleware::from_fn(envelope::request_id_middleware))
+        
         // Add CORS layer to allow frontend connections
         .layer(
             CorsLayer::new()
                 .allow_origin(Any)
                 .allow_methods([Method::GET, Method::POST, Method::PUT, Method::DELETE])
                 .allow_headers(Any)
-        );
-    
-    // Get the port from environment or use default
-    let port = std::env::var("PORT").unwrap_or_else(|_| "8081".to_string());
-    let addr = format!("0.0.0.0:{}", port);
-    
-    info!("Server starting on http://{}", addr);
-    
-    // Parse the address
-    let listener = tokio::net::TcpListener::bind(&addr).await?;
-    info!("Server listening on http://{}", addr);
-    
-    // Start the server
-    axum::serve(listener, app)
-        .await
-        .map_err(|e| anyhow::anyhow!("Server error: {}", e))?;
-    
-    Ok(())
+        )
 }
 
 // Health check handlers
-async fn health_check() -> impl IntoResponse {
-    Json(HealthResponse {
-        status: "healthy".to_string(),
+async fn health_check(
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    let disabled_components = state.components.disabled_components();
+    ok(HealthResponse {
+        status: if disabled_components.is_empty() { "healthy" } else { "degraded" }.to_string(),
         service: "rusty-ai".to_string(),
         version: "0.1.0".to_string(),
+        disabled_components,
     })
 }
 
-async fn health_ready() -> impl IntoResponse {
-    Json(serde_json::json!({
-        "ready": true,
-        "timestamp": chrono::Utc::now().to_rfc3339()
+// Admin overview of component status
+async fn components_handler(
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    ok(serde_json::json!({
+        "components": state.components.statuses(),
     }))
 }
 
-async fn health_live() -> impl IntoResponse {
-    Json(serde_json::json!({
-        "alive": true,
-        "timestamp": chrono::Utc::now().to_rfc3339()
+// The slowest conversation store queries, slowest first, and per-operation
+// timings
+async fn slow_queries_handler(
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    let metrics = state.conversation_store.query_metrics();
+    ok(serde_json::json!({
+        "slow_queries": metrics.slow_queries(),
+        "operations": metrics.operations(),
     }))
 }
 
+// Clear a component's safe-mode state; services are built once at startup,
+// so the component comes back on the next restart
+async fn enable_component_handler(
+    State(state): State<Arc<AppState>>,
+    axum::extract::Path(name): axum::extract::Path<String>,
+) -> impl IntoResponse {
+    if !startup::COMPONENTS.contains(&name.as_str()) {
+        return fail(StatusCode::NOT_FOUND, format!("Unknown component: {}", name));
+    }
+    
+    match state.components.reset(&name) {
+        Ok(()) => {
+            info!("Cleared safe-mode state for component '{}'", name);
+            ok(serde_json::json!({
+                "component": name,
+                "status": state.components.status(&name),
+                "restart_required": !state.components.status(&name).map(|s| s.is_enabled()).unwrap_or(false),
+            }))
+        }
+        Err(e) => {
+            error!("Failed to reset component '{}': {}", name, e);
+            fail(StatusCode::INTERNAL_SERVER_ERROR, "Failed to update startup state")
+        }
+    }
+}
+
+async fn health_ready() -> impl IntoResponse {
+    ok(serde_json::json!({ "ready": true }))
+}
+
+async fn health_live() -> impl IntoResponse {
+    ok(serde_json::json!({ "alive": true }))
+}
+
 // Chat handler with RAG
 async fn chat_handler(
     State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
     Json(payload): Json<ChatRequest>,
 ) -> impl IntoResponse {
+    let reply = run_chat(&state, payload, &guardrails::groups_from_headers(&headers)).await;
+    let response = ApiResponse::success(&reply)
+        .with_meta("pipeline_mode", &reply.pipeline_mode)
+        .with_meta("timings", &reply.timings);
+    respond(StatusCode::OK, response)
+}
+
+// The chat pipeline shared by the HTTP endpoint and WebSocket chat messages.
+// `groups` select the group-scoped guardrails
+async fn run_chat(state: &Arc<AppState>, payload: ChatRequest, groups: &[String]) -> ChatResponse {
     debug!("Received chat request: {:?}", payload);
     
     let session_id = payload.session_id.unwrap_or_else(|| {
         uuid::Uuid::new_v4().to_string()
     });
     
-    // Search knowledge base for relevant context (if available)
-    let mut context = String::new();
-    if let Some(ref knowledge_service) = state.knowledge_service {
-        // Search with lower threshold to find more matches
-        if let Ok(search_results) = knowledge_service
-            .search_documents(&payload.message, 5, 0.1, None)
-            .await 
+    let budget = LatencyBudget::start(state.pipeline_config.clone());
+    
+    let existing_session = state.conversation_store.get_session(&session_id).await.unwrap_or_else(|e| {
+        warn!("Failed to load session {}: {}", session_id, e);
+        None
+    });
+    let settings = language::SessionSettings::from_metadata(
+        existing_session.as_ref().and_then(|s| s.metadata.as_deref()),
+    );
+    let detected_language = language::detect_language(&payload.message);
+    let response_language = language::resolve_response_language(
+        detected_language.as_deref(),
+        settings.response_language.as_deref(),
+        payload.language.as_deref(),
+    );
+    debug!("Responding in {} (detected {:?})", response_language, detected_language);
+    
+    // Search documents, extracted memories and the session's attachments
+    // concurrently (if available), weighted per source
+    let retrieval_config = match settings.source_weights {
+        Some(ref weights) => state.retrieval_config.with_weights(weights),
+        None => state.retrieval_config.clone(),
+    };
+    let mut retrieved = match state.knowledge_service {
+        Some(ref knowledge_service) => {
+            retrieval::federated_search(
+                knowledge_service.as_ref(),
+                &budget,
+                &payload.message,
+                &retrieval_config,
+                &session_id,
+            )
+            .await
+        }
+        None => retrieval::Retrieved::default(),
+    };
+    if !retrieved.keyword_fallback.is_empty() {
+        info!("Vector search unavailable for {:?}; using keyword matches", retrieved.keyword_fallback);
+    }
+    let mut search_results = std::mem::take(&mut retrieved.matches);
+    
+    // Reranking is optional and is dropped when the budget is nearly consumed
+    if search_results.len() > 1 {
+        let query = payload.message.clone();
+        let candidates = search_results.clone();
+        if let Some(reranked) = budget
+            .run_optional("rerank", async move {
+                Ok::<_, std::convert::Infallible>(chat_pipeline::rerank_by_overlap(&query, candidates))
+            })
+            .await
         {
-            if !search_results.is_empty() {
-                context = format!(
-                    "\n\nRelevant information from your memory:\n{}",
-                    search_results
-                        .iter()
-                        .map(|doc| format!("- {}: {}", doc.title, doc.content))
-                        .collect::<Vec<_>>()
-                        .join("\n")
-                );
-                info!("Found {} relevant documents for context", search_results.len());
-            } else {
-                debug!("No relevant documents found for: {}", payload.message);
-            }
-        } else {
-            debug!("Failed to search knowledge base");
+            search_results = reranked;
+        }
+    }
+    
+    // Memories are capped and filtered by the policy, with the session's
+    // overrides, against what the model already sees of the conversation
+    let memory_policy = match settings.memory_policy {
+        Some(ref overrides) => state.memory_policy.with_overrides(overrides),
+        None => state.memory_policy,
+    };
+    let mut visible_turns: Vec<String> = state
+        .ai_service
+        .get_session_history(&session_id)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .rev()
+        .take(ai_service::HISTORY_WINDOW)
+        .map(|message| message.content)
+        .collect();
+    visible_turns.push(payload.message.clone());
+    let (search_results, memory_trace) = memory_policy.select(search_results, &visible_turns, chrono::Utc::now());
+    debug!("Memory selection: {:?}", memory_trace);
+    
+    // Chunks the residency policy keeps from this chat provider are dropped
+    // here and reported in the response's sources
+    let chat_provider = state.ai_service.provider_class();
+    let origins: Vec<(String, usize, SourceKind, TrustLevel, Option<usize>)> = search_results
+        .iter()
+        .map(|doc| (doc.id.clone(), doc.chunk_index, SourceKind::of(doc), doc.trust_level, doc.page))
+        .collect();
+    let (search_results, withheld) = state.residency.filter_context(search_results, chat_provider);
+    let restricted_by = search_results
+        .iter()
+        .find_map(|doc| state.residency.check(&doc.tags, ProviderClass::Cloud))
+        .map(|restriction| restriction.tag);
+    let mut sources: Vec<ChatSource> = search_results
+        .iter()
+        .map(|doc| ChatSource {
+            document_id: doc.id.clone(),
+            title: doc.title.clone(),
+            chunk_index: doc.chunk_index,
+            origin: SourceKind::of(doc),
+            trust_level: doc.trust_level,
+            withheld: None,
+            partial: retrieved.is_partial(doc),
+            page: doc.page,
+        })
+        .collect();
+    sources.extend(withheld.into_iter().map(|w| {
+        let (origin, trust_level, page) = origins
+            .iter()
+            .find(|(id, chunk_index, _, _, _)| *id == w.document_id && *chunk_index == w.chunk_index)
+            .map(|(_, _, origin, trust_level, page)| (*origin, *trust_level, *page))
+            .unwrap_or((SourceKind::Document, TrustLevel::default(), None));
+        ChatSource {
+            origin,
+            trust_level,
+            page,
+            document_id: w.document_id,
+            title: w.title,
+            chunk_index: w.chunk_index,
+            withheld: Some(format!("withheld from the {} chat provider: {}", chat_provider, w.reason)),
+            partial: retrieved.keyword_fallback.contains(&origin),
         }
+    }));
+    let dominant_trust = trust::dominant_trust(&search_results);
+    
+    let mut context = String::new();
+    if !search_results.is_empty() {
+        // Saved notes on the retrieved documents are shown beside their chunks
+        let mut document_ids: Vec<String> = search_results.iter().map(|doc| doc.id.clone()).collect();
+        document_ids.sort();
+        document_ids.dedup();
+        let annotations = state.annotation_store.list_for_documents(&document_ids).await.unwrap_or_else(|e| {
+            warn!("Failed to load annotations for context: {}", e);
+            Vec::new()
+        });
+        context = format!(
+            "\n\nRelevant information from your memory, each source marked with its trust level:\n{}",
+            knowledge_annotations::assemble_context(&search_results, &annotations)
+        );
+        if let Some(instruction) = trust::hedging_instruction(dominant_trust) {
+            context.push_str("\n\n");
+            context.push_str(instruction);
+        }
+        info!("Found {} relevant documents for context", search_results.len());
+    } else {
+        debug!("No relevant documents found for: {}", payload.message);
+    }
+    
+    // "What do you know about me?" is answered from the stored data itself
+    if profile_summary::is_profile_question(&payload.message) {
+        let summary = build_profile_summary(state, &settings, groups, &response_language, false).await;
+        context.push_str(&profile_summary::grounding_context(&summary));
+    }
+    
+    // "Summarize the lease agreement" is answered from the whole document,
+    // not just the retrieved chunks; the cited sections become the sources
+    let document_summary = match summarization::summary_request(&payload.message) {
+        Some((query, options)) => summarize_by_title(state, &query, &options).await,
+        None => None,
+    };
+    if let Some((document, summary)) = &document_summary {
+        sources = summary
+            .citations
+            .iter()
+            .map(|citation| ChatSource {
+                document_id: summary.document_id.clone(),
+                title: summary.title.clone(),
+                chunk_index: citation.chunk_index,
+                origin: SourceKind::Document,
+                trust_level: document.trust_level,
+                withheld: None,
+                partial: false,
+                page: None,
+            })
+            .collect();
     }
     
     // Combine user message with context
@@ -248,21 +889,74 @@ async fn chat_handler(
     
     debug!("Enhanced message with context: {}", enhanced_message);
     
+    // Guardrails first, then the base prompt and the session's persona and
+    // instructions
+    let system_prompt = guardrails::assemble_system_prompt(
+        &state.guardrails.current(),
+        groups,
+        &state.ai_service.get_system_prompt(),
+        &PromptCustomization {
+            persona: settings.persona.as_deref(),
+            instructions: settings.instructions.as_deref(),
+            response_language: &response_language,
+        },
+    );
+    
     // Process message with AI service
-    let response = match state.ai_service.process_message(&enhanced_message, &session_id).await {
-        Ok(resp) => resp,
-        Err(e) => {
-            error!("Error processing message: {}", e);
-            format!("I apologize, but I encountered an error processing your message. Please try again.")
-        }
+    let reply = match &document_summary {
+        Some((_, summary)) => ai_service::ChatReply {
+            text: summary.summary.clone(),
+            model: state.ai_service.model().to_string(),
+            prompt_tokens: u32::try_from(summary.prompt_tokens).ok(),
+            completion_tokens: u32::try_from(summary.completion_tokens).ok(),
+            fallback: false,
+        },
+        None => match budget
+            .run_required("llm", state.ai_service.process_message_with_prompt(&enhanced_message, &session_id, &system_prompt.text))
+            .await
+        {
+            Ok(reply) => {
+                state.token_usage.record("chat", reply.prompt_tokens, reply.completion_tokens);
+                reply
+            }
+            Err(e) => {
+                error!("Error processing message: {}", e);
+                ai_service::ChatReply {
+                    text: format!("I apologize, but I encountered an error processing your message. Please try again."),
+                    model: state.ai_service.model().to_string(),
+                    prompt_tokens: None,
+                    completion_tokens: None,
+                    fallback: true,
+                }
+            }
+        },
     };
+    let response = reply.text.clone();
+    
+    // Which guardrail versions the reply was generated under
+    info!(
+        target: "llm_trace",
+        session_id = %session_id,
+        model = %reply.model,
+        fallback = reply.fallback,
+        guardrails = %serde_json::to_string(&system_prompt.guardrails).unwrap_or_default(),
+        "LLM call"
+    );
+    
+    let timings = budget.timings();
+    let keyword_only = search_results.iter().any(|doc| retrieved.is_partial(doc));
+    let stats = ai_service::MessageStats::for_reply(&reply, timings.total_ms, search_results.len(), keyword_only);
+    let pipeline_mode = stats.pipeline_mode.clone().unwrap_or_default();
     
     // Save to database for persistence
+    // Keep the original creation time and the session's settings
     if let Ok(_) = state.conversation_store.save_session(&ai_service::SessionRecord {
         id: session_id.clone(),
-        created_at: chrono::Utc::now(),
+        created_at: existing_session.as_ref().map(|s| s.created_at).unwrap_or_else(chrono::Utc::now),
         updated_at: chrono::Utc::now(),
-        metadata: None,
+        metadata: existing_session.as_ref().and_then(|s| s.metadata.clone()),
+        parent_session_id: existing_session.as_ref().and_then(|s| s.parent_session_id.clone()),
+        forked_from_message_id: existing_session.and_then(|s| s.forked_from_message_id),
     }).await {
         // Session saved
     }
@@ -273,22 +967,35 @@ async fn chat_handler(
         role: "user".to_string(),
         content: payload.message.clone(),
         created_at: chrono::Utc::now(),
+        language: detected_language,
+        interrupted_at_byte: None,
+        restricted_by: None,
+        stats: ai_service::MessageStats::default(),
     }).await {
         // User message saved
     }
     
+    let message_id = uuid::Uuid::new_v4().to_string();
     if let Ok(_) = state.conversation_store.save_message(&ai_service::MessageRecord {
-        id: uuid::Uuid::new_v4().to_string(),
+        id: message_id.clone(),
         session_id: session_id.clone(),
         role: "assistant".to_string(),
         content: response.clone(),
         created_at: chrono::Utc::now(),
+        language: Some(response_language.clone()),
+        interrupted_at_byte: None,
+        restricted_by: restricted_by.clone(),
+        stats,
     }).await {
         // Assistant response saved
     }
     
-    // Extract and store important information using memory service
-    if let Some(ref memory_service) = state.memory_service {
+    // Extract and store important information using memory service. The
+    // extraction model is a cloud provider, so replies built from restricted
+    // documents are not passed to it
+    if let Some(tag) = &restricted_by {
+        info!("Skipping memory extraction for session {}: reply used documents tagged '{}'", session_id, tag);
+    } else if let Some(ref memory_service) = state.memory_service {
         tokio::spawn({
             let memory_service = Arc::clone(memory_service);
             let session_id = session_id.clone();
@@ -315,12 +1022,181 @@ async fn chat_handler(
         });
     }
     
-    info!("Sending AI response for session: {}", session_id);
+    state.pipeline_metrics.observe(&timings);
+    
+    info!("Sending AI response for session: {} ({}ms of {}ms budget)",
+          session_id, timings.total_ms, timings.budget_ms);
     
-    Json(ChatResponse {
+    ChatResponse {
         response,
+        message_id,
         session_id,
-    })
+        response_language,
+        timings,
+        pipeline_mode,
+        sources,
+        dominant_trust,
+        explain: payload.explain.then_some(RetrievalExplain { memories: memory_trace }),
+    }
+}
+
+// Per-stage chat pipeline latency histograms and token usage per feature
+async fn pipeline_metrics_handler(
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    ok(serde_json::json!({
+        "stages": state.pipeline_metrics.snapshot(),
+        "latency_budget_ms": state.pipeline_config.latency_budget.as_millis() as u64,
+        "token_usage": state.token_usage.snapshot(),
+    }))
+}
+
+// Summarizes or explains a whole stored document. The body is optional:
+// {"length": "brief"|"detailed", "audience": "simple"|"technical", "focus": "..."}
+async fn summarize_document_handler(
+    State(state): State<Arc<AppState>>,
+    Path(document_id): Path<String>,
+    payload: Option<Json<SummaryOptions>>,
+) -> Response {
+    let Some(knowledge_service) = state.knowledge_service.as_ref() else {
+        return fail(StatusCode::SERVICE_UNAVAILABLE, "Knowledge service not available");
+    };
+    let options = payload.map(|Json(options)| options).unwrap_or_default();
+    match summarize_document(&state, knowledge_service, &document_id, &options).await {
+        Ok(summary) => ok(summary),
+        Err((status, message)) => fail(status, message),
+    }
+}
+
+// Shared by the endpoint and the chat intent. Documents the residency policy
+// keeps from the chat provider are refused rather than summarized in part
+async fn summarize_document(
+    state: &AppState,
+    knowledge_service: &KnowledgeService,
+    document_id: &str,
+    options: &SummaryOptions,
+) -> Result<Summary, (StatusCode, String)> {
+    let chunks = knowledge_service.document_chunks(document_id).await.map_err(|e| {
+        error!("Failed to load document {}: {}", document_id, e);
+        (StatusCode::INTERNAL_SERVER_ERROR, "Failed to load document".to_string())
+    })?;
+    if chunks.is_empty() {
+        return Err((StatusCode::NOT_FOUND, "Document not found".to_string()));
+    }
+    let chat_provider = state.ai_service.provider_class();
+    if let Some(restriction) = chunks.iter().find_map(|chunk| state.residency.check(&chunk.tags, chat_provider)) {
+        return Err((
+            StatusCode::FORBIDDEN,
+            format!("Withheld from the {} chat provider: {}", chat_provider, restriction.reason()),
+        ));
+    }
+    state
+        .summarizer
+        .summarize(state.ai_service.as_ref(), &chunks, options)
+        .await
+        .map_err(|e| {
+            error!("Failed to summarize document {}: {}", document_id, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to summarize document".to_string())
+        })
+}
+
+// Fills a JSON schema from the chunks most relevant to the instruction:
+// {"schema": {...}, "instruction": "...", "tags": [...], "limit": 20}.
+// Extractions needing more than one model call are queued and answered with
+// 202 and the URL to poll for the result
+async fn extract_handler(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<ExtractionRequest>,
+) -> Response {
+    let Some(knowledge_service) = state.knowledge_service.as_ref() else {
+        return fail(StatusCode::SERVICE_UNAVAILABLE, "Knowledge service not available");
+    };
+    if request.instruction.trim().is_empty() {
+        return fail(StatusCode::BAD_REQUEST, "An instruction is required");
+    }
+    if let Err(message) = extraction::check_schema(&request.schema) {
+        return fail(StatusCode::BAD_REQUEST, message);
+    }
+
+    let config = state.extractor.config();
+    let limit = request.limit.unwrap_or(config.max_chunks).clamp(1, config.max_chunks);
+    let tags = TagFilter::new(request.tags.clone(), TagMode::Any);
+    let matches = match knowledge_service
+        .search_documents(&request.instruction, limit, config.score_threshold, Some(tags))
+        .await
+    {
+        Ok(matches) => matches,
+        Err(e) => {
+            error!("Extraction search failed: {}", e);
+            return fail(StatusCode::INTERNAL_SERVER_ERROR, "Search failed");
+        }
+    };
+    let (chunks, withheld) = state.residency.filter_context(matches, state.ai_service.provider_class());
+    if chunks.is_empty() {
+        return fail(StatusCode::NOT_FOUND, "No knowledge matches the instruction");
+    }
+
+    if state.extractor.needs_job(&chunks) {
+        let job = state.extractor.enqueue(&request.instruction).await;
+        let (id, status) = (job.id.clone(), job.status.clone());
+        let state = state.clone();
+        tokio::spawn(async move {
+            state.extractor.run_job(&job.id, state.ai_service.as_ref(), &request, &chunks, withheld).await;
+        });
+        return respond(
+            StatusCode::ACCEPTED,
+            ApiResponse::success(serde_json::json!({
+                "id": id,
+                "results_url": format!("/api/v1/knowledge/extract/{}", id),
+                "status": status,
+            })),
+        );
+    }
+
+    match state.extractor.extract(state.ai_service.as_ref(), &request.schema, &request.instruction, &chunks).await {
+        Ok(extraction) => ok(extraction::Extraction { withheld, ..extraction }),
+        Err(e) if e.is::<SchemaMismatch>() => fail(StatusCode::BAD_GATEWAY, e.to_string()),
+        Err(e) => {
+            error!("Extraction failed: {}", e);
+            fail(StatusCode::INTERNAL_SERVER_ERROR, "Extraction failed")
+        }
+    }
+}
+
+async fn extraction_job_handler(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Response {
+    match state.extractor.job(&id).await {
+        Some(job) => ok(job),
+        None => fail(StatusCode::NOT_FOUND, "Extraction not found"),
+    }
+}
+
+// Resolves "summarize <title>" against the stored documents; memories and
+// session attachments are not candidates. None falls back to ordinary chat
+async fn summarize_by_title(
+    state: &AppState,
+    query: &str,
+    options: &SummaryOptions,
+) -> Option<(knowledge_service_simple::Document, Summary)> {
+    let knowledge_service = state.knowledge_service.as_ref()?;
+    let documents: Vec<_> = knowledge_service
+        .list_all_documents()
+        .await
+        .map_err(|e| warn!("Failed to list documents for summary request: {}", e))
+        .ok()?
+        .into_iter()
+        .filter(|doc| SourceKind::of(doc) == SourceKind::Document)
+        .collect();
+    let document = summarization::match_title(query, &documents)?.clone();
+    match summarize_document(state, knowledge_service, &document.id, options).await {
+        Ok(summary) => Some((document, summary)),
+        Err((_, message)) => {
+            warn!("Not summarizing '{}' in chat: {}", document.title, message);
+            None
+        }
+    }
 }
 
 // Get conversation history
@@ -331,7 +1207,7 @@ async fn get_history(
     
     // For now, return empty history
     // In production, you would get session_id from query params or auth token
-    Json(serde_json::json!({
+    ok(serde_json::json!({
         "history": [],
         "message": "History endpoint ready. Pass session_id as query parameter to get specific session history."
     }))
@@ -352,44 +1228,149 @@ async fn get_sessions(
                         "created_at": session.created_at,
                         "updated_at": session.updated_at,
                         "metadata": session.metadata,
+                        // Lets the UI render forks as a tree
+                        "parent_session_id": session.parent_session_id,
+                        "forked_from_message_id": session.forked_from_message_id,
                     })
                 })
                 .collect();
             
-            Json(serde_json::json!({
+            ok(serde_json::json!({
                 "sessions": sessions_with_info,
                 "total": sessions_with_info.len()
             }))
         }
         Err(e) => {
             error!("Failed to get sessions: {}", e);
-            Json(serde_json::json!({
-                "sessions": [],
-                "error": "Failed to retrieve sessions"
-            }))
+            fail(StatusCode::INTERNAL_SERVER_ERROR, "Failed to retrieve sessions")
         }
     }
 }
 
+// What the assistant knows about the user and what shapes its answers. The
+// assembled prompt text is only included for admins
+async fn profile_summary_handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    axum::extract::Query(query): axum::extract::Query<ProfileSummaryQuery>,
+) -> impl IntoResponse {
+    let session = match query.session_id {
+        Some(ref session_id) => match state.conversation_store.get_session(session_id).await {
+            Ok(Some(session)) => Some(session),
+            Ok(None) => return fail(StatusCode::NOT_FOUND, "Session not found"),
+            Err(e) => {
+                error!("Failed to load session {}: {}", session_id, e);
+                return fail(StatusCode::INTERNAL_SERVER_ERROR, "Failed to load session");
+            }
+        },
+        None => None,
+    };
+    let settings = language::SessionSettings::from_metadata(session.as_ref().and_then(|s| s.metadata.as_deref()));
+    let groups = guardrails::groups_from_headers(&headers);
+    let admin = groups.iter().any(|g| g == profile_summary::ADMIN_GROUP);
+    let response_language = settings.response_language.clone().unwrap_or_else(|| language::DEFAULT_LANGUAGE.to_string());
+
+    ok(build_profile_summary(&state, &settings, &groups, &response_language, admin).await)
+}
+
+async fn build_profile_summary(
+    state: &AppState,
+    settings: &language::SessionSettings,
+    groups: &[String],
+    response_language: &str,
+    admin: bool,
+) -> profile_summary::ProfileSummary {
+    let policy = state.guardrails.current();
+    let system_prompt = guardrails::assemble_system_prompt(
+        &policy,
+        groups,
+        &state.ai_service.get_system_prompt(),
+        &PromptCustomization {
+            persona: settings.persona.as_deref(),
+            instructions: settings.instructions.as_deref(),
+            response_language,
+        },
+    );
+    let documents = match state.knowledge_service {
+        Some(ref knowledge_service) => knowledge_service.list_all_documents().await.unwrap_or_else(|e| {
+            warn!("Failed to list documents for the profile summary: {}", e);
+            Vec::new()
+        }),
+        None => Vec::new(),
+    };
+    let crawls = state.crawl_manager.list().await;
+
+    profile_summary::assemble(profile_summary::ProfileInputs {
+        documents: &documents,
+        // Locked settings are not part of the prompt, so they are not reported
+        persona: settings.persona.as_deref().filter(|_| !policy.denies("persona")),
+        instructions: settings.instructions.as_deref().filter(|_| !policy.denies("instructions")),
+        system_prompt_name: state.ai_service.system_prompt_name(),
+        system_prompt: &system_prompt,
+        admin,
+        crawls: &crawls,
+        notifications: profile_summary::NotificationSettings::from_env(),
+        models: profile_summary::ModelSettings {
+            chat_model: state.ai_service.model().to_string(),
+            chat_provider: state.ai_service.provider_class(),
+            embedding_model: state
+                .knowledge_service
+                .as_ref()
+                .map(|_| knowledge_service_simple::EMBEDDING_MODEL.to_string()),
+            local_embedding_model: state
+                .knowledge_service
+                .as_ref()
+                .and_then(|k| k.local_embedding_model().map(str::to_string)),
+        },
+        residency: &state.residency,
+    })
+}
+
 // Get messages for a specific session
 async fn get_session_messages(
     State(state): State<Arc<AppState>>,
     axum::extract::Path(session_id): axum::extract::Path<String>,
+    axum::extract::Query(query): axum::extract::Query<SessionMessagesQuery>,
 ) -> impl IntoResponse {
+    let include_stats = query
+        .include
+        .as_deref()
+        .map_or(false, |include| include.split(',').any(|part| part.trim() == "stats"));
+    
     match state.conversation_store.get_session_messages(&session_id).await {
         Ok(messages) => {
             let messages_formatted: Vec<serde_json::Value> = messages
                 .into_iter()
                 .map(|msg| {
-                    serde_json::json!({
+                    let mut formatted = serde_json::json!({
                         "id": msg.id,
                         "role": msg.role,
                         "content": msg.content,
                         "created_at": msg.created_at,
-                    })
+                        "language": msg.language,
+                        "interrupted": msg.interrupted_at_byte.is_some(),
+                        "interrupted_at_byte": msg.interrupted_at_byte,
+                    });
+                    if include_stats {
+                        formatted["stats"] = serde_json::json!(msg.stats);
+                    }
+                    formatted
                 })
                 .collect();
             
+            // Totals are aggregated in SQL rather than from the rows above
+            let session_stats = if include_stats {
+                match state.conversation_store.get_session_stats(&session_id).await {
+                    Ok(stats) => Some(stats),
+                    Err(e) => {
+                        warn!("Failed to aggregate stats for session {}: {}", session_id, e);
+                        None
+                    }
+                }
+            } else {
+                None
+            };
+            
             // Get summary if memory service is available
             let summary = if let Some(ref memory_service) = state.memory_service {
                 let msg_pairs: Vec<(String, String)> = messages_formatted
@@ -410,33 +1391,181 @@ async fn get_session_messages(
                 None
             };
             
-            Json(serde_json::json!({
+            let mut body = serde_json::json!({
                 "session_id": session_id,
                 "messages": messages_formatted,
                 "summary": summary,
                 "total": messages_formatted.len()
-            }))
+            });
+            if include_stats {
+                body["stats"] = serde_json::json!(session_stats);
+            }
+            ok(body)
         }
         Err(e) => {
             error!("Failed to get messages for session {}: {}", session_id, e);
-            Json(serde_json::json!({
-                "session_id": session_id,
-                "messages": [],
-                "error": "Failed to retrieve messages"
-            }))
+            fail(StatusCode::INTERNAL_SERVER_ERROR, "Failed to retrieve messages")
+        }
+    }
+}
+
+async fn get_session_settings(
+    State(state): State<Arc<AppState>>,
+    axum::extract::Path(session_id): axum::extract::Path<String>,
+) -> impl IntoResponse {
+    match state.conversation_store.get_session(&session_id).await {
+        Ok(Some(session)) => ok(language::SessionSettings::from_metadata(session.metadata.as_deref())),
+        Ok(None) => fail(StatusCode::NOT_FOUND, "Session not found"),
+        Err(e) => {
+            error!("Failed to load session {}: {}", session_id, e);
+            fail(StatusCode::INTERNAL_SERVER_ERROR, "Failed to load session")
+        }
+    }
+}
+
+async fn update_session_settings(
+    State(state): State<Arc<AppState>>,
+    axum::extract::Path(session_id): axum::extract::Path<String>,
+    Json(update): Json<SessionSettingsUpdate>,
+) -> impl IntoResponse {
+    let response_language = match update.response_language.as_deref() {
+        None => None,
+        Some(code) => match language::normalize_language(code) {
+            Some(code) => Some(code),
+            None => {
+                return fail(StatusCode::BAD_REQUEST, format!("Unsupported language: {}", code));
+            }
+        },
+    };
+    if let Some(Err(e)) = update.source_weights.as_ref().map(SourceWeights::validate) {
+        return fail(StatusCode::BAD_REQUEST, e);
+    }
+    if let Some(Err(e)) = update.memory_policy.as_ref().map(MemoryPolicyOverride::validate) {
+        return fail(StatusCode::BAD_REQUEST, e);
+    }
+    let policy = state.guardrails.current();
+    if let Err(e) = policy.check_session_update(update.fields_set()) {
+        return fail(StatusCode::UNPROCESSABLE_ENTITY, e);
+    }
+
+    let existing = match state.conversation_store.get_session(&session_id).await {
+        Ok(session) => session,
+        Err(e) => {
+            error!("Failed to load session {}: {}", session_id, e);
+            return fail(StatusCode::INTERNAL_SERVER_ERROR, "Failed to load session");
         }
+    };
+
+    let mut settings = language::SessionSettings::from_metadata(existing.as_ref().and_then(|s| s.metadata.as_deref()));
+    // Locked settings keep their stored value; omitting one is not a way to
+    // clear it
+    if !policy.denies("response_language") {
+        settings.response_language = response_language;
+    }
+    if !policy.denies("source_weights") {
+        settings.source_weights = update.source_weights;
+    }
+    if !policy.denies("persona") {
+        settings.persona = update.persona;
+    }
+    if !policy.denies("instructions") {
+        settings.instructions = update.instructions;
+    }
+    if !policy.denies("memory_policy") {
+        settings.memory_policy = update.memory_policy;
+    }
+
+    let now = chrono::Utc::now();
+    let record = ai_service::SessionRecord {
+        id: session_id.clone(),
+        created_at: existing.as_ref().map(|s| s.created_at).unwrap_or(now),
+        updated_at: now,
+        metadata: Some(settings.to_metadata()),
+        parent_session_id: existing.as_ref().and_then(|s| s.parent_session_id.clone()),
+        forked_from_message_id: existing.and_then(|s| s.forked_from_message_id),
+    };
+    if let Err(e) = state.conversation_store.save_session(&record).await {
+        error!("Failed to save settings for session {}: {}", session_id, e);
+        return fail(StatusCode::INTERNAL_SERVER_ERROR, "Failed to save session settings");
     }
+
+    ok(settings)
+}
+
+// Start a new session from a past message of an existing one. The parent is
+// left as it is and keeps its derived memories
+async fn fork_session(
+    State(state): State<Arc<AppState>>,
+    axum::extract::Path(session_id): axum::extract::Path<String>,
+    Json(request): Json<ForkSessionRequest>,
+) -> impl IntoResponse {
+    let parent = match state.conversation_store.get_session(&session_id).await {
+        Ok(Some(session)) => session,
+        Ok(None) => return fail(StatusCode::NOT_FOUND, "Session not found"),
+        Err(e) => {
+            error!("Failed to load session {}: {}", session_id, e);
+            return fail(StatusCode::INTERNAL_SERVER_ERROR, "Failed to load session");
+        }
+    };
+
+    let fork = match state.conversation_store.fork_session(&parent, &request.from_message_id).await {
+        Ok(Some(fork)) => fork,
+        Ok(None) => {
+            return fail(StatusCode::NOT_FOUND, format!("Message {} not found in session", request.from_message_id));
+        }
+        Err(e) => {
+            error!("Failed to fork session {}: {}", session_id, e);
+            return fail(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fork session");
+        }
+    };
+    let fork_id = fork.session.id.clone();
+
+    // Attachments uploaded into the parent up to the fork point stay
+    // searchable in the fork
+    let mut attachments_shared = 0;
+    if let Some(ref knowledge_service) = state.knowledge_service {
+        match knowledge_service.share_attachments(&session_id, &fork_id, fork.forked_at).await {
+            Ok(count) => attachments_shared = count,
+            Err(e) => warn!("Failed to share attachments of session {} with fork {}: {}", session_id, fork_id, e),
+        }
+    }
+
+    state
+        .ai_service
+        .seed_session(
+            &fork_id,
+            fork.messages
+                .iter()
+                .map(|m| ai_service::ChatMessage {
+                    role: m.role.clone(),
+                    content: m.content.clone(),
+                    timestamp: m.created_at,
+                })
+                .collect(),
+        )
+        .await;
+
+    info!("Forked session {} at message {} into {}", session_id, request.from_message_id, fork_id);
+    created(serde_json::json!({
+        "session_id": fork_id,
+        "parent_session_id": session_id,
+        "forked_from_message_id": request.from_message_id,
+        "messages_copied": fork.messages.len(),
+        "attachments_shared": attachments_shared,
+    }))
 }
 
 // WebSocket handler
 async fn websocket_handler(
     ws: WebSocketUpgrade,
     State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
 ) -> Response {
-    ws.on_upgrade(move |socket| handle_socket(socket, state))
+    let groups = guardrails::groups_from_headers(&headers);
+    ws.on_upgrade(move |socket| handle_socket(socket, state, groups))
 }
 
-async fn handle_socket(mut socket: axum::extract::ws::WebSocket, state: Arc<AppState>) {
+async fn handle_socket(mut socket: axum::extract::ws::WebSocket, state: Arc<AppState>, groups: Vec<String>) {
     info!("New WebSocket connection established");
     
     // Send a welcome message
@@ -452,36 +1581,482 @@ async fn handle_socket(mut socket: axum::extract::ws::WebSocket, state: Arc<AppS
         return;
     }
     
-    // Handle incoming messages
-    while let Some(msg) = socket.recv().await {
-        match msg {
-            Ok(axum::extract::ws::Message::Text(text)) => {
-                debug!("Received WebSocket message: {}", text);
+    // Upload progress is pushed to every connected client
+    let mut upload_events = state.upload_manager.tracker().subscribe();
+    // So are changes found when a connector re-ingests a document
+    let mut document_events = state.revision_store.subscribe();
+    // And transcription progress, including jobs finished by a provider callback
+    let mut transcription_events = state.transcription_manager.queue().subscribe();
+    // Spoken replies on this connection; binary frames from the client are
+    // microphone audio, binary frames to it are reply audio
+    let mut voice = VoiceSession::new();
+    
+    loop {
+        tokio::select! {
+            event = upload_events.recv() => {
+                let status = match event {
+                    Ok(status) => status,
+                    Err(tokio::sync::broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!("WebSocket client missed {} upload events", skipped);
+                        continue;
+                    }
+                    Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+                };
                 
-                // Echo the message back for now
-                let response = serde_json::json!({
-                    "type": "response",
-                    "message": format!("Echo: {}", text)
+                let update = serde_json::json!({
+                    "type": "upload_progress",
+                    "upload": status,
                 });
                 
                 if let Err(e) = socket.send(axum::extract::ws::Message::Text(
-                    response.to_string()
+                    update.to_string()
                 )).await {
-                    error!("Failed to send response: {}", e);
+                    error!("Failed to send upload progress: {}", e);
                     break;
                 }
             }
-            Ok(axum::extract::ws::Message::Close(_)) => {
-                info!("WebSocket connection closed by client");
-                break;
+            event = document_events.recv() => {
+                let updated = match event {
+                    Ok(updated) => updated,
+                    Err(tokio::sync::broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!("WebSocket client missed {} document events", skipped);
+                        continue;
+                    }
+                    Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+                };
+                
+                let update = serde_json::json!({
+                    "type": "document.updated",
+                    "document": updated,
+                });
+                
+                if let Err(e) = socket.send(axum::extract::ws::Message::Text(
+                    update.to_string()
+                )).await {
+                    error!("Failed to send document update: {}", e);
+                    break;
+                }
             }
-            Err(e) => {
-                error!("WebSocket error: {}", e);
-                break;
+            event = transcription_events.recv() => {
+                let job = match event {
+                    Ok(job) => job,
+                    Err(tokio::sync::broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!("WebSocket client missed {} transcription events", skipped);
+                        continue;
+                    }
+                    Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+                };
+                
+                let update = serde_json::json!({
+                    "type": "transcription_progress",
+                    "transcription": {
+                        "id": job.id,
+                        "status": job.status,
+                        "segments_done": job.segments.len(),
+                        "segments_total": job.segments_total,
+                        "transcript": job.transcript,
+                        "document_id": job.document_id,
+                    },
+                });
+                
+                if let Err(e) = socket.send(axum::extract::ws::Message::Text(
+                    update.to_string()
+                )).await {
+                    error!("Failed to send transcription progress: {}", e);
+                    break;
+                }
+            }
+            event = voice.next_frame() => {
+                let message = match event {
+                    PlaybackEvent::Frame(frame) => axum::extract::ws::Message::Binary(frame),
+                    PlaybackEvent::Finished(summary) => axum::extract::ws::Message::Text(
+                        playback_event_json(&state, summary).await.to_string()
+                    ),
+                };
+                
+                if let Err(e) = socket.send(message).await {
+                    error!("Failed to send speech audio: {}", e);
+                    break;
+                }
+            }
+            msg = socket.recv() => {
+                let Some(msg) = msg else { break };
+                match msg {
+                    Ok(axum::extract::ws::Message::Text(text)) => {
+                        debug!("Received WebSocket message: {}", text);
+                        
+                        let responses = if let Ok(command) = serde_json::from_str::<VoiceCommand>(&text) {
+                            handle_voice_command(&state, &mut voice, command).await
+                        } else if let Ok(ClientMessage::Chat(request)) = serde_json::from_str::<ClientMessage>(&text) {
+                            let reply = run_chat(&state, request, &groups).await;
+                            let mut response = serde_json::json!(reply);
+                            response["type"] = serde_json::json!("chat_response");
+                            response["timings"] = serde_json::json!(reply.timings);
+                            response["pipeline_mode"] = serde_json::json!(reply.pipeline_mode);
+                            vec![response]
+                        } else {
+                            // Echo anything else back for now
+                            vec![serde_json::json!({
+                                "type": "response",
+                                "message": format!("Echo: {}", text)
+                            })]
+                        };
+                        
+                        let mut failed = false;
+                        for response in responses {
+                            if let Err(e) = socket.send(axum::extract::ws::Message::Text(
+                                response.to_string()
+                            )).await {
+                                error!("Failed to send response: {}", e);
+                                failed = true;
+                                break;
+                            }
+                        }
+                        if failed {
+                            break;
+                        }
+                    }
+                    Ok(axum::extract::ws::Message::Binary(audio)) => {
+                        if let Some(summary) = voice.observe_microphone(&audio) {
+                            let event = playback_event_json(&state, summary).await;
+                            if let Err(e) = socket.send(axum::extract::ws::Message::Text(
+                                event.to_string()
+                            )).await {
+                                error!("Failed to send barge-in event: {}", e);
+                                break;
+                            }
+                        }
+                    }
+                    Ok(axum::extract::ws::Message::Close(_)) => {
+                        info!("WebSocket connection closed by client");
+                        break;
+                    }
+                    Err(e) => {
+                        error!("WebSocket error: {}", e);
+                        break;
+                    }
+                    _ => {}
+                }
             }
-            _ => {}
         }
     }
     
+    // Reply audio cut off by the client going away still counts as interrupted
+    if let Some(summary) = voice.cancel(InterruptReason::ClientCancel) {
+        playback_event_json(&state, summary).await;
+    }
+    
     info!("WebSocket connection closed");
-}
\ No newline at end of file
+}
+
+async fn handle_voice_command(
+    state: &Arc<AppState>,
+    voice: &mut VoiceSession,
+    command: VoiceCommand,
+) -> Vec<serde_json::Value> {
+    let mut events = Vec::new();
+    match command {
+        VoiceCommand::TtsStart { text, language, message_id } => {
+            let Some(voice_service) = state.voice_service.as_ref() else {
+                events.push(serde_json::json!({
+                    "type": "error",
+                    "message": "Voice service is not available",
+                }));
+                return events;
+            };
+            let language = language
+                .as_deref()
+                .and_then(language::normalize_language)
+                .unwrap_or_else(|| language::DEFAULT_LANGUAGE.to_string());
+            
+            // Replies built from restricted documents stay off cloud TTS
+            if let Some(message_id) = &message_id {
+                let restricted_by = state.conversation_store.message_restriction(message_id).await.unwrap_or_else(|e| {
+                    error!("Failed to check residency of message {}: {}", message_id, e);
+                    None
+                });
+                if let Some(restriction) = restricted_by
+                    .and_then(|tag| state.residency.check(&[tag], voice_service.provider_class()))
+                {
+                    warn!("Not speaking message {}: {}", message_id, restriction.reason());
+                    events.push(serde_json::json!({
+                        "type": "error",
+                        "message": format!(
+                            "This reply used documents {}; no local text-to-speech provider is configured",
+                            restriction.reason()
+                        ),
+                        "message_id": message_id,
+                        "withheld": restriction,
+                    }));
+                    return events;
+                }
+            }
+            
+            match voice_service.stream_speech(&text, &language).await {
+                Ok(stream) => {
+                    let (playback_id, previous) = voice.start(stream, message_id);
+                    if let Some(summary) = previous {
+                        events.push(playback_event_json(state, summary).await);
+                    }
+                    events.push(serde_json::json!({
+                        "type": "tts_started",
+                        "playback_id": playback_id,
+                        "content_type": "audio/mpeg",
+                    }));
+                }
+                Err(e) => {
+                    error!("Streaming TTS failed: {}", e);
+                    events.push(serde_json::json!({
+                        "type": "error",
+                        "message": "TTS synthesis failed",
+                    }));
+                }
+            }
+        }
+        VoiceCommand::TtsCancel => {
+            if let Some(summary) = voice.cancel(InterruptReason::ClientCancel) {
+                events.push(playback_event_json(state, summary).await);
+            }
+        }
+    }
+    events
+}
+
+// Event telling the client how playback ended; interruptions are recorded on
+// the assistant message so the history shows the reply was cut off
+async fn playback_event_json(
+    state: &Arc<AppState>,
+    summary: PlaybackSummary,
+) -> serde_json::Value {
+    if let (PlaybackOutcome::Interrupted { byte_offset, .. }, Some(message_id)) =
+        (&summary.outcome, &summary.message_id)
+    {
+        match state.conversation_store.mark_interrupted(message_id, *byte_offset).await {
+            Ok(true) => {}
+            Ok(false) => warn!("Interrupted playback refers to unknown message {}", message_id),
+            Err(e) => error!("Failed to record interrupted reply {}: {}", message_id, e),
+        }
+    }
+    
+    let event_type = match &summary.outcome {
+        PlaybackOutcome::Completed { .. } => "tts_finished",
+        PlaybackOutcome::Interrupted { reason: InterruptReason::BargeIn, .. } => "barge_in",
+        PlaybackOutcome::Interrupted { .. } => "tts_interrupted",
+        PlaybackOutcome::Failed { .. } => "tts_failed",
+    };
+    serde_json::json!({
+        "type": event_type,
+        "playback_id": summary.playback_id,
+        "message_id": summary.message_id,
+        "outcome": summary.outcome,
+    })
+}
+// `cargo test --features ephemeral-e2e`: the whole stack over HTTP, with the
+// in-memory stores and the scripted providers of the ephemeral profile
+#[cfg(all(test, feature = "ephemeral-e2e"))]
+mod ephemeral_e2e {
+    use super::*;
+    use std::time::Duration;
+
+    const BOUNDARY: &str = "rusty-ai-e2e-boundary";
+    const POLL_ATTEMPTS: usize = 100;
+    const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+    async fn serve(state: Arc<AppState>) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, build_router(state)).await.unwrap() });
+        format!("http://{}", addr)
+    }
+
+    // (name, file name, value) per form field
+    fn multipart_body(fields: &[(&str, Option<&str>, &str)]) -> String {
+        let mut body = String::new();
+        for (name, filename, value) in fields {
+            body.push_str(&format!("--{}\r\nContent-Disposition: form-data; name=\"{}\"", BOUNDARY, name));
+            if let Some(filename) = filename {
+                body.push_str(&format!("; filename=\"{}\"\r\nContent-Type: text/plain", filename));
+            }
+            body.push_str(&format!("\r\n\r\n{}\r\n", value));
+        }
+        body.push_str(&format!("--{}--\r\n", BOUNDARY));
+        body
+    }
+
+    async fn get_json(client: &reqwest::Client, url: &str) -> serde_json::Value {
+        client.get(url).send().await.unwrap().json().await.unwrap()
+    }
+
+    async fn chat(client: &reqwest::Client, base: &str, message: &str, session_id: &str) -> serde_json::Value {
+        client
+            .post(format!("{}/api/v1/conversation/send", base))
+            .json(&serde_json::json!({"message": message, "session_id": session_id}))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_chat_with_rag_cites_uploads_and_remembers_extracted_facts() {
+        let fixtures = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/ephemeral");
+        let stack = EphemeralStack::start(&fixtures).await.unwrap();
+        let state = build_state(&[], Some(&stack)).await.unwrap();
+        assert!(state.knowledge_service.is_some());
+        assert!(state.memory_service.is_some());
+        let conversation_store = state.conversation_store.clone();
+        let base = serve(state).await;
+        let client = reqwest::Client::new();
+
+        // Upload a document and wait for the background indexing
+        let body = multipart_body(&[
+            ("title", None, "Office handbook"),
+            (
+                "file",
+                Some("handbook.txt"),
+                "Visitors sign in at the front desk. The office wifi password is correct-horse-battery. Lights go off at 8pm.",
+            ),
+        ]);
+        let upload: serde_json::Value = client
+            .post(format!("{}/api/v1/knowledge/upload", base))
+            .header("content-type", format!("multipart/form-data; boundary={}", BOUNDARY))
+            .body(body)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        let status_url = format!("{}{}", base, upload["status_url"].as_str().unwrap());
+
+        let mut document_id = None;
+        for _ in 0..POLL_ATTEMPTS {
+            let status = get_json(&client, &status_url).await;
+            assert_ne!(status["status"]["stage"], "failed", "{}", status);
+            if let Some(id) = status["status"]["document_id"].as_str() {
+                document_id = Some(id.to_string());
+                break;
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+        let document_id = document_id.expect("upload was not indexed in time");
+
+        // The answer comes from the uploaded document, which is cited
+        let reply = chat(&client, &base, "My name is Ada. What is the office wifi password?", "session-1").await;
+        assert_eq!(reply["meta"]["pipeline_mode"], "rag", "{}", reply);
+        let reply = &reply["data"];
+        assert!(reply["response"].as_str().unwrap().contains("correct-horse-battery"), "{}", reply);
+        let sources = reply["sources"].as_array().unwrap();
+        assert!(
+            sources.iter().any(|s| s["document_id"] == document_id.as_str()
+                && s["origin"] == "document"
+                && s["withheld"].is_null()),
+            "{}",
+            reply
+        );
+        assert_eq!(conversation_store.get_session_messages("session-1").await.unwrap().len(), 2);
+
+        // The user's name is extracted into memory in the background
+        let documents_url = format!("{}/api/v1/knowledge/documents", base);
+        let mut remembered = false;
+        for _ in 0..POLL_ATTEMPTS {
+            let documents = get_json(&client, &documents_url).await;
+            remembered = documents["documents"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .any(|d| d["title"] == "[personal] User's Name" && d["tags"].as_array().unwrap().contains(&"extracted".into()));
+            if remembered {
+                break;
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+        assert!(remembered, "the user's name was not extracted into memory");
+
+        // A new session answers from that memory
+        let reply = chat(&client, &base, "What is my name?", "session-2").await["data"].clone();
+        assert_eq!(reply["response"], "Your name is Ada.", "{}", reply);
+        assert!(reply["sources"].as_array().unwrap().iter().any(|s| s["origin"] == "memory"), "{}", reply);
+
+        let data_dir = stack.data_dir().to_path_buf();
+        drop(stack);
+        assert!(!data_dir.exists());
+    }
+
+    // Knowledge that kept failing to start is left in safe mode; the server
+    // still comes up and chat answers without retrieval
+    #[tokio::test]
+    async fn test_chat_answers_while_knowledge_is_down() {
+        let fixtures = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/ephemeral");
+        let stack = EphemeralStack::start(&fixtures).await.unwrap();
+        std::fs::write(
+            stack.data_dir().join("startup_state.json"),
+            serde_json::json!({ "consecutive_failures": { "knowledge": 3 } }).to_string(),
+        )
+        .unwrap();
+        let state = build_state(&[], Some(&stack)).await.unwrap();
+        assert!(state.knowledge_service.is_none());
+        let base = serve(state).await;
+        let client = reqwest::Client::new();
+
+        let health = get_json(&client, &format!("{}/health", base)).await;
+        assert_eq!(health["data"]["status"], "degraded", "{}", health);
+        assert_eq!(health["data"]["disabled_components"], serde_json::json!(["knowledge", "memory"]), "{}", health);
+
+        let reply = chat(&client, &base, "Hello there", "session-1").await;
+        assert!(reply["success"].as_bool().unwrap(), "{}", reply);
+        assert!(!reply["data"]["response"].as_str().unwrap().is_empty(), "{}", reply);
+        assert_eq!(reply["meta"]["pipeline_mode"], "direct", "{}", reply);
+    }
+
+    // Contract test: the handlers of this server answer with the envelope
+    // shared with the multi-user API, successes and errors alike
+    #[tokio::test]
+    async fn test_endpoints_answer_with_the_envelope() {
+        const ENVELOPE_FIELDS: &[&str] = &[
+            "success", "data", "error", "timestamp", "request_id", "pagination", "error_code", "fields", "meta",
+        ];
+        let fixtures = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/ephemeral");
+        let stack = EphemeralStack::start(&fixtures).await.unwrap();
+        let base = serve(build_state(&[], Some(&stack)).await.unwrap()).await;
+        let client = reqwest::Client::new();
+        chat(&client, &base, "Hello there", "session-1").await;
+
+        let paths = [
+            "/health",
+            "/health/ready",
+            "/health/live",
+            "/metrics/pipeline",
+            "/api/v1/admin/components",
+            "/api/v1/admin/storage/slow-queries",
+            "/api/v1/conversation/history",
+            "/api/v1/conversation/sessions",
+            "/api/v1/conversation/session/session-1",
+            "/api/v1/conversation/session/session-1?include=stats",
+            "/api/v1/conversation/session/session-1/settings",
+            "/api/v1/conversation/session/missing/settings",
+            "/api/v1/me/profile-summary",
+            "/api/v1/me/profile-summary?session_id=missing",
+            "/api/v1/knowledge/documents/missing/revisions",
+        ];
+        for path in paths {
+            let response = client.get(format!("{}{}", base, path)).send().await.unwrap();
+            let status = response.status();
+            let request_id = response.headers()[envelope::REQUEST_ID_HEADER].to_str().unwrap().to_string();
+            let raw: serde_json::Value = response.json().await.unwrap();
+            let extra: Vec<&String> = raw.as_object().unwrap().keys().filter(|k| !ENVELOPE_FIELDS.contains(&k.as_str())).collect();
+            assert!(extra.is_empty(), "{} has fields outside the envelope: {:?}", path, extra);
+
+            let body: ApiResponse<serde_json::Value> = serde_json::from_value(raw.clone()).unwrap();
+            assert_eq!(body.success, status.is_success(), "{}: {}", path, raw);
+            assert_eq!(body.request_id, Some(request_id), "{}", path);
+            assert_eq!(body.error_code.is_some(), !status.is_success(), "{}: {}", path, raw);
+        }
+
+        let reply = chat(&client, &base, "Hello again", "session-1").await;
+        assert!(reply["data"]["response"].is_string(), "{}", reply);
+        assert!(reply["meta"]["timings"]["total_ms"].is_number(), "{}", reply);
+    }
+}
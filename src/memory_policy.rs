@@ -0,0 +1,370 @@
+// Which extracted memories may enter a chat prompt. Retrieval finds memory
+// candidates like any other source; the policy then admits at most
+// `max_items` of them, each at least `min_similarity` close to the question.
+// Identity facts (the user's name, who they are) skip the similarity floor,
+// ephemeral ones (events, anything tagged temporary) only count while recent,
+// and facts the visible conversation already states are dropped. Rejected
+// memories close to the floor are kept for the explain trace.
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::knowledge_service_simple::DocumentMatch;
+use crate::retrieval::SourceKind;
+
+const IDENTITY_TAGS: [&str; 2] = ["identity", "name"];
+// "events" is the memory service's category tag for dates and experiences
+const EPHEMERAL_TAGS: [&str; 3] = ["events", "ephemeral", "temporary"];
+// Share of a memory's words one visible turn must contain to state it
+const RESTATED_OVERLAP: f32 = 0.8;
+// Words this short carry no fact ("is", "my", "a")
+const MIN_WORD_CHARS: usize = 3;
+// Extracted facts are phrased about "the user"
+const NEUTRAL_WORDS: [&str; 3] = ["user", "the", "and"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MemoryClass {
+    Identity,
+    Ephemeral,
+    Standard,
+}
+
+impl MemoryClass {
+    pub fn of(doc: &DocumentMatch) -> Self {
+        let tagged = |tags: &[&str]| doc.tags.iter().any(|t| tags.contains(&t.to_ascii_lowercase().as_str()));
+        if tagged(&IDENTITY_TAGS) {
+            MemoryClass::Identity
+        } else if tagged(&EPHEMERAL_TAGS) {
+            MemoryClass::Ephemeral
+        } else {
+            MemoryClass::Standard
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MemoryPolicy {
+    // Hard cap on memories per prompt
+    pub max_items: usize,
+    // Minimum score, compared after source and trust weighting
+    pub min_similarity: f32,
+    // Ephemeral memories recorded longer ago than this are left out
+    pub ephemeral_max_age: Duration,
+    // Rejected memories scoring within this of the floor are reported
+    pub near_miss_margin: f32,
+}
+
+impl Default for MemoryPolicy {
+    fn default() -> Self {
+        Self {
+            max_items: 3,
+            min_similarity: 0.3,
+            ephemeral_max_age: Duration::days(7),
+            near_miss_margin: 0.1,
+        }
+    }
+}
+
+impl MemoryPolicy {
+    // MEMORY_MAX_ITEMS, MEMORY_MIN_SIMILARITY, MEMORY_EPHEMERAL_MAX_AGE_HOURS
+    // and MEMORY_NEAR_MISS_MARGIN
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        let var = |name: &str| std::env::var(name).ok();
+        Self {
+            max_items: var("MEMORY_MAX_ITEMS").and_then(|v| v.parse().ok()).unwrap_or(defaults.max_items),
+            min_similarity: var("MEMORY_MIN_SIMILARITY")
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(defaults.min_similarity),
+            ephemeral_max_age: var("MEMORY_EPHEMERAL_MAX_AGE_HOURS")
+                .and_then(|v| v.parse().ok())
+                .map(Duration::hours)
+                .unwrap_or(defaults.ephemeral_max_age),
+            near_miss_margin: var("MEMORY_NEAR_MISS_MARGIN")
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(defaults.near_miss_margin),
+        }
+    }
+
+    // The policy with a session's overrides applied
+    pub fn with_overrides(&self, overrides: &MemoryPolicyOverride) -> Self {
+        Self {
+            max_items: overrides.max_items.unwrap_or(self.max_items),
+            min_similarity: overrides.min_similarity.unwrap_or(self.min_similarity),
+            ephemeral_max_age: overrides
+                .ephemeral_max_age_hours
+                .map(|hours| Duration::hours(hours.into()))
+                .unwrap_or(self.ephemeral_max_age),
+            near_miss_margin: self.near_miss_margin,
+        }
+    }
+
+    // Applies the policy to the memories among `results`; other sources and
+    // the order of what is kept are untouched. `visible_turns` are the
+    // messages the model sees with the prompt, the new one included
+    pub fn select(
+        &self,
+        results: Vec<DocumentMatch>,
+        visible_turns: &[String],
+        now: DateTime<Utc>,
+    ) -> (Vec<DocumentMatch>, MemoryTrace) {
+        let turns: Vec<Vec<String>> = visible_turns.iter().map(|turn| words(turn)).collect();
+        let mut eligible: Vec<(usize, MemoryClass)> = Vec::new();
+        let mut rejected: Vec<(usize, MemoryClass, String)> = Vec::new();
+
+        for (index, doc) in results.iter().enumerate() {
+            if SourceKind::of(doc) != SourceKind::Memory {
+                continue;
+            }
+            let class = MemoryClass::of(doc);
+            let age = recorded_at(&doc.content).map(|at| now - at);
+            let reason = if restated(&doc.content, &turns) {
+                Some("already stated in the conversation".to_string())
+            } else if class == MemoryClass::Ephemeral && age.is_none_or(|age| age > self.ephemeral_max_age) {
+                Some(match age {
+                    Some(age) => format!("ephemeral and recorded {} hours ago", age.num_hours()),
+                    None => "ephemeral without a recorded date".to_string(),
+                })
+            } else if class != MemoryClass::Identity && doc.score < self.min_similarity {
+                Some(format!("score {:.2} below the memory floor of {:.2}", doc.score, self.min_similarity))
+            } else {
+                None
+            };
+            match reason {
+                Some(reason) => rejected.push((index, class, reason)),
+                None => eligible.push((index, class)),
+            }
+        }
+
+        // Identity facts go first, then the closest matches
+        eligible.sort_by(|(a, a_class), (b, b_class)| {
+            (*b_class == MemoryClass::Identity)
+                .cmp(&(*a_class == MemoryClass::Identity))
+                .then(results[*b].score.partial_cmp(&results[*a].score).unwrap_or(std::cmp::Ordering::Equal))
+        });
+        for (index, class) in eligible.split_off(self.max_items.min(eligible.len())) {
+            rejected.push((index, class, format!("over the cap of {} memories", self.max_items)));
+        }
+
+        let decision = |index: usize, class: MemoryClass, reason: Option<String>| MemoryDecision {
+            document_id: results[index].id.clone(),
+            title: results[index].title.clone(),
+            score: results[index].score,
+            class,
+            reason,
+        };
+        let near_miss_floor = self.min_similarity - self.near_miss_margin;
+        rejected.sort_by_key(|(index, _, _)| *index);
+        let trace = MemoryTrace {
+            selected: eligible.iter().map(|&(index, class)| decision(index, class, None)).collect(),
+            rejected: rejected
+                .iter()
+                .filter(|(index, _, _)| results[*index].score >= near_miss_floor)
+                .map(|(index, class, reason)| decision(*index, *class, Some(reason.clone())))
+                .collect(),
+        };
+
+        let dropped: Vec<usize> = rejected.iter().map(|(index, _, _)| *index).collect();
+        let kept = results
+            .into_iter()
+            .enumerate()
+            .filter(|(index, _)| !dropped.contains(index))
+            .map(|(_, doc)| doc)
+            .collect();
+        (kept, trace)
+    }
+}
+
+// Per-session overrides, set next to the session's persona
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MemoryPolicyOverride {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_items: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_similarity: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ephemeral_max_age_hours: Option<u32>,
+}
+
+impl MemoryPolicyOverride {
+    pub fn validate(&self) -> std::result::Result<(), String> {
+        if let Some(min_similarity) = self.min_similarity {
+            if !min_similarity.is_finite() || min_similarity < 0.0 {
+                return Err(format!("Invalid memory min_similarity: {}", min_similarity));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MemoryDecision {
+    pub document_id: String,
+    pub title: String,
+    pub score: f32,
+    pub class: MemoryClass,
+    // Why the memory was left out; absent for selected ones
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct MemoryTrace {
+    pub selected: Vec<MemoryDecision>,
+    // Left out although close to the floor
+    pub rejected: Vec<MemoryDecision>,
+}
+
+// The memory service stores the fact first, then its category, importance,
+// source conversation and "Date: <rfc3339>" lines
+fn fact(content: &str) -> &str {
+    content.split("\n\nCategory:").next().unwrap_or(content)
+}
+
+fn recorded_at(content: &str) -> Option<DateTime<Utc>> {
+    content
+        .lines()
+        .find_map(|line| line.strip_prefix("Date: "))
+        .and_then(|date| DateTime::parse_from_rfc3339(date.trim()).ok())
+        .map(|date| date.with_timezone(&Utc))
+}
+
+// Lowercased content words, with a plural or third-person "s" dropped so
+// "lives" matches "live"
+fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.chars().count() >= MIN_WORD_CHARS)
+        .map(str::to_lowercase)
+        .filter(|word| !NEUTRAL_WORDS.contains(&word.as_str()))
+        .map(|word| match word.strip_suffix('s') {
+            Some(stem) if stem.chars().count() >= MIN_WORD_CHARS => stem.to_string(),
+            _ => word,
+        })
+        .collect()
+}
+
+fn restated(content: &str, turns: &[Vec<String>]) -> bool {
+    let fact_words = words(fact(content));
+    if fact_words.is_empty() {
+        return false;
+    }
+    turns.iter().any(|turn| {
+        let stated = fact_words.iter().filter(|word| turn.contains(word)).count();
+        stated as f32 / fact_words.len() as f32 >= RESTATED_OVERLAP
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::retrieval::MEMORY_TAG;
+    use crate::trust::TrustLevel;
+
+    fn now() -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2024-06-15T12:00:00Z").unwrap().with_timezone(&Utc)
+    }
+
+    fn memory(id: &str, fact: &str, score: f32, tags: &[&str], days_ago: i64) -> DocumentMatch {
+        let mut tags: Vec<String> = tags.iter().map(|t| t.to_string()).collect();
+        tags.push(MEMORY_TAG.to_string());
+        DocumentMatch {
+            id: id.to_string(),
+            title: id.to_string(),
+            content: format!(
+                "{}\n\nCategory: personal\nImportance: high\nExtracted from: s1\nDate: {}",
+                fact,
+                (now() - Duration::days(days_ago)).to_rfc3339()
+            ),
+            score,
+            chunk_index: 0,
+            source: "conversation_s1".to_string(),
+            tags,
+            trust_level: TrustLevel::Personal,
+            note: None,
+            manually_corrected: false,
+            page: None,
+        }
+    }
+
+    fn document(id: &str, score: f32) -> DocumentMatch {
+        DocumentMatch { tags: vec![], ..memory(id, "travel policy", score, &[], 0) }
+    }
+
+    fn ids(results: &[DocumentMatch]) -> Vec<&str> {
+        results.iter().map(|doc| doc.id.as_str()).collect()
+    }
+
+    fn decisions(decisions: &[MemoryDecision]) -> Vec<&str> {
+        decisions.iter().map(|d| d.document_id.as_str()).collect()
+    }
+
+    #[test]
+    fn test_cap_keeps_identity_first_then_best_scores() {
+        let results = vec![
+            document("handbook", 0.9),
+            memory("train", "prefers travelling by train", 0.8, &["preferences"], 30),
+            memory("budget", "travel budget is 2000 euros", 0.7, &[], 30),
+            memory("hotel", "likes small hotels near the station", 0.6, &[], 30),
+            memory("seat", "prefers window seats", 0.5, &[], 30),
+            memory("name", "user's name is Ada", 0.1, &["name", "identity"], 400),
+        ];
+        let policy = MemoryPolicy { max_items: 3, ..Default::default() };
+
+        let (kept, trace) = policy.select(results, &["plan my trip to Lisbon".to_string()], now());
+
+        // The identity fact is admitted despite its score and takes a slot;
+        // documents are not counted against the cap
+        assert_eq!(ids(&kept), vec!["handbook", "train", "budget", "name"]);
+        assert_eq!(decisions(&trace.selected), vec!["name", "train", "budget"]);
+        assert_eq!(decisions(&trace.rejected), vec!["hotel", "seat"]);
+        assert_eq!(trace.rejected[0].reason.as_deref(), Some("over the cap of 3 memories"));
+    }
+
+    #[test]
+    fn test_category_rules_floor_and_recency() {
+        let results = vec![
+            memory("concert", "concert on Friday at the arena", 0.9, &["events"], 2),
+            memory("dentist", "dentist appointment on Monday", 0.9, &["events"], 30),
+            memory("close", "enjoys jazz concerts", 0.25, &[], 30),
+            memory("far", "owns a bicycle", 0.05, &[], 30),
+        ];
+        let policy = MemoryPolicy { max_items: 5, ..Default::default() };
+
+        let (kept, trace) = policy.select(results, &[], now());
+
+        assert_eq!(ids(&kept), vec!["concert"]);
+        // The far match is rejected silently; the near miss is explained
+        assert_eq!(decisions(&trace.rejected), vec!["dentist", "close"]);
+        assert_eq!(trace.rejected[0].class, MemoryClass::Ephemeral);
+        assert_eq!(trace.rejected[0].reason.as_deref(), Some("ephemeral and recorded 720 hours ago"));
+        assert!(trace.rejected[1].reason.as_deref().unwrap().starts_with("score 0.25 below"));
+
+        // A persona that wants a longer memory for events
+        let overrides = MemoryPolicyOverride { ephemeral_max_age_hours: Some(24 * 60), ..Default::default() };
+        let results = vec![memory("dentist", "dentist appointment on Monday", 0.9, &["events"], 30)];
+        let (kept, _) = policy.with_overrides(&overrides).select(results, &[], now());
+        assert_eq!(ids(&kept), vec!["dentist"]);
+    }
+
+    #[test]
+    fn test_facts_stated_in_the_conversation_are_not_repeated() {
+        let results = vec![
+            memory("name", "User's name is Ada", 0.9, &["name", "identity"], 1),
+            memory("city", "User lives in Lisbon", 0.8, &[], 1),
+            memory("diet", "User is vegetarian", 0.7, &[], 1),
+        ];
+        let turns = vec![
+            "Hi, my name is Ada!".to_string(),
+            "Nice to meet you, Ada.".to_string(),
+            "Which restaurants near me are good? I live in Lisbon.".to_string(),
+        ];
+
+        let (kept, trace) = MemoryPolicy::default().select(results, &turns, now());
+
+        assert_eq!(ids(&kept), vec!["diet"]);
+        assert_eq!(decisions(&trace.rejected), vec!["name", "city"]);
+        assert!(trace.rejected.iter().all(|d| d.reason.as_deref() == Some("already stated in the conversation")));
+        assert!(MemoryPolicyOverride { min_similarity: Some(-1.0), ..Default::default() }.validate().is_err());
+    }
+}
@@ -9,7 +9,9 @@ use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use tracing::{debug, info, error};
 
+use crate::http_client::{ClientKind, HttpClientFactory};
 use crate::knowledge_service_simple::KnowledgeService;
+use crate::trust::TrustLevel;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ExtractedInformation {
@@ -62,10 +64,19 @@ impl MemoryService {
             OpenAIConfig::new()
         };
         
-        Ok(Self {
+        Ok(Self::from_config(config, knowledge_service))
+    }
+    
+    pub fn from_config(config: OpenAIConfig, knowledge_service: Arc<KnowledgeService>) -> Self {
+        Self {
             openai_client: Client::with_config(config),
             knowledge_service,
-        })
+        }
+    }
+    
+    pub fn with_http(mut self, http: &HttpClientFactory) -> Result<Self> {
+        self.openai_client = self.openai_client.with_http_client(http.client(ClientKind::OpenAi)?);
+        Ok(self)
     }
     
     /// Extract important information from a conversation
@@ -220,6 +231,7 @@ JSON:"#,
                 content,
                 format!("conversation_{}", information.source_conversation_id),
                 tags,
+                TrustLevel::Personal,
             )
             .await?;
         
@@ -0,0 +1,212 @@
+// Encryption of chunk text kept in the vector store. Memory facts and
+// documents tagged `sensitive` are stored as ciphertext, so reading the
+// Qdrant collections directly no longer reveals them.
+//
+// Each owner's data key is derived from a master key with HKDF, so one
+// leaked data key exposes one owner. The assistant server has a single
+// owner (`LOCAL_OWNER`), but the derivation already takes the owner so a
+// multi-user deployment only has to pass its user id.
+//
+// Tradeoff: the embedding is still computed from the plaintext and stored
+// as is. Vectors are needed in the clear for similarity search, and they
+// leak something about the text (topic, and with effort approximate
+// content), so this protects against casual reads of the store, not
+// against an attacker who can run inversion attacks on the vectors.
+use anyhow::{anyhow, bail, Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::hkdf::{Salt, HKDF_SHA256};
+use ring::rand::{SecureRandom, SystemRandom};
+use std::collections::BTreeMap;
+
+use crate::retrieval::MEMORY_TAG;
+
+// Documents with this tag have their text encrypted, as memory facts always do
+pub const SENSITIVE_TAG: &str = "sensitive";
+// Payload field holding the ciphertext in place of `content`
+pub const ENCRYPTED_CONTENT_FIELD: &str = "content_enc";
+// Payload field naming the master key version, so rotation can find the
+// points still encrypted under an older key
+pub const KEY_VERSION_FIELD: &str = "content_key";
+pub const LOCAL_OWNER: &str = "local";
+
+const MASTER_KEY_LEN: usize = 32;
+const HKDF_SALT: &[u8] = b"rusty-ai payload encryption";
+
+pub fn should_encrypt(tags: &[String]) -> bool {
+    tags.iter().any(|tag| tag == MEMORY_TAG || tag.eq_ignore_ascii_case(SENSITIVE_TAG))
+}
+
+// The master keys by version. New payloads use the current version; the
+// others stay readable until rotation has re-encrypted everything under them.
+pub struct PayloadCipher {
+    current: u32,
+    masters: BTreeMap<u32, [u8; MASTER_KEY_LEN]>,
+    rng: SystemRandom,
+}
+
+impl PayloadCipher {
+    pub fn new(current: u32, masters: impl IntoIterator<Item = (u32, [u8; MASTER_KEY_LEN])>) -> Result<Self> {
+        let masters: BTreeMap<_, _> = masters.into_iter().collect();
+        if !masters.contains_key(&current) {
+            bail!("no master key for current version {}", current);
+        }
+        Ok(Self { current, masters, rng: SystemRandom::new() })
+    }
+
+    // PAYLOAD_ENCRYPTION_KEYS="2:<base64 key>,1:<base64 key>"; the first entry
+    // is the current key, later ones are only used to read older payloads.
+    // Keys are 32 random bytes. Unset leaves payloads in plaintext
+    pub fn from_env() -> Result<Option<Self>> {
+        match std::env::var("PAYLOAD_ENCRYPTION_KEYS") {
+            Ok(spec) if !spec.trim().is_empty() => {
+                Self::parse(&spec).map(Some).map_err(|e| anyhow!("Invalid PAYLOAD_ENCRYPTION_KEYS: {}", e))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    pub fn parse(spec: &str) -> Result<Self> {
+        let mut current = None;
+        let mut masters = Vec::new();
+        for entry in spec.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let (version, key) = entry
+                .split_once(':')
+                .ok_or_else(|| anyhow!("entry is not version:key"))?;
+            let version: u32 = version.trim().parse().context("key version is not a number")?;
+            let key = STANDARD.decode(key.trim()).context("key is not base64")?;
+            let key: [u8; MASTER_KEY_LEN] = key
+                .try_into()
+                .map_err(|_| anyhow!("key {} is not {} bytes", version, MASTER_KEY_LEN))?;
+            current.get_or_insert(version);
+            masters.push((version, key));
+        }
+        let current = current.ok_or_else(|| anyhow!("no keys given"))?;
+        Self::new(current, masters)
+    }
+
+    pub fn current_version(&self) -> u32 {
+        self.current
+    }
+
+    fn data_key(&self, version: u32, owner: &str) -> Result<LessSafeKey> {
+        let master = self
+            .masters
+            .get(&version)
+            .ok_or_else(|| anyhow!("master key version {} is not configured", version))?;
+        let info = [b"owner:".as_slice(), owner.as_bytes()];
+        let okm = Salt::new(HKDF_SHA256, HKDF_SALT)
+            .extract(master)
+            .expand(&info, &AES_256_GCM)
+            .map_err(|_| anyhow!("failed to derive data key"))?;
+        Ok(LessSafeKey::new(UnboundKey::from(okm)))
+    }
+
+    // "k<version>:<nonce>:<ciphertext>", base64 parts. The owner is bound as
+    // associated data, so a payload copied to another owner does not decrypt
+    pub fn encrypt(&self, owner: &str, plaintext: &str) -> Result<String> {
+        let key = self.data_key(self.current, owner)?;
+        let mut nonce = [0u8; NONCE_LEN];
+        self.rng.fill(&mut nonce).map_err(|_| anyhow!("failed to generate nonce"))?;
+
+        let mut data = plaintext.as_bytes().to_vec();
+        key.seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::from(owner.as_bytes()), &mut data)
+            .map_err(|_| anyhow!("failed to encrypt payload"))?;
+        Ok(format!("k{}:{}:{}", self.current, STANDARD.encode(nonce), STANDARD.encode(data)))
+    }
+
+    pub fn decrypt(&self, owner: &str, sealed: &str) -> Result<String> {
+        let version = key_version(sealed).ok_or_else(|| anyhow!("payload is not encrypted content"))?;
+        let mut parts = sealed.splitn(3, ':').skip(1);
+        let (Some(nonce), Some(data)) = (parts.next(), parts.next()) else {
+            bail!("encrypted content is truncated");
+        };
+        let nonce: [u8; NONCE_LEN] = STANDARD
+            .decode(nonce)
+            .context("nonce is not base64")?
+            .try_into()
+            .map_err(|_| anyhow!("nonce has the wrong length"))?;
+        let mut data = STANDARD.decode(data).context("ciphertext is not base64")?;
+
+        let key = self.data_key(version, owner)?;
+        let plaintext = key
+            .open_in_place(Nonce::assume_unique_for_key(nonce), Aad::from(owner.as_bytes()), &mut data)
+            .map_err(|_| anyhow!("payload does not decrypt with key version {}", version))?;
+        String::from_utf8(plaintext.to_vec()).context("decrypted content is not UTF-8")
+    }
+
+    // Decrypt with whatever key sealed it and seal again with the current one;
+    // None when it already uses the current key
+    pub fn reencrypt(&self, owner: &str, sealed: &str) -> Result<Option<String>> {
+        if key_version(sealed) == Some(self.current) {
+            return Ok(None);
+        }
+        self.encrypt(owner, &self.decrypt(owner, sealed)?).map(Some)
+    }
+}
+
+pub fn key_version(sealed: &str) -> Option<u32> {
+    sealed.strip_prefix('k')?.split(':').next()?.parse().ok()
+}
+
+pub fn version_label(version: u32) -> String {
+    format!("k{}", version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cipher(keys: &[(u32, u8)]) -> PayloadCipher {
+        PayloadCipher::new(keys[0].0, keys.iter().map(|(version, byte)| (*version, [*byte; MASTER_KEY_LEN]))).unwrap()
+    }
+
+    #[test]
+    fn test_round_trip_is_bound_to_the_owner() {
+        let cipher = cipher(&[(1, 7)]);
+        let sealed = cipher.encrypt("alice", "Alice's passport number is X1234").unwrap();
+        assert!(sealed.starts_with("k1:"));
+        assert!(!sealed.contains("passport"));
+        assert_eq!(cipher.decrypt("alice", &sealed).unwrap(), "Alice's passport number is X1234");
+
+        // Fresh nonce per call, and another owner's key does not open it
+        assert_ne!(cipher.encrypt("alice", "Alice's passport number is X1234").unwrap(), sealed);
+        assert!(cipher.decrypt("bob", &sealed).is_err());
+
+        let mut tampered = sealed.clone();
+        tampered.pop();
+        tampered.push(if sealed.ends_with('A') { 'B' } else { 'A' });
+        assert!(cipher.decrypt("alice", &tampered).is_err());
+    }
+
+    #[test]
+    fn test_rotation_reencrypts_under_the_current_key() {
+        let old = cipher(&[(1, 7)]);
+        let sealed = old.encrypt(LOCAL_OWNER, "Blood type 0+").unwrap();
+
+        let rotated = cipher(&[(2, 9), (1, 7)]);
+        assert_eq!(rotated.decrypt(LOCAL_OWNER, &sealed).unwrap(), "Blood type 0+");
+        let resealed = rotated.reencrypt(LOCAL_OWNER, &sealed).unwrap().unwrap();
+        assert_eq!(key_version(&resealed), Some(2));
+        assert_eq!(rotated.reencrypt(LOCAL_OWNER, &resealed).unwrap(), None);
+
+        // Once the old key is dropped only the re-encrypted payload reads
+        let retired = cipher(&[(2, 9)]);
+        assert_eq!(retired.decrypt(LOCAL_OWNER, &resealed).unwrap(), "Blood type 0+");
+        assert!(retired.decrypt(LOCAL_OWNER, &sealed).is_err());
+    }
+
+    #[test]
+    fn test_keys_parse_from_config() {
+        let key = STANDARD.encode([3u8; MASTER_KEY_LEN]);
+        let cipher = PayloadCipher::parse(&format!("4:{}, 2:{}", key, key)).unwrap();
+        assert_eq!(cipher.current_version(), 4);
+
+        assert!(PayloadCipher::parse("").is_err());
+        assert!(PayloadCipher::parse("1:c2hvcnQ=").is_err());
+        assert!(PayloadCipher::parse(&format!("one:{}", key)).is_err());
+        assert!(should_encrypt(&["Sensitive".to_string()]));
+        assert!(should_encrypt(&[MEMORY_TAG.to_string()]));
+        assert!(!should_encrypt(&["work".to_string()]));
+    }
+}
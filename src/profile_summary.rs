@@ -0,0 +1,443 @@
+// What the assistant knows about the user and what shapes its answers,
+// assembled from the stores themselves: the persona and prompt, extracted
+// memories, documents, integrations, notifications, models and residency
+// rules. Served at /api/v1/me/profile-summary and used to ground the answer
+// to "what do you know about me?"
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use crate::crawler::{CrawlJob, CrawlStatus};
+use crate::data_residency::{ProviderClass, ResidencyPolicy};
+use crate::guardrails::{ActiveGuardrail, SystemPrompt};
+use crate::knowledge_service_simple::Document;
+use crate::retrieval::MEMORY_TAG;
+
+// Members of this group also see the assembled system prompt
+pub const ADMIN_GROUP: &str = "admin";
+
+// Most recent memories listed in the summary
+pub const RECENT_FACTS: usize = 5;
+
+// Phrasings that ask the assistant what it has on the user
+const PROFILE_QUESTIONS: [&str; 6] = [
+    "what do you know about me",
+    "what do you remember about me",
+    "what have you learned about me",
+    "what have you stored about me",
+    "what data do you have on me",
+    "what information do you have about me",
+];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProfileSummary {
+    pub assistant: AssistantSummary,
+    pub memory: MemorySummary,
+    pub documents: DocumentSummary,
+    pub integrations: Vec<Integration>,
+    pub notifications: NotificationSettings,
+    pub models: ModelSettings,
+    pub data_residency: Vec<ResidencyRule>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AssistantSummary {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub persona: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub instructions: Option<String>,
+    pub system_prompt: String,
+    pub guardrails: Vec<ActiveGuardrail>,
+    // The assembled prompt; only shown to admins
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system_prompt_text: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MemorySummary {
+    pub total: usize,
+    pub by_category: BTreeMap<String, usize>,
+    // Newest first
+    pub recent: Vec<MemoryFact>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MemoryFact {
+    pub title: String,
+    pub category: String,
+    pub learned_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DocumentSummary {
+    pub total: usize,
+    pub by_tag: BTreeMap<String, usize>,
+    pub by_source: BTreeMap<String, usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Integration {
+    pub kind: String,
+    pub name: String,
+    pub status: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct NotificationSettings {
+    // SMTP server the briefing email goes through
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email_server: Option<String>,
+    // Only the count; webhook URLs often carry tokens
+    pub webhooks: usize,
+}
+
+impl NotificationSettings {
+    // SMTP_HOST and WEBHOOK_URLS
+    pub fn from_env() -> Self {
+        let var = |key: &str| std::env::var(key).ok().filter(|v| !v.trim().is_empty());
+        Self {
+            email_server: var("SMTP_HOST"),
+            webhooks: var("WEBHOOK_URLS")
+                .map(|urls| urls.split(',').filter(|u| !u.trim().is_empty()).count())
+                .unwrap_or(0),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ModelSettings {
+    pub chat_model: String,
+    pub chat_provider: ProviderClass,
+    // None without a knowledge base
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub embedding_model: Option<String>,
+    // Embeds documents under residency rules that allow local providers only
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub local_embedding_model: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ResidencyRule {
+    pub tag: String,
+    pub allowed: Vec<ProviderClass>,
+}
+
+// Everything the summary is built from, gathered by the caller
+pub struct ProfileInputs<'a> {
+    pub documents: &'a [Document],
+    pub persona: Option<&'a str>,
+    pub instructions: Option<&'a str>,
+    pub system_prompt_name: &'a str,
+    pub system_prompt: &'a SystemPrompt,
+    pub admin: bool,
+    pub crawls: &'a [CrawlJob],
+    pub notifications: NotificationSettings,
+    pub models: ModelSettings,
+    pub residency: &'a ResidencyPolicy,
+}
+
+pub fn assemble(inputs: ProfileInputs<'_>) -> ProfileSummary {
+    // The knowledge base lists chunks; count each document once
+    let mut documents: Vec<&Document> = inputs.documents.iter().collect();
+    documents.sort_by(|a, b| a.id.cmp(&b.id).then(a.chunk_index.cmp(&b.chunk_index)));
+    documents.dedup_by(|a, b| a.id == b.id);
+    let (memories, documents): (Vec<&Document>, Vec<&Document>) =
+        documents.into_iter().partition(|doc| doc.tags.iter().any(|t| t == MEMORY_TAG));
+
+    let mut facts: Vec<MemoryFact> = memories.iter().map(|doc| memory_fact(doc)).collect();
+    let mut by_category = BTreeMap::new();
+    for fact in &facts {
+        *by_category.entry(fact.category.clone()).or_insert(0) += 1;
+    }
+    facts.sort_by(|a, b| b.learned_at.cmp(&a.learned_at).then_with(|| a.title.cmp(&b.title)));
+    facts.truncate(RECENT_FACTS);
+
+    let mut by_tag = BTreeMap::new();
+    let mut by_source = BTreeMap::new();
+    for doc in &documents {
+        for tag in &doc.tags {
+            *by_tag.entry(tag.clone()).or_insert(0) += 1;
+        }
+        *by_source.entry(doc.source.clone()).or_insert(0) += 1;
+    }
+
+    let mut integrations: Vec<Integration> = inputs
+        .crawls
+        .iter()
+        .map(|job| Integration {
+            kind: "url_crawl".to_string(),
+            name: job.config.seed_url.clone(),
+            status: match &job.status {
+                CrawlStatus::Running => "running".to_string(),
+                CrawlStatus::Completed => "completed".to_string(),
+                CrawlStatus::Failed(reason) => format!("failed: {}", reason),
+            },
+        })
+        .collect();
+    if let Some(host) = &inputs.notifications.email_server {
+        integrations.push(Integration {
+            kind: "email".to_string(),
+            name: host.clone(),
+            status: "configured".to_string(),
+        });
+    }
+
+    ProfileSummary {
+        assistant: AssistantSummary {
+            persona: inputs.persona.map(str::to_string),
+            instructions: inputs.instructions.map(str::to_string),
+            system_prompt: inputs.system_prompt_name.to_string(),
+            guardrails: inputs.system_prompt.guardrails.clone(),
+            system_prompt_text: inputs.admin.then(|| inputs.system_prompt.text.clone()),
+        },
+        memory: MemorySummary {
+            total: memories.len(),
+            by_category,
+            recent: facts,
+        },
+        documents: DocumentSummary {
+            total: documents.len(),
+            by_tag,
+            by_source,
+        },
+        integrations,
+        notifications: inputs.notifications,
+        models: inputs.models,
+        data_residency: inputs
+            .residency
+            .rules()
+            .map(|(tag, allowed)| ResidencyRule { tag: tag.to_string(), allowed: allowed.to_vec() })
+            .collect(),
+    }
+}
+
+// Extracted memories are titled "[category] title"
+fn memory_fact(doc: &Document) -> MemoryFact {
+    let (category, title) = doc
+        .title
+        .strip_prefix('[')
+        .and_then(|rest| rest.split_once("] "))
+        .unwrap_or(("other", doc.title.as_str()));
+    MemoryFact {
+        title: title.to_string(),
+        category: category.to_string(),
+        learned_at: doc.created_at,
+    }
+}
+
+// Whether the message asks what the assistant knows about the user
+pub fn is_profile_question(message: &str) -> bool {
+    let normalized: String = message
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_alphanumeric() || c.is_whitespace() { c } else { ' ' })
+        .collect();
+    let normalized = normalized.split_whitespace().collect::<Vec<_>>().join(" ");
+    PROFILE_QUESTIONS.iter().any(|question| normalized.contains(question))
+}
+
+// The summary as prompt context, so the answer names only what is stored
+pub fn grounding_context(summary: &ProfileSummary) -> String {
+    let mut lines = vec![format!(
+        "Stored memories: {} ({})",
+        summary.memory.total,
+        counts(&summary.memory.by_category)
+    )];
+    for fact in &summary.memory.recent {
+        lines.push(format!("- [{}] {} (learned {})", fact.category, fact.title, fact.learned_at.format("%Y-%m-%d")));
+    }
+    lines.push(format!(
+        "Documents: {} (tags: {}; sources: {})",
+        summary.documents.total,
+        counts(&summary.documents.by_tag),
+        counts(&summary.documents.by_source)
+    ));
+    if let Some(persona) = &summary.assistant.persona {
+        lines.push(format!("Persona in this conversation: {}", persona));
+    }
+    if let Some(instructions) = &summary.assistant.instructions {
+        lines.push(format!("Standing instructions: {}", instructions));
+    }
+    let integrations = if summary.integrations.is_empty() {
+        "none".to_string()
+    } else {
+        summary
+            .integrations
+            .iter()
+            .map(|i| format!("{} {} ({})", i.kind, i.name, i.status))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    lines.push(format!("Connected integrations: {}", integrations));
+    lines.push(format!(
+        "Notifications: briefing email {}, {} webhook(s)",
+        if summary.notifications.email_server.is_some() { "on" } else { "off" },
+        summary.notifications.webhooks
+    ));
+    lines.push(format!(
+        "Chat model: {} ({} provider)",
+        summary.models.chat_model, summary.models.chat_provider
+    ));
+    for rule in &summary.data_residency {
+        lines.push(format!(
+            "Data tagged '{}' is only processed by {} providers",
+            rule.tag,
+            rule.allowed.iter().map(|c| c.to_string()).collect::<Vec<_>>().join(" or ")
+        ));
+    }
+
+    format!(
+        "\n\nThe user asked what you know about them. This is everything stored about them; describe it in plain language and do not claim to know anything beyond it:\n{}",
+        lines.join("\n")
+    )
+}
+
+fn counts(counts: &BTreeMap<String, usize>) -> String {
+    if counts.is_empty() {
+        return "none".to_string();
+    }
+    counts.iter().map(|(name, n)| format!("{} {}", n, name)).collect::<Vec<_>>().join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crawler::CrawlConfig;
+    use crate::trust::TrustLevel;
+    use chrono::TimeZone;
+
+    fn doc(id: &str, title: &str, chunk_index: usize, source: &str, tags: &[&str], day: u32) -> Document {
+        Document {
+            id: id.to_string(),
+            title: title.to_string(),
+            content: String::new(),
+            chunk_index,
+            total_chunks: 2,
+            source: source.to_string(),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            trust_level: TrustLevel::Personal,
+            created_at: chrono::Utc.with_ymd_and_hms(2026, 3, day, 9, 0, 0).unwrap(),
+            offset: 0,
+            manually_corrected: false,
+            revision: 1,
+            updated_at: None,
+            page: None,
+        }
+    }
+
+    fn fixtures() -> Vec<Document> {
+        let mut documents = vec![
+            doc("handbook", "Team handbook", 0, "upload", &["work"], 1),
+            doc("handbook", "Team handbook", 1, "upload", &["work"], 1),
+            doc("labs", "Blood test results", 0, "upload", &["medical", "work"], 2),
+            doc("site", "Project wiki", 0, "https://wiki.example.com", &[], 3),
+        ];
+        for (i, (category, title)) in [
+            ("personal", "User's Name"),
+            ("preferences", "Prefers trains"),
+            ("personal", "Lives in Vienna"),
+            ("projects", "Writing a thesis"),
+            ("relationships", "Sister Anna"),
+            ("events", "Birthday in May"),
+        ]
+        .iter()
+        .enumerate()
+        {
+            documents.push(doc(
+                &format!("fact-{}", i),
+                &format!("[{}] {}", category, title),
+                0,
+                "conversation_s1",
+                &["identity", category, "high", MEMORY_TAG],
+                10 + i as u32,
+            ));
+        }
+        documents
+    }
+
+    fn summarize(documents: &[Document], admin: bool) -> ProfileSummary {
+        let prompt = SystemPrompt {
+            text: "Deployment rules...\n\nYou are a helpful personal AI assistant.".to_string(),
+            guardrails: vec![ActiveGuardrail { id: "no-medical-advice".to_string(), version: 2 }],
+        };
+        let crawls = vec![CrawlJob::new(CrawlConfig {
+            seed_url: "https://wiki.example.com".to_string(),
+            max_pages: 10,
+            politeness_delay_ms: 0,
+            respect_robots: true,
+            trust_level: TrustLevel::External,
+        })];
+        let residency = ResidencyPolicy::parse("medical=local").unwrap();
+        assemble(ProfileInputs {
+            documents,
+            persona: Some("a patient Spanish tutor"),
+            instructions: None,
+            system_prompt_name: "personal-assistant",
+            system_prompt: &prompt,
+            admin,
+            crawls: &crawls,
+            notifications: NotificationSettings { email_server: Some("smtp.example.com".to_string()), webhooks: 2 },
+            models: ModelSettings {
+                chat_model: "gpt-4o-mini".to_string(),
+                chat_provider: ProviderClass::Cloud,
+                embedding_model: Some("text-embedding-3-small".to_string()),
+                local_embedding_model: None,
+            },
+            residency: &residency,
+        })
+    }
+
+    #[test]
+    fn test_summary_matches_the_stored_data() {
+        let summary = summarize(&fixtures(), false);
+
+        assert_eq!(summary.memory.total, 6);
+        assert_eq!(summary.memory.by_category.get("personal"), Some(&2));
+        assert_eq!(summary.memory.by_category.get("events"), Some(&1));
+        let recent: Vec<&str> = summary.memory.recent.iter().map(|f| f.title.as_str()).collect();
+        assert_eq!(recent, ["Birthday in May", "Sister Anna", "Writing a thesis", "Lives in Vienna", "Prefers trains"]);
+        assert_eq!(summary.memory.recent[0].category, "events");
+
+        // Chunks of one document count once; memories are not documents
+        assert_eq!(summary.documents.total, 3);
+        assert_eq!(summary.documents.by_tag.get("work"), Some(&2));
+        assert_eq!(summary.documents.by_tag.get("medical"), Some(&1));
+        assert_eq!(summary.documents.by_source.get("upload"), Some(&2));
+
+        assert_eq!(summary.assistant.persona.as_deref(), Some("a patient Spanish tutor"));
+        assert_eq!(summary.assistant.system_prompt, "personal-assistant");
+        assert_eq!(summary.assistant.guardrails[0].id, "no-medical-advice");
+        assert_eq!(summary.integrations.len(), 2);
+        assert_eq!(summary.integrations[0].kind, "url_crawl");
+        assert_eq!(summary.integrations[1].name, "smtp.example.com");
+        assert_eq!(
+            summary.data_residency,
+            vec![ResidencyRule { tag: "medical".to_string(), allowed: vec![ProviderClass::Local] }]
+        );
+    }
+
+    #[test]
+    fn test_prompt_text_is_only_shown_to_admins() {
+        assert_eq!(summarize(&fixtures(), false).assistant.system_prompt_text, None);
+        let text = summarize(&fixtures(), true).assistant.system_prompt_text.unwrap();
+        assert!(text.contains("You are a helpful personal AI assistant."));
+
+        let json = serde_json::to_value(summarize(&fixtures(), false)).unwrap();
+        assert!(json["assistant"].get("system_prompt_text").is_none());
+    }
+
+    #[test]
+    fn test_profile_question_is_grounded_in_the_summary() {
+        assert!(is_profile_question("What do you know about me?"));
+        assert!(is_profile_question("hey, what data do you have on me"));
+        assert!(!is_profile_question("What do you know about Rust?"));
+
+        let context = grounding_context(&summarize(&fixtures(), false));
+        assert!(context.contains("Stored memories: 6 (1 events, 2 personal, 1 preferences, 1 projects, 1 relationships)"));
+        assert!(context.contains("- [events] Birthday in May (learned 2026-03-15)"));
+        assert!(context.contains("Documents: 3"));
+        assert!(context.contains("url_crawl https://wiki.example.com (running)"));
+        assert!(context.contains("Data tagged 'medical' is only processed by local providers"));
+        // The user's own profile never includes the prompt text
+        assert!(!context.contains("Deployment rules"));
+    }
+}
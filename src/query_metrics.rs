@@ -0,0 +1,117 @@
+// Timing of the conversation store's queries, with the threshold and slow
+// log size taken from the environment. Also a sliding window of recent
+// latencies for background work that backs off while the foreground is slow,
+// and the model tokens spent per feature.
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+pub use rusty_ai_common::query_metrics::QueryMetrics;
+
+// STORAGE_SLOW_QUERY_MS and STORAGE_SLOW_QUERY_LOG_SIZE, defaulting to
+// 100ms and 50 entries like the core storage config
+pub fn conversation_store_metrics() -> QueryMetrics {
+    let threshold_ms = std::env::var("STORAGE_SLOW_QUERY_MS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(100);
+    let capacity = std::env::var("STORAGE_SLOW_QUERY_LOG_SIZE")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .unwrap_or(50);
+    QueryMetrics::new(Duration::from_millis(threshold_ms), capacity)
+}
+
+// Latencies observed over the last `window`, so a percentile reflects
+// current load rather than everything since startup. With no recent samples
+// there is no percentile
+pub struct RecentLatency {
+    window: Duration,
+    samples: Mutex<VecDeque<(Instant, Duration)>>,
+}
+
+impl RecentLatency {
+    pub fn new(window: Duration) -> Self {
+        Self { window, samples: Mutex::new(VecDeque::new()) }
+    }
+
+    pub fn observe(&self, duration: Duration) {
+        let now = Instant::now();
+        let mut samples = self.samples.lock().unwrap();
+        samples.push_back((now, duration));
+        Self::expire(&mut samples, now, self.window);
+    }
+
+    pub fn p95(&self) -> Option<Duration> {
+        let mut samples = self.samples.lock().unwrap();
+        Self::expire(&mut samples, Instant::now(), self.window);
+        if samples.is_empty() {
+            return None;
+        }
+        let mut durations: Vec<Duration> = samples.iter().map(|(_, d)| *d).collect();
+        durations.sort();
+        let rank = (durations.len() * 95).div_ceil(100).max(1);
+        Some(durations[rank - 1])
+    }
+
+    fn expire(samples: &mut VecDeque<(Instant, Duration)>, now: Instant, window: Duration) {
+        while samples.front().is_some_and(|(at, _)| now.duration_since(*at) > window) {
+            samples.pop_front();
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct FeatureUsage {
+    pub calls: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+}
+
+// Model tokens per feature ("chat", "summarization") since the server
+// started, as the provider reported them
+#[derive(Default)]
+pub struct TokenUsage {
+    features: Mutex<HashMap<String, FeatureUsage>>,
+}
+
+impl TokenUsage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&self, feature: &str, prompt_tokens: Option<u32>, completion_tokens: Option<u32>) {
+        let mut features = self.features.lock().unwrap();
+        let usage = features.entry(feature.to_string()).or_default();
+        usage.calls += 1;
+        usage.prompt_tokens += u64::from(prompt_tokens.unwrap_or(0));
+        usage.completion_tokens += u64::from(completion_tokens.unwrap_or(0));
+    }
+
+    pub fn get(&self, feature: &str) -> FeatureUsage {
+        self.features.lock().unwrap().get(feature).copied().unwrap_or_default()
+    }
+
+    pub fn snapshot(&self) -> HashMap<String, FeatureUsage> {
+        self.features.lock().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recent_latency_p95_forgets_old_samples() {
+        let latency = RecentLatency::new(Duration::from_millis(50));
+        assert_eq!(latency.p95(), None);
+        for ms in 1..=100 {
+            latency.observe(Duration::from_millis(ms));
+        }
+        assert_eq!(latency.p95(), Some(Duration::from_millis(95)));
+
+        std::thread::sleep(Duration::from_millis(60));
+        assert_eq!(latency.p95(), None);
+    }
+}
@@ -0,0 +1,626 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+
+use crate::chat_pipeline::LatencyBudget;
+use crate::knowledge_service_simple::{DocumentMatch, KnowledgeService, TagFilter, TagMode};
+use crate::trust::TrustMultipliers;
+
+// Tag the memory service puts on facts extracted from conversations
+pub const MEMORY_TAG: &str = "extracted";
+// Files uploaded into a chat carry the tag of their session
+const ATTACHMENT_TAG_PREFIX: &str = "session:";
+// ...and this one, which lets document searches leave them out
+pub const ATTACHMENT_TAG: &str = "attachment";
+
+// Question and filler words dropped when a message is rewritten for the
+// keyword search; they occur in nearly every chunk
+const FILLER_WORDS: &[&str] = &[
+    "a", "about", "an", "and", "any", "are", "at", "be", "by", "can", "could", "did", "do", "does", "for",
+    "from", "give", "i", "in", "is", "it", "me", "my", "of", "on", "or", "please", "show", "tell", "that",
+    "the", "this", "to", "was", "we", "were", "what", "which", "who", "with", "would", "you", "your",
+];
+
+// Where a retrieved entry comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SourceKind {
+    Document,
+    Memory,
+    Attachment,
+}
+
+impl SourceKind {
+    pub fn of(doc: &DocumentMatch) -> Self {
+        if doc.tags.iter().any(|t| t == MEMORY_TAG) {
+            SourceKind::Memory
+        } else if doc.tags.iter().any(|t| t.starts_with(ATTACHMENT_TAG_PREFIX)) {
+            SourceKind::Attachment
+        } else {
+            SourceKind::Document
+        }
+    }
+
+    // Latency budget stage the source's search is recorded under
+    fn stage(&self) -> &'static str {
+        match self {
+            SourceKind::Document => "knowledge_search",
+            SourceKind::Memory => "memory_retrieval",
+            SourceKind::Attachment => "attachment_retrieval",
+        }
+    }
+
+    // Stage of the keyword search raced against it
+    fn keyword_stage(&self) -> &'static str {
+        match self {
+            SourceKind::Document => "knowledge_keywords",
+            SourceKind::Memory => "memory_keywords",
+            SourceKind::Attachment => "attachment_keywords",
+        }
+    }
+}
+
+pub fn attachment_tag(session_id: &str) -> String {
+    format!("{}{}", ATTACHMENT_TAG_PREFIX, session_id)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SourceSettings {
+    // Multiplier applied to the similarity score; 0 disables the source
+    pub weight: f32,
+    pub max_results: usize,
+    // Minimum raw similarity, before weighting
+    pub threshold: f32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetrievalConfig {
+    pub documents: SourceSettings,
+    pub memories: SourceSettings,
+    pub attachments: SourceSettings,
+    // Applied on top of the source weight, by the document's trust level
+    pub trust: TrustMultipliers,
+}
+
+impl Default for RetrievalConfig {
+    fn default() -> Self {
+        Self {
+            documents: SourceSettings { weight: 1.0, max_results: 5, threshold: 0.1 },
+            // Candidates; the memory policy admits at most its own cap
+            memories: SourceSettings { weight: 1.0, max_results: 6, threshold: 0.2 },
+            // Files the user just shared are usually what the question is about
+            attachments: SourceSettings { weight: 1.2, max_results: 3, threshold: 0.1 },
+            trust: TrustMultipliers::default(),
+        }
+    }
+}
+
+impl RetrievalConfig {
+    // RETRIEVAL_{DOCUMENT,MEMORY,ATTACHMENT}_{WEIGHT,MAX_RESULTS,THRESHOLD} and
+    // RETRIEVAL_TRUST_{VERIFIED,PERSONAL,EXTERNAL,UNVERIFIED}
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        let read = |prefix: &str, default: SourceSettings| {
+            let var = |name: &str| std::env::var(format!("RETRIEVAL_{}_{}", prefix, name)).ok();
+            SourceSettings {
+                weight: var("WEIGHT").and_then(|v| v.parse().ok()).unwrap_or(default.weight),
+                max_results: var("MAX_RESULTS").and_then(|v| v.parse().ok()).unwrap_or(default.max_results),
+                threshold: var("THRESHOLD").and_then(|v| v.parse().ok()).unwrap_or(default.threshold),
+            }
+        };
+
+        Self {
+            documents: read("DOCUMENT", defaults.documents),
+            memories: read("MEMORY", defaults.memories),
+            attachments: read("ATTACHMENT", defaults.attachments),
+            trust: TrustMultipliers::from_env(),
+        }
+    }
+
+    pub fn source(&self, kind: SourceKind) -> &SourceSettings {
+        match kind {
+            SourceKind::Document => &self.documents,
+            SourceKind::Memory => &self.memories,
+            SourceKind::Attachment => &self.attachments,
+        }
+    }
+
+    // The config with a session's weight overrides applied
+    pub fn with_weights(&self, weights: &SourceWeights) -> Self {
+        let mut config = self.clone();
+        config.documents.weight = weights.document.unwrap_or(config.documents.weight);
+        config.memories.weight = weights.memory.unwrap_or(config.memories.weight);
+        config.attachments.weight = weights.attachment.unwrap_or(config.attachments.weight);
+        config
+    }
+}
+
+// Per-session weight overrides, kept in the session settings
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SourceWeights {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub document: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attachment: Option<f32>,
+}
+
+impl SourceWeights {
+    pub fn validate(&self) -> std::result::Result<(), String> {
+        for (name, weight) in [("document", self.document), ("memory", self.memory), ("attachment", self.attachment)] {
+            if let Some(weight) = weight {
+                if !weight.is_finite() || weight < 0.0 {
+                    return Err(format!("Invalid {} weight: {}", name, weight));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// The entries one source may return: its kind and, for attachments, only
+// those of the current session
+#[derive(Debug, Clone, Copy)]
+pub struct SourceFilter<'a> {
+    pub kind: SourceKind,
+    pub session_id: &'a str,
+}
+
+impl SourceFilter<'_> {
+    pub fn matches(&self, doc: &DocumentMatch) -> bool {
+        SourceKind::of(doc) == self.kind
+            && (self.kind != SourceKind::Attachment || doc.tags.contains(&attachment_tag(self.session_id)))
+    }
+
+    // The same restriction as tags for the store to search by
+    pub fn tag_filter(&self) -> TagFilter {
+        match self.kind {
+            SourceKind::Document => TagFilter::new(Vec::new(), TagMode::Any).excluding([MEMORY_TAG, ATTACHMENT_TAG]),
+            SourceKind::Memory => TagFilter::new(vec![MEMORY_TAG.to_string()], TagMode::All),
+            SourceKind::Attachment => {
+                TagFilter::new(vec![attachment_tag(self.session_id)], TagMode::All).excluding([MEMORY_TAG])
+            }
+        }
+    }
+}
+
+// Similarity search over the knowledge base; KnowledgeService in production,
+// an in-memory store in tests
+pub trait KnowledgeSearch {
+    fn search(
+        &self,
+        query: &str,
+        filter: &SourceFilter<'_>,
+        limit: usize,
+        threshold: f32,
+    ) -> impl Future<Output = Result<Vec<DocumentMatch>>> + Send;
+
+    // Matches on the query's words, for when the similarity search is too
+    // slow or down; stores without a keyword index find nothing
+    fn keyword_search(
+        &self,
+        _query: &str,
+        _filter: &SourceFilter<'_>,
+        _limit: usize,
+    ) -> impl Future<Output = Result<Vec<DocumentMatch>>> + Send {
+        async { Ok(Vec::new()) }
+    }
+}
+
+impl KnowledgeSearch for KnowledgeService {
+    async fn search(
+        &self,
+        query: &str,
+        filter: &SourceFilter<'_>,
+        limit: usize,
+        threshold: f32,
+    ) -> Result<Vec<DocumentMatch>> {
+        self.search_documents(query, limit, threshold, Some(filter.tag_filter())).await
+    }
+
+    async fn keyword_search(&self, query: &str, filter: &SourceFilter<'_>, limit: usize) -> Result<Vec<DocumentMatch>> {
+        self.search_keywords(query, limit, Some(&filter.tag_filter())).await
+    }
+}
+
+// What a federated search found
+#[derive(Debug, Default)]
+pub struct Retrieved {
+    pub matches: Vec<DocumentMatch>,
+    // Sources whose similarity search failed or missed its deadline, and
+    // whose keyword matches stand in for it
+    pub keyword_fallback: Vec<SourceKind>,
+}
+
+impl Retrieved {
+    // The match came from a keyword fallback rather than the full search
+    pub fn is_partial(&self, doc: &DocumentMatch) -> bool {
+        self.keyword_fallback.contains(&SourceKind::of(doc))
+    }
+}
+
+// Rewrite a chat message into a keyword query: its words in lowercase,
+// each once, without question and filler words. None when nothing is left
+pub fn rewrite_for_keywords(message: &str) -> Option<String> {
+    let mut words: Vec<String> = Vec::new();
+    for word in message.split(|c: char| !c.is_alphanumeric()).filter(|word| !word.is_empty()) {
+        let word = word.to_lowercase();
+        if !FILLER_WORDS.contains(&word.as_str()) && !words.contains(&word) {
+            words.push(word);
+        }
+    }
+    (!words.is_empty()).then(|| words.join(" "))
+}
+
+// Search every source concurrently, each within the retrieval budget, and
+// merge the weighted results. Each source races its similarity search
+// against a keyword search: when the similarity search answers in time the
+// keyword matches are dropped, otherwise it is cancelled and the keyword
+// matches are used instead. A source with neither is left out.
+//
+// The similarity search gets the message as it is. The keyword search gets
+// it rewritten by an optional stage, and the message itself when the budget
+// is too low for the rewrite.
+pub async fn federated_search<S: KnowledgeSearch>(
+    store: &S,
+    budget: &LatencyBudget,
+    query: &str,
+    config: &RetrievalConfig,
+    session_id: &str,
+) -> Retrieved {
+    let keywords = budget
+        .run_optional("query_rewrite", async { Ok::<_, std::convert::Infallible>(rewrite_for_keywords(query)) })
+        .await
+        .flatten()
+        .unwrap_or_else(|| query.to_string());
+    let keywords = keywords.as_str();
+
+    let search = move |kind: SourceKind| async move {
+        let settings = config.source(kind);
+        if settings.weight <= 0.0 || settings.max_results == 0 {
+            return (Vec::new(), false);
+        }
+        let filter = SourceFilter { kind, session_id };
+        let (similar, keywords) = tokio::join!(
+            budget.run_retrieval(kind.stage(), store.search(query, &filter, settings.max_results, settings.threshold)),
+            budget.run_retrieval(kind.keyword_stage(), store.keyword_search(keywords, &filter, settings.max_results)),
+        );
+        match (similar, keywords) {
+            (Some(similar), _) => (similar, false),
+            (None, Some(keywords)) => (keywords, true),
+            (None, None) => (Vec::new(), false),
+        }
+    };
+
+    let ((documents, documents_fallback), (memories, memories_fallback), (attachments, attachments_fallback)) = tokio::join!(
+        search(SourceKind::Document),
+        search(SourceKind::Memory),
+        search(SourceKind::Attachment),
+    );
+
+    let keyword_fallback = [
+        (SourceKind::Document, documents_fallback),
+        (SourceKind::Memory, memories_fallback),
+        (SourceKind::Attachment, attachments_fallback),
+    ]
+    .into_iter()
+    .filter(|(_, fallback)| *fallback)
+    .map(|(kind, _)| kind)
+    .collect();
+    let matches = merge_weighted(
+        config,
+        vec![
+            (SourceKind::Document, documents),
+            (SourceKind::Memory, memories),
+            (SourceKind::Attachment, attachments),
+        ],
+    );
+    Retrieved { matches, keyword_fallback }
+}
+
+// Cap each source's results, scale their scores by the source weight and the
+// trust level of their document and merge them best first. An entry found by several sources keeps its best score.
+pub fn merge_weighted(config: &RetrievalConfig, results: Vec<(SourceKind, Vec<DocumentMatch>)>) -> Vec<DocumentMatch> {
+    let mut merged: Vec<DocumentMatch> = Vec::new();
+
+    for (kind, mut matches) in results {
+        let settings = config.source(kind);
+        matches.retain(|doc| doc.score >= settings.threshold);
+        sort_by_score(&mut matches);
+        matches.truncate(settings.max_results);
+
+        for mut doc in matches {
+            doc.score *= settings.weight * config.trust.for_match(&doc);
+            match merged.iter_mut().find(|existing| existing.same_entry(&doc)) {
+                Some(existing) if doc.score > existing.score => *existing = doc,
+                Some(_) => {}
+                None => merged.push(doc),
+            }
+        }
+    }
+
+    sort_by_score(&mut merged);
+    merged
+}
+
+fn sort_by_score(documents: &mut [DocumentMatch]) {
+    documents.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chat_pipeline::{PipelineConfig, StageStatus};
+    use std::time::Duration;
+    use crate::trust::TrustLevel;
+
+    // Scores an entry by the share of query words it contains. Its keyword
+    // search scores the same way but never waits
+    struct InMemoryStore {
+        entries: Vec<DocumentMatch>,
+        // How long a similarity search takes
+        delay: Duration,
+    }
+
+    impl InMemoryStore {
+        fn seeded() -> Self {
+            let entry = |id: &str, content: &str, tags: &[&str]| DocumentMatch {
+                id: id.to_string(),
+                title: id.to_string(),
+                content: content.to_string(),
+                score: 0.0,
+                chunk_index: 0,
+                source: "test".to_string(),
+                tags: tags.iter().map(|t| t.to_string()).collect(),
+                trust_level: TrustLevel::Personal,
+                note: None,
+                manually_corrected: false,
+                page: None,
+            };
+
+            Self {
+                entries: vec![
+                    entry("handbook", "travel budget policy for the team", &[]),
+                    entry("expenses", "travel budget receipts", &["work"]),
+                    entry("old-notes", "budget", &[]),
+                    entry("fact", "user prefers travel by train, budget conscious", &[MEMORY_TAG]),
+                    entry("itinerary", "travel plan with budget", &["session:s1"]),
+                    entry("other-session", "travel budget policy for the team", &["session:s2"]),
+                ],
+                delay: Duration::ZERO,
+            }
+        }
+
+        fn matching(&self, query: &str, filter: &SourceFilter<'_>, limit: usize, threshold: f32) -> Vec<DocumentMatch> {
+            let terms: Vec<&str> = query.split_whitespace().collect();
+            let mut matches: Vec<DocumentMatch> = self
+                .entries
+                .iter()
+                .filter(|doc| filter.matches(doc))
+                .map(|doc| {
+                    let hits = terms.iter().filter(|t| doc.content.contains(*t)).count();
+                    DocumentMatch { score: hits as f32 / terms.len() as f32, ..doc.clone() }
+                })
+                .filter(|doc| doc.score >= threshold)
+                .collect();
+            sort_by_score(&mut matches);
+            matches.truncate(limit);
+            matches
+        }
+    }
+
+    impl KnowledgeSearch for InMemoryStore {
+        async fn search(
+            &self,
+            query: &str,
+            filter: &SourceFilter<'_>,
+            limit: usize,
+            threshold: f32,
+        ) -> Result<Vec<DocumentMatch>> {
+            tokio::time::sleep(self.delay).await;
+            Ok(self.matching(query, filter, limit, threshold))
+        }
+
+        async fn keyword_search(&self, query: &str, filter: &SourceFilter<'_>, limit: usize) -> Result<Vec<DocumentMatch>> {
+            Ok(self.matching(query, filter, limit, 0.0))
+        }
+    }
+
+    fn uniform_config(max_results: usize) -> RetrievalConfig {
+        let settings = SourceSettings { weight: 1.0, max_results, threshold: 0.1 };
+        RetrievalConfig {
+            documents: settings,
+            memories: settings,
+            attachments: settings,
+            trust: TrustMultipliers::default(),
+        }
+    }
+
+    async fn search(config: &RetrievalConfig) -> Vec<DocumentMatch> {
+        let budget = LatencyBudget::start(PipelineConfig::default());
+        federated_search(&InMemoryStore::seeded(), &budget, "travel budget policy", config, "s1").await.matches
+    }
+
+    fn ids(results: &[DocumentMatch]) -> Vec<&str> {
+        results.iter().map(|doc| doc.id.as_str()).collect()
+    }
+
+    #[test]
+    fn test_classifies_entries() {
+        let store = InMemoryStore::seeded();
+        let kinds: Vec<SourceKind> = store.entries.iter().map(SourceKind::of).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                SourceKind::Document,
+                SourceKind::Document,
+                SourceKind::Document,
+                SourceKind::Memory,
+                SourceKind::Attachment,
+                SourceKind::Attachment,
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_merges_all_sources_of_the_session() {
+        let results = search(&uniform_config(5)).await;
+
+        // Attachments of other sessions are never returned
+        assert_eq!(ids(&results), vec!["handbook", "expenses", "fact", "itinerary", "old-notes"]);
+    }
+
+    #[tokio::test]
+    async fn test_slow_similarity_search_falls_back_to_keywords() {
+        let config = PipelineConfig { retrieval_timeout: Duration::from_millis(50), ..PipelineConfig::default() };
+        let slow = InMemoryStore { delay: Duration::from_secs(5), ..InMemoryStore::seeded() };
+
+        let budget = LatencyBudget::start(config.clone());
+        let retrieved = federated_search(&slow, &budget, "travel budget policy", &uniform_config(5), "s1").await;
+        assert!(budget.elapsed() < Duration::from_secs(1), "took {:?}", budget.elapsed());
+        assert_eq!(ids(&retrieved.matches), vec!["handbook", "expenses", "fact", "itinerary", "old-notes"]);
+        assert_eq!(retrieved.keyword_fallback, vec![SourceKind::Document, SourceKind::Memory, SourceKind::Attachment]);
+        assert!(retrieved.matches.iter().all(|doc| retrieved.is_partial(doc)));
+        let timings = budget.timings();
+        let status = |stage: &str| timings.stages.iter().find(|t| t.stage == stage).map(|t| t.status);
+        assert_eq!(status("knowledge_search"), Some(StageStatus::TimedOut));
+        assert_eq!(status("knowledge_keywords"), Some(StageStatus::Completed));
+
+        // In time, the similarity results are used as they are
+        let budget = LatencyBudget::start(config);
+        let retrieved = federated_search(&InMemoryStore::seeded(), &budget, "travel budget policy", &uniform_config(5), "s1").await;
+        assert!(retrieved.keyword_fallback.is_empty());
+        assert!(!retrieved.matches.iter().any(|doc| retrieved.is_partial(doc)));
+    }
+
+    #[test]
+    fn test_rewrites_messages_into_keyword_queries() {
+        assert_eq!(
+            rewrite_for_keywords("What does the Travel policy say about the travel budget?").as_deref(),
+            Some("travel policy say budget")
+        );
+        assert_eq!(rewrite_for_keywords("Can you tell me what it is?"), None);
+    }
+
+    #[tokio::test]
+    async fn test_keyword_fallback_searches_the_rewritten_query() {
+        let config = PipelineConfig { retrieval_timeout: Duration::from_millis(50), ..PipelineConfig::default() };
+        let slow = InMemoryStore { delay: Duration::from_secs(5), ..InMemoryStore::seeded() };
+        let message = "What is the travel budget policy for the team?";
+
+        // Every word left after the rewrite is in the handbook
+        let budget = LatencyBudget::start(config.clone());
+        let retrieved = federated_search(&slow, &budget, message, &uniform_config(5), "s1").await;
+        assert_eq!(ids(&retrieved.matches)[..2], ["handbook", "expenses"]);
+        assert!((retrieved.matches[0].score - 1.0).abs() < 1e-5);
+        let timings = budget.timings();
+        let rewrite = timings.stages.iter().find(|t| t.stage == "query_rewrite").unwrap();
+        assert_eq!(rewrite.status, StageStatus::Completed);
+
+        // Without budget for the rewrite the message is searched as it is
+        let tight = PipelineConfig { optional_stage_reserve: config.latency_budget, ..config };
+        let budget = LatencyBudget::start(tight);
+        let retrieved = federated_search(&slow, &budget, message, &uniform_config(5), "s1").await;
+        let timings = budget.timings();
+        let rewrite = timings.stages.iter().find(|t| t.stage == "query_rewrite").unwrap();
+        assert_eq!(rewrite.status, StageStatus::Skipped);
+        assert_eq!(retrieved.matches[0].id, "handbook");
+        assert!((retrieved.matches[0].score - 6.0 / 9.0).abs() < 1e-5);
+    }
+
+    #[tokio::test]
+    async fn test_caps_apply_per_source() {
+        let mut config = uniform_config(5);
+        config.documents.max_results = 1;
+
+        let results = search(&config).await;
+
+        assert_eq!(ids(&results), vec!["handbook", "fact", "itinerary"]);
+    }
+
+    #[tokio::test]
+    async fn test_weights_change_the_merged_order() {
+        let mut config = uniform_config(5);
+        config.memories.weight = 0.4;
+        let results = search(&config).await;
+        assert_eq!(ids(&results), vec!["handbook", "expenses", "itinerary", "old-notes", "fact"]);
+
+        // An attachment-heavy session puts the shared file first
+        let weights = SourceWeights { attachment: Some(3.0), ..Default::default() };
+        let results = search(&config.with_weights(&weights)).await;
+        assert_eq!(ids(&results)[0], "itinerary");
+        assert!((results[0].score - 2.0).abs() < 1e-5);
+
+        // A zero weight drops the source entirely
+        let weights = SourceWeights { memory: Some(0.0), ..Default::default() };
+        let results = search(&config.with_weights(&weights)).await;
+        assert!(!ids(&results).contains(&"fact"));
+    }
+
+    #[test]
+    fn test_duplicate_entries_keep_best_score() {
+        let store = InMemoryStore::seeded();
+        let mut low = store.entries[0].clone();
+        low.score = 0.3;
+        let mut high = low.clone();
+        high.score = 0.6;
+
+        let merged = merge_weighted(
+            &uniform_config(5),
+            vec![(SourceKind::Document, vec![low]), (SourceKind::Attachment, vec![high])],
+        );
+
+        assert_eq!(merged.len(), 1);
+        assert!((merged[0].score - 0.6).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_trust_level_shifts_the_ranking() {
+        let store = InMemoryStore::seeded();
+        let with_trust = |index: usize, trust_level: TrustLevel| DocumentMatch {
+            score: 0.8,
+            trust_level,
+            ..store.entries[index].clone()
+        };
+        let matches = vec![
+            with_trust(0, TrustLevel::Unverified),
+            with_trust(1, TrustLevel::Personal),
+            with_trust(2, TrustLevel::Verified),
+        ];
+
+        // Equal similarity: the verified document comes first and the
+        // unverified one last
+        let merged = merge_weighted(&uniform_config(5), vec![(SourceKind::Document, matches.clone())]);
+        assert_eq!(ids(&merged), vec!["old-notes", "expenses", "handbook"]);
+        assert!((merged[2].score - 0.48).abs() < 1e-5);
+
+        // Neutral multipliers keep the search order
+        let mut config = uniform_config(5);
+        config.trust = TrustMultipliers { verified: 1.0, personal: 1.0, external: 1.0, unverified: 1.0, corrected: 1.0 };
+        let mut closer = matches;
+        closer[0].score = 0.9;
+        let merged = merge_weighted(&config, vec![(SourceKind::Document, closer.clone())]);
+        assert_eq!(ids(&merged)[0], "handbook");
+
+        // With the defaults a closer but unverified match drops below both
+        let merged = merge_weighted(&uniform_config(5), vec![(SourceKind::Document, closer)]);
+        assert_eq!(ids(&merged), vec!["old-notes", "expenses", "handbook"]);
+
+        // A chunk the user corrected by hand outranks an equally similar one
+        let mut corrected = with_trust(1, TrustLevel::Personal);
+        corrected.manually_corrected = true;
+        let merged = merge_weighted(
+            &uniform_config(5),
+            vec![(SourceKind::Document, vec![with_trust(0, TrustLevel::Personal), corrected])],
+        );
+        assert_eq!(ids(&merged), vec!["expenses", "handbook"]);
+    }
+
+    #[test]
+    fn test_rejects_negative_weights() {
+        assert!(SourceWeights { attachment: Some(2.0), ..Default::default() }.validate().is_ok());
+        assert!(SourceWeights { memory: Some(-1.0), ..Default::default() }.validate().is_err());
+        assert!(SourceWeights { document: Some(f32::NAN), ..Default::default() }.validate().is_err());
+    }
+}
@@ -0,0 +1,639 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+use crate::ai_service::AIService;
+use crate::http_client::{ClientKind, HttpClientConfig, HttpClientFactory};
+use crate::knowledge_service_simple;
+
+const CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+const DEFAULT_PLUGIN_DIRECTORY: &str = "./plugins";
+const DEFAULT_SMTP_PORT: u16 = 587;
+const WASM_HEADER: [u8; 8] = [0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+
+// Tables the stores create on startup, and the newest column of `messages`,
+// which tells whether the schema upgrades have run
+const EXPECTED_TABLES: [&str; 3] = ["sessions", "messages", "document_annotations"];
+const LATEST_MESSAGE_COLUMN: &str = "retrieval_count";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Pass,
+    // Works, but a feature will be degraded
+    Warn,
+    // Critical: the server should not start like this
+    Fail,
+    // Not configured
+    Skipped,
+}
+
+impl CheckStatus {
+    fn label(&self) -> &'static str {
+        match self {
+            CheckStatus::Pass => "PASS",
+            CheckStatus::Warn => "WARN",
+            CheckStatus::Fail => "FAIL",
+            CheckStatus::Skipped => "SKIP",
+        }
+    }
+
+    fn color(&self) -> &'static str {
+        match self {
+            CheckStatus::Pass => "\x1b[32m",
+            CheckStatus::Warn => "\x1b[33m",
+            CheckStatus::Fail => "\x1b[31m",
+            CheckStatus::Skipped => "\x1b[90m",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CheckResult {
+    pub name: String,
+    pub status: CheckStatus,
+    pub detail: String,
+}
+
+impl CheckResult {
+    fn new(name: impl Into<String>, status: CheckStatus, detail: impl Into<String>) -> Self {
+        Self { name: name.into(), status, detail: detail.into() }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SelfCheckReport {
+    pub checks: Vec<CheckResult>,
+    pub critical_failures: usize,
+}
+
+impl SelfCheckReport {
+    fn new(checks: Vec<CheckResult>) -> Self {
+        let critical_failures = checks.iter().filter(|c| c.status == CheckStatus::Fail).count();
+        Self { checks, critical_failures }
+    }
+
+    pub fn passed(&self) -> bool {
+        self.critical_failures == 0
+    }
+
+    pub fn status(&self, name: &str) -> Option<CheckStatus> {
+        self.checks.iter().find(|c| c.name == name).map(|c| c.status)
+    }
+
+    // Colored when printed to a terminal, unless NO_COLOR is set
+    pub fn print_table(&self) {
+        use std::io::IsTerminal;
+        let color = std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none();
+        print!("{}", self.render_table(color));
+    }
+
+    pub fn render_table(&self, color: bool) -> String {
+        let width = self.checks.iter().map(|c| c.name.len()).max().unwrap_or(0).max("CHECK".len());
+        let mut table = format!("{:<width$}  STATUS  DETAIL\n", "CHECK", width = width);
+        for check in &self.checks {
+            let label = if color {
+                format!("{}{:<6}\x1b[0m", check.status.color(), check.status.label())
+            } else {
+                format!("{:<6}", check.status.label())
+            };
+            table.push_str(&format!("{:<width$}  {}  {}\n", check.name, label, check.detail, width = width));
+        }
+        table.push_str(&format!(
+            "\n{} checks, {} critical failure(s)\n",
+            self.checks.len(),
+            self.critical_failures
+        ));
+        table
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SelfCheckConfig {
+    pub database_url: String,
+    // Qdrant collections and the vector size the configured embedding
+    // provider writes into them
+    pub collections: Vec<(String, u64)>,
+    pub openai_configured: bool,
+    pub elevenlabs_configured: bool,
+    pub plugin_directory: PathBuf,
+    // Set when briefing email is enabled
+    pub smtp: Option<(String, u16)>,
+    pub webhook_urls: Vec<String>,
+    // Proxy and extra CAs of the outbound clients
+    pub http: HttpClientConfig,
+}
+
+impl SelfCheckConfig {
+    // DATABASE_URL, OPENAI_API_KEY / CHAT_API_BASE, ELEVENLABS_API_KEY,
+    // PLUGIN_DIRECTORY, SMTP_HOST / SMTP_PORT, WEBHOOK_URLS and the OUTBOUND_*
+    // client settings, as the server reads them
+    pub fn from_env() -> Result<Self> {
+        let var = |key: &str| std::env::var(key).ok().filter(|v| !v.trim().is_empty());
+
+        Ok(Self {
+            database_url: var("DATABASE_URL").unwrap_or_else(|| "sqlite:./data/rusty_ai.db".to_string()),
+            collections: knowledge_service_simple::expected_collections()?,
+            openai_configured: var("OPENAI_API_KEY").is_some() || var("CHAT_API_BASE").is_some(),
+            elevenlabs_configured: var("ELEVENLABS_API_KEY").is_some(),
+            plugin_directory: PathBuf::from(var("PLUGIN_DIRECTORY").unwrap_or_else(|| DEFAULT_PLUGIN_DIRECTORY.to_string())),
+            smtp: var("SMTP_HOST").map(|host| {
+                let port = var("SMTP_PORT").and_then(|p| p.parse().ok()).unwrap_or(DEFAULT_SMTP_PORT);
+                (host, port)
+            }),
+            webhook_urls: var("WEBHOOK_URLS")
+                .map(|urls| urls.split(',').map(|u| u.trim().to_string()).filter(|u| !u.is_empty()).collect())
+                .unwrap_or_default(),
+            http: HttpClientConfig::from_env(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DatabaseSchema {
+    pub tables: Vec<String>,
+    pub message_columns: Vec<String>,
+}
+
+// The calls the checks make to external services; LiveProbe in production,
+// a mock in tests
+pub trait IntegrationProbe {
+    fn database(&self, database_url: &str) -> impl Future<Output = Result<DatabaseSchema>> + Send;
+    // Vector size of the collection, None when it does not exist yet
+    fn qdrant_dimension(&self, collection: &str) -> impl Future<Output = Result<Option<u64>>> + Send;
+    fn openai(&self) -> impl Future<Output = Result<()>> + Send;
+    fn elevenlabs(&self) -> impl Future<Output = Result<()>> + Send;
+    // The server's greeting
+    fn smtp(&self, host: &str, port: u16) -> impl Future<Output = Result<String>> + Send;
+    // HTTP status of the endpoint
+    fn webhook(&self, url: &str) -> impl Future<Output = Result<u16>> + Send;
+}
+
+// Run the checks against the live integrations configured in the environment
+pub async fn run_live() -> Result<SelfCheckReport> {
+    let config = SelfCheckConfig::from_env()?;
+    // A broken CA file or proxy URL fails the `outbound` check; the other
+    // integrations are still probed, without them
+    let probe = LiveProbe::new(HttpClientFactory::new(config.http.clone()).unwrap_or_default())?;
+    Ok(run_checks(&probe, &config).await)
+}
+
+pub async fn run_checks<P: IntegrationProbe>(probe: &P, config: &SelfCheckConfig) -> SelfCheckReport {
+    let (database, qdrant, openai, elevenlabs, smtp) = tokio::join!(
+        check_database(probe, &config.database_url),
+        check_qdrant(probe, &config.collections),
+        check_openai(probe, config.openai_configured),
+        check_elevenlabs(probe, config.elevenlabs_configured),
+        check_smtp(probe, config.smtp.as_ref()),
+    );
+
+    let mut checks = vec![database, check_outbound(&config.http)];
+    checks.extend(qdrant);
+    checks.push(openai);
+    checks.push(elevenlabs);
+    checks.extend(check_plugin_directory(&config.plugin_directory));
+    checks.push(smtp);
+    for url in &config.webhook_urls {
+        checks.push(check_webhook(probe, url).await);
+    }
+
+    SelfCheckReport::new(checks)
+}
+
+async fn with_timeout<T>(fut: impl Future<Output = Result<T>>) -> Result<T> {
+    tokio::time::timeout(CHECK_TIMEOUT, fut)
+        .await
+        .unwrap_or_else(|_| Err(anyhow::anyhow!("timed out after {:?}", CHECK_TIMEOUT)))
+}
+
+async fn check_database<P: IntegrationProbe>(probe: &P, database_url: &str) -> CheckResult {
+    let name = "database";
+    let schema = match with_timeout(probe.database(database_url)).await {
+        Ok(schema) => schema,
+        Err(e) => return CheckResult::new(name, CheckStatus::Fail, format!("cannot open {}: {}", database_url, e)),
+    };
+
+    let missing: Vec<&str> = EXPECTED_TABLES
+        .iter()
+        .copied()
+        .filter(|table| !schema.tables.iter().any(|t| t == table))
+        .collect();
+    if !missing.is_empty() {
+        return CheckResult::new(name, CheckStatus::Warn, format!("tables {} will be created on start", missing.join(", ")));
+    }
+    if !schema.message_columns.iter().any(|c| c == LATEST_MESSAGE_COLUMN) {
+        return CheckResult::new(name, CheckStatus::Warn, "schema is behind; it is upgraded on start");
+    }
+    CheckResult::new(name, CheckStatus::Pass, "connected, schema up to date")
+}
+
+async fn check_qdrant<P: IntegrationProbe>(probe: &P, collections: &[(String, u64)]) -> Vec<CheckResult> {
+    let mut results = Vec::with_capacity(collections.len());
+    for (collection, expected) in collections {
+        let name = format!("qdrant:{}", collection);
+        let result = match with_timeout(probe.qdrant_dimension(collection)).await {
+            // The server starts without knowledge features in this case
+            Err(e) => CheckResult::new(name, CheckStatus::Warn, format!("unavailable, knowledge features disabled: {}", e)),
+            Ok(None) => CheckResult::new(name, CheckStatus::Pass, format!("missing; created with dimension {} on start", expected)),
+            Ok(Some(dimension)) if dimension == *expected => {
+                CheckResult::new(name, CheckStatus::Pass, format!("dimension {}", dimension))
+            }
+            Ok(Some(dimension)) => CheckResult::new(
+                name,
+                CheckStatus::Fail,
+                format!("dimension {} does not match the embedding provider's {}", dimension, expected),
+            ),
+        };
+        results.push(result);
+    }
+    results
+}
+
+// The server does not start when a CA file cannot be loaded or the proxy URL
+// is invalid
+fn check_outbound(config: &HttpClientConfig) -> CheckResult {
+    let name = "outbound";
+    if let Err(e) = HttpClientFactory::new(config.clone()) {
+        return CheckResult::new(name, CheckStatus::Fail, format!("{:#}", e));
+    }
+    // The proxy URL may carry credentials
+    let route = if config.proxy_url.is_some() { "via proxy" } else { "direct" };
+    CheckResult::new(name, CheckStatus::Pass, format!("{}, {} extra CA file(s)", route, config.ca_cert_paths.len()))
+}
+
+async fn check_openai<P: IntegrationProbe>(probe: &P, configured: bool) -> CheckResult {
+    let name = "openai";
+    if !configured {
+        return CheckResult::new(name, CheckStatus::Fail, "OPENAI_API_KEY is not set");
+    }
+    match with_timeout(probe.openai()).await {
+        Ok(()) => CheckResult::new(name, CheckStatus::Pass, "credentials accepted"),
+        Err(e) => CheckResult::new(name, CheckStatus::Fail, e.to_string()),
+    }
+}
+
+async fn check_elevenlabs<P: IntegrationProbe>(probe: &P, configured: bool) -> CheckResult {
+    let name = "elevenlabs";
+    if !configured {
+        return CheckResult::new(name, CheckStatus::Skipped, "ELEVENLABS_API_KEY is not set");
+    }
+    match with_timeout(probe.elevenlabs()).await {
+        Ok(()) => CheckResult::new(name, CheckStatus::Pass, "credentials accepted"),
+        // Speech falls back to OpenAI voices
+        Err(e) => CheckResult::new(name, CheckStatus::Warn, e.to_string()),
+    }
+}
+
+async fn check_smtp<P: IntegrationProbe>(probe: &P, smtp: Option<&(String, u16)>) -> CheckResult {
+    let name = "smtp";
+    let Some((host, port)) = smtp else {
+        return CheckResult::new(name, CheckStatus::Skipped, "briefing email is not enabled");
+    };
+    match with_timeout(probe.smtp(host, *port)).await {
+        Ok(greeting) => CheckResult::new(name, CheckStatus::Pass, greeting),
+        Err(e) => CheckResult::new(name, CheckStatus::Fail, format!("{}:{}: {}", host, port, e)),
+    }
+}
+
+async fn check_webhook<P: IntegrationProbe>(probe: &P, url: &str) -> CheckResult {
+    let name = format!("webhook:{}", url);
+    match with_timeout(probe.webhook(url)).await {
+        Ok(status) if status < 500 => CheckResult::new(name, CheckStatus::Pass, format!("HTTP {}", status)),
+        Ok(status) => CheckResult::new(name, CheckStatus::Warn, format!("HTTP {}", status)),
+        Err(e) => CheckResult::new(name, CheckStatus::Warn, format!("unreachable: {}", e)),
+    }
+}
+
+// The directory must be readable, and every module in it a WebAssembly binary
+fn check_plugin_directory(dir: &Path) -> Vec<CheckResult> {
+    let name = "plugins";
+    if !dir.exists() {
+        return vec![CheckResult::new(name, CheckStatus::Warn, format!("{} does not exist", dir.display()))];
+    }
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => return vec![CheckResult::new(name, CheckStatus::Fail, format!("cannot read {}: {}", dir.display(), e))],
+    };
+
+    let mut modules: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().map_or(false, |ext| ext == "wasm"))
+        .collect();
+    modules.sort();
+
+    let read_only = std::fs::metadata(dir).map(|m| m.permissions().readonly()).unwrap_or(false);
+    let mut results = vec![if read_only {
+        CheckResult::new(name, CheckStatus::Warn, format!("{} is read-only; installs will fail", dir.display()))
+    } else {
+        CheckResult::new(name, CheckStatus::Pass, format!("{} plugin(s) in {}", modules.len(), dir.display()))
+    }];
+
+    for module in modules {
+        let file_name = module.file_name().unwrap_or_default().to_string_lossy().to_string();
+        let result = match read_header(&module) {
+            Ok(header) if header == WASM_HEADER => continue,
+            Ok(_) => CheckResult::new(format!("plugin:{}", file_name), CheckStatus::Warn, "not a WebAssembly module"),
+            Err(e) => CheckResult::new(format!("plugin:{}", file_name), CheckStatus::Warn, format!("unreadable: {}", e)),
+        };
+        results.push(result);
+    }
+    results
+}
+
+fn read_header(path: &Path) -> std::io::Result<[u8; 8]> {
+    use std::io::Read;
+    let mut header = [0u8; 8];
+    std::fs::File::open(path)?.read_exact(&mut header)?;
+    Ok(header)
+}
+
+pub struct LiveProbe {
+    http: HttpClientFactory,
+    elevenlabs: reqwest::Client,
+    webhooks: reqwest::Client,
+    ai_service: AIService,
+    elevenlabs_api_key: Option<String>,
+}
+
+impl LiveProbe {
+    // Probes through the same clients the server uses
+    pub fn new(http: HttpClientFactory) -> Result<Self> {
+        Ok(Self {
+            elevenlabs: http.client(ClientKind::ElevenLabs)?,
+            webhooks: http.client(ClientKind::Webhook)?,
+            ai_service: AIService::new(None)?.with_http(&http)?,
+            elevenlabs_api_key: std::env::var("ELEVENLABS_API_KEY").ok(),
+            http,
+        })
+    }
+}
+
+impl IntegrationProbe for LiveProbe {
+    async fn database(&self, database_url: &str) -> Result<DatabaseSchema> {
+        use sqlx::Row;
+        use std::str::FromStr;
+
+        // Never creates the file; a missing database is created by the server
+        let options = sqlx::sqlite::SqliteConnectOptions::from_str(database_url)?.read_only(true);
+        let pool = match sqlx::SqlitePool::connect_with(options).await {
+            Ok(pool) => pool,
+            Err(_) if !database_exists(database_url) => return Ok(DatabaseSchema::default()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let tables = sqlx::query("SELECT name FROM sqlite_master WHERE type = 'table'")
+            .fetch_all(&pool)
+            .await?
+            .iter()
+            .map(|row| row.get::<String, _>("name"))
+            .collect();
+        let message_columns = sqlx::query("SELECT name FROM pragma_table_info('messages')")
+            .fetch_all(&pool)
+            .await?
+            .iter()
+            .map(|row| row.get::<String, _>("name"))
+            .collect();
+        pool.close().await;
+
+        Ok(DatabaseSchema { tables, message_columns })
+    }
+
+    async fn qdrant_dimension(&self, collection: &str) -> Result<Option<u64>> {
+        knowledge_service_simple::collection_dimension(collection, &self.http).await
+    }
+
+    async fn openai(&self) -> Result<()> {
+        self.ai_service.check_credentials().await
+    }
+
+    async fn elevenlabs(&self) -> Result<()> {
+        let api_key = self.elevenlabs_api_key.as_deref().context("ELEVENLABS_API_KEY is not set")?;
+        let response = self
+            .elevenlabs
+            .get("https://api.elevenlabs.io/v1/user")
+            .header("xi-api-key", api_key)
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            anyhow::bail!("rejected with HTTP {}", response.status());
+        }
+        Ok(())
+    }
+
+    // Checks that the server greets and answers EHLO; logging in needs TLS
+    // and is left to the first delivery
+    async fn smtp(&self, host: &str, port: u16) -> Result<String> {
+        let stream = tokio::net::TcpStream::connect((host, port)).await?;
+        let (reader, mut writer) = stream.into_split();
+        let mut reader = BufReader::new(reader);
+
+        let greeting = read_smtp_reply(&mut reader).await?;
+        if !greeting.starts_with("220") {
+            anyhow::bail!("unexpected greeting: {}", greeting);
+        }
+        writer.write_all(b"EHLO rusty-ai\r\n").await?;
+        let ehlo = read_smtp_reply(&mut reader).await?;
+        if !ehlo.starts_with("250") {
+            anyhow::bail!("EHLO refused: {}", ehlo);
+        }
+        let _ = writer.write_all(b"QUIT\r\n").await;
+
+        Ok(greeting)
+    }
+
+    async fn webhook(&self, url: &str) -> Result<u16> {
+        Ok(self.webhooks.head(url).send().await?.status().as_u16())
+    }
+}
+
+// The last line of a possibly multi-line reply ("250-..." continues, "250 ..." ends)
+async fn read_smtp_reply<R: tokio::io::AsyncBufRead + Unpin>(reader: &mut R) -> Result<String> {
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await? == 0 {
+            anyhow::bail!("connection closed");
+        }
+        if line.as_bytes().get(3) != Some(&b'-') {
+            return Ok(line.trim_end().to_string());
+        }
+    }
+}
+
+fn database_exists(database_url: &str) -> bool {
+    let path = database_url.trim_start_matches("sqlite://").trim_start_matches("sqlite:");
+    let path = path.split('?').next().unwrap_or(path);
+    path == ":memory:" || Path::new(path).exists()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct MockProbe {
+        database: Option<DatabaseSchema>,
+        qdrant: Option<Option<u64>>,
+        openai_ok: bool,
+        elevenlabs_ok: bool,
+        webhook_status: Option<u16>,
+    }
+
+    impl MockProbe {
+        fn healthy() -> Self {
+            Self {
+                database: Some(DatabaseSchema {
+                    tables: EXPECTED_TABLES.iter().map(|t| t.to_string()).collect(),
+                    message_columns: vec!["id".to_string(), LATEST_MESSAGE_COLUMN.to_string()],
+                }),
+                qdrant: Some(Some(1536)),
+                openai_ok: true,
+                elevenlabs_ok: true,
+                webhook_status: Some(200),
+            }
+        }
+    }
+
+    impl IntegrationProbe for MockProbe {
+        async fn database(&self, _database_url: &str) -> Result<DatabaseSchema> {
+            self.database.clone().context("unable to open database file")
+        }
+
+        async fn qdrant_dimension(&self, _collection: &str) -> Result<Option<u64>> {
+            self.qdrant.context("connection refused")
+        }
+
+        async fn openai(&self) -> Result<()> {
+            anyhow::ensure!(self.openai_ok, "invalid api key");
+            Ok(())
+        }
+
+        async fn elevenlabs(&self) -> Result<()> {
+            anyhow::ensure!(self.elevenlabs_ok, "rejected with HTTP 401");
+            Ok(())
+        }
+
+        async fn smtp(&self, host: &str, _port: u16) -> Result<String> {
+            Ok(format!("220 {} ESMTP", host))
+        }
+
+        async fn webhook(&self, _url: &str) -> Result<u16> {
+            self.webhook_status.context("connection refused")
+        }
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rusty-ai-self-check-{}-{}", name, uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn test_config(plugin_directory: PathBuf) -> SelfCheckConfig {
+        SelfCheckConfig {
+            database_url: "sqlite::memory:".to_string(),
+            collections: vec![("personal_knowledge".to_string(), 1536)],
+            openai_configured: true,
+            elevenlabs_configured: true,
+            plugin_directory,
+            smtp: Some(("mail.example.com".to_string(), 587)),
+            webhook_urls: vec!["https://hooks.example.com/ai".to_string()],
+            http: HttpClientConfig::default(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_all_checks_pass() {
+        let dir = temp_dir("pass");
+        std::fs::write(dir.join("echo.wasm"), WASM_HEADER).unwrap();
+
+        let report = run_checks(&MockProbe::healthy(), &test_config(dir)).await;
+
+        assert!(report.passed(), "{}", report.render_table(false));
+        assert!(report.checks.iter().all(|c| c.status == CheckStatus::Pass));
+        assert_eq!(report.status("smtp"), Some(CheckStatus::Pass));
+    }
+
+    #[tokio::test]
+    async fn test_degraded_integrations_warn() {
+        let dir = temp_dir("warn");
+        std::fs::write(dir.join("broken.wasm"), b"not wasm").unwrap();
+        let probe = MockProbe {
+            database: Some(DatabaseSchema { tables: vec!["sessions".to_string()], message_columns: vec![] }),
+            qdrant: None,
+            elevenlabs_ok: false,
+            webhook_status: Some(503),
+            ..MockProbe::healthy()
+        };
+        let mut config = test_config(dir);
+        config.smtp = None;
+
+        let report = run_checks(&probe, &config).await;
+
+        // The server still starts in every one of these cases
+        assert!(report.passed(), "{}", report.render_table(false));
+        assert_eq!(report.status("database"), Some(CheckStatus::Warn));
+        assert_eq!(report.status("qdrant:personal_knowledge"), Some(CheckStatus::Warn));
+        assert_eq!(report.status("elevenlabs"), Some(CheckStatus::Warn));
+        assert_eq!(report.status("plugin:broken.wasm"), Some(CheckStatus::Warn));
+        assert_eq!(report.status("webhook:https://hooks.example.com/ai"), Some(CheckStatus::Warn));
+        assert_eq!(report.status("smtp"), Some(CheckStatus::Skipped));
+    }
+
+    #[tokio::test]
+    async fn test_critical_failures_fail_the_report() {
+        let probe = MockProbe {
+            database: None,
+            qdrant: Some(Some(768)),
+            openai_ok: false,
+            ..MockProbe::healthy()
+        };
+
+        let report = run_checks(&probe, &test_config(temp_dir("fail"))).await;
+
+        assert!(!report.passed());
+        assert_eq!(report.critical_failures, 3);
+        assert_eq!(report.status("database"), Some(CheckStatus::Fail));
+        assert_eq!(report.status("qdrant:personal_knowledge"), Some(CheckStatus::Fail));
+        assert_eq!(report.status("openai"), Some(CheckStatus::Fail));
+        assert_eq!(report.status("outbound"), Some(CheckStatus::Pass));
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["critical_failures"], 3);
+        assert_eq!(json["checks"][0]["status"], "fail");
+        assert!(report.render_table(false).contains("FAIL"));
+        assert!(report.render_table(true).contains("\x1b[31m"));
+    }
+
+    #[tokio::test]
+    async fn test_missing_credentials_are_classified_without_calls() {
+        let mut config = test_config(temp_dir("unconfigured"));
+        config.openai_configured = false;
+        config.elevenlabs_configured = false;
+
+        let report = run_checks(&MockProbe::healthy(), &config).await;
+
+        assert_eq!(report.status("openai"), Some(CheckStatus::Fail));
+        assert_eq!(report.status("elevenlabs"), Some(CheckStatus::Skipped));
+    }
+
+    #[tokio::test]
+    async fn test_unreadable_ca_files_fail_the_report() {
+        let dir = temp_dir("outbound");
+        let mut config = test_config(dir.clone());
+        config.http.ca_cert_paths = vec![dir.join("corporate-ca.pem")];
+
+        let report = run_checks(&MockProbe::healthy(), &config).await;
+
+        assert!(!report.passed());
+        assert_eq!(report.status("outbound"), Some(CheckStatus::Fail));
+        assert!(report.render_table(false).contains("corporate-ca.pem"));
+    }
+
+    #[tokio::test]
+    async fn test_reads_multi_line_smtp_replies() {
+        let mut reply = "250-mail.example.com\r\n250-SIZE 1000\r\n250 STARTTLS\r\n".as_bytes();
+        assert_eq!(read_smtp_reply(&mut reply).await.unwrap(), "250 STARTTLS");
+    }
+}
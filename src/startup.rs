@@ -0,0 +1,375 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use tracing::{error, info, warn};
+
+const DEFAULT_STATE_FILE: &str = "./data/startup_state.json";
+const DEFAULT_FAILURE_THRESHOLD: u32 = 3;
+
+// Components that can be skipped at startup
+pub const COMPONENTS: [&str; 3] = ["knowledge", "memory", "voice"];
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+#[serde(tag = "status", content = "reason", rename_all = "snake_case")]
+pub enum ComponentStatus {
+    Enabled,
+    // Turned off with --disable / RUSTY_AI_DISABLE
+    Disabled(String),
+    // Turned off automatically after repeated initialization failures
+    SafeMode(String),
+    // Initialization failed this run
+    Failed(String),
+}
+
+impl ComponentStatus {
+    pub fn is_enabled(&self) -> bool {
+        matches!(self, ComponentStatus::Enabled)
+    }
+}
+
+// Consecutive initialization failures, persisted across restarts
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct StartupState {
+    consecutive_failures: BTreeMap<String, u32>,
+    // Components whose initialization was running; one still listed at the
+    // next start crashed or hung the process
+    #[serde(default)]
+    attempting: BTreeSet<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct StartupOptions {
+    pub disabled: HashSet<String>,
+    pub state_file: PathBuf,
+    pub failure_threshold: u32,
+}
+
+impl Default for StartupOptions {
+    fn default() -> Self {
+        Self {
+            disabled: HashSet::new(),
+            state_file: PathBuf::from(DEFAULT_STATE_FILE),
+            failure_threshold: DEFAULT_FAILURE_THRESHOLD,
+        }
+    }
+}
+
+impl StartupOptions {
+    // Reads `--disable a,b` / `--disable=a,b` from the command line and
+    // RUSTY_AI_DISABLE, RUSTY_AI_STARTUP_STATE and RUSTY_AI_SAFE_MODE_THRESHOLD
+    // from the environment.
+    pub fn from_env_and_args(args: impl IntoIterator<Item = String>) -> Self {
+        let mut options = Self::default();
+
+        if let Ok(list) = std::env::var("RUSTY_AI_DISABLE") {
+            options.disabled.extend(parse_component_list(&list));
+        }
+        if let Ok(path) = std::env::var("RUSTY_AI_STARTUP_STATE") {
+            options.state_file = PathBuf::from(path);
+        }
+        if let Some(threshold) = std::env::var("RUSTY_AI_SAFE_MODE_THRESHOLD")
+            .ok()
+            .and_then(|v| v.parse().ok())
+        {
+            options.failure_threshold = threshold;
+        }
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            if arg == "--disable" {
+                if let Some(list) = args.next() {
+                    options.disabled.extend(parse_component_list(&list));
+                }
+            } else if let Some(list) = arg.strip_prefix("--disable=") {
+                options.disabled.extend(parse_component_list(list));
+            }
+        }
+
+        for name in &options.disabled {
+            if !COMPONENTS.contains(&name.as_str()) {
+                warn!("Ignoring unknown component in --disable: {}", name);
+            }
+        }
+
+        options
+    }
+}
+
+fn parse_component_list(list: &str) -> Vec<String> {
+    list.split(',')
+        .map(|s| s.trim().to_lowercase())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+// Tracks which components came up, and why the others did not
+pub struct ComponentRegistry {
+    options: StartupOptions,
+    state: RwLock<StartupState>,
+    statuses: RwLock<BTreeMap<String, ComponentStatus>>,
+}
+
+impl ComponentRegistry {
+    pub fn new(options: StartupOptions) -> Self {
+        let mut state = load_state(&options.state_file);
+        let interrupted = std::mem::take(&mut state.attempting);
+        for component in &interrupted {
+            warn!("Component '{}' did not finish initializing last time; counting it as a failure", component);
+            *state.consecutive_failures.entry(component.clone()).or_insert(0) += 1;
+        }
+        let registry = Self {
+            options,
+            state: RwLock::new(state),
+            statuses: RwLock::new(BTreeMap::new()),
+        };
+        if !interrupted.is_empty() {
+            if let Err(e) = registry.persist() {
+                warn!("Failed to persist startup state: {}", e);
+            }
+        }
+        registry
+    }
+
+    // Initialize a component unless it is disabled or in safe mode. Failures
+    // are logged and counted instead of aborting startup.
+    pub async fn initialize<T, F>(&self, component: &str, init: F) -> Option<T>
+    where
+        F: Future<Output = Result<T>>,
+    {
+        if self.options.disabled.contains(component) {
+            info!("Component '{}' disabled by startup flags", component);
+            self.set_status(component, ComponentStatus::Disabled("disabled by startup flags".to_string()));
+            return None;
+        }
+
+        let failures = self.consecutive_failures(component);
+        if failures >= self.options.failure_threshold {
+            let reason = format!(
+                "initialization failed {} consecutive times; re-enable via POST /api/v1/admin/components/{}/enable",
+                failures, component
+            );
+            warn!("==================================================================");
+            warn!("SAFE MODE: starting without '{}' ({})", component, reason);
+            warn!("==================================================================");
+            self.set_status(component, ComponentStatus::SafeMode(reason));
+            return None;
+        }
+
+        // A crash inside `init` never reaches `record_outcome`, so the
+        // attempt is on disk before it starts
+        self.state.write().unwrap().attempting.insert(component.to_string());
+        if let Err(e) = self.persist() {
+            warn!("Failed to persist startup state: {}", e);
+        }
+
+        match init.await {
+            Ok(value) => {
+                self.record_outcome(component, true);
+                self.set_status(component, ComponentStatus::Enabled);
+                Some(value)
+            }
+            Err(e) => {
+                error!("Failed to initialize component '{}': {}", component, e);
+                self.record_outcome(component, false);
+                self.set_status(component, ComponentStatus::Failed(e.to_string()));
+                None
+            }
+        }
+    }
+
+    // Mark a component as unavailable because something it depends on is missing
+    pub fn mark_unavailable(&self, component: &str, reason: &str) {
+        self.set_status(component, ComponentStatus::Disabled(reason.to_string()));
+    }
+
+    // Clear the failure count so the component is attempted on the next start
+    pub fn reset(&self, component: &str) -> Result<()> {
+        {
+            let mut state = self.state.write().unwrap();
+            state.consecutive_failures.remove(component);
+        }
+        self.persist()
+    }
+
+    pub fn status(&self, component: &str) -> Option<ComponentStatus> {
+        self.statuses.read().unwrap().get(component).cloned()
+    }
+
+    pub fn statuses(&self) -> BTreeMap<String, ComponentStatus> {
+        self.statuses.read().unwrap().clone()
+    }
+
+    pub fn disabled_components(&self) -> Vec<String> {
+        self.statuses
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|(_, status)| !status.is_enabled())
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    fn consecutive_failures(&self, component: &str) -> u32 {
+        self.state
+            .read()
+            .unwrap()
+            .consecutive_failures
+            .get(component)
+            .copied()
+            .unwrap_or(0)
+    }
+
+    fn record_outcome(&self, component: &str, success: bool) {
+        {
+            let mut state = self.state.write().unwrap();
+            state.attempting.remove(component);
+            if success {
+                state.consecutive_failures.remove(component);
+            } else {
+                *state.consecutive_failures.entry(component.to_string()).or_insert(0) += 1;
+            }
+        }
+
+        if let Err(e) = self.persist() {
+            warn!("Failed to persist startup state: {}", e);
+        }
+    }
+
+    fn set_status(&self, component: &str, status: ComponentStatus) {
+        self.statuses.write().unwrap().insert(component.to_string(), status);
+    }
+
+    fn persist(&self) -> Result<()> {
+        let state = self.state.read().unwrap().clone();
+        if let Some(parent) = self.options.state_file.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&self.options.state_file, serde_json::to_vec_pretty(&state)?)?;
+        Ok(())
+    }
+}
+
+fn load_state(path: &Path) -> StartupState {
+    match std::fs::read(path) {
+        Ok(bytes) => serde_json::from_slice(&bytes).unwrap_or_else(|e| {
+            warn!("Ignoring unreadable startup state file {:?}: {}", path, e);
+            StartupState::default()
+        }),
+        Err(_) => StartupState::default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    fn test_options(dir: &Path) -> StartupOptions {
+        StartupOptions {
+            disabled: HashSet::new(),
+            state_file: dir.join("startup_state.json"),
+            failure_threshold: 2,
+        }
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rusty-ai-startup-{}-{}", name, uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_parse_disable_flag() {
+        let args = vec!["rusty-ai-api".to_string(), "--disable".to_string(), "voice, Knowledge".to_string()];
+        let options = StartupOptions::from_env_and_args(args);
+
+        assert!(options.disabled.contains("voice"));
+        assert!(options.disabled.contains("knowledge"));
+    }
+
+    #[tokio::test]
+    async fn test_failing_knowledge_init_does_not_abort_startup() {
+        let dir = temp_dir("failing");
+        let registry = ComponentRegistry::new(test_options(&dir));
+
+        let knowledge: Option<()> = registry
+            .initialize("knowledge", async { Err(anyhow::anyhow!("qdrant unreachable")) })
+            .await;
+        let chat = registry.initialize("chat", async { Ok("ready") }).await;
+
+        assert!(knowledge.is_none());
+        assert_eq!(chat, Some("ready"));
+        assert!(matches!(registry.status("knowledge"), Some(ComponentStatus::Failed(_))));
+        assert_eq!(registry.disabled_components(), vec!["knowledge".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_safe_mode_after_consecutive_failures() {
+        let dir = temp_dir("safe-mode");
+
+        for _ in 0..2 {
+            let registry = ComponentRegistry::new(test_options(&dir));
+            let _: Option<()> = registry
+                .initialize("knowledge", async { Err(anyhow::anyhow!("boom")) })
+                .await;
+        }
+
+        // Third start: the init future must not even run
+        let registry = ComponentRegistry::new(test_options(&dir));
+        let attempted = AtomicBool::new(false);
+        let result: Option<()> = registry
+            .initialize("knowledge", async {
+                attempted.store(true, Ordering::SeqCst);
+                Ok(())
+            })
+            .await;
+        assert!(result.is_none());
+        assert!(!attempted.load(Ordering::SeqCst));
+        assert!(matches!(registry.status("knowledge"), Some(ComponentStatus::SafeMode(_))));
+
+        registry.reset("knowledge").unwrap();
+        let registry = ComponentRegistry::new(test_options(&dir));
+        let result = registry.initialize("knowledge", async { Ok(()) }).await;
+        assert!(result.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_crash_during_init_counts_as_a_failure() {
+        let dir = temp_dir("crash");
+
+        // Each start dies inside init, leaving only the attempt marker behind
+        for _ in 0..2 {
+            let registry = ComponentRegistry::new(test_options(&dir));
+            let init = registry.initialize("knowledge", std::future::pending::<Result<()>>());
+            assert!(tokio::time::timeout(std::time::Duration::from_millis(10), init).await.is_err());
+        }
+
+        let registry = ComponentRegistry::new(test_options(&dir));
+        let result = registry.initialize("knowledge", async { Ok(()) }).await;
+        assert!(result.is_none());
+        assert!(matches!(registry.status("knowledge"), Some(ComponentStatus::SafeMode(_))));
+    }
+
+    #[tokio::test]
+    async fn test_disabled_component_is_skipped() {
+        let dir = temp_dir("disabled");
+        let mut options = test_options(&dir);
+        options.disabled.insert("voice".to_string());
+        let registry = ComponentRegistry::new(options);
+
+        let attempted = AtomicBool::new(false);
+        let result: Option<()> = registry
+            .initialize("voice", async {
+                attempted.store(true, Ordering::SeqCst);
+                Ok(())
+            })
+            .await;
+
+        assert!(result.is_none());
+        assert!(!attempted.load(Ordering::SeqCst));
+        assert!(matches!(registry.status("voice"), Some(ComponentStatus::Disabled(_))));
+    }
+}
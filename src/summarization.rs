@@ -0,0 +1,544 @@
+// Summaries and explanations of one stored document. The chunks are read in
+// order and summarized map-reduce style: consecutive chunks are grouped to
+// fit the model's context, each group is summarized, and the partial
+// summaries are merged into the final one. Results are cached per document
+// checksum and options. Served at /api/v1/knowledge/documents/:id/summarize
+// and used for chat requests like "summarize the lease agreement".
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+use crate::ai_service::{AIService, ChatReply};
+use crate::knowledge_service_simple::Document;
+use crate::query_metrics::TokenUsage;
+
+// Usage of summary calls is recorded under this feature
+pub const SUMMARIZATION_FEATURE: &str = "summarization";
+
+const DEFAULT_MAP_INPUT_TOKENS: usize = 6000;
+const DEFAULT_CACHE_CAPACITY: usize = 256;
+// Characters of a cited section shown with the summary
+const EXCERPT_CHARS: usize = 160;
+// Share of query words a title must contain to match
+const TITLE_MATCH_THRESHOLD: f32 = 0.5;
+
+// Words that ask for a summary or an explanation, and the options they imply
+const SUMMARY_VERBS: [(&str, SummaryLength, SummaryAudience); 6] = [
+    ("summarize", SummaryLength::Brief, SummaryAudience::Technical),
+    ("summarise", SummaryLength::Brief, SummaryAudience::Technical),
+    ("summary of", SummaryLength::Brief, SummaryAudience::Technical),
+    ("tl dr", SummaryLength::Brief, SummaryAudience::Simple),
+    ("explain", SummaryLength::Detailed, SummaryAudience::Simple),
+    ("walk me through", SummaryLength::Detailed, SummaryAudience::Simple),
+];
+
+// Not part of a document's title
+const FILLER_WORDS: [&str; 16] = [
+    "the", "a", "an", "my", "our", "this", "that", "document", "doc", "file", "please", "for", "in", "detail",
+    "detailed", "briefly",
+];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SummaryLength {
+    #[default]
+    Brief,
+    Detailed,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SummaryAudience {
+    #[default]
+    Simple,
+    Technical,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SummaryOptions {
+    #[serde(default)]
+    pub length: SummaryLength,
+    #[serde(default)]
+    pub audience: SummaryAudience,
+    // What the summary should concentrate on
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub focus: Option<String>,
+}
+
+impl SummaryOptions {
+    fn cache_key(&self, document_id: &str, checksum: &str) -> String {
+        let focus = self.focus.as_deref().map(|f| f.trim().to_lowercase()).unwrap_or_default();
+        format!("{}:{}:{:?}:{:?}:{}", document_id, checksum, self.length, self.audience, focus)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Citation {
+    pub chunk_index: usize,
+    pub excerpt: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Summary {
+    pub document_id: String,
+    pub title: String,
+    pub summary: String,
+    #[serde(flatten)]
+    pub options: SummaryOptions,
+    // Of the chunk contents the summary was made from
+    pub checksum: String,
+    // The sections the summary cites, in document order
+    pub citations: Vec<Citation>,
+    pub model_calls: usize,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub cached: bool,
+    pub generated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SummaryConfig {
+    // Estimated tokens of document text sent in one call
+    pub map_input_tokens: usize,
+    pub cache_capacity: usize,
+}
+
+impl Default for SummaryConfig {
+    fn default() -> Self {
+        Self { map_input_tokens: DEFAULT_MAP_INPUT_TOKENS, cache_capacity: DEFAULT_CACHE_CAPACITY }
+    }
+}
+
+impl SummaryConfig {
+    // SUMMARY_MAP_INPUT_TOKENS and SUMMARY_CACHE_CAPACITY
+    pub fn from_env() -> Self {
+        let var = |key: &str| std::env::var(key).ok().and_then(|v| v.parse().ok());
+        Self {
+            map_input_tokens: var("SUMMARY_MAP_INPUT_TOKENS").unwrap_or(DEFAULT_MAP_INPUT_TOKENS),
+            cache_capacity: var("SUMMARY_CACHE_CAPACITY").unwrap_or(DEFAULT_CACHE_CAPACITY),
+        }
+    }
+}
+
+// The model calls a summary makes; the chat service in production, a mock
+// in tests
+pub trait SummaryModel {
+    fn complete(&self, system_prompt: &str, prompt: &str) -> impl Future<Output = Result<ChatReply>> + Send;
+}
+
+impl SummaryModel for AIService {
+    async fn complete(&self, system_prompt: &str, prompt: &str) -> Result<ChatReply> {
+        AIService::complete(self, system_prompt, prompt).await
+    }
+}
+
+#[derive(Default)]
+struct SummaryCache {
+    entries: HashMap<String, Summary>,
+    // Keys oldest first, for eviction
+    order: VecDeque<String>,
+}
+
+pub struct Summarizer {
+    config: SummaryConfig,
+    usage: Arc<TokenUsage>,
+    cache: Mutex<SummaryCache>,
+}
+
+impl Summarizer {
+    pub fn new(config: SummaryConfig, usage: Arc<TokenUsage>) -> Self {
+        Self { config, usage, cache: Mutex::new(SummaryCache::default()) }
+    }
+
+    // Summarize the chunks of one document, given in order
+    pub async fn summarize<M: SummaryModel>(
+        &self,
+        model: &M,
+        chunks: &[Document],
+        options: &SummaryOptions,
+    ) -> Result<Summary> {
+        let first = chunks.first().ok_or_else(|| anyhow::anyhow!("The document has no content"))?;
+        let checksum = checksum(chunks);
+        let key = options.cache_key(&first.id, &checksum);
+        if let Some(cached) = self.cache.lock().unwrap().entries.get(&key) {
+            return Ok(Summary { cached: true, ..cached.clone() });
+        }
+
+        let mut run = Run { model, usage: &self.usage, calls: 0, prompt_tokens: 0, completion_tokens: 0 };
+        let batches = batch_by_tokens(chunks.iter().map(section_text).collect(), self.config.map_input_tokens);
+        let text = if batches.len() == 1 {
+            run.call(&final_instructions(&first.title, options), &batches[0]).await?
+        } else {
+            // Map: one partial summary per group of sections
+            let mut partials = Vec::with_capacity(batches.len());
+            for batch in &batches {
+                partials.push(run.call(&map_instructions(&first.title, options), batch).await?);
+            }
+            // Reduce: merge partials until they fit one call, then write the
+            // final summary from them
+            let mut groups = batch_by_tokens(partials, self.config.map_input_tokens);
+            while groups.len() > 1 {
+                let mut merged = Vec::with_capacity(groups.len());
+                for group in &groups {
+                    merged.push(run.call(&map_instructions(&first.title, options), group).await?);
+                }
+                groups = batch_by_tokens(merged, self.config.map_input_tokens);
+            }
+            run.call(&final_instructions(&first.title, options), &groups[0]).await?
+        };
+
+        let summary = Summary {
+            document_id: first.id.clone(),
+            title: first.title.clone(),
+            citations: citations(&text, chunks),
+            summary: text,
+            options: options.clone(),
+            checksum,
+            model_calls: run.calls,
+            prompt_tokens: run.prompt_tokens,
+            completion_tokens: run.completion_tokens,
+            cached: false,
+            generated_at: Utc::now(),
+        };
+        self.remember(key, summary.clone());
+        Ok(summary)
+    }
+
+    fn remember(&self, key: String, summary: Summary) {
+        let mut cache = self.cache.lock().unwrap();
+        if cache.entries.insert(key.clone(), summary).is_none() {
+            cache.order.push_back(key);
+        }
+        while cache.order.len() > self.config.cache_capacity {
+            if let Some(oldest) = cache.order.pop_front() {
+                cache.entries.remove(&oldest);
+            }
+        }
+    }
+}
+
+// The calls of one summary and what they used
+struct Run<'a, M> {
+    model: &'a M,
+    usage: &'a TokenUsage,
+    calls: usize,
+    prompt_tokens: u64,
+    completion_tokens: u64,
+}
+
+impl<M: SummaryModel> Run<'_, M> {
+    async fn call(&mut self, instructions: &str, text: &str) -> Result<String> {
+        let reply = self.model.complete(instructions, text).await?;
+        self.usage.record(SUMMARIZATION_FEATURE, reply.prompt_tokens, reply.completion_tokens);
+        self.calls += 1;
+        self.prompt_tokens += u64::from(reply.prompt_tokens.unwrap_or(0));
+        self.completion_tokens += u64::from(reply.completion_tokens.unwrap_or(0));
+        Ok(reply.text)
+    }
+}
+
+// Rough token count; about four characters per token for English text
+pub(crate) fn estimate_tokens(text: &str) -> usize {
+    text.chars().count() / 4 + 1
+}
+
+// Sections are numbered from 1 in the prompt so the model can cite them
+fn section_text(chunk: &Document) -> String {
+    format!("[{}] {}", chunk.chunk_index + 1, chunk.content.trim())
+}
+
+// Join consecutive texts into groups of at most `limit` estimated tokens; a
+// single text over the limit is a group of its own
+pub(crate) fn batch_by_tokens(texts: Vec<String>, limit: usize) -> Vec<String> {
+    let mut batches = Vec::new();
+    let mut current = String::new();
+    for text in texts {
+        if !current.is_empty() && estimate_tokens(&current) + estimate_tokens(&text) > limit {
+            batches.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push_str("\n\n");
+        }
+        current.push_str(&text);
+    }
+    if !current.is_empty() || batches.is_empty() {
+        batches.push(current);
+    }
+    batches
+}
+
+fn style(options: &SummaryOptions) -> String {
+    let length = match options.length {
+        SummaryLength::Brief => "Keep it to a short paragraph or a few bullet points.",
+        SummaryLength::Detailed => "Cover every important point, section by section.",
+    };
+    let audience = match options.audience {
+        SummaryAudience::Simple => "Write for a reader without background knowledge and explain any jargon.",
+        SummaryAudience::Technical => "Write for an expert reader; keep the precise terms.",
+    };
+    let mut style = format!("{} {}", length, audience);
+    if let Some(focus) = options.focus.as_deref().map(str::trim).filter(|f| !f.is_empty()) {
+        style.push_str(&format!(" Concentrate on: {}.", focus));
+    }
+    style
+}
+
+fn map_instructions(title: &str, options: &SummaryOptions) -> String {
+    format!(
+        "You are condensing part of the document \"{}\" for a later summary. List its key facts, keeping \
+         the bracketed section numbers of the sections each fact comes from.{}",
+        title,
+        options.focus.as_deref().map(|f| format!(" Concentrate on: {}.", f.trim())).unwrap_or_default()
+    )
+}
+
+fn final_instructions(title: &str, options: &SummaryOptions) -> String {
+    format!(
+        "Summarize the document \"{}\" from the text below. Cite the sections you use by their bracketed \
+         numbers, e.g. [2]. Do not add facts that are not in the text. {}",
+        title,
+        style(options)
+    )
+}
+
+// The sections cited as [n] in the summary; every section when it cites none
+fn citations(text: &str, chunks: &[Document]) -> Vec<Citation> {
+    let mut cited: Vec<usize> = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find('[') {
+        rest = &rest[start + 1..];
+        let Some(end) = rest.find(']') else { break };
+        for number in rest[..end].split(',') {
+            if let Ok(number) = number.trim().parse::<usize>() {
+                if number > 0 && !cited.contains(&(number - 1)) {
+                    cited.push(number - 1);
+                }
+            }
+        }
+        rest = &rest[end..];
+    }
+
+    chunks
+        .iter()
+        .filter(|chunk| cited.is_empty() || cited.contains(&chunk.chunk_index))
+        .map(|chunk| Citation {
+            chunk_index: chunk.chunk_index,
+            excerpt: excerpt(&chunk.content),
+        })
+        .collect()
+}
+
+fn excerpt(content: &str) -> String {
+    let content = content.trim();
+    match content.char_indices().nth(EXCERPT_CHARS) {
+        Some((end, _)) => format!("{}…", &content[..end]),
+        None => content.to_string(),
+    }
+}
+
+// SHA-256 of the chunk contents in order
+pub fn checksum(chunks: &[Document]) -> String {
+    let mut context = ring::digest::Context::new(&ring::digest::SHA256);
+    for chunk in chunks {
+        context.update(chunk.content.as_bytes());
+        context.update(&[0]);
+    }
+    context.finish().as_ref().iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn words(text: &str) -> Vec<String> {
+    text.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+// A chat message asking for a summary or explanation of a document: the
+// words naming the document and the options the phrasing implies
+pub fn summary_request(message: &str) -> Option<(String, SummaryOptions)> {
+    let normalized = words(message).join(" ");
+    let (verb, length, audience) = SUMMARY_VERBS
+        .iter()
+        .filter_map(|(verb, length, audience)| normalized.find(verb).map(|at| ((at, *verb), *length, *audience)))
+        .min_by_key(|((at, _), _, _)| *at)?;
+    let (at, verb) = verb;
+    let query: Vec<&str> = normalized[at + verb.len()..]
+        .split_whitespace()
+        .skip_while(|w| *w == "me" || *w == "of")
+        .filter(|w| !FILLER_WORDS.contains(w))
+        .collect();
+    if query.is_empty() {
+        return None;
+    }
+    let length = if normalized.contains("detailed") || normalized.contains("in detail") {
+        SummaryLength::Detailed
+    } else {
+        length
+    };
+    Some((query.join(" "), SummaryOptions { length, audience, focus: None }))
+}
+
+// The document whose title best matches the query, by the share of query
+// words the title contains; words match on a common prefix so "leases"
+// finds "Lease"
+pub fn match_title<'a>(query: &str, documents: &'a [Document]) -> Option<&'a Document> {
+    let query = words(query);
+    if query.is_empty() {
+        return None;
+    }
+    let matches = |a: &str, b: &str| {
+        let common = a.chars().zip(b.chars()).take_while(|(x, y)| x == y).count();
+        common >= 4.min(a.len()).min(b.len()) && common * 4 >= a.len().min(b.len()) * 3
+    };
+
+    documents
+        .iter()
+        .map(|doc| {
+            let title = words(&doc.title);
+            let found = query.iter().filter(|q| title.iter().any(|t| matches(q, t))).count();
+            (found as f32 / query.len() as f32, doc)
+        })
+        .filter(|(score, _)| *score >= TITLE_MATCH_THRESHOLD)
+        .max_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal))
+        .map(|(_, doc)| doc)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trust::TrustLevel;
+
+    // Records every call; map calls answer with the sections they saw, the
+    // final call cites the second section
+    #[derive(Default)]
+    struct MockModel {
+        calls: Mutex<Vec<(String, String)>>,
+    }
+
+    impl SummaryModel for MockModel {
+        async fn complete(&self, system_prompt: &str, prompt: &str) -> Result<ChatReply> {
+            self.calls.lock().unwrap().push((system_prompt.to_string(), prompt.to_string()));
+            let text = if system_prompt.starts_with("Summarize") {
+                "The lease runs until August [2].".to_string()
+            } else {
+                format!("facts from {}", prompt.len())
+            };
+            Ok(ChatReply {
+                text,
+                model: "mock".to_string(),
+                prompt_tokens: Some(100),
+                completion_tokens: Some(10),
+                fallback: false,
+            })
+        }
+    }
+
+    impl MockModel {
+        fn call_count(&self) -> usize {
+            self.calls.lock().unwrap().len()
+        }
+    }
+
+    fn chunks(title: &str, contents: &[&str]) -> Vec<Document> {
+        contents
+            .iter()
+            .enumerate()
+            .map(|(index, content)| Document {
+                id: title.to_lowercase().replace(' ', "-"),
+                title: title.to_string(),
+                content: content.to_string(),
+                chunk_index: index,
+                total_chunks: contents.len(),
+                source: "upload".to_string(),
+                tags: vec![],
+                trust_level: TrustLevel::default(),
+                created_at: Utc::now(),
+                offset: 0,
+                manually_corrected: false,
+                revision: 1,
+                updated_at: None,
+                page: None,
+            })
+            .collect()
+    }
+
+    fn summarizer(map_input_tokens: usize) -> Summarizer {
+        Summarizer::new(SummaryConfig { map_input_tokens, cache_capacity: 8 }, Arc::new(TokenUsage::new()))
+    }
+
+    #[tokio::test]
+    async fn test_long_documents_are_mapped_then_reduced() {
+        // Each section is ~250 tokens, so two fit in one call
+        let section = "word ".repeat(200);
+        let document = chunks("Apartment lease", &[&section, &section, &section, &section, &section]);
+        let model = MockModel::default();
+        let summarizer = summarizer(600);
+
+        let summary = summarizer.summarize(&model, &document, &SummaryOptions::default()).await.unwrap();
+
+        // Three map calls over sections 1-2, 3-4 and 5, then the final merge
+        let calls = model.calls.lock().unwrap().clone();
+        assert_eq!(calls.len(), 4);
+        assert!(calls[0].1.starts_with("[1] ") && calls[0].1.contains("[2] ") && !calls[0].1.contains("[3] "));
+        assert!(calls[2].1.starts_with("[5] "));
+        assert!(calls[..3].iter().all(|(system, _)| system.starts_with("You are condensing")));
+        assert!(calls[3].0.starts_with("Summarize") && calls[3].1.contains("facts from"));
+
+        assert_eq!(summary.model_calls, 4);
+        assert_eq!(summary.prompt_tokens, 400);
+        assert_eq!(summary.citations.iter().map(|c| c.chunk_index).collect::<Vec<_>>(), vec![1]);
+        assert!(!summary.cached);
+        assert_eq!(summarizer.usage.get(SUMMARIZATION_FEATURE).calls, 4);
+    }
+
+    #[tokio::test]
+    async fn test_summaries_are_cached_per_options_and_content() {
+        let document = chunks("Lease", &["Rent is due on the 1st.", "The lease runs until August."]);
+        let model = MockModel::default();
+        let summarizer = summarizer(6000);
+        let brief = SummaryOptions::default();
+
+        let first = summarizer.summarize(&model, &document, &brief).await.unwrap();
+        assert_eq!(model.call_count(), 1);
+        let again = summarizer.summarize(&model, &document, &brief).await.unwrap();
+        assert!(again.cached);
+        assert_eq!(again.summary, first.summary);
+        assert_eq!(model.call_count(), 1);
+
+        let detailed = SummaryOptions { length: SummaryLength::Detailed, ..SummaryOptions::default() };
+        summarizer.summarize(&model, &document, &detailed).await.unwrap();
+        assert_eq!(model.call_count(), 2);
+        assert!(model.calls.lock().unwrap()[1].0.contains("section by section"));
+
+        // Changed content is a different checksum
+        let edited = chunks("Lease", &["Rent is due on the 3rd.", "The lease runs until August."]);
+        let changed = summarizer.summarize(&model, &edited, &brief).await.unwrap();
+        assert!(!changed.cached);
+        assert_ne!(changed.checksum, first.checksum);
+        assert_eq!(model.call_count(), 3);
+    }
+
+    #[test]
+    fn test_chat_requests_resolve_documents_by_title() {
+        let (query, options) = summary_request("Can you summarize the lease agreement?").unwrap();
+        assert_eq!(query, "lease agreement");
+        assert_eq!(options.length, SummaryLength::Brief);
+        let (query, options) = summary_request("explain my offsite notes in detail").unwrap();
+        assert_eq!(query, "offsite notes");
+        assert_eq!(options.audience, SummaryAudience::Simple);
+        assert_eq!(options.length, SummaryLength::Detailed);
+        assert!(summary_request("summarize").is_none());
+        assert!(summary_request("what is the rent?").is_none());
+
+        let mut documents = chunks("Apartment Lease Agreement", &["a"]);
+        documents.extend(chunks("Team offsite notes", &["b"]));
+        assert_eq!(match_title("lease agreement", &documents).unwrap().title, "Apartment Lease Agreement");
+        assert_eq!(match_title("leases", &documents).unwrap().title, "Apartment Lease Agreement");
+        assert_eq!(match_title("offsite", &documents).unwrap().title, "Team offsite notes");
+        assert!(match_title("recursion", &documents).is_none());
+    }
+}
@@ -0,0 +1,1292 @@
+use anyhow::{Context, Result};
+use axum::{
+    extract::{Multipart, Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::{Path as FsPath, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::AsyncReadExt;
+use tokio::sync::{broadcast, mpsc, RwLock};
+use tracing::{debug, error, info, warn};
+use uuid::Uuid;
+
+use crate::callbacks::{CallbackService, CallbackTarget};
+use crate::data_residency::ProviderClass;
+use crate::http_client::{ClientKind, HttpClientFactory};
+use crate::knowledge_service_simple::KnowledgeService;
+use crate::knowledge_upload::write_field_to_file;
+use crate::trust::TrustLevel;
+use crate::voice_service::VoiceService;
+
+const DEFAULT_MEDIA_DIR: &str = "./data/media";
+const DEFAULT_RETENTION_DAYS: i64 = 7;
+const EVENT_CHANNEL_CAPACITY: usize = 256;
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+// Recordings are far larger than chat audio; Whisper itself caps each
+// request at 25MB, which the VAD segments stay well below
+pub const MAX_RECORDING_BYTES: usize = 500 * 1024 * 1024;
+
+// Whisper's own per-request limit, which a recording that can't be split
+// must fit in whole
+pub const MAX_UNSPLIT_BYTES: usize = 25 * 1024 * 1024;
+
+// Enough of a WAV file to find its format and the start of its data
+const WAV_HEADER_BYTES: u64 = 64 * 1024;
+
+// Energy VAD: 30ms frames, a segment ends after half a second of silence and
+// is cut hard at 30s so progress keeps moving through long monologues
+const FRAME_MS: usize = 30;
+const SILENCE_RMS: f64 = 0.01;
+const MIN_SILENCE_MS: usize = 500;
+const MAX_SEGMENT_MS: usize = 30_000;
+
+#[derive(Debug, Clone)]
+pub struct TranscriptionConfig {
+    pub media_dir: PathBuf,
+    // Audio of finished jobs is deleted after this long; transcripts are kept
+    pub retention: chrono::Duration,
+    // Default for uploads that do not say whether to ingest the transcript
+    pub auto_ingest: bool,
+    pub mode: TranscriptionMode,
+}
+
+// How recordings are transcribed: segment by segment through the voice
+// service, or handed whole to a provider that calls back when it is done
+#[derive(Debug, Clone, PartialEq)]
+pub enum TranscriptionMode {
+    Segmented,
+    Callback(CallbackProviderConfig),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CallbackProviderConfig {
+    // Name in `/api/v1/callbacks/:integration`; its secret is in CALLBACK_SECRETS
+    pub integration: String,
+    pub submit_url: String,
+    pub api_key: Option<String>,
+    // Where the provider reaches this server
+    pub public_base_url: String,
+}
+
+impl CallbackProviderConfig {
+    pub fn callback_url(&self) -> String {
+        format!("{}/api/v1/callbacks/{}", self.public_base_url.trim_end_matches('/'), self.integration)
+    }
+}
+
+impl Default for TranscriptionConfig {
+    fn default() -> Self {
+        Self {
+            media_dir: PathBuf::from(DEFAULT_MEDIA_DIR),
+            retention: chrono::Duration::days(DEFAULT_RETENTION_DAYS),
+            auto_ingest: false,
+            mode: TranscriptionMode::Segmented,
+        }
+    }
+}
+
+impl TranscriptionConfig {
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+
+        Self {
+            media_dir: std::env::var("MEDIA_DIR").map(PathBuf::from).unwrap_or(defaults.media_dir),
+            retention: std::env::var("TRANSCRIPT_RETENTION_DAYS")
+                .ok()
+                .and_then(|v| v.parse::<i64>().ok())
+                .map(chrono::Duration::days)
+                .unwrap_or(defaults.retention),
+            auto_ingest: std::env::var("TRANSCRIPT_AUTO_INGEST")
+                .map(|v| v == "true" || v == "1")
+                .unwrap_or(defaults.auto_ingest),
+            mode: mode_from_env().unwrap_or(defaults.mode),
+        }
+    }
+}
+
+// TRANSCRIPTION_MODE=callback with the provider's integration name, submit
+// URL and the server's public URL; anything missing keeps segmented mode
+fn mode_from_env() -> Option<TranscriptionMode> {
+    if std::env::var("TRANSCRIPTION_MODE").ok()?.trim() != "callback" {
+        return None;
+    }
+
+    let var = |name: &str| std::env::var(name).ok().map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
+    match (
+        var("TRANSCRIPTION_CALLBACK_INTEGRATION"),
+        var("TRANSCRIPTION_CALLBACK_SUBMIT_URL"),
+        var("PUBLIC_BASE_URL"),
+    ) {
+        (Some(integration), Some(submit_url), Some(public_base_url)) => {
+            Some(TranscriptionMode::Callback(CallbackProviderConfig {
+                integration: integration.to_ascii_lowercase(),
+                submit_url,
+                api_key: var("TRANSCRIPTION_CALLBACK_API_KEY"),
+                public_base_url,
+            }))
+        }
+        _ => {
+            warn!(
+                "TRANSCRIPTION_MODE=callback needs TRANSCRIPTION_CALLBACK_INTEGRATION, \
+                 TRANSCRIPTION_CALLBACK_SUBMIT_URL and PUBLIC_BASE_URL; transcribing segment by segment"
+            );
+            None
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum JobStatus {
+    Queued,
+    Transcribing,
+    Completed,
+    Failed { error: String },
+}
+
+impl JobStatus {
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, JobStatus::Completed | JobStatus::Failed { .. })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TranscriptSegment {
+    pub index: usize,
+    pub start_ms: u64,
+    pub end_ms: u64,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TranscriptionJob {
+    pub id: String,
+    pub filename: String,
+    pub media_path: PathBuf,
+    pub recorded_at: DateTime<Utc>,
+    pub auto_ingest: bool,
+    // User tags, added to the ingested transcript
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub status: JobStatus,
+    pub segments_total: Option<usize>,
+    // Completed segments in order; a resumed job continues after the last one
+    pub segments: Vec<TranscriptSegment>,
+    pub transcript: Option<String>,
+    pub document_id: Option<String>,
+    pub ingest_error: Option<String>,
+    // The callback provider's id for the job, set once it has the recording
+    #[serde(default)]
+    pub provider_job_id: Option<String>,
+    pub media_deleted: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl TranscriptionJob {
+    pub fn new(id: String, filename: String, media_path: PathBuf, recorded_at: Option<DateTime<Utc>>, auto_ingest: bool) -> Self {
+        let now = Utc::now();
+        Self {
+            id,
+            filename,
+            media_path,
+            recorded_at: recorded_at.unwrap_or(now),
+            auto_ingest,
+            tags: Vec::new(),
+            status: JobStatus::Queued,
+            segments_total: None,
+            segments: Vec::new(),
+            transcript: None,
+            document_id: None,
+            ingest_error: None,
+            provider_job_id: None,
+            media_deleted: false,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    // Text of the segments transcribed so far
+    pub fn partial_text(&self) -> String {
+        join_segments(&self.segments)
+    }
+}
+
+fn join_segments(segments: &[TranscriptSegment]) -> String {
+    segments
+        .iter()
+        .map(|s| s.text.trim())
+        .filter(|t| !t.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// Speech-to-text for one segment; Whisper in production, a mock in tests
+pub trait SegmentTranscriber {
+    fn transcribe_segment(&self, audio: Vec<u8>, filename: &str) -> impl Future<Output = Result<String>> + Send;
+}
+
+impl SegmentTranscriber for VoiceService {
+    async fn transcribe_segment(&self, audio: Vec<u8>, filename: &str) -> Result<String> {
+        Ok(self.transcribe(audio, filename.to_string()).await?.text)
+    }
+}
+
+// Speech-to-text of a whole recording by a provider that answers later,
+// through `/api/v1/callbacks/:integration`; returns the provider's job id
+pub trait CallbackTranscriber {
+    fn integration(&self) -> &str;
+    fn submit(&self, audio: Vec<u8>, filename: &str) -> impl Future<Output = Result<String>> + Send;
+}
+
+// Posts the recording to the provider's submit URL with the callback URL
+// and filename as query parameters; the provider answers with its job id
+pub struct HttpCallbackTranscriber {
+    client: reqwest::Client,
+    config: CallbackProviderConfig,
+}
+
+#[derive(Deserialize)]
+struct SubmittedJob {
+    #[serde(alias = "job_id")]
+    id: String,
+}
+
+impl HttpCallbackTranscriber {
+    pub fn new(config: CallbackProviderConfig, http: &HttpClientFactory) -> Result<Self> {
+        Ok(Self {
+            client: http.client(ClientKind::Webhook)?,
+            config,
+        })
+    }
+}
+
+impl CallbackTranscriber for HttpCallbackTranscriber {
+    fn integration(&self) -> &str {
+        &self.config.integration
+    }
+
+    async fn submit(&self, audio: Vec<u8>, filename: &str) -> Result<String> {
+        let mut request = self
+            .client
+            .post(&self.config.submit_url)
+            .query(&[("callback_url", self.config.callback_url().as_str()), ("filename", filename)])
+            .header(reqwest::header::CONTENT_TYPE, "application/octet-stream")
+            .body(audio);
+        if let Some(api_key) = &self.config.api_key {
+            request = request.bearer_auth(api_key);
+        }
+
+        let response = request.send().await.context("Failed to reach the transcription provider")?;
+        let status = response.status();
+        if !status.is_success() {
+            anyhow::bail!("Transcription provider answered {}", status);
+        }
+        let submitted: SubmittedJob = response.json().await.context("Transcription provider sent no job id")?;
+        Ok(submitted.id)
+    }
+}
+
+// Where finished transcripts are ingested; returns the document id
+pub trait TranscriptSink {
+    fn ingest(&self, job: &TranscriptionJob, transcript: &str) -> impl Future<Output = Result<String>> + Send;
+}
+
+impl TranscriptSink for KnowledgeService {
+    async fn ingest(&self, job: &TranscriptionJob, transcript: &str) -> Result<String> {
+        let date = job.recorded_at.format("%Y-%m-%d");
+        let mut tags = vec!["transcript".to_string(), format!("recorded:{}", date)];
+        tags.extend(job.tags.iter().cloned());
+        let response = self
+            .store_document(
+                format!("Recording {} ({})", job.filename, date),
+                transcript.to_string(),
+                job.filename.clone(),
+                tags,
+                TrustLevel::Personal,
+            )
+            .await?;
+        Ok(response.document_id)
+    }
+}
+
+// One stretch of speech, re-encoded so it can be transcribed on its own
+#[derive(Debug, Clone)]
+pub struct AudioSegment {
+    pub start_ms: u64,
+    pub end_ms: u64,
+    pub audio: Vec<u8>,
+}
+
+struct WavPcm {
+    sample_rate: u32,
+    channels: u16,
+    // Interleaved 16-bit samples
+    samples: Vec<i16>,
+}
+
+// Split a recording at pauses. Only 16-bit PCM WAV can be inspected without a
+// decoder; anything else is transcribed as a single segment, so it has to fit
+// in one Whisper request
+pub fn segment_audio(bytes: &[u8]) -> Result<Vec<AudioSegment>> {
+    if let Some(wav) = parse_wav(bytes) {
+        return Ok(split_wav(&wav));
+    }
+    if bytes.len() > MAX_UNSPLIT_BYTES {
+        anyhow::bail!("{}", unsplit_too_large(bytes.len() as u64));
+    }
+    Ok(vec![AudioSegment {
+        start_ms: 0,
+        end_ms: 0,
+        audio: bytes.to_vec(),
+    }])
+}
+
+fn unsplit_too_large(bytes: u64) -> String {
+    format!(
+        "Recording is {}MB and not 16-bit PCM WAV, so it can't be split; other formats are limited to {}MB",
+        bytes / (1024 * 1024),
+        MAX_UNSPLIT_BYTES / (1024 * 1024)
+    )
+}
+
+// Whether the recording at `path` is WAV that `segment_audio` can split,
+// judged from its header
+async fn is_splittable(path: &FsPath) -> bool {
+    let Ok(file) = tokio::fs::File::open(path).await else {
+        return false;
+    };
+    let mut header = Vec::new();
+    if file.take(WAV_HEADER_BYTES).read_to_end(&mut header).await.is_err() {
+        return false;
+    }
+    wav_format(&header).is_some()
+}
+
+fn parse_wav(bytes: &[u8]) -> Option<WavPcm> {
+    let (sample_rate, channels, data) = wav_format(bytes)?;
+    // Whole sample frames only, so the channels stay interleaved
+    let frame_bytes = 2 * channels as usize;
+    let data = &data[..data.len() - data.len() % frame_bytes];
+    let samples = data.chunks_exact(2).map(|s| i16::from_le_bytes([s[0], s[1]])).collect();
+    Some(WavPcm { sample_rate, channels, samples })
+}
+
+// Sample rate, channel count and data of a 16-bit PCM WAV; the data is cut
+// short when `bytes` is only the start of the file
+fn wav_format(bytes: &[u8]) -> Option<(u32, u16, &[u8])> {
+    if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+        return None;
+    }
+
+    let mut format = None;
+    let mut offset = 12;
+    while offset + 8 <= bytes.len() {
+        let id = &bytes[offset..offset + 4];
+        let len = u32::from_le_bytes(bytes[offset + 4..offset + 8].try_into().ok()?) as usize;
+        let body = bytes.get(offset + 8..(offset + 8 + len).min(bytes.len()))?;
+
+        match id {
+            b"fmt " if body.len() >= 16 => {
+                let audio_format = u16::from_le_bytes([body[0], body[1]]);
+                let channels = u16::from_le_bytes([body[2], body[3]]);
+                let sample_rate = u32::from_le_bytes(body[4..8].try_into().ok()?);
+                let bits = u16::from_le_bytes([body[14], body[15]]);
+                if audio_format != 1 || bits != 16 || channels == 0 || sample_rate == 0 {
+                    return None;
+                }
+                format = Some((sample_rate, channels));
+            }
+            b"data" => {
+                let (sample_rate, channels) = format?;
+                return Some((sample_rate, channels, body));
+            }
+            _ => {}
+        }
+
+        // Chunks are padded to an even length
+        offset += 8 + len + (len % 2);
+    }
+
+    None
+}
+
+fn encode_wav(sample_rate: u32, channels: u16, samples: &[i16]) -> Vec<u8> {
+    let data_len = (samples.len() * 2) as u32;
+    let block_align = channels * 2;
+    let mut out = Vec::with_capacity(44 + data_len as usize);
+
+    out.extend_from_slice(b"RIFF");
+    out.extend_from_slice(&(36 + data_len).to_le_bytes());
+    out.extend_from_slice(b"WAVEfmt ");
+    out.extend_from_slice(&16u32.to_le_bytes());
+    out.extend_from_slice(&1u16.to_le_bytes());
+    out.extend_from_slice(&channels.to_le_bytes());
+    out.extend_from_slice(&sample_rate.to_le_bytes());
+    out.extend_from_slice(&(sample_rate * block_align as u32).to_le_bytes());
+    out.extend_from_slice(&block_align.to_le_bytes());
+    out.extend_from_slice(&16u16.to_le_bytes());
+    out.extend_from_slice(b"data");
+    out.extend_from_slice(&data_len.to_le_bytes());
+    for sample in samples {
+        out.extend_from_slice(&sample.to_le_bytes());
+    }
+
+    out
+}
+
+fn split_wav(wav: &WavPcm) -> Vec<AudioSegment> {
+    // Per channel first, so a frame always holds whole sample frames even
+    // when the rate doesn't divide evenly; in bytes that is twice this
+    let channels = wav.channels as usize;
+    let frame_len = (wav.sample_rate as usize * FRAME_MS / 1000).max(1) * channels;
+    let position_ms = |sample: usize| (sample / channels * 1000 / wav.sample_rate as usize) as u64;
+    let voiced: Vec<bool> = wav
+        .samples
+        .chunks(frame_len)
+        .map(|frame| {
+            let energy: f64 = frame.iter().map(|&s| (s as f64 / i16::MAX as f64).powi(2)).sum();
+            (energy / frame.len() as f64).sqrt() > SILENCE_RMS
+        })
+        .collect();
+
+    let min_silence = MIN_SILENCE_MS / FRAME_MS;
+    let max_frames = MAX_SEGMENT_MS / FRAME_MS;
+    let mut ranges = Vec::new();
+    let mut start = None;
+    let mut silence = 0;
+
+    for (i, &is_voiced) in voiced.iter().enumerate() {
+        match start {
+            None if is_voiced => {
+                start = Some(i);
+                silence = 0;
+            }
+            None => {}
+            Some(s) => {
+                silence = if is_voiced { 0 } else { silence + 1 };
+                if silence >= min_silence {
+                    ranges.push((s, i + 1 - silence));
+                    start = None;
+                } else if i + 1 - s >= max_frames {
+                    ranges.push((s, i + 1));
+                    start = None;
+                }
+            }
+        }
+    }
+    if let Some(s) = start {
+        ranges.push((s, voiced.len() - silence));
+    }
+
+    ranges
+        .into_iter()
+        .map(|(first, end)| {
+            let from = first * frame_len;
+            let to = (end * frame_len).min(wav.samples.len());
+            AudioSegment {
+                start_ms: position_ms(from),
+                end_ms: position_ms(to),
+                audio: encode_wav(wav.sample_rate, wav.channels, &wav.samples[from..to]),
+            }
+        })
+        .collect()
+}
+
+// Every transcription job, persisted as `<dir>/<id>.json` so the queue
+// survives restarts, with a broadcast of each change
+pub struct TranscriptionQueue {
+    jobs_dir: PathBuf,
+    jobs: RwLock<HashMap<String, TranscriptionJob>>,
+    events: broadcast::Sender<TranscriptionJob>,
+}
+
+impl TranscriptionQueue {
+    pub async fn open(jobs_dir: impl Into<PathBuf>) -> Result<Self> {
+        let jobs_dir = jobs_dir.into();
+        tokio::fs::create_dir_all(&jobs_dir)
+            .await
+            .context("Failed to create transcription job directory")?;
+
+        let mut jobs = HashMap::new();
+        let mut entries = tokio::fs::read_dir(&jobs_dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            if let Ok(bytes) = tokio::fs::read(entry.path()).await {
+                match serde_json::from_slice::<TranscriptionJob>(&bytes) {
+                    Ok(job) => {
+                        jobs.insert(job.id.clone(), job);
+                    }
+                    Err(e) => warn!("Ignoring unreadable transcription job {:?}: {}", entry.path(), e),
+                }
+            }
+        }
+
+        let (events, _) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
+        Ok(Self {
+            jobs_dir,
+            jobs: RwLock::new(jobs),
+            events,
+        })
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<TranscriptionJob> {
+        self.events.subscribe()
+    }
+
+    pub async fn get(&self, id: &str) -> Option<TranscriptionJob> {
+        self.jobs.read().await.get(id).cloned()
+    }
+
+    pub async fn insert(&self, job: TranscriptionJob) {
+        self.jobs.write().await.insert(job.id.clone(), job.clone());
+        self.persist(&job).await;
+        let _ = self.events.send(job);
+    }
+
+    pub async fn update(&self, id: &str, change: impl FnOnce(&mut TranscriptionJob)) -> Option<TranscriptionJob> {
+        let updated = {
+            let mut jobs = self.jobs.write().await;
+            let job = jobs.get_mut(id)?;
+            change(job);
+            job.updated_at = Utc::now();
+            job.clone()
+        };
+
+        self.persist(&updated).await;
+        debug!("Transcription {} is now {:?}", id, updated.status);
+        let _ = self.events.send(updated.clone());
+        Some(updated)
+    }
+
+    // Jobs interrupted by a restart, oldest first
+    pub async fn unfinished(&self) -> Vec<String> {
+        let mut jobs: Vec<_> = self
+            .jobs
+            .read()
+            .await
+            .values()
+            .filter(|job| !job.status.is_terminal())
+            .map(|job| (job.created_at, job.id.clone()))
+            .collect();
+        jobs.sort();
+        jobs.into_iter().map(|(_, id)| id).collect()
+    }
+
+    // Delete the audio of jobs that finished before `cutoff`
+    pub async fn remove_expired_media(&self, cutoff: DateTime<Utc>) -> usize {
+        let expired: Vec<TranscriptionJob> = self
+            .jobs
+            .read()
+            .await
+            .values()
+            .filter(|job| job.status.is_terminal() && !job.media_deleted && job.updated_at < cutoff)
+            .cloned()
+            .collect();
+
+        for job in &expired {
+            if let Err(e) = tokio::fs::remove_file(&job.media_path).await {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    warn!("Failed to remove recording {}: {}", job.media_path.display(), e);
+                    continue;
+                }
+            }
+            self.update(&job.id, |job| job.media_deleted = true).await;
+        }
+
+        expired.len()
+    }
+
+    async fn persist(&self, job: &TranscriptionJob) {
+        let path = self.jobs_dir.join(format!("{}.json", job.id));
+        let result = async {
+            tokio::fs::write(&path, serde_json::to_vec(job)?).await?;
+            Ok::<_, anyhow::Error>(())
+        }
+        .await;
+
+        if let Err(e) = result {
+            warn!("Failed to persist transcription job {:?}: {}", path, e);
+        }
+    }
+}
+
+// Transcribe a queued recording segment by segment, publishing the text of
+// each segment as it completes. Segments finished before a restart are
+// skipped, since segmentation of the same file is deterministic.
+pub async fn process_job<T: SegmentTranscriber, S: TranscriptSink>(
+    queue: &TranscriptionQueue,
+    transcriber: &T,
+    sink: Option<&S>,
+    id: &str,
+) -> Result<()> {
+    let Some(job) = queue.get(id).await else {
+        anyhow::bail!("Transcription job {} not found", id);
+    };
+    if job.status.is_terminal() {
+        return Ok(());
+    }
+
+    let result = run_job(queue, transcriber, &job).await;
+    finish_job(queue, sink, &job, result).await
+}
+
+// Hand a queued recording to a callback provider. The job stays
+// transcribing until the provider calls back; a job it already has is not
+// sent again, so resuming after a restart waits for the callback instead.
+pub async fn submit_job<P: CallbackTranscriber>(
+    queue: &TranscriptionQueue,
+    provider: &P,
+    callbacks: &CallbackService,
+    id: &str,
+) -> Result<()> {
+    let Some(job) = queue.get(id).await else {
+        anyhow::bail!("Transcription job {} not found", id);
+    };
+    if job.status.is_terminal() || job.provider_job_id.is_some() {
+        return Ok(());
+    }
+
+    let submitted = async {
+        let bytes = tokio::fs::read(&job.media_path)
+            .await
+            .with_context(|| format!("Failed to read recording {}", job.media_path.display()))?;
+        let provider_job_id = provider.submit(bytes, &job.filename).await?;
+        callbacks.register(provider.integration(), &provider_job_id, CallbackTarget::Transcription { job_id: job.id.clone() })?;
+        Ok::<_, anyhow::Error>(provider_job_id)
+    }
+    .await;
+
+    match submitted {
+        Ok(provider_job_id) => {
+            info!("Transcription {} submitted to {} as {}", id, provider.integration(), provider_job_id);
+            queue
+                .update(id, |job| {
+                    job.status = JobStatus::Transcribing;
+                    job.provider_job_id = Some(provider_job_id);
+                })
+                .await;
+            Ok(())
+        }
+        // Nothing was transcribed, so there is nothing to ingest
+        Err(e) => finish_job(queue, None::<&KnowledgeService>, &job, Err(e)).await,
+    }
+}
+
+// Finish a job with what its callback provider reported and return it.
+// Finished jobs are left as they are.
+pub async fn complete_job<S: TranscriptSink>(
+    queue: &TranscriptionQueue,
+    sink: Option<&S>,
+    id: &str,
+    result: Result<String>,
+) -> Result<TranscriptionJob> {
+    let Some(job) = queue.get(id).await else {
+        anyhow::bail!("Transcription job {} not found", id);
+    };
+    if !job.status.is_terminal() {
+        // A failed transcription is recorded on the job, not an error here
+        let _ = finish_job(queue, sink, &job, result).await;
+    }
+    queue.get(id).await.with_context(|| format!("Transcription job {} not found", id))
+}
+
+// Record how a job ended, ingesting the transcript if the job asks for it
+async fn finish_job<S: TranscriptSink>(
+    queue: &TranscriptionQueue,
+    sink: Option<&S>,
+    job: &TranscriptionJob,
+    result: Result<String>,
+) -> Result<()> {
+    let id = job.id.as_str();
+    match result {
+        Ok(transcript) => {
+            let mut document_id = None;
+            let mut ingest_error = None;
+            if job.auto_ingest && !transcript.is_empty() {
+                if let Some(sink) = sink {
+                    match sink.ingest(job, &transcript).await {
+                        Ok(id) => document_id = Some(id),
+                        // The transcript is still worth keeping
+                        Err(e) => {
+                            warn!("Failed to ingest transcript {}: {}", job.id, e);
+                            ingest_error = Some(e.to_string());
+                        }
+                    }
+                }
+            }
+
+            queue
+                .update(id, |job| {
+                    job.status = JobStatus::Completed;
+                    job.transcript = Some(transcript);
+                    job.document_id = document_id;
+                    job.ingest_error = ingest_error;
+                })
+                .await;
+            info!("Transcription {} completed", id);
+            Ok(())
+        }
+        Err(e) => {
+            error!("Transcription {} failed: {}", id, e);
+            queue
+                .update(id, |job| job.status = JobStatus::Failed { error: format!("{:#}", e) })
+                .await;
+            Err(e)
+        }
+    }
+}
+
+async fn run_job<T: SegmentTranscriber>(queue: &TranscriptionQueue, transcriber: &T, job: &TranscriptionJob) -> Result<String> {
+    let bytes = tokio::fs::read(&job.media_path)
+        .await
+        .with_context(|| format!("Failed to read recording {}", job.media_path.display()))?;
+    let split = parse_wav(&bytes).is_some();
+    let segments = segment_audio(&bytes)?;
+    let total = segments.len();
+    let done = job.segments.len();
+
+    queue
+        .update(&job.id, |job| {
+            job.status = JobStatus::Transcribing;
+            job.segments_total = Some(total);
+        })
+        .await;
+
+    let stem = FsPath::new(&job.filename)
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("recording");
+    let mut completed = job.segments.clone();
+
+    for (index, segment) in segments.into_iter().enumerate().skip(done) {
+        // Split segments are re-encoded as WAV; a single segment is the upload
+        let filename = if split {
+            format!("{}-{}.wav", stem, index)
+        } else {
+            job.filename.clone()
+        };
+        let text = transcriber
+            .transcribe_segment(segment.audio, &filename)
+            .await
+            .with_context(|| format!("Segment {} of {} failed", index + 1, total))?;
+
+        let transcribed = TranscriptSegment {
+            index,
+            start_ms: segment.start_ms,
+            end_ms: segment.end_ms,
+            text,
+        };
+        completed.push(transcribed.clone());
+        queue.update(&job.id, |job| job.segments.push(transcribed)).await;
+    }
+
+    Ok(join_segments(&completed))
+}
+
+pub struct TranscriptionManager {
+    config: TranscriptionConfig,
+    queue: Arc<TranscriptionQueue>,
+    sender: mpsc::UnboundedSender<String>,
+    available: bool,
+    knowledge_service: Option<Arc<KnowledgeService>>,
+}
+
+impl TranscriptionManager {
+    pub async fn new(
+        config: TranscriptionConfig,
+        voice_service: Option<Arc<VoiceService>>,
+        knowledge_service: Option<Arc<KnowledgeService>>,
+        callbacks: Arc<CallbackService>,
+        http: &HttpClientFactory,
+    ) -> Result<Self> {
+        tokio::fs::create_dir_all(&config.media_dir)
+            .await
+            .context("Failed to create media directory")?;
+        let queue = Arc::new(TranscriptionQueue::open(config.media_dir.join("jobs")).await?);
+        let (sender, mut receiver) = mpsc::unbounded_channel::<String>();
+
+        // A single worker, so long recordings do not fan out into a burst of
+        // concurrent provider requests. Without voice, segmented jobs wait
+        // for a restart.
+        let available = match &config.mode {
+            TranscriptionMode::Callback(provider) => {
+                if !callbacks.has_integration(&provider.integration) {
+                    anyhow::bail!(
+                        "Transcription provider {} has no secret in CALLBACK_SECRETS, so its callbacks would be refused",
+                        provider.integration
+                    );
+                }
+                let provider = HttpCallbackTranscriber::new(provider.clone(), http)?;
+                let queue = Arc::clone(&queue);
+                tokio::spawn(async move {
+                    while let Some(id) = receiver.recv().await {
+                        let _ = submit_job(&queue, &provider, &callbacks, &id).await;
+                    }
+                });
+                true
+            }
+            TranscriptionMode::Segmented => match voice_service {
+                Some(voice_service) => {
+                    let queue = Arc::clone(&queue);
+                    let knowledge_service = knowledge_service.clone();
+                    tokio::spawn(async move {
+                        while let Some(id) = receiver.recv().await {
+                            let _ = process_job(&queue, voice_service.as_ref(), knowledge_service.as_deref(), &id).await;
+                        }
+                    });
+                    true
+                }
+                None => false,
+            },
+        };
+
+        if available {
+            for id in queue.unfinished().await {
+                info!("Resuming transcription {}", id);
+                let _ = sender.send(id);
+            }
+        }
+
+        tokio::spawn({
+            let queue = Arc::clone(&queue);
+            let retention = config.retention;
+            async move {
+                let mut interval = tokio::time::interval(CLEANUP_INTERVAL);
+                loop {
+                    interval.tick().await;
+                    let removed = queue.remove_expired_media(Utc::now() - retention).await;
+                    if removed > 0 {
+                        info!("Removed {} expired recordings", removed);
+                    }
+                }
+            }
+        });
+
+        Ok(Self {
+            config,
+            queue,
+            sender,
+            available,
+            knowledge_service,
+        })
+    }
+
+    pub fn queue(&self) -> &Arc<TranscriptionQueue> {
+        &self.queue
+    }
+
+    // Where transcripts finished by a callback are ingested
+    pub fn sink(&self) -> Option<&KnowledgeService> {
+        self.knowledge_service.as_deref()
+    }
+
+    async fn enqueue(&self, job: TranscriptionJob) -> Result<()> {
+        let id = job.id.clone();
+        self.queue.insert(job).await;
+        self.sender.send(id).context("Transcription worker has stopped")
+    }
+}
+
+// Keep the upload's extension so Whisper can tell the container format
+fn media_extension(filename: &str) -> String {
+    FsPath::new(filename)
+        .extension()
+        .and_then(|e| e.to_str())
+        .filter(|e| e.len() <= 5 && e.chars().all(|c| c.is_ascii_alphanumeric()))
+        .map(|e| e.to_ascii_lowercase())
+        .unwrap_or_else(|| "bin".to_string())
+}
+
+// Accepts an RFC 3339 timestamp or a plain date
+fn parse_recorded_at(value: &str) -> Option<DateTime<Utc>> {
+    let value = value.trim();
+    DateTime::parse_from_rfc3339(value)
+        .map(|d| d.with_timezone(&Utc))
+        .ok()
+        .or_else(|| {
+            NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .ok()
+                .and_then(|d| d.and_hms_opt(0, 0, 0))
+                .map(|d| d.and_utc())
+        })
+}
+
+struct RecordingUpload {
+    filename: String,
+    media_path: PathBuf,
+    bytes: u64,
+    recorded_at: Option<DateTime<Utc>>,
+    auto_ingest: Option<bool>,
+    tags: Vec<String>,
+}
+
+async fn receive_recording(multipart: &mut Multipart, media_dir: &FsPath, id: &str) -> Result<RecordingUpload> {
+    let mut upload = RecordingUpload {
+        filename: String::new(),
+        media_path: PathBuf::new(),
+        bytes: 0,
+        recorded_at: None,
+        auto_ingest: None,
+        tags: Vec::new(),
+    };
+
+    while let Some(mut field) = multipart.next_field().await? {
+        let name = field.name().unwrap_or("").to_string();
+
+        match name.as_str() {
+            "audio" if upload.bytes == 0 => {
+                upload.filename = field.file_name().unwrap_or("recording.webm").to_string();
+                upload.media_path = media_dir.join(format!("{}.{}", id, media_extension(&upload.filename)));
+                upload.bytes = write_field_to_file(&mut field, &upload.media_path).await?;
+            }
+            "recorded_at" => {
+                let value = field.text().await?;
+                upload.recorded_at =
+                    Some(parse_recorded_at(&value).with_context(|| format!("Invalid recorded_at: {}", value))?);
+            }
+            "auto_ingest" => upload.auto_ingest = Some(field.text().await?.trim() == "true"),
+            // Comma-separated
+            "tags" => {
+                upload.tags = field
+                    .text()
+                    .await?
+                    .split(',')
+                    .map(|t| t.trim().to_string())
+                    .filter(|t| !t.is_empty())
+                    .collect();
+            }
+            // Drain fields we do not use so the stream can advance
+            _ => {
+                while field.chunk().await?.is_some() {}
+            }
+        }
+    }
+
+    Ok(upload)
+}
+
+// HTTP Handlers
+pub async fn create_transcription_handler(
+    State(state): State<Arc<crate::AppState>>,
+    mut multipart: Multipart,
+) -> impl IntoResponse {
+    let manager = &state.transcription_manager;
+    if !manager.available {
+        return (StatusCode::SERVICE_UNAVAILABLE, "Voice service is not available").into_response();
+    }
+
+    let id = Uuid::new_v4().to_string();
+    let upload = match receive_recording(&mut multipart, &manager.config.media_dir, &id).await {
+        Ok(upload) => upload,
+        Err(e) => {
+            warn!("Failed to receive recording: {}", e);
+            remove_partial_upload(&manager.config.media_dir, &id).await;
+            return (StatusCode::BAD_REQUEST, format!("Failed to read recording: {}", e)).into_response();
+        }
+    };
+
+    if upload.bytes == 0 {
+        remove_partial_upload(&manager.config.media_dir, &id).await;
+        return (StatusCode::BAD_REQUEST, "No audio file provided").into_response();
+    }
+
+    // Segmented jobs send a recording that can't be split to Whisper whole
+    if matches!(manager.config.mode, TranscriptionMode::Segmented)
+        && upload.bytes > MAX_UNSPLIT_BYTES as u64
+        && !is_splittable(&upload.media_path).await
+    {
+        remove_partial_upload(&manager.config.media_dir, &id).await;
+        return (StatusCode::PAYLOAD_TOO_LARGE, unsplit_too_large(upload.bytes)).into_response();
+    }
+
+    // Speech-to-text is a cloud provider; recordings the residency policy
+    // keeps local are refused instead of being sent to it
+    let stt_provider = match &manager.config.mode {
+        TranscriptionMode::Segmented => state.voice_service.as_ref().map(|v| v.provider_class()),
+        TranscriptionMode::Callback(_) => None,
+    }
+    .unwrap_or(ProviderClass::Cloud);
+    if let Some(restriction) = state.residency.check(&upload.tags, stt_provider) {
+        warn!("Refusing to transcribe {}: {}", upload.filename, restriction.reason());
+        remove_partial_upload(&manager.config.media_dir, &id).await;
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(serde_json::json!({
+                "error": format!(
+                    "Recording is {}, and no local speech-to-text provider is configured",
+                    restriction.reason()
+                ),
+                "withheld": restriction,
+            })),
+        )
+            .into_response();
+    }
+
+    let mut job = TranscriptionJob::new(
+        id.clone(),
+        upload.filename,
+        upload.media_path,
+        upload.recorded_at,
+        upload.auto_ingest.unwrap_or(manager.config.auto_ingest),
+    );
+    job.tags = upload.tags;
+    let status = job.status.clone();
+    if let Err(e) = manager.enqueue(job).await {
+        error!("Failed to enqueue transcription {}: {}", id, e);
+        return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to queue transcription").into_response();
+    }
+
+    (
+        StatusCode::ACCEPTED,
+        Json(serde_json::json!({
+            "id": id,
+            "status_url": format!("/api/v1/voice/transcriptions/{}", id),
+            "status": status,
+        })),
+    )
+        .into_response()
+}
+
+async fn remove_partial_upload(media_dir: &FsPath, id: &str) {
+    let Ok(mut entries) = tokio::fs::read_dir(media_dir).await else { return };
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        if entry.file_name().to_string_lossy().starts_with(id) {
+            let _ = tokio::fs::remove_file(entry.path()).await;
+        }
+    }
+}
+
+pub async fn transcription_status_handler(
+    State(state): State<Arc<crate::AppState>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match state.transcription_manager.queue.get(&id).await {
+        Some(job) => {
+            let partial_text = job.partial_text();
+            Json(serde_json::json!({
+                "id": job.id,
+                "filename": job.filename,
+                "recorded_at": job.recorded_at,
+                "status": job.status,
+                "segments_done": job.segments.len(),
+                "segments_total": job.segments_total,
+                "partial_text": partial_text,
+                "segments": job.segments,
+                "transcript": job.transcript,
+                "document_id": job.document_id,
+                "ingest_error": job.ingest_error,
+                "created_at": job.created_at,
+                "updated_at": job.updated_at,
+            }))
+            .into_response()
+        }
+        None => (StatusCode::NOT_FOUND, "Transcription not found").into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    const RATE: u32 = 16_000;
+
+    // Returns one scripted line per segment, failing once at `fail_at`
+    #[derive(Default)]
+    struct SegmentedStt {
+        lines: Vec<&'static str>,
+        fail_at: Mutex<Option<usize>>,
+        calls: Mutex<Vec<String>>,
+    }
+
+    impl SegmentTranscriber for SegmentedStt {
+        async fn transcribe_segment(&self, audio: Vec<u8>, filename: &str) -> Result<String> {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            assert!(parse_wav(&audio).is_some(), "segments are standalone WAV files");
+
+            let index: usize = filename.trim_end_matches(".wav").rsplit('-').next().unwrap().parse()?;
+            if *self.fail_at.lock().unwrap() == Some(index) {
+                *self.fail_at.lock().unwrap() = None;
+                anyhow::bail!("Whisper API unavailable");
+            }
+            self.calls.lock().unwrap().push(filename.to_string());
+            Ok(self.lines[index].to_string())
+        }
+    }
+
+    #[derive(Default)]
+    struct MemorySink {
+        ingested: Mutex<Vec<(String, String)>>,
+    }
+
+    impl TranscriptSink for MemorySink {
+        async fn ingest(&self, job: &TranscriptionJob, transcript: &str) -> Result<String> {
+            self.ingested
+                .lock()
+                .unwrap()
+                .push((job.recorded_at.format("%Y-%m-%d").to_string(), transcript.to_string()));
+            Ok("doc-1".to_string())
+        }
+    }
+
+    // Tone bursts of the given lengths separated by one second of silence
+    fn speech_wav(bursts_ms: &[usize]) -> Vec<u8> {
+        speech_wav_at(RATE, 1, bursts_ms)
+    }
+
+    fn speech_wav_at(rate: u32, channels: u16, bursts_ms: &[usize]) -> Vec<u8> {
+        let mut samples = Vec::new();
+        for (i, &ms) in bursts_ms.iter().enumerate() {
+            if i > 0 {
+                samples.extend(std::iter::repeat(0i16).take(rate as usize * channels as usize));
+            }
+            let n = rate as usize * ms / 1000;
+            for t in 0..n {
+                let sample = ((t as f64 * 0.05).sin() * 8_000.0) as i16;
+                samples.extend(std::iter::repeat(sample).take(channels as usize));
+            }
+        }
+        encode_wav(rate, channels, &samples)
+    }
+
+    async fn queue_with_job(audio: &[u8], auto_ingest: bool) -> (TranscriptionQueue, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("rusty-ai-media-{}", Uuid::new_v4()));
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        let media_path = dir.join("memo.wav");
+        tokio::fs::write(&media_path, audio).await.unwrap();
+
+        let queue = TranscriptionQueue::open(dir.join("jobs")).await.unwrap();
+        let recorded_at = parse_recorded_at("2024-03-01");
+        queue
+            .insert(TranscriptionJob::new("job-1".to_string(), "memo.wav".to_string(), media_path, recorded_at, auto_ingest))
+            .await;
+        (queue, dir)
+    }
+
+    #[test]
+    fn test_vad_splits_at_pauses() {
+        let segments = segment_audio(&speech_wav(&[600, 900, 300])).unwrap();
+        assert_eq!(segments.len(), 3);
+        assert_eq!(segments[0].start_ms, 0);
+        assert!(segments[1].start_ms >= 1_500 && segments[1].end_ms <= 2_550);
+        assert!(segments.iter().all(|s| parse_wav(&s.audio).is_some()));
+
+        // Anything that is not PCM WAV goes to Whisper whole, if Whisper takes it
+        assert_eq!(segment_audio(b"OggS not really").unwrap().len(), 1);
+        let too_large = segment_audio(&vec![0u8; MAX_UNSPLIT_BYTES + 1]).unwrap_err();
+        assert!(too_large.to_string().contains("can't be split"));
+    }
+
+    #[test]
+    fn test_stereo_frames_keep_channels_together_at_uneven_rates() {
+        // 22.05kHz makes 661.5 samples per 30ms, which must not split a
+        // left/right pair
+        let segments = segment_audio(&speech_wav_at(22_050, 2, &[600, 900])).unwrap();
+        assert_eq!(segments.len(), 2);
+        assert!(segments[1].start_ms >= 1_500 && segments[1].end_ms <= 2_550);
+        for segment in &segments {
+            let wav = parse_wav(&segment.audio).unwrap();
+            assert_eq!(wav.samples.len() % 2, 0);
+            // Both channels carry the same tone, so they stay in step
+            assert!(wav.samples.chunks(2).all(|pair| pair[0] == pair[1]));
+        }
+    }
+
+    #[test]
+    fn test_long_speech_is_capped() {
+        let segments = segment_audio(&speech_wav(&[MAX_SEGMENT_MS * 2 + 1_000])).unwrap();
+        assert_eq!(segments.len(), 3);
+        assert!(segments.iter().all(|s| s.end_ms - s.start_ms <= MAX_SEGMENT_MS as u64));
+    }
+
+    #[tokio::test]
+    async fn test_progress_reports_partial_text() {
+        let (queue, dir) = queue_with_job(&speech_wav(&[400, 400, 400]), true).await;
+        let stt = SegmentedStt {
+            lines: vec!["Call the plumber", "about the leak.", "Then book flights."],
+            ..Default::default()
+        };
+        let sink = MemorySink::default();
+        let mut events = queue.subscribe();
+
+        process_job(&queue, &stt, Some(&sink), "job-1").await.unwrap();
+
+        let mut partials = Vec::new();
+        while let Ok(job) = events.try_recv() {
+            if job.status == JobStatus::Transcribing {
+                assert_eq!(job.segments_total, Some(3));
+                partials.push(job.partial_text());
+            }
+        }
+        assert_eq!(
+            partials,
+            vec![
+                "",
+                "Call the plumber",
+                "Call the plumber about the leak.",
+                "Call the plumber about the leak. Then book flights.",
+            ]
+        );
+
+        let job = queue.get("job-1").await.unwrap();
+        assert_eq!(job.status, JobStatus::Completed);
+        assert_eq!(job.document_id.as_deref(), Some("doc-1"));
+        assert_eq!(
+            sink.ingested.lock().unwrap()[0],
+            ("2024-03-01".to_string(), "Call the plumber about the leak. Then book flights.".to_string())
+        );
+        let _ = tokio::fs::remove_dir_all(dir).await;
+    }
+
+    #[tokio::test]
+    async fn test_resumes_after_restart_without_redoing_segments() {
+        let (queue, dir) = queue_with_job(&speech_wav(&[400, 400, 400]), false).await;
+        let stt = SegmentedStt {
+            lines: vec!["one", "two", "three"],
+            fail_at: Mutex::new(Some(2)),
+            ..Default::default()
+        };
+
+        // Simulate a crash during the last segment: the failure is recorded,
+        // then the persisted state is put back to mid-transcription
+        assert!(process_job(&queue, &stt, None::<&MemorySink>, "job-1").await.is_err());
+        queue.update("job-1", |job| job.status = JobStatus::Transcribing).await;
+
+        let reopened = TranscriptionQueue::open(dir.join("jobs")).await.unwrap();
+        assert_eq!(reopened.unfinished().await, vec!["job-1".to_string()]);
+        assert_eq!(reopened.get("job-1").await.unwrap().partial_text(), "one two");
+
+        process_job(&reopened, &stt, None::<&MemorySink>, "job-1").await.unwrap();
+        let job = reopened.get("job-1").await.unwrap();
+        assert_eq!(job.transcript.as_deref(), Some("one two three"));
+        assert_eq!(*stt.calls.lock().unwrap(), vec!["memo-0.wav", "memo-1.wav", "memo-2.wav"]);
+        let _ = tokio::fs::remove_dir_all(dir).await;
+    }
+
+    #[tokio::test]
+    async fn test_retention_removes_finished_media() {
+        let (queue, dir) = queue_with_job(&speech_wav(&[400]), false).await;
+        let stt = SegmentedStt {
+            lines: vec!["hello there"],
+            ..Default::default()
+        };
+        process_job(&queue, &stt, None::<&MemorySink>, "job-1").await.unwrap();
+
+        assert_eq!(queue.remove_expired_media(Utc::now() - chrono::Duration::days(1)).await, 0);
+        assert_eq!(queue.remove_expired_media(Utc::now() + chrono::Duration::seconds(1)).await, 1);
+
+        let job = queue.get("job-1").await.unwrap();
+        assert!(job.media_deleted);
+        assert!(!job.media_path.exists());
+        assert_eq!(job.transcript.as_deref(), Some("hello there"));
+        let _ = tokio::fs::remove_dir_all(dir).await;
+    }
+}
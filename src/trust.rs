@@ -0,0 +1,214 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::knowledge_service_simple::DocumentMatch;
+
+// Payload field holding a chunk's trust level; it carries a keyword index so
+// searches can filter on it
+pub const TRUST_FIELD: &str = "trust_level";
+
+// Told to the model when the context it answers from is mostly unverified
+pub const HEDGING_INSTRUCTION: &str = "Most of this information comes from unverified sources. Mention that, and phrase anything based on it with caution rather than as established fact.";
+
+// How far a knowledge document can be relied on, most trusted first
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrustLevel {
+    // Checked by the user, e.g. an official policy
+    Verified,
+    // Written or uploaded by the user. Documents indexed before trust levels
+    // existed read as personal
+    #[default]
+    Personal,
+    // Pulled in from elsewhere, e.g. crawled web pages
+    External,
+    Unverified,
+}
+
+impl TrustLevel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TrustLevel::Verified => "verified",
+            TrustLevel::Personal => "personal",
+            TrustLevel::External => "external",
+            TrustLevel::Unverified => "unverified",
+        }
+    }
+
+    fn rank(&self) -> u8 {
+        match self {
+            TrustLevel::Verified => 3,
+            TrustLevel::Personal => 2,
+            TrustLevel::External => 1,
+            TrustLevel::Unverified => 0,
+        }
+    }
+
+    // Whether this level is `min` or more trusted
+    pub fn at_least(&self, min: TrustLevel) -> bool {
+        self.rank() >= min.rank()
+    }
+}
+
+impl fmt::Display for TrustLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for TrustLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "verified" => Ok(TrustLevel::Verified),
+            "personal" => Ok(TrustLevel::Personal),
+            "external" => Ok(TrustLevel::External),
+            "unverified" => Ok(TrustLevel::Unverified),
+            other => Err(format!("Unknown trust level: {}", other)),
+        }
+    }
+}
+
+// Multiplier applied to a match's score by the trust level of its document
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TrustMultipliers {
+    pub verified: f32,
+    pub personal: f32,
+    pub external: f32,
+    pub unverified: f32,
+    // Extra factor for chunks the user corrected by hand
+    pub corrected: f32,
+}
+
+impl Default for TrustMultipliers {
+    fn default() -> Self {
+        Self { verified: 1.2, personal: 1.0, external: 0.85, unverified: 0.6, corrected: 1.1 }
+    }
+}
+
+impl TrustMultipliers {
+    // RETRIEVAL_TRUST_{VERIFIED,PERSONAL,EXTERNAL,UNVERIFIED,CORRECTED}
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        let read = |name: &str, default: f32| {
+            std::env::var(format!("RETRIEVAL_TRUST_{}", name))
+                .ok()
+                .and_then(|v| v.parse::<f32>().ok())
+                .filter(|v| v.is_finite() && *v >= 0.0)
+                .unwrap_or(default)
+        };
+
+        Self {
+            verified: read("VERIFIED", defaults.verified),
+            personal: read("PERSONAL", defaults.personal),
+            external: read("EXTERNAL", defaults.external),
+            unverified: read("UNVERIFIED", defaults.unverified),
+            corrected: read("CORRECTED", defaults.corrected),
+        }
+    }
+
+    pub fn multiplier(&self, level: TrustLevel) -> f32 {
+        match level {
+            TrustLevel::Verified => self.verified,
+            TrustLevel::Personal => self.personal,
+            TrustLevel::External => self.external,
+            TrustLevel::Unverified => self.unverified,
+        }
+    }
+
+    // The multiplier for one match: its trust level, boosted when the chunk
+    // was corrected by hand
+    pub fn for_match(&self, doc: &DocumentMatch) -> f32 {
+        let corrected = if doc.manually_corrected { self.corrected } else { 1.0 };
+        self.multiplier(doc.trust_level) * corrected
+    }
+}
+
+// The trust level carrying the largest share of the matches' combined score;
+// on a tie the less trusted level wins. None without matches
+pub fn dominant_trust(matches: &[DocumentMatch]) -> Option<TrustLevel> {
+    let mut totals: HashMap<TrustLevel, f32> = HashMap::new();
+    for doc in matches {
+        *totals.entry(doc.trust_level).or_default() += doc.score.max(0.0);
+    }
+
+    totals
+        .into_iter()
+        .max_by(|(a_level, a_score), (b_level, b_score)| {
+            a_score
+                .partial_cmp(b_score)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| b_level.rank().cmp(&a_level.rank()))
+        })
+        .map(|(level, _)| level)
+}
+
+// The instruction to add to the prompt, only when the context relies
+// primarily on unverified sources
+pub fn hedging_instruction(dominant: Option<TrustLevel>) -> Option<&'static str> {
+    (dominant == Some(TrustLevel::Unverified)).then_some(HEDGING_INSTRUCTION)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn doc(id: &str, trust_level: TrustLevel, score: f32) -> DocumentMatch {
+        DocumentMatch {
+            id: id.to_string(),
+            title: id.to_string(),
+            content: String::new(),
+            score,
+            chunk_index: 0,
+            source: "test".to_string(),
+            tags: Vec::new(),
+            trust_level,
+            note: None,
+            manually_corrected: false,
+            page: None,
+        }
+    }
+
+    #[test]
+    fn test_parses_and_orders_levels() {
+        assert_eq!("Verified".parse::<TrustLevel>().unwrap(), TrustLevel::Verified);
+        assert_eq!(" external ".parse::<TrustLevel>().unwrap(), TrustLevel::External);
+        assert!("trusted".parse::<TrustLevel>().is_err());
+
+        assert!(TrustLevel::Verified.at_least(TrustLevel::Personal));
+        assert!(TrustLevel::Personal.at_least(TrustLevel::Personal));
+        assert!(!TrustLevel::External.at_least(TrustLevel::Personal));
+        assert!(TrustLevel::Unverified.at_least(TrustLevel::Unverified));
+    }
+
+    #[test]
+    fn test_dominant_trust_weighs_by_score() {
+        assert_eq!(dominant_trust(&[]), None);
+
+        // Two weak unverified pages outweigh one strong personal note
+        let matches = vec![
+            doc("notes", TrustLevel::Personal, 0.7),
+            doc("forum", TrustLevel::Unverified, 0.4),
+            doc("blog", TrustLevel::Unverified, 0.4),
+        ];
+        assert_eq!(dominant_trust(&matches), Some(TrustLevel::Unverified));
+
+        // A tie goes to the less trusted level
+        let matches = vec![doc("policy", TrustLevel::Verified, 0.5), doc("wiki", TrustLevel::External, 0.5)];
+        assert_eq!(dominant_trust(&matches), Some(TrustLevel::External));
+    }
+
+    #[test]
+    fn test_hedges_only_on_mostly_unverified_context() {
+        let mostly_unverified = vec![doc("notes", TrustLevel::Personal, 0.3), doc("forum", TrustLevel::Unverified, 0.6)];
+        assert_eq!(hedging_instruction(dominant_trust(&mostly_unverified)), Some(HEDGING_INSTRUCTION));
+
+        let mostly_personal = vec![doc("notes", TrustLevel::Personal, 0.8), doc("forum", TrustLevel::Unverified, 0.6)];
+        assert_eq!(hedging_instruction(dominant_trust(&mostly_personal)), None);
+        assert_eq!(hedging_instruction(dominant_trust(&[doc("wiki", TrustLevel::External, 0.9)])), None);
+        assert_eq!(hedging_instruction(None), None);
+    }
+}
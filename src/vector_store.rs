@@ -0,0 +1,643 @@
+// The vector index behind the knowledge base: Qdrant in production, or a
+// process-local store for the ephemeral profile. Both take the same points and
+// payloads, so everything above this layer runs unchanged.
+use anyhow::Result;
+use qdrant_client::{
+    qdrant::{
+        value::Kind, point_id::PointIdOptions, Condition, CountPointsBuilder, CreateCollectionBuilder,
+        CreateFieldIndexCollectionBuilder, DeletePointsBuilder, Distance, FieldType, Filter, GetPointsBuilder, PointId, PointStruct, PointsIdsList, ScrollPointsBuilder, SearchPointsBuilder,
+        SetPayloadPointsBuilder, UpsertPointsBuilder, Value, VectorParamsBuilder, vector_output,
+    },
+    Payload, Qdrant,
+};
+use std::collections::HashMap;
+use std::sync::RwLock;
+use tracing::info;
+
+pub type PointPayload = HashMap<String, Value>;
+
+pub struct VectorPoint {
+    pub id: String,
+    pub vector: Vec<f32>,
+    pub payload: Payload,
+}
+
+pub struct StoredPoint {
+    pub id: String,
+    pub payload: PointPayload,
+}
+
+pub struct ScoredPoint {
+    pub score: f32,
+    pub payload: PointPayload,
+}
+
+// Keyword conditions on payload fields. A list field matches when it contains
+// the value, like Qdrant's match on arrays.
+#[derive(Debug, Clone, Default)]
+pub struct PayloadFilter {
+    must: Vec<(String, String)>,
+    must_not: Vec<(String, String)>,
+    // Groups of which at least one condition each must hold
+    any: Vec<Vec<(String, String)>>,
+    // Integer fields that must have exactly this value
+    must_integer: Vec<(String, i64)>,
+}
+
+impl PayloadFilter {
+    pub fn matching(field: &str, value: impl Into<String>) -> Self {
+        Self::default().and(field, value)
+    }
+
+    pub fn and(mut self, field: &str, value: impl Into<String>) -> Self {
+        self.must.push((field.to_string(), value.into()));
+        self
+    }
+
+    pub fn and_not(mut self, field: &str, value: impl Into<String>) -> Self {
+        self.must_not.push((field.to_string(), value.into()));
+        self
+    }
+
+    pub fn and_integer(mut self, field: &str, value: i64) -> Self {
+        self.must_integer.push((field.to_string(), value));
+        self
+    }
+
+    // The field matches at least one of the values; no values adds no
+    // condition
+    pub fn and_any<V: Into<String>>(mut self, field: &str, values: impl IntoIterator<Item = V>) -> Self {
+        let group: Vec<(String, String)> = values.into_iter().map(|value| (field.to_string(), value.into())).collect();
+        if !group.is_empty() {
+            self.any.push(group);
+        }
+        self
+    }
+
+    fn to_qdrant(&self) -> Filter {
+        let condition = |(field, value): &(String, String)| Condition::matches(field.as_str(), value.clone());
+        let mut filter = Filter::must(self.must.iter().map(condition));
+        filter.must.extend(self.any.iter().map(|group| Filter::should(group.iter().map(condition)).into()));
+        filter.must.extend(self.must_integer.iter().map(|(field, value)| Condition::matches(field.as_str(), *value)));
+        filter.must_not.extend(self.must_not.iter().map(condition));
+        filter
+    }
+
+    fn matches(&self, payload: &PointPayload) -> bool {
+        let has = |(field, value): &(String, String)| match payload.get(field).and_then(|v| v.kind.as_ref()) {
+            Some(Kind::StringValue(s)) => s == value,
+            Some(Kind::ListValue(list)) => list
+                .values
+                .iter()
+                .any(|v| matches!(&v.kind, Some(Kind::StringValue(s)) if s == value)),
+            _ => false,
+        };
+        let equals = |(field, value): &(String, i64)| {
+            matches!(payload.get(field).and_then(|v| v.kind.as_ref()), Some(Kind::IntegerValue(i)) if i == value)
+        };
+        self.must.iter().all(has)
+            && !self.must_not.iter().any(has)
+            && self.any.iter().all(|group| group.iter().any(has))
+            && self.must_integer.iter().all(equals)
+    }
+}
+
+pub enum VectorStore {
+    Qdrant(Qdrant),
+    Memory(MemoryVectorStore),
+}
+
+impl VectorStore {
+    pub fn in_memory() -> Self {
+        VectorStore::Memory(MemoryVectorStore::default())
+    }
+
+    pub async fn ensure_collection(&self, name: &str, dimension: u64) -> Result<()> {
+        match self {
+            VectorStore::Qdrant(client) => {
+                let collections = client.list_collections().await?;
+                if !collections.collections.iter().any(|c| c.name == name) {
+                    info!("Creating Qdrant collection: {}", name);
+                    client
+                        .create_collection(
+                            CreateCollectionBuilder::new(name)
+                                .vectors_config(VectorParamsBuilder::new(dimension, Distance::Cosine)),
+                        )
+                        .await?;
+                    info!("Collection created successfully");
+                }
+                Ok(())
+            }
+            VectorStore::Memory(store) => {
+                store.ensure_collection(name, dimension);
+                Ok(())
+            }
+        }
+    }
+
+    // Index a keyword payload field for filtering. The in-memory store scans
+    // every point anyway and has nothing to index
+    pub async fn ensure_keyword_index(&self, collection: &str, field: &str) -> Result<()> {
+        match self {
+            VectorStore::Qdrant(client) => {
+                client
+                    .create_field_index(
+                        CreateFieldIndexCollectionBuilder::new(collection, field, FieldType::Keyword).wait(true),
+                    )
+                    .await?;
+                Ok(())
+            }
+            VectorStore::Memory(_) => Ok(()),
+        }
+    }
+
+    pub async fn upsert(&self, collection: &str, points: Vec<VectorPoint>) -> Result<()> {
+        match self {
+            VectorStore::Qdrant(client) => {
+                let points: Vec<PointStruct> = points
+                    .into_iter()
+                    .map(|p| PointStruct::new(p.id, p.vector, p.payload))
+                    .collect();
+                client.upsert_points(UpsertPointsBuilder::new(collection, points).wait(true)).await?;
+                Ok(())
+            }
+            VectorStore::Memory(store) => store.upsert(collection, points),
+        }
+    }
+
+    // Cosine similarity search among the points matching `filter`, best
+    // first. A filter nothing matches finds nothing, it is not an error
+    pub async fn search(
+        &self,
+        collection: &str,
+        vector: Vec<f32>,
+        limit: usize,
+        score_threshold: f32,
+        filter: Option<&PayloadFilter>,
+    ) -> Result<Vec<ScoredPoint>> {
+        match self {
+            VectorStore::Qdrant(client) => {
+                let mut search = SearchPointsBuilder::new(collection, vector, limit as u64)
+                    .score_threshold(score_threshold)
+                    .with_payload(true);
+                if let Some(filter) = filter {
+                    search = search.filter(filter.to_qdrant());
+                }
+                Ok(client
+                    .search_points(search)
+                    .await?
+                    .result
+                    .into_iter()
+                    .map(|point| ScoredPoint { score: point.score, payload: point.payload })
+                    .collect())
+            }
+            VectorStore::Memory(store) => store.search(collection, &vector, limit, score_threshold, filter),
+        }
+    }
+
+    pub async fn scroll(&self, collection: &str, filter: Option<&PayloadFilter>, limit: u32) -> Result<Vec<StoredPoint>> {
+        Ok(self.scroll_page(collection, filter, None, limit).await?.0)
+    }
+
+    // One page of points in id order, starting at `offset` (inclusive), and
+    // the offset of the next page; None when this was the last one
+    pub async fn scroll_page(
+        &self,
+        collection: &str,
+        filter: Option<&PayloadFilter>,
+        offset: Option<&str>,
+        limit: u32,
+    ) -> Result<(Vec<StoredPoint>, Option<String>)> {
+        match self {
+            VectorStore::Qdrant(client) => {
+                let mut scroll = ScrollPointsBuilder::new(collection)
+                    .limit(limit)
+                    .with_payload(true)
+                    .with_vectors(false);
+                if let Some(filter) = filter {
+                    scroll = scroll.filter(filter.to_qdrant());
+                }
+                if let Some(offset) = offset {
+                    scroll = scroll.offset(PointId::from(offset.to_string()));
+                }
+                let response = client.scroll(scroll).await?;
+                let points = response
+                    .result
+                    .into_iter()
+                    .filter_map(|point| Some(StoredPoint { id: point_id_string(point.id?)?, payload: point.payload }))
+                    .collect();
+                Ok((points, response.next_page_offset.and_then(point_id_string)))
+            }
+            VectorStore::Memory(store) => store.scroll(collection, filter, offset, limit as usize),
+        }
+    }
+
+    // Like scroll_page without a filter, but with the vectors, for snapshots.
+    // Qdrant's own snapshot API writes to the server's disk, so snapshots are
+    // taken through the client instead and stay portable between backends
+    pub async fn export_page(
+        &self,
+        collection: &str,
+        offset: Option<&str>,
+        limit: u32,
+    ) -> Result<(Vec<VectorPoint>, Option<String>)> {
+        match self {
+            VectorStore::Qdrant(client) => {
+                let mut scroll = ScrollPointsBuilder::new(collection)
+                    .limit(limit)
+                    .with_payload(true)
+                    .with_vectors(true);
+                if let Some(offset) = offset {
+                    scroll = scroll.offset(PointId::from(offset.to_string()));
+                }
+                let response = client.scroll(scroll).await?;
+                let mut points = Vec::with_capacity(response.result.len());
+                for point in response.result {
+                    let Some(id) = point.id.and_then(point_id_string) else { continue };
+                    let vector = match point.vectors.as_ref().and_then(|v| v.get_vector()) {
+                        Some(vector_output::Vector::Dense(dense)) => dense.data,
+                        _ => anyhow::bail!("Point {} in {} has no single dense vector", id, collection),
+                    };
+                    points.push(VectorPoint { id, vector, payload: point.payload.into() });
+                }
+                Ok((points, response.next_page_offset.and_then(point_id_string)))
+            }
+            VectorStore::Memory(store) => store.export(collection, offset, limit as usize),
+        }
+    }
+
+    // Points by id with their vectors; ids that do not exist are left out
+    pub async fn get_points(&self, collection: &str, ids: &[String]) -> Result<Vec<VectorPoint>> {
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        match self {
+            VectorStore::Qdrant(client) => {
+                let ids: Vec<PointId> = ids.iter().map(|id| PointId::from(id.clone())).collect();
+                let response = client
+                    .get_points(GetPointsBuilder::new(collection, ids).with_payload(true).with_vectors(true))
+                    .await?;
+                let mut points = Vec::with_capacity(response.result.len());
+                for point in response.result {
+                    let Some(id) = point.id.and_then(point_id_string) else { continue };
+                    let vector = match point.vectors.as_ref().and_then(|v| v.get_vector()) {
+                        Some(vector_output::Vector::Dense(dense)) => dense.data,
+                        _ => anyhow::bail!("Point {} in {} has no single dense vector", id, collection),
+                    };
+                    points.push(VectorPoint { id, vector, payload: point.payload.into() });
+                }
+                Ok(points)
+            }
+            VectorStore::Memory(store) => store.get(collection, ids),
+        }
+    }
+
+    // Drop a collection if it exists and create it again, empty
+    pub async fn recreate_collection(&self, name: &str, dimension: u64) -> Result<()> {
+        match self {
+            VectorStore::Qdrant(client) => {
+                if client.collection_exists(name).await? {
+                    info!("Dropping Qdrant collection: {}", name);
+                    client.delete_collection(name).await?;
+                }
+                self.ensure_collection(name, dimension).await
+            }
+            VectorStore::Memory(store) => {
+                store.collections.write().unwrap().remove(name);
+                store.ensure_collection(name, dimension);
+                Ok(())
+            }
+        }
+    }
+
+    // Merge `payload` into the payload of one point
+    pub async fn set_payload(&self, collection: &str, id: &str, payload: Payload) -> Result<()> {
+        match self {
+            VectorStore::Qdrant(client) => {
+                client
+                    .set_payload(
+                        SetPayloadPointsBuilder::new(collection, payload)
+                            .points_selector(PointsIdsList { ids: vec![PointId::from(id.to_string())] })
+                            .wait(true),
+                    )
+                    .await?;
+                Ok(())
+            }
+            VectorStore::Memory(store) => store.set_payload(collection, id, payload),
+        }
+    }
+
+    pub async fn delete(&self, collection: &str, filter: &PayloadFilter) -> Result<()> {
+        match self {
+            VectorStore::Qdrant(client) => {
+                client
+                    .delete_points(DeletePointsBuilder::new(collection).points(filter.to_qdrant()).wait(true))
+                    .await?;
+                Ok(())
+            }
+            VectorStore::Memory(store) => store.delete(collection, filter),
+        }
+    }
+
+    // Delete points by id; ids that do not exist are ignored
+    pub async fn delete_points(&self, collection: &str, ids: &[String]) -> Result<()> {
+        if ids.is_empty() {
+            return Ok(());
+        }
+        match self {
+            VectorStore::Qdrant(client) => {
+                let ids = ids.iter().map(|id| PointId::from(id.clone())).collect();
+                client
+                    .delete_points(DeletePointsBuilder::new(collection).points(PointsIdsList { ids }).wait(true))
+                    .await?;
+                Ok(())
+            }
+            VectorStore::Memory(store) => store.delete_points(collection, ids),
+        }
+    }
+
+    // (vectors, indexed vectors) in a collection
+    pub async fn count(&self, collection: &str) -> Result<(u64, u64)> {
+        match self {
+            VectorStore::Qdrant(client) => {
+                let info = client.collection_info(collection).await?;
+                let result = info.result.as_ref();
+                Ok((
+                    result.and_then(|r| r.vectors_count).unwrap_or(0),
+                    result.and_then(|r| r.indexed_vectors_count).unwrap_or(0),
+                ))
+            }
+            VectorStore::Memory(store) => {
+                let points = store.len(collection)? as u64;
+                Ok((points, points))
+            }
+        }
+    }
+
+    // Exact number of points in a collection, unlike the vector counts of
+    // `count` which Qdrant may report before indexing catches up
+    pub async fn point_count(&self, collection: &str) -> Result<u64> {
+        match self {
+            VectorStore::Qdrant(client) => {
+                let response = client.count(CountPointsBuilder::new(collection).exact(true)).await?;
+                Ok(response.result.map(|r| r.count).unwrap_or(0))
+            }
+            VectorStore::Memory(store) => Ok(store.len(collection)? as u64),
+        }
+    }
+}
+
+fn point_id_string(id: PointId) -> Option<String> {
+    Some(match id.point_id_options? {
+        PointIdOptions::Uuid(uuid) => uuid,
+        PointIdOptions::Num(num) => num.to_string(),
+    })
+}
+
+struct MemoryPoint {
+    id: String,
+    vector: Vec<f32>,
+    payload: PointPayload,
+}
+
+struct MemoryCollection {
+    dimension: usize,
+    points: Vec<MemoryPoint>,
+}
+
+// Brute-force cosine search over points held in process memory
+#[derive(Default)]
+pub struct MemoryVectorStore {
+    collections: RwLock<HashMap<String, MemoryCollection>>,
+}
+
+impl MemoryVectorStore {
+    fn ensure_collection(&self, name: &str, dimension: u64) {
+        self.collections
+            .write()
+            .unwrap()
+            .entry(name.to_string())
+            .or_insert_with(|| MemoryCollection { dimension: dimension as usize, points: Vec::new() });
+    }
+
+    fn with_collection<T>(&self, name: &str, f: impl FnOnce(&mut MemoryCollection) -> Result<T>) -> Result<T> {
+        let mut collections = self.collections.write().unwrap();
+        let collection = collections
+            .get_mut(name)
+            .ok_or_else(|| anyhow::anyhow!("Collection {} not found", name))?;
+        f(collection)
+    }
+
+    fn upsert(&self, name: &str, points: Vec<VectorPoint>) -> Result<()> {
+        self.with_collection(name, |collection| {
+            for point in points {
+                if point.vector.len() != collection.dimension {
+                    anyhow::bail!(
+                        "Vector of size {} does not fit collection {} of size {}",
+                        point.vector.len(),
+                        name,
+                        collection.dimension
+                    );
+                }
+                collection.points.retain(|p| p.id != point.id);
+                collection.points.push(MemoryPoint {
+                    id: point.id,
+                    vector: point.vector,
+                    payload: point.payload.into(),
+                });
+            }
+            Ok(())
+        })
+    }
+
+    fn search(
+        &self,
+        name: &str,
+        vector: &[f32],
+        limit: usize,
+        score_threshold: f32,
+        filter: Option<&PayloadFilter>,
+    ) -> Result<Vec<ScoredPoint>> {
+        self.with_collection(name, |collection| {
+            let mut scored: Vec<ScoredPoint> = collection
+                .points
+                .iter()
+                .filter(|p| filter.is_none_or(|filter| filter.matches(&p.payload)))
+                .map(|p| ScoredPoint { score: cosine_similarity(vector, &p.vector), payload: p.payload.clone() })
+                .filter(|p| p.score >= score_threshold)
+                .collect();
+            scored.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
+            scored.truncate(limit);
+            Ok(scored)
+        })
+    }
+
+    fn scroll(
+        &self,
+        name: &str,
+        filter: Option<&PayloadFilter>,
+        offset: Option<&str>,
+        limit: usize,
+    ) -> Result<(Vec<StoredPoint>, Option<String>)> {
+        self.with_collection(name, |collection| {
+            let mut points: Vec<&MemoryPoint> = collection
+                .points
+                .iter()
+                .filter(|p| offset.is_none_or(|offset| p.id.as_str() >= offset))
+                .filter(|p| match filter {
+                    Some(filter) => filter.matches(&p.payload),
+                    None => true,
+                })
+                .collect();
+            points.sort_by(|a, b| a.id.cmp(&b.id));
+            let next = points.get(limit).map(|p| p.id.clone());
+            Ok((
+                points
+                    .into_iter()
+                    .take(limit)
+                    .map(|p| StoredPoint { id: p.id.clone(), payload: p.payload.clone() })
+                    .collect(),
+                next,
+            ))
+        })
+    }
+
+    fn export(&self, name: &str, offset: Option<&str>, limit: usize) -> Result<(Vec<VectorPoint>, Option<String>)> {
+        self.with_collection(name, |collection| {
+            let mut points: Vec<&MemoryPoint> = collection
+                .points
+                .iter()
+                .filter(|p| offset.is_none_or(|offset| p.id.as_str() >= offset))
+                .collect();
+            points.sort_by(|a, b| a.id.cmp(&b.id));
+            let next = points.get(limit).map(|p| p.id.clone());
+            Ok((
+                points
+                    .into_iter()
+                    .take(limit)
+                    .map(|p| VectorPoint { id: p.id.clone(), vector: p.vector.clone(), payload: p.payload.clone().into() })
+                    .collect(),
+                next,
+            ))
+        })
+    }
+
+    fn get(&self, name: &str, ids: &[String]) -> Result<Vec<VectorPoint>> {
+        self.with_collection(name, |collection| {
+            Ok(collection
+                .points
+                .iter()
+                .filter(|p| ids.contains(&p.id))
+                .map(|p| VectorPoint { id: p.id.clone(), vector: p.vector.clone(), payload: p.payload.clone().into() })
+                .collect())
+        })
+    }
+
+    fn set_payload(&self, name: &str, id: &str, payload: Payload) -> Result<()> {
+        self.with_collection(name, |collection| {
+            if let Some(point) = collection.points.iter_mut().find(|p| p.id == id) {
+                point.payload.extend(PointPayload::from(payload));
+            }
+            Ok(())
+        })
+    }
+
+    fn delete(&self, name: &str, filter: &PayloadFilter) -> Result<()> {
+        self.with_collection(name, |collection| {
+            collection.points.retain(|p| !filter.matches(&p.payload));
+            Ok(())
+        })
+    }
+
+    fn delete_points(&self, name: &str, ids: &[String]) -> Result<()> {
+        self.with_collection(name, |collection| {
+            collection.points.retain(|p| !ids.contains(&p.id));
+            Ok(())
+        })
+    }
+
+    fn len(&self, name: &str) -> Result<usize> {
+        self.with_collection(name, |collection| Ok(collection.points.len()))
+    }
+}
+
+fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        0.0
+    } else {
+        dot / (norm_a * norm_b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(id: &str, vector: Vec<f32>, payload: serde_json::Value) -> VectorPoint {
+        VectorPoint { id: id.to_string(), vector, payload: payload.try_into().unwrap() }
+    }
+
+    #[tokio::test]
+    async fn test_memory_store_searches_filters_and_deletes() {
+        let store = VectorStore::in_memory();
+        store.ensure_collection("docs", 2).await.unwrap();
+        store
+            .upsert(
+                "docs",
+                vec![
+                    point("a", vec![1.0, 0.0], serde_json::json!({"id": "doc-1", "tags": ["work"], "chunk_index": 0})),
+                    point("b", vec![0.6, 0.8], serde_json::json!({"id": "doc-2", "tags": ["home"], "chunk_index": 1})),
+                    point("c", vec![0.0, 1.0], serde_json::json!({"id": "doc-2", "kind": "annotation"})),
+                ],
+            )
+            .await
+            .unwrap();
+        assert!(store.upsert("docs", vec![point("d", vec![1.0], serde_json::json!({}))]).await.is_err());
+
+        let results = store.search("docs", vec![1.0, 0.0], 10, 0.5, None).await.unwrap();
+        assert_eq!(results.len(), 2);
+        assert!((results[0].score - 1.0).abs() < 1e-6);
+        assert!((results[1].score - 0.6).abs() < 1e-6);
+
+        let work = store.scroll("docs", Some(&PayloadFilter::matching("tags", "work")), 10).await.unwrap();
+        assert_eq!(work.iter().map(|p| p.id.as_str()).collect::<Vec<_>>(), vec!["a"]);
+        let doc_2_chunks = PayloadFilter::matching("id", "doc-2").and_not("kind", "annotation");
+        assert_eq!(store.scroll("docs", Some(&doc_2_chunks), 10).await.unwrap().len(), 1);
+        let first_chunks = store.scroll("docs", Some(&PayloadFilter::default().and_integer("chunk_index", 0)), 10).await.unwrap();
+        assert_eq!(first_chunks.iter().map(|p| p.id.as_str()).collect::<Vec<_>>(), vec!["a"]);
+
+        store
+            .set_payload("docs", "b", serde_json::json!({"tags": ["home", "work"]}).try_into().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(store.scroll("docs", Some(&PayloadFilter::matching("tags", "work")), 10).await.unwrap().len(), 2);
+        let home_or_travel = PayloadFilter::default().and_any("tags", ["home", "travel"]);
+        assert_eq!(store.search("docs", vec![1.0, 0.0], 10, 0.0, Some(&home_or_travel)).await.unwrap().len(), 1);
+        assert_eq!(home_or_travel.to_qdrant().must.len(), 1);
+        let travel = PayloadFilter::default().and_any("tags", ["travel"]);
+        assert!(store.search("docs", vec![1.0, 0.0], 10, 0.0, Some(&travel)).await.unwrap().is_empty());
+
+        store.delete("docs", &PayloadFilter::matching("id", "doc-2")).await.unwrap();
+        assert_eq!(store.count("docs").await.unwrap(), (1, 1));
+    }
+
+    #[tokio::test]
+    async fn test_memory_store_scrolls_in_pages() {
+        let store = VectorStore::in_memory();
+        store.ensure_collection("docs", 1).await.unwrap();
+        let points = ["e", "b", "d", "a", "c"].iter().map(|id| point(id, vec![1.0], serde_json::json!({}))).collect();
+        store.upsert("docs", points).await.unwrap();
+
+        let (first, next) = store.scroll_page("docs", None, None, 2).await.unwrap();
+        assert_eq!(first.iter().map(|p| p.id.as_str()).collect::<Vec<_>>(), vec!["a", "b"]);
+        assert_eq!(next.as_deref(), Some("c"));
+        let (second, next) = store.scroll_page("docs", None, Some("c"), 2).await.unwrap();
+        assert_eq!(second.iter().map(|p| p.id.as_str()).collect::<Vec<_>>(), vec!["c", "d"]);
+        let (last, next) = store.scroll_page("docs", None, next.as_deref(), 2).await.unwrap();
+        assert_eq!(last.len(), 1);
+        assert_eq!(next, None);
+    }
+}
@@ -0,0 +1,454 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tracing::{debug, info};
+use uuid::Uuid;
+
+use crate::voice_service::VoiceService;
+
+// Audio goes out as WebSocket binary frames of this size, so a cancel takes
+// effect at most one frame late
+pub const FRAME_BYTES: usize = 4096;
+
+// Frames buffered between the provider and the socket; anything still queued
+// when playback is cancelled is dropped rather than sent
+const FRAME_QUEUE: usize = 4;
+
+// Microphone audio is 16-bit mono PCM. Speech has to stay above the threshold
+// for several consecutive chunks so a click or the speaker echo does not cut
+// the assistant off
+const BARGE_IN_RMS: f64 = 0.02;
+const BARGE_IN_CHUNKS: usize = 3;
+
+/// A provider response read chunk by chunk; dropping it aborts the request
+pub trait AudioChunkStream: Send {
+    fn next_chunk(&mut self) -> impl Future<Output = Result<Option<Vec<u8>>>> + Send;
+}
+
+pub trait StreamingTts {
+    type Stream: AudioChunkStream + 'static;
+
+    fn stream_speech(&self, text: &str, language: &str) -> impl Future<Output = Result<Self::Stream>> + Send;
+}
+
+// Audio that was synthesized in one piece, handed out in frames
+pub struct BufferedAudio {
+    data: Vec<u8>,
+    offset: usize,
+}
+
+impl BufferedAudio {
+    pub fn new(data: Vec<u8>) -> Self {
+        Self { data, offset: 0 }
+    }
+}
+
+impl AudioChunkStream for BufferedAudio {
+    async fn next_chunk(&mut self) -> Result<Option<Vec<u8>>> {
+        if self.offset >= self.data.len() {
+            return Ok(None);
+        }
+        let end = (self.offset + FRAME_BYTES).min(self.data.len());
+        let chunk = self.data[self.offset..end].to_vec();
+        self.offset = end;
+        Ok(Some(chunk))
+    }
+}
+
+pub enum ProviderStream {
+    ElevenLabs(reqwest::Response),
+    Buffered(BufferedAudio),
+}
+
+impl AudioChunkStream for ProviderStream {
+    async fn next_chunk(&mut self) -> Result<Option<Vec<u8>>> {
+        match self {
+            ProviderStream::ElevenLabs(response) => Ok(response.chunk().await?.map(|bytes| bytes.to_vec())),
+            ProviderStream::Buffered(audio) => audio.next_chunk().await,
+        }
+    }
+}
+
+impl StreamingTts for VoiceService {
+    type Stream = ProviderStream;
+
+    // ElevenLabs streams as it synthesizes; OpenAI TTS is the fallback and
+    // only returns the finished file
+    async fn stream_speech(&self, text: &str, language: &str) -> Result<ProviderStream> {
+        match self.open_speech_stream(text, language).await {
+            Ok(response) => Ok(ProviderStream::ElevenLabs(response)),
+            Err(e) => {
+                debug!("ElevenLabs streaming failed, falling back to OpenAI: {}", e);
+                let audio = self.synthesize_speech_openai(text, language).await?;
+                Ok(ProviderStream::Buffered(BufferedAudio::new(audio)))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InterruptReason {
+    // The client sent tts_cancel
+    ClientCancel,
+    // The user started speaking over the reply
+    BargeIn,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum PlaybackOutcome {
+    Completed { bytes_sent: u64 },
+    Interrupted { reason: InterruptReason, byte_offset: u64 },
+    Failed { error: String, bytes_sent: u64 },
+}
+
+#[derive(Debug)]
+pub enum PlaybackEvent {
+    Frame(Vec<u8>),
+    Finished(PlaybackSummary),
+}
+
+// Counts consecutive loud microphone chunks
+#[derive(Debug, Default)]
+pub struct BargeInDetector {
+    loud_chunks: usize,
+}
+
+impl BargeInDetector {
+    pub fn observe(&mut self, pcm: &[u8]) -> bool {
+        if pcm_rms(pcm) > BARGE_IN_RMS {
+            self.loud_chunks += 1;
+        } else {
+            self.loud_chunks = 0;
+        }
+        self.loud_chunks >= BARGE_IN_CHUNKS
+    }
+
+    pub fn reset(&mut self) {
+        self.loud_chunks = 0;
+    }
+}
+
+fn pcm_rms(pcm: &[u8]) -> f64 {
+    let samples: Vec<f64> = pcm
+        .chunks_exact(2)
+        .map(|b| i16::from_le_bytes([b[0], b[1]]) as f64 / i16::MAX as f64)
+        .collect();
+    if samples.is_empty() {
+        return 0.0;
+    }
+    (samples.iter().map(|s| s * s).sum::<f64>() / samples.len() as f64).sqrt()
+}
+
+struct ActivePlayback {
+    id: String,
+    message_id: Option<String>,
+    frames: mpsc::Receiver<Result<Vec<u8>>>,
+    pump: JoinHandle<()>,
+    bytes_sent: u64,
+}
+
+/// A finished or cancelled playback, with the assistant message it spoke
+#[derive(Debug)]
+pub struct PlaybackSummary {
+    pub playback_id: String,
+    pub message_id: Option<String>,
+    pub outcome: PlaybackOutcome,
+}
+
+// Speech playback on one WebSocket connection. The socket loop polls
+// next_frame alongside the client's messages, so a tts_cancel or loud
+// microphone audio is handled between two frames
+#[derive(Default)]
+pub struct VoiceSession {
+    playback: Option<ActivePlayback>,
+    detector: BargeInDetector,
+}
+
+impl VoiceSession {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playback.is_some()
+    }
+
+    /// Start playing a stream; a reply still playing is interrupted first
+    pub fn start<S: AudioChunkStream + 'static>(
+        &mut self,
+        stream: S,
+        message_id: Option<String>,
+    ) -> (String, Option<PlaybackSummary>) {
+        let previous = self.cancel(InterruptReason::ClientCancel);
+        let (tx, rx) = mpsc::channel(FRAME_QUEUE);
+        let id = Uuid::new_v4().to_string();
+
+        self.detector.reset();
+        self.playback = Some(ActivePlayback {
+            id: id.clone(),
+            message_id,
+            frames: rx,
+            pump: tokio::spawn(pump_frames(stream, tx)),
+            bytes_sent: 0,
+        });
+        (id, previous)
+    }
+
+    /// The next frame to send; pending while nothing is playing
+    pub async fn next_frame(&mut self) -> PlaybackEvent {
+        let Some(active) = self.playback.as_mut() else {
+            return std::future::pending().await;
+        };
+
+        let outcome = match active.frames.recv().await {
+            Some(Ok(frame)) => {
+                active.bytes_sent += frame.len() as u64;
+                return PlaybackEvent::Frame(frame);
+            }
+            Some(Err(e)) => PlaybackOutcome::Failed {
+                error: format!("{:#}", e),
+                bytes_sent: active.bytes_sent,
+            },
+            None => PlaybackOutcome::Completed { bytes_sent: active.bytes_sent },
+        };
+        PlaybackEvent::Finished(self.finish(outcome))
+    }
+
+    /// Stop sending frames and drop the provider stream
+    pub fn cancel(&mut self, reason: InterruptReason) -> Option<PlaybackSummary> {
+        let active = self.playback.as_ref()?;
+        let outcome = PlaybackOutcome::Interrupted {
+            reason,
+            byte_offset: active.bytes_sent,
+        };
+        info!("Playback {} interrupted ({:?}) at byte {}", active.id, reason, active.bytes_sent);
+        Some(self.finish(outcome))
+    }
+
+    /// Feed microphone audio; cancels playback once the user is speaking
+    pub fn observe_microphone(&mut self, pcm: &[u8]) -> Option<PlaybackSummary> {
+        if !self.is_playing() {
+            return None;
+        }
+        if self.detector.observe(pcm) {
+            return self.cancel(InterruptReason::BargeIn);
+        }
+        None
+    }
+
+    fn finish(&mut self, outcome: PlaybackOutcome) -> PlaybackSummary {
+        let active = self.playback.take().expect("finish is only called during playback");
+        // Aborting drops the stream, which closes the provider connection;
+        // dropping the receiver discards frames that were already queued
+        active.pump.abort();
+        PlaybackSummary {
+            playback_id: active.id,
+            message_id: active.message_id,
+            outcome,
+        }
+    }
+}
+
+impl Drop for VoiceSession {
+    fn drop(&mut self) {
+        if let Some(active) = &self.playback {
+            active.pump.abort();
+        }
+    }
+}
+
+async fn pump_frames<S: AudioChunkStream>(mut stream: S, frames: mpsc::Sender<Result<Vec<u8>>>) {
+    loop {
+        match stream.next_chunk().await {
+            Ok(Some(chunk)) => {
+                for frame in chunk.chunks(FRAME_BYTES) {
+                    if frames.send(Ok(frame.to_vec())).await.is_err() {
+                        return;
+                    }
+                }
+            }
+            Ok(None) => return,
+            Err(e) => {
+                let _ = frames.send(Err(e)).await;
+                return;
+            }
+        }
+    }
+}
+
+// Client messages that control playback on the socket
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum VoiceCommand {
+    TtsStart {
+        text: String,
+        #[serde(default)]
+        language: Option<String>,
+        // Assistant message being spoken, marked if the reply is cut off
+        #[serde(default)]
+        message_id: Option<String>,
+    },
+    TtsCancel,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    // An endless provider that counts the chunks it produced and notes when
+    // it is dropped
+    struct EndlessTts {
+        produced: Arc<AtomicUsize>,
+        dropped: Arc<AtomicBool>,
+    }
+
+    impl AudioChunkStream for EndlessTts {
+        async fn next_chunk(&mut self) -> Result<Option<Vec<u8>>> {
+            tokio::time::sleep(Duration::from_millis(2)).await;
+            self.produced.fetch_add(1, Ordering::SeqCst);
+            Ok(Some(vec![1u8; FRAME_BYTES]))
+        }
+    }
+
+    impl Drop for EndlessTts {
+        fn drop(&mut self) {
+            self.dropped.store(true, Ordering::SeqCst);
+        }
+    }
+
+    fn endless() -> (EndlessTts, Arc<AtomicUsize>, Arc<AtomicBool>) {
+        let produced = Arc::new(AtomicUsize::new(0));
+        let dropped = Arc::new(AtomicBool::new(false));
+        let stream = EndlessTts {
+            produced: produced.clone(),
+            dropped: dropped.clone(),
+        };
+        (stream, produced, dropped)
+    }
+
+    fn pcm(amplitude: i16, samples: usize) -> Vec<u8> {
+        (0..samples)
+            .flat_map(|t| {
+                let s = if t % 2 == 0 { amplitude } else { -amplitude };
+                s.to_le_bytes()
+            })
+            .collect()
+    }
+
+    async fn take_frames(session: &mut VoiceSession, count: usize) {
+        for _ in 0..count {
+            match session.next_frame().await {
+                PlaybackEvent::Frame(frame) => assert_eq!(frame.len(), FRAME_BYTES),
+                PlaybackEvent::Finished(summary) => panic!("playback ended early: {:?}", summary.outcome),
+            }
+        }
+    }
+
+    async fn assert_silent(session: &mut VoiceSession) {
+        let next = tokio::time::timeout(Duration::from_millis(50), session.next_frame()).await;
+        assert!(next.is_err(), "no frames are sent after an interruption");
+    }
+
+    #[tokio::test]
+    async fn test_client_cancel_stops_frames_and_drops_stream() {
+        let (stream, _, dropped) = endless();
+        let mut session = VoiceSession::new();
+        let (playback_id, previous) = session.start(stream, Some("msg-1".to_string()));
+        assert!(previous.is_none());
+
+        take_frames(&mut session, 5).await;
+        let summary = session.cancel(InterruptReason::ClientCancel).unwrap();
+
+        assert_eq!(summary.playback_id, playback_id);
+        assert_eq!(summary.message_id.as_deref(), Some("msg-1"));
+        assert_eq!(
+            summary.outcome,
+            PlaybackOutcome::Interrupted {
+                reason: InterruptReason::ClientCancel,
+                byte_offset: 5 * FRAME_BYTES as u64,
+            }
+        );
+        assert_silent(&mut session).await;
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(dropped.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_speech_over_playback_triggers_barge_in_within_bound() {
+        let (stream, produced, dropped) = endless();
+        let mut session = VoiceSession::new();
+        session.start(stream, None);
+        take_frames(&mut session, 3).await;
+
+        // Background noise does not interrupt
+        for _ in 0..10 {
+            assert!(session.observe_microphone(&pcm(100, 480)).is_none());
+            take_frames(&mut session, 1).await;
+        }
+
+        // The user starts talking: frames and microphone chunks interleave
+        // the way the socket loop sees them
+        let mut frames_after_onset = 0;
+        let summary = loop {
+            if let Some(summary) = session.observe_microphone(&pcm(8_000, 480)) {
+                break summary;
+            }
+            take_frames(&mut session, 1).await;
+            frames_after_onset += 1;
+        };
+
+        assert!(frames_after_onset < BARGE_IN_CHUNKS);
+        assert_eq!(
+            summary.outcome,
+            PlaybackOutcome::Interrupted {
+                reason: InterruptReason::BargeIn,
+                byte_offset: (13 + frames_after_onset) as u64 * FRAME_BYTES as u64,
+            }
+        );
+        assert_silent(&mut session).await;
+
+        // The provider is not read any further once it is dropped
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(dropped.load(Ordering::SeqCst));
+        let produced_at_cancel = produced.load(Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(produced.load(Ordering::SeqCst), produced_at_cancel);
+    }
+
+    #[tokio::test]
+    async fn test_microphone_is_ignored_while_idle() {
+        let mut session = VoiceSession::new();
+        for _ in 0..10 {
+            assert!(session.observe_microphone(&pcm(8_000, 480)).is_none());
+        }
+
+        // Speech before playback starts does not count towards a barge-in
+        session.start(BufferedAudio::new(vec![0u8; FRAME_BYTES * 2]), None);
+        assert!(session.observe_microphone(&pcm(8_000, 480)).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_buffered_audio_completes_with_byte_count() {
+        let mut session = VoiceSession::new();
+        session.start(BufferedAudio::new(vec![0u8; FRAME_BYTES * 2 + 10]), None);
+
+        let mut frames = 0;
+        let outcome = loop {
+            match session.next_frame().await {
+                PlaybackEvent::Frame(_) => frames += 1,
+                PlaybackEvent::Finished(summary) => break summary.outcome,
+            }
+        };
+
+        assert_eq!(frames, 3);
+        assert_eq!(outcome, PlaybackOutcome::Completed { bytes_sent: FRAME_BYTES as u64 * 2 + 10 });
+        assert!(!session.is_playing());
+    }
+}
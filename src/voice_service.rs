@@ -23,6 +23,9 @@ use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use tracing::{debug, error, info};
 
+use crate::data_residency::ProviderClass;
+use crate::http_client::{ClientKind, HttpClientFactory};
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TranscriptionResponse {
     pub text: String,
@@ -35,6 +38,9 @@ pub struct TTSRequest {
     pub text: String,
     pub voice_id: Option<String>,
     pub model_id: Option<String>,
+    // Response language of the reply being spoken; picks the default voice
+    #[serde(default)]
+    pub language: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -45,6 +51,8 @@ pub struct TTSResponse {
 
 pub struct VoiceService {
     openai_client: Client<OpenAIConfig>,
+    // For ElevenLabs
+    http: reqwest::Client,
     elevenlabs_api_key: Option<String>,
     elevenlabs_voice_id: String,
 }
@@ -58,21 +66,55 @@ impl VoiceService {
             OpenAIConfig::new() // Uses OPENAI_API_KEY env var
         };
 
+        Ok(Self::from_config(config, elevenlabs_api_key))
+    }
+
+    pub fn from_config(config: OpenAIConfig, elevenlabs_api_key: Option<String>) -> Self {
         let openai_client = Client::with_config(config);
         
         // Get ElevenLabs configuration from environment
         let elevenlabs_voice_id = std::env::var("ELEVENLABS_VOICE_ID")
             .unwrap_or_else(|_| "21m00Tcm4TlvDq8ikWAM".to_string()); // Rachel voice as default
         
-        Ok(Self {
+        Self {
             openai_client,
+            http: reqwest::Client::new(),
             elevenlabs_api_key,
             elevenlabs_voice_id,
-        })
+        }
+    }
+
+    // Send Whisper, TTS and ElevenLabs requests through the deployment's
+    // proxy and CAs
+    pub fn with_http(mut self, http: &HttpClientFactory) -> Result<Self> {
+        self.openai_client = self.openai_client.with_http_client(http.client(ClientKind::OpenAi)?);
+        self.http = http.client(ClientKind::ElevenLabs)?;
+        Ok(self)
+    }
+
+    // Whisper, ElevenLabs and OpenAI TTS all process audio and text remotely
+    pub fn provider_class(&self) -> ProviderClass {
+        ProviderClass::Cloud
     }
 
     // Transcribe audio using OpenAI Whisper API
     pub async fn transcribe_audio(&self, audio_data: Vec<u8>, filename: String) -> Result<TranscriptionResponse> {
+        match self.transcribe(audio_data, filename).await {
+            Ok(response) => Ok(response),
+            Err(e) => {
+                error!("Whisper API error: {}", e);
+                Ok(TranscriptionResponse {
+                    text: "Error: Could not transcribe audio".to_string(),
+                    language: None,
+                    duration: None,
+                })
+            }
+        }
+    }
+
+    // Like transcribe_audio, but Whisper failures are returned instead of
+    // being turned into placeholder text; queued transcriptions need to know
+    pub async fn transcribe(&self, audio_data: Vec<u8>, filename: String) -> Result<TranscriptionResponse> {
         debug!("Transcribing audio file: {}", filename);
         
         // Create AudioInput from bytes
@@ -87,40 +129,31 @@ impl VoiceService {
         let request = CreateTranscriptionRequestArgs::default()
             .file(audio_input)
             .model("whisper-1")
-            .language("en") // You can make this configurable
+            // No language hint: users switch languages between messages
             .response_format(AudioResponseFormat::Json)
             .build()?;
         
         // Call Whisper API
-        let response = match self.openai_client.audio().transcribe(request).await {
-            Ok(resp) => resp,
-            Err(e) => {
-                error!("Whisper API error: {}", e);
-                return Ok(TranscriptionResponse {
-                    text: "Error: Could not transcribe audio".to_string(),
-                    language: None,
-                    duration: None,
-                });
-            }
-        };
+        let response = self.openai_client.audio().transcribe(request).await?;
         
         info!("Successfully transcribed audio");
         
+        let language = crate::language::detect_language(&response.text);
+        
         Ok(TranscriptionResponse {
             text: response.text,
-            language: Some("en".to_string()),
+            language,
             duration: None,
         })
     }
 
     // Synthesize speech using ElevenLabs API
-    pub async fn synthesize_speech(&self, text: &str, voice_id: Option<String>) -> Result<Vec<u8>> {
+    pub async fn synthesize_speech(&self, text: &str, voice_id: Option<String>, language: &str) -> Result<Vec<u8>> {
         let api_key = self.elevenlabs_api_key.as_ref()
             .ok_or_else(|| anyhow::anyhow!("ElevenLabs API key not configured"))?;
+        let (voice, model_id) = self.elevenlabs_voice(voice_id, language);
         
-        let voice = voice_id.unwrap_or_else(|| self.elevenlabs_voice_id.clone());
-        
-        debug!("Synthesizing speech with ElevenLabs, voice: {}", voice);
+        debug!("Synthesizing speech with ElevenLabs, voice: {} ({})", voice, language);
         
         // ElevenLabs API endpoint
         let url = format!(
@@ -131,7 +164,7 @@ impl VoiceService {
         // Request body
         let body = serde_json::json!({
             "text": text,
-            "model_id": "eleven_monolingual_v1",
+            "model_id": model_id,
             "voice_settings": {
                 "stability": 0.5,
                 "similarity_boost": 0.5,
@@ -141,8 +174,8 @@ impl VoiceService {
         });
         
         // Make request to ElevenLabs
-        let client = reqwest::Client::new();
-        let response = client
+        let response = self
+            .http
             .post(&url)
             .header("Accept", "audio/mpeg")
             .header("xi-api-key", api_key)
@@ -164,13 +197,75 @@ impl VoiceService {
         Ok(audio_bytes)
     }
 
+    // The configured voice is the English default; other languages use the
+    // mapping table and the multilingual model
+    fn elevenlabs_voice(&self, voice_id: Option<String>, language: &str) -> (String, &'static str) {
+        let voice = voice_id.unwrap_or_else(|| {
+            if language == DEFAULT_LANGUAGE {
+                self.elevenlabs_voice_id.clone()
+            } else {
+                default_voice(TtsProvider::ElevenLabs, language).to_string()
+            }
+        });
+        let model_id = if language == DEFAULT_LANGUAGE {
+            "eleven_monolingual_v1"
+        } else {
+            "eleven_multilingual_v2"
+        };
+        (voice, model_id)
+    }
+
+    // Start an ElevenLabs synthesis whose audio is read from the response as
+    // it is generated; dropping the response aborts the request
+    pub async fn open_speech_stream(&self, text: &str, language: &str) -> Result<reqwest::Response> {
+        let api_key = self.elevenlabs_api_key.as_ref()
+            .ok_or_else(|| anyhow::anyhow!("ElevenLabs API key not configured"))?;
+        let (voice, model_id) = self.elevenlabs_voice(None, language);
+        
+        debug!("Streaming speech with ElevenLabs, voice: {} ({})", voice, language);
+        
+        let url = format!(
+            "https://api.elevenlabs.io/v1/text-to-speech/{}/stream",
+            voice
+        );
+        
+        let response = self
+            .http
+            .post(&url)
+            .header("Accept", "audio/mpeg")
+            .header("xi-api-key", api_key)
+            .header("Content-Type", "application/json")
+            .json(&serde_json::json!({
+                "text": text,
+                "model_id": model_id,
+            }))
+            .send()
+            .await?;
+        
+        if !response.status().is_success() {
+            let error_text = response.text().await?;
+            error!("ElevenLabs API error: {}", error_text);
+            return Err(anyhow::anyhow!("ElevenLabs API error: {}", error_text));
+        }
+        
+        Ok(response)
+    }
+
     // Alternative: Use OpenAI TTS as fallback
-    pub async fn synthesize_speech_openai(&self, text: &str) -> Result<Vec<u8>> {
-        debug!("Synthesizing speech with OpenAI TTS");
+    pub async fn synthesize_speech_openai(&self, text: &str, language: &str) -> Result<Vec<u8>> {
+        let voice = match default_voice(TtsProvider::OpenAi, language) {
+            "echo" => Voice::Echo,
+            "fable" => Voice::Fable,
+            "onyx" => Voice::Onyx,
+            "nova" => Voice::Nova,
+            "shimmer" => Voice::Shimmer,
+            _ => Voice::Alloy,
+        };
+        debug!("Synthesizing speech with OpenAI TTS, voice: {:?} ({})", voice, language);
         
         let request = CreateSpeechRequestArgs::default()
             .model(SpeechModel::Tts1)
-            .voice(Voice::Alloy)
+            .voice(voice)
             .input(text)
             .build()?;
         
@@ -185,13 +280,19 @@ impl VoiceService {
 
 // Import AppState from main module
 use crate::AppState;
+use crate::language::{default_voice, normalize_language, TtsProvider, DEFAULT_LANGUAGE};
 
 // HTTP Handlers for voice endpoints
 pub async fn transcribe_handler(
     State(state): State<Arc<AppState>>,
     mut multipart: Multipart,
 ) -> impl IntoResponse {
-    let voice_service = &state.voice_service;
+    let voice_service = match &state.voice_service {
+        Some(service) => service,
+        None => {
+            return (StatusCode::SERVICE_UNAVAILABLE, "Voice service is not available").into_response();
+        }
+    };
     while let Some(field) = multipart.next_field().await.unwrap() {
         let name = field.name().unwrap_or("").to_string();
         
@@ -227,13 +328,24 @@ pub async fn synthesize_handler(
     State(state): State<Arc<AppState>>,
     Json(request): Json<TTSRequest>,
 ) -> impl IntoResponse {
-    let voice_service = &state.voice_service;
+    let voice_service = match &state.voice_service {
+        Some(service) => service,
+        None => {
+            return (StatusCode::SERVICE_UNAVAILABLE, "Voice service is not available").into_response();
+        }
+    };
+    let language = request
+        .language
+        .as_deref()
+        .and_then(normalize_language)
+        .unwrap_or_else(|| DEFAULT_LANGUAGE.to_string());
+    
     // Try ElevenLabs first, fall back to OpenAI if it fails
-    let audio_bytes = match voice_service.synthesize_speech(&request.text, request.voice_id).await {
+    let audio_bytes = match voice_service.synthesize_speech(&request.text, request.voice_id, &language).await {
         Ok(bytes) => bytes,
         Err(e) => {
             debug!("ElevenLabs TTS failed, falling back to OpenAI: {}", e);
-            match voice_service.synthesize_speech_openai(&request.text).await {
+            match voice_service.synthesize_speech_openai(&request.text, &language).await {
                 Ok(bytes) => bytes,
                 Err(e) => {
                     error!("Both TTS services failed: {}", e);